@@ -41,6 +41,8 @@ mod wire {
             max_seg_size: None,
             sack_permitted: false,
             sack_ranges: [None, None, None],
+            timestamp: None,
+            urgent_at: None,
             payload: &PAYLOAD_BYTES,
         };
         let mut bytes = vec![0xa5; repr.buffer_len()];
@@ -88,6 +90,7 @@ mod wire {
             next_header: IpProtocol::Tcp,
             payload_len: 100,
             hop_limit: 64,
+            dscp: 0,
         };
         let mut bytes = vec![0xa5; repr.buffer_len()];
 
@@ -106,6 +109,8 @@ mod wire {
             next_header: IpProtocol::Tcp,
             payload_len: 100,
             hop_limit: 64,
+            traffic_class: 0,
+            flow_label: 0,
         };
         let mut bytes = vec![0xa5; repr.buffer_len()];
 
@@ -5,9 +5,9 @@ mod utils;
 use std::cmp;
 use std::io::{Read, Write};
 use std::net::TcpStream;
-use std::os::unix::io::AsRawFd;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
+use utils::AsRawFd;
 
 use smoltcp::iface::{Config, Interface, SocketSet};
 use smoltcp::phy::{wait as phy_wait, Device, Medium};
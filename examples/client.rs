@@ -1,8 +1,8 @@
 mod utils;
 
 use log::debug;
-use std::os::unix::io::AsRawFd;
 use std::str::{self, FromStr};
+use utils::AsRawFd;
 
 use smoltcp::iface::{Config, Interface, SocketSet};
 use smoltcp::phy::{wait as phy_wait, Device, Medium};
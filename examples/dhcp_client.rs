@@ -2,7 +2,7 @@
 mod utils;
 
 use log::*;
-use std::os::unix::io::AsRawFd;
+use utils::AsRawFd;
 
 use smoltcp::iface::{Config, Interface, SocketSet};
 use smoltcp::socket::dhcpv4;
@@ -1,9 +1,9 @@
 mod utils;
 
 use log::debug;
-use std::os::unix::io::AsRawFd;
 use std::str::{self, FromStr};
 use url::Url;
+use utils::AsRawFd;
 
 use smoltcp::iface::{Config, Interface, SocketSet};
 use smoltcp::phy::{wait as phy_wait, Device, Medium};
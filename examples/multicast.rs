@@ -1,6 +1,6 @@
 mod utils;
 
-use std::os::unix::io::AsRawFd;
+use utils::AsRawFd;
 
 use smoltcp::iface::{Config, Interface, SocketSet};
 use smoltcp::phy::{wait as phy_wait, Device, Medium};
@@ -4,8 +4,8 @@ use byteorder::{ByteOrder, NetworkEndian};
 use smoltcp::iface::{Interface, SocketSet};
 use std::cmp;
 use std::collections::HashMap;
-use std::os::unix::io::AsRawFd;
 use std::str::FromStr;
+use utils::AsRawFd;
 
 use smoltcp::iface::Config;
 use smoltcp::phy::wait as phy_wait;
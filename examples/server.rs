@@ -2,7 +2,7 @@ mod utils;
 
 use log::debug;
 use std::fmt::Write;
-use std::os::unix::io::AsRawFd;
+use utils::AsRawFd;
 
 use smoltcp::iface::{Config, Interface, SocketSet};
 use smoltcp::phy::{wait as phy_wait, Device, Medium};
@@ -18,6 +18,24 @@ use smoltcp::phy::{Device, FaultInjector, Medium, Tracer};
 use smoltcp::phy::{PcapMode, PcapWriter};
 use smoltcp::time::{Duration, Instant};
 
+/// The file descriptor of the TUN/TAP interface, passed to `phy::wait`.
+#[cfg(unix)]
+#[allow(unused_imports)]
+pub use std::os::unix::io::AsRawFd;
+
+/// On Windows, the examples wait on the read event of the Wintun adapter instead.
+#[cfg(windows)]
+pub trait AsRawFd {
+    fn as_raw_fd(&self) -> std::os::windows::io::RawHandle;
+}
+
+#[cfg(windows)]
+impl<T: std::os::windows::io::AsRawHandle> AsRawFd for T {
+    fn as_raw_fd(&self) -> std::os::windows::io::RawHandle {
+        self.as_raw_handle()
+    }
+}
+
 #[cfg(feature = "log")]
 pub fn setup_logging_with_clock<F>(filter: &str, since_startup: F)
 where
@@ -108,7 +126,12 @@ pub fn parse_tuntap_options(matches: &mut Matches) -> TunTapInterface {
 }
 
 pub fn add_middleware_options(opts: &mut Options, _free: &mut [&str]) {
-    opts.optopt("", "pcap", "Write a packet capture file", "FILE");
+    opts.optopt(
+        "",
+        "pcap",
+        "Write a packet capture file (pcapng if named *.pcapng)",
+        "FILE",
+    );
     opts.optopt(
         "",
         "drop-chance",
@@ -147,6 +170,30 @@ pub fn add_middleware_options(opts: &mut Options, _free: &mut [&str]) {
         "Sets the interval for rate limiting (ms)",
         "RATE",
     );
+    opts.optopt(
+        "",
+        "reorder-chance",
+        "Chance of reordering a received packet (%)",
+        "CHANCE",
+    );
+    opts.optopt(
+        "",
+        "reorder-window",
+        "Time a reordered packet is held back for (ms)",
+        "TIME",
+    );
+    opts.optopt(
+        "",
+        "duplicate-chance",
+        "Chance of duplicating a received packet (%)",
+        "CHANCE",
+    );
+    opts.optopt(
+        "",
+        "jitter",
+        "Maximum latency added to a received packet (ms)",
+        "TIME",
+    );
 }
 
 pub fn parse_middleware_options<D>(
@@ -181,8 +228,28 @@ where
         .opt_str("shaping-interval")
         .map(|s| u64::from_str(&s).unwrap())
         .unwrap_or(0);
+    let reorder_chance = matches
+        .opt_str("reorder-chance")
+        .map(|s| u8::from_str(&s).unwrap())
+        .unwrap_or(0);
+    let reorder_window = matches
+        .opt_str("reorder-window")
+        .map(|s| u64::from_str(&s).unwrap())
+        .unwrap_or(0);
+    let duplicate_chance = matches
+        .opt_str("duplicate-chance")
+        .map(|s| u8::from_str(&s).unwrap())
+        .unwrap_or(0);
+    let jitter = matches
+        .opt_str("jitter")
+        .map(|s| u64::from_str(&s).unwrap())
+        .unwrap_or(0);
 
-    let pcap_writer: Box<dyn io::Write> = match matches.opt_str("pcap") {
+    let pcap_filename = matches.opt_str("pcap");
+    let pcapng = pcap_filename
+        .as_ref()
+        .map_or(false, |name| name.ends_with(".pcapng"));
+    let pcap_writer: Box<dyn io::Write> = match pcap_filename {
         Some(pcap_filename) => Box::new(File::create(pcap_filename).expect("cannot open file")),
         None => Box::new(io::sink()),
     };
@@ -192,15 +259,16 @@ where
         .unwrap()
         .subsec_nanos();
 
-    let device = PcapWriter::new(
-        device,
-        pcap_writer,
-        if loopback {
-            PcapMode::TxOnly
-        } else {
-            PcapMode::Both
-        },
-    );
+    let pcap_mode = if loopback {
+        PcapMode::TxOnly
+    } else {
+        PcapMode::Both
+    };
+    let device = if pcapng {
+        PcapWriter::new_ng(device, pcap_writer, pcap_mode, None)
+    } else {
+        PcapWriter::new(device, pcap_writer, pcap_mode)
+    };
 
     let device = Tracer::new(device, |_timestamp, _printer| {
         #[cfg(feature = "log")]
@@ -214,5 +282,9 @@ where
     device.set_max_tx_rate(tx_rate_limit);
     device.set_max_rx_rate(rx_rate_limit);
     device.set_bucket_interval(Duration::from_millis(shaping_interval));
+    device.set_reorder_chance(reorder_chance);
+    device.set_reorder_window(Duration::from_millis(reorder_window));
+    device.set_duplicate_chance(duplicate_chance);
+    device.set_max_jitter(Duration::from_millis(jitter));
     device
 }
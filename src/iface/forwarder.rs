@@ -0,0 +1,230 @@
+use core::cmp::Reverse;
+use heapless::Vec;
+
+use super::interface::MAX_IP_ADDR_COUNT;
+#[cfg(feature = "proto-ipv4")]
+use super::nat::Nat44;
+use super::route::{Route, MAX_ROUTE_COUNT};
+use super::{Interface, SocketSet};
+use crate::phy::Device;
+use crate::storage;
+use crate::time::{Duration, Instant};
+#[cfg(feature = "proto-ipv4")]
+use crate::wire::Ipv4Address;
+use crate::wire::{IpAddress, IpCidr, IpVersion};
+
+pub const MAX_PORT_COUNT: usize = 4;
+
+/// A packet waiting to be forwarded, stamped with the time it was received.
+pub type ForwardPacketMetadata = storage::PacketMetadata<Instant>;
+
+/// The queue of packets waiting to be forwarded out of a port.
+pub type ForwardPacketBuffer<'a> = storage::PacketBuffer<'a, Instant>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PortTableFull;
+
+/// An interface attached to a [`Forwarder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PortId(pub(crate) usize);
+
+#[derive(Debug)]
+struct Port<'a> {
+    queue: ForwardPacketBuffer<'a>,
+    // A copy of the interface configuration, taken every time the port is polled.
+    ip_addrs: Vec<IpCidr, MAX_IP_ADDR_COUNT>,
+    routes: Vec<Route, MAX_ROUTE_COUNT>,
+    #[cfg(feature = "proto-ipv4")]
+    ipv4_mtu: usize,
+    #[cfg(feature = "proto-ipv6")]
+    ipv6_mtu: usize,
+}
+
+/// Forward IP packets between several interfaces.
+///
+/// Each interface is attached to the forwarder as a *port*, with a queue for the packets
+/// waiting to be sent out of it. Instead of calling [`Interface::poll`], poll every
+/// interface through [`Forwarder::poll`], with the same [`SocketSet`]:
+///
+/// ```rust,ignore
+/// let lan = forwarder.add_port(ForwardPacketBuffer::new(lan_meta, lan_payload))?;
+/// let wan = forwarder.add_port(ForwardPacketBuffer::new(wan_meta, wan_payload))?;
+/// loop {
+///     let now = Instant::now();
+///     forwarder.poll(lan, now, &mut lan_iface, &mut lan_device, &mut sockets);
+///     forwarder.poll(wan, now, &mut wan_iface, &mut wan_device, &mut sockets);
+/// }
+/// ```
+///
+/// A unicast packet received on a port and not addressed to its interface is sent out of
+/// the port whose addresses or routes match its destination best: longest prefix first,
+/// then lowest metric, then the port added first. Its TTL or hop limit is decremented on
+/// the way. When it runs out, or when no port can reach the destination, an ICMP error
+/// is sent back to the source instead. Packets that are too large for the egress link,
+/// or whose next hop cannot be resolved in time, are dropped.
+///
+/// IPv6 link-local packets are never forwarded, and IEEE 802.15.4 interfaces cannot be
+/// ports. IPv4 connections can be translated with a [`Nat44`] as they leave through
+/// one of the ports.
+#[derive(Debug)]
+pub struct Forwarder<'a> {
+    ports: Vec<Port<'a>, MAX_PORT_COUNT>,
+    #[cfg(feature = "proto-ipv4")]
+    nat: Option<Nat44>,
+}
+
+impl<'a> Forwarder<'a> {
+    /// How long a packet may wait for its next hop to be resolved.
+    pub(crate) const QUEUE_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Create a forwarder without any port.
+    pub fn new() -> Self {
+        Self {
+            ports: Vec::new(),
+            #[cfg(feature = "proto-ipv4")]
+            nat: None,
+        }
+    }
+
+    /// Add a port, queueing the packets to send out of it in `queue`.
+    pub fn add_port(&mut self, queue: ForwardPacketBuffer<'a>) -> Result<PortId, PortTableFull> {
+        let port = Port {
+            queue,
+            ip_addrs: Vec::new(),
+            routes: Vec::new(),
+            #[cfg(feature = "proto-ipv4")]
+            ipv4_mtu: 0,
+            #[cfg(feature = "proto-ipv6")]
+            ipv6_mtu: 0,
+        };
+        self.ports.push(port).map_err(|_| PortTableFull)?;
+        Ok(PortId(self.ports.len() - 1))
+    }
+
+    /// Poll the interface attached to `port`.
+    ///
+    /// This does everything [`Interface::poll`] does, and in addition forwards the
+    /// packets received on the interface, and sends the packets forwarded to it.
+    /// Returns whether any packet was processed or emitted.
+    ///
+    /// # Panics
+    /// This function panics if `port` was not returned by this forwarder.
+    pub fn poll<D>(
+        &mut self,
+        port: PortId,
+        timestamp: Instant,
+        iface: &mut Interface,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+    ) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        self.sync(port, iface);
+        iface.poll_forwarding(timestamp, device, sockets, self, port)
+    }
+
+    /// Return the network address translator, if any.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn nat(&self) -> Option<&Nat44> {
+        self.nat.as_ref()
+    }
+
+    /// Return the network address translator mutably, if any.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn nat_mut(&mut self) -> Option<&mut Nat44> {
+        self.nat.as_mut()
+    }
+
+    /// Set the network address translator. Setting it to `None` disables translation.
+    ///
+    /// # Panics
+    /// This function panics if the outside port of `nat` was not returned by this
+    /// forwarder.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn set_nat(&mut self, nat: Option<Nat44>) {
+        if let Some(nat) = &nat {
+            assert!(nat.outside().0 < self.ports.len());
+        }
+        self.nat = nat;
+    }
+
+    /// Return the first IPv4 address of the interface attached to `port`.
+    #[cfg(feature = "proto-ipv4")]
+    pub(crate) fn ipv4_addr(&self, port: PortId) -> Option<Ipv4Address> {
+        self.ports[port.0]
+            .ip_addrs
+            .iter()
+            .find_map(|cidr| match cidr {
+                IpCidr::Ipv4(cidr) => Some(cidr.address()),
+                #[allow(unreachable_patterns)]
+                _ => None,
+            })
+    }
+
+    pub(crate) fn sync(&mut self, port: PortId, iface: &Interface) {
+        let port = &mut self.ports[port.0];
+        port.ip_addrs.clear();
+        // Both tables have the same capacity as the ones of the interface.
+        let _ = port.ip_addrs.extend_from_slice(iface.ip_addrs());
+        port.routes.clear();
+        for route in iface.routes().iter() {
+            let _ = port.routes.push(*route);
+        }
+        #[cfg(feature = "proto-ipv4")]
+        {
+            port.ipv4_mtu = iface.ip_mtu_of(IpVersion::Ipv4);
+        }
+        #[cfg(feature = "proto-ipv6")]
+        {
+            port.ipv6_mtu = iface.ip_mtu_of(IpVersion::Ipv6);
+        }
+    }
+
+    /// Return the MTU of the IP packets of a version sent out of `port`.
+    pub(crate) fn ip_mtu(&self, port: PortId, version: IpVersion) -> usize {
+        let port = &self.ports[port.0];
+        match version {
+            #[cfg(feature = "proto-ipv4")]
+            IpVersion::Ipv4 => port.ipv4_mtu,
+            #[cfg(feature = "proto-ipv6")]
+            IpVersion::Ipv6 => port.ipv6_mtu,
+        }
+    }
+
+    /// Return the port to send packets to `addr` out of.
+    pub(crate) fn lookup(&self, addr: &IpAddress, timestamp: Instant) -> Option<PortId> {
+        self.ports
+            .iter()
+            .enumerate()
+            .flat_map(|(i, port)| {
+                let on_link = port
+                    .ip_addrs
+                    .iter()
+                    .filter(|cidr| cidr.contains_addr(addr))
+                    .map(|cidr| (cidr.prefix_len(), 0));
+                let routed = port
+                    .routes
+                    .iter()
+                    .filter(|route| !route.is_expired(timestamp) && route.cidr.contains_addr(addr))
+                    .map(|route| (route.cidr.prefix_len(), route.metric));
+                on_link
+                    .chain(routed)
+                    .map(move |(prefix_len, metric)| (prefix_len, Reverse(metric), Reverse(i)))
+            })
+            .max()
+            .map(|(_, _, Reverse(i))| PortId(i))
+    }
+
+    pub(crate) fn queue_mut(&mut self, port: PortId) -> &mut ForwardPacketBuffer<'a> {
+        &mut self.ports[port.0].queue
+    }
+}
+
+impl<'a> Default for Forwarder<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -7,25 +7,35 @@ use managed::{ManagedMap, ManagedSlice};
 use crate::storage::Assembler;
 use crate::time::{Duration, Instant};
 
-// TODO: make configurable.
-const BUFFER_SIZE: usize = 1500;
+/// The default size of a reassembled packet.
+pub(crate) const BUFFER_SIZE: usize = 1500;
 
-#[cfg(feature = "alloc")]
-type Buffer = alloc::vec::Vec<u8>;
-#[cfg(not(feature = "alloc"))]
-type Buffer = [u8; BUFFER_SIZE];
+/// The default number of packets reassembled at the same time.
+pub(crate) const PACKET_ASSEMBLER_COUNT: usize = 4;
 
-const PACKET_ASSEMBLER_COUNT: usize = 4;
+/// The largest number of packets that can be reassembled at the same time.
+pub(crate) const MAX_PACKET_ASSEMBLER_COUNT: usize = 16;
 
 /// Problem when assembling: something was out of bounds.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct AssemblerError;
 
-/// Packet assembler is full
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+/// Occupancy of a set of packet assemblers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub struct AssemblerFullError;
+pub struct ReassemblyStats {
+    /// Number of packets that can be reassembled at the same time.
+    pub capacity: usize,
+    /// Number of packets being reassembled.
+    pub in_use: usize,
+    /// Number of octets received for the packets being reassembled.
+    pub buffered: usize,
+    /// Number of packets given up on to make room for a new one.
+    pub evicted: u32,
+    /// Number of packets given up on because their fragments did not all arrive in time.
+    pub expired: u32,
+}
 
 /// Holds different fragments of one packet, used for assembling fragmented packets.
 ///
@@ -35,7 +45,8 @@ pub struct AssemblerFullError;
 #[derive(Debug)]
 pub struct PacketAssembler<K> {
     key: Option<K>,
-    buffer: Buffer,
+    buffer: ManagedSlice<'static, u8>,
+    max_size: usize,
 
     assembler: Assembler,
     total_size: Option<usize>,
@@ -44,15 +55,29 @@ pub struct PacketAssembler<K> {
 
 impl<K> PacketAssembler<K> {
     /// Create a new empty buffer for fragments.
+    #[cfg(feature = "alloc")]
     pub fn new() -> Self {
-        Self {
-            key: None,
+        Self::with_max_size(BUFFER_SIZE)
+    }
 
-            #[cfg(feature = "alloc")]
-            buffer: Buffer::new(),
-            #[cfg(not(feature = "alloc"))]
-            buffer: [0u8; BUFFER_SIZE],
+    /// Create a new empty buffer for fragments of packets of up to `max_size` octets,
+    /// allocated as fragments arrive.
+    #[cfg(feature = "alloc")]
+    pub fn with_max_size(max_size: usize) -> Self {
+        Self::with_buffer(alloc::vec::Vec::new(), max_size)
+    }
+
+    /// Create a new empty buffer for fragments, reassembling packets in `buffer`.
+    pub fn with_storage(buffer: &'static mut [u8]) -> Self {
+        let max_size = buffer.len();
+        Self::with_buffer(buffer, max_size)
+    }
 
+    fn with_buffer<B: Into<ManagedSlice<'static, u8>>>(buffer: B, max_size: usize) -> Self {
+        Self {
+            key: None,
+            buffer: buffer.into(),
+            max_size,
             assembler: Assembler::new(),
             total_size: None,
             expires_at: Instant::ZERO,
@@ -74,14 +99,15 @@ impl<K> PacketAssembler<K> {
             }
         }
 
-        #[cfg(not(feature = "alloc"))]
-        if self.buffer.len() < size {
+        if self.max_size < size {
             return Err(AssemblerError);
         }
 
         #[cfg(feature = "alloc")]
-        if self.buffer.len() < size {
-            self.buffer.resize(size, 0);
+        if let ManagedSlice::Owned(buffer) = &mut self.buffer {
+            if buffer.len() < size {
+                buffer.resize(size, 0);
+            }
         }
 
         self.total_size = Some(size);
@@ -122,14 +148,15 @@ impl<K> PacketAssembler<K> {
     /// - Returns [`Error::PacketAssemblerBufferTooSmall`] when trying to add data into the buffer at a non-existing
     /// place.
     pub(crate) fn add(&mut self, data: &[u8], offset: usize) -> Result<(), AssemblerError> {
-        #[cfg(not(feature = "alloc"))]
-        if self.buffer.len() < offset + data.len() {
+        if self.max_size < offset + data.len() {
             return Err(AssemblerError);
         }
 
         #[cfg(feature = "alloc")]
-        if self.buffer.len() < offset + data.len() {
-            self.buffer.resize(offset + data.len(), 0);
+        if let ManagedSlice::Owned(buffer) = &mut self.buffer {
+            if buffer.len() < offset + data.len() {
+                buffer.resize(offset + data.len(), 0);
+            }
         }
 
         let len = data.len();
@@ -172,47 +199,92 @@ impl<K> PacketAssembler<K> {
 /// Set holding multiple [`PacketAssembler`].
 #[derive(Debug)]
 pub struct PacketAssemblerSet<K: Eq + Copy> {
-    assemblers: [PacketAssembler<K>; PACKET_ASSEMBLER_COUNT],
+    assemblers: heapless::Vec<PacketAssembler<K>, MAX_PACKET_ASSEMBLER_COUNT>,
+    evicted: u32,
+    expired: u32,
 }
 
 impl<K: Eq + Copy> PacketAssemblerSet<K> {
     /// Create a new set of packet assemblers.
+    #[cfg(feature = "alloc")]
     pub fn new() -> Self {
-        Self {
-            // TODO: support any PACKET_ASSEMBLER_COUNT
-            assemblers: [
-                PacketAssembler::new(),
-                PacketAssembler::new(),
-                PacketAssembler::new(),
-                PacketAssembler::new(),
-            ],
-        }
+        Self::with_config(PACKET_ASSEMBLER_COUNT, BUFFER_SIZE)
     }
 
-    /// Get a [`PacketAssembler`] for a specific key.
+    /// Create a set of `count` packet assemblers, for packets of up to `max_size` octets.
     ///
-    /// If it doesn't exist, it is created, with the `expires_at` timestamp.
+    /// # Panics
+    /// This function panics if `count` is zero or larger than [`MAX_PACKET_ASSEMBLER_COUNT`].
+    #[cfg(feature = "alloc")]
+    pub fn with_config(count: usize, max_size: usize) -> Self {
+        assert!(count > 0 && count <= MAX_PACKET_ASSEMBLER_COUNT);
+        let mut assemblers = heapless::Vec::new();
+        for _ in 0..count {
+            let _ = assemblers.push(PacketAssembler::with_max_size(max_size));
+        }
+        Self::with_assemblers(assemblers)
+    }
+
+    /// Create a set of `count` packet assemblers, sharing `storage` equally for their
+    /// buffers.
     ///
-    /// If the assembler set is full, in which case an error is returned.
-    pub(crate) fn get(
-        &mut self,
-        key: &K,
-        expires_at: Instant,
-    ) -> Result<&mut PacketAssembler<K>, AssemblerFullError> {
-        let mut empty_slot = None;
-        for slot in &mut self.assemblers {
-            if slot.key.as_ref() == Some(key) {
-                return Ok(slot);
-            }
-            if slot.is_free() {
-                empty_slot = Some(slot)
-            }
+    /// # Panics
+    /// This function panics if `count` is zero or larger than [`MAX_PACKET_ASSEMBLER_COUNT`].
+    pub fn with_storage(count: usize, storage: &'static mut [u8]) -> Self {
+        assert!(count > 0 && count <= MAX_PACKET_ASSEMBLER_COUNT);
+        let size = storage.len() / count;
+        let mut rest = storage;
+        let mut assemblers = heapless::Vec::new();
+        for _ in 0..count {
+            let (buffer, tail) = core::mem::take(&mut rest).split_at_mut(size);
+            rest = tail;
+            let _ = assemblers.push(PacketAssembler::with_storage(buffer));
+        }
+        Self::with_assemblers(assemblers)
+    }
+
+    fn with_assemblers(
+        assemblers: heapless::Vec<PacketAssembler<K>, MAX_PACKET_ASSEMBLER_COUNT>,
+    ) -> Self {
+        Self {
+            assemblers,
+            evicted: 0,
+            expired: 0,
         }
+    }
 
-        let slot = empty_slot.ok_or(AssemblerFullError)?;
+    /// Get a [`PacketAssembler`] for a specific key.
+    ///
+    /// If it doesn't exist, it is created, with the `expires_at` timestamp. If the
+    /// assembler set is full, the assembler closest to expiry is given up on and reused.
+    pub(crate) fn get(&mut self, key: &K, expires_at: Instant) -> &mut PacketAssembler<K> {
+        let i = match self
+            .assemblers
+            .iter()
+            .position(|slot| slot.key.as_ref() == Some(key))
+        {
+            Some(i) => return &mut self.assemblers[i],
+            None => match self.assemblers.iter().position(|slot| slot.is_free()) {
+                Some(i) => i,
+                None => {
+                    net_debug!("frag assembler: full, evicting the oldest packet");
+                    self.evicted = self.evicted.wrapping_add(1);
+                    let (i, _) = self
+                        .assemblers
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, slot)| slot.expires_at)
+                        .unwrap();
+                    self.assemblers[i].reset();
+                    i
+                }
+            },
+        };
+
+        let slot = &mut self.assemblers[i];
         slot.key = Some(*key);
         slot.expires_at = expires_at;
-        Ok(slot)
+        slot
     }
 
     /// Remove all [`PacketAssembler`]s that are expired.
@@ -220,9 +292,25 @@ impl<K: Eq + Copy> PacketAssemblerSet<K> {
         for frag in &mut self.assemblers {
             if !frag.is_free() && frag.expires_at < timestamp {
                 frag.reset();
+                self.expired = self.expired.wrapping_add(1);
             }
         }
     }
+
+    /// Return the occupancy of the set.
+    pub fn stats(&self) -> ReassemblyStats {
+        let in_use = self.assemblers.iter().filter(|slot| !slot.is_free());
+        ReassemblyStats {
+            capacity: self.assemblers.len(),
+            in_use: in_use.clone().count(),
+            buffered: in_use
+                .flat_map(|slot| slot.assembler.iter_data(0))
+                .map(|(start, end)| end - start)
+                .sum(),
+            evicted: self.evicted,
+            expired: self.expired,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -285,17 +373,54 @@ mod tests {
 
         let mut set = PacketAssemblerSet::new();
 
-        assert!(set.get(&key, Instant::ZERO).is_ok());
+        set.get(&key, Instant::ZERO);
+        assert_eq!(set.stats().in_use, 1);
     }
 
     #[test]
     fn packet_assembler_set_full() {
-        let mut set = PacketAssemblerSet::new();
-        set.get(&Key { id: 0 }, Instant::ZERO).unwrap();
-        set.get(&Key { id: 1 }, Instant::ZERO).unwrap();
-        set.get(&Key { id: 2 }, Instant::ZERO).unwrap();
-        set.get(&Key { id: 3 }, Instant::ZERO).unwrap();
-        assert!(set.get(&Key { id: 4 }, Instant::ZERO).is_err());
+        let mut set = PacketAssemblerSet::with_config(2, 8);
+        set.get(&Key { id: 0 }, Instant::from_millis(2))
+            .add(b"ab", 0)
+            .unwrap();
+        set.get(&Key { id: 1 }, Instant::from_millis(1))
+            .add(b"cd", 0)
+            .unwrap();
+        assert_eq!(
+            set.stats(),
+            ReassemblyStats {
+                capacity: 2,
+                in_use: 2,
+                buffered: 4,
+                evicted: 0,
+                expired: 0,
+            }
+        );
+
+        // The packet closest to expiry makes room for the new one.
+        set.get(&Key { id: 2 }, Instant::from_millis(3));
+        assert_eq!(set.stats().evicted, 1);
+        assert_eq!(set.stats().buffered, 2);
+        assert!(set.get(&Key { id: 0 }, Instant::ZERO).add(b"ef", 2).is_ok());
+        assert!(set.get(&Key { id: 0 }, Instant::ZERO).add(b"x", 8).is_err());
+
+        set.remove_expired(Instant::from_millis(4));
+        assert_eq!(set.stats().in_use, 0);
+        assert_eq!(set.stats().expired, 2);
+    }
+
+    #[test]
+    fn packet_assembler_set_storage() {
+        let storage = std::boxed::Box::leak(std::boxed::Box::new([0; 10]));
+        let mut set = PacketAssemblerSet::with_storage(2, storage);
+        assert_eq!(set.stats().capacity, 2);
+
+        // Each assembler gets half of the storage.
+        let assr = set.get(&Key { id: 0 }, Instant::ZERO);
+        assert!(assr.add(b"abcde", 0).is_ok());
+        assert!(assr.add(b"f", 5).is_err());
+        let assr = set.get(&Key { id: 1 }, Instant::ZERO);
+        assert!(assr.set_total_size(6).is_err());
     }
 
     #[test]
@@ -303,33 +428,33 @@ mod tests {
         let mut set = PacketAssemblerSet::new();
 
         let key = Key { id: 0 };
-        let assr = set.get(&key, Instant::ZERO).unwrap();
+        let assr = set.get(&key, Instant::ZERO);
         assert_eq!(assr.assemble(), None);
         assr.set_total_size(0).unwrap();
         assr.assemble().unwrap();
 
         // Test that `.assemble()` effectively deletes it.
-        let assr = set.get(&key, Instant::ZERO).unwrap();
+        let assr = set.get(&key, Instant::ZERO);
         assert_eq!(assr.assemble(), None);
         assr.set_total_size(0).unwrap();
         assr.assemble().unwrap();
 
         let key = Key { id: 1 };
-        let assr = set.get(&key, Instant::ZERO).unwrap();
+        let assr = set.get(&key, Instant::ZERO);
         assr.set_total_size(0).unwrap();
         assr.assemble().unwrap();
 
         let key = Key { id: 2 };
-        let assr = set.get(&key, Instant::ZERO).unwrap();
+        let assr = set.get(&key, Instant::ZERO);
         assr.set_total_size(0).unwrap();
         assr.assemble().unwrap();
 
         let key = Key { id: 2 };
-        let assr = set.get(&key, Instant::ZERO).unwrap();
+        let assr = set.get(&key, Instant::ZERO);
         assr.set_total_size(2).unwrap();
         assr.add(&[0x00], 0).unwrap();
         assert_eq!(assr.assemble(), None);
-        let assr = set.get(&key, Instant::ZERO).unwrap();
+        let assr = set.get(&key, Instant::ZERO);
         assr.add(&[0x01], 1).unwrap();
         assert_eq!(assr.assemble(), Some(&[0x00, 0x01][..]));
     }
@@ -0,0 +1,240 @@
+use heapless::LinearMap;
+
+use crate::time::{Duration, Instant};
+use crate::wire::IpAddress;
+
+#[cfg(not(test))]
+pub const HOST_CACHE_SIZE: usize = 8;
+#[cfg(test)]
+pub const HOST_CACHE_SIZE: usize = 3;
+
+/// Path metrics learned from a finished TCP connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HostMetrics {
+    /// Smoothed round-trip time, in milliseconds.
+    pub rtt: u32,
+    /// Round-trip time variation, in milliseconds.
+    pub rttvar: u32,
+    /// Slow-start threshold, in octets, if the connection ever lowered it.
+    pub ssthresh: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Entry {
+    metrics: HostMetrics,
+    expires_at: Instant,
+}
+
+/// A cache of TCP path metrics, keyed by remote address.
+///
+/// When a TCP connection closes after having measured the round-trip time to its remote
+/// endpoint, the estimate is stored here, along with the slow-start threshold if congestion
+/// was detected. A later connection to or from the same address starts from the cached
+/// values instead of the conservative defaults, so that its first retransmission timeout
+/// fits the path and it does not overshoot the capacity found by the previous connection.
+#[derive(Debug)]
+pub struct HostCache {
+    storage: LinearMap<IpAddress, Entry, HOST_CACHE_SIZE>,
+    enabled: bool,
+}
+
+impl HostCache {
+    /// Host cache entry lifetime.
+    pub(crate) const ENTRY_LIFETIME: Duration = Duration::from_secs(3_600);
+
+    /// Create an empty, enabled cache.
+    pub fn new() -> Self {
+        Self {
+            storage: LinearMap::new(),
+            enabled: true,
+        }
+    }
+
+    /// Return whether connections record and use cached metrics.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable the cache. Disabling it also forgets all entries.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    /// Record the metrics measured by a connection to `addr`.
+    ///
+    /// If the address is already known, the new measurement is averaged with the
+    /// cached one. When the cache is full, the entry closest to expiry is evicted.
+    pub fn fill(&mut self, addr: IpAddress, metrics: HostMetrics, timestamp: Instant) {
+        if !self.enabled {
+            return;
+        }
+
+        let metrics = match self.lookup(&addr, timestamp) {
+            Some(old) => HostMetrics {
+                rtt: (old.rtt + metrics.rtt) / 2,
+                rttvar: (old.rttvar + metrics.rttvar) / 2,
+                ssthresh: match (old.ssthresh, metrics.ssthresh) {
+                    (Some(old), Some(new)) => Some((old + new) / 2),
+                    (old, new) => new.or(old),
+                },
+            },
+            None => metrics,
+        };
+        let entry = Entry {
+            metrics,
+            expires_at: timestamp + Self::ENTRY_LIFETIME,
+        };
+
+        if let Err((addr, entry)) = self.storage.insert(addr, entry) {
+            let old_addr = *self
+                .storage
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .expect("empty host cache storage")
+                .0;
+            self.storage.remove(&old_addr);
+            net_trace!("host cache: evicted {}", old_addr);
+            // We've just made room.
+            let _ = self.storage.insert(addr, entry);
+        }
+        net_trace!(
+            "host cache: {} rtt={} rttvar={} ssthresh={:?}",
+            addr,
+            metrics.rtt,
+            metrics.rttvar,
+            metrics.ssthresh
+        );
+    }
+
+    /// Return the metrics cached for `addr`, unless they have expired.
+    pub fn lookup(&self, addr: &IpAddress, timestamp: Instant) -> Option<HostMetrics> {
+        match self.storage.get(addr) {
+            Some(entry) if entry.expires_at > timestamp => Some(entry.metrics),
+            _ => None,
+        }
+    }
+
+    /// Forget the metrics cached for `addr`.
+    pub fn remove(&mut self, addr: &IpAddress) -> Option<HostMetrics> {
+        self.storage.remove(addr).map(|entry| entry.metrics)
+    }
+
+    /// Forget all cached metrics.
+    pub fn clear(&mut self) {
+        self.storage.clear();
+    }
+}
+
+impl Default for HostCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::wire::ip::test::{MOCK_IP_ADDR_1, MOCK_IP_ADDR_2, MOCK_IP_ADDR_3, MOCK_IP_ADDR_4};
+
+    const METRICS_A: HostMetrics = HostMetrics {
+        rtt: 100,
+        rttvar: 20,
+        ssthresh: None,
+    };
+    const METRICS_B: HostMetrics = HostMetrics {
+        rtt: 300,
+        rttvar: 40,
+        ssthresh: Some(4000),
+    };
+
+    #[test]
+    fn test_fill_average() {
+        let mut cache = HostCache::new();
+        let addr = MOCK_IP_ADDR_1;
+
+        assert_eq!(cache.lookup(&addr, Instant::from_millis(0)), None);
+        cache.fill(addr, METRICS_A, Instant::from_millis(0));
+        assert_eq!(
+            cache.lookup(&addr, Instant::from_millis(0)),
+            Some(METRICS_A)
+        );
+        cache.fill(addr, METRICS_B, Instant::from_millis(0));
+        assert_eq!(
+            cache.lookup(&addr, Instant::from_millis(0)),
+            Some(HostMetrics {
+                rtt: 200,
+                rttvar: 30,
+                ssthresh: Some(4000),
+            })
+        );
+        cache.fill(
+            addr,
+            HostMetrics {
+                ssthresh: Some(2000),
+                ..METRICS_B
+            },
+            Instant::from_millis(0),
+        );
+        assert_eq!(
+            cache.lookup(&addr, Instant::from_millis(0)),
+            Some(HostMetrics {
+                rtt: 250,
+                rttvar: 35,
+                ssthresh: Some(3000),
+            })
+        );
+    }
+
+    #[test]
+    fn test_expire() {
+        let mut cache = HostCache::new();
+        let addr = MOCK_IP_ADDR_1;
+
+        cache.fill(addr, METRICS_A, Instant::from_millis(0));
+        assert_eq!(
+            cache.lookup(&addr, Instant::from_millis(0) + HostCache::ENTRY_LIFETIME),
+            None
+        );
+        // An expired entry is replaced rather than averaged.
+        cache.fill(
+            addr,
+            METRICS_B,
+            Instant::from_millis(0) + HostCache::ENTRY_LIFETIME,
+        );
+        assert_eq!(
+            cache.lookup(&addr, Instant::from_millis(0) + HostCache::ENTRY_LIFETIME),
+            Some(METRICS_B)
+        );
+    }
+
+    #[test]
+    fn test_evict() {
+        let mut cache = HostCache::new();
+
+        cache.fill(MOCK_IP_ADDR_1, METRICS_A, Instant::from_millis(100));
+        cache.fill(MOCK_IP_ADDR_2, METRICS_A, Instant::from_millis(50));
+        cache.fill(MOCK_IP_ADDR_3, METRICS_A, Instant::from_millis(200));
+        cache.fill(MOCK_IP_ADDR_4, METRICS_B, Instant::from_millis(300));
+
+        let now = Instant::from_millis(300);
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_2, now), None);
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_4, now), Some(METRICS_B));
+    }
+
+    #[test]
+    fn test_disable() {
+        let mut cache = HostCache::new();
+        let addr = MOCK_IP_ADDR_1;
+
+        cache.fill(addr, METRICS_A, Instant::from_millis(0));
+        cache.set_enabled(false);
+        assert_eq!(cache.lookup(&addr, Instant::from_millis(0)), None);
+        cache.fill(addr, METRICS_A, Instant::from_millis(0));
+        assert_eq!(cache.lookup(&addr, Instant::from_millis(0)), None);
+    }
+}
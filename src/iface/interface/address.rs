@@ -0,0 +1,456 @@
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+use super::EthernetPacket;
+#[cfg(all(
+    feature = "proto-ipv6",
+    any(feature = "medium-ethernet", feature = "medium-ieee802154")
+))]
+use super::IpPacket;
+use super::{DispatchError, Interface, InterfaceInner};
+use crate::phy::{Device, Medium};
+use crate::time::{Duration, Instant};
+use crate::wire::*;
+
+use core::result::Result;
+
+/// Maximum number of address events queued on an interface.
+pub(crate) const MAX_ADDRESS_EVENT_COUNT: usize = 8;
+
+/// Interval between Duplicate Address Detection probes, `RetransTimer` in
+/// [RFC 4861 § 10].
+///
+/// [RFC 4861 § 10]: https://www.rfc-editor.org/rfc/rfc4861#section-10
+const DAD_RETRANS_TIMER: Duration = Duration::from_secs(1);
+
+/// Number of Duplicate Address Detection probes, `DupAddrDetectTransmits` in
+/// [RFC 4862 § 5.1].
+///
+/// [RFC 4862 § 5.1]: https://www.rfc-editor.org/rfc/rfc4862#section-5.1
+const DAD_TRANSMITS: u8 = 1;
+
+/// Number of announcements sent by [`Interface::announce`], and the interval between
+/// them, `ANNOUNCE_NUM` and `ANNOUNCE_INTERVAL` in [RFC 5227 § 1.1].
+///
+/// [RFC 5227 § 1.1]: https://www.rfc-editor.org/rfc/rfc5227#section-1.1
+const ANNOUNCE_NUM: u8 = 2;
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The state of an address assigned to an interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AddressState {
+    /// Duplicate Address Detection is in progress. The address is neither used as a
+    /// source address nor does the interface answer for it.
+    Tentative,
+    /// The address can be used without restriction.
+    Preferred,
+    /// The address is still valid, but other addresses are preferred as the source
+    /// address of new communications.
+    Deprecated,
+}
+
+/// A change of the addresses assigned to an interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AddressEvent {
+    /// An address was assigned to the interface.
+    Added(IpCidr),
+    /// An address was removed from the interface.
+    Removed(IpCidr),
+    /// The state of an address changed.
+    StateChanged(IpCidr, AddressState),
+    /// Duplicate Address Detection found another node using the address, which was
+    /// removed from the interface.
+    Duplicate(IpCidr),
+    /// Another node claimed an address assigned to the interface, with an ARP packet or
+    /// a Neighbor Advertisement. The address is kept; it is up to the application to
+    /// defend it with [`Interface::announce`], or to give it up.
+    Conflict(IpCidr),
+}
+
+/// Error returned by [`Interface::add_ip_addr`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AddressError {
+    /// The address is not a unicast address.
+    Unaddressable,
+    /// The address is already assigned to the interface.
+    AlreadyAssigned,
+    /// The interface already has as many addresses as it can hold.
+    TableFull,
+}
+
+/// State kept for an address added with [`Interface::add_ip_addr`], or deprecated.
+///
+/// Addresses without an entry are preferred.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct AddressInfo {
+    state: AddressState,
+    /// Number of ARP announcements or Neighbor Solicitations left to send.
+    probes_left: u8,
+    /// When to send the next probe, or to finish Duplicate Address Detection.
+    next_probe_at: Instant,
+}
+
+impl Interface {
+    /// Assign an address to the interface.
+    ///
+    /// On Ethernet, an IPv4 address is announced with a gratuitous ARP request. An IPv6
+    /// address first goes through Duplicate Address Detection: it stays
+    /// [tentative](AddressState::Tentative) until no other node claimed it for a second,
+    /// and is removed with an [`AddressEvent::Duplicate`] event otherwise.
+    ///
+    /// The probes are sent by the next calls to [`poll`](Self::poll).
+    pub fn add_ip_addr(&mut self, cidr: IpCidr) -> Result<(), AddressError> {
+        let addr = cidr.address();
+        if !addr.is_unicast() {
+            return Err(AddressError::Unaddressable);
+        }
+        if self.inner.ip_addrs.iter().any(|c| c.address() == addr) {
+            return Err(AddressError::AlreadyAssigned);
+        }
+        self.inner
+            .ip_addrs
+            .push(cidr)
+            .map_err(|_| AddressError::TableFull)?;
+
+        let (state, probes_left) = match addr {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(_) => match self.inner.caps.medium {
+                #[cfg(feature = "medium-ethernet")]
+                Medium::Ethernet => (AddressState::Preferred, 1),
+                #[allow(unreachable_patterns)]
+                _ => (AddressState::Preferred, 0),
+            },
+            #[cfg(feature = "proto-ipv6")]
+            IpAddress::Ipv6(_) => match self.inner.caps.medium {
+                #[cfg(feature = "medium-ip")]
+                Medium::Ip => (AddressState::Preferred, 0),
+                // The router detects duplicates when the address is registered.
+                #[cfg(feature = "medium-ieee802154")]
+                Medium::Ieee802154 if self.inner.registration.is_some() => {
+                    (AddressState::Preferred, 0)
+                }
+                #[allow(unreachable_patterns)]
+                _ => (AddressState::Tentative, DAD_TRANSMITS),
+            },
+        };
+        let info = AddressInfo {
+            state,
+            probes_left,
+            next_probe_at: self.inner.now,
+        };
+        // NOTE(unwrap): the map holds as many entries as there are addresses.
+        self.inner.addr_info.insert(addr, info).unwrap();
+
+        net_debug!("iface: added address {} ({:?})", cidr, state);
+        self.inner.push_address_event(AddressEvent::Added(cidr));
+        Ok(())
+    }
+
+    /// Remove an address from the interface, returning it if it was assigned.
+    pub fn remove_ip_addr<T: Into<IpAddress>>(&mut self, addr: T) -> Option<IpCidr> {
+        self.inner
+            .remove_ip_addr(addr.into(), AddressEvent::Removed)
+    }
+
+    /// Return the state of an address assigned to the interface.
+    pub fn ip_addr_state<T: Into<IpAddress>>(&self, addr: T) -> Option<AddressState> {
+        let addr = addr.into();
+        self.inner
+            .ip_addrs
+            .iter()
+            .any(|cidr| cidr.address() == addr)
+            .then(|| self.inner.address_state(&addr))
+    }
+
+    /// Mark an address as deprecated, or as preferred again, e.g. when the preferred
+    /// lifetime of a prefix expires or is renewed.
+    ///
+    /// Returns `false` if the address is not assigned, or still tentative.
+    pub fn set_ip_addr_deprecated<T: Into<IpAddress>>(
+        &mut self,
+        addr: T,
+        deprecated: bool,
+    ) -> bool {
+        let addr = addr.into();
+        let cidr = match self
+            .inner
+            .ip_addrs
+            .iter()
+            .find(|cidr| cidr.address() == addr)
+        {
+            Some(cidr) => *cidr,
+            None => return false,
+        };
+        let state = match deprecated {
+            true => AddressState::Deprecated,
+            false => AddressState::Preferred,
+        };
+        let now = self.inner.now;
+        let info = match self.inner.addr_info.get_mut(&addr) {
+            Some(info) if info.state == AddressState::Tentative => return false,
+            Some(info) => info,
+            None => {
+                let info = AddressInfo {
+                    state: AddressState::Preferred,
+                    probes_left: 0,
+                    next_probe_at: now,
+                };
+                // NOTE(unwrap): the map holds as many entries as there are addresses.
+                self.inner.addr_info.insert(addr, info).unwrap();
+                self.inner.addr_info.get_mut(&addr).unwrap()
+            }
+        };
+        if info.state != state {
+            info.state = state;
+            self.inner
+                .push_address_event(AddressEvent::StateChanged(cidr, state));
+        }
+        true
+    }
+
+    /// Announce the addresses assigned to the interface to the other nodes of the link,
+    /// e.g. after the link came back up or to defend an address after a
+    /// [conflict](AddressEvent::Conflict).
+    ///
+    /// On Ethernet, IPv4 addresses are announced with gratuitous ARP requests; on
+    /// Ethernet and IEEE 802.15.4, IPv6 addresses with unsolicited Neighbor
+    /// Advertisements. Tentative and loopback addresses are not announced. The
+    /// announcements are sent by the next calls to [`poll`](Self::poll).
+    pub fn announce(&mut self) {
+        for i in 0..self.inner.ip_addrs.len() {
+            let addr = self.inner.ip_addrs[i].address();
+            let announced = match (addr, self.inner.caps.medium) {
+                #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+                (IpAddress::Ipv4(addr), Medium::Ethernet) => !addr.is_loopback(),
+                #[cfg(all(feature = "medium-ip", feature = "proto-ipv6"))]
+                (IpAddress::Ipv6(_), Medium::Ip) => false,
+                #[cfg(feature = "proto-ipv6")]
+                (IpAddress::Ipv6(addr), _) => !addr.is_loopback(),
+                #[allow(unreachable_patterns)]
+                _ => false,
+            };
+            if !announced || self.inner.is_tentative(&addr) {
+                continue;
+            }
+            self.inner.schedule_announcements(addr, ANNOUNCE_NUM);
+        }
+    }
+
+    /// Return the next change of the addresses assigned to the interface, if any.
+    ///
+    /// If the events are not polled, only the most recent ones are kept.
+    pub fn poll_address_event(&mut self) -> Option<AddressEvent> {
+        self.inner.addr_events.pop_front()
+    }
+
+    /// Send the pending announcements and Duplicate Address Detection probes, and
+    /// finish Duplicate Address Detection for addresses nobody else claimed.
+    pub(crate) fn address_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let now = self.inner.now;
+        let mut did_something = false;
+
+        for i in 0..self.inner.ip_addrs.len() {
+            let cidr = self.inner.ip_addrs[i];
+            let addr = cidr.address();
+            let info = match self.inner.addr_info.get_mut(&addr) {
+                Some(info) if info.next_probe_at <= now => info,
+                _ => continue,
+            };
+
+            if info.probes_left == 0 {
+                if info.state == AddressState::Tentative {
+                    info.state = AddressState::Preferred;
+                    net_debug!("iface: address {} is unique", addr);
+                    self.inner.push_address_event(AddressEvent::StateChanged(
+                        cidr,
+                        AddressState::Preferred,
+                    ));
+                    did_something = true;
+                }
+                continue;
+            }
+
+            let tx_token = match device.transmit(now) {
+                Some(tx_token) => tx_token,
+                None => return did_something,
+            };
+            let state = info.state;
+            info.probes_left -= 1;
+            info.next_probe_at = now
+                + match state {
+                    AddressState::Tentative => DAD_RETRANS_TIMER,
+                    _ => ANNOUNCE_INTERVAL,
+                };
+
+            let result: Result<(), DispatchError> = match addr {
+                #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+                IpAddress::Ipv4(addr) => {
+                    net_debug!("iface: announcing address {}", addr);
+                    let arp_repr = ArpRepr::EthernetIpv4 {
+                        operation: ArpOperation::Request,
+                        source_hardware_addr: self.inner.hardware_addr.unwrap().ethernet_or_panic(),
+                        source_protocol_addr: addr,
+                        target_hardware_addr: EthernetAddress::BROADCAST,
+                        target_protocol_addr: addr,
+                    };
+                    self.inner
+                        .dispatch(tx_token, EthernetPacket::Arp(arp_repr), None)
+                }
+                #[cfg(all(
+                    feature = "proto-ipv6",
+                    any(feature = "medium-ethernet", feature = "medium-ieee802154")
+                ))]
+                IpAddress::Ipv6(addr) if state != AddressState::Tentative => {
+                    net_debug!("iface: announcing address {}", addr);
+                    let advert = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+                        flags: NdiscNeighborFlags::OVERRIDE,
+                        target_addr: addr,
+                        lladdr: self.inner.hardware_addr.map(|addr| addr.into()),
+                        aro: None,
+                    });
+                    let ipv6_repr = Ipv6Repr {
+                        src_addr: addr,
+                        dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+                        next_header: IpProtocol::Icmpv6,
+                        payload_len: advert.buffer_len(),
+                        hop_limit: 0xff,
+                        traffic_class: 0,
+                        flow_label: 0,
+                    };
+                    self.inner
+                        .dispatch_ip(tx_token, IpPacket::Icmpv6((ipv6_repr, advert)), None)
+                }
+                #[cfg(all(
+                    feature = "proto-ipv6",
+                    any(feature = "medium-ethernet", feature = "medium-ieee802154")
+                ))]
+                IpAddress::Ipv6(addr) => {
+                    net_debug!("iface: probing address {}", addr);
+                    let solicit = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
+                        target_addr: addr,
+                        lladdr: None,
+                        aro: None,
+                    });
+                    let ipv6_repr = Ipv6Repr {
+                        src_addr: Ipv6Address::UNSPECIFIED,
+                        dst_addr: addr.solicited_node(),
+                        next_header: IpProtocol::Icmpv6,
+                        payload_len: solicit.buffer_len(),
+                        hop_limit: 0xff,
+                        traffic_class: 0,
+                        flow_label: 0,
+                    };
+                    self.inner
+                        .dispatch_ip(tx_token, IpPacket::Icmpv6((ipv6_repr, solicit)), None)
+                }
+                #[allow(unreachable_patterns)]
+                _ => Ok(()),
+            };
+            if let Err(e) = result {
+                net_debug!("iface: failed to send address probe: {:?}", e);
+            }
+            did_something = true;
+        }
+
+        did_something
+    }
+}
+
+impl InterfaceInner {
+    /// Return the state of an address assigned to the interface.
+    pub(crate) fn address_state(&self, addr: &IpAddress) -> AddressState {
+        self.addr_info
+            .get(addr)
+            .map_or(AddressState::Preferred, |info| info.state)
+    }
+
+    /// Return whether the address is assigned, but still going through Duplicate
+    /// Address Detection.
+    pub(crate) fn is_tentative(&self, addr: &IpAddress) -> bool {
+        self.address_state(addr) == AddressState::Tentative
+    }
+
+    /// Return the time at which the next address probe is due.
+    pub(crate) fn address_poll_at(&self) -> Option<Instant> {
+        self.addr_info
+            .values()
+            .filter(|info| info.probes_left > 0 || info.state == AddressState::Tentative)
+            .map(|info| info.next_probe_at)
+            .min()
+    }
+
+    /// Send `count` announcements of an assigned address with the next polls.
+    pub(crate) fn schedule_announcements(&mut self, addr: IpAddress, count: u8) {
+        let info = AddressInfo {
+            state: self.address_state(&addr),
+            probes_left: count,
+            next_probe_at: self.now,
+        };
+        // NOTE(unwrap): the map holds as many entries as there are addresses.
+        self.addr_info.insert(addr, info).unwrap();
+    }
+
+    pub(crate) fn push_address_event(&mut self, event: AddressEvent) {
+        if self.addr_events.is_full() {
+            self.addr_events.pop_front();
+        }
+        // NOTE(unwrap): we've just made room.
+        self.addr_events.push_back(event).unwrap();
+    }
+
+    /// Remove an address, and report it with the given event.
+    pub(crate) fn remove_ip_addr(
+        &mut self,
+        addr: IpAddress,
+        event: fn(IpCidr) -> AddressEvent,
+    ) -> Option<IpCidr> {
+        let index = self
+            .ip_addrs
+            .iter()
+            .position(|cidr| cidr.address() == addr)?;
+        let cidr = self.ip_addrs.remove(index);
+        self.addr_info.remove(&addr);
+        self.flush_cache();
+
+        net_debug!("iface: removed address {}", cidr);
+        self.push_address_event(event(cidr));
+        Some(cidr)
+    }
+
+    /// Report the addresses added and removed by [`Interface::update_ip_addrs`].
+    pub(crate) fn diff_ip_addrs(&mut self, old: &[IpCidr]) {
+        for cidr in old {
+            if !self.ip_addrs.contains(cidr) {
+                self.addr_info.remove(&cidr.address());
+                self.push_address_event(AddressEvent::Removed(*cidr));
+            }
+        }
+        for i in 0..self.ip_addrs.len() {
+            let cidr = self.ip_addrs[i];
+            if !old.contains(&cidr) {
+                self.push_address_event(AddressEvent::Added(cidr));
+            }
+        }
+    }
+
+    /// Report that another node claimed one of our addresses.
+    pub(crate) fn address_conflict(&mut self, addr: IpAddress) {
+        if let Some(cidr) = self.ip_addrs.iter().find(|cidr| cidr.address() == addr) {
+            net_debug!("iface: address {} claimed by another node", addr);
+            self.push_address_event(AddressEvent::Conflict(*cidr));
+        }
+    }
+
+    /// Handle a Neighbor Advertisement for one of our tentative addresses: another
+    /// node already uses it.
+    #[cfg(feature = "proto-ipv6")]
+    pub(crate) fn duplicate_address_detected(&mut self, addr: Ipv6Address) {
+        net_debug!("iface: address {} is a duplicate", addr);
+        self.remove_ip_addr(addr.into(), AddressEvent::Duplicate);
+    }
+}
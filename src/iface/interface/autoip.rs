@@ -0,0 +1,346 @@
+// IPv4 link-local address auto-configuration, see RFC 3927.
+//
+// A host that got no address from DHCP picks a random address in 169.254/16, checks
+// with ARP probes that no other node uses it, then claims and announces it. The
+// candidates are drawn from a generator seeded with the MAC address, so that a host
+// tends to get the same address each time it joins the link.
+
+use super::{AddressEvent, EthernetPacket, Interface, InterfaceInner};
+use crate::phy::Device;
+use crate::rand::Rand;
+use crate::time::{Duration, Instant};
+use crate::wire::*;
+
+/// Largest random delay before the first probe, `PROBE_WAIT` in [RFC 3927 § 9].
+///
+/// [RFC 3927 § 9]: https://www.rfc-editor.org/rfc/rfc3927#section-9
+const PROBE_WAIT: Duration = Duration::from_secs(1);
+
+/// Number of probes, and bounds of the random interval between them, `PROBE_NUM`,
+/// `PROBE_MIN` and `PROBE_MAX` in [RFC 3927 § 9].
+///
+/// [RFC 3927 § 9]: https://www.rfc-editor.org/rfc/rfc3927#section-9
+const PROBE_NUM: u8 = 3;
+const PROBE_MIN: Duration = Duration::from_secs(1);
+const PROBE_MAX: Duration = Duration::from_secs(2);
+
+/// Delay between the last probe and claiming the address, `ANNOUNCE_WAIT` in
+/// [RFC 3927 § 9].
+///
+/// [RFC 3927 § 9]: https://www.rfc-editor.org/rfc/rfc3927#section-9
+const ANNOUNCE_WAIT: Duration = Duration::from_secs(2);
+
+/// Number of announcements of a claimed address, `ANNOUNCE_NUM` in [RFC 3927 § 9].
+///
+/// [RFC 3927 § 9]: https://www.rfc-editor.org/rfc/rfc3927#section-9
+const ANNOUNCE_NUM: u8 = 2;
+
+/// Number of conflicts after which new addresses are probed at most once per
+/// `RATE_LIMIT_INTERVAL`, `MAX_CONFLICTS` and `RATE_LIMIT_INTERVAL` in
+/// [RFC 3927 § 9].
+///
+/// [RFC 3927 § 9]: https://www.rfc-editor.org/rfc/rfc3927#section-9
+const MAX_CONFLICTS: u8 = 10;
+const RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Shortest interval between two defenses of a claimed address, `DEFEND_INTERVAL` in
+/// [RFC 3927 § 9].
+///
+/// [RFC 3927 § 9]: https://www.rfc-editor.org/rfc/rfc3927#section-9
+const DEFEND_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Prefix length of the link-local network.
+const PREFIX_LEN: u8 = 16;
+
+/// State of the link-local address auto-configuration of an interface.
+#[derive(Debug)]
+pub(crate) struct AutoIp {
+    /// Generator of the candidate addresses, seeded with the MAC address.
+    rand: Rand,
+    /// Number of conflicts since an address was last claimed.
+    conflicts: u8,
+    state: State,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    /// Checking that no other node uses the address.
+    Probing {
+        addr: Ipv4Address,
+        probes_sent: u8,
+        /// When to send the next probe, or to claim the address.
+        next_at: Instant,
+    },
+    /// The address is assigned to the interface.
+    Bound {
+        addr: Ipv4Address,
+        /// When the address was last defended against a conflict.
+        defended_at: Option<Instant>,
+    },
+}
+
+impl AutoIp {
+    /// Create the state for an interface with the given MAC address, probing the
+    /// first candidate address at `probe_at`.
+    fn new(mac: EthernetAddress, probe_at: Instant) -> Self {
+        let mut seed = [0; 8];
+        seed[..6].copy_from_slice(mac.as_bytes());
+        let mut rand = Rand::new(u64::from_le_bytes(seed));
+        AutoIp {
+            state: State::Probing {
+                addr: candidate(&mut rand),
+                probes_sent: 0,
+                next_at: probe_at,
+            },
+            rand,
+            conflicts: 0,
+        }
+    }
+
+    /// Start probing a new candidate address after `delay`, or after
+    /// `RATE_LIMIT_INTERVAL` if there were too many conflicts.
+    fn restart(&mut self, now: Instant, delay: Duration) {
+        let delay = match self.conflicts >= MAX_CONFLICTS {
+            true => RATE_LIMIT_INTERVAL,
+            false => delay,
+        };
+        self.state = State::Probing {
+            addr: candidate(&mut self.rand),
+            probes_sent: 0,
+            next_at: now + delay,
+        };
+    }
+}
+
+/// Return a candidate address, in 169.254.1.0 to 169.254.254.255, the first and last
+/// 256 addresses being reserved.
+fn candidate(rand: &mut Rand) -> Ipv4Address {
+    let n = 0x100 + rand.rand_u32() % (254 * 0x100);
+    Ipv4Address([169, 254, (n >> 8) as u8, n as u8])
+}
+
+/// Return a random duration in `min..max`.
+fn random_delay(rand: &mut Rand, min: Duration, max: Duration) -> Duration {
+    let range = (max - min).total_millis();
+    min + Duration::from_millis(rand.rand_u32() as u64 % range)
+}
+
+impl Interface {
+    /// Return whether link-local address auto-configuration is enabled.
+    pub fn autoip(&self) -> bool {
+        self.inner.autoip.is_some()
+    }
+
+    /// Return the link-local address claimed by auto-configuration, if any.
+    pub fn autoip_addr(&self) -> Option<Ipv4Address> {
+        match self.inner.autoip.as_ref()?.state {
+            State::Bound { addr, .. } => Some(addr),
+            State::Probing { .. } => None,
+        }
+    }
+
+    /// Enable or disable IPv4 link-local address auto-configuration, as described in
+    /// [RFC 3927], e.g. when no DHCP server answered.
+    ///
+    /// A random address in 169.254.0.0/16 is probed with ARP, and assigned to the
+    /// interface with an [`AddressEvent::Added`] event if no other node uses it.
+    /// Another address is tried after each conflict. Once assigned, the address is
+    /// defended against a conflict with an announcement, unless it was already
+    /// defended in the last 10 seconds; it is then given up with an
+    /// [`AddressEvent::Duplicate`] event, and a new address is probed.
+    ///
+    /// The address can coexist with the addresses assigned later, e.g. from a DHCP
+    /// lease: it is only used as the source address of packets to link-local
+    /// destinations, or when the interface has no other IPv4 address. Removing it
+    /// with [`remove_ip_addr`](Self::remove_ip_addr) or
+    /// [`update_ip_addrs`](Self::update_ip_addrs) disables auto-configuration, and so
+    /// does disabling it here.
+    ///
+    /// Auto-configuration only works on Ethernet interfaces; enabling it on other
+    /// interfaces has no effect. The probes are sent by the next calls to
+    /// [`poll`](Self::poll).
+    ///
+    /// [RFC 3927]: https://www.rfc-editor.org/rfc/rfc3927
+    pub fn set_autoip(&mut self, enabled: bool) {
+        if enabled == self.autoip() {
+            return;
+        }
+        if !enabled {
+            if let Some(addr) = self.autoip_addr() {
+                self.inner
+                    .remove_ip_addr(addr.into(), AddressEvent::Removed);
+            }
+            self.inner.autoip = None;
+            return;
+        }
+
+        let mac = match self.inner.hardware_addr {
+            Some(HardwareAddress::Ethernet(mac)) => mac,
+            #[allow(unreachable_patterns)]
+            _ => {
+                net_debug!("autoip: not an Ethernet interface");
+                return;
+            }
+        };
+        let now = self.inner.now;
+        let delay = random_delay(&mut self.inner.rand, Duration::ZERO, PROBE_WAIT);
+        self.inner.autoip = Some(AutoIp::new(mac, now + delay));
+    }
+
+    /// Send the pending probe, or claim the probed address.
+    pub(crate) fn autoip_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let now = self.inner.now;
+        let (addr, probes_sent) = match self.inner.autoip {
+            Some(AutoIp {
+                state:
+                    State::Probing {
+                        addr,
+                        probes_sent,
+                        next_at,
+                    },
+                ..
+            }) if next_at <= now => (addr, probes_sent),
+            Some(AutoIp {
+                state: State::Bound { addr, .. },
+                ..
+            }) if !self
+                .inner
+                .ip_addrs
+                .iter()
+                .any(|c| c.address() == addr.into()) =>
+            {
+                net_debug!("autoip: address {} was removed, disabling", addr);
+                self.inner.autoip = None;
+                return false;
+            }
+            _ => return false,
+        };
+
+        if probes_sent == PROBE_NUM {
+            let cidr = IpCidr::Ipv4(Ipv4Cidr::new(addr, PREFIX_LEN));
+            if let Err(e) = self.add_ip_addr(cidr) {
+                net_debug!("autoip: cannot assign {}: {:?}", addr, e);
+                self.inner.autoip_restart();
+                return false;
+            }
+            net_debug!("autoip: claimed {}", addr);
+            self.inner.schedule_announcements(addr.into(), ANNOUNCE_NUM);
+            // NOTE(unwrap): we've just checked auto-configuration is enabled.
+            let autoip = self.inner.autoip.as_mut().unwrap();
+            autoip.conflicts = 0;
+            autoip.state = State::Bound {
+                addr,
+                defended_at: None,
+            };
+            return true;
+        }
+
+        let tx_token = match device.transmit(now) {
+            Some(tx_token) => tx_token,
+            None => return false,
+        };
+        net_debug!("autoip: probing {}", addr);
+        let arp_repr = ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: self.inner.hardware_addr.unwrap().ethernet_or_panic(),
+            source_protocol_addr: Ipv4Address::UNSPECIFIED,
+            target_hardware_addr: EthernetAddress::BROADCAST,
+            target_protocol_addr: addr,
+        };
+        if let Err(e) = self
+            .inner
+            .dispatch(tx_token, EthernetPacket::Arp(arp_repr), None)
+        {
+            net_debug!("autoip: failed to send probe: {:?}", e);
+        }
+
+        let probes_sent = probes_sent + 1;
+        let next_at = now
+            + match probes_sent {
+                PROBE_NUM => ANNOUNCE_WAIT,
+                _ => random_delay(&mut self.inner.rand, PROBE_MIN, PROBE_MAX),
+            };
+        // NOTE(unwrap): we've just checked auto-configuration is enabled.
+        self.inner.autoip.as_mut().unwrap().state = State::Probing {
+            addr,
+            probes_sent,
+            next_at,
+        };
+        true
+    }
+}
+
+impl InterfaceInner {
+    /// Return the time at which the next probe is due, or the probed address is
+    /// claimed.
+    pub(crate) fn autoip_poll_at(&self) -> Option<Instant> {
+        match self.autoip.as_ref()?.state {
+            State::Probing { next_at, .. } => Some(next_at),
+            State::Bound { .. } => None,
+        }
+    }
+
+    /// Pick a new candidate address and start probing it.
+    fn autoip_restart(&mut self) {
+        let delay = random_delay(&mut self.rand, Duration::ZERO, PROBE_WAIT);
+        if let Some(autoip) = self.autoip.as_mut() {
+            autoip.restart(self.now, delay);
+        }
+    }
+
+    /// Check whether an ARP packet from another node conflicts with the address being
+    /// probed or claimed, and handle the conflict.
+    pub(crate) fn autoip_process_arp(
+        &mut self,
+        source_hardware_addr: EthernetAddress,
+        source_protocol_addr: Ipv4Address,
+        target_protocol_addr: Ipv4Address,
+    ) {
+        if Some(HardwareAddress::Ethernet(source_hardware_addr)) == self.hardware_addr {
+            return;
+        }
+        let now = self.now;
+        let autoip = match self.autoip.as_mut() {
+            Some(autoip) => autoip,
+            None => return,
+        };
+
+        match autoip.state {
+            State::Probing { addr, .. } => {
+                // Another node uses the address, or is probing it too.
+                let probed_too =
+                    source_protocol_addr.is_unspecified() && target_protocol_addr == addr;
+                if source_protocol_addr != addr && !probed_too {
+                    return;
+                }
+                net_debug!("autoip: {} is used by another node", addr);
+                autoip.conflicts = autoip.conflicts.saturating_add(1);
+                self.autoip_restart();
+            }
+            State::Bound { addr, defended_at } => {
+                if source_protocol_addr != addr {
+                    return;
+                }
+                match defended_at {
+                    Some(at) if now < at + DEFEND_INTERVAL => {
+                        net_debug!("autoip: giving up {}", addr);
+                        autoip.conflicts = autoip.conflicts.saturating_add(1);
+                        self.remove_ip_addr(addr.into(), AddressEvent::Duplicate);
+                        self.autoip_restart();
+                    }
+                    _ => {
+                        net_debug!("autoip: defending {}", addr);
+                        autoip.state = State::Bound {
+                            addr,
+                            defended_at: Some(now),
+                        };
+                        self.schedule_announcements(addr.into(), 1);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use super::*;
+
+/// A limit on the work done by a single call to [`Interface::poll`].
+///
+/// Frames received and sent are both counted against the budget. When it runs out,
+/// the interface stops asking the device for frames and returns, leaving the rest of
+/// the work to the next poll; [`Interface::poll_pending`] then returns `true`.
+///
+/// The budget is checked before each frame, so the byte limit can be exceeded by at
+/// most one frame. The default budget is unlimited.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PollBudget {
+    /// The maximum number of frames, or `None` for no limit.
+    pub packets: Option<usize>,
+    /// The maximum number of bytes, including link-layer headers, or `None` for no
+    /// limit.
+    pub bytes: Option<usize>,
+}
+
+impl PollBudget {
+    /// A budget of at most `packets` frames per poll.
+    pub const fn packets(packets: usize) -> Self {
+        Self {
+            packets: Some(packets),
+            bytes: None,
+        }
+    }
+
+    /// Return whether `packets` frames totalling `bytes` use up the budget.
+    pub(super) fn exhausted_by(&self, packets: u64, bytes: u64) -> bool {
+        self.packets.map_or(false, |limit| packets >= limit as u64)
+            || self.bytes.map_or(false, |limit| bytes >= limit as u64)
+    }
+}
+
+impl Interface {
+    /// Return the budget of each poll.
+    pub fn poll_budget(&self) -> PollBudget {
+        self.poll_budget
+    }
+
+    /// Set the budget of each poll, to keep a busy link from delaying the rest of the
+    /// application for too long.
+    pub fn set_poll_budget(&mut self, budget: PollBudget) {
+        self.poll_budget = budget;
+    }
+
+    /// Return whether the last poll stopped because its budget ran out, in which case
+    /// there may be more frames to process or send, and the interface should be polled
+    /// again without waiting for [`poll_delay`](Interface::poll_delay).
+    pub fn poll_pending(&self) -> bool {
+        self.poll_pending
+    }
+}
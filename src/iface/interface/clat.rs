@@ -0,0 +1,334 @@
+use super::*;
+
+/// The prefix lengths allowed for IPv4-embedded IPv6 addresses, see [RFC 6052].
+///
+/// [RFC 6052]: https://tools.ietf.org/html/rfc6052#section-2.2
+#[cfg(feature = "proto-ipv4")]
+pub(crate) const PREFIX_LENS: [u8; 6] = [32, 40, 48, 56, 64, 96];
+
+/// The configuration of a customer-side translator (CLAT), see [RFC 6877].
+///
+/// The CLAT lets sockets use IPv4 on an IPv6-only network: packets sent from
+/// `ipv4_addr` are translated into IPv6 packets from `ipv6_addr` to the NAT64 prefix,
+/// and the packets coming back are translated into IPv4, as described in [RFC 7915].
+/// Only TCP, UDP and ICMP echo messages are translated; other packets sent from
+/// `ipv4_addr` are dropped.
+///
+/// `ipv4_addr` must be one of the addresses of the interface. On media with neighbor
+/// discovery, so must `ipv6_addr`, for the neighbors to find it.
+///
+/// [RFC 6877]: https://tools.ietf.org/html/rfc6877
+/// [RFC 7915]: https://tools.ietf.org/html/rfc7915
+#[cfg(feature = "proto-ipv4")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ClatConfig {
+    /// The IPv4 address used by the sockets, usually taken from `192.0.0.0/29`.
+    pub ipv4_addr: Ipv4Address,
+    /// The IPv6 address the translated packets are sent from.
+    pub ipv6_addr: Ipv6Address,
+    /// The NAT64 prefix, or `None` to use the one advertised by routers.
+    pub prefix: Option<Ipv6Cidr>,
+}
+
+#[cfg(feature = "proto-ipv4")]
+impl ClatConfig {
+    /// A translator using the NAT64 prefix advertised by routers.
+    pub const fn new(ipv4_addr: Ipv4Address, ipv6_addr: Ipv6Address) -> Self {
+        Self {
+            ipv4_addr,
+            ipv6_addr,
+            prefix: None,
+        }
+    }
+}
+
+impl Interface {
+    /// Return the NAT64 prefix advertised by routers, if it has not expired.
+    pub fn nat64_prefix(&self) -> Option<Ipv6Cidr> {
+        self.inner.nat64_prefix()
+    }
+
+    /// Return the configuration of the translator, if it is enabled.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn clat(&self) -> Option<ClatConfig> {
+        self.inner.clat
+    }
+
+    /// Enable the translator with the given configuration, or disable it with `None`.
+    ///
+    /// # Panics
+    /// This function panics if the configured prefix is not 32, 40, 48, 56, 64 or 96
+    /// bits long.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn set_clat(&mut self, config: Option<ClatConfig>) {
+        if let Some(prefix) = config.and_then(|config| config.prefix) {
+            assert!(
+                PREFIX_LENS.contains(&prefix.prefix_len()),
+                "invalid NAT64 prefix length"
+            );
+        }
+        self.inner.clat = config;
+    }
+}
+
+impl InterfaceInner {
+    pub(crate) fn nat64_prefix(&self) -> Option<Ipv6Cidr> {
+        match self.nat64_prefix {
+            Some((prefix, expires_at)) if expires_at > self.now => Some(prefix),
+            _ => None,
+        }
+    }
+
+    /// Remember the NAT64 prefix advertised by a router, or forget it if its lifetime
+    /// is zero.
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+    pub(super) fn learn_nat64_prefix(&mut self, pref64: NdiscPref64) {
+        if pref64.lifetime == Duration::ZERO {
+            if matches!(self.nat64_prefix, Some((prefix, _)) if prefix == pref64.prefix) {
+                net_debug!("nat64 prefix {} withdrawn", pref64.prefix);
+                self.nat64_prefix = None;
+            }
+            return;
+        }
+        net_trace!("nat64 prefix {} for {}", pref64.prefix, pref64.lifetime);
+        self.nat64_prefix = Some((pref64.prefix, self.now + pref64.lifetime));
+    }
+
+    /// Return the configuration of the translator and the prefix it uses, if it is
+    /// enabled and has a prefix.
+    #[cfg(feature = "proto-ipv4")]
+    fn clat_prefix(&self) -> Option<(ClatConfig, Ipv6Cidr)> {
+        let clat = self.clat?;
+        let prefix = clat.prefix.or_else(|| self.nat64_prefix())?;
+        Some((clat, prefix))
+    }
+
+    /// Return whether packets sent from `src_addr` are translated.
+    #[cfg(feature = "proto-ipv4")]
+    pub(super) fn clat_translates(&self, src_addr: &IpAddress) -> bool {
+        match (src_addr, self.clat_prefix()) {
+            (IpAddress::Ipv4(addr), Some((clat, _))) => *addr == clat.ipv4_addr,
+            _ => false,
+        }
+    }
+
+    /// Return the IPv6 address packets to `addr` are translated to, if the interface
+    /// has no route to it otherwise.
+    #[cfg(feature = "proto-ipv4")]
+    pub(super) fn clat_route(&self, addr: &IpAddress) -> Option<IpAddress> {
+        match addr {
+            IpAddress::Ipv4(addr) => {
+                let (_, prefix) = self.clat_prefix()?;
+                Some(embed(prefix, *addr).into())
+            }
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// Translate a packet sent from the IPv4 address of the translator to IPv6.
+    ///
+    /// Other packets are returned unchanged. Returns `None` if the packet cannot be
+    /// translated and must be dropped.
+    #[cfg(feature = "proto-ipv4")]
+    pub(super) fn clat_egress<'p>(&self, packet: IpPacket<'p>) -> Option<IpPacket<'p>> {
+        let ipv4_repr = match packet.ip_repr() {
+            IpRepr::Ipv4(repr) if self.clat_translates(&repr.src_addr.into()) => repr,
+            _ => return Some(packet),
+        };
+        let (clat, prefix) = self.clat_prefix()?;
+        let ipv6_repr = Ipv6Repr {
+            src_addr: clat.ipv6_addr,
+            dst_addr: embed(prefix, ipv4_repr.dst_addr),
+            next_header: ipv4_repr.next_header,
+            payload_len: ipv4_repr.payload_len,
+            hop_limit: ipv4_repr.hop_limit,
+            traffic_class: ipv4_repr.dscp << 2,
+            flow_label: 0,
+        };
+
+        match packet {
+            #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
+            IpPacket::Udp((_, udp_repr, payload)) => {
+                Some(IpPacket::Udp((ipv6_repr.into(), udp_repr, payload)))
+            }
+            #[cfg(feature = "socket-tcp")]
+            IpPacket::Tcp((_, tcp_repr)) => Some(IpPacket::Tcp((ipv6_repr.into(), tcp_repr))),
+            IpPacket::Icmpv4((_, icmp_repr)) => {
+                let icmp_repr = match icmp_repr {
+                    Icmpv4Repr::EchoRequest {
+                        ident,
+                        seq_no,
+                        data,
+                    } => Icmpv6Repr::EchoRequest {
+                        ident,
+                        seq_no,
+                        data,
+                    },
+                    Icmpv4Repr::EchoReply {
+                        ident,
+                        seq_no,
+                        data,
+                    } => Icmpv6Repr::EchoReply {
+                        ident,
+                        seq_no,
+                        data,
+                    },
+                    _ => {
+                        net_debug!("clat: cannot translate {:?}", icmp_repr);
+                        return None;
+                    }
+                };
+                let ipv6_repr = Ipv6Repr {
+                    next_header: IpProtocol::Icmpv6,
+                    ..ipv6_repr
+                };
+                Some(IpPacket::Icmpv6((ipv6_repr, icmp_repr)))
+            }
+            _ => {
+                net_debug!("clat: cannot translate {} packet", ipv4_repr.next_header);
+                None
+            }
+        }
+    }
+
+    /// Return whether a received packet is for the translator.
+    #[cfg(feature = "proto-ipv4")]
+    pub(super) fn clat_accepts(&self, ipv6_repr: &Ipv6Repr) -> bool {
+        match self.clat_prefix() {
+            Some((clat, prefix)) => {
+                ipv6_repr.dst_addr == clat.ipv6_addr && prefix.contains_addr(&ipv6_repr.src_addr)
+            }
+            None => false,
+        }
+    }
+
+    /// Translate a packet received by the translator to IPv4.
+    ///
+    /// The packet is returned as if it were a reply, addressed to the IPv4 address of the
+    /// translator, for the interface to deliver it locally.
+    #[cfg(feature = "proto-ipv4")]
+    pub(super) fn clat_ingress<'frame>(
+        &mut self,
+        ipv6_repr: Ipv6Repr,
+        ip_payload: &'frame [u8],
+    ) -> Option<IpPacket<'frame>> {
+        let (clat, prefix) = self.clat_prefix()?;
+        let (src_addr, dst_addr) = (ipv6_repr.src_addr.into(), ipv6_repr.dst_addr.into());
+        let ipv4_repr = Ipv4Repr {
+            src_addr: extract(prefix, ipv6_repr.src_addr),
+            dst_addr: clat.ipv4_addr,
+            next_header: ipv6_repr.next_header,
+            payload_len: ipv6_repr.payload_len,
+            hop_limit: ipv6_repr.hop_limit,
+            dscp: ipv6_repr.traffic_class >> 2,
+        };
+
+        match ipv6_repr.next_header {
+            #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
+            IpProtocol::Udp => {
+                let udp_packet = check!(UdpPacket::new_checked(ip_payload), self.stats, udp_errors);
+                let udp_repr = check!(
+                    UdpRepr::parse(&udp_packet, &src_addr, &dst_addr, &self.caps.checksum),
+                    self.stats,
+                    udp_errors,
+                    bad_checksum: self.caps.checksum.udp.rx()
+                        && !udp_packet.verify_checksum(&src_addr, &dst_addr)
+                );
+                Some(IpPacket::Udp((
+                    ipv4_repr.into(),
+                    udp_repr,
+                    udp_packet.payload(),
+                )))
+            }
+            #[cfg(feature = "socket-tcp")]
+            IpProtocol::Tcp => {
+                let tcp_packet = check!(TcpPacket::new_checked(ip_payload), self.stats, tcp_errors);
+                let tcp_repr = check!(
+                    TcpRepr::parse(&tcp_packet, &src_addr, &dst_addr, &self.caps.checksum),
+                    self.stats,
+                    tcp_errors,
+                    bad_checksum: self.caps.checksum.tcp.rx()
+                        && !tcp_packet.verify_checksum(&src_addr, &dst_addr)
+                );
+                Some(IpPacket::Tcp((ipv4_repr.into(), tcp_repr)))
+            }
+            IpProtocol::Icmpv6 => {
+                let icmp_packet = check!(
+                    Icmpv6Packet::new_checked(ip_payload),
+                    self.stats,
+                    icmp_errors
+                );
+                let icmp_repr = check!(
+                    Icmpv6Repr::parse(&src_addr, &dst_addr, &icmp_packet, &self.caps.checksum),
+                    self.stats,
+                    icmp_errors,
+                    bad_checksum: self.caps.checksum.icmpv6.rx()
+                        && !icmp_packet.verify_checksum(&src_addr, &dst_addr)
+                );
+                let icmp_repr = match icmp_repr {
+                    Icmpv6Repr::EchoRequest {
+                        ident,
+                        seq_no,
+                        data,
+                    } => Icmpv4Repr::EchoRequest {
+                        ident,
+                        seq_no,
+                        data,
+                    },
+                    Icmpv6Repr::EchoReply {
+                        ident,
+                        seq_no,
+                        data,
+                    } => Icmpv4Repr::EchoReply {
+                        ident,
+                        seq_no,
+                        data,
+                    },
+                    _ => {
+                        net_debug!("clat: cannot translate {:?}", icmp_repr);
+                        return None;
+                    }
+                };
+                let ipv4_repr = Ipv4Repr {
+                    next_header: IpProtocol::Icmp,
+                    ..ipv4_repr
+                };
+                Some(IpPacket::Icmpv4((ipv4_repr, icmp_repr)))
+            }
+            _ => {
+                net_debug!("clat: cannot translate {} packet", ipv6_repr.next_header);
+                None
+            }
+        }
+    }
+}
+
+/// Embed an IPv4 address in the NAT64 prefix, skipping bits 64 to 71, see
+/// [RFC 6052 § 2.2].
+///
+/// [RFC 6052 § 2.2]: https://tools.ietf.org/html/rfc6052#section-2.2
+#[cfg(feature = "proto-ipv4")]
+pub(crate) fn embed(prefix: Ipv6Cidr, addr: Ipv4Address) -> Ipv6Address {
+    let start = prefix.prefix_len() as usize / 8;
+    let mut bytes = [0; 16];
+    bytes[..start].copy_from_slice(&prefix.address().as_bytes()[..start]);
+    let positions = (start..16).filter(|&i| i != 8);
+    for (i, byte) in positions.zip(addr.as_bytes()) {
+        bytes[i] = *byte;
+    }
+    Ipv6Address(bytes)
+}
+
+/// Extract the IPv4 address embedded in an address of the NAT64 prefix.
+#[cfg(feature = "proto-ipv4")]
+fn extract(prefix: Ipv6Cidr, addr: Ipv6Address) -> Ipv4Address {
+    let start = prefix.prefix_len() as usize / 8;
+    let mut bytes = [0; 4];
+    let positions = (start..16).filter(|&i| i != 8);
+    for (byte, i) in bytes.iter_mut().zip(positions) {
+        *byte = addr.as_bytes()[i];
+    }
+    Ipv4Address(bytes)
+}
@@ -7,6 +7,8 @@ use super::SocketSet;
 use core::result::Result;
 
 use crate::phy::TxToken;
+#[cfg(feature = "socket-packet")]
+use crate::socket::{packet, AnySocket};
 use crate::wire::*;
 
 impl InterfaceInner {
@@ -17,7 +19,11 @@ impl InterfaceInner {
         frame: &'frame T,
         _fragments: &'frame mut FragmentsBuffer,
     ) -> Option<EthernetPacket<'frame>> {
-        let eth_frame = check!(EthernetFrame::new_checked(frame));
+        let eth_frame = check!(
+            EthernetFrame::new_checked(frame),
+            self.stats,
+            ethernet_errors
+        );
 
         // Ignore any packets not directed to our hardware address or any of the multicast groups.
         if !eth_frame.dst_addr().is_broadcast()
@@ -27,12 +33,28 @@ impl InterfaceInner {
             return None;
         }
 
+        #[cfg(feature = "socket-packet")]
+        for packet_socket in sockets
+            .items_mut()
+            .filter_map(|i| packet::Socket::downcast_mut(&mut i.socket))
+        {
+            if packet_socket.accepts(&eth_frame) {
+                packet_socket.process(self, &eth_frame);
+            }
+        }
+
         match eth_frame.ethertype() {
             #[cfg(feature = "proto-ipv4")]
             EthernetProtocol::Arp => self.process_arp(self.now, &eth_frame),
             #[cfg(feature = "proto-ipv4")]
+            EthernetProtocol::Rarp => self.process_rarp(&eth_frame),
+            #[cfg(feature = "proto-ipv4")]
             EthernetProtocol::Ipv4 => {
-                let ipv4_packet = check!(Ipv4Packet::new_checked(eth_frame.payload()));
+                let ipv4_packet = check!(
+                    Ipv4Packet::new_checked(eth_frame.payload()),
+                    self.stats,
+                    ipv4_errors
+                );
 
                 #[cfg(feature = "proto-ipv4-fragmentation")]
                 {
@@ -48,10 +70,18 @@ impl InterfaceInner {
             }
             #[cfg(feature = "proto-ipv6")]
             EthernetProtocol::Ipv6 => {
-                let ipv6_packet = check!(Ipv6Packet::new_checked(eth_frame.payload()));
+                let ipv6_packet = check!(
+                    Ipv6Packet::new_checked(eth_frame.payload()),
+                    self.stats,
+                    ipv6_errors
+                );
                 self.process_ipv6(sockets, &ipv6_packet)
                     .map(EthernetPacket::Ip)
             }
+            EthernetProtocol::MacControl => {
+                self.process_pause(&eth_frame);
+                None
+            }
             // Drop all other traffic.
             _ => None,
         }
@@ -61,6 +91,7 @@ impl InterfaceInner {
     pub(super) fn dispatch_ethernet<Tx, F>(
         &mut self,
         tx_token: Tx,
+        ethertype: EthernetProtocol,
         buffer_len: usize,
         f: F,
     ) -> Result<(), DispatchError>
@@ -68,13 +99,14 @@ impl InterfaceInner {
         Tx: TxToken,
         F: FnOnce(EthernetFrame<&mut [u8]>),
     {
-        let tx_len = EthernetFrame::<&[u8]>::buffer_len(buffer_len);
+        let tx_len = self.ethernet_header_len() + buffer_len;
         tx_token.consume(tx_len, |tx_buffer| {
             debug_assert!(tx_buffer.as_ref().len() == tx_len);
             let mut frame = EthernetFrame::new_unchecked(tx_buffer);
 
             let src_addr = self.hardware_addr.unwrap().ethernet_or_panic();
             frame.set_src_addr(src_addr);
+            frame.set_encapsulated_ethertype(self.ethernet_encapsulation, ethertype);
 
             f(frame);
 
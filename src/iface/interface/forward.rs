@@ -0,0 +1,498 @@
+use super::*;
+
+use crate::iface::forwarder::{ForwardPacketBuffer, Forwarder, PortId};
+#[cfg(feature = "proto-ipv4")]
+use crate::iface::nat;
+
+/// What to do with a packet received on a port of a [`Forwarder`].
+#[allow(clippy::large_enum_variant)]
+pub(super) enum Forwarding<'a> {
+    /// The packet is for this interface, process it as usual.
+    Local,
+    /// The packet was queued on its egress port, or dropped.
+    Consumed,
+    /// The packet cannot be forwarded; send this ICMP error back to its source.
+    Reply(IpPacket<'a>),
+}
+
+impl Interface {
+    pub(crate) fn poll_forwarding<D>(
+        &mut self,
+        timestamp: Instant,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+        forwarder: &mut Forwarder<'_>,
+        port: PortId,
+    ) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        self.poll_with(timestamp, device, sockets, &mut Some((forwarder, port)))
+    }
+
+    pub(crate) fn ip_mtu_of(&self, version: IpVersion) -> usize {
+        self.inner.ip_mtu_of(version)
+    }
+
+    /// Send the packets queued on `port`, until the device is busy or the next hop of
+    /// the first packet is still being resolved.
+    pub(super) fn forward_egress<D>(
+        &mut self,
+        device: &mut D,
+        forwarder: &mut Forwarder<'_>,
+        port: PortId,
+    ) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let queue = forwarder.queue_mut(port);
+        let now = self.inner.now;
+        let inner = &mut self.inner;
+        let mut emitted_any = false;
+
+        loop {
+            emitted_any |= drop_expired(queue, now);
+            if queue.is_empty() {
+                break;
+            }
+
+            let mut pending = false;
+            let sent = device.transmit_batch(now, FORWARD_BATCH_SIZE, |tx_token| {
+                emitted_any |= drop_expired(queue, now);
+                let packet = match queue.peek() {
+                    Ok((_, packet)) => packet,
+                    Err(_) => return false,
+                };
+                match inner.dispatch_forwarded(tx_token, packet) {
+                    Ok(()) => (),
+                    Err(DispatchError::NeighborPending) => {
+                        pending = true;
+                        return false;
+                    }
+                    Err(DispatchError::NoRoute) => {
+                        net_debug!("forwarding: no route, dropping packet");
+                    }
+                }
+                let _ = queue.dequeue();
+                emitted_any = true;
+                true
+            });
+            inner.end_batch();
+
+            if sent == 0 || pending {
+                break;
+            }
+        }
+
+        emitted_any
+    }
+}
+
+/// The maximum number of forwarded packets sent to the device in one batch.
+const FORWARD_BATCH_SIZE: usize = 16;
+
+/// Drop the packets at the front of `queue` that waited too long for their next hop,
+/// returning whether there were any.
+fn drop_expired(queue: &mut ForwardPacketBuffer<'_>, now: Instant) -> bool {
+    let mut dropped_any = false;
+    while let Ok((&received_at, _)) = queue.peek() {
+        if now < received_at + Forwarder::QUEUE_TIMEOUT {
+            break;
+        }
+        net_debug!("forwarding: next hop unresolved, dropping packet");
+        let _ = queue.dequeue();
+        dropped_any = true;
+    }
+    dropped_any
+}
+
+impl InterfaceInner {
+    /// Decide whether a frame received on `port` is to be forwarded, and if so, queue
+    /// it on its egress port.
+    pub(super) fn forward_ingress<'frame>(
+        &mut self,
+        frame: &'frame [u8],
+        forwarder: &mut Forwarder<'_>,
+        port: PortId,
+    ) -> Forwarding<'frame> {
+        let packet = match self.caps.medium {
+            #[cfg(feature = "medium-ethernet")]
+            Medium::Ethernet => {
+                let eth_frame = match EthernetFrame::new_checked(frame) {
+                    Ok(eth_frame) => eth_frame,
+                    Err(_) => return Forwarding::Local,
+                };
+                if Some(HardwareAddress::Ethernet(eth_frame.dst_addr())) != self.hardware_addr {
+                    return Forwarding::Local;
+                }
+                match eth_frame.ethertype() {
+                    #[cfg(feature = "proto-ipv4")]
+                    EthernetProtocol::Ipv4 => eth_frame.payload(),
+                    #[cfg(feature = "proto-ipv6")]
+                    EthernetProtocol::Ipv6 => eth_frame.payload(),
+                    _ => return Forwarding::Local,
+                }
+            }
+            #[cfg(feature = "medium-ip")]
+            Medium::Ip => frame,
+            #[cfg(feature = "medium-ieee802154")]
+            Medium::Ieee802154 => return Forwarding::Local,
+        };
+
+        match IpVersion::of_packet(packet) {
+            #[cfg(feature = "proto-ipv4")]
+            Ok(IpVersion::Ipv4) => self.forward_ipv4(packet, forwarder, port),
+            #[cfg(feature = "proto-ipv6")]
+            Ok(IpVersion::Ipv6) => self.forward_ipv6(packet, forwarder),
+            _ => Forwarding::Local,
+        }
+    }
+
+    #[cfg(feature = "proto-ipv4")]
+    fn forward_ipv4<'frame>(
+        &mut self,
+        packet: &'frame [u8],
+        forwarder: &mut Forwarder<'_>,
+        port: PortId,
+    ) -> Forwarding<'frame> {
+        let ipv4_packet = match Ipv4Packet::new_checked(packet) {
+            Ok(ipv4_packet) => ipv4_packet,
+            Err(_) => return Forwarding::Local,
+        };
+        let ipv4_repr = match Ipv4Repr::parse(&ipv4_packet, &self.caps.checksum) {
+            Ok(ipv4_repr) => ipv4_repr,
+            Err(_) => return Forwarding::Local,
+        };
+        let (src_addr, dst_addr) = (ipv4_repr.src_addr, ipv4_repr.dst_addr);
+        if !self.is_unicast_v4(dst_addr) || !self.is_unicast_v4(src_addr) {
+            return Forwarding::Local;
+        }
+
+        let packet = &packet[..ipv4_packet.total_len() as usize];
+        let transport = &packet[ipv4_packet.header_len() as usize..];
+        let protocol = ipv4_repr.next_header;
+        let ports = if ipv4_packet.more_frags() || ipv4_packet.frag_offset() != 0 {
+            None
+        } else {
+            nat::transport_ports(protocol, transport)
+        };
+
+        // A packet to a mapped port of the outside address of the translator is for the
+        // inside host holding the mapping, wherever it comes from.
+        let now = self.now;
+        let outside = forwarder.nat().map(|nat| nat.outside());
+        let outside_addr = outside.and_then(|outside| forwarder.ipv4_addr(outside));
+        let inside_dst = match (forwarder.nat_mut(), ports) {
+            (Some(nat), Some((_, dst_port))) if Some(dst_addr) == outside_addr => {
+                let timeouts = nat.timeouts();
+                nat.inbound(protocol, dst_port, now).map(|mapping| {
+                    mapping.refresh(&timeouts, transport, now);
+                    (mapping.inside_addr, mapping.inside_port)
+                })
+            }
+            _ => None,
+        };
+        if inside_dst.is_none()
+            && (self.is_own_addr(&dst_addr.into())
+                || self.is_any_ip(dst_addr, ipv4_repr.next_header))
+        {
+            return Forwarding::Local;
+        }
+        let next_addr = inside_dst.map_or(dst_addr, |(addr, _)| addr);
+
+        let payload = &transport
+            [..icmp_reply_payload_len(transport.len(), IPV4_MIN_MTU, ipv4_repr.buffer_len())];
+
+        if ipv4_repr.hop_limit <= 1 {
+            net_debug!("forwarding: TTL of packet to {} expired", dst_addr);
+            let icmp_repr = Icmpv4Repr::TimeExceeded {
+                reason: Icmpv4TimeExceeded::TtlExpired,
+                header: ipv4_repr,
+                data: payload,
+            };
+            return self.forward_icmpv4_error(ipv4_repr, icmp_repr);
+        }
+
+        let egress = match forwarder.lookup(&next_addr.into(), now) {
+            Some(egress) => egress,
+            None => {
+                net_debug!("forwarding: no route to {}", next_addr);
+                self.stats.dropped_no_route = self.stats.dropped_no_route.wrapping_add(1);
+                let icmp_repr = Icmpv4Repr::DstUnreachable {
+                    reason: Icmpv4DstUnreachable::NetUnreachable,
+                    header: ipv4_repr,
+                    data: payload,
+                };
+                return self.forward_icmpv4_error(ipv4_repr, icmp_repr);
+            }
+        };
+
+        let mtu = forwarder.ip_mtu(egress, IpVersion::Ipv4);
+        if packet.len() > mtu {
+            // Forwarded packets are never fragmented; tell the source to lower its path
+            // MTU if it asked for the packet not to be.
+            if !ipv4_packet.dont_frag() {
+                net_debug!("forwarding: packet to {} too big, dropping it", dst_addr);
+                return Forwarding::Consumed;
+            }
+            net_debug!("forwarding: packet to {} exceeds MTU {}", dst_addr, mtu);
+            let icmp_repr = Icmpv4Repr::FragRequired {
+                mtu: mtu.min(u16::MAX as usize) as u16,
+                header: ipv4_repr,
+                data: payload,
+            };
+            return self.forward_icmpv4_error(ipv4_repr, icmp_repr);
+        }
+
+        // Connections from inside leaving through the outside port, or hairpinned back
+        // inside, take the outside address as source.
+        let outside_src = match outside {
+            Some(outside) if port != outside && (egress == outside || inside_dst.is_some()) => {
+                let nat = forwarder.nat_mut().unwrap();
+                let timeouts = nat.timeouts();
+                let src = match (ports, outside_addr) {
+                    (Some((src_port, _)), Some(outside_addr)) => nat
+                        .outbound(protocol, src_addr, src_port, now)
+                        .map(|mapping| {
+                            mapping.refresh(&timeouts, transport, now);
+                            (outside_addr, mapping.outside_port)
+                        }),
+                    _ => None,
+                };
+                if src.is_none() {
+                    net_debug!("nat: cannot translate packet from {}", src_addr);
+                    return Forwarding::Consumed;
+                }
+                src
+            }
+            _ => None,
+        };
+
+        match forwarder.queue_mut(egress).enqueue(packet.len(), now) {
+            Ok(buf) => {
+                buf.copy_from_slice(packet);
+                let mut ipv4_packet = Ipv4Packet::new_unchecked(buf);
+                ipv4_packet.set_hop_limit(ipv4_repr.hop_limit - 1);
+                if let Some((src_port, dst_port)) = ports {
+                    if outside_src.is_some() || inside_dst.is_some() {
+                        let src = outside_src.unwrap_or((src_addr, src_port));
+                        let dst = inside_dst.unwrap_or((dst_addr, dst_port));
+                        ipv4_packet.set_src_addr(src.0);
+                        ipv4_packet.set_dst_addr(dst.0);
+                        nat::rewrite_transport(protocol, ipv4_packet.payload_mut(), src, dst);
+                    }
+                }
+                // The header checksum is filled on egress, if the egress device does not.
+            }
+            Err(_) => net_debug!("forwarding: egress queue full, dropping packet"),
+        }
+        Forwarding::Consumed
+    }
+
+    #[cfg(feature = "proto-ipv6")]
+    fn forward_ipv6<'frame>(
+        &mut self,
+        packet: &'frame [u8],
+        forwarder: &mut Forwarder<'_>,
+    ) -> Forwarding<'frame> {
+        let ipv6_packet = match Ipv6Packet::new_checked(packet) {
+            Ok(ipv6_packet) => ipv6_packet,
+            Err(_) => return Forwarding::Local,
+        };
+        let ipv6_repr = match Ipv6Repr::parse(&ipv6_packet) {
+            Ok(ipv6_repr) => ipv6_repr,
+            Err(_) => return Forwarding::Local,
+        };
+        let (src_addr, dst_addr) = (ipv6_repr.src_addr, ipv6_repr.dst_addr);
+        if !dst_addr.is_unicast()
+            || !src_addr.is_unicast()
+            || dst_addr.is_link_local()
+            || src_addr.is_link_local()
+            || dst_addr.is_loopback()
+            || self.is_own_addr(&dst_addr.into())
+            || self.is_any_ip(dst_addr, ipv6_repr.next_header)
+        {
+            return Forwarding::Local;
+        }
+
+        let packet = &packet[..ipv6_packet.total_len()];
+        let payload = &packet[ipv6_repr.buffer_len()..];
+        let payload =
+            &payload[..icmp_reply_payload_len(payload.len(), IPV6_MIN_MTU, ipv6_repr.buffer_len())];
+
+        if ipv6_repr.hop_limit <= 1 {
+            net_debug!("forwarding: hop limit of packet to {} exceeded", dst_addr);
+            let icmp_repr = Icmpv6Repr::TimeExceeded {
+                reason: Icmpv6TimeExceeded::HopLimitExceeded,
+                header: ipv6_repr,
+                data: payload,
+            };
+            return self.forward_icmpv6_error(ipv6_repr, icmp_repr);
+        }
+
+        let egress = match forwarder.lookup(&dst_addr.into(), self.now) {
+            Some(egress) => egress,
+            None => {
+                net_debug!("forwarding: no route to {}", dst_addr);
+                self.stats.dropped_no_route = self.stats.dropped_no_route.wrapping_add(1);
+                let icmp_repr = Icmpv6Repr::DstUnreachable {
+                    reason: Icmpv6DstUnreachable::NoRoute,
+                    header: ipv6_repr,
+                    data: payload,
+                };
+                return self.forward_icmpv6_error(ipv6_repr, icmp_repr);
+            }
+        };
+
+        let mtu = forwarder.ip_mtu(egress, IpVersion::Ipv6);
+        if packet.len() > mtu {
+            net_debug!("forwarding: packet to {} exceeds MTU {}", dst_addr, mtu);
+            let icmp_repr = Icmpv6Repr::PktTooBig {
+                mtu: mtu as u32,
+                header: ipv6_repr,
+                data: payload,
+            };
+            return self.forward_icmpv6_error(ipv6_repr, icmp_repr);
+        }
+
+        match forwarder.queue_mut(egress).enqueue(packet.len(), self.now) {
+            Ok(buf) => {
+                buf.copy_from_slice(packet);
+                Ipv6Packet::new_unchecked(buf).set_hop_limit(ipv6_repr.hop_limit - 1);
+            }
+            Err(_) => net_debug!("forwarding: egress queue full, dropping packet"),
+        }
+        Forwarding::Consumed
+    }
+
+    /// Return whether `addr` is assigned to this interface, even tentatively.
+    fn is_own_addr(&self, addr: &IpAddress) -> bool {
+        self.ip_addrs.iter().any(|cidr| cidr.address() == *addr)
+    }
+
+    /// Unlike an ICMP reply, an ICMP error about a forwarded packet is sent from an
+    /// address of this interface rather than from the destination of the packet.
+    #[cfg(feature = "proto-ipv4")]
+    fn forward_icmpv4_error<'frame>(
+        &mut self,
+        ipv4_repr: Ipv4Repr,
+        icmp_repr: Icmpv4Repr<'frame>,
+    ) -> Forwarding<'frame> {
+        match self.get_source_address_ipv4(ipv4_repr.src_addr) {
+            Some(src_addr) => Forwarding::Reply(IpPacket::Icmpv4((
+                Ipv4Repr {
+                    src_addr,
+                    dst_addr: ipv4_repr.src_addr,
+                    next_header: IpProtocol::Icmp,
+                    payload_len: icmp_repr.buffer_len(),
+                    hop_limit: 64,
+                    dscp: 0,
+                },
+                icmp_repr,
+            ))),
+            None => Forwarding::Consumed,
+        }
+    }
+
+    #[cfg(feature = "proto-ipv6")]
+    fn forward_icmpv6_error<'frame>(
+        &mut self,
+        ipv6_repr: Ipv6Repr,
+        icmp_repr: Icmpv6Repr<'frame>,
+    ) -> Forwarding<'frame> {
+        match self.get_source_address_ipv6(ipv6_repr.src_addr) {
+            Some(src_addr) => Forwarding::Reply(IpPacket::Icmpv6((
+                Ipv6Repr {
+                    src_addr,
+                    dst_addr: ipv6_repr.src_addr,
+                    next_header: IpProtocol::Icmpv6,
+                    payload_len: icmp_repr.buffer_len(),
+                    hop_limit: 64,
+                    traffic_class: 0,
+                    flow_label: 0,
+                },
+                icmp_repr,
+            ))),
+            None => Forwarding::Consumed,
+        }
+    }
+
+    /// Send a forwarded IP packet to its next hop. Packets that do not fit the link,
+    /// because its MTU changed after they were queued, are dropped.
+    fn dispatch_forwarded<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        packet: &[u8],
+    ) -> Result<(), DispatchError> {
+        let (version, dst_addr) = match IpVersion::of_packet(packet) {
+            #[cfg(feature = "proto-ipv4")]
+            Ok(version @ IpVersion::Ipv4) => (
+                version,
+                IpAddress::Ipv4(Ipv4Packet::new_unchecked(packet).dst_addr()),
+            ),
+            #[cfg(feature = "proto-ipv6")]
+            Ok(version @ IpVersion::Ipv6) => (
+                version,
+                IpAddress::Ipv6(Ipv6Packet::new_unchecked(packet).dst_addr()),
+            ),
+            _ => return Ok(()),
+        };
+        if packet.len() > self.ip_mtu_of(version) {
+            net_debug!("forwarding: packet too big, dropping it");
+            return Ok(());
+        }
+
+        let checksum_caps = self.checksum_caps();
+        match self.caps.medium {
+            #[cfg(feature = "medium-ethernet")]
+            Medium::Ethernet => {
+                // The source address is only used to resolve the next hop.
+                let src_addr = self
+                    .get_source_address(dst_addr)
+                    .ok_or(DispatchError::NoRoute)?;
+                let (dst_hardware_addr, tx_token) =
+                    self.lookup_hardware_addr(tx_token, &src_addr, &dst_addr)?;
+                let ethertype = match version {
+                    #[cfg(feature = "proto-ipv4")]
+                    IpVersion::Ipv4 => EthernetProtocol::Ipv4,
+                    #[cfg(feature = "proto-ipv6")]
+                    IpVersion::Ipv6 => EthernetProtocol::Ipv6,
+                };
+                self.dispatch_ethernet(tx_token, ethertype, packet.len(), |mut frame| {
+                    frame.set_dst_addr(dst_hardware_addr.ethernet_or_panic());
+                    frame.payload_mut().copy_from_slice(packet);
+                    fill_forwarded_checksum(&checksum_caps, version, frame.payload_mut());
+                })
+            }
+            #[cfg(feature = "medium-ip")]
+            Medium::Ip => {
+                if self.route(&dst_addr, self.now).is_none() {
+                    self.stats.dropped_no_route = self.stats.dropped_no_route.wrapping_add(1);
+                    return Err(DispatchError::NoRoute);
+                }
+                let mut tx_token = tx_token;
+                tx_token.set_ip_version(version);
+                tx_token.consume(packet.len(), |buf| {
+                    buf.copy_from_slice(packet);
+                    fill_forwarded_checksum(&checksum_caps, version, buf);
+                });
+                Ok(())
+            }
+            #[cfg(feature = "medium-ieee802154")]
+            Medium::Ieee802154 => {
+                let _ = version;
+                net_debug!("forwarding: not supported on IEEE 802.15.4 links");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Fill the header checksum of a forwarded IPv4 packet, unless the device computes it.
+fn fill_forwarded_checksum(caps: &ChecksumCapabilities, version: IpVersion, packet: &mut [u8]) {
+    match version {
+        #[cfg(feature = "proto-ipv4")]
+        IpVersion::Ipv4 if caps.ipv4.tx() => Ipv4Packet::new_unchecked(packet).fill_checksum(),
+        _ => (),
+    }
+}
@@ -0,0 +1,158 @@
+use super::*;
+
+/// What to do with a packet seen by a [`PacketHook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Verdict {
+    /// Let the packet through unchanged.
+    Accept,
+    /// Silently discard the packet.
+    Drop,
+    /// Let the packet through with the header as changed by the hook.
+    ///
+    /// Only the hop limit, the traffic class and the flow label can be changed; changes
+    /// to the other fields of the header are ignored, as they would invalidate the
+    /// transport checksum.
+    Mangle,
+}
+
+/// A callback inspecting the packets of an interface.
+///
+/// It is called with the current time, the IP header of the packet, and for TCP and
+/// UDP packets, their source and destination ports. See
+/// [`Interface::set_prerouting_hook`] and [`Interface::set_postrouting_hook`].
+pub type PacketHook =
+    fn(timestamp: Instant, ip_repr: &mut IpRepr, ports: Option<(u16, u16)>) -> Verdict;
+
+impl Interface {
+    /// Set the hook called on every IP packet received for the interface, before it is
+    /// passed to the sockets. `None` removes the hook.
+    ///
+    /// Packets forwarded by a [`Forwarder`] do not go through the hook.
+    pub fn set_prerouting_hook(&mut self, hook: Option<PacketHook>) {
+        self.inner.prerouting_hook = hook;
+    }
+
+    /// Set the hook called on every IP packet sent by the interface, whether it comes
+    /// from a socket or from the interface itself. `None` removes the hook.
+    ///
+    /// Packets forwarded by a [`Forwarder`] do not go through the hook.
+    pub fn set_postrouting_hook(&mut self, hook: Option<PacketHook>) {
+        self.inner.postrouting_hook = hook;
+    }
+}
+
+impl InterfaceInner {
+    /// Run the pre-routing hook on a received packet, returning its header, or `None`
+    /// if it must be dropped.
+    pub(super) fn prerouting(&self, ip_repr: IpRepr, ip_payload: &[u8]) -> Option<IpRepr> {
+        let hook = match self.prerouting_hook {
+            Some(hook) => hook,
+            None => return Some(ip_repr),
+        };
+        let ports = match ip_repr.next_header() {
+            IpProtocol::Tcp | IpProtocol::Udp if ip_payload.len() >= 4 => Some((
+                u16::from_be_bytes([ip_payload[0], ip_payload[1]]),
+                u16::from_be_bytes([ip_payload[2], ip_payload[3]]),
+            )),
+            _ => None,
+        };
+        run(hook, self.now, ip_repr, ports)
+    }
+
+    /// Run the post-routing hook on a packet about to be sent, returning `false` if it
+    /// must be dropped.
+    pub(super) fn postrouting(&self, packet: &mut IpPacket) -> bool {
+        let hook = match self.postrouting_hook {
+            Some(hook) => hook,
+            None => return true,
+        };
+        let ports = match packet {
+            #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
+            IpPacket::Udp((_, udp_repr, _)) => Some((udp_repr.src_port, udp_repr.dst_port)),
+            #[cfg(feature = "socket-tcp")]
+            IpPacket::Tcp((_, tcp_repr)) => Some((tcp_repr.src_port, tcp_repr.dst_port)),
+            #[cfg(feature = "socket-dhcpv4")]
+            IpPacket::Dhcpv4((_, udp_repr, _)) => Some((udp_repr.src_port, udp_repr.dst_port)),
+            #[cfg(feature = "socket-dhcpv6")]
+            IpPacket::Dhcpv6((_, udp_repr, _)) => Some((udp_repr.src_port, udp_repr.dst_port)),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        };
+        match run(hook, self.now, packet.ip_repr(), ports) {
+            Some(ip_repr) => {
+                packet.set_mangled(&ip_repr);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn run(
+    hook: PacketHook,
+    timestamp: Instant,
+    mut ip_repr: IpRepr,
+    ports: Option<(u16, u16)>,
+) -> Option<IpRepr> {
+    let mut changed = ip_repr.clone();
+    match hook(timestamp, &mut changed, ports) {
+        Verdict::Accept => Some(ip_repr),
+        Verdict::Drop => {
+            net_trace!("iface: hook dropped packet to {}", ip_repr.dst_addr());
+            None
+        }
+        Verdict::Mangle => {
+            mangle(&mut ip_repr, &changed);
+            Some(ip_repr)
+        }
+    }
+}
+
+/// Copy the fields a hook may change from `changed` to `ip_repr`.
+fn mangle(ip_repr: &mut IpRepr, changed: &IpRepr) {
+    match ip_repr {
+        #[cfg(feature = "proto-ipv4")]
+        IpRepr::Ipv4(repr) => mangle_ipv4(repr, changed),
+        #[cfg(feature = "proto-ipv6")]
+        IpRepr::Ipv6(repr) => mangle_ipv6(repr, changed),
+    }
+}
+
+#[cfg(feature = "proto-ipv4")]
+fn mangle_ipv4(repr: &mut Ipv4Repr, changed: &IpRepr) {
+    repr.hop_limit = changed.hop_limit();
+    repr.dscp = changed.traffic_class() >> 2;
+}
+
+#[cfg(feature = "proto-ipv6")]
+fn mangle_ipv6(repr: &mut Ipv6Repr, changed: &IpRepr) {
+    repr.hop_limit = changed.hop_limit();
+    repr.traffic_class = changed.traffic_class();
+    if let Some(flow_label) = changed.flow_label() {
+        repr.flow_label = flow_label;
+    }
+}
+
+impl<'a> IpPacket<'a> {
+    fn set_mangled(&mut self, changed: &IpRepr) {
+        match self {
+            #[cfg(feature = "proto-ipv4")]
+            IpPacket::Icmpv4((repr, _)) => mangle_ipv4(repr, changed),
+            #[cfg(feature = "proto-igmp")]
+            IpPacket::Igmp((repr, _)) => mangle_ipv4(repr, changed),
+            #[cfg(feature = "proto-ipv6")]
+            IpPacket::Icmpv6((repr, _)) => mangle_ipv6(repr, changed),
+            #[cfg(feature = "socket-raw")]
+            IpPacket::Raw((repr, _)) => mangle(repr, changed),
+            #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
+            IpPacket::Udp((repr, _, _)) => mangle(repr, changed),
+            #[cfg(feature = "socket-tcp")]
+            IpPacket::Tcp((repr, _)) => mangle(repr, changed),
+            #[cfg(feature = "socket-dhcpv4")]
+            IpPacket::Dhcpv4((repr, _, _)) => mangle_ipv4(repr, changed),
+            #[cfg(feature = "socket-dhcpv6")]
+            IpPacket::Dhcpv6((repr, _, _)) => mangle_ipv6(repr, changed),
+        }
+    }
+}
@@ -1,5 +1,7 @@
-use super::{check, IgmpReportState, Interface, InterfaceInner, IpPacket};
+use super::{check, Counting, IgmpReportState, Interface, InterfaceInner, IpPacket};
+use crate::iface::SocketSet;
 use crate::phy::Device;
+use crate::storage::TableStorage;
 use crate::time::{Duration, Instant};
 use crate::wire::*;
 
@@ -17,6 +19,22 @@ pub enum MulticastError {
     Ipv6NotSupported,
 }
 
+/// Who asked the interface to be a member of a multicast group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MulticastRefs {
+    /// The group was joined with `Interface::join_multicast_group`.
+    pub explicit: bool,
+    /// The number of sockets that joined the group.
+    pub sockets: usize,
+}
+
+/// Storage for one entry of the table of joined multicast groups, see
+/// [`Config::ipv4_multicast_group_storage`].
+///
+/// [`Config::ipv4_multicast_group_storage`]: crate::iface::Config::ipv4_multicast_group_storage
+pub type MulticastGroupStorage = TableStorage<(Ipv4Address, MulticastRefs)>;
+
 impl Interface {
     /// Add an address to a list of subscribed multicast IP addresses.
     ///
@@ -35,23 +53,36 @@ impl Interface {
 
         match addr.into() {
             IpAddress::Ipv4(addr) => {
-                let is_not_new = self
-                    .inner
-                    .ipv4_multicast_groups
-                    .insert(addr, ())
-                    .map_err(|_| MulticastError::GroupTableFull)?
-                    .is_some();
+                let is_not_new = match self.inner.ipv4_multicast_groups.get_value_mut(&addr) {
+                    Some(refs) => {
+                        refs.explicit = true;
+                        true
+                    }
+                    None => {
+                        let refs = MulticastRefs {
+                            explicit: true,
+                            sockets: 0,
+                        };
+                        self.inner
+                            .ipv4_multicast_groups
+                            .insert(addr, refs)
+                            .map_err(|_| MulticastError::GroupTableFull)?;
+                        false
+                    }
+                };
                 if is_not_new {
                     Ok(false)
                 } else if let Some(pkt) = self.inner.igmp_report_packet(IgmpVersion::Version2, addr)
                 {
                     // Send initial membership report
+                    let mut device = Counting::new(device);
                     let tx_token = device
                         .transmit(timestamp)
                         .ok_or(MulticastError::Exhausted)?;
 
                     // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
                     self.inner.dispatch_ip(tx_token, pkt, None).unwrap();
+                    device.count_into(&mut self.inner.stats);
 
                     Ok(true)
                 } else {
@@ -67,7 +98,8 @@ impl Interface {
     /// Remove an address from the subscribed multicast IP addresses.
     ///
     /// Returns `Ok(leave_sent)` if the address was removed successfully, where `leave_sent`
-    /// indicates whether an immediate leave packet has been sent.
+    /// indicates whether an immediate leave packet has been sent. The interface stays a
+    /// member of the group while sockets have joined it.
     pub fn leave_multicast_group<D, T: Into<IpAddress>>(
         &mut self,
         device: &mut D,
@@ -81,20 +113,31 @@ impl Interface {
 
         match addr.into() {
             IpAddress::Ipv4(addr) => {
-                let was_not_present = self.inner.ipv4_multicast_groups.remove(&addr).is_none();
-                if was_not_present {
+                let still_needed = match self.inner.ipv4_multicast_groups.get_value_mut(&addr) {
+                    Some(refs) if refs.explicit => {
+                        refs.explicit = false;
+                        refs.sockets > 0
+                    }
+                    _ => true,
+                };
+                if still_needed {
                     Ok(false)
                 } else if let Some(pkt) = self.inner.igmp_leave_packet(addr) {
+                    self.inner.ipv4_multicast_groups.remove_key(&addr);
+
                     // Send group leave packet
+                    let mut device = Counting::new(device);
                     let tx_token = device
                         .transmit(timestamp)
                         .ok_or(MulticastError::Exhausted)?;
 
                     // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
                     self.inner.dispatch_ip(tx_token, pkt, None).unwrap();
+                    device.count_into(&mut self.inner.stats);
 
                     Ok(true)
                 } else {
+                    self.inner.ipv4_multicast_groups.remove_key(&addr);
                     Ok(false)
                 }
             }
@@ -104,6 +147,63 @@ impl Interface {
         }
     }
 
+    /// Bring the multicast group memberships of the interface in line with the groups
+    /// joined by sockets, reporting newly joined groups and leaving groups that neither
+    /// a socket nor the application needs anymore.
+    ///
+    /// Reports that cannot be sent because the device is exhausted are not retried; the
+    /// membership is reported again in response to the next query.
+    pub(crate) fn sync_multicast_groups<D>(&mut self, device: &mut D, sockets: &SocketSet<'_>)
+    where
+        D: Device + ?Sized,
+    {
+        for (_, refs) in self.inner.ipv4_multicast_groups.iter_mut() {
+            refs.sockets = 0;
+        }
+
+        for item in sockets.items() {
+            for group in item.socket.multicast_groups() {
+                let groups = &mut self.inner.ipv4_multicast_groups;
+                if let Some(refs) = groups.get_value_mut(group) {
+                    refs.sockets += 1;
+                    continue;
+                }
+                let refs = MulticastRefs {
+                    explicit: false,
+                    sockets: 1,
+                };
+                if groups.insert(*group, refs).is_err() {
+                    net_debug!("multicast group table full, cannot join {}", group);
+                    continue;
+                }
+
+                if let Some(pkt) = self.inner.igmp_report_packet(IgmpVersion::Version2, *group) {
+                    if let Some(tx_token) = device.transmit(self.inner.now) {
+                        // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+                        self.inner.dispatch_ip(tx_token, pkt, None).unwrap();
+                    }
+                }
+            }
+        }
+
+        loop {
+            let left = self
+                .inner
+                .ipv4_multicast_groups
+                .iter()
+                .find(|(_, refs)| !refs.explicit && refs.sockets == 0)
+                .map(|(addr, _)| *addr);
+            let Some(addr) = left else { break };
+            self.inner.ipv4_multicast_groups.remove_key(&addr);
+            if let Some(pkt) = self.inner.igmp_leave_packet(addr) {
+                if let Some(tx_token) = device.transmit(self.inner.now) {
+                    // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+                    self.inner.dispatch_ip(tx_token, pkt, None).unwrap();
+                }
+            }
+        }
+    }
+
     /// Check whether the interface listens to given destination multicast IP address.
     pub fn has_multicast_group<T: Into<IpAddress>>(&self, addr: T) -> bool {
         self.inner.has_multicast_group(addr)
@@ -145,7 +245,7 @@ impl Interface {
                     .ipv4_multicast_groups
                     .iter()
                     .nth(next_index)
-                    .map(|(addr, ())| *addr);
+                    .map(|(addr, _)| *addr);
 
                 match addr {
                     Some(addr) => {
@@ -189,7 +289,7 @@ impl InterfaceInner {
         match addr.into() {
             IpAddress::Ipv4(key) => {
                 key == Ipv4Address::MULTICAST_ALL_SYSTEMS
-                    || self.ipv4_multicast_groups.get(&key).is_some()
+                    || self.ipv4_multicast_groups.get_value(&key).is_some()
             }
             #[allow(unreachable_patterns)]
             _ => false,
@@ -206,8 +306,8 @@ impl InterfaceInner {
         ipv4_repr: Ipv4Repr,
         ip_payload: &'frame [u8],
     ) -> Option<IpPacket<'frame>> {
-        let igmp_packet = check!(IgmpPacket::new_checked(ip_payload));
-        let igmp_repr = check!(IgmpRepr::parse(&igmp_packet));
+        let igmp_packet = check!(IgmpPacket::new_checked(ip_payload), self.stats, igmp_errors);
+        let igmp_repr = check!(IgmpRepr::parse(&igmp_packet), self.stats, igmp_errors);
 
         // FIXME: report membership after a delay
         match igmp_repr {
@@ -29,7 +29,12 @@ impl InterfaceInner {
         ipv4_packet: &Ipv4Packet<&'payload T>,
         _fragments: Option<&'output mut PacketAssemblerSet<Ipv4FragKey>>,
     ) -> Option<IpPacket<'output>> {
-        let ipv4_repr = check!(Ipv4Repr::parse(ipv4_packet, &self.caps.checksum));
+        let ipv4_repr = check!(
+            Ipv4Repr::parse(ipv4_packet, &self.caps.checksum),
+            self.stats,
+            ipv4_errors,
+            bad_checksum: self.caps.checksum.ipv4.rx() && !ipv4_packet.verify_checksum()
+        );
         if !self.is_unicast_v4(ipv4_repr.src_addr) && ipv4_repr.src_addr.is_unspecified() == false {
             // Discard packets with non-unicast source addresses.
             net_debug!("non-unicast source address");
@@ -45,13 +50,7 @@ impl InterfaceInner {
             if ipv4_packet.more_frags() || ipv4_packet.frag_offset() != 0 {
                 let key = ipv4_packet.get_key();
 
-                let f = match fragments.get(&key, self.now + REASSEMBLY_TIMEOUT) {
-                    Ok(f) => f,
-                    Err(_) => {
-                        net_debug!("No available packet assembler for fragmented packet");
-                        return None;
-                    }
-                };
+                let f = fragments.get(&key, self.now + REASSEMBLY_TIMEOUT);
 
                 if !ipv4_packet.more_frags() {
                     // This is the last fragment, so we know the total size
@@ -81,6 +80,10 @@ impl InterfaceInner {
         #[cfg(not(feature = "proto-ipv4-fragmentation"))]
         let ip_payload = ipv4_packet.payload();
 
+        let ipv4_repr = match self.prerouting(ipv4_repr.into(), ip_payload) {
+            Some(IpRepr::Ipv4(ipv4_repr)) => ipv4_repr,
+            _ => return None,
+        };
         let ip_repr = IpRepr::Ipv4(ipv4_repr);
 
         #[cfg(feature = "socket-raw")]
@@ -91,7 +94,7 @@ impl InterfaceInner {
         #[cfg(feature = "socket-dhcpv4")]
         {
             if ipv4_repr.next_header == IpProtocol::Udp && self.hardware_addr.is_some() {
-                let udp_packet = check!(UdpPacket::new_checked(ip_payload));
+                let udp_packet = check!(UdpPacket::new_checked(ip_payload), self.stats, udp_errors);
                 if let Some(dhcp_socket) = sockets
                     .items_mut()
                     .find_map(|i| dhcpv4::Socket::downcast_mut(&mut i.socket))
@@ -102,12 +105,13 @@ impl InterfaceInner {
                         && udp_packet.dst_port() == dhcp_socket.client_port
                     {
                         let (src_addr, dst_addr) = (ip_repr.src_addr(), ip_repr.dst_addr());
-                        let udp_repr = check!(UdpRepr::parse(
-                            &udp_packet,
-                            &src_addr,
-                            &dst_addr,
-                            &self.caps.checksum
-                        ));
+                        let udp_repr = check!(
+                            UdpRepr::parse(&udp_packet, &src_addr, &dst_addr, &self.caps.checksum),
+                            self.stats,
+                            udp_errors,
+                            bad_checksum: self.caps.checksum.udp.rx()
+                                && !udp_packet.verify_checksum(&src_addr, &dst_addr)
+                        );
                         let udp_payload = udp_packet.payload();
 
                         dhcp_socket.process(self, &ipv4_repr, &udp_repr, udp_payload);
@@ -122,13 +126,15 @@ impl InterfaceInner {
             && !self.is_broadcast_v4(ipv4_repr.dst_addr)
         {
             // Ignore IP packets not directed at us, or broadcast, or any of the multicast groups.
-            // If AnyIP is enabled, also check if the packet is routed locally.
-            if !self.any_ip
-                || !ipv4_repr.dst_addr.is_unicast()
-                || self
-                    .routes
-                    .lookup(&IpAddress::Ipv4(ipv4_repr.dst_addr), self.now)
-                    .map_or(true, |router_addr| !self.has_ip_addr(router_addr))
+            // If AnyIP is enabled, also check if the packet is routed locally, or sent to
+            // one of the AnyIP prefixes.
+            if !self.is_any_ip(ipv4_repr.dst_addr, ipv4_repr.next_header)
+                && (!self.any_ip
+                    || !ipv4_repr.dst_addr.is_unicast()
+                    || self
+                        .routes
+                        .lookup(&IpAddress::Ipv4(ipv4_repr.dst_addr), self.now)
+                        .map_or(true, |router_addr| !self.has_ip_addr(router_addr)))
             {
                 return None;
             }
@@ -142,13 +148,15 @@ impl InterfaceInner {
 
             #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
             IpProtocol::Udp => {
-                let udp_packet = check!(UdpPacket::new_checked(ip_payload));
-                let udp_repr = check!(UdpRepr::parse(
-                    &udp_packet,
-                    &ipv4_repr.src_addr.into(),
-                    &ipv4_repr.dst_addr.into(),
-                    &self.checksum_caps(),
-                ));
+                let udp_packet = check!(UdpPacket::new_checked(ip_payload), self.stats, udp_errors);
+                let (src_addr, dst_addr) = (ipv4_repr.src_addr.into(), ipv4_repr.dst_addr.into());
+                let udp_repr = check!(
+                    UdpRepr::parse(&udp_packet, &src_addr, &dst_addr, &self.checksum_caps()),
+                    self.stats,
+                    udp_errors,
+                    bad_checksum: self.checksum_caps().udp.rx()
+                        && !udp_packet.verify_checksum(&src_addr, &dst_addr)
+                );
 
                 self.process_udp(
                     sockets,
@@ -188,8 +196,12 @@ impl InterfaceInner {
         timestamp: Instant,
         eth_frame: &EthernetFrame<&'frame T>,
     ) -> Option<EthernetPacket<'frame>> {
-        let arp_packet = check!(ArpPacket::new_checked(eth_frame.payload()));
-        let arp_repr = check!(ArpRepr::parse(&arp_packet));
+        let arp_packet = check!(
+            ArpPacket::new_checked(eth_frame.payload()),
+            self.stats,
+            arp_errors
+        );
+        let arp_repr = check!(ArpRepr::parse(&arp_packet), self.stats, arp_errors);
 
         match arp_repr {
             ArpRepr::EthernetIpv4 {
@@ -199,8 +211,17 @@ impl InterfaceInner {
                 target_protocol_addr,
                 ..
             } => {
-                // Only process ARP packets for us.
-                if !self.has_ip_addr(target_protocol_addr) {
+                self.autoip_process_arp(
+                    source_hardware_addr,
+                    source_protocol_addr,
+                    target_protocol_addr,
+                );
+
+                // Another node sending from one of our addresses is a conflict.
+                if self.has_ip_addr(source_protocol_addr)
+                    && Some(HardwareAddress::Ethernet(source_hardware_addr)) != self.hardware_addr
+                {
+                    self.address_conflict(source_protocol_addr.into());
                     return None;
                 }
 
@@ -209,6 +230,21 @@ impl InterfaceInner {
                     net_debug!("arp: unknown operation code");
                     return None;
                 }
+                if operation.is_reverse() {
+                    net_debug!("arp: reverse operation code");
+                    return None;
+                }
+
+                // Only process ARP packets for us, answering requests for proxied
+                // addresses on their behalf.
+                if !self.has_ip_addr(target_protocol_addr) {
+                    return self.proxy_arp_reply(
+                        operation,
+                        source_hardware_addr,
+                        source_protocol_addr,
+                        target_protocol_addr,
+                    );
+                }
 
                 // Discard packets with non-unicast source addresses.
                 if !source_protocol_addr.is_unicast() || !source_hardware_addr.is_unicast() {
@@ -230,6 +266,7 @@ impl InterfaceInner {
                     source_hardware_addr.into(),
                     timestamp,
                 );
+                self.end_batch();
 
                 if operation == ArpOperation::Request {
                     let src_hardware_addr = match self.hardware_addr {
@@ -251,14 +288,102 @@ impl InterfaceInner {
         }
     }
 
+    #[cfg(feature = "medium-ethernet")]
+    fn proxy_arp_reply<'frame>(
+        &self,
+        operation: ArpOperation,
+        source_hardware_addr: EthernetAddress,
+        source_protocol_addr: Ipv4Address,
+        target_protocol_addr: Ipv4Address,
+    ) -> Option<EthernetPacket<'frame>> {
+        // Neither answer for the segment the requester is on, nor gratuitous requests.
+        if operation != ArpOperation::Request
+            || !source_protocol_addr.is_unicast()
+            || !source_hardware_addr.is_unicast()
+            || !self.proxy_arp_prefixes.iter().any(|cidr| {
+                cidr.contains_addr(&target_protocol_addr)
+                    && !cidr.contains_addr(&source_protocol_addr)
+            })
+        {
+            return None;
+        }
+
+        net_debug!(
+            "arp: answering for proxied address {}",
+            target_protocol_addr
+        );
+        let src_hardware_addr = match self.hardware_addr {
+            Some(HardwareAddress::Ethernet(addr)) => addr,
+            _ => unreachable!(),
+        };
+        Some(EthernetPacket::Arp(ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Reply,
+            source_hardware_addr: src_hardware_addr,
+            source_protocol_addr: target_protocol_addr,
+            target_hardware_addr: source_hardware_addr,
+            target_protocol_addr: source_protocol_addr,
+        }))
+    }
+
+    #[cfg(feature = "medium-ethernet")]
+    pub(super) fn process_rarp<'frame, T: AsRef<[u8]>>(
+        &mut self,
+        eth_frame: &EthernetFrame<&'frame T>,
+    ) -> Option<EthernetPacket<'frame>> {
+        let arp_packet = check!(
+            ArpPacket::new_checked(eth_frame.payload()),
+            self.stats,
+            arp_errors
+        );
+        let arp_repr = check!(ArpRepr::parse(&arp_packet), self.stats, arp_errors);
+
+        match arp_repr {
+            ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::RequestReverse,
+                target_hardware_addr,
+                ..
+            } => {
+                let target_protocol_addr = *self.rarp_table.get(&target_hardware_addr)?;
+                let (src_hardware_addr, src_protocol_addr) =
+                    match (self.hardware_addr, self.ipv4_addr()) {
+                        (Some(HardwareAddress::Ethernet(hw)), Some(ip)) => (hw, ip),
+                        _ => return None,
+                    };
+
+                net_debug!(
+                    "rarp: answering {} with {}",
+                    target_hardware_addr,
+                    target_protocol_addr
+                );
+                Some(EthernetPacket::Arp(ArpRepr::EthernetIpv4 {
+                    operation: ArpOperation::ReplyReverse,
+                    source_hardware_addr: src_hardware_addr,
+                    source_protocol_addr: src_protocol_addr,
+                    target_hardware_addr,
+                    target_protocol_addr,
+                }))
+            }
+            _ => None,
+        }
+    }
+
     pub(super) fn process_icmpv4<'frame>(
         &mut self,
         _sockets: &mut SocketSet,
         ip_repr: IpRepr,
         ip_payload: &'frame [u8],
     ) -> Option<IpPacket<'frame>> {
-        let icmp_packet = check!(Icmpv4Packet::new_checked(ip_payload));
-        let icmp_repr = check!(Icmpv4Repr::parse(&icmp_packet, &self.caps.checksum));
+        let icmp_packet = check!(
+            Icmpv4Packet::new_checked(ip_payload),
+            self.stats,
+            icmp_errors
+        );
+        let icmp_repr = check!(
+            Icmpv4Repr::parse(&icmp_packet, &self.caps.checksum),
+            self.stats,
+            icmp_errors,
+            bad_checksum: self.caps.checksum.icmpv4.rx() && !icmp_packet.verify_checksum()
+        );
 
         #[cfg(feature = "socket-icmp")]
         let mut handled_by_icmp_socket = false;
@@ -268,7 +393,11 @@ impl InterfaceInner {
             .items_mut()
             .filter_map(|i| icmp::Socket::downcast_mut(&mut i.socket))
         {
-            if icmp_socket.accepts(self, &ip_repr, &icmp_repr.into()) {
+            if icmp_socket.accepts(self, &ip_repr, &icmp_repr.into())
+                && icmp_socket
+                    .filter()
+                    .passes_ipv4(icmp_packet.msg_type(), icmp_packet.msg_code())
+            {
                 icmp_socket.process(self, &ip_repr, &icmp_repr.into());
                 handled_by_icmp_socket = true;
             }
@@ -323,6 +452,7 @@ impl InterfaceInner {
                 next_header: IpProtocol::Icmp,
                 payload_len: icmp_repr.buffer_len(),
                 hop_limit: 64,
+                dscp: 0,
             };
             Some(IpPacket::Icmpv4((ipv4_reply_repr, icmp_repr)))
         } else if self.is_broadcast_v4(ipv4_repr.dst_addr) {
@@ -336,6 +466,7 @@ impl InterfaceInner {
                             next_header: IpProtocol::Icmp,
                             payload_len: icmp_repr.buffer_len(),
                             hop_limit: 64,
+                            dscp: 0,
                         };
                         Some(IpPacket::Icmpv4((ipv4_reply_repr, icmp_repr)))
                     }
@@ -351,7 +482,7 @@ impl InterfaceInner {
     #[cfg(feature = "proto-ipv4-fragmentation")]
     pub(super) fn dispatch_ipv4_out_packet<Tx: TxToken>(
         &mut self,
-        tx_token: Tx,
+        mut tx_token: Tx,
         out_packet: &mut Ipv4OutPacket,
     ) {
         let Ipv4OutPacket {
@@ -367,7 +498,7 @@ impl InterfaceInner {
 
         let caps = self.caps.clone();
 
-        let mtu_max = self.ip_mtu();
+        let mtu_max = self.ipv4_fragment_len(repr.buffer_len());
         let ip_len = (*packet_len - *sent_bytes + repr.buffer_len()).min(mtu_max);
         let payload_len = ip_len - repr.buffer_len();
 
@@ -378,7 +509,7 @@ impl InterfaceInner {
         let mut tx_len = ip_len;
         #[cfg(feature = "medium-ethernet")]
         if matches!(caps.medium, Medium::Ethernet) {
-            tx_len += EthernetFrame::<&[u8]>::header_len();
+            tx_len += self.ethernet_header_len();
         }
 
         // Emit function for the Ethernet header.
@@ -390,19 +521,21 @@ impl InterfaceInner {
             frame.set_src_addr(src_addr);
             frame.set_dst_addr(*dst_hardware_addr);
 
-            match repr.version() {
+            let ethertype = match repr.version() {
                 #[cfg(feature = "proto-ipv4")]
-                IpVersion::Ipv4 => frame.set_ethertype(EthernetProtocol::Ipv4),
+                IpVersion::Ipv4 => EthernetProtocol::Ipv4,
                 #[cfg(feature = "proto-ipv6")]
-                IpVersion::Ipv6 => frame.set_ethertype(EthernetProtocol::Ipv6),
-            }
+                IpVersion::Ipv6 => EthernetProtocol::Ipv6,
+            };
+            frame.set_encapsulated_ethertype(self.ethernet_encapsulation, ethertype);
         };
 
+        tx_token.set_ip_version(IpVersion::Ipv4);
         tx_token.consume(tx_len, |mut tx_buffer| {
             #[cfg(feature = "medium-ethernet")]
             if matches!(self.caps.medium, Medium::Ethernet) {
                 emit_ethernet(&IpRepr::Ipv4(*repr), tx_buffer);
-                tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
+                tx_buffer = &mut tx_buffer[self.ethernet_header_len()..];
             }
 
             let mut packet = Ipv4Packet::new_unchecked(&mut tx_buffer[..repr.buffer_len()]);
@@ -444,6 +577,7 @@ impl InterfaceInner {
                 next_header: IpProtocol::Igmp,
                 payload_len: igmp_repr.buffer_len(),
                 hop_limit: 1,
+                dscp: 0,
                 // [#183](https://github.com/m-labs/smoltcp/issues/183).
             },
             igmp_repr,
@@ -465,6 +599,7 @@ impl InterfaceInner {
                     next_header: IpProtocol::Igmp,
                     payload_len: igmp_repr.buffer_len(),
                     hop_limit: 1,
+                    dscp: 0,
                 },
                 igmp_repr,
             ))
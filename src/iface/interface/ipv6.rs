@@ -10,6 +10,9 @@ use crate::socket::AnySocket;
 
 use crate::wire::*;
 
+/// The offset of the Next Header field in the IPv6 header.
+const NXT_HDR_FIELD: usize = 6;
+
 impl InterfaceInner {
     #[cfg(feature = "proto-ipv6")]
     pub(super) fn process_ipv6<'frame, T: AsRef<[u8]> + ?Sized>(
@@ -17,7 +20,7 @@ impl InterfaceInner {
         sockets: &mut SocketSet,
         ipv6_packet: &Ipv6Packet<&'frame T>,
     ) -> Option<IpPacket<'frame>> {
-        let ipv6_repr = check!(Ipv6Repr::parse(ipv6_packet));
+        let ipv6_repr = check!(Ipv6Repr::parse(ipv6_packet), self.stats, ipv6_errors);
 
         if !ipv6_repr.src_addr.is_unicast() {
             // Discard packets with non-unicast source addresses.
@@ -26,6 +29,15 @@ impl InterfaceInner {
         }
 
         let ip_payload = ipv6_packet.payload();
+        let ipv6_repr = match self.prerouting(ipv6_repr.into(), ip_payload) {
+            Some(IpRepr::Ipv6(ipv6_repr)) => ipv6_repr,
+            _ => return None,
+        };
+
+        #[cfg(feature = "proto-ipv4")]
+        if self.clat_accepts(&ipv6_repr) {
+            return self.clat_ingress(ipv6_repr, ip_payload);
+        }
 
         #[cfg(feature = "socket-raw")]
         let handled_by_raw_socket = self.raw_socket_filter(sockets, &ipv6_repr.into(), ip_payload);
@@ -36,36 +48,45 @@ impl InterfaceInner {
             sockets,
             ipv6_repr,
             ipv6_repr.next_header,
+            NXT_HDR_FIELD,
             handled_by_raw_socket,
             ip_payload,
+            0,
         )
     }
 
     /// Given the next header value forward the payload onto the correct process
     /// function.
+    ///
+    /// The header starts at `offset` in `ip_payload`, and its type was given by the
+    /// Next Header field at `nxt_hdr_pointer` in the packet.
     #[cfg(feature = "proto-ipv6")]
+    #[allow(clippy::too_many_arguments)]
     pub(super) fn process_nxt_hdr<'frame>(
         &mut self,
         sockets: &mut SocketSet,
         ipv6_repr: Ipv6Repr,
         nxt_hdr: IpProtocol,
+        nxt_hdr_pointer: usize,
         handled_by_raw_socket: bool,
         ip_payload: &'frame [u8],
+        offset: usize,
     ) -> Option<IpPacket<'frame>> {
+        let payload = &ip_payload[offset..];
         match nxt_hdr {
-            IpProtocol::Icmpv6 => {
-                self.process_icmpv6(sockets, ipv6_repr.into(), ip_payload)
-            },
+            IpProtocol::Icmpv6 => self.process_icmpv6(sockets, ipv6_repr.into(), payload),
 
             #[cfg(any(feature = "socket-udp", feature = "socket-dns", feature = "socket-dhcpv6"))]
             IpProtocol::Udp => {
-                let udp_packet = check!(UdpPacket::new_checked(ip_payload));
-                let udp_repr = check!(UdpRepr::parse(
-                    &udp_packet,
-                    &ipv6_repr.src_addr.into(),
-                    &ipv6_repr.dst_addr.into(),
-                    &self.checksum_caps(),
-                ));
+                let udp_packet = check!(UdpPacket::new_checked(payload), self.stats, udp_errors);
+                let (src_addr, dst_addr) = (ipv6_repr.src_addr.into(), ipv6_repr.dst_addr.into());
+                let udp_repr = check!(
+                    UdpRepr::parse(&udp_packet, &src_addr, &dst_addr, &self.checksum_caps()),
+                    self.stats,
+                    udp_errors,
+                    bad_checksum: self.checksum_caps().udp.rx()
+                        && !udp_packet.verify_checksum(&src_addr, &dst_addr)
+                );
 
                 self.process_udp(
                     sockets,
@@ -80,29 +101,53 @@ impl InterfaceInner {
             #[cfg(feature = "socket-tcp")]
             IpProtocol::Tcp => {
                 let reset_orphans = handled_by_raw_socket == false;
-                self.process_tcp(sockets, ipv6_repr.into(), ip_payload, reset_orphans)
-            },
-
-            IpProtocol::HopByHop => {
-                self.process_hopbyhop(sockets, ipv6_repr, handled_by_raw_socket, ip_payload)
+                self.process_tcp(sockets, ipv6_repr.into(), payload, reset_orphans)
             }
 
+            // The Hop-by-Hop Options header is only allowed right after the IPv6 header.
+            IpProtocol::HopByHop if offset == 0 => self.process_options(
+                sockets,
+                ipv6_repr,
+                handled_by_raw_socket,
+                ip_payload,
+                offset,
+            ),
+
+            IpProtocol::Ipv6Opts => self.process_options(
+                sockets,
+                ipv6_repr,
+                handled_by_raw_socket,
+                ip_payload,
+                offset,
+            ),
+
+            IpProtocol::Ipv6Route => self.process_routing(
+                sockets,
+                ipv6_repr,
+                handled_by_raw_socket,
+                ip_payload,
+                offset,
+            ),
+
+            IpProtocol::Ipv6Frag => self.process_fragment(
+                sockets,
+                ipv6_repr,
+                handled_by_raw_socket,
+                ip_payload,
+                offset,
+            ),
+
+            IpProtocol::Ipv6NoNxt => None,
+
             #[cfg(feature = "socket-raw")]
             _ if handled_by_raw_socket => None,
 
-            _ => {
-                // Send back as much of the original payload as we can.
-                let payload_len =
-                    icmp_reply_payload_len(ip_payload.len(), IPV6_MIN_MTU, ipv6_repr.buffer_len());
-                let icmp_reply_repr = Icmpv6Repr::ParamProblem {
-                    reason: Icmpv6ParamProblem::UnrecognizedNxtHdr,
-                    // The offending packet is after the IPv6 header.
-                    pointer: ipv6_repr.buffer_len() as u32,
-                    header: ipv6_repr,
-                    data: &ip_payload[0..payload_len],
-                };
-                self.icmpv6_reply(ipv6_repr, icmp_reply_repr)
-            }
+            _ => self.param_problem(
+                ipv6_repr,
+                ip_payload,
+                Icmpv6ParamProblem::UnrecognizedNxtHdr,
+                nxt_hdr_pointer,
+            ),
         }
     }
 
@@ -113,14 +158,59 @@ impl InterfaceInner {
         ip_repr: IpRepr,
         ip_payload: &'frame [u8],
     ) -> Option<IpPacket<'frame>> {
-        let icmp_packet = check!(Icmpv6Packet::new_checked(ip_payload));
-        let icmp_repr = check!(Icmpv6Repr::parse(
-            &ip_repr.src_addr(),
-            &ip_repr.dst_addr(),
-            &icmp_packet,
-            &self.caps.checksum,
-        ));
-        let icmp_payload = icmp_packet.payload();
+        let icmp_packet = check!(
+            Icmpv6Packet::new_checked(ip_payload),
+            self.stats,
+            icmp_errors
+        );
+        let (src_addr, dst_addr) = (ip_repr.src_addr(), ip_repr.dst_addr());
+        let icmp_repr = check!(
+            Icmpv6Repr::parse(&src_addr, &dst_addr, &icmp_packet, &self.caps.checksum),
+            self.stats,
+            icmp_errors,
+            bad_checksum: self.caps.checksum.icmpv6.rx()
+                && !icmp_packet.verify_checksum(&src_addr, &dst_addr)
+        );
+
+        // Learn the NAT64 prefix before the DHCPv6 socket takes the router advertisement.
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        if let Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+            pref64: Some(pref64),
+            ..
+        }) = icmp_repr
+        {
+            if ip_repr.hop_limit() == 0xff {
+                self.learn_nat64_prefix(pref64);
+            }
+        }
+
+        // Likewise for the 6LoWPAN compression context.
+        #[cfg(feature = "medium-ieee802154")]
+        if let Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+            sixlowpan_context: Some(context),
+            ..
+        }) = icmp_repr
+        {
+            if ip_repr.hop_limit() == 0xff {
+                self.learn_sixlowpan_context(context);
+            }
+        }
+
+        // And for the router to register addresses with.
+        #[cfg(feature = "medium-ieee802154")]
+        if let (
+            IpRepr::Ipv6(ipv6_repr),
+            Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+                router_lifetime,
+                lladdr,
+                ..
+            }),
+        ) = (&ip_repr, &icmp_repr)
+        {
+            if ipv6_repr.hop_limit == 0xff {
+                self.learn_registration_router(ipv6_repr.src_addr, *router_lifetime, *lladdr);
+            }
+        }
 
         #[cfg(feature = "socket-dhcpv6")]
         {
@@ -130,7 +220,7 @@ impl InterfaceInner {
             {
                 if icmp_packet.msg_type() == Icmpv6Message::RouterAdvert {
                     if let IpRepr::Ipv6(ipv6_repr) = ip_repr {
-                        dhcp_socket.process_icmpv6(self, &ipv6_repr, &icmp_repr, icmp_payload);
+                        dhcp_socket.process_icmpv6(self, &ipv6_repr, &icmp_repr);
                         return None;
                     } else {
                         net_trace!("ignoring IPv4 packet sent with ICMPv6 payload");
@@ -149,7 +239,11 @@ impl InterfaceInner {
             .items_mut()
             .filter_map(|i| icmp::Socket::downcast_mut(&mut i.socket))
         {
-            if icmp_socket.accepts(self, &ip_repr, &icmp_repr.into()) {
+            if icmp_socket.accepts(self, &ip_repr, &icmp_repr.into())
+                && icmp_socket
+                    .filter()
+                    .passes_ipv6(icmp_packet.msg_type(), icmp_packet.msg_code())
+            {
                 icmp_socket.process(self, &ip_repr, &icmp_repr.into());
                 handled_by_icmp_socket = true;
             }
@@ -185,6 +279,13 @@ impl InterfaceInner {
                 _ => unreachable!(),
             },
 
+            #[cfg(feature = "proto-rpl")]
+            Icmpv6Repr::Rpl(repr) => match ip_repr {
+                IpRepr::Ipv6(ipv6_repr) => self.process_rpl(ipv6_repr, repr),
+                #[allow(unreachable_patterns)]
+                _ => unreachable!(),
+            },
+
             // Don't report an error if a packet with unknown type
             // has been handled by an ICMP socket
             #[cfg(feature = "socket-icmp")]
@@ -205,14 +306,45 @@ impl InterfaceInner {
         repr: NdiscRepr<'frame>,
     ) -> Option<IpPacket<'frame>> {
         match repr {
+            #[cfg(feature = "medium-ieee802154")]
+            NdiscRepr::NeighborAdvert {
+                target_addr,
+                aro: Some(aro),
+                ..
+            } if self.is_registration_reply(ip_repr.src_addr, target_addr) => {
+                self.process_registration(target_addr, aro);
+                None
+            }
+            // Solicitations from other nodes probing the same tentative address are not
+            // treated as a conflict, since they can't be told apart from our own probes
+            // looped back by the device.
+            NdiscRepr::NeighborAdvert { target_addr, .. }
+                if self.is_tentative(&target_addr.into()) =>
+            {
+                self.duplicate_address_detected(target_addr);
+                None
+            }
+            #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+            NdiscRepr::NeighborAdvert {
+                lladdr: Some(lladdr),
+                target_addr,
+                ..
+            } if self.has_ip_addr(target_addr)
+                && !self.is_tentative(&target_addr.into())
+                && self.hardware_addr.map(|addr| addr.into()) != Some(lladdr) =>
+            {
+                self.address_conflict(target_addr.into());
+                None
+            }
             NdiscRepr::NeighborAdvert {
                 lladdr,
                 target_addr,
                 flags,
+                ..
             } => {
                 let ip_addr = ip_repr.src_addr.into();
                 if let Some(lladdr) = lladdr {
-                    let lladdr = check!(lladdr.parse(self.caps.medium));
+                    let lladdr = check!(lladdr.parse(self.caps.medium), self.stats, icmp_errors);
                     if !lladdr.is_unicast() || !target_addr.is_unicast() {
                         return None;
                     }
@@ -227,7 +359,8 @@ impl InterfaceInner {
                         self.neighbor_cache
                             .as_mut()
                             .unwrap()
-                            .fill(ip_addr, lladdr, self.now)
+                            .fill(ip_addr, lladdr, self.now);
+                        self.end_batch();
                     }
                 }
                 None
@@ -238,7 +371,7 @@ impl InterfaceInner {
                 ..
             } => {
                 if let Some(lladdr) = lladdr {
-                    let lladdr = check!(lladdr.parse(self.caps.medium));
+                    let lladdr = check!(lladdr.parse(self.caps.medium), self.stats, icmp_errors);
                     if !lladdr.is_unicast() || !target_addr.is_unicast() {
                         return None;
                     }
@@ -247,69 +380,285 @@ impl InterfaceInner {
                         lladdr,
                         self.now,
                     );
+                    self.end_batch();
                 }
 
                 if self.has_solicited_node(ip_repr.dst_addr) && self.has_ip_addr(target_addr) {
+                    // A solicitation from the unspecified address comes from a node
+                    // performing Duplicate Address Detection, which can only be answered
+                    // by multicast, see RFC 4861 § 7.2.4.
+                    // The address is ours, so the advertisement overrides any other
+                    // cached link-layer address, see RFC 4861 § 7.2.4.
+                    let (flags, dst_addr) = if ip_repr.src_addr.is_unspecified() {
+                        (
+                            NdiscNeighborFlags::OVERRIDE,
+                            Ipv6Address::LINK_LOCAL_ALL_NODES,
+                        )
+                    } else {
+                        (
+                            NdiscNeighborFlags::SOLICITED | NdiscNeighborFlags::OVERRIDE,
+                            ip_repr.src_addr,
+                        )
+                    };
                     let advert = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
-                        flags: NdiscNeighborFlags::SOLICITED,
+                        flags,
                         target_addr,
                         #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
                         lladdr: Some(self.hardware_addr.unwrap().into()),
+                        aro: None,
                     });
                     let ip_repr = Ipv6Repr {
                         src_addr: target_addr,
-                        dst_addr: ip_repr.src_addr,
+                        dst_addr,
                         next_header: IpProtocol::Icmpv6,
                         hop_limit: 0xff,
                         payload_len: advert.buffer_len(),
+                        traffic_class: 0,
+                        flow_label: 0,
                     };
                     Some(IpPacket::Icmpv6((ip_repr, advert)))
                 } else {
+                    #[cfg(feature = "medium-ethernet")]
+                    return self.proxy_nd_advert(ip_repr, target_addr);
+                    #[cfg(not(feature = "medium-ethernet"))]
                     None
                 }
             }
+            #[cfg(feature = "medium-ethernet")]
+            NdiscRepr::RouterSolicit { .. } => {
+                self.router_solicited();
+                None
+            }
             _ => None,
         }
     }
 
+    #[cfg(feature = "medium-ethernet")]
+    fn proxy_nd_advert<'frame>(
+        &mut self,
+        ip_repr: Ipv6Repr,
+        target_addr: Ipv6Address,
+    ) -> Option<IpPacket<'frame>> {
+        // Neither answer for the segment the requester is on, nor for nodes performing
+        // Duplicate Address Detection.
+        if !ip_repr.src_addr.is_unicast()
+            || !target_addr.is_unicast()
+            || (ip_repr.dst_addr != target_addr.solicited_node() && ip_repr.dst_addr != target_addr)
+            || !self.proxy_nd_prefixes.iter().any(|cidr| {
+                cidr.contains_addr(&target_addr) && !cidr.contains_addr(&ip_repr.src_addr)
+            })
+        {
+            return None;
+        }
+
+        // The advertisement is sent from an address of ours rather than the proxied one,
+        // preferably a link-local one.
+        let src_addr = self
+            .ip_addrs
+            .iter()
+            .filter_map(|cidr| match cidr {
+                IpCidr::Ipv6(cidr) => Some(cidr.address()),
+                #[allow(unreachable_patterns)]
+                _ => None,
+            })
+            .filter(|addr| !addr.is_loopback() && self.has_ip_addr(*addr))
+            .min_by_key(|addr| !addr.is_link_local())?;
+        net_debug!("ndisc: answering for proxied address {}", target_addr);
+        // Without the Override flag, so the owner of the address wins if it is on-link,
+        // see RFC 4861 § 7.2.8.
+        let advert = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+            flags: NdiscNeighborFlags::SOLICITED,
+            target_addr,
+            lladdr: Some(self.hardware_addr.unwrap().into()),
+            aro: None,
+        });
+        let ip_repr = Ipv6Repr {
+            src_addr,
+            dst_addr: ip_repr.src_addr,
+            next_header: IpProtocol::Icmpv6,
+            hop_limit: 0xff,
+            payload_len: advert.buffer_len(),
+            traffic_class: 0,
+            flow_label: 0,
+        };
+        Some(IpPacket::Icmpv6((ip_repr, advert)))
+    }
+
+    /// Process a Hop-by-Hop Options or a Destination Options header, which share the
+    /// same format.
     #[cfg(feature = "proto-ipv6")]
-    pub(super) fn process_hopbyhop<'frame>(
+    fn process_options<'frame>(
         &mut self,
         sockets: &mut SocketSet,
         ipv6_repr: Ipv6Repr,
         handled_by_raw_socket: bool,
         ip_payload: &'frame [u8],
+        offset: usize,
     ) -> Option<IpPacket<'frame>> {
-        let hbh_pkt = check!(Ipv6HopByHopHeader::new_checked(ip_payload));
-        let hbh_repr = check!(Ipv6HopByHopRepr::parse(&hbh_pkt));
-        for opt_repr in hbh_repr.options() {
-            let opt_repr = check!(opt_repr);
-            match opt_repr {
-                Ipv6OptionRepr::Pad1 | Ipv6OptionRepr::PadN(_) => (),
-                Ipv6OptionRepr::Unknown { type_, .. } => {
-                    match Ipv6OptionFailureType::from(type_) {
-                        Ipv6OptionFailureType::Skip => (),
-                        Ipv6OptionFailureType::Discard => {
-                            return None;
-                        }
-                        _ => {
-                            // FIXME(dlrobertson): Send an ICMPv6 parameter problem message
-                            // here.
-                            return None;
-                        }
+        let hdr_pkt = check!(
+            Ipv6HopByHopHeader::new_checked(&ip_payload[offset..]),
+            self.stats,
+            ipv6_errors
+        );
+        let hdr_repr = check!(Ipv6HopByHopRepr::parse(&hdr_pkt), self.stats, ipv6_errors);
+
+        // The options follow the Next Header and Hdr Ext Len fields.
+        let mut pointer = ipv6_repr.buffer_len() + offset + 2;
+        for opt_repr in hdr_repr.options() {
+            let opt_repr = check!(opt_repr, self.stats, ipv6_errors);
+            if let Ipv6OptionRepr::Unknown { type_, .. } = opt_repr {
+                match Ipv6OptionFailureType::from(type_) {
+                    Ipv6OptionFailureType::Skip => (),
+                    Ipv6OptionFailureType::DiscardSendAll => {
+                        return self.param_problem(
+                            ipv6_repr,
+                            ip_payload,
+                            Icmpv6ParamProblem::UnrecognizedOption,
+                            pointer,
+                        );
+                    }
+                    Ipv6OptionFailureType::DiscardSendUnicast
+                        if !ipv6_repr.dst_addr.is_multicast() =>
+                    {
+                        return self.param_problem(
+                            ipv6_repr,
+                            ip_payload,
+                            Icmpv6ParamProblem::UnrecognizedOption,
+                            pointer,
+                        );
+                    }
+                    _ => {
+                        net_debug!("unrecognized IPv6 option {}, discarding packet", type_);
+                        return None;
                     }
                 }
             }
+            pointer += opt_repr.buffer_len();
+        }
+
+        self.process_nxt_hdr(
+            sockets,
+            ipv6_repr,
+            hdr_repr.next_header,
+            ipv6_repr.buffer_len() + offset,
+            handled_by_raw_socket,
+            ip_payload,
+            offset + hdr_repr.buffer_len(),
+        )
+    }
+
+    /// Process a Routing header. Source routes are not forwarded, so only headers with
+    /// no segments left are skipped; others are reported as errors.
+    #[cfg(feature = "proto-ipv6")]
+    fn process_routing<'frame>(
+        &mut self,
+        sockets: &mut SocketSet,
+        ipv6_repr: Ipv6Repr,
+        handled_by_raw_socket: bool,
+        ip_payload: &'frame [u8],
+        offset: usize,
+    ) -> Option<IpPacket<'frame>> {
+        let routing_hdr = check!(
+            Ipv6RoutingHeader::new_checked(&ip_payload[offset..]),
+            self.stats,
+            ipv6_errors
+        );
+
+        if routing_hdr.segments_left() != 0 {
+            net_debug!(
+                "IPv6 routing header of type {} not supported",
+                routing_hdr.routing_type()
+            );
+            // Point at the Routing Type field.
+            return self.param_problem(
+                ipv6_repr,
+                ip_payload,
+                Icmpv6ParamProblem::ErroneousHdrField,
+                ipv6_repr.buffer_len() + offset + 2,
+            );
         }
+
         self.process_nxt_hdr(
             sockets,
             ipv6_repr,
-            hbh_repr.next_header,
+            routing_hdr.next_header(),
+            ipv6_repr.buffer_len() + offset,
             handled_by_raw_socket,
-            &ip_payload[hbh_repr.buffer_len()..],
+            ip_payload,
+            offset + (routing_hdr.header_len() as usize + 1) * 8,
         )
     }
 
+    /// Process a Fragment header. Packets are not reassembled, so only atomic
+    /// fragments (RFC 6946) are accepted.
+    #[cfg(feature = "proto-ipv6")]
+    fn process_fragment<'frame>(
+        &mut self,
+        sockets: &mut SocketSet,
+        ipv6_repr: Ipv6Repr,
+        handled_by_raw_socket: bool,
+        ip_payload: &'frame [u8],
+        offset: usize,
+    ) -> Option<IpPacket<'frame>> {
+        let frag_hdr = check!(
+            Ipv6FragmentHeader::new_checked(&ip_payload[offset..]),
+            self.stats,
+            ipv6_errors
+        );
+
+        if frag_hdr.frag_offset() != 0 || frag_hdr.more_frags() {
+            net_debug!("IPv6 fragments are not reassembled, dropping");
+            return None;
+        }
+
+        self.process_nxt_hdr(
+            sockets,
+            ipv6_repr,
+            frag_hdr.next_header(),
+            ipv6_repr.buffer_len() + offset,
+            handled_by_raw_socket,
+            ip_payload,
+            offset + 8,
+        )
+    }
+
+    /// Reply to a packet with a Parameter Problem message, `pointer` being the offset
+    /// of the offending field in the packet.
+    #[cfg(feature = "proto-ipv6")]
+    fn param_problem<'frame>(
+        &mut self,
+        ipv6_repr: Ipv6Repr,
+        ip_payload: &'frame [u8],
+        reason: Icmpv6ParamProblem,
+        pointer: usize,
+    ) -> Option<IpPacket<'frame>> {
+        // Send back as much of the original payload as we can.
+        let payload_len =
+            icmp_reply_payload_len(ip_payload.len(), IPV6_MIN_MTU, ipv6_repr.buffer_len());
+        let icmp_reply_repr = Icmpv6Repr::ParamProblem {
+            reason,
+            pointer: pointer as u32,
+            header: ipv6_repr,
+            data: &ip_payload[0..payload_len],
+        };
+        // Unlike other errors, unrecognized options may be reported to multicast
+        // destinations, as asked by their sender (RFC 4443 § 2.4 (e)).
+        if ipv6_repr.dst_addr.is_multicast() && reason == Icmpv6ParamProblem::UnrecognizedOption {
+            let src_addr = self.get_source_address_ipv6(ipv6_repr.src_addr)?;
+            let ipv6_reply_repr = Ipv6Repr {
+                src_addr,
+                dst_addr: ipv6_repr.src_addr,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: icmp_reply_repr.buffer_len(),
+                hop_limit: 64,
+                traffic_class: 0,
+                flow_label: 0,
+            };
+            return Some(IpPacket::Icmpv6((ipv6_reply_repr, icmp_reply_repr)));
+        }
+        self.icmpv6_reply(ipv6_repr, icmp_reply_repr)
+    }
+
     #[cfg(feature = "proto-ipv6")]
     pub(super) fn icmpv6_reply<'frame, 'icmp: 'frame>(
         &self,
@@ -323,6 +672,8 @@ impl InterfaceInner {
                 next_header: IpProtocol::Icmpv6,
                 payload_len: icmp_repr.buffer_len(),
                 hop_limit: 64,
+                traffic_class: 0,
+                flow_label: 0,
             };
             Some(IpPacket::Icmpv6((ipv6_reply_repr, icmp_repr)))
         } else {
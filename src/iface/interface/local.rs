@@ -0,0 +1,168 @@
+use super::*;
+
+/// The room, in bytes, for packets sent to local addresses and waiting to be received.
+pub(super) const LOCAL_BUFFER_LEN: usize = 4096;
+
+/// Each queued packet is prefixed with its length.
+const LEN_PREFIX: usize = 2;
+
+/// A queue of the packets sent by the interface to one of its own addresses.
+pub(super) struct LocalQueue {
+    buffer: [u8; LOCAL_BUFFER_LEN],
+    len: usize,
+}
+
+impl LocalQueue {
+    pub(super) const fn new() -> Self {
+        Self {
+            buffer: [0; LOCAL_BUFFER_LEN],
+            len: 0,
+        }
+    }
+
+    /// Return the free room after the last packet.
+    pub(super) fn free(&mut self) -> &mut [u8] {
+        &mut self.buffer[self.len..]
+    }
+
+    /// Return the packet at the front of the queue, and the free room after the last
+    /// packet.
+    pub(super) fn front(&mut self) -> Option<(&[u8], &mut [u8])> {
+        if self.len == 0 {
+            return None;
+        }
+        let (used, free) = self.buffer.split_at_mut(self.len);
+        let len = u16::from_be_bytes([used[0], used[1]]) as usize;
+        Some((&used[LEN_PREFIX..LEN_PREFIX + len], free))
+    }
+
+    /// Add the packet written to the free room to the queue.
+    pub(super) fn commit(&mut self, size: usize) {
+        self.len += size;
+    }
+
+    /// Remove the packet at the front of the queue.
+    pub(super) fn pop_front(&mut self) {
+        let len = u16::from_be_bytes([self.buffer[0], self.buffer[1]]) as usize;
+        let size = LEN_PREFIX + len;
+        self.buffer.copy_within(size..self.len, 0);
+        self.len -= size;
+    }
+}
+
+impl Interface {
+    /// Receive the packets the sockets sent to local addresses.
+    ///
+    /// This function returns a boolean value indicating whether any packets were
+    /// processed or emitted, and thus, whether the readiness of any socket might
+    /// have changed.
+    pub(super) fn local_ingress<D>(&mut self, device: &mut D, sockets: &mut SocketSet<'_>) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let mut processed_any = false;
+
+        while let Some((packet, free)) = self.local.front() {
+            processed_any = true;
+
+            let size = match self.inner.process_ip(sockets, &packet, &mut self.fragments) {
+                Some(mut reply) if self.inner.is_local(reply.ip_repr().dst_addr()) => self
+                    .inner
+                    .emit_local(&mut reply, None, free)
+                    .unwrap_or_else(|| {
+                        net_debug!("local queue full, dropping reply");
+                        0
+                    }),
+                Some(reply) => {
+                    if let Some(tx_token) = device.transmit(self.inner.now) {
+                        if let Err(err) =
+                            self.inner
+                                .dispatch_ip(tx_token, reply, Some(&mut self.out_packets))
+                        {
+                            net_debug!("Failed to send response: {:?}", err);
+                        }
+                    }
+                    0
+                }
+                None => 0,
+            };
+
+            self.local.commit(size);
+            self.local.pop_front();
+        }
+
+        processed_any
+    }
+}
+
+impl InterfaceInner {
+    /// Check whether packets to `addr` are delivered without going through the device.
+    pub(super) fn is_local(&self, addr: IpAddress) -> bool {
+        self.has_ip_addr(addr)
+    }
+
+    /// Queue a reply to a received packet if it is addressed to a local address, such as
+    /// the packets translated by the CLAT, or return it to be sent otherwise.
+    pub(super) fn queue_local<'p>(
+        &self,
+        local: &mut LocalQueue,
+        mut packet: IpPacket<'p>,
+    ) -> Option<IpPacket<'p>> {
+        if !self.is_local(packet.ip_repr().dst_addr()) {
+            return Some(packet);
+        }
+        match self.emit_local(&mut packet, None, local.free()) {
+            Some(size) => local.commit(size),
+            None => net_debug!("local queue full, dropping reply"),
+        }
+        None
+    }
+
+    /// Return the capabilities to emit local packets with. They never go through the device,
+    /// so they carry exactly the checksums that are verified on reception.
+    fn local_caps(&self) -> DeviceCapabilities {
+        let local = |checksum: Checksum| match checksum.rx() {
+            true => Checksum::Both,
+            false => Checksum::None,
+        };
+        let mut caps = self.caps.clone();
+        caps.checksum.ipv4 = local(caps.checksum.ipv4);
+        caps.checksum.udp = local(caps.checksum.udp);
+        caps.checksum.tcp = local(caps.checksum.tcp);
+        caps.checksum.sctp = local(caps.checksum.sctp);
+        #[cfg(feature = "proto-ipv4")]
+        {
+            caps.checksum.icmpv4 = local(caps.checksum.icmpv4);
+        }
+        #[cfg(feature = "proto-ipv6")]
+        {
+            caps.checksum.icmpv6 = local(caps.checksum.icmpv6);
+        }
+        caps
+    }
+
+    /// Write a packet to the free room of the local queue, inserting `ext_headers` after
+    /// the header of IPv6 packets. Returns the room used, or `None` if it does not fit.
+    pub(super) fn emit_local(
+        &self,
+        packet: &mut IpPacket,
+        ext_headers: Option<&Ipv6ExtHeaders>,
+        buffer: &mut [u8],
+    ) -> Option<usize> {
+        let len = packet.ip_repr_with_ext(ext_headers).buffer_len();
+        let buffer = buffer.get_mut(..LEN_PREFIX + len)?;
+        if !self.postrouting(packet) {
+            return Some(0);
+        }
+
+        let ip_repr = packet.ip_repr_with_ext(ext_headers);
+        let caps = self.local_caps();
+        buffer[..LEN_PREFIX].copy_from_slice(&(len as u16).to_be_bytes());
+        let buffer = &mut buffer[LEN_PREFIX..];
+        ip_repr.emit(&mut *buffer, &caps.checksum);
+        let payload = &mut buffer[ip_repr.header_len()..];
+        packet.emit_payload_with_ext(&ip_repr, payload, ext_headers, &caps);
+        net_trace!("iface: delivering packet to {} locally", ip_repr.dst_addr());
+        Some(LEN_PREFIX + len)
+    }
+}
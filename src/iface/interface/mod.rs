@@ -4,10 +4,46 @@
 #[cfg(test)]
 mod tests;
 
+mod address;
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+mod autoip;
+mod budget;
+#[cfg(feature = "proto-ipv6")]
+mod clat;
 #[cfg(feature = "medium-ethernet")]
 mod ethernet;
+mod forward;
+mod hook;
+mod local;
+#[cfg(feature = "medium-ieee802154")]
+mod pan;
+#[cfg(feature = "medium-ethernet")]
+mod pause;
+#[cfg(any(
+    feature = "socket-udp",
+    feature = "socket-dns",
+    feature = "socket-dhcpv6"
+))]
+mod port_unreachable;
+#[cfg(any(
+    feature = "socket-udp",
+    feature = "socket-dns",
+    feature = "socket-dhcpv6",
+    feature = "socket-tcp"
+))]
+mod rate_limit;
+#[cfg(feature = "medium-ieee802154")]
+mod registration;
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+mod router_advert;
+#[cfg(feature = "proto-rpl")]
+mod rpl;
 #[cfg(feature = "proto-sixlowpan")]
 mod sixlowpan;
+mod socket_event;
+mod stats;
+#[cfg(feature = "socket-tcp")]
+mod tcp_reset;
 
 #[cfg(feature = "proto-ipv4")]
 mod ipv4;
@@ -19,30 +55,99 @@ mod igmp;
 
 use core::cmp;
 use core::result::Result;
-use heapless::{LinearMap, Vec};
+use heapless::{Deque, LinearMap, Vec};
 
+use super::forwarder::{Forwarder, PortId};
 #[cfg(any(feature = "proto-ipv4", feature = "proto-sixlowpan"))]
-use super::fragmentation::PacketAssemblerSet;
+use super::fragmentation::{self, PacketAssemblerSet, ReassemblyStats};
+#[cfg(feature = "socket-tcp")]
+use super::host_cache::HostCache;
 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
-use super::neighbor::{Answer as NeighborAnswer, Cache as NeighborCache};
-use super::socket_set::SocketSet;
+use super::neighbor::{Answer as NeighborAnswer, Cache as NeighborCache, NeighborStorage};
+use super::route::RouteStorage;
+use super::socket_meta::SocketPriority;
+use super::socket_set::{Flow, SocketSet};
 use crate::iface::Routes;
-use crate::phy::{ChecksumCapabilities, Device, DeviceCapabilities, Medium, RxToken, TxToken};
-use crate::rand::Rand;
+use crate::phy::{
+    Checksum, ChecksumCapabilities, Device, DeviceCapabilities, Medium, RxToken, TxToken,
+};
+#[cfg(feature = "socket-tcp")]
+use crate::rand::SipHasher;
+use crate::rand::{EntropyQuality, Rand, Rng};
 #[cfg(feature = "socket-dns")]
 use crate::socket::dns;
+#[cfg(feature = "socket-dns-sd")]
+use crate::socket::dns_sd;
 use crate::socket::*;
-use crate::time::{Duration, Instant};
+#[cfg(feature = "proto-igmp")]
+use crate::storage::Table;
+use crate::time::{Clock, Duration, Instant};
 use crate::wire::*;
+pub use address::{AddressError, AddressEvent, AddressState};
+use address::{AddressInfo, MAX_ADDRESS_EVENT_COUNT};
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+use autoip::AutoIp;
+pub use budget::PollBudget;
+#[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+pub use clat::ClatConfig;
+#[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+pub(crate) use clat::{embed as nat64_embed, PREFIX_LENS as NAT64_PREFIX_LENS};
+pub use hook::{PacketHook, Verdict};
+use local::LocalQueue;
+#[cfg(feature = "medium-ieee802154")]
+pub use pan::PanAssociation;
+#[cfg(feature = "medium-ieee802154")]
+use pan::PanState;
+#[cfg(feature = "medium-ethernet")]
+use pause::Pause;
+#[cfg(any(
+    feature = "socket-udp",
+    feature = "socket-dns",
+    feature = "socket-dhcpv6"
+))]
+use port_unreachable::PortUnreachable;
+#[cfg(feature = "medium-ieee802154")]
+use registration::Registration;
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+use router_advert::RouterAdvert;
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+pub use router_advert::RouterAdvertConfig;
+#[cfg(feature = "proto-rpl")]
+use rpl::Rpl;
+#[cfg(feature = "proto-rpl")]
+pub use rpl::RplConfig;
+pub use socket_event::SocketEvent;
+use socket_event::SocketEvents;
+use stats::Counting;
+pub use stats::InterfaceStats;
+#[cfg(feature = "socket-tcp")]
+pub use tcp_reset::TcpResetPolicy;
+#[cfg(feature = "socket-tcp")]
+use tcp_reset::TcpResets;
+
+/// Extension headers only exist for IPv6 packets.
+#[cfg(not(feature = "proto-ipv6"))]
+type Ipv6ExtHeaders = ();
 #[cfg(feature = "proto-igmp")]
-pub use igmp::MulticastError;
-
-const MAX_IP_ADDR_COUNT: usize = 5;
+use igmp::MulticastRefs;
+#[cfg(feature = "proto-igmp")]
+pub use igmp::{MulticastError, MulticastGroupStorage};
+
+pub(crate) const MAX_IP_ADDR_COUNT: usize = 5;
+const MAX_ANY_IP_PREFIX_COUNT: usize = 4;
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+const MAX_PROXY_ARP_PREFIX_COUNT: usize = 4;
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+const MAX_RARP_ENTRY_COUNT: usize = 4;
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+const MAX_PROXY_ND_PREFIX_COUNT: usize = 4;
+/// The number of IPv4 multicast groups that can be joined, unless storage is provided
+/// for them.
 #[cfg(feature = "proto-igmp")]
 const MAX_IPV4_MULTICAST_GROUPS: usize = 4;
 const FRAGMENTATION_BUFFER_SIZE: usize = 1500;
-#[cfg(feature = "proto-sixlowpan")]
-const SIXLOWPAN_ADDRESS_CONTEXT_COUNT: usize = 4;
+/// The maximum number of packets received from the device in one batch.
+const RECEIVE_BATCH_SIZE: usize = 16;
 
 pub(crate) struct FragmentsBuffer {
     #[cfg(feature = "proto-sixlowpan")]
@@ -117,6 +222,7 @@ impl Ipv4OutPacket {
                 next_header: IpProtocol::Unknown(0),
                 payload_len: 0,
                 hop_limit: 0,
+                dscp: 0,
             },
             #[cfg(feature = "medium-ethernet")]
             dst_hardware_addr: EthernetAddress::default(),
@@ -147,6 +253,7 @@ impl Ipv4OutPacket {
             next_header: IpProtocol::Unknown(0),
             payload_len: 0,
             hop_limit: 0,
+            dscp: 0,
         };
         #[cfg(feature = "medium-ethernet")]
         {
@@ -234,6 +341,30 @@ macro_rules! check {
             }
         }
     };
+    // Count the error in the `$errors` counter of `$stats`, and in its checksum
+    // errors counter when `$bad_checksum` is true.
+    ($e:expr, $stats:expr, $errors:ident) => {
+        check!($e, $stats, $errors, false)
+    };
+    ($e:expr, $stats:expr, $errors:ident, bad_checksum: $bad_checksum:expr) => {
+        check!($e, $stats, $errors, $bad_checksum)
+    };
+    ($e:expr, $stats:expr, $errors:ident, $bad_checksum:expr) => {
+        match $e {
+            Ok(x) => x,
+            Err(_) => {
+                $stats.$errors = $stats.$errors.wrapping_add(1);
+                if $bad_checksum {
+                    $stats.checksum_errors = $stats.checksum_errors.wrapping_add(1);
+                }
+                #[cfg(not(feature = "defmt"))]
+                net_trace!(concat!("iface: malformed ", stringify!($e)));
+                #[cfg(feature = "defmt")]
+                net_trace!("iface: malformed");
+                return Default::default();
+            }
+        }
+    };
 }
 use check;
 
@@ -246,6 +377,12 @@ pub struct Interface {
     inner: InterfaceInner,
     fragments: FragmentsBuffer,
     out_packets: OutPackets,
+    poll_budget: PollBudget,
+    poll_pending: bool,
+    /// The slot of the socket to serve first on the next egress.
+    egress_cursor: usize,
+    local: LocalQueue,
+    socket_events: SocketEvents,
 }
 
 /// The device independent part of an Ethernet network interface.
@@ -262,8 +399,15 @@ pub struct InterfaceInner {
 
     #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
     neighbor_cache: Option<NeighborCache>,
+    /// The last destination resolved by the neighbor cache during the current batch.
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+    batch_neighbor: Option<(IpAddress, HardwareAddress)>,
     #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
     hardware_addr: Option<HardwareAddress>,
+    #[cfg(feature = "medium-ethernet")]
+    ethernet_encapsulation: EthernetEncapsulation,
+    #[cfg(feature = "medium-ethernet")]
+    pause: Pause,
     #[cfg(feature = "medium-ieee802154")]
     sequence_no: u8,
     #[cfg(feature = "medium-ieee802154")]
@@ -271,18 +415,57 @@ pub struct InterfaceInner {
     #[cfg(feature = "proto-ipv4-fragmentation")]
     ipv4_id: u16,
     #[cfg(feature = "proto-sixlowpan")]
-    sixlowpan_address_context: Vec<SixlowpanAddressContext, SIXLOWPAN_ADDRESS_CONTEXT_COUNT>,
+    sixlowpan_address_context:
+        [Option<(SixlowpanAddressContext, Option<Instant>)>; SIXLOWPAN_ADDRESS_CONTEXT_COUNT],
     #[cfg(feature = "proto-sixlowpan-fragmentation")]
     tag: u16,
     ip_addrs: Vec<IpCidr, MAX_IP_ADDR_COUNT>,
+    addr_info: LinearMap<IpAddress, AddressInfo, MAX_IP_ADDR_COUNT>,
+    addr_events: Deque<AddressEvent, MAX_ADDRESS_EVENT_COUNT>,
     #[cfg(feature = "proto-ipv4")]
     any_ip: bool,
+    any_ip_prefixes: Vec<IpCidr, MAX_ANY_IP_PREFIX_COUNT>,
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+    proxy_arp_prefixes: Vec<Ipv4Cidr, MAX_PROXY_ARP_PREFIX_COUNT>,
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+    rarp_table: LinearMap<EthernetAddress, Ipv4Address, MAX_RARP_ENTRY_COUNT>,
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+    proxy_nd_prefixes: Vec<Ipv6Cidr, MAX_PROXY_ND_PREFIX_COUNT>,
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+    router_advert: Option<RouterAdvert>,
+    prerouting_hook: Option<PacketHook>,
+    postrouting_hook: Option<PacketHook>,
+    stats: InterfaceStats,
     routes: Routes,
+    #[cfg(feature = "socket-tcp")]
+    host_cache: HostCache,
+    #[cfg(feature = "socket-tcp")]
+    tcp_isn_secret: [u8; 16],
+    #[cfg(any(
+        feature = "socket-udp",
+        feature = "socket-dns",
+        feature = "socket-dhcpv6"
+    ))]
+    port_unreachable: PortUnreachable,
+    #[cfg(feature = "socket-tcp")]
+    tcp_resets: TcpResets,
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+    autoip: Option<AutoIp>,
     #[cfg(feature = "proto-igmp")]
-    ipv4_multicast_groups: LinearMap<Ipv4Address, (), MAX_IPV4_MULTICAST_GROUPS>,
+    ipv4_multicast_groups: Table<'static, (Ipv4Address, MulticastRefs)>,
     /// When to report for (all or) the next multicast group membership via IGMP
     #[cfg(feature = "proto-igmp")]
     igmp_report_state: IgmpReportState,
+    #[cfg(feature = "proto-ipv6")]
+    nat64_prefix: Option<(Ipv6Cidr, Instant)>,
+    #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+    clat: Option<ClatConfig>,
+    #[cfg(feature = "medium-ieee802154")]
+    registration: Option<Registration>,
+    #[cfg(feature = "proto-rpl")]
+    rpl: Option<Rpl>,
+    #[cfg(feature = "medium-ieee802154")]
+    pan: PanState,
 }
 
 /// Configuration structure used for creating a network interface.
@@ -296,6 +479,25 @@ pub struct Config {
     /// The seed doesn't have to be cryptographically secure.
     pub random_seed: u64,
 
+    /// Set the random number generator the interface and its sockets draw from,
+    /// instead of a generator seeded with `random_seed`.
+    ///
+    /// The built-in generator is predictable: an attacker who sees a few TCP sequence
+    /// numbers or DNS query IDs can guess the next ones, and spoof the answers. Use a
+    /// cryptographically secure generator, such as a hardware RNG, to resist this.
+    pub rng: Option<&'static mut (dyn Rng + Send)>,
+
+    /// Set the secret key TCP initial sequence numbers are derived from, as described
+    /// in [RFC 6528].
+    ///
+    /// By default, a key is drawn from the random number generator. Storing the key
+    /// and providing it again after a reboot keeps the sequence numbers of new
+    /// connections from overlapping those of the connections before the reboot.
+    ///
+    /// [RFC 6528]: https://www.rfc-editor.org/rfc/rfc6528
+    #[cfg(feature = "socket-tcp")]
+    pub tcp_isn_secret: Option<[u8; 16]>,
+
     /// Set the Hardware address the interface will use.
     ///
     /// # Panics
@@ -308,16 +510,111 @@ pub struct Config {
     /// **NOTE**: we use the same PAN ID for destination and source.
     #[cfg(feature = "medium-ieee802154")]
     pub pan_id: Option<Ieee802154Pan>,
+
+    /// Set how many fragmented IPv4 packets can be reassembled at the same time.
+    ///
+    /// When a fragment of yet another packet arrives, the packet closest to its
+    /// reassembly timeout is given up on.
+    ///
+    /// # Panics
+    /// Creating the interface panics if this is zero, or larger than 16.
+    #[cfg(feature = "proto-ipv4-fragmentation")]
+    pub ipv4_reassembly_contexts: usize,
+
+    /// Set the largest IPv4 packet that can be reassembled, in octets.
+    #[cfg(feature = "proto-ipv4-fragmentation")]
+    pub ipv4_reassembly_buffer_size: usize,
+
+    /// Set the storage IPv4 packets are reassembled in, shared equally by the
+    /// [reassembly contexts](Self::ipv4_reassembly_contexts). It replaces
+    /// [`ipv4_reassembly_buffer_size`](Self::ipv4_reassembly_buffer_size).
+    ///
+    /// By default, buffers are allocated as fragments arrive. Without the `alloc`
+    /// feature, fragmented packets are dropped unless this is set.
+    #[cfg(feature = "proto-ipv4-fragmentation")]
+    pub ipv4_reassembly_storage: Option<&'static mut [u8]>,
+
+    /// Set how many fragmented 6LoWPAN packets can be reassembled at the same time.
+    ///
+    /// Fragments are told apart by their link-layer addresses, datagram size and
+    /// datagram tag, so the packets of several neighbors are reassembled side by side.
+    /// When a fragment of yet another packet arrives, the packet closest to its
+    /// reassembly timeout is given up on.
+    ///
+    /// # Panics
+    /// Creating the interface panics if this is zero, or larger than 16.
+    #[cfg(feature = "proto-sixlowpan-fragmentation")]
+    pub sixlowpan_reassembly_contexts: usize,
+
+    /// Set the largest decompressed IPv6 packet that can be reassembled from 6LoWPAN
+    /// fragments, in octets.
+    #[cfg(feature = "proto-sixlowpan-fragmentation")]
+    pub sixlowpan_reassembly_buffer_size: usize,
+
+    /// Set the storage 6LoWPAN packets are reassembled in, shared equally by the
+    /// [reassembly contexts](Self::sixlowpan_reassembly_contexts). It replaces
+    /// [`sixlowpan_reassembly_buffer_size`](Self::sixlowpan_reassembly_buffer_size).
+    ///
+    /// By default, buffers are allocated as fragments arrive. Without the `alloc`
+    /// feature, fragmented packets are dropped unless this is set.
+    #[cfg(feature = "proto-sixlowpan-fragmentation")]
+    pub sixlowpan_reassembly_storage: Option<&'static mut [u8]>,
+
+    /// Set the storage of the neighbor cache, which holds as many neighbors as the
+    /// storage has entries.
+    ///
+    /// By default, a cache of 16 neighbors is allocated.
+    ///
+    /// # Panics
+    /// Without the `alloc` feature, creating an Ethernet or IEEE 802.15.4 interface
+    /// panics if this is not set.
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+    pub neighbor_cache_storage: Option<&'static mut [NeighborStorage]>,
+
+    /// Set the storage of the routing table, which holds as many routes as the
+    /// storage has entries.
+    ///
+    /// By default, a table of 8 routes is allocated. Without the `alloc` feature, no
+    /// route can be added unless this is set.
+    pub route_storage: Option<&'static mut [RouteStorage]>,
+
+    /// Set the storage of the table of joined IPv4 multicast groups, which holds as
+    /// many groups as the storage has entries.
+    ///
+    /// By default, a table of 4 groups is allocated. Without the `alloc` feature, no
+    /// group can be joined unless this is set.
+    #[cfg(feature = "proto-igmp")]
+    pub ipv4_multicast_group_storage: Option<&'static mut [MulticastGroupStorage]>,
 }
 
 impl Config {
     pub fn new() -> Self {
         Config {
             random_seed: 0,
+            rng: None,
+            #[cfg(feature = "socket-tcp")]
+            tcp_isn_secret: None,
             #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
             hardware_addr: None,
             #[cfg(feature = "medium-ieee802154")]
             pan_id: None,
+            #[cfg(feature = "proto-ipv4-fragmentation")]
+            ipv4_reassembly_contexts: fragmentation::PACKET_ASSEMBLER_COUNT,
+            #[cfg(feature = "proto-ipv4-fragmentation")]
+            ipv4_reassembly_buffer_size: fragmentation::BUFFER_SIZE,
+            #[cfg(feature = "proto-ipv4-fragmentation")]
+            ipv4_reassembly_storage: None,
+            #[cfg(feature = "proto-sixlowpan-fragmentation")]
+            sixlowpan_reassembly_contexts: fragmentation::PACKET_ASSEMBLER_COUNT,
+            #[cfg(feature = "proto-sixlowpan-fragmentation")]
+            sixlowpan_reassembly_buffer_size: fragmentation::BUFFER_SIZE,
+            #[cfg(feature = "proto-sixlowpan-fragmentation")]
+            sixlowpan_reassembly_storage: None,
+            #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+            neighbor_cache_storage: None,
+            route_storage: None,
+            #[cfg(feature = "proto-igmp")]
+            ipv4_multicast_group_storage: None,
         }
     }
 }
@@ -380,6 +677,39 @@ impl<'a> IpPacket<'a> {
         }
     }
 
+    /// Return the header of the packet once `ext_headers` are inserted after it.
+    #[cfg_attr(not(feature = "proto-ipv6"), allow(unused_variables))]
+    fn ip_repr_with_ext(&self, ext_headers: Option<&Ipv6ExtHeaders>) -> IpRepr {
+        #[allow(unused_mut)]
+        let mut ip_repr = self.ip_repr();
+        #[cfg(feature = "proto-ipv6")]
+        if let (IpRepr::Ipv6(repr), Some(headers)) = (&mut ip_repr, ext_headers) {
+            repr.next_header = headers.first_header(repr.next_header);
+            repr.payload_len += headers.buffer_len();
+        }
+        ip_repr
+    }
+
+    /// Emit the payload of the packet, preceded by `ext_headers` for IPv6 packets.
+    fn emit_payload_with_ext(
+        &self,
+        ip_repr: &IpRepr,
+        payload: &mut [u8],
+        ext_headers: Option<&Ipv6ExtHeaders>,
+        caps: &DeviceCapabilities,
+    ) {
+        let payload = match (ip_repr, ext_headers) {
+            #[cfg(feature = "proto-ipv6")]
+            (IpRepr::Ipv6(_), Some(headers)) => {
+                let (ext, payload) = payload.split_at_mut(headers.buffer_len());
+                headers.emit(self.ip_repr().next_header(), ext);
+                payload
+            }
+            _ => payload,
+        };
+        self.emit_payload(ip_repr, payload, caps)
+    }
+
     pub(crate) fn emit_payload(
         &self,
         _ip_repr: &IpRepr,
@@ -533,7 +863,10 @@ impl Interface {
             ),
         };
 
-        let mut rand = Rand::new(config.random_seed);
+        let mut rand = match config.rng {
+            Some(rng) => Rand::with_rng(rng),
+            None => Rand::new(config.random_seed),
+        };
 
         #[cfg(feature = "medium-ieee802154")]
         let mut sequence_no;
@@ -556,6 +889,41 @@ impl Interface {
             }
         }
 
+        #[cfg(feature = "socket-tcp")]
+        let tcp_isn_secret = config.tcp_isn_secret.unwrap_or_else(|| rand.rand_uuid());
+
+        let routes = match config.route_storage {
+            Some(storage) => Routes::with_storage(storage),
+            #[cfg(feature = "alloc")]
+            None => Routes::new(),
+            #[cfg(not(feature = "alloc"))]
+            None => Routes::with_storage(&mut [][..]),
+        };
+
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        let neighbor_cache = match config.neighbor_cache_storage {
+            Some(storage) => NeighborCache::with_storage(storage),
+            #[cfg(feature = "alloc")]
+            None => NeighborCache::new(),
+            #[cfg(not(feature = "alloc"))]
+            None => {
+                assert!(
+                    hardware_addr.is_none(),
+                    "neighbor_cache_storage required option was not set"
+                );
+                NeighborCache::with_storage(&mut [][..])
+            }
+        };
+
+        #[cfg(feature = "proto-igmp")]
+        let ipv4_multicast_groups = match config.ipv4_multicast_group_storage {
+            Some(storage) => Table::new(storage),
+            #[cfg(feature = "alloc")]
+            None => Table::with_capacity(MAX_IPV4_MULTICAST_GROUPS),
+            #[cfg(not(feature = "alloc"))]
+            None => Table::new(&mut [][..]),
+        };
+
         #[cfg(feature = "proto-ipv4")]
         let mut ipv4_id;
 
@@ -573,9 +941,38 @@ impl Interface {
                 decompress_buf: [0u8; sixlowpan::MAX_DECOMPRESSED_LEN],
 
                 #[cfg(feature = "proto-ipv4-fragmentation")]
-                ipv4_fragments: PacketAssemblerSet::new(),
+                ipv4_fragments: match config.ipv4_reassembly_storage {
+                    Some(storage) => {
+                        PacketAssemblerSet::with_storage(config.ipv4_reassembly_contexts, storage)
+                    }
+                    #[cfg(feature = "alloc")]
+                    None => PacketAssemblerSet::with_config(
+                        config.ipv4_reassembly_contexts,
+                        config.ipv4_reassembly_buffer_size,
+                    ),
+                    #[cfg(not(feature = "alloc"))]
+                    None => PacketAssemblerSet::with_storage(
+                        config.ipv4_reassembly_contexts,
+                        &mut [][..],
+                    ),
+                },
                 #[cfg(feature = "proto-sixlowpan-fragmentation")]
-                sixlowpan_fragments: PacketAssemblerSet::new(),
+                sixlowpan_fragments: match config.sixlowpan_reassembly_storage {
+                    Some(storage) => PacketAssemblerSet::with_storage(
+                        config.sixlowpan_reassembly_contexts,
+                        storage,
+                    ),
+                    #[cfg(feature = "alloc")]
+                    None => PacketAssemblerSet::with_config(
+                        config.sixlowpan_reassembly_contexts,
+                        config.sixlowpan_reassembly_buffer_size,
+                    ),
+                    #[cfg(not(feature = "alloc"))]
+                    None => PacketAssemblerSet::with_storage(
+                        config.sixlowpan_reassembly_contexts,
+                        &mut [][..],
+                    ),
+                },
                 #[cfg(feature = "proto-sixlowpan-fragmentation")]
                 sixlowpan_fragments_cache_timeout: Duration::from_secs(60),
             },
@@ -585,21 +982,70 @@ impl Interface {
                 #[cfg(feature = "proto-sixlowpan-fragmentation")]
                 sixlowpan_out_packet: SixlowpanOutPacket::new(),
             },
+            poll_budget: PollBudget::default(),
+            poll_pending: false,
+            egress_cursor: 0,
+            local: LocalQueue::new(),
+            socket_events: SocketEvents::new(),
             inner: InterfaceInner {
                 now: Instant::from_secs(0),
                 caps,
                 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
                 hardware_addr,
+                #[cfg(feature = "medium-ethernet")]
+                ethernet_encapsulation: EthernetEncapsulation::EthernetII,
+                #[cfg(feature = "medium-ethernet")]
+                pause: Pause::default(),
                 ip_addrs: Vec::new(),
+                addr_info: LinearMap::new(),
+                addr_events: Deque::new(),
+                #[cfg(any(
+                    feature = "socket-udp",
+                    feature = "socket-dns",
+                    feature = "socket-dhcpv6"
+                ))]
+                port_unreachable: PortUnreachable::new(),
+                #[cfg(feature = "socket-tcp")]
+                tcp_resets: TcpResets::new(),
+                #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+                autoip: None,
                 #[cfg(feature = "proto-ipv4")]
                 any_ip: false,
-                routes: Routes::new(),
+                any_ip_prefixes: Vec::new(),
+                #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+                proxy_arp_prefixes: Vec::new(),
+                #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+                rarp_table: LinearMap::new(),
+                #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+                proxy_nd_prefixes: Vec::new(),
+                #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+                router_advert: None,
+                prerouting_hook: None,
+                postrouting_hook: None,
+                stats: InterfaceStats::default(),
+                routes,
+                #[cfg(feature = "socket-tcp")]
+                host_cache: HostCache::new(),
+                #[cfg(feature = "socket-tcp")]
+                tcp_isn_secret,
                 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
-                neighbor_cache: Some(NeighborCache::new()),
+                neighbor_cache: Some(neighbor_cache),
+                #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+                batch_neighbor: None,
                 #[cfg(feature = "proto-igmp")]
-                ipv4_multicast_groups: LinearMap::new(),
+                ipv4_multicast_groups,
                 #[cfg(feature = "proto-igmp")]
                 igmp_report_state: IgmpReportState::Inactive,
+                #[cfg(feature = "proto-ipv6")]
+                nat64_prefix: None,
+                #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+                clat: None,
+                #[cfg(feature = "medium-ieee802154")]
+                registration: None,
+                #[cfg(feature = "proto-rpl")]
+                rpl: None,
+                #[cfg(feature = "medium-ieee802154")]
+                pan: PanState::default(),
                 #[cfg(feature = "medium-ieee802154")]
                 sequence_no,
                 #[cfg(feature = "medium-ieee802154")]
@@ -609,7 +1055,7 @@ impl Interface {
                 #[cfg(feature = "proto-ipv4-fragmentation")]
                 ipv4_id,
                 #[cfg(feature = "proto-sixlowpan")]
-                sixlowpan_address_context: Vec::new(),
+                sixlowpan_address_context: [None; SIXLOWPAN_ADDRESS_CONTEXT_COUNT],
                 rand,
             },
         }
@@ -664,6 +1110,25 @@ impl Interface {
         self.inner.hardware_addr = Some(addr);
     }
 
+    /// Get the encapsulation of the packets sent by the interface.
+    ///
+    /// See [`set_ethernet_encapsulation`](Self::set_ethernet_encapsulation).
+    #[cfg(feature = "medium-ethernet")]
+    pub fn ethernet_encapsulation(&self) -> EthernetEncapsulation {
+        self.inner.ethernet_encapsulation
+    }
+
+    /// Set the encapsulation of the packets sent by the interface.
+    ///
+    /// Frames are received with either encapsulation, but sent as Ethernet II frames
+    /// unless [`EthernetEncapsulation::Snap`] is set here, for networks where IP is
+    /// still carried in LLC/SNAP. The IP MTU is reduced by the size of the LLC and
+    /// SNAP headers accordingly.
+    #[cfg(feature = "medium-ethernet")]
+    pub fn set_ethernet_encapsulation(&mut self, encapsulation: EthernetEncapsulation) {
+        self.inner.ethernet_encapsulation = encapsulation;
+    }
+
     /// Get the IP addresses of the interface.
     pub fn ip_addrs(&self) -> &[IpCidr] {
         self.inner.ip_addrs.as_ref()
@@ -683,12 +1148,19 @@ impl Interface {
 
     /// Update the IP addresses of the interface.
     ///
+    /// The added and removed addresses are reported by
+    /// [`poll_address_event`](Self::poll_address_event). Unlike with
+    /// [`add_ip_addr`](Self::add_ip_addr), added addresses are neither announced nor
+    /// probed, and are preferred right away.
+    ///
     /// # Panics
     /// This function panics if any of the addresses are not unicast.
     pub fn update_ip_addrs<F: FnOnce(&mut Vec<IpCidr, MAX_IP_ADDR_COUNT>)>(&mut self, f: F) {
+        let old = self.inner.ip_addrs.clone();
         f(&mut self.inner.ip_addrs);
         InterfaceInner::flush_cache(&mut self.inner);
-        InterfaceInner::check_ip_addrs(&self.inner.ip_addrs)
+        InterfaceInner::check_ip_addrs(&self.inner.ip_addrs);
+        self.inner.diff_ip_addrs(&old);
     }
 
     /// Check whether the interface has the given IP address assigned.
@@ -704,6 +1176,19 @@ impl Interface {
         &mut self.inner.routes
     }
 
+    /// Get the TCP host cache, which carries round-trip time estimates over from one
+    /// connection to the next one with the same remote address.
+    #[cfg(feature = "socket-tcp")]
+    pub fn host_cache(&self) -> &HostCache {
+        &self.inner.host_cache
+    }
+
+    /// Get the TCP host cache mutably, e.g. to clear or disable it.
+    #[cfg(feature = "socket-tcp")]
+    pub fn host_cache_mut(&mut self) -> &mut HostCache {
+        &mut self.inner.host_cache
+    }
+
     /// Enable or disable the AnyIP capability.
     ///
     /// AnyIP allowins packets to be received
@@ -729,20 +1214,149 @@ impl Interface {
         self.inner.any_ip
     }
 
-    /// Get the 6LoWPAN address contexts.
+    /// Get how hard the random numbers of the interface are to predict.
+    ///
+    /// See [`Config::rng`].
+    pub fn rand_quality(&self) -> EntropyQuality {
+        self.inner.rand.quality()
+    }
+
+    /// Get the prefixes the interface accepts TCP and UDP traffic for.
+    ///
+    /// See [`update_any_ip_prefixes`](Self::update_any_ip_prefixes).
+    pub fn any_ip_prefixes(&self) -> &[IpCidr] {
+        &self.inner.any_ip_prefixes
+    }
+
+    /// Update the prefixes the interface accepts TCP and UDP traffic for.
+    ///
+    /// TCP segments and UDP datagrams sent to any unicast address within one of these
+    /// prefixes are delivered to the sockets as if the address was assigned to the
+    /// interface, and are not forwarded by a [`Forwarder`]. Other protocols are not
+    /// accepted. Sockets bound to an unspecified address learn which address the
+    /// traffic was sent to from the local endpoint of a TCP connection, or from
+    /// the [`UdpMetadata`](crate::socket::udp::UdpMetadata) of a UDP datagram, and
+    /// reply from it. This is how transparent proxies and captive portals intercept
+    /// traffic routed to them.
+    ///
+    /// Unlike with [`set_any_ip`](Self::set_any_ip), no route is needed, and IPv6
+    /// prefixes are supported.
+    pub fn update_any_ip_prefixes<F: FnOnce(&mut Vec<IpCidr, MAX_ANY_IP_PREFIX_COUNT>)>(
+        &mut self,
+        f: F,
+    ) {
+        f(&mut self.inner.any_ip_prefixes);
+    }
+
+    /// Get the prefixes the interface answers ARP requests for.
+    ///
+    /// See [`update_proxy_arp_prefixes`](Self::update_proxy_arp_prefixes).
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+    pub fn proxy_arp_prefixes(&self) -> &[Ipv4Cidr] {
+        &self.inner.proxy_arp_prefixes
+    }
+
+    /// Update the prefixes the interface answers ARP requests for.
+    ///
+    /// ARP requests for an address within one of these prefixes are answered with the
+    /// hardware address of the interface, unless they come from within the same prefix.
+    /// This is how a point-to-point link, or any network reached through a [`Forwarder`],
+    /// is bridged into the Ethernet segment without configuring a route on its hosts.
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+    pub fn update_proxy_arp_prefixes<F: FnOnce(&mut Vec<Ipv4Cidr, MAX_PROXY_ARP_PREFIX_COUNT>)>(
+        &mut self,
+        f: F,
+    ) {
+        f(&mut self.inner.proxy_arp_prefixes);
+    }
+
+    /// Get the prefixes the interface answers Neighbor Solicitations for.
+    ///
+    /// See [`update_proxy_nd_prefixes`](Self::update_proxy_nd_prefixes).
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+    pub fn proxy_nd_prefixes(&self) -> &[Ipv6Cidr] {
+        &self.inner.proxy_nd_prefixes
+    }
+
+    /// Update the prefixes the interface answers Neighbor Solicitations for.
+    ///
+    /// This is the IPv6 counterpart of proxy ARP: solicitations for an address within
+    /// one of these prefixes are answered with the hardware address of the interface,
+    /// unless they come from within the same prefix. The advertisements do not have the
+    /// Override flag set, so that a node actually owning the address on the link takes
+    /// precedence, as required by RFC 4861 § 7.2.8.
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+    pub fn update_proxy_nd_prefixes<F: FnOnce(&mut Vec<Ipv6Cidr, MAX_PROXY_ND_PREFIX_COUNT>)>(
+        &mut self,
+        f: F,
+    ) {
+        f(&mut self.inner.proxy_nd_prefixes);
+    }
+
+    /// Get the table the interface answers Reverse ARP requests from.
+    ///
+    /// See [`update_rarp_table`](Self::update_rarp_table).
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+    pub fn rarp_table(&self) -> &LinearMap<EthernetAddress, Ipv4Address, MAX_RARP_ENTRY_COUNT> {
+        &self.inner.rarp_table
+    }
+
+    /// Update the table the interface answers Reverse ARP requests from.
+    ///
+    /// A Reverse ARP request for a hardware address in the table is answered with the
+    /// IPv4 address it maps to, letting diskless or unconfigured hosts on the segment
+    /// learn their address. Requests for other hardware addresses are ignored.
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+    pub fn update_rarp_table<
+        F: FnOnce(&mut LinearMap<EthernetAddress, Ipv4Address, MAX_RARP_ENTRY_COUNT>),
+    >(
+        &mut self,
+        f: F,
+    ) {
+        f(&mut self.inner.rarp_table);
+    }
+
+    /// Get the 6LoWPAN address context with the given context identifier, if it is
+    /// configured and has not expired.
     #[cfg(feature = "proto-sixlowpan")]
-    pub fn sixlowpan_address_context(
-        &self,
-    ) -> &Vec<SixlowpanAddressContext, SIXLOWPAN_ADDRESS_CONTEXT_COUNT> {
-        &self.inner.sixlowpan_address_context
+    pub fn sixlowpan_address_context(&self, context_id: u8) -> Option<SixlowpanAddressContext> {
+        self.inner
+            .sixlowpan_address_contexts()
+            .get(context_id as usize)
+            .copied()
+            .flatten()
     }
 
-    /// Get a mutable reference to the 6LoWPAN address contexts.
+    /// Set or remove the 6LoWPAN address context with the given context identifier.
+    ///
+    /// Address contexts are used to decompress the addresses of received packets, whose
+    /// prefix is elided when it matches the context. They are also learned from the
+    /// 6LoWPAN Context options of router advertisements, in which case they expire
+    /// with the lifetime of the option, while a context set here does not expire.
+    ///
+    /// # Panics
+    /// This function panics if the context identifier is not below
+    /// [`SIXLOWPAN_ADDRESS_CONTEXT_COUNT`](crate::wire::SIXLOWPAN_ADDRESS_CONTEXT_COUNT).
     #[cfg(feature = "proto-sixlowpan")]
-    pub fn sixlowpan_address_context_mut(
+    pub fn set_sixlowpan_address_context(
         &mut self,
-    ) -> &mut Vec<SixlowpanAddressContext, SIXLOWPAN_ADDRESS_CONTEXT_COUNT> {
-        &mut self.inner.sixlowpan_address_context
+        context_id: u8,
+        context: Option<SixlowpanAddressContext>,
+    ) {
+        self.inner.sixlowpan_address_context[context_id as usize] =
+            context.map(|context| (context, None));
+    }
+
+    /// Return the occupancy of the IPv4 reassembly buffers.
+    #[cfg(feature = "proto-ipv4-fragmentation")]
+    pub fn ipv4_reassembly_stats(&self) -> ReassemblyStats {
+        self.fragments.ipv4_fragments.stats()
+    }
+
+    /// Return the occupancy of the 6LoWPAN reassembly buffers.
+    #[cfg(feature = "proto-sixlowpan-fragmentation")]
+    pub fn sixlowpan_reassembly_stats(&self) -> ReassemblyStats {
+        self.fragments.sixlowpan_fragments.stats()
     }
 
     /// Get the packet reassembly timeout.
@@ -770,16 +1384,67 @@ impl Interface {
     /// This function returns a boolean value indicating whether any packets were
     /// processed or emitted, and thus, whether the readiness of any socket might
     /// have changed.
+    ///
+    /// The work done is bounded by the [poll budget](Interface::set_poll_budget);
+    /// [`poll_pending`](Interface::poll_pending) tells whether some was left over.
+    /// Sockets are served in turn, starting with the one that was cut off by the
+    /// budget of the previous poll.
     pub fn poll<D>(
         &mut self,
         timestamp: Instant,
         device: &mut D,
         sockets: &mut SocketSet<'_>,
     ) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        self.poll_with(timestamp, device, sockets, &mut None)
+    }
+
+    /// Like [`poll`](Interface::poll), but read the timestamp from a clock.
+    pub fn poll_clock<C, D>(
+        &mut self,
+        clock: &C,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+    ) -> bool
+    where
+        C: Clock + ?Sized,
+        D: Device + ?Sized,
+    {
+        self.poll(clock.now(), device, sockets)
+    }
+
+    fn poll_with<D>(
+        &mut self,
+        timestamp: Instant,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+        forward: &mut Option<(&mut Forwarder<'_>, PortId)>,
+    ) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let mut device = Counting::with_budget(device, self.poll_budget);
+        let result = self.poll_device(timestamp, &mut device, sockets, forward);
+        device.count_into(&mut self.inner.stats);
+        self.poll_pending = device.exhausted();
+        self.record_socket_events(sockets);
+        result
+    }
+
+    fn poll_device<D>(
+        &mut self,
+        timestamp: Instant,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+        forward: &mut Option<(&mut Forwarder<'_>, PortId)>,
+    ) -> bool
     where
         D: Device + ?Sized,
     {
         self.inner.now = timestamp;
+        self.inner.end_batch();
 
         #[cfg(feature = "proto-ipv4-fragmentation")]
         self.fragments.ipv4_fragments.remove_expired(timestamp);
@@ -787,6 +1452,8 @@ impl Interface {
         #[cfg(feature = "proto-sixlowpan-fragmentation")]
         self.fragments.sixlowpan_fragments.remove_expired(timestamp);
 
+        self.inner.routes.remove_expired(timestamp);
+
         #[cfg(feature = "proto-ipv4-fragmentation")]
         if self.ipv4_egress(device) {
             return true;
@@ -797,18 +1464,60 @@ impl Interface {
             return true;
         }
 
+        #[cfg(feature = "proto-igmp")]
+        self.sync_multicast_groups(device, sockets);
+
         let mut readiness_may_have_changed = false;
 
         loop {
             let mut did_something = false;
-            did_something |= self.socket_ingress(device, sockets);
+            did_something |= self.socket_ingress(device, sockets, forward);
+
+            // Keep receiving, but send nothing, while the link partner asks for a pause.
+            #[cfg(feature = "medium-ethernet")]
+            if self.inner.egress_paused() {
+                if !did_something {
+                    break;
+                }
+                readiness_may_have_changed = true;
+                continue;
+            }
+
             did_something |= self.socket_egress(device, sockets);
+            did_something |= self.local_ingress(device, sockets);
+
+            if let Some((forwarder, port)) = forward {
+                did_something |= self.forward_egress(device, forwarder, *port);
+            }
 
             #[cfg(feature = "proto-igmp")]
             {
                 did_something |= self.igmp_egress(device);
             }
 
+            did_something |= self.address_egress(device);
+
+            #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+            {
+                did_something |= self.autoip_egress(device);
+            }
+
+            #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+            {
+                did_something |= self.router_advert_egress(device);
+            }
+
+            #[cfg(feature = "medium-ieee802154")]
+            {
+                did_something |= self.registration_egress(device);
+                did_something |= self.pan_egress(device);
+            }
+
+            #[cfg(feature = "proto-rpl")]
+            {
+                did_something |= self.rpl_egress(device);
+            }
+
             if did_something {
                 readiness_may_have_changed = true;
             } else {
@@ -836,6 +1545,25 @@ impl Interface {
         }
 
         let inner = &mut self.inner;
+        let address_poll_at = inner.address_poll_at();
+        #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+        let address_poll_at = address_poll_at.into_iter().chain(inner.autoip_poll_at());
+        #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+        let address_poll_at = address_poll_at
+            .into_iter()
+            .chain(inner.router_advert_poll_at());
+        #[cfg(feature = "medium-ieee802154")]
+        let address_poll_at = address_poll_at
+            .into_iter()
+            .chain(inner.registration_poll_at())
+            .chain(inner.pan_poll_at());
+        #[cfg(feature = "proto-rpl")]
+        let address_poll_at = address_poll_at.into_iter().chain(inner.rpl_poll_at());
+        // Nothing can be sent before a pause ends.
+        #[cfg(feature = "medium-ethernet")]
+        let paused_until = inner.pause_poll_at().unwrap_or(Instant::ZERO);
+        #[cfg(not(feature = "medium-ethernet"))]
+        let paused_until = Instant::ZERO;
 
         sockets
             .items()
@@ -850,7 +1578,9 @@ impl Interface {
                     PollAt::Now => Some(Instant::from_millis(0)),
                 }
             })
+            .chain(address_poll_at)
             .min()
+            .map(|poll_at| poll_at.max(paused_until))
     }
 
     /// Return an _advisory wait time_ for calling [poll] the next time.
@@ -869,61 +1599,104 @@ impl Interface {
         }
     }
 
-    fn socket_ingress<D>(&mut self, device: &mut D, sockets: &mut SocketSet<'_>) -> bool
+    fn socket_ingress<D>(
+        &mut self,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+        forward: &mut Option<(&mut Forwarder<'_>, PortId)>,
+    ) -> bool
     where
         D: Device + ?Sized,
     {
         let mut processed_any = false;
 
-        while let Some((rx_token, tx_token)) = device.receive(self.inner.now) {
-            rx_token.consume(|frame| {
-                match self.inner.caps.medium {
-                    #[cfg(feature = "medium-ethernet")]
-                    Medium::Ethernet => {
-                        if let Some(packet) =
-                            self.inner
-                                .process_ethernet(sockets, &frame, &mut self.fragments)
-                        {
-                            if let Err(err) =
-                                self.inner
-                                    .dispatch(tx_token, packet, Some(&mut self.out_packets))
-                            {
-                                net_debug!("Failed to send response: {:?}", err);
+        loop {
+            let received =
+                device.receive_batch(self.inner.now, RECEIVE_BATCH_SIZE, |rx_token, tx_token| {
+                    rx_token.consume(|frame| {
+                        processed_any = true;
+
+                        if let Some((forwarder, port)) = forward {
+                            match self.inner.forward_ingress(frame, forwarder, *port) {
+                                forward::Forwarding::Local => (),
+                                forward::Forwarding::Consumed => return,
+                                forward::Forwarding::Reply(packet) => {
+                                    if let Err(err) = self.inner.dispatch_ip(tx_token, packet, None)
+                                    {
+                                        net_debug!("Failed to send ICMP error: {:?}", err);
+                                    }
+                                    return;
+                                }
                             }
                         }
-                    }
-                    #[cfg(feature = "medium-ip")]
-                    Medium::Ip => {
-                        if let Some(packet) =
-                            self.inner.process_ip(sockets, &frame, &mut self.fragments)
-                        {
-                            if let Err(err) = self.inner.dispatch_ip(
-                                tx_token,
-                                packet,
-                                Some(&mut self.out_packets),
-                            ) {
-                                net_debug!("Failed to send response: {:?}", err);
+
+                        match self.inner.caps.medium {
+                            #[cfg(feature = "medium-ethernet")]
+                            Medium::Ethernet => {
+                                let packet = match self.inner.process_ethernet(
+                                    sockets,
+                                    &frame,
+                                    &mut self.fragments,
+                                ) {
+                                    Some(EthernetPacket::Ip(packet)) => self
+                                        .inner
+                                        .queue_local(&mut self.local, packet)
+                                        .map(EthernetPacket::Ip),
+                                    packet => packet,
+                                };
+                                if let Some(packet) = packet {
+                                    if let Err(err) = self.inner.dispatch(
+                                        tx_token,
+                                        packet,
+                                        Some(&mut self.out_packets),
+                                    ) {
+                                        net_debug!("Failed to send response: {:?}", err);
+                                    }
+                                }
                             }
-                        }
-                    }
-                    #[cfg(feature = "medium-ieee802154")]
-                    Medium::Ieee802154 => {
-                        if let Some(packet) =
-                            self.inner
-                                .process_ieee802154(sockets, &frame, &mut self.fragments)
-                        {
-                            if let Err(err) = self.inner.dispatch_ip(
-                                tx_token,
-                                packet,
-                                Some(&mut self.out_packets),
-                            ) {
-                                net_debug!("Failed to send response: {:?}", err);
+                            #[cfg(feature = "medium-ip")]
+                            Medium::Ip => {
+                                if let Some(packet) = self
+                                    .inner
+                                    .process_ip(sockets, &frame, &mut self.fragments)
+                                    .and_then(|packet| {
+                                        self.inner.queue_local(&mut self.local, packet)
+                                    })
+                                {
+                                    if let Err(err) = self.inner.dispatch_ip(
+                                        tx_token,
+                                        packet,
+                                        Some(&mut self.out_packets),
+                                    ) {
+                                        net_debug!("Failed to send response: {:?}", err);
+                                    }
+                                }
+                            }
+                            #[cfg(feature = "medium-ieee802154")]
+                            Medium::Ieee802154 => {
+                                if let Some(packet) = self
+                                    .inner
+                                    .process_ieee802154(sockets, &frame, &mut self.fragments)
+                                    .and_then(|packet| {
+                                        self.inner.queue_local(&mut self.local, packet)
+                                    })
+                                {
+                                    if let Err(err) = self.inner.dispatch_ip(
+                                        tx_token,
+                                        packet,
+                                        Some(&mut self.out_packets),
+                                    ) {
+                                        net_debug!("Failed to send response: {:?}", err);
+                                    }
+                                }
                             }
                         }
-                    }
-                }
-                processed_any = true;
-            });
+                    });
+                });
+            self.inner.end_batch();
+            if received == 0 {
+                break;
+            }
         }
 
         processed_any
@@ -941,108 +1714,173 @@ impl Interface {
         }
 
         let mut emitted_any = false;
-        for item in sockets.items_mut() {
-            if item.socket.wait_on_neighbor_discovery() {
-                if !item
-                    .meta
-                    .egress_permitted(self.inner.now, |ip_addr| self.inner.has_neighbor(&ip_addr))
-                {
+        // Serve the higher priority classes first, so that their sockets get the
+        // transmit buffers of the device before they run out.
+        'classes: for priority in SocketPriority::DESCENDING {
+            for (index, item) in sockets.items_mut_from(self.egress_cursor) {
+                if item.meta.priority() != priority {
                     continue;
                 }
-            }
-
-            let mut neighbor_addr = None;
-            let mut respond = |inner: &mut InterfaceInner, response: IpPacket| {
-                neighbor_addr = Some(response.ip_repr().dst_addr());
-                let t = device.transmit(inner.now).ok_or_else(|| {
-                    net_debug!("failed to transmit IP: device exhausted");
-                    EgressError::Exhausted
-                })?;
 
-                #[cfg(any(
-                    feature = "proto-ipv4-fragmentation",
-                    feature = "proto-sixlowpan-fragmentation"
-                ))]
-                inner
-                    .dispatch_ip(t, response, Some(&mut self.out_packets))
-                    .map_err(EgressError::Dispatch)?;
-
-                #[cfg(not(any(
-                    feature = "proto-ipv4-fragmentation",
-                    feature = "proto-sixlowpan-fragmentation"
-                )))]
-                inner
-                    .dispatch_ip(t, response, None)
-                    .map_err(EgressError::Dispatch)?;
+                if item.socket.wait_on_neighbor_discovery() {
+                    if !item.meta.egress_permitted(self.inner.now, |ip_addr| {
+                        self.inner.has_neighbor(&ip_addr)
+                    }) {
+                        continue;
+                    }
+                }
 
-                emitted_any = true;
+                #[cfg(feature = "proto-ipv6")]
+                let ext_headers = item.socket.ipv6_ext_headers().cloned();
+                #[cfg(not(feature = "proto-ipv6"))]
+                let ext_headers = None;
+
+                let mut neighbor_addr = None;
+                let mut respond = |inner: &mut InterfaceInner, mut response: IpPacket| {
+                    neighbor_addr = Some(response.ip_repr().dst_addr());
+
+                    // Packets to local addresses are delivered without going through the
+                    // device, unless they are too big to be queued.
+                    if inner.is_local(response.ip_repr().dst_addr()) {
+                        let free = self.local.free();
+                        if let Some(size) =
+                            inner.emit_local(&mut response, ext_headers.as_ref(), free)
+                        {
+                            self.local.commit(size);
+                            emitted_any = true;
+                            return Ok(());
+                        }
+                    }
 
-                Ok(())
-            };
+                    let t = device.transmit(inner.now).ok_or_else(|| {
+                        net_debug!("failed to transmit IP: device exhausted");
+                        EgressError::Exhausted
+                    })?;
+
+                    #[cfg(any(
+                        feature = "proto-ipv4-fragmentation",
+                        feature = "proto-sixlowpan-fragmentation"
+                    ))]
+                    inner
+                        .dispatch_ip_with_ext(
+                            t,
+                            response,
+                            ext_headers.as_ref(),
+                            Some(&mut self.out_packets),
+                        )
+                        .map_err(EgressError::Dispatch)?;
+
+                    #[cfg(not(any(
+                        feature = "proto-ipv4-fragmentation",
+                        feature = "proto-sixlowpan-fragmentation"
+                    )))]
+                    inner
+                        .dispatch_ip_with_ext(t, response, ext_headers.as_ref(), None)
+                        .map_err(EgressError::Dispatch)?;
+
+                    emitted_any = true;
+
+                    Ok(())
+                };
 
-            let result = match &mut item.socket {
-                #[cfg(feature = "socket-raw")]
-                Socket::Raw(socket) => socket.dispatch(&mut self.inner, |inner, response| {
-                    respond(inner, IpPacket::Raw(response))
-                }),
-                #[cfg(feature = "socket-icmp")]
-                Socket::Icmp(socket) => {
-                    socket.dispatch(&mut self.inner, |inner, response| match response {
-                        #[cfg(feature = "proto-ipv4")]
-                        (IpRepr::Ipv4(ipv4_repr), IcmpRepr::Ipv4(icmpv4_repr)) => {
-                            respond(inner, IpPacket::Icmpv4((ipv4_repr, icmpv4_repr)))
-                        }
-                        #[cfg(feature = "proto-ipv6")]
-                        (IpRepr::Ipv6(ipv6_repr), IcmpRepr::Ipv6(icmpv6_repr)) => {
-                            respond(inner, IpPacket::Icmpv6((ipv6_repr, icmpv6_repr)))
-                        }
-                        #[allow(unreachable_patterns)]
-                        _ => unreachable!(),
-                    })
-                }
-                #[cfg(feature = "socket-udp")]
-                Socket::Udp(socket) => socket.dispatch(&mut self.inner, |inner, response| {
-                    respond(inner, IpPacket::Udp(response))
-                }),
-                #[cfg(feature = "socket-tcp")]
-                Socket::Tcp(socket) => socket.dispatch(&mut self.inner, |inner, response| {
-                    respond(inner, IpPacket::Tcp(response))
-                }),
-                #[cfg(feature = "socket-dhcpv4")]
-                Socket::Dhcpv4(socket) => socket.dispatch(&mut self.inner, |inner, response| {
-                    respond(inner, IpPacket::Dhcpv4(response))
-                }),
-                #[cfg(feature = "socket-dhcpv6")]
-                Socket::Dhcpv6(socket) => socket.dispatch(&mut self.inner, |inner, response| {
-                    use crate::socket::dhcpv6::DispatchEmit;
-                    match response {
-                        DispatchEmit::Dhcp(ip, udp, dhcp) => respond(inner, IpPacket::Dhcpv6((ip, udp, dhcp))),
-                        DispatchEmit::Icmp(ip, icmp) => respond(inner, IpPacket::Icmpv6((ip, icmp))),
+                let result = match &mut item.socket {
+                    #[cfg(feature = "socket-raw")]
+                    Socket::Raw(socket) => socket.dispatch(&mut self.inner, |inner, response| {
+                        respond(inner, IpPacket::Raw(response))
+                    }),
+                    #[cfg(feature = "socket-icmp")]
+                    Socket::Icmp(socket) => {
+                        socket.dispatch(&mut self.inner, |inner, response| match response {
+                            #[cfg(feature = "proto-ipv4")]
+                            (IpRepr::Ipv4(ipv4_repr), IcmpRepr::Ipv4(icmpv4_repr)) => {
+                                respond(inner, IpPacket::Icmpv4((ipv4_repr, icmpv4_repr)))
+                            }
+                            #[cfg(feature = "proto-ipv6")]
+                            (IpRepr::Ipv6(ipv6_repr), IcmpRepr::Ipv6(icmpv6_repr)) => {
+                                respond(inner, IpPacket::Icmpv6((ipv6_repr, icmpv6_repr)))
+                            }
+                            #[allow(unreachable_patterns)]
+                            _ => unreachable!(),
+                        })
                     }
-                    
-                }),
-                #[cfg(feature = "socket-dns")]
-                Socket::Dns(ref mut socket) => socket
-                    .dispatch(&mut self.inner, |inner, response| {
+                    #[cfg(feature = "socket-udp")]
+                    Socket::Udp(socket) => socket.dispatch(&mut self.inner, |inner, response| {
                         respond(inner, IpPacket::Udp(response))
                     }),
-            };
+                    #[cfg(feature = "socket-tcp")]
+                    Socket::Tcp(socket) => socket.dispatch(&mut self.inner, |inner, response| {
+                        respond(inner, IpPacket::Tcp(response))
+                    }),
+                    #[cfg(feature = "socket-dhcpv4")]
+                    Socket::Dhcpv4(socket) => socket
+                        .dispatch(&mut self.inner, |inner, response| {
+                            respond(inner, IpPacket::Dhcpv4(response))
+                        }),
+                    #[cfg(feature = "socket-dhcpv6")]
+                    Socket::Dhcpv6(socket) => {
+                        socket.dispatch(&mut self.inner, |inner, response| {
+                            use crate::socket::dhcpv6::DispatchEmit;
+                            match response {
+                                DispatchEmit::Dhcp(ip, udp, dhcp) => {
+                                    respond(inner, IpPacket::Dhcpv6((ip, udp, dhcp)))
+                                }
+                                DispatchEmit::Icmp(ip, icmp) => {
+                                    respond(inner, IpPacket::Icmpv6((ip, icmp)))
+                                }
+                            }
+                        })
+                    }
+                    #[cfg(feature = "socket-dns")]
+                    Socket::Dns(ref mut socket) => socket
+                        .dispatch(&mut self.inner, |inner, response| {
+                            respond(inner, IpPacket::Udp(response))
+                        }),
+                    #[cfg(feature = "socket-dns-sd")]
+                    Socket::DnsSd(socket) => socket.dispatch(&mut self.inner, |inner, response| {
+                        respond(inner, IpPacket::Udp(response))
+                    }),
+                    #[cfg(feature = "socket-tftp")]
+                    Socket::Tftp(socket) => socket.dispatch(&mut self.inner, |inner, response| {
+                        respond(inner, IpPacket::Udp(response))
+                    }),
+                    #[cfg(feature = "socket-stamp")]
+                    Socket::Stamp(socket) => socket.dispatch(&mut self.inner, |inner, response| {
+                        respond(inner, IpPacket::Udp(response))
+                    }),
+                    #[cfg(feature = "socket-packet")]
+                    Socket::Packet(socket) => socket.dispatch(&mut self.inner, |inner, frame| {
+                        if inner.caps.medium != Medium::Ethernet {
+                            net_debug!("dropping frame: interface medium is not ethernet");
+                            return Ok(());
+                        }
+                        let t = device.transmit(inner.now).ok_or_else(|| {
+                            net_debug!("failed to transmit frame: device exhausted");
+                            EgressError::Exhausted
+                        })?;
+                        t.consume(frame.len(), |buffer| buffer.copy_from_slice(frame));
+                        emitted_any = true;
+                        Ok(())
+                    }),
+                };
 
-            match result {
-                Err(EgressError::Exhausted) => break, // Device buffer full.
-                Err(EgressError::Dispatch(_)) => {
-                    // `NeighborCache` already takes care of rate limiting the neighbor discovery
-                    // requests from the socket. However, without an additional rate limiting
-                    // mechanism, we would spin on every socket that has yet to discover its
-                    // neighbor.]
-                    let neighbor_addr = neighbor_addr.expect("non-IP response packet");
-                    item.socket.set_neighbor_discovering(neighbor_addr);
-                    item.meta.neighbor_missing(
-                        self.inner.now,
-                        neighbor_addr,
-                    );
+                match result {
+                    Err(EgressError::Exhausted) => {
+                        // Device buffer full, or poll budget exhausted; serve this socket
+                        // first next time.
+                        self.egress_cursor = index;
+                        break 'classes;
+                    }
+                    Err(EgressError::Dispatch(_)) => {
+                        // `NeighborCache` already takes care of rate limiting the neighbor discovery
+                        // requests from the socket. However, without an additional rate limiting
+                        // mechanism, we would spin on every socket that has yet to discover its
+                        // neighbor.]
+                        let neighbor_addr = neighbor_addr.expect("non-IP response packet");
+                        item.socket.set_neighbor_discovering(neighbor_addr);
+                        item.meta.neighbor_missing(self.inner.now, neighbor_addr);
+                    }
+                    Ok(()) => {}
                 }
-                Ok(()) => {}
             }
         }
         emitted_any
@@ -1140,39 +1978,115 @@ impl InterfaceInner {
 
     #[allow(unused)] // unused depending on which sockets are enabled
     pub(crate) fn ip_mtu(&self) -> usize {
+        #[cfg(feature = "medium-ethernet")]
+        if self.caps.medium == Medium::Ethernet {
+            return self.caps.max_transmission_unit - self.ethernet_encapsulation.header_len();
+        }
         self.caps.ip_mtu()
     }
 
+    /// Return the length of the headers of the Ethernet frames sent by the interface.
+    #[cfg(feature = "medium-ethernet")]
+    pub(super) fn ethernet_header_len(&self) -> usize {
+        self.ethernet_encapsulation.header_len()
+    }
+
+    /// Return the MTU of the IP packets of a version, which devices carrying both
+    /// IPv4 and IPv6 may limit separately.
+    #[allow(unused)] // unused depending on which sockets are enabled
+    pub(crate) fn ip_mtu_of(&self, version: IpVersion) -> usize {
+        self.ip_mtu().min(self.caps.ip_mtu_of(version))
+    }
+
+    /// Return the length of the IPv4 fragments that fit the MTU, with a header of
+    /// `header_len` and a payload that is a multiple of 8 octets.
+    #[cfg(feature = "proto-ipv4-fragmentation")]
+    pub(super) fn ipv4_fragment_len(&self, header_len: usize) -> usize {
+        let payload_len = self.ip_mtu_of(IpVersion::Ipv4) - header_len;
+        header_len + payload_len / 8 * 8
+    }
+
+    /// Return the MTU of IP packets sent from `src_addr`, which is smaller for the
+    /// packets translated to IPv6 by the CLAT.
+    #[allow(unused)] // unused depending on which sockets are enabled
+    pub(crate) fn ip_mtu_from(&self, src_addr: &IpAddress) -> usize {
+        #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+        if self.clat_translates(src_addr) {
+            return self.ip_mtu_of(IpVersion::Ipv6) - (IPV6_HEADER_LEN - IPV4_HEADER_LEN);
+        }
+        self.ip_mtu_of(src_addr.version())
+    }
+
+    /// Return the largest TCP segment, headers included, that the device splits into
+    /// segments of the MTU when sent from `src_addr` to `dst_addr`, if it does.
+    ///
+    /// Segments delivered locally or translated by the CLAT are never split.
+    #[allow(unused)] // unused depending on which sockets are enabled
+    pub(crate) fn tcp_segmentation_size(
+        &self,
+        _src_addr: &IpAddress,
+        dst_addr: &IpAddress,
+    ) -> Option<usize> {
+        let size = self.caps.max_segmentation_size?;
+        if self.is_local(*dst_addr) {
+            return None;
+        }
+        #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+        if self.clat_translates(_src_addr) {
+            return None;
+        }
+        Some(size)
+    }
+
     #[allow(unused)] // unused depending on which sockets are enabled, and in tests
     pub(crate) fn rand(&mut self) -> &mut Rand {
         &mut self.rand
     }
 
+    /// Find a source address among the assigned ones, preferring those that are not
+    /// deprecated. Tentative addresses are never used.
+    fn find_source_address<T>(&self, f: impl Fn(&IpCidr) -> Option<T>) -> Option<T> {
+        let with_state = |state| {
+            self.ip_addrs
+                .iter()
+                .filter(move |cidr| self.address_state(&cidr.address()) == state)
+                .find_map(&f)
+        };
+        with_state(AddressState::Preferred).or_else(|| with_state(AddressState::Deprecated))
+    }
+
     #[allow(unused)] // unused depending on which sockets are enabled
     pub(crate) fn get_source_address(&mut self, dst_addr: IpAddress) -> Option<IpAddress> {
+        #[cfg(feature = "proto-ipv4")]
+        if let IpAddress::Ipv4(dst_addr) = dst_addr {
+            return self.get_source_address_ipv4(dst_addr).map(IpAddress::Ipv4);
+        }
         let v = dst_addr.version();
-        for cidr in self.ip_addrs.iter() {
+        self.find_source_address(|cidr| {
             let addr = cidr.address();
-            if addr.version() == v {
-                return Some(addr);
-            }
-        }
-        None
+            (addr.version() == v).then_some(addr)
+        })
     }
 
     #[cfg(feature = "proto-ipv4")]
     #[allow(unused)]
-    pub(crate) fn get_source_address_ipv4(
-        &mut self,
-        _dst_addr: Ipv4Address,
-    ) -> Option<Ipv4Address> {
-        for cidr in self.ip_addrs.iter() {
-            #[allow(irrefutable_let_patterns)] // if only ipv4 is enabled
-            if let IpCidr::Ipv4(cidr) = cidr {
-                return Some(cidr.address());
-            }
-        }
-        None
+    pub(crate) fn get_source_address_ipv4(&mut self, dst_addr: Ipv4Address) -> Option<Ipv4Address> {
+        // Link-local addresses are only used to reach link-local destinations, and the
+        // other addresses to reach the other destinations, unless there is no choice,
+        // see RFC 3927 § 2.6.1.
+        let link_local = dst_addr.is_link_local();
+        let find = |same_scope: bool| {
+            self.find_source_address(|cidr| match cidr {
+                IpCidr::Ipv4(cidr)
+                    if !same_scope || cidr.address().is_link_local() == link_local =>
+                {
+                    Some(cidr.address())
+                }
+                #[allow(unreachable_patterns)]
+                _ => None,
+            })
+        };
+        find(true).or_else(|| find(false))
     }
 
     #[cfg(feature = "proto-ipv6")]
@@ -1181,13 +2095,11 @@ impl InterfaceInner {
         &mut self,
         _dst_addr: Ipv6Address,
     ) -> Option<Ipv6Address> {
-        for cidr in self.ip_addrs.iter() {
-            #[allow(irrefutable_let_patterns)] // if only ipv6 is enabled
-            if let IpCidr::Ipv6(cidr) = cidr {
-                return Some(cidr.address());
-            }
-        }
-        None
+        self.find_source_address(|cidr| match cidr {
+            IpCidr::Ipv6(cidr) => Some(cidr.address()),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        })
     }
 
     #[cfg(test)]
@@ -1208,9 +2120,15 @@ impl InterfaceInner {
                     icmpv6: crate::phy::Checksum::Both,
                     ipv4: crate::phy::Checksum::Both,
                     tcp: crate::phy::Checksum::Both,
+                    sctp: crate::phy::Checksum::Both,
                     udp: crate::phy::Checksum::Both,
                 },
                 max_burst_size: None,
+                max_segmentation_size: None,
+                #[cfg(feature = "proto-ipv4")]
+                ipv4_mtu: None,
+                #[cfg(feature = "proto-ipv6")]
+                ipv6_mtu: None,
                 #[cfg(feature = "medium-ethernet")]
                 max_transmission_unit: 1514,
                 #[cfg(not(feature = "medium-ethernet"))]
@@ -1228,11 +2146,49 @@ impl InterfaceInner {
                 )),
             ])
             .unwrap(),
+            addr_info: LinearMap::new(),
+            addr_events: Deque::new(),
             rand: Rand::new(1234),
             routes: Routes::new(),
-
+            #[cfg(feature = "socket-tcp")]
+            host_cache: HostCache::new(),
+            #[cfg(feature = "socket-tcp")]
+            tcp_isn_secret: [0; 16],
+
+            #[cfg(any(
+                feature = "socket-udp",
+                feature = "socket-dns",
+                feature = "socket-dhcpv6"
+            ))]
+            port_unreachable: PortUnreachable::new(),
+            #[cfg(feature = "socket-tcp")]
+            tcp_resets: TcpResets::new(),
+            #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+            autoip: None,
             #[cfg(feature = "proto-ipv4")]
             any_ip: false,
+            any_ip_prefixes: Vec::new(),
+            #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+            proxy_arp_prefixes: Vec::new(),
+            #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+            rarp_table: LinearMap::new(),
+            #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+            proxy_nd_prefixes: Vec::new(),
+            #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+            router_advert: None,
+            prerouting_hook: None,
+            postrouting_hook: None,
+            stats: InterfaceStats::default(),
+            #[cfg(feature = "proto-ipv6")]
+            nat64_prefix: None,
+            #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+            clat: None,
+            #[cfg(feature = "medium-ieee802154")]
+            registration: None,
+            #[cfg(feature = "proto-rpl")]
+            rpl: None,
+            #[cfg(feature = "medium-ieee802154")]
+            pan: PanState::default(),
 
             #[cfg(feature = "medium-ieee802154")]
             pan_id: Some(crate::wire::Ieee802154Pan(0xabcd)),
@@ -1243,7 +2199,7 @@ impl InterfaceInner {
             tag: 1,
 
             #[cfg(feature = "proto-sixlowpan")]
-            sixlowpan_address_context: Vec::new(),
+            sixlowpan_address_context: [None; SIXLOWPAN_ADDRESS_CONTEXT_COUNT],
 
             #[cfg(feature = "proto-ipv4-fragmentation")]
             ipv4_id: 1,
@@ -1258,23 +2214,63 @@ impl InterfaceInner {
                     0x02, 0x02, 0x02, 0x02, 0x02, 0x02, 0x2, 0x2,
                 ]),
             )),
+            #[cfg(feature = "medium-ethernet")]
+            ethernet_encapsulation: EthernetEncapsulation::EthernetII,
+            #[cfg(feature = "medium-ethernet")]
+            pause: Pause::default(),
 
             #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
             neighbor_cache: None,
+            #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+            batch_neighbor: None,
 
             #[cfg(feature = "proto-igmp")]
             igmp_report_state: IgmpReportState::Inactive,
             #[cfg(feature = "proto-igmp")]
-            ipv4_multicast_groups: LinearMap::new(),
+            ipv4_multicast_groups: Table::with_capacity(MAX_IPV4_MULTICAST_GROUPS),
         }
     }
 
+    /// Return the initial sequence number of a TCP connection, as described in
+    /// [RFC 6528]: a clock ticking every 4 microseconds, offset by a keyed hash of the
+    /// connection, so that it can be guessed neither by off-path attackers nor from
+    /// the sequence numbers of other connections.
+    ///
+    /// [RFC 6528]: https://www.rfc-editor.org/rfc/rfc6528
+    #[cfg(feature = "socket-tcp")]
+    pub(crate) fn tcp_isn(&self, local: IpEndpoint, remote: IpEndpoint) -> TcpSeqNumber {
+        let mut hasher = SipHasher::new(&self.tcp_isn_secret);
+        hasher.write(local.addr.as_bytes());
+        hasher.write(&local.port.to_be_bytes());
+        hasher.write(remote.addr.as_bytes());
+        hasher.write(&remote.port.to_be_bytes());
+        let offset = hasher.finish() as u32;
+        let clock = (self.now.total_micros() / 4) as u32;
+        TcpSeqNumber(clock.wrapping_add(offset) as i32)
+    }
+
+    #[cfg(feature = "socket-tcp")]
+    pub(crate) fn host_cache(&self) -> &HostCache {
+        &self.host_cache
+    }
+
+    #[cfg(feature = "socket-tcp")]
+    pub(crate) fn host_cache_mut(&mut self) -> &mut HostCache {
+        &mut self.host_cache
+    }
+
     #[cfg(test)]
     #[allow(unused)] // unused depending on which sockets are enabled
     pub(crate) fn set_now(&mut self, now: Instant) {
         self.now = now
     }
 
+    #[cfg(test)]
+    #[allow(unused)] // unused depending on which sockets are enabled
+    pub(crate) fn caps_mut(&mut self) -> &mut DeviceCapabilities {
+        &mut self.caps
+    }
+
     #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
     fn check_hardware_addr(addr: &HardwareAddress) {
         if !addr.is_unicast() {
@@ -1330,10 +2326,23 @@ impl InterfaceInner {
         })
     }
 
-    /// Check whether the interface has the given IP address assigned.
-    fn has_ip_addr<T: Into<IpAddress>>(&self, addr: T) -> bool {
+    /// Check whether the interface has the given IP address assigned, and done with
+    /// Duplicate Address Detection.
+    pub(crate) fn has_ip_addr<T: Into<IpAddress>>(&self, addr: T) -> bool {
         let addr = addr.into();
-        self.ip_addrs.iter().any(|probe| probe.address() == addr)
+        self.ip_addrs.iter().any(|probe| probe.address() == addr) && !self.is_tentative(&addr)
+    }
+
+    /// Check whether traffic of the given protocol to `addr` is accepted because of
+    /// an AnyIP prefix.
+    fn is_any_ip<T: Into<IpAddress>>(&self, addr: T, protocol: IpProtocol) -> bool {
+        let addr = addr.into();
+        matches!(protocol, IpProtocol::Tcp | IpProtocol::Udp)
+            && addr.is_unicast()
+            && self
+                .any_ip_prefixes
+                .iter()
+                .any(|prefix| prefix.contains_addr(&addr))
     }
 
     /// Get the first IPv4 address of the interface.
@@ -1361,7 +2370,6 @@ impl InterfaceInner {
         false
     }
 
-    #[cfg(feature = "medium-ip")]
     fn process_ip<'frame, T: AsRef<[u8]>>(
         &mut self,
         sockets: &mut SocketSet,
@@ -1371,7 +2379,8 @@ impl InterfaceInner {
         match IpVersion::of_packet(ip_payload.as_ref()) {
             #[cfg(feature = "proto-ipv4")]
             Ok(IpVersion::Ipv4) => {
-                let ipv4_packet = check!(Ipv4Packet::new_checked(ip_payload));
+                let ipv4_packet =
+                    check!(Ipv4Packet::new_checked(ip_payload), self.stats, ipv4_errors);
 
                 #[cfg(feature = "proto-ipv4-fragmentation")]
                 {
@@ -1385,7 +2394,8 @@ impl InterfaceInner {
             }
             #[cfg(feature = "proto-ipv6")]
             Ok(IpVersion::Ipv6) => {
-                let ipv6_packet = check!(Ipv6Packet::new_checked(ip_payload));
+                let ipv6_packet =
+                    check!(Ipv6Packet::new_checked(ip_payload), self.stats, ipv6_errors);
                 self.process_ipv6(sockets, &ipv6_packet)
             }
             // Drop all other traffic.
@@ -1435,6 +2445,18 @@ impl InterfaceInner {
         address.is_broadcast() || self.is_subnet_broadcast(address)
     }
 
+    /// Checks if an address is a broadcast address, either the limited broadcast address
+    /// or the directed broadcast address of one of the interface's subnets.
+    #[allow(unused)] // unused depending on which sockets are enabled
+    pub(crate) fn is_broadcast(&self, address: &IpAddress) -> bool {
+        match *address {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(address) => self.is_broadcast_v4(address),
+            #[cfg(feature = "proto-ipv6")]
+            IpAddress::Ipv6(_) => false,
+        }
+    }
+
     /// Checks if an ipv4 address is unicast, taking into account subnet broadcast addresses
     #[cfg(feature = "proto-ipv4")]
     fn is_unicast_v4(&self, address: Ipv4Address) -> bool {
@@ -1472,12 +2494,38 @@ impl InterfaceInner {
         }
 
         #[cfg(feature = "socket-udp")]
-        for udp_socket in sockets
+        {
+            let flow = Flow::new(
+                IpProtocol::Udp,
+                &ip_repr,
+                udp_repr.dst_port,
+                udp_repr.src_port,
+            );
+            let handle = sockets.find_flow(&flow, |socket| {
+                udp::Socket::downcast(socket).map_or(false, |udp_socket| {
+                    udp_socket.accepts(self, &ip_repr, &udp_repr)
+                })
+            });
+            if let Some(handle) = handle {
+                sockets.cache_flow(&flow, handle);
+                sockets.get_mut::<udp::Socket>(handle).process(
+                    self,
+                    &ip_repr,
+                    &udp_repr,
+                    udp_payload,
+                );
+                return None;
+            }
+        }
+
+        #[cfg(feature = "socket-dns-sd")]
+        for dns_sd_socket in sockets
             .items_mut()
-            .filter_map(|i| udp::Socket::downcast_mut(&mut i.socket))
+            .filter_map(|i| dns_sd::Socket::downcast_mut(&mut i.socket))
         {
-            if udp_socket.accepts(self, &ip_repr, &udp_repr) {
-                udp_socket.process(self, &ip_repr, &udp_repr, udp_payload);
+            if dns_sd_socket.accepts(&ip_repr, &udp_repr)
+                && dns_sd_socket.process(self, &ip_repr, &udp_repr, udp_payload)
+            {
                 return None;
             }
         }
@@ -1493,8 +2541,33 @@ impl InterfaceInner {
             }
         }
 
+        #[cfg(feature = "socket-tftp")]
+        for tftp_socket in sockets
+            .items_mut()
+            .filter_map(|i| tftp::Socket::downcast_mut(&mut i.socket))
+        {
+            if tftp_socket.accepts(&ip_repr, &udp_repr) {
+                tftp_socket.process(self, &ip_repr, &udp_repr, udp_payload);
+                return None;
+            }
+        }
+
+        #[cfg(feature = "socket-stamp")]
+        for stamp_socket in sockets
+            .items_mut()
+            .filter_map(|i| stamp::Socket::downcast_mut(&mut i.socket))
+        {
+            if stamp_socket.accepts(&ip_repr, &udp_repr) {
+                stamp_socket.process(self, &ip_repr, &udp_repr, udp_payload);
+                return None;
+            }
+        }
+
         // The packet wasn't handled by a socket, send an ICMP port unreachable packet.
-        match ip_repr {
+        if !handled_by_raw_socket {
+            self.stats.dropped_no_socket = self.stats.dropped_no_socket.wrapping_add(1);
+        }
+        let reply = match ip_repr {
             #[cfg(feature = "proto-ipv4")]
             IpRepr::Ipv4(_) if handled_by_raw_socket => None,
             #[cfg(feature = "proto-ipv6")]
@@ -1521,6 +2594,11 @@ impl InterfaceInner {
                 };
                 self.icmpv6_reply(ipv6_repr, icmpv6_reply_repr)
             }
+        };
+        // Only the errors actually sent count against the rate limit.
+        match reply {
+            Some(_) if self.take_port_unreachable() => reply,
+            _ => None,
         }
     }
 
@@ -1533,38 +2611,49 @@ impl InterfaceInner {
         reset_orphans: bool,
     ) -> Option<IpPacket<'frame>> {
         let (src_addr, dst_addr) = (ip_repr.src_addr(), ip_repr.dst_addr());
-        let tcp_packet = check!(TcpPacket::new_checked(ip_payload));
-        let tcp_repr = check!(TcpRepr::parse(
-            &tcp_packet,
-            &src_addr,
-            &dst_addr,
-            &self.caps.checksum
-        ));
+        let tcp_packet = check!(TcpPacket::new_checked(ip_payload), self.stats, tcp_errors);
+        let tcp_repr = check!(
+            TcpRepr::parse(&tcp_packet, &src_addr, &dst_addr, &self.caps.checksum),
+            self.stats,
+            tcp_errors,
+            bad_checksum: self.caps.checksum.tcp.rx()
+                && !tcp_packet.verify_checksum(&src_addr, &dst_addr)
+        );
+
+        let flow = Flow::new(
+            IpProtocol::Tcp,
+            &ip_repr,
+            tcp_repr.dst_port,
+            tcp_repr.src_port,
+        );
+        let handle = sockets.find_flow(&flow, |socket| {
+            tcp::Socket::downcast(socket).map_or(false, |tcp_socket| {
+                tcp_socket.accepts(self, &ip_repr, &tcp_repr)
+            })
+        });
 
         let mut accept_tcp = None;
-        for (handle, tcp_socket) in sockets
-            .items_and_handles_mut()
-            .filter_map(|(h, i)| tcp::Socket::downcast_mut(&mut i.socket).map(|i| (h, i)))
-        {
-            if tcp_socket.accepts(self, &ip_repr, &tcp_repr) {
-                let was_listening = tcp_socket.is_listening();
-                let ret = tcp_socket
-                    .process(self, &ip_repr, &tcp_repr)
-                    .map(IpPacket::Tcp);
-
-                // If the socket was listening but is not any more then we have
-                // to rebuild the socket and put the old one in a queue
-                if was_listening && tcp_socket.is_listening() == false && tcp_socket.has_backlog() {
-                    accept_tcp = Some((handle.clone(), tcp_socket, ret));
-                    break;
-                }
+        if let Some(handle) = handle {
+            sockets.cache_flow(&flow, handle);
+            let tcp_socket = sockets.get_mut::<tcp::Socket>(handle);
+            let was_listening = tcp_socket.is_listening();
+            let ret = tcp_socket
+                .process(self, &ip_repr, &tcp_repr)
+                .map(IpPacket::Tcp);
+
+            // If the socket was listening but is not any more then we have
+            // to rebuild the socket and put the old one in a queue
+            if was_listening && tcp_socket.is_listening() == false && tcp_socket.has_backlog() {
+                accept_tcp = Some((handle, ret));
+            } else {
                 return ret;
             }
         }
 
         // If the interface has a TCP socket with a backlog that has accepted a connection then
         // we create a new connection that will listen for the next connection
-        if let Some((listener_handle, tcp_socket, ret)) = accept_tcp {
+        if let Some((listener_handle, ret)) = accept_tcp {
+            let tcp_socket = sockets.get_mut::<tcp::Socket>(listener_handle);
             let new_socket = self.rebuild_tcp_listener(tcp_socket);
             let new_handle = sockets.swap(listener_handle, new_socket);
 
@@ -1592,10 +2681,13 @@ impl InterfaceInner {
             }
         }
 
+        if reset_orphans {
+            self.stats.dropped_no_socket = self.stats.dropped_no_socket.wrapping_add(1);
+        }
         if tcp_repr.control == TcpControl::Rst {
             // Never reply to a TCP RST packet with another TCP RST packet.
             None
-        } else if reset_orphans {
+        } else if reset_orphans && self.take_tcp_reset(&tcp_repr) {
             // The packet wasn't handled by a socket, send a TCP RST packet.
             net_trace!(
                 "packet wasn't handled by a socket, sending a TCP RST packet. (src_addr={}, dst_addr={})",
@@ -1610,6 +2702,7 @@ impl InterfaceInner {
         }
     }
 
+    #[cfg(feature = "socket-tcp")]
     fn rebuild_tcp_listener<'a>(&mut self, tcp_socket: &mut tcp::Socket<'a>) -> tcp::Socket<'a> {
         let rx_buffer = tcp::SocketBuffer::new(vec![0; tcp_socket.recv_capacity()]);
         let tx_buffer = tcp::SocketBuffer::new(vec![0; tcp_socket.send_capacity()]);
@@ -1617,10 +2710,25 @@ impl InterfaceInner {
         let mut new_socket = tcp::Socket::new(rx_buffer, tx_buffer);
         new_socket.swap_backlogs(tcp_socket);
         new_socket.set_hop_limit(tcp_socket.hop_limit());
+        new_socket.set_traffic_class(tcp_socket.traffic_class());
+        new_socket.set_flow_label(tcp_socket.flow_label());
         new_socket.set_ack_delay(tcp_socket.ack_delay());
+        new_socket.set_ack_delay_segments(tcp_socket.ack_delay_segments());
+        new_socket.set_initial_quick_acks(tcp_socket.initial_quick_acks());
         new_socket.set_nagle_enabled(tcp_socket.nagle_enabled());
+        new_socket.set_urgent_inline(tcp_socket.urgent_inline());
         new_socket.set_keep_alive(tcp_socket.keep_alive());
         new_socket.set_timeout(tcp_socket.timeout());
+        new_socket.set_linger(tcp_socket.linger());
+        new_socket.set_fin_wait_2_timeout(tcp_socket.fin_wait_2_timeout());
+        new_socket.set_time_wait_timeout(tcp_socket.time_wait_timeout());
+        new_socket.set_timestamps_enabled(tcp_socket.timestamps_enabled());
+        new_socket.set_assembler_capacity(tcp_socket.assembler_capacity());
+        new_socket.set_window_scale(tcp_socket.window_scale());
+        new_socket.set_initial_window(tcp_socket.initial_window());
+        new_socket.set_recv_window_autotuning(tcp_socket.recv_window_autotuning());
+        #[cfg(feature = "async")]
+        new_socket.set_send_low_water(tcp_socket.send_low_water());
         new_socket
             .listen(tcp_socket.listen_endpoint())
             .ok();
@@ -1647,9 +2755,15 @@ impl InterfaceInner {
                     } => target_hardware_addr,
                 };
 
-                self.dispatch_ethernet(tx_token, arp_repr.buffer_len(), |mut frame| {
+                let ethertype = match arp_repr {
+                    ArpRepr::EthernetIpv4 { operation, .. } if operation.is_reverse() => {
+                        EthernetProtocol::Rarp
+                    }
+                    _ => EthernetProtocol::Arp,
+                };
+
+                self.dispatch_ethernet(tx_token, ethertype, arp_repr.buffer_len(), |mut frame| {
                     frame.set_dst_addr(dst_hardware_addr);
-                    frame.set_ethertype(EthernetProtocol::Arp);
 
                     let mut packet = ArpPacket::new_unchecked(frame.payload_mut());
                     arp_repr.emit(&mut packet);
@@ -1674,7 +2788,13 @@ impl InterfaceInner {
     }
 
     fn has_neighbor(&self, addr: &IpAddress) -> bool {
-        match self.route(addr, self.now) {
+        let route = self.route(addr, self.now);
+        #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+        let route = route.or_else(|| {
+            let addr = self.clat_route(addr)?;
+            self.route(&addr, self.now)
+        });
+        match route {
             Some(_routed_addr) => match self.caps.medium {
                 #[cfg(feature = "medium-ethernet")]
                 Medium::Ethernet => self
@@ -1707,7 +2827,7 @@ impl InterfaceInner {
     where
         Tx: TxToken,
     {
-        if dst_addr.is_broadcast() {
+        if self.is_broadcast(dst_addr) {
             let hardware_addr = match self.caps.medium {
                 #[cfg(feature = "medium-ethernet")]
                 Medium::Ethernet => HardwareAddress::Ethernet(EthernetAddress::BROADCAST),
@@ -1753,19 +2873,36 @@ impl InterfaceInner {
             return Ok((hardware_addr, tx_token));
         }
 
-        let dst_addr = self
-            .route(dst_addr, self.now)
-            .ok_or(DispatchError::NoRoute)?;
+        // Packets of a batch mostly go to the same destination, skip the route and
+        // neighbor cache lookups for it.
+        let batch_addr = *dst_addr;
+        match self.batch_neighbor {
+            Some((addr, hardware_addr)) if addr == batch_addr => {
+                return Ok((hardware_addr, tx_token));
+            }
+            _ => (),
+        }
 
-        match self
+        let dst_addr = match self.route(dst_addr, self.now) {
+            Some(dst_addr) => dst_addr,
+            None => {
+                self.stats.dropped_no_route = self.stats.dropped_no_route.wrapping_add(1);
+                return Err(DispatchError::NoRoute);
+            }
+        };
+
+        let answer = self
             .neighbor_cache
             .as_mut()
             .unwrap()
-            .lookup(&dst_addr, self.now)
-        {
-            NeighborAnswer::Found(hardware_addr) => return Ok((hardware_addr, tx_token)),
-            NeighborAnswer::RateLimited => return Err(DispatchError::NeighborPending),
-            _ => (), // XXX
+            .lookup(&dst_addr, self.now);
+        if let NeighborAnswer::Found(hardware_addr) = answer {
+            self.batch_neighbor = Some((batch_addr, hardware_addr));
+            return Ok((hardware_addr, tx_token));
+        }
+        self.stats.neighbor_misses = self.stats.neighbor_misses.wrapping_add(1);
+        if let NeighborAnswer::RateLimited = answer {
+            return Err(DispatchError::NeighborPending);
         }
 
         match (src_addr, dst_addr) {
@@ -1785,14 +2922,16 @@ impl InterfaceInner {
                     target_protocol_addr: dst_addr,
                 };
 
-                if let Err(e) =
-                    self.dispatch_ethernet(tx_token, arp_repr.buffer_len(), |mut frame| {
+                if let Err(e) = self.dispatch_ethernet(
+                    tx_token,
+                    EthernetProtocol::Arp,
+                    arp_repr.buffer_len(),
+                    |mut frame| {
                         frame.set_dst_addr(EthernetAddress::BROADCAST);
-                        frame.set_ethertype(EthernetProtocol::Arp);
 
                         arp_repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()))
-                    })
-                {
+                    },
+                ) {
                     net_debug!("Failed to dispatch ARP request: {:?}", e);
                     return Err(DispatchError::NeighborPending);
                 }
@@ -1808,6 +2947,7 @@ impl InterfaceInner {
                 let solicit = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
                     target_addr: dst_addr,
                     lladdr: Some(self.hardware_addr.unwrap().into()),
+                    aro: None,
                 });
 
                 let packet = IpPacket::Icmpv6((
@@ -1817,6 +2957,8 @@ impl InterfaceInner {
                         next_header: IpProtocol::Icmpv6,
                         payload_len: solicit.buffer_len(),
                         hop_limit: 0xff,
+                        traffic_class: 0,
+                        flow_label: 0,
                     },
                     solicit,
                 ));
@@ -1841,22 +2983,64 @@ impl InterfaceInner {
         if let Some(cache) = self.neighbor_cache.as_mut() {
             cache.flush()
         }
+        self.end_batch();
+    }
+
+    /// Forget the destination resolved during the current batch of packets.
+    ///
+    /// This is also done at the start of each poll and whenever the neighbor cache
+    /// changes, so that a remembered destination is never older than the cache entry
+    /// it was resolved from.
+    fn end_batch(&mut self) {
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        {
+            self.batch_neighbor = None;
+        }
     }
 
     #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
-    pub fn inject_neighbor(&mut self, protocol_addr: IpAddress, hardware_addr: HardwareAddress, timestamp: Instant) {
+    pub fn inject_neighbor(
+        &mut self,
+        protocol_addr: IpAddress,
+        hardware_addr: HardwareAddress,
+        timestamp: Instant,
+    ) {
         if let Some(cache) = self.neighbor_cache.as_mut() {
             cache.fill(protocol_addr, hardware_addr, timestamp)
         }
+        self.end_batch();
     }
 
     fn dispatch_ip<Tx: TxToken>(
         &mut self,
         tx_token: Tx,
         packet: IpPacket,
+        out_packet: Option<&mut OutPackets>,
+    ) -> Result<(), DispatchError> {
+        self.dispatch_ip_with_ext(tx_token, packet, None, out_packet)
+    }
+
+    /// Like `dispatch_ip`, inserting `ext_headers` after the header of IPv6 packets.
+    fn dispatch_ip_with_ext<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        mut packet: IpPacket,
+        ext_headers: Option<&Ipv6ExtHeaders>,
         _out_packet: Option<&mut OutPackets>,
     ) -> Result<(), DispatchError> {
-        let mut ip_repr = packet.ip_repr();
+        #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+        {
+            packet = match self.clat_egress(packet) {
+                Some(packet) => packet,
+                None => return Ok(()),
+            };
+        }
+
+        if !self.postrouting(&mut packet) {
+            return Ok(());
+        }
+
+        let mut ip_repr = packet.ip_repr_with_ext(ext_headers);
         assert!(!ip_repr.dst_addr().is_unspecified());
 
         // Dispatch IEEE802.15.4:
@@ -1884,7 +3068,7 @@ impl InterfaceInner {
         // Add the size of the Ethernet header if the medium is Ethernet.
         #[cfg(feature = "medium-ethernet")]
         if matches!(self.caps.medium, Medium::Ethernet) {
-            total_len = EthernetFrame::<&[u8]>::buffer_len(total_len);
+            total_len += self.ethernet_header_len();
         }
 
         // If the medium is Ethernet, then we need to retrieve the destination hardware address.
@@ -1904,6 +3088,10 @@ impl InterfaceInner {
             _ => (EthernetAddress([0; 6]), tx_token),
         };
 
+        // Tell devices carrying both IPv4 and IPv6 which one the packet is.
+        let mut tx_token = tx_token;
+        tx_token.set_ip_version(ip_repr.version());
+
         // Emit function for the Ethernet header.
         #[cfg(feature = "medium-ethernet")]
         let emit_ethernet = |repr: &IpRepr, tx_buffer: &mut [u8]| {
@@ -1913,12 +3101,13 @@ impl InterfaceInner {
             frame.set_src_addr(src_addr);
             frame.set_dst_addr(dst_hardware_addr);
 
-            match repr.version() {
+            let ethertype = match repr.version() {
                 #[cfg(feature = "proto-ipv4")]
-                IpVersion::Ipv4 => frame.set_ethertype(EthernetProtocol::Ipv4),
+                IpVersion::Ipv4 => EthernetProtocol::Ipv4,
                 #[cfg(feature = "proto-ipv6")]
-                IpVersion::Ipv6 => frame.set_ethertype(EthernetProtocol::Ipv6),
-            }
+                IpVersion::Ipv6 => EthernetProtocol::Ipv6,
+            };
+            frame.set_encapsulated_ethertype(self.ethernet_encapsulation, ethertype);
 
             Ok(())
         };
@@ -1928,17 +3117,26 @@ impl InterfaceInner {
             repr.emit(&mut tx_buffer, &self.caps.checksum);
 
             let payload = &mut tx_buffer[repr.header_len()..];
-            packet.emit_payload(repr, payload, &caps);
+            packet.emit_payload_with_ext(repr, payload, ext_headers, &caps);
         };
 
         let total_ip_len = ip_repr.buffer_len();
+        #[cfg(feature = "socket-tcp")]
+        let segmented = matches!(packet, IpPacket::Tcp(_))
+            && self
+                .caps
+                .max_segmentation_size
+                .map_or(false, |size| total_ip_len <= size);
+        #[cfg(not(feature = "socket-tcp"))]
+        let segmented = false;
 
         match ip_repr {
             #[cfg(feature = "proto-ipv4")]
             #[allow(unused_variables)]
             IpRepr::Ipv4(ref mut repr) => {
                 // If we have an IPv4 packet, then we need to check if we need to fragment it.
-                if total_ip_len > self.caps.max_transmission_unit {
+                // TCP segments are split by the device instead, if it can.
+                if total_ip_len > self.ip_mtu_of(IpVersion::Ipv4) && !segmented {
                     #[cfg(feature = "proto-ipv4-fragmentation")]
                     {
                         net_debug!("start fragmentation");
@@ -1954,11 +3152,16 @@ impl InterfaceInner {
                                 dst_hardware_addr: dst_address,
                         } = &mut _out_packet.unwrap().ipv4_out_packet;
 
-                        // Calculate how much we will send now (including the Ethernet header).
-                        let tx_len = self.caps.max_transmission_unit;
-
                         let ip_header_len = repr.buffer_len();
-                        let first_frag_ip_len = self.caps.ip_mtu();
+                        let first_frag_ip_len = self.ipv4_fragment_len(ip_header_len);
+
+                        // Calculate how much we will send now (including the Ethernet header).
+                        #[allow(unused_mut)]
+                        let mut tx_len = first_frag_ip_len;
+                        #[cfg(feature = "medium-ethernet")]
+                        if matches!(self.caps.medium, Medium::Ethernet) {
+                            tx_len += self.ethernet_header_len();
+                        }
 
                         if buffer.len() < first_frag_ip_len {
                             net_debug!(
@@ -2004,7 +3207,7 @@ impl InterfaceInner {
                             #[cfg(feature = "medium-ethernet")]
                             if matches!(self.caps.medium, Medium::Ethernet) {
                                 emit_ethernet(&ip_repr, tx_buffer)?;
-                                tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
+                                tx_buffer = &mut tx_buffer[self.ethernet_header_len()..];
                             }
 
                             // Change the offset for the next packet.
@@ -2029,7 +3232,7 @@ impl InterfaceInner {
                         #[cfg(feature = "medium-ethernet")]
                         if matches!(self.caps.medium, Medium::Ethernet) {
                             emit_ethernet(&ip_repr, tx_buffer)?;
-                            tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
+                            tx_buffer = &mut tx_buffer[self.ethernet_header_len()..];
                         }
 
                         emit_ip(&ip_repr, tx_buffer);
@@ -2043,7 +3246,7 @@ impl InterfaceInner {
                 #[cfg(feature = "medium-ethernet")]
                 if matches!(self.caps.medium, Medium::Ethernet) {
                     emit_ethernet(&ip_repr, tx_buffer)?;
-                    tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
+                    tx_buffer = &mut tx_buffer[self.ethernet_header_len()..];
                 }
 
                 emit_ip(&ip_repr, tx_buffer);
@@ -0,0 +1,587 @@
+// Joining an IEEE 802.15.4 PAN, and coordinating one, see IEEE 802.15.4 § 5.1.3.
+//
+// A device finds the coordinators around it by broadcasting beacon requests, and
+// associates with the first one that permits it. The coordinator doesn't answer the
+// association request at once: the device polls it for the response with a data
+// request. A coordinator answers beacon requests with a beacon, and allocates a short
+// address to each device that associates with it.
+
+use heapless::LinearMap;
+
+use super::{check, Interface, InterfaceInner};
+use crate::phy::{Device, TxToken};
+use crate::time::{Duration, Instant};
+use crate::wire::*;
+
+/// Interval between beacon requests while looking for a coordinator.
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time to wait before polling a coordinator for the association response,
+/// `macResponseWaitTime` of the 2.4 GHz O-QPSK PHY.
+const RESPONSE_WAIT_TIME: Duration = Duration::from_micros(491_520);
+
+/// Number of polls for an association response before looking for a coordinator again.
+const MAX_ASSOCIATION_POLLS: u8 = 3;
+
+/// Largest number of devices associated with a coordinator.
+const MAX_PAN_DEVICE_COUNT: usize = 16;
+
+/// Number of association responses a coordinator holds until the devices poll them.
+const MAX_PENDING_RESPONSE_COUNT: usize = 4;
+
+/// The short address of a device that must use its extended address.
+const NO_SHORT_ADDR: Ieee802154Address = Ieee802154Address::Short([0xff, 0xfe]);
+
+/// The association of an interface with the coordinator of a PAN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PanAssociation {
+    /// The identifier of the PAN.
+    pub pan_id: Ieee802154Pan,
+    /// The address of the coordinator.
+    pub coordinator: Ieee802154Address,
+    /// The short address allocated by the coordinator, if any.
+    pub short_addr: Option<Ieee802154Address>,
+}
+
+/// State of the PAN association and coordination of an interface.
+#[derive(Debug, Default)]
+pub(crate) struct PanState {
+    join: Option<Join>,
+    association: Option<PanAssociation>,
+    coordinator: Option<Coordinator>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Join {
+    /// The PAN to join, or `None` to join any PAN.
+    pan_id: Option<Ieee802154Pan>,
+    state: JoinState,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum JoinState {
+    /// A beacon request is broadcast at `next_at`.
+    Scanning { next_at: Instant },
+    /// An association request is sent to the coordinator at `next_at`.
+    Requesting {
+        pan_id: Ieee802154Pan,
+        coordinator: Ieee802154Address,
+        next_at: Instant,
+    },
+    /// The coordinator is polled for the association response at `next_at`.
+    Polling {
+        pan_id: Ieee802154Pan,
+        coordinator: Ieee802154Address,
+        next_at: Instant,
+        polls: u8,
+    },
+}
+
+impl JoinState {
+    fn next_at(&self) -> Instant {
+        match *self {
+            JoinState::Scanning { next_at }
+            | JoinState::Requesting { next_at, .. }
+            | JoinState::Polling { next_at, .. } => next_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingResponse {
+    short_addr: Ieee802154Address,
+    status: Ieee802154AssociationStatus,
+    /// Whether the device polled for the response.
+    polled: bool,
+}
+
+#[derive(Debug, Default)]
+struct Coordinator {
+    /// Whether a beacon request has to be answered.
+    beacon_due: bool,
+    /// The short address allocated to each associated device, by extended address.
+    devices: LinearMap<Ieee802154Address, Ieee802154Address, MAX_PAN_DEVICE_COUNT>,
+    /// The association responses the devices haven't polled yet, by extended address.
+    responses: LinearMap<Ieee802154Address, PendingResponse, MAX_PENDING_RESPONSE_COUNT>,
+    /// The last short address allocated.
+    last_short_addr: u16,
+}
+
+impl Coordinator {
+    /// Return whether something has to be sent.
+    fn is_due(&self) -> bool {
+        self.beacon_due || self.responses.values().any(|response| response.polled)
+    }
+
+    /// Allocate a short address that no device uses.
+    fn allocate_short_addr(&mut self) -> Ieee802154Address {
+        loop {
+            // Skip the broadcast address and the address meaning there is none.
+            self.last_short_addr = match self.last_short_addr {
+                0xfffd.. => 0x0000,
+                addr => addr + 1,
+            };
+            let addr = Ieee802154Address::Short(self.last_short_addr.to_be_bytes());
+            if !self.devices.values().any(|short_addr| *short_addr == addr) {
+                return addr;
+            }
+        }
+    }
+
+    /// Handle an association request, and prepare its response.
+    fn associate(
+        &mut self,
+        device: Ieee802154Address,
+        capability: Ieee802154CapabilityInformation,
+    ) {
+        let (short_addr, status) = match self.devices.get(&device) {
+            Some(short_addr) => (*short_addr, Ieee802154AssociationStatus::Success),
+            None if self.devices.len() == self.devices.capacity() => (
+                Ieee802154Address::BROADCAST,
+                Ieee802154AssociationStatus::PanAtCapacity,
+            ),
+            None => {
+                let short_addr = if capability.allocate_address {
+                    self.allocate_short_addr()
+                } else {
+                    NO_SHORT_ADDR
+                };
+                // NOTE(unwrap): the map isn't full.
+                self.devices.insert(device, short_addr).unwrap();
+                (short_addr, Ieee802154AssociationStatus::Success)
+            }
+        };
+        net_debug!("iface: association of {}: {}", device, status);
+
+        let response = PendingResponse {
+            short_addr,
+            status,
+            polled: false,
+        };
+        if self.responses.insert(device, response).is_err() {
+            net_debug!("iface: too many pending association responses");
+        }
+    }
+}
+
+impl Interface {
+    /// Return the identifier of the PAN of the interface.
+    pub fn pan_id(&self) -> Option<Ieee802154Pan> {
+        self.inner.pan_id
+    }
+
+    /// Join a PAN, or any PAN if `pan_id` is `None`.
+    ///
+    /// The interface broadcasts beacon requests until a coordinator answers that it
+    /// permits association, and then associates with it, as described in
+    /// [IEEE 802.15.4] § 5.1.3.1. Once the coordinator accepts, the interface uses the
+    /// identifier of its PAN, and [pan_association](#method.pan_association) returns
+    /// the short address it allocated. If the coordinator refuses or doesn't answer,
+    /// the interface looks for a coordinator again.
+    ///
+    /// The association request is sent from the extended address of the interface, so
+    /// the hardware address must be an extended IEEE 802.15.4 address.
+    ///
+    /// [IEEE 802.15.4]: https://standards.ieee.org/ieee/802.15.4/7029/
+    pub fn join_pan(&mut self, pan_id: Option<Ieee802154Pan>) {
+        self.inner.pan.association = None;
+        self.inner.pan.join = Some(Join {
+            pan_id,
+            state: JoinState::Scanning {
+                next_at: self.inner.now,
+            },
+        });
+    }
+
+    /// Return whether the interface is looking for a PAN, or associating with it.
+    pub fn is_joining_pan(&self) -> bool {
+        self.inner.pan.join.is_some()
+    }
+
+    /// Return the association of the interface with the coordinator of its PAN.
+    pub fn pan_association(&self) -> Option<PanAssociation> {
+        self.inner.pan.association
+    }
+
+    /// Act as the coordinator of the PAN of the interface, or stop.
+    ///
+    /// The coordinator answers beacon requests, and accepts association requests,
+    /// allocating a short address to each device that asks for one. The PAN identifier
+    /// must be set in the interface [configuration](struct.Config.html#structfield.pan_id).
+    pub fn set_pan_coordinator(&mut self, enabled: bool) {
+        if enabled != self.inner.pan.coordinator.is_some() {
+            self.inner.pan.coordinator = enabled.then(Coordinator::default);
+        }
+    }
+
+    /// Return whether the interface is the coordinator of its PAN.
+    pub fn is_pan_coordinator(&self) -> bool {
+        self.inner.pan.coordinator.is_some()
+    }
+
+    /// Return the extended address of the devices associated with the coordinator, and
+    /// their short address.
+    pub fn pan_devices(&self) -> impl Iterator<Item = (Ieee802154Address, Ieee802154Address)> + '_ {
+        self.inner
+            .pan
+            .coordinator
+            .iter()
+            .flat_map(|coordinator| coordinator.devices.iter())
+            .map(|(device, short_addr)| (*device, *short_addr))
+    }
+
+    /// Send the beacons, association responses and requests that are due.
+    pub(crate) fn pan_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let hardware_addr = match self.inner.hardware_addr {
+            Some(HardwareAddress::Ieee802154(addr)) => addr,
+            _ => return false,
+        };
+        let now = self.inner.now;
+        let mut did_something = false;
+
+        if let (Some(pan_id), Some(coordinator)) =
+            (self.inner.pan_id, self.inner.pan.coordinator.as_mut())
+        {
+            if coordinator.beacon_due {
+                let tx_token = match device.transmit(now) {
+                    Some(tx_token) => tx_token,
+                    None => return did_something,
+                };
+                coordinator.beacon_due = false;
+                let beacon = Ieee802154BeaconRepr {
+                    superframe_spec: Ieee802154SuperframeSpecification::nonbeacon(
+                        true,
+                        coordinator.devices.len() < coordinator.devices.capacity(),
+                    ),
+                    payload: &[],
+                };
+                net_trace!("iface: sending a beacon");
+                self.inner.dispatch_ieee802154_mac(
+                    tx_token,
+                    Ieee802154FrameType::Beacon,
+                    None,
+                    Some((pan_id, hardware_addr)),
+                    beacon.buffer_len(),
+                    |buffer| beacon.emit(buffer),
+                );
+                did_something = true;
+            }
+
+            loop {
+                // NOTE(unwrap): the coordinator was just borrowed.
+                let coordinator = self.inner.pan.coordinator.as_mut().unwrap();
+                let (device_addr, response) = match coordinator
+                    .responses
+                    .iter()
+                    .find(|(_, response)| response.polled)
+                {
+                    Some((device_addr, response)) => (*device_addr, *response),
+                    None => break,
+                };
+                let tx_token = match device.transmit(now) {
+                    Some(tx_token) => tx_token,
+                    None => return did_something,
+                };
+                coordinator.responses.remove(&device_addr);
+                let command = Ieee802154CommandRepr::AssociationResponse {
+                    short_addr: response.short_addr,
+                    status: response.status,
+                };
+                self.inner.dispatch_ieee802154_command(
+                    tx_token,
+                    Some((pan_id, device_addr)),
+                    Some((pan_id, hardware_addr)),
+                    command,
+                );
+                did_something = true;
+            }
+        }
+
+        let join = match self.inner.pan.join {
+            Some(join) if join.state.next_at() <= now => join,
+            _ => return did_something,
+        };
+        if !matches!(hardware_addr, Ieee802154Address::Extended(_)) {
+            return did_something;
+        }
+        if let JoinState::Polling {
+            coordinator, polls, ..
+        } = join.state
+        {
+            if polls >= MAX_ASSOCIATION_POLLS {
+                net_debug!("iface: no association response from {}", coordinator);
+                self.inner.pan.join = Some(Join {
+                    state: JoinState::Scanning { next_at: now },
+                    ..join
+                });
+                return true;
+            }
+        }
+
+        let tx_token = match device.transmit(now) {
+            Some(tx_token) => tx_token,
+            None => return did_something,
+        };
+        let state = match join.state {
+            JoinState::Scanning { .. } => {
+                net_trace!("iface: looking for a PAN coordinator");
+                self.inner.dispatch_ieee802154_command(
+                    tx_token,
+                    Some((Ieee802154Pan::BROADCAST, Ieee802154Address::BROADCAST)),
+                    None,
+                    Ieee802154CommandRepr::BeaconRequest,
+                );
+                JoinState::Scanning {
+                    next_at: now + SCAN_INTERVAL,
+                }
+            }
+            JoinState::Requesting {
+                pan_id,
+                coordinator,
+                ..
+            } => {
+                net_debug!("iface: associating with {}", coordinator);
+                let capability = Ieee802154CapabilityInformation {
+                    receiver_on_when_idle: true,
+                    allocate_address: true,
+                    ..Ieee802154CapabilityInformation::default()
+                };
+                // The device isn't in the PAN yet.
+                self.inner.dispatch_ieee802154_command(
+                    tx_token,
+                    Some((pan_id, coordinator)),
+                    Some((Ieee802154Pan::BROADCAST, hardware_addr)),
+                    Ieee802154CommandRepr::AssociationRequest { capability },
+                );
+                JoinState::Polling {
+                    pan_id,
+                    coordinator,
+                    next_at: now + RESPONSE_WAIT_TIME,
+                    polls: 0,
+                }
+            }
+            JoinState::Polling {
+                pan_id,
+                coordinator,
+                polls,
+                ..
+            } => {
+                self.inner.dispatch_ieee802154_command(
+                    tx_token,
+                    Some((pan_id, coordinator)),
+                    Some((pan_id, hardware_addr)),
+                    Ieee802154CommandRepr::DataRequest,
+                );
+                JoinState::Polling {
+                    pan_id,
+                    coordinator,
+                    next_at: now + RESPONSE_WAIT_TIME,
+                    polls: polls + 1,
+                }
+            }
+        };
+        self.inner.pan.join = Some(Join { state, ..join });
+        true
+    }
+}
+
+impl InterfaceInner {
+    /// Return the time at which the next beacon, association response or request is due.
+    pub(crate) fn pan_poll_at(&self) -> Option<Instant> {
+        let coordinator = match &self.pan.coordinator {
+            Some(coordinator) if self.pan_id.is_some() && coordinator.is_due() => Some(self.now),
+            _ => None,
+        };
+        let join = self.pan.join.map(|join| join.state.next_at());
+        coordinator.into_iter().chain(join).min()
+    }
+
+    /// Handle a beacon or a MAC command frame.
+    pub(super) fn process_ieee802154_mac(&mut self, repr: &Ieee802154Repr, payload: &[u8]) {
+        let hardware_addr = match self.hardware_addr {
+            Some(HardwareAddress::Ieee802154(addr)) => addr,
+            _ => return,
+        };
+        if repr.frame_type == Ieee802154FrameType::Beacon {
+            let beacon = check!(
+                Ieee802154BeaconRepr::parse(payload),
+                self.stats,
+                ieee802154_errors
+            );
+            return self.process_beacon(repr, &beacon);
+        }
+
+        let command = check!(
+            Ieee802154CommandRepr::parse(payload),
+            self.stats,
+            ieee802154_errors
+        );
+        let to_us = repr.dst_addr == Some(hardware_addr);
+        let src_addr = match repr.src_addr {
+            Some(Ieee802154Address::Absent) | None => None,
+            src_addr => src_addr,
+        };
+        match (command, self.pan.coordinator.as_mut()) {
+            (Ieee802154CommandRepr::BeaconRequest, Some(coordinator)) if self.pan_id.is_some() => {
+                coordinator.beacon_due = true;
+            }
+            (Ieee802154CommandRepr::AssociationRequest { capability }, Some(coordinator))
+                if to_us && repr.dst_pan_id.is_some() && repr.dst_pan_id == self.pan_id =>
+            {
+                match src_addr {
+                    Some(device @ Ieee802154Address::Extended(_)) => {
+                        coordinator.associate(device, capability)
+                    }
+                    _ => net_debug!("iface: association request without an extended address"),
+                }
+            }
+            (Ieee802154CommandRepr::DataRequest, Some(coordinator)) if to_us => {
+                if let Some(response) =
+                    src_addr.and_then(|addr| coordinator.responses.get_mut(&addr))
+                {
+                    response.polled = true;
+                }
+            }
+            (Ieee802154CommandRepr::DisassociationNotification { .. }, Some(coordinator))
+                if to_us =>
+            {
+                if let Some(device) = src_addr {
+                    if coordinator.devices.remove(&device).is_some() {
+                        net_debug!("iface: {} left the PAN", device);
+                    }
+                }
+            }
+            (Ieee802154CommandRepr::AssociationResponse { short_addr, status }, _) if to_us => {
+                self.process_association_response(short_addr, status)
+            }
+            (Ieee802154CommandRepr::DisassociationNotification { .. }, _) if to_us => {
+                if let Some(association) = self.pan.association {
+                    if src_addr == Some(association.coordinator) {
+                        net_debug!("iface: disassociated from {}", association.coordinator);
+                        self.pan.association = None;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Associate with the coordinator that sent a beacon, if we are looking for one.
+    fn process_beacon(&mut self, repr: &Ieee802154Repr, beacon: &Ieee802154BeaconRepr) {
+        let join = match self.pan.join.as_mut() {
+            Some(join) if matches!(join.state, JoinState::Scanning { .. }) => join,
+            _ => return,
+        };
+        let (pan_id, coordinator) = match (repr.src_pan_id, repr.src_addr) {
+            (Some(pan_id), Some(coordinator)) if coordinator != Ieee802154Address::Absent => {
+                (pan_id, coordinator)
+            }
+            _ => return,
+        };
+        if !beacon.superframe_spec.association_permit
+            || join
+                .pan_id
+                .map_or(false, |join_pan_id| join_pan_id != pan_id)
+        {
+            net_trace!("iface: ignoring the beacon of {}", coordinator);
+            return;
+        }
+        join.state = JoinState::Requesting {
+            pan_id,
+            coordinator,
+            next_at: self.now,
+        };
+    }
+
+    /// Handle the answer of the coordinator to our association request.
+    fn process_association_response(
+        &mut self,
+        short_addr: Ieee802154Address,
+        status: Ieee802154AssociationStatus,
+    ) {
+        let (pan_id, coordinator) = match self.pan.join.map(|join| join.state) {
+            Some(JoinState::Polling {
+                pan_id,
+                coordinator,
+                ..
+            }) => (pan_id, coordinator),
+            _ => return,
+        };
+        if status != Ieee802154AssociationStatus::Success {
+            net_debug!("iface: association refused by {}: {}", coordinator, status);
+            // NOTE(unwrap): we are polling for the response.
+            self.pan.join.as_mut().unwrap().state = JoinState::Scanning {
+                next_at: self.now + SCAN_INTERVAL,
+            };
+            return;
+        }
+
+        net_debug!("iface: associated with {} as {}", coordinator, short_addr);
+        self.pan.join = None;
+        self.pan.association = Some(PanAssociation {
+            pan_id,
+            coordinator,
+            short_addr: (short_addr != NO_SHORT_ADDR && short_addr.is_unicast())
+                .then_some(short_addr),
+        });
+        self.pan_id = Some(pan_id);
+    }
+
+    /// Send a MAC command.
+    fn dispatch_ieee802154_command<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        dst: Option<(Ieee802154Pan, Ieee802154Address)>,
+        src: Option<(Ieee802154Pan, Ieee802154Address)>,
+        command: Ieee802154CommandRepr,
+    ) {
+        self.dispatch_ieee802154_mac(
+            tx_token,
+            Ieee802154FrameType::MacCommand,
+            dst,
+            src,
+            command.buffer_len(),
+            |buffer| command.emit(buffer),
+        )
+    }
+
+    /// Send a beacon or a MAC command frame, from and to the given PAN and address.
+    fn dispatch_ieee802154_mac<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        frame_type: Ieee802154FrameType,
+        dst: Option<(Ieee802154Pan, Ieee802154Address)>,
+        src: Option<(Ieee802154Pan, Ieee802154Address)>,
+        payload_len: usize,
+        emit_payload: impl FnOnce(&mut [u8]),
+    ) {
+        // The source PAN is omitted when it is the destination PAN.
+        let pan_id_compression = match (dst, src) {
+            (Some((dst_pan_id, _)), Some((src_pan_id, _))) => dst_pan_id == src_pan_id,
+            _ => false,
+        };
+        let ieee_repr = Ieee802154Repr {
+            frame_type,
+            security_enabled: false,
+            frame_pending: false,
+            ack_request: dst.map_or(false, |(_, dst_addr)| dst_addr.is_unicast()),
+            sequence_number: Some(self.get_sequence_number()),
+            pan_id_compression,
+            frame_version: Ieee802154FrameVersion::Ieee802154_2003,
+            dst_pan_id: dst.map(|(pan_id, _)| pan_id),
+            dst_addr: dst.map(|(_, addr)| addr),
+            src_pan_id: src.map(|(pan_id, _)| pan_id),
+            src_addr: src.map(|(_, addr)| addr),
+        };
+        let ieee_len = ieee_repr.buffer_len();
+        tx_token.consume(ieee_len + payload_len, |tx_buf| {
+            let mut ieee_packet = Ieee802154Frame::new_unchecked(&mut tx_buf[..ieee_len]);
+            ieee_repr.emit(&mut ieee_packet);
+            emit_payload(&mut tx_buf[ieee_len..]);
+        });
+    }
+}
@@ -0,0 +1,130 @@
+// IEEE 802.3x flow control, for devices without a MAC doing it in hardware.
+//
+// A PAUSE frame asks its receiver to stop sending for a number of quanta of 512 bit
+// times, and one with zero quanta lets it resume. While paused, the interface keeps
+// receiving, but neither polls sockets for packets to send nor sends packets of its
+// own. Frames answering received ones, such as ARP replies, are still sent.
+
+use super::check;
+use super::{Interface, InterfaceInner};
+use crate::phy::{Device, TxToken};
+use crate::time::Instant;
+use crate::wire::*;
+
+/// State of the flow control of an interface.
+#[derive(Debug, Default)]
+pub(crate) struct Pause {
+    /// The speed of the link in bits per second, if PAUSE frames are honored.
+    link_speed: Option<u64>,
+    /// When the pause requested by the link partner ends.
+    until: Option<Instant>,
+}
+
+impl Interface {
+    /// Return the link speed PAUSE frames are honored with, if they are.
+    pub fn pause_link_speed(&self) -> Option<u64> {
+        self.inner.pause.link_speed
+    }
+
+    /// Honor the PAUSE frames sent by the link partner, given the speed of the link in
+    /// bits per second to know how long they last, or ignore them with `None`.
+    ///
+    /// PAUSE frames are ignored by default, as most devices handle them in hardware.
+    ///
+    /// # Panics
+    /// This function panics if the speed is zero.
+    pub fn set_pause_link_speed(&mut self, link_speed: Option<u64>) {
+        assert!(link_speed != Some(0), "link speed must not be zero");
+        self.inner.pause.link_speed = link_speed;
+        if link_speed.is_none() {
+            self.inner.pause.until = None;
+        }
+    }
+
+    /// Return when the pause requested by the link partner ends, if the interface is
+    /// paused.
+    pub fn paused_until(&self) -> Option<Instant> {
+        self.inner
+            .pause
+            .until
+            .filter(|until| *until > self.inner.now)
+    }
+
+    /// Ask the link partner to stop sending for `quanta` of 512 bit times, e.g. when
+    /// the receive buffers of the device are nearly full, or to resume with zero.
+    ///
+    /// See [`PauseRepr::quanta_for`] to turn a duration into quanta. Returns whether
+    /// the device had room for the frame.
+    pub fn send_pause<D>(&mut self, device: &mut D, quanta: u16) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let src_addr = match self.inner.hardware_addr {
+            Some(HardwareAddress::Ethernet(addr)) => addr,
+            #[allow(unreachable_patterns)]
+            _ => return false,
+        };
+        let tx_token = match device.transmit(self.inner.now) {
+            Some(tx_token) => tx_token,
+            None => return false,
+        };
+
+        net_debug!("pause: asking for {} quanta", quanta);
+        let repr = PauseRepr { quanta };
+        tx_token.consume(
+            EthernetFrame::<&[u8]>::buffer_len(repr.buffer_len()),
+            |buffer| {
+                // MAC Control frames are never encapsulated in LLC/SNAP.
+                let mut frame = EthernetFrame::new_unchecked(buffer);
+                frame.set_dst_addr(PAUSE_MULTICAST_ADDR);
+                frame.set_src_addr(src_addr);
+                frame.set_ethertype(EthernetProtocol::MacControl);
+                repr.emit(&mut PausePacket::new_unchecked(frame.payload_mut()));
+            },
+        );
+        true
+    }
+}
+
+impl InterfaceInner {
+    /// Pause or resume sending as a PAUSE frame received asks, if they are honored.
+    pub(super) fn process_pause<T: AsRef<[u8]> + ?Sized>(&mut self, eth_frame: &EthernetFrame<&T>) {
+        let link_speed = match self.pause.link_speed {
+            Some(link_speed) => link_speed,
+            None => return,
+        };
+        let packet = check!(
+            PausePacket::new_checked(eth_frame.payload()),
+            self.stats,
+            ethernet_errors
+        );
+        // Other MAC Control frames, e.g. for priority-based flow control, are ignored.
+        let repr = match PauseRepr::parse(&packet) {
+            Ok(repr) => repr,
+            Err(_) => return,
+        };
+
+        net_trace!("pause: link partner asks for {} quanta", repr.quanta);
+        self.pause.until = match repr.quanta {
+            0 => None,
+            _ => Some(self.now + repr.duration(link_speed)),
+        };
+    }
+
+    /// Return whether sending is paused at the request of the link partner.
+    pub(super) fn egress_paused(&mut self) -> bool {
+        match self.pause.until {
+            Some(until) if until > self.now => true,
+            Some(_) => {
+                self.pause.until = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Return when the pause requested by the link partner ends, if sending is paused.
+    pub(super) fn pause_poll_at(&self) -> Option<Instant> {
+        self.pause.until.filter(|until| *until > self.now)
+    }
+}
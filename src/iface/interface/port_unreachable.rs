@@ -0,0 +1,60 @@
+// ICMP "port unreachable" errors for UDP datagrams no socket accepted.
+//
+// These errors let peers fail fast instead of waiting for an answer, but are
+// rate-limited so that a flood of datagrams to closed ports does not turn into a flood
+// of errors.
+
+use super::rate_limit::RateLimit;
+use super::{Interface, InterfaceInner};
+use crate::time::Duration;
+
+pub(super) struct PortUnreachable {
+    enabled: bool,
+    limit: RateLimit,
+}
+
+impl PortUnreachable {
+    pub(super) const fn new() -> Self {
+        Self {
+            enabled: true,
+            limit: RateLimit::new(10, Duration::from_millis(100)),
+        }
+    }
+}
+
+impl Interface {
+    /// Return whether ICMP "port unreachable" errors are sent for UDP datagrams.
+    pub fn udp_port_unreachable(&self) -> bool {
+        self.inner.port_unreachable.enabled
+    }
+
+    /// Enable or disable sending an ICMP or ICMPv6 "port unreachable" error when a
+    /// UDP datagram arrives on a port no socket is bound to.
+    ///
+    /// The error makes the `connect` or `send` calls of the peer fail fast, instead of
+    /// waiting for an answer. Errors are never sent for datagrams also received by a
+    /// raw socket, or sent to a broadcast or multicast address.
+    ///
+    /// This is enabled by default, and rate-limited, see
+    /// [`set_udp_port_unreachable_rate_limit`](Self::set_udp_port_unreachable_rate_limit).
+    pub fn set_udp_port_unreachable(&mut self, enabled: bool) {
+        self.inner.port_unreachable.enabled = enabled;
+    }
+
+    /// Limit the rate of the ICMP "port unreachable" errors sent for UDP datagrams.
+    ///
+    /// Up to `burst` errors can be sent at once, then one more each `interval`. With a
+    /// zero `interval`, the rate is not limited. By default, 10 errors can be sent at
+    /// once, then one each 100 ms.
+    pub fn set_udp_port_unreachable_rate_limit(&mut self, burst: u16, interval: Duration) {
+        let now = self.inner.now;
+        self.inner.port_unreachable.limit.set(burst, interval, now);
+    }
+}
+
+impl InterfaceInner {
+    /// Return whether a "port unreachable" error can be sent now, and account for it.
+    pub(super) fn take_port_unreachable(&mut self) -> bool {
+        self.port_unreachable.enabled && self.port_unreachable.limit.take(self.now)
+    }
+}
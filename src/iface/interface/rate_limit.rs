@@ -0,0 +1,55 @@
+// A token bucket limiting the rate of the error replies sent by an interface.
+//
+// A burst of replies can be sent at once, then one more per interval, so that a flood
+// of unwanted packets does not turn into a flood of replies.
+
+use crate::time::{Duration, Instant};
+
+pub(super) struct RateLimit {
+    burst: u16,
+    interval: Duration,
+    tokens: u16,
+    /// When the last token was added to the bucket.
+    refilled_at: Instant,
+}
+
+impl RateLimit {
+    pub(super) const fn new(burst: u16, interval: Duration) -> Self {
+        Self {
+            burst,
+            interval,
+            tokens: burst,
+            refilled_at: Instant::ZERO,
+        }
+    }
+
+    /// Change the limit, and fill the bucket.
+    pub(super) fn set(&mut self, burst: u16, interval: Duration, now: Instant) {
+        *self = Self::new(burst, interval);
+        self.refilled_at = now;
+    }
+
+    /// Return whether a reply can be sent at `now`, and account for it.
+    ///
+    /// With a zero interval, the rate is not limited.
+    pub(super) fn take(&mut self, now: Instant) -> bool {
+        if self.interval == Duration::ZERO {
+            return true;
+        }
+
+        if self.tokens >= self.burst || now < self.refilled_at {
+            self.refilled_at = now;
+        } else {
+            let count = (now - self.refilled_at).total_micros() / self.interval.total_micros();
+            let count = count.min(u64::from(self.burst - self.tokens)) as u16;
+            self.tokens += count;
+            self.refilled_at += self.interval * u32::from(count);
+        }
+
+        if self.tokens == 0 {
+            return false;
+        }
+        self.tokens -= 1;
+        true
+    }
+}
@@ -0,0 +1,305 @@
+// Address registration with a 6LoWPAN router, see RFC 6775 § 5.5.
+//
+// Instead of multicasting Neighbor Solicitations for Duplicate Address Detection and
+// address resolution, which is impractical on IEEE 802.15.4, a host registers each of
+// its addresses with a router. The router detects duplicates and keeps the host in its
+// neighbor cache until the registration expires.
+
+use heapless::LinearMap;
+
+use super::{AddressEvent, Interface, InterfaceInner, IpPacket, MAX_IP_ADDR_COUNT};
+use crate::phy::Device;
+use crate::time::{Duration, Instant};
+use crate::wire::*;
+
+/// Initial interval between registration attempts, `RETRANS_TIMER` in
+/// [RFC 4861 § 10].
+///
+/// [RFC 4861 § 10]: https://www.rfc-editor.org/rfc/rfc4861#section-10
+const REGISTRATION_RETRANS_TIMER: Duration = Duration::from_secs(1);
+
+/// Largest interval between registration attempts, `MAX_RTR_SOLICITATION_INTERVAL` in
+/// [RFC 6775 § 9].
+///
+/// [RFC 6775 § 9]: https://www.rfc-editor.org/rfc/rfc6775#section-9
+const REGISTRATION_MAX_RETRANS_TIMER: Duration = Duration::from_secs(60);
+
+/// State of the address registration of an interface.
+#[derive(Debug, Clone)]
+pub(crate) struct Registration {
+    /// Lifetime requested for each registration.
+    lifetime: Duration,
+    /// The router the addresses are registered with, and when it stops being a router.
+    router: Option<(Ipv6Address, Instant)>,
+    /// Addresses for which a registration was sent.
+    entries: LinearMap<Ipv6Address, RegistrationInfo, MAX_IP_ADDR_COUNT>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RegistrationInfo {
+    /// When the registration granted by the router expires, if it was granted.
+    expires_at: Option<Instant>,
+    /// When to send the next registration, to retransmit or to refresh it.
+    next_at: Instant,
+    /// Interval before the next retransmission, doubled with each attempt.
+    retrans_timer: Duration,
+}
+
+impl Registration {
+    pub(crate) fn new(lifetime: Duration) -> Self {
+        Registration {
+            lifetime,
+            router: None,
+            entries: LinearMap::new(),
+        }
+    }
+}
+
+impl Interface {
+    /// Return the lifetime requested when registering addresses, if address
+    /// registration is enabled.
+    pub fn address_registration(&self) -> Option<Duration> {
+        self.inner
+            .registration
+            .as_ref()
+            .map(|registration| registration.lifetime)
+    }
+
+    /// Enable registering the IPv6 addresses of the interface with a router, with the
+    /// given lifetime, or disable it.
+    ///
+    /// Once a router is learned from a router advertisement, each address is
+    /// registered with a Neighbor Solicitation carrying an Address Registration option,
+    /// as described in [RFC 6775 § 5.5]. Registrations are refreshed before their
+    /// lifetime expires, and retried with an exponential backoff until the router
+    /// answers. Addresses added while registration is enabled skip multicast Duplicate
+    /// Address Detection, and are removed with an [`AddressEvent::Duplicate`] event if
+    /// the router reports another node registered them.
+    ///
+    /// The option identifies the interface by its EUI-64, so registrations are only
+    /// sent when the hardware address is an extended IEEE 802.15.4 address. Disabling
+    /// registration does not withdraw the existing registrations, which expire at the
+    /// router.
+    ///
+    /// [RFC 6775 § 5.5]: https://www.rfc-editor.org/rfc/rfc6775#section-5.5
+    pub fn set_address_registration(&mut self, lifetime: Option<Duration>) {
+        self.inner.registration = lifetime.map(Registration::new);
+    }
+
+    /// Return the router the addresses of the interface are registered with.
+    pub fn registration_router(&self) -> Option<Ipv6Address> {
+        self.inner.registration_router()
+    }
+
+    /// Return whether an address is currently registered with the router.
+    pub fn ip_addr_registered(&self, addr: Ipv6Address) -> bool {
+        let now = self.inner.now;
+        self.inner
+            .registration
+            .as_ref()
+            .and_then(|registration| registration.entries.get(&addr))
+            .and_then(|info| info.expires_at)
+            .map_or(false, |expires_at| expires_at > now)
+    }
+
+    /// Send the registrations that are due, to a new router or to refresh them.
+    pub(crate) fn registration_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let router = match self.inner.registration_router() {
+            Some(router) => router,
+            None => return false,
+        };
+        let eui64 = match self.inner.hardware_addr {
+            Some(HardwareAddress::Ieee802154(Ieee802154Address::Extended(eui64))) => eui64,
+            _ => return false,
+        };
+        let now = self.inner.now;
+        let mut did_something = false;
+
+        for i in 0..self.inner.ip_addrs.len() {
+            let addr = match self.inner.ip_addrs[i].address() {
+                IpAddress::Ipv6(addr) => addr,
+                #[allow(unreachable_patterns)]
+                _ => continue,
+            };
+            if !self
+                .inner
+                .registration_due_at(addr)
+                .map_or(false, |at| at <= now)
+            {
+                continue;
+            }
+
+            let tx_token = match device.transmit(now) {
+                Some(tx_token) => tx_token,
+                None => return did_something,
+            };
+            // NOTE(unwrap): a registration is only due when it is enabled.
+            let registration = self.inner.registration.as_mut().unwrap();
+            let lifetime = registration.lifetime;
+            let info = match registration.entries.get_mut(&addr) {
+                Some(info) => info,
+                None => {
+                    let info = RegistrationInfo {
+                        expires_at: None,
+                        next_at: now,
+                        retrans_timer: REGISTRATION_RETRANS_TIMER,
+                    };
+                    // NOTE(unwrap): the map holds as many entries as there are addresses.
+                    registration.entries.insert(addr, info).unwrap();
+                    registration.entries.get_mut(&addr).unwrap()
+                }
+            };
+            info.next_at = now + info.retrans_timer;
+            info.retrans_timer = (info.retrans_timer * 2).min(REGISTRATION_MAX_RETRANS_TIMER);
+
+            net_debug!("iface: registering address {} with {}", addr, router);
+            let solicit = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
+                target_addr: addr,
+                lladdr: self.inner.hardware_addr.map(|addr| addr.into()),
+                aro: Some(NdiscAddressRegistration {
+                    status: NdiscAddressRegistrationStatus::Success,
+                    lifetime,
+                    eui64,
+                }),
+            });
+            let ipv6_repr = Ipv6Repr {
+                src_addr: addr,
+                dst_addr: router,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: solicit.buffer_len(),
+                hop_limit: 0xff,
+                traffic_class: 0,
+                flow_label: 0,
+            };
+            let packet = IpPacket::Icmpv6((ipv6_repr, solicit));
+            if let Err(e) = self.inner.dispatch_ip(tx_token, packet, None) {
+                net_debug!("iface: failed to send address registration: {:?}", e);
+            }
+            did_something = true;
+        }
+
+        did_something
+    }
+}
+
+impl InterfaceInner {
+    /// Return the router addresses are registered with, if it is still a router.
+    pub(super) fn registration_router(&self) -> Option<Ipv6Address> {
+        match self.registration.as_ref()?.router {
+            Some((router, expires_at)) if expires_at > self.now => Some(router),
+            _ => None,
+        }
+    }
+
+    /// Return when the registration of an address is due, if it has to be registered.
+    fn registration_due_at(&self, addr: Ipv6Address) -> Option<Instant> {
+        let registration = self.registration.as_ref()?;
+        if !addr.is_unicast() || addr.is_loopback() || self.is_tentative(&addr.into()) {
+            return None;
+        }
+        match registration.entries.get(&addr) {
+            Some(info) => Some(info.next_at),
+            None => Some(self.now),
+        }
+    }
+
+    /// Return the time at which the next registration is due.
+    pub(crate) fn registration_poll_at(&self) -> Option<Instant> {
+        self.registration_router()?;
+        self.ip_addrs
+            .iter()
+            .filter_map(|cidr| match cidr.address() {
+                IpAddress::Ipv6(addr) => self.registration_due_at(addr),
+                #[allow(unreachable_patterns)]
+                _ => None,
+            })
+            .min()
+    }
+
+    /// Learn the router to register addresses with from a router advertisement.
+    ///
+    /// Addresses are registered with the first router heard from, until its router
+    /// lifetime expires.
+    pub(super) fn learn_registration_router(
+        &mut self,
+        router: Ipv6Address,
+        router_lifetime: Duration,
+        lladdr: Option<RawHardwareAddress>,
+    ) {
+        let current = self.registration_router();
+        let registration = match self.registration.as_mut() {
+            Some(registration) => registration,
+            None => return,
+        };
+        if current.map_or(false, |current| current != router) {
+            return;
+        }
+        if router_lifetime == Duration::ZERO {
+            if current.is_some() {
+                net_debug!("iface: registration router {} withdrawn", router);
+                registration.router = None;
+                registration.entries.clear();
+            }
+            return;
+        }
+        if current.is_none() {
+            net_debug!("iface: registering addresses with {}", router);
+            registration.entries.clear();
+        }
+        registration.router = Some((router, self.now + router_lifetime));
+
+        // Registrations are sent to the router directly, without resolving its address.
+        if let Some(lladdr) = lladdr.and_then(|lladdr| lladdr.parse(self.caps.medium).ok()) {
+            if lladdr.is_unicast() {
+                self.neighbor_cache
+                    .as_mut()
+                    .unwrap()
+                    .fill(router.into(), lladdr, self.now);
+                self.end_batch();
+            }
+        }
+    }
+
+    /// Return whether a Neighbor Advertisement for one of our addresses answers its
+    /// registration.
+    pub(super) fn is_registration_reply(&self, router: Ipv6Address, addr: Ipv6Address) -> bool {
+        self.registration_router() == Some(router)
+            && self.registration.as_ref().map_or(false, |registration| {
+                registration.entries.contains_key(&addr)
+            })
+    }
+
+    /// Handle the answer of the router to the registration of an address.
+    pub(super) fn process_registration(
+        &mut self,
+        addr: Ipv6Address,
+        aro: NdiscAddressRegistration,
+    ) {
+        let now = self.now;
+        // NOTE(unwrap): only replies to a registration are processed.
+        let registration = self.registration.as_mut().unwrap();
+        let info = registration.entries.get_mut(&addr).unwrap();
+        match aro.status {
+            NdiscAddressRegistrationStatus::Success => {
+                net_trace!("iface: address {} registered for {}", addr, aro.lifetime);
+                info.expires_at = Some(now + aro.lifetime);
+                // Refresh the registration once three quarters of its lifetime passed.
+                info.next_at = now + aro.lifetime * 3 / 4;
+                info.retrans_timer = REGISTRATION_RETRANS_TIMER;
+            }
+            NdiscAddressRegistrationStatus::Duplicate => {
+                registration.entries.remove(&addr);
+                net_debug!("iface: address {} is registered by another node", addr);
+                self.remove_ip_addr(addr.into(), AddressEvent::Duplicate);
+            }
+            NdiscAddressRegistrationStatus::NeighborCacheFull => {
+                net_debug!("iface: router cannot register address {}", addr);
+                info.next_at = now + REGISTRATION_MAX_RETRANS_TIMER;
+            }
+            NdiscAddressRegistrationStatus::Unknown(_) => {}
+        }
+    }
+}
@@ -0,0 +1,244 @@
+// Router Advertisements, sent by an interface acting as the IPv6 router of its link,
+// see RFC 4861 § 6.2.
+//
+// Advertisements are multicast to all nodes every `interval`, the first few more
+// often so that hosts configure themselves quickly, and soon after a host asks for
+// one with a Router Solicitation. They carry a single prefix, for hosts to configure
+// addresses from with SLAAC.
+
+use super::{Interface, InterfaceInner, IpPacket};
+use crate::phy::Device;
+use crate::time::{Duration, Instant};
+use crate::wire::*;
+
+/// Number and longest interval of the first advertisements,
+/// `MAX_INITIAL_RTR_ADVERTISEMENTS` and `MAX_INITIAL_RTR_ADVERT_INTERVAL` in
+/// [RFC 4861 § 10].
+///
+/// [RFC 4861 § 10]: https://www.rfc-editor.org/rfc/rfc4861#section-10
+const MAX_INITIAL_ADVERTS: u8 = 3;
+const MAX_INITIAL_ADVERT_INTERVAL: Duration = Duration::from_secs(16);
+
+/// Shortest interval between two multicast advertisements, `MIN_DELAY_BETWEEN_RAS` in
+/// [RFC 4861 § 10].
+///
+/// [RFC 4861 § 10]: https://www.rfc-editor.org/rfc/rfc4861#section-10
+const MIN_DELAY_BETWEEN_ADVERTS: Duration = Duration::from_secs(3);
+
+/// The configuration of the Router Advertisements sent by an interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RouterAdvertConfig {
+    /// The prefix hosts configure their addresses from. It should be 64 bits long.
+    pub prefix: Ipv6Cidr,
+    /// How long the prefix can be used by hosts.
+    pub valid_lifetime: Duration,
+    /// How long addresses configured from the prefix are preferred.
+    pub preferred_lifetime: Duration,
+    /// How long hosts can use the interface as a default router, or zero if they
+    /// should not.
+    pub router_lifetime: Duration,
+    /// The MTU of the link, if hosts should be told about it.
+    pub mtu: Option<u32>,
+    /// The interval between unsolicited advertisements.
+    pub interval: Duration,
+}
+
+impl RouterAdvertConfig {
+    /// Advertise `prefix` and the interface as the default router, with the default
+    /// lifetimes and interval of [RFC 4861 § 6.2.1].
+    ///
+    /// [RFC 4861 § 6.2.1]: https://www.rfc-editor.org/rfc/rfc4861#section-6.2.1
+    pub const fn new(prefix: Ipv6Cidr) -> Self {
+        Self {
+            prefix,
+            valid_lifetime: Duration::from_secs(30 * 24 * 60 * 60),
+            preferred_lifetime: Duration::from_secs(7 * 24 * 60 * 60),
+            router_lifetime: Duration::from_secs(1800),
+            mtu: None,
+            interval: Duration::from_secs(600),
+        }
+    }
+}
+
+/// State of the Router Advertisements of an interface.
+#[derive(Debug)]
+pub(crate) struct RouterAdvert {
+    config: RouterAdvertConfig,
+    /// Number of advertisements sent since advertising started.
+    sent: u8,
+    /// When the last advertisement was sent.
+    sent_at: Option<Instant>,
+    /// When to send the next advertisement.
+    next_at: Instant,
+    /// Whether advertising stopped, and a last advertisement with a router lifetime of
+    /// zero is due.
+    withdrawn: bool,
+}
+
+impl Interface {
+    /// Return the configuration of the Router Advertisements sent by the interface, if
+    /// any are.
+    pub fn router_advert(&self) -> Option<RouterAdvertConfig> {
+        self.inner
+            .router_advert
+            .as_ref()
+            .filter(|advert| !advert.withdrawn)
+            .map(|advert| advert.config)
+    }
+
+    /// Make the interface act as the IPv6 router of its link, advertising the given
+    /// configuration to hosts, or stop it with `None`.
+    ///
+    /// The advertisements are sent from a link-local address of the interface, which
+    /// must have one. They are multicast every `interval`, the first three at most 16
+    /// seconds apart, and at most 3 seconds after a host sends a Router Solicitation.
+    /// A change of configuration is advertised at once. Stopping sends a last
+    /// advertisement with a router lifetime of zero, so hosts stop using the interface
+    /// as their default router.
+    ///
+    /// Forwarding packets for the hosts is done separately, with a
+    /// [`Forwarder`](crate::iface::Forwarder).
+    pub fn set_router_advert(&mut self, config: Option<RouterAdvertConfig>) {
+        let now = self.inner.now;
+        match (self.inner.router_advert.as_mut(), config) {
+            (Some(advert), Some(config)) if advert.config == config && !advert.withdrawn => (),
+            (Some(advert), Some(config)) => {
+                advert.config = config;
+                advert.next_at = now;
+                advert.withdrawn = false;
+            }
+            (None, Some(config)) => {
+                self.inner.router_advert = Some(RouterAdvert {
+                    config,
+                    sent: 0,
+                    sent_at: None,
+                    next_at: now,
+                    withdrawn: false,
+                });
+            }
+            (Some(advert), None) => {
+                advert.config.router_lifetime = Duration::ZERO;
+                advert.next_at = now;
+                advert.withdrawn = true;
+            }
+            (None, None) => (),
+        }
+    }
+
+    /// Send the pending Router Advertisement.
+    pub(crate) fn router_advert_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let now = self.inner.now;
+        let config = match &self.inner.router_advert {
+            Some(advert) if advert.next_at <= now => advert.config,
+            _ => return false,
+        };
+
+        let src_addr = match self.inner.link_local_ipv6_addr() {
+            Some(addr) => addr,
+            None => {
+                net_debug!("ndisc: no link-local address to advertise from");
+                self.inner.router_advert_sent(now);
+                return false;
+            }
+        };
+        let tx_token = match device.transmit(now) {
+            Some(tx_token) => tx_token,
+            None => return false,
+        };
+
+        net_debug!("ndisc: advertising prefix {}", config.prefix);
+        let advert = Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+            hop_limit: 0,
+            flags: NdiscRouterFlags::empty(),
+            router_lifetime: config.router_lifetime,
+            reachable_time: Duration::ZERO,
+            retrans_time: Duration::ZERO,
+            lladdr: self.inner.hardware_addr.map(|addr| addr.into()),
+            mtu: config.mtu,
+            prefix_info: Some(NdiscPrefixInformation {
+                prefix_len: config.prefix.prefix_len(),
+                flags: NdiscPrefixInfoFlags::ON_LINK | NdiscPrefixInfoFlags::ADDRCONF,
+                valid_lifetime: config.valid_lifetime,
+                preferred_lifetime: config.preferred_lifetime,
+                prefix: config.prefix.address(),
+            }),
+            captive_portal: None,
+            pref64: None,
+            sixlowpan_context: None,
+        });
+        let ipv6_repr = Ipv6Repr {
+            src_addr,
+            dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: advert.buffer_len(),
+            hop_limit: 0xff,
+            traffic_class: 0,
+            flow_label: 0,
+        };
+        if let Err(e) =
+            self.inner
+                .dispatch_ip(tx_token, IpPacket::Icmpv6((ipv6_repr, advert)), None)
+        {
+            net_debug!("ndisc: failed to send router advertisement: {:?}", e);
+        }
+
+        self.inner.router_advert_sent(now);
+        true
+    }
+}
+
+impl InterfaceInner {
+    /// Return the time at which the next Router Advertisement is due.
+    pub(crate) fn router_advert_poll_at(&self) -> Option<Instant> {
+        self.router_advert.as_ref().map(|advert| advert.next_at)
+    }
+
+    /// Schedule the next Router Advertisement after one was sent, or was due, at `now`.
+    fn router_advert_sent(&mut self, now: Instant) {
+        if matches!(&self.router_advert, Some(advert) if advert.withdrawn) {
+            self.router_advert = None;
+        }
+        if let Some(advert) = self.router_advert.as_mut() {
+            advert.sent = advert.sent.saturating_add(1);
+            advert.sent_at = Some(now);
+            advert.next_at = now
+                + match advert.sent < MAX_INITIAL_ADVERTS {
+                    true => advert.config.interval.min(MAX_INITIAL_ADVERT_INTERVAL),
+                    false => advert.config.interval,
+                };
+        }
+    }
+
+    /// Answer a Router Solicitation with the next advertisement, as soon as allowed.
+    pub(super) fn router_solicited(&mut self) {
+        if let Some(advert) = self
+            .router_advert
+            .as_mut()
+            .filter(|advert| !advert.withdrawn)
+        {
+            let earliest = match advert.sent_at {
+                Some(sent_at) => sent_at + MIN_DELAY_BETWEEN_ADVERTS,
+                None => self.now,
+            };
+            advert.next_at = advert.next_at.min(earliest.max(self.now));
+        }
+    }
+
+    /// Return a link-local address of the interface, done with Duplicate Address
+    /// Detection.
+    fn link_local_ipv6_addr(&self) -> Option<Ipv6Address> {
+        self.ip_addrs.iter().find_map(|cidr| match cidr {
+            IpCidr::Ipv6(cidr)
+                if cidr.address().is_link_local() && self.has_ip_addr(cidr.address()) =>
+            {
+                Some(cidr.address())
+            }
+            #[allow(unreachable_patterns)]
+            _ => None,
+        })
+    }
+}
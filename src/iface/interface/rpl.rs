@@ -0,0 +1,862 @@
+// A minimal RPL node in storing mode, see RFC 6550.
+//
+// The node joins the DODAG advertised in the DIO messages of its neighbors, picks the
+// neighbor with the lowest rank as its preferred parent, and routes upwards through it
+// with a default route. In storing mode, each node advertises the addresses of its
+// sub-DODAG to its preferred parent in DAO messages, and installs routes downwards to
+// the children that advertised them. Ranks are computed with the Objective Function
+// Zero of RFC 6552.
+
+use heapless::{LinearMap, Vec};
+
+use super::{Interface, InterfaceInner, IpPacket, MAX_IP_ADDR_COUNT};
+use crate::iface::route::{Route, MAX_ROUTE_COUNT};
+use crate::phy::Device;
+use crate::rand::Rand;
+use crate::time::{Duration, Instant};
+use crate::wire::*;
+
+/// Largest number of neighbors kept as candidate parents.
+const MAX_PARENT_COUNT: usize = 4;
+
+/// Interval between DIS messages while no DODAG is joined.
+const DIS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Delay before sending a DAO after the sub-DODAG or the parent changed,
+/// `DEFAULT_DAO_DELAY` in [RFC 6550 § 17].
+///
+/// [RFC 6550 § 17]: https://tools.ietf.org/html/rfc6550#section-17
+const DAO_DELAY: Duration = Duration::from_secs(1);
+
+/// Delay before a DAO that was not acknowledged is sent again.
+const DAO_ACK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of times a DAO is sent again before giving up until the next refresh.
+const MAX_DAO_RETRANSMISSIONS: u8 = 3;
+
+/// Initial value of the lollipop counters, see [RFC 6550 § 7.2].
+///
+/// [RFC 6550 § 7.2]: https://tools.ietf.org/html/rfc6550#section-7.2
+const SEQUENCE_INITIAL: u8 = 240;
+
+/// `DEFAULT_STEP_OF_RANK` of the Objective Function Zero, see [RFC 6552 § 6.1].
+///
+/// [RFC 6552 § 6.1]: https://tools.ietf.org/html/rfc6552#section-6.1
+const STEP_OF_RANK: u16 = 3;
+
+const IPV6_DEFAULT: Ipv6Cidr = Ipv6Cidr::new(Ipv6Address::UNSPECIFIED, 0);
+
+/// The configuration of the RPL node of an interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RplConfig {
+    /// The RPL instance the node takes part in.
+    pub instance_id: u8,
+    /// The DODAG the node is the root of, or `None` to join the one advertised by its
+    /// neighbors.
+    pub root: Option<Ipv6Address>,
+    /// The parameters the root advertises to the DODAG.
+    pub dodag_configuration: RplDodagConfiguration,
+}
+
+impl RplConfig {
+    /// A node joining a DODAG of the given instance.
+    pub fn new(instance_id: u8) -> Self {
+        Self {
+            instance_id,
+            root: None,
+            dodag_configuration: RplDodagConfiguration::default(),
+        }
+    }
+
+    /// The root of the DODAG `dodag_id`, which is usually one of its global addresses.
+    pub fn new_root(instance_id: u8, dodag_id: Ipv6Address) -> Self {
+        Self {
+            root: Some(dodag_id),
+            ..Self::new(instance_id)
+        }
+    }
+}
+
+/// State of the RPL node of an interface.
+#[derive(Debug, Clone)]
+pub(crate) struct Rpl {
+    config: RplConfig,
+    /// The DODAG the node is part of.
+    dodag: Option<Dodag>,
+    /// When to solicit DIOs, while no DODAG is joined.
+    dis_at: Instant,
+    trickle: Trickle,
+    /// Destination Advertisement Trigger Sequence Number, sent in our DIOs.
+    dtsn: u8,
+    dao_sequence: u8,
+    /// When to send the next DAO.
+    dao_at: Option<Instant>,
+    /// Number of times the current DAO was sent without being acknowledged.
+    dao_retransmissions: u8,
+    /// A former parent our routes must be withdrawn from.
+    no_path_to: Option<Ipv6Address>,
+    /// Routes installed for the targets advertised by our children.
+    downward: LinearMap<Ipv6Cidr, DownwardRoute, MAX_ROUTE_COUNT>,
+}
+
+#[derive(Debug, Clone)]
+struct Dodag {
+    id: Ipv6Address,
+    version: u8,
+    grounded: bool,
+    preference: u8,
+    mode_of_operation: RplModeOfOperation,
+    configuration: RplDodagConfiguration,
+    rank: u16,
+    parents: LinearMap<Ipv6Address, Parent, MAX_PARENT_COUNT>,
+    preferred_parent: Option<Ipv6Address>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Parent {
+    rank: u16,
+    dtsn: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DownwardRoute {
+    next_hop: Ipv6Address,
+    expires_at: Option<Instant>,
+}
+
+/// The Trickle timer pacing DIO messages, see [RFC 6206].
+///
+/// [RFC 6206]: https://tools.ietf.org/html/rfc6206
+#[derive(Debug, Clone, Copy)]
+struct Trickle {
+    interval: Duration,
+    /// When to send in the current interval, if not sent yet.
+    send_at: Option<Instant>,
+    end_at: Instant,
+    /// Number of consistent messages heard in the current interval.
+    counter: u8,
+}
+
+impl Trickle {
+    fn new(now: Instant) -> Self {
+        Trickle {
+            interval: Duration::ZERO,
+            send_at: None,
+            end_at: now,
+            counter: 0,
+        }
+    }
+
+    fn interval_min(conf: &RplDodagConfiguration) -> Duration {
+        Duration::from_millis(1 << conf.dio_interval_min.min(32))
+    }
+
+    fn interval_max(conf: &RplDodagConfiguration) -> Duration {
+        let doublings = conf
+            .dio_interval_min
+            .saturating_add(conf.dio_interval_doublings);
+        Duration::from_millis(1 << doublings.min(32))
+    }
+
+    /// Restart the timer with its shortest interval, unless it is already using it.
+    fn reset(&mut self, now: Instant, conf: &RplDodagConfiguration, rand: &mut Rand) {
+        if self.interval != Self::interval_min(conf) || self.end_at <= now {
+            self.interval = Self::interval_min(conf);
+            self.start_interval(now, rand);
+        }
+    }
+
+    fn start_interval(&mut self, now: Instant, rand: &mut Rand) {
+        let half = self.interval.total_millis() / 2;
+        let offset = half + (rand.rand_u32() as u64) % half.max(1);
+        self.send_at = Some(now + Duration::from_millis(offset));
+        self.end_at = now + self.interval;
+        self.counter = 0;
+    }
+
+    /// Return whether to send a message now, and double the interval once it ended.
+    fn poll(&mut self, now: Instant, conf: &RplDodagConfiguration, rand: &mut Rand) -> bool {
+        let send = match self.send_at {
+            Some(send_at) if send_at <= now => {
+                self.send_at = None;
+                conf.dio_redundancy_constant == 0 || self.counter < conf.dio_redundancy_constant
+            }
+            _ => false,
+        };
+        if self.end_at <= now {
+            self.interval = (self.interval * 2).min(Self::interval_max(conf));
+            self.start_interval(now, rand);
+        }
+        send
+    }
+
+    fn poll_at(&self) -> Instant {
+        self.send_at.unwrap_or(self.end_at)
+    }
+}
+
+/// Return whether the lollipop counter `a` is newer than `b`.
+fn sequence_newer(a: u8, b: u8) -> bool {
+    (a.wrapping_sub(b) as i8) > 0
+}
+
+/// Return the lifetime of routes in the DODAG, `None` meaning "forever".
+fn path_lifetime(conf: &RplDodagConfiguration, lifetime: u8) -> Option<Duration> {
+    match lifetime {
+        0xff => None,
+        _ => Some(Duration::from_secs(
+            lifetime as u64 * conf.lifetime_unit as u64,
+        )),
+    }
+}
+
+/// Return when the routes advertised to our parent must be refreshed, once three
+/// quarters of their lifetime passed.
+fn dao_refresh_at(now: Instant, conf: &RplDodagConfiguration) -> Option<Instant> {
+    path_lifetime(conf, conf.default_lifetime).map(|lifetime| now + lifetime * 3 / 4)
+}
+
+impl Rpl {
+    pub(crate) fn new(config: RplConfig, now: Instant) -> Self {
+        let dodag = config.root.map(|id| Dodag {
+            id,
+            version: SEQUENCE_INITIAL,
+            grounded: true,
+            preference: 0,
+            mode_of_operation: RplModeOfOperation::StoringWithoutMulticast,
+            configuration: config.dodag_configuration,
+            rank: config.dodag_configuration.min_hop_rank_increase,
+            parents: LinearMap::new(),
+            preferred_parent: None,
+        });
+        Rpl {
+            config,
+            dodag,
+            dis_at: now,
+            trickle: Trickle::new(now),
+            dtsn: SEQUENCE_INITIAL,
+            dao_sequence: SEQUENCE_INITIAL,
+            dao_at: None,
+            dao_retransmissions: 0,
+            no_path_to: None,
+            downward: LinearMap::new(),
+        }
+    }
+}
+
+impl Dodag {
+    /// Pick the parent with the lowest rank, and drop the parents that are not above
+    /// our new rank. Return the previous preferred parent if it changed.
+    fn select_parent(&mut self) -> Option<Option<Ipv6Address>> {
+        let previous = self.preferred_parent;
+        let best = self
+            .parents
+            .iter()
+            .min_by_key(|(_, parent)| parent.rank)
+            .map(|(addr, parent)| (*addr, parent.rank));
+        match best {
+            Some((addr, rank)) => {
+                let step = STEP_OF_RANK.saturating_mul(self.configuration.min_hop_rank_increase);
+                self.rank = rank.saturating_add(step);
+                self.preferred_parent = Some(addr);
+            }
+            None => {
+                self.rank = RPL_INFINITE_RANK;
+                self.preferred_parent = None;
+            }
+        }
+        let rank = self.rank;
+        let stale: Vec<Ipv6Address, MAX_PARENT_COUNT> = self
+            .parents
+            .iter()
+            .filter(|(_, parent)| parent.rank >= rank)
+            .map(|(addr, _)| *addr)
+            .collect();
+        for addr in stale {
+            self.parents.remove(&addr);
+        }
+        (previous != self.preferred_parent).then_some(previous)
+    }
+}
+
+impl Interface {
+    /// Return the configuration of the RPL node, if it is enabled.
+    pub fn rpl(&self) -> Option<RplConfig> {
+        self.inner.rpl.as_ref().map(|rpl| rpl.config)
+    }
+
+    /// Enable the RPL node with the given configuration, or disable it with `None`.
+    ///
+    /// The node solicits DIO messages until it hears of a DODAG of its instance in
+    /// storing mode, joins it, and installs a default route through the neighbor with
+    /// the lowest rank. It then advertises the DODAG in its own DIO messages, paced
+    /// by a Trickle timer, and sends the global addresses of the interface and of its
+    /// sub-DODAG to its parent in DAO messages. The DAO messages of its children
+    /// install routes to their targets, which expire with the lifetime they were
+    /// advertised with.
+    ///
+    /// The messages are sent from a link-local address, so the interface needs one.
+    /// Forwarding the packets of other nodes along the installed routes is left to a
+    /// [`Forwarder`](crate::iface::Forwarder).
+    ///
+    /// Disabling the node removes the routes it installed.
+    pub fn set_rpl(&mut self, config: Option<RplConfig>) {
+        self.inner.rpl_detach();
+        self.inner.rpl = config.map(|config| Rpl::new(config, self.inner.now));
+        if let Some(rpl) = self.inner.rpl.as_mut() {
+            if let Some(dodag) = rpl.dodag.as_ref() {
+                rpl.trickle
+                    .reset(self.inner.now, &dodag.configuration, &mut self.inner.rand);
+            }
+        }
+    }
+
+    /// Return the DODAG the RPL node is part of, if it joined one.
+    pub fn rpl_dodag_id(&self) -> Option<Ipv6Address> {
+        Some(self.inner.rpl.as_ref()?.dodag.as_ref()?.id)
+    }
+
+    /// Return the rank of the RPL node in its DODAG, if it joined one.
+    pub fn rpl_rank(&self) -> Option<u16> {
+        Some(self.inner.rpl.as_ref()?.dodag.as_ref()?.rank)
+    }
+
+    /// Return the preferred parent of the RPL node, if it has one.
+    pub fn rpl_preferred_parent(&self) -> Option<Ipv6Address> {
+        self.inner.rpl.as_ref()?.dodag.as_ref()?.preferred_parent
+    }
+
+    /// Send the RPL messages that are due.
+    pub(crate) fn rpl_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let now = self.inner.now;
+        let src_addr = match self.inner.rpl_src_addr() {
+            Some(src_addr) => src_addr,
+            None => return false,
+        };
+        let inner = &mut self.inner;
+        let rpl = match inner.rpl.as_mut() {
+            Some(rpl) => rpl,
+            None => return false,
+        };
+        let instance_id = rpl.config.instance_id;
+
+        let dodag = match rpl.dodag.as_ref() {
+            Some(dodag) => dodag,
+            None => {
+                if rpl.dis_at > now {
+                    return false;
+                }
+                rpl.dis_at = now + DIS_INTERVAL;
+                net_trace!("rpl: soliciting DODAG information");
+                let repr = RplRepr::DodagInformationSolicitation { options: &[] };
+                return self.rpl_send(
+                    device,
+                    src_addr,
+                    Ipv6Address::LINK_LOCAL_ALL_RPL_NODES,
+                    repr,
+                );
+            }
+        };
+
+        if rpl.trickle.poll(now, &dodag.configuration, &mut inner.rand) {
+            let conf = RplOptionRepr::DodagConfiguration(dodag.configuration);
+            let mut options = [0; 16];
+            conf.emit(&mut RplOption::new_unchecked(&mut options[..]));
+            let repr = RplRepr::DodagInformationObject {
+                rpl_instance_id: instance_id,
+                version_number: dodag.version,
+                rank: dodag.rank,
+                grounded: dodag.grounded,
+                mode_of_operation: dodag.mode_of_operation,
+                dodag_preference: dodag.preference,
+                dtsn: rpl.dtsn,
+                dodag_id: dodag.id,
+                options: &options[..conf.buffer_len()],
+            };
+            net_trace!("rpl: advertising DODAG {} at rank {}", dodag.id, dodag.rank);
+            let dst_addr = Ipv6Address::LINK_LOCAL_ALL_RPL_NODES;
+            if !self.rpl_send(device, src_addr, dst_addr, repr) {
+                return false;
+            }
+        }
+
+        // NOTE(unwrap): the DODAG is still joined, nothing above leaves it.
+        let rpl = self.inner.rpl.as_mut().unwrap();
+        if let Some(parent) = rpl.no_path_to.take() {
+            net_trace!("rpl: withdrawing routes from {}", parent);
+            if !self.rpl_send_dao(device, src_addr, parent, 0) {
+                return false;
+            }
+        }
+
+        let rpl = self.inner.rpl.as_mut().unwrap();
+        let dodag = rpl.dodag.as_ref().unwrap();
+        if let (Some(parent), Some(dao_at)) = (dodag.preferred_parent, rpl.dao_at) {
+            if dao_at <= now {
+                let lifetime = dodag.configuration.default_lifetime;
+                rpl.dao_sequence = rpl.dao_sequence.wrapping_add(1);
+                if rpl.dao_retransmissions < MAX_DAO_RETRANSMISSIONS {
+                    rpl.dao_retransmissions += 1;
+                    rpl.dao_at = Some(now + DAO_ACK_TIMEOUT);
+                } else {
+                    rpl.dao_retransmissions = 0;
+                    rpl.dao_at = dao_refresh_at(now, &dodag.configuration);
+                }
+                net_trace!("rpl: advertising routes to {}", parent);
+                return self.rpl_send_dao(device, src_addr, parent, lifetime);
+            }
+        }
+
+        false
+    }
+
+    /// Send a DAO to `parent` for our global addresses and the targets of our
+    /// sub-DODAG, with the given path lifetime.
+    fn rpl_send_dao<D>(
+        &mut self,
+        device: &mut D,
+        src_addr: Ipv6Address,
+        parent: Ipv6Address,
+        path_lifetime: u8,
+    ) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let now = self.inner.now;
+        // NOTE(unwrap): DAOs are only sent by nodes that joined a DODAG.
+        let rpl = self.inner.rpl.as_ref().unwrap();
+        let dodag = rpl.dodag.as_ref().unwrap();
+
+        let own = self
+            .inner
+            .ip_addrs
+            .iter()
+            .filter_map(|cidr| match cidr.address() {
+                IpAddress::Ipv6(addr)
+                    if addr.is_unicast()
+                        && !addr.is_link_local()
+                        && !addr.is_loopback()
+                        && !self.inner.is_tentative(&addr.into()) =>
+                {
+                    Some(Ipv6Cidr::new(addr, 128))
+                }
+                #[allow(unreachable_patterns)]
+                _ => None,
+            });
+        let downward = rpl
+            .downward
+            .iter()
+            .filter(|(_, route)| route.expires_at.map_or(true, |at| at > now))
+            .map(|(cidr, _)| *cidr);
+
+        let mut options = [0; (MAX_IP_ADDR_COUNT + MAX_ROUTE_COUNT) * 20 + 6];
+        let mut len = 0;
+        for target in own.chain(downward) {
+            let target = RplOptionRepr::RplTarget(target);
+            target.emit(&mut RplOption::new_unchecked(&mut options[len..]));
+            len += target.buffer_len();
+        }
+        let transit = RplOptionRepr::TransitInformation(RplTransitInformation {
+            external: false,
+            path_control: 0,
+            path_sequence: rpl.dao_sequence,
+            path_lifetime,
+            parent_address: None,
+        });
+        transit.emit(&mut RplOption::new_unchecked(&mut options[len..]));
+        len += transit.buffer_len();
+
+        let repr = RplRepr::DestinationAdvertisementObject {
+            rpl_instance_id: rpl.config.instance_id,
+            expect_ack: path_lifetime != 0,
+            sequence: rpl.dao_sequence,
+            dodag_id: Some(dodag.id),
+            options: &options[..len],
+        };
+        self.rpl_send(device, src_addr, parent, repr)
+    }
+
+    fn rpl_send<D>(
+        &mut self,
+        device: &mut D,
+        src_addr: Ipv6Address,
+        dst_addr: Ipv6Address,
+        repr: RplRepr,
+    ) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let tx_token = match device.transmit(self.inner.now) {
+            Some(tx_token) => tx_token,
+            None => return false,
+        };
+        let icmp_repr = Icmpv6Repr::Rpl(repr);
+        let ipv6_repr = Ipv6Repr {
+            src_addr,
+            dst_addr,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 0xff,
+            traffic_class: 0,
+            flow_label: 0,
+        };
+        let packet = IpPacket::Icmpv6((ipv6_repr, icmp_repr));
+        if let Err(e) = self.inner.dispatch_ip(tx_token, packet, None) {
+            net_debug!("rpl: failed to send {}: {:?}", repr, e);
+        }
+        true
+    }
+}
+
+impl InterfaceInner {
+    /// Return the link-local address RPL messages are sent from.
+    fn rpl_src_addr(&self) -> Option<Ipv6Address> {
+        self.ip_addrs.iter().find_map(|cidr| match cidr.address() {
+            IpAddress::Ipv6(addr) if addr.is_link_local() && !self.is_tentative(&addr.into()) => {
+                Some(addr)
+            }
+            #[allow(unreachable_patterns)]
+            _ => None,
+        })
+    }
+
+    /// Return the time at which the next RPL message is due.
+    pub(crate) fn rpl_poll_at(&self) -> Option<Instant> {
+        let rpl = self.rpl.as_ref()?;
+        self.rpl_src_addr()?;
+        match rpl.dodag.as_ref() {
+            None => Some(rpl.dis_at),
+            Some(dodag) => {
+                if rpl.no_path_to.is_some() {
+                    return Some(self.now);
+                }
+                let dao_at = rpl.dao_at.filter(|_| dodag.preferred_parent.is_some());
+                Some(rpl.trickle.poll_at()).into_iter().chain(dao_at).min()
+            }
+        }
+    }
+
+    /// Remove the routes installed by the RPL node.
+    fn rpl_detach(&mut self) {
+        let rpl = match self.rpl.as_mut() {
+            Some(rpl) => rpl,
+            None => return,
+        };
+        if let Some(parent) = rpl.dodag.as_ref().and_then(|dodag| dodag.preferred_parent) {
+            self.routes.remove(IPV6_DEFAULT.into(), Some(parent.into()));
+        }
+        for (cidr, route) in rpl.downward.iter() {
+            self.routes
+                .remove((*cidr).into(), Some(route.next_hop.into()));
+        }
+        rpl.downward.clear();
+    }
+
+    pub(super) fn process_rpl<'frame>(
+        &mut self,
+        ipv6_repr: Ipv6Repr,
+        repr: RplRepr<'frame>,
+    ) -> Option<IpPacket<'frame>> {
+        let rpl = self.rpl.as_mut()?;
+        match repr {
+            // Unicast solicitations are answered with multicast DIOs as well.
+            RplRepr::DodagInformationSolicitation { .. } => {
+                if let Some(dodag) = rpl.dodag.as_ref() {
+                    rpl.trickle
+                        .reset(self.now, &dodag.configuration, &mut self.rand);
+                }
+                None
+            }
+            RplRepr::DodagInformationObject {
+                rpl_instance_id, ..
+            } if rpl_instance_id == rpl.config.instance_id => {
+                self.process_rpl_dio(ipv6_repr.src_addr, repr);
+                None
+            }
+            RplRepr::DestinationAdvertisementObject {
+                rpl_instance_id, ..
+            } if rpl_instance_id == rpl.config.instance_id => self.process_rpl_dao(ipv6_repr, repr),
+            RplRepr::DestinationAdvertisementObjectAck {
+                rpl_instance_id,
+                sequence,
+                status,
+                ..
+            } if rpl_instance_id == rpl.config.instance_id => {
+                let dodag = rpl.dodag.as_ref()?;
+                if sequence == rpl.dao_sequence && rpl.dao_retransmissions > 0 {
+                    if status >= 128 {
+                        net_debug!("rpl: routes rejected by {}", ipv6_repr.src_addr);
+                    }
+                    rpl.dao_retransmissions = 0;
+                    rpl.dao_at = dao_refresh_at(self.now, &dodag.configuration);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn process_rpl_dio(&mut self, src_addr: Ipv6Address, repr: RplRepr) {
+        let RplRepr::DodagInformationObject {
+            version_number,
+            rank,
+            grounded,
+            mode_of_operation,
+            dodag_preference,
+            dtsn,
+            dodag_id,
+            ..
+        } = repr
+        else {
+            return;
+        };
+        let now = self.now;
+        // NOTE(unwrap): only called when the node is enabled.
+        let rpl = self.rpl.as_mut().unwrap();
+
+        // Only storing mode is supported, for the node to be able to route downwards.
+        match mode_of_operation {
+            RplModeOfOperation::NoDownwardRoutes
+            | RplModeOfOperation::StoringWithoutMulticast
+            | RplModeOfOperation::StoringWithMulticast => (),
+            _ => return,
+        }
+        let mut configuration = None;
+        for option in repr.options() {
+            if let Ok(RplOptionRepr::DodagConfiguration(conf)) = option {
+                configuration = Some(conf);
+            }
+        }
+
+        if rpl.config.root.is_some() {
+            let dodag = rpl.dodag.as_ref().unwrap();
+            if dodag_id == dodag.id && version_number == dodag.version {
+                rpl.trickle.counter = rpl.trickle.counter.saturating_add(1);
+            }
+            return;
+        }
+
+        let dodag = match rpl.dodag.as_mut() {
+            Some(dodag) if dodag.id != dodag_id => return,
+            Some(dodag) if sequence_newer(version_number, dodag.version) => {
+                net_debug!("rpl: DODAG {} version {}", dodag_id, version_number);
+                // A new version rebuilds the DODAG, starting from this neighbor.
+                if let Some(parent) = dodag.preferred_parent {
+                    self.routes.remove(IPV6_DEFAULT.into(), Some(parent.into()));
+                }
+                dodag.version = version_number;
+                dodag.parents.clear();
+                dodag.preferred_parent = None;
+                dodag.rank = RPL_INFINITE_RANK;
+                rpl.dtsn = rpl.dtsn.wrapping_add(1);
+                dodag
+            }
+            Some(dodag) if dodag.version != version_number => return,
+            Some(dodag) => dodag,
+            None if rank == RPL_INFINITE_RANK => return,
+            None => {
+                net_debug!("rpl: joining DODAG {} via {}", dodag_id, src_addr);
+                rpl.dodag.insert(Dodag {
+                    id: dodag_id,
+                    version: version_number,
+                    grounded,
+                    preference: dodag_preference,
+                    mode_of_operation,
+                    configuration: configuration.unwrap_or_default(),
+                    rank: RPL_INFINITE_RANK,
+                    parents: LinearMap::new(),
+                    preferred_parent: None,
+                })
+            }
+        };
+
+        let is_preferred = dodag.preferred_parent == Some(src_addr);
+        if rank == RPL_INFINITE_RANK {
+            dodag.parents.remove(&src_addr);
+        } else if rank < dodag.rank || dodag.parents.contains_key(&src_addr) {
+            let parent = Parent { rank, dtsn };
+            if let Some(previous) = dodag.parents.get_mut(&src_addr) {
+                // Children advertise their routes again when the parent asks for them.
+                if is_preferred && sequence_newer(dtsn, previous.dtsn) {
+                    rpl.dao_at = Some(now + DAO_DELAY);
+                }
+                *previous = parent;
+            } else if dodag.parents.insert(src_addr, parent).is_err() {
+                // Replace the worst parent if this one is better.
+                let worst = dodag
+                    .parents
+                    .iter()
+                    .max_by_key(|(_, parent)| parent.rank)
+                    .map(|(addr, parent)| (*addr, parent.rank));
+                if let Some((worst, worst_rank)) = worst {
+                    if rank < worst_rank {
+                        dodag.parents.remove(&worst);
+                        let _ = dodag.parents.insert(src_addr, parent);
+                    }
+                }
+            }
+        }
+        if is_preferred {
+            if let Some(conf) = configuration {
+                dodag.configuration = conf;
+            }
+            dodag.grounded = grounded;
+            dodag.preference = dodag_preference;
+            dodag.mode_of_operation = mode_of_operation;
+        }
+
+        let previous = match dodag.select_parent() {
+            Some(previous) => previous,
+            None => {
+                rpl.trickle.counter = rpl.trickle.counter.saturating_add(1);
+                if is_preferred {
+                    self.rpl_update_default_route(None);
+                }
+                return;
+            }
+        };
+        self.rpl_update_default_route(previous);
+    }
+
+    /// Point the default route to the preferred parent, after it changed from
+    /// `previous`, and schedule the messages advertising the change.
+    fn rpl_update_default_route(&mut self, previous: Option<Ipv6Address>) {
+        let now = self.now;
+        let rpl = self.rpl.as_mut().unwrap();
+        let dodag = rpl.dodag.as_ref().unwrap();
+        if let Some(previous) = previous {
+            self.routes
+                .remove(IPV6_DEFAULT.into(), Some(previous.into()));
+        }
+        let parent = match dodag.preferred_parent {
+            Some(parent) => parent,
+            None => {
+                net_debug!("rpl: no parent left in DODAG {}", dodag.id);
+                rpl.dodag = None;
+                rpl.dao_at = None;
+                rpl.dis_at = now;
+                return;
+            }
+        };
+        let conf = &dodag.configuration;
+        let route = Route {
+            expires_at: path_lifetime(conf, conf.default_lifetime).map(|lifetime| now + lifetime),
+            ..Route::new_ipv6_gateway(parent)
+        };
+        if self.routes.add(route).is_err() {
+            net_debug!("rpl: no room for the default route via {}", parent);
+        }
+        if previous == Some(parent) {
+            return;
+        }
+        net_debug!("rpl: preferred parent {} at rank {}", parent, dodag.rank);
+        if previous.is_some() && dodag.mode_of_operation != RplModeOfOperation::NoDownwardRoutes {
+            rpl.no_path_to = previous;
+        }
+        if dodag.mode_of_operation != RplModeOfOperation::NoDownwardRoutes {
+            rpl.dao_at = Some(now + DAO_DELAY);
+            rpl.dao_retransmissions = 0;
+        }
+        rpl.trickle.reset(now, &dodag.configuration, &mut self.rand);
+    }
+
+    fn process_rpl_dao<'frame>(
+        &mut self,
+        ipv6_repr: Ipv6Repr,
+        repr: RplRepr<'frame>,
+    ) -> Option<IpPacket<'frame>> {
+        let RplRepr::DestinationAdvertisementObject {
+            rpl_instance_id,
+            expect_ack,
+            sequence,
+            dodag_id,
+            ..
+        } = repr
+        else {
+            return None;
+        };
+        let now = self.now;
+        let rpl = self.rpl.as_mut()?;
+        let dodag = rpl.dodag.as_ref()?;
+        if dodag_id.map_or(false, |id| id != dodag.id)
+            || dodag.mode_of_operation == RplModeOfOperation::NoDownwardRoutes
+            || dodag.preferred_parent == Some(ipv6_repr.src_addr)
+        {
+            return None;
+        }
+        let conf = dodag.configuration;
+        let child = ipv6_repr.src_addr;
+
+        // Each Transit Information option applies to the targets preceding it.
+        let mut status = 0;
+        let mut targets: Vec<Ipv6Cidr, MAX_ROUTE_COUNT> = Vec::new();
+        for option in repr.options() {
+            match option {
+                Ok(RplOptionRepr::RplTarget(_)) if targets.is_full() => status = 128,
+                // NOTE(unwrap): there is room for the target.
+                Ok(RplOptionRepr::RplTarget(target)) => targets.push(target).unwrap(),
+                Ok(RplOptionRepr::TransitInformation(transit)) => {
+                    for &target in targets.iter() {
+                        let route = Route::new(target.into(), Some(child.into()));
+                        if transit.path_lifetime == 0 {
+                            if self.routes.remove(route.cidr, route.via_router).is_some() {
+                                net_trace!("rpl: route to {} via {} withdrawn", target, child);
+                            }
+                            rpl.downward.remove(&target);
+                            continue;
+                        }
+                        let expires_at = path_lifetime(&conf, transit.path_lifetime)
+                            .map(|lifetime| now + lifetime);
+                        if let Some(previous) = rpl.downward.get(&target) {
+                            let via = Some(previous.next_hop.into());
+                            self.routes.remove(target.into(), via);
+                        }
+                        let downward = DownwardRoute {
+                            next_hop: child,
+                            expires_at,
+                        };
+                        if rpl.downward.insert(target, downward).is_err()
+                            || self
+                                .routes
+                                .add(Route {
+                                    expires_at,
+                                    ..route
+                                })
+                                .is_err()
+                        {
+                            net_debug!("rpl: no room for the route to {}", target);
+                            rpl.downward.remove(&target);
+                            status = 128;
+                            continue;
+                        }
+                        net_trace!("rpl: route to {} via {}", target, child);
+                    }
+                    targets.clear();
+                }
+                _ => (),
+            }
+        }
+
+        // Advertise the changed sub-DODAG upwards.
+        if rpl.config.root.is_none() && rpl.dao_at.map_or(true, |at| at > now + DAO_DELAY) {
+            rpl.dao_at = Some(now + DAO_DELAY);
+            rpl.dao_retransmissions = 0;
+        }
+
+        if !expect_ack {
+            return None;
+        }
+        let ack = RplRepr::DestinationAdvertisementObjectAck {
+            rpl_instance_id,
+            sequence,
+            status,
+            dodag_id,
+            options: &[],
+        };
+        self.icmpv6_reply(ipv6_repr, Icmpv6Repr::Rpl(ack))
+    }
+}
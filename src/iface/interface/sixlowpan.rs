@@ -10,6 +10,8 @@ use super::SixlowpanOutPacket;
 
 use crate::phy::ChecksumCapabilities;
 use crate::phy::TxToken;
+#[cfg(feature = "medium-ieee802154")]
+use crate::time::Duration;
 use crate::wire::*;
 
 // Max len of non-fragmented packets after decompression (including ipv6 header and payload)
@@ -17,6 +19,39 @@ use crate::wire::*;
 pub(crate) const MAX_DECOMPRESSED_LEN: usize = 1500;
 
 impl InterfaceInner {
+    /// Return the address contexts that have not expired, indexed by their context identifier.
+    pub(super) fn sixlowpan_address_contexts(
+        &self,
+    ) -> [Option<SixlowpanAddressContext>; SIXLOWPAN_ADDRESS_CONTEXT_COUNT] {
+        self.sixlowpan_address_context.map(|entry| match entry {
+            Some((context, expires_at)) if expires_at.map_or(true, |at| at > self.now) => {
+                Some(context)
+            }
+            _ => None,
+        })
+    }
+
+    /// Learn or withdraw an address context advertised by a router.
+    #[cfg(feature = "medium-ieee802154")]
+    pub(super) fn learn_sixlowpan_context(&mut self, context: NdiscSixlowpanContext) {
+        let entry = &mut self.sixlowpan_address_context[context.context_id as usize];
+        if context.valid_lifetime == Duration::ZERO {
+            if entry.is_some() {
+                net_debug!("6lowpan context {} withdrawn", context.context_id);
+            }
+            *entry = None;
+            return;
+        }
+        net_trace!(
+            "6lowpan context {} is {} for {}",
+            context.context_id,
+            context.prefix,
+            context.valid_lifetime
+        );
+        let address_context = SixlowpanAddressContext::from_prefix(context.prefix.address());
+        *entry = Some((address_context, Some(self.now + context.valid_lifetime)));
+    }
+
     #[cfg(feature = "medium-ieee802154")]
     pub(super) fn process_ieee802154<'output, 'payload: 'output, T: AsRef<[u8]> + ?Sized>(
         &mut self,
@@ -24,9 +59,27 @@ impl InterfaceInner {
         sixlowpan_payload: &'payload T,
         _fragments: &'output mut FragmentsBuffer,
     ) -> Option<IpPacket<'output>> {
-        let ieee802154_frame = check!(Ieee802154Frame::new_checked(sixlowpan_payload));
-        let ieee802154_repr = check!(Ieee802154Repr::parse(&ieee802154_frame));
+        let ieee802154_frame = check!(
+            Ieee802154Frame::new_checked(sixlowpan_payload),
+            self.stats,
+            ieee802154_errors
+        );
+        let ieee802154_repr = check!(
+            Ieee802154Repr::parse(&ieee802154_frame),
+            self.stats,
+            ieee802154_errors
+        );
 
+        if matches!(
+            ieee802154_repr.frame_type,
+            Ieee802154FrameType::Beacon | Ieee802154FrameType::MacCommand
+        ) && !ieee802154_repr.security_enabled
+        {
+            if let Some(payload) = ieee802154_frame.payload() {
+                self.process_ieee802154_mac(&ieee802154_repr, payload);
+            }
+            return None;
+        }
         if ieee802154_repr.frame_type != Ieee802154FrameType::Data {
             return None;
         }
@@ -58,7 +111,11 @@ impl InterfaceInner {
         payload: &'payload T,
         f: &'output mut FragmentsBuffer,
     ) -> Option<IpPacket<'output>> {
-        let payload = match check!(SixlowpanPacket::dispatch(payload)) {
+        let payload = match check!(
+            SixlowpanPacket::dispatch(payload),
+            self.stats,
+            sixlowpan_errors
+        ) {
             #[cfg(not(feature = "proto-sixlowpan-fragmentation"))]
             SixlowpanPacket::FragmentHeader => {
                 net_debug!("Fragmentation is not supported, use the `proto-sixlowpan-fragmentation` feature to add support.");
@@ -87,7 +144,10 @@ impl InterfaceInner {
             }
         };
 
-        self.process_ipv6(sockets, &check!(Ipv6Packet::new_checked(payload)))
+        self.process_ipv6(
+            sockets,
+            &check!(Ipv6Packet::new_checked(payload), self.stats, ipv6_errors),
+        )
     }
 
     #[cfg(feature = "proto-sixlowpan-fragmentation")]
@@ -97,11 +157,15 @@ impl InterfaceInner {
         payload: &'payload T,
         f: &'output mut FragmentsBuffer,
     ) -> Option<&'output [u8]> {
-        use crate::iface::fragmentation::{AssemblerError, AssemblerFullError};
+        use crate::iface::fragmentation::AssemblerError;
 
         // We have a fragment header, which means we cannot process the 6LoWPAN packet,
         // unless we have a complete one after processing this fragment.
-        let frag = check!(SixlowpanFragPacket::new_checked(payload));
+        let frag = check!(
+            SixlowpanFragPacket::new_checked(payload),
+            self.stats,
+            sixlowpan_errors
+        );
 
         // The key specifies to which 6LoWPAN fragment it belongs too.
         // It is based on the link layer addresses, the tag and the size.
@@ -115,16 +179,9 @@ impl InterfaceInner {
         // This information is the total size of the packet when it is fully assmbled.
         // We also pass the header size, since this is needed when other fragments
         // (other than the first one) are added.
-        let frag_slot = match f
+        let frag_slot = f
             .sixlowpan_fragments
-            .get(&key, self.now + f.sixlowpan_fragments_cache_timeout)
-        {
-            Ok(frag) => frag,
-            Err(AssemblerFullError) => {
-                net_debug!("No available packet assembler for fragmented packet");
-                return None;
-            }
-        };
+            .get(&key, self.now + f.sixlowpan_fragments_cache_timeout);
 
         if frag.is_first_fragment() {
             // The first fragment contains the total size of the IPv6 packet.
@@ -138,6 +195,8 @@ impl InterfaceInner {
             let total_size = frag.datagram_size() as usize;
             if frag_slot.set_total_size(total_size).is_err() {
                 net_debug!("No available packet assembler for fragmented packet");
+                // The packet can't be completed, so free its assembler for the others.
+                frag_slot.reset();
                 return None;
             }
 
@@ -147,12 +206,14 @@ impl InterfaceInner {
                     .map_err(|_| AssemblerError)
             }) {
                 net_debug!("fragmentation error: {:?}", e);
+                frag_slot.reset();
                 return None;
             }
         } else {
             // Add the fragment to the packet assembler.
             if let Err(e) = frag_slot.add(frag.payload(), offset) {
                 net_debug!("fragmentation error: {:?}", e);
+                frag_slot.reset();
                 return None;
             }
         }
@@ -178,7 +239,7 @@ impl InterfaceInner {
             &iphc,
             ieee802154_repr.src_addr,
             ieee802154_repr.dst_addr,
-            &self.sixlowpan_address_context,
+            &self.sixlowpan_address_contexts(),
         )?;
 
         let mut decompressed_size = 40 + iphc.payload().len();
@@ -226,6 +287,8 @@ impl InterfaceInner {
             next_header,
             payload_len: total_size - 40,
             hop_limit: iphc_repr.hop_limit,
+            traffic_class: 0,
+            flow_label: 0,
         };
 
         // Emit the decompressed IPHC header (decompressed to an IPv6 header).
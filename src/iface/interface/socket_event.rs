@@ -0,0 +1,181 @@
+// Socket events, an alternative to querying every socket after each poll.
+//
+// At the end of each poll, the readiness of each socket is compared with the one
+// recorded at the previous poll, and an event is queued for each condition that
+// became true.
+
+use heapless::Deque;
+
+use super::Interface;
+use crate::iface::socket_meta::Readiness;
+use crate::iface::{SocketHandle, SocketSet};
+use crate::socket::Socket;
+
+/// Maximum number of socket events queued on an interface.
+const MAX_SOCKET_EVENT_COUNT: usize = 32;
+
+/// A change of the readiness of a socket, see [`Interface::poll_socket_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SocketEvent {
+    /// The socket has data to receive.
+    Readable(SocketHandle),
+    /// The socket has room to send data.
+    Writable(SocketHandle),
+    /// The TCP connection of the socket was established.
+    Connected(SocketHandle),
+    /// The TCP connection of the socket was closed.
+    Closed(SocketHandle),
+    /// The configuration provided by a DHCP socket changed.
+    ConfigChanged(SocketHandle),
+}
+
+impl SocketEvent {
+    /// Return the handle of the socket the event is about.
+    pub fn handle(&self) -> SocketHandle {
+        match *self {
+            SocketEvent::Readable(handle)
+            | SocketEvent::Writable(handle)
+            | SocketEvent::Connected(handle)
+            | SocketEvent::Closed(handle)
+            | SocketEvent::ConfigChanged(handle) => handle,
+        }
+    }
+}
+
+/// The socket events queued on an interface.
+pub(super) struct SocketEvents {
+    enabled: bool,
+    /// Whether events were dropped because the queue was full.
+    lost: bool,
+    queue: Deque<SocketEvent, MAX_SOCKET_EVENT_COUNT>,
+}
+
+impl SocketEvents {
+    pub(super) const fn new() -> Self {
+        Self {
+            enabled: false,
+            lost: false,
+            queue: Deque::new(),
+        }
+    }
+
+    fn push(&mut self, event: SocketEvent) {
+        if self.queue.is_full() {
+            self.queue.pop_front();
+            self.lost = true;
+        }
+        // NOTE(unwrap): we've just made room.
+        self.queue.push_back(event).unwrap();
+    }
+}
+
+/// Return the current readiness of a socket.
+fn readiness(socket: &Socket) -> Readiness {
+    let mut readiness = Readiness::empty();
+    match socket {
+        #[cfg(feature = "socket-raw")]
+        Socket::Raw(s) => {
+            readiness.set(Readiness::READABLE, s.can_recv());
+            readiness.set(Readiness::WRITABLE, s.can_send());
+        }
+        #[cfg(feature = "socket-icmp")]
+        Socket::Icmp(s) => {
+            readiness.set(Readiness::READABLE, s.can_recv());
+            readiness.set(Readiness::WRITABLE, s.can_send());
+        }
+        #[cfg(feature = "socket-udp")]
+        Socket::Udp(s) => {
+            readiness.set(Readiness::READABLE, s.can_recv());
+            readiness.set(Readiness::WRITABLE, s.can_send());
+        }
+        #[cfg(feature = "socket-tcp")]
+        Socket::Tcp(s) => {
+            readiness.set(Readiness::READABLE, s.can_recv());
+            readiness.set(Readiness::WRITABLE, s.can_send());
+            readiness.set(Readiness::CONNECTED, s.may_send() || s.may_recv());
+            readiness.set(Readiness::CLOSED, !s.is_open());
+        }
+        #[cfg(feature = "socket-dhcpv4")]
+        Socket::Dhcpv4(s) => readiness.set(Readiness::CONFIG_CHANGED, s.has_config_changed()),
+        #[cfg(feature = "socket-dhcpv6")]
+        Socket::Dhcpv6(s) => readiness.set(Readiness::CONFIG_CHANGED, s.has_config_changed()),
+        #[cfg(feature = "socket-packet")]
+        Socket::Packet(s) => {
+            readiness.set(Readiness::READABLE, s.can_recv());
+            readiness.set(Readiness::WRITABLE, s.can_send());
+        }
+        #[cfg(feature = "socket-tftp")]
+        Socket::Tftp(s) => {
+            readiness.set(Readiness::READABLE, s.can_recv());
+            readiness.set(Readiness::WRITABLE, s.can_send());
+        }
+        #[allow(unreachable_patterns)]
+        _ => {}
+    }
+    readiness
+}
+
+impl Interface {
+    /// Return whether socket events are recorded.
+    pub fn socket_events(&self) -> bool {
+        self.socket_events.enabled
+    }
+
+    /// Enable or disable recording socket events.
+    ///
+    /// When enabled, each [`poll`](Self::poll) compares the readiness of every socket
+    /// with the one at the previous poll, and queues a [`SocketEvent`] for each
+    /// condition that became true: a socket became readable or writable, a TCP
+    /// connection was established or closed, or a DHCP socket has a new configuration.
+    /// Applications can then drain the events with
+    /// [`poll_socket_event`](Self::poll_socket_event) instead of querying every socket.
+    ///
+    /// A condition already true when a socket is first seen is reported too, except
+    /// that a TCP socket is closed.
+    pub fn set_socket_events(&mut self, enabled: bool) {
+        self.socket_events.enabled = enabled;
+        if !enabled {
+            self.socket_events.queue.clear();
+            self.socket_events.lost = false;
+        }
+    }
+
+    /// Return the next socket event, if any.
+    ///
+    /// If the events are not polled, only the most recent ones are kept.
+    pub fn poll_socket_event(&mut self) -> Option<SocketEvent> {
+        self.socket_events.queue.pop_front()
+    }
+
+    /// Return whether socket events were dropped because too many were queued, and
+    /// clear this condition.
+    ///
+    /// When events were dropped, applications should query every socket once.
+    pub fn socket_events_lost(&mut self) -> bool {
+        core::mem::replace(&mut self.socket_events.lost, false)
+    }
+
+    /// Queue the events of the sockets whose readiness changed since the last poll.
+    pub(super) fn record_socket_events(&mut self, sockets: &mut SocketSet<'_>) {
+        if !self.socket_events.enabled {
+            return;
+        }
+        for item in sockets.items_mut() {
+            let handle = item.meta.handle;
+            let readiness = readiness(&item.socket);
+            let previous = item.meta.update_readiness(readiness);
+            for (flag, event) in [
+                (Readiness::CONNECTED, SocketEvent::Connected as fn(_) -> _),
+                (Readiness::READABLE, SocketEvent::Readable),
+                (Readiness::WRITABLE, SocketEvent::Writable),
+                (Readiness::CLOSED, SocketEvent::Closed),
+                (Readiness::CONFIG_CHANGED, SocketEvent::ConfigChanged),
+            ] {
+                if readiness.became(previous, flag) {
+                    self.socket_events.push(event(handle));
+                }
+            }
+        }
+    }
+}
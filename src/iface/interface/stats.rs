@@ -0,0 +1,256 @@
+use core::cell::Cell;
+
+use super::*;
+use crate::phy::HardwareTimestamp;
+
+/// Counters of the traffic going through an interface, and of the packets it dropped.
+///
+/// The counters start at zero when the interface is created, and wrap around on
+/// overflow.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct InterfaceStats {
+    /// Frames received from the device.
+    pub rx_packets: u64,
+    /// Bytes received from the device, including link-layer headers.
+    pub rx_bytes: u64,
+    /// Frames sent to the device.
+    pub tx_packets: u64,
+    /// Bytes sent to the device, including link-layer headers.
+    pub tx_bytes: u64,
+
+    /// Malformed Ethernet frames.
+    pub ethernet_errors: u32,
+    /// Malformed IEEE 802.15.4 frames.
+    pub ieee802154_errors: u32,
+    /// Malformed 6LoWPAN packets.
+    pub sixlowpan_errors: u32,
+    /// Malformed ARP packets.
+    pub arp_errors: u32,
+    /// Malformed IPv4 packets.
+    pub ipv4_errors: u32,
+    /// Malformed IPv6 packets, including their extension headers.
+    pub ipv6_errors: u32,
+    /// Malformed ICMP and ICMPv6 messages.
+    pub icmp_errors: u32,
+    /// Malformed IGMP messages.
+    pub igmp_errors: u32,
+    /// Malformed UDP datagrams.
+    pub udp_errors: u32,
+    /// Malformed TCP segments.
+    pub tcp_errors: u32,
+    /// Packets of the above dropped because of an invalid checksum.
+    pub checksum_errors: u32,
+
+    /// UDP datagrams and TCP segments for which no socket was listening.
+    pub dropped_no_socket: u32,
+    /// Packets dropped because their destination could not be reached.
+    pub dropped_no_route: u32,
+    /// Packets that could not be sent at once because the link-layer address of their
+    /// next hop was not in the neighbor cache.
+    pub neighbor_misses: u32,
+}
+
+impl InterfaceStats {
+    fn count_frames(&mut self, rx: (u64, u64), tx: (u64, u64)) {
+        self.rx_packets = self.rx_packets.wrapping_add(rx.0);
+        self.rx_bytes = self.rx_bytes.wrapping_add(rx.1);
+        self.tx_packets = self.tx_packets.wrapping_add(tx.0);
+        self.tx_bytes = self.tx_bytes.wrapping_add(tx.1);
+    }
+}
+
+impl Interface {
+    /// Return the counters of the interface.
+    pub fn stats(&self) -> InterfaceStats {
+        self.inner.stats
+    }
+
+    /// Reset all the counters of the interface to zero.
+    pub fn reset_stats(&mut self) {
+        self.inner.stats = InterfaceStats::default();
+    }
+}
+
+/// A device counting the frames going through it, as `(packets, bytes)`, and refusing
+/// to hand out tokens once they use up its budget.
+pub(super) struct Counting<'d, D: ?Sized> {
+    device: &'d mut D,
+    budget: PollBudget,
+    exhausted: bool,
+    rx: Cell<(u64, u64)>,
+    tx: Cell<(u64, u64)>,
+}
+
+impl<'d, D: ?Sized> Counting<'d, D> {
+    pub(super) fn new(device: &'d mut D) -> Self {
+        Self::with_budget(device, PollBudget::default())
+    }
+
+    pub(super) fn with_budget(device: &'d mut D, budget: PollBudget) -> Self {
+        Self {
+            device,
+            budget,
+            exhausted: false,
+            rx: Cell::new((0, 0)),
+            tx: Cell::new((0, 0)),
+        }
+    }
+
+    /// Return whether a token was refused because the budget ran out.
+    pub(super) fn exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    fn check_budget(&mut self) -> bool {
+        let (rx, tx) = (self.rx.get(), self.tx.get());
+        if self.budget.exhausted_by(rx.0 + tx.0, rx.1 + tx.1) {
+            net_trace!("iface: poll budget exhausted");
+            self.exhausted = true;
+        }
+        !self.exhausted
+    }
+
+    /// Add the frames counted so far to `stats`.
+    pub(super) fn count_into(&self, stats: &mut InterfaceStats) {
+        stats.count_frames(self.rx.take(), self.tx.take());
+    }
+}
+
+impl<'d, D: Device + ?Sized> Device for Counting<'d, D> {
+    type RxToken<'a>
+        = CountingToken<'a, D::RxToken<'a>>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = CountingToken<'a, D::TxToken<'a>>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        if !self.check_budget() {
+            return None;
+        }
+        let Self { device, rx, tx, .. } = self;
+        device.receive(timestamp).map(|(rx_token, tx_token)| {
+            let rx_token = CountingToken {
+                token: rx_token,
+                counter: rx,
+            };
+            let tx_token = CountingToken {
+                token: tx_token,
+                counter: tx,
+            };
+            (rx_token, tx_token)
+        })
+    }
+
+    fn receive_batch<'a, F>(&'a mut self, timestamp: Instant, max: usize, mut f: F) -> usize
+    where
+        F: FnMut(Self::RxToken<'a>, Self::TxToken<'a>),
+    {
+        // The budget is checked before each frame, so only unlimited budgets let the
+        // device hand over several frames at once.
+        if !self.check_budget() {
+            return 0;
+        }
+        let max = if self.budget == PollBudget::default() {
+            max
+        } else {
+            max.min(1)
+        };
+        let Self { device, rx, tx, .. } = self;
+        device.receive_batch(timestamp, max, |rx_token, tx_token| {
+            let rx_token = CountingToken {
+                token: rx_token,
+                counter: rx,
+            };
+            let tx_token = CountingToken {
+                token: tx_token,
+                counter: tx,
+            };
+            f(rx_token, tx_token)
+        })
+    }
+
+    fn transmit_batch<'a, F>(&'a mut self, timestamp: Instant, max: usize, mut f: F) -> usize
+    where
+        F: FnMut(Self::TxToken<'a>) -> bool,
+    {
+        if !self.check_budget() {
+            return 0;
+        }
+        let max = if self.budget == PollBudget::default() {
+            max
+        } else {
+            max.min(1)
+        };
+        let Self { device, tx, .. } = self;
+        device.transmit_batch(timestamp, max, |token| {
+            f(CountingToken { token, counter: tx })
+        })
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        if !self.check_budget() {
+            return None;
+        }
+        let Self { device, tx, .. } = self;
+        device
+            .transmit(timestamp)
+            .map(|token| CountingToken { token, counter: tx })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.device.capabilities()
+    }
+}
+
+pub(super) struct CountingToken<'a, T> {
+    token: T,
+    counter: &'a Cell<(u64, u64)>,
+}
+
+fn count(counter: &Cell<(u64, u64)>, len: usize) {
+    let (packets, bytes) = counter.get();
+    counter.set((packets.wrapping_add(1), bytes.wrapping_add(len as u64)));
+}
+
+impl<'a, T: RxToken> RxToken for CountingToken<'a, T> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        self.token.consume(|buffer| {
+            count(self.counter, buffer.len());
+            f(buffer)
+        })
+    }
+
+    fn hardware_timestamp(&self) -> Option<HardwareTimestamp> {
+        self.token.hardware_timestamp()
+    }
+}
+
+impl<'a, T: TxToken> TxToken for CountingToken<'a, T> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        count(self.counter, len);
+        self.token.consume(len, f)
+    }
+
+    fn consume_timestamped<R, F>(self, len: usize, f: F) -> (R, Option<HardwareTimestamp>)
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        count(self.counter, len);
+        self.token.consume_timestamped(len, f)
+    }
+
+    fn set_ip_version(&mut self, version: IpVersion) {
+        self.token.set_ip_version(version)
+    }
+}
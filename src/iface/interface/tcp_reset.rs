@@ -0,0 +1,95 @@
+// TCP RST segments sent for segments no socket accepted.
+//
+// A RST tells the peer that a port is closed, which some deployments must not
+// reveal, so whether they are sent is configurable, and they are rate-limited.
+
+use super::rate_limit::RateLimit;
+use super::{Interface, InterfaceInner};
+use crate::time::Duration;
+use crate::wire::{TcpControl, TcpRepr};
+
+/// Which TCP segments no socket accepted are answered with a RST.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TcpResetPolicy {
+    /// Answer a SYN to a port no socket listens on.
+    pub closed_ports: bool,
+    /// Answer any other segment that does not belong to a connection.
+    pub unknown_connections: bool,
+}
+
+impl TcpResetPolicy {
+    /// Answer every segment no socket accepted, as required by RFC 9293.
+    pub const STANDARD: Self = Self {
+        closed_ports: true,
+        unknown_connections: true,
+    };
+
+    /// Silently drop every segment no socket accepted, so that closed ports cannot be
+    /// told apart from filtered ones.
+    pub const STEALTH: Self = Self {
+        closed_ports: false,
+        unknown_connections: false,
+    };
+}
+
+impl Default for TcpResetPolicy {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+pub(super) struct TcpResets {
+    policy: TcpResetPolicy,
+    limit: RateLimit,
+}
+
+impl TcpResets {
+    pub(super) const fn new() -> Self {
+        Self {
+            policy: TcpResetPolicy::STANDARD,
+            limit: RateLimit::new(10, Duration::from_millis(100)),
+        }
+    }
+}
+
+impl Interface {
+    /// Get which TCP segments no socket accepted are answered with a RST.
+    pub fn tcp_reset_policy(&self) -> TcpResetPolicy {
+        self.inner.tcp_resets.policy
+    }
+
+    /// Set which TCP segments no socket accepted are answered with a RST.
+    ///
+    /// Segments also received by a raw socket are never answered. Sockets still reset
+    /// their own connections, e.g. when their backlog is full.
+    ///
+    /// The default is [`TcpResetPolicy::STANDARD`], and the RSTs are rate-limited, see
+    /// [`set_tcp_reset_rate_limit`](Self::set_tcp_reset_rate_limit).
+    pub fn set_tcp_reset_policy(&mut self, policy: TcpResetPolicy) {
+        self.inner.tcp_resets.policy = policy;
+    }
+
+    /// Limit the rate of the RSTs sent for TCP segments no socket accepted.
+    ///
+    /// Up to `burst` RSTs can be sent at once, then one more each `interval`. With a
+    /// zero `interval`, the rate is not limited. By default, 10 RSTs can be sent at
+    /// once, then one each 100 ms.
+    pub fn set_tcp_reset_rate_limit(&mut self, burst: u16, interval: Duration) {
+        let now = self.inner.now;
+        self.inner.tcp_resets.limit.set(burst, interval, now);
+    }
+}
+
+impl InterfaceInner {
+    /// Return whether a RST can be sent now in reply to `repr`, which no socket
+    /// accepted, and account for it.
+    pub(super) fn take_tcp_reset(&mut self, repr: &TcpRepr) -> bool {
+        let policy = self.tcp_resets.policy;
+        let allowed = match (repr.control, repr.ack_number) {
+            (TcpControl::Syn, None) => policy.closed_ports,
+            _ => policy.unknown_connections,
+        };
+        allowed && self.tcp_resets.limit.take(self.now)
+    }
+}
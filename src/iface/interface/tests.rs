@@ -157,6 +157,7 @@ fn test_no_icmp_no_unicast_ipv4() {
         next_header: IpProtocol::Unknown(0x0c),
         payload_len: 0,
         hop_limit: 0x40,
+        dscp: 0,
     });
 
     let mut bytes = vec![0u8; 54];
@@ -196,6 +197,8 @@ fn test_no_icmp_no_unicast_ipv6() {
         next_header: IpProtocol::Unknown(0x0c),
         payload_len: 0,
         hop_limit: 0x40,
+        traffic_class: 0,
+        flow_label: 0,
     });
 
     let mut bytes = vec![0u8; 54];
@@ -221,6 +224,7 @@ fn test_icmp_error_no_payload() {
         next_header: IpProtocol::Unknown(0x0c),
         payload_len: 0,
         hop_limit: 0x40,
+        dscp: 0,
     });
 
     let mut bytes = vec![0u8; 34];
@@ -237,6 +241,7 @@ fn test_icmp_error_no_payload() {
             next_header: IpProtocol::Unknown(12),
             payload_len: 0,
             hop_limit: 64,
+            dscp: 0,
         },
         data: &NO_BYTES,
     };
@@ -248,6 +253,7 @@ fn test_icmp_error_no_payload() {
             next_header: IpProtocol::Icmp,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 64,
+            dscp: 0,
         },
         icmp_repr,
     ));
@@ -350,6 +356,7 @@ fn test_icmp_error_port_unreachable() {
         next_header: IpProtocol::Udp,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
         hop_limit: 64,
+        dscp: 0,
     });
 
     // Emit the representations to a packet
@@ -374,6 +381,7 @@ fn test_icmp_error_port_unreachable() {
             next_header: IpProtocol::Udp,
             payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
             hop_limit: 64,
+            dscp: 0,
         },
         data,
     };
@@ -384,6 +392,7 @@ fn test_icmp_error_port_unreachable() {
             next_header: IpProtocol::Icmp,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 64,
+            dscp: 0,
         },
         icmp_repr,
     ));
@@ -403,6 +412,7 @@ fn test_icmp_error_port_unreachable() {
         next_header: IpProtocol::Udp,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
         hop_limit: 64,
+        dscp: 0,
     });
 
     // Emit the representations to a packet
@@ -431,6 +441,131 @@ fn test_icmp_error_port_unreachable() {
     );
 }
 
+#[test]
+#[cfg(all(feature = "socket-udp", feature = "proto-ipv4"))]
+fn test_icmp_error_port_unreachable_rate_limit() {
+    let (mut iface, mut sockets, _device) = create(MEDIUM);
+
+    let udp_repr = UdpRepr {
+        src_port: 67,
+        dst_port: 68,
+    };
+    let ip_repr = IpRepr::Ipv4(Ipv4Repr {
+        src_addr: Ipv4Address([0x7f, 0x00, 0x00, 0x02]),
+        dst_addr: Ipv4Address([0x7f, 0x00, 0x00, 0x01]),
+        next_header: IpProtocol::Udp,
+        payload_len: udp_repr.header_len(),
+        hop_limit: 64,
+        dscp: 0,
+    });
+    let mut bytes = vec![0u8; udp_repr.header_len()];
+    udp_repr.emit(
+        &mut UdpPacket::new_unchecked(&mut bytes),
+        &ip_repr.src_addr(),
+        &ip_repr.dst_addr(),
+        0,
+        |_| {},
+        &ChecksumCapabilities::default(),
+    );
+    let mut process = |iface: &mut Interface| {
+        iface
+            .inner
+            .process_udp(&mut sockets, ip_repr.clone(), udp_repr, false, &[], &bytes)
+            .is_some()
+    };
+
+    assert!(iface.udp_port_unreachable());
+    iface.set_udp_port_unreachable(false);
+    assert!(!process(&mut iface));
+    iface.set_udp_port_unreachable(true);
+
+    iface.set_udp_port_unreachable_rate_limit(2, Duration::from_secs(1));
+    assert!(process(&mut iface));
+    assert!(process(&mut iface));
+    assert!(!process(&mut iface));
+
+    iface.inner.now += Duration::from_millis(1500);
+    assert!(process(&mut iface));
+    assert!(!process(&mut iface));
+    iface.inner.now += Duration::from_millis(500);
+    assert!(process(&mut iface));
+
+    iface.set_udp_port_unreachable_rate_limit(0, Duration::ZERO);
+    for _ in 0..100 {
+        assert!(process(&mut iface));
+    }
+}
+
+#[test]
+#[cfg(all(feature = "socket-tcp", feature = "proto-ipv4"))]
+fn test_tcp_reset_policy() {
+    use crate::wire::{TcpControl, TcpRepr, TcpSeqNumber};
+
+    let (mut iface, mut sockets, _device) = create(MEDIUM);
+
+    let src_addr = IpAddress::v4(127, 0, 0, 2);
+    let dst_addr = IpAddress::v4(127, 0, 0, 1);
+    let segment = |control, ack_number| {
+        let tcp_repr = TcpRepr {
+            src_port: 49152,
+            dst_port: 80,
+            control,
+            seq_number: TcpSeqNumber(1000),
+            ack_number,
+            window_len: 1024,
+            window_scale: None,
+            max_seg_size: None,
+            sack_permitted: false,
+            sack_ranges: [None, None, None],
+            timestamp: None,
+            urgent_at: None,
+            payload: &[],
+        };
+        let mut bytes = vec![0u8; tcp_repr.buffer_len()];
+        tcp_repr.emit(
+            &mut TcpPacket::new_unchecked(&mut bytes),
+            &src_addr,
+            &dst_addr,
+            &ChecksumCapabilities::default(),
+        );
+        bytes
+    };
+    let syn = segment(TcpControl::Syn, None);
+    let ack = segment(TcpControl::None, Some(TcpSeqNumber(2000)));
+    let ip_repr = IpRepr::new(src_addr, dst_addr, IpProtocol::Tcp, syn.len(), 64);
+    let mut process = |iface: &mut Interface, bytes: &[u8]| {
+        iface
+            .inner
+            .process_tcp(&mut sockets, ip_repr.clone(), bytes, true)
+            .is_some()
+    };
+
+    assert_eq!(iface.tcp_reset_policy(), TcpResetPolicy::STANDARD);
+    assert!(process(&mut iface, &syn));
+    assert!(process(&mut iface, &ack));
+
+    iface.set_tcp_reset_policy(TcpResetPolicy::STEALTH);
+    assert!(!process(&mut iface, &syn));
+    assert!(!process(&mut iface, &ack));
+
+    iface.set_tcp_reset_policy(TcpResetPolicy {
+        closed_ports: false,
+        unknown_connections: true,
+    });
+    assert!(!process(&mut iface, &syn));
+    assert!(process(&mut iface, &ack));
+
+    iface.set_tcp_reset_policy(TcpResetPolicy::STANDARD);
+    iface.set_tcp_reset_rate_limit(2, Duration::from_secs(1));
+    assert!(process(&mut iface, &syn));
+    assert!(process(&mut iface, &ack));
+    assert!(!process(&mut iface, &syn));
+
+    iface.inner.now += Duration::from_secs(1);
+    assert!(process(&mut iface, &syn));
+    assert!(!process(&mut iface, &ack));
+}
+
 #[test]
 #[cfg(feature = "socket-udp")]
 fn test_handle_udp_broadcast() {
@@ -467,6 +602,8 @@ fn test_handle_udp_broadcast() {
         next_header: IpProtocol::Udp,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
         hop_limit: 0x40,
+        traffic_class: 0,
+        flow_label: 0,
     });
     #[cfg(all(not(feature = "proto-ipv6"), feature = "proto-ipv4"))]
     let ip_repr = IpRepr::Ipv4(Ipv4Repr {
@@ -475,6 +612,7 @@ fn test_handle_udp_broadcast() {
         next_header: IpProtocol::Udp,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
         hop_limit: 0x40,
+        dscp: 0,
     });
 
     // Bind the socket to port 68
@@ -540,6 +678,7 @@ fn test_handle_ipv4_broadcast() {
         next_header: IpProtocol::Icmp,
         hop_limit: 64,
         payload_len: icmpv4_repr.buffer_len(),
+        dscp: 0,
     };
 
     // Emit to ip frame
@@ -568,6 +707,7 @@ fn test_handle_ipv4_broadcast() {
         next_header: IpProtocol::Icmp,
         hop_limit: 64,
         payload_len: expected_icmpv4_repr.buffer_len(),
+        dscp: 0,
     };
     let expected_packet = IpPacket::Icmpv4((expected_ipv4_repr, expected_icmpv4_repr));
 
@@ -637,6 +777,7 @@ fn test_icmp_reply_size() {
         next_header: IpProtocol::Udp,
         hop_limit: 64,
         payload_len: udp_repr.header_len() + MAX_PAYLOAD_LEN,
+        dscp: 0,
     };
     #[cfg(feature = "proto-ipv6")]
     let ip_repr = Ipv6Repr {
@@ -645,6 +786,8 @@ fn test_icmp_reply_size() {
         next_header: IpProtocol::Udp,
         hop_limit: 64,
         payload_len: udp_repr.header_len() + MAX_PAYLOAD_LEN,
+        traffic_class: 0,
+        flow_label: 0,
     };
     let payload = packet.into_inner();
 
@@ -662,6 +805,8 @@ fn test_icmp_reply_size() {
         next_header: IpProtocol::Icmpv6,
         hop_limit: 64,
         payload_len: expected_icmp_repr.buffer_len(),
+        traffic_class: 0,
+        flow_label: 0,
     };
     #[cfg(all(feature = "proto-ipv4", not(feature = "proto-ipv6")))]
     let expected_icmp_repr = Icmpv4Repr::DstUnreachable {
@@ -676,6 +821,7 @@ fn test_icmp_reply_size() {
         next_header: IpProtocol::Icmp,
         hop_limit: 64,
         payload_len: expected_icmp_repr.buffer_len(),
+        dscp: 0,
     };
 
     // The expected packet does not exceed the IPV4_MIN_MTU
@@ -717,6 +863,41 @@ fn test_icmp_reply_size() {
     );
 }
 
+#[test]
+#[cfg(feature = "medium-ethernet")]
+fn test_pause_frames() {
+    let (mut iface, mut sockets, mut device) = create_ethernet();
+
+    // The frames sent loop back, so the interface pauses itself.
+    assert!(iface.send_pause(&mut device, 100));
+    let frame = device.queue.front().unwrap();
+    let frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+    assert_eq!(frame.dst_addr(), PAUSE_MULTICAST_ADDR);
+    assert_eq!(frame.ethertype(), EthernetProtocol::MacControl);
+    let packet = PausePacket::new_checked(frame.payload()).unwrap();
+    assert_eq!(PauseRepr::parse(&packet), Ok(PauseRepr { quanta: 100 }));
+
+    // PAUSE frames are ignored until the link speed is known.
+    iface.poll(Instant::ZERO, &mut device, &mut sockets);
+    assert_eq!(iface.paused_until(), None);
+
+    // 100 quanta last 512µs at 100 Mbit/s.
+    iface.set_pause_link_speed(Some(100_000_000));
+    assert!(iface.send_pause(&mut device, 100));
+    iface.poll(Instant::ZERO, &mut device, &mut sockets);
+    assert_eq!(iface.paused_until(), Some(Instant::from_micros(512)));
+
+    assert!(iface.send_pause(&mut device, 0));
+    iface.poll(Instant::from_micros(100), &mut device, &mut sockets);
+    assert_eq!(iface.paused_until(), None);
+
+    assert!(iface.send_pause(&mut device, 100));
+    iface.poll(Instant::from_micros(200), &mut device, &mut sockets);
+    assert_eq!(iface.paused_until(), Some(Instant::from_micros(712)));
+    iface.poll(Instant::from_micros(712), &mut device, &mut sockets);
+    assert_eq!(iface.paused_until(), None);
+}
+
 #[test]
 #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
 fn test_handle_valid_arp_request() {
@@ -769,6 +950,260 @@ fn test_handle_valid_arp_request() {
     );
 }
 
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+fn test_handle_snap_arp_request() {
+    let (mut iface, mut sockets, _device) = create_ethernet();
+
+    let mut eth_bytes = vec![0u8; 60];
+
+    let local_ip_addr = Ipv4Address([0x7f, 0x00, 0x00, 0x01]);
+    let remote_ip_addr = Ipv4Address([0x7f, 0x00, 0x00, 0x02]);
+    let local_hw_addr = EthernetAddress([0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+
+    let repr = ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Request,
+        source_hardware_addr: remote_hw_addr,
+        source_protocol_addr: remote_ip_addr,
+        target_hardware_addr: EthernetAddress::default(),
+        target_protocol_addr: local_ip_addr,
+    };
+
+    // The ARP packet is followed by the padding of the frame
+    let frame_len = EthernetEncapsulation::Snap.header_len() + repr.buffer_len();
+    let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes[..frame_len]);
+    frame.set_dst_addr(EthernetAddress::BROADCAST);
+    frame.set_src_addr(remote_hw_addr);
+    frame.set_snap_header(EthernetProtocol::Arp);
+    let mut packet = ArpPacket::new_unchecked(frame.payload_mut());
+    repr.emit(&mut packet);
+
+    // Ensure an ARP Request in an LLC/SNAP frame triggers an ARP Reply
+    assert_eq!(
+        iface
+            .inner
+            .process_ethernet(&mut sockets, &eth_bytes, &mut iface.fragments),
+        Some(EthernetPacket::Arp(ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Reply,
+            source_hardware_addr: local_hw_addr,
+            source_protocol_addr: local_ip_addr,
+            target_hardware_addr: remote_hw_addr,
+            target_protocol_addr: remote_ip_addr
+        }))
+    );
+
+    // Ensure the IP MTU leaves room for the LLC and SNAP headers
+    let ip_mtu = iface.inner.ip_mtu();
+    iface.set_ethernet_encapsulation(EthernetEncapsulation::Snap);
+    assert_eq!(iface.inner.ip_mtu(), ip_mtu - ETHERNET_SNAP_HEADER_LEN);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+fn test_handle_proxy_arp_request() {
+    let (mut iface, mut sockets, _device) = create_ethernet();
+    iface.update_proxy_arp_prefixes(|prefixes| {
+        prefixes
+            .push(Ipv4Cidr::new(Ipv4Address::new(10, 0, 0, 0), 24))
+            .unwrap();
+    });
+
+    let local_hw_addr = EthernetAddress([0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+    let remote_ip_addr = Ipv4Address([0x7f, 0x00, 0x00, 0x02]);
+
+    let request = |source_protocol_addr, target_protocol_addr| {
+        let repr = ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: remote_hw_addr,
+            source_protocol_addr,
+            target_hardware_addr: EthernetAddress::default(),
+            target_protocol_addr,
+        };
+
+        let mut eth_bytes = vec![0u8; 42];
+        let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
+        frame.set_dst_addr(EthernetAddress::BROADCAST);
+        frame.set_src_addr(remote_hw_addr);
+        frame.set_ethertype(EthernetProtocol::Arp);
+        let mut packet = ArpPacket::new_unchecked(frame.payload_mut());
+        repr.emit(&mut packet);
+        eth_bytes
+    };
+
+    // Ensure an ARP Request for a proxied address is answered on its behalf
+    assert_eq!(
+        iface.inner.process_ethernet(
+            &mut sockets,
+            &request(remote_ip_addr, Ipv4Address::new(10, 0, 0, 7)),
+            &mut iface.fragments
+        ),
+        Some(EthernetPacket::Arp(ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Reply,
+            source_hardware_addr: local_hw_addr,
+            source_protocol_addr: Ipv4Address::new(10, 0, 0, 7),
+            target_hardware_addr: remote_hw_addr,
+            target_protocol_addr: remote_ip_addr
+        }))
+    );
+
+    // Ensure requests outside of the proxied prefixes are ignored
+    assert_eq!(
+        iface.inner.process_ethernet(
+            &mut sockets,
+            &request(remote_ip_addr, Ipv4Address::new(10, 0, 1, 7)),
+            &mut iface.fragments
+        ),
+        None
+    );
+
+    // Ensure requests from within the proxied prefix are ignored
+    assert_eq!(
+        iface.inner.process_ethernet(
+            &mut sockets,
+            &request(Ipv4Address::new(10, 0, 0, 8), Ipv4Address::new(10, 0, 0, 7)),
+            &mut iface.fragments
+        ),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+fn test_handle_rarp_request() {
+    let (mut iface, mut sockets, _device) = create_ethernet();
+
+    let local_ip_addr = Ipv4Address([0x7f, 0x00, 0x00, 0x01]);
+    let local_hw_addr = EthernetAddress([0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+    let unknown_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x01]);
+    let assigned_ip_addr = Ipv4Address([0x7f, 0x00, 0x00, 0x02]);
+    iface.update_rarp_table(|table| {
+        table.insert(remote_hw_addr, assigned_ip_addr).unwrap();
+    });
+
+    let request = |target_hardware_addr| {
+        let repr = ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::RequestReverse,
+            source_hardware_addr: target_hardware_addr,
+            source_protocol_addr: Ipv4Address::UNSPECIFIED,
+            target_hardware_addr,
+            target_protocol_addr: Ipv4Address::UNSPECIFIED,
+        };
+
+        let mut eth_bytes = vec![0u8; 42];
+        let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
+        frame.set_dst_addr(EthernetAddress::BROADCAST);
+        frame.set_src_addr(target_hardware_addr);
+        frame.set_ethertype(EthernetProtocol::Rarp);
+        let mut packet = ArpPacket::new_unchecked(frame.payload_mut());
+        repr.emit(&mut packet);
+        eth_bytes
+    };
+
+    // Ensure a Reverse ARP Request for a known hardware address is answered
+    assert_eq!(
+        iface
+            .inner
+            .process_ethernet(&mut sockets, &request(remote_hw_addr), &mut iface.fragments),
+        Some(EthernetPacket::Arp(ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::ReplyReverse,
+            source_hardware_addr: local_hw_addr,
+            source_protocol_addr: local_ip_addr,
+            target_hardware_addr: remote_hw_addr,
+            target_protocol_addr: assigned_ip_addr
+        }))
+    );
+
+    // Ensure requests for unknown hardware addresses are ignored
+    assert_eq!(
+        iface.inner.process_ethernet(
+            &mut sockets,
+            &request(unknown_hw_addr),
+            &mut iface.fragments
+        ),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+fn test_directed_broadcast_hardware_addr() {
+    let (mut iface, _sockets, _device) = create_ethernet();
+
+    // The directed broadcast address of 127.0.0.1/8 is sent to the Ethernet broadcast
+    // address, without resolving it first.
+    assert_eq!(
+        iface.inner.lookup_hardware_addr(
+            MockTxToken,
+            &IpAddress::v4(127, 0, 0, 1),
+            &IpAddress::v4(127, 255, 255, 255)
+        ),
+        Ok((
+            HardwareAddress::Ethernet(EthernetAddress::BROADCAST),
+            MockTxToken
+        ))
+    );
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+fn test_batch_neighbor() {
+    let (mut iface, _sockets, _device) = create_ethernet();
+
+    let local_ip_addr = IpAddress::v4(127, 0, 0, 1);
+    let remote_ip_addr = IpAddress::v4(127, 0, 0, 2);
+    let remote_hw_addr = HardwareAddress::Ethernet(EthernetAddress([0x52, 0x54, 0, 0, 0, 0]));
+    let other_hw_addr = HardwareAddress::Ethernet(EthernetAddress([0x52, 0x54, 0, 0, 0, 1]));
+
+    iface
+        .inner
+        .inject_neighbor(remote_ip_addr, remote_hw_addr, Instant::ZERO);
+    assert_eq!(
+        iface
+            .inner
+            .lookup_hardware_addr(MockTxToken, &local_ip_addr, &remote_ip_addr),
+        Ok((remote_hw_addr, MockTxToken))
+    );
+
+    // The destination is remembered until the end of the batch.
+    iface.inner.neighbor_cache.as_mut().unwrap().flush();
+    assert_eq!(
+        iface
+            .inner
+            .lookup_hardware_addr(MockTxToken, &local_ip_addr, &remote_ip_addr),
+        Ok((remote_hw_addr, MockTxToken))
+    );
+    iface.inner.end_batch();
+    assert_eq!(
+        iface
+            .inner
+            .lookup_hardware_addr(MockTxToken, &local_ip_addr, &remote_ip_addr),
+        Err(DispatchError::NeighborPending)
+    );
+
+    // Changes to the neighbor cache are seen at once.
+    iface
+        .inner
+        .inject_neighbor(remote_ip_addr, remote_hw_addr, Instant::ZERO);
+    assert_eq!(
+        iface
+            .inner
+            .lookup_hardware_addr(MockTxToken, &local_ip_addr, &remote_ip_addr),
+        Ok((remote_hw_addr, MockTxToken))
+    );
+    iface
+        .inner
+        .inject_neighbor(remote_ip_addr, other_hw_addr, Instant::ZERO);
+    assert_eq!(
+        iface
+            .inner
+            .lookup_hardware_addr(MockTxToken, &local_ip_addr, &remote_ip_addr),
+        Ok((other_hw_addr, MockTxToken))
+    );
+}
+
 #[test]
 #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
 fn test_handle_valid_ndisc_request() {
@@ -784,6 +1219,7 @@ fn test_handle_valid_ndisc_request() {
     let solicit = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
         target_addr: local_ip_addr,
         lladdr: Some(remote_hw_addr.into()),
+        aro: None,
     });
     let ip_repr = IpRepr::Ipv6(Ipv6Repr {
         src_addr: remote_ip_addr,
@@ -791,6 +1227,8 @@ fn test_handle_valid_ndisc_request() {
         next_header: IpProtocol::Icmpv6,
         hop_limit: 0xff,
         payload_len: solicit.buffer_len(),
+        traffic_class: 0,
+        flow_label: 0,
     });
 
     let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
@@ -806,9 +1244,10 @@ fn test_handle_valid_ndisc_request() {
     );
 
     let icmpv6_expected = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
-        flags: NdiscNeighborFlags::SOLICITED,
+        flags: NdiscNeighborFlags::SOLICITED | NdiscNeighborFlags::OVERRIDE,
         target_addr: local_ip_addr,
         lladdr: Some(local_hw_addr.into()),
+        aro: None,
     });
 
     let ipv6_expected = Ipv6Repr {
@@ -817,6 +1256,8 @@ fn test_handle_valid_ndisc_request() {
         next_header: IpProtocol::Icmpv6,
         hop_limit: 0xff,
         payload_len: icmpv6_expected.buffer_len(),
+        traffic_class: 0,
+        flow_label: 0,
     };
 
     // Ensure an Neighbor Solicitation triggers a Neighbor Advertisement
@@ -993,6 +1434,7 @@ fn test_icmpv4_socket() {
         next_header: IpProtocol::Icmp,
         payload_len: 24,
         hop_limit: 64,
+        dscp: 0,
     };
     let ip_repr = IpRepr::Ipv4(ipv4_repr);
 
@@ -1027,6 +1469,72 @@ fn test_icmpv4_socket() {
     );
 }
 
+#[test]
+#[cfg(all(feature = "socket-icmp", feature = "proto-ipv4"))]
+fn test_icmpv4_socket_demux() {
+    use crate::wire::Icmpv4Packet;
+
+    let (mut iface, mut sockets, _device) = create(MEDIUM);
+
+    let mut socket = |ident, filter| {
+        let rx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 24]);
+        let tx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 24]);
+        let mut socket = icmp::Socket::new(rx_buffer, tx_buffer);
+        socket.bind(icmp::Endpoint::Ident(ident)).unwrap();
+        socket.set_filter(filter);
+        sockets.add(socket)
+    };
+    let mut replies_only = icmp::Filter::block_all();
+    replies_only.set_ipv4(Icmpv4Message::EchoReply, true);
+    let first = socket(0x1234, icmp::Filter::pass_all());
+    let second = socket(0x1234, replies_only);
+    let other = socket(0x4321, icmp::Filter::pass_all());
+
+    let ip_repr = IpRepr::Ipv4(Ipv4Repr {
+        src_addr: Ipv4Address::new(0x7f, 0x00, 0x00, 0x02),
+        dst_addr: Ipv4Address::new(0x7f, 0x00, 0x00, 0x01),
+        next_header: IpProtocol::Icmp,
+        payload_len: 24,
+        hop_limit: 64,
+        dscp: 0,
+    });
+    let mut process = |iface: &mut Interface, sockets: &mut SocketSet, repr: Icmpv4Repr| {
+        let mut bytes = [0xff; 24];
+        repr.emit(
+            &mut Icmpv4Packet::new_unchecked(&mut bytes[..]),
+            &ChecksumCapabilities::default(),
+        );
+        iface.inner.process_icmpv4(sockets, ip_repr.clone(), &bytes);
+    };
+
+    // Every socket bound to the identifier receives the message, if its filter passes it.
+    process(
+        &mut iface,
+        &mut sockets,
+        Icmpv4Repr::EchoRequest {
+            ident: 0x1234,
+            seq_no: 1,
+            data: &[0xff; 16],
+        },
+    );
+    assert!(sockets.get_mut::<icmp::Socket>(first).recv().is_ok());
+    assert!(!sockets.get_mut::<icmp::Socket>(second).can_recv());
+    assert!(!sockets.get_mut::<icmp::Socket>(other).can_recv());
+
+    process(
+        &mut iface,
+        &mut sockets,
+        Icmpv4Repr::EchoReply {
+            ident: 0x1234,
+            seq_no: 1,
+            data: &[0xff; 16],
+        },
+    );
+    assert!(sockets.get_mut::<icmp::Socket>(first).recv().is_ok());
+    assert!(sockets.get_mut::<icmp::Socket>(second).recv().is_ok());
+    assert!(!sockets.get_mut::<icmp::Socket>(other).can_recv());
+}
+
 #[test]
 #[cfg(feature = "proto-ipv6")]
 fn test_solicited_node_addrs() {
@@ -1071,6 +1579,8 @@ fn test_icmpv6_nxthdr_unknown() {
         next_header: IpProtocol::HopByHop,
         payload_len: 12,
         hop_limit: 0x40,
+        traffic_class: 0,
+        flow_label: 0,
     };
 
     let mut bytes = vec![0; 52];
@@ -1096,11 +1606,13 @@ fn test_icmpv6_nxthdr_unknown() {
         Ipv6Packet::new_unchecked(&bytes)
     };
 
+    // The pointer is to the Next Header field of the Hop-by-Hop Options header, and
+    // the data includes the header.
     let reply_icmp_repr = Icmpv6Repr::ParamProblem {
         reason: Icmpv6ParamProblem::UnrecognizedNxtHdr,
         pointer: 40,
         header: ipv6_repr,
-        data: &payload[..],
+        data: &bytes[40..],
     };
 
     let reply_ipv6_repr = Ipv6Repr {
@@ -1109,6 +1621,8 @@ fn test_icmpv6_nxthdr_unknown() {
         next_header: IpProtocol::Icmpv6,
         payload_len: reply_icmp_repr.buffer_len(),
         hop_limit: 0x40,
+        traffic_class: 0,
+        flow_label: 0,
     };
 
     // Ensure the unknown next header causes a ICMPv6 Parameter Problem
@@ -1119,35 +1633,36 @@ fn test_icmpv6_nxthdr_unknown() {
     );
 }
 
+#[cfg(feature = "proto-igmp")]
+fn recv_igmp(device: &mut Loopback, timestamp: Instant) -> Vec<(Ipv4Repr, IgmpRepr)> {
+    let caps = device.capabilities();
+    let checksum_caps = &caps.checksum;
+    recv_all(device, timestamp)
+        .iter()
+        .filter_map(|frame| {
+            let ipv4_packet = match caps.medium {
+                #[cfg(feature = "medium-ethernet")]
+                Medium::Ethernet => {
+                    let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+                    Ipv4Packet::new_checked(eth_frame.payload()).ok()?
+                }
+                #[cfg(feature = "medium-ip")]
+                Medium::Ip => Ipv4Packet::new_checked(&frame[..]).ok()?,
+                #[cfg(feature = "medium-ieee802154")]
+                Medium::Ieee802154 => todo!(),
+            };
+            let ipv4_repr = Ipv4Repr::parse(&ipv4_packet, checksum_caps).ok()?;
+            let ip_payload = ipv4_packet.payload();
+            let igmp_packet = IgmpPacket::new_checked(ip_payload).ok()?;
+            let igmp_repr = IgmpRepr::parse(&igmp_packet).ok()?;
+            Some((ipv4_repr, igmp_repr))
+        })
+        .collect::<Vec<_>>()
+}
+
 #[test]
 #[cfg(feature = "proto-igmp")]
 fn test_handle_igmp() {
-    fn recv_igmp(device: &mut Loopback, timestamp: Instant) -> Vec<(Ipv4Repr, IgmpRepr)> {
-        let caps = device.capabilities();
-        let checksum_caps = &caps.checksum;
-        recv_all(device, timestamp)
-            .iter()
-            .filter_map(|frame| {
-                let ipv4_packet = match caps.medium {
-                    #[cfg(feature = "medium-ethernet")]
-                    Medium::Ethernet => {
-                        let eth_frame = EthernetFrame::new_checked(frame).ok()?;
-                        Ipv4Packet::new_checked(eth_frame.payload()).ok()?
-                    }
-                    #[cfg(feature = "medium-ip")]
-                    Medium::Ip => Ipv4Packet::new_checked(&frame[..]).ok()?,
-                    #[cfg(feature = "medium-ieee802154")]
-                    Medium::Ieee802154 => todo!(),
-                };
-                let ipv4_repr = Ipv4Repr::parse(&ipv4_packet, checksum_caps).ok()?;
-                let ip_payload = ipv4_packet.payload();
-                let igmp_packet = IgmpPacket::new_checked(ip_payload).ok()?;
-                let igmp_repr = IgmpRepr::parse(&igmp_packet).ok()?;
-                Some((ipv4_repr, igmp_repr))
-            })
-            .collect::<Vec<_>>()
-    }
-
     let groups = [
         Ipv4Address::new(224, 0, 0, 22),
         Ipv4Address::new(224, 0, 0, 56),
@@ -1196,7 +1711,7 @@ fn test_handle_igmp() {
     // loopback have been processed, including responses to
     // GENERAL_QUERY_BYTES. Therefore `recv_all()` would return 0
     // pkts that could be checked.
-    iface.socket_ingress(&mut device, &mut sockets);
+    iface.socket_ingress(&mut device, &mut sockets, &mut None);
 
     // Leave multicast groups
     let timestamp = Instant::now();
@@ -1215,6 +1730,61 @@ fn test_handle_igmp() {
     }
 }
 
+#[test]
+#[cfg(all(feature = "proto-igmp", feature = "socket-udp"))]
+fn test_socket_multicast_membership() {
+    let group = Ipv4Address::new(224, 0, 0, 56);
+
+    let (mut iface, mut sockets, mut device) = create(MEDIUM);
+
+    let rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 15]);
+    let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 15]);
+    let mut udp_socket = udp::Socket::new(rx_buffer, tx_buffer);
+    udp_socket.bind(5353).unwrap();
+    assert_eq!(udp_socket.join_multicast(group), Ok(true));
+    let handle = sockets.add(udp_socket);
+
+    // The first socket joining a group makes the interface report membership. The
+    // memberships are synced at the start of every poll; sync directly so that the
+    // loopback device doesn't swallow the reports.
+    let timestamp = Instant::now();
+    iface.sync_multicast_groups(&mut device, &sockets);
+    assert!(iface.has_multicast_group(group));
+    let reports = recv_igmp(&mut device, timestamp);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(
+        reports[0].1,
+        IgmpRepr::MembershipReport {
+            group_addr: group,
+            version: IgmpVersion::Version2,
+        }
+    );
+
+    // Joining explicitly as well does not report again, and leaving explicitly keeps
+    // the membership of the socket.
+    assert_eq!(
+        iface.join_multicast_group(&mut device, group, timestamp),
+        Ok(false)
+    );
+    assert_eq!(
+        iface.leave_multicast_group(&mut device, group, timestamp),
+        Ok(false)
+    );
+    iface.sync_multicast_groups(&mut device, &sockets);
+    assert!(iface.has_multicast_group(group));
+    assert!(recv_igmp(&mut device, timestamp).is_empty());
+
+    // The last socket leaving the group makes the interface leave it.
+    assert!(sockets
+        .get_mut::<udp::Socket>(handle)
+        .leave_multicast(group));
+    iface.sync_multicast_groups(&mut device, &sockets);
+    assert!(!iface.has_multicast_group(group));
+    let leaves = recv_igmp(&mut device, timestamp);
+    assert_eq!(leaves.len(), 1);
+    assert_eq!(leaves[0].1, IgmpRepr::LeaveGroup { group_addr: group });
+}
+
 #[test]
 #[cfg(all(feature = "proto-ipv4", feature = "socket-raw"))]
 fn test_raw_socket_no_reply() {
@@ -1257,6 +1827,7 @@ fn test_raw_socket_no_reply() {
         next_header: IpProtocol::Udp,
         hop_limit: 64,
         payload_len: udp_repr.header_len() + PAYLOAD_LEN,
+        dscp: 0,
     };
 
     // Emit to frame
@@ -1348,6 +1919,7 @@ fn test_raw_socket_with_udp_socket() {
         next_header: IpProtocol::Udp,
         hop_limit: 64,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
+        dscp: 0,
     };
 
     // Emit to frame
@@ -1450,7 +2022,7 @@ fn test_echo_request_sixlowpan_128_bytes() {
         &request_first_part_iphc_packet,
         ieee802154_repr.src_addr,
         ieee802154_repr.dst_addr,
-        &iface.inner.sixlowpan_address_context,
+        &iface.inner.sixlowpan_address_contexts(),
     )
     .unwrap();
 
@@ -1523,6 +2095,8 @@ fn test_echo_request_sixlowpan_128_bytes() {
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 136,
                 hop_limit: 64,
+                traffic_class: 0,
+                flow_label: 0,
             },
             Icmpv6Repr::EchoReply {
                 ident: 39,
@@ -1578,9 +2152,231 @@ fn test_echo_request_sixlowpan_128_bytes() {
     );
 }
 
-#[cfg(all(
-    not(feature = "medium-ethernet"),
-    feature = "proto-sixlowpan",
+#[test]
+#[cfg(feature = "medium-ieee802154")]
+fn test_sixlowpan_context_learned() {
+    let (mut iface, mut sockets, _device) = create_ieee802154();
+
+    let prefix = Ipv6Cidr::new(Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0), 64);
+    let router_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    let advertise = |iface: &mut Interface, sockets: &mut SocketSet, lifetime| {
+        let icmp_repr = Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+            hop_limit: 64,
+            flags: NdiscRouterFlags::empty(),
+            router_lifetime: Duration::from_secs(1800),
+            reachable_time: Duration::ZERO,
+            retrans_time: Duration::ZERO,
+            lladdr: None,
+            mtu: None,
+            prefix_info: None,
+            captive_portal: None,
+            pref64: None,
+            sixlowpan_context: Some(NdiscSixlowpanContext {
+                context_id: 3,
+                compression: true,
+                valid_lifetime: lifetime,
+                prefix,
+            }),
+        });
+        let ipv6_repr = Ipv6Repr {
+            src_addr: router_addr,
+            dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 0xff,
+            traffic_class: 0,
+            flow_label: 0,
+        };
+        let mut bytes = vec![0; ipv6_repr.buffer_len() + icmp_repr.buffer_len()];
+        ipv6_repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes));
+        icmp_repr.emit(
+            &router_addr.into(),
+            &Ipv6Address::LINK_LOCAL_ALL_NODES.into(),
+            &mut Icmpv6Packet::new_unchecked(&mut bytes[ipv6_repr.buffer_len()..]),
+            &ChecksumCapabilities::default(),
+        );
+        iface
+            .inner
+            .process_ipv6(sockets, &Ipv6Packet::new_unchecked(&bytes));
+    };
+
+    // The context is learned from the router advertisement, and expires with it.
+    let context = SixlowpanAddressContext([0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x01]);
+    advertise(&mut iface, &mut sockets, Duration::from_secs(600));
+    assert_eq!(iface.sixlowpan_address_context(3), Some(context));
+    assert_eq!(iface.sixlowpan_address_context(0), None);
+    iface.inner.now = Instant::from_secs(600);
+    assert_eq!(iface.sixlowpan_address_context(3), None);
+
+    // A zero lifetime withdraws the context.
+    advertise(&mut iface, &mut sockets, Duration::from_secs(600));
+    advertise(&mut iface, &mut sockets, Duration::ZERO);
+    assert_eq!(iface.sixlowpan_address_context(3), None);
+
+    // Contexts configured by hand don't expire.
+    iface.set_sixlowpan_address_context(0, Some(context));
+    iface.inner.now = Instant::from_secs(100_000);
+    assert_eq!(iface.sixlowpan_address_context(0), Some(context));
+    iface.set_sixlowpan_address_context(0, None);
+    assert_eq!(iface.sixlowpan_address_context(0), None);
+}
+
+#[test]
+#[cfg(feature = "medium-ieee802154")]
+fn test_address_registration() {
+    let (mut iface, mut sockets, mut device) = create_ieee802154();
+    let link_local_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+    iface.update_ip_addrs(|ip_addrs| {
+        ip_addrs
+            .push(IpCidr::new(link_local_addr.into(), 64))
+            .unwrap();
+    });
+    iface.set_address_registration(Some(Duration::from_secs(600)));
+    while iface.poll_address_event().is_some() {}
+
+    let addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1);
+    let router_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    let router_lladdr = Ieee802154Address::Extended([0x02, 0, 0, 0, 0, 0, 0, 0x01]);
+    let advertise = |iface: &mut Interface, sockets: &mut SocketSet, router_lifetime| {
+        let icmp_repr = Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+            hop_limit: 64,
+            flags: NdiscRouterFlags::empty(),
+            router_lifetime,
+            reachable_time: Duration::ZERO,
+            retrans_time: Duration::ZERO,
+            lladdr: Some(router_lladdr.into()),
+            mtu: None,
+            prefix_info: None,
+            captive_portal: None,
+            pref64: None,
+            sixlowpan_context: None,
+        });
+        let ipv6_repr = Ipv6Repr {
+            src_addr: router_addr,
+            dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 0xff,
+            traffic_class: 0,
+            flow_label: 0,
+        };
+        let mut bytes = vec![0; ipv6_repr.buffer_len() + icmp_repr.buffer_len()];
+        ipv6_repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes));
+        icmp_repr.emit(
+            &router_addr.into(),
+            &Ipv6Address::LINK_LOCAL_ALL_NODES.into(),
+            &mut Icmpv6Packet::new_unchecked(&mut bytes[ipv6_repr.buffer_len()..]),
+            &ChecksumCapabilities::default(),
+        );
+        iface
+            .inner
+            .process_ipv6(sockets, &Ipv6Packet::new_unchecked(&bytes));
+    };
+    let reply = |iface: &mut Interface, target_addr, status| {
+        let ip_repr = Ipv6Repr {
+            src_addr: router_addr,
+            dst_addr: target_addr,
+            next_header: IpProtocol::Icmpv6,
+            hop_limit: 0xff,
+            payload_len: 40,
+            traffic_class: 0,
+            flow_label: 0,
+        };
+        let advert = NdiscRepr::NeighborAdvert {
+            flags: NdiscNeighborFlags::SOLICITED,
+            target_addr,
+            lladdr: None,
+            aro: Some(NdiscAddressRegistration {
+                status,
+                lifetime: Duration::from_secs(600),
+                eui64: [0; 8],
+            }),
+        };
+        assert_eq!(iface.inner.process_ndisc(ip_repr, advert), None);
+    };
+
+    // Nothing is registered before a router is known.
+    assert!(!iface.registration_egress(&mut device));
+    assert_eq!(iface.poll_at(Instant::from_millis(0), &sockets), None);
+
+    advertise(&mut iface, &mut sockets, Duration::from_secs(1800));
+    assert_eq!(iface.registration_router(), Some(router_addr));
+    assert_eq!(
+        iface.poll_at(Instant::from_millis(0), &sockets),
+        Some(Instant::from_millis(0))
+    );
+
+    // Each address is registered with a Neighbor Solicitation sent to the router.
+    assert!(iface.registration_egress(&mut device));
+    assert_eq!(device.queue.len(), 2);
+    let frame = device.queue.pop_front().unwrap();
+    device.queue.clear();
+    let frame = Ieee802154Frame::new_checked(&frame[..]).unwrap();
+    assert_eq!(frame.dst_addr(), Some(router_lladdr));
+    let iphc_packet = SixlowpanIphcPacket::new_checked(frame.payload().unwrap()).unwrap();
+    let iphc_repr =
+        SixlowpanIphcRepr::parse(&iphc_packet, frame.src_addr(), frame.dst_addr(), &[]).unwrap();
+    assert_eq!(iphc_repr.src_addr, addr);
+    assert_eq!(iphc_repr.dst_addr, router_addr);
+    let icmp_packet = Icmpv6Packet::new_checked(iphc_packet.payload()).unwrap();
+    assert_eq!(
+        NdiscRepr::parse(&icmp_packet),
+        Ok(NdiscRepr::NeighborSolicit {
+            target_addr: addr,
+            lladdr: Some(Ieee802154Address::default().into()),
+            aro: Some(NdiscAddressRegistration {
+                status: NdiscAddressRegistrationStatus::Success,
+                lifetime: Duration::from_secs(600),
+                eui64: [0; 8],
+            }),
+        })
+    );
+
+    // It is retransmitted until the router answers.
+    assert!(!iface.registration_egress(&mut device));
+    assert_eq!(
+        iface.poll_at(Instant::from_millis(0), &sockets),
+        Some(Instant::from_secs(1))
+    );
+
+    // And refreshed before it expires once the router accepted it.
+    reply(&mut iface, addr, NdiscAddressRegistrationStatus::Success);
+    reply(
+        &mut iface,
+        link_local_addr,
+        NdiscAddressRegistrationStatus::Success,
+    );
+    assert!(iface.ip_addr_registered(addr));
+    assert_eq!(
+        iface.poll_at(Instant::from_millis(0), &sockets),
+        Some(Instant::from_secs(450))
+    );
+
+    // New addresses skip Duplicate Address Detection, and are removed when the router
+    // reports another node registered them.
+    let addr2 = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2);
+    let cidr2 = IpCidr::new(addr2.into(), 64);
+    assert_eq!(iface.add_ip_addr(cidr2), Ok(()));
+    assert_eq!(iface.ip_addr_state(addr2), Some(AddressState::Preferred));
+    assert!(iface.registration_egress(&mut device));
+    device.queue.clear();
+    reply(&mut iface, addr2, NdiscAddressRegistrationStatus::Duplicate);
+    assert_eq!(iface.ip_addr_state(addr2), None);
+    assert_eq!(iface.poll_address_event(), Some(AddressEvent::Added(cidr2)));
+    assert_eq!(
+        iface.poll_address_event(),
+        Some(AddressEvent::Duplicate(cidr2))
+    );
+
+    // A zero router lifetime withdraws the router.
+    advertise(&mut iface, &mut sockets, Duration::ZERO);
+    assert_eq!(iface.registration_router(), None);
+    assert!(!iface.ip_addr_registered(addr));
+}
+
+#[cfg(all(
+    not(feature = "medium-ethernet"),
+    feature = "proto-sixlowpan",
     feature = "proto-sixlowpan-fragmentation"
 ))]
 #[test]
@@ -1688,6 +2484,8 @@ fn test_sixlowpan_udp_with_fragmentation() {
                 next_header: IpProtocol::Udp,
                 payload_len: udp_data.len(),
                 hop_limit: 64,
+                traffic_class: 0,
+                flow_label: 0,
             }),
             UdpRepr {
                 src_port: 1234,
@@ -1728,3 +2526,2177 @@ fn test_sixlowpan_udp_with_fragmentation() {
         ]
     );
 }
+
+#[cfg(all(
+    feature = "medium-ieee802154",
+    feature = "proto-sixlowpan-fragmentation",
+    feature = "socket-udp"
+))]
+#[test]
+fn test_sixlowpan_reassembly_pool() {
+    use crate::phy::Checksum;
+
+    let mut device = Loopback::new(Medium::Ieee802154);
+    let mut config = Config::new();
+    config.hardware_addr = Some(Ieee802154Address::default().into());
+    config.sixlowpan_reassembly_contexts = 2;
+    let mut iface = Interface::new(config, &mut device);
+    iface.inner.caps.checksum.udp = Checksum::None;
+    let mut sockets = SocketSet::new(vec![]);
+    let udp_rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 4], vec![0; 1024]);
+    let udp_tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 64]);
+    let handle = sockets.add(udp::Socket::new(udp_rx_buffer, udp_tx_buffer));
+    sockets.get_mut::<udp::Socket>(handle).bind(6969).unwrap();
+
+    let ieee802154_repr = |src: u8| Ieee802154Repr {
+        frame_type: Ieee802154FrameType::Data,
+        security_enabled: false,
+        frame_pending: false,
+        ack_request: false,
+        sequence_number: Some(5),
+        pan_id_compression: true,
+        frame_version: Ieee802154FrameVersion::Ieee802154_2003,
+        dst_pan_id: Some(Ieee802154Pan(0xbeef)),
+        dst_addr: Some(Ieee802154Address::Extended([
+            0x90, 0xfc, 0x48, 0xc2, 0xa4, 0x41, 0xfc, 0x76,
+        ])),
+        src_pan_id: Some(Ieee802154Pan(0xbeef)),
+        src_addr: Some(Ieee802154Address::Extended([
+            0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x0b, src,
+        ])),
+    };
+    let sender = |src: u8| -> IpAddress {
+        Ipv6Address([
+            0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0x40, 0x42, 0x42, 0x42, 0x42, 0x42, 0x0b, src,
+        ])
+        .into()
+    };
+    // The two fragments of a UDP datagram to port 6969, with the tag 0x0092.
+    let first_part = &[
+        0xc0, 0xbc, 0x00, 0x92, 0x6e, 0x33, 0x07, 0xe7, 0xdc, 0xf0, 0xd3, 0xc9, 0x1b, 0x39, 0xbf,
+        0xa0, 0x4c, 0x6f, 0x72, 0x65, 0x6d, 0x20, 0x69, 0x70, 0x73, 0x75, 0x6d, 0x20, 0x64, 0x6f,
+        0x6c, 0x6f, 0x72, 0x20, 0x73, 0x69, 0x74, 0x20, 0x61, 0x6d, 0x65, 0x74, 0x2c, 0x20, 0x63,
+        0x6f, 0x6e, 0x73, 0x65, 0x63, 0x74, 0x65, 0x74, 0x75, 0x72, 0x20, 0x61, 0x64, 0x69, 0x70,
+        0x69, 0x73, 0x63, 0x69, 0x6e, 0x67, 0x20, 0x65, 0x6c, 0x69, 0x74, 0x2e, 0x20, 0x49, 0x6e,
+        0x20, 0x61, 0x74, 0x20, 0x72, 0x68, 0x6f, 0x6e, 0x63, 0x75, 0x73, 0x20, 0x74, 0x6f, 0x72,
+        0x74, 0x6f, 0x72, 0x2e, 0x20, 0x43, 0x72, 0x61, 0x73, 0x20, 0x62, 0x6c, 0x61, 0x6e,
+    ];
+    let second_part = &[
+        0xe0, 0xbc, 0x00, 0x92, 0x11, 0x64, 0x69, 0x74, 0x20, 0x74, 0x65, 0x6c, 0x6c, 0x75, 0x73,
+        0x20, 0x64, 0x69, 0x61, 0x6d, 0x2c, 0x20, 0x76, 0x61, 0x72, 0x69, 0x75, 0x73, 0x20, 0x76,
+        0x65, 0x73, 0x74, 0x69, 0x62, 0x75, 0x6c, 0x75, 0x6d, 0x20, 0x6e, 0x69, 0x62, 0x68, 0x20,
+        0x63, 0x6f, 0x6d, 0x6d, 0x6f, 0x64, 0x6f, 0x20, 0x6e, 0x65, 0x63, 0x2e,
+    ];
+    let mut receive = |iface: &mut Interface, src: u8, fragment: &[u8]| {
+        let repr = ieee802154_repr(src);
+        let result =
+            iface
+                .inner
+                .process_sixlowpan(&mut sockets, &repr, fragment, &mut iface.fragments);
+        assert_eq!(result, None);
+    };
+
+    // Two neighbors sending with the same tag don't mix up their fragments.
+    receive(&mut iface, 0x1a, first_part);
+    receive(&mut iface, 0x1b, first_part);
+    assert_eq!(iface.sixlowpan_reassembly_stats().in_use, 2);
+    receive(&mut iface, 0x1b, second_part);
+    receive(&mut iface, 0x1a, second_part);
+    assert_eq!(iface.sixlowpan_reassembly_stats().in_use, 0);
+
+    // A third neighbor evicts the packet closest to its timeout.
+    iface.inner.now = Instant::from_millis(0);
+    receive(&mut iface, 0x1a, first_part);
+    iface.inner.now = Instant::from_millis(10);
+    receive(&mut iface, 0x1b, first_part);
+    receive(&mut iface, 0x1c, first_part);
+    let stats = iface.sixlowpan_reassembly_stats();
+    assert_eq!(stats.capacity, 2);
+    assert_eq!(stats.in_use, 2);
+    assert_eq!(stats.evicted, 1);
+    receive(&mut iface, 0x1c, second_part);
+    receive(&mut iface, 0x1b, second_part);
+
+    let socket = sockets.get_mut::<udp::Socket>(handle);
+    for src in [0x1b, 0x1a, 0x1c, 0x1b] {
+        assert_eq!(
+            socket.recv().map(|(_, endpoint)| endpoint.addr),
+            Ok(sender(src))
+        );
+    }
+    assert!(!socket.can_recv());
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "socket-packet"))]
+fn test_packet_socket_loopback() {
+    use crate::socket::packet;
+
+    const LLDP: EthernetProtocol = EthernetProtocol::Unknown(0x88cc);
+
+    let (mut iface, mut sockets, mut device) = create_ethernet();
+
+    let buffer = || packet::PacketBuffer::new(vec![packet::PacketMetadata::EMPTY; 2], vec![0; 128]);
+    let lldp = sockets.add(packet::Socket::new(Some(LLDP), buffer(), buffer()));
+    let any = sockets.add(packet::Socket::new(None, buffer(), buffer()));
+
+    let mut frame = [0u8; 18];
+    {
+        let mut eth_frame = EthernetFrame::new_unchecked(&mut frame[..]);
+        eth_frame.set_dst_addr(EthernetAddress([0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e]));
+        eth_frame.set_src_addr(EthernetAddress::default());
+        eth_frame.set_ethertype(LLDP);
+        eth_frame
+            .payload_mut()
+            .copy_from_slice(&[0xaa, 0x00, 0x00, 0xff]);
+    }
+    sockets
+        .get_mut::<packet::Socket>(lldp)
+        .send_slice(&frame)
+        .unwrap();
+
+    // The frame goes out on the device as-is, and the loopback device brings it back.
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+
+    assert_eq!(
+        sockets.get_mut::<packet::Socket>(lldp).recv(),
+        Ok(&frame[..])
+    );
+    assert_eq!(
+        sockets.get_mut::<packet::Socket>(any).recv(),
+        Ok(&frame[..])
+    );
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+fn test_add_ip_addr_dad() {
+    let (mut iface, mut sockets, mut device) = create_ethernet();
+    while iface.poll_address_event().is_some() {}
+
+    let addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2);
+    let cidr = IpCidr::new(addr.into(), 64);
+    assert_eq!(iface.add_ip_addr(cidr), Ok(()));
+    assert_eq!(iface.add_ip_addr(cidr), Err(AddressError::AlreadyAssigned));
+    assert_eq!(iface.ip_addr_state(addr), Some(AddressState::Tentative));
+    assert!(!iface.has_ip_addr(addr));
+    assert_eq!(
+        iface.poll_at(Instant::from_millis(0), &sockets),
+        Some(Instant::from_millis(0))
+    );
+
+    // A Neighbor Solicitation probes the address.
+    assert!(iface.address_egress(&mut device));
+    let frame = device.queue.pop_front().unwrap();
+    let frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+    let ipv6_packet = Ipv6Packet::new_checked(frame.payload()).unwrap();
+    assert_eq!(ipv6_packet.src_addr(), Ipv6Address::UNSPECIFIED);
+    assert_eq!(ipv6_packet.dst_addr(), addr.solicited_node());
+    let icmp_packet = Icmpv6Packet::new_checked(ipv6_packet.payload()).unwrap();
+    assert_eq!(
+        NdiscRepr::parse(&icmp_packet),
+        Ok(NdiscRepr::NeighborSolicit {
+            target_addr: addr,
+            lladdr: None,
+            aro: None,
+        })
+    );
+    assert!(!iface.address_egress(&mut device));
+    assert_eq!(
+        iface.poll_at(Instant::from_millis(0), &sockets),
+        Some(Instant::from_secs(1))
+    );
+
+    // Nobody claimed the address after a second.
+    iface.poll(Instant::from_secs(1), &mut device, &mut sockets);
+    assert_eq!(iface.ip_addr_state(addr), Some(AddressState::Preferred));
+    assert!(iface.has_ip_addr(addr));
+    assert_eq!(iface.poll_address_event(), Some(AddressEvent::Added(cidr)));
+    assert_eq!(
+        iface.poll_address_event(),
+        Some(AddressEvent::StateChanged(cidr, AddressState::Preferred))
+    );
+    assert_eq!(iface.poll_address_event(), None);
+
+    assert_eq!(iface.remove_ip_addr(addr), Some(cidr));
+    assert_eq!(iface.remove_ip_addr(addr), None);
+    assert_eq!(
+        iface.poll_address_event(),
+        Some(AddressEvent::Removed(cidr))
+    );
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+fn test_add_ip_addr_duplicate() {
+    let (mut iface, _sockets, _device) = create_ethernet();
+    while iface.poll_address_event().is_some() {}
+
+    let addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2);
+    let cidr = IpCidr::new(addr.into(), 64);
+    assert_eq!(iface.add_ip_addr(cidr), Ok(()));
+
+    // The owner of the address answers the probe.
+    let ip_repr = Ipv6Repr {
+        src_addr: addr,
+        dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+        next_header: IpProtocol::Icmpv6,
+        hop_limit: 0xff,
+        payload_len: 32,
+        traffic_class: 0,
+        flow_label: 0,
+    };
+    let advert = NdiscRepr::NeighborAdvert {
+        flags: NdiscNeighborFlags::OVERRIDE,
+        target_addr: addr,
+        lladdr: Some(EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]).into()),
+        aro: None,
+    };
+    assert_eq!(iface.inner.process_ndisc(ip_repr, advert), None);
+
+    assert_eq!(iface.ip_addr_state(addr), None);
+    assert_eq!(iface.poll_address_event(), Some(AddressEvent::Added(cidr)));
+    assert_eq!(
+        iface.poll_address_event(),
+        Some(AddressEvent::Duplicate(cidr))
+    );
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+fn test_handle_proxy_ndisc_request() {
+    let (mut iface, _sockets, _device) = create_ethernet();
+    iface.update_proxy_nd_prefixes(|prefixes| {
+        prefixes
+            .push(Ipv6Cidr::new(
+                Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0),
+                64,
+            ))
+            .unwrap();
+    });
+
+    let local_ip_addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1);
+    let remote_ip_addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2);
+    let proxied_ip_addr = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 7);
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+
+    let solicit = |src_addr: Ipv6Address| {
+        (
+            Ipv6Repr {
+                src_addr,
+                dst_addr: proxied_ip_addr.solicited_node(),
+                next_header: IpProtocol::Icmpv6,
+                hop_limit: 0xff,
+                payload_len: 32,
+                traffic_class: 0,
+                flow_label: 0,
+            },
+            NdiscRepr::NeighborSolicit {
+                target_addr: proxied_ip_addr,
+                lladdr: (!src_addr.is_unspecified()).then(|| remote_hw_addr.into()),
+                aro: None,
+            },
+        )
+    };
+
+    // Ensure a Neighbor Solicitation for a proxied address is answered on its behalf,
+    // without overriding the cache entry of a node owning it.
+    let advert = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+        flags: NdiscNeighborFlags::SOLICITED,
+        target_addr: proxied_ip_addr,
+        lladdr: Some(EthernetAddress::default().into()),
+        aro: None,
+    });
+    let (ip_repr, repr) = solicit(remote_ip_addr);
+    assert_eq!(
+        iface.inner.process_ndisc(ip_repr, repr),
+        Some(IpPacket::Icmpv6((
+            Ipv6Repr {
+                src_addr: local_ip_addr,
+                dst_addr: remote_ip_addr,
+                next_header: IpProtocol::Icmpv6,
+                hop_limit: 0xff,
+                payload_len: advert.buffer_len(),
+                traffic_class: 0,
+                flow_label: 0,
+            },
+            advert
+        )))
+    );
+
+    // Ensure solicitations from within the proxied prefix, or for Duplicate Address
+    // Detection, are not answered
+    let (ip_repr, repr) = solicit(Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2));
+    assert_eq!(iface.inner.process_ndisc(ip_repr, repr), None);
+    let (ip_repr, repr) = solicit(Ipv6Address::UNSPECIFIED);
+    assert_eq!(iface.inner.process_ndisc(ip_repr, repr), None);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+fn test_router_advert() {
+    let (mut iface, _sockets, mut device) = create_ethernet();
+    let link_local_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    iface.update_ip_addrs(|addrs| {
+        addrs.push(IpCidr::new(link_local_addr.into(), 64)).unwrap();
+    });
+
+    let prefix = Ipv6Cidr::new(Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0), 64);
+    let config = RouterAdvertConfig::new(prefix);
+    iface.set_router_advert(Some(config));
+    assert_eq!(iface.router_advert(), Some(config));
+
+    let sent_advert = |iface: &mut Interface, device: &mut Loopback| {
+        assert!(iface.router_advert_egress(device));
+        let frame = device.queue.pop_front().unwrap();
+        let frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+        let ipv6_packet = Ipv6Packet::new_checked(frame.payload()).unwrap();
+        assert_eq!(ipv6_packet.src_addr(), link_local_addr);
+        assert_eq!(ipv6_packet.dst_addr(), Ipv6Address::LINK_LOCAL_ALL_NODES);
+        let icmp_packet = Icmpv6Packet::new_checked(ipv6_packet.payload()).unwrap();
+        match NdiscRepr::parse(&icmp_packet).unwrap() {
+            NdiscRepr::RouterAdvert {
+                router_lifetime,
+                prefix_info: Some(prefix_info),
+                ..
+            } => {
+                assert_eq!(prefix_info.prefix, prefix.address());
+                assert_eq!(prefix_info.prefix_len, 64);
+                router_lifetime
+            }
+            repr => panic!("unexpected {:?}", repr),
+        }
+    };
+
+    // The first advertisements are sent at once, then at most 16 seconds apart.
+    assert_eq!(sent_advert(&mut iface, &mut device), config.router_lifetime);
+    assert!(!iface.router_advert_egress(&mut device));
+    assert_eq!(
+        iface.inner.router_advert_poll_at(),
+        Some(Instant::from_secs(16))
+    );
+
+    // Solicitations are answered, no earlier than 3 seconds after the last one.
+    let solicit = NdiscRepr::RouterSolicit { lladdr: None };
+    let ip_repr = Ipv6Repr {
+        src_addr: Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2),
+        dst_addr: Ipv6Address::LINK_LOCAL_ALL_ROUTERS,
+        next_header: IpProtocol::Icmpv6,
+        hop_limit: 0xff,
+        payload_len: 8,
+        traffic_class: 0,
+        flow_label: 0,
+    };
+    assert_eq!(iface.inner.process_ndisc(ip_repr, solicit), None);
+    assert_eq!(
+        iface.inner.router_advert_poll_at(),
+        Some(Instant::from_secs(3))
+    );
+
+    // Stopping advertises a router lifetime of zero.
+    iface.set_router_advert(None);
+    assert_eq!(iface.router_advert(), None);
+    assert_eq!(sent_advert(&mut iface, &mut device), Duration::ZERO);
+    assert_eq!(iface.inner.router_advert_poll_at(), None);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+fn test_answer_dad_probe() {
+    let (mut iface, _sockets, _device) = create_ethernet();
+
+    let local_ip_addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1);
+    let ip_repr = Ipv6Repr {
+        src_addr: Ipv6Address::UNSPECIFIED,
+        dst_addr: local_ip_addr.solicited_node(),
+        next_header: IpProtocol::Icmpv6,
+        hop_limit: 0xff,
+        payload_len: 24,
+        traffic_class: 0,
+        flow_label: 0,
+    };
+    let solicit = NdiscRepr::NeighborSolicit {
+        target_addr: local_ip_addr,
+        lladdr: None,
+        aro: None,
+    };
+
+    // Probes from nodes performing Duplicate Address Detection are answered by multicast.
+    let advert = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+        flags: NdiscNeighborFlags::OVERRIDE,
+        target_addr: local_ip_addr,
+        lladdr: Some(EthernetAddress::default().into()),
+        aro: None,
+    });
+    assert_eq!(
+        iface.inner.process_ndisc(ip_repr, solicit),
+        Some(IpPacket::Icmpv6((
+            Ipv6Repr {
+                src_addr: local_ip_addr,
+                dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+                next_header: IpProtocol::Icmpv6,
+                hop_limit: 0xff,
+                payload_len: advert.buffer_len(),
+                traffic_class: 0,
+                flow_label: 0,
+            },
+            advert
+        )))
+    );
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+fn test_add_ip_addr_announce() {
+    let (mut iface, _sockets, mut device) = create_ethernet();
+
+    let addr = Ipv4Address::new(192, 168, 1, 10);
+    let cidr = IpCidr::new(addr.into(), 24);
+    assert_eq!(
+        iface.add_ip_addr(IpCidr::new(Ipv4Address::BROADCAST.into(), 24)),
+        Err(AddressError::Unaddressable)
+    );
+    assert_eq!(iface.add_ip_addr(cidr), Ok(()));
+    assert_eq!(iface.ip_addr_state(addr), Some(AddressState::Preferred));
+
+    // A gratuitous ARP request announces the address.
+    assert!(iface.address_egress(&mut device));
+    let frame = device.queue.pop_front().unwrap();
+    let frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+    assert_eq!(frame.dst_addr(), EthernetAddress::BROADCAST);
+    assert_eq!(
+        ArpRepr::parse(&ArpPacket::new_checked(frame.payload()).unwrap()),
+        Ok(ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: EthernetAddress::default(),
+            source_protocol_addr: addr,
+            target_hardware_addr: EthernetAddress::BROADCAST,
+            target_protocol_addr: addr,
+        })
+    );
+    assert!(!iface.address_egress(&mut device));
+    assert_eq!(iface.inner.address_poll_at(), None);
+}
+
+#[test]
+#[cfg(all(
+    feature = "medium-ethernet",
+    feature = "proto-ipv4",
+    feature = "proto-ipv6"
+))]
+fn test_announce() {
+    let (mut iface, _sockets, mut device) = create_ethernet();
+
+    let addr = Ipv4Address::new(192, 168, 1, 10);
+    assert_eq!(iface.add_ip_addr(IpCidr::new(addr.into(), 24)), Ok(()));
+    while iface.address_egress(&mut device) {}
+    device.queue.clear();
+
+    // Every address but the loopback ones is announced, twice.
+    iface.announce();
+    let mut arp = 0;
+    let mut advert = 0;
+    for _ in 0..2 {
+        while iface.address_egress(&mut device) {}
+        iface.inner.now += Duration::from_secs(2);
+    }
+    while let Some(frame) = device.queue.pop_front() {
+        let frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+        match frame.ethertype() {
+            EthernetProtocol::Arp => {
+                let packet = ArpPacket::new_checked(frame.payload()).unwrap();
+                assert_eq!(
+                    ArpRepr::parse(&packet),
+                    Ok(ArpRepr::EthernetIpv4 {
+                        operation: ArpOperation::Request,
+                        source_hardware_addr: EthernetAddress::default(),
+                        source_protocol_addr: addr,
+                        target_hardware_addr: EthernetAddress::BROADCAST,
+                        target_protocol_addr: addr,
+                    })
+                );
+                arp += 1;
+            }
+            EthernetProtocol::Ipv6 => {
+                let packet = Ipv6Packet::new_checked(frame.payload()).unwrap();
+                let ipv6_repr = Ipv6Repr::parse(&packet).unwrap();
+                assert_eq!(ipv6_repr.dst_addr, Ipv6Address::LINK_LOCAL_ALL_NODES);
+                let icmp = Icmpv6Packet::new_checked(packet.payload()).unwrap();
+                let repr = Icmpv6Repr::parse(
+                    &ipv6_repr.src_addr.into(),
+                    &ipv6_repr.dst_addr.into(),
+                    &icmp,
+                    &ChecksumCapabilities::default(),
+                )
+                .unwrap();
+                assert_eq!(
+                    repr,
+                    Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+                        flags: NdiscNeighborFlags::OVERRIDE,
+                        target_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1),
+                        lladdr: Some(EthernetAddress::default().into()),
+                        aro: None,
+                    })
+                );
+                advert += 1;
+            }
+            ethertype => panic!("unexpected {}", ethertype),
+        }
+    }
+    assert_eq!((arp, advert), (2, 2));
+    assert_eq!(iface.inner.address_poll_at(), None);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+fn test_arp_conflict() {
+    let (mut iface, mut sockets, _device) = create_ethernet();
+
+    let addr = Ipv4Address::new(192, 168, 1, 10);
+    let cidr = IpCidr::new(addr.into(), 24);
+    assert_eq!(iface.add_ip_addr(cidr), Ok(()));
+    while iface.poll_address_event().is_some() {}
+
+    // Another node announces our address.
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+    let repr = ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Request,
+        source_hardware_addr: remote_hw_addr,
+        source_protocol_addr: addr,
+        target_hardware_addr: EthernetAddress::default(),
+        target_protocol_addr: addr,
+    };
+    let mut eth_bytes = vec![0u8; 42];
+    let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
+    frame.set_dst_addr(EthernetAddress::BROADCAST);
+    frame.set_src_addr(remote_hw_addr);
+    frame.set_ethertype(EthernetProtocol::Arp);
+    repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()));
+
+    assert_eq!(
+        iface
+            .inner
+            .process_ethernet(&mut sockets, frame.into_inner(), &mut iface.fragments),
+        None
+    );
+    assert_eq!(
+        iface.poll_address_event(),
+        Some(AddressEvent::Conflict(cidr))
+    );
+    // The address is kept, and not bound to the other node.
+    assert_eq!(iface.ip_addr_state(addr), Some(AddressState::Preferred));
+    assert!(!iface
+        .inner
+        .neighbor_cache
+        .as_ref()
+        .unwrap()
+        .lookup(&addr.into(), iface.inner.now)
+        .found());
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+fn test_autoip() {
+    let (mut iface, mut sockets, mut device) = create_ethernet();
+    while iface.poll_address_event().is_some() {}
+
+    let mut run = |iface: &mut Interface, sockets: &mut SocketSet, secs| {
+        let end = iface.inner.now + Duration::from_secs(secs);
+        while let Some(at) = iface.poll_at(iface.inner.now, sockets) {
+            if at > end {
+                break;
+            }
+            iface.poll(at.max(iface.inner.now), &mut device, sockets);
+        }
+    };
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+    let claim = |iface: &mut Interface, sockets: &mut SocketSet, addr| {
+        let repr = ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: remote_hw_addr,
+            source_protocol_addr: addr,
+            target_hardware_addr: EthernetAddress::default(),
+            target_protocol_addr: addr,
+        };
+        let mut eth_bytes = vec![0u8; 42];
+        let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
+        frame.set_dst_addr(EthernetAddress::BROADCAST);
+        frame.set_src_addr(remote_hw_addr);
+        frame.set_ethertype(EthernetProtocol::Arp);
+        repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()));
+        iface
+            .inner
+            .process_ethernet(sockets, frame.into_inner(), &mut iface.fragments);
+    };
+
+    iface.set_autoip(true);
+    assert!(iface.autoip());
+    assert_eq!(iface.autoip_addr(), None);
+
+    // Three probes, then the address is claimed.
+    run(&mut iface, &mut sockets, 10);
+    let addr = iface.autoip_addr().unwrap();
+    assert!(addr.is_link_local());
+    let cidr = IpCidr::new(addr.into(), 16);
+    assert_eq!(iface.poll_address_event(), Some(AddressEvent::Added(cidr)));
+    assert_eq!(iface.ip_addr_state(addr), Some(AddressState::Preferred));
+
+    // The link-local address is only used to reach link-local destinations.
+    let peer = Ipv4Address::new(169, 254, 0x12, 0x34);
+    assert_eq!(iface.inner.get_source_address_ipv4(peer), Some(addr));
+    let remote = Ipv4Address::new(192, 168, 1, 1);
+    assert_ne!(iface.inner.get_source_address_ipv4(remote), Some(addr));
+
+    // The first conflict is defended, the second one gives up the address.
+    claim(&mut iface, &mut sockets, addr);
+    assert_eq!(
+        iface.poll_address_event(),
+        Some(AddressEvent::Conflict(cidr))
+    );
+    assert_eq!(iface.autoip_addr(), Some(addr));
+    claim(&mut iface, &mut sockets, addr);
+    assert_eq!(
+        iface.poll_address_event(),
+        Some(AddressEvent::Duplicate(cidr))
+    );
+    assert_eq!(iface.autoip_addr(), None);
+    assert!(!iface.has_ip_addr(addr));
+
+    // Another address is claimed.
+    run(&mut iface, &mut sockets, 10);
+    let new_addr = iface.autoip_addr().unwrap();
+    assert_ne!(new_addr, addr);
+    assert!(iface.has_ip_addr(new_addr));
+
+    iface.set_autoip(false);
+    assert!(!iface.has_ip_addr(new_addr));
+    assert_eq!(iface.autoip_addr(), None);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+fn test_ndisc_conflict() {
+    let (mut iface, _sockets, _device) = create_ethernet();
+    while iface.poll_address_event().is_some() {}
+
+    let addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1);
+    let ip_repr = Ipv6Repr {
+        src_addr: addr,
+        dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+        next_header: IpProtocol::Icmpv6,
+        hop_limit: 0xff,
+        payload_len: 32,
+        traffic_class: 0,
+        flow_label: 0,
+    };
+    let advert = |lladdr: EthernetAddress| NdiscRepr::NeighborAdvert {
+        flags: NdiscNeighborFlags::OVERRIDE,
+        target_addr: addr,
+        lladdr: Some(lladdr.into()),
+        aro: None,
+    };
+
+    // Our own advertisements, looped back, are not conflicts.
+    assert_eq!(
+        iface
+            .inner
+            .process_ndisc(ip_repr, advert(EthernetAddress::default())),
+        None
+    );
+    assert_eq!(iface.poll_address_event(), None);
+
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+    assert_eq!(
+        iface.inner.process_ndisc(ip_repr, advert(remote_hw_addr)),
+        None
+    );
+    assert_eq!(
+        iface.poll_address_event(),
+        Some(AddressEvent::Conflict(IpCidr::new(addr.into(), 64)))
+    );
+    assert_eq!(iface.ip_addr_state(addr), Some(AddressState::Preferred));
+}
+
+#[test]
+#[cfg(feature = "proto-ipv6")]
+fn test_update_ip_addrs_events() {
+    let (mut iface, _sockets, _device) = create(MEDIUM);
+    while iface.poll_address_event().is_some() {}
+
+    let loopback = IpCidr::new(Ipv6Address::LOOPBACK.into(), 128);
+    let new_addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 3);
+    let cidr = IpCidr::new(new_addr.into(), 64);
+    iface.update_ip_addrs(|ip_addrs| {
+        ip_addrs.retain(|addr| *addr != loopback);
+        ip_addrs.push(cidr).unwrap();
+    });
+    assert_eq!(
+        iface.poll_address_event(),
+        Some(AddressEvent::Removed(loopback))
+    );
+    assert_eq!(iface.poll_address_event(), Some(AddressEvent::Added(cidr)));
+    assert_eq!(iface.poll_address_event(), None);
+
+    // Deprecated addresses are only used as source when there is no other choice.
+    let addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1);
+    let dst_addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2);
+    assert!(iface.set_ip_addr_deprecated(addr, true));
+    assert_eq!(
+        iface.poll_address_event(),
+        Some(AddressEvent::StateChanged(
+            IpCidr::new(addr.into(), 64),
+            AddressState::Deprecated
+        ))
+    );
+    assert_eq!(
+        iface.inner.get_source_address_ipv6(dst_addr),
+        Some(new_addr)
+    );
+    assert!(iface.set_ip_addr_deprecated(new_addr, true));
+    assert_eq!(iface.inner.get_source_address_ipv6(dst_addr), Some(addr));
+    assert!(!iface.set_ip_addr_deprecated(Ipv6Address::LOOPBACK, true));
+}
+
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4"))]
+fn create_forwarding<'a>() -> (Forwarder<'a>, [(Interface, Loopback, PortId); 2]) {
+    use crate::iface::{ForwardPacketBuffer, ForwardPacketMetadata};
+
+    let mut forwarder = Forwarder::new();
+    let ports = [Ipv4Address::new(10, 0, 0, 1), Ipv4Address::new(10, 1, 0, 1)].map(|addr| {
+        let mut device = Loopback::new(Medium::Ip);
+        let mut iface = Interface::new(Config::new(), &mut device);
+        iface.update_ip_addrs(|ip_addrs| {
+            ip_addrs.push(IpCidr::new(addr.into(), 24)).unwrap();
+        });
+        let queue =
+            ForwardPacketBuffer::new(vec![ForwardPacketMetadata::EMPTY; 4], vec![0u8; 1500]);
+        let port = forwarder.add_port(queue).unwrap();
+        forwarder.sync(port, &iface);
+        (iface, device, port)
+    });
+    (forwarder, ports)
+}
+
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4"))]
+fn ipv4_packet_to(dst_addr: Ipv4Address, hop_limit: u8) -> std::vec::Vec<u8> {
+    let repr = Ipv4Repr {
+        src_addr: Ipv4Address::new(10, 0, 0, 2),
+        dst_addr,
+        next_header: IpProtocol::Udp,
+        payload_len: 8,
+        hop_limit,
+        dscp: 0,
+    };
+    let mut bytes = vec![0xa5; repr.buffer_len() + repr.payload_len];
+    repr.emit(
+        &mut Ipv4Packet::new_unchecked(&mut bytes),
+        &ChecksumCapabilities::default(),
+    );
+    bytes
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4"))]
+fn test_forward_ipv4() {
+    let (mut forwarder, [(mut iface_a, mut device_a, port_a), (mut iface_b, mut device_b, port_b)]) =
+        create_forwarding();
+    let mut sockets = SocketSet::new(vec![]);
+    let dst_addr = Ipv4Address::new(10, 1, 0, 2);
+
+    device_a.queue.push_back(ipv4_packet_to(dst_addr, 64));
+    assert!(forwarder.poll(
+        port_a,
+        crate::time::Instant::from_millis(0),
+        &mut iface_a,
+        &mut device_a,
+        &mut sockets
+    ));
+    assert!(device_a.queue.is_empty());
+    assert!(!forwarder.queue_mut(port_b).is_empty());
+
+    assert!(iface_b.forward_egress(&mut device_b, &mut forwarder, port_b));
+    assert!(forwarder.queue_mut(port_b).is_empty());
+    let packet = device_b.queue.pop_front().unwrap();
+    let packet = Ipv4Packet::new_checked(&packet[..]).unwrap();
+    assert_eq!(packet.dst_addr(), dst_addr);
+    assert_eq!(packet.hop_limit(), 63);
+    assert!(packet.verify_checksum());
+    assert_eq!(packet.payload(), &[0xa5; 8]);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4"))]
+fn test_forward_ipv4_checksum_offload() {
+    let (mut forwarder, [(mut iface_a, mut device_a, port_a), (mut iface_b, mut device_b, port_b)]) =
+        create_forwarding();
+    let mut sockets = SocketSet::new(vec![]);
+    let dst_addr = Ipv4Address::new(10, 1, 0, 2);
+
+    // The header checksum is left to the egress device when it computes it.
+    iface_b.inner.caps.checksum.ipv4 = Checksum::Rx;
+    device_a.queue.push_back(ipv4_packet_to(dst_addr, 64));
+    forwarder.poll(
+        port_a,
+        crate::time::Instant::from_millis(0),
+        &mut iface_a,
+        &mut device_a,
+        &mut sockets,
+    );
+    assert!(iface_b.forward_egress(&mut device_b, &mut forwarder, port_b));
+    let packet = device_b.queue.pop_front().unwrap();
+    let packet = Ipv4Packet::new_checked(&packet[..]).unwrap();
+    assert_eq!(packet.hop_limit(), 63);
+    assert!(!packet.verify_checksum());
+}
+
+/// A token recording the IP version it was told and the length of the packet sent.
+#[cfg(feature = "medium-ip")]
+struct VersionTxToken<'a> {
+    sent: &'a mut std::vec::Vec<(Option<IpVersion>, usize)>,
+    version: Option<IpVersion>,
+}
+
+#[cfg(feature = "medium-ip")]
+impl<'a> TxToken for VersionTxToken<'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.sent.push((self.version, len));
+        f(&mut vec![0; len])
+    }
+
+    fn set_ip_version(&mut self, version: IpVersion) {
+        self.version = Some(version);
+    }
+}
+
+#[test]
+#[cfg(all(
+    feature = "medium-ip",
+    feature = "proto-ipv4-fragmentation",
+    feature = "proto-ipv6"
+))]
+fn test_ip_mtu_per_version() {
+    let (mut iface, _, _) = create_ip();
+    iface.inner.caps.ipv4_mtu = Some(1000);
+    assert_eq!(iface.inner.ip_mtu_of(IpVersion::Ipv4), 1000);
+    assert_eq!(iface.inner.ip_mtu_of(IpVersion::Ipv6), 65535);
+    assert_eq!(iface.inner.ip_mtu_from(&IpAddress::v4(127, 0, 0, 1)), 1000);
+
+    let udp = |src_addr: IpAddress, dst_addr: IpAddress| {
+        let payload = &[0xaa; 1172][..];
+        let ip_repr = IpRepr::new(src_addr, dst_addr, IpProtocol::Udp, 8 + payload.len(), 64);
+        let udp_repr = UdpRepr {
+            src_port: 1234,
+            dst_port: 5678,
+        };
+        IpPacket::Udp((ip_repr, udp_repr, payload))
+    };
+
+    // IPv4 packets are fragmented to the MTU of IPv4, with payloads that are multiples
+    // of 8 octets.
+    let mut sent = Vec::new();
+    let tx_token = VersionTxToken {
+        sent: &mut sent,
+        version: None,
+    };
+    let packet = udp(IpAddress::v4(127, 0, 0, 1), IpAddress::v4(127, 0, 0, 2));
+    assert_eq!(
+        iface
+            .inner
+            .dispatch_ip(tx_token, packet, Some(&mut iface.out_packets)),
+        Ok(())
+    );
+    let tx_token = VersionTxToken {
+        sent: &mut sent,
+        version: None,
+    };
+    iface
+        .inner
+        .dispatch_ipv4_out_packet(tx_token, &mut iface.out_packets.ipv4_out_packet);
+    assert_eq!(
+        sent,
+        [
+            (Some(IpVersion::Ipv4), 20 + 976),
+            (Some(IpVersion::Ipv4), 20 + 8 + 1172 - 976)
+        ]
+    );
+
+    // IPv6 packets are not limited by it.
+    let mut sent = Vec::new();
+    let tx_token = VersionTxToken {
+        sent: &mut sent,
+        version: None,
+    };
+    let packet = udp(
+        IpAddress::v6(0xfdbe, 0, 0, 0, 0, 0, 0, 1),
+        IpAddress::v6(0xfdbe, 0, 0, 0, 0, 0, 0, 2),
+    );
+    assert_eq!(iface.inner.dispatch_ip(tx_token, packet, None), Ok(()));
+    assert_eq!(sent, [(Some(IpVersion::Ipv6), 40 + 8 + 1172)]);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4"))]
+fn test_forward_ipv4_errors() {
+    let (mut forwarder, [(mut iface_a, mut device_a, port_a), (mut iface_b, _, port_b)]) =
+        create_forwarding();
+    let mut sockets = SocketSet::new(vec![]);
+
+    // The link of port B only fits 24-octet packets.
+    iface_b.inner.caps.max_transmission_unit = 24;
+    forwarder.sync(port_b, &iface_b);
+
+    let mut ingress = |packet: std::vec::Vec<u8>| {
+        device_a.queue.push_back(packet);
+        let mut forward = Some((&mut forwarder, port_a));
+        assert!(iface_a.socket_ingress(&mut device_a, &mut sockets, &mut forward));
+        // The loopback device hands the error back to the interface, which forwards it
+        // to its on-link destination.
+        let (_, reply) = forwarder.queue_mut(port_a).dequeue().unwrap();
+        // Its header checksum is only filled on egress.
+        let ipv4_packet = Ipv4Packet::new_checked(&reply[..]).unwrap();
+        let ipv4_repr = Ipv4Repr::parse(&ipv4_packet, &ChecksumCapabilities::ignored()).unwrap();
+        assert_eq!(ipv4_repr.src_addr, Ipv4Address::new(10, 0, 0, 1));
+        assert_eq!(ipv4_repr.dst_addr, Ipv4Address::new(10, 0, 0, 2));
+        let icmp_packet = Icmpv4Packet::new_checked(ipv4_packet.payload()).unwrap();
+        (icmp_packet.msg_type(), icmp_packet.msg_code())
+    };
+
+    // The TTL runs out on this hop.
+    assert_eq!(
+        ingress(ipv4_packet_to(Ipv4Address::new(10, 1, 0, 2), 1)),
+        (Icmpv4Message::TimeExceeded, 0)
+    );
+    // No port has a route to the destination.
+    assert_eq!(
+        ingress(ipv4_packet_to(Ipv4Address::new(192, 0, 2, 1), 64)),
+        (Icmpv4Message::DstUnreachable, 0)
+    );
+    // The packet does not fit the next hop and has the "don't fragment" flag set.
+    assert_eq!(
+        ingress(ipv4_packet_to(Ipv4Address::new(10, 1, 0, 2), 64)),
+        (Icmpv4Message::DstUnreachable, 4)
+    );
+}
+
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4"))]
+fn udp_packet(src: (Ipv4Address, u16), dst: (Ipv4Address, u16)) -> std::vec::Vec<u8> {
+    let ipv4_repr = Ipv4Repr {
+        src_addr: src.0,
+        dst_addr: dst.0,
+        next_header: IpProtocol::Udp,
+        payload_len: 8 + 4,
+        hop_limit: 64,
+        dscp: 0,
+    };
+    let udp_repr = UdpRepr {
+        src_port: src.1,
+        dst_port: dst.1,
+    };
+    let mut bytes = vec![0; ipv4_repr.buffer_len() + ipv4_repr.payload_len];
+    let caps = ChecksumCapabilities::default();
+    let mut packet = Ipv4Packet::new_unchecked(&mut bytes);
+    ipv4_repr.emit(&mut packet, &caps);
+    udp_repr.emit(
+        &mut UdpPacket::new_unchecked(packet.payload_mut()),
+        &src.0.into(),
+        &dst.0.into(),
+        4,
+        |payload| payload.copy_from_slice(b"ping"),
+        &caps,
+    );
+    bytes
+}
+
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4"))]
+fn udp_endpoints(packet: &[u8]) -> ((Ipv4Address, u16), (Ipv4Address, u16)) {
+    let ipv4_packet = Ipv4Packet::new_checked(packet).unwrap();
+    assert!(ipv4_packet.verify_checksum());
+    let (src_addr, dst_addr) = (ipv4_packet.src_addr(), ipv4_packet.dst_addr());
+    let udp_packet = UdpPacket::new_checked(ipv4_packet.payload()).unwrap();
+    assert!(udp_packet.verify_checksum(&src_addr.into(), &dst_addr.into()));
+    assert_eq!(udp_packet.payload(), b"ping");
+    (
+        (src_addr, udp_packet.src_port()),
+        (dst_addr, udp_packet.dst_port()),
+    )
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4"))]
+fn test_forward_nat44() {
+    let (mut forwarder, [(mut iface_a, mut device_a, port_a), (mut iface_b, mut device_b, port_b)]) =
+        create_forwarding();
+    forwarder.set_nat(Some(crate::iface::Nat44::new(port_b)));
+    let mut sockets = SocketSet::new(vec![]);
+
+    let host_1 = (Ipv4Address::new(10, 0, 0, 2), 5000);
+    let host_2 = (Ipv4Address::new(10, 0, 0, 3), 6000);
+    let server = (Ipv4Address::new(10, 1, 0, 2), 53);
+    let mapped_1 = (Ipv4Address::new(10, 1, 0, 1), 49152);
+    let mapped_2 = (Ipv4Address::new(10, 1, 0, 1), 49153);
+
+    // Outbound: the source is translated.
+    device_a.queue.push_back(udp_packet(host_1, server));
+    let mut forward = Some((&mut forwarder, port_a));
+    assert!(iface_a.socket_ingress(&mut device_a, &mut sockets, &mut forward));
+    assert!(iface_b.forward_egress(&mut device_b, &mut forwarder, port_b));
+    let packet = device_b.queue.pop_front().unwrap();
+    assert_eq!(udp_endpoints(&packet), (mapped_1, server));
+
+    // Inbound: the destination is translated back.
+    device_b.queue.push_back(udp_packet(server, mapped_1));
+    let mut forward = Some((&mut forwarder, port_b));
+    assert!(iface_b.socket_ingress(&mut device_b, &mut sockets, &mut forward));
+    assert!(iface_a.forward_egress(&mut device_a, &mut forwarder, port_a));
+    let packet = device_a.queue.pop_front().unwrap();
+    assert_eq!(udp_endpoints(&packet), (server, host_1));
+
+    // Hairpinning: both are translated.
+    device_a.queue.push_back(udp_packet(host_2, mapped_1));
+    let mut forward = Some((&mut forwarder, port_a));
+    assert!(iface_a.socket_ingress(&mut device_a, &mut sockets, &mut forward));
+    assert!(iface_a.forward_egress(&mut device_a, &mut forwarder, port_a));
+    let packet = device_a.queue.pop_front().unwrap();
+    assert_eq!(udp_endpoints(&packet), (mapped_2, host_1));
+
+    // Unmapped ports of the outside address are for the interface itself.
+    device_b
+        .queue
+        .push_back(udp_packet(server, (mapped_1.0, 1234)));
+    let mut forward = Some((&mut forwarder, port_b));
+    assert!(iface_b.socket_ingress(&mut device_b, &mut sockets, &mut forward));
+    assert!(forwarder.queue_mut(port_a).is_empty());
+
+    let nat = forwarder.nat().unwrap();
+    assert_eq!(nat.mappings().count(), 2);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4-fragmentation"))]
+fn test_ipv4_reassembly_config() {
+    let mut device = Loopback::new(Medium::Ip);
+    let mut config = Config::new();
+    config.ipv4_reassembly_contexts = 2;
+    config.ipv4_reassembly_buffer_size = 64;
+    let mut iface = Interface::new(config, &mut device);
+    iface.update_ip_addrs(|ip_addrs| {
+        ip_addrs
+            .push(IpCidr::new(IpAddress::v4(10, 0, 0, 1), 24))
+            .unwrap();
+    });
+    let mut sockets = SocketSet::new(vec![]);
+
+    let fragment = |ident: u16, frag_offset: u16, more_frags: bool| {
+        let repr = Ipv4Repr {
+            src_addr: Ipv4Address::new(10, 0, 0, 2),
+            dst_addr: Ipv4Address::new(10, 0, 0, 1),
+            next_header: IpProtocol::Udp,
+            payload_len: 16,
+            hop_limit: 64,
+            dscp: 0,
+        };
+        let mut bytes = vec![0; repr.buffer_len() + repr.payload_len];
+        let mut packet = Ipv4Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet, &ChecksumCapabilities::default());
+        packet.set_ident(ident);
+        packet.set_frag_offset(frag_offset);
+        packet.set_more_frags(more_frags);
+        packet.fill_checksum();
+        bytes
+    };
+
+    for ident in 1..=3 {
+        device.queue.push_back(fragment(ident, 0, true));
+    }
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    let stats = iface.ipv4_reassembly_stats();
+    assert_eq!(stats.capacity, 2);
+    assert_eq!(stats.in_use, 2);
+    assert_eq!(stats.buffered, 32);
+    assert_eq!(stats.evicted, 1);
+
+    // The packet would not fit the reassembly buffer.
+    device.queue.push_back(fragment(3, 64, false));
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    assert_eq!(iface.ipv4_reassembly_stats().buffered, 32);
+}
+
+#[test]
+#[cfg(all(
+    feature = "medium-ip",
+    feature = "proto-ipv4-fragmentation",
+    feature = "socket-udp"
+))]
+fn test_packet_hooks() {
+    let (mut iface, mut sockets, mut device) = create_ip();
+    let local = Ipv4Address::new(127, 0, 0, 1);
+    let remote = Ipv4Address::new(127, 0, 0, 2);
+
+    // Drop the packets to port 9, and mark the others.
+    let hook: PacketHook = |_, ip_repr, ports| match ports {
+        Some((_, 9)) => Verdict::Drop,
+        _ => {
+            ip_repr.set_traffic_class(0xb8);
+            ip_repr.set_payload_len(0);
+            Verdict::Mangle
+        }
+    };
+    iface.set_prerouting_hook(Some(hook));
+    iface.set_postrouting_hook(Some(hook));
+
+    let bytes = udp_packet((remote, 1234), (local, 9));
+    let frame = Ipv4Packet::new_checked(&bytes[..]).unwrap();
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            &frame,
+            Some(&mut iface.fragments.ipv4_fragments)
+        ),
+        None
+    );
+
+    // The port unreachable error quotes the mangled header.
+    let bytes = udp_packet((remote, 1234), (local, 7));
+    let frame = Ipv4Packet::new_checked(&bytes[..]).unwrap();
+    match iface.inner.process_ipv4(
+        &mut sockets,
+        &frame,
+        Some(&mut iface.fragments.ipv4_fragments),
+    ) {
+        Some(IpPacket::Icmpv4((_, Icmpv4Repr::DstUnreachable { header, .. }))) => {
+            assert_eq!(header.dscp, 0xb8 >> 2);
+            assert_eq!(header.payload_len, 12);
+        }
+        packet => panic!("unexpected {packet:?}"),
+    }
+
+    let udp = |dst_port| {
+        let ip_repr = IpRepr::new(local.into(), remote.into(), IpProtocol::Udp, 8 + 4, 64);
+        let udp_repr = UdpRepr {
+            src_port: 1234,
+            dst_port,
+        };
+        IpPacket::Udp((ip_repr, udp_repr, &b"ping"[..]))
+    };
+    let tx_token = device.transmit(Instant::from_millis(0)).unwrap();
+    assert_eq!(iface.inner.dispatch_ip(tx_token, udp(9), None), Ok(()));
+    assert!(device.queue.is_empty());
+
+    let tx_token = device.transmit(Instant::from_millis(0)).unwrap();
+    assert_eq!(iface.inner.dispatch_ip(tx_token, udp(7), None), Ok(()));
+    let bytes = device.queue.pop_front().unwrap();
+    let packet = Ipv4Packet::new_checked(&bytes[..]).unwrap();
+    assert_eq!(packet.dscp(), 0xb8 >> 2);
+    assert_eq!(packet.total_len(), 20 + 8 + 4);
+    let udp_packet = UdpPacket::new_checked(packet.payload()).unwrap();
+    assert!(udp_packet.verify_checksum(&local.into(), &remote.into()));
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4", feature = "socket-udp"))]
+fn test_interface_stats() {
+    let (mut iface, mut sockets, mut device) = create_ip();
+    let local = Ipv4Address::new(127, 0, 0, 1);
+    let remote = Ipv4Address::new(127, 0, 0, 2);
+
+    // A datagram with a bad checksum is dropped.
+    let mut bytes = udp_packet((remote, 1234), (local, 7));
+    let len = bytes.len();
+    bytes[len - 1] ^= 0xff;
+    device.queue.push_back(bytes);
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    let stats = iface.stats();
+    assert_eq!((stats.rx_packets, stats.rx_bytes), (1, len as u64));
+    assert_eq!(stats.udp_errors, 1);
+    assert_eq!(stats.checksum_errors, 1);
+    assert_eq!(stats.tx_packets, 0);
+
+    // A datagram for a closed port is answered with an ICMP error, which the loopback
+    // device hands back to the interface.
+    iface.reset_stats();
+    device
+        .queue
+        .push_back(udp_packet((remote, 1234), (local, 7)));
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    let stats = iface.stats();
+    assert_eq!(stats.dropped_no_socket, 1);
+    assert_eq!(stats.tx_packets, 1);
+    assert_eq!(stats.rx_packets, 2);
+    assert_eq!(stats.tx_bytes + len as u64, stats.rx_bytes);
+    assert_eq!(stats.udp_errors, 0);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4", feature = "socket-udp"))]
+fn test_any_ip_prefixes() {
+    let (mut iface, mut sockets, mut device) = create_ip();
+    let remote = Ipv4Address::new(127, 0, 0, 2);
+    let intercepted = Ipv4Address::new(10, 9, 0, 5);
+
+    let rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 15]);
+    let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 15]);
+    let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+    socket.bind(7).unwrap();
+    let handle = sockets.add(socket);
+
+    // Traffic to other addresses is ignored by default.
+    device
+        .queue
+        .push_back(udp_packet((remote, 1234), (intercepted, 7)));
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    assert!(!sockets.get_mut::<udp::Socket>(handle).can_recv());
+    assert!(device.queue.is_empty());
+
+    iface.update_any_ip_prefixes(|prefixes| {
+        prefixes
+            .push(IpCidr::new(Ipv4Address::new(10, 9, 0, 0).into(), 16))
+            .unwrap();
+    });
+    device
+        .queue
+        .push_back(udp_packet((remote, 1234), (intercepted, 7)));
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    let socket = sockets.get_mut::<udp::Socket>(handle);
+    let (payload, metadata) = socket.recv_with_metadata().unwrap();
+    assert_eq!(payload, b"ping");
+    assert_eq!(metadata.local_address, Some(intercepted.into()));
+
+    // The reply comes from the address the datagram was sent to.
+    socket.send_slice_with_metadata(b"pong", metadata).unwrap();
+    iface.socket_egress(&mut device, &mut sockets);
+    let bytes = device.queue.pop_front().unwrap();
+    let packet = Ipv4Packet::new_checked(&bytes[..]).unwrap();
+    assert_eq!(packet.src_addr(), intercepted);
+    assert_eq!(packet.dst_addr(), remote);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4", feature = "socket-udp"))]
+fn test_poll_budget() {
+    let (mut iface, mut sockets, mut device) = create_ip();
+    let remote = IpEndpoint::new(Ipv4Address::new(127, 0, 0, 2).into(), 9);
+
+    for port in 1..=2 {
+        let rx_buffer = udp::PacketBuffer::new(vec![], vec![]);
+        let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 8]);
+        let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+        socket.bind(port).unwrap();
+        socket.send_slice(b"1", remote).unwrap();
+        socket.send_slice(b"2", remote).unwrap();
+        sockets.add(socket);
+    }
+
+    // With a budget of one frame per poll, the sockets take turns.
+    iface.set_poll_budget(PollBudget::packets(1));
+    for (port, payload) in [(1, b"1"), (2, b"1"), (1, b"2"), (2, b"2")] {
+        device.queue.clear();
+        assert!(iface.poll(Instant::from_millis(0), &mut device, &mut sockets));
+        assert!(iface.poll_pending());
+        assert_eq!(device.queue.len(), 1);
+        let packet = Ipv4Packet::new_checked(&device.queue[0][..]).unwrap();
+        let udp = UdpPacket::new_checked(packet.payload()).unwrap();
+        assert_eq!((udp.src_port(), udp.payload()), (port, &payload[..]));
+    }
+
+    // The last datagram, looped back by the device, is processed once the budget
+    // allows it.
+    iface.set_poll_budget(PollBudget::default());
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    assert!(!iface.poll_pending());
+    assert!(device.queue.is_empty());
+    assert_eq!(iface.stats().tx_packets, 4);
+    assert_eq!(iface.stats().rx_packets, 1);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv6", feature = "socket-udp"))]
+fn test_ipv6_ext_headers_egress() {
+    let (mut iface, mut sockets, mut device) = create_ip();
+
+    let mut ext_headers = Ipv6ExtHeaders::new();
+    // Router Alert, and an option that must be skipped by the receiver.
+    ext_headers
+        .push_hop_by_hop(Ipv6OptionRepr::Unknown {
+            type_: 5.into(),
+            length: 2,
+            data: &[0, 0],
+        })
+        .unwrap();
+    ext_headers
+        .push_destination(Ipv6OptionRepr::Unknown {
+            type_: 0x1e.into(),
+            length: 1,
+            data: &[0],
+        })
+        .unwrap();
+
+    let mut handles = vec![];
+    for port in [1, 2] {
+        let rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 8]);
+        let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 8]);
+        let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+        socket.bind(port).unwrap();
+        handles.push(sockets.add(socket));
+    }
+    let socket = sockets.get_mut::<udp::Socket>(handles[0]);
+    socket.set_ipv6_ext_headers(ext_headers);
+    socket
+        .send_slice(b"ping", (Ipv6Address::LOOPBACK, 2).into())
+        .unwrap();
+
+    // The packet is delivered locally, with its extension headers.
+    iface.socket_egress(&mut device, &mut sockets);
+    assert!(device.queue.is_empty());
+    let (bytes, _) = iface.local.front().unwrap();
+    let packet = Ipv6Packet::new_checked(bytes).unwrap();
+    assert_eq!(packet.next_header(), IpProtocol::HopByHop);
+    assert_eq!(packet.payload_len(), 8 + 8 + 12);
+    let payload = packet.payload();
+    assert_eq!(payload[0], u8::from(IpProtocol::Ipv6Opts));
+    assert_eq!(payload[8], u8::from(IpProtocol::Udp));
+
+    // The receiver skips the extension headers.
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    let socket = sockets.get_mut::<udp::Socket>(handles[1]);
+    assert_eq!(
+        socket.recv(),
+        Ok((&b"ping"[..], (Ipv6Address::LOOPBACK, 1).into()))
+    );
+}
+
+#[test]
+#[cfg(feature = "proto-ipv6")]
+fn test_ipv6_ext_headers_ingress() {
+    let (mut iface, mut sockets, _device) = create(MEDIUM);
+    let remote_ip_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+
+    let mut process = |dst_addr: Ipv6Address, next_header: IpProtocol, ext: &[u8]| {
+        let ipv6_repr = Ipv6Repr {
+            src_addr: remote_ip_addr,
+            dst_addr,
+            next_header,
+            payload_len: ext.len(),
+            hop_limit: 0x40,
+            traffic_class: 0,
+            flow_label: 0,
+        };
+        let mut bytes = vec![0; ipv6_repr.buffer_len() + ext.len()];
+        ipv6_repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes));
+        bytes[ipv6_repr.buffer_len()..].copy_from_slice(ext);
+        let reply = match iface
+            .inner
+            .process_ipv6(&mut sockets, &Ipv6Packet::new_unchecked(&bytes))
+        {
+            Some(IpPacket::Icmpv6((
+                _,
+                Icmpv6Repr::ParamProblem {
+                    reason, pointer, ..
+                },
+            ))) => Some((reason, pointer)),
+            None => None,
+            packet => panic!("unexpected reply {packet:?}"),
+        };
+        reply
+    };
+
+    let no_nxt = u8::from(IpProtocol::Ipv6NoNxt);
+    let option = |type_: u8| [no_nxt, 0, type_, 0, 1, 2, 0, 0];
+
+    // Unrecognized options are handled according to the two highest bits of their type.
+    let local = Ipv6Address::LOOPBACK;
+    let all_nodes = Ipv6Address::LINK_LOCAL_ALL_NODES;
+    let hbh = IpProtocol::HopByHop;
+    assert_eq!(process(local, hbh, &option(0x1e)), None);
+    assert_eq!(process(local, hbh, &option(0x5e)), None);
+    assert_eq!(
+        process(local, hbh, &option(0x9e)),
+        Some((Icmpv6ParamProblem::UnrecognizedOption, 42))
+    );
+    assert_eq!(
+        process(all_nodes, hbh, &option(0x9e)),
+        Some((Icmpv6ParamProblem::UnrecognizedOption, 42))
+    );
+    assert_eq!(
+        process(local, IpProtocol::Ipv6Opts, &option(0xde)),
+        Some((Icmpv6ParamProblem::UnrecognizedOption, 42))
+    );
+    assert_eq!(process(all_nodes, hbh, &option(0xde)), None);
+
+    // A Hop-by-Hop Options header is only allowed first.
+    let mut ext = option(0x1e);
+    ext[0] = u8::from(IpProtocol::HopByHop);
+    let ext = [ext, option(0x1e)].concat();
+    assert_eq!(
+        process(local, IpProtocol::Ipv6Opts, &ext),
+        Some((Icmpv6ParamProblem::UnrecognizedNxtHdr, 40))
+    );
+
+    // Routing headers with segments left are not forwarded.
+    let routing = |segments_left: u8, next_header: IpProtocol| {
+        [u8::from(next_header), 0, 4, segments_left, 0, 0, 0, 0]
+    };
+    assert_eq!(
+        process(
+            local,
+            IpProtocol::Ipv6Route,
+            &routing(1, IpProtocol::Ipv6NoNxt)
+        ),
+        Some((Icmpv6ParamProblem::ErroneousHdrField, 42))
+    );
+
+    // Atomic fragments are accepted, other fragments are not.
+    let fragment = |more_frags: u8, next_header: IpProtocol| {
+        [u8::from(next_header), 0, 0, more_frags, 0, 0, 0, 1]
+    };
+    assert_eq!(
+        process(
+            local,
+            IpProtocol::Ipv6Frag,
+            &fragment(1, IpProtocol::Unknown(0x0c))
+        ),
+        None
+    );
+    let ext = [
+        routing(0, IpProtocol::Ipv6Frag),
+        fragment(0, IpProtocol::Unknown(0x0c)),
+    ]
+    .concat();
+    assert_eq!(
+        process(local, IpProtocol::Ipv6Route, &ext),
+        Some((Icmpv6ParamProblem::UnrecognizedNxtHdr, 48))
+    );
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4", feature = "socket-tcp"))]
+fn test_local_delivery() {
+    let (mut iface, mut sockets, mut device) = create_ip();
+
+    let tcp_socket = || {
+        let rx_buffer = tcp::SocketBuffer::new(vec![0; 64]);
+        let tx_buffer = tcp::SocketBuffer::new(vec![0; 64]);
+        tcp::Socket::new(rx_buffer, tx_buffer)
+    };
+    let mut server = tcp_socket();
+    server.listen(80).unwrap();
+    let server = sockets.add(server);
+    let client = sockets.add(tcp_socket());
+
+    sockets
+        .get_mut::<tcp::Socket>(client)
+        .connect(iface.context(), (Ipv4Address::new(127, 0, 0, 1), 80), 49152)
+        .unwrap();
+    // A single poll is enough for the handshake, and nothing goes through the device.
+    assert!(iface.poll(Instant::from_millis(0), &mut device, &mut sockets));
+    assert!(device.queue.is_empty());
+    assert_eq!(
+        sockets.get::<tcp::Socket>(client).state(),
+        tcp::State::Established
+    );
+    assert_eq!(
+        sockets.get::<tcp::Socket>(server).state(),
+        tcp::State::Established
+    );
+
+    sockets
+        .get_mut::<tcp::Socket>(client)
+        .send_slice(b"ping")
+        .unwrap();
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    let mut buffer = [0; 4];
+    let server = sockets.get_mut::<tcp::Socket>(server);
+    assert_eq!(server.recv_slice(&mut buffer), Ok(4));
+    assert_eq!(&buffer, b"ping");
+    assert!(device.queue.is_empty());
+    assert_eq!(iface.stats().tx_packets, 0);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4", feature = "socket-udp"))]
+fn test_local_delivery_checksum_offload() {
+    let (mut iface, mut sockets, mut device) = create_ip();
+    let local = IpEndpoint::new(Ipv4Address::new(127, 0, 0, 1).into(), 2);
+
+    // The device computes checksums, but the interface verifies them: local packets
+    // must carry them anyway.
+    iface.inner.caps.checksum.ipv4 = Checksum::Rx;
+    iface.inner.caps.checksum.udp = Checksum::Rx;
+
+    let udp_socket = |port| {
+        let rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 8]);
+        let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 8]);
+        let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+        socket.bind(port).unwrap();
+        socket
+    };
+    let mut client = udp_socket(1);
+    client.send_slice(b"ping", local).unwrap();
+    sockets.add(client);
+    let server = sockets.add(udp_socket(2));
+
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    assert!(device.queue.is_empty());
+    let server = sockets.get_mut::<udp::Socket>(server);
+    assert_eq!(server.recv().map(|(data, _)| data), Ok(&b"ping"[..]));
+    assert_eq!(iface.stats().checksum_errors, 0);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4", feature = "socket-udp"))]
+fn test_checksum_offload() {
+    let (mut iface, mut sockets, mut device) = create_ip();
+    let local = Ipv4Address::new(127, 0, 0, 1);
+    let remote = Ipv4Address::new(127, 0, 0, 2);
+
+    // The device computes and verifies all checksums.
+    iface.inner.caps.checksum = ChecksumCapabilities::ignored();
+
+    let rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 8]);
+    let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 8]);
+    let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+    socket.bind(7).unwrap();
+    socket
+        .send_slice(b"ping", IpEndpoint::new(remote.into(), 9))
+        .unwrap();
+    let handle = sockets.add(socket);
+
+    iface.socket_egress(&mut device, &mut sockets);
+    let bytes = device.queue.pop_front().unwrap();
+    let packet = Ipv4Packet::new_checked(&bytes[..]).unwrap();
+    assert_eq!(packet.checksum(), 0);
+    let udp = UdpPacket::new_checked(packet.payload()).unwrap();
+    assert_eq!(udp.checksum(), 0);
+    assert_eq!(udp.payload(), b"ping");
+
+    // Received checksums are not verified.
+    let mut bytes = udp_packet((remote, 9), (local, 7));
+    bytes[10] ^= 0xff;
+    bytes[20 + 6] ^= 0xff;
+    device.queue.push_back(bytes);
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    let socket = sockets.get_mut::<udp::Socket>(handle);
+    assert_eq!(socket.recv().map(|(data, _)| data), Ok(&b"ping"[..]));
+    assert_eq!(iface.stats().checksum_errors, 0);
+}
+
+#[test]
+#[cfg(all(
+    feature = "medium-ip",
+    feature = "medium-ethernet",
+    feature = "proto-ipv4",
+    feature = "proto-ipv6",
+    feature = "socket-udp"
+))]
+fn test_clat() {
+    let (mut iface, mut sockets, mut device) = create_ip();
+    let clat_ipv4_addr = Ipv4Address::new(192, 0, 0, 2);
+    let clat_ipv6_addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2);
+    let remote_ipv4_addr = Ipv4Address::new(8, 8, 8, 8);
+    let remote_ipv6_addr = Ipv6Address::new(0x64, 0xff9b, 0, 0, 0, 0, 0x0808, 0x0808);
+    iface.update_ip_addrs(|ip_addrs| {
+        ip_addrs
+            .push(IpCidr::new(clat_ipv4_addr.into(), 29))
+            .unwrap();
+    });
+    iface.set_clat(Some(ClatConfig::new(clat_ipv4_addr, clat_ipv6_addr)));
+
+    // The NAT64 prefix is learned from router advertisements.
+    let prefix = Ipv6Cidr::new(Ipv6Address::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0), 96);
+    let router_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    let icmp_repr = Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+        hop_limit: 64,
+        flags: NdiscRouterFlags::empty(),
+        router_lifetime: Duration::from_secs(1800),
+        reachable_time: Duration::ZERO,
+        retrans_time: Duration::ZERO,
+        lladdr: None,
+        mtu: None,
+        prefix_info: None,
+        captive_portal: None,
+        pref64: Some(NdiscPref64 {
+            lifetime: Duration::from_secs(600),
+            prefix,
+        }),
+        sixlowpan_context: None,
+    });
+    let ipv6_repr = Ipv6Repr {
+        src_addr: router_addr,
+        dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+        next_header: IpProtocol::Icmpv6,
+        payload_len: icmp_repr.buffer_len(),
+        hop_limit: 0xff,
+        traffic_class: 0,
+        flow_label: 0,
+    };
+    let mut bytes = vec![0; ipv6_repr.buffer_len() + icmp_repr.buffer_len()];
+    ipv6_repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes));
+    icmp_repr.emit(
+        &router_addr.into(),
+        &Ipv6Address::LINK_LOCAL_ALL_NODES.into(),
+        &mut Icmpv6Packet::new_unchecked(&mut bytes[ipv6_repr.buffer_len()..]),
+        &ChecksumCapabilities::default(),
+    );
+    iface
+        .inner
+        .process_ipv6(&mut sockets, &Ipv6Packet::new_unchecked(&bytes));
+    assert_eq!(iface.nat64_prefix(), Some(prefix));
+    assert_eq!(
+        iface.context().ip_mtu_from(&clat_ipv4_addr.into()),
+        iface.context().ip_mtu() - 20
+    );
+
+    let rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 8]);
+    let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 8]);
+    let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+    socket.bind((clat_ipv4_addr, 49152)).unwrap();
+    socket
+        .send_slice(b"ping", (remote_ipv4_addr, 53).into())
+        .unwrap();
+    let handle = sockets.add(socket);
+
+    // Datagrams sent to IPv4 addresses go out as IPv6 datagrams to the NAT64 prefix.
+    iface.socket_egress(&mut device, &mut sockets);
+    let bytes = device.queue.pop_front().unwrap();
+    let packet = Ipv6Packet::new_checked(&bytes[..]).unwrap();
+    assert_eq!(packet.src_addr(), clat_ipv6_addr);
+    assert_eq!(packet.dst_addr(), remote_ipv6_addr);
+    let udp_packet = UdpPacket::new_checked(packet.payload()).unwrap();
+    assert!(udp_packet.verify_checksum(&clat_ipv6_addr.into(), &remote_ipv6_addr.into()));
+    assert_eq!(udp_packet.payload(), b"ping");
+
+    // And the replies come back from the IPv4 address.
+    let udp_repr = UdpRepr {
+        src_port: 53,
+        dst_port: 49152,
+    };
+    let ipv6_repr = Ipv6Repr {
+        src_addr: remote_ipv6_addr,
+        dst_addr: clat_ipv6_addr,
+        next_header: IpProtocol::Udp,
+        payload_len: udp_repr.header_len() + 4,
+        hop_limit: 64,
+        traffic_class: 0,
+        flow_label: 0,
+    };
+    let mut bytes = vec![0; ipv6_repr.buffer_len() + ipv6_repr.payload_len];
+    ipv6_repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes));
+    udp_repr.emit(
+        &mut UdpPacket::new_unchecked(&mut bytes[ipv6_repr.buffer_len()..]),
+        &remote_ipv6_addr.into(),
+        &clat_ipv6_addr.into(),
+        4,
+        |buf| buf.copy_from_slice(b"pong"),
+        &ChecksumCapabilities::default(),
+    );
+    device.queue.push_back(bytes);
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    assert_eq!(
+        sockets.get_mut::<udp::Socket>(handle).recv(),
+        Ok((&b"pong"[..], (remote_ipv4_addr, 53).into()))
+    );
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-rpl"))]
+fn test_rpl_storing_mode() {
+    let (mut iface, _, mut device) = create_ip();
+    let link_local_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    iface.update_ip_addrs(|ip_addrs| {
+        ip_addrs
+            .push(IpCidr::new(link_local_addr.into(), 64))
+            .unwrap();
+    });
+    while iface.poll_address_event().is_some() {}
+    iface.set_rpl(Some(RplConfig::new(0x1e)));
+
+    let dodag_id = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 0x100);
+    let parent_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+    let child_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 3);
+    let child_target = Ipv6Cidr::new(Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 3), 128);
+    let src_repr = |src_addr, dst_addr| Ipv6Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::Icmpv6,
+        payload_len: 0,
+        hop_limit: 0xff,
+        traffic_class: 0,
+        flow_label: 0,
+    };
+    let mut conf = [0; 16];
+    RplOptionRepr::DodagConfiguration(RplDodagConfiguration::default())
+        .emit(&mut RplOption::new_unchecked(&mut conf[..]));
+    let dio = |rank| RplRepr::DodagInformationObject {
+        rpl_instance_id: 0x1e,
+        version_number: 240,
+        rank,
+        grounded: true,
+        mode_of_operation: RplModeOfOperation::StoringWithoutMulticast,
+        dodag_preference: 0,
+        dtsn: 240,
+        dodag_id,
+        options: &conf,
+    };
+    let sent = |device: &mut Loopback| -> std::vec::Vec<(Ipv6Address, std::vec::Vec<u8>)> {
+        device
+            .queue
+            .drain(..)
+            .map(|bytes| {
+                let packet = Ipv6Packet::new_checked(&bytes[..]).unwrap();
+                (packet.dst_addr(), packet.payload().to_vec())
+            })
+            .collect()
+    };
+    let parse = |dst_addr: Ipv6Address, payload: &[u8]| -> RplRepr<'static> {
+        let payload = std::vec::Vec::leak(payload.to_vec());
+        let packet = Icmpv6Packet::new_checked(&payload[..]).unwrap();
+        match Icmpv6Repr::parse(
+            &link_local_addr.into(),
+            &dst_addr.into(),
+            &packet,
+            &ChecksumCapabilities::default(),
+        ) {
+            Ok(Icmpv6Repr::Rpl(repr)) => repr,
+            repr => panic!("unexpected {repr:?}"),
+        }
+    };
+
+    // DODAG information is solicited until a DODAG is joined.
+    assert!(iface.rpl_egress(&mut device));
+    let packets = sent(&mut device);
+    assert_eq!(packets.len(), 1);
+    assert_eq!(packets[0].0, Ipv6Address::LINK_LOCAL_ALL_RPL_NODES);
+    assert_eq!(
+        parse(packets[0].0, &packets[0].1),
+        RplRepr::DodagInformationSolicitation { options: &[] }
+    );
+    assert!(!iface.rpl_egress(&mut device));
+
+    // The root becomes the preferred parent, and the default route goes through it.
+    let from_parent = src_repr(parent_addr, Ipv6Address::LINK_LOCAL_ALL_RPL_NODES);
+    assert_eq!(iface.inner.process_rpl(from_parent, dio(256)), None);
+    assert_eq!(iface.rpl_dodag_id(), Some(dodag_id));
+    assert_eq!(iface.rpl_preferred_parent(), Some(parent_addr));
+    assert_eq!(iface.rpl_rank(), Some(256 + 3 * 256));
+    assert!(iface
+        .routes()
+        .iter()
+        .any(|route| route.via_router == Some(parent_addr.into())));
+
+    // A child advertises its address, and a route downwards is installed.
+    let mut options = [0; 26];
+    let target = RplOptionRepr::RplTarget(child_target);
+    target.emit(&mut RplOption::new_unchecked(&mut options[..]));
+    RplOptionRepr::TransitInformation(RplTransitInformation {
+        external: false,
+        path_control: 0,
+        path_sequence: 240,
+        path_lifetime: 30,
+        parent_address: None,
+    })
+    .emit(&mut RplOption::new_unchecked(
+        &mut options[target.buffer_len()..],
+    ));
+    let dao = RplRepr::DestinationAdvertisementObject {
+        rpl_instance_id: 0x1e,
+        expect_ack: true,
+        sequence: 241,
+        dodag_id: Some(dodag_id),
+        options: &options,
+    };
+    match iface
+        .inner
+        .process_rpl(src_repr(child_addr, link_local_addr), dao)
+    {
+        Some(IpPacket::Icmpv6((ipv6_repr, Icmpv6Repr::Rpl(ack)))) => {
+            assert_eq!(ipv6_repr.dst_addr, child_addr);
+            assert_eq!(
+                ack,
+                RplRepr::DestinationAdvertisementObjectAck {
+                    rpl_instance_id: 0x1e,
+                    sequence: 241,
+                    status: 0,
+                    dodag_id: Some(dodag_id),
+                    options: &[],
+                }
+            );
+        }
+        packet => panic!("unexpected {packet:?}"),
+    }
+    assert!(iface
+        .routes()
+        .iter()
+        .any(|route| route.cidr == child_target.into()
+            && route.via_router == Some(child_addr.into())));
+
+    // The node advertises the DODAG, and its own address and its child's to its parent.
+    iface.inner.now = Instant::from_secs(1);
+    while iface.rpl_egress(&mut device) {}
+    let packets = sent(&mut device);
+    assert!(packets.iter().any(|(dst_addr, payload)| matches!(
+        parse(*dst_addr, payload),
+        RplRepr::DodagInformationObject { rank: 1024, .. }
+    )));
+    let (_, payload) = packets
+        .iter()
+        .find(|(dst_addr, _)| *dst_addr == parent_addr)
+        .unwrap();
+    let targets: std::vec::Vec<_> = parse(parent_addr, payload)
+        .options()
+        .filter_map(|option| match option {
+            Ok(RplOptionRepr::RplTarget(target)) => Some(target),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        targets,
+        [
+            Ipv6Cidr::new(Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1), 128),
+            child_target
+        ]
+    );
+
+    // The parent leaving the DODAG leaves the node without a default route.
+    assert_eq!(
+        iface.inner.process_rpl(from_parent, dio(RPL_INFINITE_RANK)),
+        None
+    );
+    assert_eq!(iface.rpl_dodag_id(), None);
+    assert!(!iface
+        .routes()
+        .iter()
+        .any(|route| route.via_router == Some(parent_addr.into())));
+}
+
+#[test]
+#[cfg(feature = "medium-ieee802154")]
+fn test_ieee802154_pan_association() {
+    let (mut coordinator, _, mut coordinator_device) = create_ieee802154();
+    let coordinator_addr = Ieee802154Address::Extended([0x01; 8]);
+    coordinator.set_hardware_addr(coordinator_addr.into());
+    coordinator.inner.pan_id = Some(Ieee802154Pan(0xbeef));
+    coordinator.set_pan_coordinator(true);
+
+    let (mut node, _, mut node_device) = create_ieee802154();
+    let node_addr = Ieee802154Address::Extended([0x02; 8]);
+    node.set_hardware_addr(node_addr.into());
+    node.join_pan(None);
+
+    // Deliver the frames sent by an interface to the other one.
+    let deliver = |from: &mut Loopback, to: &mut Interface| -> usize {
+        let frames: std::vec::Vec<std::vec::Vec<u8>> = from.queue.drain(..).collect();
+        let mut sockets = SocketSet::new(vec![]);
+        for frame in &frames {
+            let reply = to
+                .inner
+                .process_ieee802154(&mut sockets, &frame[..], &mut to.fragments);
+            assert!(reply.is_none());
+        }
+        frames.len()
+    };
+
+    // The node looks for a coordinator, and asks it to associate.
+    assert!(node.pan_egress(&mut node_device));
+    assert_eq!(deliver(&mut node_device, &mut coordinator), 1);
+    assert!(coordinator.pan_egress(&mut coordinator_device));
+    assert_eq!(deliver(&mut coordinator_device, &mut node), 1);
+    assert!(node.pan_egress(&mut node_device));
+    assert_eq!(deliver(&mut node_device, &mut coordinator), 1);
+
+    // The response waits for the node to poll it.
+    assert!(!coordinator.pan_egress(&mut coordinator_device));
+    assert!(!node.pan_egress(&mut node_device));
+    node.inner.now += Duration::from_millis(500);
+    assert!(node.pan_egress(&mut node_device));
+    assert_eq!(deliver(&mut node_device, &mut coordinator), 1);
+    assert!(coordinator.pan_egress(&mut coordinator_device));
+    assert_eq!(deliver(&mut coordinator_device, &mut node), 1);
+
+    let short_addr = Ieee802154Address::Short([0x00, 0x01]);
+    assert!(!node.is_joining_pan());
+    assert_eq!(node.pan_id(), Some(Ieee802154Pan(0xbeef)));
+    assert_eq!(
+        node.pan_association(),
+        Some(PanAssociation {
+            pan_id: Ieee802154Pan(0xbeef),
+            coordinator: coordinator_addr,
+            short_addr: Some(short_addr),
+        })
+    );
+    assert_eq!(
+        coordinator.pan_devices().collect::<std::vec::Vec<_>>(),
+        vec![(node_addr, short_addr)]
+    );
+}
+
+#[test]
+#[cfg(all(feature = "socket-udp", feature = "proto-ipv4"))]
+fn test_udp_flow_demux() {
+    static UDP_PAYLOAD: [u8; 5] = [0x48, 0x65, 0x6c, 0x6c, 0x6f];
+
+    let (mut iface, mut sockets, _device) = create(MEDIUM);
+    let add_socket = |sockets: &mut SocketSet, port| {
+        let rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 30]);
+        let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 15]);
+        let mut udp_socket = udp::Socket::new(rx_buffer, tx_buffer);
+        udp_socket.bind(port).unwrap();
+        sockets.add(udp_socket)
+    };
+    let handles: std::vec::Vec<_> = (1000..1003)
+        .map(|port| add_socket(&mut sockets, port))
+        .collect();
+
+    // Return whether a socket accepted a datagram sent to the port.
+    let deliver = |iface: &mut Interface, sockets: &mut SocketSet, dst_port| {
+        let udp_repr = UdpRepr {
+            src_port: 4000,
+            dst_port,
+        };
+        let ip_repr = IpRepr::Ipv4(Ipv4Repr {
+            src_addr: Ipv4Address::new(127, 0, 0, 2),
+            dst_addr: Ipv4Address::new(127, 0, 0, 1),
+            next_header: IpProtocol::Udp,
+            payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
+            hop_limit: 0x40,
+            dscp: 0,
+        });
+        let mut udp_bytes = vec![0u8; 13];
+        let mut packet = UdpPacket::new_unchecked(&mut udp_bytes);
+        udp_repr.emit(
+            &mut packet,
+            &ip_repr.src_addr(),
+            &ip_repr.dst_addr(),
+            UDP_PAYLOAD.len(),
+            |buf| buf.copy_from_slice(&UDP_PAYLOAD),
+            &ChecksumCapabilities::default(),
+        );
+        let response = iface.inner.process_udp(
+            sockets,
+            ip_repr,
+            udp_repr,
+            false,
+            &UDP_PAYLOAD,
+            packet.into_inner(),
+        );
+        response.is_none()
+    };
+
+    // The second datagram of the flow is found in the flow table.
+    assert!(deliver(&mut iface, &mut sockets, 1001));
+    assert!(deliver(&mut iface, &mut sockets, 1001));
+    assert!(!sockets.get_mut::<udp::Socket>(handles[0]).can_recv());
+    let socket = sockets.get_mut::<udp::Socket>(handles[1]);
+    assert!(socket.recv().is_ok());
+    assert!(socket.recv().is_ok());
+
+    // A socket reusing the slot doesn't receive the datagrams of the flow.
+    sockets.remove(handles[1]);
+    let handle = add_socket(&mut sockets, 2000);
+    assert!(!deliver(&mut iface, &mut sockets, 1001));
+    assert!(!sockets.get_mut::<udp::Socket>(handle).can_recv());
+    assert!(deliver(&mut iface, &mut sockets, 2000));
+    assert!(sockets.get_mut::<udp::Socket>(handle).can_recv());
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4", feature = "socket-udp"))]
+fn test_socket_priority() {
+    let (mut iface, mut sockets, mut device) = create_ip();
+    let remote = IpEndpoint::new(Ipv4Address::new(127, 0, 0, 2).into(), 9);
+
+    for (port, priority) in [
+        (1, SocketPriority::Bulk),
+        (2, SocketPriority::Normal),
+        (3, SocketPriority::Control),
+    ] {
+        let rx_buffer = udp::PacketBuffer::new(vec![], vec![]);
+        let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 8]);
+        let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+        socket.bind(port).unwrap();
+        socket.send_slice(b"1", remote).unwrap();
+        socket.send_slice(b"2", remote).unwrap();
+        let handle = sockets.add(socket);
+        assert_eq!(sockets.priority(handle), SocketPriority::Normal);
+        sockets.set_priority(handle, priority);
+    }
+
+    // With a budget of one frame per poll, the higher classes transmit first.
+    iface.set_poll_budget(PollBudget::packets(1));
+    for (port, payload) in [
+        (3, b"1"),
+        (3, b"2"),
+        (2, b"1"),
+        (2, b"2"),
+        (1, b"1"),
+        (1, b"2"),
+    ] {
+        device.queue.clear();
+        iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+        assert_eq!(device.queue.len(), 1);
+        let packet = Ipv4Packet::new_checked(&device.queue[0][..]).unwrap();
+        let udp = UdpPacket::new_checked(packet.payload()).unwrap();
+        assert_eq!((udp.src_port(), udp.payload()), (port, &payload[..]));
+    }
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4", feature = "socket-udp"))]
+fn test_socket_events() {
+    let (mut iface, mut sockets, mut device) = create_ip();
+    let local = IpEndpoint::new(Ipv4Address::new(127, 0, 0, 1).into(), 1);
+
+    let handles: std::vec::Vec<_> = (1..=2)
+        .map(|port| {
+            let rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 8]);
+            let tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 8]);
+            let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+            socket.bind(port).unwrap();
+            sockets.add(socket)
+        })
+        .collect();
+
+    iface.set_socket_events(true);
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    assert_eq!(
+        iface.poll_socket_event(),
+        Some(SocketEvent::Writable(handles[0]))
+    );
+    assert_eq!(
+        iface.poll_socket_event(),
+        Some(SocketEvent::Writable(handles[1]))
+    );
+    assert_eq!(iface.poll_socket_event(), None);
+
+    // Only the receiving socket changed, as the sender was writable already.
+    sockets
+        .get_mut::<udp::Socket>(handles[1])
+        .send_slice(b"abc", local)
+        .unwrap();
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    assert_eq!(
+        iface.poll_socket_event(),
+        Some(SocketEvent::Readable(handles[0]))
+    );
+    assert_eq!(iface.poll_socket_event(), None);
+
+    sockets.get_mut::<udp::Socket>(handles[0]).recv().unwrap();
+    iface.poll(Instant::from_millis(0), &mut device, &mut sockets);
+    assert_eq!(iface.poll_socket_event(), None);
+    assert!(!iface.socket_events_lost());
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_user_rng() {
+    struct Counter(u32);
+
+    impl Rng for Counter {
+        fn next_u32(&mut self) -> u32 {
+            self.0 += 1;
+            self.0
+        }
+
+        fn quality(&self) -> EntropyQuality {
+            EntropyQuality::Cryptographic
+        }
+    }
+
+    let mut device = Loopback::new(Medium::Ip);
+    let config = Config::new();
+    let iface = Interface::new(config, &mut device);
+    assert_eq!(iface.rand_quality(), EntropyQuality::Predictable);
+
+    let mut config = Config::new();
+    config.rng = Some(std::boxed::Box::leak(std::boxed::Box::new(Counter(100))));
+    let mut iface = Interface::new(config, &mut device);
+    assert_eq!(iface.rand_quality(), EntropyQuality::Cryptographic);
+
+    let first = iface.inner.rand().rand_u32();
+    assert!(first > 100);
+    assert_eq!(iface.inner.rand().rand_u32(), first + 1);
+}
+
+#[test]
+#[cfg(all(feature = "socket-tcp", feature = "proto-ipv4", feature = "medium-ip"))]
+fn test_tcp_isn() {
+    let local = IpEndpoint::new(IpAddress::v4(127, 0, 0, 1), 49500);
+    let remote = IpEndpoint::new(IpAddress::v4(127, 0, 0, 2), 80);
+
+    let mut device = Loopback::new(Medium::Ip);
+    let mut config = Config::new();
+    config.tcp_isn_secret = Some([0x42; 16]);
+    let mut iface = Interface::new(config, &mut device);
+
+    // The sequence number advances with the clock...
+    let isn = iface.inner.tcp_isn(local, remote);
+    iface.inner.now = Instant::from_micros(400);
+    assert_eq!(iface.inner.tcp_isn(local, remote), isn + 100);
+
+    // ...and differs between connections.
+    let other = IpEndpoint::new(remote.addr, 81);
+    assert_ne!(iface.inner.tcp_isn(local, other), isn + 100);
+
+    // The same secret gives the same sequence numbers after a reboot.
+    let mut config = Config::new();
+    config.tcp_isn_secret = Some([0x42; 16]);
+    let mut rebooted = Interface::new(config, &mut device);
+    rebooted.inner.now = Instant::from_micros(400);
+    assert_eq!(rebooted.inner.tcp_isn(local, remote), isn + 100);
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_poll_clock() {
+    let (mut iface, mut sockets, mut device) = create_ip();
+
+    let clock = || Instant::from_micros(1_000_250);
+    iface.poll_clock(&clock, &mut device, &mut sockets);
+    assert_eq!(iface.inner.now, Instant::from_micros(1_000_250));
+}
+
+#[test]
+#[cfg(all(
+    feature = "medium-ethernet",
+    feature = "proto-ipv4-fragmentation",
+    feature = "proto-igmp"
+))]
+fn test_caller_provided_storage() {
+    use crate::iface::{
+        MulticastGroupStorage, NeighborStorage, Route, RouteStorage, RouteTableFull,
+    };
+    use std::boxed::Box;
+
+    let mut device = Loopback::new(Medium::Ethernet);
+    let mut config = Config::new();
+    config.hardware_addr = Some(EthernetAddress::default().into());
+    config.neighbor_cache_storage = Some(Box::leak(Box::new([NeighborStorage::EMPTY; 2])));
+    config.route_storage = Some(Box::leak(Box::new([RouteStorage::EMPTY; 1])));
+    config.ipv4_multicast_group_storage =
+        Some(Box::leak(Box::new([MulticastGroupStorage::EMPTY; 1])));
+    config.ipv4_reassembly_contexts = 2;
+    config.ipv4_reassembly_storage = Some(Box::leak(Box::new([0; 1000])));
+    let mut iface = Interface::new(config, &mut device);
+
+    // The routing table holds as many routes as the storage has entries.
+    let gateway = Ipv4Address::new(192, 168, 1, 1);
+    assert_eq!(iface.routes_mut().add_default_ipv4_route(gateway), Ok(None));
+    let route = Route::new_host(IpAddress::v4(10, 0, 0, 1), None);
+    assert_eq!(iface.routes_mut().add(route), Err(RouteTableFull));
+
+    // So does the table of multicast groups.
+    let timestamp = Instant::ZERO;
+    let group = Ipv4Address::new(224, 0, 0, 56);
+    assert!(iface
+        .join_multicast_group(&mut device, group, timestamp)
+        .is_ok());
+    assert_eq!(
+        iface.join_multicast_group(&mut device, Ipv4Address::new(224, 0, 0, 57), timestamp),
+        Err(MulticastError::GroupTableFull)
+    );
+
+    // Reassembly buffers are split equally from the storage.
+    assert_eq!(iface.ipv4_reassembly_stats().capacity, 2);
+}
@@ -4,17 +4,58 @@ The `iface` module deals with the *network interfaces*. It filters incoming fram
 provides lookup and caching of hardware addresses, and handles management packets.
 */
 
+mod forwarder;
 #[cfg(any(feature = "proto-ipv4", feature = "proto-sixlowpan"))]
 mod fragmentation;
+#[cfg(feature = "socket-tcp")]
+mod host_cache;
 mod interface;
+#[cfg(feature = "proto-ipv4")]
+mod nat;
 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
 mod neighbor;
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+mod prefix_share;
+#[cfg(all(feature = "socket-dhcpv4", feature = "socket-dhcpv6"))]
+mod provisioner;
 mod route;
 mod socket_meta;
 mod socket_set;
 
-pub use self::interface::{Config, Interface, InterfaceInner as Context};
-pub use self::route::{Route, RouteTableFull, Routes};
+pub use self::forwarder::{
+    ForwardPacketBuffer, ForwardPacketMetadata, Forwarder, PortId, PortTableFull,
+};
+#[cfg(any(feature = "proto-ipv4", feature = "proto-sixlowpan"))]
+pub use self::fragmentation::ReassemblyStats;
+#[cfg(feature = "socket-tcp")]
+pub use self::host_cache::{HostCache, HostMetrics};
+pub use self::interface::{
+    AddressError, AddressEvent, AddressState, Config, Interface, InterfaceInner as Context,
+    InterfaceStats, PacketHook, PollBudget, SocketEvent, Verdict,
+};
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+pub use self::neighbor::NeighborStorage;
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+pub use self::prefix_share::PrefixShare;
+#[cfg(all(feature = "socket-dhcpv4", feature = "socket-dhcpv6"))]
+pub use self::provisioner::{ConfigOrigin, ProvisionEvent, ProvisionedItem, Provisioner};
+pub use self::route::{Route, RouteStorage, RouteTableFull, Routes};
+pub use crate::rand::{EntropyQuality, Rng};
+#[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+pub use interface::ClatConfig;
+#[cfg(feature = "medium-ieee802154")]
+pub use interface::PanAssociation;
+#[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+pub use interface::RouterAdvertConfig;
+#[cfg(feature = "proto-rpl")]
+pub use interface::RplConfig;
+#[cfg(feature = "socket-tcp")]
+pub use interface::TcpResetPolicy;
+#[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+pub(crate) use interface::{nat64_embed, NAT64_PREFIX_LENS};
 #[cfg(feature = "proto-igmp")]
-pub use interface::MulticastError;
+pub use interface::{MulticastError, MulticastGroupStorage};
+#[cfg(feature = "proto-ipv4")]
+pub use nat::{Nat44, NatMapping, NatTimeouts};
+pub use socket_meta::SocketPriority;
 pub use socket_set::{SocketHandle, SocketSet, SocketStorage};
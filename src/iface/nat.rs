@@ -0,0 +1,354 @@
+use heapless::Vec;
+
+use super::forwarder::PortId;
+use crate::time::{Duration, Instant};
+use crate::wire::{
+    Icmpv4Message, Icmpv4Packet, IpAddress, IpProtocol, Ipv4Address, TcpPacket, UdpPacket,
+};
+
+pub const MAX_NAT_MAPPING_COUNT: usize = 64;
+
+/// How long an idle mapping is kept, per protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NatTimeouts {
+    /// TCP connections that completed their handshake.
+    pub tcp_established: Duration,
+    /// TCP connections being opened or closed.
+    pub tcp_transitory: Duration,
+    pub udp: Duration,
+    /// ICMP echo queries.
+    pub icmp: Duration,
+}
+
+impl Default for NatTimeouts {
+    /// The defaults of RFC 5382, RFC 4787 and RFC 5508.
+    fn default() -> Self {
+        Self {
+            tcp_established: Duration::from_secs(7440),
+            tcp_transitory: Duration::from_secs(240),
+            udp: Duration::from_secs(300),
+            icmp: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A connection tracked by the translator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NatMapping {
+    /// `Tcp`, `Udp` or `Icmp`.
+    pub protocol: IpProtocol,
+    pub inside_addr: Ipv4Address,
+    /// The port, or for ICMP, the echo identifier, used by the inside host.
+    pub inside_port: u16,
+    /// The port, or echo identifier, the connection uses outside.
+    pub outside_port: u16,
+    pub expires_at: Instant,
+    tcp_state: TcpState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum TcpState {
+    Opening,
+    Established,
+    Closing,
+}
+
+impl NatMapping {
+    /// Extend the lifetime of the mapping after it carried a packet with the given
+    /// transport payload.
+    pub(crate) fn refresh(&mut self, timeouts: &NatTimeouts, payload: &[u8], timestamp: Instant) {
+        let timeout = match self.protocol {
+            IpProtocol::Tcp => {
+                if let Ok(packet) = TcpPacket::new_checked(payload) {
+                    if packet.fin() || packet.rst() {
+                        self.tcp_state = TcpState::Closing;
+                    } else if !packet.syn() && self.tcp_state == TcpState::Opening {
+                        self.tcp_state = TcpState::Established;
+                    }
+                }
+                match self.tcp_state {
+                    TcpState::Established => timeouts.tcp_established,
+                    _ => timeouts.tcp_transitory,
+                }
+            }
+            IpProtocol::Udp => timeouts.udp,
+            _ => timeouts.icmp,
+        };
+        self.expires_at = timestamp + timeout;
+    }
+}
+
+/// A network address and port translator (NAPT) for IPv4.
+///
+/// Packets forwarded out of the `outside` port of a [`Forwarder`] from any other port
+/// have their source address replaced with the first IPv4 address of the outside
+/// interface, and their source port, or ICMP echo identifier, replaced with one from the
+/// port range of the translator. Packets received on the outside port for a mapped port
+/// are translated back and forwarded to the inside host. Packets sent by inside hosts to
+/// a mapped port of the outside address are translated both ways and sent back inside
+/// (hairpinning).
+///
+/// Mappings are endpoint-independent: an inside host keeps the same outside port for all
+/// destinations, and any outside host can reach it through the mapping, as recommended
+/// by RFC 4787 for application transparency. Only TCP, UDP and ICMP echo are translated;
+/// other protocols, IP fragments, and ICMP errors are dropped rather than forwarded
+/// untranslated.
+///
+/// [`Forwarder`]: super::Forwarder
+#[derive(Debug)]
+pub struct Nat44 {
+    outside: PortId,
+    timeouts: NatTimeouts,
+    first_port: u16,
+    last_port: u16,
+    next_port: u16,
+    mappings: Vec<NatMapping, MAX_NAT_MAPPING_COUNT>,
+}
+
+impl Nat44 {
+    /// Create a translator for the connections leaving through `outside`, with the
+    /// default timeouts and the 49152..=65535 port range.
+    pub fn new(outside: PortId) -> Self {
+        Self {
+            outside,
+            timeouts: NatTimeouts::default(),
+            first_port: 49152,
+            last_port: 65535,
+            next_port: 49152,
+            mappings: Vec::new(),
+        }
+    }
+
+    /// Return the port connections are translated on.
+    pub fn outside(&self) -> PortId {
+        self.outside
+    }
+
+    /// Return the idle timeouts of the mappings.
+    pub fn timeouts(&self) -> NatTimeouts {
+        self.timeouts
+    }
+
+    /// Set the idle timeouts of the mappings.
+    pub fn set_timeouts(&mut self, timeouts: NatTimeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Set the range outside ports are allocated from. Local sockets of the outside
+    /// interface should not be bound in this range.
+    ///
+    /// # Panics
+    /// This function panics if `first` is greater than `last`.
+    pub fn set_port_range(&mut self, first: u16, last: u16) {
+        assert!(first <= last);
+        self.first_port = first;
+        self.last_port = last;
+        self.next_port = first;
+        self.mappings.clear();
+    }
+
+    /// Return an iterator over the tracked connections.
+    pub fn mappings(&self) -> impl Iterator<Item = &NatMapping> {
+        self.mappings.iter()
+    }
+
+    /// Forget all tracked connections.
+    pub fn clear(&mut self) {
+        self.mappings.clear();
+    }
+
+    /// Return the mapping of an outgoing connection, creating it if needed.
+    pub(crate) fn outbound(
+        &mut self,
+        protocol: IpProtocol,
+        inside_addr: Ipv4Address,
+        inside_port: u16,
+        timestamp: Instant,
+    ) -> Option<&mut NatMapping> {
+        self.mappings
+            .retain(|mapping| mapping.expires_at > timestamp);
+
+        let i = match self.mappings.iter().position(|mapping| {
+            mapping.protocol == protocol
+                && mapping.inside_addr == inside_addr
+                && mapping.inside_port == inside_port
+        }) {
+            Some(i) => i,
+            None => {
+                let outside_port = self.allocate_port(protocol)?;
+                let mapping = NatMapping {
+                    protocol,
+                    inside_addr,
+                    inside_port,
+                    outside_port,
+                    expires_at: timestamp,
+                    tcp_state: TcpState::Opening,
+                };
+                if self.mappings.push(mapping).is_err() {
+                    net_debug!("nat: mapping table full");
+                    return None;
+                }
+                net_trace!(
+                    "nat: mapped {}:{} to port {}",
+                    inside_addr,
+                    inside_port,
+                    outside_port
+                );
+                self.mappings.len() - 1
+            }
+        };
+        Some(&mut self.mappings[i])
+    }
+
+    /// Return the mapping of an incoming connection, if any.
+    pub(crate) fn inbound(
+        &mut self,
+        protocol: IpProtocol,
+        outside_port: u16,
+        timestamp: Instant,
+    ) -> Option<&mut NatMapping> {
+        self.mappings.iter_mut().find(|mapping| {
+            mapping.protocol == protocol
+                && mapping.outside_port == outside_port
+                && mapping.expires_at > timestamp
+        })
+    }
+
+    fn allocate_port(&mut self, protocol: IpProtocol) -> Option<u16> {
+        let count = u32::from(self.last_port - self.first_port) + 1;
+        for _ in 0..count {
+            let port = self.next_port;
+            self.next_port = if port == self.last_port {
+                self.first_port
+            } else {
+                port + 1
+            };
+            if !self
+                .mappings
+                .iter()
+                .any(|mapping| mapping.protocol == protocol && mapping.outside_port == port)
+            {
+                return Some(port);
+            }
+        }
+        net_debug!("nat: no outside port left");
+        None
+    }
+}
+
+/// Return the port, or echo identifier, of the source and the destination of a
+/// transport payload, if it can be translated.
+pub(crate) fn transport_ports(protocol: IpProtocol, payload: &[u8]) -> Option<(u16, u16)> {
+    let word = |offset: usize| {
+        payload
+            .get(offset..offset + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    };
+    match protocol {
+        IpProtocol::Tcp | IpProtocol::Udp => Some((word(0)?, word(2)?)),
+        // The identifier of an echo request stands for its source port, the one of an
+        // echo reply for its destination port.
+        IpProtocol::Icmp => match payload.first() {
+            Some(8) => Some((word(4)?, 0)),
+            Some(0) => Some((0, word(4)?)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Rewrite the source and destination of a transport payload, and its checksum.
+pub(crate) fn rewrite_transport(
+    protocol: IpProtocol,
+    payload: &mut [u8],
+    (src_addr, src_port): (Ipv4Address, u16),
+    (dst_addr, dst_port): (Ipv4Address, u16),
+) {
+    let (src_addr, dst_addr) = (IpAddress::Ipv4(src_addr), IpAddress::Ipv4(dst_addr));
+    match protocol {
+        IpProtocol::Tcp => {
+            let mut packet = TcpPacket::new_unchecked(payload);
+            packet.set_src_port(src_port);
+            packet.set_dst_port(dst_port);
+            packet.fill_checksum(&src_addr, &dst_addr);
+        }
+        IpProtocol::Udp => {
+            let mut packet = UdpPacket::new_unchecked(payload);
+            packet.set_src_port(src_port);
+            packet.set_dst_port(dst_port);
+            // A zero checksum means there is none.
+            if packet.checksum() != 0 {
+                packet.fill_checksum(&src_addr, &dst_addr);
+            }
+        }
+        IpProtocol::Icmp => {
+            let mut packet = Icmpv4Packet::new_unchecked(payload);
+            match packet.msg_type() {
+                Icmpv4Message::EchoRequest => packet.set_echo_ident(src_port),
+                _ => packet.set_echo_ident(dst_port),
+            }
+            packet.fill_checksum();
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const INSIDE_ADDR: Ipv4Address = Ipv4Address([192, 168, 1, 2]);
+
+    #[test]
+    fn test_mapping() {
+        let mut nat = Nat44::new(PortId(0));
+        nat.set_port_range(1000, 1001);
+        let now = Instant::from_millis(0);
+        let timeouts = nat.timeouts();
+        let mut map = |protocol, inside_port| {
+            let mapping = nat.outbound(protocol, INSIDE_ADDR, inside_port, now)?;
+            mapping.refresh(&timeouts, &[], now);
+            Some(mapping.outside_port)
+        };
+
+        assert_eq!(map(IpProtocol::Udp, 5000), Some(1000));
+        // Mappings are endpoint-independent.
+        assert_eq!(map(IpProtocol::Udp, 5000), Some(1000));
+        assert_eq!(map(IpProtocol::Udp, 5001), Some(1001));
+        // The port range is exhausted, but only for UDP.
+        assert_eq!(map(IpProtocol::Udp, 5002), None);
+        assert_eq!(map(IpProtocol::Icmp, 5002), Some(1000));
+    }
+
+    #[test]
+    fn test_timeouts() {
+        let mut nat = Nat44::new(PortId(0));
+        let now = Instant::from_millis(0);
+        let timeouts = nat.timeouts();
+
+        let mapping = nat
+            .outbound(IpProtocol::Tcp, INSIDE_ADDR, 5000, now)
+            .unwrap();
+        let mut syn = [0u8; 20];
+        let mut packet = TcpPacket::new_unchecked(&mut syn[..]);
+        packet.set_header_len(20);
+        packet.set_syn(true);
+        mapping.refresh(&timeouts, &syn, now);
+        assert_eq!(mapping.expires_at, now + timeouts.tcp_transitory);
+
+        let mut ack = [0u8; 20];
+        let mut packet = TcpPacket::new_unchecked(&mut ack[..]);
+        packet.set_header_len(20);
+        packet.set_ack(true);
+        mapping.refresh(&timeouts, &ack, now);
+        assert_eq!(mapping.expires_at, now + timeouts.tcp_established);
+
+        let later = now + timeouts.tcp_established;
+        assert!(nat.inbound(IpProtocol::Tcp, 49152, now).is_some());
+        assert!(nat.inbound(IpProtocol::Tcp, 49152, later).is_none());
+        assert!(nat.inbound(IpProtocol::Udp, 49152, now).is_none());
+    }
+}
@@ -1,11 +1,13 @@
 // Heads up! Before working on this file you should read, at least,
 // the parts of RFC 1122 that discuss ARP.
 
-use heapless::LinearMap;
+use managed::ManagedSlice;
 
+use crate::storage::{Table, TableStorage};
 use crate::time::{Duration, Instant};
 use crate::wire::{HardwareAddress, IpAddress};
 
+/// The number of entries of the neighbor cache, unless storage is provided for it.
 #[cfg(not(test))]
 pub const NEIGHBOR_CACHE_SIZE: usize = 16;
 #[cfg(test)]
@@ -45,10 +47,15 @@ impl Answer {
     }
 }
 
+/// Storage for one entry of the neighbor cache, see [`Config::neighbor_cache_storage`].
+///
+/// [`Config::neighbor_cache_storage`]: crate::iface::Config::neighbor_cache_storage
+pub type NeighborStorage = TableStorage<(IpAddress, Neighbor)>;
+
 /// A neighbor cache backed by a map.
 #[derive(Debug)]
 pub struct Cache {
-    storage: LinearMap<IpAddress, Neighbor, NEIGHBOR_CACHE_SIZE>,
+    storage: Table<'static, (IpAddress, Neighbor)>,
     silent_until: Instant,
 }
 
@@ -59,10 +66,23 @@ impl Cache {
     /// Neighbor entry lifetime, in milliseconds.
     pub(crate) const ENTRY_LIFETIME: Duration = Duration::from_millis(60_000);
 
-    /// Create a cache.
+    /// Create a cache of [`NEIGHBOR_CACHE_SIZE`] entries.
+    #[cfg(feature = "alloc")]
     pub fn new() -> Self {
         Self {
-            storage: LinearMap::new(),
+            storage: Table::with_capacity(NEIGHBOR_CACHE_SIZE),
+            silent_until: Instant::from_millis(0),
+        }
+    }
+
+    /// Create a cache with the given storage, holding as many neighbors as it has
+    /// entries.
+    pub fn with_storage<S>(storage: S) -> Self
+    where
+        S: Into<ManagedSlice<'static, NeighborStorage>>,
+    {
+        Self {
+            storage: Table::new(storage),
             silent_until: Instant::from_millis(0),
         }
     }
@@ -96,14 +116,14 @@ impl Cache {
             }
             Err((protocol_addr, neighbor)) => {
                 // If we're going down this branch, it means the cache is full, and we need to evict an entry.
-                let old_protocol_addr = *self
+                let old_protocol_addr = self
                     .storage
                     .iter()
                     .min_by_key(|(_, neighbor)| neighbor.expires_at)
                     .expect("empty neighbor cache storage")
                     .0;
 
-                let _old_neighbor = self.storage.remove(&old_protocol_addr).unwrap();
+                let _old_neighbor = self.storage.remove_key(&old_protocol_addr).unwrap();
                 match self.storage.insert(protocol_addr, neighbor) {
                     Ok(None) => {
                         net_trace!(
@@ -127,7 +147,7 @@ impl Cache {
         if let Some(&Neighbor {
             expires_at,
             hardware_addr,
-        }) = self.storage.get(protocol_addr)
+        }) = self.storage.get_value(protocol_addr)
         {
             if timestamp < expires_at {
                 return Answer::Found(hardware_addr);
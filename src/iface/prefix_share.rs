@@ -0,0 +1,228 @@
+// Sharing the /64 of an upstream interface with the hosts of a downstream link, as
+// described in RFC 7278 ("64share").
+//
+// Mobile networks give a device a single /64 on a point-to-point link. To share it,
+// the device keeps only its own address of the prefix upstream, as a /128, moves the
+// /64 to the downstream interface, and advertises it there for the hosts to configure
+// addresses with SLAAC. Neighbor Solicitations for these addresses coming from
+// upstream are answered with ND proxying, for upstream links with neighbor discovery.
+
+use super::interface::RouterAdvertConfig;
+use super::{AddressState, Interface};
+use crate::wire::{IpAddress, IpCidr, Ipv6Address, Ipv6Cidr};
+
+/// Length of the prefix shared, the only one SLAAC works with.
+const PREFIX_LEN: u8 = 64;
+
+/// The prefix shared, and the address of the device in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Shared {
+    prefix: Ipv6Cidr,
+    addr: Ipv6Address,
+}
+
+/// Share the IPv6 prefix learned on an upstream interface with a downstream link.
+///
+/// Once the upstream interface has a global /64 address, e.g. configured with SLAAC
+/// from the advertisements of a cellular network, [`update`](Self::update):
+///
+/// * narrows that address to a /128 on the upstream interface,
+/// * answers Neighbor Solicitations for the rest of the prefix on the upstream
+///   interface, see [`Interface::update_proxy_nd_prefixes`],
+/// * assigns the address as a /64 to the downstream interface, and
+/// * advertises the prefix on the downstream link, see [`Interface::set_router_advert`].
+///
+/// When the address goes away upstream, all of this is undone, and the downstream
+/// hosts are told the device is no longer a router. The downstream interface needs a
+/// link-local address to send the advertisements from. Packets are forwarded between
+/// the two interfaces by a [`Forwarder`](super::Forwarder) with a default route
+/// upstream:
+///
+/// ```rust,ignore
+/// let mut share = PrefixShare::new();
+/// loop {
+///     let now = Instant::now();
+///     forwarder.poll(wan, now, &mut wan_iface, &mut wan_device, &mut sockets);
+///     forwarder.poll(lan, now, &mut lan_iface, &mut lan_device, &mut sockets);
+///     share.update(&mut wan_iface, &mut lan_iface);
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct PrefixShare {
+    shared: Option<Shared>,
+    mtu: Option<u32>,
+}
+
+impl PrefixShare {
+    /// Create a helper sharing no prefix yet.
+    pub const fn new() -> Self {
+        Self {
+            shared: None,
+            mtu: None,
+        }
+    }
+
+    /// Return the prefix shared, if any.
+    pub fn prefix(&self) -> Option<Ipv6Cidr> {
+        self.shared.map(|shared| shared.prefix)
+    }
+
+    /// Set the MTU advertised to the downstream hosts, e.g. the MTU of the upstream
+    /// link when it is smaller than the downstream one, or `None` to advertise none.
+    pub fn set_mtu(&mut self, mtu: Option<u32>) {
+        self.mtu = mtu;
+    }
+
+    /// Follow the addresses of the upstream interface, and share or stop sharing its
+    /// prefix with the downstream interface. Returns whether the prefix shared changed.
+    ///
+    /// Call this after polling the interfaces, or whenever the address configuration
+    /// of the upstream interface changes.
+    pub fn update(&mut self, upstream: &mut Interface, downstream: &mut Interface) -> bool {
+        if let Some(shared) = self.shared {
+            let kept = upstream
+                .ip_addrs()
+                .contains(&IpCidr::Ipv6(Ipv6Cidr::new(shared.addr, 128)));
+            if kept {
+                downstream.set_router_advert(Some(self.advert_config(shared.prefix)));
+                return false;
+            }
+            self.unshare(shared, upstream, downstream);
+        }
+
+        let shared = match shareable(upstream) {
+            Some(shared) => shared,
+            None => return self.shared.take().is_some(),
+        };
+        net_debug!("prefix share: sharing {}", shared.prefix);
+
+        upstream.update_ip_addrs(|addrs| {
+            for cidr in addrs.iter_mut() {
+                if cidr.address() == IpAddress::Ipv6(shared.addr) {
+                    *cidr = IpCidr::Ipv6(Ipv6Cidr::new(shared.addr, 128));
+                }
+            }
+        });
+        upstream.update_proxy_nd_prefixes(|prefixes| {
+            if !prefixes.contains(&shared.prefix) && prefixes.push(shared.prefix).is_err() {
+                net_debug!("prefix share: too many proxied prefixes upstream");
+            }
+        });
+        if let Err(e) = downstream.add_ip_addr(IpCidr::Ipv6(Ipv6Cidr::new(shared.addr, PREFIX_LEN)))
+        {
+            net_debug!(
+                "prefix share: cannot assign {} downstream: {:?}",
+                shared.addr,
+                e
+            );
+        }
+        downstream.set_router_advert(Some(self.advert_config(shared.prefix)));
+
+        self.shared = Some(shared);
+        true
+    }
+
+    /// Undo what sharing `shared` did on both interfaces.
+    fn unshare(&mut self, shared: Shared, upstream: &mut Interface, downstream: &mut Interface) {
+        net_debug!("prefix share: no longer sharing {}", shared.prefix);
+        upstream.update_proxy_nd_prefixes(|prefixes| {
+            prefixes.retain(|prefix| *prefix != shared.prefix);
+        });
+        downstream.remove_ip_addr(shared.addr);
+        downstream.set_router_advert(None);
+    }
+
+    fn advert_config(&self, prefix: Ipv6Cidr) -> RouterAdvertConfig {
+        RouterAdvertConfig {
+            mtu: self.mtu,
+            ..RouterAdvertConfig::new(prefix)
+        }
+    }
+}
+
+/// Return the first global /64 address of the interface done with Duplicate Address
+/// Detection, and its prefix.
+fn shareable(iface: &Interface) -> Option<Shared> {
+    iface.ip_addrs().iter().find_map(|cidr| match cidr {
+        IpCidr::Ipv6(cidr)
+            if cidr.prefix_len() == PREFIX_LEN
+                && cidr.address().is_unicast()
+                && !cidr.address().is_link_local()
+                && !cidr.address().is_loopback()
+                && iface.ip_addr_state(cidr.address()) == Some(AddressState::Preferred) =>
+        {
+            let mut prefix = cidr.address();
+            prefix.0[PREFIX_LEN as usize / 8..].fill(0);
+            Some(Shared {
+                prefix: Ipv6Cidr::new(prefix, PREFIX_LEN),
+                addr: cidr.address(),
+            })
+        }
+        #[allow(unreachable_patterns)]
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::iface::Config;
+    use crate::phy::{Loopback, Medium};
+    use crate::wire::EthernetAddress;
+
+    fn iface(mac: u8) -> Interface {
+        let mut device = Loopback::new(Medium::Ethernet);
+        let mut config = Config::new();
+        config.hardware_addr = Some(EthernetAddress([0x02, 0, 0, 0, 0, mac]).into());
+        let mut iface = Interface::new(config, &mut device);
+        iface.update_ip_addrs(|addrs| {
+            addrs
+                .push(IpCidr::new(
+                    IpAddress::v6(0xfe80, 0, 0, 0, 0, 0, 0, mac as u16),
+                    64,
+                ))
+                .unwrap();
+        });
+        iface
+    }
+
+    #[test]
+    fn test_share_prefix() {
+        let mut upstream = iface(1);
+        let mut downstream = iface(2);
+        let mut share = PrefixShare::new();
+
+        let prefix = Ipv6Cidr::new(Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0), 64);
+        let addr = Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0x10);
+
+        // Nothing to share until the upstream interface has a global address.
+        assert!(!share.update(&mut upstream, &mut downstream));
+        assert_eq!(downstream.router_advert(), None);
+
+        upstream.update_ip_addrs(|addrs| {
+            addrs.push(IpCidr::new(addr.into(), 64)).unwrap();
+        });
+        assert!(share.update(&mut upstream, &mut downstream));
+        assert_eq!(share.prefix(), Some(prefix));
+        assert!(upstream.ip_addrs().contains(&IpCidr::new(addr.into(), 128)));
+        assert_eq!(upstream.proxy_nd_prefixes(), &[prefix]);
+        assert!(downstream
+            .ip_addrs()
+            .contains(&IpCidr::new(addr.into(), 64)));
+        assert_eq!(
+            downstream.router_advert(),
+            Some(RouterAdvertConfig::new(prefix))
+        );
+
+        // Sharing goes on as long as the address is kept.
+        assert!(!share.update(&mut upstream, &mut downstream));
+        assert_eq!(share.prefix(), Some(prefix));
+
+        upstream.remove_ip_addr(addr);
+        assert!(share.update(&mut upstream, &mut downstream));
+        assert_eq!(share.prefix(), None);
+        assert_eq!(upstream.proxy_nd_prefixes(), &[]);
+        assert_eq!(downstream.ip_addr_state(addr), None);
+        assert_eq!(downstream.router_advert(), None);
+    }
+}
@@ -0,0 +1,387 @@
+// Dual-stack provisioning: one event stream for the configuration provided by DHCPv4,
+// DHCPv6 and stateless address autoconfiguration.
+//
+// Each source provides a set of items: addresses, routers and DNS servers. When a
+// source changes, its new set is compared with the previous one, and an event is
+// queued for each item added or removed.
+
+use heapless::{Deque, Vec};
+
+use super::{Interface, SocketHandle, SocketSet};
+use crate::socket::{dhcpv4, dhcpv6};
+use crate::time::Instant;
+use crate::wire::dhcpv6::MAX_IA_ADDRESSES;
+use crate::wire::{HardwareAddress, IpAddress, IpCidr, Ipv6Address, DHCP_MAX_DNS_SERVER_COUNT};
+
+/// Maximum number of provisioning events queued.
+const MAX_EVENT_COUNT: usize = 32;
+
+/// Maximum number of items provided by all the sources together.
+const MAX_ITEM_COUNT: usize = 2 * (1 + DHCP_MAX_DNS_SERVER_COUNT) + MAX_IA_ADDRESSES + 2;
+
+/// Where a piece of configuration comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigOrigin {
+    /// A DHCPv4 lease.
+    Dhcpv4,
+    /// A DHCPv6 lease.
+    Dhcpv6,
+    /// A router advertisement: the IPv6 default router, and the address formed with
+    /// stateless address autoconfiguration (SLAAC).
+    Slaac,
+}
+
+/// A piece of configuration provided by a [`Provisioner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProvisionedItem {
+    /// An address to assign to the interface.
+    Address(IpCidr),
+    /// A default router.
+    Router(IpAddress),
+    /// A DNS server.
+    DnsServer(IpAddress),
+}
+
+/// A change of the configuration provided by a [`Provisioner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProvisionEvent {
+    /// A source provided a new item.
+    Added(ProvisionedItem, ConfigOrigin),
+    /// A source stopped providing an item, e.g. because its lease expired.
+    Removed(ProvisionedItem, ConfigOrigin),
+}
+
+/// Dual-stack provisioning of an interface.
+///
+/// A provisioner owns a DHCPv4 and a DHCPv6 socket, and follows the router
+/// advertisements received by the latter for stateless address autoconfiguration. It
+/// merges the configuration they provide into a single stream of events, each tagged
+/// with its [`ConfigOrigin`], so that applications do not have to poll each source:
+///
+/// ```no_run
+/// # use smoltcp::iface::{Interface, ProvisionEvent, ProvisionedItem, Provisioner, SocketSet};
+/// # fn f(iface: &mut Interface, sockets: &mut SocketSet) {
+/// let mut provisioner = Provisioner::new(sockets);
+/// // After each call to `Interface::poll`:
+/// provisioner.poll(iface, sockets);
+/// while let Some(event) = provisioner.poll_event() {
+///     match event {
+///         ProvisionEvent::Added(ProvisionedItem::Address(cidr), _) => {
+///             iface.add_ip_addr(cidr).ok();
+///         }
+///         ProvisionEvent::Removed(ProvisionedItem::Address(cidr), _) => {
+///             iface.remove_ip_addr(cidr.address());
+///         }
+///         _ => {}
+///     }
+/// }
+/// # }
+/// ```
+///
+/// The provisioner does not configure the interface itself.
+#[derive(Debug)]
+pub struct Provisioner {
+    dhcpv4: SocketHandle,
+    dhcpv6: SocketHandle,
+    /// The items currently provided, with their origin.
+    items: Vec<(ProvisionedItem, ConfigOrigin), MAX_ITEM_COUNT>,
+    events: Deque<ProvisionEvent, MAX_EVENT_COUNT>,
+}
+
+impl Provisioner {
+    /// Create a provisioner, adding its DHCP sockets to `sockets`.
+    pub fn new(sockets: &mut SocketSet<'_>) -> Self {
+        Provisioner {
+            dhcpv4: sockets.add(dhcpv4::Socket::new()),
+            dhcpv6: sockets.add(dhcpv6::Socket::new()),
+            items: Vec::new(),
+            events: Deque::new(),
+        }
+    }
+
+    /// Return the handle of the DHCPv4 socket, e.g. to change its settings.
+    pub fn dhcpv4_handle(&self) -> SocketHandle {
+        self.dhcpv4
+    }
+
+    /// Return the handle of the DHCPv6 socket, e.g. to change its settings.
+    pub fn dhcpv6_handle(&self) -> SocketHandle {
+        self.dhcpv6
+    }
+
+    /// Return the items currently provided, with their origin.
+    pub fn items(&self) -> impl Iterator<Item = (ProvisionedItem, ConfigOrigin)> + '_ {
+        self.items.iter().copied()
+    }
+
+    /// Query the sources for configuration changes, and queue the resulting events.
+    ///
+    /// This must be called after every call to [`Interface::poll`].
+    pub fn poll(&mut self, iface: &mut Interface, sockets: &mut SocketSet<'_>) {
+        let cx = iface.context();
+        let mut items = Vec::<_, MAX_ITEM_COUNT>::new();
+
+        match sockets.get_mut::<dhcpv4::Socket>(self.dhcpv4).poll() {
+            Some(dhcpv4::Event::Configured(config)) => {
+                items
+                    .push(ProvisionedItem::Address(config.address.into()))
+                    .ok();
+                if let Some(router) = config.router {
+                    items.push(ProvisionedItem::Router(router.into())).ok();
+                }
+                for server in config.dns_servers.iter() {
+                    items
+                        .push(ProvisionedItem::DnsServer((*server).into()))
+                        .ok();
+                }
+                self.update(ConfigOrigin::Dhcpv4, &items);
+            }
+            Some(dhcpv4::Event::Deconfigured) => self.update(ConfigOrigin::Dhcpv4, &[]),
+            None => {}
+        }
+
+        let socket = sockets.get_mut::<dhcpv6::Socket>(self.dhcpv6);
+        let router_config = socket.router_config().copied();
+        items.clear();
+        match socket.poll() {
+            Some(dhcpv6::Event::Configured(config)) => {
+                for (addr, cidr) in config.addresses.iter() {
+                    let cidr = IpCidr::new((*addr).into(), cidr.prefix_len());
+                    items.push(ProvisionedItem::Address(cidr)).ok();
+                }
+                for server in config.dns_servers.iter() {
+                    items
+                        .push(ProvisionedItem::DnsServer((*server).into()))
+                        .ok();
+                }
+                self.update(ConfigOrigin::Dhcpv6, &items);
+            }
+            Some(dhcpv6::Event::Deconfigured) => self.update(ConfigOrigin::Dhcpv6, &[]),
+            None => {}
+        }
+
+        items.clear();
+        match router_config {
+            Some(config) if cx.now() < config.expires_at => {
+                if let Some(router) = config.router {
+                    items.push(ProvisionedItem::Router(router.into())).ok();
+                }
+                let addr = config.slaac_prefix.zip(cx.hardware_addr()).and_then(
+                    |(prefix, hardware_addr)| slaac_address(prefix.address(), hardware_addr),
+                );
+                if let Some(addr) = addr {
+                    items
+                        .push(ProvisionedItem::Address(IpCidr::new(addr.into(), 64)))
+                        .ok();
+                }
+            }
+            _ => {}
+        }
+        self.update(ConfigOrigin::Slaac, &items);
+    }
+
+    /// Return the time at which the configuration provided by a router advertisement
+    /// expires, if any, to call [`poll`](Self::poll) then.
+    pub fn poll_at(&self, sockets: &SocketSet<'_>) -> Option<Instant> {
+        sockets
+            .get::<dhcpv6::Socket>(self.dhcpv6)
+            .router_config()
+            .map(|config| config.expires_at)
+    }
+
+    /// Return the next configuration change, if any.
+    ///
+    /// If the events are not polled, only the most recent ones are kept; the current
+    /// configuration can always be read with [`items`](Self::items).
+    pub fn poll_event(&mut self) -> Option<ProvisionEvent> {
+        self.events.pop_front()
+    }
+
+    /// Replace the items provided by `origin`.
+    fn update(&mut self, origin: ConfigOrigin, items: &[ProvisionedItem]) {
+        let mut index = 0;
+        while index < self.items.len() {
+            let (item, o) = self.items[index];
+            if o == origin && !items.contains(&item) {
+                self.items.remove(index);
+                self.push_event(ProvisionEvent::Removed(item, origin));
+            } else {
+                index += 1;
+            }
+        }
+        for item in items {
+            if !self.items.contains(&(*item, origin)) && self.items.push((*item, origin)).is_ok() {
+                self.push_event(ProvisionEvent::Added(*item, origin));
+            }
+        }
+    }
+
+    fn push_event(&mut self, event: ProvisionEvent) {
+        if self.events.is_full() {
+            self.events.pop_front();
+        }
+        // NOTE(unwrap): we've just made room.
+        self.events.push_back(event).unwrap();
+    }
+}
+
+/// Form the address of an interface from a 64-bit prefix and the modified EUI-64
+/// interface identifier derived from its MAC address, see RFC 4291 § 2.5.1.
+fn slaac_address(prefix: Ipv6Address, hardware_addr: HardwareAddress) -> Option<Ipv6Address> {
+    let mac = match hardware_addr {
+        HardwareAddress::Ethernet(mac) => mac.0,
+        #[allow(unreachable_patterns)]
+        _ => return None,
+    };
+    let mut addr = prefix.0;
+    addr[8..].copy_from_slice(&[
+        mac[0] ^ 0x02,
+        mac[1],
+        mac[2],
+        0xff,
+        0xfe,
+        mac[3],
+        mac[4],
+        mac[5],
+    ]);
+    Some(Ipv6Address(addr))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::wire::{Ipv4Address, Ipv4Cidr};
+
+    #[test]
+    fn test_update() {
+        let mut sockets = SocketSet::new(std::vec![]);
+        let mut provisioner = Provisioner::new(&mut sockets);
+
+        let addr =
+            ProvisionedItem::Address(Ipv4Cidr::new(Ipv4Address::new(10, 0, 0, 2), 24).into());
+        let router = ProvisionedItem::Router(Ipv4Address::new(10, 0, 0, 1).into());
+        let dns = ProvisionedItem::DnsServer(Ipv4Address::new(10, 0, 0, 1).into());
+        provisioner.update(ConfigOrigin::Dhcpv4, &[addr, router]);
+        provisioner.update(ConfigOrigin::Slaac, &[router]);
+        assert_eq!(
+            provisioner.poll_event(),
+            Some(ProvisionEvent::Added(addr, ConfigOrigin::Dhcpv4))
+        );
+        assert_eq!(
+            provisioner.poll_event(),
+            Some(ProvisionEvent::Added(router, ConfigOrigin::Dhcpv4))
+        );
+        assert_eq!(
+            provisioner.poll_event(),
+            Some(ProvisionEvent::Added(router, ConfigOrigin::Slaac))
+        );
+
+        // Only the changes are reported, and the other sources are left alone.
+        provisioner.update(ConfigOrigin::Dhcpv4, &[addr, dns]);
+        assert_eq!(
+            provisioner.poll_event(),
+            Some(ProvisionEvent::Removed(router, ConfigOrigin::Dhcpv4))
+        );
+        assert_eq!(
+            provisioner.poll_event(),
+            Some(ProvisionEvent::Added(dns, ConfigOrigin::Dhcpv4))
+        );
+        assert_eq!(provisioner.poll_event(), None);
+        assert_eq!(provisioner.items().count(), 3);
+
+        provisioner.update(ConfigOrigin::Dhcpv4, &[]);
+        assert_eq!(
+            provisioner.items().collect::<std::vec::Vec<_>>(),
+            [(router, ConfigOrigin::Slaac)]
+        );
+    }
+
+    #[test]
+    fn test_slaac() {
+        use crate::iface::Config;
+        use crate::phy::{Loopback, Medium};
+        use crate::time::Duration;
+        use crate::wire::{
+            EthernetAddress, Icmpv6Repr, IpProtocol, Ipv6Repr, NdiscPrefixInfoFlags,
+            NdiscPrefixInformation, NdiscRepr, NdiscRouterFlags,
+        };
+
+        let mut device = Loopback::new(Medium::Ethernet);
+        let mac = EthernetAddress([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+        let mut config = Config::new();
+        config.hardware_addr = Some(mac.into());
+        let mut iface = Interface::new(config, &mut device);
+        let mut sockets = SocketSet::new(std::vec![]);
+        let mut provisioner = Provisioner::new(&mut sockets);
+
+        let router_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+        let ip_repr = Ipv6Repr {
+            src_addr: router_addr,
+            dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: 0,
+            hop_limit: 0xff,
+            traffic_class: 0,
+            flow_label: 0,
+        };
+        let icmp_repr = Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+            hop_limit: 64,
+            flags: NdiscRouterFlags::empty(),
+            router_lifetime: Duration::from_secs(1800),
+            reachable_time: Duration::ZERO,
+            retrans_time: Duration::ZERO,
+            lladdr: None,
+            mtu: None,
+            prefix_info: Some(NdiscPrefixInformation {
+                prefix_len: 64,
+                flags: NdiscPrefixInfoFlags::ON_LINK | NdiscPrefixInfoFlags::ADDRCONF,
+                valid_lifetime: Duration::from_secs(600),
+                preferred_lifetime: Duration::from_secs(300),
+                prefix: Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0),
+            }),
+            captive_portal: None,
+            pref64: None,
+            sixlowpan_context: None,
+        });
+        sockets
+            .get_mut::<dhcpv6::Socket>(provisioner.dhcpv6_handle())
+            .process_icmpv6(iface.context(), &ip_repr, &icmp_repr);
+
+        provisioner.poll(&mut iface, &mut sockets);
+        let addr = Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0x5054, 0x00ff, 0xfe12, 0x3456);
+        let slaac = [
+            ProvisionedItem::Router(router_addr.into()),
+            ProvisionedItem::Address(IpCidr::new(addr.into(), 64)),
+        ];
+        let events: std::vec::Vec<_> = core::iter::from_fn(|| provisioner.poll_event()).collect();
+        for item in slaac {
+            assert!(events.contains(&ProvisionEvent::Added(item, ConfigOrigin::Slaac)));
+        }
+        assert_eq!(provisioner.poll_at(&sockets), Some(Instant::from_secs(600)));
+
+        // The configuration expires with the shortest lifetime.
+        iface.context().set_now(Instant::from_secs(600));
+        provisioner.poll(&mut iface, &mut sockets);
+        for item in slaac {
+            assert_eq!(
+                provisioner.poll_event(),
+                Some(ProvisionEvent::Removed(item, ConfigOrigin::Slaac))
+            );
+        }
+    }
+
+    #[test]
+    fn test_slaac_address() {
+        let prefix = Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0);
+        let mac = crate::wire::EthernetAddress([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+        assert_eq!(
+            slaac_address(prefix, mac.into()),
+            Some(Ipv6Address::new(
+                0x2001, 0xdb8, 0, 1, 0x5054, 0x00ff, 0xfe12, 0x3456
+            ))
+        );
+    }
+}
@@ -1,5 +1,7 @@
+use managed::ManagedSlice;
+
+use crate::storage::{Table, TableStorage};
 use crate::time::Instant;
-use heapless::Vec;
 
 use crate::wire::{IpAddress, IpCidr};
 #[cfg(feature = "proto-ipv4")]
@@ -7,18 +9,27 @@ use crate::wire::{Ipv4Address, Ipv4Cidr};
 #[cfg(feature = "proto-ipv6")]
 use crate::wire::{Ipv6Address, Ipv6Cidr};
 
-pub const MAX_ROUTE_COUNT: usize = 4;
+/// The number of routes of a routing table, unless storage is provided for it.
+pub const MAX_ROUTE_COUNT: usize = 8;
+
+/// Storage for one route of a routing table, see [`Routes::with_storage`].
+pub type RouteStorage = TableStorage<Route>;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct RouteTableFull;
 
-/// A prefix of addresses that should be routed via a router
-#[derive(Debug, Clone, Copy)]
+/// A prefix of addresses that should be routed via a router, or that is reachable
+/// directly on the link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Route {
     pub cidr: IpCidr,
-    pub via_router: IpAddress,
+    /// `None` means the prefix is on-link: packets are sent directly to their destination.
+    pub via_router: Option<IpAddress>,
+    /// Among the routes with the longest matching prefix, the one with the lowest
+    /// metric is used.
+    pub metric: u32,
     /// `None` means "forever".
     pub preferred_until: Option<Instant>,
     /// `None` means "forever".
@@ -32,48 +43,113 @@ const IPV6_DEFAULT: IpCidr =
     IpCidr::Ipv6(Ipv6Cidr::new(Ipv6Address::new(0, 0, 0, 0, 0, 0, 0, 0), 0));
 
 impl Route {
-    /// Returns a route to 0.0.0.0/0 via the `gateway`, with no expiry.
-    #[cfg(feature = "proto-ipv4")]
-    pub fn new_ipv4_gateway(gateway: Ipv4Address) -> Route {
+    /// Returns a route to `cidr` via `via_router`, or on-link if `None`, with a metric
+    /// of 0 and no expiry.
+    pub const fn new(cidr: IpCidr, via_router: Option<IpAddress>) -> Route {
         Route {
-            cidr: IPV4_DEFAULT,
-            via_router: gateway.into(),
+            cidr,
+            via_router,
+            metric: 0,
             preferred_until: None,
             expires_at: None,
         }
     }
 
+    /// Returns a route to 0.0.0.0/0 via the `gateway`, with no expiry.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn new_ipv4_gateway(gateway: Ipv4Address) -> Route {
+        Route::new(IPV4_DEFAULT, Some(gateway.into()))
+    }
+
     /// Returns a route to ::/0 via the `gateway`, with no expiry.
     #[cfg(feature = "proto-ipv6")]
     pub fn new_ipv6_gateway(gateway: Ipv6Address) -> Route {
-        Route {
-            cidr: IPV6_DEFAULT,
-            via_router: gateway.into(),
-            preferred_until: None,
-            expires_at: None,
-        }
+        Route::new(IPV6_DEFAULT, Some(gateway.into()))
+    }
+
+    /// Returns a route to the single address `addr` via `via_router`, or on-link if
+    /// `None`, with no expiry.
+    pub fn new_host(addr: IpAddress, via_router: Option<IpAddress>) -> Route {
+        let prefix_len = match addr {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(_) => 32,
+            #[cfg(feature = "proto-ipv6")]
+            IpAddress::Ipv6(_) => 128,
+        };
+        Route::new(IpCidr::new(addr, prefix_len), via_router)
+    }
+
+    pub(crate) fn is_expired(&self, timestamp: Instant) -> bool {
+        matches!(self.expires_at, Some(expires_at) if timestamp > expires_at)
     }
 }
 
 /// A routing table.
+///
+/// Destinations are looked up by longest prefix match. Routes that expired are
+/// ignored, and removed the next time the interface is polled.
 #[derive(Debug)]
 pub struct Routes {
-    storage: Vec<Route, MAX_ROUTE_COUNT>,
+    storage: Table<'static, Route>,
 }
 
 impl Routes {
-    /// Creates a new empty routing table.
+    /// Creates a new empty routing table, holding up to [`MAX_ROUTE_COUNT`] routes.
+    #[cfg(feature = "alloc")]
     pub fn new() -> Self {
         Self {
-            storage: Vec::new(),
+            storage: Table::with_capacity(MAX_ROUTE_COUNT),
+        }
+    }
+
+    /// Creates a new empty routing table with the given storage, holding as many
+    /// routes as it has entries.
+    pub fn with_storage<S>(storage: S) -> Self
+    where
+        S: Into<ManagedSlice<'static, RouteStorage>>,
+    {
+        Self {
+            storage: Table::new(storage),
         }
     }
 
     /// Update the routes of this node.
-    pub fn update<F: FnOnce(&mut Vec<Route, MAX_ROUTE_COUNT>)>(&mut self, f: F) {
+    pub fn update<F: FnOnce(&mut Table<'static, Route>)>(&mut self, f: F) {
         f(&mut self.storage);
     }
 
+    /// Return an iterator over the routes of this node.
+    pub fn iter(&self) -> impl Iterator<Item = &Route> {
+        self.storage.iter()
+    }
+
+    /// Add a route (ie. "ip route replace `cidr` via `via_router`").
+    ///
+    /// A route to the same prefix via the same router is replaced, e.g. to refresh its
+    /// expiry when a router advertises it again. On success, returns the replaced
+    /// route, if any.
+    pub fn add(&mut self, route: Route) -> Result<Option<Route>, RouteTableFull> {
+        let old = self.remove(route.cidr, route.via_router);
+        self.storage.push(route).map_err(|_| RouteTableFull)?;
+        Ok(old)
+    }
+
+    /// Remove the route to `cidr` via `via_router`, or the on-link one if `None`.
+    ///
+    /// Returns the removed route, if any.
+    pub fn remove(&mut self, cidr: IpCidr, via_router: Option<IpAddress>) -> Option<Route> {
+        let i = self
+            .storage
+            .iter()
+            .position(|r| r.cidr == cidr && r.via_router == via_router)?;
+        Some(self.storage.remove(i))
+    }
+
+    /// Remove the routes that expired at `timestamp`.
+    pub fn remove_expired(&mut self, timestamp: Instant) {
+        self.storage.retain(|route| !route.is_expired(timestamp))
+    }
+
     /// Add a default ipv4 gateway (ie. "ip route add 0.0.0.0/0 via `gateway`").
     ///
     /// On success, returns the previous default route, if any.
@@ -109,16 +185,8 @@ impl Routes {
     /// On success, returns the previous default route, if any.
     #[cfg(feature = "proto-ipv4")]
     pub fn remove_default_ipv4_route(&mut self) -> Option<Route> {
-        if let Some((i, _)) = self
-            .storage
-            .iter()
-            .enumerate()
-            .find(|(_, r)| r.cidr == IPV4_DEFAULT)
-        {
-            Some(self.storage.remove(i))
-        } else {
-            None
-        }
+        let i = self.storage.iter().position(|r| r.cidr == IPV4_DEFAULT)?;
+        Some(self.storage.remove(i))
     }
 
     /// Remove the default ipv6 gateway
@@ -126,35 +194,22 @@ impl Routes {
     /// On success, returns the previous default route, if any.
     #[cfg(feature = "proto-ipv6")]
     pub fn remove_default_ipv6_route(&mut self) -> Option<Route> {
-        if let Some((i, _)) = self
-            .storage
-            .iter()
-            .enumerate()
-            .find(|(_, r)| r.cidr == IPV6_DEFAULT)
-        {
-            Some(self.storage.remove(i))
-        } else {
-            None
-        }
+        let i = self.storage.iter().position(|r| r.cidr == IPV6_DEFAULT)?;
+        Some(self.storage.remove(i))
     }
 
+    /// Return the next hop towards `addr`: the router to send packets to, or `addr`
+    /// itself if it is on-link.
     pub(crate) fn lookup(&self, addr: &IpAddress, timestamp: Instant) -> Option<IpAddress> {
         assert!(addr.is_unicast());
 
         self.storage
             .iter()
             // Keep only matching routes
-            .filter(|route| {
-                if let Some(expires_at) = route.expires_at {
-                    if timestamp > expires_at {
-                        return false;
-                    }
-                }
-                route.cidr.contains_addr(addr)
-            })
-            // pick the most specific one (highest prefix_len)
-            .max_by_key(|route| route.cidr.prefix_len())
-            .map(|route| route.via_router)
+            .filter(|route| !route.is_expired(timestamp) && route.cidr.contains_addr(addr))
+            // pick the most specific one (highest prefix_len), then the lowest metric
+            .max_by_key(|route| (route.cidr.prefix_len(), core::cmp::Reverse(route.metric)))
+            .map(|route| route.via_router.unwrap_or(*addr))
     }
 }
 
@@ -235,7 +290,8 @@ mod test {
 
         let route = Route {
             cidr: cidr_1().into(),
-            via_router: ADDR_1A.into(),
+            via_router: Some(ADDR_1A.into()),
+            metric: 0,
             preferred_until: None,
             expires_at: None,
         };
@@ -266,7 +322,8 @@ mod test {
 
         let route2 = Route {
             cidr: cidr_2().into(),
-            via_router: ADDR_2A.into(),
+            via_router: Some(ADDR_2A.into()),
+            metric: 0,
             preferred_until: Some(Instant::from_millis(10)),
             expires_at: Some(Instant::from_millis(10)),
         };
@@ -316,4 +373,71 @@ mod test {
             Some(ADDR_2A.into())
         );
     }
+
+    #[test]
+    fn test_longest_prefix_match() {
+        let mut routes = Routes::new();
+        let now = Instant::from_millis(0);
+
+        // A default route, and a host route for one of its addresses.
+        #[cfg(feature = "proto-ipv6")]
+        assert_eq!(routes.add_default_ipv6_route(ADDR_2A), Ok(None));
+        #[cfg(all(feature = "proto-ipv4", not(feature = "proto-ipv6")))]
+        assert_eq!(routes.add_default_ipv4_route(ADDR_2A), Ok(None));
+        let host = Route::new_host(ADDR_1C.into(), Some(ADDR_2B.into()));
+        assert_eq!(routes.add(host), Ok(None));
+        // The prefix is on-link.
+        let on_link = Route::new(cidr_1().into(), None);
+        assert_eq!(routes.add(on_link), Ok(None));
+
+        assert_eq!(routes.lookup(&ADDR_1A.into(), now), Some(ADDR_1A.into()));
+        assert_eq!(routes.lookup(&ADDR_1C.into(), now), Some(ADDR_2B.into()));
+        assert_eq!(routes.lookup(&ADDR_2B.into(), now), Some(ADDR_2A.into()));
+        assert_eq!(routes.iter().count(), 3);
+
+        assert_eq!(routes.remove(cidr_1().into(), None), Some(on_link));
+        assert_eq!(routes.remove(cidr_1().into(), None), None);
+        assert_eq!(routes.lookup(&ADDR_1A.into(), now), Some(ADDR_2A.into()));
+    }
+
+    #[test]
+    fn test_metric_and_expiry() {
+        let mut routes = Routes::new();
+
+        // Two routers advertise the same prefix.
+        let primary = Route {
+            metric: 10,
+            expires_at: Some(Instant::from_millis(10)),
+            ..Route::new(cidr_2().into(), Some(ADDR_1A.into()))
+        };
+        let backup = Route {
+            metric: 20,
+            ..Route::new(cidr_2().into(), Some(ADDR_1B.into()))
+        };
+        assert_eq!(routes.add(backup), Ok(None));
+        assert_eq!(routes.add(primary), Ok(None));
+        assert_eq!(
+            routes.lookup(&ADDR_2A.into(), Instant::from_millis(10)),
+            Some(ADDR_1A.into())
+        );
+        assert_eq!(
+            routes.lookup(&ADDR_2A.into(), Instant::from_millis(11)),
+            Some(ADDR_1B.into())
+        );
+
+        // The primary router advertises the prefix again.
+        let refreshed = Route {
+            expires_at: Some(Instant::from_millis(20)),
+            ..primary
+        };
+        assert_eq!(routes.add(refreshed), Ok(Some(primary)));
+        assert_eq!(routes.iter().count(), 2);
+        assert_eq!(
+            routes.lookup(&ADDR_2A.into(), Instant::from_millis(11)),
+            Some(ADDR_1A.into())
+        );
+
+        routes.remove_expired(Instant::from_millis(21));
+        assert_eq!(routes.iter().collect::<std::vec::Vec<_>>(), [&backup]);
+    }
 }
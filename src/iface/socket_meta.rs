@@ -28,6 +28,71 @@ impl Default for NeighborState {
     }
 }
 
+/// Readiness of a socket, as last reported by socket events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct Readiness(u8);
+
+impl Readiness {
+    pub(crate) const READABLE: Readiness = Readiness(0x01);
+    pub(crate) const WRITABLE: Readiness = Readiness(0x02);
+    pub(crate) const CONNECTED: Readiness = Readiness(0x04);
+    pub(crate) const CLOSED: Readiness = Readiness(0x08);
+    pub(crate) const CONFIG_CHANGED: Readiness = Readiness(0x10);
+
+    pub(crate) const fn empty() -> Readiness {
+        Readiness(0)
+    }
+
+    /// Set `flag` if `condition` holds.
+    pub(crate) fn set(&mut self, flag: Readiness, condition: bool) {
+        if condition {
+            self.0 |= flag.0;
+        }
+    }
+
+    /// Return whether `flag` is set in `self` but not in `previous`.
+    pub(crate) fn became(&self, previous: Readiness, flag: Readiness) -> bool {
+        self.0 & !previous.0 & flag.0 != 0
+    }
+}
+
+impl Default for Readiness {
+    /// A new socket is considered closed, so that being closed isn't reported.
+    fn default() -> Self {
+        Readiness::CLOSED
+    }
+}
+
+/// Priority class of a socket, deciding the order in which the interface lets sockets
+/// transmit, see [SocketSet::set_priority](super::SocketSet::set_priority).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SocketPriority {
+    /// Bulk transfers, which transmit after the other sockets.
+    Bulk,
+    /// The priority class of new sockets.
+    Normal,
+    /// Control traffic, such as pings or keep-alives, which transmits before the other
+    /// sockets.
+    Control,
+}
+
+impl SocketPriority {
+    /// The priority classes, from the highest to the lowest.
+    pub(crate) const DESCENDING: [SocketPriority; 3] = [
+        SocketPriority::Control,
+        SocketPriority::Normal,
+        SocketPriority::Bulk,
+    ];
+}
+
+impl Default for SocketPriority {
+    fn default() -> Self {
+        SocketPriority::Normal
+    }
+}
+
 /// Network socket metadata.
 ///
 /// This includes things that only external (to the socket, that is) code
@@ -45,6 +110,12 @@ pub(crate) struct Meta {
     /// neighbor discovery will not prevent packets from going out
     /// and clearing down the tx queue
     non_blocking: bool,
+    /// Tag set by the application to associate its own context with the socket.
+    user_data: u32,
+    /// Priority class of the socket on egress.
+    priority: SocketPriority,
+    /// Readiness of the socket when socket events were last recorded.
+    readiness: Readiness,
 }
 
 impl Meta {
@@ -60,10 +131,31 @@ impl Meta {
         self.non_blocking = val;
     }
 
-    pub(crate) fn non_blocking(&mut self) -> bool {
+    pub(crate) fn non_blocking(&self) -> bool {
         self.non_blocking
     }
 
+    pub(crate) fn user_data(&self) -> u32 {
+        self.user_data
+    }
+
+    pub(crate) fn set_user_data(&mut self, user_data: u32) {
+        self.user_data = user_data;
+    }
+
+    pub(crate) fn priority(&self) -> SocketPriority {
+        self.priority
+    }
+
+    pub(crate) fn set_priority(&mut self, priority: SocketPriority) {
+        self.priority = priority;
+    }
+
+    /// Record the readiness of the socket, and return the previous one.
+    pub(crate) fn update_readiness(&mut self, readiness: Readiness) -> Readiness {
+        core::mem::replace(&mut self.readiness, readiness)
+    }
+
     pub(crate) fn poll_at<F>(&self, socket_poll_at: PollAt, has_neighbor: F) -> PollAt
     where
         F: Fn(IpAddress) -> bool,
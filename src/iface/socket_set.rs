@@ -1,8 +1,12 @@
 use core::fmt;
 use managed::ManagedSlice;
 
-use super::socket_meta::Meta;
+use super::socket_meta::{Meta, SocketPriority};
 use crate::socket::{AnySocket, Socket};
+use crate::wire::{IpEndpoint, IpProtocol, IpRepr};
+
+/// Number of flows whose socket is remembered by a socket set.
+const FLOW_TABLE_SIZE: usize = 128;
 
 /// Opaque struct with space for storing one socket.
 ///
@@ -11,10 +15,16 @@ use crate::socket::{AnySocket, Socket};
 #[derive(Debug, Default)]
 pub struct SocketStorage<'a> {
     inner: Option<Item<'a>>,
+    /// Generation of the handle of the next socket stored here, incremented each time
+    /// a socket is removed so that the handles of removed sockets become stale.
+    generation: u32,
 }
 
 impl<'a> SocketStorage<'a> {
-    pub const EMPTY: Self = Self { inner: None };
+    pub const EMPTY: Self = Self {
+        inner: None,
+        generation: 0,
+    };
 }
 
 /// An item of a socket set.
@@ -24,14 +34,106 @@ pub(crate) struct Item<'a> {
     pub(crate) socket: Socket<'a>,
 }
 
+impl<'a> Item<'a> {
+    fn set_non_blocking(&mut self, non_blocking: bool) {
+        self.meta.set_non_blocking(non_blocking);
+        #[cfg(feature = "socket-tcp")]
+        if let Socket::Tcp(socket) = &mut self.socket {
+            socket.set_non_blocking(non_blocking);
+        }
+    }
+}
+
 /// A handle, identifying a socket in an Interface.
+///
+/// A handle holds the slot of the socket in the set and the generation of the slot
+/// when the socket was added, so that the handle of a removed socket doesn't address
+/// another socket added to the same slot.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub struct SocketHandle(usize);
+pub struct SocketHandle {
+    index: usize,
+    generation: u32,
+}
 
 impl fmt::Display for SocketHandle {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "#{}", self.0)
+        write!(f, "#{}", self.index)
+    }
+}
+
+/// The protocol and the endpoints of the packets exchanged by a socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Flow {
+    protocol: IpProtocol,
+    local: IpEndpoint,
+    remote: IpEndpoint,
+}
+
+impl Flow {
+    /// Return the flow of a received packet.
+    pub(crate) fn new(
+        protocol: IpProtocol,
+        ip_repr: &IpRepr,
+        dst_port: u16,
+        src_port: u16,
+    ) -> Flow {
+        Flow {
+            protocol,
+            local: IpEndpoint::new(ip_repr.dst_addr(), dst_port),
+            remote: IpEndpoint::new(ip_repr.src_addr(), src_port),
+        }
+    }
+
+    /// Return the FNV-1a hash of the flow.
+    fn hash(&self) -> u32 {
+        let mut hash = 0x811c9dc5u32;
+        let mut write = |bytes: &[u8]| {
+            for &byte in bytes {
+                hash = (hash ^ byte as u32).wrapping_mul(0x01000193);
+            }
+        };
+        write(&[self.protocol.into()]);
+        write(self.local.addr.as_bytes());
+        write(&self.local.port.to_be_bytes());
+        write(self.remote.addr.as_bytes());
+        write(&self.remote.port.to_be_bytes());
+        hash
+    }
+}
+
+/// A table remembering the slot of the socket that accepted each flow, so that the
+/// socket of a packet is found without trying every socket of the set.
+///
+/// The table is direct-mapped: a flow evicts the flow with the same index. An entry
+/// only holds a guess, which is checked against the socket before it is used, so the
+/// entries aren't removed when sockets are closed or removed.
+#[derive(Debug)]
+struct FlowTable {
+    /// The upper half of the hash of the flow, and the slot of its socket.
+    entries: [Option<(u16, u16)>; FLOW_TABLE_SIZE],
+}
+
+impl FlowTable {
+    const fn new() -> FlowTable {
+        FlowTable {
+            entries: [None; FLOW_TABLE_SIZE],
+        }
+    }
+
+    fn get(&self, flow: &Flow) -> Option<usize> {
+        let hash = flow.hash();
+        match self.entries[hash as usize % FLOW_TABLE_SIZE] {
+            Some((tag, index)) if tag == (hash >> 16) as u16 => Some(index as usize),
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, flow: &Flow, index: usize) {
+        let hash = flow.hash();
+        if let Ok(index) = u16::try_from(index) {
+            self.entries[hash as usize % FLOW_TABLE_SIZE] = Some(((hash >> 16) as u16, index));
+        }
     }
 }
 
@@ -41,6 +143,7 @@ impl fmt::Display for SocketHandle {
 #[derive(Debug)]
 pub struct SocketSet<'a> {
     sockets: ManagedSlice<'a, SocketStorage<'a>>,
+    flows: FlowTable,
 }
 
 impl<'a> SocketSet<'a> {
@@ -50,7 +153,10 @@ impl<'a> SocketSet<'a> {
         SocketsT: Into<ManagedSlice<'a, SocketStorage<'a>>>,
     {
         let sockets = sockets.into();
-        SocketSet { sockets }
+        SocketSet {
+            sockets,
+            flows: FlowTable::new(),
+        }
     }
 
     /// Add a socket to the set, and return its handle.
@@ -66,17 +172,13 @@ impl<'a> SocketSet<'a> {
     /// # Panics
     /// This function panics if the storage is fixed-size (not a `Vec`) and is full.
     pub fn swap<T: AnySocket<'a>>(&mut self, handle: SocketHandle, socket: T) -> SocketHandle {
-        net_trace!("[{}]: swapping", handle.0);
+        net_trace!("[{}]: swapping", handle.index);
 
-        let non_blocking;
         let mut socket = socket.upcast();
-        match self.sockets[handle.0].inner.as_mut() {
-            Some(item) => {
-                non_blocking = item.meta.non_blocking();
-                std::mem::swap(&mut socket, &mut item.socket);
-            },
-            None => panic!("handle does not refer to a valid socket"),
-        }
+        let item = self.item_mut(handle);
+        let non_blocking = item.meta.non_blocking();
+        std::mem::swap(&mut socket, &mut item.socket);
+        item.set_non_blocking(non_blocking);
 
         let handle = self.add_ext_internal(socket, non_blocking);
         return handle;
@@ -84,6 +186,8 @@ impl<'a> SocketSet<'a> {
 
     /// Add a socket to the set, with the possibility to adjust the metadata, and return its handle.
     ///
+    /// See [set_non_blocking](Self::set_non_blocking) for the meaning of `non_blocking`.
+    ///
     /// # Panics
     /// This function panics if the storage is fixed-size (not a `Vec`) and is full.
     pub fn add_ext<T: AnySocket<'a>>(&mut self, socket: T, non_blocking: bool) -> SocketHandle
@@ -92,23 +196,29 @@ impl<'a> SocketSet<'a> {
         self.add_ext_internal(socket, non_blocking)
     }
 
-    fn add_ext_internal(&mut self, socket: Socket<'a>, non_blocking: bool) -> SocketHandle
-    {
-        fn put<'a, D>(index: usize, slot: &mut SocketStorage<'a>, socket: Socket<'a>, meta_func: D) -> SocketHandle
-        where D: FnOnce(&mut Meta) {
+    fn add_ext_internal(&mut self, socket: Socket<'a>, non_blocking: bool) -> SocketHandle {
+        fn put<'a, D>(
+            index: usize,
+            slot: &mut SocketStorage<'a>,
+            socket: Socket<'a>,
+            meta_func: D,
+        ) -> SocketHandle
+        where
+            D: FnOnce(&mut Item<'a>),
+        {
             net_trace!("[{}]: adding", index);
-            let handle = SocketHandle(index);
+            let handle = SocketHandle {
+                index,
+                generation: slot.generation,
+            };
             let mut meta = Meta::default();
             meta.handle = handle;
-            meta_func(&mut meta);
-            *slot = SocketStorage {
-                inner: Some(Item { meta, socket }),
-            };
+            meta_func(slot.inner.insert(Item { meta, socket }));
             handle
         }
 
-        let meta_func = |meta: &mut Meta| {
-            meta.set_non_blocking(non_blocking);
+        let meta_func = |item: &mut Item<'a>| {
+            item.set_non_blocking(non_blocking);
         };
 
         for (index, slot) in self.sockets.iter_mut().enumerate() {
@@ -121,50 +231,133 @@ impl<'a> SocketSet<'a> {
             ManagedSlice::Borrowed(_) => panic!("adding a socket to a full SocketSet"),
             #[cfg(feature = "alloc")]
             ManagedSlice::Owned(ref mut sockets) => {
-                sockets.push(SocketStorage { inner: None });
+                sockets.push(SocketStorage::EMPTY);
                 let index = sockets.len() - 1;
                 put(index, &mut sockets[index], socket, meta_func)
             }
         }
     }
 
+    /// Return the item of a socket, checking that the handle isn't stale.
+    fn item(&self, handle: SocketHandle) -> &Item<'a> {
+        match self.sockets[handle.index].inner.as_ref() {
+            Some(item) if item.meta.handle == handle => item,
+            Some(_) => panic!("handle refers to a removed socket whose slot was reused"),
+            None => panic!("handle does not refer to a valid socket"),
+        }
+    }
+
+    /// Return the mutable item of a socket, checking that the handle isn't stale.
+    fn item_mut(&mut self, handle: SocketHandle) -> &mut Item<'a> {
+        match self.sockets[handle.index].inner.as_mut() {
+            Some(item) if item.meta.handle == handle => item,
+            Some(_) => panic!("handle refers to a removed socket whose slot was reused"),
+            None => panic!("handle does not refer to a valid socket"),
+        }
+    }
+
     /// Get a socket from the set by its handle, as mutable.
     ///
     /// # Panics
     /// This function may panic if the handle does not belong to this socket set
-    /// or the socket has the wrong type.
+    /// or the socket has the wrong type. It panics if the socket was removed, even
+    /// if another socket was added to its slot since.
     pub fn get<T: AnySocket<'a>>(&self, handle: SocketHandle) -> &T {
-        match self.sockets[handle.0].inner.as_ref() {
-            Some(item) => {
-                T::downcast(&item.socket).expect("handle refers to a socket of a wrong type")
-            }
-            None => panic!("handle does not refer to a valid socket"),
-        }
+        T::downcast(&self.item(handle).socket).expect("handle refers to a socket of a wrong type")
     }
 
     /// Get a mutable socket from the set by its handle, as mutable.
     ///
     /// # Panics
     /// This function may panic if the handle does not belong to this socket set
-    /// or the socket has the wrong type.
+    /// or the socket has the wrong type. It panics if the socket was removed, even
+    /// if another socket was added to its slot since.
     pub fn get_mut<T: AnySocket<'a>>(&mut self, handle: SocketHandle) -> &mut T {
-        match self.sockets[handle.0].inner.as_mut() {
-            Some(item) => T::downcast_mut(&mut item.socket)
-                .expect("handle refers to a socket of a wrong type"),
-            None => panic!("handle does not refer to a valid socket"),
-        }
+        T::downcast_mut(&mut self.item_mut(handle).socket)
+            .expect("handle refers to a socket of a wrong type")
+    }
+
+    /// Return whether a socket is non-blocking.
+    ///
+    /// # Panics
+    /// This function may panic if the handle does not belong to this socket set.
+    pub fn non_blocking(&self, handle: SocketHandle) -> bool {
+        self.item(handle).meta.non_blocking()
+    }
+
+    /// Make a socket non-blocking, or not.
+    ///
+    /// Sending to a full transmit buffer and receiving from an empty receive buffer of a
+    /// non-blocking TCP socket return a `WouldBlock` error instead of `Ok(0)`, see
+    /// [tcp::Socket::set_non_blocking]. UDP sockets always return `BufferFull` and
+    /// `Exhausted` errors in these cases.
+    ///
+    /// When the neighbor a socket sends to is missing, a non-blocking socket doesn't
+    /// retry the neighbor discovery itself after the silence period, and waits for the
+    /// neighbor to be discovered instead.
+    ///
+    /// [tcp::Socket::set_non_blocking]: crate::socket::tcp::Socket::set_non_blocking
+    ///
+    /// # Panics
+    /// This function may panic if the handle does not belong to this socket set.
+    pub fn set_non_blocking(&mut self, handle: SocketHandle, non_blocking: bool) {
+        self.item_mut(handle).set_non_blocking(non_blocking)
+    }
+
+    /// Return the priority class of a socket.
+    ///
+    /// # Panics
+    /// This function may panic if the handle does not belong to this socket set.
+    pub fn priority(&self, handle: SocketHandle) -> SocketPriority {
+        self.item(handle).meta.priority()
+    }
+
+    /// Set the priority class of a socket.
+    ///
+    /// When the interface polls the sockets for packets to transmit, all the sockets of
+    /// a class transmit before those of the lower classes. As the device has a limited
+    /// number of transmit buffers, this lets e.g. a control socket send its packets
+    /// while bulk TCP transfers have data pending. Sockets of the same class take turns.
+    ///
+    /// # Panics
+    /// This function may panic if the handle does not belong to this socket set.
+    pub fn set_priority(&mut self, handle: SocketHandle, priority: SocketPriority) {
+        self.item_mut(handle).meta.set_priority(priority)
+    }
+
+    /// Return the tag the application associated with a socket, zero if none was set.
+    ///
+    /// # Panics
+    /// This function may panic if the handle does not belong to this socket set.
+    pub fn user_data(&self, handle: SocketHandle) -> u32 {
+        self.item(handle).meta.user_data()
+    }
+
+    /// Associate a tag with a socket, for example the index of the context of a
+    /// connection in a table of the application.
+    ///
+    /// The tag belongs to the handle: it is kept by [`swap`](Self::swap), and dropped
+    /// when the socket is removed.
+    ///
+    /// # Panics
+    /// This function may panic if the handle does not belong to this socket set.
+    pub fn set_user_data(&mut self, handle: SocketHandle, user_data: u32) {
+        self.item_mut(handle).meta.set_user_data(user_data)
     }
 
     /// Remove a socket from the set, without changing its state.
     ///
     /// # Panics
     /// This function may panic if the handle does not belong to this socket set.
+    /// It panics if the socket was already removed, even if another socket was
+    /// added to its slot since.
     pub fn remove(&mut self, handle: SocketHandle) -> Socket<'a> {
-        net_trace!("[{}]: removing", handle.0);
-        match self.sockets[handle.0].inner.take() {
-            Some(item) => item.socket,
-            None => panic!("handle does not refer to a valid socket"),
-        }
+        net_trace!("[{}]: removing", handle.index);
+        self.item(handle);
+        let slot = &mut self.sockets[handle.index];
+        slot.generation = slot.generation.wrapping_add(1);
+        // NOTE(unwrap): the handle was checked to refer to a socket.
+        slot.inner.take().unwrap().socket
     }
 
     /// Get an iterator to the inner sockets.
@@ -187,8 +380,107 @@ impl<'a> SocketSet<'a> {
         self.sockets.iter_mut().filter_map(|x| x.inner.as_mut())
     }
 
-    /// Iterate every socket in this set with its handle.
-    pub(crate) fn items_and_handles_mut(&mut self) -> impl Iterator<Item = (SocketHandle, &mut Item<'a>)> + '_ {
-        self.sockets.iter_mut().enumerate().filter_map(|(n, x)| x.inner.as_mut().map(|x| (SocketHandle(n), x)))
+    /// Iterate every socket in this set with the index of its slot, starting at slot
+    /// `start` and wrapping around.
+    pub(crate) fn items_mut_from(
+        &mut self,
+        start: usize,
+    ) -> impl Iterator<Item = (usize, &mut Item<'a>)> + '_ {
+        let start = if start < self.sockets.len() { start } else { 0 };
+        let (head, tail) = self.sockets.split_at_mut(start);
+        let tail = tail
+            .iter_mut()
+            .enumerate()
+            .map(move |(n, x)| (start + n, x));
+        tail.chain(head.iter_mut().enumerate())
+            .filter_map(|(n, x)| x.inner.as_mut().map(|x| (n, x)))
+    }
+
+    /// Return the handle of the first socket that `accepts` a packet of `flow`.
+    ///
+    /// The socket that last accepted a packet of the flow is tried first, and every
+    /// socket of the set after it.
+    pub(crate) fn find_flow(
+        &self,
+        flow: &Flow,
+        mut accepts: impl FnMut(&Socket<'a>) -> bool,
+    ) -> Option<SocketHandle> {
+        if let Some(index) = self.flows.get(flow) {
+            match self.sockets.get(index).and_then(|slot| slot.inner.as_ref()) {
+                Some(item) if accepts(&item.socket) => return Some(item.meta.handle),
+                _ => (),
+            }
+        }
+        self.items()
+            .find(|item| accepts(&item.socket))
+            .map(|item| item.meta.handle)
+    }
+
+    /// Remember the socket that accepted a packet of `flow`.
+    pub(crate) fn cache_flow(&mut self, flow: &Flow, handle: SocketHandle) {
+        self.flows.insert(flow, handle.index);
+    }
+}
+
+#[cfg(all(test, feature = "socket-tcp"))]
+mod test {
+    use super::*;
+    use crate::socket::tcp;
+
+    fn socket() -> tcp::Socket<'static> {
+        let rx_buffer = tcp::SocketBuffer::new(vec![0; 64]);
+        let tx_buffer = tcp::SocketBuffer::new(vec![0; 64]);
+        tcp::Socket::new(rx_buffer, tx_buffer)
+    }
+
+    #[test]
+    #[should_panic(expected = "handle refers to a removed socket")]
+    fn test_stale_handle() {
+        let mut sockets = SocketSet::new(vec![]);
+
+        let stale = sockets.add(socket());
+        sockets.remove(stale);
+        // The new socket is added to the slot of the removed one.
+        let handle = sockets.add(socket());
+        assert_ne!(handle, stale);
+        sockets.get_mut::<tcp::Socket>(stale);
+    }
+
+    #[test]
+    fn test_user_data() {
+        let mut sockets = SocketSet::new(vec![]);
+
+        let handle = sockets.add(socket());
+        assert_eq!(sockets.user_data(handle), 0);
+        sockets.set_user_data(handle, 42);
+        assert_eq!(sockets.user_data(handle), 42);
+
+        // The old socket gets a new handle, without the tag.
+        let old = sockets.swap(handle, socket());
+        assert_eq!(sockets.user_data(handle), 42);
+        assert_eq!(sockets.user_data(old), 0);
+
+        // A socket added to the slot of a removed one starts without a tag.
+        sockets.remove(handle);
+        let handle = sockets.add(socket());
+        assert_eq!(sockets.user_data(handle), 0);
+    }
+
+    #[test]
+    fn test_non_blocking() {
+        let mut sockets = SocketSet::new(vec![]);
+
+        let handle = sockets.add_ext(socket(), true);
+        assert!(sockets.non_blocking(handle));
+        assert!(sockets.get::<tcp::Socket>(handle).non_blocking());
+
+        // The socket swapped in takes the flag of the handle.
+        let old = sockets.swap(handle, socket());
+        assert!(sockets.get::<tcp::Socket>(handle).non_blocking());
+        assert!(sockets.get::<tcp::Socket>(old).non_blocking());
+
+        sockets.set_non_blocking(handle, false);
+        assert!(!sockets.non_blocking(handle));
+        assert!(!sockets.get::<tcp::Socket>(handle).non_blocking());
     }
 }
@@ -107,9 +107,10 @@ compile_error!("You must enable at least one of the following features: proto-ip
         feature = "socket-icmp",
         feature = "socket-dhcpv4",
         feature = "socket-dns",
+        feature = "socket-packet",
     ))
 ))]
-compile_error!("If you enable the socket feature, you must enable at least one of the following features: socket-raw, socket-udp, socket-tcp, socket-icmp, socket-dhcpv4, socket-dns");
+compile_error!("If you enable the socket feature, you must enable at least one of the following features: socket-raw, socket-udp, socket-tcp, socket-icmp, socket-dhcpv4, socket-dns, socket-packet");
 
 #[cfg(all(
     feature = "socket",
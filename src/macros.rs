@@ -1,44 +1,77 @@
+// Each macro takes the level of the message, then an optional `target: "..."` naming
+// the log target instead of the module path, then the format string and its arguments.
+// Format strings must stay compatible with `defmt`, which ignores the target.
+
 #[cfg(not(test))]
 #[cfg(feature = "log")]
 macro_rules! net_log {
+    (trace, target: $target:expr, $($arg:expr),*) => { log::trace!(target: $target, $($arg),*) };
+    (debug, target: $target:expr, $($arg:expr),*) => { log::debug!(target: $target, $($arg),*) };
+    (info, target: $target:expr, $($arg:expr),*) => { log::info!(target: $target, $($arg),*) };
+    (warn, target: $target:expr, $($arg:expr),*) => { log::warn!(target: $target, $($arg),*) };
+    (error, target: $target:expr, $($arg:expr),*) => { log::error!(target: $target, $($arg),*) };
     (trace, $($arg:expr),*) => { log::trace!($($arg),*) };
     (debug, $($arg:expr),*) => { log::debug!($($arg),*) };
+    (info, $($arg:expr),*) => { log::info!($($arg),*) };
     (warn, $($arg:expr),*) => { log::warn!($($arg),*) };
+    (error, $($arg:expr),*) => { log::error!($($arg),*) };
 }
 
 #[cfg(test)]
 #[cfg(feature = "log")]
 macro_rules! net_log {
+    ($level:ident, target: $target:expr, $($arg:expr),*) => { net_log!($level, $($arg),*) };
     (trace, $($arg:expr),*) => { println!($($arg),*) };
     (debug, $($arg:expr),*) => { println!($($arg),*) };
+    (info, $($arg:expr),*) => { println!($($arg),*) };
     (warn, $($arg:expr),*) => { println!($($arg),*) };
+    (error, $($arg:expr),*) => { println!($($arg),*) };
 }
 
 #[cfg(feature = "defmt")]
 macro_rules! net_log {
+    ($level:ident, target: $target:expr, $($arg:expr),*) => { net_log!($level, $($arg),*) };
     (trace, $($arg:expr),*) => { defmt::trace!($($arg),*) };
     (debug, $($arg:expr),*) => { defmt::debug!($($arg),*) };
+    (info, $($arg:expr),*) => { defmt::info!($($arg),*) };
     (warn, $($arg:expr),*) => { defmt::warn!($($arg),*) };
+    (error, $($arg:expr),*) => { defmt::error!($($arg),*) };
 }
 
 #[cfg(not(any(feature = "log", feature = "defmt")))]
 macro_rules! net_log {
+    ($level:ident, target: $target:expr, $($arg:expr),*) => {{ let _ = $target; $( let _ = $arg; )* }};
     ($level:ident, $($arg:expr),*) => {{ $( let _ = $arg; )* }}
 }
 
 macro_rules! net_trace {
+    (target: $target:expr, $($arg:expr),*) => (net_log!(trace, target: $target, $($arg),*));
     ($($arg:expr),*) => (net_log!(trace, $($arg),*));
 }
 
 macro_rules! net_debug {
+    (target: $target:expr, $($arg:expr),*) => (net_log!(debug, target: $target, $($arg),*));
     ($($arg:expr),*) => (net_log!(debug, $($arg),*));
 }
 
+#[allow(unused_macros)]
+macro_rules! net_info {
+    (target: $target:expr, $($arg:expr),*) => (net_log!(info, target: $target, $($arg),*));
+    ($($arg:expr),*) => (net_log!(info, $($arg),*));
+}
+
 #[allow(unused_macros)]
 macro_rules! net_warn {
+    (target: $target:expr, $($arg:expr),*) => (net_log!(warn, target: $target, $($arg),*));
     ($($arg:expr),*) => (net_log!(warn, $($arg),*));
 }
 
+#[allow(unused_macros)]
+macro_rules! net_error {
+    (target: $target:expr, $($arg:expr),*) => (net_log!(error, target: $target, $($arg),*));
+    ($($arg:expr),*) => (net_log!(error, $($arg),*));
+}
+
 macro_rules! enum_with_unknown {
     (
         $( #[$enum_attr:meta] )*
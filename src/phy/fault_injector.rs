@@ -1,5 +1,6 @@
 use crate::phy::{self, Device, DeviceCapabilities};
 use crate::time::{Duration, Instant};
+use crate::wire::IpVersion;
 
 // We use our own RNG to stay compatible with #![no_std].
 // The use of the RNG below has a slight bias, but it doesn't matter.
@@ -15,6 +16,9 @@ fn xorshift32(state: &mut u32) -> u32 {
 // This could be fixed once associated consts are stable.
 const MTU: usize = 1536;
 
+/// The number of received packets that can be held back at once.
+const HELD_PACKETS: usize = 4;
+
 #[derive(Debug, Default, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 struct Config {
@@ -24,6 +28,10 @@ struct Config {
     max_tx_rate: u64,
     max_rx_rate: u64,
     interval: Duration,
+    reorder_pct: u8,
+    reorder_window: Duration,
+    duplicate_pct: u8,
+    max_jitter: Duration,
 }
 
 #[derive(Debug, Clone)]
@@ -48,6 +56,13 @@ impl State {
         buffer[index] ^= bit;
     }
 
+    fn jitter(&mut self, config: &Config) -> Duration {
+        match config.max_jitter.total_micros() {
+            0 => Duration::ZERO,
+            max => Duration::from_micros(xorshift32(&mut self.rng_seed) as u64 % (max + 1)),
+        }
+    }
+
     fn refill(&mut self, config: &Config, timestamp: Instant) {
         if timestamp - self.refilled_at > config.interval {
             self.tx_bucket = config.max_tx_rate;
@@ -85,17 +100,40 @@ impl State {
     }
 }
 
+/// A received packet held back by the fault injector.
+#[derive(Debug, Clone, Copy)]
+struct Held {
+    buf: [u8; MTU],
+    len: usize,
+    release_at: Option<Instant>,
+}
+
+impl Held {
+    const EMPTY: Held = Held {
+        buf: [0; MTU],
+        len: 0,
+        release_at: None,
+    };
+}
+
 /// A fault injector device.
 ///
 /// A fault injector is a device that alters packets traversing through it to simulate
-/// adverse network conditions (such as random packet loss or corruption), or software
-/// or hardware limitations (such as a limited number or size of usable network buffers).
+/// adverse network conditions (such as random packet loss, corruption, reordering,
+/// duplication or latency jitter), or software or hardware limitations (such as a limited
+/// number or size of usable network buffers).
+///
+/// Reordering, duplication and jitter only apply to received packets; wrap the devices
+/// at both ends of a link to disturb both directions. Packets held back are released
+/// by the first call to `receive` after they are due, and only a few of them can be
+/// held at once; beyond that, packets are received without delay.
 #[derive(Debug)]
 pub struct FaultInjector<D: Device> {
     inner: D,
     state: State,
     config: Config,
     rx_buf: [u8; MTU],
+    held: [Held; HELD_PACKETS],
 }
 
 impl<D: Device> FaultInjector<D> {
@@ -111,6 +149,7 @@ impl<D: Device> FaultInjector<D> {
             },
             config: Config::default(),
             rx_buf: [0u8; MTU],
+            held: [Held::EMPTY; HELD_PACKETS],
         }
     }
 
@@ -149,6 +188,26 @@ impl<D: Device> FaultInjector<D> {
         self.config.interval
     }
 
+    /// Return the probability of reordering a received packet, in percents.
+    pub fn reorder_chance(&self) -> u8 {
+        self.config.reorder_pct
+    }
+
+    /// Return the time a reordered packet is held back for.
+    pub fn reorder_window(&self) -> Duration {
+        self.config.reorder_window
+    }
+
+    /// Return the probability of duplicating a received packet, in percents.
+    pub fn duplicate_chance(&self) -> u8 {
+        self.config.duplicate_pct
+    }
+
+    /// Return the maximum latency added to a received packet.
+    pub fn max_jitter(&self) -> Duration {
+        self.config.max_jitter
+    }
+
     /// Set the probability of corrupting a packet, in percents.
     ///
     /// # Panics
@@ -171,6 +230,46 @@ impl<D: Device> FaultInjector<D> {
         self.config.drop_pct = pct
     }
 
+    /// Set the probability of reordering a received packet, in percents.
+    ///
+    /// A reordered packet is held back for the [reorder window], letting the packets
+    /// received in the meantime overtake it.
+    ///
+    /// # Panics
+    /// This function panics if the probability is not between 0% and 100%.
+    ///
+    /// [reorder window]: #method.set_reorder_window
+    pub fn set_reorder_chance(&mut self, pct: u8) {
+        if pct > 100 {
+            panic!("percentage out of range")
+        }
+        self.config.reorder_pct = pct
+    }
+
+    /// Set the time a reordered packet is held back for.
+    pub fn set_reorder_window(&mut self, window: Duration) {
+        self.config.reorder_window = window
+    }
+
+    /// Set the probability of duplicating a received packet, in percents.
+    ///
+    /// # Panics
+    /// This function panics if the probability is not between 0% and 100%.
+    pub fn set_duplicate_chance(&mut self, pct: u8) {
+        if pct > 100 {
+            panic!("percentage out of range")
+        }
+        self.config.duplicate_pct = pct
+    }
+
+    /// Set the maximum latency added to a received packet.
+    ///
+    /// Each received packet is delayed by a random time up to this maximum, so that
+    /// packets may also be reordered.
+    pub fn set_max_jitter(&mut self, jitter: Duration) {
+        self.config.max_jitter = jitter
+    }
+
     /// Set the maximum packet size, in octets.
     pub fn set_max_packet_size(&mut self, size: usize) {
         self.config.max_size = size
@@ -193,6 +292,32 @@ impl<D: Device> FaultInjector<D> {
     }
 }
 
+impl<D: Device> FaultInjector<D> {
+    /// Return the index of the held back packet to release first, if any is due.
+    fn held_due(&self, timestamp: Instant) -> Option<usize> {
+        self.held
+            .iter()
+            .enumerate()
+            .filter_map(|(index, held)| Some((index, held.release_at?)))
+            .filter(|&(_, release_at)| release_at <= timestamp)
+            .min_by_key(|&(_, release_at)| release_at)
+            .map(|(index, _)| index)
+    }
+
+    /// Hold back a copy of the packet until `release_at`, returning false if there is no room.
+    fn hold(held: &mut [Held], packet: &[u8], release_at: Instant) -> bool {
+        match held.iter_mut().find(|held| held.release_at.is_none()) {
+            Some(held) => {
+                held.buf[..packet.len()].copy_from_slice(packet);
+                held.len = packet.len();
+                held.release_at = Some(release_at);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
 impl<D: Device> Device for FaultInjector<D> {
     type RxToken<'a> = RxToken<'a>
     where
@@ -206,10 +331,31 @@ impl<D: Device> Device for FaultInjector<D> {
         if caps.max_transmission_unit > MTU {
             caps.max_transmission_unit = MTU;
         }
+        // Segments would not fit the buffers either.
+        caps.max_segmentation_size = None;
         caps
     }
 
     fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        if let Some(index) = self.held_due(timestamp) {
+            let tx_token = self.inner.transmit(timestamp)?;
+            let held = &mut self.held[index];
+            held.release_at = None;
+            net_trace!("rx: releasing a held back packet");
+
+            let rx = RxToken {
+                buf: &held.buf[..held.len],
+            };
+            let tx = TxToken {
+                state: &mut self.state,
+                config: self.config,
+                token: tx_token,
+                junk: [0; MTU],
+                timestamp,
+            };
+            return Some((rx, tx));
+        }
+
         let (rx_token, tx_token) = self.inner.receive(timestamp)?;
 
         let len = super::RxToken::consume(rx_token, |buffer| {
@@ -240,6 +386,22 @@ impl<D: Device> Device for FaultInjector<D> {
             self.state.corrupt(&mut buf[..]);
         }
 
+        if self.state.maybe(self.config.duplicate_pct) {
+            let release_at = timestamp + self.state.jitter(&self.config);
+            if Self::hold(&mut self.held, buf, release_at) {
+                net_trace!("rx: randomly duplicating a packet");
+            }
+        }
+
+        let mut delay = self.state.jitter(&self.config);
+        if self.state.maybe(self.config.reorder_pct) {
+            delay += self.config.reorder_window;
+        }
+        if delay > Duration::ZERO && Self::hold(&mut self.held, buf, timestamp + delay) {
+            net_trace!("rx: holding back a packet for {}", delay);
+            return None;
+        }
+
         let rx = RxToken { buf };
         let tx = TxToken {
             state: &mut self.state,
@@ -315,4 +477,8 @@ impl<'a, Tx: phy::TxToken> phy::TxToken for TxToken<'a, Tx> {
             f(buf)
         })
     }
+
+    fn set_ip_version(&mut self, version: IpVersion) {
+        self.token.set_ip_version(version)
+    }
 }
@@ -1,5 +1,6 @@
 use crate::phy::{self, Device, DeviceCapabilities};
 use crate::time::Instant;
+use crate::wire::IpVersion;
 
 // This could be fixed once associated consts are stable.
 const MTU: usize = 1536;
@@ -58,6 +59,8 @@ where
         if caps.max_transmission_unit > MTU {
             caps.max_transmission_unit = MTU;
         }
+        // Segments would not fit the buffers either.
+        caps.max_segmentation_size = None;
         caps
     }
 
@@ -129,4 +132,8 @@ impl<'a, Tx: phy::TxToken, FTx: Fuzzer> phy::TxToken for TxToken<'a, Tx, FTx> {
             result
         })
     }
+
+    fn set_ip_version(&mut self, version: IpVersion) {
+        self.token.set_ip_version(version)
+    }
 }
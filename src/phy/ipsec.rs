@@ -0,0 +1,1093 @@
+use alloc::vec::Vec;
+
+use super::tunnel::Endpoints;
+use crate::phy::{
+    self, ChecksumCapabilities, Device, DeviceCapabilities, HardwareTimestamp, Medium,
+};
+use crate::time::Instant;
+#[cfg(feature = "proto-ipv4")]
+use crate::wire::Ipv4Packet;
+use crate::wire::{
+    AhPacket, AhRepr, EspPacket, EspRepr, EspTrailer, IpAddress, IpCidr, IpProtocol, IpVersion,
+    AH_HEADER_LEN, ESP_HEADER_LEN, ESP_TRAILER_LEN,
+};
+#[cfg(feature = "medium-ethernet")]
+use crate::wire::{EthernetFrame, EthernetProtocol, ETHERNET_HEADER_LEN};
+#[cfg(feature = "proto-ipv6")]
+use crate::wire::{Ipv6Packet, IPV6_HEADER_LEN};
+
+/// The default hop limit of the outer header of tunnel mode packets.
+const DEFAULT_HOP_LIMIT: u8 = 64;
+
+/// The largest integrity check value of an AH security association.
+const MAX_ICV_LEN: usize = 32;
+
+/// The length of the start of an IP header that holds all the fields an AH integrity
+/// check value does not cover.
+const MUTABLE_FIELDS_LEN: usize = 12;
+
+/// The cryptographic transforms of the security associations of an [Ipsec] device.
+///
+/// smoltcp handles the headers, the padding, the sequence numbers and the replay windows
+/// of the packets, and leaves the ciphers and the integrity checks to this trait, which
+/// receives the SPI of the security association whose keys to use.
+pub trait Transform {
+    /// Encrypt `data` in place for the ESP security association `spi`, fill in the
+    /// initialization vector `iv`, and compute the integrity check value `icv` of the
+    /// ESP `header`, the initialization vector and the encrypted data.
+    ///
+    /// AEAD ciphers use the ESP header as associated data.
+    fn seal(&mut self, spi: u32, header: &[u8], iv: &mut [u8], data: &mut [u8], icv: &mut [u8]);
+
+    /// Verify the integrity check value `icv` of the ESP `header`, the initialization
+    /// vector `iv` and the encrypted `data` for the ESP security association `spi`, and
+    /// decrypt `data` in place.
+    ///
+    /// Return whether the integrity check value is valid, the packet is dropped otherwise.
+    fn open(&mut self, spi: u32, header: &[u8], iv: &[u8], data: &mut [u8], icv: &[u8]) -> bool;
+
+    /// Compute the integrity check value `icv` of an IP packet for the AH security
+    /// association `spi`.
+    ///
+    /// The mutable fields of the IP header and the integrity check value of the
+    /// Authentication Header are zeroed in `packet`.
+    fn authenticate(&mut self, spi: u32, packet: &[u8], icv: &mut [u8]);
+}
+
+/// The IPsec protocol of a [SecurityAssociation].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Protocol {
+    /// The Encapsulating Security Payload (RFC 4303), that encrypts and authenticates the
+    /// payload of the packets.
+    Esp,
+    /// The Authentication Header (RFC 4302), that authenticates the packets, including the
+    /// immutable fields of their IP header.
+    Ah,
+}
+
+/// The mode of a [SecurityAssociation].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    /// Protect the payload of the packets, between the hosts they are addressed to.
+    Transport,
+    /// Protect whole packets, in packets exchanged between the `local` and `remote`
+    /// security gateways.
+    Tunnel { local: IpAddress, remote: IpAddress },
+}
+
+/// A security association of an [Ipsec] device, negotiated with a peer by an external key
+/// management protocol, or configured manually.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SecurityAssociation {
+    pub protocol: Protocol,
+    pub mode: Mode,
+    /// The SPI of the packets received from the peer.
+    pub inbound_spi: u32,
+    /// The SPI of the packets sent to the peer.
+    pub outbound_spi: u32,
+    /// The length of the initialization vector of the cipher. Ignored with AH.
+    pub iv_len: usize,
+    /// The length of the integrity check value.
+    pub icv_len: usize,
+    /// The block size of the cipher, that the encrypted data is padded to. Ignored with AH.
+    pub block_size: usize,
+}
+
+/// A handle to a security association of an [Ipsec] device.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SaHandle(usize);
+
+/// The action of a [Policy].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Action {
+    /// Exchange the packets without protection.
+    Bypass,
+    /// Drop the packets.
+    Discard,
+    /// Protect the packets sent with a security association, and only accept the packets
+    /// received through it.
+    Protect(SaHandle),
+}
+
+/// An entry of the security policy database of an [Ipsec] device.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Policy {
+    /// The addresses of the peers the policy applies to.
+    pub remote: IpCidr,
+    /// The upper-layer protocol the policy applies to, or `None` for any protocol.
+    pub protocol: Option<IpProtocol>,
+    /// The local TCP or UDP port the policy applies to, or `None` for any port.
+    pub local_port: Option<u16>,
+    /// The remote TCP or UDP port the policy applies to, or `None` for any port.
+    pub remote_port: Option<u16>,
+    pub action: Action,
+}
+
+impl Policy {
+    /// Return whether the policy applies to the packets exchanged with `remote`.
+    ///
+    /// The ports are unknown for the fragments and the packets of other protocols, which
+    /// do not match policies with port selectors.
+    fn matches(&self, remote: IpAddress, protocol: IpProtocol, ports: Option<(u16, u16)>) -> bool {
+        if !self.remote.contains_addr(&remote) || self.protocol.map_or(false, |p| p != protocol) {
+            return false;
+        }
+        match (self.local_port, self.remote_port, ports) {
+            (None, None, _) => true,
+            (_, _, None) => false,
+            (local_port, remote_port, Some((local, remote))) => {
+                local_port.map_or(true, |port| port == local)
+                    && remote_port.map_or(true, |port| port == remote)
+            }
+        }
+    }
+}
+
+/// The anti-replay window of the inbound packets of a security association, see
+/// RFC 4303 § 3.4.3.
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct ReplayWindow {
+    /// The highest sequence number accepted.
+    top: u32,
+    /// The sequence numbers accepted below and including `top`, as bit `top - seq`.
+    bitmap: u64,
+}
+
+impl ReplayWindow {
+    /// Return whether a packet with the sequence number `seq` may be accepted.
+    fn check(&self, seq: u32) -> bool {
+        if seq == 0 {
+            false
+        } else if seq > self.top {
+            true
+        } else {
+            let offset = self.top - seq;
+            offset < u64::BITS && self.bitmap & (1 << offset) == 0
+        }
+    }
+
+    /// Record the sequence number `seq` of an authenticated packet.
+    fn accept(&mut self, seq: u32) {
+        if seq > self.top {
+            let shift = seq - self.top;
+            self.bitmap = if shift < u64::BITS {
+                (self.bitmap << shift) | 1
+            } else {
+                1
+            };
+            self.top = seq;
+        } else {
+            self.bitmap |= 1 << (self.top - seq);
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Sa {
+    config: SecurityAssociation,
+    endpoints: Option<Endpoints>,
+    tx_sequence: u32,
+    replay_window: ReplayWindow,
+}
+
+impl Sa {
+    /// Return the sequence number of the next packet sent, or `None` once all of them
+    /// were used and the security association must be renegotiated.
+    fn next_sequence(&mut self) -> Option<u32> {
+        self.tx_sequence = self.tx_sequence.checked_add(1)?;
+        Some(self.tx_sequence)
+    }
+
+    /// Return the largest number of octets the protection adds to a packet.
+    fn overhead(&self) -> usize {
+        let outer_len = self.endpoints.map_or(0, |endpoints| endpoints.header_len());
+        let config = &self.config;
+        match config.protocol {
+            Protocol::Esp => {
+                outer_len
+                    + ESP_HEADER_LEN
+                    + config.iv_len
+                    + (alignment(config.block_size) - 1)
+                    + ESP_TRAILER_LEN
+                    + config.icv_len
+            }
+            Protocol::Ah => outer_len + round_up(AH_HEADER_LEN + config.icv_len, 8),
+        }
+    }
+}
+
+/// Return the alignment of the encrypted data of an ESP packet.
+fn alignment(block_size: usize) -> usize {
+    block_size.max(4)
+}
+
+fn round_up(len: usize, alignment: usize) -> usize {
+    (len + alignment - 1) / alignment * alignment
+}
+
+/// Compare two integrity check values in constant time.
+fn icv_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// The fields of an IP header the device reads.
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    src_addr: IpAddress,
+    dst_addr: IpAddress,
+    /// The Protocol or Next Header field of the fixed header.
+    next_header: IpProtocol,
+    /// The length of the fixed header, including the IPv4 options.
+    header_len: usize,
+    total_len: usize,
+    /// The upper-layer protocol, after the IPv6 extension headers.
+    protocol: IpProtocol,
+    /// The offset of the upper-layer header.
+    payload_offset: usize,
+    fragment: bool,
+}
+
+impl Header {
+    fn parse(checksum_caps: &ChecksumCapabilities, data: &[u8]) -> Option<Header> {
+        match IpVersion::of_packet(data.get(..1)?).ok()? {
+            #[cfg(feature = "proto-ipv4")]
+            IpVersion::Ipv4 => {
+                let packet = Ipv4Packet::new_checked(data).ok()?;
+                if checksum_caps.ipv4.rx() && !packet.verify_checksum() {
+                    return None;
+                }
+                let header_len = packet.header_len() as usize;
+                Some(Header {
+                    src_addr: packet.src_addr().into(),
+                    dst_addr: packet.dst_addr().into(),
+                    next_header: packet.next_header(),
+                    header_len,
+                    total_len: packet.total_len() as usize,
+                    protocol: packet.next_header(),
+                    payload_offset: header_len,
+                    fragment: packet.more_frags() || packet.frag_offset() != 0,
+                })
+            }
+            #[cfg(feature = "proto-ipv6")]
+            IpVersion::Ipv6 => {
+                let packet = Ipv6Packet::new_checked(data).ok()?;
+                let total_len = packet.total_len();
+                let data = &data[..total_len];
+                let mut header = Header {
+                    src_addr: packet.src_addr().into(),
+                    dst_addr: packet.dst_addr().into(),
+                    next_header: packet.next_header(),
+                    header_len: IPV6_HEADER_LEN,
+                    total_len,
+                    protocol: packet.next_header(),
+                    payload_offset: IPV6_HEADER_LEN,
+                    fragment: false,
+                };
+                loop {
+                    let ext_len = match header.protocol {
+                        IpProtocol::HopByHop | IpProtocol::Ipv6Route | IpProtocol::Ipv6Opts => {
+                            (*data.get(header.payload_offset + 1)? as usize + 1) * 8
+                        }
+                        IpProtocol::Ipv6Frag => {
+                            header.fragment = true;
+                            8
+                        }
+                        _ => break,
+                    };
+                    header.protocol = IpProtocol::from(*data.get(header.payload_offset)?);
+                    header.payload_offset += ext_len;
+                }
+                if header.payload_offset > total_len {
+                    return None;
+                }
+                Some(header)
+            }
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    fn version(&self) -> IpVersion {
+        self.src_addr.version()
+    }
+
+    /// Return the local and remote TCP or UDP ports of `packet`, if known.
+    fn ports(&self, packet: &[u8], inbound: bool) -> Option<(u16, u16)> {
+        if self.fragment || !matches!(self.protocol, IpProtocol::Tcp | IpProtocol::Udp) {
+            return None;
+        }
+        let ports = packet.get(self.payload_offset..self.payload_offset + 4)?;
+        let src_port = u16::from_be_bytes([ports[0], ports[1]]);
+        let dst_port = u16::from_be_bytes([ports[2], ports[3]]);
+        if inbound {
+            Some((dst_port, src_port))
+        } else {
+            Some((src_port, dst_port))
+        }
+    }
+}
+
+/// Set the protocol and length fields of the IP header at the start of `packet`, whose
+/// fixed header is followed by the payload without any extension header.
+fn rewrite(checksum_caps: &ChecksumCapabilities, next_header: IpProtocol, packet: &mut [u8]) {
+    let total_len = packet.len();
+    match IpVersion::of_packet(packet) {
+        #[cfg(feature = "proto-ipv4")]
+        Ok(IpVersion::Ipv4) => {
+            let mut packet = Ipv4Packet::new_unchecked(packet);
+            packet.set_next_header(next_header);
+            packet.set_total_len(total_len as u16);
+            if checksum_caps.ipv4.tx() {
+                packet.fill_checksum();
+            } else {
+                packet.set_checksum(0);
+            }
+        }
+        #[cfg(feature = "proto-ipv6")]
+        Ok(IpVersion::Ipv6) => {
+            let mut packet = Ipv6Packet::new_unchecked(packet);
+            packet.set_next_header(next_header);
+            packet.set_payload_len((total_len - IPV6_HEADER_LEN) as u16);
+        }
+        #[allow(unreachable_patterns)]
+        _ => unreachable!(),
+    }
+}
+
+/// Compute the AH integrity check value of `packet`, with the mutable fields of its IP
+/// header zeroed, see RFC 4302 § 3.3.3.1.
+fn authenticate<T: Transform>(transform: &mut T, spi: u32, packet: &mut [u8], icv: &mut [u8]) {
+    let mut saved = [0; MUTABLE_FIELDS_LEN];
+    saved.copy_from_slice(&packet[..MUTABLE_FIELDS_LEN]);
+    match IpVersion::of_packet(packet) {
+        #[cfg(feature = "proto-ipv4")]
+        Ok(IpVersion::Ipv4) => {
+            // Type of service, flags and fragment offset, time to live, and checksum.
+            packet[1] = 0;
+            packet[6..9].fill(0);
+            packet[10..12].fill(0);
+        }
+        #[cfg(feature = "proto-ipv6")]
+        Ok(IpVersion::Ipv6) => {
+            // Traffic class, flow label, and hop limit.
+            packet[0] &= 0xf0;
+            packet[1..4].fill(0);
+            packet[7] = 0;
+        }
+        #[allow(unreachable_patterns)]
+        _ => unreachable!(),
+    }
+    transform.authenticate(spi, packet, icv);
+    packet[..MUTABLE_FIELDS_LEN].copy_from_slice(&saved);
+}
+
+/// Return the length of the link-layer header in front of the IP packet in `frame`, or
+/// `None` if the frame does not carry an IP packet.
+fn link_header_len(medium: Medium, frame: &[u8]) -> Option<usize> {
+    match medium {
+        Medium::Ip => Some(0),
+        #[cfg(feature = "medium-ethernet")]
+        Medium::Ethernet => {
+            let frame = EthernetFrame::new_checked(frame).ok()?;
+            match frame.ethertype() {
+                EthernetProtocol::Ipv4 | EthernetProtocol::Ipv6 => Some(ETHERNET_HEADER_LEN),
+                _ => None,
+            }
+        }
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// Set the protocol of the link-layer header of `frame` to the version of the IP packet
+/// following it.
+#[cfg(feature = "medium-ethernet")]
+fn set_link_protocol(medium: Medium, frame: &mut [u8], version: IpVersion) {
+    if medium == Medium::Ethernet {
+        let ethertype = match version {
+            #[cfg(feature = "proto-ipv4")]
+            IpVersion::Ipv4 => EthernetProtocol::Ipv4,
+            #[cfg(feature = "proto-ipv6")]
+            IpVersion::Ipv6 => EthernetProtocol::Ipv6,
+        };
+        EthernetFrame::new_unchecked(frame).set_ethertype(ethertype);
+    }
+}
+
+#[cfg(not(feature = "medium-ethernet"))]
+fn set_link_protocol(_medium: Medium, _frame: &mut [u8], _version: IpVersion) {}
+
+/// The security policy and security association databases of an [Ipsec] device.
+#[derive(Debug)]
+struct Database {
+    policies: Vec<Policy>,
+    sas: Vec<Sa>,
+}
+
+impl Database {
+    /// Return the action of the first policy matching the IP packet at the start of
+    /// `packet`, or bypass if there is none.
+    fn action(&self, header: &Header, packet: &[u8], inbound: bool) -> Action {
+        let remote = if inbound {
+            header.src_addr
+        } else {
+            header.dst_addr
+        };
+        let ports = header.ports(packet, inbound);
+        self.policies
+            .iter()
+            .find(|policy| policy.matches(remote, header.protocol, ports))
+            .map_or(Action::Bypass, |policy| policy.action)
+    }
+
+    /// Process a frame sent by the interface in `buffer`, in place.
+    ///
+    /// Return whether to send the resulting frame.
+    fn outbound<T: Transform>(
+        &mut self,
+        transform: &mut T,
+        checksum_caps: &ChecksumCapabilities,
+        medium: Medium,
+        buffer: &mut Vec<u8>,
+    ) -> bool {
+        let link_len = match link_header_len(medium, buffer) {
+            Some(link_len) => link_len,
+            None => return true,
+        };
+        // The interface emits correct headers, the checksum is not filled in yet if the
+        // underlying device offloads it.
+        let header = match Header::parse(&ChecksumCapabilities::ignored(), &buffer[link_len..]) {
+            Some(header) => header,
+            None => return true,
+        };
+        match self.action(&header, &buffer[link_len..], false) {
+            Action::Bypass => true,
+            Action::Discard => false,
+            Action::Protect(handle) => {
+                let sa = &mut self.sas[handle.0];
+                if sa.endpoints.is_none()
+                    && (header.fragment || header.payload_offset != header.header_len)
+                {
+                    net_debug!(
+                        "ipsec: cannot protect fragments or extension headers in transport mode"
+                    );
+                    return false;
+                }
+                protect(
+                    sa,
+                    transform,
+                    checksum_caps,
+                    medium,
+                    buffer,
+                    link_len,
+                    &header,
+                )
+            }
+        }
+    }
+
+    /// Process a frame received from the underlying device in `buffer`, in place.
+    ///
+    /// Return whether to pass the resulting frame to the interface.
+    fn inbound<T: Transform>(
+        &mut self,
+        transform: &mut T,
+        checksum_caps: &ChecksumCapabilities,
+        medium: Medium,
+        buffer: &mut Vec<u8>,
+    ) -> bool {
+        let link_len = match link_header_len(medium, buffer) {
+            Some(link_len) => link_len,
+            None => return true,
+        };
+        let header = match Header::parse(checksum_caps, &buffer[link_len..]) {
+            Some(header) => header,
+            None => return false,
+        };
+        buffer.truncate(link_len + header.total_len);
+
+        if !matches!(header.protocol, IpProtocol::Esp | IpProtocol::Ah) {
+            return self.action(&header, &buffer[link_len..], true) == Action::Bypass;
+        }
+        if header.fragment || header.payload_offset != header.header_len {
+            net_debug!("ipsec: dropping fragment or packet with extension headers");
+            return false;
+        }
+        let handle = match unprotect(&mut self.sas, transform, medium, buffer, link_len, &header) {
+            Some(handle) => handle,
+            None => return false,
+        };
+
+        // The packet must have been sent through the security association its policy
+        // requires.
+        let inner = match Header::parse(&ChecksumCapabilities::ignored(), &buffer[link_len..]) {
+            Some(inner) => inner,
+            None => return false,
+        };
+        match self.action(&inner, &buffer[link_len..], true) {
+            Action::Protect(required) if required == handle => true,
+            _ => {
+                net_debug!("ipsec: dropping packet not matching the policy of its SA");
+                false
+            }
+        }
+    }
+}
+
+/// Protect the IP packet at `link_len` in `buffer` with the security association `sa`.
+fn protect<T: Transform>(
+    sa: &mut Sa,
+    transform: &mut T,
+    checksum_caps: &ChecksumCapabilities,
+    medium: Medium,
+    buffer: &mut Vec<u8>,
+    link_len: usize,
+    header: &Header,
+) -> bool {
+    let sequence_number = match sa.next_sequence() {
+        Some(sequence_number) => sequence_number,
+        None => {
+            net_debug!(
+                "ipsec: sequence numbers of spi 0x{:08x} exhausted",
+                sa.config.outbound_spi
+            );
+            return false;
+        }
+    };
+    buffer.truncate(link_len + header.total_len);
+
+    // The transport mode keeps the IP header in front of the protocol header, the tunnel
+    // mode puts the whole packet behind it.
+    let (prefix_len, inner_offset, next_header, version) = match sa.endpoints {
+        None => (
+            header.header_len,
+            link_len + header.header_len,
+            header.next_header,
+            header.version(),
+        ),
+        Some(endpoints) => {
+            let next_header = match header.version() {
+                #[cfg(feature = "proto-ipv4")]
+                IpVersion::Ipv4 => IpProtocol::IpIp,
+                #[cfg(feature = "proto-ipv6")]
+                IpVersion::Ipv6 => IpProtocol::Ipv6,
+            };
+            (
+                endpoints.header_len(),
+                link_len,
+                next_header,
+                endpoints.local().version(),
+            )
+        }
+    };
+    let inner_len = buffer.len() - inner_offset;
+    let config = sa.config;
+
+    let (protocol, data_offset, len) = match config.protocol {
+        Protocol::Esp => {
+            let trailer = EspTrailer::new(inner_len, alignment(config.block_size), next_header);
+            let data_offset = link_len + prefix_len + ESP_HEADER_LEN + config.iv_len;
+            let len = data_offset + inner_len + trailer.buffer_len() + config.icv_len;
+            (IpProtocol::Esp, data_offset, len)
+        }
+        Protocol::Ah => {
+            // The header is aligned to 32 bits in IPv4 packets, and 64 bits in IPv6 packets.
+            let alignment = match version {
+                #[cfg(feature = "proto-ipv4")]
+                IpVersion::Ipv4 => 4,
+                #[cfg(feature = "proto-ipv6")]
+                IpVersion::Ipv6 => 8,
+            };
+            let header_len = round_up(AH_HEADER_LEN + config.icv_len, alignment);
+            let data_offset = link_len + prefix_len + header_len;
+            (IpProtocol::Ah, data_offset, data_offset + inner_len)
+        }
+    };
+    buffer.resize(len, 0);
+    buffer.copy_within(inner_offset..inner_offset + inner_len, data_offset);
+    match sa.endpoints {
+        None => rewrite(checksum_caps, protocol, &mut buffer[link_len..]),
+        Some(endpoints) => {
+            endpoints.emit(
+                checksum_caps,
+                protocol,
+                DEFAULT_HOP_LIMIT,
+                &mut buffer[link_len..],
+            );
+            set_link_protocol(medium, buffer, version);
+        }
+    }
+
+    let packet = &mut buffer[link_len + prefix_len..];
+    match config.protocol {
+        Protocol::Esp => {
+            let repr = EspRepr {
+                spi: config.outbound_spi,
+                sequence_number,
+            };
+            repr.emit(&mut EspPacket::new_unchecked(&mut *packet));
+            let icv_offset = packet.len() - config.icv_len;
+            let (esp_header, rest) = packet.split_at_mut(ESP_HEADER_LEN);
+            let (iv, rest) = rest.split_at_mut(config.iv_len);
+            let (data, icv) = rest.split_at_mut(icv_offset - ESP_HEADER_LEN - config.iv_len);
+            EspTrailer::new(inner_len, alignment(config.block_size), next_header).emit(data);
+            transform.seal(config.outbound_spi, esp_header, iv, data, icv);
+        }
+        Protocol::Ah => {
+            let repr = AhRepr {
+                next_header,
+                spi: config.outbound_spi,
+                sequence_number,
+                icv_len: data_offset - link_len - prefix_len - AH_HEADER_LEN,
+            };
+            repr.emit(&mut AhPacket::new_unchecked(&mut *packet));
+            let mut icv = [0; MAX_ICV_LEN];
+            let icv = &mut icv[..config.icv_len];
+            authenticate(transform, config.outbound_spi, &mut buffer[link_len..], icv);
+            let icv_offset = link_len + prefix_len + AH_HEADER_LEN;
+            buffer[icv_offset..icv_offset + icv.len()].copy_from_slice(icv);
+        }
+    }
+    true
+}
+
+/// Verify and strip the protection of the IP packet at `link_len` in `buffer`.
+///
+/// Return the handle of the security association that protected the packet.
+fn unprotect<T: Transform>(
+    sas: &mut [Sa],
+    transform: &mut T,
+    medium: Medium,
+    buffer: &mut Vec<u8>,
+    link_len: usize,
+    header: &Header,
+) -> Option<SaHandle> {
+    let offset = link_len + header.header_len;
+    let (spi, sequence_number) = match header.protocol {
+        IpProtocol::Esp => {
+            let repr = EspRepr::parse(&EspPacket::new_checked(&buffer[offset..]).ok()?).ok()?;
+            (repr.spi, repr.sequence_number)
+        }
+        _ => {
+            let repr = AhRepr::parse(&AhPacket::new_checked(&buffer[offset..]).ok()?).ok()?;
+            (repr.spi, repr.sequence_number)
+        }
+    };
+    let protocol = match header.protocol {
+        IpProtocol::Esp => Protocol::Esp,
+        _ => Protocol::Ah,
+    };
+    let index = match sas
+        .iter()
+        .position(|sa| sa.config.protocol == protocol && sa.config.inbound_spi == spi)
+    {
+        Some(index) => index,
+        None => {
+            net_debug!("ipsec: dropping packet for unknown spi 0x{:08x}", spi);
+            return None;
+        }
+    };
+    let sa = &mut sas[index];
+    let config = sa.config;
+    if let Some(endpoints) = sa.endpoints {
+        if header.src_addr != endpoints.remote() || header.dst_addr != endpoints.local() {
+            return None;
+        }
+    }
+    if !sa.replay_window.check(sequence_number) {
+        net_debug!(
+            "ipsec: dropping replayed packet {} of spi 0x{:08x}",
+            sequence_number,
+            spi
+        );
+        return None;
+    }
+
+    let (inner_offset, inner_len, next_header) = match protocol {
+        Protocol::Esp => {
+            let packet = &mut buffer[offset..];
+            if packet.len() < ESP_HEADER_LEN + config.iv_len + ESP_TRAILER_LEN + config.icv_len {
+                return None;
+            }
+            let icv_offset = packet.len() - config.icv_len;
+            let (esp_header, rest) = packet.split_at_mut(ESP_HEADER_LEN);
+            let (iv, rest) = rest.split_at_mut(config.iv_len);
+            let (data, icv) = rest.split_at_mut(icv_offset - ESP_HEADER_LEN - config.iv_len);
+            if !transform.open(spi, esp_header, iv, data, icv) {
+                net_debug!("ipsec: dropping packet failing authentication");
+                return None;
+            }
+            sa.replay_window.accept(sequence_number);
+            let trailer = EspTrailer::parse(data).ok()?;
+            let inner_len = data.len() - trailer.buffer_len();
+            (
+                offset + ESP_HEADER_LEN + config.iv_len,
+                inner_len,
+                trailer.next_header,
+            )
+        }
+        Protocol::Ah => {
+            let packet = AhPacket::new_unchecked(&buffer[offset..]);
+            let repr = AhRepr::parse(&packet).ok()?;
+            if repr.icv_len < config.icv_len {
+                return None;
+            }
+            let icv_offset = offset + AH_HEADER_LEN;
+            let mut received = [0; MAX_ICV_LEN];
+            let received = &mut received[..config.icv_len];
+            received.copy_from_slice(&buffer[icv_offset..icv_offset + config.icv_len]);
+            buffer[icv_offset..icv_offset + repr.icv_len].fill(0);
+            let mut computed = [0; MAX_ICV_LEN];
+            let computed = &mut computed[..config.icv_len];
+            authenticate(transform, spi, &mut buffer[link_len..], computed);
+            if !icv_eq(received, computed) {
+                net_debug!("ipsec: dropping packet failing authentication");
+                return None;
+            }
+            sa.replay_window.accept(sequence_number);
+            let inner_offset = offset + repr.buffer_len();
+            (inner_offset, buffer.len() - inner_offset, repr.next_header)
+        }
+    };
+
+    match sa.endpoints {
+        None => {
+            buffer.copy_within(inner_offset..inner_offset + inner_len, offset);
+            buffer.truncate(offset + inner_len);
+            rewrite(
+                &ChecksumCapabilities::default(),
+                next_header,
+                &mut buffer[link_len..],
+            );
+        }
+        Some(_) => {
+            // Dummy packets, sent to hide the traffic patterns, carry no next header.
+            if !matches!(next_header, IpProtocol::IpIp | IpProtocol::Ipv6) {
+                return None;
+            }
+            buffer.copy_within(inner_offset..inner_offset + inner_len, link_len);
+            buffer.truncate(link_len + inner_len);
+            let version = IpVersion::of_packet(buffer.get(link_len..link_len + 1)?).ok()?;
+            set_link_protocol(medium, buffer, version);
+        }
+    }
+    Some(SaHandle(index))
+}
+
+/// An IPsec device.
+///
+/// An IPsec device protects the IP packets the interface using it exchanges through the
+/// underlying device with the Encapsulating Security Payload or the Authentication Header
+/// of its security associations, as required by its security policies. The keys are
+/// negotiated and the ciphers computed outside of smoltcp, by a [Transform].
+///
+/// The policies are searched in the order they were added, and the packets matching none
+/// of them are exchanged without protection. The packets received through a security
+/// association are dropped unless they match a policy protecting them with it, and the
+/// unprotected packets received are dropped unless they match no policy or a bypass one.
+///
+/// The underlying device must have the IP or Ethernet medium. The frames that do not
+/// carry IP packets, such as ARP, are not protected.
+#[derive(Debug)]
+pub struct Ipsec<D: Device, T: Transform> {
+    lower: D,
+    transform: T,
+    database: Database,
+    rx_buffer: Vec<u8>,
+    tx_buffer: Vec<u8>,
+}
+
+impl<D: Device, T: Transform> Ipsec<D, T> {
+    /// Create an IPsec device without any security policy or security association.
+    ///
+    /// # Panics
+    /// This function panics if the underlying device does not have the IP or Ethernet
+    /// medium.
+    pub fn new(lower: D, transform: T) -> Ipsec<D, T> {
+        let caps = lower.capabilities();
+        match caps.medium {
+            Medium::Ip => (),
+            #[cfg(feature = "medium-ethernet")]
+            Medium::Ethernet => (),
+            #[allow(unreachable_patterns)]
+            _ => panic!("ipsec requires a device with the IP or Ethernet medium"),
+        }
+        Ipsec {
+            lower,
+            transform,
+            database: Database {
+                policies: Vec::new(),
+                sas: Vec::new(),
+            },
+            rx_buffer: Vec::with_capacity(caps.max_transmission_unit),
+            tx_buffer: Vec::with_capacity(caps.max_transmission_unit),
+        }
+    }
+
+    /// Get a reference to the underlying device.
+    ///
+    /// Even if the device offers reading through a standard reference, it is inadvisable to
+    /// directly read from the device as doing so will circumvent the protection.
+    pub fn get_ref(&self) -> &D {
+        &self.lower
+    }
+
+    /// Get a mutable reference to the underlying device.
+    ///
+    /// It is inadvisable to directly read from the device as doing so will circumvent the
+    /// protection.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.lower
+    }
+
+    /// Return the underlying device, consuming the IPsec device.
+    pub fn into_inner(self) -> D {
+        self.lower
+    }
+
+    /// Get a reference to the transform.
+    pub fn transform(&self) -> &T {
+        &self.transform
+    }
+
+    /// Get a mutable reference to the transform, to install or rekey the security
+    /// associations.
+    pub fn transform_mut(&mut self) -> &mut T {
+        &mut self.transform
+    }
+
+    /// Add a security association, and return a handle to use in the policies.
+    ///
+    /// # Panics
+    /// This function panics if an SPI is zero, if an inbound SPI is already used by another
+    /// security association of the same protocol, if the tunnel addresses are not of the
+    /// same family, if the block size is zero or larger than 256, or if the integrity
+    /// check value of an AH security association is longer than 32 octets.
+    pub fn add_sa(&mut self, config: SecurityAssociation) -> SaHandle {
+        if config.inbound_spi == 0 || config.outbound_spi == 0 {
+            panic!("spi must not be zero")
+        }
+        if self.database.sas.iter().any(|sa| {
+            sa.config.protocol == config.protocol && sa.config.inbound_spi == config.inbound_spi
+        }) {
+            panic!("inbound spi already used")
+        }
+        match config.protocol {
+            Protocol::Esp if config.block_size == 0 || config.block_size > 256 => {
+                panic!("block size must be between 1 and 256")
+            }
+            Protocol::Ah if config.icv_len > MAX_ICV_LEN => {
+                panic!("icv must not be longer than {MAX_ICV_LEN} octets")
+            }
+            _ => (),
+        }
+        let endpoints = match config.mode {
+            Mode::Transport => None,
+            Mode::Tunnel { local, remote } => Some(Endpoints::new(local, remote)),
+        };
+        self.database.sas.push(Sa {
+            config,
+            endpoints,
+            tx_sequence: 0,
+            replay_window: ReplayWindow::default(),
+        });
+        SaHandle(self.database.sas.len() - 1)
+    }
+
+    /// Return the configuration of a security association.
+    ///
+    /// # Panics
+    /// This function panics if the handle does not belong to this device.
+    pub fn sa(&self, handle: SaHandle) -> &SecurityAssociation {
+        &self.database.sas[handle.0].config
+    }
+
+    /// Add a security policy, searched after the ones already added.
+    ///
+    /// # Panics
+    /// This function panics if the policy protects packets with a security association
+    /// that does not belong to this device.
+    pub fn add_policy(&mut self, policy: Policy) {
+        if let Action::Protect(handle) = policy.action {
+            if handle.0 >= self.database.sas.len() {
+                panic!("unknown security association")
+            }
+        }
+        self.database.policies.push(policy)
+    }
+
+    /// Remove all the security policies.
+    pub fn clear_policies(&mut self) {
+        self.database.policies.clear()
+    }
+
+    /// Return the security policies, in the order they are searched.
+    pub fn policies(&self) -> &[Policy] {
+        &self.database.policies
+    }
+}
+
+impl<D: Device, T: Transform> Device for Ipsec<D, T> {
+    type RxToken<'a>
+        = RxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, D::TxToken<'a>, T>
+    where
+        Self: 'a;
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let caps = self.lower.capabilities();
+        let overhead = self
+            .database
+            .sas
+            .iter()
+            .map(Sa::overhead)
+            .max()
+            .unwrap_or(0);
+        // The underlying device only offloads the checksums of the protected packets.
+        DeviceCapabilities {
+            max_transmission_unit: caps.max_transmission_unit.saturating_sub(overhead),
+            max_segmentation_size: None,
+            checksum: ChecksumCapabilities::default(),
+            ..caps
+        }
+    }
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let caps = self.lower.capabilities();
+        let Ipsec {
+            lower,
+            transform,
+            database,
+            rx_buffer,
+            tx_buffer,
+        } = self;
+
+        let (rx_token, tx_token) = lower.receive(timestamp)?;
+        let hardware_timestamp = phy::RxToken::hardware_timestamp(&rx_token);
+        phy::RxToken::consume(rx_token, |frame| {
+            rx_buffer.clear();
+            rx_buffer.extend_from_slice(frame);
+        });
+        if !database.inbound(transform, &caps.checksum, caps.medium, rx_buffer) {
+            return None;
+        }
+
+        let rx = RxToken {
+            buffer: rx_buffer,
+            hardware_timestamp,
+        };
+        let tx = TxToken {
+            lower: tx_token,
+            transform,
+            database,
+            buffer: tx_buffer,
+            checksum_caps: caps.checksum,
+            medium: caps.medium,
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        let caps = self.lower.capabilities();
+        let lower = self.lower.transmit(timestamp)?;
+        Some(TxToken {
+            lower,
+            transform: &mut self.transform,
+            database: &mut self.database,
+            buffer: &mut self.tx_buffer,
+            checksum_caps: caps.checksum,
+            medium: caps.medium,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken<'a> {
+    buffer: &'a [u8],
+    hardware_timestamp: Option<HardwareTimestamp>,
+}
+
+impl<'a> phy::RxToken for RxToken<'a> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(self.buffer)
+    }
+
+    fn hardware_timestamp(&self) -> Option<HardwareTimestamp> {
+        self.hardware_timestamp
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<'a, Tx: phy::TxToken, T: Transform> {
+    lower: Tx,
+    transform: &'a mut T,
+    database: &'a mut Database,
+    buffer: &'a mut Vec<u8>,
+    checksum_caps: ChecksumCapabilities,
+    medium: Medium,
+}
+
+impl<'a, Tx: phy::TxToken, T: Transform> TxToken<'a, Tx, T> {
+    /// Fill the buffer with the frame of the interface, and protect it.
+    ///
+    /// Return whether to send the resulting frame.
+    fn fill<R, F>(&mut self, len: usize, f: F) -> (R, bool)
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.buffer.clear();
+        self.buffer.resize(len, 0);
+        let result = f(self.buffer);
+        let send = self.database.outbound(
+            self.transform,
+            &self.checksum_caps,
+            self.medium,
+            self.buffer,
+        );
+        (result, send)
+    }
+}
+
+impl<'a, Tx: phy::TxToken, T: Transform> phy::TxToken for TxToken<'a, Tx, T> {
+    fn consume<R, F>(mut self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let (result, send) = self.fill(len, f);
+        if send {
+            let buffer = &self.buffer;
+            self.lower
+                .consume(buffer.len(), |frame| frame.copy_from_slice(buffer));
+        }
+        result
+    }
+
+    fn consume_timestamped<R, F>(mut self, len: usize, f: F) -> (R, Option<HardwareTimestamp>)
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let (result, send) = self.fill(len, f);
+        if !send {
+            return (result, None);
+        }
+        let buffer = &self.buffer;
+        let ((), timestamp) = self
+            .lower
+            .consume_timestamped(buffer.len(), |frame| frame.copy_from_slice(buffer));
+        (result, timestamp)
+    }
+}
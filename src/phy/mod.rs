@@ -5,11 +5,20 @@ for transmitting and receiving frames, [Device](trait.Device.html)
 and implementations of it:
 
   * the [_loopback_](struct.Loopback.html), for zero dependency testing;
-  * _middleware_ [Tracer](struct.Tracer.html) and
-    [FaultInjector](struct.FaultInjector.html), to facilitate debugging;
+  * _middleware_ [Tracer](struct.Tracer.html),
+    [FaultInjector](struct.FaultInjector.html) and [Shaper](struct.Shaper.html),
+    to facilitate debugging;
   * _adapters_ [RawSocket](struct.RawSocket.html) and
     [TunTapInterface](struct.TunTapInterface.html), to transmit and receive frames
-    on the host OS.
+    on the host OS. On Windows, `TunTapInterface` uses a [Wintun](https://www.wintun.net)
+    adapter, and only supports `Medium::Ip`;
+  * the [Ppp](struct.Ppp.html) device, to bring up a PPP link over a serial port;
+  * the [Ieee802154Mac](struct.Ieee802154Mac.html) device, to send and acknowledge
+    IEEE 802.15.4 frames with CSMA-CA over a simple [Radio](trait.Radio.html);
+  * the [Tunnel](struct.Tunnel.html) and [Vxlan](struct.Vxlan.html) devices, to terminate
+    IP-in-IP and GRE tunnels, and to join VXLAN overlay networks;
+  * the [Ipsec](struct.Ipsec.html) device, to protect the packets with ESP or AH;
+  * the [Wireguard](struct.Wireguard.html) device, to bring up a WireGuard tunnel.
 */
 #![cfg_attr(
     feature = "medium-ethernet",
@@ -89,46 +98,124 @@ impl<'a> phy::TxToken for StmPhyTxToken<'a> {
 )]
 
 use crate::time::Instant;
-
-#[cfg(all(
-    any(feature = "phy-raw_socket", feature = "phy-tuntap_interface"),
-    unix
+use crate::wire::IpVersion;
+
+#[cfg(any(
+    all(
+        any(feature = "phy-raw_socket", feature = "phy-tuntap_interface"),
+        unix
+    ),
+    all(feature = "phy-tuntap_interface", windows)
 ))]
 mod sys;
 
 mod fault_injector;
 mod fuzz_injector;
+#[cfg(all(
+    feature = "alloc",
+    feature = "medium-ip",
+    any(feature = "proto-ipv4", feature = "proto-ipv6")
+))]
+mod ipsec;
 #[cfg(feature = "alloc")]
 mod loopback;
 mod pcap_writer;
+#[cfg(feature = "phy-ppp")]
+mod ppp;
+#[cfg(feature = "medium-ieee802154")]
+mod radio;
 #[cfg(all(feature = "phy-raw_socket", unix))]
 mod raw_socket;
+#[cfg(feature = "alloc")]
+mod shaper;
 mod tracer;
+#[cfg(all(
+    feature = "alloc",
+    feature = "medium-ip",
+    any(feature = "proto-ipv4", feature = "proto-ipv6")
+))]
+mod tunnel;
 #[cfg(all(
     feature = "phy-tuntap_interface",
-    any(target_os = "linux", target_os = "android")
+    any(target_os = "linux", target_os = "android", windows)
 ))]
 mod tuntap_interface;
-
 #[cfg(all(
-    any(feature = "phy-raw_socket", feature = "phy-tuntap_interface"),
-    unix
+    feature = "alloc",
+    feature = "medium-ethernet",
+    feature = "medium-ip",
+    any(feature = "proto-ipv4", feature = "proto-ipv6")
+))]
+mod vxlan;
+#[cfg(all(
+    feature = "alloc",
+    feature = "medium-ip",
+    any(feature = "proto-ipv4", feature = "proto-ipv6")
+))]
+mod wireguard;
+
+#[cfg(any(
+    all(
+        any(feature = "phy-raw_socket", feature = "phy-tuntap_interface"),
+        unix
+    ),
+    all(feature = "phy-tuntap_interface", windows)
 ))]
 pub use self::sys::wait;
 
 pub use self::fault_injector::FaultInjector;
 pub use self::fuzz_injector::{FuzzInjector, Fuzzer};
+#[cfg(all(
+    feature = "alloc",
+    feature = "medium-ip",
+    any(feature = "proto-ipv4", feature = "proto-ipv6")
+))]
+pub use self::ipsec::{
+    Action as IpsecAction, Ipsec, Mode as IpsecMode, Policy as IpsecPolicy,
+    Protocol as IpsecProtocol, SaHandle, SecurityAssociation, Transform as IpsecTransform,
+};
 #[cfg(feature = "alloc")]
 pub use self::loopback::Loopback;
 pub use self::pcap_writer::{PcapLinkType, PcapMode, PcapSink, PcapWriter};
+#[cfg(all(feature = "phy-ppp", feature = "proto-ipv4"))]
+pub use self::ppp::Ipv4Config as PppIpv4Config;
+#[cfg(all(feature = "phy-ppp", feature = "proto-ipv6"))]
+pub use self::ppp::Ipv6Config as PppIpv6Config;
+#[cfg(feature = "phy-ppp")]
+pub use self::ppp::{ChapResponder, Config as PppConfig, Event as PppEvent, Ppp, Serial};
+#[cfg(feature = "medium-ieee802154")]
+pub use self::radio::{
+    Config as Ieee802154MacConfig, Ieee802154Mac, Radio, Stats as Ieee802154MacStats,
+};
 #[cfg(all(feature = "phy-raw_socket", unix))]
 pub use self::raw_socket::RawSocket;
+#[cfg(feature = "alloc")]
+pub use self::shaper::Shaper;
 pub use self::tracer::Tracer;
+#[cfg(all(
+    feature = "alloc",
+    feature = "medium-ip",
+    any(feature = "proto-ipv4", feature = "proto-ipv6")
+))]
+pub use self::tunnel::{Tunnel, TunnelMode};
 #[cfg(all(
     feature = "phy-tuntap_interface",
-    any(target_os = "linux", target_os = "android")
+    any(target_os = "linux", target_os = "android", windows)
 ))]
 pub use self::tuntap_interface::TunTapInterface;
+#[cfg(all(
+    feature = "alloc",
+    feature = "medium-ethernet",
+    feature = "medium-ip",
+    any(feature = "proto-ipv4", feature = "proto-ipv6")
+))]
+pub use self::vxlan::Vxlan;
+#[cfg(all(
+    feature = "alloc",
+    feature = "medium-ip",
+    any(feature = "proto-ipv4", feature = "proto-ipv6")
+))]
+pub use self::wireguard::{Noise as WireguardNoise, Wireguard};
 
 /// A description of checksum behavior for a particular protocol.
 #[derive(Debug, Clone, Copy)]
@@ -176,6 +263,7 @@ pub struct ChecksumCapabilities {
     pub ipv4: Checksum,
     pub udp: Checksum,
     pub tcp: Checksum,
+    pub sctp: Checksum,
     #[cfg(feature = "proto-ipv4")]
     pub icmpv4: Checksum,
     #[cfg(feature = "proto-ipv6")]
@@ -190,6 +278,7 @@ impl ChecksumCapabilities {
             ipv4: Checksum::None,
             udp: Checksum::None,
             tcp: Checksum::None,
+            sctp: Checksum::None,
             #[cfg(feature = "proto-ipv4")]
             icmpv4: Checksum::None,
             #[cfg(feature = "proto-ipv6")]
@@ -236,6 +325,33 @@ pub struct DeviceCapabilities {
     /// dynamically allocated.
     pub max_burst_size: Option<usize>,
 
+    /// Maximum size of the TCP segments the device splits into segments that fit the MTU
+    /// (TCP segmentation offload), including the IP and TCP headers.
+    ///
+    /// When set, TCP sockets send segments up to this size, which the device splits on the
+    /// boundaries of the MTU, copying the headers and adjusting their lengths, sequence numbers
+    /// and checksums. It must not exceed 65535 octets. If `None`, the device does not split
+    /// segments.
+    ///
+    /// Devices may also coalesce received segments into packets larger than the MTU (large
+    /// receive offload), which are accepted whether or not this is set.
+    pub max_segmentation_size: Option<usize>,
+
+    /// Maximum transmission unit of IPv4 packets, if smaller than the one of the medium.
+    ///
+    /// Devices of the IP medium carrying both IPv4 and IPv6, such as cellular modems with
+    /// a PDP context for each, may have a different MTU for each family. Packets are
+    /// fragmented, and TCP segments sized, to the MTU of their family. If `None`,
+    /// [`ip_mtu`](Self::ip_mtu) applies.
+    #[cfg(feature = "proto-ipv4")]
+    pub ipv4_mtu: Option<usize>,
+
+    /// Maximum transmission unit of IPv6 packets, if smaller than the one of the medium.
+    ///
+    /// See [`ipv4_mtu`](Self::ipv4_mtu).
+    #[cfg(feature = "proto-ipv6")]
+    pub ipv6_mtu: Option<usize>,
+
     /// Checksum behavior.
     ///
     /// If the network device is capable of verifying or computing checksums for some protocols,
@@ -256,6 +372,18 @@ impl DeviceCapabilities {
             Medium::Ieee802154 => self.max_transmission_unit, // TODO(thvdveld): what is the MTU for Medium::IEEE802
         }
     }
+
+    /// Return the maximum transmission unit of the packets of an IP version, the
+    /// smallest of [`ip_mtu`](Self::ip_mtu) and the MTU of the version, if any.
+    pub fn ip_mtu_of(&self, version: IpVersion) -> usize {
+        let mtu = match version {
+            #[cfg(feature = "proto-ipv4")]
+            IpVersion::Ipv4 => self.ipv4_mtu,
+            #[cfg(feature = "proto-ipv6")]
+            IpVersion::Ipv6 => self.ipv6_mtu,
+        };
+        mtu.map_or(self.ip_mtu(), |mtu| mtu.min(self.ip_mtu()))
+    }
 }
 
 /// Type of medium of a device.
@@ -331,6 +459,50 @@ pub trait Device {
     /// arbitrary moment in time, such as system startup.
     fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>>;
 
+    /// Receive up to `max` packets, calling `f` with a token pair for each of them, and
+    /// return the number of packets received.
+    ///
+    /// Devices with descriptor rings may override this to hand over several packets per
+    /// call, e.g. all the descriptors filled since the last call. The default implementation
+    /// hands over at most one packet, obtained from [receive](#tymethod.receive).
+    fn receive_batch<'a, F>(&'a mut self, timestamp: Instant, max: usize, mut f: F) -> usize
+    where
+        F: FnMut(Self::RxToken<'a>, Self::TxToken<'a>),
+    {
+        if max == 0 {
+            return 0;
+        }
+        match self.receive(timestamp) {
+            Some((rx_token, tx_token)) => {
+                f(rx_token, tx_token);
+                1
+            }
+            None => 0,
+        }
+    }
+
+    /// Call `f` with up to `max` transmit tokens, until it returns `false`, and return
+    /// the number of tokens `f` was called with.
+    ///
+    /// Devices with descriptor rings may override this to reserve several descriptors
+    /// per call. The default implementation hands over at most one token, obtained from
+    /// [transmit](#tymethod.transmit).
+    fn transmit_batch<'a, F>(&'a mut self, timestamp: Instant, max: usize, mut f: F) -> usize
+    where
+        F: FnMut(Self::TxToken<'a>) -> bool,
+    {
+        if max == 0 {
+            return 0;
+        }
+        match self.transmit(timestamp) {
+            Some(tx_token) => {
+                f(tx_token);
+                1
+            }
+            None => 0,
+        }
+    }
+
     /// Get a description of device capabilities.
     fn capabilities(&self) -> DeviceCapabilities;
 }
@@ -344,6 +516,14 @@ pub trait RxToken {
     fn consume<R, F>(self, f: F) -> R
     where
         F: FnOnce(&[u8]) -> R;
+
+    /// Return the time at which the hardware received the packet.
+    ///
+    /// Devices that timestamp the packets they receive, e.g. for the Precision Time
+    /// Protocol, may override this. The default implementation returns `None`.
+    fn hardware_timestamp(&self) -> Option<HardwareTimestamp> {
+        None
+    }
 }
 
 /// A token to transmit a single network packet.
@@ -357,4 +537,40 @@ pub trait TxToken {
     fn consume<R, F>(self, len: usize, f: F) -> R
     where
         F: FnOnce(&mut [u8]) -> R;
+
+    /// Tell the device the version of the IP packet the token is about to be consumed
+    /// for.
+    ///
+    /// Devices of the IP medium carrying both IPv4 and IPv6, such as PPP links, may
+    /// override this to send the packet on the channel of its version without looking
+    /// into it. The interface calls it before [consume](#tymethod.consume) for the IP
+    /// packets it sends; the default implementation does nothing.
+    fn set_ip_version(&mut self, _version: IpVersion) {}
+
+    /// Consumes the token to send a single network packet like [consume](#tymethod.consume),
+    /// and return the time at which the hardware transmitted the packet as well.
+    ///
+    /// Devices that timestamp the packets they transmit, e.g. for the Precision Time
+    /// Protocol, may override this to wait for the timestamp of the packet. The default
+    /// implementation sends the packet with [consume](#tymethod.consume), and returns no
+    /// timestamp.
+    fn consume_timestamped<R, F>(self, len: usize, f: F) -> (R, Option<HardwareTimestamp>)
+    where
+        Self: Sized,
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        (self.consume(len, f), None)
+    }
+}
+
+/// The time at which the hardware received or transmitted a packet.
+///
+/// Hardware timestamps are read from the clock of the device, such as a PTP hardware
+/// clock, and are unrelated to the timestamps passed to [Device](trait.Device.html).
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HardwareTimestamp {
+    pub seconds: u64,
+    /// The nanoseconds, less than 1_000_000_000.
+    pub nanoseconds: u32,
 }
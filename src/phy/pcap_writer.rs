@@ -4,8 +4,9 @@ use phy::Medium;
 #[cfg(feature = "std")]
 use std::io::Write;
 
-use crate::phy::{self, Device, DeviceCapabilities};
+use crate::phy::{self, Device, DeviceCapabilities, HardwareTimestamp};
 use crate::time::Instant;
+use crate::wire::IpVersion;
 
 enum_with_unknown! {
     /// Captured packet header type.
@@ -31,6 +32,27 @@ pub enum PcapMode {
     TxOnly,
 }
 
+/// The pcapng block types written by the sink.
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d0d0a;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 0x00000001;
+const PCAPNG_ENHANCED_PACKET: u32 = 0x00000006;
+
+/// The pcapng option codes written by the sink.
+const PCAPNG_OPT_END: u16 = 0;
+const PCAPNG_OPT_COMMENT: u16 = 1;
+const PCAPNG_IF_NAME: u16 = 2;
+const PCAPNG_IF_TSRESOL: u16 = 9;
+
+/// Return the length of a pcapng field of `len` bytes, including its padding.
+const fn pcapng_padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Return the length of a pcapng option with a value of `len` bytes.
+const fn pcapng_option_len(len: usize) -> usize {
+    4 + pcapng_padded(len)
+}
+
 /// A packet capture sink.
 pub trait PcapSink {
     /// Write data into the sink.
@@ -89,6 +111,92 @@ pub trait PcapSink {
         self.write(packet);
         self.flush();
     }
+
+    /// Write `data` followed by the zero padding that aligns it to 32 bits into the sink.
+    fn write_padded(&mut self, data: &[u8]) {
+        self.write(data);
+        self.write(&[0; 3][..pcapng_padded(data.len()) - data.len()]);
+    }
+
+    /// Write a pcapng option into the sink.
+    fn pcapng_option(&mut self, code: u16, value: &[u8]) {
+        self.write_u16(code);
+        self.write_u16(value.len() as u16);
+        self.write_padded(value);
+    }
+
+    /// Write the pcapng Section Header Block into the sink.
+    ///
+    /// A section holds the descriptions of its interfaces, followed by their packets.
+    ///
+    /// See also the note for [global_header](#method.global_header).
+    fn section_header(&mut self) {
+        self.write_u32(PCAPNG_SECTION_HEADER); // block type
+        self.write_u32(28); // block length
+        self.write_u32(0x1a2b3c4d); // byte-order magic
+        self.write_u16(1); // major version
+        self.write_u16(0); // minor version
+        self.write_u32(0xffffffff); // section length (unspecified), high part
+        self.write_u32(0xffffffff); // section length (unspecified), low part
+        self.write_u32(28); // block length
+    }
+
+    /// Write a pcapng Interface Description Block into the sink.
+    ///
+    /// Interfaces are numbered from zero, in the order of their descriptions in the section.
+    /// Their timestamps have a resolution of one nanosecond.
+    ///
+    /// See also the note for [global_header](#method.global_header).
+    fn interface_description(&mut self, link_type: PcapLinkType, name: Option<&str>) {
+        let name_len = name.map_or(0, |name| pcapng_option_len(name.len()));
+        let length = (20 + name_len + pcapng_option_len(1) + pcapng_option_len(0)) as u32;
+
+        self.write_u32(PCAPNG_INTERFACE_DESCRIPTION); // block type
+        self.write_u32(length); // block length
+        self.write_u16(u32::from(link_type) as u16); // link-layer header type
+        self.write_u16(0); // reserved
+        self.write_u32(0); // maximum packet length (unlimited)
+        if let Some(name) = name {
+            self.pcapng_option(PCAPNG_IF_NAME, name.as_bytes());
+        }
+        self.pcapng_option(PCAPNG_IF_TSRESOL, &[9]); // nanoseconds
+        self.pcapng_option(PCAPNG_OPT_END, &[]);
+        self.write_u32(length); // block length
+    }
+
+    /// Write a pcapng Enhanced Packet Block into the sink, with an optional comment
+    /// (e.g. the reason the packet was dropped).
+    ///
+    /// See also the note for [global_header](#method.global_header).
+    fn enhanced_packet(
+        &mut self,
+        interface_id: u32,
+        timestamp: Instant,
+        packet: &[u8],
+        comment: Option<&str>,
+    ) {
+        let options_len = match comment {
+            Some(comment) => pcapng_option_len(comment.len()) + pcapng_option_len(0),
+            None => 0,
+        };
+        let length = (32 + pcapng_padded(packet.len()) + options_len) as u32;
+        let nanos = timestamp.total_micros() as u64 * 1000;
+
+        self.write_u32(PCAPNG_ENHANCED_PACKET); // block type
+        self.write_u32(length); // block length
+        self.write_u32(interface_id); // interface id
+        self.write_u32((nanos >> 32) as u32); // timestamp, high part
+        self.write_u32(nanos as u32); // timestamp, low part
+        self.write_u32(packet.len() as u32); // captured length
+        self.write_u32(packet.len() as u32); // original length
+        self.write_padded(packet);
+        if let Some(comment) = comment {
+            self.pcapng_option(PCAPNG_OPT_COMMENT, comment.as_bytes());
+            self.pcapng_option(PCAPNG_OPT_END, &[]);
+        }
+        self.write_u32(length); // block length
+        self.flush();
+    }
 }
 
 #[cfg(feature = "std")]
@@ -102,17 +210,30 @@ impl<T: Write> PcapSink for T {
     }
 }
 
+/// The file format written by a [PcapWriter](struct.PcapWriter.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum PcapFormat {
+    Pcap,
+    PcapNg { interface_id: u32 },
+}
+
 /// A packet capture writer device.
 ///
 /// Every packet transmitted or received through this device is timestamped
-/// and written (in the [libpcap] format) using the provided [sink].
+/// and written (in the [libpcap] or [pcapng] format) using the provided [sink].
 /// Note that writes are fine-grained, and buffering is recommended.
 ///
 /// The packet sink should be cheaply cloneable, as it is cloned on every
 /// transmitted packet. For example, `&'a mut Vec<u8>` is cheaply cloneable
 /// but `&std::io::File`
 ///
+/// To capture several devices into one pcapng file, wrap the first one with
+/// [new_ng](#method.new_ng) and the others with [add_ng](#method.add_ng),
+/// passing each a handle to the same sink (e.g. `&std::fs::File`).
+///
 /// [libpcap]: https://wiki.wireshark.org/Development/LibpcapFileFormat
+/// [pcapng]: https://www.ietf.org/archive/id/draft-ietf-opsawg-pcapng-01.html
 /// [sink]: trait.PcapSink.html
 #[derive(Debug)]
 pub struct PcapWriter<D, S>
@@ -123,28 +244,78 @@ where
     lower: D,
     sink: RefCell<S>,
     mode: PcapMode,
+    format: PcapFormat,
+    comment: Option<&'static str>,
 }
 
 impl<D: Device, S: PcapSink> PcapWriter<D, S> {
     /// Creates a packet capture writer.
     pub fn new(lower: D, mut sink: S, mode: PcapMode) -> PcapWriter<D, S> {
-        let medium = lower.capabilities().medium;
-        let link_type = match medium {
+        sink.global_header(Self::link_type(&lower));
+        PcapWriter {
+            lower,
+            sink: RefCell::new(sink),
+            mode,
+            format: PcapFormat::Pcap,
+            comment: None,
+        }
+    }
+
+    /// Creates a packet capture writer in the pcapng format.
+    ///
+    /// This starts a new section in the sink, in which the device is the interface 0.
+    /// `name` is the interface name shown by tools such as Wireshark.
+    pub fn new_ng(lower: D, mut sink: S, mode: PcapMode, name: Option<&str>) -> PcapWriter<D, S> {
+        sink.section_header();
+        PcapWriter::add_ng(lower, sink, mode, 0, name)
+    }
+
+    /// Creates a packet capture writer in the pcapng format, for another device captured
+    /// into the section started by [new_ng](#method.new_ng).
+    ///
+    /// `interface_id` must be the number of devices already captured into the section,
+    /// as pcapng numbers interfaces in the order they are described.
+    pub fn add_ng(
+        lower: D,
+        mut sink: S,
+        mode: PcapMode,
+        interface_id: u32,
+        name: Option<&str>,
+    ) -> PcapWriter<D, S> {
+        sink.interface_description(Self::link_type(&lower), name);
+        PcapWriter {
+            lower,
+            sink: RefCell::new(sink),
+            mode,
+            format: PcapFormat::PcapNg { interface_id },
+            comment: None,
+        }
+    }
+
+    fn link_type(lower: &D) -> PcapLinkType {
+        match lower.capabilities().medium {
             #[cfg(feature = "medium-ip")]
             Medium::Ip => PcapLinkType::Ip,
             #[cfg(feature = "medium-ethernet")]
             Medium::Ethernet => PcapLinkType::Ethernet,
             #[cfg(feature = "medium-ieee802154")]
             Medium::Ieee802154 => PcapLinkType::Ieee802154WithFcs,
-        };
-        sink.global_header(link_type);
-        PcapWriter {
-            lower,
-            sink: RefCell::new(sink),
-            mode,
         }
     }
 
+    /// Set the comment attached to the packets captured from now on, e.g. the reason
+    /// why the device is dropping them.
+    ///
+    /// Comments are only written in the pcapng format.
+    pub fn set_comment(&mut self, comment: Option<&'static str>) {
+        self.comment = comment
+    }
+
+    /// Return the comment attached to the captured packets.
+    pub fn comment(&self) -> Option<&'static str> {
+        self.comment
+    }
+
     /// Get a reference to the underlying device.
     ///
     /// Even if the device offers reading through a standard reference, it is inadvisable to
@@ -177,68 +348,137 @@ where
     }
 
     fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
-        let sink = &self.sink;
-        let mode = self.mode;
+        let capture = Capture {
+            sink: &self.sink,
+            mode: self.mode,
+            format: self.format,
+            comment: self.comment,
+            timestamp,
+        };
         self.lower
             .receive(timestamp)
             .map(move |(rx_token, tx_token)| {
                 let rx = RxToken {
                     token: rx_token,
-                    sink,
-                    mode,
-                    timestamp,
+                    capture,
                 };
                 let tx = TxToken {
                     token: tx_token,
-                    sink,
-                    mode,
-                    timestamp,
+                    capture,
                 };
                 (rx, tx)
             })
     }
 
     fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
-        let sink = &self.sink;
-        let mode = self.mode;
-        self.lower.transmit(timestamp).map(move |token| TxToken {
-            token,
-            sink,
-            mode,
+        let capture = Capture {
+            sink: &self.sink,
+            mode: self.mode,
+            format: self.format,
+            comment: self.comment,
             timestamp,
-        })
+        };
+        self.lower
+            .transmit(timestamp)
+            .map(move |token| TxToken { token, capture })
+    }
+
+    fn receive_batch<'a, F>(&'a mut self, timestamp: Instant, max: usize, mut f: F) -> usize
+    where
+        F: FnMut(Self::RxToken<'a>, Self::TxToken<'a>),
+    {
+        let capture = Capture {
+            sink: &self.sink,
+            mode: self.mode,
+            format: self.format,
+            comment: self.comment,
+            timestamp,
+        };
+        self.lower
+            .receive_batch(timestamp, max, |rx_token, tx_token| {
+                let rx = RxToken {
+                    token: rx_token,
+                    capture,
+                };
+                let tx = TxToken {
+                    token: tx_token,
+                    capture,
+                };
+                f(rx, tx)
+            })
+    }
+
+    fn transmit_batch<'a, F>(&'a mut self, timestamp: Instant, max: usize, mut f: F) -> usize
+    where
+        F: FnMut(Self::TxToken<'a>) -> bool,
+    {
+        let capture = Capture {
+            sink: &self.sink,
+            mode: self.mode,
+            format: self.format,
+            comment: self.comment,
+            timestamp,
+        };
+        self.lower
+            .transmit_batch(timestamp, max, |token| f(TxToken { token, capture }))
     }
 }
 
-#[doc(hidden)]
-pub struct RxToken<'a, Rx: phy::RxToken, S: PcapSink> {
-    token: Rx,
+/// What the tokens need to capture a packet.
+struct Capture<'a, S: PcapSink> {
     sink: &'a RefCell<S>,
     mode: PcapMode,
+    format: PcapFormat,
+    comment: Option<&'static str>,
     timestamp: Instant,
 }
 
+impl<'a, S: PcapSink> Clone for Capture<'a, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, S: PcapSink> Copy for Capture<'a, S> {}
+
+impl<'a, S: PcapSink> Capture<'a, S> {
+    fn packet(&self, packet: &[u8]) {
+        let mut sink = self.sink.borrow_mut();
+        match self.format {
+            PcapFormat::Pcap => sink.packet(self.timestamp, packet),
+            PcapFormat::PcapNg { interface_id } => {
+                sink.enhanced_packet(interface_id, self.timestamp, packet, self.comment)
+            }
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken<'a, Rx: phy::RxToken, S: PcapSink> {
+    token: Rx,
+    capture: Capture<'a, S>,
+}
+
 impl<'a, Rx: phy::RxToken, S: PcapSink> phy::RxToken for RxToken<'a, Rx, S> {
     fn consume<R, F: FnOnce(&[u8]) -> R>(self, f: F) -> R {
         self.token.consume(|buffer| {
-            match self.mode {
-                PcapMode::Both | PcapMode::RxOnly => self
-                    .sink
-                    .borrow_mut()
-                    .packet(self.timestamp, buffer.as_ref()),
+            match self.capture.mode {
+                PcapMode::Both | PcapMode::RxOnly => self.capture.packet(buffer),
                 PcapMode::TxOnly => (),
             }
             f(buffer)
         })
     }
+
+    fn hardware_timestamp(&self) -> Option<HardwareTimestamp> {
+        self.token.hardware_timestamp()
+    }
 }
 
 #[doc(hidden)]
 pub struct TxToken<'a, Tx: phy::TxToken, S: PcapSink> {
     token: Tx,
-    sink: &'a RefCell<S>,
-    mode: PcapMode,
-    timestamp: Instant,
+    capture: Capture<'a, S>,
 }
 
 impl<'a, Tx: phy::TxToken, S: PcapSink> phy::TxToken for TxToken<'a, Tx, S> {
@@ -248,13 +488,29 @@ impl<'a, Tx: phy::TxToken, S: PcapSink> phy::TxToken for TxToken<'a, Tx, S> {
     {
         self.token.consume(len, |buffer| {
             let result = f(buffer);
-            match self.mode {
-                PcapMode::Both | PcapMode::TxOnly => {
-                    self.sink.borrow_mut().packet(self.timestamp, buffer)
-                }
+            match self.capture.mode {
+                PcapMode::Both | PcapMode::TxOnly => self.capture.packet(buffer),
                 PcapMode::RxOnly => (),
             };
             result
         })
     }
+
+    fn consume_timestamped<R, F>(self, len: usize, f: F) -> (R, Option<HardwareTimestamp>)
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.token.consume_timestamped(len, |buffer| {
+            let result = f(buffer);
+            match self.capture.mode {
+                PcapMode::Both | PcapMode::TxOnly => self.capture.packet(buffer),
+                PcapMode::RxOnly => (),
+            };
+            result
+        })
+    }
+
+    fn set_ip_version(&mut self, version: IpVersion) {
+        self.token.set_ip_version(version)
+    }
 }
@@ -0,0 +1,1276 @@
+use heapless::Vec;
+
+use crate::phy::{self, Device, DeviceCapabilities, Medium};
+use crate::time::{Duration, Instant};
+use crate::wire::{
+    ppp_fcs_update, IpVersion, PppCode, PppConfigOption, PppLcpOption, PppPacket, PppProtocol,
+    PPP_FCS_GOOD, PPP_FCS_INIT, PPP_HDLC_ADDRESS, PPP_HDLC_CONTROL, PPP_HDLC_ESCAPE, PPP_HDLC_FLAG,
+    PPP_HEADER_LEN,
+};
+#[cfg(feature = "proto-ipv4")]
+use crate::wire::{Ipv4Address, PppIpcpOption};
+#[cfg(feature = "proto-ipv6")]
+use crate::wire::{Ipv6Address, PppIpv6cpOption};
+
+/// The maximum receive unit, which is also the largest packet sent.
+const MRU: usize = 1500;
+
+/// The longest frame received: address, control, protocol, information and FCS.
+const FRAME_LEN: usize = 4 + MRU + 2;
+
+/// The longest LCP, IPCP, IPv6CP, PAP or CHAP packet sent.
+const CONTROL_LEN: usize = 256;
+
+/// The time to wait for an answer to a request before sending it again.
+const RESTART_INTERVAL: Duration = Duration::from_secs(3);
+
+/// The number of requests sent before giving up.
+const MAX_CONFIGURE: u8 = 10;
+
+/// The longest user name or password used for authentication.
+const MAX_CREDENTIAL_LEN: usize = 64;
+
+/// The CHAP algorithm identifier of MD5.
+const CHAP_MD5: u8 = 5;
+
+/// A function answering a CHAP challenge, see [Ppp::set_chap_responder].
+pub type ChapResponder = fn(identifier: u8, challenge: &[u8]) -> [u8; 16];
+
+/// A serial port carrying a PPP link, e.g. a cellular modem in data mode.
+pub trait Serial {
+    /// Read the bytes received so far into `buffer`, without blocking, and return how
+    /// many were read.
+    fn read(&mut self, buffer: &mut [u8]) -> usize;
+
+    /// Write all of `data`.
+    fn write(&mut self, data: &[u8]);
+}
+
+/// An event of a [Ppp] link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// The link went down, the interface must forget the configuration.
+    Deconfigured,
+    /// The link is up, or its configuration changed.
+    Configured(Config),
+}
+
+/// The configuration negotiated by a [Ppp] link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// The IPv4 configuration, if IPCP is open and the peer gave an address.
+    #[cfg(feature = "proto-ipv4")]
+    pub ipv4: Option<Ipv4Config>,
+    /// The IPv6 configuration, if IPv6CP is open.
+    #[cfg(feature = "proto-ipv6")]
+    pub ipv6: Option<Ipv6Config>,
+}
+
+/// The IPv4 configuration negotiated by IPCP.
+#[cfg(feature = "proto-ipv4")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ipv4Config {
+    /// The address of this end of the link.
+    pub address: Ipv4Address,
+    /// The address of the peer, if it told it.
+    pub peer: Option<Ipv4Address>,
+    /// The DNS servers given by the peer.
+    pub dns_servers: Vec<Ipv4Address, 2>,
+}
+
+/// The IPv6 configuration negotiated by IPv6CP.
+#[cfg(feature = "proto-ipv6")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ipv6Config {
+    /// The link-local address of this end of the link.
+    pub address: Ipv6Address,
+    /// The link-local address of the peer.
+    pub peer: Ipv6Address,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Phase {
+    Establish,
+    Authenticate,
+    Network,
+}
+
+/// The authentication required by the peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Auth {
+    Pap,
+    Chap,
+}
+
+/// The state of a LCP, IPCP or IPv6CP automaton, or of PAP.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Negotiation {
+    identifier: u8,
+    ack_received: bool,
+    ack_sent: bool,
+    /// The peer does not support the protocol, or does not answer.
+    rejected: bool,
+    retransmit_at: Instant,
+    requests: u8,
+}
+
+impl Negotiation {
+    const fn new(start_at: Instant) -> Negotiation {
+        Negotiation {
+            identifier: 0,
+            ack_received: false,
+            ack_sent: false,
+            rejected: false,
+            retransmit_at: start_at,
+            requests: 0,
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.ack_received && self.ack_sent && !self.rejected
+    }
+
+    fn poll_at(&self) -> Option<Instant> {
+        if self.ack_received || self.rejected {
+            None
+        } else {
+            Some(self.retransmit_at)
+        }
+    }
+}
+
+/// What to answer to an option of a Configure-Request.
+enum Verdict {
+    Ack,
+    /// Suggest another value for the option.
+    Nak([u8; 8], usize),
+    Reject,
+}
+
+/// An option list being built into a buffer.
+struct Options {
+    buffer: [u8; CONTROL_LEN],
+    len: usize,
+}
+
+impl Options {
+    const fn new() -> Options {
+        Options {
+            buffer: [0; CONTROL_LEN],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, kind: u8, data: &[u8]) {
+        let option = PppConfigOption { kind, data };
+        let end = self.len + option.buffer_len();
+        if end <= self.buffer.len() {
+            option.emit(&mut self.buffer[self.len..end]);
+            self.len = end;
+        }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+}
+
+/// The HDLC-like framing of a link, see [RFC 1662].
+///
+/// [RFC 1662]: https://www.rfc-editor.org/rfc/rfc1662
+#[derive(Debug)]
+struct Decoder {
+    frame: [u8; FRAME_LEN],
+    len: usize,
+    escaped: bool,
+    overrun: bool,
+}
+
+impl Decoder {
+    const fn new() -> Decoder {
+        Decoder {
+            frame: [0; FRAME_LEN],
+            len: 0,
+            escaped: false,
+            overrun: false,
+        }
+    }
+
+    /// Decode `byte`, returning the length of the frame it ends without its FCS, if any.
+    fn push(&mut self, byte: u8) -> Option<usize> {
+        match byte {
+            PPP_HDLC_FLAG => {
+                let (len, valid) = (self.len, !self.overrun && !self.escaped);
+                self.len = 0;
+                self.escaped = false;
+                self.overrun = false;
+                if len == 0 {
+                    // Back-to-back flags.
+                    None
+                } else if !valid || len < 3 {
+                    net_debug!("ppp: dropping a truncated or overlong frame");
+                    None
+                } else if ppp_fcs_update(PPP_FCS_INIT, &self.frame[..len]) != PPP_FCS_GOOD {
+                    net_debug!("ppp: dropping a frame with an invalid FCS");
+                    None
+                } else {
+                    Some(len - 2)
+                }
+            }
+            PPP_HDLC_ESCAPE => {
+                self.escaped = true;
+                None
+            }
+            _ => {
+                let byte = if self.escaped { byte ^ 0x20 } else { byte };
+                self.escaped = false;
+                if self.len < self.frame.len() {
+                    self.frame[self.len] = byte;
+                    self.len += 1;
+                } else {
+                    self.overrun = true;
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Write a frame of `protocol` made of `parts` to `serial`.
+///
+/// All the control characters are escaped, which is valid whatever the peer's
+/// Async-Control-Character-Map.
+fn write_frame<S: Serial>(serial: &mut S, protocol: PppProtocol, parts: &[&[u8]]) {
+    let mut chunk = [0u8; 64];
+    let mut len = 0;
+    let mut fcs = PPP_FCS_INIT;
+
+    let mut push = |byte: u8, escape: bool, chunk: &mut [u8; 64], len: &mut usize| {
+        if *len + 2 > chunk.len() {
+            serial.write(&chunk[..*len]);
+            *len = 0;
+        }
+        if escape && (byte < 0x20 || byte == PPP_HDLC_FLAG || byte == PPP_HDLC_ESCAPE) {
+            chunk[*len] = PPP_HDLC_ESCAPE;
+            chunk[*len + 1] = byte ^ 0x20;
+            *len += 2;
+        } else {
+            chunk[*len] = byte;
+            *len += 1;
+        }
+    };
+
+    let protocol = u16::from(protocol).to_be_bytes();
+    let header = [PPP_HDLC_ADDRESS, PPP_HDLC_CONTROL, protocol[0], protocol[1]];
+    push(PPP_HDLC_FLAG, false, &mut chunk, &mut len);
+    for part in core::iter::once(&header[..]).chain(parts.iter().copied()) {
+        fcs = ppp_fcs_update(fcs, part);
+        for &byte in part {
+            push(byte, true, &mut chunk, &mut len);
+        }
+    }
+    for byte in (!fcs).to_le_bytes() {
+        push(byte, true, &mut chunk, &mut len);
+    }
+    push(PPP_HDLC_FLAG, false, &mut chunk, &mut len);
+    serial.write(&chunk[..len]);
+}
+
+/// Split a frame into its protocol and the offset of its information field.
+fn split_frame(frame: &[u8]) -> Option<(PppProtocol, usize)> {
+    // The address and control fields are omitted when they are compressed.
+    let offset = match frame {
+        [PPP_HDLC_ADDRESS, PPP_HDLC_CONTROL, ..] => 2,
+        _ => 0,
+    };
+    // The protocol field is one octet long when it is compressed.
+    match frame.get(offset..)? {
+        [protocol, ..] if protocol & 1 == 1 => {
+            Some((PppProtocol::from(*protocol as u16), offset + 1))
+        }
+        [high, low, ..] if low & 1 == 1 => Some((
+            PppProtocol::from(u16::from_be_bytes([*high, *low])),
+            offset + 2,
+        )),
+        _ => None,
+    }
+}
+
+/// A PPP device.
+///
+/// A PPP device runs the Point-to-Point Protocol over a [serial port](Serial), e.g. a
+/// cellular modem after it was switched to data mode with `ATD*99#`. It frames packets
+/// as described in [RFC 1662], and negotiates the link with the minimal LCP ([RFC 1661]),
+/// IPCP ([RFC 1332], [RFC 1877]) and IPv6CP ([RFC 5072]) needed to bring it up.
+/// If the peer requires it, the device authenticates with [PAP] or [CHAP]-MD5.
+///
+/// The link is negotiated as the device is polled by the interface, and packets can only
+/// be transmitted once it is up. Its negotiated addresses and DNS servers are returned
+/// by [poll](#method.poll), and must be added to the interface (which must use
+/// [Medium::Ip]), as with the DHCP sockets.
+///
+/// [RFC 1661]: https://www.rfc-editor.org/rfc/rfc1661
+/// [RFC 1662]: https://www.rfc-editor.org/rfc/rfc1662
+/// [RFC 1332]: https://www.rfc-editor.org/rfc/rfc1332
+/// [RFC 1877]: https://www.rfc-editor.org/rfc/rfc1877
+/// [RFC 5072]: https://www.rfc-editor.org/rfc/rfc5072
+/// [PAP]: https://www.rfc-editor.org/rfc/rfc1334
+/// [CHAP]: https://www.rfc-editor.org/rfc/rfc1994
+#[derive(Debug)]
+pub struct Ppp<S: Serial> {
+    serial: S,
+    decoder: Decoder,
+    stash: [u8; 64],
+    stash_pos: usize,
+    stash_len: usize,
+    tx_buffer: [u8; MRU],
+    now: Instant,
+    phase: Phase,
+
+    lcp: Negotiation,
+    magic: u32,
+    request_accm: bool,
+    peer_mru: usize,
+
+    auth: Option<Auth>,
+    pap: Negotiation,
+    authenticated: bool,
+    username: Vec<u8, MAX_CREDENTIAL_LEN>,
+    password: Option<Vec<u8, MAX_CREDENTIAL_LEN>>,
+    chap_responder: Option<ChapResponder>,
+
+    #[cfg(feature = "proto-ipv4")]
+    ipcp: Negotiation,
+    #[cfg(feature = "proto-ipv4")]
+    ipv4_address: Ipv4Address,
+    #[cfg(feature = "proto-ipv4")]
+    ipv4_peer: Option<Ipv4Address>,
+    #[cfg(feature = "proto-ipv4")]
+    dns_servers: [Option<Ipv4Address>; 2],
+
+    #[cfg(feature = "proto-ipv6")]
+    ipv6cp: Negotiation,
+    #[cfg(feature = "proto-ipv6")]
+    interface_id: [u8; 8],
+    #[cfg(feature = "proto-ipv6")]
+    peer_interface_id: Option<[u8; 8]>,
+
+    reported: Option<Config>,
+}
+
+impl<S: Serial> Ppp<S> {
+    /// Create a PPP device, using the given seed for the LCP magic number and the
+    /// IPv6 interface identifier.
+    pub fn new(serial: S, seed: u32) -> Ppp<S> {
+        let mut ppp = Ppp {
+            serial,
+            decoder: Decoder::new(),
+            stash: [0; 64],
+            stash_pos: 0,
+            stash_len: 0,
+            tx_buffer: [0; MRU],
+            now: Instant::ZERO,
+            phase: Phase::Establish,
+
+            lcp: Negotiation::new(Instant::ZERO),
+            magic: seed | 1,
+            request_accm: true,
+            peer_mru: MRU,
+
+            auth: None,
+            pap: Negotiation::new(Instant::ZERO),
+            authenticated: false,
+            username: Vec::new(),
+            password: None,
+            chap_responder: None,
+
+            #[cfg(feature = "proto-ipv4")]
+            ipcp: Negotiation::new(Instant::ZERO),
+            #[cfg(feature = "proto-ipv4")]
+            ipv4_address: Ipv4Address::UNSPECIFIED,
+            #[cfg(feature = "proto-ipv4")]
+            ipv4_peer: None,
+            #[cfg(feature = "proto-ipv4")]
+            dns_servers: [Some(Ipv4Address::UNSPECIFIED); 2],
+
+            #[cfg(feature = "proto-ipv6")]
+            ipv6cp: Negotiation::new(Instant::ZERO),
+            #[cfg(feature = "proto-ipv6")]
+            interface_id: [0; 8],
+            #[cfg(feature = "proto-ipv6")]
+            peer_interface_id: None,
+
+            reported: None,
+        };
+        #[cfg(feature = "proto-ipv6")]
+        {
+            let magic = ppp.magic.to_be_bytes();
+            ppp.interface_id = [0x02, magic[0], magic[1], 0xff, 0xfe, magic[2], magic[3], 0];
+        }
+        ppp.link_down(Instant::ZERO);
+        ppp
+    }
+
+    /// Get a reference to the serial port.
+    pub fn get_ref(&self) -> &S {
+        &self.serial
+    }
+
+    /// Get a mutable reference to the serial port.
+    ///
+    /// It is inadvisable to directly read from the serial port as doing so will
+    /// corrupt the frames being received.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.serial
+    }
+
+    /// Return the serial port, consuming the device.
+    pub fn into_inner(self) -> S {
+        self.serial
+    }
+
+    /// Authenticate with PAP, if the peer requires it.
+    ///
+    /// # Panics
+    /// This function panics if the user name or the password is longer than 64 octets.
+    pub fn set_pap_credentials(&mut self, username: &[u8], password: &[u8]) {
+        self.username = Vec::from_slice(username).expect("user name too long");
+        self.password = Some(Vec::from_slice(password).expect("password too long"));
+    }
+
+    /// Authenticate with CHAP-MD5, if the peer requires it.
+    ///
+    /// `responder` is called with the identifier and value of each challenge, and must
+    /// return the MD5 hash of the identifier, the secret and the challenge value.
+    ///
+    /// # Panics
+    /// This function panics if the name is longer than 64 octets.
+    pub fn set_chap_responder(&mut self, name: &[u8], responder: ChapResponder) {
+        self.username = Vec::from_slice(name).expect("name too long");
+        self.chap_responder = Some(responder);
+    }
+
+    /// Return whether the link is up, i.e. packets can be sent and received.
+    pub fn is_up(&self) -> bool {
+        self.open_protocols() != (false, false)
+    }
+
+    /// Return whether IPv4 and IPv6 packets can be sent.
+    fn open_protocols(&self) -> (bool, bool) {
+        if self.phase != Phase::Network {
+            return (false, false);
+        }
+        #[cfg(feature = "proto-ipv4")]
+        let ipv4 = self.ipcp.is_open();
+        #[cfg(not(feature = "proto-ipv4"))]
+        let ipv4 = false;
+        #[cfg(feature = "proto-ipv6")]
+        let ipv6 = self.ipv6cp.is_open();
+        #[cfg(not(feature = "proto-ipv6"))]
+        let ipv6 = false;
+        (ipv4, ipv6)
+    }
+
+    /// Return the negotiated configuration, if the link is up.
+    pub fn config(&self) -> Option<Config> {
+        if !self.is_up() {
+            return None;
+        }
+        Some(Config {
+            #[cfg(feature = "proto-ipv4")]
+            ipv4: (self.ipcp.is_open() && !self.ipv4_address.is_unspecified()).then(|| {
+                Ipv4Config {
+                    address: self.ipv4_address,
+                    peer: self.ipv4_peer,
+                    dns_servers: self
+                        .dns_servers
+                        .iter()
+                        .flatten()
+                        .filter(|addr| !addr.is_unspecified())
+                        .copied()
+                        .collect(),
+                }
+            }),
+            #[cfg(feature = "proto-ipv6")]
+            ipv6: match self.peer_interface_id {
+                Some(peer) if self.ipv6cp.is_open() => Some(Ipv6Config {
+                    address: link_local(&self.interface_id),
+                    peer: link_local(&peer),
+                }),
+                _ => None,
+            },
+        })
+    }
+
+    /// Return the change of the negotiated configuration since the last call, if any.
+    pub fn poll(&mut self) -> Option<Event> {
+        let config = self.config();
+        if config == self.reported {
+            return None;
+        }
+        self.reported = config.clone();
+        match config {
+            Some(config) => Some(Event::Configured(config)),
+            None => Some(Event::Deconfigured),
+        }
+    }
+
+    /// Return the time at which a request is due to be sent again, if any.
+    pub fn poll_at(&self) -> Option<Instant> {
+        let negotiation = match self.phase {
+            Phase::Establish => self.lcp.poll_at(),
+            Phase::Authenticate if self.auth == Some(Auth::Pap) => self.pap.poll_at(),
+            Phase::Authenticate => None,
+            Phase::Network => None,
+        };
+        #[allow(unused_mut)]
+        let mut poll_at = negotiation;
+        if self.phase == Phase::Network {
+            #[cfg(feature = "proto-ipv4")]
+            {
+                poll_at = min(poll_at, self.ipcp.poll_at());
+            }
+            #[cfg(feature = "proto-ipv6")]
+            {
+                poll_at = min(poll_at, self.ipv6cp.poll_at());
+            }
+        }
+        poll_at
+    }
+
+    /// Restart the negotiation of the link at `restart_at`.
+    fn link_down(&mut self, restart_at: Instant) {
+        self.phase = Phase::Establish;
+        self.lcp = Negotiation::new(restart_at);
+        self.request_accm = true;
+        self.peer_mru = MRU;
+        self.auth = None;
+        self.authenticated = false;
+        self.network_down();
+    }
+
+    /// Reset the network control protocols, e.g. when LCP is renegotiated.
+    fn network_down(&mut self) {
+        #[cfg(feature = "proto-ipv4")]
+        {
+            self.ipcp = Negotiation::new(self.now);
+            self.ipv4_address = Ipv4Address::UNSPECIFIED;
+            self.ipv4_peer = None;
+            self.dns_servers = [Some(Ipv4Address::UNSPECIFIED); 2];
+        }
+        #[cfg(feature = "proto-ipv6")]
+        {
+            self.ipv6cp = Negotiation::new(self.now);
+            self.peer_interface_id = None;
+        }
+    }
+
+    /// Move on to the next phase once LCP is open, and the peer authenticated us.
+    fn update_phase(&mut self) {
+        if self.phase == Phase::Establish && self.lcp.is_open() {
+            net_debug!("ppp: LCP open");
+            self.phase = Phase::Authenticate;
+            self.pap = Negotiation::new(self.now);
+        }
+        if self.phase == Phase::Authenticate && (self.auth.is_none() || self.authenticated) {
+            self.phase = Phase::Network;
+            self.network_down();
+        }
+    }
+
+    /// Terminate the link, e.g. when authentication failed.
+    fn terminate(&mut self) {
+        self.lcp.identifier = self.lcp.identifier.wrapping_add(1);
+        let identifier = self.lcp.identifier;
+        self.send_control(PppProtocol::Lcp, PppCode::TerminateRequest, identifier, &[]);
+        self.link_down(self.now + RESTART_INTERVAL);
+    }
+
+    fn negotiation_mut(&mut self, protocol: PppProtocol) -> Option<&mut Negotiation> {
+        match protocol {
+            PppProtocol::Lcp => Some(&mut self.lcp),
+            #[cfg(feature = "proto-ipv4")]
+            PppProtocol::Ipcp => Some(&mut self.ipcp),
+            #[cfg(feature = "proto-ipv6")]
+            PppProtocol::Ipv6cp => Some(&mut self.ipv6cp),
+            _ => None,
+        }
+    }
+
+    /// Send the requests that are due.
+    fn poll_timers(&mut self) {
+        match self.phase {
+            Phase::Establish => {
+                if self.lcp.poll_at().map_or(false, |at| at <= self.now) {
+                    if self.lcp.requests >= MAX_CONFIGURE {
+                        net_debug!("ppp: peer does not answer LCP, restarting");
+                        self.link_down(self.now);
+                    }
+                    self.send_request(PppProtocol::Lcp);
+                }
+            }
+            Phase::Authenticate => {
+                if self.auth == Some(Auth::Pap)
+                    && self.pap.poll_at().map_or(false, |at| at <= self.now)
+                {
+                    if self.pap.requests >= MAX_CONFIGURE {
+                        net_debug!("ppp: peer does not answer PAP, terminating");
+                        self.terminate();
+                    } else {
+                        self.send_pap_request();
+                    }
+                }
+            }
+            Phase::Network => {
+                #[cfg(feature = "proto-ipv4")]
+                self.poll_ncp(PppProtocol::Ipcp);
+                #[cfg(feature = "proto-ipv6")]
+                self.poll_ncp(PppProtocol::Ipv6cp);
+            }
+        }
+    }
+
+    #[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
+    fn poll_ncp(&mut self, protocol: PppProtocol) {
+        let now = self.now;
+        let negotiation = match self.negotiation_mut(protocol) {
+            Some(negotiation) => negotiation,
+            None => return,
+        };
+        if negotiation.poll_at().map_or(true, |at| at > now) {
+            return;
+        }
+        if negotiation.requests >= MAX_CONFIGURE {
+            net_debug!("ppp: peer does not answer {}, giving up", protocol);
+            negotiation.rejected = true;
+            return;
+        }
+        self.send_request(protocol);
+    }
+
+    fn send_control(&mut self, protocol: PppProtocol, code: PppCode, identifier: u8, data: &[u8]) {
+        let data = &data[..data.len().min(CONTROL_LEN)];
+        let length = ((PPP_HEADER_LEN + data.len()) as u16).to_be_bytes();
+        let header = [code.into(), identifier, length[0], length[1]];
+        write_frame(&mut self.serial, protocol, &[&header, data]);
+    }
+
+    /// Send a Configure-Request with the options of `protocol`.
+    fn send_request(&mut self, protocol: PppProtocol) {
+        let mut options = Options::new();
+        match protocol {
+            PppProtocol::Lcp => {
+                if self.request_accm {
+                    options.push(PppLcpOption::Accm.into(), &[0; 4]);
+                }
+                if self.magic != 0 {
+                    options.push(PppLcpOption::MagicNumber.into(), &self.magic.to_be_bytes());
+                }
+            }
+            #[cfg(feature = "proto-ipv4")]
+            PppProtocol::Ipcp => {
+                options.push(
+                    PppIpcpOption::IpAddress.into(),
+                    self.ipv4_address.as_bytes(),
+                );
+                let kinds = [PppIpcpOption::PrimaryDns, PppIpcpOption::SecondaryDns];
+                for (kind, addr) in kinds.iter().zip(self.dns_servers) {
+                    if let Some(addr) = addr {
+                        options.push((*kind).into(), addr.as_bytes());
+                    }
+                }
+            }
+            #[cfg(feature = "proto-ipv6")]
+            PppProtocol::Ipv6cp => {
+                options.push(PppIpv6cpOption::InterfaceId.into(), &self.interface_id);
+            }
+            _ => return,
+        }
+
+        let now = self.now;
+        let negotiation = match self.negotiation_mut(protocol) {
+            Some(negotiation) => negotiation,
+            None => return,
+        };
+        negotiation.identifier = negotiation.identifier.wrapping_add(1);
+        negotiation.requests += 1;
+        negotiation.retransmit_at = now + RESTART_INTERVAL;
+        let identifier = negotiation.identifier;
+        net_trace!("ppp: sending {} Configure-Request", protocol);
+        self.send_control(
+            protocol,
+            PppCode::ConfigureRequest,
+            identifier,
+            options.as_slice(),
+        );
+    }
+
+    fn send_pap_request(&mut self) {
+        let password = match &self.password {
+            Some(password) => password,
+            None => return,
+        };
+        let mut data = [0; 2 + 2 * MAX_CREDENTIAL_LEN];
+        let mut len = 0;
+        for field in [&self.username[..], &password[..]] {
+            data[len] = field.len() as u8;
+            data[len + 1..len + 1 + field.len()].copy_from_slice(field);
+            len += 1 + field.len();
+        }
+
+        self.pap.identifier = self.pap.identifier.wrapping_add(1);
+        self.pap.requests += 1;
+        self.pap.retransmit_at = self.now + RESTART_INTERVAL;
+        let identifier = self.pap.identifier;
+        // Authenticate-Request
+        self.send_control_raw(PppProtocol::Pap, 1, identifier, &data[..len]);
+    }
+
+    /// Send a PAP or CHAP packet, whose codes are not those of the control protocols.
+    fn send_control_raw(&mut self, protocol: PppProtocol, code: u8, identifier: u8, data: &[u8]) {
+        self.send_control(protocol, PppCode::from(code), identifier, data)
+    }
+
+    /// Process a frame, returning the range of the packet it carries, if any.
+    fn process_frame(&mut self, len: usize) -> Option<(usize, usize)> {
+        let (protocol, offset) = match split_frame(&self.decoder.frame[..len]) {
+            Some(split) => split,
+            None => {
+                net_debug!("ppp: dropping a frame without a protocol");
+                return None;
+            }
+        };
+
+        // Frames are copied out of the decoder so that the answers can be sent without
+        // borrowing it.
+        let mut info = [0; CONTROL_LEN];
+        let info_len = (len - offset).min(CONTROL_LEN);
+        match protocol {
+            #[cfg(feature = "proto-ipv4")]
+            PppProtocol::Ipv4 if self.phase == Phase::Network && self.ipcp.is_open() => {
+                return Some((offset, len))
+            }
+            #[cfg(feature = "proto-ipv6")]
+            PppProtocol::Ipv6 if self.phase == Phase::Network && self.ipv6cp.is_open() => {
+                return Some((offset, len))
+            }
+            PppProtocol::Ipv4 | PppProtocol::Ipv6 => {
+                net_debug!("ppp: dropping a {} packet, the link is not up", protocol);
+            }
+            PppProtocol::Lcp
+            | PppProtocol::Ipcp
+            | PppProtocol::Ipv6cp
+            | PppProtocol::Pap
+            | PppProtocol::Chap => {
+                info[..info_len].copy_from_slice(&self.decoder.frame[offset..offset + info_len]);
+                match PppPacket::new_checked(&info[..info_len]) {
+                    Ok(packet) => self.process_control(protocol, &packet),
+                    Err(_) => net_debug!("ppp: dropping a malformed {} packet", protocol),
+                }
+            }
+            PppProtocol::Unknown(id) => {
+                if self.phase != Phase::Establish {
+                    net_debug!("ppp: rejecting unknown protocol 0x{:04x}", id);
+                    let reject_len = (len - offset).min(CONTROL_LEN - 2);
+                    info[..2].copy_from_slice(&id.to_be_bytes());
+                    info[2..2 + reject_len]
+                        .copy_from_slice(&self.decoder.frame[offset..offset + reject_len]);
+                    self.lcp.identifier = self.lcp.identifier.wrapping_add(1);
+                    let identifier = self.lcp.identifier;
+                    self.send_control(
+                        PppProtocol::Lcp,
+                        PppCode::ProtocolReject,
+                        identifier,
+                        &info[..2 + reject_len],
+                    );
+                }
+            }
+        }
+        None
+    }
+
+    fn process_control(&mut self, protocol: PppProtocol, packet: &PppPacket<&[u8]>) {
+        match protocol {
+            PppProtocol::Pap => self.process_pap(packet),
+            PppProtocol::Chap => self.process_chap(packet),
+            PppProtocol::Lcp => self.process_cp(protocol, packet),
+            _ if self.phase == Phase::Network => self.process_cp(protocol, packet),
+            _ => net_debug!("ppp: dropping a {} packet, LCP is not open", protocol),
+        }
+        self.update_phase();
+    }
+
+    /// Process a LCP, IPCP or IPv6CP packet.
+    fn process_cp(&mut self, protocol: PppProtocol, packet: &PppPacket<&[u8]>) {
+        let identifier = packet.identifier();
+        match packet.code() {
+            PppCode::ConfigureRequest => {
+                let was_open = self
+                    .negotiation_mut(protocol)
+                    .map_or(false, |n| n.is_open());
+                if was_open {
+                    net_debug!("ppp: peer renegotiates {}", protocol);
+                    if protocol == PppProtocol::Lcp {
+                        self.phase = Phase::Establish;
+                        self.authenticated = false;
+                        self.network_down();
+                    }
+                    if let Some(negotiation) = self.negotiation_mut(protocol) {
+                        negotiation.ack_received = false;
+                        negotiation.requests = 0;
+                    }
+                    self.send_request(protocol);
+                }
+                self.answer_request(protocol, packet);
+            }
+            PppCode::ConfigureAck => {
+                if let Some(negotiation) = self.negotiation_mut(protocol) {
+                    if identifier == negotiation.identifier {
+                        negotiation.ack_received = true;
+                    }
+                }
+            }
+            code @ (PppCode::ConfigureNak | PppCode::ConfigureReject) => {
+                let now = self.now;
+                match self.negotiation_mut(protocol) {
+                    Some(negotiation) if identifier == negotiation.identifier => {
+                        negotiation.retransmit_at = now;
+                    }
+                    _ => return,
+                }
+                for option in packet.options().flatten() {
+                    if code == PppCode::ConfigureNak {
+                        self.adopt_option(protocol, option);
+                    } else {
+                        self.drop_option(protocol, option);
+                    }
+                }
+            }
+            PppCode::TerminateRequest => {
+                self.send_control(protocol, PppCode::TerminateAck, identifier, &[]);
+                if protocol == PppProtocol::Lcp {
+                    net_debug!("ppp: peer terminated the link");
+                    self.link_down(self.now + RESTART_INTERVAL);
+                } else if let Some(negotiation) = self.negotiation_mut(protocol) {
+                    net_debug!("ppp: peer terminated {}", protocol);
+                    *negotiation = Negotiation::new(negotiation.retransmit_at);
+                    negotiation.rejected = true;
+                }
+            }
+            PppCode::EchoRequest if protocol == PppProtocol::Lcp && self.lcp.is_open() => {
+                // The reply carries our magic number, followed by the data of the request.
+                let mut data = [0; CONTROL_LEN];
+                let len = packet.data().len();
+                if len >= 4 {
+                    data[..len].copy_from_slice(packet.data());
+                    data[..4].copy_from_slice(&self.magic.to_be_bytes());
+                    self.send_control(protocol, PppCode::EchoReply, identifier, &data[..len]);
+                }
+            }
+            PppCode::ProtocolReject if protocol == PppProtocol::Lcp => {
+                if let [high, low, ..] = *packet.data() {
+                    let rejected = PppProtocol::from(u16::from_be_bytes([high, low]));
+                    net_debug!("ppp: peer rejected {}", rejected);
+                    if let Some(negotiation) = self.negotiation_mut(rejected) {
+                        negotiation.rejected = true;
+                    }
+                }
+            }
+            PppCode::TerminateAck
+            | PppCode::CodeReject
+            | PppCode::EchoRequest
+            | PppCode::EchoReply
+            | PppCode::DiscardRequest
+            | PppCode::ProtocolReject => (),
+            PppCode::Unknown(_) => {
+                let data = packet.as_ref();
+                self.send_control(protocol, PppCode::CodeReject, identifier, data);
+            }
+        }
+    }
+
+    /// Answer a Configure-Request with an Ack, a Nak or a Reject.
+    fn answer_request(&mut self, protocol: PppProtocol, packet: &PppPacket<&[u8]>) {
+        let mut naks = Options::new();
+        let mut rejects = Options::new();
+        for option in packet.options() {
+            let option = match option {
+                Ok(option) => option,
+                Err(_) => {
+                    net_debug!("ppp: dropping a malformed {} Configure-Request", protocol);
+                    return;
+                }
+            };
+            match self.verdict(protocol, option) {
+                Verdict::Ack => (),
+                Verdict::Nak(data, len) => naks.push(option.kind, &data[..len]),
+                Verdict::Reject => rejects.push(option.kind, option.data),
+            }
+        }
+
+        let identifier = packet.identifier();
+        let (code, data) = if rejects.len > 0 {
+            (PppCode::ConfigureReject, rejects.as_slice())
+        } else if naks.len > 0 {
+            (PppCode::ConfigureNak, naks.as_slice())
+        } else {
+            for option in packet.options().flatten() {
+                self.apply_option(protocol, option);
+            }
+            (PppCode::ConfigureAck, packet.data())
+        };
+        if let Some(negotiation) = self.negotiation_mut(protocol) {
+            negotiation.ack_sent = code == PppCode::ConfigureAck;
+        }
+        self.send_control(protocol, code, identifier, data);
+    }
+
+    /// Decide what to answer to an option requested by the peer.
+    fn verdict(&self, protocol: PppProtocol, option: PppConfigOption) -> Verdict {
+        let mut suggestion = [0; 8];
+        match (protocol, option.data.len()) {
+            (PppProtocol::Lcp, len) => match (PppLcpOption::from(option.kind), len) {
+                (PppLcpOption::Mru, 2) | (PppLcpOption::Accm, 4) => Verdict::Ack,
+                (PppLcpOption::ProtocolCompression, 0) => Verdict::Ack,
+                (PppLcpOption::AddressControlCompression, 0) => Verdict::Ack,
+                (PppLcpOption::MagicNumber, 4) => {
+                    let magic = u32::from_be_bytes(option.data.try_into().unwrap());
+                    if magic == self.magic {
+                        // The link is looped back, or both ends picked the same number.
+                        suggestion[..4].copy_from_slice(&(!magic).to_be_bytes());
+                        Verdict::Nak(suggestion, 4)
+                    } else {
+                        Verdict::Ack
+                    }
+                }
+                (PppLcpOption::AuthProtocol, _) => {
+                    let pap = u16::from(PppProtocol::Pap).to_be_bytes();
+                    let chap = u16::from(PppProtocol::Chap).to_be_bytes();
+                    match option.data {
+                        [high, low] if [*high, *low] == pap && self.password.is_some() => {
+                            Verdict::Ack
+                        }
+                        [high, low, CHAP_MD5] if [*high, *low] == chap => {
+                            if self.chap_responder.is_some() {
+                                Verdict::Ack
+                            } else if self.password.is_some() {
+                                suggestion[..2].copy_from_slice(&pap);
+                                Verdict::Nak(suggestion, 2)
+                            } else {
+                                Verdict::Reject
+                            }
+                        }
+                        _ if self.password.is_some() => {
+                            suggestion[..2].copy_from_slice(&pap);
+                            Verdict::Nak(suggestion, 2)
+                        }
+                        _ if self.chap_responder.is_some() => {
+                            suggestion[..2].copy_from_slice(&chap);
+                            suggestion[2] = CHAP_MD5;
+                            Verdict::Nak(suggestion, 3)
+                        }
+                        _ => Verdict::Reject,
+                    }
+                }
+                _ => Verdict::Reject,
+            },
+            #[cfg(feature = "proto-ipv4")]
+            (PppProtocol::Ipcp, 4) if option.kind == PppIpcpOption::IpAddress.into() => {
+                Verdict::Ack
+            }
+            #[cfg(feature = "proto-ipv6")]
+            (PppProtocol::Ipv6cp, 8) if option.kind == PppIpv6cpOption::InterfaceId.into() => {
+                if option.data == [0; 8] || option.data == self.interface_id {
+                    suggestion.copy_from_slice(&self.interface_id);
+                    suggestion[7] ^= 0xff;
+                    Verdict::Nak(suggestion, 8)
+                } else {
+                    Verdict::Ack
+                }
+            }
+            _ => Verdict::Reject,
+        }
+    }
+
+    /// Apply an option acknowledged to the peer.
+    fn apply_option(&mut self, protocol: PppProtocol, option: PppConfigOption) {
+        match protocol {
+            PppProtocol::Lcp => match PppLcpOption::from(option.kind) {
+                PppLcpOption::Mru => {
+                    let mru = u16::from_be_bytes([option.data[0], option.data[1]]);
+                    self.peer_mru = (mru as usize).min(MRU);
+                }
+                PppLcpOption::AuthProtocol => {
+                    self.auth = match PppProtocol::from(u16::from_be_bytes([
+                        option.data[0],
+                        option.data[1],
+                    ])) {
+                        PppProtocol::Pap => Some(Auth::Pap),
+                        _ => Some(Auth::Chap),
+                    }
+                }
+                _ => (),
+            },
+            #[cfg(feature = "proto-ipv4")]
+            PppProtocol::Ipcp => {
+                let addr = Ipv4Address::from_bytes(option.data);
+                self.ipv4_peer = (!addr.is_unspecified()).then_some(addr);
+            }
+            #[cfg(feature = "proto-ipv6")]
+            PppProtocol::Ipv6cp => {
+                self.peer_interface_id = option.data.try_into().ok();
+            }
+            _ => (),
+        }
+    }
+
+    /// Adopt the value suggested by the peer in a Configure-Nak.
+    fn adopt_option(&mut self, protocol: PppProtocol, option: PppConfigOption) {
+        match (protocol, option.data.len()) {
+            (PppProtocol::Lcp, 4) if option.kind == PppLcpOption::MagicNumber.into() => {
+                self.magic = self.magic.wrapping_mul(1_103_515_245).wrapping_add(12345) | 1;
+            }
+            #[cfg(feature = "proto-ipv4")]
+            (PppProtocol::Ipcp, 4) => {
+                let addr = Ipv4Address::from_bytes(option.data);
+                match PppIpcpOption::from(option.kind) {
+                    PppIpcpOption::IpAddress => self.ipv4_address = addr,
+                    PppIpcpOption::PrimaryDns => self.dns_servers[0] = Some(addr),
+                    PppIpcpOption::SecondaryDns => self.dns_servers[1] = Some(addr),
+                    PppIpcpOption::Unknown(_) => (),
+                }
+            }
+            #[cfg(feature = "proto-ipv6")]
+            (PppProtocol::Ipv6cp, 8) if option.kind == PppIpv6cpOption::InterfaceId.into() => {
+                self.interface_id.copy_from_slice(option.data);
+            }
+            _ => (),
+        }
+    }
+
+    /// Stop requesting an option rejected by the peer.
+    fn drop_option(&mut self, protocol: PppProtocol, option: PppConfigOption) {
+        match protocol {
+            PppProtocol::Lcp => match PppLcpOption::from(option.kind) {
+                PppLcpOption::Accm => self.request_accm = false,
+                PppLcpOption::MagicNumber => self.magic = 0,
+                _ => (),
+            },
+            #[cfg(feature = "proto-ipv4")]
+            PppProtocol::Ipcp => match PppIpcpOption::from(option.kind) {
+                PppIpcpOption::PrimaryDns => self.dns_servers[0] = None,
+                PppIpcpOption::SecondaryDns => self.dns_servers[1] = None,
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+
+    fn process_pap(&mut self, packet: &PppPacket<&[u8]>) {
+        if self.phase != Phase::Authenticate || self.auth != Some(Auth::Pap) {
+            return;
+        }
+        if packet.identifier() != self.pap.identifier {
+            return;
+        }
+        match u8::from(packet.code()) {
+            // Authenticate-Ack
+            2 => {
+                net_debug!("ppp: PAP authentication succeeded");
+                self.authenticated = true;
+            }
+            // Authenticate-Nak
+            3 => {
+                net_debug!("ppp: PAP authentication failed");
+                self.terminate();
+            }
+            _ => (),
+        }
+    }
+
+    fn process_chap(&mut self, packet: &PppPacket<&[u8]>) {
+        if self.phase != Phase::Authenticate || self.auth != Some(Auth::Chap) {
+            return;
+        }
+        match u8::from(packet.code()) {
+            // Challenge
+            1 => {
+                let responder = match self.chap_responder {
+                    Some(responder) => responder,
+                    None => return,
+                };
+                let challenge = match packet.data() {
+                    [size, rest @ ..] if *size as usize <= rest.len() => &rest[..*size as usize],
+                    _ => return,
+                };
+                let mut data = [0; 1 + 16 + MAX_CREDENTIAL_LEN];
+                data[0] = 16;
+                data[1..17].copy_from_slice(&responder(packet.identifier(), challenge));
+                data[17..17 + self.username.len()].copy_from_slice(&self.username);
+                let len = 17 + self.username.len();
+                // Response
+                self.send_control_raw(PppProtocol::Chap, 2, packet.identifier(), &data[..len]);
+            }
+            // Success
+            3 => {
+                net_debug!("ppp: CHAP authentication succeeded");
+                self.authenticated = true;
+            }
+            // Failure
+            4 => {
+                net_debug!("ppp: CHAP authentication failed");
+                self.terminate();
+            }
+            _ => (),
+        }
+    }
+
+    /// Read bytes from the serial port until they end a frame, returning its length.
+    fn read_frame(&mut self) -> Option<usize> {
+        loop {
+            if self.stash_pos == self.stash_len {
+                self.stash_pos = 0;
+                self.stash_len = self.serial.read(&mut self.stash);
+                if self.stash_len == 0 {
+                    return None;
+                }
+            }
+            let byte = self.stash[self.stash_pos];
+            self.stash_pos += 1;
+            if let Some(len) = self.decoder.push(byte) {
+                return Some(len);
+            }
+        }
+    }
+}
+
+fn min(a: Option<Instant>, b: Option<Instant>) -> Option<Instant> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(feature = "proto-ipv6")]
+fn link_local(interface_id: &[u8; 8]) -> Ipv6Address {
+    let mut addr = [0; 16];
+    addr[..2].copy_from_slice(&[0xfe, 0x80]);
+    addr[8..].copy_from_slice(interface_id);
+    Ipv6Address(addr)
+}
+
+impl<S: Serial> Device for Ppp<S> {
+    type RxToken<'a>
+        = RxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, S>
+    where
+        Self: 'a;
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            medium: Medium::Ip,
+            max_transmission_unit: self.peer_mru,
+            ..DeviceCapabilities::default()
+        }
+    }
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        self.now = timestamp;
+        let (start, end) = loop {
+            self.poll_timers();
+            let len = self.read_frame()?;
+            if let Some(range) = self.process_frame(len) {
+                break range;
+            }
+        };
+
+        let protocols = self.open_protocols();
+        let rx = RxToken {
+            buffer: &self.decoder.frame[start..end],
+        };
+        let tx = TxToken {
+            serial: &mut self.serial,
+            buffer: &mut self.tx_buffer,
+            protocols,
+            version: None,
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        self.now = timestamp;
+        self.poll_timers();
+        if !self.is_up() {
+            return None;
+        }
+        let protocols = self.open_protocols();
+        Some(TxToken {
+            serial: &mut self.serial,
+            buffer: &mut self.tx_buffer,
+            protocols,
+            version: None,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> phy::RxToken for RxToken<'a> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(self.buffer)
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<'a, S: Serial> {
+    serial: &'a mut S,
+    buffer: &'a mut [u8; MRU],
+    protocols: (bool, bool),
+    version: Option<IpVersion>,
+}
+
+impl<'a, S: Serial> phy::TxToken for TxToken<'a, S> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let buffer = &mut self.buffer[..len];
+        let result = f(buffer);
+        let version = match self.version {
+            Some(version) => Some(version),
+            None => IpVersion::of_packet(buffer).ok(),
+        };
+        let protocol = match (version, self.protocols) {
+            #[cfg(feature = "proto-ipv4")]
+            (Some(IpVersion::Ipv4), (true, _)) => PppProtocol::Ipv4,
+            #[cfg(feature = "proto-ipv6")]
+            (Some(IpVersion::Ipv6), (_, true)) => PppProtocol::Ipv6,
+            _ => {
+                net_debug!("ppp: dropping a packet, its protocol is not open");
+                return result;
+            }
+        };
+        write_frame(self.serial, protocol, &[buffer]);
+        result
+    }
+
+    fn set_ip_version(&mut self, version: IpVersion) {
+        self.version = Some(version);
+    }
+}
@@ -0,0 +1,628 @@
+use crate::phy::{self, Device, DeviceCapabilities, Medium};
+use crate::rand::Rand;
+use crate::time::{Duration, Instant};
+use crate::wire::{Ieee802154Address, Ieee802154Frame, Ieee802154FrameType};
+
+/// The longest frame, `aMaxPhyPacketSize` without the frame check sequence.
+const FRAME_LEN: usize = 125;
+
+/// The length of an acknowledgment frame, without the frame check sequence.
+const ACK_LEN: usize = 3;
+
+/// The duration of a backoff period, `aUnitBackoffPeriod`, i.e. 20 symbols of the
+/// 2.4 GHz O-QPSK PHY.
+const UNIT_BACKOFF_PERIOD: Duration = Duration::from_micros(320);
+
+/// An IEEE 802.15.4 radio transceiver.
+///
+/// Frames are given without their frame check sequence, which the radio appends when
+/// transmitting, and checks when receiving.
+pub trait Radio {
+    /// Perform a clear channel assessment, and return whether the channel is idle.
+    fn clear_channel(&mut self) -> bool;
+
+    /// Transmit `frame` at once, without checking the channel.
+    fn transmit(&mut self, frame: &[u8]);
+
+    /// Read a frame received with a valid frame check sequence into `buffer`, without
+    /// blocking, and return its length.
+    fn receive(&mut self, buffer: &mut [u8]) -> Option<usize>;
+}
+
+/// The MAC parameters of an [Ieee802154Mac], defaulting to the values of IEEE 802.15.4.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// The initial backoff exponent, `macMinBe`.
+    pub min_be: u8,
+    /// The largest backoff exponent, `macMaxBe`.
+    pub max_be: u8,
+    /// The number of times the channel is found busy before a frame is dropped,
+    /// `macMaxCsmaBackoffs`.
+    pub max_csma_backoffs: u8,
+    /// The number of retransmissions of a frame that was not acknowledged before it is
+    /// dropped, `macMaxFrameRetries`.
+    pub max_frame_retries: u8,
+    /// The time to wait for an acknowledgment, `macAckWaitDuration`.
+    pub ack_wait_duration: Duration,
+    /// Whether to request an acknowledgment for the unicast data frames sent.
+    pub request_ack: bool,
+    /// The address for which received frames are acknowledged, if the radio doesn't
+    /// acknowledge them by itself.
+    pub ack_address: Option<Ieee802154Address>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            min_be: 3,
+            max_be: 5,
+            max_csma_backoffs: 4,
+            max_frame_retries: 3,
+            ack_wait_duration: Duration::from_micros(864),
+            request_ack: true,
+            ack_address: None,
+        }
+    }
+}
+
+/// Counters of the frames sent and received by an [Ieee802154Mac].
+///
+/// The counters start at zero when the device is created, and wrap around on overflow.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Stats {
+    /// Frames sent, and acknowledged if they requested it.
+    pub tx_frames: u32,
+    /// Retransmissions of frames that were not acknowledged in time.
+    pub tx_retries: u32,
+    /// Frames dropped because they were never acknowledged.
+    pub tx_no_ack: u32,
+    /// Frames dropped because the channel was busy at each attempt.
+    pub tx_channel_busy: u32,
+    /// Frames dropped because another one was being sent.
+    pub tx_dropped: u32,
+    /// Retransmitted frames received again, and dropped.
+    pub rx_duplicates: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TxState {
+    /// No frame is being sent.
+    Idle,
+    /// The channel is assessed at `at`, and the frame sent if it is idle.
+    Backoff {
+        at: Instant,
+        backoffs: u8,
+        be: u8,
+        retries: u8,
+    },
+    /// The frame was sent, and is sent again if it isn't acknowledged before `until`.
+    AwaitAck {
+        until: Instant,
+        sequence_number: u8,
+        retries: u8,
+    },
+}
+
+/// The frame being sent, and the progress of its transmission.
+#[derive(Debug)]
+struct Transmitter {
+    buffer: [u8; FRAME_LEN],
+    len: usize,
+    state: TxState,
+    rand: Rand,
+}
+
+impl Transmitter {
+    /// Wait a random number of backoff periods before assessing the channel.
+    fn backoff(&mut self, now: Instant, backoffs: u8, be: u8, retries: u8) {
+        let periods = self.rand.rand_u32() % (1 << be.min(31));
+        self.state = TxState::Backoff {
+            at: now + UNIT_BACKOFF_PERIOD * periods,
+            backoffs,
+            be,
+            retries,
+        };
+    }
+
+    /// Advance the transmission of the frame, as far as possible at `now`.
+    fn poll<R: Radio>(&mut self, radio: &mut R, config: &Config, stats: &mut Stats, now: Instant) {
+        loop {
+            match self.state {
+                TxState::Idle => return,
+                TxState::Backoff { at, .. } if at > now => return,
+                TxState::Backoff {
+                    backoffs,
+                    be,
+                    retries,
+                    ..
+                } => {
+                    if !radio.clear_channel() {
+                        if backoffs >= config.max_csma_backoffs {
+                            net_debug!("radio: channel busy, dropping a frame");
+                            stats.tx_channel_busy = stats.tx_channel_busy.wrapping_add(1);
+                            self.state = TxState::Idle;
+                        } else {
+                            let be = (be + 1).min(config.max_be);
+                            self.backoff(now, backoffs + 1, be, retries);
+                        }
+                        continue;
+                    }
+
+                    let frame = &self.buffer[..self.len];
+                    radio.transmit(frame);
+                    let frame = Ieee802154Frame::new_unchecked(frame);
+                    match frame.sequence_number() {
+                        Some(sequence_number) if frame.ack_request() => {
+                            self.state = TxState::AwaitAck {
+                                until: now + config.ack_wait_duration,
+                                sequence_number,
+                                retries,
+                            };
+                        }
+                        _ => {
+                            stats.tx_frames = stats.tx_frames.wrapping_add(1);
+                            self.state = TxState::Idle;
+                        }
+                    }
+                }
+                TxState::AwaitAck { until, .. } if until > now => return,
+                TxState::AwaitAck { retries, .. } => {
+                    if retries >= config.max_frame_retries {
+                        net_debug!("radio: frame not acknowledged, dropping it");
+                        stats.tx_no_ack = stats.tx_no_ack.wrapping_add(1);
+                        self.state = TxState::Idle;
+                    } else {
+                        stats.tx_retries = stats.tx_retries.wrapping_add(1);
+                        self.backoff(now, 0, config.min_be, retries + 1);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handle a received acknowledgment.
+    fn process_ack(&mut self, sequence_number: u8, stats: &mut Stats) {
+        match self.state {
+            TxState::AwaitAck {
+                sequence_number: expected,
+                ..
+            } if expected == sequence_number => {
+                stats.tx_frames = stats.tx_frames.wrapping_add(1);
+                self.state = TxState::Idle;
+            }
+            _ => net_trace!("radio: ignoring an unexpected acknowledgment"),
+        }
+    }
+}
+
+/// An IEEE 802.15.4 device with a software MAC.
+///
+/// An `Ieee802154Mac` implements the unslotted CSMA-CA and the retransmissions of
+/// [IEEE 802.15.4] on top of a simple [radio](Radio), so that radio drivers don't have
+/// to. Before a frame is sent, the channel is assessed after a random backoff, which
+/// grows each time the channel is found busy. Unicast data frames request an
+/// acknowledgment, and are sent again until one is received, or the retries are
+/// exhausted. If the radio doesn't acknowledge frames by itself, the device can send
+/// the acknowledgments, and drops the retransmitted frames it already received.
+///
+/// One frame is sent at a time: the device doesn't give a transmit token until the
+/// previous frame is acknowledged or dropped. Backoffs and acknowledgment timeouts
+/// elapse as the device is polled by the interface, which must be polled again at
+/// [poll_at](#method.poll_at).
+///
+/// [IEEE 802.15.4]: https://standards.ieee.org/ieee/802.15.4/7029/
+#[derive(Debug)]
+pub struct Ieee802154Mac<R: Radio> {
+    radio: R,
+    config: Config,
+    now: Instant,
+    rx_buffer: [u8; FRAME_LEN],
+    tx: Transmitter,
+    last_rx: Option<(Ieee802154Address, u8)>,
+    stats: Stats,
+}
+
+impl<R: Radio> Ieee802154Mac<R> {
+    /// Create an IEEE 802.15.4 device with the default MAC parameters, using the given
+    /// seed for the random backoffs.
+    pub fn new(radio: R, seed: u32) -> Ieee802154Mac<R> {
+        Ieee802154Mac {
+            radio,
+            config: Config::default(),
+            now: Instant::ZERO,
+            rx_buffer: [0; FRAME_LEN],
+            tx: Transmitter {
+                buffer: [0; FRAME_LEN],
+                len: 0,
+                state: TxState::Idle,
+                rand: Rand::new(seed as u64),
+            },
+            last_rx: None,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Get a reference to the radio.
+    pub fn get_ref(&self) -> &R {
+        &self.radio
+    }
+
+    /// Get a mutable reference to the radio.
+    ///
+    /// It is inadvisable to directly transmit or receive frames with the radio, as
+    /// doing so will disrupt the acknowledgments.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.radio
+    }
+
+    /// Return the radio, consuming the device.
+    pub fn into_inner(self) -> R {
+        self.radio
+    }
+
+    /// Return the MAC parameters.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Set the MAC parameters.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Return the counters of the device.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Return whether a frame is being sent.
+    pub fn is_busy(&self) -> bool {
+        self.tx.state != TxState::Idle
+    }
+
+    /// Return the time at which the channel is assessed or an acknowledgment times out,
+    /// if a frame is being sent.
+    pub fn poll_at(&self) -> Option<Instant> {
+        match self.tx.state {
+            TxState::Idle => None,
+            TxState::Backoff { at, .. } => Some(at),
+            TxState::AwaitAck { until, .. } => Some(until),
+        }
+    }
+
+    fn poll_timers(&mut self) {
+        self.tx
+            .poll(&mut self.radio, &self.config, &mut self.stats, self.now);
+    }
+
+    /// Handle a frame of `len` octets in the receive buffer, and return whether it is
+    /// given to the interface.
+    fn process_frame(&mut self, len: usize) -> bool {
+        if len < ACK_LEN {
+            return true;
+        }
+        let frame = Ieee802154Frame::new_unchecked(&self.rx_buffer[..len]);
+        if frame.frame_type() == Ieee802154FrameType::Acknowledgement {
+            if let Some(sequence_number) = frame.sequence_number() {
+                self.tx.process_ack(sequence_number, &mut self.stats);
+                self.poll_timers();
+            }
+            return false;
+        }
+        if !frame.ack_request() || frame.check_len().is_err() {
+            return true;
+        }
+        let sequence_number = match frame.sequence_number() {
+            Some(sequence_number) => sequence_number,
+            None => return true,
+        };
+
+        if let Some(address) = self.config.ack_address {
+            if frame.dst_addr() == Some(address) {
+                let mut ack = [0; ACK_LEN];
+                let mut ack_frame = Ieee802154Frame::new_unchecked(&mut ack[..]);
+                ack_frame.set_frame_type(Ieee802154FrameType::Acknowledgement);
+                ack_frame.set_sequence_number(sequence_number);
+                self.radio.transmit(&ack);
+            }
+        }
+
+        // A frame is retransmitted when its acknowledgment is lost.
+        if let Some(src_addr) = frame.src_addr() {
+            if self.last_rx == Some((src_addr, sequence_number)) {
+                net_trace!("radio: dropping a duplicate frame from {}", src_addr);
+                self.stats.rx_duplicates = self.stats.rx_duplicates.wrapping_add(1);
+                return false;
+            }
+            self.last_rx = Some((src_addr, sequence_number));
+        }
+        true
+    }
+}
+
+impl<R: Radio> Device for Ieee802154Mac<R> {
+    type RxToken<'a>
+        = RxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, R>
+    where
+        Self: 'a;
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            medium: Medium::Ieee802154,
+            max_transmission_unit: FRAME_LEN,
+            max_burst_size: Some(1),
+            ..DeviceCapabilities::default()
+        }
+    }
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        self.now = timestamp;
+        let len = loop {
+            self.poll_timers();
+            let len = self.radio.receive(&mut self.rx_buffer)?.min(FRAME_LEN);
+            if self.process_frame(len) {
+                break len;
+            }
+        };
+
+        let rx = RxToken {
+            buffer: &self.rx_buffer[..len],
+        };
+        let tx = TxToken {
+            radio: &mut self.radio,
+            config: &self.config,
+            tx: &mut self.tx,
+            stats: &mut self.stats,
+            now: self.now,
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        self.now = timestamp;
+        self.poll_timers();
+        if self.is_busy() {
+            return None;
+        }
+        Some(TxToken {
+            radio: &mut self.radio,
+            config: &self.config,
+            tx: &mut self.tx,
+            stats: &mut self.stats,
+            now: self.now,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> phy::RxToken for RxToken<'a> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(self.buffer)
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<'a, R: Radio> {
+    radio: &'a mut R,
+    config: &'a Config,
+    tx: &'a mut Transmitter,
+    stats: &'a mut Stats,
+    now: Instant,
+}
+
+impl<'a, R: Radio> phy::TxToken for TxToken<'a, R> {
+    fn consume<T, F>(self, len: usize, f: F) -> T
+    where
+        F: FnOnce(&mut [u8]) -> T,
+    {
+        if self.tx.state != TxState::Idle || len > FRAME_LEN {
+            // The token was given with a received frame, while another one is being sent.
+            net_debug!("radio: dropping a frame, the radio is busy");
+            self.stats.tx_dropped = self.stats.tx_dropped.wrapping_add(1);
+            let mut buffer = [0; FRAME_LEN];
+            return f(&mut buffer[..len.min(FRAME_LEN)]);
+        }
+
+        let result = f(&mut self.tx.buffer[..len]);
+        self.tx.len = len;
+        if self.config.request_ack && len >= ACK_LEN {
+            let mut frame = Ieee802154Frame::new_unchecked(&mut self.tx.buffer[..len]);
+            if frame.frame_type() == Ieee802154FrameType::Data
+                && frame.check_len().is_ok()
+                && matches!(frame.dst_addr(), Some(addr) if addr.is_unicast() && addr != Ieee802154Address::Absent)
+            {
+                frame.set_ack_request(true);
+            }
+        }
+        self.tx.backoff(self.now, 0, self.config.min_be, 0);
+        self.tx.poll(self.radio, self.config, self.stats, self.now);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::phy::{RxToken as _, TxToken as _};
+    use crate::wire::{Ieee802154FrameVersion, Ieee802154Pan, Ieee802154Repr};
+
+    #[derive(Debug, Default)]
+    struct TestRadio {
+        busy: bool,
+        assessments: usize,
+        sent: Vec<Vec<u8>>,
+        received: Vec<Vec<u8>>,
+    }
+
+    impl Radio for TestRadio {
+        fn clear_channel(&mut self) -> bool {
+            self.assessments += 1;
+            !self.busy
+        }
+
+        fn transmit(&mut self, frame: &[u8]) {
+            self.sent.push(frame.to_vec());
+        }
+
+        fn receive(&mut self, buffer: &mut [u8]) -> Option<usize> {
+            if self.received.is_empty() {
+                return None;
+            }
+            let frame = self.received.remove(0);
+            buffer[..frame.len()].copy_from_slice(&frame);
+            Some(frame.len())
+        }
+    }
+
+    const LOCAL: Ieee802154Address = Ieee802154Address::Short([0x00, 0x01]);
+    const REMOTE: Ieee802154Address = Ieee802154Address::Short([0x00, 0x02]);
+
+    fn data_frame(
+        src_addr: Ieee802154Address,
+        dst_addr: Ieee802154Address,
+        sequence_number: u8,
+        ack_request: bool,
+    ) -> Vec<u8> {
+        let repr = Ieee802154Repr {
+            frame_type: Ieee802154FrameType::Data,
+            security_enabled: false,
+            frame_pending: false,
+            ack_request,
+            sequence_number: Some(sequence_number),
+            pan_id_compression: true,
+            frame_version: Ieee802154FrameVersion::Ieee802154_2006,
+            dst_pan_id: Some(Ieee802154Pan(0xbeef)),
+            dst_addr: Some(dst_addr),
+            src_pan_id: None,
+            src_addr: Some(src_addr),
+        };
+        let mut buffer = std::vec![0; repr.buffer_len() + 4];
+        repr.emit(&mut Ieee802154Frame::new_unchecked(&mut buffer[..]));
+        buffer
+    }
+
+    fn ack_frame(sequence_number: u8) -> Vec<u8> {
+        let mut buffer = std::vec![0; ACK_LEN];
+        let mut frame = Ieee802154Frame::new_unchecked(&mut buffer[..]);
+        frame.set_frame_type(Ieee802154FrameType::Acknowledgement);
+        frame.set_sequence_number(sequence_number);
+        buffer
+    }
+
+    fn send(mac: &mut Ieee802154Mac<TestRadio>, now: Instant, frame: &[u8]) {
+        let token = mac.transmit(now).unwrap();
+        token.consume(frame.len(), |buffer| buffer.copy_from_slice(frame));
+    }
+
+    /// Poll the device until `until`, as the interface would when it is idle.
+    fn run(mac: &mut Ieee802154Mac<TestRadio>, now: &mut Instant, until: Instant) {
+        while *now < until {
+            while let Some((rx, _tx)) = mac.receive(*now) {
+                rx.consume(|_| ());
+            }
+            *now = match mac.poll_at() {
+                Some(at) if at > *now && at < until => at,
+                Some(at) if at <= *now => *now,
+                _ => until,
+            };
+        }
+    }
+
+    #[test]
+    fn test_acknowledged() {
+        let mut mac = Ieee802154Mac::new(TestRadio::default(), 0x1234);
+        let mut config = mac.config().clone();
+        config.ack_wait_duration = Duration::from_secs(1);
+        mac.set_config(config);
+        let mut now = Instant::from_millis(0);
+        send(&mut mac, now, &data_frame(LOCAL, REMOTE, 7, false));
+        run(&mut mac, &mut now, Instant::from_millis(15));
+        assert_eq!(mac.get_ref().sent.len(), 1);
+        assert!(Ieee802154Frame::new_unchecked(&mac.get_ref().sent[0]).ack_request());
+        assert!(mac.is_busy());
+        assert!(mac.transmit(now).is_none());
+
+        mac.get_mut().received.push(ack_frame(7));
+        assert!(mac.receive(now).is_none());
+        assert!(!mac.is_busy());
+        assert_eq!(mac.stats().tx_frames, 1);
+        assert_eq!(mac.stats().tx_retries, 0);
+    }
+
+    #[test]
+    fn test_retransmit() {
+        let mut mac = Ieee802154Mac::new(TestRadio::default(), 0x1234);
+        let mut now = Instant::from_millis(0);
+        send(&mut mac, now, &data_frame(LOCAL, REMOTE, 7, false));
+        run(&mut mac, &mut now, Instant::from_millis(100));
+        assert_eq!(mac.get_ref().sent.len(), 4);
+        assert!(!mac.is_busy());
+        assert_eq!(mac.stats().tx_retries, 3);
+        assert_eq!(mac.stats().tx_no_ack, 1);
+        assert_eq!(mac.stats().tx_frames, 0);
+    }
+
+    #[test]
+    fn test_broadcast_without_ack() {
+        let mut mac = Ieee802154Mac::new(TestRadio::default(), 0x1234);
+        let mut now = Instant::from_millis(0);
+        send(
+            &mut mac,
+            now,
+            &data_frame(LOCAL, Ieee802154Address::BROADCAST, 7, false),
+        );
+        run(&mut mac, &mut now, Instant::from_millis(100));
+        assert_eq!(mac.get_ref().sent.len(), 1);
+        assert!(!Ieee802154Frame::new_unchecked(&mac.get_ref().sent[0]).ack_request());
+        assert_eq!(mac.stats().tx_frames, 1);
+    }
+
+    #[test]
+    fn test_channel_busy() {
+        let mut mac = Ieee802154Mac::new(TestRadio::default(), 0x1234);
+        mac.get_mut().busy = true;
+        let mut now = Instant::from_millis(0);
+        send(&mut mac, now, &data_frame(LOCAL, REMOTE, 7, false));
+        run(&mut mac, &mut now, Instant::from_millis(100));
+        assert!(mac.get_ref().sent.is_empty());
+        assert_eq!(mac.get_ref().assessments, 5);
+        assert_eq!(mac.stats().tx_channel_busy, 1);
+        assert!(!mac.is_busy());
+    }
+
+    #[test]
+    fn test_auto_ack() {
+        let mut mac = Ieee802154Mac::new(TestRadio::default(), 0x1234);
+        let mut config = mac.config().clone();
+        config.ack_address = Some(LOCAL);
+        mac.set_config(config);
+
+        let frame = data_frame(REMOTE, LOCAL, 9, true);
+        mac.get_mut().received.push(frame.clone());
+        mac.get_mut().received.push(frame.clone());
+        let now = Instant::from_millis(0);
+        let (rx, _tx) = mac.receive(now).unwrap();
+        rx.consume(|buffer| assert_eq!(buffer, &frame[..]));
+        // The retransmission is acknowledged again, but not given to the interface.
+        assert!(mac.receive(now).is_none());
+        assert_eq!(mac.get_ref().sent, std::vec![ack_frame(9), ack_frame(9)]);
+        assert_eq!(mac.stats().rx_duplicates, 1);
+    }
+}
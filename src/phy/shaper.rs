@@ -0,0 +1,319 @@
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::phy::{self, Device, DeviceCapabilities};
+use crate::time::{Duration, Instant};
+
+/// The default number of packets queued in each direction.
+const DEFAULT_QUEUE_LIMIT: usize = 64;
+
+/// A token bucket, counting bits in millionths so that it refills by the bitrate every microsecond.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Bucket {
+    bitrate: u64,
+    tokens: i64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    const fn new() -> Bucket {
+        Bucket {
+            bitrate: 0,
+            tokens: 0,
+            refilled_at: Instant::ZERO,
+        }
+    }
+
+    /// Take the tokens for a packet of `len` octets, returning when it leaves the bucket.
+    ///
+    /// The bucket goes into debt when it is short of tokens, so that the packets after
+    /// this one wait for it.
+    fn take(&mut self, timestamp: Instant, len: usize, burst_size: usize) -> Instant {
+        if self.bitrate == 0 {
+            return timestamp;
+        }
+
+        let burst = (burst_size as i64).saturating_mul(8_000_000);
+        if timestamp > self.refilled_at {
+            let elapsed = (timestamp - self.refilled_at).total_micros() as i64;
+            let refill = elapsed.saturating_mul(self.bitrate as i64);
+            self.tokens = self.tokens.saturating_add(refill).min(burst);
+            self.refilled_at = timestamp;
+        }
+
+        self.tokens -= (len as i64).saturating_mul(8_000_000);
+        if self.tokens >= 0 {
+            timestamp
+        } else {
+            timestamp + Duration::from_micros((-self.tokens) as u64 / self.bitrate)
+        }
+    }
+}
+
+/// A packet queued by the shaper, with the time it reaches the other side of the link.
+#[derive(Debug)]
+struct Queued {
+    arrival: Instant,
+    packet: Vec<u8>,
+}
+
+/// One direction of a shaped link.
+#[derive(Debug)]
+struct Link {
+    bucket: Bucket,
+    queue: VecDeque<Queued>,
+}
+
+impl Link {
+    fn new() -> Link {
+        Link {
+            bucket: Bucket::new(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, config: &Config, timestamp: Instant, packet: Vec<u8>) {
+        let departure = self.bucket.take(timestamp, packet.len(), config.burst_size);
+        self.queue.push_back(Queued {
+            arrival: departure + config.delay,
+            packet,
+        })
+    }
+
+    fn pop(&mut self, timestamp: Instant) -> Option<Vec<u8>> {
+        match self.queue.front() {
+            Some(queued) if queued.arrival <= timestamp => {
+                self.queue.pop_front().map(|queued| queued.packet)
+            }
+            _ => None,
+        }
+    }
+
+    fn is_full(&self, config: &Config) -> bool {
+        self.queue.len() >= config.queue_limit
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Config {
+    burst_size: usize,
+    delay: Duration,
+    queue_limit: usize,
+}
+
+/// A bandwidth and latency shaping device.
+///
+/// A shaper is a device that emulates a constrained link (such as a LoRa backhaul or a
+/// satellite link) by limiting the bitrate of packets traversing through it with a token
+/// bucket, and delaying them by a fixed propagation delay.
+///
+/// Packets are queued until they are due, and are only moved along when the shaper is
+/// polled; use [poll_at](#method.poll_at) to find out when the next one is due. When a
+/// queue is full, the shaper stops transmitting, or receiving from the underlying device.
+#[derive(Debug)]
+pub struct Shaper<D: Device> {
+    inner: D,
+    config: Config,
+    tx: Link,
+    rx: Link,
+}
+
+impl<D: Device> Shaper<D> {
+    /// Create a shaper device, which does not limit the bitrate or delay packets until configured.
+    pub fn new(inner: D) -> Shaper<D> {
+        Shaper {
+            inner,
+            config: Config {
+                burst_size: 0,
+                delay: Duration::ZERO,
+                queue_limit: DEFAULT_QUEUE_LIMIT,
+            },
+            tx: Link::new(),
+            rx: Link::new(),
+        }
+    }
+
+    /// Return the underlying device, consuming the shaper and the packets it queued.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+
+    /// Return the maximum transmission bitrate, in bits per second.
+    pub fn tx_bitrate(&self) -> u64 {
+        self.tx.bucket.bitrate
+    }
+
+    /// Return the maximum reception bitrate, in bits per second.
+    pub fn rx_bitrate(&self) -> u64 {
+        self.rx.bucket.bitrate
+    }
+
+    /// Return the size of the bursts allowed above the bitrate, in octets.
+    pub fn burst_size(&self) -> usize {
+        self.config.burst_size
+    }
+
+    /// Return the propagation delay.
+    pub fn delay(&self) -> Duration {
+        self.config.delay
+    }
+
+    /// Return the maximum number of packets queued in each direction.
+    pub fn queue_limit(&self) -> usize {
+        self.config.queue_limit
+    }
+
+    /// Set the maximum transmission bitrate, in bits per second.
+    ///
+    /// A bitrate of zero does not limit transmission.
+    pub fn set_tx_bitrate(&mut self, bitrate: u64) {
+        self.tx.bucket.bitrate = bitrate
+    }
+
+    /// Set the maximum reception bitrate, in bits per second.
+    ///
+    /// A bitrate of zero does not limit reception.
+    pub fn set_rx_bitrate(&mut self, bitrate: u64) {
+        self.rx.bucket.bitrate = bitrate
+    }
+
+    /// Set the size of the bursts allowed above the bitrate, in octets.
+    pub fn set_burst_size(&mut self, size: usize) {
+        self.config.burst_size = size
+    }
+
+    /// Set the propagation delay.
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.config.delay = delay
+    }
+
+    /// Set the maximum number of packets queued in each direction.
+    ///
+    /// # Panics
+    /// This function panics if the limit is zero.
+    pub fn set_queue_limit(&mut self, limit: usize) {
+        if limit == 0 {
+            panic!("queue limit must not be zero")
+        }
+        self.config.queue_limit = limit
+    }
+
+    /// Return the time at which the next queued packet is due, if any.
+    pub fn poll_at(&self) -> Option<Instant> {
+        let tx_at = self.tx.queue.front().map(|queued| queued.arrival);
+        let rx_at = self.rx.queue.front().map(|queued| queued.arrival);
+        match (tx_at, rx_at) {
+            (Some(tx_at), Some(rx_at)) => Some(tx_at.min(rx_at)),
+            (tx_at, rx_at) => tx_at.or(rx_at),
+        }
+    }
+}
+
+/// Transmit the queued packets that are due through the underlying device.
+fn flush<D: Device>(inner: &mut D, tx: &mut Link, timestamp: Instant) {
+    while tx
+        .queue
+        .front()
+        .map_or(false, |queued| queued.arrival <= timestamp)
+    {
+        let token = match inner.transmit(timestamp) {
+            Some(token) => token,
+            None => return,
+        };
+        if let Some(packet) = tx.pop(timestamp) {
+            phy::TxToken::consume(token, packet.len(), |buffer| {
+                buffer.copy_from_slice(&packet)
+            });
+        }
+    }
+}
+
+impl<D: Device> Device for Shaper<D> {
+    type RxToken<'a>
+        = RxToken
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, D>
+    where
+        Self: 'a;
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        flush(&mut self.inner, &mut self.tx, timestamp);
+
+        while !self.rx.is_full(&self.config) {
+            let (rx_token, _) = match self.inner.receive(timestamp) {
+                Some(tokens) => tokens,
+                None => break,
+            };
+            let packet = phy::RxToken::consume(rx_token, |buffer| buffer.to_vec());
+            self.rx.push(&self.config, timestamp, packet);
+        }
+
+        let buffer = self.rx.pop(timestamp)?;
+        let rx = RxToken { buffer };
+        let tx = TxToken {
+            inner: &mut self.inner,
+            tx: &mut self.tx,
+            config: self.config,
+            timestamp,
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        flush(&mut self.inner, &mut self.tx, timestamp);
+
+        if self.tx.is_full(&self.config) {
+            return None;
+        }
+        Some(TxToken {
+            inner: &mut self.inner,
+            tx: &mut self.tx,
+            config: self.config,
+            timestamp,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken {
+    buffer: Vec<u8>,
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.buffer)
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<'a, D: Device> {
+    inner: &'a mut D,
+    tx: &'a mut Link,
+    config: Config,
+    timestamp: Instant,
+}
+
+impl<'a, D: Device> phy::TxToken for TxToken<'a, D> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buffer = vec![0; len];
+        let result = f(&mut buffer);
+        self.tx.push(&self.config, self.timestamp, buffer);
+        flush(self.inner, self.tx, self.timestamp);
+        result
+    }
+}
@@ -1,8 +1,13 @@
 #![allow(unsafe_code)]
 
 use crate::time::Duration;
+use std::io;
+#[cfg(unix)]
 use std::os::unix::io::RawFd;
-use std::{io, mem, ptr};
+#[cfg(windows)]
+use std::os::windows::io::RawHandle;
+#[cfg(unix)]
+use std::{mem, ptr};
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 #[path = "linux.rs"]
@@ -41,8 +46,19 @@ pub use self::raw_socket::RawSocketDesc;
     any(target_os = "linux", target_os = "android")
 ))]
 pub use self::tuntap_interface::TunTapInterfaceDesc;
+#[cfg(all(feature = "phy-tuntap_interface", windows))]
+pub mod wintun;
+#[cfg(all(feature = "phy-tuntap_interface", windows))]
+pub use self::wintun::TunTapInterfaceDesc;
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn WaitForSingleObject(handle: RawHandle, millis: u32) -> u32;
+}
 
 /// Wait until given file descriptor becomes readable, but no longer than given timeout.
+#[cfg(unix)]
 pub fn wait(fd: RawFd, duration: Option<Duration>) -> io::Result<()> {
     unsafe {
         let mut readfds = {
@@ -134,3 +150,19 @@ fn ifreq_ioctl(
 
     Ok(ifreq.ifr_data)
 }
+
+/// Wait until given event is signaled, but no longer than given timeout.
+#[cfg(windows)]
+pub fn wait(handle: RawHandle, duration: Option<Duration>) -> io::Result<()> {
+    const INFINITE: u32 = 0xffff_ffff;
+    const WAIT_FAILED: u32 = 0xffff_ffff;
+
+    let millis = match duration {
+        Some(duration) => duration.total_millis().min(INFINITE as u64 - 1) as u32,
+        None => INFINITE,
+    };
+    if unsafe { WaitForSingleObject(handle, millis) } == WAIT_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
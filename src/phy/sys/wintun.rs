@@ -0,0 +1,222 @@
+use crate::phy::Medium;
+use std::ffi::c_void;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::os::windows::io::RawHandle;
+use std::ptr;
+use std::string::String;
+use std::vec::Vec;
+
+type Handle = *mut c_void;
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn LoadLibraryW(name: *const u16) -> Handle;
+    fn GetProcAddress(module: Handle, name: *const u8) -> *const c_void;
+    fn FreeLibrary(module: Handle) -> i32;
+}
+
+const ERROR_NO_MORE_ITEMS: i32 = 259;
+const ERROR_BUFFER_OVERFLOW: i32 = 111;
+
+/// The size of the rings shared with the driver, a power of two between 128 KiB and 64 MiB.
+const RING_CAPACITY: u32 = 0x40_0000;
+
+/// The MTU Windows gives to new Wintun adapters.
+const DEFAULT_MTU: usize = 1500;
+
+/// Encode a string as a NUL-terminated UTF-16 string.
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+/// The functions of `wintun.dll`, see <https://www.wintun.net>.
+struct Wintun {
+    module: Handle,
+    create_adapter: unsafe extern "system" fn(*const u16, *const u16, *const c_void) -> Handle,
+    open_adapter: unsafe extern "system" fn(*const u16) -> Handle,
+    close_adapter: unsafe extern "system" fn(Handle),
+    start_session: unsafe extern "system" fn(Handle, u32) -> Handle,
+    end_session: unsafe extern "system" fn(Handle),
+    get_read_wait_event: unsafe extern "system" fn(Handle) -> Handle,
+    receive_packet: unsafe extern "system" fn(Handle, *mut u32) -> *mut u8,
+    release_receive_packet: unsafe extern "system" fn(Handle, *const u8),
+    allocate_send_packet: unsafe extern "system" fn(Handle, u32) -> *mut u8,
+    send_packet: unsafe extern "system" fn(Handle, *const u8),
+}
+
+impl Wintun {
+    fn load() -> io::Result<Wintun> {
+        let module = unsafe { LoadLibraryW(wide("wintun.dll").as_ptr()) };
+        if module.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        macro_rules! function {
+            ($name:literal) => {{
+                let address = unsafe { GetProcAddress(module, concat!($name, "\0").as_ptr()) };
+                if address.is_null() {
+                    let err = io::Error::last_os_error();
+                    unsafe { FreeLibrary(module) };
+                    return Err(err);
+                }
+                unsafe { mem::transmute(address) }
+            }};
+        }
+
+        Ok(Wintun {
+            module,
+            create_adapter: function!("WintunCreateAdapter"),
+            open_adapter: function!("WintunOpenAdapter"),
+            close_adapter: function!("WintunCloseAdapter"),
+            start_session: function!("WintunStartSession"),
+            end_session: function!("WintunEndSession"),
+            get_read_wait_event: function!("WintunGetReadWaitEvent"),
+            receive_packet: function!("WintunReceivePacket"),
+            release_receive_packet: function!("WintunReleaseReceivePacket"),
+            allocate_send_packet: function!("WintunAllocateSendPacket"),
+            send_packet: function!("WintunSendPacket"),
+        })
+    }
+}
+
+impl Drop for Wintun {
+    fn drop(&mut self) {
+        unsafe { FreeLibrary(self.module) };
+    }
+}
+
+/// A Wintun adapter, standing in for the TUN interfaces of other systems.
+///
+/// Wintun only carries IP packets, so there is no equivalent of TAP interfaces.
+pub struct TunTapInterfaceDesc {
+    wintun: Wintun,
+    name: Vec<u16>,
+    adapter: Handle,
+    session: Handle,
+}
+
+impl fmt::Debug for TunTapInterfaceDesc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TunTapInterfaceDesc")
+            .field(
+                "name",
+                &String::from_utf16_lossy(&self.name[..self.name.len() - 1]),
+            )
+            .field("adapter", &self.adapter)
+            .field("session", &self.session)
+            .finish()
+    }
+}
+
+impl TunTapInterfaceDesc {
+    pub fn new(name: &str, medium: Medium) -> io::Result<TunTapInterfaceDesc> {
+        #[allow(unreachable_patterns)]
+        match medium {
+            #[cfg(feature = "medium-ip")]
+            Medium::Ip => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Wintun adapters only support Medium::Ip",
+                ))
+            }
+        }
+
+        Ok(TunTapInterfaceDesc {
+            wintun: Wintun::load()?,
+            name: wide(name),
+            adapter: ptr::null_mut(),
+            session: ptr::null_mut(),
+        })
+    }
+
+    /// Open the adapter, or create it if it does not exist, and start a session on it.
+    ///
+    /// Creating an adapter requires administrator privileges.
+    pub fn attach_interface(&mut self) -> io::Result<()> {
+        unsafe {
+            let mut adapter = (self.wintun.open_adapter)(self.name.as_ptr());
+            if adapter.is_null() {
+                let tunnel_type = wide("smoltcp");
+                adapter = (self.wintun.create_adapter)(
+                    self.name.as_ptr(),
+                    tunnel_type.as_ptr(),
+                    ptr::null(),
+                );
+            }
+            if adapter.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            self.adapter = adapter;
+
+            let session = (self.wintun.start_session)(adapter, RING_CAPACITY);
+            if session.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            self.session = session;
+        }
+        Ok(())
+    }
+
+    /// Return the MTU of the adapter.
+    ///
+    /// Wintun does not report it, so this is the MTU Windows gives to new adapters.
+    pub fn interface_mtu(&mut self) -> io::Result<usize> {
+        Ok(DEFAULT_MTU)
+    }
+
+    /// Return the event signaled when packets are ready to be received.
+    pub fn read_event(&self) -> RawHandle {
+        unsafe { (self.wintun.get_read_wait_event)(self.session) as RawHandle }
+    }
+
+    pub fn recv(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        unsafe {
+            let mut size = 0;
+            let packet = (self.wintun.receive_packet)(self.session, &mut size);
+            if packet.is_null() {
+                let err = io::Error::last_os_error();
+                return match err.raw_os_error() {
+                    Some(ERROR_NO_MORE_ITEMS) => Err(io::ErrorKind::WouldBlock.into()),
+                    _ => Err(err),
+                };
+            }
+            // Packets longer than the buffer are truncated, as they are by a TUN interface.
+            let size = (size as usize).min(buffer.len());
+            ptr::copy_nonoverlapping(packet, buffer.as_mut_ptr(), size);
+            (self.wintun.release_receive_packet)(self.session, packet);
+            Ok(size)
+        }
+    }
+
+    pub fn send(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        unsafe {
+            let packet = (self.wintun.allocate_send_packet)(self.session, buffer.len() as u32);
+            if packet.is_null() {
+                let err = io::Error::last_os_error();
+                return match err.raw_os_error() {
+                    Some(ERROR_BUFFER_OVERFLOW) => Err(io::ErrorKind::WouldBlock.into()),
+                    _ => Err(err),
+                };
+            }
+            ptr::copy_nonoverlapping(buffer.as_ptr(), packet, buffer.len());
+            (self.wintun.send_packet)(self.session, packet);
+            Ok(buffer.len())
+        }
+    }
+}
+
+impl Drop for TunTapInterfaceDesc {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.session.is_null() {
+                (self.wintun.end_session)(self.session);
+            }
+            if !self.adapter.is_null() {
+                (self.wintun.close_adapter)(self.adapter);
+            }
+        }
+    }
+}
@@ -1,8 +1,8 @@
 use core::fmt;
 
-use crate::phy::{self, Device, DeviceCapabilities, Medium};
+use crate::phy::{self, Device, DeviceCapabilities, HardwareTimestamp, Medium};
 use crate::time::Instant;
-use crate::wire::pretty_print::{PrettyIndent, PrettyPrint};
+use crate::wire::IpVersion;
 
 /// A tracer device.
 ///
@@ -90,6 +90,51 @@ impl<D: Device> Device for Tracer<D> {
             timestamp,
         })
     }
+
+    fn receive_batch<'a, F>(&'a mut self, timestamp: Instant, max: usize, mut f: F) -> usize
+    where
+        F: FnMut(Self::RxToken<'a>, Self::TxToken<'a>),
+    {
+        let &mut Self {
+            ref mut inner,
+            writer,
+        } = self;
+        let medium = inner.capabilities().medium;
+        inner.receive_batch(timestamp, max, |rx_token, tx_token| {
+            let rx = RxToken {
+                token: rx_token,
+                writer,
+                medium,
+                timestamp,
+            };
+            let tx = TxToken {
+                token: tx_token,
+                writer,
+                medium,
+                timestamp,
+            };
+            f(rx, tx)
+        })
+    }
+
+    fn transmit_batch<'a, F>(&'a mut self, timestamp: Instant, max: usize, mut f: F) -> usize
+    where
+        F: FnMut(Self::TxToken<'a>) -> bool,
+    {
+        let &mut Self {
+            ref mut inner,
+            writer,
+        } = self;
+        let medium = inner.capabilities().medium;
+        inner.transmit_batch(timestamp, max, |tx_token| {
+            f(TxToken {
+                token: tx_token,
+                medium,
+                writer,
+                timestamp,
+            })
+        })
+    }
 }
 
 #[doc(hidden)]
@@ -117,6 +162,10 @@ impl<Rx: phy::RxToken> phy::RxToken for RxToken<Rx> {
             f(buffer)
         })
     }
+
+    fn hardware_timestamp(&self) -> Option<HardwareTimestamp> {
+        self.token.hardware_timestamp()
+    }
 }
 
 #[doc(hidden)]
@@ -145,6 +194,28 @@ impl<Tx: phy::TxToken> phy::TxToken for TxToken<Tx> {
             result
         })
     }
+
+    fn consume_timestamped<R, F>(self, len: usize, f: F) -> (R, Option<HardwareTimestamp>)
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.token.consume_timestamped(len, |buffer| {
+            let result = f(buffer);
+            (self.writer)(
+                self.timestamp,
+                Packet {
+                    buffer,
+                    medium: self.medium,
+                    prefix: "-> ",
+                },
+            );
+            result
+        })
+    }
+
+    fn set_ip_version(&mut self, version: IpVersion) {
+        self.token.set_ip_version(version)
+    }
 }
 
 pub struct Packet<'a> {
@@ -155,36 +226,9 @@ pub struct Packet<'a> {
 
 impl<'a> fmt::Display for Packet<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut indent = PrettyIndent::new(self.prefix);
-        match self.medium {
-            #[cfg(feature = "medium-ethernet")]
-            Medium::Ethernet => crate::wire::EthernetFrame::<&'static [u8]>::pretty_print(
-                &self.buffer,
-                f,
-                &mut indent,
-            ),
-            #[cfg(feature = "medium-ip")]
-            Medium::Ip => match crate::wire::IpVersion::of_packet(self.buffer) {
-                #[cfg(feature = "proto-ipv4")]
-                Ok(crate::wire::IpVersion::Ipv4) => {
-                    crate::wire::Ipv4Packet::<&'static [u8]>::pretty_print(
-                        &self.buffer,
-                        f,
-                        &mut indent,
-                    )
-                }
-                #[cfg(feature = "proto-ipv6")]
-                Ok(crate::wire::IpVersion::Ipv6) => {
-                    crate::wire::Ipv6Packet::<&'static [u8]>::pretty_print(
-                        &self.buffer,
-                        f,
-                        &mut indent,
-                    )
-                }
-                _ => f.write_str("unrecognized IP version"),
-            },
-            #[cfg(feature = "medium-ieee802154")]
-            Medium::Ieee802154 => Ok(()), // XXX
-        }
+        crate::wire::dissect(self.medium, self.buffer)
+            .with_prefix(self.prefix)
+            .without_hex_dump()
+            .fmt(f)
     }
 }
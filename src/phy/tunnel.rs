@@ -0,0 +1,510 @@
+use alloc::vec::Vec;
+
+use crate::phy::{
+    self, ChecksumCapabilities, Device, DeviceCapabilities, HardwareTimestamp, Medium,
+};
+use crate::time::Instant;
+use crate::wire::{GrePacket, GreProtocol, GreRepr, IpAddress, IpProtocol};
+#[cfg(feature = "proto-ipv4")]
+use crate::wire::{Ipv4Address, Ipv4Packet, Ipv4Repr};
+#[cfg(feature = "proto-ipv6")]
+use crate::wire::{Ipv6Address, Ipv6Packet, Ipv6Repr};
+
+/// The default hop limit of the outer header.
+const DEFAULT_HOP_LIMIT: u8 = 64;
+
+/// The encapsulation used by a [Tunnel](struct.Tunnel.html).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TunnelMode {
+    /// IP-in-IP encapsulation, as IP protocol 4 for IPv4 packets (RFC 2003), and as
+    /// IP protocol 41 for IPv6 packets (RFC 4213, also known as 6in4).
+    Ip,
+    /// Generic Routing Encapsulation, as IP protocol 47 (RFC 2784 and RFC 2890).
+    Gre {
+        /// The key sent in every packet, and required in every packet received.
+        key: Option<u32>,
+        /// Whether to number the packets sent, and drop the packets received out of order.
+        sequence: bool,
+    },
+}
+
+/// The outer addresses of a tunnel.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(super) enum Endpoints {
+    #[cfg(feature = "proto-ipv4")]
+    Ipv4 {
+        local: Ipv4Address,
+        remote: Ipv4Address,
+    },
+    #[cfg(feature = "proto-ipv6")]
+    Ipv6 {
+        local: Ipv6Address,
+        remote: Ipv6Address,
+    },
+}
+
+impl Endpoints {
+    /// Create the outer addresses of a tunnel.
+    ///
+    /// # Panics
+    /// This function panics if the addresses are not of the same family.
+    pub(super) fn new(local: IpAddress, remote: IpAddress) -> Endpoints {
+        match (local, remote) {
+            #[cfg(feature = "proto-ipv4")]
+            (IpAddress::Ipv4(local), IpAddress::Ipv4(remote)) => Endpoints::Ipv4 { local, remote },
+            #[cfg(feature = "proto-ipv6")]
+            (IpAddress::Ipv6(local), IpAddress::Ipv6(remote)) => Endpoints::Ipv6 { local, remote },
+            #[allow(unreachable_patterns)]
+            _ => panic!("tunnel addresses must be of the same family"),
+        }
+    }
+
+    /// Return the local outer address.
+    pub(super) fn local(&self) -> IpAddress {
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            Endpoints::Ipv4 { local, .. } => IpAddress::Ipv4(local),
+            #[cfg(feature = "proto-ipv6")]
+            Endpoints::Ipv6 { local, .. } => IpAddress::Ipv6(local),
+        }
+    }
+
+    /// Return the remote outer address.
+    pub(super) fn remote(&self) -> IpAddress {
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            Endpoints::Ipv4 { remote, .. } => IpAddress::Ipv4(remote),
+            #[cfg(feature = "proto-ipv6")]
+            Endpoints::Ipv6 { remote, .. } => IpAddress::Ipv6(remote),
+        }
+    }
+
+    /// Return the length of the outer IP header.
+    pub(super) fn header_len(&self) -> usize {
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            Endpoints::Ipv4 { .. } => crate::wire::IPV4_HEADER_LEN,
+            #[cfg(feature = "proto-ipv6")]
+            Endpoints::Ipv6 { .. } => crate::wire::IPV6_HEADER_LEN,
+        }
+    }
+
+    /// Return the protocol and the payload of a packet sent from the remote address to
+    /// the local address.
+    pub(super) fn parse<'p>(
+        &self,
+        checksum_caps: &ChecksumCapabilities,
+        packet: &'p [u8],
+    ) -> Option<(IpProtocol, &'p [u8])> {
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            Endpoints::Ipv4 { local, remote } => {
+                let packet = Ipv4Packet::new_checked(packet).ok()?;
+                let repr = Ipv4Repr::parse(&packet, checksum_caps).ok()?;
+                // Fragmented outer packets are not reassembled.
+                if repr.src_addr != remote
+                    || repr.dst_addr != local
+                    || packet.more_frags()
+                    || packet.frag_offset() != 0
+                {
+                    return None;
+                }
+                Some((repr.next_header, packet.payload()))
+            }
+            #[cfg(feature = "proto-ipv6")]
+            Endpoints::Ipv6 { local, remote } => {
+                let packet = Ipv6Packet::new_checked(packet).ok()?;
+                let repr = Ipv6Repr::parse(&packet).ok()?;
+                if repr.src_addr != remote || repr.dst_addr != local {
+                    return None;
+                }
+                Some((repr.next_header, packet.payload()))
+            }
+        }
+    }
+
+    /// Emit the header of a packet sent from the local address to the remote address,
+    /// carrying the rest of `buffer`, and return that payload.
+    pub(super) fn emit<'p>(
+        &self,
+        checksum_caps: &ChecksumCapabilities,
+        next_header: IpProtocol,
+        hop_limit: u8,
+        buffer: &'p mut [u8],
+    ) -> &'p mut [u8] {
+        let payload_len = buffer.len() - self.header_len();
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            Endpoints::Ipv4 { local, remote } => {
+                let repr = Ipv4Repr {
+                    src_addr: local,
+                    dst_addr: remote,
+                    next_header,
+                    payload_len,
+                    hop_limit,
+                    dscp: 0,
+                };
+                let mut packet = Ipv4Packet::new_unchecked(buffer);
+                repr.emit(&mut packet, checksum_caps);
+                &mut packet.into_inner()[repr.buffer_len()..]
+            }
+            #[cfg(feature = "proto-ipv6")]
+            Endpoints::Ipv6 { local, remote } => {
+                let repr = Ipv6Repr {
+                    src_addr: local,
+                    dst_addr: remote,
+                    next_header,
+                    payload_len,
+                    hop_limit,
+                    traffic_class: 0,
+                    flow_label: 0,
+                };
+                let mut packet = Ipv6Packet::new_unchecked(buffer);
+                repr.emit(&mut packet);
+                &mut packet.into_inner()[repr.buffer_len()..]
+            }
+        }
+    }
+}
+
+/// Return the capabilities of a device encapsulating its packets in those of a lower
+/// device, with `overhead` octets of headers added to each.
+pub(super) fn inner_capabilities(lower: DeviceCapabilities, overhead: usize) -> DeviceCapabilities {
+    // The lower device only offloads the checksums of the outer packets, and does not
+    // know how to split the inner ones.
+    DeviceCapabilities {
+        max_transmission_unit: lower.max_transmission_unit.saturating_sub(overhead),
+        max_segmentation_size: None,
+        checksum: ChecksumCapabilities::default(),
+        ..lower
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Config {
+    endpoints: Endpoints,
+    mode: TunnelMode,
+    hop_limit: u8,
+}
+
+impl Config {
+    /// Return the length of the headers prepended to the inner packets.
+    fn header_len(&self) -> usize {
+        let ip_len = self.endpoints.header_len();
+        match self.mode {
+            TunnelMode::Ip => ip_len,
+            TunnelMode::Gre { key, sequence } => {
+                let gre_repr = GreRepr {
+                    protocol: GreProtocol::Ipv4,
+                    key,
+                    sequence_number: sequence.then_some(0),
+                };
+                ip_len + gre_repr.buffer_len()
+            }
+        }
+    }
+
+    /// Return the inner packet of a packet received from the remote end of the tunnel.
+    fn decapsulate<'p>(
+        &self,
+        checksum_caps: &ChecksumCapabilities,
+        rx_sequence: &mut Option<u32>,
+        packet: &'p [u8],
+    ) -> Option<&'p [u8]> {
+        let (next_header, payload) = self.endpoints.parse(checksum_caps, packet)?;
+        let (version, payload) = match self.mode {
+            TunnelMode::Ip => match next_header {
+                IpProtocol::IpIp => (4, payload),
+                IpProtocol::Ipv6 => (6, payload),
+                _ => return None,
+            },
+            TunnelMode::Gre { key, sequence } => {
+                if next_header != IpProtocol::Gre {
+                    return None;
+                }
+                let packet = GrePacket::new_checked(payload).ok()?;
+                let repr = GreRepr::parse(&packet).ok()?;
+                let version = match repr.protocol {
+                    GreProtocol::Ipv4 => 4,
+                    GreProtocol::Ipv6 => 6,
+                    GreProtocol::Unknown(_) => return None,
+                };
+                if repr.key != key {
+                    return None;
+                }
+                if sequence {
+                    let sequence_number = repr.sequence_number?;
+                    // Sequence numbers are compared with serial number arithmetic (RFC 1982).
+                    if let Some(last) = *rx_sequence {
+                        if sequence_number.wrapping_sub(last) as i32 <= 0 {
+                            net_debug!("tunnel: dropping out of order packet");
+                            return None;
+                        }
+                    }
+                    *rx_sequence = Some(sequence_number);
+                }
+                (version, packet.payload())
+            }
+        };
+
+        match payload.first() {
+            Some(octet) if octet >> 4 == version => Some(payload),
+            _ => None,
+        }
+    }
+
+    /// Fill in the headers in front of the inner packet at the end of `buffer`.
+    fn encapsulate(
+        &self,
+        checksum_caps: &ChecksumCapabilities,
+        tx_sequence: &mut u32,
+        buffer: &mut [u8],
+    ) {
+        let header_len = self.header_len();
+        let (ip_protocol, gre_protocol) = match buffer.get(header_len).map(|octet| octet >> 4) {
+            Some(6) => (IpProtocol::Ipv6, GreProtocol::Ipv6),
+            _ => (IpProtocol::IpIp, GreProtocol::Ipv4),
+        };
+        let (next_header, gre_repr) = match self.mode {
+            TunnelMode::Ip => (ip_protocol, None),
+            TunnelMode::Gre { key, sequence } => {
+                let sequence_number = sequence.then(|| {
+                    let sequence_number = *tx_sequence;
+                    *tx_sequence = sequence_number.wrapping_add(1);
+                    sequence_number
+                });
+                let gre_repr = GreRepr {
+                    protocol: gre_protocol,
+                    key,
+                    sequence_number,
+                };
+                (IpProtocol::Gre, Some(gre_repr))
+            }
+        };
+
+        let payload = self
+            .endpoints
+            .emit(checksum_caps, next_header, self.hop_limit, buffer);
+        if let Some(gre_repr) = gre_repr {
+            gre_repr.emit(&mut GrePacket::new_unchecked(payload));
+        }
+    }
+}
+
+/// A static IP tunnel device.
+///
+/// A tunnel is a device that encapsulates the IP packets of the interface using it into
+/// the IP packets it exchanges with a configured remote endpoint through the underlying
+/// device, such as the static IPv6 over IPv4 tunnels offered by tunnel brokers. Both IPv4
+/// and IPv6 packets are carried, either directly in the outer packets or within GRE.
+///
+/// The underlying device must have the IP medium. Received packets that do not come from
+/// the remote endpoint, or that do not match the encapsulation, are dropped.
+#[derive(Debug)]
+pub struct Tunnel<D: Device> {
+    lower: D,
+    config: Config,
+    rx_buffer: Vec<u8>,
+    rx_sequence: Option<u32>,
+    tx_sequence: u32,
+}
+
+impl<D: Device> Tunnel<D> {
+    /// Create a tunnel device between the `local` and `remote` outer addresses.
+    ///
+    /// # Panics
+    /// This function panics if the underlying device does not have the IP medium, or if
+    /// the addresses are not of the same family.
+    pub fn new(lower: D, local: IpAddress, remote: IpAddress, mode: TunnelMode) -> Tunnel<D> {
+        let caps = lower.capabilities();
+        if caps.medium != Medium::Ip {
+            panic!("tunnel requires a device with the IP medium")
+        }
+        Tunnel {
+            lower,
+            config: Config {
+                endpoints: Endpoints::new(local, remote),
+                mode,
+                hop_limit: DEFAULT_HOP_LIMIT,
+            },
+            rx_buffer: Vec::with_capacity(caps.max_transmission_unit),
+            rx_sequence: None,
+            tx_sequence: 0,
+        }
+    }
+
+    /// Get a reference to the underlying device.
+    ///
+    /// Even if the device offers reading through a standard reference, it is inadvisable to
+    /// directly read from the device as doing so will circumvent the tunnel.
+    pub fn get_ref(&self) -> &D {
+        &self.lower
+    }
+
+    /// Get a mutable reference to the underlying device.
+    ///
+    /// It is inadvisable to directly read from the device as doing so will circumvent the tunnel.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.lower
+    }
+
+    /// Return the underlying device, consuming the tunnel.
+    pub fn into_inner(self) -> D {
+        self.lower
+    }
+
+    /// Return the encapsulation.
+    pub fn mode(&self) -> TunnelMode {
+        self.config.mode
+    }
+
+    /// Return the hop limit of the outer packets.
+    pub fn hop_limit(&self) -> u8 {
+        self.config.hop_limit
+    }
+
+    /// Set the hop limit of the outer packets.
+    ///
+    /// # Panics
+    /// This function panics if the hop limit is zero.
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        if hop_limit == 0 {
+            panic!("hop limit must not be zero")
+        }
+        self.config.hop_limit = hop_limit
+    }
+}
+
+impl<D: Device> Device for Tunnel<D> {
+    type RxToken<'a>
+        = RxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, D::TxToken<'a>>
+    where
+        Self: 'a;
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        inner_capabilities(self.lower.capabilities(), self.config.header_len())
+    }
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let checksum_caps = self.lower.capabilities().checksum;
+        let Tunnel {
+            lower,
+            config,
+            rx_buffer,
+            rx_sequence,
+            tx_sequence,
+        } = self;
+
+        let (rx_token, tx_token) = lower.receive(timestamp)?;
+        let hardware_timestamp = phy::RxToken::hardware_timestamp(&rx_token);
+        let accepted = phy::RxToken::consume(rx_token, |packet| {
+            match config.decapsulate(&checksum_caps, rx_sequence, packet) {
+                Some(payload) => {
+                    rx_buffer.clear();
+                    rx_buffer.extend_from_slice(payload);
+                    true
+                }
+                None => false,
+            }
+        });
+        if !accepted {
+            net_trace!("tunnel: dropping packet not matching the tunnel");
+            return None;
+        }
+
+        let rx = RxToken {
+            buffer: rx_buffer,
+            hardware_timestamp,
+        };
+        let tx = TxToken {
+            lower: tx_token,
+            config: *config,
+            checksum_caps,
+            tx_sequence,
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        let checksum_caps = self.lower.capabilities().checksum;
+        let lower = self.lower.transmit(timestamp)?;
+        Some(TxToken {
+            lower,
+            config: self.config,
+            checksum_caps,
+            tx_sequence: &mut self.tx_sequence,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken<'a> {
+    buffer: &'a [u8],
+    hardware_timestamp: Option<HardwareTimestamp>,
+}
+
+impl<'a> phy::RxToken for RxToken<'a> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(self.buffer)
+    }
+
+    fn hardware_timestamp(&self) -> Option<HardwareTimestamp> {
+        self.hardware_timestamp
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<'a, Tx: phy::TxToken> {
+    lower: Tx,
+    config: Config,
+    checksum_caps: ChecksumCapabilities,
+    tx_sequence: &'a mut u32,
+}
+
+impl<'a, Tx: phy::TxToken> phy::TxToken for TxToken<'a, Tx> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let TxToken {
+            lower,
+            config,
+            checksum_caps,
+            tx_sequence,
+        } = self;
+        let header_len = config.header_len();
+        lower.consume(header_len + len, |buffer| {
+            let result = f(&mut buffer[header_len..]);
+            config.encapsulate(&checksum_caps, tx_sequence, buffer);
+            result
+        })
+    }
+
+    fn consume_timestamped<R, F>(self, len: usize, f: F) -> (R, Option<HardwareTimestamp>)
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let TxToken {
+            lower,
+            config,
+            checksum_caps,
+            tx_sequence,
+        } = self;
+        let header_len = config.header_len();
+        lower.consume_timestamped(header_len + len, |buffer| {
+            let result = f(&mut buffer[header_len..]);
+            config.encapsulate(&checksum_caps, tx_sequence, buffer);
+            result
+        })
+    }
+}
@@ -1,6 +1,9 @@
 use std::cell::RefCell;
 use std::io;
+#[cfg(unix)]
 use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawHandle, RawHandle};
 use std::rc::Rc;
 use std::vec::Vec;
 
@@ -15,18 +18,32 @@ pub struct TunTapInterface {
     medium: Medium,
 }
 
+#[cfg(unix)]
 impl AsRawFd for TunTapInterface {
     fn as_raw_fd(&self) -> RawFd {
         self.lower.borrow().as_raw_fd()
     }
 }
 
+/// The handle is the event signaled when packets are ready to be received, to be
+/// passed to [`wait`](crate::phy::wait).
+#[cfg(windows)]
+impl AsRawHandle for TunTapInterface {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.lower.borrow().read_event()
+    }
+}
+
 impl TunTapInterface {
     /// Attaches to a TUN/TAP interface called `name`, or creates it if it does not exist.
     ///
     /// If `name` is a persistent interface configured with UID of the current user,
     /// no special privileges are needed. Otherwise, this requires superuser privileges
     /// or a corresponding capability set on the executable.
+    ///
+    /// On Windows, `name` is a Wintun adapter, and creating it requires administrator
+    /// privileges. `wintun.dll` must be in the DLL search path, and `medium` must be
+    /// `Medium::Ip`.
     pub fn new(name: &str, medium: Medium) -> io::Result<TunTapInterface> {
         let mut lower = sys::TunTapInterfaceDesc::new(name, medium)?;
         lower.attach_interface()?;
@@ -0,0 +1,312 @@
+use alloc::vec::Vec;
+
+use super::tunnel::{inner_capabilities, Endpoints};
+use crate::phy::{
+    self, ChecksumCapabilities, Device, DeviceCapabilities, HardwareTimestamp, Medium,
+};
+use crate::time::Instant;
+use crate::wire::{
+    IpAddress, IpProtocol, UdpPacket, UdpRepr, VxlanPacket, VxlanRepr, UDP_HEADER_LEN,
+    VXLAN_HEADER_LEN, VXLAN_MAX_VNI, VXLAN_PORT,
+};
+
+/// The default hop limit of the outer header.
+const DEFAULT_HOP_LIMIT: u8 = 64;
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Config {
+    endpoints: Endpoints,
+    vni: u32,
+    hop_limit: u8,
+}
+
+impl Config {
+    /// Return the length of the headers prepended to the inner frames.
+    fn header_len(&self) -> usize {
+        self.endpoints.header_len() + UDP_HEADER_LEN + VXLAN_HEADER_LEN
+    }
+
+    /// Return the inner frame of a packet received from the remote VTEP.
+    fn decapsulate<'p>(
+        &self,
+        checksum_caps: &ChecksumCapabilities,
+        packet: &'p [u8],
+    ) -> Option<&'p [u8]> {
+        let (next_header, payload) = self.endpoints.parse(checksum_caps, packet)?;
+        if next_header != IpProtocol::Udp {
+            return None;
+        }
+
+        let packet = UdpPacket::new_checked(payload).ok()?;
+        let src_addr = self.endpoints.remote();
+        let dst_addr = self.endpoints.local();
+        let repr = UdpRepr::parse(&packet, &src_addr, &dst_addr, checksum_caps).ok()?;
+        if repr.dst_port != VXLAN_PORT {
+            return None;
+        }
+
+        let packet = VxlanPacket::new_checked(packet.payload()).ok()?;
+        let repr = VxlanRepr::parse(&packet).ok()?;
+        if repr.vni != self.vni {
+            net_trace!("vxlan: dropping frame for vni {}", repr.vni);
+            return None;
+        }
+        Some(packet.payload())
+    }
+
+    /// Fill in the headers in front of the inner frame at the end of `buffer`.
+    fn encapsulate(&self, checksum_caps: &ChecksumCapabilities, buffer: &mut [u8]) {
+        let header_len = self.header_len();
+        let src_port = source_port(&buffer[header_len..]);
+        let payload_len = buffer.len() - header_len + VXLAN_HEADER_LEN;
+
+        let payload = self
+            .endpoints
+            .emit(checksum_caps, IpProtocol::Udp, self.hop_limit, buffer);
+        let repr = UdpRepr {
+            src_port,
+            dst_port: VXLAN_PORT,
+        };
+        repr.emit(
+            &mut UdpPacket::new_unchecked(payload),
+            &self.endpoints.local(),
+            &self.endpoints.remote(),
+            payload_len,
+            |payload| {
+                let repr = VxlanRepr { vni: self.vni };
+                repr.emit(&mut VxlanPacket::new_unchecked(payload))
+            },
+            checksum_caps,
+        );
+    }
+}
+
+/// Return the UDP source port of an inner frame.
+///
+/// As recommended by RFC 7348, the port is a hash of the inner Ethernet addresses in the
+/// dynamic port range, so that the outer packets of a flow are balanced consistently by
+/// the routers on the way.
+fn source_port(frame: &[u8]) -> u16 {
+    let addresses = &frame[..frame.len().min(12)];
+    let hash = addresses
+        .iter()
+        .fold(0u16, |hash, &octet| hash.rotate_left(5) ^ octet as u16);
+    0xc000 | (hash & 0x3fff)
+}
+
+/// A Virtual eXtensible Local Area Network device.
+///
+/// A VXLAN device is a device that encapsulates the Ethernet frames of the interface
+/// using it into the UDP packets it exchanges through the underlying device with a
+/// configured remote VXLAN tunnel end point (VTEP), tagged with a VXLAN network identifier
+/// (VNI). This lets the interface join the overlay networks of test labs and clouds.
+///
+/// The underlying device must have the IP medium. Received packets that do not come from
+/// the remote VTEP, or that are tagged with another VNI, are dropped.
+#[derive(Debug)]
+pub struct Vxlan<D: Device> {
+    lower: D,
+    config: Config,
+    rx_buffer: Vec<u8>,
+}
+
+impl<D: Device> Vxlan<D> {
+    /// Create a VXLAN device for the network `vni`, between the `local` and `remote` VTEP
+    /// addresses.
+    ///
+    /// # Panics
+    /// This function panics if the underlying device does not have the IP medium, if the
+    /// addresses are not of the same family, or if the VNI does not fit in 24 bits.
+    pub fn new(lower: D, local: IpAddress, remote: IpAddress, vni: u32) -> Vxlan<D> {
+        let caps = lower.capabilities();
+        if caps.medium != Medium::Ip {
+            panic!("vxlan requires a device with the IP medium")
+        }
+        if vni > VXLAN_MAX_VNI {
+            panic!("vni must fit in 24 bits")
+        }
+        Vxlan {
+            lower,
+            config: Config {
+                endpoints: Endpoints::new(local, remote),
+                vni,
+                hop_limit: DEFAULT_HOP_LIMIT,
+            },
+            rx_buffer: Vec::with_capacity(caps.max_transmission_unit),
+        }
+    }
+
+    /// Get a reference to the underlying device.
+    ///
+    /// Even if the device offers reading through a standard reference, it is inadvisable to
+    /// directly read from the device as doing so will circumvent the encapsulation.
+    pub fn get_ref(&self) -> &D {
+        &self.lower
+    }
+
+    /// Get a mutable reference to the underlying device.
+    ///
+    /// It is inadvisable to directly read from the device as doing so will circumvent the
+    /// encapsulation.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.lower
+    }
+
+    /// Return the underlying device, consuming the VXLAN device.
+    pub fn into_inner(self) -> D {
+        self.lower
+    }
+
+    /// Return the VXLAN network identifier.
+    pub fn vni(&self) -> u32 {
+        self.config.vni
+    }
+
+    /// Return the address of the remote VTEP.
+    pub fn remote(&self) -> IpAddress {
+        self.config.endpoints.remote()
+    }
+
+    /// Return the hop limit of the outer packets.
+    pub fn hop_limit(&self) -> u8 {
+        self.config.hop_limit
+    }
+
+    /// Set the hop limit of the outer packets.
+    ///
+    /// # Panics
+    /// This function panics if the hop limit is zero.
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        if hop_limit == 0 {
+            panic!("hop limit must not be zero")
+        }
+        self.config.hop_limit = hop_limit
+    }
+}
+
+impl<D: Device> Device for Vxlan<D> {
+    type RxToken<'a>
+        = RxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<D::TxToken<'a>>
+    where
+        Self: 'a;
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            medium: Medium::Ethernet,
+            ..inner_capabilities(self.lower.capabilities(), self.config.header_len())
+        }
+    }
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let checksum_caps = self.lower.capabilities().checksum;
+        let Vxlan {
+            lower,
+            config,
+            rx_buffer,
+        } = self;
+
+        let (rx_token, tx_token) = lower.receive(timestamp)?;
+        let hardware_timestamp = phy::RxToken::hardware_timestamp(&rx_token);
+        let accepted = phy::RxToken::consume(rx_token, |packet| {
+            match config.decapsulate(&checksum_caps, packet) {
+                Some(frame) => {
+                    rx_buffer.clear();
+                    rx_buffer.extend_from_slice(frame);
+                    true
+                }
+                None => false,
+            }
+        });
+        if !accepted {
+            return None;
+        }
+
+        let rx = RxToken {
+            buffer: rx_buffer,
+            hardware_timestamp,
+        };
+        let tx = TxToken {
+            lower: tx_token,
+            config: *config,
+            checksum_caps,
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        let checksum_caps = self.lower.capabilities().checksum;
+        let lower = self.lower.transmit(timestamp)?;
+        Some(TxToken {
+            lower,
+            config: self.config,
+            checksum_caps,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken<'a> {
+    buffer: &'a [u8],
+    hardware_timestamp: Option<HardwareTimestamp>,
+}
+
+impl<'a> phy::RxToken for RxToken<'a> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(self.buffer)
+    }
+
+    fn hardware_timestamp(&self) -> Option<HardwareTimestamp> {
+        self.hardware_timestamp
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<Tx: phy::TxToken> {
+    lower: Tx,
+    config: Config,
+    checksum_caps: ChecksumCapabilities,
+}
+
+impl<Tx: phy::TxToken> phy::TxToken for TxToken<Tx> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let TxToken {
+            lower,
+            config,
+            checksum_caps,
+        } = self;
+        let header_len = config.header_len();
+        lower.consume(header_len + len, |buffer| {
+            let result = f(&mut buffer[header_len..]);
+            config.encapsulate(&checksum_caps, buffer);
+            result
+        })
+    }
+
+    fn consume_timestamped<R, F>(self, len: usize, f: F) -> (R, Option<HardwareTimestamp>)
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let TxToken {
+            lower,
+            config,
+            checksum_caps,
+        } = self;
+        let header_len = config.header_len();
+        lower.consume_timestamped(header_len + len, |buffer| {
+            let result = f(&mut buffer[header_len..]);
+            config.encapsulate(&checksum_caps, buffer);
+            result
+        })
+    }
+}
@@ -0,0 +1,905 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use super::tunnel::{inner_capabilities, Endpoints};
+use crate::phy::{
+    self, ChecksumCapabilities, Device, DeviceCapabilities, HardwareTimestamp, Medium,
+};
+use crate::time::{Duration, Instant};
+#[cfg(feature = "proto-ipv4")]
+use crate::wire::Ipv4Packet;
+#[cfg(feature = "proto-ipv6")]
+use crate::wire::Ipv6Packet;
+use crate::wire::{
+    IpAddress, IpCidr, IpEndpoint, IpProtocol, IpVersion, UdpPacket, UdpRepr, WireguardMessageType,
+    WireguardPacket, UDP_HEADER_LEN, WIREGUARD_HANDSHAKE_INITIATION_LEN,
+    WIREGUARD_HANDSHAKE_RESPONSE_LEN, WIREGUARD_TAG_LEN, WIREGUARD_TRANSPORT_HEADER_LEN,
+};
+
+/// The default hop limit of the outer header.
+const DEFAULT_HOP_LIMIT: u8 = 64;
+
+// The timers and limits of the protocol, see section 6.1 of the WireGuard paper.
+const REKEY_AFTER_MESSAGES: u64 = 1 << 60;
+const REJECT_AFTER_MESSAGES: u64 = u64::MAX - (1 << 13);
+const REKEY_AFTER_TIME: Duration = Duration::from_secs(120);
+const REJECT_AFTER_TIME: Duration = Duration::from_secs(180);
+const REKEY_ATTEMPT_TIME: Duration = Duration::from_secs(90);
+const REKEY_TIMEOUT: Duration = Duration::from_secs(5);
+const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The inner packets are padded to a multiple of this length.
+const PADDING_ALIGNMENT: usize = 16;
+
+/// The cryptography of a [Wireguard] device.
+///
+/// smoltcp handles the messages, the session indices and counters, the replay windows and
+/// the timers of the protocol, and leaves the Noise_IKpsk2 handshake, the cookies, and the
+/// ChaCha20-Poly1305 transport keys to this trait, which holds the private key of the
+/// interface and the public key of the peer.
+///
+/// The sessions are identified by their local index, the sender index of the handshake
+/// message the interface sent to establish them.
+pub trait Noise {
+    /// Return a new random session index.
+    fn new_index(&mut self) -> u32;
+
+    /// Fill in the ephemeral key, the encrypted static key and timestamp, and the MACs of a
+    /// handshake initiation to the peer, whose type and sender index are filled in.
+    fn create_initiation(&mut self, timestamp: Instant, packet: &mut WireguardPacket<&mut [u8]>);
+
+    /// Consume a handshake response to the initiation of the session with the receiver
+    /// index of the response, and derive its transport keys.
+    ///
+    /// Return whether the response is valid, the session is abandoned otherwise.
+    fn consume_response(&mut self, timestamp: Instant, packet: &WireguardPacket<&[u8]>) -> bool;
+
+    /// Consume a handshake initiation from the peer, fill in the ephemeral key, the
+    /// encrypted empty field and the MACs of the `response`, whose type and indices are
+    /// filled in, and derive the transport keys of the session with the sender index of the
+    /// response.
+    ///
+    /// Return whether the initiation is valid, no response is sent otherwise.
+    fn consume_initiation(
+        &mut self,
+        timestamp: Instant,
+        initiation: &WireguardPacket<&[u8]>,
+        response: &mut WireguardPacket<&mut [u8]>,
+    ) -> bool;
+
+    /// Consume a cookie reply to the last handshake message sent, to compute the second
+    /// MAC of the next ones.
+    fn consume_cookie_reply(&mut self, packet: &WireguardPacket<&[u8]>);
+
+    /// Encrypt `data` in place with the sending key of the session `index` and the nonce
+    /// `counter`, and fill in its authentication `tag`.
+    fn encrypt(&mut self, index: u32, counter: u64, data: &mut [u8], tag: &mut [u8]);
+
+    /// Verify the authentication `tag` of `data` with the receiving key of the session
+    /// `index` and the nonce `counter`, and decrypt it in place.
+    ///
+    /// Return whether the tag is valid, the packet is dropped otherwise.
+    fn decrypt(&mut self, index: u32, counter: u64, data: &mut [u8], tag: &[u8]) -> bool;
+
+    /// Erase the keys of the session `index`, which expired or was replaced.
+    fn forget(&mut self, index: u32);
+}
+
+/// The sliding window of the counters of the transport data messages received in a
+/// session, see RFC 6479.
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct ReplayWindow {
+    /// The counter after the highest one accepted.
+    next: u64,
+    /// The counters accepted below `next`, as bit `next - 1 - counter`.
+    bitmap: u64,
+}
+
+impl ReplayWindow {
+    /// Return whether a message with the counter `counter` may be accepted.
+    fn check(&self, counter: u64) -> bool {
+        if counter >= REJECT_AFTER_MESSAGES {
+            false
+        } else if counter >= self.next {
+            true
+        } else {
+            let offset = self.next - 1 - counter;
+            offset < u64::BITS as u64 && self.bitmap & (1 << offset) == 0
+        }
+    }
+
+    /// Record the counter `counter` of an authenticated message.
+    fn accept(&mut self, counter: u64) {
+        if counter >= self.next {
+            let shift = counter + 1 - self.next;
+            self.bitmap = if shift < u64::BITS as u64 {
+                (self.bitmap << shift) | 1
+            } else {
+                1
+            };
+            self.next = counter + 1;
+        } else {
+            self.bitmap |= 1 << (self.next - 1 - counter);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Session {
+    local_index: u32,
+    remote_index: u32,
+    established_at: Instant,
+    initiator: bool,
+    tx_counter: u64,
+    replay_window: ReplayWindow,
+}
+
+impl Session {
+    fn expired(&self, timestamp: Instant) -> bool {
+        timestamp >= self.established_at + REJECT_AFTER_TIME
+    }
+
+    /// Return whether transport data may be sent in the session.
+    fn usable(&self, timestamp: Instant) -> bool {
+        !self.expired(timestamp) && self.tx_counter < REJECT_AFTER_MESSAGES
+    }
+
+    /// Return whether the initiator of the session must renew it.
+    fn stale(&self, timestamp: Instant) -> bool {
+        (self.initiator && timestamp >= self.established_at + REKEY_AFTER_TIME)
+            || self.tx_counter >= REKEY_AFTER_MESSAGES
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Handshake {
+    local_index: u32,
+    started_at: Instant,
+    sent_at: Instant,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Config {
+    endpoints: Endpoints,
+    local_port: u16,
+    remote_port: u16,
+    hop_limit: u8,
+}
+
+impl Config {
+    /// Return the length of the headers prepended to the messages.
+    fn header_len(&self) -> usize {
+        self.endpoints.header_len() + UDP_HEADER_LEN
+    }
+
+    /// Return the message in a packet received from the peer.
+    fn decapsulate<'p>(
+        &self,
+        checksum_caps: &ChecksumCapabilities,
+        packet: &'p [u8],
+    ) -> Option<&'p [u8]> {
+        let (next_header, payload) = self.endpoints.parse(checksum_caps, packet)?;
+        if next_header != IpProtocol::Udp {
+            return None;
+        }
+
+        let packet = UdpPacket::new_checked(payload).ok()?;
+        let src_addr = self.endpoints.remote();
+        let dst_addr = self.endpoints.local();
+        let repr = UdpRepr::parse(&packet, &src_addr, &dst_addr, checksum_caps).ok()?;
+        if repr.src_port != self.remote_port || repr.dst_port != self.local_port {
+            return None;
+        }
+        Some(packet.payload())
+    }
+
+    /// Fill in the headers in front of the message at the end of `buffer`.
+    fn encapsulate(&self, checksum_caps: &ChecksumCapabilities, buffer: &mut [u8]) {
+        let payload_len = buffer.len() - self.header_len();
+        let payload = self
+            .endpoints
+            .emit(checksum_caps, IpProtocol::Udp, self.hop_limit, buffer);
+        let repr = UdpRepr {
+            src_port: self.local_port,
+            dst_port: self.remote_port,
+        };
+        repr.emit(
+            &mut UdpPacket::new_unchecked(payload),
+            &self.endpoints.local(),
+            &self.endpoints.remote(),
+            payload_len,
+            |_| (),
+            checksum_caps,
+        );
+    }
+
+    /// Send a message of `len` octets, filled in by `f`, to the peer.
+    fn send<Tx, F>(&self, checksum_caps: &ChecksumCapabilities, token: Tx, len: usize, f: F)
+    where
+        Tx: phy::TxToken,
+        F: FnOnce(&mut [u8]),
+    {
+        let header_len = self.header_len();
+        token.consume(header_len + len, |buffer| {
+            f(&mut buffer[header_len..]);
+            self.encapsulate(checksum_caps, buffer)
+        })
+    }
+}
+
+/// Fill in a transport data message carrying `data_len` octets of plaintext data in
+/// `message`, and encrypt it.
+fn seal<N: Noise>(noise: &mut N, session: &mut Session, message: &mut [u8], data_len: usize) {
+    let counter = session.tx_counter;
+    session.tx_counter += 1;
+    let mut packet = WireguardPacket::new_unchecked(message);
+    packet.set_message_type(WireguardMessageType::TransportData);
+    packet.set_receiver_index(session.remote_index);
+    packet.set_counter(counter);
+    let (data, tag) = packet.encrypted_packet_mut().split_at_mut(data_len);
+    noise.encrypt(session.local_index, counter, data, tag);
+}
+
+/// Return the length of an inner packet, without its padding.
+fn packet_len(data: &[u8]) -> Option<usize> {
+    match IpVersion::of_packet(data.get(..1)?).ok()? {
+        #[cfg(feature = "proto-ipv4")]
+        IpVersion::Ipv4 => Some(Ipv4Packet::new_checked(data).ok()?.total_len() as usize),
+        #[cfg(feature = "proto-ipv6")]
+        IpVersion::Ipv6 => Some(Ipv6Packet::new_checked(data).ok()?.total_len()),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// Return the source address of an inner packet.
+fn src_addr(data: &[u8]) -> Option<IpAddress> {
+    match IpVersion::of_packet(data.get(..1)?).ok()? {
+        #[cfg(feature = "proto-ipv4")]
+        IpVersion::Ipv4 => Some(Ipv4Packet::new_checked(data).ok()?.src_addr().into()),
+        #[cfg(feature = "proto-ipv6")]
+        IpVersion::Ipv6 => Some(Ipv6Packet::new_checked(data).ok()?.src_addr().into()),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// The state of a [Wireguard] device, apart from the underlying device.
+#[derive(Debug)]
+struct State<N: Noise> {
+    noise: N,
+    config: Config,
+    allowed_ips: Vec<IpCidr>,
+    persistent_keepalive: Option<Duration>,
+    /// The session used to send transport data.
+    current: Option<Session>,
+    /// The session replaced by the current one, still accepted until it expires.
+    previous: Option<Session>,
+    /// The session established by answering an initiation, used once the peer confirms it.
+    next: Option<Session>,
+    handshake: Option<Handshake>,
+    last_sent: Option<Instant>,
+    /// The time a keepalive must be sent at, if nothing else is, to acknowledge the
+    /// transport data received.
+    keepalive_at: Option<Instant>,
+}
+
+impl<N: Noise> State<N> {
+    /// Return the session to send transport data in, if any.
+    fn sending_session(&mut self, timestamp: Instant) -> Option<&mut Session> {
+        self.current
+            .as_mut()
+            .filter(|session| session.usable(timestamp))
+    }
+
+    /// Replace a session, and erase the keys of the one replaced.
+    fn replace(noise: &mut N, slot: &mut Option<Session>, session: Option<Session>) {
+        if let Some(old) = core::mem::replace(slot, session) {
+            noise.forget(old.local_index)
+        }
+    }
+
+    /// Make `session` the current session.
+    fn rotate(&mut self, session: Session) {
+        let current = self.current.replace(session);
+        Self::replace(&mut self.noise, &mut self.previous, current);
+    }
+
+    fn sent(&mut self, timestamp: Instant) {
+        self.last_sent = Some(timestamp);
+        self.keepalive_at = None;
+    }
+
+    /// Send a handshake initiation to the peer, starting a new handshake or retrying the
+    /// one in progress.
+    fn initiate<Tx: phy::TxToken>(
+        &mut self,
+        timestamp: Instant,
+        checksum_caps: &ChecksumCapabilities,
+        token: Tx,
+    ) {
+        let local_index = self.noise.new_index();
+        let State { noise, config, .. } = self;
+        config.send(
+            checksum_caps,
+            token,
+            WIREGUARD_HANDSHAKE_INITIATION_LEN,
+            |message| {
+                let mut packet = WireguardPacket::new_unchecked(message);
+                packet.set_message_type(WireguardMessageType::HandshakeInitiation);
+                packet.set_sender_index(local_index);
+                noise.create_initiation(timestamp, &mut packet);
+            },
+        );
+        net_debug!("wireguard: sent handshake initiation {:08x}", local_index);
+        self.handshake = Some(Handshake {
+            local_index,
+            started_at: self
+                .handshake
+                .map_or(timestamp, |handshake| handshake.started_at),
+            sent_at: timestamp,
+        });
+        self.sent(timestamp);
+    }
+
+    /// Send a keepalive, an empty transport data message, to the peer.
+    fn send_keepalive<Tx: phy::TxToken>(
+        &mut self,
+        timestamp: Instant,
+        checksum_caps: &ChecksumCapabilities,
+        token: Tx,
+    ) {
+        let State {
+            noise,
+            config,
+            current,
+            ..
+        } = self;
+        let session = match current.as_mut() {
+            Some(session) if session.usable(timestamp) => session,
+            _ => return,
+        };
+        config.send(
+            checksum_caps,
+            token,
+            WIREGUARD_TRANSPORT_HEADER_LEN + WIREGUARD_TAG_LEN,
+            |message| seal(noise, session, message, 0),
+        );
+        self.sent(timestamp);
+    }
+
+    /// Retry the handshake in progress, and send the keepalives due.
+    fn poll<D: Device>(&mut self, lower: &mut D, timestamp: Instant) {
+        let checksum_caps = lower.capabilities().checksum;
+
+        if let Some(handshake) = self.handshake {
+            if timestamp >= handshake.started_at + REKEY_ATTEMPT_TIME {
+                net_debug!("wireguard: handshake timed out");
+                self.handshake = None;
+            } else if timestamp >= handshake.sent_at + REKEY_TIMEOUT {
+                if let Some(token) = lower.transmit(timestamp) {
+                    self.initiate(timestamp, &checksum_caps, token)
+                }
+            }
+        }
+
+        let persistent_keepalive_due = match (self.persistent_keepalive, self.last_sent) {
+            (Some(interval), Some(last_sent)) => timestamp >= last_sent + interval,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        let keepalive_due = self.keepalive_at.map_or(false, |at| timestamp >= at);
+        if !persistent_keepalive_due && !keepalive_due {
+            return;
+        }
+        if self.sending_session(timestamp).is_some() {
+            if let Some(token) = lower.transmit(timestamp) {
+                self.send_keepalive(timestamp, &checksum_caps, token)
+            }
+        } else if persistent_keepalive_due && self.handshake.is_none() {
+            if let Some(token) = lower.transmit(timestamp) {
+                self.initiate(timestamp, &checksum_caps, token)
+            }
+        }
+    }
+
+    /// Process a message received from the peer in `buffer`, answering it through `token`
+    /// if needed.
+    ///
+    /// Return the range of the inner packet in `buffer`, if it carries one.
+    fn process<Tx: phy::TxToken>(
+        &mut self,
+        timestamp: Instant,
+        checksum_caps: &ChecksumCapabilities,
+        token: Tx,
+        buffer: &mut [u8],
+    ) -> Option<Range<usize>> {
+        let packet = WireguardPacket::new_checked(&*buffer).ok()?;
+        match packet.message_type() {
+            WireguardMessageType::HandshakeInitiation => {
+                let local_index = self.noise.new_index();
+                let remote_index = packet.sender_index();
+                let mut message = [0; WIREGUARD_HANDSHAKE_RESPONSE_LEN];
+                let mut response = WireguardPacket::new_unchecked(&mut message[..]);
+                response.set_message_type(WireguardMessageType::HandshakeResponse);
+                response.set_sender_index(local_index);
+                response.set_receiver_index(remote_index);
+                if !self
+                    .noise
+                    .consume_initiation(timestamp, &packet, &mut response)
+                {
+                    net_debug!("wireguard: dropping invalid handshake initiation");
+                    return None;
+                }
+                self.config
+                    .send(checksum_caps, token, message.len(), |buffer| {
+                        buffer.copy_from_slice(&message)
+                    });
+                net_debug!(
+                    "wireguard: answered handshake initiation {:08x}",
+                    remote_index
+                );
+                let session = Session {
+                    local_index,
+                    remote_index,
+                    established_at: timestamp,
+                    initiator: false,
+                    tx_counter: 0,
+                    replay_window: ReplayWindow::default(),
+                };
+                Self::replace(&mut self.noise, &mut self.next, Some(session));
+                self.sent(timestamp);
+                None
+            }
+            WireguardMessageType::HandshakeResponse => {
+                let handshake = self.handshake?;
+                if packet.receiver_index() != handshake.local_index {
+                    return None;
+                }
+                if !self.noise.consume_response(timestamp, &packet) {
+                    net_debug!("wireguard: dropping invalid handshake response");
+                    return None;
+                }
+                net_debug!(
+                    "wireguard: handshake {:08x} complete",
+                    handshake.local_index
+                );
+                self.handshake = None;
+                self.rotate(Session {
+                    local_index: handshake.local_index,
+                    remote_index: packet.sender_index(),
+                    established_at: timestamp,
+                    initiator: true,
+                    tx_counter: 0,
+                    replay_window: ReplayWindow::default(),
+                });
+                // Confirm the session to the peer.
+                self.send_keepalive(timestamp, checksum_caps, token);
+                None
+            }
+            WireguardMessageType::CookieReply => {
+                let handshake = self.handshake?;
+                if packet.receiver_index() == handshake.local_index {
+                    self.noise.consume_cookie_reply(&packet);
+                }
+                None
+            }
+            WireguardMessageType::TransportData | WireguardMessageType::Unknown(_) => None,
+        }
+    }
+
+    /// Decrypt a transport data message received from the peer in `buffer`.
+    ///
+    /// Return the range of the inner packet in `buffer`, if it carries one.
+    fn process_data(&mut self, timestamp: Instant, buffer: &mut [u8]) -> Option<Range<usize>> {
+        let packet = WireguardPacket::new_checked(&*buffer).ok()?;
+        if packet.message_type() != WireguardMessageType::TransportData {
+            return None;
+        }
+        let index = packet.receiver_index();
+        let counter = packet.counter();
+        let State {
+            noise,
+            current,
+            previous,
+            next,
+            ..
+        } = self;
+        let session = [current, previous, next]
+            .into_iter()
+            .filter_map(|session| session.as_mut())
+            .find(|session| session.local_index == index)?;
+        if session.expired(timestamp) || !session.replay_window.check(counter) {
+            net_debug!(
+                "wireguard: dropping expired or replayed message {}",
+                counter
+            );
+            return None;
+        }
+        let data_offset = WIREGUARD_TRANSPORT_HEADER_LEN;
+        let tag_offset = buffer.len() - WIREGUARD_TAG_LEN;
+        let (data, tag) = buffer[data_offset..].split_at_mut(tag_offset - data_offset);
+        if !noise.decrypt(index, counter, data, tag) {
+            net_debug!("wireguard: dropping message failing authentication");
+            return None;
+        }
+        session.replay_window.accept(counter);
+
+        // The first message of the peer in a session it initiated confirms it.
+        if self.next.map_or(false, |next| next.local_index == index) {
+            let session = self.next.take().unwrap();
+            self.rotate(session);
+        }
+        if data.is_empty() {
+            return None;
+        }
+        if self.keepalive_at.is_none() {
+            self.keepalive_at = Some(timestamp + KEEPALIVE_TIMEOUT);
+        }
+
+        let data = &buffer[data_offset..tag_offset];
+        let len = packet_len(data).filter(|&len| len <= data.len())?;
+        let src_addr = src_addr(data)?;
+        if !self
+            .allowed_ips
+            .iter()
+            .any(|cidr| cidr.contains_addr(&src_addr))
+        {
+            net_debug!("wireguard: dropping packet from {}", src_addr);
+            return None;
+        }
+        Some(data_offset..data_offset + len)
+    }
+}
+
+/// A WireGuard device.
+///
+/// A WireGuard device is a device that encrypts the IP packets of the interface using it
+/// into the UDP messages it exchanges through the underlying device with a configured
+/// peer. The handshakes and the transport keys are computed outside of smoltcp, by a
+/// [Noise] implementation; the device sends the handshake initiations, answers the
+/// ones of the peer, numbers the messages, rejects the replayed ones, and renews the
+/// sessions as the WireGuard protocol requires.
+///
+/// The underlying device must have the IP medium. The packets the interface sends
+/// before a session is established stay in its sockets, and the packets received from
+/// source addresses outside of the allowed IPs are dropped.
+#[derive(Debug)]
+pub struct Wireguard<D: Device, N: Noise> {
+    lower: D,
+    state: State<N>,
+    rx_buffer: Vec<u8>,
+}
+
+impl<D: Device, N: Noise> Wireguard<D, N> {
+    /// Create a WireGuard device exchanging messages between the `local` and `remote`
+    /// endpoints.
+    ///
+    /// # Panics
+    /// This function panics if the underlying device does not have the IP medium, or if
+    /// the addresses are not of the same family.
+    pub fn new(lower: D, noise: N, local: IpEndpoint, remote: IpEndpoint) -> Wireguard<D, N> {
+        let caps = lower.capabilities();
+        if caps.medium != Medium::Ip {
+            panic!("wireguard requires a device with the IP medium")
+        }
+        Wireguard {
+            lower,
+            state: State {
+                noise,
+                config: Config {
+                    endpoints: Endpoints::new(local.addr, remote.addr),
+                    local_port: local.port,
+                    remote_port: remote.port,
+                    hop_limit: DEFAULT_HOP_LIMIT,
+                },
+                allowed_ips: Vec::new(),
+                persistent_keepalive: None,
+                current: None,
+                previous: None,
+                next: None,
+                handshake: None,
+                last_sent: None,
+                keepalive_at: None,
+            },
+            rx_buffer: Vec::with_capacity(caps.max_transmission_unit),
+        }
+    }
+
+    /// Get a reference to the underlying device.
+    ///
+    /// Even if the device offers reading through a standard reference, it is inadvisable to
+    /// directly read from the device as doing so will circumvent the encryption.
+    pub fn get_ref(&self) -> &D {
+        &self.lower
+    }
+
+    /// Get a mutable reference to the underlying device.
+    ///
+    /// It is inadvisable to directly read from the device as doing so will circumvent the
+    /// encryption.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.lower
+    }
+
+    /// Return the underlying device, consuming the WireGuard device.
+    pub fn into_inner(self) -> D {
+        self.lower
+    }
+
+    /// Get a reference to the Noise implementation.
+    pub fn noise(&self) -> &N {
+        &self.state.noise
+    }
+
+    /// Get a mutable reference to the Noise implementation.
+    pub fn noise_mut(&mut self) -> &mut N {
+        &mut self.state.noise
+    }
+
+    /// Allow the packets received from the addresses of `cidr`.
+    pub fn add_allowed_ip(&mut self, cidr: IpCidr) {
+        self.state.allowed_ips.push(cidr)
+    }
+
+    /// Return the addresses the packets received may come from.
+    pub fn allowed_ips(&self) -> &[IpCidr] {
+        &self.state.allowed_ips
+    }
+
+    /// Return the interval of the persistent keepalives.
+    pub fn persistent_keepalive(&self) -> Option<Duration> {
+        self.state.persistent_keepalive
+    }
+
+    /// Set the interval of the persistent keepalives, sent when nothing else was sent to
+    /// keep the mappings of the NATs and firewalls on the way open.
+    ///
+    /// A session is established, and kept established, as long as they are enabled.
+    pub fn set_persistent_keepalive(&mut self, interval: Option<Duration>) {
+        self.state.persistent_keepalive = interval
+    }
+
+    /// Return whether a session is established with the peer.
+    pub fn is_established(&self, timestamp: Instant) -> bool {
+        self.state
+            .current
+            .map_or(false, |session| session.usable(timestamp))
+    }
+
+    /// Return the hop limit of the outer packets.
+    pub fn hop_limit(&self) -> u8 {
+        self.state.config.hop_limit
+    }
+
+    /// Set the hop limit of the outer packets.
+    ///
+    /// # Panics
+    /// This function panics if the hop limit is zero.
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        if hop_limit == 0 {
+            panic!("hop limit must not be zero")
+        }
+        self.state.config.hop_limit = hop_limit
+    }
+}
+
+impl<D: Device, N: Noise> Device for Wireguard<D, N> {
+    type RxToken<'a>
+        = RxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, D::TxToken<'a>, N>
+    where
+        Self: 'a;
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let overhead =
+            self.state.config.header_len() + WIREGUARD_TRANSPORT_HEADER_LEN + WIREGUARD_TAG_LEN;
+        inner_capabilities(self.lower.capabilities(), overhead)
+    }
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let Wireguard {
+            lower,
+            state,
+            rx_buffer,
+        } = self;
+        state.poll(lower, timestamp);
+
+        let caps = lower.capabilities();
+        let (rx_token, tx_token) = lower.receive(timestamp)?;
+        let hardware_timestamp = phy::RxToken::hardware_timestamp(&rx_token);
+        let accepted = phy::RxToken::consume(rx_token, |packet| {
+            match state.config.decapsulate(&caps.checksum, packet) {
+                Some(message) => {
+                    rx_buffer.clear();
+                    rx_buffer.extend_from_slice(message);
+                    true
+                }
+                None => false,
+            }
+        });
+        if !accepted {
+            return None;
+        }
+
+        let is_data = WireguardPacket::new_checked(&rx_buffer[..]).map_or(false, |packet| {
+            packet.message_type() == WireguardMessageType::TransportData
+        });
+        if !is_data {
+            state.process(timestamp, &caps.checksum, tx_token, rx_buffer);
+            return None;
+        }
+        let range = state.process_data(timestamp, rx_buffer)?;
+
+        let rx = RxToken {
+            buffer: &rx_buffer[range],
+            hardware_timestamp,
+        };
+        let tx = TxToken {
+            lower: tx_token,
+            state,
+            timestamp,
+            checksum_caps: caps.checksum,
+            mtu: caps.max_transmission_unit,
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        let Wireguard { lower, state, .. } = self;
+        state.poll(lower, timestamp);
+
+        let caps = lower.capabilities();
+        let stale = match state.sending_session(timestamp) {
+            Some(session) => session.stale(timestamp),
+            None => true,
+        };
+        if stale && state.handshake.is_none() {
+            let token = lower.transmit(timestamp)?;
+            state.initiate(timestamp, &caps.checksum, token);
+        }
+        // The packets wait in the sockets until a session is established.
+        state.sending_session(timestamp)?;
+
+        let lower = lower.transmit(timestamp)?;
+        Some(TxToken {
+            lower,
+            state,
+            timestamp,
+            checksum_caps: caps.checksum,
+            mtu: caps.max_transmission_unit,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken<'a> {
+    buffer: &'a [u8],
+    hardware_timestamp: Option<HardwareTimestamp>,
+}
+
+impl<'a> phy::RxToken for RxToken<'a> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(self.buffer)
+    }
+
+    fn hardware_timestamp(&self) -> Option<HardwareTimestamp> {
+        self.hardware_timestamp
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<'a, Tx: phy::TxToken, N: Noise> {
+    lower: Tx,
+    state: &'a mut State<N>,
+    timestamp: Instant,
+    checksum_caps: ChecksumCapabilities,
+    mtu: usize,
+}
+
+impl<'a, Tx: phy::TxToken, N: Noise> TxToken<'a, Tx, N> {
+    /// Return the length of the message carrying an inner packet of `len` octets, padded
+    /// without exceeding the MTU of the underlying device.
+    fn message_len(&self, len: usize) -> (usize, usize) {
+        let config = &self.state.config;
+        let max_len = self
+            .mtu
+            .saturating_sub(
+                config.header_len() + WIREGUARD_TRANSPORT_HEADER_LEN + WIREGUARD_TAG_LEN,
+            )
+            .max(len);
+        let padded_len =
+            ((len + PADDING_ALIGNMENT - 1) / PADDING_ALIGNMENT * PADDING_ALIGNMENT).min(max_len);
+        let message_len = WIREGUARD_TRANSPORT_HEADER_LEN + padded_len + WIREGUARD_TAG_LEN;
+        (config.header_len() + message_len, padded_len)
+    }
+}
+
+/// Fill in the message carrying the inner packet written by `f` at the end of `buffer`.
+fn fill<N, R, F>(
+    state: &mut State<N>,
+    checksum_caps: &ChecksumCapabilities,
+    buffer: &mut [u8],
+    len: usize,
+    padded_len: usize,
+    f: F,
+) -> R
+where
+    N: Noise,
+    F: FnOnce(&mut [u8]) -> R,
+{
+    let header_len = state.config.header_len();
+    let data_offset = header_len + WIREGUARD_TRANSPORT_HEADER_LEN;
+    let result = f(&mut buffer[data_offset..data_offset + len]);
+    buffer[data_offset + len..data_offset + padded_len].fill(0);
+
+    let State {
+        noise,
+        config,
+        current,
+        ..
+    } = state;
+    // The transmit token is only created with a usable session.
+    let session = current.as_mut().unwrap();
+    seal(noise, session, &mut buffer[header_len..], padded_len);
+    config.encapsulate(checksum_caps, buffer);
+    result
+}
+
+impl<'a, Tx: phy::TxToken, N: Noise> phy::TxToken for TxToken<'a, Tx, N> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let (total_len, padded_len) = self.message_len(len);
+        let TxToken {
+            lower,
+            state,
+            timestamp,
+            checksum_caps,
+            ..
+        } = self;
+        if state.sending_session(timestamp).is_none() {
+            // The session expired since the token was created, drop the packet.
+            return f(&mut vec![0; len]);
+        }
+        let result = lower.consume(total_len, |buffer| {
+            fill(state, &checksum_caps, buffer, len, padded_len, f)
+        });
+        state.sent(timestamp);
+        result
+    }
+
+    fn consume_timestamped<R, F>(self, len: usize, f: F) -> (R, Option<HardwareTimestamp>)
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let (total_len, padded_len) = self.message_len(len);
+        let TxToken {
+            lower,
+            state,
+            timestamp,
+            checksum_caps,
+            ..
+        } = self;
+        if state.sending_session(timestamp).is_none() {
+            return (f(&mut vec![0; len]), None);
+        }
+        let result = lower.consume_timestamped(total_len, |buffer| {
+            fill(state, &checksum_caps, buffer, len, padded_len, f)
+        });
+        state.sent(timestamp);
+        result
+    }
+}
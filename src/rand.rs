@@ -1,17 +1,75 @@
 #![allow(unsafe_code)]
 #![allow(unused)]
 
-#[derive(Debug)]
+use core::fmt;
+
+/// How hard it is to predict the numbers of a random number generator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EntropyQuality {
+    /// The numbers can be predicted by an attacker who sees some of them, or who
+    /// guesses the seed, as with the generator built in the interface.
+    Predictable,
+    /// The numbers come from a cryptographically secure generator.
+    Cryptographic,
+}
+
+/// A random number generator for an interface.
+///
+/// The interface draws from it the numbers that off-path attackers must not guess to
+/// spoof packets, such as TCP initial sequence numbers, DNS query IDs and source
+/// ports, and DHCP transaction IDs, as well as identifiers and protocol timers.
+pub trait Rng {
+    /// Return the next random number.
+    fn next_u32(&mut self) -> u32;
+
+    /// Return how hard the numbers are to predict.
+    fn quality(&self) -> EntropyQuality {
+        EntropyQuality::Predictable
+    }
+}
+
 pub(crate) struct Rand {
     state: u64,
+    rng: Option<&'static mut (dyn Rng + Send)>,
+}
+
+impl fmt::Debug for Rand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Rand")
+            .field("quality", &self.quality())
+            .finish()
+    }
 }
 
 impl Rand {
     pub(crate) const fn new(seed: u64) -> Self {
-        Self { state: seed }
+        Self {
+            state: seed,
+            rng: None,
+        }
+    }
+
+    /// Draw the numbers from `rng` instead of the built-in generator.
+    pub(crate) fn with_rng(rng: &'static mut (dyn Rng + Send)) -> Self {
+        Self {
+            state: 0,
+            rng: Some(rng),
+        }
+    }
+
+    pub(crate) fn quality(&self) -> EntropyQuality {
+        match &self.rng {
+            Some(rng) => rng.quality(),
+            None => EntropyQuality::Predictable,
+        }
     }
 
     pub(crate) fn rand_u32(&mut self) -> u32 {
+        if let Some(rng) = &mut self.rng {
+            return rng.next_u32();
+        }
+
         // sPCG32 from https://www.pcg-random.org/paper.html
         // see also https://nullprogram.com/blog/2017/09/21/
         const M: u64 = 0xbb2efcec3c39611d;
@@ -64,3 +122,95 @@ impl Rand {
         ]
     }
 }
+
+/// The SipHash-2-4 keyed hash function, used to derive values that must be
+/// unpredictable without the key, such as TCP initial sequence numbers.
+pub(crate) struct SipHasher {
+    v: [u64; 4],
+    /// The bytes of the current word not hashed yet.
+    tail: u64,
+    /// The number of bytes written so far.
+    len: usize,
+}
+
+impl SipHasher {
+    pub(crate) fn new(key: &[u8; 16]) -> Self {
+        let mut k0 = [0; 8];
+        let mut k1 = [0; 8];
+        k0.copy_from_slice(&key[..8]);
+        k1.copy_from_slice(&key[8..]);
+        let k0 = u64::from_le_bytes(k0);
+        let k1 = u64::from_le_bytes(k1);
+        Self {
+            v: [
+                k0 ^ 0x736f6d6570736575,
+                k1 ^ 0x646f72616e646f6d,
+                k0 ^ 0x6c7967656e657261,
+                k1 ^ 0x7465646279746573,
+            ],
+            tail: 0,
+            len: 0,
+        }
+    }
+
+    fn round(&mut self) {
+        let [v0, v1, v2, v3] = &mut self.v;
+        *v0 = v0.wrapping_add(*v1);
+        *v1 = v1.rotate_left(13) ^ *v0;
+        *v0 = v0.rotate_left(32);
+        *v2 = v2.wrapping_add(*v3);
+        *v3 = v3.rotate_left(16) ^ *v2;
+        *v0 = v0.wrapping_add(*v3);
+        *v3 = v3.rotate_left(21) ^ *v0;
+        *v2 = v2.wrapping_add(*v1);
+        *v1 = v1.rotate_left(17) ^ *v2;
+        *v2 = v2.rotate_left(32);
+    }
+
+    fn compress(&mut self, word: u64) {
+        self.v[3] ^= word;
+        self.round();
+        self.round();
+        self.v[0] ^= word;
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.tail |= (byte as u64) << (8 * (self.len % 8));
+            self.len += 1;
+            if self.len % 8 == 0 {
+                self.compress(self.tail);
+                self.tail = 0;
+            }
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> u64 {
+        self.compress(self.tail | (self.len as u64) << 56);
+        self.v[2] ^= 0xff;
+        for _ in 0..4 {
+            self.round();
+        }
+        self.v[0] ^ self.v[1] ^ self.v[2] ^ self.v[3]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_siphash() {
+        // Test vectors from the reference implementation of SipHash-2-4.
+        let mut key = [0; 16];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        assert_eq!(SipHasher::new(&key).finish(), 0x726fdb47dd0e0e31);
+
+        let mut hasher = SipHasher::new(&key);
+        hasher.write(&[0, 1, 2]);
+        hasher.write(&[3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14]);
+        assert_eq!(hasher.finish(), 0xa129ca6149be45e5);
+    }
+}
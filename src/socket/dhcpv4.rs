@@ -6,10 +6,11 @@ use crate::time::{Duration, Instant};
 use crate::wire::dhcpv4::field as dhcpv4_field;
 use crate::wire::{
     DhcpMessageType, DhcpPacket, DhcpRepr, IpAddress, IpProtocol, Ipv4Address, Ipv4Cidr, Ipv4Repr,
-    UdpRepr, DHCP_CLIENT_PORT, DHCP_MAX_DNS_SERVER_COUNT, DHCP_SERVER_PORT, UDP_HEADER_LEN,
+    UdpRepr, DHCP_CLIENT_PORT, DHCP_MAX_CAPTIVE_PORTAL_LEN, DHCP_MAX_DNS_SERVER_COUNT,
+    DHCP_SERVER_PORT, UDP_HEADER_LEN,
 };
-use crate::wire::{DhcpOption, HardwareAddress};
-use heapless::Vec;
+use crate::wire::{DhcpOption, HardwareAddress, IpVersion};
+use heapless::{String, Vec};
 
 #[cfg(feature = "async")]
 use super::WakerRegistration;
@@ -22,6 +23,7 @@ const DEFAULT_PARAMETER_REQUEST_LIST: &[u8] = &[
     dhcpv4_field::OPT_SUBNET_MASK,
     dhcpv4_field::OPT_ROUTER,
     dhcpv4_field::OPT_DOMAIN_NAME_SERVER,
+    dhcpv4_field::OPT_CAPTIVE_PORTAL,
 ];
 
 /// IPv4 configuration data provided by the DHCP server.
@@ -38,6 +40,9 @@ pub struct Config<'a> {
     pub router: Option<Ipv4Address>,
     /// DNS servers
     pub dns_servers: Vec<Ipv4Address, DHCP_MAX_DNS_SERVER_COUNT>,
+    /// The URI of the captive portal API of the network (RFC 8910), if the network has a
+    /// captive portal.
+    pub captive_portal: Option<String<DHCP_MAX_CAPTIVE_PORTAL_LEN>>,
     /// Received DHCP packet
     pub packet: Option<DhcpPacket<&'a [u8]>>,
 }
@@ -90,6 +95,7 @@ struct RenewState {
 
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(clippy::large_enum_variant)]
 enum ClientState {
     /// Discovering the DHCP server
     Discovering(DiscoverState),
@@ -121,9 +127,18 @@ impl Default for RetryConfig {
     }
 }
 
+/// A function called with each option of a received DHCP packet the socket does not
+/// interpret, along with the type of the message carrying it.
+///
+/// This lets applications consume site-specific options, such as the ACS URL of
+/// TR-069 or PXE boot options, without the socket knowing about them. It is called
+/// only for messages belonging to the current transaction.
+pub type OptionHandler = fn(DhcpMessageType, DhcpOption<'_>);
+
 /// Return value for the `Dhcpv4Socket::poll` function
 #[derive(Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(clippy::large_enum_variant)]
 pub enum Event<'a> {
     /// Configuration has been lost (for example, the lease has expired)
     Deconfigured,
@@ -163,6 +178,8 @@ pub struct Socket<'a> {
 
     /// Incoming DHCP packets are copied into this buffer, overwriting the previous.
     receive_packet_buffer: Option<&'a mut [u8]>,
+    /// Called with the options of incoming DHCP packets the socket does not interpret.
+    option_handler: Option<OptionHandler>,
 
     /// Waker registration
     #[cfg(feature = "async")]
@@ -190,6 +207,7 @@ impl<'a> Socket<'a> {
             outgoing_options: &[],
             parameter_request_list: None,
             receive_packet_buffer: None,
+            option_handler: None,
             #[cfg(feature = "async")]
             waker: WakerRegistration::new(),
             server_port: DHCP_SERVER_PORT,
@@ -212,10 +230,17 @@ impl<'a> Socket<'a> {
         self.receive_packet_buffer = Some(buffer);
     }
 
+    /// Set the function called with the options of incoming DHCP packets that the
+    /// socket does not interpret, or `None` to ignore them.
+    pub fn set_option_handler(&mut self, handler: Option<OptionHandler>) {
+        self.option_handler = handler;
+    }
+
     /// Set the parameter request list.
     ///
     /// This should contain at least `OPT_SUBNET_MASK` (`1`), `OPT_ROUTER`
-    /// (`3`), and `OPT_DOMAIN_NAME_SERVER` (`6`).
+    /// (`3`), and `OPT_DOMAIN_NAME_SERVER` (`6`). The default list also contains
+    /// `OPT_CAPTIVE_PORTAL` (`114`).
     pub fn set_parameter_request_list(&mut self, parameter_request_list: &'a [u8]) {
         self.parameter_request_list = Some(parameter_request_list);
     }
@@ -335,6 +360,12 @@ impl<'a> Socket<'a> {
             }
         }
 
+        if let Some(handler) = self.option_handler {
+            for option in dhcp_packet.additional_options() {
+                handler(dhcp_repr.message_type, option);
+            }
+        }
+
         match (&mut self.state, dhcp_repr.message_type) {
             (ClientState::Discovering(_state), DhcpMessageType::Offer) => {
                 if !dhcp_repr.your_ip.is_unicast() {
@@ -462,6 +493,7 @@ impl<'a> Socket<'a> {
             address: Ipv4Cidr::new(dhcp_repr.your_ip, prefix_len),
             router: dhcp_repr.router,
             dns_servers,
+            captive_portal: dhcp_repr.captive_portal.and_then(super::captive_portal),
             packet: None,
         };
 
@@ -534,11 +566,14 @@ impl<'a> Socket<'a> {
                 self.parameter_request_list
                     .unwrap_or(DEFAULT_PARAMETER_REQUEST_LIST),
             ),
-            max_size: Some((cx.ip_mtu() - MAX_IPV4_HEADER_LEN - UDP_HEADER_LEN) as u16),
+            max_size: Some(
+                (cx.ip_mtu_of(IpVersion::Ipv4) - MAX_IPV4_HEADER_LEN - UDP_HEADER_LEN) as u16,
+            ),
             lease_duration: None,
             renew_duration: None,
             rebind_duration: None,
             dns_servers: None,
+            captive_portal: None,
             additional_options: self.outgoing_options,
         };
 
@@ -553,6 +588,7 @@ impl<'a> Socket<'a> {
             next_header: IpProtocol::Udp,
             payload_len: 0, // filled right before emit
             hop_limit: 64,
+            dscp: 0,
         };
 
         match &mut self.state {
@@ -672,6 +708,7 @@ impl<'a> Socket<'a> {
                 address: state.config.address,
                 router: state.config.router,
                 dns_servers: state.config.dns_servers.clone(),
+                captive_portal: state.config.captive_portal.clone(),
                 packet: self
                     .receive_packet_buffer
                     .as_deref()
@@ -692,6 +729,11 @@ impl<'a> Socket<'a> {
         self.waker.wake_all();
     }
 
+    /// Return whether `poll` would yield an event.
+    pub(crate) fn has_config_changed(&self) -> bool {
+        self.config_changed
+    }
+
     /// Register a waker.
     ///
     /// The waker is woken on state changes that might affect the return value
@@ -851,6 +893,7 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        dscp: 0,
     };
 
     const IP_SERVER_BROADCAST: Ipv4Repr = Ipv4Repr {
@@ -859,6 +902,7 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        dscp: 0,
     };
 
     const IP_RECV: Ipv4Repr = Ipv4Repr {
@@ -867,6 +911,7 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        dscp: 0,
     };
 
     const IP_SEND: Ipv4Repr = Ipv4Repr {
@@ -875,6 +920,7 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        dscp: 0,
     };
 
     const UDP_SEND: UdpRepr = UdpRepr {
@@ -919,13 +965,14 @@ mod test {
         renew_duration: None,
         rebind_duration: None,
         lease_duration: None,
+        captive_portal: None,
         additional_options: &[],
     };
 
     const DHCP_DISCOVER: DhcpRepr = DhcpRepr {
         message_type: DhcpMessageType::Discover,
         client_identifier: Some(MY_MAC),
-        parameter_request_list: Some(&[1, 3, 6]),
+        parameter_request_list: Some(&[1, 3, 6, 114]),
         max_size: Some(1432),
         ..DHCP_DEFAULT
     };
@@ -953,7 +1000,7 @@ mod test {
         max_size: Some(1432),
 
         requested_ip: Some(MY_IP),
-        parameter_request_list: Some(&[1, 3, 6]),
+        parameter_request_list: Some(&[1, 3, 6, 114]),
         ..DHCP_DEFAULT
     };
 
@@ -988,7 +1035,7 @@ mod test {
         max_size: Some(1432),
 
         requested_ip: None,
-        parameter_request_list: Some(&[1, 3, 6]),
+        parameter_request_list: Some(&[1, 3, 6, 114]),
         ..DHCP_DEFAULT
     };
 
@@ -1026,6 +1073,7 @@ mod test {
                 address: Ipv4Cidr::new(MY_IP, 24),
                 dns_servers: Vec::from_slice(DNS_IPS).unwrap(),
                 router: Some(SERVER_IP),
+                captive_portal: None,
                 packet: None,
             },
             renew_at: Instant::from_secs(500),
@@ -1057,6 +1105,7 @@ mod test {
                 address: Ipv4Cidr::new(MY_IP, 24),
                 dns_servers: Vec::from_slice(DNS_IPS).unwrap(),
                 router: Some(SERVER_IP),
+                captive_portal: None,
                 packet: None,
             }))
         );
@@ -1070,6 +1119,71 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_bind_captive_portal() {
+        let mut s = socket();
+
+        recv!(s, [(IP_BROADCAST, UDP_SEND, DHCP_DISCOVER)]);
+        send!(s, (IP_RECV, UDP_RECV, dhcp_offer()));
+        recv!(s, [(IP_BROADCAST, UDP_SEND, DHCP_REQUEST)]);
+        send!(
+            s,
+            (
+                IP_RECV,
+                UDP_RECV,
+                DhcpRepr {
+                    captive_portal: Some("https://portal.example.net/api"),
+                    ..dhcp_ack()
+                }
+            )
+        );
+
+        match s.poll() {
+            Some(Event::Configured(config)) => assert_eq!(
+                config.captive_portal.as_deref(),
+                Some("https://portal.example.net/api")
+            ),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn test_option_handler() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static HANDLED: AtomicUsize = AtomicUsize::new(0);
+        fn handler(message_type: DhcpMessageType, option: DhcpOption<'_>) {
+            assert_eq!(message_type, DhcpMessageType::Ack);
+            assert_eq!(option.kind, 43);
+            assert_eq!(option.data, b"acs");
+            HANDLED.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut s = socket();
+        s.set_option_handler(Some(handler));
+
+        recv!(s, [(IP_BROADCAST, UDP_SEND, DHCP_DISCOVER)]);
+        send!(s, (IP_RECV, UDP_RECV, dhcp_offer()));
+        recv!(s, [(IP_BROADCAST, UDP_SEND, DHCP_REQUEST)]);
+        send!(
+            s,
+            (
+                IP_RECV,
+                UDP_RECV,
+                DhcpRepr {
+                    additional_options: &[DhcpOption {
+                        kind: 43,
+                        data: b"acs",
+                    }],
+                    ..dhcp_ack()
+                }
+            )
+        );
+
+        assert!(matches!(s.poll(), Some(Event::Configured(_))));
+        assert_eq!(HANDLED.load(Ordering::Relaxed), 1);
+    }
+
     #[test]
     fn test_bind_different_ports() {
         let mut s = socket_different_port();
@@ -1092,6 +1206,7 @@ mod test {
                 address: Ipv4Cidr::new(MY_IP, 24),
                 dns_servers: Vec::from_slice(DNS_IPS).unwrap(),
                 router: Some(SERVER_IP),
+                captive_portal: None,
                 packet: None,
             }))
         );
@@ -5,12 +5,13 @@ use crate::iface::Context;
 use crate::time::{Duration, Instant};
 use crate::wire::dhcpv6::{self, StatusCode, MAX_IA_ADDRESSES, ReprIaPrefix};
 use crate::wire::{
-    Dhcpv6MessageType, Dhcpv6Packet, Dhcpv6Repr, IpProtocol, Ipv6Address, Ipv6Cidr, Ipv6Repr,
-    UdpRepr, DHCPV6_CLIENT_PORT, DHCPV6_SERVER_PORT, DHCP_MAX_DNS_SERVER_COUNT,
-    Icmpv6Repr, NdiscRepr, NdiscRouterFlags, NdiscPrefixInformation, Dhcpv6ReprIaNa
+    Dhcpv6MessageType, Dhcpv6Packet, Dhcpv6Repr, Dhcpv6ReprIaNa, Icmpv6Repr, IpProtocol,
+    Ipv6Address, Ipv6Cidr, Ipv6Repr, NdiscPrefixInfoFlags, NdiscPrefixInformation, NdiscRepr,
+    NdiscRouterFlags, UdpRepr, DHCPV6_CLIENT_PORT, DHCPV6_SERVER_PORT, DHCP_MAX_CAPTIVE_PORTAL_LEN,
+    DHCP_MAX_DNS_SERVER_COUNT,
 };
 use crate::wire::{Dhcpv6Option, HardwareAddress};
-use heapless::Vec;
+use heapless::{String, Vec};
 
 #[cfg(feature = "async")]
 use super::WakerRegistration;
@@ -33,10 +34,36 @@ pub struct Config<'a> {
     pub router: Ipv6Cidr,
     /// DNS servers
     pub dns_servers: Vec<Ipv6Address, DHCP_MAX_DNS_SERVER_COUNT>,
-    /// Received DHCP packet
+    /// The URI of the captive portal API of the network (RFC 8910), as given by the
+    /// DHCPv6 server or else by the router advertisement, if the network has a captive
+    /// portal.
+    pub captive_portal: Option<String<DHCP_MAX_CAPTIVE_PORTAL_LEN>>,
+    /// Received DHCP packet, if a receive packet buffer is set and the packet fit in it.
     pub packet: Option<Dhcpv6Packet<&'a [u8]>>,
 }
 
+impl<'a> Config<'a> {
+    /// Parse the received DHCP packet, if any.
+    ///
+    /// The options the representation does not cover, such as vendor-specific ones,
+    /// are iterated with [`additional_options`](#method.additional_options).
+    pub fn repr(&self) -> Option<Dhcpv6Repr<'a>> {
+        let packet = self.packet?;
+        packet.check_len().ok()?;
+        Dhcpv6Repr::parse(&packet).ok()
+    }
+
+    /// Return an iterator over the options of the received DHCP packet that are not
+    /// represented by [`repr`](#method.repr).
+    pub fn additional_options(&self) -> impl Iterator<Item = Dhcpv6Option<'_>> + '_ {
+        self.packet
+            .as_ref()
+            .filter(|packet| packet.check_len().is_ok())
+            .into_iter()
+            .flat_map(|packet| packet.additional_options())
+    }
+}
+
 /// Information on how to reach a DHCPV6 server.
 #[derive(Debug, Clone, Eq, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -48,6 +75,19 @@ pub struct ServerInfo {
     pub identifier: Vec<u8, MAX_IDENTIFIER_LEN>,
 }
 
+/// IPv6 configuration data provided by the last router advertisement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RouterConfig {
+    /// Address of the router, if it can be used as default router.
+    pub router: Option<Ipv6Address>,
+    /// Prefix to form addresses from with stateless address autoconfiguration (SLAAC),
+    /// if the router does not manage addresses with DHCPv6.
+    pub slaac_prefix: Option<Ipv6Cidr>,
+    /// When the configuration expires, unless a new router advertisement refreshes it.
+    pub expires_at: Instant,
+}
+
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 struct RouterSolicitState {
@@ -150,9 +190,18 @@ impl Default for RetryConfig {
     }
 }
 
+/// A function called with each option of a received DHCP packet the socket does not
+/// interpret, along with the type of the message carrying it.
+///
+/// This lets applications consume site-specific options, such as the ACS URL of
+/// TR-069 or PXE boot options, without the socket knowing about them. It is called
+/// only for messages belonging to the current transaction.
+pub type OptionHandler = fn(Dhcpv6MessageType, Dhcpv6Option<'_>);
+
 /// Return value for the `Dhcpv4Socket::poll` function
 #[derive(Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(clippy::large_enum_variant)]
 pub enum Event<'a> {
     /// Configuration has been lost (for example, the lease has expired)
     Deconfigured,
@@ -194,6 +243,16 @@ pub struct Socket<'a> {
 
     /// Incoming DHCP packets are copied into this buffer, overwriting the previous.
     receive_packet_buffer: Option<&'a mut [u8]>,
+    /// Length of the packet in `receive_packet_buffer`, if one fit.
+    receive_packet_len: Option<usize>,
+    /// Called with the options of incoming DHCP packets the socket does not interpret.
+    option_handler: Option<OptionHandler>,
+
+    /// The captive portal URI of the last router advertisement.
+    ra_captive_portal: Option<String<DHCP_MAX_CAPTIVE_PORTAL_LEN>>,
+
+    /// The configuration provided by the last router advertisement.
+    router_config: Option<RouterConfig>,
 
     /// Waker registration
     #[cfg(feature = "async")]
@@ -227,6 +286,10 @@ impl<'a> Socket<'a> {
             outgoing_options: &[],
             parameter_request_list: None,
             receive_packet_buffer: None,
+            receive_packet_len: None,
+            option_handler: None,
+            ra_captive_portal: None,
+            router_config: None,
             #[cfg(feature = "async")]
             waker: WakerRegistration::new(),
             server_port: DHCPV6_SERVER_PORT,
@@ -249,6 +312,12 @@ impl<'a> Socket<'a> {
         self.receive_packet_buffer = Some(buffer);
     }
 
+    /// Set the function called with the options of incoming DHCP packets that the
+    /// socket does not interpret, or `None` to ignore them.
+    pub fn set_option_handler(&mut self, handler: Option<OptionHandler>) {
+        self.option_handler = handler;
+    }
+
     /// Set the parameter request list.
     ///
     /// This should contain at least `OPT_SUBNET_MASK` (`1`), `OPT_ROUTER`
@@ -316,7 +385,6 @@ impl<'a> Socket<'a> {
         cx: &mut Context,
         ip_repr: &Ipv6Repr,
         repr: &Icmpv6Repr,
-        payload: &[u8],
     ) {
         let src_ip = ip_repr.src_addr;
 
@@ -326,24 +394,43 @@ impl<'a> Socket<'a> {
             src_ip            
         );
 
-        // Copy over the payload into the receive packet buffer.
-        if let Some(buffer) = self.receive_packet_buffer.as_mut() {
-            if let Some(buffer) = buffer.get_mut(..payload.len()) {
-                buffer.copy_from_slice(payload);
+        if let Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+            flags,
+            router_lifetime,
+            prefix_info,
+            ..
+        }) = repr
+        {
+            if ip_repr.hop_limit == 0xff {
+                self.router_config = Self::parse_router_config(
+                    cx.now(),
+                    src_ip,
+                    *flags,
+                    *router_lifetime,
+                    prefix_info,
+                );
             }
         }
 
         match (&mut self.state, repr) {
-            (ClientState::RouterSolicit(_), Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
-                hop_limit: _hop_limit,
-                flags,
-                router_lifetime: _router_lifetime,
-                reachable_time: _reachable_time,
-                retrans_time: _retrans_time,
-                lladdr: _lladdr,
-                mtu,
-                prefix_info
-            })) => {
+            (
+                ClientState::RouterSolicit(_),
+                Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+                    hop_limit: _hop_limit,
+                    flags,
+                    router_lifetime: _router_lifetime,
+                    reachable_time: _reachable_time,
+                    retrans_time: _retrans_time,
+                    lladdr: _lladdr,
+                    mtu,
+                    prefix_info,
+                    captive_portal,
+                    pref64: _pref64,
+                    sixlowpan_context: _sixlowpan_context,
+                }),
+            ) => {
+                self.ra_captive_portal = captive_portal.and_then(super::captive_portal);
+
                 // Flag that indicates if we are to go into stateful DHCP mode
                 if flags.contains(NdiscRouterFlags::MANAGED) {
                     let mtu = match mtu {
@@ -389,7 +476,8 @@ impl<'a> Socket<'a> {
                 );
             }
             _ => {
-                // silently ignore ICMP packets when we are pasted the route solicit phase
+                // Ignore ICMP packets once we are past the router solicit phase
+                net_trace!("ICMPv6 ignoring {:?}: router already found", repr);
             }
         }
     }
@@ -454,14 +542,28 @@ impl<'a> Socket<'a> {
 
         // Copy over the payload into the receive packet buffer.
         if let Some(buffer) = self.receive_packet_buffer.as_mut() {
-            if let Some(buffer) = buffer.get_mut(..payload.len()) {
-                buffer.copy_from_slice(payload);
+            self.receive_packet_len = match buffer.get_mut(..payload.len()) {
+                Some(buffer) => {
+                    buffer.copy_from_slice(payload);
+                    Some(payload.len())
+                }
+                None => None,
+            };
+        }
+
+        if let Some(handler) = self.option_handler {
+            for option in dhcp_packet.additional_options() {
+                handler(dhcp_repr.message_type, option);
             }
         }
 
         match (&mut self.state, dhcp_repr.message_type) {
             (ClientState::RouterSolicit(_), _) => {
-                // Silently ignore DHCP requests when we are still soliciting the router address
+                // Ignore DHCP requests when we are still soliciting the router address
+                net_trace!(
+                    "DHCPv6 ignoring {:?}: still soliciting a router",
+                    dhcp_repr.message_type
+                );
             }
             (ClientState::DhcpSolicit(state), Dhcpv6MessageType::Advertise) => {
                 let ia_na = match dhcp_repr.ia_na {
@@ -542,7 +644,7 @@ impl<'a> Socket<'a> {
                 match dhcp_repr.server_id {
                     Some(s) if s.len() == state.server.identifier.len() && s == state.server.identifier => {},
                     Some(s) => {
-                        net_debug!("DHCPv6 ignoring confirm because its server identifier does not match (expected={:?} actual={:?}", &state.server.identifier, s);
+                        net_debug!("DHCPv6 ignoring confirm because its server identifier does not match (expected={:?} actual={:?})", &state.server.identifier, s);
                         return;
                     }
                     None => {
@@ -550,19 +652,23 @@ impl<'a> Socket<'a> {
                         return;
                     }
                 };
-                if let Some((config, renew_at, expires_at)) =
-                    Self::parse_ack(
-                        cx.now(),
-                        &dhcp_repr,
-                        self.max_lease_duration,
-                        state.server.clone(),
-                        &ia_na,
-                        &state.prefix_info
-                    )
-                {
+                if let Some((config, renew_at, expires_at)) = Self::parse_ack(
+                    cx.now(),
+                    &dhcp_repr,
+                    self.max_lease_duration,
+                    state.server.clone(),
+                    &ia_na,
+                    &state.prefix_info,
+                    self.ra_captive_portal.as_ref(),
+                ) {
                     let mut client_id = Vec::new();
                     client_id.extend_from_slice(&state.client_id).ok();
 
+                    net_info!(
+                        "DHCPv6 lease from {} obtained, renewing in {}",
+                        src_ip,
+                        renew_at - cx.now()
+                    );
                     self.state = ClientState::DhcpRenewing(DhcpRenewState {
                         client_id,
                         iaid: state.iaid,
@@ -576,7 +682,13 @@ impl<'a> Socket<'a> {
                 }
             }
             (ClientState::DhcpRequesting(_), Dhcpv6MessageType::Decline) => {
-                if !self.ignore_naks {
+                if self.ignore_naks {
+                    net_debug!("DHCPv6 ignoring decline from {}", src_ip);
+                } else {
+                    net_info!(
+                        "DHCPv6 request declined by {}, restarting discovery",
+                        src_ip
+                    );
                     self.reset();
                 }
             }
@@ -602,7 +714,7 @@ impl<'a> Socket<'a> {
                 match dhcp_repr.server_id {
                     Some(s) if s.len() == state.config.server.identifier.len() && s == state.config.server.identifier => {},
                     Some(s) => {
-                        net_debug!("DHCPv6 ignoring confirm because its server identifier does not match (expected={:?} actual={:?}", &state.config.server.identifier, s);
+                        net_debug!("DHCPv6 ignoring confirm because its server identifier does not match (expected={:?} actual={:?})", &state.config.server.identifier, s);
                         return;
                     }
                     None => {
@@ -616,7 +728,8 @@ impl<'a> Socket<'a> {
                     self.max_lease_duration,
                     state.config.server.clone(),
                     &ia_na,
-                    &state.prefix_info
+                    &state.prefix_info,
+                    self.ra_captive_portal.as_ref(),
                 ) {
                     state.renew_at = renew_at;
                     state.expires_at = expires_at;
@@ -636,7 +749,13 @@ impl<'a> Socket<'a> {
                 }
             }
             (ClientState::DhcpRenewing(_), Dhcpv6MessageType::Decline) => {
-                if !self.ignore_naks {
+                if self.ignore_naks {
+                    net_debug!("DHCPv6 ignoring decline from {}", src_ip);
+                } else {
+                    net_info!(
+                        "DHCPv6 renewal declined by {}, restarting discovery",
+                        src_ip
+                    );
                     self.reset();
                 }
             }
@@ -656,14 +775,23 @@ impl<'a> Socket<'a> {
         server: ServerInfo,
         ia_na: &Dhcpv6ReprIaNa,
         prefix_info: &NdiscPrefixInformation,
+        ra_captive_portal: Option<&String<DHCP_MAX_CAPTIVE_PORTAL_LEN>>,
     ) -> Option<(Config<'static>, Instant, Instant)> {
         if ia_na.addresses.is_empty() {
             net_debug!("DHCPv6 ignoring confirm because its missing addresses in the IA_NA section");
             return None;
         }
 
-        if StatusCode::Success != ia_na.status_code.as_ref().map(|s| s.status_code).unwrap_or(StatusCode::Success) {
-            net_debug!("DHCPv6 ignoring confirm its status code is not success");
+        if let Some(status) = ia_na
+            .status_code
+            .as_ref()
+            .filter(|status| status.status_code != StatusCode::Success)
+        {
+            net_debug!(
+                "DHCPv6 ignoring confirm because its IA_NA status is {} ({})",
+                status.status_code,
+                status.status_message
+            );
             return None;
         }
 
@@ -696,6 +824,10 @@ impl<'a> Socket<'a> {
             addresses,
             router: Ipv6Cidr::new(prefix_info.prefix, prefix_info.prefix_len),
             dns_servers,
+            captive_portal: match dhcp_repr.captive_portal {
+                Some(uri) => super::captive_portal(uri),
+                None => ra_captive_portal.cloned(),
+            },
             packet: None,
         };
 
@@ -740,9 +872,11 @@ impl<'a> Socket<'a> {
             ia_ta: None,
             request_options: None,
             dns_servers: None,
+            captive_portal: None,
             additional_options: &[],
         };
         dhcp_repr.add_request_option(dhcpv6::field::OPT_DNS_SERVERS);
+        dhcp_repr.add_request_option(dhcpv6::field::OPT_CAPTIVE_PORTAL);
 
         let udp_repr = UdpRepr {
             src_port: self.client_port,
@@ -755,6 +889,8 @@ impl<'a> Socket<'a> {
             next_header: IpProtocol::Udp,
             payload_len: 0, // filled right before emit
             hop_limit: 64,
+            traffic_class: 0,
+            flow_label: 0,
         };
 
         match &mut self.state {
@@ -773,6 +909,8 @@ impl<'a> Socket<'a> {
                     next_header: IpProtocol::Icmpv6,
                     payload_len: icmp_repr.buffer_len(),
                     hop_limit: 64,
+                    traffic_class: 0,
+                    flow_label: 0,
                 };
                 // send packet
                 net_debug!(
@@ -818,7 +956,7 @@ impl<'a> Socket<'a> {
                 }
 
                 if state.retry >= self.retry_config.request_retries {
-                    net_debug!("DHCPv6 request retries exceeded, restarting discovery");
+                    net_info!("DHCPv6 request retries exceeded, restarting discovery");
                     drop(dhcp_repr);
 
                     self.reset();
@@ -872,7 +1010,7 @@ impl<'a> Socket<'a> {
             }
             ClientState::DhcpRenewing(state) => {
                 if state.expires_at <= cx.now() {
-                    net_debug!("DHCPv6 lease expired");
+                    net_info!("DHCPv6 lease expired");
                     drop(dhcp_repr);
 
                     self.reset();
@@ -935,6 +1073,43 @@ impl<'a> Socket<'a> {
         }
     }
 
+    fn parse_router_config(
+        now: Instant,
+        src_addr: Ipv6Address,
+        flags: NdiscRouterFlags,
+        router_lifetime: Duration,
+        prefix_info: &Option<NdiscPrefixInformation>,
+    ) -> Option<RouterConfig> {
+        // Addresses are formed from a 64-bit interface identifier, see RFC 4862 § 5.5.3.
+        let slaac_prefix = prefix_info.as_ref().filter(|info| {
+            !flags.contains(NdiscRouterFlags::MANAGED)
+                && info.flags.contains(NdiscPrefixInfoFlags::ADDRCONF)
+                && info.prefix_len == 64
+                && info.valid_lifetime > Duration::ZERO
+                && !info.prefix.is_link_local()
+        });
+        let router = (router_lifetime > Duration::ZERO).then_some(src_addr);
+
+        let lifetime = match (router, slaac_prefix) {
+            (None, None) => return None,
+            (Some(_), None) => router_lifetime,
+            (None, Some(info)) => info.valid_lifetime,
+            (Some(_), Some(info)) => router_lifetime.min(info.valid_lifetime),
+        };
+        Some(RouterConfig {
+            router,
+            slaac_prefix: slaac_prefix.map(|info| Ipv6Cidr::new(info.prefix, info.prefix_len)),
+            expires_at: now + lifetime,
+        })
+    }
+
+    /// Return the configuration provided by the last router advertisement, if any.
+    ///
+    /// It is up to the caller to check whether it expired.
+    pub fn router_config(&self) -> Option<&RouterConfig> {
+        self.router_config.as_ref()
+    }
+
     /// Reset state and restart discovery phase.
     ///
     /// Use this to speed up acquisition of an address in a new
@@ -948,6 +1123,8 @@ impl<'a> Socket<'a> {
             retry_at: Instant::from_millis(0),
             retry: 0,
         });
+        self.ra_captive_portal = None;
+        self.router_config = None;
     }
 
     /// Query the socket for configuration changes.
@@ -959,15 +1136,18 @@ impl<'a> Socket<'a> {
             None
         } else if let ClientState::DhcpRenewing(state) = &self.state {
             self.config_changed = false;
+            let received = self
+                .receive_packet_buffer
+                .as_deref()
+                .zip(self.receive_packet_len)
+                .map(|(buffer, len)| &buffer[..len]);
             Some(Event::Configured(Config {
                 server: state.config.server.clone(),
                 addresses: state.config.addresses.clone(),
                 router: state.config.router,
                 dns_servers: state.config.dns_servers.clone(),
-                packet: self
-                    .receive_packet_buffer
-                    .as_deref()
-                    .map(Dhcpv6Packet::new_unchecked),
+                captive_portal: state.config.captive_portal.clone(),
+                packet: received.map(Dhcpv6Packet::new_unchecked),
             }))
         } else {
             self.config_changed = false;
@@ -984,6 +1164,11 @@ impl<'a> Socket<'a> {
         self.waker.wake_all();
     }
 
+    /// Return whether `poll` would yield an event.
+    pub(crate) fn has_config_changed(&self) -> bool {
+        self.config_changed
+    }
+
     /// Register a waker.
     ///
     /// The waker is woken on state changes that might affect the return value
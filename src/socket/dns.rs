@@ -1,19 +1,39 @@
 #[cfg(feature = "async")]
-use core::task::Waker;
+use core::future::Future;
+#[cfg(feature = "async")]
+use core::marker::PhantomData;
+#[cfg(feature = "async")]
+use core::ops::DerefMut;
+#[cfg(feature = "async")]
+use core::pin::Pin;
+#[cfg(feature = "async")]
+use core::task::{self, Poll, Waker};
 
 use heapless::Vec;
 use managed::ManagedSlice;
 
+#[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+use crate::iface::{nat64_embed, NAT64_PREFIX_LENS};
 use crate::socket::{Context, PollAt};
 use crate::time::{Duration, Instant};
-use crate::wire::dns::{Flags, Opcode, Packet, Question, Rcode, Record, RecordData, Repr, Type};
+use crate::wire::dns::{
+    Flags, Opcode, Packet, Question, Rcode, Record, RecordData, Repr, TsigMac, TsigRepr, Type,
+    UpdateRecord, UpdateRepr,
+};
+#[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+use crate::wire::Ipv6Cidr;
 use crate::wire::{self, IpAddress, IpProtocol, IpRepr, UdpRepr};
 
 #[cfg(feature = "async")]
 use super::WakerRegistration;
 
 pub const MAX_ADDRESS_COUNT: usize = 4;
+pub const MAX_RECORD_COUNT: usize = 4;
 pub const MAX_SERVER_COUNT: usize = 4;
+pub const MAX_SEARCH_COUNT: usize = 4;
+pub const MAX_TXT_LEN: usize = 255;
+pub const MAX_UPDATE_COUNT: usize = 4;
+pub const MAX_UPDATE_LEN: usize = 512;
 
 const DNS_PORT: u16 = 53;
 const MDNS_DNS_PORT: u16 = 5353;
@@ -22,6 +42,12 @@ const RETRANSMIT_DELAY: Duration = Duration::from_millis(1_000);
 const MAX_RETRANSMIT_DELAY: Duration = Duration::from_millis(10_000);
 const RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(10_000); // Should generally be 2-10 secs
 
+// Fits in the minimum IPv6 MTU without fragmentation, as recommended by the DNS flag day 2020.
+const DEFAULT_EDNS_UDP_PAYLOAD_SIZE: u16 = 1232;
+const DEFAULT_NDOTS: u8 = 1;
+// Recommended by RFC 8945 § 10.
+const TSIG_FUDGE: u16 = 300;
+
 #[cfg(feature = "proto-ipv6")]
 const MDNS_IPV6_ADDR: IpAddress = IpAddress::Ipv6(crate::wire::Ipv6Address([
     0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfb,
@@ -37,6 +63,9 @@ pub enum StartQueryError {
     NoFreeSlot,
     InvalidName,
     NameTooLong,
+    /// The update has more than [`MAX_UPDATE_COUNT`] changes or doesn't fit in
+    /// [`MAX_UPDATE_LEN`] octets.
+    MessageTooLong,
 }
 
 /// Error returned by [`Socket::get_query_result`]
@@ -73,6 +102,7 @@ impl DnsQuery {
 #[allow(clippy::large_enum_variant)]
 enum State {
     Pending(PendingQuery),
+    Update(PendingUpdate),
     Completed(CompletedQuery),
     Failure,
 }
@@ -89,8 +119,158 @@ struct PendingQuery {
     retransmit_at: Instant,
     delay: Duration,
 
+    /// Index of the first server asked, and number of servers given up on since.
+    first_server: usize,
     server_idx: usize,
     mdns: MulticastDns,
+    /// Whether an EDNS0 OPT record is sent; cleared if the server doesn't understand it.
+    edns: bool,
+    search: Option<SearchState>,
+
+    #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+    dual: Option<DualQuery>,
+    /// The NAT64 prefix AAAA answers are synthesized with, once an AAAA query got no
+    /// answer and was replaced with an A query.
+    #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+    dns64: Option<Ipv6Cidr>,
+}
+
+/// Progress of a query through the search list.
+#[derive(Debug)]
+struct SearchState {
+    /// The name as given, in wire format without the root label.
+    base: Vec<u8, MAX_NAME_LEN>,
+    /// Whether the name is tried as-is before the search domains rather than after.
+    as_is_first: bool,
+    /// Index of the next candidate name.
+    next: usize,
+}
+
+impl SearchState {
+    /// Return the next candidate name that fits, in wire format.
+    fn next_name(&mut self, search: &[Vec<u8, MAX_NAME_LEN>]) -> Option<Vec<u8, MAX_NAME_LEN>> {
+        while self.next <= search.len() {
+            let index = self.next;
+            self.next += 1;
+            let suffix: &[u8] = match (self.as_is_first, index) {
+                (true, 0) => &[],
+                (true, i) => &search[i - 1],
+                (false, i) if i == search.len() => &[],
+                (false, i) => &search[i],
+            };
+
+            let mut name = self.base.clone();
+            if name.extend_from_slice(suffix).is_ok() && name.push(0x00).is_ok() {
+                return Some(name);
+            }
+        }
+        None
+    }
+}
+
+impl PendingQuery {
+    /// Restart the query with the next name from the search list, if any.
+    fn next_search_name(&mut self, cx: &mut Context, search: &[Vec<u8, MAX_NAME_LEN>]) -> bool {
+        let name = match self.search.as_mut().and_then(|s| s.next_name(search)) {
+            Some(name) => name,
+            None => return false,
+        };
+        net_trace!("trying next name from the search list");
+
+        self.name = name;
+        self.txid = cx.rand().rand_u16();
+        self.next_server();
+        self.server_idx = 0;
+        #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+        if self.dns64.take().is_some() {
+            self.type_ = Type::Aaaa;
+        }
+        #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+        if let Some(dual) = &mut self.dual {
+            dual.txid = cx.rand().rand_u16();
+            dual.a_done = false;
+            dual.aaaa_done = false;
+            dual.aaaa_due = false;
+            dual.addresses.clear();
+        }
+        true
+    }
+
+    /// Give up on the current server, the query is retransmitted to the next one.
+    fn next_server(&mut self) {
+        self.server_idx += 1;
+        self.retransmit_at = Instant::ZERO;
+        self.delay = RETRANSMIT_DELAY;
+        self.timeout_at = None;
+    }
+
+    /// Replace an AAAA query that got no answer with an A query to the same server,
+    /// whose answers are synthesized into AAAA answers with `prefix`.
+    #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+    fn start_dns64(&mut self, cx: &mut Context, prefix: Ipv6Cidr) {
+        net_trace!("no AAAA records, synthesizing them with {}", prefix);
+        self.type_ = Type::A;
+        self.dns64 = Some(prefix);
+        self.txid = cx.rand().rand_u16();
+        self.retransmit_at = Instant::ZERO;
+        self.delay = RETRANSMIT_DELAY;
+        self.timeout_at = None;
+    }
+}
+
+/// State for an in-progress dynamic update.
+#[derive(Debug)]
+struct PendingUpdate {
+    /// The encoded message, which is sent as-is on every retransmission.
+    message: Vec<u8, MAX_UPDATE_LEN>,
+
+    port: u16,
+    txid: u16,
+
+    timeout_at: Option<Instant>,
+    retransmit_at: Instant,
+    delay: Duration,
+
+    first_server: usize,
+    server_idx: usize,
+}
+
+impl PendingUpdate {
+    /// Give up on the current server, the update is retransmitted to the next one.
+    fn next_server(&mut self) {
+        self.server_idx += 1;
+        self.retransmit_at = Instant::ZERO;
+        self.delay = RETRANSMIT_DELAY;
+        self.timeout_at = None;
+    }
+}
+
+/// A change to the records of a zone, see [`Socket::start_update`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Update<'a> {
+    /// Add an A or AAAA record holding `addr` to `name`.
+    Add {
+        name: &'a str,
+        addr: IpAddress,
+        ttl: u32,
+    },
+    /// Delete the A or AAAA record holding `addr` from `name`.
+    Delete { name: &'a str, addr: IpAddress },
+    /// Delete all the records of a type from `name`.
+    DeleteAll { name: &'a str, type_: Type },
+}
+
+/// How to sign a dynamic update with TSIG ([RFC 8945]).
+///
+/// [RFC 8945]: https://tools.ietf.org/html/rfc8945
+pub struct Tsig<'a> {
+    /// Name of the key shared with the server.
+    pub key_name: &'a str,
+    /// Algorithm and secret of the key.
+    pub mac: &'a dyn TsigMac,
+    /// Current time, in seconds since the UNIX epoch. Servers reject messages
+    /// signed more than five minutes away from their clock.
+    pub time_signed: u64,
 }
 
 #[derive(Debug)]
@@ -103,6 +283,77 @@ pub enum MulticastDns {
 #[derive(Debug)]
 struct CompletedQuery {
     addresses: Vec<IpAddress, MAX_ADDRESS_COUNT>,
+    records: Vec<QueryRecord, MAX_RECORD_COUNT>,
+}
+
+/// A service location, from an SRV record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    /// Name of the host providing the service, in dotted form without a trailing dot.
+    pub target: Vec<u8, MAX_NAME_LEN>,
+}
+
+/// A record other than an address returned by a query.
+///
+/// Only records of the queried type are returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryRecord {
+    Srv(SrvRecord),
+    /// The raw character-strings of a TXT record, each prefixed by its length.
+    Txt(Vec<u8, MAX_TXT_LEN>),
+    /// A domain name, in dotted form without a trailing dot.
+    Ptr(Vec<u8, MAX_NAME_LEN>),
+}
+
+/// Order of the addresses returned by a dual-stack query.
+#[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AddressPreference {
+    /// Sort addresses following the destination address selection rules of [RFC 6724 § 6]
+    /// that apply without a routing table: addresses of a family the interface has no
+    /// address for come last, the others are ordered by the default policy table precedence.
+    ///
+    /// [RFC 6724 § 6]: https://tools.ietf.org/html/rfc6724#section-6
+    #[default]
+    Rfc6724,
+    /// Return IPv4 addresses first.
+    Ipv4,
+    /// Return IPv6 addresses first.
+    Ipv6,
+}
+
+/// Whether and how AAAA answers are synthesized from A records, see [`Socket::set_dns64`].
+#[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Dns64 {
+    /// AAAA answers are never synthesized.
+    #[default]
+    Disabled,
+    /// AAAA answers are synthesized with the NAT64 prefix advertised by routers
+    /// ([RFC 8781]), if any.
+    ///
+    /// [RFC 8781]: https://tools.ietf.org/html/rfc8781
+    Advertised,
+    /// AAAA answers are synthesized with a fixed NAT64 prefix.
+    Prefix(Ipv6Cidr),
+}
+
+/// State of the AAAA half of a dual-stack query, whose A half is tracked by `PendingQuery`.
+#[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+#[derive(Debug)]
+struct DualQuery {
+    txid: u16,
+    preference: AddressPreference,
+    a_done: bool,
+    aaaa_done: bool,
+    /// The AAAA query is sent right after the A query, outside of the retransmit schedule.
+    aaaa_due: bool,
+    addresses: Vec<IpAddress, MAX_ADDRESS_COUNT>,
 }
 
 /// A handle to an in-progress DNS query.
@@ -120,6 +371,18 @@ pub struct Socket<'a> {
 
     /// The time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
     hop_limit: Option<u8>,
+    /// The traffic class (IPv6) or type of service (IPv4) value used in outgoing packets.
+    traffic_class: Option<u8>,
+    /// How long to wait for a server before asking the next one.
+    server_timeout: Duration,
+    edns_udp_payload_size: Option<u16>,
+    /// The server that answered last, which new queries ask first.
+    preferred_server: usize,
+    /// Domains appended to relative names, in wire format without the root label.
+    search: Vec<Vec<u8, MAX_NAME_LEN>, MAX_SEARCH_COUNT>,
+    ndots: u8,
+    #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+    dns64: Dns64,
 }
 
 impl<'a> Socket<'a> {
@@ -136,6 +399,14 @@ impl<'a> Socket<'a> {
             servers: Vec::from_slice(servers).unwrap(),
             queries: queries.into(),
             hop_limit: None,
+            traffic_class: None,
+            server_timeout: RETRANSMIT_TIMEOUT,
+            edns_udp_payload_size: Some(DEFAULT_EDNS_UDP_PAYLOAD_SIZE),
+            preferred_server: 0,
+            search: Vec::new(),
+            ndots: DEFAULT_NDOTS,
+            #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+            dns64: Dns64::Disabled,
         }
     }
 
@@ -146,6 +417,84 @@ impl<'a> Socket<'a> {
     /// Panics if `servers.len() > MAX_SERVER_COUNT`
     pub fn update_servers(&mut self, servers: &[IpAddress]) {
         self.servers = Vec::from_slice(servers).unwrap();
+        self.preferred_server = 0;
+    }
+
+    /// Update the search list, will replace all existing search domains
+    ///
+    /// Relative names passed to [`start_query`] are looked up with each of the domains
+    /// appended in turn, until one exists, like the `search` option of `resolv.conf`.
+    /// The list usually comes from the DHCP domain search option or an IPv6 router
+    /// advertisement.
+    ///
+    /// [`start_query`]: #method.start_query
+    ///
+    /// # Panics
+    ///
+    /// Panics if `domains.len() > MAX_SEARCH_COUNT`
+    pub fn update_search_domains(&mut self, domains: &[&str]) -> Result<(), StartQueryError> {
+        assert!(domains.len() <= MAX_SEARCH_COUNT);
+
+        let mut search = Vec::new();
+        for domain in domains {
+            let (mut name, _) = encode_name(domain)?;
+            name.pop(); // Remove terminator.
+            search.push(name).unwrap();
+        }
+        self.search = search;
+        Ok(())
+    }
+
+    /// Return the number of dots from which a name is first tried as-is.
+    ///
+    /// See also the [set_ndots](#method.set_ndots) method
+    pub fn ndots(&self) -> u8 {
+        self.ndots
+    }
+
+    /// Set the number of dots from which a name is first tried as-is.
+    ///
+    /// Names with fewer dots are first tried with the search domains appended, and
+    /// as-is last. Names with at least this many dots are tried as-is first. The
+    /// default is 1, as for the `ndots` option of `resolv.conf`.
+    pub fn set_ndots(&mut self, ndots: u8) {
+        self.ndots = ndots
+    }
+
+    /// Return how long a query waits for a server before asking the next one.
+    ///
+    /// See also the [set_server_timeout](#method.set_server_timeout) method
+    pub fn server_timeout(&self) -> Duration {
+        self.server_timeout
+    }
+
+    /// Set how long a query waits for a server before asking the next one.
+    ///
+    /// The query is retransmitted to the same server with exponential backoff in the
+    /// meantime. A server answering with SERVFAIL, REFUSED or NOTIMP is skipped right
+    /// away, and the query fails once every server was tried. New queries start with the
+    /// server that answered last, so that an unresponsive server only delays the first
+    /// lookup after it went down. The default is 10 seconds.
+    pub fn set_server_timeout(&mut self, timeout: Duration) {
+        self.server_timeout = timeout
+    }
+
+    /// Return the UDP payload size advertised in an EDNS0 OPT record, if any.
+    ///
+    /// See also the [set_edns_udp_payload_size](#method.set_edns_udp_payload_size) method
+    pub fn edns_udp_payload_size(&self) -> Option<u16> {
+        self.edns_udp_payload_size
+    }
+
+    /// Set the UDP payload size advertised in an EDNS0 OPT record ([RFC 6891]), or disable EDNS0.
+    ///
+    /// The size should match what the interface can receive. The default is 1232 octets.
+    /// Queries to a server that responds with FORMERR are retried without the OPT record.
+    /// Multicast DNS queries never carry one.
+    ///
+    /// [RFC 6891]: https://tools.ietf.org/html/rfc6891
+    pub fn set_edns_udp_payload_size(&mut self, size: Option<u16>) {
+        self.edns_udp_payload_size = size
     }
 
     /// Return the time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
@@ -175,6 +524,68 @@ impl<'a> Socket<'a> {
         self.hop_limit = hop_limit
     }
 
+    /// Return the traffic class (IPv6) or type of service (IPv4) value used in outgoing
+    /// packets.
+    ///
+    /// See also the [set_traffic_class](#method.set_traffic_class) method
+    pub fn traffic_class(&self) -> Option<u8> {
+        self.traffic_class
+    }
+
+    /// Set the traffic class (IPv6) or type of service (IPv4) value used in outgoing packets.
+    ///
+    /// For IPv4 only the DSCP, i.e. the upper six bits, is used. A socket without an
+    /// explicitly set traffic class sends packets with a traffic class of zero.
+    pub fn set_traffic_class(&mut self, traffic_class: Option<u8>) {
+        self.traffic_class = traffic_class
+    }
+
+    /// Return whether and how AAAA answers are synthesized from A records.
+    ///
+    /// See also the [set_dns64](#method.set_dns64) method
+    #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+    pub fn dns64(&self) -> Dns64 {
+        self.dns64
+    }
+
+    /// Set whether and how AAAA answers are synthesized from A records, as a DNS64
+    /// server does ([RFC 6147]).
+    ///
+    /// This lets an IPv6-only host reach IPv4-only services through a NAT64 gateway
+    /// when the network provides no DNS64 server. When an AAAA query gets an answer
+    /// without AAAA records, the name is queried for A records, and their addresses
+    /// are embedded in the NAT64 prefix ([RFC 6052]) to form the result. A dual-stack
+    /// query without AAAA records returns the synthesized addresses after the IPv4 ones.
+    /// Names that do not exist are not queried again. The default is
+    /// [`Dns64::Disabled`].
+    ///
+    /// # Panics
+    /// This function panics if a prefix is given that is not 32, 40, 48, 56, 64 or 96
+    /// bits long.
+    ///
+    /// [RFC 6147]: https://tools.ietf.org/html/rfc6147
+    /// [RFC 6052]: https://tools.ietf.org/html/rfc6052#section-2.2
+    #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+    pub fn set_dns64(&mut self, dns64: Dns64) {
+        if let Dns64::Prefix(prefix) = dns64 {
+            assert!(
+                NAT64_PREFIX_LENS.contains(&prefix.prefix_len()),
+                "invalid NAT64 prefix length"
+            );
+        }
+        self.dns64 = dns64;
+    }
+
+    /// Return the NAT64 prefix AAAA answers are synthesized with, if any.
+    #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+    fn dns64_prefix(&self, cx: &Context) -> Option<Ipv6Cidr> {
+        match self.dns64 {
+            Dns64::Disabled => None,
+            Dns64::Advertised => cx.nat64_prefix(),
+            Dns64::Prefix(prefix) => Some(prefix),
+        }
+    }
+
     fn find_free_query(&mut self) -> Option<QueryHandle> {
         for (i, q) in self.queries.iter().enumerate() {
             if q.is_none() {
@@ -196,60 +607,68 @@ impl<'a> Socket<'a> {
     /// Start a query.
     ///
     /// `name` is specified in human-friendly format, such as `"rust-lang.org"`.
-    /// It accepts names both with and without trailing dot, and they're treated
-    /// the same (there's no support for DNS search path).
+    /// A name with a trailing dot is absolute. Other names are looked up in the
+    /// domains of the search list too, see [`update_search_domains`].
+    ///
+    /// [`update_search_domains`]: #method.update_search_domains
     pub fn start_query(
         &mut self,
         cx: &mut Context,
         name: &str,
         query_type: Type,
     ) -> Result<QueryHandle, StartQueryError> {
-        let mut name = name.as_bytes();
-
-        if name.is_empty() {
-            net_trace!("invalid name: zero length");
-            return Err(StartQueryError::InvalidName);
-        }
-
-        // Remove trailing dot, if any
-        if name[name.len() - 1] == b'.' {
-            name = &name[..name.len() - 1];
-        }
-
-        let mut raw_name: Vec<u8, MAX_NAME_LEN> = Vec::new();
-
-        let mut mdns = MulticastDns::Disabled;
-        #[cfg(feature = "socket-mdns")]
-        if name.split(|&c| c == b'.').last().unwrap() == b"local" {
-            net_trace!("Starting a mDNS query");
-            mdns = MulticastDns::Enabled;
+        let (raw_name, mdns) = encode_name(name)?;
+        let relative = !name.ends_with('.') && matches!(mdns, MulticastDns::Disabled);
+        let handle = self.start_query_raw(cx, &raw_name, query_type, mdns)?;
+        if !relative || self.search.is_empty() {
+            return Ok(handle);
         }
 
-        for s in name.split(|&c| c == b'.') {
-            if s.len() > 63 {
-                net_trace!("invalid name: too long label");
-                return Err(StartQueryError::InvalidName);
-            }
-            if s.is_empty() {
-                net_trace!("invalid name: zero length label");
-                return Err(StartQueryError::InvalidName);
+        let dots = name.bytes().filter(|&c| c == b'.').count();
+        let mut search = SearchState {
+            base: Vec::from_slice(&raw_name[..raw_name.len() - 1]).unwrap(),
+            as_is_first: dots >= self.ndots as usize,
+            next: 0,
+        };
+        let first_name = search.next_name(&self.search);
+        if let State::Pending(pq) = &mut self.queries[handle.0].as_mut().unwrap().state {
+            if let Some(first_name) = first_name {
+                pq.name = first_name;
             }
-
-            // Push label
-            raw_name
-                .push(s.len() as u8)
-                .map_err(|_| StartQueryError::NameTooLong)?;
-            raw_name
-                .extend_from_slice(s)
-                .map_err(|_| StartQueryError::NameTooLong)?;
+            pq.search = Some(search);
         }
+        Ok(handle)
+    }
 
-        // Push terminator.
-        raw_name
-            .push(0x00)
-            .map_err(|_| StartQueryError::NameTooLong)?;
-
-        self.start_query_raw(cx, &raw_name, query_type, mdns)
+    /// Start A and AAAA queries for a name at the same time.
+    ///
+    /// The query completes once both have been answered. [`get_query_result`] then returns
+    /// the addresses of both families, ordered according to `preference`; at most half
+    /// of [`MAX_ADDRESS_COUNT`] addresses are kept for each family. This is the basis for
+    /// connecting Happy-Eyeballs-style ([RFC 8305]).
+    ///
+    /// [`get_query_result`]: #method.get_query_result
+    /// [RFC 8305]: https://tools.ietf.org/html/rfc8305
+    #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+    pub fn start_dual_query(
+        &mut self,
+        cx: &mut Context,
+        name: &str,
+        preference: AddressPreference,
+    ) -> Result<QueryHandle, StartQueryError> {
+        let handle = self.start_query(cx, name, Type::A)?;
+        let txid = cx.rand().rand_u16();
+        if let State::Pending(pq) = &mut self.queries[handle.0].as_mut().unwrap().state {
+            pq.dual = Some(DualQuery {
+                txid,
+                preference,
+                a_done: false,
+                aaaa_done: false,
+                aaaa_due: false,
+                addresses: Vec::new(),
+            });
+        }
+        Ok(handle)
     }
 
     /// Start a query with a raw (wire-format) DNS name.
@@ -264,6 +683,11 @@ impl<'a> Socket<'a> {
         mdns: MulticastDns,
     ) -> Result<QueryHandle, StartQueryError> {
         let handle = self.find_free_query().ok_or(StartQueryError::NoFreeSlot)?;
+        let (first_server, edns) = match mdns {
+            MulticastDns::Disabled => (self.preferred_server, self.edns_udp_payload_size.is_some()),
+            #[cfg(feature = "socket-mdns")]
+            MulticastDns::Enabled => (0, false),
+        };
 
         self.queries[handle.0] = Some(DnsQuery {
             state: State::Pending(PendingQuery {
@@ -274,8 +698,155 @@ impl<'a> Socket<'a> {
                 delay: RETRANSMIT_DELAY,
                 timeout_at: None,
                 retransmit_at: Instant::ZERO,
+                first_server,
                 server_idx: 0,
                 mdns,
+                edns,
+                search: None,
+                #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+                dual: None,
+                #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+                dns64: None,
+            }),
+            #[cfg(feature = "async")]
+            waker: WakerRegistration::new(),
+        });
+        Ok(handle)
+    }
+
+    /// Start a reverse lookup, querying the PTR record of an address.
+    ///
+    /// The name is built under `in-addr.arpa` or `ip6.arpa`, as described in
+    /// [RFC 1035 § 3.5] and [RFC 3596 § 2.5].
+    ///
+    /// [RFC 1035 § 3.5]: https://tools.ietf.org/html/rfc1035#section-3.5
+    /// [RFC 3596 § 2.5]: https://tools.ietf.org/html/rfc3596#section-2.5
+    pub fn start_reverse_query(
+        &mut self,
+        cx: &mut Context,
+        addr: IpAddress,
+    ) -> Result<QueryHandle, StartQueryError> {
+        let mut raw_name: Vec<u8, MAX_NAME_LEN> = Vec::new();
+        match addr {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(addr) => {
+                for octet in addr.as_bytes().iter().rev() {
+                    push_decimal_label(&mut raw_name, *octet);
+                }
+                raw_name
+                    .extend_from_slice(b"\x07in-addr\x04arpa\x00")
+                    .unwrap();
+            }
+            #[cfg(feature = "proto-ipv6")]
+            IpAddress::Ipv6(addr) => {
+                const HEX: &[u8; 16] = b"0123456789abcdef";
+                for octet in addr.as_bytes().iter().rev() {
+                    for nibble in [octet & 0xf, octet >> 4] {
+                        raw_name.push(1).unwrap();
+                        raw_name.push(HEX[nibble as usize]).unwrap();
+                    }
+                }
+                raw_name.extend_from_slice(b"\x03ip6\x04arpa\x00").unwrap();
+            }
+        }
+
+        self.start_query_raw(cx, &raw_name, Type::Ptr, MulticastDns::Disabled)
+    }
+
+    /// Start a dynamic update of the records of a zone ([RFC 2136]).
+    ///
+    /// The update is sent to the servers of the socket in turn, like a query, so they
+    /// should include the primary server of `zone`. Most servers only accept updates
+    /// signed with a key, given in `tsig`; the signature of the response is not checked.
+    /// [`get_query_result`] returns an empty address list once a server applied the
+    /// changes, and fails if the server refused them.
+    ///
+    /// To register the address an interface got from DHCP or SLAAC, replacing any
+    /// previous one, delete all the records of its type before adding it.
+    ///
+    /// [RFC 2136]: https://tools.ietf.org/html/rfc2136
+    /// [`get_query_result`]: #method.get_query_result
+    pub fn start_update(
+        &mut self,
+        cx: &mut Context,
+        zone: &str,
+        updates: &[Update],
+        tsig: Option<&Tsig>,
+    ) -> Result<QueryHandle, StartQueryError> {
+        if updates.len() > MAX_UPDATE_COUNT {
+            return Err(StartQueryError::MessageTooLong);
+        }
+
+        let (zone, _) = encode_name(zone)?;
+        // Names are encoded back to back, and sliced once all are in place.
+        let mut names: Vec<u8, MAX_UPDATE_LEN> = Vec::new();
+        for update in updates {
+            let (Update::Add { name, .. }
+            | Update::Delete { name, .. }
+            | Update::DeleteAll { name, .. }) = update;
+            let (name, _) = encode_name(name)?;
+            names
+                .extend_from_slice(&name)
+                .map_err(|_| StartQueryError::MessageTooLong)?;
+        }
+
+        let mut records: Vec<UpdateRecord, MAX_UPDATE_COUNT> = Vec::new();
+        let mut rest = &names[..];
+        for update in updates {
+            let (name, tail) = rest.split_at(name_len(rest));
+            rest = tail;
+            let record = match *update {
+                Update::Add { addr, ttl, .. } => UpdateRecord::Add(Record {
+                    name,
+                    ttl,
+                    data: address_data(addr),
+                }),
+                Update::Delete { addr, .. } => UpdateRecord::DeleteRecord {
+                    name,
+                    data: address_data(addr),
+                },
+                Update::DeleteAll { type_, .. } => UpdateRecord::DeleteRrset { name, type_ },
+            };
+            records.push(record).unwrap();
+        }
+
+        let repr = UpdateRepr {
+            transaction_id: cx.rand().rand_u16(),
+            zone: &zone,
+            updates: &records,
+        };
+        let mut buffer = [0u8; MAX_UPDATE_LEN];
+        let mut len = repr.buffer_len();
+        if len > buffer.len() {
+            return Err(StartQueryError::MessageTooLong);
+        }
+        repr.emit(&mut Packet::new_unchecked(&mut buffer[..len]));
+
+        if let Some(tsig) = tsig {
+            let (mut key_name, _) = encode_name(tsig.key_name)?;
+            key_name.make_ascii_lowercase();
+            let tsig_repr = TsigRepr {
+                key_name: &key_name,
+                time_signed: tsig.time_signed,
+                fudge: TSIG_FUDGE,
+            };
+            if len + tsig_repr.buffer_len(tsig.mac) > buffer.len() {
+                return Err(StartQueryError::MessageTooLong);
+            }
+            len = tsig_repr.emit(tsig.mac, &mut buffer, len);
+        }
+
+        let handle = self.find_free_query().ok_or(StartQueryError::NoFreeSlot)?;
+        self.queries[handle.0] = Some(DnsQuery {
+            state: State::Update(PendingUpdate {
+                message: Vec::from_slice(&buffer[..len]).unwrap(),
+                port: cx.rand().rand_source_port(),
+                txid: repr.transaction_id,
+                timeout_at: None,
+                retransmit_at: Instant::ZERO,
+                delay: RETRANSMIT_DELAY,
+                first_server: self.preferred_server,
+                server_idx: 0,
             }),
             #[cfg(feature = "async")]
             waker: WakerRegistration::new(),
@@ -297,7 +868,7 @@ impl<'a> Socket<'a> {
         let q = slot.as_mut().unwrap();
         match &mut q.state {
             // Query is not done yet.
-            State::Pending(_) => Err(GetQueryResultError::Pending),
+            State::Pending(_) | State::Update(_) => Err(GetQueryResultError::Pending),
             // Query is done
             State::Completed(q) => {
                 let res = q.addresses.clone();
@@ -311,6 +882,35 @@ impl<'a> Socket<'a> {
         }
     }
 
+    /// Get the SRV, TXT or PTR records returned by a query.
+    ///
+    /// If the query is completed, the query slot is automatically freed. Addresses
+    /// returned by the query are discarded, use [`get_query_result`] for A and AAAA queries.
+    ///
+    /// [`get_query_result`]: #method.get_query_result
+    ///
+    /// # Panics
+    /// Panics if the QueryHandle corresponds to a free slot.
+    pub fn get_query_records(
+        &mut self,
+        handle: QueryHandle,
+    ) -> Result<Vec<QueryRecord, MAX_RECORD_COUNT>, GetQueryResultError> {
+        let slot = &mut self.queries[handle.0];
+        let q = slot.as_mut().unwrap();
+        match &mut q.state {
+            State::Pending(_) | State::Update(_) => Err(GetQueryResultError::Pending),
+            State::Completed(q) => {
+                let res = core::mem::take(&mut q.records);
+                *slot = None;
+                Ok(res)
+            }
+            State::Failure => {
+                *slot = None;
+                Err(GetQueryResultError::Failed)
+            }
+        }
+    }
+
     /// Cancels a query, freeing the slot.
     ///
     /// # Panics
@@ -371,7 +971,7 @@ impl<'a> Socket<'a> {
 
     pub(crate) fn process(
         &mut self,
-        _cx: &mut Context,
+        cx: &mut Context,
         ip_repr: &IpRepr,
         udp_repr: &UdpRepr,
         payload: &[u8],
@@ -394,32 +994,66 @@ impl<'a> Socket<'a> {
                 return;
             }
         };
-        if p.opcode() != Opcode::Query {
-            net_trace!("unwanted opcode {:?}", p.opcode());
-            return;
-        }
-
         if !p.flags().contains(Flags::RESPONSE) {
             net_trace!("packet doesn't have response bit set");
             return;
         }
 
+        match p.opcode() {
+            Opcode::Query => {}
+            Opcode::Update => return self.process_update(&p, udp_repr),
+            opcode => {
+                net_trace!("unwanted opcode {:?}", opcode);
+                return;
+            }
+        }
+
         if p.question_count() != 1 {
             net_trace!("bad question count {:?}", p.question_count());
             return;
         }
 
+        #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+        let dns64_prefix = self.dns64_prefix(cx);
+
         // Find pending query
         for q in self.queries.iter_mut().flatten() {
             if let State::Pending(pq) = &mut q.state {
-                if udp_repr.dst_port != pq.port || p.transaction_id() != pq.txid {
+                if udp_repr.dst_port != pq.port {
                     continue;
                 }
-
-                if p.rcode() == Rcode::NXDomain {
-                    net_trace!("rcode NXDomain");
-                    q.set_state(State::Failure);
+                let type_ = if p.transaction_id() == pq.txid {
+                    pq.type_
+                } else {
+                    #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+                    match &pq.dual {
+                        Some(dual) if p.transaction_id() == dual.txid => Type::Aaaa,
+                        _ => continue,
+                    }
+                    #[cfg(not(all(feature = "proto-ipv4", feature = "proto-ipv6")))]
                     continue;
+                };
+
+                match p.rcode() {
+                    Rcode::NXDomain => {
+                        net_trace!("rcode NXDomain");
+                        if !pq.next_search_name(cx, &self.search) {
+                            q.set_state(State::Failure);
+                        }
+                        return;
+                    }
+                    Rcode::FormErr if pq.edns => {
+                        net_trace!("rcode FormErr, retrying without EDNS0");
+                        pq.edns = false;
+                        pq.retransmit_at = Instant::ZERO;
+                        return;
+                    }
+                    rcode @ (Rcode::FormErr | Rcode::ServFail | Rcode::NotImp | Rcode::Refused) => {
+                        net_trace!("rcode {:?}, trying next server", rcode);
+                        pq.next_server();
+                        return;
+                    }
+                    _ => {}
                 }
 
                 let payload = p.payload();
@@ -431,7 +1065,7 @@ impl<'a> Socket<'a> {
                     }
                 };
 
-                if question.type_ != pq.type_ {
+                if question.type_ != type_ {
                     net_trace!("question type mismatch");
                     return;
                 }
@@ -448,7 +1082,18 @@ impl<'a> Socket<'a> {
                     }
                 }
 
-                let mut addresses = Vec::new();
+                if let MulticastDns::Disabled = pq.mdns {
+                    if !self.servers.is_empty() {
+                        self.preferred_server =
+                            (pq.first_server + pq.server_idx) % self.servers.len();
+                    }
+                }
+
+                // CNAMEs are followed on a copy, the name is still needed by the
+                // other half of a dual-stack query.
+                let mut name: Vec<u8, MAX_NAME_LEN> = pq.name.clone();
+                let mut addresses: Vec<IpAddress, MAX_ADDRESS_COUNT> = Vec::new();
+                let mut records = Vec::new();
 
                 for _ in 0..p.answer_record_count() {
                     let (payload2, r) = match Record::parse(payload) {
@@ -460,7 +1105,7 @@ impl<'a> Socket<'a> {
                     };
                     payload = payload2;
 
-                    match eq_names(p.parse_name(r.name), p.parse_name(&pq.name)) {
+                    match eq_names(p.parse_name(r.name), p.parse_name(&name)) {
                         Ok(true) => {}
                         Ok(false) => {
                             net_trace!("answer name mismatch: {:?}", r);
@@ -487,8 +1132,8 @@ impl<'a> Socket<'a> {
                                 net_trace!("too many addresses in response, ignoring {:?}", addr);
                             }
                         }
-                        RecordData::Cname(name) => {
-                            net_trace!("CNAME: {:?}", name);
+                        RecordData::Cname(cname) => {
+                            net_trace!("CNAME: {:?}", cname);
 
                             // When faced with a CNAME, recursive resolvers are supposed to
                             // resolve the CNAME and append the results for it.
@@ -497,21 +1142,143 @@ impl<'a> Socket<'a> {
                             // records for the CNAME when we parse them later.
                             // I believe it's mandatory the CNAME results MUST come *after* in the
                             // packet, so it's enough to do one linear pass over it.
-                            if copy_name(&mut pq.name, p.parse_name(name)).is_err() {
+                            if copy_name(&mut name, p.parse_name(cname)).is_err() {
                                 net_trace!("dns answer cname malformed");
                                 return;
                             }
                         }
-                        RecordData::Other(type_, data) => {
-                            net_trace!("unknown: {:?} {:?}", type_, data)
+                        data if data.type_() == type_ => {
+                            net_trace!("{:?}", data);
+                            let record = match data {
+                                RecordData::Srv {
+                                    priority,
+                                    weight,
+                                    port,
+                                    target,
+                                } => {
+                                    let mut name = Vec::new();
+                                    if copy_text_name(&mut name, p.parse_name(target)).is_err() {
+                                        net_trace!("dns answer srv target malformed");
+                                        continue;
+                                    }
+                                    QueryRecord::Srv(SrvRecord {
+                                        priority,
+                                        weight,
+                                        port,
+                                        target: name,
+                                    })
+                                }
+                                RecordData::Txt(data) => match Vec::from_slice(data) {
+                                    Ok(data) => QueryRecord::Txt(data),
+                                    Err(_) => {
+                                        net_trace!("dns answer txt too long");
+                                        continue;
+                                    }
+                                },
+                                RecordData::Ptr(name) => {
+                                    let mut text = Vec::new();
+                                    if copy_text_name(&mut text, p.parse_name(name)).is_err() {
+                                        net_trace!("dns answer ptr malformed");
+                                        continue;
+                                    }
+                                    QueryRecord::Ptr(text)
+                                }
+                                other => {
+                                    net_trace!("unknown: {:?}", other);
+                                    continue;
+                                }
+                            };
+                            if records.push(record).is_err() {
+                                net_trace!("too many records in response, ignoring");
+                            }
+                        }
+                        other => {
+                            net_trace!("unknown: {:?}", other)
                         }
                     }
                 }
 
-                q.set_state(if addresses.is_empty() {
+                #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+                if let Some(prefix) = pq.dns64 {
+                    for addr in addresses.iter_mut() {
+                        if let IpAddress::Ipv4(ipv4_addr) = *addr {
+                            *addr = nat64_embed(prefix, ipv4_addr).into();
+                        }
+                    }
+                }
+
+                #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+                if let Some(dual) = &mut pq.dual {
+                    if type_ == Type::Aaaa {
+                        dual.aaaa_done = true;
+                    } else {
+                        dual.a_done = true;
+                    }
+                    for addr in addresses {
+                        let same_family = dual
+                            .addresses
+                            .iter()
+                            .filter(|a| a.version() == addr.version())
+                            .count();
+                        if same_family < MAX_ADDRESS_COUNT / 2 {
+                            dual.addresses.push(addr).unwrap();
+                        }
+                    }
+                    if !(dual.a_done && dual.aaaa_done) {
+                        return;
+                    }
+
+                    let mut addresses = core::mem::take(&mut dual.addresses);
+                    let preference = dual.preference;
+                    if let Some(prefix) = dns64_prefix {
+                        if !addresses.iter().any(|a| matches!(a, IpAddress::Ipv6(_))) {
+                            for i in 0..addresses.len() {
+                                if let IpAddress::Ipv4(ipv4_addr) = addresses[i] {
+                                    // NOTE(unwrap): there are at most half as many IPv4
+                                    // addresses as there is room for, see above.
+                                    addresses
+                                        .push(nat64_embed(prefix, ipv4_addr).into())
+                                        .unwrap();
+                                }
+                            }
+                        }
+                    }
+                    if addresses.is_empty() && pq.next_search_name(cx, &self.search) {
+                        return;
+                    }
+                    sort_addresses(cx, &mut addresses, preference);
+                    q.set_state(if addresses.is_empty() {
+                        State::Failure
+                    } else {
+                        State::Completed(CompletedQuery {
+                            addresses,
+                            records: Vec::new(),
+                        })
+                    });
+                    return;
+                }
+
+                // An answer without records for an existing name is the cue to synthesize.
+                #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+                if let (true, Some(prefix)) = (
+                    addresses.is_empty() && type_ == Type::Aaaa && p.rcode() == Rcode::NoError,
+                    dns64_prefix,
+                ) {
+                    pq.start_dns64(cx, prefix);
+                    return;
+                }
+
+                if addresses.is_empty()
+                    && records.is_empty()
+                    && pq.next_search_name(cx, &self.search)
+                {
+                    return;
+                }
+
+                q.set_state(if addresses.is_empty() && records.is_empty() {
                     State::Failure
                 } else {
-                    State::Completed(CompletedQuery { addresses })
+                    State::Completed(CompletedQuery { addresses, records })
                 });
 
                 // If we get here, packet matched the current query, stop processing.
@@ -523,13 +1290,101 @@ impl<'a> Socket<'a> {
         net_trace!("no query matched");
     }
 
+    fn process_update(&mut self, p: &Packet<&[u8]>, udp_repr: &UdpRepr) {
+        for q in self.queries.iter_mut().flatten() {
+            if let State::Update(pu) = &mut q.state {
+                if udp_repr.dst_port != pu.port || p.transaction_id() != pu.txid {
+                    continue;
+                }
+
+                match p.rcode() {
+                    Rcode::NoError => {
+                        net_trace!("update applied");
+                        q.set_state(State::Completed(CompletedQuery {
+                            addresses: Vec::new(),
+                            records: Vec::new(),
+                        }));
+                    }
+                    rcode @ (Rcode::ServFail | Rcode::NotImp | Rcode::Refused) => {
+                        net_trace!("rcode {:?}, trying next server", rcode);
+                        pu.next_server();
+                    }
+                    rcode => {
+                        net_trace!("update rejected with rcode {:?}", rcode);
+                        q.set_state(State::Failure);
+                    }
+                }
+                return;
+            }
+        }
+
+        net_trace!("no update matched");
+    }
+
     pub(crate) fn dispatch<F, E>(&mut self, cx: &mut Context, emit: F) -> Result<(), E>
     where
         F: FnOnce(&mut Context, (IpRepr, UdpRepr, &[u8])) -> Result<(), E>,
     {
         let hop_limit = self.hop_limit.unwrap_or(64);
+        let traffic_class = self.traffic_class;
 
         for q in self.queries.iter_mut().flatten() {
+            if let State::Update(pu) = &mut q.state {
+                let timeout = *pu.timeout_at.get_or_insert(cx.now() + self.server_timeout);
+                if timeout <= cx.now() {
+                    pu.next_server();
+                    pu.timeout_at = Some(cx.now() + self.server_timeout);
+                }
+                if pu.server_idx >= self.servers.len() {
+                    net_trace!("already tried all servers.");
+                    q.set_state(State::Failure);
+                    continue;
+                }
+                if pu.retransmit_at > cx.now() {
+                    continue;
+                }
+
+                let server = self.servers[(pu.first_server + pu.server_idx) % self.servers.len()];
+                if server.is_unspecified() {
+                    net_trace!("invalid unspecified DNS server addr.");
+                    q.set_state(State::Failure);
+                    continue;
+                }
+
+                let udp_repr = UdpRepr {
+                    src_port: pu.port,
+                    dst_port: DNS_PORT,
+                };
+                let Some(src_addr) = cx.get_source_address(server) else {
+                    net_trace!("no source address for DNS server {}.", server);
+                    q.set_state(State::Failure);
+                    continue;
+                };
+                let mut ip_repr = IpRepr::new(
+                    src_addr,
+                    server,
+                    IpProtocol::Udp,
+                    udp_repr.header_len() + pu.message.len(),
+                    hop_limit,
+                );
+                if let Some(traffic_class) = traffic_class {
+                    ip_repr.set_traffic_class(traffic_class);
+                }
+
+                net_trace!(
+                    "sending update of {} octets to {} from port {}",
+                    pu.message.len(),
+                    server,
+                    pu.port
+                );
+
+                emit(cx, (ip_repr, udp_repr, &pu.message))?;
+
+                pu.retransmit_at = cx.now() + pu.delay;
+                pu.delay = MAX_RETRANSMIT_DELAY.min(pu.delay * 2);
+                return Ok(());
+            }
+
             if let State::Pending(pq) = &mut q.state {
                 // As per RFC 6762 any DNS query ending in .local. MUST be sent as mdns
                 // so we internally overwrite the servers for any of those queries
@@ -548,20 +1403,16 @@ impl<'a> Socket<'a> {
                 let timeout = if let Some(timeout) = pq.timeout_at {
                     timeout
                 } else {
-                    let v = cx.now() + RETRANSMIT_TIMEOUT;
+                    let v = cx.now() + self.server_timeout;
                     pq.timeout_at = Some(v);
                     v
                 };
 
                 // Check timeout
-                if timeout < cx.now() {
-                    // DNS timeout
-                    pq.timeout_at = Some(cx.now() + RETRANSMIT_TIMEOUT);
-                    pq.retransmit_at = Instant::ZERO;
-                    pq.delay = RETRANSMIT_DELAY;
-
-                    // Try next server. We check below whether we've tried all servers.
-                    pq.server_idx += 1;
+                if timeout <= cx.now() {
+                    // DNS timeout. Try next server, we check below whether we've tried all servers.
+                    pq.next_server();
+                    pq.timeout_at = Some(cx.now() + self.server_timeout);
                 }
                 // Check if we've run out of servers to try.
                 if pq.server_idx >= servers.len() {
@@ -570,26 +1421,49 @@ impl<'a> Socket<'a> {
                     continue;
                 }
 
+                let server = servers[(pq.first_server + pq.server_idx) % servers.len()];
+
                 // Check so the IP address is valid
-                if servers[pq.server_idx].is_unspecified() {
+                if server.is_unspecified() {
                     net_trace!("invalid unspecified DNS server addr.");
                     q.set_state(State::Failure);
                     continue;
                 }
 
-                if pq.retransmit_at > cx.now() {
+                #[cfg_attr(
+                    not(all(feature = "proto-ipv4", feature = "proto-ipv6")),
+                    allow(unused_mut)
+                )]
+                let (mut txid, mut type_, mut retransmit) = (pq.txid, pq.type_, true);
+                #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+                if let Some(dual) = &mut pq.dual {
+                    if dual.aaaa_due {
+                        // Follow up on the A query that was just sent.
+                        dual.aaaa_due = false;
+                        (txid, type_, retransmit) = (dual.txid, Type::Aaaa, false);
+                    } else if pq.retransmit_at <= cx.now() {
+                        if dual.a_done {
+                            (txid, type_) = (dual.txid, Type::Aaaa);
+                        } else {
+                            dual.aaaa_due = !dual.aaaa_done;
+                        }
+                    }
+                }
+
+                if retransmit && pq.retransmit_at > cx.now() {
                     // query is waiting for retransmit
                     continue;
                 }
 
                 let repr = Repr {
-                    transaction_id: pq.txid,
+                    transaction_id: txid,
                     flags: Flags::RECURSION_DESIRED,
                     opcode: Opcode::Query,
                     question: Question {
                         name: &pq.name,
-                        type_: pq.type_,
+                        type_,
                     },
+                    edns_udp_payload_size: self.edns_udp_payload_size.filter(|_| pq.edns),
                 };
 
                 let mut payload = [0u8; 512];
@@ -607,15 +1481,18 @@ impl<'a> Socket<'a> {
                     dst_port,
                 };
 
-                let dst_addr = servers[pq.server_idx];
+                let dst_addr = server;
                 let src_addr = cx.get_source_address(dst_addr).unwrap(); // TODO remove unwrap
-                let ip_repr = IpRepr::new(
+                let mut ip_repr = IpRepr::new(
                     src_addr,
                     dst_addr,
                     IpProtocol::Udp,
                     udp_repr.header_len() + payload.len(),
                     hop_limit,
                 );
+                if let Some(traffic_class) = traffic_class {
+                    ip_repr.set_traffic_class(traffic_class);
+                }
 
                 net_trace!(
                     "sending {} octets to {} from port {}",
@@ -626,8 +1503,10 @@ impl<'a> Socket<'a> {
 
                 emit(cx, (ip_repr, udp_repr, payload))?;
 
-                pq.retransmit_at = cx.now() + pq.delay;
-                pq.delay = MAX_RETRANSMIT_DELAY.min(pq.delay * 2);
+                if retransmit {
+                    pq.retransmit_at = cx.now() + pq.delay;
+                    pq.delay = MAX_RETRANSMIT_DELAY.min(pq.delay * 2);
+                }
 
                 return Ok(());
             }
@@ -642,7 +1521,19 @@ impl<'a> Socket<'a> {
             .iter()
             .flatten()
             .filter_map(|q| match &q.state {
-                State::Pending(pq) => Some(PollAt::Time(pq.retransmit_at)),
+                #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+                State::Pending(PendingQuery {
+                    dual: Some(DualQuery { aaaa_due: true, .. }),
+                    ..
+                }) => Some(PollAt::Now),
+                State::Pending(pq) => Some(PollAt::Time(match pq.timeout_at {
+                    Some(timeout_at) => pq.retransmit_at.min(timeout_at),
+                    None => pq.retransmit_at,
+                })),
+                State::Update(pu) => Some(PollAt::Time(match pu.timeout_at {
+                    Some(timeout_at) => pu.retransmit_at.min(timeout_at),
+                    None => pu.retransmit_at,
+                })),
                 State::Completed(_) => None,
                 State::Failure => None,
             })
@@ -651,7 +1542,7 @@ impl<'a> Socket<'a> {
     }
 }
 
-fn eq_names<'a>(
+pub(super) fn eq_names<'a>(
     mut a: impl Iterator<Item = wire::Result<&'a [u8]>>,
     mut b: impl Iterator<Item = wire::Result<&'a [u8]>>,
 ) -> wire::Result<bool> {
@@ -668,9 +1559,9 @@ fn eq_names<'a>(
             (None, _) => return Ok(false),
             (_, None) => return Ok(false),
 
-            // Got two labels, check if they're equal
+            // Got two labels, check if they're equal. Names are case-insensitive.
             (Some(Ok(la)), Some(Ok(lb))) => {
-                if la != lb {
+                if !la.eq_ignore_ascii_case(lb) {
                     return Ok(false);
                 }
             }
@@ -678,7 +1569,7 @@ fn eq_names<'a>(
     }
 }
 
-fn copy_name<'a, const N: usize>(
+pub(super) fn copy_name<'a, const N: usize>(
     dest: &mut Vec<u8, N>,
     name: impl Iterator<Item = wire::Result<&'a [u8]>>,
 ) -> Result<(), wire::Error> {
@@ -695,3 +1586,674 @@ fn copy_name<'a, const N: usize>(
 
     Ok(())
 }
+
+/// Convert a human-friendly name to wire format, and find out whether it must be
+/// resolved with multicast DNS.
+fn encode_name(name: &str) -> Result<(Vec<u8, MAX_NAME_LEN>, MulticastDns), StartQueryError> {
+    let mut name = name.as_bytes();
+
+    if name.is_empty() {
+        net_trace!("invalid name: zero length");
+        return Err(StartQueryError::InvalidName);
+    }
+
+    // Remove trailing dot, if any
+    if name[name.len() - 1] == b'.' {
+        name = &name[..name.len() - 1];
+    }
+
+    let mut raw_name: Vec<u8, MAX_NAME_LEN> = Vec::new();
+
+    let mut mdns = MulticastDns::Disabled;
+    #[cfg(feature = "socket-mdns")]
+    if name.split(|&c| c == b'.').last().unwrap() == b"local" {
+        net_trace!("Starting a mDNS query");
+        mdns = MulticastDns::Enabled;
+    }
+
+    for s in name.split(|&c| c == b'.') {
+        if s.len() > 63 {
+            net_trace!("invalid name: too long label");
+            return Err(StartQueryError::InvalidName);
+        }
+        if s.is_empty() {
+            net_trace!("invalid name: zero length label");
+            return Err(StartQueryError::InvalidName);
+        }
+
+        // Push label
+        raw_name
+            .push(s.len() as u8)
+            .map_err(|_| StartQueryError::NameTooLong)?;
+        raw_name
+            .extend_from_slice(s)
+            .map_err(|_| StartQueryError::NameTooLong)?;
+    }
+
+    // Push terminator.
+    raw_name
+        .push(0x00)
+        .map_err(|_| StartQueryError::NameTooLong)?;
+
+    Ok((raw_name, mdns))
+}
+
+/// Start a query and return a future that resolves to its addresses.
+///
+/// `socket` is called whenever the future needs the DNS socket, typically to borrow
+/// it out of a `RefCell` shared with the task polling the interface, e.g.
+/// `|| RefMut::map(sockets.borrow_mut(), |s| s.get_mut::<dns::Socket>(handle))`.
+/// See [`Socket::start_query`] for the meaning of `name`.
+#[cfg(feature = "async")]
+pub fn get_host_by_name<'a, F, S>(
+    cx: &mut Context,
+    mut socket: F,
+    name: &str,
+    query_type: Type,
+) -> Result<QueryFuture<'a, F, S>, StartQueryError>
+where
+    F: FnMut() -> S,
+    S: DerefMut<Target = Socket<'a>>,
+{
+    let handle = socket().start_query(cx, name, query_type)?;
+    Ok(QueryFuture::new(socket, handle))
+}
+
+/// A future that resolves to the result of a query.
+///
+/// Dropping the future before it completes cancels the query.
+#[cfg(feature = "async")]
+pub struct QueryFuture<'a, F, S>
+where
+    F: FnMut() -> S,
+    S: DerefMut<Target = Socket<'a>>,
+{
+    socket: F,
+    handle: Option<QueryHandle>,
+    _socket: PhantomData<fn() -> S>,
+}
+
+#[cfg(feature = "async")]
+impl<'a, F, S> QueryFuture<'a, F, S>
+where
+    F: FnMut() -> S,
+    S: DerefMut<Target = Socket<'a>>,
+{
+    /// Wait for a query started with any of the `start_query` methods.
+    pub fn new(socket: F, handle: QueryHandle) -> Self {
+        QueryFuture {
+            socket,
+            handle: Some(handle),
+            _socket: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, F, S> Future for QueryFuture<'a, F, S>
+where
+    F: FnMut() -> S + Unpin,
+    S: DerefMut<Target = Socket<'a>>,
+{
+    type Output = Result<Vec<IpAddress, MAX_ADDRESS_COUNT>, GetQueryResultError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let handle = this.handle.expect("QueryFuture polled after completion");
+        let mut socket = (this.socket)();
+        match socket.get_query_result(handle) {
+            Err(GetQueryResultError::Pending) => {
+                socket.register_query_waker(handle, cx.waker());
+                Poll::Pending
+            }
+            result => {
+                this.handle = None;
+                Poll::Ready(result)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, F, S> Drop for QueryFuture<'a, F, S>
+where
+    F: FnMut() -> S,
+    S: DerefMut<Target = Socket<'a>>,
+{
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            (self.socket)().cancel_query(handle);
+        }
+    }
+}
+
+/// Sort the addresses of a dual-stack query, keeping the server's order among equals.
+#[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+fn sort_addresses(cx: &Context, addresses: &mut [IpAddress], preference: AddressPreference) {
+    let has_ipv4 = cx.ipv4_addr().is_some();
+    let has_ipv6 = cx.ipv6_addr().is_some();
+    let rank = |addr: &IpAddress| -> (bool, u8) {
+        match (preference, addr) {
+            (AddressPreference::Ipv4, IpAddress::Ipv4(_)) => (false, 0),
+            (AddressPreference::Ipv4, IpAddress::Ipv6(_)) => (false, 1),
+            (AddressPreference::Ipv6, IpAddress::Ipv4(_)) => (false, 1),
+            (AddressPreference::Ipv6, IpAddress::Ipv6(_)) => (false, 0),
+            // Rule 1: avoid unusable destinations. Rule 6: prefer higher precedence.
+            (AddressPreference::Rfc6724, IpAddress::Ipv4(_)) => (!has_ipv4, u8::MAX - 35),
+            (AddressPreference::Rfc6724, IpAddress::Ipv6(addr)) => {
+                (!has_ipv6, u8::MAX - ipv6_precedence(addr))
+            }
+        }
+    };
+
+    // Insertion sort, which is stable and cheap for a handful of addresses.
+    for i in 1..addresses.len() {
+        let mut j = i;
+        while j > 0 && rank(&addresses[j - 1]) > rank(&addresses[j]) {
+            addresses.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+/// Precedence of an IPv6 destination in the default policy table of RFC 6724 § 2.1.
+#[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+fn ipv6_precedence(addr: &crate::wire::Ipv6Address) -> u8 {
+    let bytes = addr.as_bytes();
+    if addr.is_loopback() {
+        50
+    } else if addr.is_ipv4_mapped() {
+        35
+    } else if bytes[..2] == [0x20, 0x02] {
+        30
+    } else if bytes[..4] == [0x20, 0x01, 0x00, 0x00] {
+        5
+    } else if bytes[0] & 0xfe == 0xfc {
+        3
+    } else if bytes[..12] == [0; 12]
+        || (bytes[0] == 0xfe && bytes[1] & 0xc0 == 0xc0)
+        || bytes[..2] == [0x3f, 0xfe]
+    {
+        1
+    } else {
+        40
+    }
+}
+
+/// Return the length of the uncompressed name at the start of `name`.
+fn name_len(name: &[u8]) -> usize {
+    let mut len = 0;
+    while name[len] != 0 {
+        len += 1 + name[len] as usize;
+    }
+    len + 1
+}
+
+/// Return the A or AAAA record data holding `addr`.
+fn address_data(addr: IpAddress) -> RecordData<'static> {
+    match addr {
+        #[cfg(feature = "proto-ipv4")]
+        IpAddress::Ipv4(addr) => RecordData::A(addr),
+        #[cfg(feature = "proto-ipv6")]
+        IpAddress::Ipv6(addr) => RecordData::Aaaa(addr),
+    }
+}
+
+/// Copy a name in dotted form, without a trailing dot.
+fn copy_text_name<'a, const N: usize>(
+    dest: &mut Vec<u8, N>,
+    name: impl Iterator<Item = wire::Result<&'a [u8]>>,
+) -> Result<(), wire::Error> {
+    dest.truncate(0);
+
+    for label in name {
+        let label = label?;
+        if !dest.is_empty() {
+            dest.push(b'.').map_err(|_| wire::Error)?;
+        }
+        dest.extend_from_slice(label).map_err(|_| wire::Error)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "proto-ipv4")]
+fn push_decimal_label<const N: usize>(dest: &mut Vec<u8, N>, value: u8) {
+    let digits = [value / 100, value / 10 % 10, value % 10];
+    let skip = match value {
+        0..=9 => 2,
+        10..=99 => 1,
+        _ => 0,
+    };
+    dest.push((3 - skip) as u8).unwrap();
+    for digit in &digits[skip..] {
+        dest.push(b'0' + digit).unwrap();
+    }
+}
+
+#[cfg(all(test, feature = "proto-ipv4", feature = "proto-ipv6"))]
+mod test {
+    use super::*;
+    use crate::wire::dns::Record;
+    use crate::wire::{Ipv4Address, Ipv6Address};
+
+    fn query_name<'s>(s: &'s Socket, handle: QueryHandle) -> &'s [u8] {
+        match &s.queries[handle.0].as_ref().unwrap().state {
+            State::Pending(pq) => &pq.name,
+            _ => unreachable!(),
+        }
+    }
+
+    fn response(query: &[u8], answers: std::vec::Vec<RecordData>) -> std::vec::Vec<u8> {
+        // Keep the header and question, dropping the OPT record.
+        let packet = Packet::new_checked(query).unwrap();
+        let (rest, question) = Question::parse(packet.payload()).unwrap();
+        let name = question.name.to_vec();
+        let mut buffer = query[..query.len() - rest.len()].to_vec();
+        let mut packet = Packet::new_unchecked(&mut buffer[..]);
+        packet.set_flags(Flags::RESPONSE);
+        packet.set_answer_record_count(answers.len() as u16);
+        packet.set_additional_record_count(0);
+        for data in answers {
+            let record = Record {
+                name: &name,
+                ttl: 60,
+                data,
+            };
+            let start = buffer.len();
+            buffer.resize(start + record.buffer_len(), 0);
+            record.emit(&mut buffer[start..]);
+        }
+        buffer
+    }
+
+    fn send(s: &mut Socket, cx: &mut Context) -> Option<(IpRepr, UdpRepr, std::vec::Vec<u8>)> {
+        let mut sent = None;
+        s.dispatch(cx, |_, (ip_repr, udp_repr, payload)| {
+            sent = Some((ip_repr, udp_repr, payload.to_vec()));
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+        sent
+    }
+
+    fn reply(
+        s: &mut Socket,
+        cx: &mut Context,
+        (ip_repr, udp_repr, query): &(IpRepr, UdpRepr, std::vec::Vec<u8>),
+        rcode: Rcode,
+        answers: std::vec::Vec<RecordData>,
+    ) {
+        let udp_repr = UdpRepr {
+            src_port: udp_repr.dst_port,
+            dst_port: udp_repr.src_port,
+        };
+        let ip_repr = IpRepr::new(
+            ip_repr.dst_addr(),
+            ip_repr.src_addr(),
+            IpProtocol::Udp,
+            0,
+            64,
+        );
+        let mut payload = response(query, answers);
+        Packet::new_unchecked(&mut payload[..]).set_rcode(rcode);
+        s.process(cx, &ip_repr, &udp_repr, &payload);
+    }
+
+    #[test]
+    fn test_server_rotation() {
+        const SERVER_A: IpAddress = IpAddress::Ipv4(Ipv4Address([192, 168, 1, 53]));
+        const SERVER_B: IpAddress = IpAddress::Ipv4(Ipv4Address([192, 168, 1, 54]));
+        const ADDR: Ipv4Address = Ipv4Address([192, 0, 2, 1]);
+
+        let mut s = Socket::new(&[SERVER_A, SERVER_B], std::vec::Vec::new());
+        let mut cx = Context::mock();
+        s.set_server_timeout(Duration::from_secs(3));
+
+        // The first server doesn't answer, the query moves on after the timeout.
+        let handle = s.start_query(&mut cx, "example.com", Type::A).unwrap();
+        let sent = send(&mut s, &mut cx).unwrap();
+        assert_eq!(sent.0.dst_addr(), SERVER_A);
+        assert_eq!(s.poll_at(&cx), PollAt::Time(cx.now() + RETRANSMIT_DELAY));
+        cx.set_now(cx.now() + Duration::from_secs(3));
+        let sent = send(&mut s, &mut cx).unwrap();
+        assert_eq!(sent.0.dst_addr(), SERVER_B);
+        reply(
+            &mut s,
+            &mut cx,
+            &sent,
+            Rcode::NoError,
+            vec![RecordData::A(ADDR)],
+        );
+        assert_eq!(&s.get_query_result(handle).unwrap()[..], &[ADDR.into()]);
+
+        // The next query starts with the server that answered, and skips it on SERVFAIL.
+        let handle = s.start_query(&mut cx, "example.com", Type::A).unwrap();
+        let sent = send(&mut s, &mut cx).unwrap();
+        assert_eq!(sent.0.dst_addr(), SERVER_B);
+        reply(&mut s, &mut cx, &sent, Rcode::ServFail, vec![]);
+        let sent = send(&mut s, &mut cx).unwrap();
+        assert_eq!(sent.0.dst_addr(), SERVER_A);
+        reply(&mut s, &mut cx, &sent, Rcode::Refused, vec![]);
+        assert!(send(&mut s, &mut cx).is_none());
+        assert_eq!(s.get_query_result(handle), Err(GetQueryResultError::Failed));
+    }
+
+    fn question_name(query: &[u8]) -> std::vec::Vec<u8> {
+        let packet = Packet::new_checked(query).unwrap();
+        Question::parse(packet.payload()).unwrap().1.name.to_vec()
+    }
+
+    #[test]
+    fn test_search_domains() {
+        const SERVER: IpAddress = IpAddress::Ipv4(Ipv4Address([192, 168, 1, 53]));
+        const ADDR: Ipv4Address = Ipv4Address([192, 0, 2, 1]);
+
+        let mut s = Socket::new(&[SERVER], std::vec::Vec::new());
+        let mut cx = Context::mock();
+        s.update_search_domains(&["lan", "example.com."]).unwrap();
+
+        // Names with fewer dots than ndots go through the search list first.
+        let handle = s.start_query(&mut cx, "printer", Type::A).unwrap();
+        let sent = send(&mut s, &mut cx).unwrap();
+        assert_eq!(question_name(&sent.2), b"\x07printer\x03lan\x00");
+        reply(&mut s, &mut cx, &sent, Rcode::NXDomain, vec![]);
+        let sent = send(&mut s, &mut cx).unwrap();
+        assert_eq!(question_name(&sent.2), b"\x07printer\x07example\x03com\x00");
+        reply(
+            &mut s,
+            &mut cx,
+            &sent,
+            Rcode::NoError,
+            vec![RecordData::A(ADDR)],
+        );
+        assert_eq!(&s.get_query_result(handle).unwrap()[..], &[ADDR.into()]);
+
+        // Others are tried as-is first, and absolute names never use the search list.
+        s.start_query(&mut cx, "rust-lang.org", Type::A).unwrap();
+        let sent = send(&mut s, &mut cx).unwrap();
+        assert_eq!(question_name(&sent.2), b"\x09rust-lang\x03org\x00");
+        reply(&mut s, &mut cx, &sent, Rcode::NoError, vec![]);
+        let sent = send(&mut s, &mut cx).unwrap();
+        assert_eq!(question_name(&sent.2), b"\x09rust-lang\x03org\x03lan\x00");
+
+        let handle = s.start_query(&mut cx, "printer.", Type::A).unwrap();
+        let sent = send(&mut s, &mut cx).unwrap();
+        assert_eq!(question_name(&sent.2), b"\x07printer\x00");
+        reply(&mut s, &mut cx, &sent, Rcode::NXDomain, vec![]);
+        assert_eq!(s.get_query_result(handle), Err(GetQueryResultError::Failed));
+    }
+
+    #[test]
+    fn test_edns_fallback() {
+        const SERVER: IpAddress = IpAddress::Ipv4(Ipv4Address([192, 168, 1, 53]));
+
+        let mut s = Socket::new(&[SERVER], std::vec::Vec::new());
+        let mut cx = Context::mock();
+        s.start_query(&mut cx, "example.com", Type::A).unwrap();
+
+        let sent = send(&mut s, &mut cx).unwrap();
+        let packet = Packet::new_checked(&sent.2[..]).unwrap();
+        assert_eq!(packet.additional_record_count(), 1);
+
+        reply(&mut s, &mut cx, &sent, Rcode::FormErr, vec![]);
+        let sent = send(&mut s, &mut cx).unwrap();
+        assert_eq!(sent.0.dst_addr(), SERVER);
+        let packet = Packet::new_checked(&sent.2[..]).unwrap();
+        assert_eq!(packet.additional_record_count(), 0);
+    }
+
+    #[test]
+    fn test_dual_query() {
+        const SERVER: IpAddress = IpAddress::Ipv4(Ipv4Address([192, 168, 1, 53]));
+        const ADDR_V4: Ipv4Address = Ipv4Address([192, 0, 2, 1]);
+        const ADDR_V6: Ipv6Address =
+            Ipv6Address([0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+        for (preference, first) in [
+            (AddressPreference::Rfc6724, IpAddress::from(ADDR_V6)),
+            (AddressPreference::Ipv4, IpAddress::from(ADDR_V4)),
+        ] {
+            let mut s = Socket::new(&[SERVER], std::vec::Vec::new());
+            let mut cx = Context::mock();
+            let handle = s
+                .start_dual_query(&mut cx, "example.com", preference)
+                .unwrap();
+
+            // The A and AAAA queries go out back to back.
+            let query_a = send(&mut s, &mut cx).unwrap();
+            assert_eq!(s.poll_at(&cx), PollAt::Now);
+            let query_aaaa = send(&mut s, &mut cx).unwrap();
+            assert!(s.poll_at(&cx) > PollAt::Now);
+
+            let answer = vec![RecordData::Aaaa(ADDR_V6)];
+            reply(&mut s, &mut cx, &query_aaaa, Rcode::NoError, answer);
+            assert_eq!(
+                s.get_query_result(handle),
+                Err(GetQueryResultError::Pending)
+            );
+
+            let answer = vec![RecordData::A(ADDR_V4)];
+            reply(&mut s, &mut cx, &query_a, Rcode::NoError, answer);
+            let addresses = s.get_query_result(handle).unwrap();
+            assert_eq!(addresses.len(), 2);
+            assert_eq!(addresses[0], first);
+        }
+    }
+
+    #[test]
+    fn test_dns64() {
+        const SERVER: IpAddress = IpAddress::Ipv4(Ipv4Address([192, 168, 1, 53]));
+        const ADDR_V4: Ipv4Address = Ipv4Address([192, 0, 2, 1]);
+        const SYNTHESIZED: Ipv6Address =
+            Ipv6Address([0x00, 0x64, 0xff, 0x9b, 0, 0, 0, 0, 0, 0, 0, 0, 192, 0, 2, 1]);
+        let prefix = Ipv6Cidr::new(
+            Ipv6Address([0x00, 0x64, 0xff, 0x9b, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            96,
+        );
+
+        let mut s = Socket::new(&[SERVER], std::vec::Vec::new());
+        let mut cx = Context::mock();
+        s.set_dns64(Dns64::Prefix(prefix));
+
+        // An AAAA query without answer is followed by an A query.
+        let handle = s.start_query(&mut cx, "example.com", Type::Aaaa).unwrap();
+        let query_aaaa = send(&mut s, &mut cx).unwrap();
+        reply(&mut s, &mut cx, &query_aaaa, Rcode::NoError, vec![]);
+        let query_a = send(&mut s, &mut cx).unwrap();
+        let packet = Packet::new_checked(&query_a.2[..]).unwrap();
+        assert_eq!(Question::parse(packet.payload()).unwrap().1.type_, Type::A);
+        reply(
+            &mut s,
+            &mut cx,
+            &query_a,
+            Rcode::NoError,
+            vec![RecordData::A(ADDR_V4)],
+        );
+        assert_eq!(
+            &s.get_query_result(handle).unwrap()[..],
+            &[SYNTHESIZED.into()]
+        );
+
+        // Names that do not exist are not queried again.
+        let handle = s.start_query(&mut cx, "example.com", Type::Aaaa).unwrap();
+        let query_aaaa = send(&mut s, &mut cx).unwrap();
+        reply(&mut s, &mut cx, &query_aaaa, Rcode::NXDomain, vec![]);
+        assert!(send(&mut s, &mut cx).is_none());
+        assert_eq!(s.get_query_result(handle), Err(GetQueryResultError::Failed));
+
+        // Dual-stack queries synthesize from the answers of their A half.
+        let handle = s
+            .start_dual_query(&mut cx, "example.com", AddressPreference::Ipv6)
+            .unwrap();
+        let query_a = send(&mut s, &mut cx).unwrap();
+        let query_aaaa = send(&mut s, &mut cx).unwrap();
+        reply(&mut s, &mut cx, &query_aaaa, Rcode::NoError, vec![]);
+        let answer = vec![RecordData::A(ADDR_V4)];
+        reply(&mut s, &mut cx, &query_a, Rcode::NoError, answer);
+        assert_eq!(
+            &s.get_query_result(handle).unwrap()[..],
+            &[SYNTHESIZED.into(), ADDR_V4.into()]
+        );
+
+        // Nothing is synthesized when disabled.
+        s.set_dns64(Dns64::Disabled);
+        let handle = s.start_query(&mut cx, "example.com", Type::Aaaa).unwrap();
+        let query_aaaa = send(&mut s, &mut cx).unwrap();
+        reply(&mut s, &mut cx, &query_aaaa, Rcode::NoError, vec![]);
+        assert!(send(&mut s, &mut cx).is_none());
+        assert_eq!(s.get_query_result(handle), Err(GetQueryResultError::Failed));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_query_future() {
+        use std::cell::RefCell;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use std::task::Wake;
+
+        struct Flag(AtomicBool);
+        impl Wake for Flag {
+            fn wake(self: Arc<Self>) {
+                self.0.store(true, Ordering::SeqCst)
+            }
+        }
+
+        const SERVER: IpAddress = IpAddress::Ipv4(Ipv4Address([192, 168, 1, 53]));
+        const ADDR: Ipv4Address = Ipv4Address([192, 0, 2, 1]);
+
+        let flag = Arc::new(Flag(AtomicBool::new(false)));
+        let waker = Waker::from(flag.clone());
+        let mut task_cx = task::Context::from_waker(&waker);
+
+        let socket = RefCell::new(Socket::new(&[SERVER], std::vec::Vec::new()));
+        let mut cx = Context::mock();
+        let mut future =
+            get_host_by_name(&mut cx, || socket.borrow_mut(), "example.com", Type::A).unwrap();
+        assert_eq!(Pin::new(&mut future).poll(&mut task_cx), Poll::Pending);
+
+        let query = send(&mut socket.borrow_mut(), &mut cx).unwrap();
+        let answer = vec![RecordData::A(ADDR)];
+        reply(
+            &mut socket.borrow_mut(),
+            &mut cx,
+            &query,
+            Rcode::NoError,
+            answer,
+        );
+        assert!(flag.0.load(Ordering::SeqCst));
+        match Pin::new(&mut future).poll(&mut task_cx) {
+            Poll::Ready(Ok(addresses)) => assert_eq!(&addresses[..], &[ADDR.into()]),
+            result => panic!("unexpected {:?}", result),
+        }
+        drop(future);
+
+        // Dropping a pending future frees its query slot.
+        let future =
+            get_host_by_name(&mut cx, || socket.borrow_mut(), "example.com", Type::A).unwrap();
+        drop(future);
+        assert!(socket.borrow().queries.iter().all(|q| q.is_none()));
+    }
+
+    #[test]
+    fn test_update() {
+        struct ZeroMac;
+        impl TsigMac for ZeroMac {
+            fn algorithm(&self) -> &[u8] {
+                b"\x0bhmac-sha256\x00"
+            }
+            fn mac_len(&self) -> usize {
+                32
+            }
+            fn sign(&self, _data: &[&[u8]], mac: &mut [u8]) {
+                mac.fill(0)
+            }
+        }
+
+        const SERVERS: [IpAddress; 2] = [
+            IpAddress::Ipv4(Ipv4Address([192, 168, 1, 53])),
+            IpAddress::Ipv4(Ipv4Address([192, 168, 1, 54])),
+        ];
+        let mut s = Socket::new(&SERVERS, std::vec::Vec::new());
+        let mut cx = Context::mock();
+
+        let name = "host.example.com";
+        let updates = [
+            Update::DeleteAll {
+                name,
+                type_: Type::A,
+            },
+            Update::Add {
+                name,
+                addr: Ipv4Address([192, 168, 1, 1]).into(),
+                ttl: 300,
+            },
+        ];
+        let tsig = Tsig {
+            key_name: "Update-Key",
+            mac: &ZeroMac,
+            time_signed: 1_700_000_000,
+        };
+        let handle = s
+            .start_update(&mut cx, "example.com", &updates, Some(&tsig))
+            .unwrap();
+
+        let sent = send(&mut s, &mut cx).unwrap();
+        assert_eq!(sent.0.dst_addr(), SERVERS[0]);
+        let packet = Packet::new_checked(&sent.2[..]).unwrap();
+        assert_eq!(packet.opcode(), Opcode::Update);
+        assert_eq!(packet.question_count(), 1);
+        assert_eq!(packet.authority_record_count(), 2);
+        assert_eq!(packet.additional_record_count(), 1);
+        assert!(sent.2.windows(11).any(|w| w == b"\x0aupdate-key"));
+
+        // A refusing server is skipped, the signed message is sent as-is to the next one.
+        reply(&mut s, &mut cx, &sent, Rcode::Refused, vec![]);
+        let retry = send(&mut s, &mut cx).unwrap();
+        assert_eq!(retry.0.dst_addr(), SERVERS[1]);
+        assert_eq!(retry.2, sent.2);
+        assert_eq!(
+            s.get_query_result(handle),
+            Err(GetQueryResultError::Pending)
+        );
+
+        reply(&mut s, &mut cx, &retry, Rcode::NoError, vec![]);
+        assert_eq!(s.get_query_result(handle), Ok(Vec::new()));
+
+        let handle = s
+            .start_update(&mut cx, "example.com", &updates, None)
+            .unwrap();
+        let sent = send(&mut s, &mut cx).unwrap();
+        reply(&mut s, &mut cx, &sent, Rcode::NotAuth, vec![]);
+        assert_eq!(s.get_query_result(handle), Err(GetQueryResultError::Failed));
+
+        let updates = [updates[0]; MAX_UPDATE_COUNT + 1];
+        assert!(matches!(
+            s.start_update(&mut cx, "example.com", &updates, None),
+            Err(StartQueryError::MessageTooLong)
+        ));
+    }
+
+    #[test]
+    fn test_reverse_query_name() {
+        let mut s = Socket::new(&[], std::vec::Vec::new());
+        let mut cx = Context::mock();
+
+        let handle = s
+            .start_reverse_query(&mut cx, Ipv4Address::new(192, 0, 2, 10).into())
+            .unwrap();
+        assert_eq!(
+            query_name(&s, handle),
+            b"\x0210\x012\x010\x03192\x07in-addr\x04arpa\x00"
+        );
+
+        let handle = s
+            .start_reverse_query(&mut cx, Ipv6Address::LOOPBACK.into())
+            .unwrap();
+        let name = query_name(&s, handle);
+        assert_eq!(&name[..4], b"\x011\x010");
+        assert_eq!(&name[name.len() - 10..], b"\x03ip6\x04arpa\x00");
+        assert_eq!(name.len(), 32 * 2 + 10);
+    }
+}
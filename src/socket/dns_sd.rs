@@ -0,0 +1,1443 @@
+#[cfg(feature = "async")]
+use core::task::Waker;
+
+use heapless::{Deque, Vec};
+use managed::ManagedSlice;
+
+use super::dns::{copy_name, eq_names, MAX_ADDRESS_COUNT};
+use crate::socket::{Context, PollAt};
+use crate::time::{Duration, Instant};
+use crate::wire::dns::{Flags, Opcode, Packet, Question, Record, RecordData, Type};
+use crate::wire::{IpAddress, IpProtocol, IpRepr, UdpRepr};
+
+#[cfg(feature = "async")]
+use super::WakerRegistration;
+
+pub const MAX_BROWSE_COUNT: usize = 2;
+pub const MAX_EVENT_COUNT: usize = 4;
+pub const MAX_NAME_LEN: usize = 128;
+pub const MAX_TXT_LEN: usize = 128;
+
+const MDNS_PORT: u16 = 5353;
+const MAX_TYPE_LEN: usize = 64;
+const MAX_PENDING_RESPONSES: usize = 2;
+const MAX_PACKET_LEN: usize = 1024;
+const HEADER_LEN: usize = 12;
+// Services are tracked in a bitmask in pending responses.
+const MAX_SERVICE_COUNT: usize = 32;
+
+// TTLs recommended by RFC 6762 § 10.
+const HOST_TTL: u32 = 120;
+const SERVICE_TTL: u32 = 4500;
+// Legacy unicast responses must not carry TTLs above 10 seconds (RFC 6762 § 6.7).
+const LEGACY_TTL: u32 = 10;
+
+const ANNOUNCE_COUNT: u8 = 2;
+const ANNOUNCE_INTERVAL: Duration = Duration::from_millis(1_000);
+const BROWSE_INTERVAL: Duration = Duration::from_millis(1_000);
+const MAX_BROWSE_INTERVAL: Duration = Duration::from_millis(3_600_000);
+const RESOLVE_INTERVAL: Duration = Duration::from_millis(1_000);
+const RESOLVE_ATTEMPTS: u8 = 3;
+
+const TYPE_ANY: Type = Type::Unknown(255);
+const DEFAULT_HOSTNAME: &[u8] = b"\x07smoltcp\x05local\x00";
+const SERVICES_NAME: &[u8] = b"\x09_services\x07_dns-sd\x04_udp\x05local\x00";
+
+#[cfg(feature = "proto-ipv4")]
+const MDNS_IPV4_ADDR: IpAddress = IpAddress::Ipv4(crate::wire::Ipv4Address([224, 0, 0, 251]));
+#[cfg(feature = "proto-ipv6")]
+const MDNS_IPV6_ADDR: IpAddress = IpAddress::Ipv6(crate::wire::Ipv6Address([
+    0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfb,
+]));
+#[cfg(feature = "proto-igmp")]
+const MDNS_GROUPS: [crate::wire::Ipv4Address; 1] = [crate::wire::Ipv4Address([224, 0, 0, 251])];
+
+/// Error returned by [`Socket::register`] and [`Socket::set_hostname`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RegisterError {
+    NoFreeSlot,
+    InvalidName,
+    NameTooLong,
+    TxtTooLong,
+}
+
+/// Error returned by [`Socket::browse`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BrowseError {
+    NoFreeSlot,
+    InvalidName,
+    NameTooLong,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum NameError {
+    Invalid,
+    TooLong,
+}
+
+impl From<NameError> for RegisterError {
+    fn from(err: NameError) -> Self {
+        match err {
+            NameError::Invalid => RegisterError::InvalidName,
+            NameError::TooLong => RegisterError::NameTooLong,
+        }
+    }
+}
+
+impl From<NameError> for BrowseError {
+    fn from(err: NameError) -> Self {
+        match err {
+            NameError::Invalid => BrowseError::InvalidName,
+            NameError::TooLong => BrowseError::NameTooLong,
+        }
+    }
+}
+
+/// A handle to a registered service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ServiceHandle(usize);
+
+/// A handle to a running browse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BrowseHandle(usize);
+
+/// A handle to a discovered service instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InstanceHandle(usize);
+
+/// A change in the set of discovered service instances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// A new instance of a browsed service type was discovered.
+    Found(InstanceHandle),
+    /// The target host, port and at least one address of an instance are known.
+    Resolved(InstanceHandle),
+    /// An instance was withdrawn or its records expired.
+    ///
+    /// The instance stays readable until the next call to [`Socket::poll_event`].
+    Lost(InstanceHandle),
+}
+
+/// A service published by the socket.
+///
+/// The only reason this struct is public is to allow the socket state
+/// to be allocated externally.
+#[derive(Debug)]
+pub struct Service {
+    instance: Vec<u8, MAX_NAME_LEN>,
+    type_: Vec<u8, MAX_TYPE_LEN>,
+    port: u16,
+    txt: Vec<u8, MAX_TXT_LEN>,
+
+    announce_left: u8,
+    announce_at: Instant,
+    withdrawing: bool,
+}
+
+#[derive(Debug)]
+struct Browse {
+    type_: Vec<u8, MAX_TYPE_LEN>,
+    query_at: Instant,
+    delay: Duration,
+}
+
+/// A service instance discovered by browsing.
+#[derive(Debug)]
+pub struct ServiceInstance {
+    browse: BrowseHandle,
+    name: Vec<u8, MAX_NAME_LEN>,
+    port: u16,
+    target: Vec<u8, MAX_NAME_LEN>,
+    txt: Vec<u8, MAX_TXT_LEN>,
+    addresses: Vec<IpAddress, MAX_ADDRESS_COUNT>,
+
+    expires_at: Instant,
+    resolve_at: Option<Instant>,
+    resolve_attempts: u8,
+    resolved: bool,
+    lost: bool,
+}
+
+impl ServiceInstance {
+    /// Return the browse that discovered the instance.
+    pub fn browse(&self) -> BrowseHandle {
+        self.browse
+    }
+
+    /// Return the full service instance name, in wire format.
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
+
+    /// Return the user-visible instance label, e.g. `My Printer`.
+    pub fn instance_name(&self) -> &[u8] {
+        let len = self.name[0] as usize;
+        &self.name[1..1 + len]
+    }
+
+    /// Return the port the service listens on, once its SRV record is known.
+    pub fn port(&self) -> Option<u16> {
+        if self.target.is_empty() {
+            None
+        } else {
+            Some(self.port)
+        }
+    }
+
+    /// Return the name of the host providing the service, in wire format.
+    ///
+    /// This is empty until the SRV record of the instance is known.
+    pub fn target(&self) -> &[u8] {
+        &self.target
+    }
+
+    /// Return the `key=value` strings of the TXT record of the instance.
+    pub fn txt(&self) -> impl Iterator<Item = &[u8]> {
+        let mut data = &self.txt[..];
+        core::iter::from_fn(move || {
+            let (&len, rest) = data.split_first()?;
+            if len as usize > rest.len() {
+                return None;
+            }
+            let (string, rest) = rest.split_at(len as usize);
+            data = rest;
+            Some(string)
+        })
+        .filter(|string| !string.is_empty())
+    }
+
+    /// Return the addresses of the target host.
+    pub fn addresses(&self) -> &[IpAddress] {
+        &self.addresses
+    }
+
+    /// Return whether the target host, port and at least one address are known.
+    pub fn is_resolved(&self) -> bool {
+        self.resolved
+    }
+}
+
+#[derive(Debug)]
+struct LegacyQuestion {
+    transaction_id: u16,
+    name: Vec<u8, MAX_NAME_LEN>,
+    type_: Type,
+}
+
+#[derive(Debug)]
+struct Response {
+    dst: IpAddress,
+    dst_port: u16,
+    /// Set for queries not sent from the mDNS port, which expect a conventional
+    /// unicast DNS response (RFC 6762 § 6.7).
+    legacy: Option<LegacyQuestion>,
+    services: u32,
+    enumerate: bool,
+    host: bool,
+}
+
+impl Response {
+    fn is_empty(&self) -> bool {
+        self.services == 0 && !self.enumerate && !self.host
+    }
+}
+
+/// A DNS Service Discovery socket.
+///
+/// The socket publishes services over multicast DNS ([RFC 6762]) as described in
+/// [RFC 6763], answering queries for their PTR, SRV and TXT records and for the
+/// addresses of the interface. It can also browse for services of a given type,
+/// keeping the instances it discovers in a bounded cache and reporting changes
+/// through [`poll_event`](#method.poll_event).
+///
+/// Probing for name conflicts is not performed, so instance names and the hostname
+/// must be unique on the link.
+///
+/// [RFC 6762]: https://tools.ietf.org/html/rfc6762
+/// [RFC 6763]: https://tools.ietf.org/html/rfc6763
+#[derive(Debug)]
+pub struct Socket<'a> {
+    hostname: Vec<u8, MAX_NAME_LEN>,
+    services: ManagedSlice<'a, Option<Service>>,
+    instances: ManagedSlice<'a, Option<ServiceInstance>>,
+    browses: [Option<Browse>; MAX_BROWSE_COUNT],
+    events: Deque<Event, MAX_EVENT_COUNT>,
+    /// A lost instance whose event was delivered, freed on the next `poll_event`.
+    released: Option<InstanceHandle>,
+    responses: Vec<Response, MAX_PENDING_RESPONSES>,
+
+    #[cfg(feature = "async")]
+    event_waker: WakerRegistration,
+}
+
+impl<'a> Socket<'a> {
+    /// Create a DNS-SD socket.
+    ///
+    /// `services` holds the services published with [`register`](#method.register),
+    /// and `instances` the cache of instances discovered by [`browse`](#method.browse).
+    pub fn new<S, I>(services: S, instances: I) -> Socket<'a>
+    where
+        S: Into<ManagedSlice<'a, Option<Service>>>,
+        I: Into<ManagedSlice<'a, Option<ServiceInstance>>>,
+    {
+        Socket {
+            hostname: Vec::from_slice(DEFAULT_HOSTNAME).unwrap(),
+            services: services.into(),
+            instances: instances.into(),
+            browses: Default::default(),
+            events: Deque::new(),
+            released: None,
+            responses: Vec::new(),
+            #[cfg(feature = "async")]
+            event_waker: WakerRegistration::new(),
+        }
+    }
+
+    /// Set the hostname the addresses of the interface are published under.
+    ///
+    /// `name` is a single label, optionally followed by `.local`. The default is `smoltcp`.
+    pub fn set_hostname(&mut self, name: &str) -> Result<(), RegisterError> {
+        let name = name.strip_suffix('.').unwrap_or(name);
+        let name = name.strip_suffix(".local").unwrap_or(name);
+
+        let mut hostname = Vec::new();
+        push_label(&mut hostname, name.as_bytes())?;
+        push_label(&mut hostname, b"local")?;
+        hostname.push(0).map_err(|_| RegisterError::NameTooLong)?;
+        self.hostname = hostname;
+        Ok(())
+    }
+
+    /// Publish a service instance.
+    ///
+    /// `instance` is the user-visible instance name, e.g. `"My Printer"`, and may contain
+    /// spaces and dots. `service_type` is the service type with or without the `.local`
+    /// domain, e.g. `"_ipp._tcp"`. `txt` lists the `key=value` strings of the TXT record.
+    ///
+    /// The service is announced twice, one second apart, and then answered on demand.
+    pub fn register(
+        &mut self,
+        instance: &str,
+        service_type: &str,
+        port: u16,
+        txt: &[&str],
+    ) -> Result<ServiceHandle, RegisterError> {
+        let type_: Vec<u8, MAX_TYPE_LEN> = service_type_name(service_type)?;
+
+        let mut name: Vec<u8, MAX_NAME_LEN> = Vec::new();
+        push_label(&mut name, instance.as_bytes())?;
+        name.extend_from_slice(&type_)
+            .map_err(|_| RegisterError::NameTooLong)?;
+
+        let mut txt_data: Vec<u8, MAX_TXT_LEN> = Vec::new();
+        for string in txt {
+            if string.len() > 255 {
+                return Err(RegisterError::TxtTooLong);
+            }
+            txt_data
+                .push(string.len() as u8)
+                .map_err(|_| RegisterError::TxtTooLong)?;
+            txt_data
+                .extend_from_slice(string.as_bytes())
+                .map_err(|_| RegisterError::TxtTooLong)?;
+        }
+        // A TXT record must contain at least one string (RFC 6763 § 6.1).
+        if txt_data.is_empty() {
+            txt_data.push(0).unwrap();
+        }
+
+        let handle = find_free(&mut self.services)
+            .filter(|&index| index < MAX_SERVICE_COUNT)
+            .ok_or(RegisterError::NoFreeSlot)?;
+        self.services[handle] = Some(Service {
+            instance: name,
+            type_,
+            port,
+            txt: txt_data,
+            announce_left: ANNOUNCE_COUNT,
+            announce_at: Instant::ZERO,
+            withdrawing: false,
+        });
+        Ok(ServiceHandle(handle))
+    }
+
+    /// Withdraw a published service.
+    ///
+    /// A goodbye packet is sent so that peers flush the records of the service right away.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ServiceHandle corresponds to a free slot.
+    pub fn unregister(&mut self, handle: ServiceHandle) {
+        let service = self.services[handle.0].as_mut().unwrap();
+        service.withdrawing = true;
+    }
+
+    /// Start browsing for instances of a service type, e.g. `"_http._tcp"`.
+    pub fn browse(&mut self, service_type: &str) -> Result<BrowseHandle, BrowseError> {
+        let type_ = service_type_name(service_type)?;
+        let index = self
+            .browses
+            .iter()
+            .position(|browse| browse.is_none())
+            .ok_or(BrowseError::NoFreeSlot)?;
+        self.browses[index] = Some(Browse {
+            type_,
+            query_at: Instant::ZERO,
+            delay: BROWSE_INTERVAL,
+        });
+        Ok(BrowseHandle(index))
+    }
+
+    /// Stop browsing, forgetting the instances discovered by the browse.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the BrowseHandle corresponds to a free slot.
+    pub fn stop_browse(&mut self, handle: BrowseHandle) {
+        if self.browses[handle.0].take().is_none() {
+            panic!("Stopping browse in a free slot.")
+        }
+
+        let instances = &mut self.instances;
+        for slot in instances.iter_mut() {
+            if matches!(slot, Some(instance) if instance.browse == handle) {
+                *slot = None;
+            }
+        }
+
+        let count = self.events.len();
+        for _ in 0..count {
+            let event = self.events.pop_front().unwrap();
+            let (Event::Found(h) | Event::Resolved(h) | Event::Lost(h)) = event;
+            if instances[h.0].is_some() {
+                let _ = self.events.push_back(event);
+            }
+        }
+    }
+
+    /// Return a discovered service instance.
+    pub fn instance(&self, handle: InstanceHandle) -> Option<&ServiceInstance> {
+        self.instances.get(handle.0)?.as_ref()
+    }
+
+    /// Return all cached service instances.
+    pub fn instances(&self) -> impl Iterator<Item = (InstanceHandle, &ServiceInstance)> {
+        self.instances
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| Some((InstanceHandle(i), slot.as_ref()?)))
+            .filter(|(_, instance)| !instance.lost)
+    }
+
+    /// Return the next change in the set of discovered service instances, if any.
+    pub fn poll_event(&mut self) -> Option<Event> {
+        if let Some(handle) = self.released.take() {
+            self.instances[handle.0] = None;
+        }
+
+        let event = self.events.pop_front()?;
+        if let Event::Lost(handle) = event {
+            self.released = Some(handle);
+        }
+        Some(event)
+    }
+
+    /// Register a waker for browse events.
+    ///
+    /// The waker is woken on state changes that might affect the return value
+    /// of `poll_event` method calls.
+    ///
+    /// Notes:
+    ///
+    /// - Only one waker can be registered at a time. If another waker was previously registered,
+    ///   it is overwritten and will no longer be woken.
+    /// - The Waker is woken only once. Once woken, you must register it again to receive more wakes.
+    #[cfg(feature = "async")]
+    pub fn register_event_waker(&mut self, waker: &Waker) {
+        self.event_waker.register(waker)
+    }
+
+    fn push_event(&mut self, event: Event) {
+        if self.events.push_back(event).is_err() {
+            net_trace!("dns-sd: event queue full, dropping {:?}", event);
+            // Nobody will learn about the loss, free the instance right away.
+            if let Event::Lost(handle) = event {
+                self.instances[handle.0] = None;
+            }
+            return;
+        }
+
+        #[cfg(feature = "async")]
+        self.event_waker.wake_all();
+    }
+
+    /// Multicast groups the socket needs the interface to join.
+    #[cfg(feature = "proto-igmp")]
+    pub(crate) fn multicast_groups(&self) -> &[crate::wire::Ipv4Address] {
+        if self.services.iter().any(|s| s.is_some()) || self.browses.iter().any(|b| b.is_some()) {
+            &MDNS_GROUPS
+        } else {
+            &[]
+        }
+    }
+
+    pub(crate) fn accepts(&self, _ip_repr: &IpRepr, udp_repr: &UdpRepr) -> bool {
+        udp_repr.dst_port == MDNS_PORT
+    }
+
+    /// Process an mDNS packet, returning whether it was meant for this socket: a query
+    /// about the published services or host name, or a response about a browsed service.
+    /// Other packets are left to the other sockets listening on the mDNS port.
+    pub(crate) fn process(
+        &mut self,
+        cx: &mut Context,
+        ip_repr: &IpRepr,
+        udp_repr: &UdpRepr,
+        payload: &[u8],
+    ) -> bool {
+        debug_assert!(self.accepts(ip_repr, udp_repr));
+
+        let p = match Packet::new_checked(payload) {
+            Ok(x) => x,
+            Err(_) => {
+                net_trace!("dns-sd: packet malformed");
+                return false;
+            }
+        };
+        if p.opcode() != Opcode::Query {
+            net_trace!("dns-sd: unwanted opcode {:?}", p.opcode());
+            return false;
+        }
+
+        if p.flags().contains(Flags::RESPONSE) {
+            self.process_response(cx, &p)
+        } else {
+            self.process_query(ip_repr, udp_repr, &p)
+        }
+    }
+
+    fn process_query(&mut self, ip_repr: &IpRepr, udp_repr: &UdpRepr, p: &Packet<&[u8]>) -> bool {
+        let mut payload = p.payload();
+        let mut legacy = None;
+        let mut services = 0u32;
+        let mut enumerate = false;
+        let mut host = false;
+
+        for _ in 0..p.question_count() {
+            let (rest, question) = match Question::parse(payload) {
+                Ok(x) => x,
+                Err(_) => {
+                    net_trace!("dns-sd: question malformed");
+                    return false;
+                }
+            };
+            payload = rest;
+
+            let matches = |type_| question.type_ == type_ || question.type_ == TYPE_ANY;
+            let mut answered = false;
+
+            if matches(Type::Ptr) && name_eq(p, question.name, SERVICES_NAME) {
+                enumerate = self.services.iter().any(|s| s.is_some());
+                answered |= enumerate;
+            }
+            for (i, service) in self.services.iter().enumerate() {
+                let service = match service {
+                    Some(service) if !service.withdrawing => service,
+                    _ => continue,
+                };
+                if (matches(Type::Ptr) && name_eq(p, question.name, &service.type_))
+                    || ((matches(Type::Srv) || matches(Type::Txt))
+                        && name_eq(p, question.name, &service.instance))
+                {
+                    services |= 1 << i;
+                    answered = true;
+                }
+            }
+            if (matches(Type::A) || matches(Type::Aaaa))
+                && name_eq(p, question.name, &self.hostname)
+            {
+                host = true;
+                answered = true;
+            }
+
+            if answered && legacy.is_none() && udp_repr.src_port != MDNS_PORT {
+                let mut name = Vec::new();
+                if copy_name(&mut name, p.parse_name(question.name)).is_err() {
+                    net_trace!("dns-sd: question name malformed");
+                    return false;
+                }
+                legacy = Some(LegacyQuestion {
+                    transaction_id: p.transaction_id(),
+                    name,
+                    type_: question.type_,
+                });
+            }
+        }
+
+        if services == 0 && !enumerate && !host {
+            return false;
+        }
+
+        let (dst, dst_port) = match legacy {
+            Some(_) => (ip_repr.src_addr(), udp_repr.src_port),
+            None => match ip_repr.src_addr() {
+                #[cfg(feature = "proto-ipv4")]
+                IpAddress::Ipv4(_) => (MDNS_IPV4_ADDR, MDNS_PORT),
+                #[cfg(feature = "proto-ipv6")]
+                IpAddress::Ipv6(_) => (MDNS_IPV6_ADDR, MDNS_PORT),
+            },
+        };
+
+        // Merge with a multicast response that's already pending.
+        if legacy.is_none() {
+            if let Some(response) = self
+                .responses
+                .iter_mut()
+                .find(|r| r.legacy.is_none() && r.dst == dst)
+            {
+                response.services |= services;
+                response.enumerate |= enumerate;
+                response.host |= host;
+                return true;
+            }
+        }
+
+        let response = Response {
+            dst,
+            dst_port,
+            legacy,
+            services,
+            enumerate,
+            host,
+        };
+        if self.responses.push(response).is_err() {
+            net_trace!("dns-sd: too many pending responses, dropping query");
+        }
+        true
+    }
+
+    fn process_response(&mut self, cx: &mut Context, p: &Packet<&[u8]>) -> bool {
+        if self.browses.iter().all(|b| b.is_none()) {
+            return false;
+        }
+
+        // Records in a response may refer to each other in any order, so service
+        // records are processed first and addresses of the target hosts second.
+        let mut matched = self.process_records(cx, p, false);
+        matched |= self.process_records(cx, p, true);
+
+        for i in 0..self.instances.len() {
+            let instance = match &mut self.instances[i] {
+                Some(instance) if !instance.lost && !instance.resolved => instance,
+                _ => continue,
+            };
+            if !instance.target.is_empty() && !instance.addresses.is_empty() {
+                instance.resolved = true;
+                instance.resolve_at = None;
+                self.push_event(Event::Resolved(InstanceHandle(i)));
+            }
+        }
+        matched
+    }
+
+    /// Process the service or address records of a response, returning whether any of
+    /// them is about a browsed service.
+    fn process_records(&mut self, cx: &mut Context, p: &Packet<&[u8]>, addresses: bool) -> bool {
+        let mut matched = false;
+        let mut payload = p.payload();
+        for _ in 0..p.question_count() {
+            payload = match Question::parse(payload) {
+                Ok((rest, _)) => rest,
+                Err(_) => {
+                    net_trace!("dns-sd: question malformed");
+                    return false;
+                }
+            };
+        }
+
+        let count = p.answer_record_count() as usize
+            + p.authority_record_count() as usize
+            + p.additional_record_count() as usize;
+        for _ in 0..count {
+            let (rest, record) = match Record::parse(payload) {
+                Ok(x) => x,
+                Err(_) => {
+                    net_trace!("dns-sd: record malformed");
+                    return matched;
+                }
+            };
+            payload = rest;
+
+            match record.data {
+                RecordData::Ptr(data) if !addresses => {
+                    matched |= self.process_ptr(cx, p, &record, data)
+                }
+                RecordData::Srv { port, target, .. } if !addresses => {
+                    for instance in self.instances_named(p, record.name) {
+                        matched = true;
+                        if record.ttl == 0 {
+                            instance.expires_at = Instant::ZERO;
+                        } else if copy_name(&mut instance.target, p.parse_name(target)).is_ok() {
+                            instance.port = port;
+                        } else {
+                            instance.target.clear();
+                        }
+                    }
+                }
+                RecordData::Txt(data) if !addresses => {
+                    for instance in self.instances_named(p, record.name) {
+                        matched = true;
+                        instance.txt.clear();
+                        if instance.txt.extend_from_slice(data).is_err() {
+                            net_trace!("dns-sd: TXT record too long");
+                            instance.txt.clear();
+                        }
+                    }
+                }
+                #[cfg(feature = "proto-ipv4")]
+                RecordData::A(addr) if addresses => {
+                    matched |= self.process_address(p, &record, addr.into())
+                }
+                #[cfg(feature = "proto-ipv6")]
+                RecordData::Aaaa(addr) if addresses => {
+                    matched |= self.process_address(p, &record, addr.into())
+                }
+                _ => {}
+            }
+        }
+        matched
+    }
+
+    /// Process a PTR record, returning whether it names a browsed service type.
+    fn process_ptr(
+        &mut self,
+        cx: &mut Context,
+        p: &Packet<&[u8]>,
+        record: &Record,
+        data: &[u8],
+    ) -> bool {
+        let browse = match self.browses.iter().position(
+            |browse| matches!(browse, Some(browse) if name_eq(p, record.name, &browse.type_)),
+        ) {
+            Some(index) => BrowseHandle(index),
+            None => return false,
+        };
+
+        let mut name = Vec::new();
+        if copy_name(&mut name, p.parse_name(data)).is_err() {
+            net_trace!("dns-sd: PTR name malformed or too long");
+            return true;
+        }
+
+        let existing = self.instances.iter().position(
+            |slot| matches!(slot, Some(i) if !i.lost && i.browse == browse && i.name == name),
+        );
+        if let Some(index) = existing {
+            let instance = self.instances[index].as_mut().unwrap();
+            // A goodbye expires the instance on the next dispatch.
+            instance.expires_at = cx.now() + Duration::from_secs(record.ttl as u64);
+            if record.ttl == 0 {
+                instance.expires_at = Instant::ZERO;
+            }
+            return true;
+        }
+        if record.ttl == 0 {
+            return true;
+        }
+
+        let index = match find_free(&mut self.instances) {
+            Some(index) => index,
+            None => {
+                net_trace!("dns-sd: instance cache full");
+                return true;
+            }
+        };
+        self.instances[index] = Some(ServiceInstance {
+            browse,
+            name,
+            port: 0,
+            target: Vec::new(),
+            txt: Vec::new(),
+            addresses: Vec::new(),
+            expires_at: cx.now() + Duration::from_secs(record.ttl as u64),
+            resolve_at: Some(cx.now()),
+            resolve_attempts: 0,
+            resolved: false,
+            lost: false,
+        });
+        self.push_event(Event::Found(InstanceHandle(index)));
+        true
+    }
+
+    /// Process an address record, returning whether it is the address of a resolved
+    /// instance's target host.
+    fn process_address(&mut self, p: &Packet<&[u8]>, record: &Record, addr: IpAddress) -> bool {
+        let mut matched = false;
+        for instance in self.instances.iter_mut().flatten() {
+            if instance.lost
+                || instance.target.is_empty()
+                || !name_eq(p, record.name, &instance.target)
+            {
+                continue;
+            }
+            matched = true;
+            if !instance.addresses.contains(&addr) && instance.addresses.push(addr).is_err() {
+                net_trace!("dns-sd: too many addresses, ignoring {}", addr);
+            }
+        }
+        matched
+    }
+
+    fn instances_named<'s>(
+        &'s mut self,
+        p: &'s Packet<&[u8]>,
+        name: &'s [u8],
+    ) -> impl Iterator<Item = &'s mut ServiceInstance> + 's {
+        self.instances
+            .iter_mut()
+            .flatten()
+            .filter(move |i| !i.lost && name_eq(p, name, &i.name))
+    }
+
+    fn expire_instances(&mut self, now: Instant) {
+        for i in 0..self.instances.len() {
+            match &mut self.instances[i] {
+                Some(instance) if !instance.lost && instance.expires_at <= now => {
+                    instance.lost = true;
+                }
+                _ => continue,
+            }
+            self.push_event(Event::Lost(InstanceHandle(i)));
+        }
+    }
+
+    pub(crate) fn dispatch<F, E>(&mut self, cx: &mut Context, emit: F) -> Result<(), E>
+    where
+        F: FnOnce(&mut Context, (IpRepr, UdpRepr, &[u8])) -> Result<(), E>,
+    {
+        self.expire_instances(cx.now());
+
+        let mut buffer = [0u8; MAX_PACKET_LEN];
+        // mDNS is best-effort: the state is advanced whether or not the packet
+        // can actually be sent.
+        let (dst_addr, dst_port, len) = match self.prepare(cx, &mut buffer) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        let payload = &buffer[..len];
+
+        let src_addr = match cx.get_source_address(dst_addr) {
+            Some(addr) => addr,
+            None => {
+                net_trace!("dns-sd: no source address for {}", dst_addr);
+                return Ok(());
+            }
+        };
+
+        let udp_repr = UdpRepr {
+            src_port: MDNS_PORT,
+            dst_port,
+        };
+        // All mDNS packets are sent with a hop limit of 255 (RFC 6762 § 11).
+        let ip_repr = IpRepr::new(
+            src_addr,
+            dst_addr,
+            IpProtocol::Udp,
+            udp_repr.header_len() + payload.len(),
+            255,
+        );
+
+        net_trace!(
+            "dns-sd: sending {} octets to {}:{}",
+            payload.len(),
+            dst_addr,
+            dst_port
+        );
+
+        emit(cx, (ip_repr, udp_repr, payload))
+    }
+
+    /// Build the next packet to send, returning its destination and length.
+    fn prepare(&mut self, cx: &mut Context, buffer: &mut [u8]) -> Option<(IpAddress, u16, usize)> {
+        let now = cx.now();
+
+        if !self.responses.is_empty() {
+            let response = &mut self.responses[0];
+            let (dst, dst_port) = (response.dst, response.dst_port);
+            let len = build_response(cx, &self.hostname, &self.services, response, buffer);
+            if response.is_empty() {
+                self.responses.remove(0);
+            }
+            return Some((dst, dst_port, len));
+        }
+
+        let dst = multicast_addr(cx)?;
+
+        if let Some(index) = self
+            .services
+            .iter()
+            .position(|s| matches!(s, Some(s) if s.withdrawing))
+        {
+            let service = self.services[index].take().unwrap();
+            let mut msg = Message::new(buffer);
+            push_service(&mut msg, &service, &self.hostname, 0);
+            return Some((dst, MDNS_PORT, msg.finish(0, response_flags())));
+        }
+
+        if let Some(service) = self
+            .services
+            .iter_mut()
+            .flatten()
+            .find(|s| s.announce_left > 0 && s.announce_at <= now)
+        {
+            let mut msg = Message::new(buffer);
+            push_service(&mut msg, service, &self.hostname, SERVICE_TTL);
+            push_host(&mut msg, cx, &self.hostname, HOST_TTL, true);
+            service.announce_left -= 1;
+            service.announce_at = now + ANNOUNCE_INTERVAL;
+            return Some((dst, MDNS_PORT, msg.finish(0, response_flags())));
+        }
+
+        if let Some(browse) = self
+            .browses
+            .iter_mut()
+            .flatten()
+            .find(|b| b.query_at <= now)
+        {
+            let mut msg = Message::new(buffer);
+            msg.question(&browse.type_, Type::Ptr);
+            browse.query_at = now + browse.delay;
+            browse.delay = MAX_BROWSE_INTERVAL.min(browse.delay * 2);
+            return Some((dst, MDNS_PORT, msg.finish(0, Flags::empty())));
+        }
+
+        if let Some(instance) = self
+            .instances
+            .iter_mut()
+            .flatten()
+            .find(|i| !i.lost && matches!(i.resolve_at, Some(resolve_at) if resolve_at <= now))
+        {
+            let mut msg = Message::new(buffer);
+            if instance.target.is_empty() {
+                msg.question(&instance.name, Type::Srv);
+                msg.question(&instance.name, Type::Txt);
+            } else {
+                #[cfg(feature = "proto-ipv4")]
+                msg.question(&instance.target, Type::A);
+                #[cfg(feature = "proto-ipv6")]
+                msg.question(&instance.target, Type::Aaaa);
+            }
+            instance.resolve_attempts += 1;
+            instance.resolve_at = if instance.resolve_attempts < RESOLVE_ATTEMPTS {
+                Some(now + RESOLVE_INTERVAL)
+            } else {
+                None
+            };
+            return Some((dst, MDNS_PORT, msg.finish(0, Flags::empty())));
+        }
+
+        None
+    }
+
+    pub(crate) fn poll_at(&self, _cx: &Context) -> PollAt {
+        if !self.responses.is_empty() || self.services.iter().flatten().any(|s| s.withdrawing) {
+            return PollAt::Now;
+        }
+
+        let services = self
+            .services
+            .iter()
+            .flatten()
+            .filter(|s| s.announce_left > 0)
+            .map(|s| s.announce_at);
+        let browses = self.browses.iter().flatten().map(|b| b.query_at);
+        let instances = self
+            .instances
+            .iter()
+            .flatten()
+            .filter(|i| !i.lost)
+            .flat_map(|i| [Some(i.expires_at), i.resolve_at])
+            .flatten();
+
+        services
+            .chain(browses)
+            .chain(instances)
+            .min()
+            .map(PollAt::Time)
+            .unwrap_or(PollAt::Ingress)
+    }
+}
+
+fn response_flags() -> Flags {
+    Flags::RESPONSE | Flags::AUTHORITATIVE
+}
+
+fn multicast_addr(cx: &Context) -> Option<IpAddress> {
+    #[cfg(feature = "proto-ipv4")]
+    if cx.ipv4_addr().is_some() {
+        return Some(MDNS_IPV4_ADDR);
+    }
+    #[cfg(feature = "proto-ipv6")]
+    if cx.ipv6_addr().is_some() {
+        return Some(MDNS_IPV6_ADDR);
+    }
+    None
+}
+
+fn build_response(
+    cx: &Context,
+    hostname: &[u8],
+    services: &[Option<Service>],
+    response: &mut Response,
+    buffer: &mut [u8],
+) -> usize {
+    let mut msg = Message::new(buffer);
+    let (transaction_id, cap) = match &response.legacy {
+        Some(question) => {
+            msg.question(&question.name, question.type_);
+            (question.transaction_id, LEGACY_TTL)
+        }
+        None => (0, u32::MAX),
+    };
+
+    if response.enumerate {
+        for service in services.iter().flatten() {
+            msg.answer(&Record {
+                name: SERVICES_NAME,
+                ttl: SERVICE_TTL.min(cap),
+                data: RecordData::Ptr(&service.type_),
+            });
+        }
+        response.enumerate = false;
+    }
+
+    // Only one service goes in each packet, so that responses stay small.
+    let mut service = None;
+    if response.services != 0 {
+        let index = response.services.trailing_zeros() as usize;
+        response.services &= !(1 << index);
+        service = services.get(index).and_then(|s| s.as_ref());
+    }
+    if let Some(service) = service {
+        push_service(&mut msg, service, hostname, SERVICE_TTL.min(cap));
+    }
+
+    if response.host {
+        push_host(&mut msg, cx, hostname, HOST_TTL.min(cap), false);
+        response.host = false;
+    } else if service.is_some() {
+        push_host(&mut msg, cx, hostname, HOST_TTL.min(cap), true);
+    }
+
+    msg.finish(transaction_id, response_flags())
+}
+
+fn push_service(msg: &mut Message, service: &Service, hostname: &[u8], ttl: u32) {
+    msg.answer(&Record {
+        name: &service.type_,
+        ttl,
+        data: RecordData::Ptr(&service.instance),
+    });
+    msg.answer(&Record {
+        name: &service.instance,
+        ttl: ttl.min(HOST_TTL),
+        data: RecordData::Srv {
+            priority: 0,
+            weight: 0,
+            port: service.port,
+            target: hostname,
+        },
+    });
+    msg.answer(&Record {
+        name: &service.instance,
+        ttl,
+        data: RecordData::Txt(&service.txt),
+    });
+}
+
+fn push_host(msg: &mut Message, cx: &Context, hostname: &[u8], ttl: u32, additional: bool) {
+    let mut push = |data| {
+        let record = Record {
+            name: hostname,
+            ttl,
+            data,
+        };
+        if additional {
+            msg.additional(&record)
+        } else {
+            msg.answer(&record)
+        }
+    };
+    #[cfg(feature = "proto-ipv4")]
+    if let Some(addr) = cx.ipv4_addr() {
+        push(RecordData::A(addr));
+    }
+    #[cfg(feature = "proto-ipv6")]
+    if let Some(addr) = cx.ipv6_addr() {
+        push(RecordData::Aaaa(addr));
+    }
+}
+
+/// A DNS message being built, section by section.
+///
+/// Records that don't fit in the buffer are left out.
+struct Message<'b> {
+    buffer: &'b mut [u8],
+    len: usize,
+    questions: u16,
+    answers: u16,
+    additional: u16,
+}
+
+impl<'b> Message<'b> {
+    fn new(buffer: &'b mut [u8]) -> Self {
+        Message {
+            buffer,
+            len: HEADER_LEN,
+            questions: 0,
+            answers: 0,
+            additional: 0,
+        }
+    }
+
+    fn question(&mut self, name: &[u8], type_: Type) {
+        debug_assert!(self.answers == 0 && self.additional == 0);
+        let question = Question { name, type_ };
+        if self.len + question.buffer_len() > self.buffer.len() {
+            net_trace!("dns-sd: question does not fit in packet");
+            return;
+        }
+        question.emit(&mut self.buffer[self.len..]);
+        self.len += question.buffer_len();
+        self.questions += 1;
+    }
+
+    fn record(&mut self, record: &Record) -> bool {
+        if self.len + record.buffer_len() > self.buffer.len() {
+            net_trace!("dns-sd: record does not fit in packet");
+            return false;
+        }
+        record.emit(&mut self.buffer[self.len..]);
+        self.len += record.buffer_len();
+        true
+    }
+
+    fn answer(&mut self, record: &Record) {
+        debug_assert!(self.additional == 0);
+        if self.record(record) {
+            self.answers += 1;
+        }
+    }
+
+    fn additional(&mut self, record: &Record) {
+        if self.record(record) {
+            self.additional += 1;
+        }
+    }
+
+    fn finish(self, transaction_id: u16, flags: Flags) -> usize {
+        let mut packet = Packet::new_unchecked(&mut self.buffer[..]);
+        packet.set_transaction_id(transaction_id);
+        packet.set_flags(flags);
+        packet.set_opcode(Opcode::Query);
+        packet.set_question_count(self.questions);
+        packet.set_answer_record_count(self.answers);
+        packet.set_authority_record_count(0);
+        packet.set_additional_record_count(self.additional);
+        self.len
+    }
+}
+
+fn find_free<T>(slots: &mut ManagedSlice<Option<T>>) -> Option<usize> {
+    if let Some(index) = slots.iter().position(|slot| slot.is_none()) {
+        return Some(index);
+    }
+
+    match slots {
+        ManagedSlice::Borrowed(_) => None,
+        #[cfg(feature = "alloc")]
+        ManagedSlice::Owned(slots) => {
+            slots.push(None);
+            Some(slots.len() - 1)
+        }
+    }
+}
+
+fn name_eq(p: &Packet<&[u8]>, name: &[u8], ours: &[u8]) -> bool {
+    eq_names(p.parse_name(name), p.parse_name(ours)).unwrap_or(false)
+}
+
+fn push_label<const N: usize>(dest: &mut Vec<u8, N>, label: &[u8]) -> Result<(), NameError> {
+    if label.is_empty() || label.len() > 63 {
+        return Err(NameError::Invalid);
+    }
+    dest.push(label.len() as u8)
+        .map_err(|_| NameError::TooLong)?;
+    dest.extend_from_slice(label)
+        .map_err(|_| NameError::TooLong)
+}
+
+/// Convert a service type such as `_http._tcp` into a wire-format name in the `local` domain.
+fn service_type_name<const N: usize>(service_type: &str) -> Result<Vec<u8, N>, NameError> {
+    let service_type = service_type.strip_suffix('.').unwrap_or(service_type);
+    let service_type = service_type.strip_suffix(".local").unwrap_or(service_type);
+
+    let mut name = Vec::new();
+    for label in service_type.split('.') {
+        push_label(&mut name, label.as_bytes())?;
+    }
+    push_label(&mut name, b"local")?;
+    name.push(0).map_err(|_| NameError::TooLong)?;
+    Ok(name)
+}
+
+#[cfg(all(test, feature = "proto-ipv4"))]
+mod test {
+    use super::*;
+    use crate::wire::Ipv4Address;
+    use std::vec::Vec as StdVec;
+
+    const PEER_ADDR: IpAddress = IpAddress::Ipv4(Ipv4Address([192, 168, 1, 2]));
+    const HTTP_TYPE: &[u8] = b"\x05_http\x04_tcp\x05local\x00";
+    const WEB_INSTANCE: &[u8] = b"\x03web\x05_http\x04_tcp\x05local\x00";
+    const PEER_HOST: &[u8] = b"\x04peer\x05local\x00";
+
+    fn socket() -> Socket<'static> {
+        Socket::new(StdVec::new(), StdVec::new())
+    }
+
+    fn ip_repr(src_addr: IpAddress, payload_len: usize) -> IpRepr {
+        IpRepr::new(
+            src_addr,
+            MDNS_IPV4_ADDR,
+            IpProtocol::Udp,
+            payload_len + 8,
+            255,
+        )
+    }
+
+    fn recv(s: &mut Socket, cx: &mut Context, src_port: u16, payload: &[u8]) -> bool {
+        let udp_repr = UdpRepr {
+            src_port,
+            dst_port: MDNS_PORT,
+        };
+        s.process(cx, &ip_repr(PEER_ADDR, payload.len()), &udp_repr, payload)
+    }
+
+    fn send(s: &mut Socket, cx: &mut Context) -> Option<(IpRepr, UdpRepr, StdVec<u8>)> {
+        let mut sent = None;
+        s.dispatch(cx, |_, (ip_repr, udp_repr, payload)| {
+            sent = Some((ip_repr, udp_repr, payload.to_vec()));
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+        sent
+    }
+
+    fn records(payload: &[u8]) -> StdVec<(StdVec<u8>, Type)> {
+        let p = Packet::new_checked(payload).unwrap();
+        let mut rest = p.payload();
+        for _ in 0..p.question_count() {
+            rest = Question::parse(rest).unwrap().0;
+        }
+        let count = p.answer_record_count() + p.additional_record_count();
+        (0..count)
+            .map(|_| {
+                let (next, record) = Record::parse(rest).unwrap();
+                rest = next;
+                (record.name.to_vec(), record.data.type_())
+            })
+            .collect()
+    }
+
+    fn query(name: &[u8], type_: Type) -> StdVec<u8> {
+        let mut buffer = vec![0; MAX_PACKET_LEN];
+        let mut msg = Message::new(&mut buffer);
+        msg.question(name, type_);
+        let len = msg.finish(0x1234, Flags::empty());
+        buffer.truncate(len);
+        buffer
+    }
+
+    #[test]
+    fn test_register_announce() {
+        let mut s = socket();
+        let mut cx = Context::mock();
+
+        s.register("web", "_http._tcp", 80, &["path=/"]).unwrap();
+        assert_eq!(s.poll_at(&cx), PollAt::Time(Instant::ZERO));
+
+        let (ip_repr, udp_repr, payload) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(ip_repr.dst_addr(), MDNS_IPV4_ADDR);
+        assert_eq!(ip_repr.hop_limit(), 255);
+        assert_eq!(udp_repr.dst_port, MDNS_PORT);
+        assert_eq!(
+            records(&payload)[..4],
+            [
+                (HTTP_TYPE.to_vec(), Type::Ptr),
+                (WEB_INSTANCE.to_vec(), Type::Srv),
+                (WEB_INSTANCE.to_vec(), Type::Txt),
+                (DEFAULT_HOSTNAME.to_vec(), Type::A),
+            ]
+        );
+
+        // The second announcement follows a second later.
+        assert!(send(&mut s, &mut cx).is_none());
+        assert_eq!(s.poll_at(&cx), PollAt::Time(cx.now() + ANNOUNCE_INTERVAL));
+    }
+
+    #[test]
+    fn test_answer_query() {
+        let mut s = socket();
+        let mut cx = Context::mock();
+        let handle = s.register("web", "_http._tcp", 80, &[]).unwrap();
+        s.services[handle.0].as_mut().unwrap().announce_left = 0;
+
+        assert!(recv(
+            &mut s,
+            &mut cx,
+            MDNS_PORT,
+            &query(HTTP_TYPE, Type::Ptr)
+        ));
+        assert_eq!(s.poll_at(&cx), PollAt::Now);
+        let (ip_repr, _, payload) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(ip_repr.dst_addr(), MDNS_IPV4_ADDR);
+        assert_eq!(records(&payload)[0], (HTTP_TYPE.to_vec(), Type::Ptr));
+
+        // Legacy unicast queries get a unicast reply echoing the question.
+        assert!(recv(
+            &mut s,
+            &mut cx,
+            40000,
+            &query(DEFAULT_HOSTNAME, Type::A)
+        ));
+        let (ip_repr, udp_repr, payload) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(ip_repr.dst_addr(), PEER_ADDR);
+        assert_eq!(udp_repr.dst_port, 40000);
+        let p = Packet::new_checked(&payload[..]).unwrap();
+        assert_eq!(p.transaction_id(), 0x1234);
+        assert_eq!(p.question_count(), 1);
+        assert_eq!(records(&payload)[0], (DEFAULT_HOSTNAME.to_vec(), Type::A));
+
+        // Nothing is answered for unknown names, and the query is left to other sockets.
+        assert!(!recv(
+            &mut s,
+            &mut cx,
+            MDNS_PORT,
+            &query(WEB_INSTANCE, Type::A)
+        ));
+        assert!(send(&mut s, &mut cx).is_none());
+
+        // Responses are only of interest when browsing.
+        assert!(!recv(&mut s, &mut cx, MDNS_PORT, &response(120)));
+
+        s.unregister(handle);
+        let (_, _, payload) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(records(&payload).len(), 3);
+        assert!(s.services[handle.0].is_none());
+    }
+
+    fn response(ttl: u32) -> StdVec<u8> {
+        let mut buffer = vec![0; MAX_PACKET_LEN];
+        let mut msg = Message::new(&mut buffer);
+        msg.answer(&Record {
+            name: HTTP_TYPE,
+            ttl,
+            data: RecordData::Ptr(WEB_INSTANCE),
+        });
+        msg.additional(&Record {
+            name: PEER_HOST,
+            ttl,
+            data: RecordData::A(Ipv4Address([192, 168, 1, 2])),
+        });
+        msg.additional(&Record {
+            name: WEB_INSTANCE,
+            ttl,
+            data: RecordData::Srv {
+                priority: 0,
+                weight: 0,
+                port: 8080,
+                target: PEER_HOST,
+            },
+        });
+        msg.additional(&Record {
+            name: WEB_INSTANCE,
+            ttl,
+            data: RecordData::Txt(b"\x06path=/\x00"),
+        });
+        let len = msg.finish(0, response_flags());
+        buffer.truncate(len);
+        buffer
+    }
+
+    #[test]
+    fn test_browse() {
+        let mut s = socket();
+        let mut cx = Context::mock();
+        let browse = s.browse("_http._tcp.local").unwrap();
+
+        let (_, _, payload) = send(&mut s, &mut cx).unwrap();
+        let p = Packet::new_checked(&payload[..]).unwrap();
+        assert_eq!(p.question_count(), 1);
+        assert_eq!(
+            Question::parse(p.payload()).unwrap().1,
+            Question {
+                name: HTTP_TYPE,
+                type_: Type::Ptr
+            }
+        );
+
+        assert!(recv(&mut s, &mut cx, MDNS_PORT, &response(120)));
+        let handle = match s.poll_event() {
+            Some(Event::Found(handle)) => handle,
+            event => panic!("unexpected event {:?}", event),
+        };
+        assert_eq!(s.poll_event(), Some(Event::Resolved(handle)));
+        assert_eq!(s.poll_event(), None);
+
+        let instance = s.instance(handle).unwrap();
+        assert_eq!(instance.browse(), browse);
+        assert_eq!(instance.instance_name(), b"web");
+        assert_eq!(instance.port(), Some(8080));
+        assert_eq!(instance.target(), PEER_HOST);
+        assert_eq!(instance.txt().collect::<StdVec<_>>(), vec![b"path=/"]);
+        assert_eq!(instance.addresses(), &[PEER_ADDR]);
+
+        // A goodbye removes the instance.
+        assert!(recv(&mut s, &mut cx, MDNS_PORT, &response(0)));
+        send(&mut s, &mut cx);
+        assert_eq!(s.poll_event(), Some(Event::Lost(handle)));
+        assert!(s.instance(handle).is_some());
+        assert_eq!(s.poll_event(), None);
+        assert!(s.instance(handle).is_none());
+    }
+
+    #[test]
+    fn test_browse_resolve() {
+        let mut s = socket();
+        let mut cx = Context::mock();
+        s.browse("_http._tcp").unwrap();
+        send(&mut s, &mut cx).unwrap();
+
+        // Only the PTR record is received, the rest has to be asked for.
+        let mut payload = response(120);
+        Packet::new_unchecked(&mut payload[..]).set_additional_record_count(0);
+        assert!(recv(&mut s, &mut cx, MDNS_PORT, &payload));
+        assert!(matches!(s.poll_event(), Some(Event::Found(_))));
+
+        let (_, _, payload) = send(&mut s, &mut cx).unwrap();
+        let p = Packet::new_checked(&payload[..]).unwrap();
+        assert_eq!(p.question_count(), 2);
+        let (rest, question) = Question::parse(p.payload()).unwrap();
+        assert_eq!(question.name, WEB_INSTANCE);
+        assert_eq!(question.type_, Type::Srv);
+        assert_eq!(Question::parse(rest).unwrap().1.type_, Type::Txt);
+    }
+}
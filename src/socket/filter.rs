@@ -0,0 +1,172 @@
+/*! Filter programs for raw and packet sockets.
+
+A [`Filter`] decides whether an incoming packet is delivered to a socket. It runs before
+the packet is copied into the receive buffer, so unwanted packets do not use up the
+buffer space of the socket.
+
+A filter is either a callback, or a small program in the spirit of classic BPF: a
+sequence of [`Insn`]s operating on a 32-bit accumulator. Jumps only go forward, so every
+program terminates after at most one pass over its instructions.
+
+```rust
+use smoltcp::socket::filter::{Filter, Insn};
+
+// Accept IPv4 packets carrying UDP, sent to port 53.
+const DNS: &[Insn] = &[
+    Insn::LoadU8(9),
+    Insn::JumpEq { k: 17, jt: 0, jf: 3 },
+    Insn::LoadU16(22),
+    Insn::JumpEq { k: 53, jt: 0, jf: 1 },
+    Insn::Accept,
+    Insn::Reject,
+];
+
+let filter = Filter::Program(DNS);
+# let _ = filter;
+```
+*/
+
+/// An instruction of a filter program.
+///
+/// Offsets are counted from the start of the packet. Jump targets are counted from the
+/// instruction following the jump, so that a target of 0 falls through.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Insn {
+    /// Load the octet at the given offset into the accumulator.
+    LoadU8(u16),
+    /// Load the big-endian 16-bit word at the given offset into the accumulator.
+    LoadU16(u16),
+    /// Load the big-endian 32-bit word at the given offset into the accumulator.
+    LoadU32(u16),
+    /// Load the length of the packet into the accumulator.
+    LoadLen,
+    /// Mask the accumulator with the given value.
+    And(u32),
+    /// Shift the accumulator right by the given number of bits.
+    Shr(u8),
+    /// Jump by `jt` instructions if the accumulator equals `k`, by `jf` otherwise.
+    JumpEq { k: u32, jt: u8, jf: u8 },
+    /// Jump by `jt` instructions if the accumulator is greater than `k`, by `jf` otherwise.
+    JumpGt { k: u32, jt: u8, jf: u8 },
+    /// Jump by `jt` instructions if the accumulator has any bit of `k` set, by `jf` otherwise.
+    JumpSet { k: u32, jt: u8, jf: u8 },
+    /// Deliver the packet.
+    Accept,
+    /// Drop the packet.
+    Reject,
+}
+
+/// A filter deciding which incoming packets a socket receives.
+///
+/// The packet is presented in two parts: its header and its payload. For a packet socket,
+/// the header is the Ethernet header; for a raw socket, it is the IP header. Program
+/// offsets are counted across both parts as if they were contiguous.
+#[derive(Debug, Clone, Copy)]
+pub enum Filter<'a> {
+    /// Run a filter program. A program that loads past the end of the packet, or runs
+    /// past its last instruction, rejects the packet.
+    Program(&'a [Insn]),
+    /// Call a function with the header and the payload of the packet.
+    Callback(fn(&[u8], &[u8]) -> bool),
+}
+
+impl<'a> Filter<'a> {
+    /// Return whether a packet with the given header and payload passes the filter.
+    pub fn run(&self, header: &[u8], payload: &[u8]) -> bool {
+        match *self {
+            Filter::Program(program) => run_program(program, header, payload).unwrap_or(false),
+            Filter::Callback(f) => f(header, payload),
+        }
+    }
+}
+
+fn load(header: &[u8], payload: &[u8], offset: u16, len: usize) -> Option<u32> {
+    let mut value = 0;
+    for i in offset as usize..offset as usize + len {
+        let octet = match i.checked_sub(header.len()) {
+            None => header[i],
+            Some(i) => *payload.get(i)?,
+        };
+        value = (value << 8) | octet as u32;
+    }
+    Some(value)
+}
+
+fn run_program(program: &[Insn], header: &[u8], payload: &[u8]) -> Option<bool> {
+    let mut acc = 0u32;
+    let mut pc = 0;
+    loop {
+        let jump = |cond: bool, jt: u8, jf: u8| usize::from(if cond { jt } else { jf });
+        match *program.get(pc)? {
+            Insn::LoadU8(offset) => acc = load(header, payload, offset, 1)?,
+            Insn::LoadU16(offset) => acc = load(header, payload, offset, 2)?,
+            Insn::LoadU32(offset) => acc = load(header, payload, offset, 4)?,
+            Insn::LoadLen => acc = (header.len() + payload.len()) as u32,
+            Insn::And(k) => acc &= k,
+            Insn::Shr(k) => acc = acc.checked_shr(k as u32).unwrap_or(0),
+            Insn::JumpEq { k, jt, jf } => pc += jump(acc == k, jt, jf),
+            Insn::JumpGt { k, jt, jf } => pc += jump(acc > k, jt, jf),
+            Insn::JumpSet { k, jt, jf } => pc += jump(acc & k != 0, jt, jf),
+            Insn::Accept => return Some(true),
+            Insn::Reject => return Some(false),
+        }
+        pc += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HEADER: [u8; 4] = [0x45, 0x00, 0x12, 0x34];
+    const PAYLOAD: [u8; 4] = [0x56, 0x78, 0x9a, 0xbc];
+
+    #[test]
+    fn test_load_across_parts() {
+        assert_eq!(load(&HEADER, &PAYLOAD, 2, 4), Some(0x12345678));
+        assert_eq!(load(&HEADER, &PAYLOAD, 6, 2), Some(0x9abc));
+        assert_eq!(load(&HEADER, &PAYLOAD, 7, 2), None);
+        assert_eq!(load(&HEADER, &[], 3, 2), None);
+    }
+
+    #[test]
+    fn test_program() {
+        // Accept if the high nibble of the first octet is 4 and the packet is short.
+        const PROGRAM: &[Insn] = &[
+            Insn::LoadU8(0),
+            Insn::Shr(4),
+            Insn::JumpEq { k: 4, jt: 0, jf: 3 },
+            Insn::LoadLen,
+            Insn::JumpGt { k: 8, jt: 1, jf: 0 },
+            Insn::Accept,
+            Insn::Reject,
+        ];
+        let filter = Filter::Program(PROGRAM);
+        assert!(filter.run(&HEADER, &PAYLOAD));
+        assert!(!filter.run(&HEADER, &[0; 5]));
+        assert!(!filter.run(&[0x60], &PAYLOAD));
+    }
+
+    #[test]
+    fn test_program_rejects() {
+        // Out of bounds loads reject.
+        let filter = Filter::Program(&[Insn::LoadU32(6), Insn::Accept]);
+        assert!(!filter.run(&HEADER, &PAYLOAD));
+        // Running off the end rejects.
+        let filter = Filter::Program(&[Insn::JumpSet {
+            k: 0x80,
+            jt: 0,
+            jf: 1,
+        }]);
+        assert!(!filter.run(&HEADER, &PAYLOAD));
+        assert!(!Filter::Program(&[]).run(&HEADER, &PAYLOAD));
+    }
+
+    #[test]
+    fn test_callback() {
+        let filter = Filter::Callback(|header, payload| header.len() == 4 && payload[0] == 0x56);
+        assert!(filter.run(&HEADER, &PAYLOAD));
+        assert!(!filter.run(&PAYLOAD, &HEADER));
+    }
+}
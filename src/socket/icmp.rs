@@ -10,9 +10,9 @@ use crate::socket::{Context, PollAt};
 use crate::storage::Empty;
 use crate::wire::IcmpRepr;
 #[cfg(feature = "proto-ipv4")]
-use crate::wire::{Icmpv4Packet, Icmpv4Repr, Ipv4Repr};
+use crate::wire::{Icmpv4Message, Icmpv4Packet, Icmpv4Repr, Ipv4Repr};
 #[cfg(feature = "proto-ipv6")]
-use crate::wire::{Icmpv6Packet, Icmpv6Repr, Ipv6Repr};
+use crate::wire::{Icmpv6Message, Icmpv6Packet, Icmpv6Repr, Ipv6Repr};
 use crate::wire::{IpAddress, IpListenEndpoint, IpProtocol, IpRepr};
 use crate::wire::{UdpPacket, UdpRepr};
 
@@ -69,6 +69,96 @@ impl Default for Endpoint {
     }
 }
 
+/// A filter on the type and code of the messages an ICMP socket receives.
+///
+/// The filter applies on top of the endpoint the socket is bound to, so that several
+/// sockets bound to the same endpoint can each receive a different kind of message.
+/// Every message the socket accepts is delivered to it, even if another socket or the
+/// interface itself handles it as well.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Filter {
+    #[cfg(feature = "proto-ipv4")]
+    ipv4: [u32; 8],
+    #[cfg(feature = "proto-ipv6")]
+    ipv6: [u32; 8],
+    code: Option<u8>,
+}
+
+impl Filter {
+    /// Return a filter passing every message.
+    pub const fn pass_all() -> Filter {
+        Filter {
+            #[cfg(feature = "proto-ipv4")]
+            ipv4: [u32::MAX; 8],
+            #[cfg(feature = "proto-ipv6")]
+            ipv6: [u32::MAX; 8],
+            code: None,
+        }
+    }
+
+    /// Return a filter blocking every message.
+    pub const fn block_all() -> Filter {
+        Filter {
+            #[cfg(feature = "proto-ipv4")]
+            ipv4: [0; 8],
+            #[cfg(feature = "proto-ipv6")]
+            ipv6: [0; 8],
+            code: None,
+        }
+    }
+
+    /// Pass or block ICMPv4 messages of the given type.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn set_ipv4(&mut self, msg_type: Icmpv4Message, pass: bool) {
+        set_bit(&mut self.ipv4, msg_type.into(), pass)
+    }
+
+    /// Pass or block ICMPv6 messages of the given type.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn set_ipv6(&mut self, msg_type: Icmpv6Message, pass: bool) {
+        set_bit(&mut self.ipv6, msg_type.into(), pass)
+    }
+
+    /// Only pass messages with the given code, or messages with any code if `None`.
+    pub fn set_code(&mut self, code: Option<u8>) {
+        self.code = code
+    }
+
+    /// Return whether an ICMPv4 message of the given type and code passes the filter.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn passes_ipv4(&self, msg_type: Icmpv4Message, msg_code: u8) -> bool {
+        get_bit(&self.ipv4, msg_type.into()) && self.code.map_or(true, |code| code == msg_code)
+    }
+
+    /// Return whether an ICMPv6 message of the given type and code passes the filter.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn passes_ipv6(&self, msg_type: Icmpv6Message, msg_code: u8) -> bool {
+        get_bit(&self.ipv6, msg_type.into()) && self.code.map_or(true, |code| code == msg_code)
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter::pass_all()
+    }
+}
+
+#[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
+fn set_bit(bits: &mut [u32; 8], index: u8, value: bool) {
+    let mask = 1 << (index % 32);
+    if value {
+        bits[index as usize / 32] |= mask;
+    } else {
+        bits[index as usize / 32] &= !mask;
+    }
+}
+
+#[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
+fn get_bit(bits: &[u32; 8], index: u8) -> bool {
+    bits[index as usize / 32] & (1 << (index % 32)) != 0
+}
+
 /// An ICMP packet metadata.
 pub type PacketMetadata = crate::storage::PacketMetadata<IpAddress>;
 
@@ -90,8 +180,11 @@ pub struct Socket<'a> {
     tx_buffer: PacketBuffer<'a>,
     /// The endpoint this socket is communicating with
     endpoint: Endpoint,
+    filter: Filter,
     /// The time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
     hop_limit: Option<u8>,
+    /// The traffic class (IPv6) or type of service (IPv4) value used in outgoing packets.
+    traffic_class: Option<u8>,
     #[cfg(feature = "async")]
     rx_waker: WakerRegistration,
     #[cfg(feature = "async")]
@@ -107,7 +200,9 @@ impl<'a> Socket<'a> {
             rx_buffer: rx_buffer,
             tx_buffer: tx_buffer,
             endpoint: Default::default(),
+            filter: Filter::pass_all(),
             hop_limit: None,
+            traffic_class: None,
             #[cfg(feature = "async")]
             rx_waker: WakerRegistration::new(),
             #[cfg(feature = "async")]
@@ -236,6 +331,36 @@ impl<'a> Socket<'a> {
         self.hop_limit = hop_limit
     }
 
+    /// Return the traffic class (IPv6) or type of service (IPv4) value used in outgoing
+    /// packets.
+    ///
+    /// See also the [set_traffic_class](#method.set_traffic_class) method
+    pub fn traffic_class(&self) -> Option<u8> {
+        self.traffic_class
+    }
+
+    /// Set the traffic class (IPv6) or type of service (IPv4) value used in outgoing packets.
+    ///
+    /// For IPv4 only the DSCP, i.e. the upper six bits, is used. A socket without an
+    /// explicitly set traffic class sends packets with a traffic class of zero.
+    pub fn set_traffic_class(&mut self, traffic_class: Option<u8>) {
+        self.traffic_class = traffic_class
+    }
+
+    /// Return the filter on the type and code of received messages.
+    ///
+    /// See also the [set_filter](#method.set_filter) method
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    /// Set the filter on the type and code of received messages.
+    ///
+    /// By default, every message matching the endpoint is received.
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter
+    }
+
     /// Bind the socket to the given endpoint.
     ///
     /// This function returns `Err(Error::Illegal)` if the socket was open
@@ -270,7 +395,8 @@ impl<'a> Socket<'a> {
     /// To [send] and [recv] ICMP packets that are not associated with a specific UDP
     /// port, the socket may be bound to a specific ICMP identifier using
     /// [IcmpEndpoint::Ident]. This is useful for sending and receiving Echo Request/Reply
-    /// messages.
+    /// messages. Error messages quoting an Echo Request with the identifier, such as the
+    /// Time Exceeded messages traceroute relies on, are received too.
     ///
     /// ```
     /// use smoltcp::wire::IpListenEndpoint;
@@ -285,7 +411,38 @@ impl<'a> Socket<'a> {
     /// icmp_socket.bind(icmp::Endpoint::Ident(0x1234)).unwrap();
     /// ```
     ///
+    /// The same applies to the Extended Echo Request/Reply messages of [RFC 8335], which
+    /// probe the state of an interface of the remote node or of one of its neighbors.
+    ///
+    /// ## Answer probes:
+    ///
+    /// The interface silently discards Extended Echo Requests, since [RFC 8335] requires
+    /// probing to be explicitly enabled. To answer them, the socket may be bound to a local
+    /// address using [IcmpEndpoint::Ip] and filtered to Extended Echo Requests, and
+    /// [send] an Extended Echo Reply describing the probed interface for each of them.
+    ///
+    /// ```
+    /// # #[cfg(feature = "proto-ipv4")]
+    /// # {
+    /// use smoltcp::wire::{Icmpv4Message, Ipv4Address};
+    /// use smoltcp::socket::icmp;
+    /// # let rx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 20]);
+    /// # let tx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 20]);
+    ///
+    /// let mut icmp_socket = // ...
+    /// # icmp::Socket::new(rx_buffer, tx_buffer);
+    ///
+    /// // Receive the probes sent to 192.168.1.1
+    /// icmp_socket.bind(icmp::Endpoint::Ip(Ipv4Address::new(192, 168, 1, 1).into())).unwrap();
+    /// let mut filter = icmp::Filter::block_all();
+    /// filter.set_ipv4(Icmpv4Message::ExtendedEchoRequest, true);
+    /// icmp_socket.set_filter(filter);
+    /// # }
+    /// ```
+    ///
+    /// [RFC 8335]: https://www.rfc-editor.org/rfc/rfc8335
     /// [is_specified]: enum.IcmpEndpoint.html#method.is_specified
+    /// [IcmpEndpoint::Ip]: enum.IcmpEndpoint.html#variant.Ip
     /// [IcmpEndpoint::Ident]: enum.IcmpEndpoint.html#variant.Ident
     /// [IcmpEndpoint::Udp]: enum.IcmpEndpoint.html#variant.Udp
     /// [send]: #method.send
@@ -449,6 +606,7 @@ impl<'a> Socket<'a> {
                 &Endpoint::Udp(endpoint),
                 &IcmpRepr::Ipv4(
                     Icmpv4Repr::DstUnreachable { data, header, .. }
+                    | Icmpv4Repr::FragRequired { data, header, .. }
                     | Icmpv4Repr::TimeExceeded { data, header, .. },
                 ),
             ) if endpoint.addr.is_none() || endpoint.addr == Some(ip_repr.dst_addr()) => {
@@ -482,26 +640,67 @@ impl<'a> Socket<'a> {
                     Err(_) => false,
                 }
             }
-            // If we are bound to a specific ICMP identifier value, only accept an
-            // Echo Request/Reply with the identifier field matching the endpoint
-            // port.
+            // If we are bound to a specific ICMP identifier value, accept errors
+            // caused by an Echo Request or Extended Echo Request we sent with it.
             #[cfg(feature = "proto-ipv4")]
             (
                 &Endpoint::Ident(bound_ident),
-                &IcmpRepr::Ipv4(Icmpv4Repr::EchoRequest { ident, .. }),
-            )
-            | (
+                &IcmpRepr::Ipv4(
+                    Icmpv4Repr::DstUnreachable { data, header, .. }
+                    | Icmpv4Repr::FragRequired { data, header, .. }
+                    | Icmpv4Repr::TimeExceeded { data, header, .. },
+                ),
+            ) if header.next_header == IpProtocol::Icmp => match Icmpv4Packet::new_checked(data) {
+                Ok(packet) => {
+                    matches!(
+                        packet.msg_type(),
+                        Icmpv4Message::EchoRequest | Icmpv4Message::ExtendedEchoRequest
+                    ) && packet.echo_ident() == bound_ident
+                }
+                Err(_) => false,
+            },
+            #[cfg(feature = "proto-ipv6")]
+            (
                 &Endpoint::Ident(bound_ident),
-                &IcmpRepr::Ipv4(Icmpv4Repr::EchoReply { ident, .. }),
+                &IcmpRepr::Ipv6(
+                    Icmpv6Repr::DstUnreachable { data, header, .. }
+                    | Icmpv6Repr::PktTooBig { data, header, .. }
+                    | Icmpv6Repr::TimeExceeded { data, header, .. }
+                    | Icmpv6Repr::ParamProblem { data, header, .. },
+                ),
+            ) if header.next_header == IpProtocol::Icmpv6 => {
+                match Icmpv6Packet::new_checked(data) {
+                    Ok(packet) => {
+                        matches!(
+                            packet.msg_type(),
+                            Icmpv6Message::EchoRequest | Icmpv6Message::ExtendedEchoRequest
+                        ) && packet.echo_ident() == bound_ident
+                    }
+                    Err(_) => false,
+                }
+            }
+            // If we are bound to a specific ICMP identifier value, only accept an
+            // Echo Request/Reply or Extended Echo Request/Reply with the identifier
+            // field matching the endpoint port.
+            #[cfg(feature = "proto-ipv4")]
+            (
+                &Endpoint::Ident(bound_ident),
+                &IcmpRepr::Ipv4(
+                    Icmpv4Repr::EchoRequest { ident, .. }
+                    | Icmpv4Repr::EchoReply { ident, .. }
+                    | Icmpv4Repr::ExtendedEchoRequest { ident, .. }
+                    | Icmpv4Repr::ExtendedEchoReply { ident, .. },
+                ),
             ) => ident == bound_ident,
             #[cfg(feature = "proto-ipv6")]
             (
                 &Endpoint::Ident(bound_ident),
-                &IcmpRepr::Ipv6(Icmpv6Repr::EchoRequest { ident, .. }),
-            )
-            | (
-                &Endpoint::Ident(bound_ident),
-                &IcmpRepr::Ipv6(Icmpv6Repr::EchoReply { ident, .. }),
+                &IcmpRepr::Ipv6(
+                    Icmpv6Repr::EchoRequest { ident, .. }
+                    | Icmpv6Repr::EchoReply { ident, .. }
+                    | Icmpv6Repr::ExtendedEchoRequest { ident, .. }
+                    | Icmpv6Repr::ExtendedEchoReply { ident, .. },
+                ),
             ) => ident == bound_ident,
             _ => {
                 match (&self.endpoint, ip_repr) {
@@ -568,6 +767,7 @@ impl<'a> Socket<'a> {
         F: FnOnce(&mut Context, (IpRepr, IcmpRepr)) -> Result<(), E>,
     {
         let hop_limit = self.hop_limit.unwrap_or(64);
+        let traffic_class = self.traffic_class.unwrap_or(0);
         let res = self.tx_buffer.dequeue_with(|remote_endpoint, packet_buf| {
             net_trace!(
                 "icmp:{}: sending {} octets",
@@ -604,6 +804,7 @@ impl<'a> Socket<'a> {
                         next_header: IpProtocol::Icmp,
                         payload_len: repr.buffer_len(),
                         hop_limit: hop_limit,
+                        dscp: traffic_class >> 2,
                     });
                     emit(cx, (ip_repr, IcmpRepr::Ipv4(repr)))
                 }
@@ -641,6 +842,8 @@ impl<'a> Socket<'a> {
                         next_header: IpProtocol::Icmpv6,
                         payload_len: repr.buffer_len(),
                         hop_limit: hop_limit,
+                        traffic_class: traffic_class,
+                        flow_label: 0,
                     });
                     emit(cx, (ip_repr, IcmpRepr::Ipv6(repr)))
                 }
@@ -696,7 +899,10 @@ mod tests_common {
 #[cfg(all(test, feature = "proto-ipv4"))]
 mod test_ipv4 {
     use super::tests_common::*;
-    use crate::wire::{Icmpv4DstUnreachable, IpEndpoint, Ipv4Address};
+    use crate::wire::{
+        IcmpExtendedEchoCode, IcmpExtendedEchoState, IcmpInterfaceIdent, Icmpv4DstUnreachable,
+        Icmpv4TimeExceeded, IpEndpoint, Ipv4Address,
+    };
 
     const REMOTE_IPV4: Ipv4Address = Ipv4Address([192, 168, 1, 2]);
     const LOCAL_IPV4: Ipv4Address = Ipv4Address([192, 168, 1, 1]);
@@ -717,6 +923,7 @@ mod test_ipv4 {
         next_header: IpProtocol::Icmp,
         payload_len: 24,
         hop_limit: 0x40,
+        dscp: 0,
     });
 
     static REMOTE_IPV4_REPR: IpRepr = IpRepr::Ipv4(Ipv4Repr {
@@ -725,6 +932,7 @@ mod test_ipv4 {
         next_header: IpProtocol::Icmp,
         payload_len: 24,
         hop_limit: 0x40,
+        dscp: 0,
     });
 
     #[test]
@@ -818,6 +1026,7 @@ mod test_ipv4 {
                         next_header: IpProtocol::Icmp,
                         payload_len: ECHOV4_REPR.buffer_len(),
                         hop_limit: 0x2a,
+                        dscp: 0,
                     })
                 );
                 Ok::<_, ()>(())
@@ -826,6 +1035,32 @@ mod test_ipv4 {
         );
     }
 
+    #[test]
+    fn test_set_traffic_class_v4() {
+        let mut s = socket(buffer(0), buffer(1));
+        let mut cx = Context::mock();
+        let checksum = ChecksumCapabilities::default();
+
+        let mut bytes = [0xff; 24];
+        let mut packet = Icmpv4Packet::new_unchecked(&mut bytes);
+        ECHOV4_REPR.emit(&mut packet, &checksum);
+
+        s.set_traffic_class(Some(0xb8));
+
+        assert_eq!(
+            s.send_slice(&*packet.into_inner(), REMOTE_IPV4.into()),
+            Ok(())
+        );
+        assert_eq!(
+            s.dispatch(&mut cx, |_, (ip_repr, _)| {
+                assert_eq!(ip_repr.hop_limit(), 64);
+                assert_eq!(ip_repr.traffic_class(), 0xb8);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+    }
+
     #[test]
     fn test_recv_process() {
         let mut socket = socket(buffer(1), buffer(1));
@@ -874,6 +1109,95 @@ mod test_ipv4 {
         assert!(!socket.accepts(&mut cx, &REMOTE_IPV4_REPR, &icmp_repr.into()));
     }
 
+    #[test]
+    fn test_accepts_ident_error() {
+        let mut socket = socket(buffer(1), buffer(1));
+        let mut cx = Context::mock();
+        assert_eq!(socket.bind(Endpoint::Ident(0x1234)), Ok(()));
+
+        let checksum = ChecksumCapabilities::default();
+        let mut bytes = [0xff; 24];
+        ECHOV4_REPR.emit(&mut Icmpv4Packet::new_unchecked(&mut bytes), &checksum);
+
+        // A router on the path reports that the echo request we sent expired.
+        let icmp_repr = Icmpv4Repr::TimeExceeded {
+            reason: Icmpv4TimeExceeded::TtlExpired,
+            header: Ipv4Repr {
+                src_addr: LOCAL_IPV4,
+                dst_addr: REMOTE_IPV4,
+                next_header: IpProtocol::Icmp,
+                payload_len: 24,
+                hop_limit: 1,
+                dscp: 0,
+            },
+            data: &bytes[..8],
+        };
+        assert!(socket.accepts(&mut cx, &REMOTE_IPV4_REPR, &icmp_repr.into()));
+
+        let mut socket = self::socket(buffer(1), buffer(1));
+        assert_eq!(socket.bind(Endpoint::Ident(0x4321)), Ok(()));
+        assert!(!socket.accepts(&mut cx, &REMOTE_IPV4_REPR, &icmp_repr.into()));
+    }
+
+    #[test]
+    fn test_accepts_ext_echo() {
+        let mut socket = socket(buffer(1), buffer(1));
+        let mut cx = Context::mock();
+        assert_eq!(socket.bind(Endpoint::Ident(0x1234)), Ok(()));
+
+        let icmp_repr = Icmpv4Repr::ExtendedEchoReply {
+            ident: 0x1234,
+            seq_no: 1,
+            code: IcmpExtendedEchoCode::NoError,
+            state: IcmpExtendedEchoState::Reserved,
+            active: true,
+            ipv4: true,
+            ipv6: false,
+        };
+        assert!(socket.accepts(&mut cx, &REMOTE_IPV4_REPR, &icmp_repr.into()));
+
+        let checksum = ChecksumCapabilities::default();
+        let request = Icmpv4Repr::ExtendedEchoRequest {
+            ident: 0x1234,
+            seq_no: 1,
+            local: true,
+            interface: IcmpInterfaceIdent::Name("eth0"),
+        };
+        let mut bytes = [0xff; 24];
+        request.emit(&mut Icmpv4Packet::new_unchecked(&mut bytes), &checksum);
+
+        // The probed node does not support probing.
+        let icmp_repr = Icmpv4Repr::DstUnreachable {
+            reason: Icmpv4DstUnreachable::ProtoUnreachable,
+            header: Ipv4Repr {
+                src_addr: LOCAL_IPV4,
+                dst_addr: REMOTE_IPV4,
+                next_header: IpProtocol::Icmp,
+                payload_len: 24,
+                hop_limit: 64,
+                dscp: 0,
+            },
+            data: &bytes[..8],
+        };
+        assert!(socket.accepts(&mut cx, &REMOTE_IPV4_REPR, &icmp_repr.into()));
+
+        let mut socket = self::socket(buffer(1), buffer(1));
+        assert_eq!(socket.bind(Endpoint::Ident(0x4321)), Ok(()));
+        assert!(!socket.accepts(&mut cx, &REMOTE_IPV4_REPR, &request.into()));
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut filter = Filter::block_all();
+        assert!(!filter.passes_ipv4(Icmpv4Message::DstUnreachable, 3));
+        filter.set_ipv4(Icmpv4Message::DstUnreachable, true);
+        assert!(filter.passes_ipv4(Icmpv4Message::DstUnreachable, 3));
+        assert!(!filter.passes_ipv4(Icmpv4Message::TimeExceeded, 0));
+        filter.set_code(Some(3));
+        assert!(!filter.passes_ipv4(Icmpv4Message::DstUnreachable, 1));
+        assert!(Filter::pass_all().passes_ipv4(Icmpv4Message::Unknown(255), 7));
+    }
+
     #[test]
     fn test_accepts_udp() {
         let mut socket = socket(buffer(1), buffer(1));
@@ -903,6 +1227,7 @@ mod test_ipv4 {
                 next_header: IpProtocol::Icmp,
                 payload_len: 12,
                 hop_limit: 0x40,
+                dscp: 0,
             },
             data: data,
         };
@@ -912,6 +1237,7 @@ mod test_ipv4 {
             next_header: IpProtocol::Icmp,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 0x40,
+            dscp: 0,
         });
 
         assert!(!socket.can_recv());
@@ -951,6 +1277,7 @@ mod test_ipv4 {
                 next_header: IpProtocol::Icmp,
                 payload_len: 12,
                 hop_limit: 0x40,
+                dscp: 0,
             },
             data,
         };
@@ -960,6 +1287,7 @@ mod test_ipv4 {
             next_header: IpProtocol::Icmp,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 0x40,
+            dscp: 0,
         });
 
         assert!(!socket.can_recv());
@@ -1005,6 +1333,8 @@ mod test_ipv6 {
         next_header: IpProtocol::Icmpv6,
         payload_len: 24,
         hop_limit: 0x40,
+        traffic_class: 0,
+        flow_label: 0,
     });
 
     static REMOTE_IPV6_REPR: IpRepr = IpRepr::Ipv6(Ipv6Repr {
@@ -1013,6 +1343,8 @@ mod test_ipv6 {
         next_header: IpProtocol::Icmpv6,
         payload_len: 24,
         hop_limit: 0x40,
+        traffic_class: 0,
+        flow_label: 0,
     });
 
     #[test]
@@ -1116,6 +1448,8 @@ mod test_ipv6 {
                         next_header: IpProtocol::Icmpv6,
                         payload_len: ECHOV6_REPR.buffer_len(),
                         hop_limit: 0x2a,
+                        traffic_class: 0,
+                        flow_label: 0,
                     })
                 );
                 Ok::<_, ()>(())
@@ -1211,6 +1545,8 @@ mod test_ipv6 {
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 12,
                 hop_limit: 0x40,
+                traffic_class: 0,
+                flow_label: 0,
             },
             data: data,
         };
@@ -1220,6 +1556,8 @@ mod test_ipv6 {
             next_header: IpProtocol::Icmpv6,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 0x40,
+            traffic_class: 0,
+            flow_label: 0,
         });
 
         assert!(!socket.can_recv());
@@ -1264,6 +1602,8 @@ mod test_ipv6 {
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 12,
                 hop_limit: 0x40,
+                traffic_class: 0,
+                flow_label: 0,
             },
             data: data,
         };
@@ -1273,6 +1613,8 @@ mod test_ipv6 {
             next_header: IpProtocol::Icmpv6,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 0x40,
+            traffic_class: 0,
+            flow_label: 0,
         });
 
         assert!(!socket.can_recv());
@@ -20,21 +20,72 @@ pub mod dhcpv4;
 pub mod dhcpv6;
 #[cfg(feature = "socket-dns")]
 pub mod dns;
+#[cfg(feature = "socket-dns-sd")]
+pub mod dns_sd;
+#[cfg(any(feature = "socket-raw", feature = "socket-packet"))]
+pub mod filter;
 #[cfg(feature = "socket-icmp")]
 pub mod icmp;
+#[cfg(feature = "socket-packet")]
+pub mod packet;
 #[cfg(feature = "socket-raw")]
 pub mod raw;
+#[cfg(feature = "socket-stamp")]
+pub mod stamp;
 #[cfg(feature = "socket-tcp")]
 pub mod tcp;
+#[cfg(feature = "socket-tftp")]
+pub mod tftp;
 #[cfg(feature = "socket-udp")]
 pub mod udp;
 
+#[cfg(all(
+    feature = "proto-igmp",
+    any(feature = "socket-udp", feature = "socket-raw")
+))]
+mod multicast;
+#[cfg(any(feature = "socket-udp", feature = "socket-raw"))]
+mod timestamp;
 #[cfg(feature = "async")]
 mod waker;
 
+#[cfg(all(
+    feature = "proto-igmp",
+    any(feature = "socket-udp", feature = "socket-raw")
+))]
+pub use self::multicast::MembershipError;
+#[cfg(all(
+    feature = "proto-igmp",
+    any(feature = "socket-udp", feature = "socket-raw")
+))]
+pub(crate) use self::multicast::Memberships;
+#[cfg(any(feature = "socket-udp", feature = "socket-raw"))]
+pub use self::timestamp::TxTimestamp;
+#[cfg(any(feature = "socket-udp", feature = "socket-raw"))]
+pub(crate) use self::timestamp::TxTimestamps;
+
 #[cfg(feature = "async")]
 pub(crate) use self::waker::WakerRegistration;
 
+/// The captive portal URI that tells a network has no captive portal, see [RFC 8910 § 2].
+///
+/// [RFC 8910 § 2]: https://tools.ietf.org/html/rfc8910#section-2
+#[cfg(any(feature = "socket-dhcpv4", feature = "socket-dhcpv6"))]
+const CAPTIVE_PORTAL_UNRESTRICTED: &str = "urn:ietf:params:capport:unrestricted";
+
+/// Copy the captive portal URI received from a server, unless it tells there is none.
+#[cfg(any(feature = "socket-dhcpv4", feature = "socket-dhcpv6"))]
+pub(crate) fn captive_portal(
+    uri: &str,
+) -> Option<heapless::String<{ crate::wire::DHCP_MAX_CAPTIVE_PORTAL_LEN }>> {
+    if uri.is_empty() || uri == CAPTIVE_PORTAL_UNRESTRICTED {
+        return None;
+    }
+    let mut portal = heapless::String::new();
+    portal.push_str(uri).ok()?;
+    Some(portal)
+}
+
 /// Gives an indication on the next time the socket should be polled.
 #[derive(Debug, PartialOrd, Ord, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -73,6 +124,14 @@ pub enum Socket<'a> {
     Dhcpv6(dhcpv6::Socket<'a>),
     #[cfg(feature = "socket-dns")]
     Dns(dns::Socket<'a>),
+    #[cfg(feature = "socket-dns-sd")]
+    DnsSd(dns_sd::Socket<'a>),
+    #[cfg(feature = "socket-packet")]
+    Packet(packet::Socket<'a>),
+    #[cfg(feature = "socket-tftp")]
+    Tftp(tftp::Socket<'a>),
+    #[cfg(feature = "socket-stamp")]
+    Stamp(stamp::Socket<'a>),
 }
 
 impl<'a> Socket<'a> {
@@ -92,6 +151,14 @@ impl<'a> Socket<'a> {
             Socket::Dhcpv6(s) => s.poll_at(cx),
             #[cfg(feature = "socket-dns")]
             Socket::Dns(s) => s.poll_at(cx),
+            #[cfg(feature = "socket-dns-sd")]
+            Socket::DnsSd(s) => s.poll_at(cx),
+            #[cfg(feature = "socket-packet")]
+            Socket::Packet(s) => s.poll_at(cx),
+            #[cfg(feature = "socket-tftp")]
+            Socket::Tftp(s) => s.poll_at(cx),
+            #[cfg(feature = "socket-stamp")]
+            Socket::Stamp(s) => s.poll_at(cx),
         }
     }
 
@@ -115,6 +182,35 @@ impl<'a> Socket<'a> {
             _ => { }
         }
     }
+
+    /// Extension headers to insert in the IPv6 packets sent by the socket.
+    #[cfg(feature = "proto-ipv6")]
+    pub(crate) fn ipv6_ext_headers(&self) -> Option<&crate::wire::Ipv6ExtHeaders> {
+        match self {
+            #[cfg(feature = "socket-udp")]
+            Socket::Udp(s) => Some(s.ipv6_ext_headers()),
+            #[cfg(feature = "socket-tcp")]
+            Socket::Tcp(s) => Some(s.ipv6_ext_headers()),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+        .filter(|headers: &&crate::wire::Ipv6ExtHeaders| !headers.is_empty())
+    }
+
+    /// Multicast groups the socket asked the interface to join.
+    #[cfg(feature = "proto-igmp")]
+    pub(crate) fn multicast_groups(&self) -> &[crate::wire::Ipv4Address] {
+        match self {
+            #[cfg(feature = "socket-raw")]
+            Socket::Raw(s) => s.multicast_groups(),
+            #[cfg(feature = "socket-udp")]
+            Socket::Udp(s) => s.multicast_groups(),
+            #[cfg(feature = "socket-dns-sd")]
+            Socket::DnsSd(s) => s.multicast_groups(),
+            #[allow(unreachable_patterns)]
+            _ => &[],
+        }
+    }
 }
 
 /// A conversion trait for network sockets.
@@ -168,3 +264,11 @@ from_socket!(dhcpv4::Socket<'a>, Dhcpv4);
 from_socket!(dhcpv6::Socket<'a>, Dhcpv6);
 #[cfg(feature = "socket-dns")]
 from_socket!(dns::Socket<'a>, Dns);
+#[cfg(feature = "socket-dns-sd")]
+from_socket!(dns_sd::Socket<'a>, DnsSd);
+#[cfg(feature = "socket-packet")]
+from_socket!(packet::Socket<'a>, Packet);
+#[cfg(feature = "socket-tftp")]
+from_socket!(tftp::Socket<'a>, Tftp);
+#[cfg(feature = "socket-stamp")]
+from_socket!(stamp::Socket<'a>, Stamp);
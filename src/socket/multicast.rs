@@ -0,0 +1,124 @@
+use heapless::Vec;
+
+use crate::wire::{IpAddress, Ipv4Address};
+
+/// Maximum number of multicast groups a single socket can join.
+pub(crate) const MAX_SOCKET_MULTICAST_GROUPS: usize = 4;
+
+/// Error returned by `join_multicast` on sockets.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MembershipError {
+    /// The address is not an IPv4 multicast address.
+    Unaddressable,
+    /// The socket already joined as many groups as it can.
+    GroupTableFull,
+}
+
+/// The multicast groups joined by a socket.
+///
+/// A socket that joined no group receives every multicast packet the interface accepts.
+/// Once it joined a group, it only receives multicast packets sent to the groups it joined.
+#[derive(Debug, Default)]
+pub(crate) struct Memberships {
+    groups: Vec<Ipv4Address, MAX_SOCKET_MULTICAST_GROUPS>,
+}
+
+impl Memberships {
+    pub fn new() -> Self {
+        Self { groups: Vec::new() }
+    }
+
+    /// Join a group, returning whether it was not already joined.
+    pub fn join(&mut self, addr: IpAddress) -> Result<bool, MembershipError> {
+        let addr = match addr {
+            IpAddress::Ipv4(addr) if addr.is_multicast() => addr,
+            _ => return Err(MembershipError::Unaddressable),
+        };
+        if self.groups.contains(&addr) {
+            return Ok(false);
+        }
+        self.groups
+            .push(addr)
+            .map_err(|_| MembershipError::GroupTableFull)?;
+        Ok(true)
+    }
+
+    /// Leave a group, returning whether it was joined.
+    pub fn leave(&mut self, addr: IpAddress) -> bool {
+        match addr {
+            IpAddress::Ipv4(addr) => match self.groups.iter().position(|group| *group == addr) {
+                Some(index) => {
+                    self.groups.swap_remove(index);
+                    true
+                }
+                None => false,
+            },
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// Leave all groups.
+    pub fn clear(&mut self) {
+        self.groups.clear()
+    }
+
+    /// Return whether a packet sent to `dst_addr` should be delivered to the socket.
+    pub fn accepts(&self, dst_addr: IpAddress) -> bool {
+        match dst_addr {
+            IpAddress::Ipv4(addr) if addr.is_multicast() && !self.groups.is_empty() => {
+                self.groups.contains(&addr)
+            }
+            _ => true,
+        }
+    }
+
+    pub fn groups(&self) -> &[Ipv4Address] {
+        &self.groups
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const GROUP_A: Ipv4Address = Ipv4Address([224, 0, 0, 22]);
+    const GROUP_B: Ipv4Address = Ipv4Address([239, 1, 2, 3]);
+
+    #[test]
+    fn test_join_leave() {
+        let mut memberships = Memberships::new();
+        assert!(memberships.accepts(GROUP_A.into()));
+        assert!(memberships.accepts(GROUP_B.into()));
+
+        assert_eq!(memberships.join(GROUP_A.into()), Ok(true));
+        assert_eq!(memberships.join(GROUP_A.into()), Ok(false));
+        assert!(memberships.accepts(GROUP_A.into()));
+        assert!(!memberships.accepts(GROUP_B.into()));
+        assert!(memberships.accepts(Ipv4Address([192, 168, 1, 1]).into()));
+
+        assert!(memberships.leave(GROUP_A.into()));
+        assert!(!memberships.leave(GROUP_A.into()));
+        assert!(memberships.accepts(GROUP_B.into()));
+    }
+
+    #[test]
+    fn test_join_invalid() {
+        let mut memberships = Memberships::new();
+        assert_eq!(
+            memberships.join(Ipv4Address([192, 168, 1, 1]).into()),
+            Err(MembershipError::Unaddressable)
+        );
+        for i in 0..MAX_SOCKET_MULTICAST_GROUPS {
+            assert_eq!(
+                memberships.join(Ipv4Address([239, 0, 0, i as u8]).into()),
+                Ok(true)
+            );
+        }
+        assert_eq!(
+            memberships.join(GROUP_A.into()),
+            Err(MembershipError::GroupTableFull)
+        );
+    }
+}
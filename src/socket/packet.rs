@@ -0,0 +1,436 @@
+use core::cmp::min;
+#[cfg(feature = "async")]
+use core::task::Waker;
+
+use crate::iface::Context;
+use crate::socket::filter::Filter;
+use crate::socket::PollAt;
+#[cfg(feature = "async")]
+use crate::socket::WakerRegistration;
+
+use crate::storage::Empty;
+use crate::wire::{EthernetFrame, EthernetProtocol};
+
+/// Error returned by [`Socket::send`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SendError {
+    BufferFull,
+}
+
+/// Error returned by [`Socket::recv`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RecvError {
+    Exhausted,
+}
+
+/// A packet socket packet metadata.
+pub type PacketMetadata = crate::storage::PacketMetadata<()>;
+
+/// A packet socket ring buffer.
+pub type PacketBuffer<'a> = crate::storage::PacketBuffer<'a, ()>;
+
+/// A packet socket.
+///
+/// A packet socket sends and receives whole Ethernet frames, header included, like an
+/// `AF_PACKET` socket. It is bound to an EtherType, or receives every frame if it is not.
+/// Received frames are still processed by the interface, so that protocols such as LLDP,
+/// PTP or EAPOL can be implemented next to the IP stack on the same device.
+///
+/// Only frames the interface accepts are received, i.e. those sent to its hardware
+/// address, to the broadcast address or to a multicast address. Frames are sent as-is,
+/// so they must carry the source address of the interface. On interfaces whose medium
+/// is not Ethernet, frames are neither received nor sent.
+#[derive(Debug)]
+pub struct Socket<'a> {
+    ethertype: Option<EthernetProtocol>,
+    rx_buffer: PacketBuffer<'a>,
+    tx_buffer: PacketBuffer<'a>,
+    #[cfg(feature = "async")]
+    rx_waker: WakerRegistration,
+    #[cfg(feature = "async")]
+    tx_waker: WakerRegistration,
+    filter: Option<Filter<'a>>,
+}
+
+impl<'a> Socket<'a> {
+    /// Create a packet socket bound to the given EtherType, or to every EtherType if
+    /// `None`, with the given buffers.
+    pub fn new(
+        ethertype: Option<EthernetProtocol>,
+        rx_buffer: PacketBuffer<'a>,
+        tx_buffer: PacketBuffer<'a>,
+    ) -> Socket<'a> {
+        Socket {
+            ethertype,
+            rx_buffer,
+            tx_buffer,
+            #[cfg(feature = "async")]
+            rx_waker: WakerRegistration::new(),
+            #[cfg(feature = "async")]
+            tx_waker: WakerRegistration::new(),
+            filter: None,
+        }
+    }
+
+    /// Register a waker for receive operations.
+    ///
+    /// The waker is woken on state changes that might affect the return value
+    /// of `recv` method calls, such as receiving data, or the socket closing.
+    ///
+    /// Notes:
+    ///
+    /// - Only one waker can be registered at a time. If another waker was previously registered,
+    ///   it is overwritten and will no longer be woken.
+    /// - The Waker is woken only once. Once woken, you must register it again to receive more wakes.
+    /// - "Spurious wakes" are allowed: a wake doesn't guarantee the result of `recv` has
+    ///   necessarily changed.
+    #[cfg(feature = "async")]
+    pub fn register_recv_waker(&mut self, waker: &Waker) {
+        self.rx_waker.register(waker)
+    }
+
+    /// Adds another waker for receive operations.
+    ///
+    /// See also [register_recv_waker](#method.register_recv_waker).
+    #[cfg(feature = "async")]
+    pub fn add_recv_waker(&mut self, waker: &Waker) {
+        self.rx_waker.add(waker)
+    }
+
+    /// Register a waker for send operations.
+    ///
+    /// The waker is woken on state changes that might affect the return value
+    /// of `send` method calls, such as space becoming available in the transmit
+    /// buffer, or the socket closing.
+    ///
+    /// Notes:
+    ///
+    /// - Only one waker can be registered at a time. If another waker was previously registered,
+    ///   it is overwritten and will no longer be woken.
+    /// - The Waker is woken only once. Once woken, you must register it again to receive more wakes.
+    /// - "Spurious wakes" are allowed: a wake doesn't guarantee the result of `send` has
+    ///   necessarily changed.
+    #[cfg(feature = "async")]
+    pub fn register_send_waker(&mut self, waker: &Waker) {
+        self.tx_waker.register(waker)
+    }
+
+    /// Adds another waker for send operations.
+    ///
+    /// See also [register_send_waker](#method.register_send_waker).
+    #[cfg(feature = "async")]
+    pub fn add_send_waker(&mut self, waker: &Waker) {
+        self.tx_waker.add(waker)
+    }
+
+    /// Clears all the wakers that were assigned to this socket
+    #[cfg(feature = "async")]
+    pub fn clear_wakers(&mut self) {
+        self.rx_waker.clear();
+        self.tx_waker.clear();
+    }
+
+    /// Return the EtherType the socket is bound to.
+    #[inline]
+    pub fn ethertype(&self) -> Option<EthernetProtocol> {
+        self.ethertype
+    }
+
+    /// Return the filter incoming frames are run against, if any.
+    #[inline]
+    pub fn filter(&self) -> Option<&Filter<'a>> {
+        self.filter.as_ref()
+    }
+
+    /// Set the filter incoming frames are run against, or remove it with `None`.
+    ///
+    /// The filter sees the Ethernet header and the payload of every frame of the bound
+    /// EtherType. Frames it rejects are dropped before being copied into the receive buffer.
+    pub fn set_filter(&mut self, filter: Option<Filter<'a>>) {
+        self.filter = filter;
+    }
+
+    /// Check whether the transmit buffer is full.
+    #[inline]
+    pub fn can_send(&self) -> bool {
+        !self.tx_buffer.is_full()
+    }
+
+    /// Check whether the receive buffer is not empty.
+    #[inline]
+    pub fn can_recv(&self) -> bool {
+        !self.rx_buffer.is_empty()
+    }
+
+    /// Return the maximum number packets the socket can receive.
+    #[inline]
+    pub fn packet_recv_capacity(&self) -> usize {
+        self.rx_buffer.packet_capacity()
+    }
+
+    /// Return the maximum number packets the socket can transmit.
+    #[inline]
+    pub fn packet_send_capacity(&self) -> usize {
+        self.tx_buffer.packet_capacity()
+    }
+
+    /// Return the maximum number of bytes inside the recv buffer.
+    #[inline]
+    pub fn payload_recv_capacity(&self) -> usize {
+        self.rx_buffer.payload_capacity()
+    }
+
+    /// Return the maximum number of bytes inside the transmit buffer.
+    #[inline]
+    pub fn payload_send_capacity(&self) -> usize {
+        self.tx_buffer.payload_capacity()
+    }
+
+    /// Enqueue a frame to send, and return a pointer to its contents.
+    ///
+    /// This function returns `Err(SendError::BufferFull)` if the transmit buffer is full
+    /// or does not have enough capacity to ever send this frame.
+    ///
+    /// Frames that are shorter than an Ethernet header, or whose EtherType doesn't match
+    /// the socket's, are silently dropped.
+    pub fn send(&mut self, size: usize) -> Result<&mut [u8], SendError> {
+        let frame_buf = self
+            .tx_buffer
+            .enqueue(size, ())
+            .map_err(|_| SendError::BufferFull)?;
+
+        net_trace!(
+            "packet:{:?}: buffer to send {} octets",
+            self.ethertype,
+            frame_buf.len()
+        );
+        Ok(frame_buf)
+    }
+
+    /// Enqueue a frame to send, and fill it from a slice.
+    ///
+    /// See also [send](#method.send).
+    pub fn send_slice(&mut self, data: &[u8]) -> Result<(), SendError> {
+        self.send(data.len())?.copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Dequeue a frame, and return a pointer to its contents.
+    ///
+    /// This function returns `Err(RecvError::Exhausted)` if the receive buffer is empty.
+    pub fn recv(&mut self) -> Result<&[u8], RecvError> {
+        let ((), frame_buf) = self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
+
+        net_trace!(
+            "packet:{:?}: receive {} buffered octets",
+            self.ethertype,
+            frame_buf.len()
+        );
+        Ok(frame_buf)
+    }
+
+    /// Dequeue a frame, and copy it into the given slice.
+    ///
+    /// See also [recv](#method.recv).
+    pub fn recv_slice(&mut self, data: &mut [u8]) -> Result<usize, RecvError> {
+        let buffer = self.recv()?;
+        let length = min(data.len(), buffer.len());
+        data[..length].copy_from_slice(&buffer[..length]);
+        Ok(length)
+    }
+
+    /// Peek at a frame in the receive buffer and return a pointer to its contents
+    /// without removing it from the receive buffer.
+    /// This function otherwise behaves identically to [recv](#method.recv).
+    pub fn peek(&mut self) -> Result<&[u8], RecvError> {
+        let ((), frame_buf) = self.rx_buffer.peek().map_err(|_| RecvError::Exhausted)?;
+        Ok(frame_buf)
+    }
+
+    pub(crate) fn accepts<T: AsRef<[u8]>>(&self, frame: &EthernetFrame<T>) -> bool {
+        match self.ethertype {
+            Some(ethertype) => frame.ethertype() == ethertype,
+            None => true,
+        }
+    }
+
+    pub(crate) fn process<T: AsRef<[u8]>>(&mut self, _cx: &mut Context, frame: &EthernetFrame<T>) {
+        debug_assert!(self.accepts(frame));
+
+        let frame = frame.as_ref();
+        if let Some(filter) = &self.filter {
+            let (header, payload) = frame.split_at(EthernetFrame::<&[u8]>::header_len());
+            if !filter.run(header, payload) {
+                net_trace!(
+                    "packet:{:?}: filter rejected incoming frame",
+                    self.ethertype
+                );
+                return;
+            }
+        }
+
+        net_trace!(
+            "packet:{:?}: receiving {} octets",
+            self.ethertype,
+            frame.len()
+        );
+
+        match self.rx_buffer.enqueue(frame.len(), ()) {
+            Ok(buf) => buf.copy_from_slice(frame),
+            Err(_) => net_trace!(
+                "packet:{:?}: buffer full, dropped incoming frame",
+                self.ethertype
+            ),
+        }
+
+        #[cfg(feature = "async")]
+        self.rx_waker.wake_all();
+    }
+
+    pub(crate) fn dispatch<F, E>(&mut self, cx: &mut Context, emit: F) -> Result<(), E>
+    where
+        F: FnOnce(&mut Context, &[u8]) -> Result<(), E>,
+    {
+        let ethertype = self.ethertype;
+        let res = self.tx_buffer.dequeue_with(|&mut (), buffer| {
+            let frame = match EthernetFrame::new_checked(&*buffer) {
+                Ok(frame) => frame,
+                Err(_) => {
+                    net_trace!("packet: malformed frame in queue, dropping.");
+                    return Ok(());
+                }
+            };
+            if let Some(ethertype) = ethertype {
+                if frame.ethertype() != ethertype {
+                    net_trace!("packet: sent frame with wrong ethertype, dropping.");
+                    return Ok(());
+                }
+            }
+
+            net_trace!("packet:{:?}: sending", ethertype);
+            emit(cx, buffer)
+        });
+        match res {
+            Err(Empty) => Ok(()),
+            Ok(Err(e)) => Err(e),
+            Ok(Ok(())) => {
+                #[cfg(feature = "async")]
+                self.tx_waker.wake_all();
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn poll_at(&self, _cx: &Context) -> PollAt {
+        if self.tx_buffer.is_empty() {
+            PollAt::Ingress
+        } else {
+            PollAt::Now
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LLDP: EthernetProtocol = EthernetProtocol::Unknown(0x88cc);
+
+    const FRAME_BYTES: [u8; 18] = [
+        0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02, 0x88, 0xcc, 0xaa,
+        0x00, 0x00, 0xff,
+    ];
+
+    fn buffer(packets: usize) -> PacketBuffer<'static> {
+        PacketBuffer::new(vec![PacketMetadata::EMPTY; packets], vec![0; 48 * packets])
+    }
+
+    #[test]
+    fn test_send_dispatch() {
+        let mut socket = Socket::new(Some(LLDP), buffer(0), buffer(2));
+        let mut cx = Context::mock();
+
+        assert_eq!(
+            socket.dispatch(&mut cx, |_, _| unreachable!()),
+            Ok::<_, ()>(())
+        );
+
+        assert_eq!(socket.send_slice(&FRAME_BYTES[..]), Ok(()));
+        assert_eq!(
+            socket.dispatch(&mut cx, |_, frame| {
+                assert_eq!(frame, &FRAME_BYTES[..]);
+                Err(())
+            }),
+            Err(())
+        );
+        assert_eq!(
+            socket.dispatch(&mut cx, |_, frame| {
+                assert_eq!(frame, &FRAME_BYTES[..]);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+
+        // Frames of another EtherType are dropped.
+        let mut other = FRAME_BYTES;
+        other[12..14].copy_from_slice(&[0x88, 0xf7]);
+        assert_eq!(socket.send_slice(&other[..]), Ok(()));
+        assert_eq!(
+            socket.dispatch(&mut cx, |_, _| unreachable!()),
+            Ok::<_, ()>(())
+        );
+        assert_eq!(socket.poll_at(&cx), PollAt::Ingress);
+    }
+
+    #[test]
+    fn test_recv() {
+        let mut socket = Socket::new(Some(LLDP), buffer(1), buffer(0));
+        let mut cx = Context::mock();
+
+        let frame = EthernetFrame::new_checked(&FRAME_BYTES[..]).unwrap();
+        assert!(socket.accepts(&frame));
+        socket.process(&mut cx, &frame);
+        assert_eq!(socket.recv(), Ok(&FRAME_BYTES[..]));
+        assert_eq!(socket.recv(), Err(RecvError::Exhausted));
+
+        let mut other = FRAME_BYTES;
+        other[12..14].copy_from_slice(&[0x88, 0xf7]);
+        let frame = EthernetFrame::new_checked(&other[..]).unwrap();
+        assert!(!socket.accepts(&frame));
+        assert!(Socket::new(None, buffer(1), buffer(0)).accepts(&frame));
+    }
+
+    #[test]
+    fn test_filter() {
+        use crate::socket::filter::Insn;
+
+        // Only accept frames whose payload starts with 0xaa.
+        const PROGRAM: &[Insn] = &[
+            Insn::LoadU8(14),
+            Insn::JumpEq {
+                k: 0xaa,
+                jt: 0,
+                jf: 1,
+            },
+            Insn::Accept,
+            Insn::Reject,
+        ];
+
+        let mut socket = Socket::new(None, buffer(1), buffer(0));
+        socket.set_filter(Some(Filter::Program(PROGRAM)));
+        let mut cx = Context::mock();
+
+        let mut other = FRAME_BYTES;
+        other[14] = 0xbb;
+        // A rejected frame leaves room for the next one.
+        socket.process(&mut cx, &EthernetFrame::new_checked(&other[..]).unwrap());
+        assert!(!socket.can_recv());
+        socket.process(
+            &mut cx,
+            &EthernetFrame::new_checked(&FRAME_BYTES[..]).unwrap(),
+        );
+        assert_eq!(socket.recv(), Ok(&FRAME_BYTES[..]));
+    }
+}
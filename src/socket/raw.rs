@@ -3,16 +3,23 @@ use core::cmp::min;
 use core::task::Waker;
 
 use crate::iface::Context;
-use crate::socket::PollAt;
+use crate::socket::filter::Filter;
 #[cfg(feature = "async")]
 use crate::socket::WakerRegistration;
+#[cfg(feature = "proto-igmp")]
+use crate::socket::{MembershipError, Memberships};
+use crate::socket::{PollAt, TxTimestamp, TxTimestamps};
 
 use crate::storage::Empty;
-use crate::wire::{IpProtocol, IpRepr, IpVersion, IpAddress, Error};
+use crate::time::Instant;
+use crate::wire::{Error, IpAddress, IpProtocol, IpRepr, IpVersion};
 #[cfg(feature = "proto-ipv4")]
 use crate::wire::{Ipv4Packet, Ipv4Repr};
 #[cfg(feature = "proto-ipv6")]
-use crate::wire::{Ipv6Packet, Ipv6Repr};
+use crate::wire::{
+    Ipv6HopByHopHeader, Ipv6Option, Ipv6OptionRepr, Ipv6Packet, Ipv6Repr, Ipv6RoutingHeader,
+    Ipv6RoutingRepr,
+};
 
 /// Error returned by [`Socket::bind`]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -36,11 +43,139 @@ pub enum RecvError {
     Exhausted,
 }
 
-/// A UDP packet metadata.
-pub type PacketMetadata = crate::storage::PacketMetadata<()>;
+/// A raw packet metadata, holding the instant at which received packets were received.
+pub type PacketMetadata = crate::storage::PacketMetadata<Instant>;
 
-/// A UDP packet ring buffer.
-pub type PacketBuffer<'a> = crate::storage::PacketBuffer<'a, ()>;
+/// A raw packet ring buffer.
+pub type PacketBuffer<'a> = crate::storage::PacketBuffer<'a, Instant>;
+
+/// IPv6 extension headers prepended to a packet by [`Socket::send_ipv6`].
+///
+/// The headers are emitted in the order recommended by [RFC 8200 § 4.1]: Hop-by-Hop
+/// Options, Routing, then Destination Options. Their `next_header` fields are chained
+/// automatically, and options headers are padded to a multiple of 8 octets.
+///
+/// [RFC 8200 § 4.1]: https://www.rfc-editor.org/rfc/rfc8200#section-4.1
+#[cfg(feature = "proto-ipv6")]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Ipv6ExtHeaders<'a> {
+    /// Options of the Hop-by-Hop Options header, if one is prepended.
+    pub hop_by_hop: Option<&'a [Ipv6OptionRepr<'a>]>,
+    /// The Routing header, if one is prepended. Its `next_header` field is ignored.
+    pub routing: Option<Ipv6RoutingRepr<'a>>,
+    /// Options of the Destination Options header, if one is prepended.
+    pub destination: Option<&'a [Ipv6OptionRepr<'a>]>,
+}
+
+#[cfg(feature = "proto-ipv6")]
+impl<'a> Ipv6ExtHeaders<'a> {
+    /// Return the length, in bytes, of the extension headers.
+    pub fn buffer_len(&self) -> usize {
+        self.hop_by_hop.map_or(0, options_header_len)
+            + self.routing.map_or(0, |routing| routing.buffer_len())
+            + self.destination.map_or(0, options_header_len)
+    }
+
+    /// Emit the extension headers into a buffer, followed by a header of type `next_header`.
+    ///
+    /// Returns the type of the first header, which goes into the IPv6 header.
+    ///
+    /// # Panics
+    /// This function panics if the buffer is shorter than [`buffer_len`](#method.buffer_len).
+    pub fn emit(&self, next_header: IpProtocol, buffer: &mut [u8]) -> IpProtocol {
+        let after_routing = match self.destination {
+            Some(_) => IpProtocol::Ipv6Opts,
+            None => next_header,
+        };
+        let after_hop_by_hop = match self.routing {
+            Some(_) => IpProtocol::Ipv6Route,
+            None => after_routing,
+        };
+
+        let mut offset = 0;
+        if let Some(options) = self.hop_by_hop {
+            offset += emit_options_header(options, after_hop_by_hop, &mut buffer[offset..]);
+        }
+        if let Some(mut routing) = self.routing {
+            match &mut routing {
+                Ipv6RoutingRepr::Type2 { next_header, .. }
+                | Ipv6RoutingRepr::Rpl { next_header, .. } => *next_header = after_routing,
+            }
+            let len = routing.buffer_len();
+            routing.emit(&mut Ipv6RoutingHeader::new(
+                &mut buffer[offset..offset + len],
+            ));
+            offset += len;
+        }
+        if let Some(options) = self.destination {
+            emit_options_header(options, next_header, &mut buffer[offset..]);
+        }
+
+        match self.hop_by_hop {
+            Some(_) => IpProtocol::HopByHop,
+            None => after_hop_by_hop,
+        }
+    }
+}
+
+#[cfg(feature = "proto-ipv6")]
+fn options_header_len(options: &[Ipv6OptionRepr]) -> usize {
+    let len = 2 + options
+        .iter()
+        .map(|option| option.buffer_len())
+        .sum::<usize>();
+    (len + 7) / 8 * 8
+}
+
+#[cfg(feature = "proto-ipv6")]
+fn emit_options_header(
+    options: &[Ipv6OptionRepr],
+    next_header: IpProtocol,
+    buffer: &mut [u8],
+) -> usize {
+    let len = options_header_len(options);
+    let mut header = Ipv6HopByHopHeader::new_unchecked(&mut buffer[..len]);
+    header.set_next_header(next_header);
+    header.set_header_len((len / 8 - 1) as u8);
+
+    let data = header.options_mut();
+    let mut offset = 0;
+    for option in options {
+        let option_len = option.buffer_len();
+        option.emit(&mut Ipv6Option::new_unchecked(
+            &mut data[offset..offset + option_len],
+        ));
+        offset += option_len;
+    }
+    let padding = match data.len() - offset {
+        0 => None,
+        1 => Some(Ipv6OptionRepr::Pad1),
+        n => Some(Ipv6OptionRepr::PadN(n as u8 - 2)),
+    };
+    if let Some(padding) = padding {
+        padding.emit(&mut Ipv6Option::new_unchecked(&mut data[offset..]));
+    }
+    len
+}
+
+/// Return the protocol of the upper-layer header of an IPv6 packet, skipping over
+/// any extension headers.
+#[cfg(feature = "proto-ipv6")]
+fn ipv6_upper_layer_protocol(packet: &Ipv6Packet<&[u8]>) -> Option<IpProtocol> {
+    let mut next_header = packet.next_header();
+    let mut payload = packet.payload();
+    loop {
+        let len = match next_header {
+            IpProtocol::HopByHop | IpProtocol::Ipv6Route | IpProtocol::Ipv6Opts => {
+                (*payload.get(1)? as usize + 1) * 8
+            }
+            IpProtocol::Ipv6Frag => 8,
+            _ => return Some(next_header),
+        };
+        next_header = IpProtocol::from(*payload.first()?);
+        payload = payload.get(len..)?;
+    }
+}
 
 /// A raw IP socket.
 ///
@@ -52,11 +187,21 @@ pub struct Socket<'a> {
     ip_protocol: Option<IpProtocol>,
     rx_buffer: PacketBuffer<'a>,
     tx_buffer: PacketBuffer<'a>,
+    #[cfg(feature = "proto-igmp")]
+    memberships: Memberships,
     #[cfg(feature = "async")]
     rx_waker: WakerRegistration,
     #[cfg(feature = "async")]
     tx_waker: WakerRegistration,
     neighbor_discovering: Option<IpAddress>,
+    filter: Option<Filter<'a>>,
+    /// The time-to-live (IPv4) or hop limit (IPv6) value overriding the one of outgoing
+    /// packets.
+    hop_limit: Option<u8>,
+    /// The traffic class (IPv6) or type of service (IPv4) value overriding the one of
+    /// outgoing packets.
+    traffic_class: Option<u8>,
+    tx_timestamps: TxTimestamps,
 }
 
 impl<'a> Socket<'a> {
@@ -73,11 +218,17 @@ impl<'a> Socket<'a> {
             ip_protocol: Some(ip_protocol),
             rx_buffer,
             tx_buffer,
+            #[cfg(feature = "proto-igmp")]
+            memberships: Memberships::new(),
             #[cfg(feature = "async")]
             rx_waker: WakerRegistration::new(),
             #[cfg(feature = "async")]
             tx_waker: WakerRegistration::new(),
             neighbor_discovering: None,
+            filter: None,
+            hop_limit: None,
+            traffic_class: None,
+            tx_timestamps: TxTimestamps::new(),
         }
     }
     /// Create a raw IP socket bound to the given IP version and datagram protocol,
@@ -91,11 +242,17 @@ impl<'a> Socket<'a> {
             ip_protocol: None,
             rx_buffer,
             tx_buffer,
+            #[cfg(feature = "proto-igmp")]
+            memberships: Memberships::new(),
             #[cfg(feature = "async")]
             rx_waker: WakerRegistration::new(),
             #[cfg(feature = "async")]
             tx_waker: WakerRegistration::new(),
             neighbor_discovering: None,
+            filter: None,
+            hop_limit: None,
+            traffic_class: None,
+            tx_timestamps: TxTimestamps::new(),
         }
     }
 
@@ -196,6 +353,92 @@ impl<'a> Socket<'a> {
         self.ip_protocol
     }
 
+    /// Return the filter incoming packets are run against, if any.
+    #[inline]
+    pub fn filter(&self) -> Option<&Filter<'a>> {
+        self.filter.as_ref()
+    }
+
+    /// Set the filter incoming packets are run against, or remove it with `None`.
+    ///
+    /// The filter sees the IP header and the payload of every packet the socket would
+    /// otherwise receive. Packets it rejects are dropped without taking any space in the
+    /// receive buffer.
+    pub fn set_filter(&mut self, filter: Option<Filter<'a>>) {
+        self.filter = filter;
+    }
+
+    /// Return the time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
+    ///
+    /// See also the [set_hop_limit](#method.set_hop_limit) method
+    pub fn hop_limit(&self) -> Option<u8> {
+        self.hop_limit
+    }
+
+    /// Set the time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
+    ///
+    /// A socket without an explicitly set hop limit value sends packets with the hop
+    /// limit written in their header.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a hop limit value of 0 is given. See [RFC 1122 § 3.2.1.7].
+    ///
+    /// [RFC 1122 § 3.2.1.7]: https://tools.ietf.org/html/rfc1122#section-3.2.1.7
+    pub fn set_hop_limit(&mut self, hop_limit: Option<u8>) {
+        // A host MUST NOT send a datagram with a hop limit value of 0
+        if let Some(0) = hop_limit {
+            panic!("the time-to-live value of a packet must not be zero")
+        }
+
+        self.hop_limit = hop_limit
+    }
+
+    /// Return the traffic class (IPv6) or type of service (IPv4) value used in outgoing
+    /// packets.
+    ///
+    /// See also the [set_traffic_class](#method.set_traffic_class) method
+    pub fn traffic_class(&self) -> Option<u8> {
+        self.traffic_class
+    }
+
+    /// Set the traffic class (IPv6) or type of service (IPv4) value used in outgoing packets.
+    ///
+    /// For IPv4 only the DSCP, i.e. the upper six bits, is used, and the ECN bits of the
+    /// header are kept. A socket without an explicitly set traffic class sends packets
+    /// with the traffic class written in their header.
+    pub fn set_traffic_class(&mut self, traffic_class: Option<u8>) {
+        self.traffic_class = traffic_class
+    }
+
+    /// Return whether the instant at which each packet is sent is recorded.
+    ///
+    /// See also the [set_tx_timestamping](#method.set_tx_timestamping) method.
+    pub fn tx_timestamping(&self) -> bool {
+        self.tx_timestamps.enabled()
+    }
+
+    /// Enable or disable recording the instant at which each packet is sent, to be
+    /// read with [poll_tx_timestamp](#method.poll_tx_timestamp).
+    ///
+    /// The instant at which each packet is received is always recorded, see
+    /// [recv_with_timestamp](#method.recv_with_timestamp).
+    pub fn set_tx_timestamping(&mut self, enabled: bool) {
+        self.tx_timestamps.set_enabled(enabled)
+    }
+
+    /// Return the number the next packet enqueued will have in its transmit timestamp.
+    pub fn tx_timestamp_id(&self) -> u32 {
+        self.tx_timestamps.next_id()
+    }
+
+    /// Dequeue the oldest transmit timestamp, if any.
+    ///
+    /// Only the last few timestamps are kept until they are read.
+    pub fn poll_tx_timestamp(&mut self) -> Option<TxTimestamp> {
+        self.tx_timestamps.pop()
+    }
+
     /// Check whether the transmit buffer is full.
     #[inline]
     pub fn can_send(&self) -> bool {
@@ -232,6 +475,31 @@ impl<'a> Socket<'a> {
         self.tx_buffer.payload_capacity()
     }
 
+    /// Join an IPv4 multicast group.
+    ///
+    /// The interface joins the group on the next poll and leaves it once neither a socket
+    /// nor the interface itself needs it anymore. A socket that joined a group no longer
+    /// receives packets sent to other multicast groups.
+    ///
+    /// Returns `Ok(true)` if the socket was not already a member of the group.
+    #[cfg(feature = "proto-igmp")]
+    pub fn join_multicast<T: Into<IpAddress>>(&mut self, addr: T) -> Result<bool, MembershipError> {
+        self.memberships.join(addr.into())
+    }
+
+    /// Leave an IPv4 multicast group joined with [join_multicast](#method.join_multicast).
+    ///
+    /// Returns `true` if the socket was a member of the group.
+    #[cfg(feature = "proto-igmp")]
+    pub fn leave_multicast<T: Into<IpAddress>>(&mut self, addr: T) -> bool {
+        self.memberships.leave(addr.into())
+    }
+
+    #[cfg(feature = "proto-igmp")]
+    pub(crate) fn multicast_groups(&self) -> &[crate::wire::Ipv4Address] {
+        self.memberships.groups()
+    }
+
     /// Flag that determines if the TX queue should block while its performing
     /// a neighbor discovery
     #[inline]
@@ -256,27 +524,28 @@ impl<'a> Socket<'a> {
             // which will in effect cause packet loss for undiscovered endpoints but it
             // will free up the TX queue so that other packets may be transmitted as
             // otherwise neighbor discovery blocks the TX queue on the dispatch code path
-            self.tx_buffer.dequeue_with(|_, buffer| {
-                match IpVersion::of_packet(buffer) {
+            let res = self
+                .tx_buffer
+                .dequeue_with(|_, buffer| match IpVersion::of_packet(buffer) {
                     #[cfg(feature = "proto-ipv4")]
-                    Ok(IpVersion::Ipv4) => {
-                        Ipv4Packet::new_checked(buffer)
-                            .and_then(|frame| match IpAddress::Ipv4(frame.dst_addr()) == neighbor_discovering {
-                                true => Ok(()),
-                                false => Err(Error)
-                            })
-                    }
+                    Ok(IpVersion::Ipv4) => Ipv4Packet::new_checked(buffer).and_then(|frame| {
+                        match IpAddress::Ipv4(frame.dst_addr()) == neighbor_discovering {
+                            true => Ok(()),
+                            false => Err(Error),
+                        }
+                    }),
                     #[cfg(feature = "proto-ipv6")]
-                    Ok(IpVersion::Ipv6) => {
-                        Ipv6Packet::new_checked(buffer)
-                            .and_then(|frame| match IpAddress::Ipv6(frame.dst_addr()) == neighbor_discovering {
-                                true => Ok(()),
-                                false => Err(Error)
-                            })
-                    }
-                    Err(_) => Err(Error)
-                }
-            }).ok();
+                    Ok(IpVersion::Ipv6) => Ipv6Packet::new_checked(buffer).and_then(|frame| {
+                        match IpAddress::Ipv6(frame.dst_addr()) == neighbor_discovering {
+                            true => Ok(()),
+                            false => Err(Error),
+                        }
+                    }),
+                    Err(_) => Err(Error),
+                });
+            if let Ok(Ok(())) = res {
+                self.tx_timestamps.dequeued(None);
+            }
         }
     }
 
@@ -295,8 +564,9 @@ impl<'a> Socket<'a> {
         self.purge_neighbor_discovery();
         let packet_buf = self
             .tx_buffer
-            .enqueue(size, ())
+            .enqueue(size, Instant::ZERO)
             .map_err(|_| SendError::BufferFull)?;
+        self.tx_timestamps.enqueued();
 
         net_trace!(
             "raw:{:?}:{:?}: buffer to send {} octets",
@@ -318,8 +588,9 @@ impl<'a> Socket<'a> {
         self.purge_neighbor_discovery();
         let size = self
             .tx_buffer
-            .enqueue_with_infallible(max_size, (), f)
+            .enqueue_with_infallible(max_size, Instant::ZERO, f)
             .map_err(|_| SendError::BufferFull)?;
+        self.tx_timestamps.enqueued();
 
         net_trace!(
             "raw:{:?}:{:?}: buffer to send {} octets",
@@ -331,6 +602,35 @@ impl<'a> Socket<'a> {
         Ok(size)
     }
 
+    /// Enqueue an IPv6 packet to send, and return a pointer to its payload.
+    ///
+    /// The IPv6 header is built from `repr`, followed by the given extension headers.
+    /// The `next_header` field of `repr` is the protocol of the payload, and its
+    /// `payload_len` field is ignored.
+    ///
+    /// This function returns `Err(SendError::BufferFull)` if the transmit buffer is full.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn send_ipv6(
+        &mut self,
+        repr: &Ipv6Repr,
+        ext_headers: &Ipv6ExtHeaders,
+        payload_len: usize,
+    ) -> Result<&mut [u8], SendError> {
+        let ext_len = ext_headers.buffer_len();
+        let header_len = repr.buffer_len() + ext_len;
+        let buffer = self.send(header_len + payload_len)?;
+
+        let (header, ext) = buffer[..header_len].split_at_mut(repr.buffer_len());
+        let repr = Ipv6Repr {
+            next_header: ext_headers.emit(repr.next_header, ext),
+            payload_len: ext_len + payload_len,
+            ..*repr
+        };
+        repr.emit(&mut Ipv6Packet::new_unchecked(header));
+
+        Ok(&mut buffer[header_len..])
+    }
+
     /// Enqueue a packet to send, and fill it from a slice.
     ///
     /// See also [send](#method.send).
@@ -346,7 +646,16 @@ impl<'a> Socket<'a> {
     /// **Note:** The IP header is parsed and re-serialized, and may not match
     /// the header actually received bit for bit.
     pub fn recv(&mut self) -> Result<&[u8], RecvError> {
-        let ((), packet_buf) = self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
+        let (packet_buf, _) = self.recv_with_timestamp()?;
+        Ok(packet_buf)
+    }
+
+    /// Dequeue a packet, and return a pointer to the payload as well as the instant at
+    /// which it was received.
+    ///
+    /// This function otherwise behaves identically to [recv](#method.recv).
+    pub fn recv_with_timestamp(&mut self) -> Result<(&[u8], Instant), RecvError> {
+        let (timestamp, packet_buf) = self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
 
         net_trace!(
             "raw:{:?}:{:?}: receive {} buffered octets",
@@ -354,7 +663,7 @@ impl<'a> Socket<'a> {
             self.ip_protocol,
             packet_buf.len()
         );
-        Ok(packet_buf)
+        Ok((packet_buf, timestamp))
     }
 
     /// Dequeue a packet, and copy the payload into the given slice.
@@ -373,7 +682,7 @@ impl<'a> Socket<'a> {
     ///
     /// It returns `Err(Error::Exhausted)` if the receive buffer is empty.
     pub fn peek(&mut self) -> Result<&[u8], RecvError> {
-        let ((), packet_buf) = self.rx_buffer.peek().map_err(|_| RecvError::Exhausted)?;
+        let (_, packet_buf) = self.rx_buffer.peek().map_err(|_| RecvError::Exhausted)?;
 
         net_trace!(
             "raw:{:?}:{:?}: receive {} buffered octets",
@@ -408,6 +717,10 @@ impl<'a> Socket<'a> {
                 return false;
             }
         }
+        #[cfg(feature = "proto-igmp")]
+        if !self.memberships.accepts(ip_repr.dst_addr()) {
+            return false;
+        }
 
         true
     }
@@ -418,6 +731,21 @@ impl<'a> Socket<'a> {
         let header_len = ip_repr.header_len();
         let total_len = header_len + payload.len();
 
+        let mut header = [0u8; 40];
+        let header = &mut header[..header_len];
+        ip_repr.emit(&mut *header, &cx.checksum_caps());
+
+        if let Some(filter) = &self.filter {
+            if !filter.run(header, payload) {
+                net_trace!(
+                    "raw:{:?}:{:?}: filter rejected incoming packet",
+                    self.ip_version,
+                    self.ip_protocol
+                );
+                return;
+            }
+        }
+
         net_trace!(
             "raw:{:?}:{:?}: receiving {} octets",
             self.ip_version,
@@ -425,9 +753,9 @@ impl<'a> Socket<'a> {
             total_len
         );
 
-        match self.rx_buffer.enqueue(total_len, ()) {
+        match self.rx_buffer.enqueue(total_len, cx.now()) {
             Ok(buf) => {
-                ip_repr.emit(&mut buf[..header_len], &cx.checksum_caps());
+                buf[..header_len].copy_from_slice(header);
                 buf[header_len..].copy_from_slice(payload);
             }
             Err(_) => net_trace!(
@@ -448,7 +776,9 @@ impl<'a> Socket<'a> {
         let ip_protocol = self.ip_protocol;
         let ip_version = self.ip_version;
         let _checksum_caps = &cx.checksum_caps();
-        let res = self.tx_buffer.dequeue_with(|&mut (), buffer| {
+        let now = cx.now();
+        let mut sent = false;
+        let res = self.tx_buffer.dequeue_with(|_, buffer| {
             match IpVersion::of_packet(buffer) {
                 #[cfg(feature = "proto-ipv4")]
                 Ok(IpVersion::Ipv4) => {
@@ -465,6 +795,12 @@ impl<'a> Socket<'a> {
                             return Ok(());
                         }
                     }
+                    if let Some(hop_limit) = self.hop_limit {
+                        packet.set_hop_limit(hop_limit);
+                    }
+                    if let Some(traffic_class) = self.traffic_class {
+                        packet.set_dscp(traffic_class >> 2);
+                    }
                     if _checksum_caps.ipv4.tx() {
                         packet.fill_checksum();
                     } else {
@@ -482,24 +818,30 @@ impl<'a> Socket<'a> {
                         }
                     };
                     net_trace!("raw:{:?}:{:?}: sending", ip_version, ip_protocol);
-                    emit(cx, (IpRepr::Ipv4(ipv4_repr), packet.payload()))
+                    emit(cx, (IpRepr::Ipv4(ipv4_repr), packet.payload())).map(|()| sent = true)
                 }
                 #[cfg(feature = "proto-ipv6")]
                 Ok(IpVersion::Ipv6) => {
-                    let packet = match Ipv6Packet::new_checked(buffer) {
+                    let mut packet = match Ipv6Packet::new_checked(buffer) {
                         Ok(x) => x,
                         Err(_) => {
                             net_trace!("raw: malformed ipv6 packet in queue, dropping.");
                             return Ok(());
                         }
                     };
+                    if let Some(hop_limit) = self.hop_limit {
+                        packet.set_hop_limit(hop_limit);
+                    }
+                    if let Some(traffic_class) = self.traffic_class {
+                        packet.set_traffic_class(traffic_class);
+                    }
+                    let packet = Ipv6Packet::new_unchecked(&*packet.into_inner());
                     if let Some(ip_protocol) = ip_protocol {
-                        if packet.next_header() != ip_protocol {
+                        if ipv6_upper_layer_protocol(&packet) != Some(ip_protocol) {
                             net_trace!("raw: sent ipv6 packet with wrong ip protocol, dropping.");
                             return Ok(());
                         }
                     }
-                    let packet = Ipv6Packet::new_unchecked(&*packet.into_inner());
                     let ipv6_repr = match Ipv6Repr::parse(&packet) {
                         Ok(x) => x,
                         Err(_) => {
@@ -509,7 +851,7 @@ impl<'a> Socket<'a> {
                     };
 
                     net_trace!("raw:{:?}:{:?}: sending", ip_version, ip_protocol);
-                    emit(cx, (IpRepr::Ipv6(ipv6_repr), packet.payload()))
+                    emit(cx, (IpRepr::Ipv6(ipv6_repr), packet.payload())).map(|()| sent = true)
                 }
                 Err(_) => {
                     net_trace!("raw: sent packet with invalid IP version, dropping.");
@@ -521,6 +863,9 @@ impl<'a> Socket<'a> {
             Err(Empty) => Ok(()),
             Ok(Err(e)) => Err(e),
             Ok(Ok(())) => {
+                self.tx_timestamps
+                    .dequeued(if sent { Some(now) } else { None });
+
                 #[cfg(feature = "async")]
                 self.tx_waker.wake_all();
                 Ok(())
@@ -573,6 +918,7 @@ mod test {
             next_header: IpProtocol::Unknown(IP_PROTO),
             payload_len: 4,
             hop_limit: 64,
+            dscp: 0,
         });
         pub const PACKET_BYTES: [u8; 24] = [
             0x45, 0x00, 0x00, 0x18, 0x00, 0x00, 0x40, 0x00, 0x40, 0x3f, 0x00, 0x00, 0x0a, 0x00,
@@ -610,6 +956,8 @@ mod test {
             next_header: IpProtocol::Unknown(IP_PROTO),
             payload_len: 4,
             hop_limit: 64,
+            traffic_class: 0,
+            flow_label: 0,
         });
 
         pub const PACKET_BYTES: [u8; 44] = [
@@ -669,6 +1017,32 @@ mod test {
                     assert!(socket.can_send());
                 }
 
+                #[test]
+                fn test_timestamps() {
+                    let mut socket = $socket(buffer(1), buffer(1));
+                    let mut cx = Context::mock();
+
+                    cx.set_now(Instant::from_millis(10));
+                    socket.process(&mut cx, &$hdr, &$payload);
+                    assert_eq!(
+                        socket.recv_with_timestamp().map(|(_, timestamp)| timestamp),
+                        Ok(Instant::from_millis(10))
+                    );
+
+                    socket.set_tx_timestamping(true);
+                    assert_eq!(socket.send_slice(&$packet[..]), Ok(()));
+                    cx.set_now(Instant::from_millis(20));
+                    assert_eq!(socket.dispatch(&mut cx, |_, _| Ok::<_, ()>(())), Ok(()));
+                    assert_eq!(
+                        socket.poll_tx_timestamp(),
+                        Some(TxTimestamp {
+                            id: 0,
+                            timestamp: Instant::from_millis(20),
+                        })
+                    );
+                    assert_eq!(socket.poll_tx_timestamp(), None);
+                }
+
                 #[test]
                 fn test_recv_truncated_slice() {
                     let mut socket = $socket(buffer(1), buffer(0));
@@ -731,6 +1105,112 @@ mod test {
         ipv6_locals::PACKET_PAYLOAD
     );
 
+    #[test]
+    #[cfg(feature = "proto-ipv6")]
+    fn test_send_ipv6_ext_headers() {
+        use crate::wire::Ipv6OptionType;
+
+        let tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; 1], vec![0; 64]);
+        let mut socket = ipv6_locals::socket(buffer(0), tx_buffer);
+        let mut cx = Context::mock();
+
+        let repr = match ipv6_locals::HEADER_REPR {
+            IpRepr::Ipv6(repr) => repr,
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        };
+        let router_alert = [Ipv6OptionRepr::Unknown {
+            type_: Ipv6OptionType::Unknown(0x05),
+            length: 2,
+            data: &[0x00, 0x00],
+        }];
+        let ext_headers = Ipv6ExtHeaders {
+            hop_by_hop: Some(&router_alert),
+            ..Default::default()
+        };
+        assert_eq!(ext_headers.buffer_len(), 8);
+        socket
+            .send_ipv6(&repr, &ext_headers, 4)
+            .unwrap()
+            .copy_from_slice(&ipv6_locals::PACKET_PAYLOAD);
+
+        assert_eq!(
+            socket.dispatch(&mut cx, |_, (ip_repr, ip_payload)| {
+                assert_eq!(ip_repr.next_header(), IpProtocol::HopByHop);
+                assert_eq!(ip_repr.payload_len(), 12);
+                assert_eq!(
+                    ip_payload,
+                    &[0x3f, 0x00, 0x05, 0x02, 0x00, 0x00, 0x01, 0x00, 0xaa, 0x00, 0x00, 0xff]
+                );
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv6")]
+    fn test_ipv6_ext_headers_chain() {
+        let ext_headers = Ipv6ExtHeaders {
+            hop_by_hop: Some(&[]),
+            routing: Some(Ipv6RoutingRepr::Type2 {
+                next_header: IpProtocol::Unknown(0),
+                length: 2,
+                segments_left: 1,
+                home_address: Ipv6Address::LOOPBACK,
+            }),
+            destination: Some(&[Ipv6OptionRepr::Pad1]),
+        };
+        assert_eq!(ext_headers.buffer_len(), 40);
+
+        let mut bytes = [0xff; 40];
+        assert_eq!(
+            ext_headers.emit(IpProtocol::Icmpv6, &mut bytes),
+            IpProtocol::HopByHop
+        );
+        assert_eq!(bytes[..8], [0x2b, 0x00, 0x01, 0x04, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(bytes[8..12], [0x3c, 0x02, 0x02, 0x01]);
+        assert_eq!(
+            bytes[32..],
+            [0x3a, 0x00, 0x00, 0x01, 0x03, 0x00, 0x00, 0x00]
+        );
+
+        let mut packet = [0u8; 80];
+        packet[0] = 0x60;
+        packet[5] = 40;
+        packet[40..].copy_from_slice(&bytes);
+        assert_eq!(
+            ipv6_upper_layer_protocol(&Ipv6Packet::new_unchecked(&packet[..])),
+            Some(IpProtocol::Icmpv6)
+        );
+        // The payload ends in the middle of the Routing header.
+        packet[5] = 20;
+        assert_eq!(
+            ipv6_upper_layer_protocol(&Ipv6Packet::new_unchecked(&packet[..])),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_set_hop_limit_traffic_class() {
+        let mut socket = ipv4_locals::socket(buffer(0), buffer(1));
+        let mut cx = Context::mock();
+
+        socket.set_hop_limit(Some(0x2a));
+        socket.set_traffic_class(Some(0xb8));
+
+        assert_eq!(socket.send_slice(&ipv4_locals::PACKET_BYTES[..]), Ok(()));
+        assert_eq!(
+            socket.dispatch(&mut cx, |_, (ip_repr, _)| {
+                assert_eq!(ip_repr.hop_limit(), 0x2a);
+                assert_eq!(ip_repr.traffic_class(), 0xb8);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+    }
+
     #[test]
     #[cfg(feature = "proto-ipv4")]
     fn test_send_illegal() {
@@ -890,6 +1370,51 @@ mod test {
         }
     }
 
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_recv_filter() {
+        use crate::socket::filter::Insn;
+
+        // Only accept packets whose payload starts with 0xaa.
+        const PROGRAM: &[Insn] = &[
+            Insn::LoadU8(20),
+            Insn::JumpEq {
+                k: 0xaa,
+                jt: 0,
+                jf: 1,
+            },
+            Insn::Accept,
+            Insn::Reject,
+        ];
+
+        let mut socket = ipv4_locals::socket(buffer(1), buffer(0));
+        socket.set_filter(Some(Filter::Program(PROGRAM)));
+        let mut cx = Context::mock();
+
+        socket.process(
+            &mut cx,
+            &ipv4_locals::HEADER_REPR,
+            &[0xbb, 0x00, 0x00, 0xff],
+        );
+        assert!(!socket.can_recv());
+        socket.process(
+            &mut cx,
+            &ipv4_locals::HEADER_REPR,
+            &ipv4_locals::PACKET_PAYLOAD,
+        );
+        assert!(socket.can_recv());
+
+        socket.set_filter(Some(Filter::Callback(|header, _| header[9] == 0x3f)));
+        assert!(socket.filter().is_some());
+        assert!(socket.recv().is_ok());
+        socket.process(
+            &mut cx,
+            &ipv4_locals::HEADER_REPR,
+            &[0xbb, 0x00, 0x00, 0xff],
+        );
+        assert!(socket.can_recv());
+    }
+
     #[test]
     fn test_doesnt_accept_wrong_proto() {
         #[cfg(feature = "proto-ipv4")]
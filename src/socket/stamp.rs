@@ -0,0 +1,359 @@
+use crate::iface::Context;
+use crate::socket::PollAt;
+use crate::storage::{PacketBuffer as RawPacketBuffer, PacketMetadata as RawPacketMetadata};
+use crate::time::Instant;
+use crate::wire::{
+    IpAddress, IpEndpoint, IpProtocol, IpRepr, StampErrorEstimate, StampPacket, StampReflectorRepr,
+    StampSenderRepr, UdpRepr, STAMP_PORT,
+};
+
+/// The hop limit of reflected packets, so that session-senders can tell how many hops
+/// they crossed, as TWAMP session-reflectors do ([RFC 5357 § 4.2]).
+///
+/// [RFC 5357 § 4.2]: https://tools.ietf.org/html/rfc5357#section-4.2
+const HOP_LIMIT: u8 = 255;
+
+/// The session-sender a test packet came from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sender {
+    /// The endpoint of the session-sender.
+    pub endpoint: IpEndpoint,
+    /// The address the test packet was sent to, which the reflected packet is sent from.
+    pub local_address: IpAddress,
+}
+
+/// A STAMP packet metadata.
+pub type PacketMetadata = RawPacketMetadata<Sender>;
+
+/// A STAMP packet ring buffer.
+pub type PacketBuffer<'a> = RawPacketBuffer<'a, Sender>;
+
+/// A Simple Two-way Active Measurement Protocol session-reflector socket.
+///
+/// The socket answers the test packets of session-senders as described in [RFC 8762],
+/// so that the delay and loss of the path to a deployed device can be monitored without
+/// any application code. The reflector is stateless: each reflected packet carries the
+/// sequence number of the test packet, the instant it was received and the instant the
+/// reflected packet is handed to the device, as well as the sequence number, timestamp,
+/// error estimate and TTL of the test packet. Only the unauthenticated mode is supported.
+///
+/// Timestamps are taken from the instants given to [`Interface::poll`], and converted
+/// to the NTP format using the offset set with [`set_clock_offset`]. Test packets wait
+/// in the buffer until they are reflected, so it should hold a few of them.
+///
+/// [RFC 8762]: https://tools.ietf.org/html/rfc8762
+/// [`Interface::poll`]: crate::iface::Interface::poll
+/// [`set_clock_offset`]: #method.set_clock_offset
+#[derive(Debug)]
+pub struct Socket<'a> {
+    buffer: PacketBuffer<'a>,
+    port: u16,
+    clock_offset: u64,
+    error_estimate: StampErrorEstimate,
+    reflected: u32,
+}
+
+impl<'a> Socket<'a> {
+    /// Create a STAMP session-reflector socket listening on the STAMP port.
+    pub fn new(buffer: PacketBuffer<'a>) -> Socket<'a> {
+        Socket {
+            buffer,
+            port: STAMP_PORT,
+            clock_offset: 0,
+            // The clock is not synchronized and has a resolution of about a microsecond.
+            error_estimate: StampErrorEstimate {
+                synchronized: false,
+                ptp: false,
+                scale: 12,
+                multiplier: 1,
+            },
+            reflected: 0,
+        }
+    }
+
+    /// Return the UDP port test packets are received on.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Set the UDP port test packets are received on. This is 862 by default.
+    pub fn set_port(&mut self, port: u16) {
+        self.port = port;
+    }
+
+    /// Return the NTP timestamp of `Instant::ZERO`.
+    pub fn clock_offset(&self) -> u64 {
+        self.clock_offset
+    }
+
+    /// Set the NTP timestamp of `Instant::ZERO`, i.e. the number of seconds since
+    /// 1900-01-01 in the upper 32 bits and a fraction of a second in the lower 32 bits.
+    ///
+    /// This is zero by default, in which case the timestamps are only meaningful
+    /// relative to each other.
+    pub fn set_clock_offset(&mut self, offset: u64) {
+        self.clock_offset = offset;
+    }
+
+    /// Return the error estimate of the timestamps of reflected packets.
+    pub fn error_estimate(&self) -> StampErrorEstimate {
+        self.error_estimate
+    }
+
+    /// Set the error estimate of the timestamps of reflected packets, for instance to
+    /// tell that the clock was synchronized with [`set_clock_offset`].
+    ///
+    /// [`set_clock_offset`]: #method.set_clock_offset
+    pub fn set_error_estimate(&mut self, error_estimate: StampErrorEstimate) {
+        self.error_estimate = error_estimate;
+    }
+
+    /// Return the number of test packets reflected so far.
+    pub fn reflected(&self) -> u32 {
+        self.reflected
+    }
+
+    /// Convert an instant to a timestamp in the NTP format.
+    fn timestamp(&self, instant: Instant) -> u64 {
+        let micros = instant.total_micros().max(0) as u64;
+        let seconds = micros / 1_000_000;
+        let fraction = ((micros % 1_000_000) << 32) / 1_000_000;
+        ((seconds << 32) | fraction).wrapping_add(self.clock_offset)
+    }
+
+    pub(crate) fn accepts(&self, _ip_repr: &IpRepr, udp_repr: &UdpRepr) -> bool {
+        udp_repr.dst_port == self.port
+    }
+
+    pub(crate) fn process(
+        &mut self,
+        cx: &mut Context,
+        ip_repr: &IpRepr,
+        udp_repr: &UdpRepr,
+        payload: &[u8],
+    ) {
+        let sender =
+            match StampPacket::new_checked(payload).and_then(|p| StampSenderRepr::parse(&p)) {
+                Ok(repr) => repr,
+                Err(_) => {
+                    net_debug!("stamp: invalid test packet");
+                    return;
+                }
+            };
+        if !ip_repr.dst_addr().is_unicast() {
+            net_debug!("stamp: ignoring test packet to {}", ip_repr.dst_addr());
+            return;
+        }
+
+        let header = Sender {
+            endpoint: IpEndpoint::new(ip_repr.src_addr(), udp_repr.src_port),
+            local_address: ip_repr.dst_addr(),
+        };
+        let receive_timestamp = self.timestamp(cx.now());
+        let buf = match self.buffer.enqueue(payload.len(), header) {
+            Ok(buf) => buf,
+            Err(_) => {
+                net_debug!("stamp: buffer full, dropping test packet");
+                return;
+            }
+        };
+        // The reflected packet is as long as the test packet; any octets past the
+        // unauthenticated fields are sent back unchanged.
+        buf.copy_from_slice(payload);
+        let repr = StampReflectorRepr {
+            seq_num: sender.seq_num,
+            // Set when the packet is sent.
+            timestamp: 0,
+            error_estimate: self.error_estimate,
+            receive_timestamp,
+            sender_seq_num: sender.seq_num,
+            sender_timestamp: sender.timestamp,
+            sender_error_estimate: sender.error_estimate,
+            sender_ttl: ip_repr.hop_limit(),
+        };
+        repr.emit(&mut StampPacket::new_unchecked(buf));
+
+        net_trace!(
+            "stamp: reflecting test packet {} from {}",
+            sender.seq_num,
+            header.endpoint
+        );
+    }
+
+    pub(crate) fn dispatch<F, E>(&mut self, cx: &mut Context, emit: F) -> Result<(), E>
+    where
+        F: FnOnce(&mut Context, (IpRepr, UdpRepr, &[u8])) -> Result<(), E>,
+    {
+        // The transmit timestamp is written anew each time, so that it stays accurate
+        // when the packet cannot be sent right away.
+        let timestamp = self.timestamp(cx.now());
+        let error_estimate = self.error_estimate;
+        let port = self.port;
+        let res = self.buffer.dequeue_with(|sender, buf| {
+            let mut packet = StampPacket::new_unchecked(&mut *buf);
+            packet.set_timestamp(timestamp);
+            packet.set_error_estimate(error_estimate);
+
+            let udp_repr = UdpRepr {
+                src_port: port,
+                dst_port: sender.endpoint.port,
+            };
+            let ip_repr = IpRepr::new(
+                sender.local_address,
+                sender.endpoint.addr,
+                IpProtocol::Udp,
+                udp_repr.header_len() + buf.len(),
+                HOP_LIMIT,
+            );
+            emit(cx, (ip_repr, udp_repr, buf))
+        });
+        match res {
+            Err(_) => Ok(()),
+            Ok(Err(e)) => Err(e),
+            Ok(Ok(())) => {
+                self.reflected = self.reflected.wrapping_add(1);
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn poll_at(&self, _cx: &Context) -> PollAt {
+        if self.buffer.is_empty() {
+            PollAt::Ingress
+        } else {
+            PollAt::Now
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::wire::{Ipv4Address, STAMP_PACKET_LEN};
+
+    const SENDER: IpAddress = IpAddress::Ipv4(Ipv4Address([192, 168, 1, 2]));
+    const LOCAL: IpAddress = IpAddress::Ipv4(Ipv4Address([192, 168, 1, 1]));
+    const SENDER_PORT: u16 = 50000;
+
+    fn socket() -> Socket<'static> {
+        Socket::new(PacketBuffer::new(
+            vec![PacketMetadata::EMPTY; 4],
+            vec![0; 4 * STAMP_PACKET_LEN],
+        ))
+    }
+
+    fn recv(s: &mut Socket, cx: &mut Context, seq_num: u32, len: usize) {
+        let repr = StampSenderRepr {
+            seq_num,
+            timestamp: 0x0000_0001_0000_0000,
+            error_estimate: StampErrorEstimate::from_bits(0x8c01),
+        };
+        let mut payload = vec![0xa5; len];
+        repr.emit(&mut StampPacket::new_unchecked(&mut payload[..]));
+        let ip_repr = IpRepr::new(SENDER, LOCAL, IpProtocol::Udp, 0, 61);
+        let udp_repr = UdpRepr {
+            src_port: SENDER_PORT,
+            dst_port: STAMP_PORT,
+        };
+        assert!(s.accepts(&ip_repr, &udp_repr));
+        s.process(cx, &ip_repr, &udp_repr, &payload);
+    }
+
+    fn send(s: &mut Socket, cx: &mut Context) -> Option<(IpRepr, UdpRepr, std::vec::Vec<u8>)> {
+        let mut sent = None;
+        s.dispatch(cx, |_, (ip_repr, udp_repr, payload)| {
+            sent = Some((ip_repr, udp_repr, payload.to_vec()));
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+        sent
+    }
+
+    #[test]
+    fn test_reflect() {
+        let mut s = socket();
+        let mut cx = Context::mock();
+        s.set_clock_offset(0x0000_0010_0000_0000);
+        assert_eq!(s.poll_at(&cx), PollAt::Ingress);
+
+        cx.set_now(Instant::from_millis(1_250));
+        recv(&mut s, &mut cx, 7, STAMP_PACKET_LEN + 4);
+        assert_eq!(s.poll_at(&cx), PollAt::Now);
+
+        cx.set_now(Instant::from_millis(1_500));
+        let (ip_repr, udp_repr, payload) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(ip_repr.src_addr(), LOCAL);
+        assert_eq!(ip_repr.dst_addr(), SENDER);
+        assert_eq!(ip_repr.hop_limit(), HOP_LIMIT);
+        assert_eq!(
+            udp_repr,
+            UdpRepr {
+                src_port: STAMP_PORT,
+                dst_port: SENDER_PORT,
+            }
+        );
+        assert_eq!(payload.len(), STAMP_PACKET_LEN + 4);
+        assert_eq!(&payload[STAMP_PACKET_LEN..], &[0xa5; 4]);
+
+        let packet = StampPacket::new_checked(&payload[..]).unwrap();
+        assert_eq!(
+            StampReflectorRepr::parse(&packet),
+            Ok(StampReflectorRepr {
+                seq_num: 7,
+                timestamp: 0x0000_0011_8000_0000,
+                error_estimate: s.error_estimate(),
+                receive_timestamp: 0x0000_0011_4000_0000,
+                sender_seq_num: 7,
+                sender_timestamp: 0x0000_0001_0000_0000,
+                sender_error_estimate: StampErrorEstimate::from_bits(0x8c01),
+                sender_ttl: 61,
+            })
+        );
+        assert_eq!(s.reflected(), 1);
+        assert_eq!(s.poll_at(&cx), PollAt::Ingress);
+        assert!(send(&mut s, &mut cx).is_none());
+    }
+
+    #[test]
+    fn test_retry_updates_timestamp() {
+        let mut s = socket();
+        let mut cx = Context::mock();
+        recv(&mut s, &mut cx, 1, STAMP_PACKET_LEN);
+
+        cx.set_now(Instant::from_millis(250));
+        assert_eq!(s.dispatch(&mut cx, |_, _| Err::<(), ()>(())), Err(()));
+        assert_eq!(s.reflected(), 0);
+
+        cx.set_now(Instant::from_millis(500));
+        let (_, _, payload) = send(&mut s, &mut cx).unwrap();
+        let packet = StampPacket::new_checked(&payload[..]).unwrap();
+        assert_eq!(packet.timestamp(), 0x0000_0000_8000_0000);
+        assert_eq!(packet.receive_timestamp(), 0);
+        assert_eq!(s.reflected(), 1);
+    }
+
+    #[test]
+    fn test_ignore_invalid() {
+        let mut s = socket();
+        let mut cx = Context::mock();
+        let udp_repr = UdpRepr {
+            src_port: SENDER_PORT,
+            dst_port: STAMP_PORT,
+        };
+
+        let ip_repr = IpRepr::new(SENDER, LOCAL, IpProtocol::Udp, 0, 64);
+        s.process(&mut cx, &ip_repr, &udp_repr, &[0; STAMP_PACKET_LEN - 1]);
+        assert_eq!(s.poll_at(&cx), PollAt::Ingress);
+
+        let ip_repr = IpRepr::new(
+            SENDER,
+            IpAddress::Ipv4(Ipv4Address::BROADCAST),
+            IpProtocol::Udp,
+            0,
+            64,
+        );
+        s.process(&mut cx, &ip_repr, &udp_repr, &[0; STAMP_PACKET_LEN]);
+        assert_eq!(s.poll_at(&cx), PollAt::Ingress);
+    }
+}
@@ -7,15 +7,17 @@ use core::fmt::Display;
 use core::task::Waker;
 use core::{cmp, fmt, mem};
 
-use crate::iface::SocketHandle;
+use crate::iface::{HostMetrics, SocketHandle};
 #[cfg(feature = "async")]
 use crate::socket::WakerRegistration;
 use crate::socket::{Context, PollAt};
 use crate::storage::{Assembler, RingBuffer};
 use crate::time::{Duration, Instant};
+#[cfg(feature = "proto-ipv6")]
+use crate::wire::Ipv6ExtHeaders;
 use crate::wire::{
     IpAddress, IpEndpoint, IpListenEndpoint, IpProtocol, IpRepr, TcpControl, TcpRepr, TcpSeqNumber,
-    TCP_HEADER_LEN,
+    TcpTimestampRepr, TCP_HEADER_LEN,
 };
 
 macro_rules! tcp_trace {
@@ -28,6 +30,8 @@ macro_rules! tcp_trace {
 pub enum ListenError {
     InvalidState,
     Unaddressable,
+    /// The socket holds the endpoint in `TIME-WAIT`, and address reuse is disabled.
+    AddrInUse,
 }
 
 /// Error returned by [`Socket::connect`]
@@ -36,6 +40,8 @@ pub enum ListenError {
 pub enum ConnectError {
     InvalidState,
     Unaddressable,
+    /// The socket holds the local endpoint in `TIME-WAIT`, and address reuse is disabled.
+    AddrInUse,
 }
 
 /// Error returned by [`Socket::send`]
@@ -43,6 +49,9 @@ pub enum ConnectError {
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SendError {
     InvalidState,
+    /// The transmit buffer is full, and the socket is non-blocking. See
+    /// [Socket::set_non_blocking].
+    WouldBlock,
 }
 
 /// Error returned by [`Socket::recv`]
@@ -51,6 +60,9 @@ pub enum SendError {
 pub enum RecvError {
     InvalidState,
     Finished,
+    /// The receive buffer is empty, and the socket is non-blocking. See
+    /// [Socket::set_non_blocking].
+    WouldBlock,
 }
 
 /// A TCP socket ring buffer.
@@ -113,6 +125,8 @@ struct RttEstimator {
     timestamp: Option<(Instant, TcpSeqNumber)>,
     max_seq_sent: Option<TcpSeqNumber>,
     rto_count: u8,
+    /// Whether the estimate is based on at least one measurement.
+    sampled: bool,
 }
 
 impl Default for RttEstimator {
@@ -123,11 +137,28 @@ impl Default for RttEstimator {
             timestamp: None,
             max_seq_sent: None,
             rto_count: 0,
+            sampled: false,
         }
     }
 }
 
 impl RttEstimator {
+    fn from_host_metrics(metrics: HostMetrics) -> Self {
+        Self {
+            rtt: metrics.rtt.clamp(RTTE_MIN_RTO, RTTE_MAX_RTO),
+            deviation: metrics.rttvar.min(RTTE_MAX_RTO),
+            ..Self::default()
+        }
+    }
+
+    fn host_metrics(&self, ssthresh: usize) -> Option<HostMetrics> {
+        self.sampled.then_some(HostMetrics {
+            rtt: self.rtt,
+            rttvar: self.deviation,
+            ssthresh: (ssthresh != usize::MAX).then_some(ssthresh),
+        })
+    }
+
     fn retransmission_timeout(&self) -> Duration {
         let margin = RTTE_MIN_MARGIN.max(self.deviation * 4);
         let ms = (self.rtt + margin).clamp(RTTE_MIN_RTO, RTTE_MAX_RTO);
@@ -141,6 +172,7 @@ impl RttEstimator {
         self.deviation = (self.deviation * 3 + diff + 3) / 4;
 
         self.rto_count = 0;
+        self.sampled = true;
 
         let rto = self.retransmission_timeout().total_millis();
         tcp_trace!(
@@ -218,7 +250,9 @@ enum Timer {
 }
 
 const ACK_DELAY_DEFAULT: Duration = Duration::from_millis(10);
+const ACK_DELAY_SEGMENTS_DEFAULT: usize = 2;
 const CLOSE_DELAY: Duration = Duration::from_millis(10_000);
+const PERSIST_MAX_DELAY: Duration = Duration::from_secs(60);
 
 impl Timer {
     fn new() -> Timer {
@@ -316,9 +350,9 @@ impl Timer {
         *self = Timer::FastRetransmit
     }
 
-    fn set_for_close(&mut self, timestamp: Instant) {
+    fn set_for_close(&mut self, timestamp: Instant, delay: Duration) {
         *self = Timer::Close {
-            expires_at: timestamp + CLOSE_DELAY,
+            expires_at: timestamp + delay,
         }
     }
 
@@ -361,7 +395,32 @@ pub struct Socket<'a> {
     state: State,
     timer: Timer,
     rtte: RttEstimator,
+    /// Round-trip time estimate of a connection that just ended, waiting to be recorded
+    /// in the host cache by the next call with a context.
+    closed_host_metrics: Option<(IpAddress, HostMetrics)>,
     assembler: Assembler,
+    /// Number of segments dropped because the assembler could not track another hole.
+    assembler_full_drops: u64,
+    /// Instant at which the next zero window probe is sent, if the persist timer is running.
+    persist_expires_at: Option<Instant>,
+    /// Current interval between zero window probes.
+    persist_delay: Duration,
+    /// Instant at which the remote window closed while we had data to send.
+    zero_window_since: Option<Instant>,
+    /// Number of zero window probes sent since the remote window closed.
+    zero_window_probes: u32,
+    /// Number of times the connection stalled on a zero window.
+    zero_window_stalls: u64,
+    /// Whether the connection stalled on a zero window since `poll_zero_window` was called.
+    zero_window_event: bool,
+    /// Interval after which, if no data is exchanged, the connection is reported idle.
+    idle_timeout: Option<Duration>,
+    /// Instant at which data was last exchanged with the remote endpoint.
+    last_activity_at: Option<Instant>,
+    /// Whether the current idle period was already reported.
+    idle_reported: bool,
+    /// Whether the connection became idle since `poll_idle` was called.
+    idle_event: bool,
     rx_buffer: SocketBuffer<'a>,
     rx_fin_received: bool,
     tx_buffer: SocketBuffer<'a>,
@@ -369,11 +428,28 @@ pub struct Socket<'a> {
     timeout: Option<Duration>,
     /// Interval at which keep-alive packets will be sent.
     keep_alive: Option<Duration>,
+    /// Interval after which a connection stuck in FIN-WAIT-2 is aborted.
+    fin_wait_2_timeout: Option<Duration>,
+    /// Instant at which the FIN-WAIT-2 timeout runs out, if the socket is in that state.
+    fin_wait_2_expires_at: Option<Instant>,
+    /// Duration of the TIME-WAIT state.
+    time_wait_timeout: Duration,
+    /// Whether the local endpoint held in TIME-WAIT can be used for a new connection.
+    reuse_addr: bool,
     /// The time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
     hop_limit: Option<u8>,
+    /// The traffic class (IPv6) or type of service (IPv4) value used in outgoing packets.
+    traffic_class: Option<u8>,
+    /// The IPv6 flow label used in outgoing packets.
+    flow_label: Option<u32>,
+    /// The extension headers inserted in outgoing IPv6 packets.
+    #[cfg(feature = "proto-ipv6")]
+    ipv6_ext_headers: Ipv6ExtHeaders,
     /// Address passed to listen(). Listen address is set when listen() is called and
     /// used every time the socket is reset back to the LISTEN state.
     listen_endpoint: IpListenEndpoint,
+    /// Whether the connection was opened with connect() rather than accepted from LISTEN.
+    active_open: bool,
     /// Current 4-tuple (local and remote endpoints).
     tuple: Option<Tuple>,
     /// The sequence number corresponding to the beginning of the transmit buffer.
@@ -393,6 +469,23 @@ pub struct Socket<'a> {
     /// The sending window scaling factor advertised to remotes which support RFC 1323.
     /// It is zero if the window <= 64KiB and/or the remote does not support it.
     remote_win_shift: u8,
+    /// The window scaling factor to advertise instead of the one derived from the
+    /// receive buffer capacity.
+    win_shift: Option<u8>,
+    /// The initial congestion window, in segments. If None, the amount of data in flight
+    /// is only limited by the remote window.
+    initial_window: Option<usize>,
+    /// The congestion window, i.e. the amount of data that may be in flight.
+    cwnd: usize,
+    /// The slow-start threshold; above it, the congestion window grows by about one
+    /// segment per round trip instead of one segment per ACK.
+    ssthresh: usize,
+    /// Whether or not the advertised receive window grows with the transfer rate.
+    rx_autotune: bool,
+    /// Upper bound of the advertised receive window when auto-tuning is enabled.
+    rx_win_limit: usize,
+    /// Octets received since `rx_win_limit` was last grown.
+    rx_win_received: usize,
     /// The remote window size, relative to local_seq_no
     /// I.e. we're allowed to send octets until local_seq_no+remote_win_len
     remote_win_len: usize,
@@ -400,6 +493,13 @@ pub struct Socket<'a> {
     remote_win_scale: Option<u8>,
     /// Whether or not the remote supports selective ACK as described in RFC 2018.
     remote_has_sack: bool,
+    /// Whether or not to offer the RFC 7323 timestamps option when connecting or to accept it
+    /// when listening.
+    timestamps: bool,
+    /// Whether or not timestamps were negotiated with the remote.
+    remote_has_timestamps: bool,
+    /// The most recent timestamp value received from the remote, echoed back in our segments.
+    remote_last_tsval: u32,
     /// The maximum number of data octets that the remote side may receive.
     remote_mss: usize,
     /// The timestamp of the last packet received.
@@ -417,6 +517,14 @@ pub struct Socket<'a> {
     /// Delayed ack timer. If set, packets containing exclusively
     /// ACK or window updates (ie, no data) won't be sent until expiry.
     ack_delay_timer: AckDelayTimer,
+    /// Number of received segments after which a delayed ACK is sent right away.
+    ack_delay_segments: usize,
+    /// Number of segments received since the delayed ack timer was started.
+    ack_delayed_segments: usize,
+    /// Number of segments acknowledged without delay once a connection is established.
+    initial_quick_acks: usize,
+    /// Number of segments left to acknowledge without delay.
+    quick_acks: usize,
 
     /// Used for rate-limiting: No more challenge ACKs will be sent until this instant.
     challenge_ack_timer: Instant,
@@ -424,6 +532,31 @@ pub struct Socket<'a> {
     /// Nagle's Algorithm enabled.
     nagle: bool,
 
+    /// Whether sending to a full buffer or receiving from an empty buffer is an error.
+    non_blocking: bool,
+
+    /// Whether received urgent data is left in the stream instead of being delivered
+    /// out-of-band.
+    urgent_inline: bool,
+    /// The sequence number of the last urgent octet received, until it has been read.
+    rx_urgent_seq: Option<TcpSeqNumber>,
+    /// The last urgent octet received, if delivered out-of-band and not yet read.
+    rx_urgent_data: Option<u8>,
+    /// The sequence number following the last urgent octet sent, until it is acknowledged.
+    tx_urgent_mark: Option<TcpSeqNumber>,
+
+    /// Maximum time a closed socket may spend in FIN-WAIT-1, FIN-WAIT-2, CLOSING or LAST-ACK
+    /// before the connection is reset. If None the socket lingers until the close completes.
+    linger: Option<Duration>,
+    /// Instant at which the linger period runs out; armed the first time the socket is
+    /// dispatched after its transmit half was closed.
+    linger_expires_at: Option<Instant>,
+    /// Set when the application closed or aborted an open connection, until it reaches
+    /// the CLOSED or TIME-WAIT state.
+    close_pending: bool,
+    /// Set when a close initiated by the application has completed, until it is polled.
+    close_completed: bool,
+
     /// Represents the backlog of connections that can be accepted
     backlog: RingBuffer<'a, SocketHandle>,
 
@@ -435,10 +568,26 @@ pub struct Socket<'a> {
     accept_waker: WakerRegistration,
     #[cfg(feature = "async")]
     state_waker: WakerRegistration,
+    #[cfg(feature = "async")]
+    connect_waker: WakerRegistration,
+    #[cfg(feature = "async")]
+    close_waker: WakerRegistration,
+    /// Minimum amount of free space in the transmit buffer before the send wakers are woken
+    /// as a result of the remote acknowledging data.
+    #[cfg(feature = "async")]
+    send_low_water: usize,
+    /// Amount of data in the receive buffer before the receive wakers are woken as a result
+    /// of the remote sending data, and minimum growth of the window before it is advertised.
+    recv_high_water: usize,
 }
 
 const DEFAULT_MSS: usize = 536;
 
+/// Receive window advertised at the start of a connection when auto-tuning is enabled.
+const RX_AUTOTUNE_INITIAL_WINDOW: usize = 10 * DEFAULT_MSS;
+/// The largest window scale factor allowed by RFC 7323.
+const MAX_WINDOW_SCALE: u8 = 14;
+
 impl<'a> Socket<'a> {
     #[allow(unused_comparisons)] // small usize platforms always pass rx_capacity check
     /// Create a socket using the given buffers.
@@ -462,14 +611,35 @@ impl<'a> Socket<'a> {
             state: State::Closed,
             timer: Timer::new(),
             rtte: RttEstimator::default(),
+            closed_host_metrics: None,
             assembler: Assembler::new(),
+            assembler_full_drops: 0,
+            persist_expires_at: None,
+            persist_delay: Duration::ZERO,
+            zero_window_since: None,
+            zero_window_probes: 0,
+            zero_window_stalls: 0,
+            zero_window_event: false,
+            idle_timeout: None,
+            last_activity_at: None,
+            idle_reported: false,
+            idle_event: false,
             tx_buffer,
             rx_buffer,
             rx_fin_received: false,
             timeout: None,
             keep_alive: None,
+            fin_wait_2_timeout: None,
+            fin_wait_2_expires_at: None,
+            time_wait_timeout: CLOSE_DELAY,
+            reuse_addr: true,
             hop_limit: None,
+            traffic_class: None,
+            flow_label: None,
+            #[cfg(feature = "proto-ipv6")]
+            ipv6_ext_headers: Ipv6ExtHeaders::new(),
             listen_endpoint: IpListenEndpoint::default(),
+            active_open: false,
             tuple: None,
             local_seq_no: TcpSeqNumber::default(),
             remote_seq_no: TcpSeqNumber::default(),
@@ -478,8 +648,18 @@ impl<'a> Socket<'a> {
             remote_last_win: 0,
             remote_win_len: 0,
             remote_win_shift: rx_cap_log2.saturating_sub(16) as u8,
+            win_shift: None,
+            initial_window: None,
+            cwnd: usize::MAX,
+            ssthresh: usize::MAX,
+            rx_autotune: false,
+            rx_win_limit: usize::MAX,
+            rx_win_received: 0,
             remote_win_scale: None,
             remote_has_sack: false,
+            timestamps: false,
+            remote_has_timestamps: false,
+            remote_last_tsval: 0,
             remote_mss: DEFAULT_MSS,
             remote_last_ts: None,
             local_rx_last_ack: None,
@@ -487,8 +667,21 @@ impl<'a> Socket<'a> {
             local_rx_dup_acks: 0,
             ack_delay: Some(ACK_DELAY_DEFAULT),
             ack_delay_timer: AckDelayTimer::Idle,
+            ack_delay_segments: ACK_DELAY_SEGMENTS_DEFAULT,
+            ack_delayed_segments: 0,
+            initial_quick_acks: 0,
+            quick_acks: 0,
             challenge_ack_timer: Instant::from_secs(0),
             nagle: true,
+            non_blocking: false,
+            urgent_inline: false,
+            rx_urgent_seq: None,
+            rx_urgent_data: None,
+            tx_urgent_mark: None,
+            linger: None,
+            linger_expires_at: None,
+            close_pending: false,
+            close_completed: false,
             backlog: RingBuffer::new([Default::default(); 0]),
 
             #[cfg(feature = "async")]
@@ -499,6 +692,13 @@ impl<'a> Socket<'a> {
             accept_waker: WakerRegistration::new().with_verbose_logging(),
             #[cfg(feature = "async")]
             state_waker: WakerRegistration::new(),
+            #[cfg(feature = "async")]
+            connect_waker: WakerRegistration::new(),
+            #[cfg(feature = "async")]
+            close_waker: WakerRegistration::new(),
+            #[cfg(feature = "async")]
+            send_low_water: 1,
+            recv_high_water: 0,
         }
     }
 
@@ -628,6 +828,101 @@ impl<'a> Socket<'a> {
         self.accept_waker.add(waker)
     }
 
+    /// Register a waker for the completion of a connection attempt.
+    ///
+    /// The waker is woken when the socket leaves the `SYN-SENT` or `SYN-RECEIVED` state,
+    /// either because the handshake completed or because the attempt failed.
+    ///
+    /// Notes:
+    ///
+    /// - Only one waker can be registered at a time. If another waker was previously registered,
+    ///   it is overwritten and will no longer be woken.
+    /// - The Waker is woken only once. Once woken, you must register it again to receive more wakes.
+    #[cfg(feature = "async")]
+    pub fn register_connect_waker(&mut self, waker: &Waker) {
+        self.connect_waker.register(waker)
+    }
+
+    /// Adds another waker for the completion of a connection attempt.
+    ///
+    /// See also [register_connect_waker](#method.register_connect_waker).
+    #[cfg(feature = "async")]
+    pub fn add_connect_waker(&mut self, waker: &Waker) {
+        self.connect_waker.add(waker)
+    }
+
+    /// Register a waker for the completion of a close.
+    ///
+    /// The waker is woken when the socket enters the `CLOSED` or `TIME-WAIT` state, i.e. once
+    /// the connection no longer needs any attention from the application.
+    ///
+    /// Notes:
+    ///
+    /// - Only one waker can be registered at a time. If another waker was previously registered,
+    ///   it is overwritten and will no longer be woken.
+    /// - The Waker is woken only once. Once woken, you must register it again to receive more wakes.
+    #[cfg(feature = "async")]
+    pub fn register_close_waker(&mut self, waker: &Waker) {
+        self.close_waker.register(waker)
+    }
+
+    /// Adds another waker for the completion of a close.
+    ///
+    /// See also [register_close_waker](#method.register_close_waker).
+    #[cfg(feature = "async")]
+    pub fn add_close_waker(&mut self, waker: &Waker) {
+        self.close_waker.add(waker)
+    }
+
+    /// Return the send low-water mark.
+    ///
+    /// See also the [set_send_low_water](#method.set_send_low_water) method.
+    #[cfg(feature = "async")]
+    pub fn send_low_water(&self) -> usize {
+        self.send_low_water
+    }
+
+    /// Set the send low-water mark.
+    ///
+    /// When the remote endpoint acknowledges data, the send wakers are only woken once at
+    /// least this many octets are free in the transmit buffer. This prevents an executor
+    /// from repeatedly waking a task that can only enqueue a handful of octets at a time.
+    /// State changes always wake the send wakers regardless of this setting.
+    ///
+    /// The value is clamped to the capacity of the transmit buffer. By default, it is 1.
+    #[cfg(feature = "async")]
+    pub fn set_send_low_water(&mut self, low_water: usize) {
+        self.send_low_water = low_water
+    }
+
+    /// Return the receive high-water mark.
+    ///
+    /// See also the [set_recv_high_water](#method.set_recv_high_water) method.
+    pub fn recv_high_water(&self) -> usize {
+        self.recv_high_water
+    }
+
+    /// Set the receive high-water mark.
+    ///
+    /// When the remote endpoint sends data, the receive wakers are only woken once at
+    /// least this many octets are in the receive buffer, so that an application reading
+    /// in large chunks is not woken for every segment. State changes, such as the remote
+    /// endpoint closing the connection, always wake the receive wakers regardless of
+    /// this setting.
+    ///
+    /// Likewise, when the application reads data, the larger window is only advertised
+    /// once it grew by at least this many octets, or by half the receive buffer if that
+    /// is less. This avoids the silly window syndrome ([RFC 1122 § 4.2.3.3]) when the
+    /// application reads a few octets at a time.
+    ///
+    /// The value is clamped to the capacity of the receive buffer. By default, it is 0:
+    /// the receive wakers are woken for every segment, and every window growth is advertised.
+    ///
+    /// [RFC 1122 § 4.2.3.3]: https://tools.ietf.org/html/rfc1122#section-4.2.3.3
+    pub fn set_recv_high_water(&mut self, high_water: usize) {
+        self.recv_high_water = high_water
+    }
+
     /// Clears all the wakers that were assigned to this socket
     #[cfg(feature = "async")]
     pub fn clear_wakers(&mut self) {
@@ -635,6 +930,8 @@ impl<'a> Socket<'a> {
         self.tx_waker.clear();
         self.accept_waker.clear();
         self.state_waker.clear();
+        self.connect_waker.clear();
+        self.close_waker.clear();
     }
 
     /// Clears all the recv wakers that was assigned to this socket
@@ -661,6 +958,18 @@ impl<'a> Socket<'a> {
         self.state_waker.clear();
     }
 
+    /// Clears all the connect wakers that was assigned to this socket
+    #[cfg(feature = "async")]
+    pub fn clear_connect_waker(&mut self) {
+        self.connect_waker.clear();
+    }
+
+    /// Clears all the close wakers that was assigned to this socket
+    #[cfg(feature = "async")]
+    pub fn clear_close_waker(&mut self) {
+        self.close_waker.clear();
+    }
+
     /// Trigger the recv waker
     #[cfg(feature = "async")]
     pub fn trigger_recv_waker(&mut self) {
@@ -685,6 +994,28 @@ impl<'a> Socket<'a> {
         self.state_waker.wake_all();
     }
 
+    /// Trigger the connect waker
+    #[cfg(feature = "async")]
+    pub fn trigger_connect_waker(&mut self) {
+        self.connect_waker.wake_all();
+    }
+
+    /// Trigger the close waker
+    #[cfg(feature = "async")]
+    pub fn trigger_close_waker(&mut self) {
+        self.close_waker.wake_all();
+    }
+
+    /// Wake the send wakers if enough space is free in the transmit buffer to satisfy
+    /// the send low-water mark.
+    #[cfg(feature = "async")]
+    fn wake_send_if_writable(&mut self) {
+        let low_water = self.send_low_water.min(self.tx_buffer.capacity());
+        if self.tx_buffer.window() >= low_water {
+            self.tx_waker.wake_all();
+        }
+    }
+
     /// Returns true if the TCP socket has a backlog attached to it
     pub(crate) fn has_backlog(&self) -> bool {
         !self.backlog.is_empty()
@@ -749,6 +1080,17 @@ impl<'a> Socket<'a> {
         self.nagle
     }
 
+    /// Return the receive window, i.e. the free space in the receive buffer, limited by
+    /// auto-tuning if enabled.
+    #[inline]
+    fn rx_window(&self) -> usize {
+        if self.rx_autotune {
+            self.rx_buffer.window().min(self.rx_win_limit)
+        } else {
+            self.rx_buffer.window()
+        }
+    }
+
     /// Return the current window field value, including scaling according to RFC 1323.
     ///
     /// Used in internal calculations as well as packet generation.
@@ -756,7 +1098,7 @@ impl<'a> Socket<'a> {
     #[inline]
     fn scaled_window(&self) -> u16 {
         cmp::min(
-            self.rx_buffer.window() >> self.remote_win_shift as usize,
+            self.rx_window() >> self.remote_win_shift as usize,
             (1 << 16) - 1,
         ) as u16
     }
@@ -783,6 +1125,52 @@ impl<'a> Socket<'a> {
         self.ack_delay = duration
     }
 
+    /// Return the number of received segments after which a delayed ACK is sent.
+    ///
+    /// See also the [set_ack_delay_segments](#method.set_ack_delay_segments) method.
+    pub fn ack_delay_segments(&self) -> usize {
+        self.ack_delay_segments
+    }
+
+    /// Set the number of received segments after which a delayed ACK is sent without waiting
+    /// for the [ACK delay](#method.set_ack_delay) to expire. A value of 1 acknowledges every
+    /// segment right away; 0 is treated as 1.
+    ///
+    /// By default, every second segment is acknowledged, as recommended by RFC 1122.
+    pub fn set_ack_delay_segments(&mut self, segments: usize) {
+        self.ack_delay_segments = segments.max(1)
+    }
+
+    /// Return the number of segments acknowledged without delay at the start of a connection.
+    ///
+    /// See also the [set_initial_quick_acks](#method.set_initial_quick_acks) method.
+    pub fn initial_quick_acks(&self) -> usize {
+        self.initial_quick_acks
+    }
+
+    /// Set the number of segments acknowledged without delay once a connection is established.
+    ///
+    /// While the remote is in slow start, each ACK lets it grow its congestion window, so
+    /// delaying the first ACKs of a connection slows down the initial transfer. Request /
+    /// response protocols with small messages benefit the most from this.
+    ///
+    /// By default, no segment is exempt from the ACK delay.
+    pub fn set_initial_quick_acks(&mut self, segments: usize) {
+        self.initial_quick_acks = segments
+    }
+
+    /// Acknowledge the next `segments` received segments without delay.
+    ///
+    /// If an ACK is currently being delayed, it is sent on the next poll. This is similar
+    /// to the `TCP_QUICKACK` option on Linux.
+    pub fn quick_ack(&mut self, segments: usize) {
+        self.quick_acks = self.quick_acks.max(segments);
+        if let AckDelayTimer::Waiting(_) = self.ack_delay_timer {
+            tcp_trace!("quick ack requested, forcing delayed ack timer expiry");
+            self.ack_delay_timer = AckDelayTimer::Immediate;
+        }
+    }
+
     /// Enable or disable Nagle's Algorithm.
     ///
     /// Also known as "tinygram prevention". By default, it is enabled.
@@ -799,6 +1187,266 @@ impl<'a> Socket<'a> {
         self.nagle = enabled
     }
 
+    /// Return whether the socket is non-blocking.
+    ///
+    /// See also the [set_non_blocking](#method.set_non_blocking) method.
+    pub fn non_blocking(&self) -> bool {
+        self.non_blocking
+    }
+
+    /// Make the socket non-blocking, or not.
+    ///
+    /// By default, sending to a full transmit buffer enqueues nothing and returns
+    /// `Ok(0)`, and receiving from an empty receive buffer returns `Ok(0)`. When the
+    /// socket is non-blocking, these return `Err(SendError::WouldBlock)` and
+    /// `Err(RecvError::WouldBlock)` instead, so that applications can tell them apart
+    /// from a zero-length read or write.
+    ///
+    /// The flag is also set by [SocketSet::set_non_blocking], which additionally lets
+    /// the socket drain its transmit buffer while the neighbor is being discovered.
+    ///
+    /// [SocketSet::set_non_blocking]: crate::iface::SocketSet::set_non_blocking
+    pub fn set_non_blocking(&mut self, non_blocking: bool) {
+        self.non_blocking = non_blocking
+    }
+
+    /// Return the window scale factor advertised to the remote, if overridden.
+    ///
+    /// See also the [set_window_scale](#method.set_window_scale) method.
+    pub fn window_scale(&self) -> Option<u8> {
+        self.win_shift
+    }
+
+    /// Set the window scale factor advertised to remotes which support RFC 7323.
+    ///
+    /// By default, the factor is the smallest one that allows advertising the whole
+    /// receive buffer. A larger factor only makes the advertised window coarser, while a
+    /// smaller one limits the window to `65535 << shift` octets. The new value takes effect
+    /// on the next call to [listen](#method.listen) or [connect](#method.connect).
+    ///
+    /// # Panics
+    /// This function panics if `shift` is greater than 14.
+    pub fn set_window_scale(&mut self, shift: Option<u8>) {
+        if let Some(shift) = shift {
+            assert!(shift <= MAX_WINDOW_SCALE, "window scale factor too large");
+        }
+        self.win_shift = shift
+    }
+
+    /// Return the initial congestion window, in segments.
+    ///
+    /// See also the [set_initial_window](#method.set_initial_window) method.
+    pub fn initial_window(&self) -> Option<usize> {
+        self.initial_window
+    }
+
+    /// Set the initial congestion window, in segments.
+    ///
+    /// When set, at most `segments` full-sized segments are sent before the first
+    /// acknowledgement. Every acknowledgement then grows the window by up to one segment
+    /// (slow start, RFC 5681), and a retransmission timeout shrinks it back to a single
+    /// segment. A value of 0 is treated as 1.
+    ///
+    /// By default, there is no congestion window and the amount of data in flight is only
+    /// limited by the window advertised by the remote.
+    pub fn set_initial_window(&mut self, segments: Option<usize>) {
+        self.initial_window = segments.map(|segments| segments.max(1))
+    }
+
+    /// Return whether receive window auto-tuning is enabled.
+    ///
+    /// See also the [set_recv_window_autotuning](#method.set_recv_window_autotuning) method.
+    pub fn recv_window_autotuning(&self) -> bool {
+        self.rx_autotune
+    }
+
+    /// Enable or disable receive window auto-tuning.
+    ///
+    /// When enabled, the advertised window starts small and doubles every time the remote
+    /// fills it, up to the receive buffer capacity. This keeps a peer on a slow or
+    /// high-latency path from queueing more data than the application drains, while still
+    /// letting fast transfers use the whole buffer. The new value takes effect on the next
+    /// call to [listen](#method.listen) or [connect](#method.connect).
+    ///
+    /// By default, auto-tuning is disabled and the whole free buffer space is advertised.
+    pub fn set_recv_window_autotuning(&mut self, enabled: bool) {
+        self.rx_autotune = enabled
+    }
+
+    /// Return whether received urgent data is left in the stream.
+    ///
+    /// See also the [set_urgent_inline](#method.set_urgent_inline) method.
+    pub fn urgent_inline(&self) -> bool {
+        self.urgent_inline
+    }
+
+    /// Set whether received urgent data is left in the stream.
+    ///
+    /// By default, the last octet of urgent data is removed from the stream and delivered
+    /// out-of-band through [recv_urgent](#method.recv_urgent), which matches what most
+    /// deployed stacks do. When enabled, the octet is received in order with the rest of the
+    /// data instead, as specified by RFC 9293. This is equivalent to the `SO_OOBINLINE`
+    /// socket option.
+    ///
+    /// In both cases, receiving stops at the urgent mark; see
+    /// [at_urgent_mark](#method.at_urgent_mark).
+    pub fn set_urgent_inline(&mut self, enabled: bool) {
+        self.urgent_inline = enabled
+    }
+
+    /// Return the linger timeout.
+    ///
+    /// See also the [set_linger](#method.set_linger) method.
+    pub fn linger(&self) -> Option<Duration> {
+        self.linger
+    }
+
+    /// Set the linger timeout.
+    ///
+    /// After a [close](#method.close) call the socket keeps its buffers and its place in the
+    /// socket set while the remote endpoint acknowledges the outstanding data and the FIN.
+    /// With a linger timeout set, a socket that is still in the `FIN-WAIT-1`, `FIN-WAIT-2`,
+    /// `CLOSING` or `LAST-ACK` state once the timeout elapses is [aborted](#method.abort).
+    /// A zero timeout makes [close](#method.close) abort the connection right away.
+    ///
+    /// By default there is no linger timeout.
+    pub fn set_linger(&mut self, duration: Option<Duration>) {
+        self.linger = duration;
+        self.linger_expires_at = None;
+    }
+
+    /// Return the FIN-WAIT-2 timeout.
+    ///
+    /// See also the [set_fin_wait_2_timeout](#method.set_fin_wait_2_timeout) method.
+    pub fn fin_wait_2_timeout(&self) -> Option<Duration> {
+        self.fin_wait_2_timeout
+    }
+
+    /// Set the FIN-WAIT-2 timeout.
+    ///
+    /// Once the remote endpoint has acknowledged our FIN, the socket waits in the `FIN-WAIT-2`
+    /// state for the remote to close its half of the connection, which it may never do. With
+    /// a timeout set, the connection is aborted if the remote FIN does not arrive in time.
+    ///
+    /// By default there is no FIN-WAIT-2 timeout.
+    pub fn set_fin_wait_2_timeout(&mut self, duration: Option<Duration>) {
+        self.fin_wait_2_timeout = duration
+    }
+
+    /// Return the duration of the TIME-WAIT state.
+    ///
+    /// See also the [set_time_wait_timeout](#method.set_time_wait_timeout) method.
+    pub fn time_wait_timeout(&self) -> Duration {
+        self.time_wait_timeout
+    }
+
+    /// Set the duration of the TIME-WAIT state.
+    ///
+    /// A socket in `TIME-WAIT` cannot be reused for another connection, so on servers with
+    /// a lot of short-lived connections it may be desirable to shorten this state. The
+    /// timer restarts whenever the remote retransmits its FIN.
+    ///
+    /// By default, the TIME-WAIT state lasts 10 seconds.
+    pub fn set_time_wait_timeout(&mut self, duration: Duration) {
+        self.time_wait_timeout = duration
+    }
+
+    /// Return whether a new connection can reuse the local endpoint of the connection of
+    /// the socket in `TIME-WAIT`.
+    ///
+    /// See also the [set_reuse_addr](#method.set_reuse_addr) method.
+    pub fn reuse_addr(&self) -> bool {
+        self.reuse_addr
+    }
+
+    /// Set whether a new connection can reuse the local endpoint of the connection of the
+    /// socket in `TIME-WAIT`.
+    ///
+    /// When enabled, [listen](#method.listen), [connect](#method.connect) and
+    /// [connect_from](#method.connect_from) give up the `TIME-WAIT` state of the socket
+    /// at once. When disabled, they return `AddrInUse` for the local endpoint of the
+    /// connection in `TIME-WAIT` until the state ends, so that stray segments of the old
+    /// connection are not taken for segments of the new one; other endpoints can be used.
+    ///
+    /// Unlike `SO_REUSEADDR`, this only concerns the socket's own connection: the
+    /// endpoints held in `TIME-WAIT` by other sockets are not checked, and the application
+    /// must not give them to another socket meanwhile.
+    ///
+    /// Also unlike `SO_REUSEADDR`, this is enabled by default. A socket in `TIME-WAIT` is
+    /// not open, so the usual pattern of calling `listen` again whenever
+    /// [is_open](#method.is_open) returns false would otherwise fail with `AddrInUse` for
+    /// as long as the state lasts after the socket closed its connection first.
+    pub fn set_reuse_addr(&mut self, enabled: bool) {
+        self.reuse_addr = enabled
+    }
+
+    /// Return whether using `local_endpoint` for a new connection would reuse the endpoint
+    /// of the connection in `TIME-WAIT`, against the settings of the socket.
+    fn time_wait_conflict(&self, local_endpoint: IpListenEndpoint) -> bool {
+        match self.tuple {
+            Some(tuple) if self.state == State::TimeWait && !self.reuse_addr => {
+                tuple.local.port == local_endpoint.port
+                    && local_endpoint
+                        .addr
+                        .map_or(true, |addr| addr == tuple.local.addr)
+            }
+            _ => false,
+        }
+    }
+
+    /// Return whether the RFC 7323 timestamps option is enabled.
+    ///
+    /// See also the [set_timestamps_enabled](#method.set_timestamps_enabled) method.
+    pub fn timestamps_enabled(&self) -> bool {
+        self.timestamps
+    }
+
+    /// Enable or disable the RFC 7323 timestamps option.
+    ///
+    /// When enabled, the socket offers timestamps in its SYN and accepts them in a remote SYN.
+    /// Once negotiated, they let a socket in the `TIME-WAIT` state that was opened with
+    /// [listen](#method.listen) accept a new connection from the same remote endpoint, as
+    /// described in RFC 6191. Without timestamps, such a connection is only accepted if its
+    /// initial sequence number is above the previous one. By default, it is disabled.
+    ///
+    /// The setting takes effect on the next connection.
+    pub fn set_timestamps_enabled(&mut self, enabled: bool) {
+        self.timestamps = enabled
+    }
+
+    /// Return the maximum number of out-of-order data ranges the socket can hold on to.
+    ///
+    /// See also the [set_assembler_capacity](#method.set_assembler_capacity) method.
+    pub fn assembler_capacity(&self) -> usize {
+        self.assembler.capacity()
+    }
+
+    /// Set the maximum number of out-of-order data ranges the socket can hold on to.
+    ///
+    /// Each range of data received after a gap in the sequence space takes up one slot
+    /// until the gap is filled. Segments that would need another slot when all of them are
+    /// in use are dropped and counted in [assembler_full_drops](#method.assembler_full_drops);
+    /// the remote has to retransmit them. Links that lose many segments in a window benefit
+    /// from a larger capacity.
+    ///
+    /// Any out-of-order data currently held is discarded.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `count` is zero.
+    #[cfg(feature = "alloc")]
+    pub fn set_assembler_capacity(&mut self, count: usize) {
+        self.assembler = Assembler::with_capacity(count);
+    }
+
+    /// Return the number of received segments dropped because the assembler had no room
+    /// left to track them, since the socket was created.
+    ///
+    /// See also the [set_assembler_capacity](#method.set_assembler_capacity) method.
+    pub fn assembler_full_drops(&self) -> u64 {
+        self.assembler_full_drops
+    }
+
     /// Return the keep-alive interval.
     ///
     /// See also the [set_keep_alive](#method.set_keep_alive) method.
@@ -827,6 +1475,36 @@ impl<'a> Socket<'a> {
         }
     }
 
+    /// Return the idle timeout.
+    ///
+    /// See also the [set_idle_timeout](#method.set_idle_timeout) method.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+
+    /// Set the idle timeout.
+    ///
+    /// A connected socket with an idle timeout set reports, through the
+    /// [poll_idle](#method.poll_idle) method, every period of that duration during which
+    /// no data, SYN or FIN was exchanged in either direction and none of the sent data was
+    /// acknowledged. Keep-alive and zero window probes, and the bare ACKs answering them,
+    /// do not count as activity, so applications can implement their own heartbeats
+    /// independently from the keep-alive functionality.
+    ///
+    /// Unlike the [timeout](#method.set_timeout), the idle timeout never aborts the
+    /// connection.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+        self.idle_reported = false;
+    }
+
+    /// Return whether the connection became idle since the last call, clearing the event.
+    ///
+    /// See also the [set_idle_timeout](#method.set_idle_timeout) method.
+    pub fn poll_idle(&mut self) -> bool {
+        mem::replace(&mut self.idle_event, false)
+    }
+
     /// Return the time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
     ///
     /// See also the [set_hop_limit](#method.set_hop_limit) method
@@ -854,6 +1532,55 @@ impl<'a> Socket<'a> {
         self.hop_limit = hop_limit
     }
 
+    /// Return the traffic class (IPv6) or type of service (IPv4) value used in outgoing
+    /// packets.
+    ///
+    /// See also the [set_traffic_class](#method.set_traffic_class) method
+    pub fn traffic_class(&self) -> Option<u8> {
+        self.traffic_class
+    }
+
+    /// Set the traffic class (IPv6) or type of service (IPv4) value used in outgoing packets.
+    ///
+    /// For IPv4 only the DSCP, i.e. the upper six bits, is used. A socket without an
+    /// explicitly set traffic class sends segments with a traffic class of zero.
+    pub fn set_traffic_class(&mut self, traffic_class: Option<u8>) {
+        self.traffic_class = traffic_class
+    }
+
+    /// Return the IPv6 flow label used in outgoing packets.
+    ///
+    /// See also the [set_flow_label](#method.set_flow_label) method
+    pub fn flow_label(&self) -> Option<u32> {
+        self.flow_label
+    }
+
+    /// Set the IPv6 flow label used in outgoing packets.
+    ///
+    /// Only the lower 20 bits are used, and IPv4 connections are not affected. A socket
+    /// without an explicitly set flow label sends segments with a flow label of zero.
+    pub fn set_flow_label(&mut self, flow_label: Option<u32>) {
+        self.flow_label = flow_label
+    }
+
+    /// Return the extension headers inserted in outgoing IPv6 packets.
+    ///
+    /// See also the [set_ipv6_ext_headers](#method.set_ipv6_ext_headers) method
+    #[cfg(feature = "proto-ipv6")]
+    pub fn ipv6_ext_headers(&self) -> &Ipv6ExtHeaders {
+        &self.ipv6_ext_headers
+    }
+
+    /// Set the extension headers inserted between the IPv6 header and the TCP header of
+    /// outgoing packets, e.g. a Router Alert option.
+    ///
+    /// The headers are not inserted on IEEE 802.15.4 links, and IPv4 packets are not
+    /// affected.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn set_ipv6_ext_headers(&mut self, headers: Ipv6ExtHeaders) {
+        self.ipv6_ext_headers = headers
+    }
+
     /// Return the listening endpoint
     #[inline]
     pub fn listen_endpoint(&self) -> IpListenEndpoint {
@@ -878,6 +1605,55 @@ impl<'a> Socket<'a> {
         self.state
     }
 
+    /// Return the maximum segment size announced by the remote, or 536 octets if it did
+    /// not announce one.
+    ///
+    /// Segments are also limited by the MTU of the path to the remote.
+    pub fn remote_mss(&self) -> usize {
+        self.remote_mss
+    }
+
+    /// Return the window scale factors negotiated with the remote as described in
+    /// RFC 7323, or `None` if the remote does not support window scaling.
+    ///
+    /// The first factor applies to the windows advertised to the remote, the second one to
+    /// the windows it advertises.
+    pub fn negotiated_window_scale(&self) -> Option<(u8, u8)> {
+        Some((self.remote_win_shift, self.remote_win_scale?))
+    }
+
+    /// Return whether selective acknowledgements (RFC 2018) are in use on the connection.
+    pub fn sack_active(&self) -> bool {
+        self.remote_has_sack
+    }
+
+    /// Return whether the RFC 7323 timestamps option is in use on the connection.
+    ///
+    /// See also the [set_timestamps_enabled](#method.set_timestamps_enabled) method.
+    pub fn timestamps_active(&self) -> bool {
+        self.remote_has_timestamps
+    }
+
+    /// Return the receive window, i.e. the number of octets the remote may send beyond
+    /// those already acknowledged, as advertised in the next segment.
+    pub fn recv_window(&self) -> usize {
+        (self.scaled_window() as usize) << self.remote_win_shift
+    }
+
+    /// Return the send window, i.e. the number of octets that can be sent beyond those in
+    /// flight, limited by both the window advertised by the remote and the congestion
+    /// window.
+    pub fn send_window(&self) -> usize {
+        let in_flight = self.remote_last_seq - self.local_seq_no;
+        self.remote_win_len.min(self.cwnd).saturating_sub(in_flight)
+    }
+
+    /// Return the window last advertised by the remote, in octets, relative to the first
+    /// octet not acknowledged yet.
+    pub fn remote_window(&self) -> usize {
+        self.remote_win_len
+    }
+
     fn reset(&mut self) {
         let rx_cap_log2 =
             mem::size_of::<usize>() * 8 - self.rx_buffer.capacity().leading_zeros() as usize;
@@ -885,11 +1661,12 @@ impl<'a> Socket<'a> {
         self.state = State::Closed;
         self.timer = Timer::new();
         self.rtte = RttEstimator::default();
-        self.assembler = Assembler::new();
+        self.assembler.clear();
         self.tx_buffer.clear();
         self.rx_buffer.clear();
         self.rx_fin_received = false;
         self.listen_endpoint = IpListenEndpoint::default();
+        self.active_open = false;
         self.tuple = None;
         self.local_seq_no = TcpSeqNumber::default();
         self.remote_seq_no = TcpSeqNumber::default();
@@ -898,11 +1675,38 @@ impl<'a> Socket<'a> {
         self.remote_last_win = 0;
         self.remote_win_len = 0;
         self.remote_win_scale = None;
-        self.remote_win_shift = rx_cap_log2.saturating_sub(16) as u8;
+        self.remote_win_shift = self
+            .win_shift
+            .unwrap_or(rx_cap_log2.saturating_sub(16) as u8);
+        self.cwnd = usize::MAX;
+        self.ssthresh = usize::MAX;
+        self.rx_win_limit = if self.rx_autotune {
+            RX_AUTOTUNE_INITIAL_WINDOW.min(self.rx_buffer.capacity())
+        } else {
+            usize::MAX
+        };
+        self.rx_win_received = 0;
+        self.remote_has_timestamps = false;
+        self.remote_last_tsval = 0;
         self.remote_mss = DEFAULT_MSS;
         self.remote_last_ts = None;
         self.ack_delay_timer = AckDelayTimer::Idle;
+        self.ack_delayed_segments = 0;
+        self.quick_acks = 0;
         self.challenge_ack_timer = Instant::from_secs(0);
+        self.linger_expires_at = None;
+        self.fin_wait_2_expires_at = None;
+        self.close_pending = false;
+        self.persist_expires_at = None;
+        self.zero_window_since = None;
+        self.zero_window_probes = 0;
+        self.zero_window_event = false;
+        self.last_activity_at = None;
+        self.idle_reported = false;
+        self.idle_event = false;
+        self.rx_urgent_seq = None;
+        self.rx_urgent_data = None;
+        self.tx_urgent_mark = None;
         self.backlog.clear();
 
         #[cfg(feature = "async")]
@@ -930,8 +1734,12 @@ impl<'a> Socket<'a> {
         if self.is_open() {
             return Err(ListenError::InvalidState);
         }
+        if self.time_wait_conflict(local_endpoint) {
+            return Err(ListenError::AddrInUse);
+        }
 
         self.reset();
+        self.close_completed = false;
         self.listen_endpoint = local_endpoint;
         self.tuple = None;
         self.set_state(State::Listen);
@@ -1019,38 +1827,101 @@ impl<'a> Socket<'a> {
         if local_endpoint.addr.version() != remote_endpoint.addr.version() {
             return Err(ConnectError::Unaddressable);
         }
+        if self.time_wait_conflict(local_endpoint.into()) {
+            return Err(ConnectError::AddrInUse);
+        }
 
         self.reset();
-        self.tuple = Some(Tuple {
+        self.close_completed = false;
+        let tuple = Tuple {
             local: local_endpoint,
             remote: remote_endpoint,
-        });
+        };
+        self.tuple = Some(tuple);
+        self.active_open = true;
+        self.seed_from_host_cache(cx, remote_endpoint.addr);
         self.set_state(State::SynSent);
 
-        let seq = Self::random_seq_no(cx);
+        let seq = Self::initial_seq_no(cx, tuple);
         self.local_seq_no = seq;
         self.remote_last_seq = seq;
         Ok(())
     }
 
-    #[cfg(test)]
-    fn random_seq_no(_cx: &mut Context) -> TcpSeqNumber {
-        TcpSeqNumber(10000)
-    }
-
-    #[cfg(not(test))]
-    fn random_seq_no(cx: &mut Context) -> TcpSeqNumber {
-        TcpSeqNumber(cx.rand().rand_u32() as i32)
-    }
-
-    /// Close the transmit half of the full-duplex connection.
+    /// Connect to a given endpoint from a given address and port of the interface.
     ///
-    /// Note that there is no corresponding function for the receive half of the full-duplex
-    /// connection; only the remote end can close it. If you no longer wish to receive any
-    /// data and would like to reuse the socket right away, use [abort](#method.abort).
-    pub fn close(&mut self) {
-        match self.state {
-            // In the LISTEN state there is no established connection.
+    /// This is [connect](#method.connect) with the local address pinned, e.g. on
+    /// interfaces with several addresses, where the address picked automatically depends
+    /// on the routes and may change as addresses come and go.
+    ///
+    /// In addition to the errors of `connect`, this function returns
+    /// `Err(ConnectError::Unaddressable)` if the local address is not assigned to the
+    /// interface, or is still going through Duplicate Address Detection.
+    pub fn connect_from<T, U>(
+        &mut self,
+        cx: &mut Context,
+        local_endpoint: T,
+        remote_endpoint: U,
+    ) -> Result<(), ConnectError>
+    where
+        T: Into<IpEndpoint>,
+        U: Into<IpEndpoint>,
+    {
+        let local_endpoint: IpEndpoint = local_endpoint.into();
+        if self.is_open() {
+            return Err(ConnectError::InvalidState);
+        }
+        if !cx.has_ip_addr(local_endpoint.addr) {
+            return Err(ConnectError::Unaddressable);
+        }
+        self.connect(cx, remote_endpoint, local_endpoint)
+    }
+
+    /// Start from the round-trip time estimate and slow-start threshold cached for `addr`,
+    /// if any.
+    fn seed_from_host_cache(&mut self, cx: &Context, addr: IpAddress) {
+        if let Some(metrics) = cx.host_cache().lookup(&addr, cx.now()) {
+            tcp_trace!(
+                "host cache: using cached rtt={} rttvar={} ssthresh={:?}",
+                metrics.rtt,
+                metrics.rttvar,
+                metrics.ssthresh
+            );
+            self.rtte = RttEstimator::from_host_metrics(metrics);
+            if let Some(ssthresh) = metrics.ssthresh {
+                self.ssthresh = ssthresh;
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn initial_seq_no(_cx: &mut Context, _tuple: Tuple) -> TcpSeqNumber {
+        TcpSeqNumber(10000)
+    }
+
+    #[cfg(not(test))]
+    fn initial_seq_no(cx: &mut Context, tuple: Tuple) -> TcpSeqNumber {
+        cx.tcp_isn(tuple.local, tuple.remote)
+    }
+
+    /// Close the transmit half of the full-duplex connection.
+    ///
+    /// Note that there is no corresponding function for the receive half of the full-duplex
+    /// connection; only the remote end can close it. If you no longer wish to receive any
+    /// data and would like to reuse the socket right away, use [abort](#method.abort).
+    ///
+    /// If a [linger timeout](#method.set_linger) is set, it bounds how long the socket waits
+    /// for the close to complete. Completion is reported by [poll_closed](#method.poll_closed).
+    pub fn close(&mut self) {
+        if self.linger == Some(Duration::ZERO) && self.is_active() {
+            self.abort();
+            return;
+        }
+        if self.is_active() {
+            self.close_pending = true;
+        }
+        match self.state {
+            // In the LISTEN state there is no established connection.
             State::Listen => self.set_state(State::Closed),
             // In the SYN-SENT state the remote endpoint is not yet synchronized and, upon
             // receiving an RST, will abort the connection.
@@ -1074,12 +1945,66 @@ impl<'a> Socket<'a> {
     /// Aborts the connection, if any.
     ///
     /// This function instantly closes the socket. One reset packet will be sent to the remote
-    /// endpoint.
+    /// endpoint, and any data still queued in the transmit or receive buffer is discarded.
     ///
     /// In terms of the TCP state machine, the socket may be in any state and is moved to
     /// the `CLOSED` state.
     pub fn abort(&mut self) {
+        if self.is_active() {
+            self.close_pending = true;
+        }
         self.set_state(State::Closed);
+
+        // Account for the discarded data so that the reset still acknowledges
+        // everything we have received.
+        self.remote_seq_no += self.rx_buffer.len();
+        self.rx_buffer.clear();
+        self.tx_buffer.clear();
+        self.assembler.clear();
+        self.linger_expires_at = None;
+        self.rx_urgent_seq = None;
+        self.rx_urgent_data = None;
+        self.tx_urgent_mark = None;
+    }
+
+    /// Return whether a close initiated by [close](#method.close) or [abort](#method.abort)
+    /// has completed since the last call, clearing the event.
+    ///
+    /// The close is complete once the socket reaches the `CLOSED` or `TIME-WAIT` state, be
+    /// it gracefully, because it was reset, or because a timeout expired.
+    pub fn poll_closed(&mut self) -> bool {
+        mem::replace(&mut self.close_completed, false)
+    }
+
+    /// Return the instant at which the connection stalled because the remote advertised a
+    /// zero window while there was data to send, or `None` if it is not stalled.
+    ///
+    /// While stalled, the window is probed with exponential backoff so that a lost window
+    /// update cannot wedge the connection. A remote that keeps answering the probes without
+    /// ever opening its window is still alive as far as the [timeout](#method.set_timeout)
+    /// is concerned, so applications that want to give up on such a peer should compare
+    /// this instant with the current time.
+    pub fn zero_window_since(&self) -> Option<Instant> {
+        self.zero_window_since
+    }
+
+    /// Return the number of zero window probes sent during the current stall.
+    pub fn zero_window_probes(&self) -> u32 {
+        self.zero_window_probes
+    }
+
+    /// Return the number of times the connection stalled on a zero window, since the socket
+    /// was created.
+    pub fn zero_window_stalls(&self) -> u64 {
+        self.zero_window_stalls
+    }
+
+    /// Return whether the connection stalled on a zero window since the last call,
+    /// clearing the event.
+    ///
+    /// See also the [zero_window_since](#method.zero_window_since) method.
+    pub fn poll_zero_window(&mut self) -> bool {
+        mem::replace(&mut self.zero_window_event, false)
     }
 
     /// Return whether the socket is passively listening for incoming connections.
@@ -1214,6 +2139,9 @@ impl<'a> Socket<'a> {
         if !self.may_send() {
             return Err(SendError::InvalidState);
         }
+        if self.non_blocking && self.tx_buffer.is_full() {
+            return Err(SendError::WouldBlock);
+        }
 
         // The connection might have been idle for a long time, and so remote_last_ts
         // would be far in the past. Unless we clear it here, we'll abort the connection
@@ -1260,6 +2188,59 @@ impl<'a> Socket<'a> {
         })
     }
 
+    /// Enqueue a sequence of octets to be sent as urgent data, and fill it from a slice.
+    ///
+    /// The urgent pointer of the outgoing segments points past the last enqueued octet
+    /// until it is acknowledged. Receivers that deliver urgent data out-of-band only
+    /// deliver that last octet this way, so `data` is usually a single octet.
+    ///
+    /// See also [send_slice](#method.send_slice).
+    pub fn send_urgent(&mut self, data: &[u8]) -> Result<usize, SendError> {
+        let size = self.send_slice(data)?;
+        if size > 0 {
+            self.tx_urgent_mark = Some(self.local_seq_no + self.tx_buffer.len());
+        }
+        Ok(size)
+    }
+
+    /// Return the last octet of urgent data received out-of-band, if any, clearing it.
+    ///
+    /// The octet is available as soon as it has been received, even before the data
+    /// preceding it in the stream has been read. A newer urgent octet replaces one that
+    /// has not been read yet. This function always returns `None` if
+    /// [urgent data is received inline](#method.set_urgent_inline).
+    pub fn recv_urgent(&mut self) -> Option<u8> {
+        self.rx_urgent_data.take()
+    }
+
+    /// Return whether the next octet to be received is the last octet of urgent data.
+    ///
+    /// Reads never go past the urgent mark, so that applications can tell the data sent
+    /// before the urgent data from the data sent after it, e.g. to discard the former.
+    /// This is equivalent to the `SIOCATMARK` ioctl.
+    pub fn at_urgent_mark(&self) -> bool {
+        self.rx_urgent_seq == Some(self.remote_seq_no)
+    }
+
+    /// Return how many octets may be received before the urgent mark, skipping the
+    /// urgent octet first if it is delivered out-of-band and we are at the mark.
+    fn urgent_recv_limit(&mut self) -> usize {
+        let Some(urgent_seq) = self.rx_urgent_seq else {
+            return usize::MAX;
+        };
+        match urgent_seq - self.remote_seq_no {
+            0 if !self.urgent_inline && !self.rx_buffer.is_empty() => {
+                tcp_trace!("rx buffer: skipping urgent octet");
+                self.rx_buffer.dequeue_allocated(1);
+                self.remote_seq_no += 1;
+                self.rx_urgent_seq = None;
+                usize::MAX
+            }
+            0 => usize::MAX,
+            offset => offset,
+        }
+    }
+
     fn recv_error_check(&mut self) -> Result<(), RecvError> {
         // We may have received some data inside the initial SYN, but until the connection
         // is fully open we must not dequeue any data, as it may be overwritten by e.g.
@@ -1270,19 +2251,26 @@ impl<'a> Socket<'a> {
             }
             return Err(RecvError::InvalidState);
         }
+        if self.non_blocking && self.rx_buffer.is_empty() {
+            return Err(RecvError::WouldBlock);
+        }
 
         Ok(())
     }
 
     fn recv_impl<'b, F, R>(&'b mut self, f: F) -> Result<R, RecvError>
     where
-        F: FnOnce(&'b mut SocketBuffer<'a>) -> (usize, R),
+        F: FnOnce(&'b mut SocketBuffer<'a>, usize) -> (usize, R),
     {
         self.recv_error_check()?;
 
+        let limit = self.urgent_recv_limit();
         let _old_length = self.rx_buffer.len();
-        let (size, result) = f(&mut self.rx_buffer);
+        let (size, result) = f(&mut self.rx_buffer, limit);
         self.remote_seq_no += size;
+        if matches!(self.rx_urgent_seq, Some(urgent_seq) if urgent_seq < self.remote_seq_no) {
+            self.rx_urgent_seq = None;
+        }
         if size > 0 {
             #[cfg(any(test, feature = "verbose"))]
             tcp_trace!(
@@ -1308,7 +2296,12 @@ impl<'a> Socket<'a> {
     where
         F: FnOnce(&'b mut [u8]) -> (usize, R),
     {
-        self.recv_impl(|rx_buffer| rx_buffer.dequeue_many_with(f))
+        self.recv_impl(|rx_buffer, limit| {
+            rx_buffer.dequeue_many_with(|buffer| {
+                let len = buffer.len().min(limit);
+                f(&mut buffer[..len])
+            })
+        })
     }
 
     /// Dequeue a sequence of received octets, and fill a slice from it.
@@ -1318,8 +2311,9 @@ impl<'a> Socket<'a> {
     ///
     /// See also [recv](#method.recv).
     pub fn recv_slice(&mut self, data: &mut [u8]) -> Result<usize, RecvError> {
-        self.recv_impl(|rx_buffer| {
-            let size = rx_buffer.dequeue_slice(data);
+        self.recv_impl(|rx_buffer, limit| {
+            let len = data.len().min(limit);
+            let size = rx_buffer.dequeue_slice(&mut data[..len]);
             (size, size)
         })
     }
@@ -1331,6 +2325,7 @@ impl<'a> Socket<'a> {
     pub fn peek(&mut self, size: usize) -> Result<&[u8], RecvError> {
         self.recv_error_check()?;
 
+        let size = size.min(self.urgent_recv_limit());
         let buffer = self.rx_buffer.get_allocated(0, size);
         if !buffer.is_empty() {
             #[cfg(any(test, feature = "verbose"))]
@@ -1371,7 +2366,28 @@ impl<'a> Socket<'a> {
             return;
         }
         tcp_trace!("state={}=>{}", self.state, state);
-        self.state = state;
+        let prev_state = mem::replace(&mut self.state, state);
+
+        if state == State::Established && matches!(prev_state, State::SynSent | State::SynReceived)
+        {
+            self.quick_acks = self.initial_quick_acks;
+            self.cwnd = self.initial_window.map_or(usize::MAX, |segments| {
+                segments.saturating_mul(self.remote_mss)
+            });
+        }
+
+        if matches!(state, State::Closed | State::TimeWait) {
+            if let (Some(tuple), Some(metrics)) =
+                (self.tuple, self.rtte.host_metrics(self.ssthresh))
+            {
+                self.closed_host_metrics = Some((tuple.remote.addr, metrics));
+            }
+        }
+
+        if self.close_pending && matches!(state, State::Closed | State::TimeWait) {
+            self.close_pending = false;
+            self.close_completed = true;
+        }
 
         #[cfg(feature = "async")]
         {
@@ -1381,6 +2397,17 @@ impl<'a> Socket<'a> {
             self.rx_waker.wake_all();
             self.tx_waker.wake_all();
             self.state_waker.wake_all();
+
+            // The connection attempt has either completed or failed. Moving from SYN-SENT
+            // to SYN-RECEIVED in a simultaneous open is neither.
+            if matches!(prev_state, State::SynSent | State::SynReceived)
+                && matches!(state, State::Established | State::Closed)
+            {
+                self.connect_waker.wake_all();
+            }
+            if matches!(state, State::Closed | State::TimeWait) {
+                self.close_waker.wake_all();
+            }
         }
     }
 
@@ -1396,6 +2423,8 @@ impl<'a> Socket<'a> {
             max_seg_size: None,
             sack_permitted: false,
             sack_ranges: [None, None, None],
+            timestamp: None,
+            urgent_at: None,
             payload: &[],
         };
         let ip_reply_repr = IpRepr::new(
@@ -1424,8 +2453,14 @@ impl<'a> Socket<'a> {
         (ip_reply_repr, reply_repr)
     }
 
-    fn ack_reply(&mut self, ip_repr: &IpRepr, repr: &TcpRepr) -> (IpRepr, TcpRepr<'static>) {
+    fn ack_reply(
+        &mut self,
+        cx: &Context,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
+    ) -> (IpRepr, TcpRepr<'static>) {
         let (mut ip_reply_repr, mut reply_repr) = Self::reply(ip_repr, repr);
+        reply_repr.timestamp = self.timestamp_repr(cx);
 
         // From RFC 793:
         // [...] an empty acknowledgment segment containing the current send-sequence number
@@ -1494,7 +2529,7 @@ impl<'a> Socket<'a> {
         // Rate-limit to 1 per second max.
         self.challenge_ack_timer = cx.now() + Duration::from_secs(1);
 
-        return Some(self.ack_reply(ip_repr, repr));
+        return Some(self.ack_reply(cx, ip_repr, repr));
     }
 
     pub(crate) fn accepts(&self, _cx: &mut Context, ip_repr: &IpRepr, repr: &TcpRepr) -> bool {
@@ -1530,9 +2565,65 @@ impl<'a> Socket<'a> {
         cx: &mut Context,
         ip_repr: &IpRepr,
         repr: &TcpRepr,
+    ) -> Option<(IpRepr, TcpRepr<'static>)> {
+        let reply = self.process_segment(cx, ip_repr, repr);
+        // The socket may be removed as soon as the connection ends, without being
+        // dispatched again.
+        self.fill_host_cache(cx);
+        reply
+    }
+
+    /// Record the metrics of the connection that just ended, if any, in the host cache.
+    fn fill_host_cache(&mut self, cx: &mut Context) {
+        if let Some((addr, metrics)) = self.closed_host_metrics.take() {
+            let now = cx.now();
+            cx.host_cache_mut().fill(addr, metrics, now);
+        }
+    }
+
+    fn process_segment(
+        &mut self,
+        cx: &mut Context,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
     ) -> Option<(IpRepr, TcpRepr<'static>)> {
         debug_assert!(self.accepts(cx, ip_repr, repr));
 
+        // A new connection attempt from the remote endpoint of a socket in TIME-WAIT can
+        // reopen the socket if it can be told apart from the old connection (RFC 6191).
+        if self.state == State::TimeWait
+            && repr.control == TcpControl::Syn
+            && repr.ack_number.is_none()
+            && self.listen_endpoint.port != 0
+            && self.syn_reopens_time_wait(repr)
+        {
+            tcp_trace!("received new SYN in TIME-WAIT, reopening");
+            let listen_endpoint = self.listen_endpoint;
+            self.reset();
+            self.listen_endpoint = listen_endpoint;
+            self.set_state(State::Listen);
+        }
+
+        // In a simultaneous open, the remote endpoint answers our SYN|ACK with a SYN|ACK of
+        // its own while we are in the SYN-RECEIVED state. Its SYN has already been accounted
+        // for in `remote_seq_no`, so strip it and process the segment as a plain ACK.
+        let simultaneous_syn_ack;
+        let repr = if self.state == State::SynReceived
+            && repr.control == TcpControl::Syn
+            && repr.ack_number.is_some()
+            && repr.seq_number + 1 == self.remote_seq_no
+        {
+            tcp_trace!("received SYN|ACK in SYN-RECEIVED, completing simultaneous open");
+            simultaneous_syn_ack = TcpRepr {
+                control: TcpControl::None,
+                seq_number: self.remote_seq_no,
+                ..*repr
+            };
+            &simultaneous_syn_ack
+        } else {
+            repr
+        };
+
         // Consider how much the sequence number space differs from the transmit buffer space.
         let (sent_syn, sent_fin) = match self.state {
             // In SYN-SENT or SYN-RECEIVED, we've just sent a SYN.
@@ -1565,6 +2656,8 @@ impl<'a> Socket<'a> {
             (State::Listen, _, None) => (),
             // This case is handled in `accepts()`.
             (State::Listen, _, Some(_)) => unreachable!(),
+            // A SYN without an acknowledgement in the SYN-SENT state is a simultaneous open.
+            (State::SynSent, TcpControl::Syn, None) => (),
             // Every packet after the initial SYN must be an acknowledgement.
             (_, _, None) => {
                 net_debug!("expecting an ACK");
@@ -1686,7 +2779,7 @@ impl<'a> Socket<'a> {
                     // If we're in the TIME-WAIT state, restart the TIME-WAIT timeout, since
                     // the remote end may not have realized we've closed the connection.
                     if self.state == State::TimeWait {
-                        self.timer.set_for_close(cx.now());
+                        self.timer.set_for_close(cx.now(), self.time_wait_timeout);
                     }
 
                     return self.challenge_ack_reply(cx, ip_repr, repr);
@@ -1694,6 +2787,12 @@ impl<'a> Socket<'a> {
             }
         }
 
+        if let (true, Some(timestamp)) = (self.remote_has_timestamps, repr.timestamp) {
+            if Self::tsval_after(timestamp.tsval, self.remote_last_tsval) {
+                self.remote_last_tsval = timestamp.tsval;
+            }
+        }
+
         // Compute the amount of acknowledged octets, removing the SYN and FIN bits
         // from the sequence space.
         let mut ack_len = 0;
@@ -1739,11 +2838,17 @@ impl<'a> Socket<'a> {
             // RSTs are not accepted in the LISTEN state.
             (State::Listen, TcpControl::Rst) => return None,
 
-            // RSTs in SYN-RECEIVED flip the socket back to the LISTEN state.
+            // RSTs in SYN-RECEIVED flip a passively opened socket back to the LISTEN
+            // state. An active open that reached SYN-RECEIVED through a simultaneous
+            // open is refused instead, and the socket is closed (RFC 793 § 3.4).
             (State::SynReceived, TcpControl::Rst) => {
                 tcp_trace!("received RST");
                 self.tuple = None;
-                self.set_state(State::Listen);
+                if self.active_open {
+                    self.set_state(State::Closed);
+                } else {
+                    self.set_state(State::Listen);
+                }
                 return None;
             }
 
@@ -1766,11 +2871,13 @@ impl<'a> Socket<'a> {
                     self.remote_mss = max_seg_size as usize
                 }
 
-                self.tuple = Some(Tuple {
+                let tuple = Tuple {
                     local: IpEndpoint::new(ip_repr.dst_addr(), repr.dst_port),
                     remote: IpEndpoint::new(ip_repr.src_addr(), repr.src_port),
-                });
-                self.local_seq_no = Self::random_seq_no(cx);
+                };
+                self.tuple = Some(tuple);
+                self.seed_from_host_cache(cx, ip_repr.src_addr());
+                self.local_seq_no = Self::initial_seq_no(cx, tuple);
                 self.remote_seq_no = repr.seq_number + 1;
                 self.remote_last_seq = self.local_seq_no;
                 self.remote_has_sack = repr.sack_permitted;
@@ -1779,6 +2886,7 @@ impl<'a> Socket<'a> {
                 if self.remote_win_scale.is_none() {
                     self.remote_win_shift = 0;
                 }
+                self.negotiate_timestamps(repr);
                 self.set_state(State::SynReceived);
                 self.timer.set_for_idle(cx.now(), self.keep_alive);
             }
@@ -1799,6 +2907,34 @@ impl<'a> Socket<'a> {
                 self.timer.set_for_idle(cx.now(), self.keep_alive);
             }
 
+            // SYN packets without an ACK in the SYN-SENT state mean both endpoints are
+            // connecting to each other at the same time. Change it to SYN-RECEIVED and
+            // answer with a SYN|ACK, as described in RFC 793 § 3.4 (Figure 8).
+            (State::SynSent, TcpControl::Syn) if repr.ack_number.is_none() => {
+                tcp_trace!("received SYN in SYN-SENT, simultaneous open");
+                if let Some(max_seg_size) = repr.max_seg_size {
+                    if max_seg_size == 0 {
+                        tcp_trace!("received SYN with zero MSS, ignoring");
+                        return None;
+                    }
+                    self.remote_mss = max_seg_size as usize;
+                }
+
+                self.remote_seq_no = repr.seq_number + 1;
+                // Rewind the last sequence number sent, so that our SYN is sent
+                // again, this time acknowledging the remote SYN.
+                self.remote_last_seq = self.local_seq_no;
+                self.remote_has_sack = repr.sack_permitted;
+                self.remote_win_scale = repr.window_scale;
+                // Remote doesn't support window scaling, don't do it.
+                if self.remote_win_scale.is_none() {
+                    self.remote_win_shift = 0;
+                }
+                self.negotiate_timestamps(repr);
+                self.set_state(State::SynReceived);
+                self.timer.set_for_idle(cx.now(), self.keep_alive);
+            }
+
             // SYN|ACK packets in the SYN-SENT state change it to ESTABLISHED.
             (State::SynSent, TcpControl::Syn) => {
                 tcp_trace!("received SYN|ACK");
@@ -1813,11 +2949,13 @@ impl<'a> Socket<'a> {
                 self.remote_seq_no = repr.seq_number + 1;
                 self.remote_last_seq = self.local_seq_no + 1;
                 self.remote_last_ack = Some(repr.seq_number);
+                self.remote_has_sack = repr.sack_permitted;
                 self.remote_win_scale = repr.window_scale;
                 // Remote doesn't support window scaling, don't do it.
                 if self.remote_win_scale.is_none() {
                     self.remote_win_shift = 0;
                 }
+                self.negotiate_timestamps(repr);
 
                 self.set_state(State::Established);
                 self.timer.set_for_idle(cx.now(), self.keep_alive);
@@ -1844,6 +2982,8 @@ impl<'a> Socket<'a> {
             (State::FinWait1, TcpControl::None) => {
                 if ack_of_fin {
                     self.set_state(State::FinWait2);
+                    self.fin_wait_2_expires_at =
+                        self.fin_wait_2_timeout.map(|timeout| cx.now() + timeout);
                 }
                 if ack_all {
                     self.timer.set_for_idle(cx.now(), self.keep_alive);
@@ -1857,7 +2997,7 @@ impl<'a> Socket<'a> {
                 self.rx_fin_received = true;
                 if ack_of_fin {
                     self.set_state(State::TimeWait);
-                    self.timer.set_for_close(cx.now());
+                    self.timer.set_for_close(cx.now(), self.time_wait_timeout);
                 } else {
                     self.set_state(State::Closing);
                     self.timer.set_for_idle(cx.now(), self.keep_alive);
@@ -1874,14 +3014,14 @@ impl<'a> Socket<'a> {
                 self.remote_seq_no += 1;
                 self.rx_fin_received = true;
                 self.set_state(State::TimeWait);
-                self.timer.set_for_close(cx.now());
+                self.timer.set_for_close(cx.now(), self.time_wait_timeout);
             }
 
             // ACK packets in CLOSING state change it to TIME-WAIT.
             (State::Closing, TcpControl::None) => {
                 if ack_of_fin {
                     self.set_state(State::TimeWait);
-                    self.timer.set_for_close(cx.now());
+                    self.timer.set_for_close(cx.now(), self.time_wait_timeout);
                 } else {
                     self.timer.set_for_idle(cx.now(), self.keep_alive);
                 }
@@ -1911,6 +3051,9 @@ impl<'a> Socket<'a> {
 
         // Update remote state.
         self.remote_last_ts = Some(cx.now());
+        if repr.segment_len() > 0 || ack_len > 0 || ack_of_fin {
+            self.note_activity(cx.now());
+        }
 
         // RFC 1323: The window field (SEG.WND) in the header of every incoming segment, with the
         // exception of SYN segments, is left-shifted by Snd.Wind.Scale bits before updating SND.WND.
@@ -1930,9 +3073,18 @@ impl<'a> Socket<'a> {
             );
             self.tx_buffer.dequeue_allocated(ack_len);
 
+            // Slow start: grow the congestion window by at most one segment per ACK, then
+            // by about one segment per round trip once past the slow-start threshold.
+            let increment = if self.cwnd < self.ssthresh {
+                ack_len.min(self.remote_mss)
+            } else {
+                (self.remote_mss * self.remote_mss / self.cwnd).max(1)
+            };
+            self.cwnd = self.cwnd.saturating_add(increment);
+
             // There's new room available in tx_buffer, wake the waiting task if any.
             #[cfg(feature = "async")]
-            self.tx_waker.wake_all();
+            self.wake_send_if_writable();
         }
 
         if let Some(ack_number) = repr.ack_number {
@@ -1983,6 +3135,9 @@ impl<'a> Socket<'a> {
             // We've processed everything in the incoming segment, so advance the local
             // sequence number past it.
             self.local_seq_no = ack_number;
+            if matches!(self.tx_urgent_mark, Some(mark) if mark <= self.local_seq_no) {
+                self.tx_urgent_mark = None;
+            }
             // During retransmission, if an earlier segment got lost but later was
             // successfully received, self.local_seq_no can move past self.remote_last_seq.
             // Do not attempt to retransmit the latter segments; not only this is pointless
@@ -1993,6 +3148,23 @@ impl<'a> Socket<'a> {
             }
         }
 
+        // Record the urgent mark. It may point past the data received so far.
+        if let Some(urgent_at) = repr.urgent_at.filter(|&urgent_at| urgent_at > 0) {
+            let urgent_seq = repr.seq_number + urgent_at as usize - 1;
+            let rx_end = self.remote_seq_no + self.rx_buffer.len();
+            if matches!(
+                self.state,
+                State::Established | State::FinWait1 | State::FinWait2
+            ) && urgent_seq >= rx_end
+                && urgent_seq < self.remote_seq_no + self.rx_buffer.capacity()
+                && self.rx_urgent_seq != Some(urgent_seq)
+            {
+                net_debug!("urgent data at seq {}", urgent_seq);
+                self.rx_urgent_seq = Some(urgent_seq);
+                self.rx_urgent_data = None;
+            }
+        }
+
         let payload_len = repr.payload.len();
         if payload_len == 0 {
             return None;
@@ -2007,6 +3179,7 @@ impl<'a> Socket<'a> {
                 payload_len,
                 payload_offset
             );
+            self.assembler_full_drops += 1;
             return None;
         };
 
@@ -2030,9 +3203,29 @@ impl<'a> Socket<'a> {
             );
             self.rx_buffer.enqueue_unallocated(contig_len);
 
+            // Copy the urgent octet out-of-band as soon as it is received in order.
+            if let (Some(urgent_seq), false) = (self.rx_urgent_seq, self.urgent_inline) {
+                let offset = urgent_seq - self.remote_seq_no;
+                if offset >= self.rx_buffer.len() - contig_len && offset < self.rx_buffer.len() {
+                    self.rx_urgent_data = Some(self.rx_buffer.get_allocated(offset, 1)[0]);
+                }
+            }
+
+            // Once the remote has filled the advertised window, let it send twice as much.
+            if self.rx_autotune {
+                self.rx_win_received += contig_len;
+                if self.rx_win_received >= self.rx_win_limit {
+                    self.rx_win_limit = (self.rx_win_limit * 2).min(self.rx_buffer.capacity());
+                    self.rx_win_received = 0;
+                    tcp_trace!("rx window limit: grown to {}", self.rx_win_limit);
+                }
+            }
+
             // There's new data in rx_buffer, notify waiting task if any.
             #[cfg(feature = "async")]
-            self.rx_waker.wake_all();
+            if self.rx_buffer.len() >= self.recv_high_water.min(self.rx_buffer.capacity()) {
+                self.rx_waker.wake_all();
+            }
         }
 
         if !self.assembler.is_empty() {
@@ -2043,19 +3236,31 @@ impl<'a> Socket<'a> {
         // Handle delayed acks
         if let Some(ack_delay) = self.ack_delay {
             if self.ack_to_transmit() || self.window_to_update() {
+                // A segment larger than our MSS was coalesced by the device from several.
+                let local_mss =
+                    cx.ip_mtu_from(&ip_repr.dst_addr()) - ip_repr.header_len() - TCP_HEADER_LEN;
+                let segments = (payload_len + local_mss - 1) / local_mss;
+                self.ack_delayed_segments += segments.max(1);
                 self.ack_delay_timer = match self.ack_delay_timer {
-                    AckDelayTimer::Idle => {
-                        tcp_trace!("starting delayed ack timer");
-
-                        AckDelayTimer::Waiting(cx.now() + ack_delay)
+                    _ if self.quick_acks > 0 => {
+                        tcp_trace!("quick ack mode, not delaying ack");
+                        self.quick_acks -= 1;
+                        AckDelayTimer::Immediate
                     }
                     // RFC1122 says "in a stream of full-sized segments there SHOULD be an ACK
                     // for at least every second segment".
-                    // For now, we send an ACK every second received packet, full-sized or not.
-                    AckDelayTimer::Waiting(_) => {
-                        tcp_trace!("delayed ack timer already started, forcing expiry");
+                    // We send an ACK every `ack_delay_segments` received packets, full-sized
+                    // or not.
+                    _ if self.ack_delayed_segments >= self.ack_delay_segments => {
+                        tcp_trace!("enough segments delayed, forcing delayed ack timer expiry");
                         AckDelayTimer::Immediate
                     }
+                    AckDelayTimer::Idle => {
+                        tcp_trace!("starting delayed ack timer");
+
+                        AckDelayTimer::Waiting(cx.now() + ack_delay)
+                    }
+                    AckDelayTimer::Waiting(expires_at) => AckDelayTimer::Waiting(expires_at),
                     AckDelayTimer::Immediate => {
                         tcp_trace!("delayed ack timer already force-expired");
                         AckDelayTimer::Immediate
@@ -2072,7 +3277,7 @@ impl<'a> Socket<'a> {
             // This is fine because smoltcp assumes that it can always transmit zero or one
             // packets for every packet it receives.
             tcp_trace!("ACKing incoming segment");
-            Some(self.ack_reply(ip_repr, repr))
+            Some(self.ack_reply(cx, ip_repr, repr))
         } else {
             None
         }
@@ -2085,19 +3290,101 @@ impl<'a> Socket<'a> {
         }
     }
 
+    fn fin_wait_2_timed_out(&self, timestamp: Instant) -> bool {
+        match self.fin_wait_2_expires_at {
+            Some(expires_at) => self.state == State::FinWait2 && timestamp >= expires_at,
+            None => false,
+        }
+    }
+
+    /// Return whether the RFC 7323 timestamp value `a` is more recent than `b`.
+    fn tsval_after(a: u32, b: u32) -> bool {
+        (a.wrapping_sub(b) as i32) > 0
+    }
+
+    fn negotiate_timestamps(&mut self, repr: &TcpRepr) {
+        self.remote_has_timestamps = self.timestamps && repr.timestamp.is_some();
+        self.remote_last_tsval = match repr.timestamp {
+            Some(timestamp) if self.remote_has_timestamps => timestamp.tsval,
+            _ => 0,
+        };
+    }
+
+    fn syn_reopens_time_wait(&self, repr: &TcpRepr) -> bool {
+        match (self.remote_has_timestamps, repr.timestamp) {
+            (true, Some(timestamp)) => Self::tsval_after(timestamp.tsval, self.remote_last_tsval),
+            // Without timestamps, fall back to the sequence number check of RFC 1122.
+            _ => repr.seq_number > self.remote_seq_no,
+        }
+    }
+
+    fn timestamp_repr(&self, cx: &Context) -> Option<TcpTimestampRepr> {
+        let tsval = cx.now().total_millis() as u32;
+        if self.state == State::SynSent && self.timestamps {
+            Some(TcpTimestampRepr { tsval, tsecr: 0 })
+        } else if self.remote_has_timestamps {
+            Some(TcpTimestampRepr {
+                tsval,
+                tsecr: self.remote_last_tsval,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Return the length of the options carried by every segment after the handshake,
+    /// which reduces the amount of data that fits in a segment.
+    fn segment_options_len(&self) -> usize {
+        if self.remote_has_timestamps {
+            // The timestamps option, padded to a multiple of 4 octets.
+            12
+        } else {
+            0
+        }
+    }
+
+    fn is_lingering(&self) -> bool {
+        self.linger.is_some()
+            && matches!(
+                self.state,
+                State::FinWait1 | State::FinWait2 | State::Closing | State::LastAck
+            )
+    }
+
+    fn linger_expired(&self, timestamp: Instant) -> bool {
+        match self.linger_expires_at {
+            Some(linger_expires_at) => self.is_lingering() && timestamp >= linger_expires_at,
+            None => false,
+        }
+    }
+
+    /// Return the length of the IP header of an outgoing packet, including the
+    /// extension headers inserted after it.
+    fn ip_header_len(&self, ip_repr: &IpRepr) -> usize {
+        match ip_repr {
+            #[cfg(feature = "proto-ipv6")]
+            IpRepr::Ipv6(_) => ip_repr.header_len() + self.ipv6_ext_headers.buffer_len(),
+            #[allow(unreachable_patterns)]
+            _ => ip_repr.header_len(),
+        }
+    }
+
     fn seq_to_transmit(&self, cx: &Context) -> bool {
-        let ip_header_len = match self.tuple.unwrap().local.addr {
+        let local_addr = self.tuple.unwrap().local.addr;
+        let ip_header_len = match local_addr {
             #[cfg(feature = "proto-ipv4")]
             IpAddress::Ipv4(_) => crate::wire::IPV4_HEADER_LEN,
             #[cfg(feature = "proto-ipv6")]
-            IpAddress::Ipv6(_) => crate::wire::IPV6_HEADER_LEN,
+            IpAddress::Ipv6(_) => crate::wire::IPV6_HEADER_LEN + self.ipv6_ext_headers.buffer_len(),
         };
 
         // Max segment size we're able to send due to MTU limitations.
-        let local_mss = cx.ip_mtu() - ip_header_len - TCP_HEADER_LEN;
+        let local_mss = cx.ip_mtu_from(&local_addr) - ip_header_len - TCP_HEADER_LEN;
 
         // The effective max segment size, taking into account our and remote's limits.
-        let effective_mss = local_mss.min(self.remote_mss);
+        let effective_mss = local_mss
+            .min(self.remote_mss)
+            .saturating_sub(self.segment_options_len());
 
         // Have we sent data that hasn't been ACKed yet?
         let data_in_flight = self.remote_last_seq != self.local_seq_no;
@@ -2109,7 +3396,7 @@ impl<'a> Socket<'a> {
 
         // max sequence number we can send.
         let max_send_seq =
-            self.local_seq_no + core::cmp::min(self.remote_win_len, self.tx_buffer.len());
+            self.local_seq_no + self.remote_win_len.min(self.cwnd).min(self.tx_buffer.len());
 
         // Max amount of octets we can send.
         let max_send = if max_send_seq >= self.remote_last_seq {
@@ -2149,6 +3436,78 @@ impl<'a> Socket<'a> {
         can_send || can_fin
     }
 
+    fn zero_window_stalled(&self) -> bool {
+        matches!(
+            self.state,
+            State::Established
+                | State::CloseWait
+                | State::FinWait1
+                | State::Closing
+                | State::LastAck
+        ) && self.remote_win_len == 0
+            && self.remote_last_seq == self.local_seq_no
+            && !self.tx_buffer.is_empty()
+    }
+
+    /// Run the persist timer, returning whether a zero window probe is due.
+    fn update_persist_timer(&mut self, timestamp: Instant) -> bool {
+        if !self.zero_window_stalled() {
+            if self.zero_window_since.take().is_some() {
+                net_debug!(
+                    "remote window reopened after {} probes",
+                    self.zero_window_probes
+                );
+            }
+            self.persist_expires_at = None;
+            return false;
+        }
+
+        match self.persist_expires_at {
+            None => {
+                net_debug!("remote window is zero, starting persist timer");
+                self.persist_delay = self.rtte.retransmission_timeout();
+                self.persist_expires_at = Some(timestamp + self.persist_delay);
+                self.zero_window_since = Some(timestamp);
+                self.zero_window_probes = 0;
+                self.zero_window_stalls += 1;
+                self.zero_window_event = true;
+
+                #[cfg(feature = "async")]
+                self.state_waker.wake_all();
+
+                false
+            }
+            Some(expires_at) if timestamp >= expires_at => {
+                self.persist_delay = (self.persist_delay * 2).min(PERSIST_MAX_DELAY);
+                self.persist_expires_at = Some(timestamp + self.persist_delay);
+                self.zero_window_probes = self.zero_window_probes.saturating_add(1);
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    fn note_activity(&mut self, timestamp: Instant) {
+        self.last_activity_at = Some(timestamp);
+        self.idle_reported = false;
+    }
+
+    fn idle_expires_at(&self) -> Option<Instant> {
+        match self.state {
+            State::Established
+            | State::FinWait1
+            | State::FinWait2
+            | State::CloseWait
+            | State::Closing
+            | State::LastAck
+                if !self.idle_reported =>
+            {
+                Some(self.last_activity_at? + self.idle_timeout?)
+            }
+            _ => None,
+        }
+    }
+
     fn delayed_ack_expired(&self, timestamp: Instant) -> bool {
         match self.ack_delay_timer {
             AckDelayTimer::Idle => true,
@@ -2171,7 +3530,18 @@ impl<'a> Socket<'a> {
             | State::SynReceived
             | State::Established
             | State::FinWait1
-            | State::FinWait2 => self.scaled_window() > self.remote_last_win,
+            | State::FinWait2 => {
+                let (window, last_window) = (self.scaled_window(), self.remote_last_win);
+                // Hold back small updates, see `set_recv_high_water`.
+                let max_window = if self.rx_autotune {
+                    self.rx_win_limit
+                } else {
+                    self.rx_buffer.capacity()
+                };
+                let threshold = self.recv_high_water.min(max_window / 2);
+                window > last_window
+                    && ((window - last_window) as usize) << self.remote_win_shift >= threshold
+            }
             _ => false,
         }
     }
@@ -2180,6 +3550,8 @@ impl<'a> Socket<'a> {
     where
         F: FnOnce(&mut Context, (IpRepr, TcpRepr)) -> Result<(), E>,
     {
+        self.fill_host_cache(cx);
+
         if self.tuple.is_none() {
             return Ok(());
         }
@@ -2195,15 +3567,39 @@ impl<'a> Socket<'a> {
             self.remote_last_ts = Some(cx.now());
         }
 
+        if self.linger_expires_at.is_none() && self.is_lingering() {
+            self.linger_expires_at = self.linger.map(|linger| cx.now() + linger);
+        }
+
+        if matches!(self.idle_expires_at(), Some(expires_at) if cx.now() >= expires_at) {
+            net_debug!("connection idle");
+            self.idle_reported = true;
+            self.idle_event = true;
+
+            #[cfg(feature = "async")]
+            self.state_waker.wake_all();
+        }
+
         // Check if any state needs to be changed because of a timer.
         if self.timed_out(cx.now()) {
             // If a timeout expires, we should abort the connection.
             net_debug!("timeout exceeded");
             self.set_state(State::Closed);
+            self.fill_host_cache(cx);
+        } else if self.fin_wait_2_timed_out(cx.now()) {
+            // If the remote never closes its half of the connection, give up on it.
+            net_debug!("FIN-WAIT-2 timeout exceeded");
+            self.set_state(State::Closed);
+            self.fill_host_cache(cx);
+        } else if self.linger_expired(cx.now()) {
+            // If the close did not complete in time, give up on it and reset the connection.
+            net_debug!("linger timeout exceeded");
+            self.abort();
         } else if !self.seq_to_transmit(cx) {
             if let Some(retransmit_delta) = self.timer.should_retransmit(cx.now()) {
                 // If a retransmit timer expired, we should resend data starting at the last ACK.
                 net_debug!("retransmitting at t+{}", retransmit_delta);
+                let flight_size = self.remote_last_seq - self.local_seq_no;
 
                 // Rewind "last sequence number sent", as if we never
                 // had sent them. This will cause all data in the queue
@@ -2218,9 +3614,19 @@ impl<'a> Socket<'a> {
 
                 // Inform RTTE, so that it can avoid bogus measurements.
                 self.rtte.on_retransmit();
+
+                // The timeout is a sign of congestion, start over from the loss window
+                // and leave slow start at half the data that was in flight (RFC 5681 § 3.1).
+                if self.initial_window.is_some() {
+                    self.ssthresh = (flight_size / 2).max(2 * self.remote_mss);
+                    self.cwnd = self.remote_mss;
+                }
             }
         }
 
+        // If the remote closed its window, probe it in case the update reopening it gets lost.
+        let is_window_probe = self.update_persist_timer(cx.now());
+
         // Decide whether we're sending a packet.
         if self.seq_to_transmit(cx) {
             // If we have data to transmit and it fits into partner's window, do it.
@@ -2234,6 +3640,9 @@ impl<'a> Socket<'a> {
         } else if self.state == State::Closed {
             // If we need to abort the connection, do it.
             tcp_trace!("outgoing segment will abort connection");
+        } else if is_window_probe {
+            // If the persist timer expired, probe the remote window.
+            tcp_trace!("persist timer expired");
         } else if self.timer.should_keep_alive(cx.now()) {
             // If we need to transmit a keep-alive packet, do it.
             tcp_trace!("keep-alive timer expired");
@@ -2258,6 +3667,12 @@ impl<'a> Socket<'a> {
             0,
             self.hop_limit.unwrap_or(64),
         );
+        if let Some(traffic_class) = self.traffic_class {
+            ip_repr.set_traffic_class(traffic_class);
+        }
+        if let Some(flow_label) = self.flow_label {
+            ip_repr.set_flow_label(flow_label);
+        }
 
         // Construct the basic TCP representation, an empty ACK packet.
         // We'll adjust this to be more specific as needed.
@@ -2272,6 +3687,8 @@ impl<'a> Socket<'a> {
             max_seg_size: None,
             sack_permitted: false,
             sack_ranges: [None, None, None],
+            timestamp: self.timestamp_repr(cx),
+            urgent_at: None,
             payload: &[],
         };
 
@@ -2290,7 +3707,7 @@ impl<'a> Socket<'a> {
             State::SynSent | State::SynReceived => {
                 repr.control = TcpControl::Syn;
                 // window len must NOT be scaled in SYNs.
-                repr.window_len = self.rx_buffer.window().min((1 << 16) - 1) as u16;
+                repr.window_len = self.rx_window().min((1 << 16) - 1) as u16;
                 if self.state == State::SynSent {
                     repr.ack_number = None;
                     repr.window_scale = Some(self.remote_win_shift);
@@ -2312,7 +3729,7 @@ impl<'a> Socket<'a> {
                 // from the transmit buffer.
 
                 // Right edge of window, ie the max sequence number we're allowed to send.
-                let win_right_edge = self.local_seq_no + self.remote_win_len;
+                let win_right_edge = self.local_seq_no + self.remote_win_len.min(self.cwnd);
 
                 // Max amount of octets we're allowed to send according to the remote window.
                 let win_limit = if win_right_edge >= self.remote_last_seq {
@@ -2327,16 +3744,32 @@ impl<'a> Socket<'a> {
                 };
 
                 // Maximum size we're allowed to send. This can be limited by 3 factors:
-                // 1. remote window
+                // 1. remote window (and congestion window)
                 // 2. MSS the remote is willing to accept, probably determined by their MTU
                 // 3. MSS we can send, determined by our MTU.
-                let size = win_limit
-                    .min(self.remote_mss)
-                    .min(cx.ip_mtu() - ip_repr.header_len() - TCP_HEADER_LEN);
+                let headers_len = self.ip_header_len(&ip_repr) + TCP_HEADER_LEN;
+                let local_mss = cx.ip_mtu_from(&ip_repr.src_addr()) - headers_len;
+                let max_size =
+                    match cx.tcp_segmentation_size(&ip_repr.src_addr(), &ip_repr.dst_addr()) {
+                        // The device splits larger segments into segments of our MSS, which
+                        // the remote must accept.
+                        Some(size) if self.remote_mss >= local_mss => size - headers_len,
+                        _ => self.remote_mss.min(local_mss),
+                    };
+                let size = win_limit.min(max_size.saturating_sub(self.segment_options_len()));
 
                 let offset = self.remote_last_seq - self.local_seq_no;
                 repr.payload = self.tx_buffer.get_allocated(offset, size);
 
+                // Point past the urgent data until the remote has acknowledged it. If it is
+                // too far ahead, point as far as we can, so that the remote still enters
+                // urgent mode.
+                if let Some(mark) = self.tx_urgent_mark {
+                    if mark > repr.seq_number {
+                        repr.urgent_at = Some((mark - repr.seq_number).min(0xffff) as u16);
+                    }
+                }
+
                 // If we've sent everything we had in the buffer, follow it with the PSH or FIN
                 // flags, depending on whether the transmit half of the connection is open.
                 if offset + repr.payload.len() == self.tx_buffer.len() {
@@ -2360,8 +3793,10 @@ impl<'a> Socket<'a> {
         // has expired, and we also have data in transmit buffer. Since any packet that occupies
         // sequence space will elicit an ACK, we only need to send an explicit packet if we
         // couldn't fill the sequence space with anything.
+        // A zero window probe has the same shape as a keep-alive: both elicit an ACK carrying
+        // the current remote window.
         let is_keep_alive;
-        if self.timer.should_keep_alive(cx.now()) && repr.is_empty() {
+        if (is_window_probe || self.timer.should_keep_alive(cx.now())) && repr.is_empty() {
             repr.seq_number = repr.seq_number - 1;
             repr.payload = b"\x00"; // RFC 1122 says we should do this
             is_keep_alive = true;
@@ -2370,7 +3805,9 @@ impl<'a> Socket<'a> {
         }
 
         // Trace a summary of what will be sent.
-        if is_keep_alive {
+        if is_keep_alive && is_window_probe {
+            tcp_trace!("sending a zero window probe");
+        } else if is_keep_alive {
             tcp_trace!("sending a keep-alive");
         } else if !repr.payload.is_empty() {
             tcp_trace!(
@@ -2394,7 +3831,8 @@ impl<'a> Socket<'a> {
 
         if repr.control == TcpControl::Syn {
             // Fill the MSS option. See RFC 6691 for an explanation of this calculation.
-            let max_segment_size = cx.ip_mtu() - ip_repr.header_len() - TCP_HEADER_LEN;
+            let max_segment_size =
+                cx.ip_mtu_from(&ip_repr.src_addr()) - self.ip_header_len(&ip_repr) - TCP_HEADER_LEN;
             repr.max_seg_size = Some(max_segment_size as u16);
         }
 
@@ -2423,6 +3861,7 @@ impl<'a> Socket<'a> {
             }
         }
         self.ack_delay_timer = AckDelayTimer::Idle;
+        self.ack_delayed_segments = 0;
 
         // Leave the rest of the state intact if sending a keep-alive packet, since those
         // carry a fake segment.
@@ -2431,6 +3870,11 @@ impl<'a> Socket<'a> {
         }
 
         // We've sent a packet successfully, so we can update the internal state now.
+        if repr.segment_len() > 0 {
+            // Not `note_activity`, since the payload still borrows the transmit buffer.
+            self.last_activity_at = Some(cx.now());
+            self.idle_reported = false;
+        }
         self.remote_last_seq = repr.seq_number + repr.segment_len();
         self.remote_last_ack = repr.ack_number;
         self.remote_last_win = repr.window_len;
@@ -2488,19 +3932,51 @@ impl<'a> Socket<'a> {
                 (_, _) => PollAt::Ingress,
             };
 
-            // We wait for the earliest of our timers to fire.
-            *[self.timer.poll_at(), timeout_poll_at, delayed_ack_poll_at]
-                .iter()
-                .min()
-                .unwrap_or(&PollAt::Ingress)
-        }
-    }
-}
+            let fin_wait_2_poll_at = match (self.state, self.fin_wait_2_expires_at) {
+                (State::FinWait2, Some(expires_at)) => PollAt::Time(expires_at),
+                (_, _) => PollAt::Ingress,
+            };
 
-impl<'a> fmt::Write for Socket<'a> {
-    fn write_str(&mut self, slice: &str) -> fmt::Result {
-        let slice = slice.as_bytes();
-        if self.send_slice(slice) == Ok(slice.len()) {
+            let persist_poll_at = match (self.zero_window_stalled(), self.persist_expires_at) {
+                (false, _) => PollAt::Ingress,
+                // The persist timer starts on the next dispatch.
+                (true, None) => PollAt::Now,
+                (true, Some(expires_at)) => PollAt::Time(expires_at),
+            };
+
+            let linger_poll_at = match (self.is_lingering(), self.linger_expires_at) {
+                (false, _) => PollAt::Ingress,
+                // The linger period starts on the next dispatch.
+                (true, None) => PollAt::Now,
+                (true, Some(linger_expires_at)) => PollAt::Time(linger_expires_at),
+            };
+
+            let idle_poll_at = match self.idle_expires_at() {
+                Some(expires_at) => PollAt::Time(expires_at),
+                None => PollAt::Ingress,
+            };
+
+            // We wait for the earliest of our timers to fire.
+            *[
+                self.timer.poll_at(),
+                timeout_poll_at,
+                delayed_ack_poll_at,
+                fin_wait_2_poll_at,
+                persist_poll_at,
+                linger_poll_at,
+                idle_poll_at,
+            ]
+            .iter()
+            .min()
+            .unwrap_or(&PollAt::Ingress)
+        }
+    }
+}
+
+impl<'a> fmt::Write for Socket<'a> {
+    fn write_str(&mut self, slice: &str) -> fmt::Result {
+        let slice = slice.as_bytes();
+        if self.send_slice(slice) == Ok(slice.len()) {
             Ok(())
         } else {
             Err(fmt::Error)
@@ -2577,6 +4053,12 @@ mod test {
         next_header: IpProtocol::Tcp,
         payload_len: 20,
         hop_limit: 64,
+        #[cfg(feature = "proto-ipv4")]
+        dscp: 0,
+        #[cfg(not(feature = "proto-ipv4"))]
+        traffic_class: 0,
+        #[cfg(not(feature = "proto-ipv4"))]
+        flow_label: 0,
     });
     const SEND_TEMPL: TcpRepr<'static> = TcpRepr {
         src_port: REMOTE_PORT,
@@ -2589,6 +4071,8 @@ mod test {
         max_seg_size: None,
         sack_permitted: false,
         sack_ranges: [None, None, None],
+        timestamp: None,
+        urgent_at: None,
         payload: &[],
     };
     const _RECV_IP_TEMPL: IpRepr = IpReprIpvX(IpvXRepr {
@@ -2597,6 +4081,12 @@ mod test {
         next_header: IpProtocol::Tcp,
         payload_len: 20,
         hop_limit: 64,
+        #[cfg(feature = "proto-ipv4")]
+        dscp: 0,
+        #[cfg(not(feature = "proto-ipv4"))]
+        traffic_class: 0,
+        #[cfg(not(feature = "proto-ipv4"))]
+        flow_label: 0,
     });
     const RECV_TEMPL: TcpRepr<'static> = TcpRepr {
         src_port: LOCAL_PORT,
@@ -2609,6 +4099,8 @@ mod test {
         max_seg_size: None,
         sack_permitted: false,
         sack_ranges: [None, None, None],
+        timestamp: None,
+        urgent_at: None,
         payload: &[],
     };
 
@@ -2647,6 +4139,12 @@ mod test {
             next_header: IpProtocol::Tcp,
             payload_len: repr.buffer_len(),
             hop_limit: 64,
+            #[cfg(feature = "proto-ipv4")]
+            dscp: 0,
+            #[cfg(not(feature = "proto-ipv4"))]
+            traffic_class: 0,
+            #[cfg(not(feature = "proto-ipv4"))]
+            flow_label: 0,
         });
         net_trace!("send: {}", repr);
 
@@ -2780,6 +4278,7 @@ mod test {
     fn socket_syn_sent_with_buffer_sizes(tx_len: usize, rx_len: usize) -> TestSocket {
         let mut s = socket_with_buffer_sizes(tx_len, rx_len);
         s.state = State::SynSent;
+        s.active_open = true;
         s.tuple = Some(TUPLE);
         s.local_seq_no = LOCAL_SEQ;
         s.remote_last_seq = LOCAL_SEQ;
@@ -3118,6 +4617,43 @@ mod test {
         sanity!(s, socket_established());
     }
 
+    #[test]
+    fn test_syn_received_ack_with_data() {
+        let mut s = socket_syn_received();
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: Some(REMOTE_SEQ + 1),
+                max_seg_size: Some(BASE_MSS),
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abcdef"[..],
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 6),
+                window_len: 58,
+                ..RECV_TEMPL
+            }]
+        );
+        let mut buf = [0u8; 6];
+        assert_eq!(s.recv_slice(&mut buf), Ok(6));
+        assert_eq!(&buf, b"abcdef");
+    }
+
     #[test]
     fn test_syn_received_ack_too_low() {
         let mut s = socket_syn_received();
@@ -3420,6 +4956,112 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_connect_from() {
+        let mut s = socket();
+        assert_eq!(
+            s.socket
+                .connect_from(&mut s.cx, (OTHER_ADDR, 80), REMOTE_END),
+            Err(ConnectError::Unaddressable)
+        );
+        assert_eq!(
+            s.socket.connect_from(&mut s.cx, LOCAL_END, REMOTE_END),
+            Ok(())
+        );
+        assert_eq!(s.tuple, Some(TUPLE));
+    }
+
+    #[test]
+    fn test_reuse_addr_time_wait() {
+        let mut s = socket_time_wait(false);
+        s.set_reuse_addr(false);
+        assert_eq!(
+            s.socket.connect(&mut s.cx, REMOTE_END, LOCAL_END),
+            Err(ConnectError::AddrInUse)
+        );
+        assert_eq!(s.socket.listen(LOCAL_PORT), Err(ListenError::AddrInUse));
+        assert_eq!(s.state, State::TimeWait);
+
+        // Another local port is free.
+        assert_eq!(
+            s.socket
+                .connect_from(&mut s.cx, (LOCAL_ADDR, 81), REMOTE_END),
+            Ok(())
+        );
+        assert_eq!(s.state, State::SynSent);
+
+        // Reuse is enabled by default.
+        let mut s = socket_time_wait(false);
+        assert!(s.socket.reuse_addr());
+        assert_eq!(s.socket.connect(&mut s.cx, REMOTE_END, LOCAL_END), Ok(()));
+        assert_eq!(s.state, State::SynSent);
+    }
+
+    #[test]
+    fn test_connect_uses_host_cache() {
+        let mut s = socket();
+        let metrics = HostMetrics {
+            rtt: 50,
+            rttvar: 10,
+            ssthresh: Some(1000),
+        };
+        let now = s.cx.now();
+        s.cx.host_cache_mut().fill(REMOTE_ADDR.into(), metrics, now);
+        s.socket.connect(&mut s.cx, REMOTE_END, LOCAL_END).unwrap();
+        assert_eq!(s.rtte.retransmission_timeout(), Duration::from_millis(90));
+        assert_eq!(s.ssthresh, 1000);
+    }
+
+    #[test]
+    fn test_close_fills_host_cache() {
+        let mut s = socket_established();
+        s.rtte.sample(80);
+        s.abort();
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Rst,
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                ..RECV_TEMPL
+            }]
+        );
+        let metrics = s.cx.host_cache().lookup(&REMOTE_ADDR.into(), s.cx.now());
+        assert_eq!(metrics, s.rtte.host_metrics(usize::MAX));
+        assert_eq!(metrics.map(|metrics| metrics.ssthresh), Some(None));
+    }
+
+    #[test]
+    fn test_close_fills_host_cache_without_dispatch() {
+        let mut s = socket_last_ack();
+        s.rtte.sample(80);
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Fin,
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 1),
+                ..RECV_TEMPL
+            }]
+        );
+        let now = s.cx.now();
+        assert_eq!(s.cx.host_cache().lookup(&REMOTE_ADDR.into(), now), None);
+
+        // The socket can be removed as soon as the connection is closed.
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 1,
+                ack_number: Some(LOCAL_SEQ + 1 + 1),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Closed);
+        let metrics = s.cx.host_cache().lookup(&REMOTE_ADDR.into(), s.cx.now());
+        assert!(metrics.is_some());
+        assert_eq!(metrics, s.rtte.host_metrics(usize::MAX));
+    }
+
     #[test]
     fn test_syn_sent_sanity() {
         let mut s = socket();
@@ -3468,7 +5110,7 @@ mod test {
     }
 
     #[test]
-    fn test_syn_sent_syn_ack_not_incremented() {
+    fn test_syn_sent_negotiated_parameters() {
         let mut s = socket_syn_sent();
         recv!(
             s,
@@ -3482,104 +5124,122 @@ mod test {
                 ..RECV_TEMPL
             }]
         );
+        assert_eq!(s.negotiated_window_scale(), None);
+        assert!(!s.sack_active());
         send!(
             s,
             TcpRepr {
                 control: TcpControl::Syn,
                 seq_number: REMOTE_SEQ,
-                ack_number: Some(LOCAL_SEQ), // WRONG
+                ack_number: Some(LOCAL_SEQ + 1),
                 max_seg_size: Some(BASE_MSS - 80),
-                window_scale: Some(0),
+                window_scale: Some(2),
+                sack_permitted: true,
                 ..SEND_TEMPL
-            },
-            Some(TcpRepr {
-                control: TcpControl::Rst,
-                seq_number: LOCAL_SEQ,
-                ack_number: None,
-                window_len: 0,
-                ..RECV_TEMPL
-            })
+            }
         );
-        assert_eq!(s.state, State::SynSent);
-    }
-
-    #[test]
-    fn test_syn_sent_rst() {
-        let mut s = socket_syn_sent();
+        assert_eq!(s.state, State::Established);
+        assert_eq!(s.remote_mss(), (BASE_MSS - 80) as usize);
+        assert_eq!(s.negotiated_window_scale(), Some((0, 2)));
+        assert!(s.sack_active());
+        assert!(!s.timestamps_active());
+        assert_eq!(s.recv_window(), 64);
+
+        // The window of the SYN|ACK is not scaled, that of the following segments is.
+        assert_eq!(s.remote_window(), 256);
         send!(
             s,
             TcpRepr {
-                control: TcpControl::Rst,
-                seq_number: REMOTE_SEQ,
+                seq_number: REMOTE_SEQ + 1,
                 ack_number: Some(LOCAL_SEQ + 1),
+                window_len: 100,
                 ..SEND_TEMPL
             }
         );
-        assert_eq!(s.state, State::Closed);
+        assert_eq!(s.remote_window(), 400);
+        s.send_slice(b"abcdef").unwrap();
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"abcdef"[..],
+                ..RECV_TEMPL
+            }]
+        );
+        assert_eq!(s.send_window(), 394);
     }
 
     #[test]
-    fn test_syn_sent_rst_no_ack() {
+    fn test_syn_sent_simultaneous_open() {
         let mut s = socket_syn_sent();
-        send!(
+        recv!(
             s,
-            TcpRepr {
-                control: TcpControl::Rst,
-                seq_number: REMOTE_SEQ,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
                 ack_number: None,
-                ..SEND_TEMPL
-            }
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                ..RECV_TEMPL
+            }]
         );
-        assert_eq!(s.state, State::SynSent);
-    }
-
-    #[test]
-    fn test_syn_sent_rst_bad_ack() {
-        let mut s = socket_syn_sent();
+        // The remote endpoint connects to us at the same time.
         send!(
             s,
             TcpRepr {
-                control: TcpControl::Rst,
+                control: TcpControl::Syn,
                 seq_number: REMOTE_SEQ,
-                ack_number: Some(TcpSeqNumber(1234)),
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS - 80),
+                window_scale: Some(0),
                 ..SEND_TEMPL
             }
         );
-        assert_eq!(s.state, State::SynSent);
-    }
-
-    #[test]
-    fn test_syn_sent_bad_ack() {
-        let mut s = socket_syn_sent();
+        assert_eq!(s.state, State::SynReceived);
+        // Our SYN is sent again, now acknowledging the remote SYN.
         recv!(
             s,
             [TcpRepr {
                 control: TcpControl::Syn,
                 seq_number: LOCAL_SEQ,
-                ack_number: None,
+                ack_number: Some(REMOTE_SEQ + 1),
                 max_seg_size: Some(BASE_MSS),
                 window_scale: Some(0),
-                sack_permitted: true,
                 ..RECV_TEMPL
             }]
         );
+        // The remote endpoint answers our SYN with a SYN|ACK of its own, carrying data.
         send!(
             s,
             TcpRepr {
-                control: TcpControl::None, // Unexpected
+                control: TcpControl::Syn,
                 seq_number: REMOTE_SEQ,
-                ack_number: Some(LOCAL_SEQ + 1), // Correct
+                ack_number: Some(LOCAL_SEQ + 1),
+                window_scale: Some(0),
+                payload: &b"abcdef"[..],
                 ..SEND_TEMPL
             }
         );
-
-        // It should trigger no response and change no state
-        recv!(s, []);
-        assert_eq!(s.state, State::SynSent);
+        assert_eq!(s.state, State::Established);
+        assert_eq!(s.remote_mss, (BASE_MSS - 80) as usize);
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 6),
+                window_len: 58,
+                ..RECV_TEMPL
+            }]
+        );
+        let mut buf = [0u8; 6];
+        assert_eq!(s.recv_slice(&mut buf), Ok(6));
+        assert_eq!(&buf, b"abcdef");
     }
 
     #[test]
-    fn test_syn_sent_bad_ack_seq_1() {
+    fn test_syn_sent_simultaneous_open_rst() {
         let mut s = socket_syn_sent();
         recv!(
             s,
@@ -3596,26 +5256,31 @@ mod test {
         send!(
             s,
             TcpRepr {
-                control: TcpControl::None,
+                control: TcpControl::Syn,
                 seq_number: REMOTE_SEQ,
-                ack_number: Some(LOCAL_SEQ), // WRONG
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
                 ..SEND_TEMPL
-            },
-            Some(TcpRepr {
+            }
+        );
+        assert_eq!(s.state, State::SynReceived);
+        // The connection is refused; an active open does not fall back to LISTEN.
+        send!(
+            s,
+            TcpRepr {
                 control: TcpControl::Rst,
-                seq_number: LOCAL_SEQ, // matching the ack_number of the unexpected ack
+                seq_number: REMOTE_SEQ + 1,
                 ack_number: None,
-                window_len: 0,
-                ..RECV_TEMPL
-            })
+                ..SEND_TEMPL
+            }
         );
-
-        // It should trigger a RST, and change no state
-        assert_eq!(s.state, State::SynSent);
+        assert_eq!(s.state, State::Closed);
+        assert_eq!(s.tuple, None);
     }
 
     #[test]
-    fn test_syn_sent_bad_ack_seq_2() {
+    fn test_syn_sent_syn_ack_not_incremented() {
         let mut s = socket_syn_sent();
         recv!(
             s,
@@ -3632,26 +5297,173 @@ mod test {
         send!(
             s,
             TcpRepr {
-                control: TcpControl::None,
+                control: TcpControl::Syn,
                 seq_number: REMOTE_SEQ,
-                ack_number: Some(LOCAL_SEQ + 123456), // WRONG
+                ack_number: Some(LOCAL_SEQ), // WRONG
+                max_seg_size: Some(BASE_MSS - 80),
+                window_scale: Some(0),
                 ..SEND_TEMPL
             },
             Some(TcpRepr {
                 control: TcpControl::Rst,
-                seq_number: LOCAL_SEQ + 123456, // matching the ack_number of the unexpected ack
+                seq_number: LOCAL_SEQ,
                 ack_number: None,
                 window_len: 0,
                 ..RECV_TEMPL
             })
         );
-
-        // It should trigger a RST, and change no state
         assert_eq!(s.state, State::SynSent);
     }
 
     #[test]
-    fn test_syn_sent_close() {
+    fn test_syn_sent_rst() {
+        let mut s = socket_syn_sent();
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Rst,
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(LOCAL_SEQ + 1),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Closed);
+    }
+
+    #[test]
+    fn test_syn_sent_rst_no_ack() {
+        let mut s = socket_syn_sent();
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Rst,
+                seq_number: REMOTE_SEQ,
+                ack_number: None,
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::SynSent);
+    }
+
+    #[test]
+    fn test_syn_sent_rst_bad_ack() {
+        let mut s = socket_syn_sent();
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Rst,
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(TcpSeqNumber(1234)),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::SynSent);
+    }
+
+    #[test]
+    fn test_syn_sent_bad_ack() {
+        let mut s = socket_syn_sent();
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::None, // Unexpected
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(LOCAL_SEQ + 1), // Correct
+                ..SEND_TEMPL
+            }
+        );
+
+        // It should trigger no response and change no state
+        recv!(s, []);
+        assert_eq!(s.state, State::SynSent);
+    }
+
+    #[test]
+    fn test_syn_sent_bad_ack_seq_1() {
+        let mut s = socket_syn_sent();
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::None,
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(LOCAL_SEQ), // WRONG
+                ..SEND_TEMPL
+            },
+            Some(TcpRepr {
+                control: TcpControl::Rst,
+                seq_number: LOCAL_SEQ, // matching the ack_number of the unexpected ack
+                ack_number: None,
+                window_len: 0,
+                ..RECV_TEMPL
+            })
+        );
+
+        // It should trigger a RST, and change no state
+        assert_eq!(s.state, State::SynSent);
+    }
+
+    #[test]
+    fn test_syn_sent_bad_ack_seq_2() {
+        let mut s = socket_syn_sent();
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::None,
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(LOCAL_SEQ + 123456), // WRONG
+                ..SEND_TEMPL
+            },
+            Some(TcpRepr {
+                control: TcpControl::Rst,
+                seq_number: LOCAL_SEQ + 123456, // matching the ack_number of the unexpected ack
+                ack_number: None,
+                window_len: 0,
+                ..RECV_TEMPL
+            })
+        );
+
+        // It should trigger a RST, and change no state
+        assert_eq!(s.state, State::SynSent);
+    }
+
+    #[test]
+    fn test_syn_sent_close() {
         let mut s = socket();
         s.close();
         assert_eq!(s.state, State::Closed);
@@ -3693,6 +5505,35 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_syn_sent_win_scale_override() {
+        let mut s = socket_with_buffer_sizes(64, 1048576);
+        s.local_seq_no = LOCAL_SEQ;
+        s.set_window_scale(Some(2));
+        s.socket.connect(&mut s.cx, REMOTE_END, LOCAL_END).unwrap();
+        assert_eq!(s.remote_win_shift, 2);
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(2),
+                window_len: 65535,
+                sack_permitted: true,
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "window scale factor too large")]
+    fn test_win_scale_override_too_large() {
+        let mut s = socket();
+        s.set_window_scale(Some(15));
+    }
+
     #[test]
     fn test_syn_sent_syn_ack_no_window_scaling() {
         let mut s = socket_syn_sent_with_buffer_sizes(1048576, 1048576);
@@ -4094,6 +5935,93 @@ mod test {
         }));
     }
 
+    #[test]
+    fn test_established_send_urgent() {
+        let mut s = socket_established();
+        s.send_slice(b"abc").unwrap();
+        s.send_urgent(b"!").unwrap();
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                urgent_at: Some(4),
+                payload: &b"abc!"[..],
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1 + 4),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.tx_urgent_mark, None);
+        s.send_slice(b"def").unwrap();
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1 + 4,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"def"[..],
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_established_recv_urgent() {
+        let mut s = socket_established();
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                urgent_at: Some(4),
+                payload: &b"abc!def"[..],
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.recv_urgent(), Some(b'!'));
+        assert_eq!(s.recv_urgent(), None);
+
+        // Receiving stops at the mark, and the urgent octet is skipped.
+        let mut buffer = [0; 16];
+        assert_eq!(s.recv_slice(&mut buffer), Ok(3));
+        assert_eq!(&buffer[..3], b"abc");
+        assert!(s.at_urgent_mark());
+        assert_eq!(s.recv_slice(&mut buffer), Ok(3));
+        assert_eq!(&buffer[..3], b"def");
+        assert!(!s.at_urgent_mark());
+    }
+
+    #[test]
+    fn test_established_recv_urgent_inline() {
+        let mut s = socket_established();
+        s.set_urgent_inline(true);
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                urgent_at: Some(4),
+                payload: &b"abc!def"[..],
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.recv_urgent(), None);
+
+        let mut buffer = [0; 16];
+        assert_eq!(s.recv_slice(&mut buffer), Ok(3));
+        assert_eq!(&buffer[..3], b"abc");
+        assert!(s.at_urgent_mark());
+        assert_eq!(s.recv_slice(&mut buffer), Ok(4));
+        assert_eq!(&buffer[..4], b"!def");
+        assert!(!s.at_urgent_mark());
+    }
+
     #[test]
     fn test_established_no_ack() {
         let mut s = socket_established();
@@ -4326,37 +6254,101 @@ mod test {
     }
 
     #[test]
-    fn test_established_rst_bad_seq() {
-        let mut s = socket_established();
-        send!(
+    fn test_established_abort_frees_buffers() {
+        let mut s = socket_recved();
+        s.send_slice(b"xyz").unwrap();
+        s.abort();
+        assert_eq!(s.state, State::Closed);
+        assert_eq!(s.recv_queue(), 0);
+        assert_eq!(s.send_queue(), 0);
+        assert!(s.poll_closed());
+        assert!(!s.poll_closed());
+        recv!(
             s,
-            TcpRepr {
+            [TcpRepr {
                 control: TcpControl::Rst,
-                seq_number: REMOTE_SEQ, // Wrong seq
-                ack_number: None,
-                ..SEND_TEMPL
-            },
-            Some(TcpRepr {
                 seq_number: LOCAL_SEQ + 1,
-                ack_number: Some(REMOTE_SEQ + 1),
+                ack_number: Some(REMOTE_SEQ + 1 + 6),
+                window_len: 64,
                 ..RECV_TEMPL
-            })
-        );
-
-        assert_eq!(s.state, State::Established);
-
-        // Send something to advance seq by 1
-        send!(
-            s,
-            TcpRepr {
-                seq_number: REMOTE_SEQ + 1, // correct seq
-                ack_number: Some(LOCAL_SEQ + 1),
-                payload: &b"a"[..],
-                ..SEND_TEMPL
-            }
+            }]
         );
+    }
 
-        // Send wrong rst again, check that the challenge ack is correctly updated
+    #[test]
+    fn test_established_close_linger_timeout() {
+        let mut s = socket_established();
+        s.set_linger(Some(Duration::from_millis(500)));
+        s.close();
+        assert_eq!(s.state, State::FinWait1);
+        recv!(s, time 0, Ok(TcpRepr {
+            control: TcpControl::Fin,
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            ..RECV_TEMPL
+        }));
+        assert_eq!(s.poll_at(&s.cx), PollAt::Time(Instant::from_millis(500)));
+        recv_nothing!(s, time 100);
+        assert!(!s.poll_closed());
+        recv!(s, time 500, Ok(TcpRepr {
+            control: TcpControl::Rst,
+            seq_number: LOCAL_SEQ + 1 + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            ..RECV_TEMPL
+        }));
+        assert_eq!(s.state, State::Closed);
+        assert!(s.poll_closed());
+    }
+
+    #[test]
+    fn test_established_close_linger_zero() {
+        let mut s = socket_established();
+        s.set_linger(Some(Duration::ZERO));
+        s.close();
+        assert_eq!(s.state, State::Closed);
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Rst,
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_established_rst_bad_seq() {
+        let mut s = socket_established();
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Rst,
+                seq_number: REMOTE_SEQ, // Wrong seq
+                ack_number: None,
+                ..SEND_TEMPL
+            },
+            Some(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                ..RECV_TEMPL
+            })
+        );
+
+        assert_eq!(s.state, State::Established);
+
+        // Send something to advance seq by 1
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1, // correct seq
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"a"[..],
+                ..SEND_TEMPL
+            }
+        );
+
+        // Send wrong rst again, check that the challenge ack is correctly updated
         // The ack number must be updated even if we don't call dispatch on the socket
         // See https://github.com/smoltcp-rs/smoltcp/issues/338
         send!(
@@ -4641,6 +6633,149 @@ mod test {
         assert_eq!(s.state, State::Closed);
     }
 
+    #[test]
+    fn test_time_wait_timeout_configured() {
+        let mut s = socket_fin_wait_2();
+        s.set_time_wait_timeout(Duration::from_millis(1_000));
+        send!(s, time 0, TcpRepr {
+            control: TcpControl::Fin,
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 1),
+            ..SEND_TEMPL
+        });
+        assert_eq!(s.state, State::TimeWait);
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1 + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 1),
+                ..RECV_TEMPL
+            }]
+        );
+        recv_nothing!(s, time 999);
+        assert_eq!(s.state, State::TimeWait);
+        recv_nothing!(s, time 1_000);
+        assert_eq!(s.state, State::Closed);
+    }
+
+    fn socket_time_wait_with_timestamps() -> TestSocket {
+        let mut s = socket_time_wait(false);
+        s.listen_endpoint = LISTEN_END;
+        s.set_timestamps_enabled(true);
+        s.remote_has_timestamps = true;
+        s.remote_last_tsval = 1000;
+        s
+    }
+
+    #[test]
+    fn test_time_wait_reopen_newer_timestamp() {
+        let mut s = socket_time_wait_with_timestamps();
+        // The sequence number alone would not tell this SYN apart from an old duplicate.
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: None,
+                timestamp: Some(TcpTimestampRepr {
+                    tsval: 2000,
+                    tsecr: 0
+                }),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::SynReceived);
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: Some(REMOTE_SEQ + 1),
+                max_seg_size: Some(BASE_MSS),
+                timestamp: Some(TcpTimestampRepr {
+                    tsval: 0,
+                    tsecr: 2000
+                }),
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_time_wait_reopen_old_timestamp() {
+        let mut s = socket_time_wait_with_timestamps();
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ + 100,
+                ack_number: None,
+                timestamp: Some(TcpTimestampRepr {
+                    tsval: 500,
+                    tsecr: 0
+                }),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::TimeWait);
+    }
+
+    #[test]
+    fn test_time_wait_reopen_newer_seq_without_timestamps() {
+        let mut s = socket_time_wait(false);
+        s.listen_endpoint = LISTEN_END;
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ + 100,
+                ack_number: None,
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::SynReceived);
+    }
+
+    // =========================================================================================//
+    // Tests for the FIN-WAIT-2 timeout.
+    // =========================================================================================//
+
+    #[test]
+    fn test_fin_wait_2_timeout() {
+        let mut s = socket_fin_wait_1();
+        s.set_fin_wait_2_timeout(Some(Duration::from_millis(1_000)));
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Fin,
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1 + 1),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::FinWait2);
+        assert_eq!(
+            s.socket.poll_at(&mut s.cx),
+            PollAt::Time(Instant::from_millis(1_000))
+        );
+        recv_nothing!(s, time 999);
+        recv!(s, time 1_000, Ok(TcpRepr {
+            control: TcpControl::Rst,
+            seq_number: LOCAL_SEQ + 1 + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            ..RECV_TEMPL
+        }));
+        assert_eq!(s.state, State::Closed);
+    }
+
     // =========================================================================================//
     // Tests for the CLOSE-WAIT state.
     // =========================================================================================//
@@ -4703,6 +6838,33 @@ mod test {
         assert_eq!(s.state, State::Closed);
     }
 
+    #[test]
+    fn test_close_wait_close_completes() {
+        let mut s = socket_close_wait();
+        s.close();
+        assert_eq!(s.state, State::LastAck);
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Fin,
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 1),
+                ..RECV_TEMPL
+            }]
+        );
+        assert!(!s.poll_closed());
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 1,
+                ack_number: Some(LOCAL_SEQ + 1 + 1),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Closed);
+        assert!(s.poll_closed());
+    }
+
     #[test]
     fn test_last_ack_ack_not_of_fin() {
         let mut s = socket_last_ack();
@@ -5222,11 +7384,52 @@ mod test {
     }
 
     #[test]
-    fn test_data_retransmit_bursts_half_ack() {
-        let mut s = socket_established();
+    fn test_segmentation_offload() {
+        let mut s = socket_established_with_buffer_sizes(4096, 64);
+        s.remote_mss = BASE_MSS as usize;
+        s.remote_win_len = 4096;
+        s.cx.caps_mut().max_segmentation_size = Some(3000);
+        s.send_slice(&[0x5a; 4000]).unwrap();
+
+        // The device splits segments up to the segmentation size...
+        let headers_len = _RECV_IP_TEMPL.header_len() + TCP_HEADER_LEN;
+        recv(&mut s, Instant::from_millis(0), |result| {
+            assert_eq!(result.unwrap().payload.len(), 3000 - headers_len)
+        });
+        // ...into segments of our MSS, so it is not used when the remote accepts less.
+        s.remote_mss = 536;
+        recv(&mut s, Instant::from_millis(0), |result| {
+            assert_eq!(result.unwrap().payload.len(), 536)
+        });
+    }
+
+    #[test]
+    fn test_initial_window() {
+        let mut s = socket_syn_received();
+        s.set_initial_window(Some(1));
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: Some(REMOTE_SEQ + 1),
+                max_seg_size: Some(BASE_MSS),
+                ..RECV_TEMPL
+            }]
+        );
         s.remote_mss = 6;
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
         s.send_slice(b"abcdef012345").unwrap();
 
+        // Only one segment may be in flight before the first ACK...
         recv!(s, time 0, Ok(TcpRepr {
             control:    TcpControl::None,
             seq_number: LOCAL_SEQ + 1,
@@ -5234,22 +7437,16 @@ mod test {
             payload:    &b"abcdef"[..],
             ..RECV_TEMPL
         }), exact);
-        recv!(s, time 0, Ok(TcpRepr {
-            control:    TcpControl::Psh,
-            seq_number: LOCAL_SEQ + 1 + 6,
-            ack_number: Some(REMOTE_SEQ + 1),
-            payload:    &b"012345"[..],
-            ..RECV_TEMPL
-        }), exact);
-        // Acknowledge the first packet
+        recv_nothing!(s, time 0);
+
+        // ...which grows the window by one segment.
         send!(s, time 5, TcpRepr {
             seq_number: REMOTE_SEQ + 1,
             ack_number: Some(LOCAL_SEQ + 1 + 6),
-            window_len: 6,
             ..SEND_TEMPL
         });
-        // The second packet should be re-sent.
-        recv!(s, time 1500, Ok(TcpRepr {
+        assert_eq!(s.cwnd, 12);
+        recv!(s, time 5, Ok(TcpRepr {
             control:    TcpControl::Psh,
             seq_number: LOCAL_SEQ + 1 + 6,
             ack_number: Some(REMOTE_SEQ + 1),
@@ -5257,21 +7454,142 @@ mod test {
             ..RECV_TEMPL
         }), exact);
 
-        recv_nothing!(s, time 1550);
-    }
-
-    #[test]
-    fn test_data_retransmit_bursts_half_ack_close() {
-        let mut s = socket_established();
-        s.remote_mss = 6;
-        s.send_slice(b"abcdef012345").unwrap();
-        s.close();
-
-        recv!(s, time 0, Ok(TcpRepr {
-            control:    TcpControl::None,
-            seq_number: LOCAL_SEQ + 1,
+        // A retransmission timeout shrinks it back to a single segment.
+        recv!(s, time 1000, Ok(TcpRepr {
+            control:    TcpControl::Psh,
+            seq_number: LOCAL_SEQ + 1 + 6,
             ack_number: Some(REMOTE_SEQ + 1),
-            payload:    &b"abcdef"[..],
+            payload:    &b"012345"[..],
+            ..RECV_TEMPL
+        }), exact);
+        assert_eq!(s.cwnd, 6);
+        assert_eq!(s.ssthresh, 12);
+
+        // Slow start resumes up to the threshold...
+        send!(s, time 1005, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 12),
+            ..SEND_TEMPL
+        });
+        assert_eq!(s.cwnd, 12);
+
+        // ...past which the window grows by a fraction of a segment per ACK.
+        s.send_slice(b"abcdef").unwrap();
+        recv!(s, time 1005, Ok(TcpRepr {
+            control:    TcpControl::Psh,
+            seq_number: LOCAL_SEQ + 1 + 12,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }), exact);
+        send!(s, time 1010, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 18),
+            ..SEND_TEMPL
+        });
+        assert_eq!(s.cwnd, 15);
+    }
+
+    #[test]
+    fn test_recv_window_autotuning() {
+        let mut s = socket_established_with_buffer_sizes(64, 64);
+        s.set_recv_window_autotuning(true);
+        s.reset();
+        assert_eq!(s.rx_win_limit, 64);
+
+        let mut s = socket_established_with_buffer_sizes(64, 64);
+        s.set_recv_window_autotuning(true);
+        s.rx_win_limit = 16;
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abcdef"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 6),
+                window_len: 16,
+                ..RECV_TEMPL
+            }]
+        );
+        // Filling the window doubles it.
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 6,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"ghijklmnop"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 16),
+                window_len: 32,
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_data_retransmit_bursts_half_ack() {
+        let mut s = socket_established();
+        s.remote_mss = 6;
+        s.send_slice(b"abcdef012345").unwrap();
+
+        recv!(s, time 0, Ok(TcpRepr {
+            control:    TcpControl::None,
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }), exact);
+        recv!(s, time 0, Ok(TcpRepr {
+            control:    TcpControl::Psh,
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"012345"[..],
+            ..RECV_TEMPL
+        }), exact);
+        // Acknowledge the first packet
+        send!(s, time 5, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            window_len: 6,
+            ..SEND_TEMPL
+        });
+        // The second packet should be re-sent.
+        recv!(s, time 1500, Ok(TcpRepr {
+            control:    TcpControl::Psh,
+            seq_number: LOCAL_SEQ + 1 + 6,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"012345"[..],
+            ..RECV_TEMPL
+        }), exact);
+
+        recv_nothing!(s, time 1550);
+    }
+
+    #[test]
+    fn test_data_retransmit_bursts_half_ack_close() {
+        let mut s = socket_established();
+        s.remote_mss = 6;
+        s.send_slice(b"abcdef012345").unwrap();
+        s.close();
+
+        recv!(s, time 0, Ok(TcpRepr {
+            control:    TcpControl::None,
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
             ..RECV_TEMPL
         }), exact);
         recv!(s, time 0, Ok(TcpRepr {
@@ -6082,6 +8400,64 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_zero_window_persist_timer() {
+        let mut s = socket_established();
+        send!(s, time 0, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            window_len: 0,
+            ..SEND_TEMPL
+        });
+        s.send_slice(b"abcdef").unwrap();
+        recv_nothing!(s, time 0);
+        assert_eq!(s.zero_window_since(), Some(Instant::from_millis(0)));
+        assert_eq!(s.zero_window_stalls(), 1);
+        assert!(s.poll_zero_window());
+        assert!(!s.poll_zero_window());
+
+        // The window is probed after one RTO, then with exponential backoff.
+        assert_eq!(
+            s.socket.poll_at(&mut s.cx),
+            PollAt::Time(Instant::from_millis(700))
+        );
+        recv_nothing!(s, time 695);
+        recv!(s, time 700, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &[0],
+            ..RECV_TEMPL
+        }));
+        assert_eq!(
+            s.socket.poll_at(&mut s.cx),
+            PollAt::Time(Instant::from_millis(2100))
+        );
+        recv_nothing!(s, time 2095);
+        recv!(s, time 2100, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &[0],
+            ..RECV_TEMPL
+        }));
+        assert_eq!(s.zero_window_probes(), 2);
+
+        // Once the window reopens, the data goes out and the stall is over.
+        send!(s, time 2150, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            window_len: 64,
+            ..SEND_TEMPL
+        });
+        recv!(s, time 2150, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload:    &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+        assert_eq!(s.zero_window_since(), None);
+        assert_eq!(s.zero_window_stalls(), 1);
+    }
+
     #[test]
     fn test_zero_window_ack_on_window_growth() {
         let mut s = socket_established();
@@ -6234,6 +8610,72 @@ mod test {
         assert!(s.window_to_update());
     }
 
+    #[test]
+    fn test_window_update_high_water() {
+        let mut s = socket_established();
+        s.set_recv_high_water(16);
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &[0xaa; 32][..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 32),
+                window_len: 32,
+                ..RECV_TEMPL
+            }]
+        );
+
+        // Small reads don't open the window until it grew by the high-water mark.
+        s.recv(|buffer| (8, buffer.len())).unwrap();
+        assert!(!s.window_to_update());
+        recv_nothing!(s, time 0);
+        s.recv(|buffer| (8, buffer.len())).unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1 + 32),
+            window_len: 48,
+            ..RECV_TEMPL
+        }));
+
+        // The mark is clamped to half the receive buffer.
+        s.set_recv_high_water(1000);
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 32,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &[0xaa; 32][..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 64),
+                window_len: 16,
+                ..RECV_TEMPL
+            }]
+        );
+        s.recv(|buffer| (16, buffer.len())).unwrap();
+        recv_nothing!(s, time 0);
+        s.recv(|buffer| (16, buffer.len())).unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1 + 64),
+            window_len: 48,
+            ..RECV_TEMPL
+        }));
+    }
+
     // =========================================================================================//
     // Tests for timeouts.
     // =========================================================================================//
@@ -6496,6 +8938,88 @@ mod test {
         }));
     }
 
+    #[test]
+    fn test_idle_timeout() {
+        let mut s = socket_established();
+        s.set_ack_delay(None);
+        s.set_keep_alive(Some(Duration::from_millis(100)));
+        s.set_idle_timeout(Some(Duration::from_millis(250)));
+
+        send!(s, time 0, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            payload:    &b"abc"[..],
+            ..SEND_TEMPL
+        });
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1 + 3),
+            window_len: 61,
+            ..RECV_TEMPL
+        }));
+
+        // Keep-alive probes and their answers do not count as activity.
+        recv!(s, time 100, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ,
+            ack_number: Some(REMOTE_SEQ + 1 + 3),
+            window_len: 61,
+            payload:    &[0],
+            ..RECV_TEMPL
+        }));
+        send!(s, time 100, TcpRepr {
+            seq_number: REMOTE_SEQ + 1 + 3,
+            ack_number: Some(LOCAL_SEQ + 1),
+            ..SEND_TEMPL
+        });
+        recv!(s, time 200, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ,
+            ack_number: Some(REMOTE_SEQ + 1 + 3),
+            window_len: 61,
+            payload:    &[0],
+            ..RECV_TEMPL
+        }));
+        assert!(!s.poll_idle());
+        assert_eq!(
+            s.socket.poll_at(&mut s.cx),
+            PollAt::Time(Instant::from_millis(250))
+        );
+        recv_nothing!(s, time 250);
+        assert!(s.poll_idle());
+        assert!(!s.poll_idle());
+
+        // The idle period is reported only once.
+        recv!(s, time 300, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ,
+            ack_number: Some(REMOTE_SEQ + 1 + 3),
+            window_len: 61,
+            payload:    &[0],
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 400, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ,
+            ack_number: Some(REMOTE_SEQ + 1 + 3),
+            window_len: 61,
+            payload:    &[0],
+            ..RECV_TEMPL
+        }));
+        assert!(!s.poll_idle());
+
+        // Sending data starts a new idle period.
+        s.set_keep_alive(None);
+        s.send_slice(b"xyz").unwrap();
+        recv!(s, time 600, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1 + 3),
+            window_len: 61,
+            payload:    &b"xyz"[..],
+            ..RECV_TEMPL
+        }));
+        recv_nothing!(s, time 849);
+        assert!(!s.poll_idle());
+        recv_nothing!(s, time 850);
+        assert!(s.poll_idle());
+    }
+
     // =========================================================================================//
     // Tests for time-to-live configuration.
     // =========================================================================================//
@@ -6526,10 +9050,30 @@ mod test {
         s.set_hop_limit(Some(0));
     }
 
-    // =========================================================================================//
-    // Tests for reassembly.
-    // =========================================================================================//
-
+    #[test]
+    fn test_set_traffic_class_flow_label() {
+        let mut s = socket_syn_received();
+
+        s.set_traffic_class(Some(0xb8));
+        s.set_flow_label(Some(0x54321));
+        assert_eq!(
+            s.socket.dispatch(&mut s.cx, |_, (ip_repr, _)| {
+                assert_eq!(ip_repr.traffic_class(), 0xb8);
+                assert!(matches!(ip_repr.flow_label(), None | Some(0x54321)));
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+
+        s.reset();
+        assert_eq!(s.traffic_class(), Some(0xb8));
+        assert_eq!(s.flow_label(), Some(0x54321));
+    }
+
+    // =========================================================================================//
+    // Tests for reassembly.
+    // =========================================================================================//
+
     #[test]
     fn test_out_of_order() {
         let mut s = socket_established();
@@ -6574,6 +9118,54 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn test_out_of_order_assembler_full() {
+        let mut s = socket_established();
+        s.set_assembler_capacity(1);
+        assert_eq!(s.assembler_capacity(), 1);
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 3,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"def"[..],
+                ..SEND_TEMPL
+            },
+            Some(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                ..RECV_TEMPL
+            })
+        );
+        // A second hole does not fit, so the segment is dropped.
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 9,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"jkl"[..],
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.assembler_full_drops(), 1);
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abc"[..],
+                ..SEND_TEMPL
+            },
+            Some(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 6),
+                window_len: 58,
+                ..RECV_TEMPL
+            })
+        );
+        assert_eq!(s.assembler_full_drops(), 1);
+    }
+
     #[test]
     fn test_buffer_wraparound_rx() {
         let mut s = socket_established();
@@ -6607,6 +9199,20 @@ mod test {
         assert_eq!(data, &b"defghi"[..]);
     }
 
+    #[test]
+    fn test_non_blocking() {
+        let mut s = socket_established_with_buffer_sizes(6, 6);
+        s.set_non_blocking(true);
+        assert_eq!(s.recv_slice(&mut [0; 6]), Err(RecvError::WouldBlock));
+        assert_eq!(s.peek(6), Err(RecvError::WouldBlock));
+        assert_eq!(s.send_slice(b"abcdef"), Ok(6));
+        assert_eq!(s.send_slice(b"ghi"), Err(SendError::WouldBlock));
+
+        s.set_non_blocking(false);
+        assert_eq!(s.recv_slice(&mut [0; 6]), Ok(0));
+        assert_eq!(s.send_slice(b"ghi"), Ok(0));
+    }
+
     #[test]
     fn test_buffer_wraparound_tx() {
         let mut s = socket_established();
@@ -7018,6 +9624,140 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_delayed_ack_segments() {
+        let mut s = socket_established();
+        s.set_ack_delay(Some(ACK_DELAY_DEFAULT));
+        s.set_ack_delay_segments(3);
+        for (i, payload) in [b"abc", b"def"].iter().enumerate() {
+            send!(
+                s,
+                TcpRepr {
+                    seq_number: REMOTE_SEQ + 1 + i * 3,
+                    ack_number: Some(LOCAL_SEQ + 1),
+                    payload: &payload[..],
+                    ..SEND_TEMPL
+                }
+            );
+            recv_nothing!(s);
+        }
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 6,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"ghi"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv!(
+            s,
+            Ok(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 9),
+                window_len: 55,
+                ..RECV_TEMPL
+            })
+        );
+    }
+
+    #[test]
+    fn test_delayed_ack_coalesced_segment() {
+        let mut s = socket_established_with_buffer_sizes(64, 4096);
+        s.set_ack_delay(Some(ACK_DELAY_DEFAULT));
+        // A segment coalesced by the device from two full-sized ones counts as two.
+        let payload = vec![0; 2 * BASE_MSS as usize];
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &payload,
+                ..SEND_TEMPL
+            }
+        );
+        recv(&mut s, Instant::from_millis(0), |result| {
+            assert_eq!(
+                result.unwrap().ack_number,
+                Some(REMOTE_SEQ + 1 + payload.len())
+            )
+        });
+    }
+
+    #[test]
+    fn test_initial_quick_acks() {
+        let mut s = socket_syn_received();
+        s.set_ack_delay(Some(ACK_DELAY_DEFAULT));
+        s.set_initial_quick_acks(1);
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: Some(REMOTE_SEQ + 1),
+                max_seg_size: Some(BASE_MSS),
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abc"[..],
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
+        // The first segment is acknowledged right away...
+        recv!(
+            s,
+            Ok(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 3),
+                window_len: 61,
+                ..RECV_TEMPL
+            })
+        );
+        // ...but the next one is delayed as usual.
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1 + 3,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"def"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv_nothing!(s);
+    }
+
+    #[test]
+    fn test_quick_ack_flushes_delayed_ack() {
+        let mut s = socket_established();
+        s.set_ack_delay(Some(ACK_DELAY_DEFAULT));
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                payload: &b"abc"[..],
+                ..SEND_TEMPL
+            }
+        );
+        recv_nothing!(s);
+        s.quick_ack(1);
+        recv!(
+            s,
+            Ok(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1 + 3),
+                window_len: 61,
+                ..RECV_TEMPL
+            })
+        );
+    }
+
     // =========================================================================================//
     // Tests for Nagle's Algorithm
     // =========================================================================================//
@@ -7151,6 +9891,12 @@ mod test {
             next_header: IpProtocol::Tcp,
             payload_len: tcp_repr.buffer_len(),
             hop_limit: 64,
+            #[cfg(feature = "proto-ipv4")]
+            dscp: 0,
+            #[cfg(not(feature = "proto-ipv4"))]
+            traffic_class: 0,
+            #[cfg(not(feature = "proto-ipv4"))]
+            flow_label: 0,
         });
         assert!(s.socket.accepts(&mut s.cx, &ip_repr, &tcp_repr));
 
@@ -7160,6 +9906,12 @@ mod test {
             next_header: IpProtocol::Tcp,
             payload_len: tcp_repr.buffer_len(),
             hop_limit: 64,
+            #[cfg(feature = "proto-ipv4")]
+            dscp: 0,
+            #[cfg(not(feature = "proto-ipv4"))]
+            traffic_class: 0,
+            #[cfg(not(feature = "proto-ipv4"))]
+            flow_label: 0,
         });
         assert!(!s.socket.accepts(&mut s.cx, &ip_repr_wrong_src, &tcp_repr));
 
@@ -7169,10 +9921,231 @@ mod test {
             next_header: IpProtocol::Tcp,
             payload_len: tcp_repr.buffer_len(),
             hop_limit: 64,
+            #[cfg(feature = "proto-ipv4")]
+            dscp: 0,
+            #[cfg(not(feature = "proto-ipv4"))]
+            traffic_class: 0,
+            #[cfg(not(feature = "proto-ipv4"))]
+            flow_label: 0,
         });
         assert!(!s.socket.accepts(&mut s.cx, &ip_repr_wrong_dst, &tcp_repr));
     }
 
+    // =========================================================================================//
+    // Tests for wakers
+    // =========================================================================================//
+
+    #[cfg(feature = "async")]
+    mod wakers {
+        use super::*;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::task::Wake;
+
+        #[derive(Default)]
+        struct CountingWaker(AtomicUsize);
+
+        impl CountingWaker {
+            fn count(&self) -> usize {
+                self.0.load(Ordering::SeqCst)
+            }
+        }
+
+        impl Wake for CountingWaker {
+            fn wake(self: Arc<Self>) {
+                self.wake_by_ref()
+            }
+
+            fn wake_by_ref(self: &Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        fn counting_waker() -> (Arc<CountingWaker>, Waker) {
+            let counter = Arc::new(CountingWaker::default());
+            let waker = Waker::from(counter.clone());
+            (counter, waker)
+        }
+
+        #[test]
+        fn test_connect_waker_on_established() {
+            let mut s = socket_syn_sent();
+            let (counter, waker) = counting_waker();
+            s.register_connect_waker(&waker);
+            send!(
+                s,
+                TcpRepr {
+                    control: TcpControl::Syn,
+                    seq_number: REMOTE_SEQ,
+                    ack_number: Some(LOCAL_SEQ + 1),
+                    max_seg_size: Some(BASE_MSS - 80),
+                    window_scale: Some(0),
+                    ..SEND_TEMPL
+                }
+            );
+            assert_eq!(s.state, State::Established);
+            assert_eq!(counter.count(), 1);
+        }
+
+        #[test]
+        fn test_connect_waker_on_rst() {
+            let mut s = socket_syn_sent();
+            let (counter, waker) = counting_waker();
+            s.register_connect_waker(&waker);
+            send!(
+                s,
+                TcpRepr {
+                    control: TcpControl::Rst,
+                    seq_number: REMOTE_SEQ,
+                    ack_number: Some(LOCAL_SEQ + 1),
+                    ..SEND_TEMPL
+                }
+            );
+            assert_eq!(s.state, State::Closed);
+            assert_eq!(counter.count(), 1);
+        }
+
+        #[test]
+        fn test_connect_waker_on_simultaneous_open() {
+            let mut s = socket_syn_sent();
+            let (counter, waker) = counting_waker();
+            s.register_connect_waker(&waker);
+            recv!(
+                s,
+                [TcpRepr {
+                    control: TcpControl::Syn,
+                    seq_number: LOCAL_SEQ,
+                    ack_number: None,
+                    max_seg_size: Some(BASE_MSS),
+                    window_scale: Some(0),
+                    sack_permitted: true,
+                    ..RECV_TEMPL
+                }]
+            );
+            send!(
+                s,
+                TcpRepr {
+                    control: TcpControl::Syn,
+                    seq_number: REMOTE_SEQ,
+                    ack_number: None,
+                    max_seg_size: Some(BASE_MSS),
+                    window_scale: Some(0),
+                    ..SEND_TEMPL
+                }
+            );
+            assert_eq!(s.state, State::SynReceived);
+            assert_eq!(counter.count(), 0);
+
+            send!(
+                s,
+                TcpRepr {
+                    seq_number: REMOTE_SEQ + 1,
+                    ack_number: Some(LOCAL_SEQ + 1),
+                    ..SEND_TEMPL
+                }
+            );
+            assert_eq!(s.state, State::Established);
+            assert_eq!(counter.count(), 1);
+        }
+
+        #[test]
+        fn test_close_waker_on_last_ack() {
+            let mut s = socket_last_ack();
+            let (counter, waker) = counting_waker();
+            s.register_close_waker(&waker);
+            s.timer = Timer::new();
+            recv!(
+                s,
+                [TcpRepr {
+                    control: TcpControl::Fin,
+                    seq_number: LOCAL_SEQ + 1,
+                    ack_number: Some(REMOTE_SEQ + 1 + 1),
+                    ..RECV_TEMPL
+                }]
+            );
+            assert_eq!(counter.count(), 0);
+            send!(
+                s,
+                TcpRepr {
+                    seq_number: REMOTE_SEQ + 1 + 1,
+                    ack_number: Some(LOCAL_SEQ + 1 + 1),
+                    ..SEND_TEMPL
+                }
+            );
+            assert_eq!(s.state, State::Closed);
+            assert_eq!(counter.count(), 1);
+        }
+
+        #[test]
+        fn test_send_waker_low_water() {
+            let mut s = socket_established();
+            s.set_send_low_water(32);
+            s.send_slice(&[0xaa; 64]).unwrap();
+            recv!(
+                s,
+                [TcpRepr {
+                    seq_number: LOCAL_SEQ + 1,
+                    ack_number: Some(REMOTE_SEQ + 1),
+                    payload: &[0xaa; 64][..],
+                    ..RECV_TEMPL
+                }]
+            );
+
+            let (counter, waker) = counting_waker();
+            s.register_send_waker(&waker);
+            send!(
+                s,
+                TcpRepr {
+                    seq_number: REMOTE_SEQ + 1,
+                    ack_number: Some(LOCAL_SEQ + 1 + 16),
+                    ..SEND_TEMPL
+                }
+            );
+            assert_eq!(s.tx_buffer.window(), 16);
+            assert_eq!(counter.count(), 0);
+
+            send!(
+                s,
+                TcpRepr {
+                    seq_number: REMOTE_SEQ + 1,
+                    ack_number: Some(LOCAL_SEQ + 1 + 48),
+                    ..SEND_TEMPL
+                }
+            );
+            assert_eq!(s.tx_buffer.window(), 48);
+            assert_eq!(counter.count(), 1);
+        }
+
+        #[test]
+        fn test_recv_waker_high_water() {
+            let mut s = socket_established();
+            s.set_recv_high_water(32);
+            let (counter, waker) = counting_waker();
+            s.register_recv_waker(&waker);
+            send!(
+                s,
+                TcpRepr {
+                    seq_number: REMOTE_SEQ + 1,
+                    ack_number: Some(LOCAL_SEQ + 1),
+                    payload: &[0xaa; 16][..],
+                    ..SEND_TEMPL
+                }
+            );
+            assert_eq!(counter.count(), 0);
+
+            send!(
+                s,
+                TcpRepr {
+                    seq_number: REMOTE_SEQ + 1 + 16,
+                    ack_number: Some(LOCAL_SEQ + 1),
+                    payload: &[0xaa; 16][..],
+                    ..SEND_TEMPL
+                }
+            );
+            assert_eq!(counter.count(), 1);
+        }
+    }
+
     // =========================================================================================//
     // Timer tests
     // =========================================================================================//
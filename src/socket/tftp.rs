@@ -0,0 +1,1119 @@
+#[cfg(feature = "async")]
+use core::task::Waker;
+
+use heapless::String;
+
+use crate::iface::Context;
+use crate::socket::PollAt;
+#[cfg(feature = "async")]
+use crate::socket::WakerRegistration;
+use crate::storage::RingBuffer;
+use crate::time::{Duration, Instant};
+use crate::wire::{
+    IpAddress, IpEndpoint, IpProtocol, IpRepr, TftpErrorCode, TftpMode, TftpOpcode, TftpPacket,
+    TftpRepr, TftpTransferOptions, UdpRepr, TFTP_DEFAULT_BLOCK_SIZE, TFTP_MIN_BLOCK_SIZE,
+    TFTP_SERVER_PORT,
+};
+
+/// The longest file name of a request.
+pub const MAX_FILENAME_LEN: usize = 128;
+
+/// The largest block size the socket negotiates, so that a block fits in an IPv4 packet
+/// of 1500 octets.
+pub const MAX_BLOCK_SIZE: u16 = 1468;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1_000);
+const MAX_RETRANSMISSIONS: u8 = 5;
+const DEFAULT_HOP_LIMIT: u8 = 64;
+
+/// A TFTP ring buffer.
+pub type SocketBuffer<'a> = RingBuffer<'a, u8>;
+
+/// Error returned by [`Socket::start_read`], [`Socket::start_write`], [`Socket::listen`],
+/// [`Socket::accept`] and [`Socket::reject`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StartError {
+    InvalidState,
+    Unaddressable,
+    FilenameTooLong,
+}
+
+/// Error returned by [`Socket::send_slice`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SendError {
+    InvalidState,
+}
+
+/// Error returned by [`Socket::recv_slice`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RecvError {
+    InvalidState,
+    Finished,
+}
+
+/// The kind of a TFTP request.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RequestKind {
+    /// The client reads a file from the server.
+    Read,
+    /// The client writes a file to the server.
+    Write,
+}
+
+/// A request received by a listening socket.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Request<'a> {
+    pub kind: RequestKind,
+    pub filename: &'a str,
+    pub mode: TftpMode,
+    /// The endpoint of the client.
+    pub endpoint: IpEndpoint,
+}
+
+/// The state of a TFTP socket.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum State {
+    /// No transfer is in progress.
+    Closed,
+    /// The socket waits for a request on the TFTP server port.
+    Listening,
+    /// A request was received, and must be accepted or rejected.
+    Requested,
+    /// The file is being received.
+    Receiving,
+    /// The file is being sent.
+    Sending,
+    /// The whole file was transferred.
+    Finished,
+    /// The transfer failed, see [`Socket::failure`].
+    Failed,
+}
+
+/// The reason a transfer failed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Failure {
+    /// The peer stopped answering.
+    TimedOut,
+    /// The peer sent an error.
+    Peer(TftpErrorCode),
+    /// The peer did not follow the protocol, or acknowledged unexpected options.
+    Protocol,
+}
+
+/// A message the socket sends, and retransmits until it gets a reply.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Message {
+    Request,
+    OptionAck,
+    Ack,
+    Data,
+    Error(TftpErrorCode),
+}
+
+/// A Trivial File Transfer Protocol socket.
+///
+/// The socket transfers a single file at a time, as described in [RFC 1350], either as a
+/// client with [`start_read`] and [`start_write`], or as a server with [`listen`]. The
+/// block size ([RFC 2348]) and timeout interval ([RFC 2349]) options are negotiated.
+///
+/// The file contents go through the receive and transmit buffers. The receive buffer
+/// should hold a few blocks, since blocks that do not fit are dropped and retransmitted
+/// by the peer after its timeout. The transmit buffer must hold at least a block, which
+/// is kept until it is acknowledged. Mode conversions, such as the end of lines of the
+/// `netascii` mode, are left to the application.
+///
+/// [RFC 1350]: https://tools.ietf.org/html/rfc1350
+/// [RFC 2348]: https://tools.ietf.org/html/rfc2348
+/// [RFC 2349]: https://tools.ietf.org/html/rfc2349
+/// [`start_read`]: #method.start_read
+/// [`start_write`]: #method.start_write
+/// [`listen`]: #method.listen
+#[derive(Debug)]
+pub struct Socket<'a> {
+    state: State,
+    failure: Option<Failure>,
+    rx_buffer: SocketBuffer<'a>,
+    tx_buffer: SocketBuffer<'a>,
+    /// The transfer identifier, i.e. the port, of the socket.
+    local_port: u16,
+    /// The peer of the transfer. A client only learns its port from the first reply.
+    endpoint: Option<IpEndpoint>,
+    endpoint_locked: bool,
+    server: bool,
+    kind: RequestKind,
+    filename: String<MAX_FILENAME_LEN>,
+    mode: TftpMode,
+    /// The options requested by the client, and then acknowledged by the server.
+    options: TftpTransferOptions,
+    block_size: u16,
+    timeout: Option<u8>,
+    /// The block size and retransmission timeout of the current transfer.
+    transfer_block_size: u16,
+    retransmit_timeout: Duration,
+    /// The number of the last block received, or sent.
+    block: u16,
+    /// The length of the last block sent, until it is acknowledged.
+    in_flight: Option<u16>,
+    closing: bool,
+    outgoing: Option<Message>,
+    retransmit_at: Instant,
+    retransmissions: u8,
+    hop_limit: Option<u8>,
+    #[cfg(feature = "async")]
+    rx_waker: WakerRegistration,
+    #[cfg(feature = "async")]
+    tx_waker: WakerRegistration,
+    #[cfg(feature = "async")]
+    state_waker: WakerRegistration,
+}
+
+impl<'a> Socket<'a> {
+    /// Create a TFTP socket with the given buffers.
+    pub fn new<T>(rx_buffer: T, tx_buffer: T) -> Socket<'a>
+    where
+        T: Into<SocketBuffer<'a>>,
+    {
+        Socket {
+            state: State::Closed,
+            failure: None,
+            rx_buffer: rx_buffer.into(),
+            tx_buffer: tx_buffer.into(),
+            local_port: 0,
+            endpoint: None,
+            endpoint_locked: false,
+            server: false,
+            kind: RequestKind::Read,
+            filename: String::new(),
+            mode: TftpMode::Octet,
+            options: TftpTransferOptions::default(),
+            block_size: TFTP_DEFAULT_BLOCK_SIZE,
+            timeout: None,
+            transfer_block_size: TFTP_DEFAULT_BLOCK_SIZE,
+            retransmit_timeout: DEFAULT_TIMEOUT,
+            block: 0,
+            in_flight: None,
+            closing: false,
+            outgoing: None,
+            retransmit_at: Instant::ZERO,
+            retransmissions: 0,
+            hop_limit: None,
+            #[cfg(feature = "async")]
+            rx_waker: WakerRegistration::new(),
+            #[cfg(feature = "async")]
+            tx_waker: WakerRegistration::new(),
+            #[cfg(feature = "async")]
+            state_waker: WakerRegistration::new(),
+        }
+    }
+
+    /// Register a waker for receive operations.
+    ///
+    /// The waker is woken on state changes that might affect the return value
+    /// of `recv_slice` method calls, such as receiving data, or the transfer finishing.
+    #[cfg(feature = "async")]
+    pub fn register_recv_waker(&mut self, waker: &Waker) {
+        self.rx_waker.register(waker)
+    }
+
+    /// Register a waker for send operations.
+    ///
+    /// The waker is woken on state changes that might affect the return value
+    /// of `send_slice` method calls, such as a block being acknowledged.
+    #[cfg(feature = "async")]
+    pub fn register_send_waker(&mut self, waker: &Waker) {
+        self.tx_waker.register(waker)
+    }
+
+    /// Register a waker for state change.
+    ///
+    /// The waker is woken on state changes, such as a request being received.
+    #[cfg(feature = "async")]
+    pub fn register_state_waker(&mut self, waker: &Waker) {
+        self.state_waker.register(waker)
+    }
+
+    /// Clears all the wakers that were assigned to this socket
+    #[cfg(feature = "async")]
+    pub fn clear_wakers(&mut self) {
+        self.rx_waker.clear();
+        self.tx_waker.clear();
+        self.state_waker.clear();
+    }
+
+    /// Return the state of the socket.
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Return the reason the last transfer failed, if it did.
+    pub fn failure(&self) -> Option<Failure> {
+        self.failure
+    }
+
+    /// Return the block size requested by the socket as a client, or the largest one it
+    /// accepts as a server.
+    ///
+    /// See also the [set_block_size](#method.set_block_size) method.
+    pub fn block_size(&self) -> u16 {
+        self.block_size
+    }
+
+    /// Set the block size requested by the socket as a client, or the largest one it
+    /// accepts as a server. The default is 512 octets, which does not need a negotiation.
+    ///
+    /// The setting applies to the next transfer.
+    ///
+    /// # Panics
+    /// This function panics if the block size is smaller than 8 octets or larger than
+    /// [MAX_BLOCK_SIZE].
+    pub fn set_block_size(&mut self, block_size: u16) {
+        if !(TFTP_MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size) {
+            panic!("unsupported block size {}", block_size)
+        }
+        self.block_size = block_size
+    }
+
+    /// Return the timeout interval, in seconds, requested by the socket as a client.
+    ///
+    /// See also the [set_timeout](#method.set_timeout) method.
+    pub fn timeout(&self) -> Option<u8> {
+        self.timeout
+    }
+
+    /// Set the timeout interval, in seconds, requested by the socket as a client, or
+    /// `None` to not request one. Unless the server acknowledges a timeout interval,
+    /// packets are retransmitted after one second.
+    ///
+    /// The setting applies to the next transfer.
+    ///
+    /// # Panics
+    /// This function panics if the timeout interval is zero.
+    pub fn set_timeout(&mut self, timeout: Option<u8>) {
+        if let Some(0) = timeout {
+            panic!("the timeout interval must not be zero")
+        }
+        self.timeout = timeout
+    }
+
+    /// Return the time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
+    ///
+    /// See also the [set_hop_limit](#method.set_hop_limit) method
+    pub fn hop_limit(&self) -> Option<u8> {
+        self.hop_limit
+    }
+
+    /// Set the time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
+    ///
+    /// A socket without an explicitly set hop limit value uses the default [IANA recommended]
+    /// value (64).
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a hop limit value of 0 is given. See [RFC 1122 § 3.2.1.7].
+    ///
+    /// [IANA recommended]: https://www.iana.org/assignments/ip-parameters/ip-parameters.xhtml
+    /// [RFC 1122 § 3.2.1.7]: https://tools.ietf.org/html/rfc1122#section-3.2.1.7
+    pub fn set_hop_limit(&mut self, hop_limit: Option<u8>) {
+        // A host MUST NOT send a datagram with a hop limit value of 0
+        if let Some(0) = hop_limit {
+            panic!("the time-to-live value of a packet must not be zero")
+        }
+
+        self.hop_limit = hop_limit
+    }
+
+    /// Start reading the file `filename` from a server, in the `octet` mode.
+    pub fn start_read(
+        &mut self,
+        cx: &mut Context,
+        server: IpAddress,
+        filename: &str,
+    ) -> Result<(), StartError> {
+        self.start(cx, server, filename, RequestKind::Read)
+    }
+
+    /// Start writing the file `filename` to a server, in the `octet` mode.
+    ///
+    /// The contents are sent with [send_slice](#method.send_slice), and their end is
+    /// marked with [close](#method.close).
+    pub fn start_write(
+        &mut self,
+        cx: &mut Context,
+        server: IpAddress,
+        filename: &str,
+    ) -> Result<(), StartError> {
+        self.start(cx, server, filename, RequestKind::Write)
+    }
+
+    fn start(
+        &mut self,
+        cx: &mut Context,
+        server: IpAddress,
+        filename: &str,
+        kind: RequestKind,
+    ) -> Result<(), StartError> {
+        if self.is_active() {
+            return Err(StartError::InvalidState);
+        }
+        if !server.is_unicast() {
+            return Err(StartError::Unaddressable);
+        }
+        let mut name = String::new();
+        name.push_str(filename)
+            .map_err(|_| StartError::FilenameTooLong)?;
+
+        self.reset();
+        self.local_port = cx.rand().rand_source_port();
+        self.endpoint = Some(IpEndpoint::new(server, TFTP_SERVER_PORT));
+        self.server = false;
+        self.kind = kind;
+        self.filename = name;
+        self.mode = TftpMode::Octet;
+        self.options = TftpTransferOptions {
+            block_size: Some(self.block_size).filter(|&size| size != TFTP_DEFAULT_BLOCK_SIZE),
+            timeout: self.timeout,
+        };
+        self.send(Message::Request, cx.now());
+        self.set_state(match kind {
+            RequestKind::Read => State::Receiving,
+            RequestKind::Write => State::Sending,
+        });
+        Ok(())
+    }
+
+    /// Wait for a request on the TFTP server port.
+    ///
+    /// Once a request is received, the socket is in the `Requested` state and the request
+    /// must be accepted or rejected.
+    pub fn listen(&mut self) -> Result<(), StartError> {
+        if self.is_active() {
+            return Err(StartError::InvalidState);
+        }
+        self.reset();
+        self.server = true;
+        self.set_state(State::Listening);
+        Ok(())
+    }
+
+    /// Return the request received by a listening socket, until it is accepted or
+    /// rejected.
+    pub fn request(&self) -> Option<Request<'_>> {
+        if self.state != State::Requested {
+            return None;
+        }
+        Some(Request {
+            kind: self.kind,
+            filename: &self.filename,
+            mode: self.mode,
+            endpoint: self.endpoint?,
+        })
+    }
+
+    /// Accept the received request, and start the transfer.
+    pub fn accept(&mut self) -> Result<(), StartError> {
+        if self.state != State::Requested {
+            return Err(StartError::InvalidState);
+        }
+
+        let requested = self.options;
+        if let Some(block_size) = requested.block_size {
+            self.transfer_block_size = block_size.min(self.block_size);
+        }
+        if let Some(timeout) = requested.timeout {
+            self.retransmit_timeout = Duration::from_secs(timeout as u64);
+        }
+        self.options = TftpTransferOptions {
+            block_size: requested.block_size.map(|_| self.transfer_block_size),
+            timeout: requested.timeout,
+        };
+
+        if self.options != TftpTransferOptions::default() {
+            self.send(Message::OptionAck, Instant::ZERO);
+        } else if self.kind == RequestKind::Write {
+            self.send(Message::Ack, Instant::ZERO);
+        }
+        self.set_state(match self.kind {
+            RequestKind::Read => State::Sending,
+            RequestKind::Write => State::Receiving,
+        });
+        Ok(())
+    }
+
+    /// Reject the received request with an error, and wait for another one.
+    pub fn reject(&mut self, code: TftpErrorCode) -> Result<(), StartError> {
+        if self.state != State::Requested {
+            return Err(StartError::InvalidState);
+        }
+        self.send(Message::Error(code), Instant::ZERO);
+        self.set_state(State::Listening);
+        Ok(())
+    }
+
+    /// Abort the transfer, sending an error to the peer, or stop listening.
+    pub fn abort(&mut self) {
+        let active = matches!(
+            self.state,
+            State::Requested | State::Receiving | State::Sending
+        );
+        self.reset();
+        if active {
+            self.send(Message::Error(TftpErrorCode::Undefined), Instant::ZERO);
+        }
+        self.set_state(State::Closed);
+    }
+
+    /// Mark the end of the file being sent.
+    ///
+    /// The transfer finishes once the data in the transmit buffer is acknowledged.
+    pub fn close(&mut self) {
+        if self.state == State::Sending {
+            self.closing = true;
+        }
+    }
+
+    /// Check whether data can be enqueued with [send_slice](#method.send_slice).
+    pub fn can_send(&self) -> bool {
+        self.state == State::Sending && !self.closing && !self.tx_buffer.is_full()
+    }
+
+    /// Check whether received data can be dequeued with [recv_slice](#method.recv_slice).
+    pub fn can_recv(&self) -> bool {
+        !self.rx_buffer.is_empty()
+    }
+
+    /// Enqueue data of the file being sent, and return the amount of octets enqueued.
+    pub fn send_slice(&mut self, data: &[u8]) -> Result<usize, SendError> {
+        if self.state != State::Sending || self.closing {
+            return Err(SendError::InvalidState);
+        }
+        Ok(self.tx_buffer.enqueue_slice(data))
+    }
+
+    /// Dequeue data of the file being received, and return the amount of octets dequeued.
+    ///
+    /// Returns `Err(RecvError::Finished)` once the whole file was dequeued.
+    pub fn recv_slice(&mut self, data: &mut [u8]) -> Result<usize, RecvError> {
+        let receiving = match self.state {
+            State::Receiving => true,
+            State::Finished => self.receiving(),
+            _ => false,
+        };
+        if !receiving {
+            return Err(RecvError::InvalidState);
+        }
+        if self.state == State::Finished && self.rx_buffer.is_empty() {
+            return Err(RecvError::Finished);
+        }
+        Ok(self.rx_buffer.dequeue_slice(data))
+    }
+
+    fn is_active(&self) -> bool {
+        matches!(
+            self.state,
+            State::Listening | State::Requested | State::Receiving | State::Sending
+        )
+    }
+
+    /// Check whether the file is sent to the socket, rather than by it.
+    fn receiving(&self) -> bool {
+        (self.kind == RequestKind::Write) == self.server
+    }
+
+    fn reset(&mut self) {
+        self.failure = None;
+        self.rx_buffer.clear();
+        self.tx_buffer.clear();
+        self.endpoint_locked = false;
+        self.options = TftpTransferOptions::default();
+        self.transfer_block_size = TFTP_DEFAULT_BLOCK_SIZE;
+        self.retransmit_timeout = DEFAULT_TIMEOUT;
+        self.block = 0;
+        self.in_flight = None;
+        self.closing = false;
+        self.outgoing = None;
+    }
+
+    fn set_state(&mut self, state: State) {
+        if self.state != state {
+            net_trace!("tftp: state {:?} -> {:?}", self.state, state);
+            self.state = state;
+
+            #[cfg(feature = "async")]
+            {
+                self.rx_waker.wake_all();
+                self.tx_waker.wake_all();
+                self.state_waker.wake_all();
+            }
+        }
+    }
+
+    fn fail(&mut self, failure: Failure) {
+        self.failure = Some(failure);
+        self.set_state(State::Failed);
+    }
+
+    fn send(&mut self, message: Message, at: Instant) {
+        self.outgoing = Some(message);
+        self.retransmit_at = at;
+        self.retransmissions = 0;
+    }
+
+    /// Apply the options acknowledged by a server, which may only decrease the requested
+    /// block size.
+    fn negotiate(&mut self, options: TftpTransferOptions) -> bool {
+        match (self.options.block_size, options.block_size) {
+            (Some(requested), Some(block_size)) if block_size <= requested => {
+                self.transfer_block_size = block_size
+            }
+            (_, None) => self.transfer_block_size = TFTP_DEFAULT_BLOCK_SIZE,
+            _ => return false,
+        }
+        match (self.options.timeout, options.timeout) {
+            (Some(requested), Some(timeout)) if timeout == requested => {
+                self.retransmit_timeout = Duration::from_secs(timeout as u64)
+            }
+            (_, None) => self.retransmit_timeout = DEFAULT_TIMEOUT,
+            _ => return false,
+        }
+        true
+    }
+
+    pub(crate) fn accepts(&self, ip_repr: &IpRepr, udp_repr: &UdpRepr) -> bool {
+        match (self.state, self.endpoint) {
+            (State::Closed | State::Failed, _) => false,
+            (State::Listening, _) => udp_repr.dst_port == TFTP_SERVER_PORT,
+            // A server also swallows the retransmissions of the request it is serving.
+            (_, Some(endpoint)) => {
+                ip_repr.src_addr() == endpoint.addr
+                    && (!self.endpoint_locked || udp_repr.src_port == endpoint.port)
+                    && (udp_repr.dst_port == self.local_port
+                        || (self.server && udp_repr.dst_port == TFTP_SERVER_PORT))
+            }
+            (_, None) => false,
+        }
+    }
+
+    pub(crate) fn process(
+        &mut self,
+        cx: &mut Context,
+        ip_repr: &IpRepr,
+        udp_repr: &UdpRepr,
+        payload: &[u8],
+    ) {
+        let repr = match TftpPacket::new_checked(payload).and_then(|p| TftpRepr::parse(&p)) {
+            Ok(repr) => repr,
+            Err(_) => {
+                net_debug!("tftp: invalid packet");
+                return;
+            }
+        };
+        let endpoint = IpEndpoint::new(ip_repr.src_addr(), udp_repr.src_port);
+        net_trace!("tftp: received {} from {}", repr, endpoint);
+
+        if self.state == State::Listening {
+            // Wait until the rejection of the previous request is sent.
+            if self.outgoing.is_none() {
+                self.process_request(cx, endpoint, repr);
+            }
+            return;
+        }
+        if udp_repr.dst_port != self.local_port {
+            net_trace!("tftp: ignoring retransmitted request");
+            return;
+        }
+        if !self.endpoint_locked {
+            self.endpoint = Some(endpoint);
+            self.endpoint_locked = true;
+        }
+
+        let requesting = self.outgoing == Some(Message::Request);
+        match (self.state, repr) {
+            (State::Finished, TftpRepr::Data { block_num, .. }) if self.receiving() => {
+                if block_num == self.block {
+                    self.send(Message::Ack, cx.now());
+                }
+            }
+            (State::Finished, _) => (),
+            (_, TftpRepr::Error { code, message }) => {
+                net_debug!("tftp: peer error ({}) {}", code, message);
+                self.outgoing = None;
+                self.fail(Failure::Peer(code));
+            }
+            (State::Receiving | State::Sending, TftpRepr::OptionAck { options }) if requesting => {
+                if !self.negotiate(options) {
+                    net_debug!("tftp: unexpected options acknowledged");
+                    self.send(Message::Error(TftpErrorCode::OptionNegotiation), cx.now());
+                    self.fail(Failure::Protocol);
+                } else if self.state == State::Receiving {
+                    self.send(Message::Ack, cx.now());
+                } else {
+                    self.outgoing = None;
+                }
+            }
+            (State::Receiving, TftpRepr::Data { block_num, data }) => {
+                if requesting {
+                    // The server ignored the options.
+                    self.negotiate(TftpTransferOptions::default());
+                    self.outgoing = None;
+                }
+                self.process_data(cx, block_num, data);
+            }
+            (State::Sending, TftpRepr::Ack { block_num }) => {
+                if requesting || self.outgoing == Some(Message::OptionAck) {
+                    if block_num == 0 {
+                        if requesting {
+                            self.negotiate(TftpTransferOptions::default());
+                        }
+                        self.outgoing = None;
+                    }
+                } else if let Some(len) = self.in_flight.filter(|_| block_num == self.block) {
+                    self.tx_buffer.dequeue_allocated(len as usize);
+                    self.in_flight = None;
+                    self.outgoing = None;
+                    #[cfg(feature = "async")]
+                    self.tx_waker.wake_all();
+                    if len < self.transfer_block_size {
+                        self.set_state(State::Finished);
+                    }
+                }
+                // Duplicate acknowledgements are ignored, so that blocks are not sent
+                // twice over (the "Sorcerer's Apprentice" bug).
+            }
+            (_, TftpRepr::OptionAck { .. } | TftpRepr::Ack { .. }) => {
+                net_debug!("tftp: ignoring unexpected {}", repr);
+            }
+            _ => {
+                net_debug!("tftp: illegal {}", repr);
+                self.send(Message::Error(TftpErrorCode::IllegalOperation), cx.now());
+                self.fail(Failure::Protocol);
+            }
+        }
+    }
+
+    fn process_request(&mut self, cx: &mut Context, endpoint: IpEndpoint, repr: TftpRepr) {
+        let (kind, filename, mode, options) = match repr {
+            TftpRepr::ReadRequest {
+                filename,
+                mode,
+                options,
+            } => (RequestKind::Read, filename, mode, options),
+            TftpRepr::WriteRequest {
+                filename,
+                mode,
+                options,
+            } => (RequestKind::Write, filename, mode, options),
+            _ => {
+                net_debug!("tftp: ignoring {} while listening", repr);
+                return;
+            }
+        };
+        let mut name = String::new();
+        if name.push_str(filename).is_err() {
+            net_debug!("tftp: file name too long");
+            return;
+        }
+
+        self.reset();
+        self.local_port = cx.rand().rand_source_port();
+        self.endpoint = Some(endpoint);
+        self.endpoint_locked = true;
+        self.kind = kind;
+        self.filename = name;
+        self.mode = mode;
+        self.options = options;
+        self.set_state(State::Requested);
+    }
+
+    fn process_data(&mut self, cx: &mut Context, block_num: u16, data: &[u8]) {
+        if block_num == self.block.wrapping_add(1) {
+            if data.len() > self.transfer_block_size as usize {
+                net_debug!("tftp: block {} is too long", block_num);
+                self.send(Message::Error(TftpErrorCode::IllegalOperation), cx.now());
+                self.fail(Failure::Protocol);
+                return;
+            }
+            if self.rx_buffer.window() < data.len() {
+                net_debug!("tftp: rx buffer full, dropping block {}", block_num);
+                return;
+            }
+            let _ = self.rx_buffer.enqueue_slice(data);
+            self.block = block_num;
+            #[cfg(feature = "async")]
+            self.rx_waker.wake_all();
+            if data.len() < self.transfer_block_size as usize {
+                self.set_state(State::Finished);
+            }
+        } else if block_num != self.block {
+            net_debug!("tftp: ignoring block {}", block_num);
+            return;
+        }
+        // Acknowledge the new block, or the previous one again if its acknowledgement was
+        // lost.
+        self.send(Message::Ack, cx.now());
+    }
+
+    pub(crate) fn dispatch<F, E>(&mut self, cx: &mut Context, emit: F) -> Result<(), E>
+    where
+        F: FnOnce(&mut Context, (IpRepr, UdpRepr, &[u8])) -> Result<(), E>,
+    {
+        if self.state == State::Sending && self.outgoing.is_none() && self.in_flight.is_none() {
+            let len = self.tx_buffer.len().min(self.transfer_block_size as usize);
+            if len == self.transfer_block_size as usize || self.closing {
+                self.block = self.block.wrapping_add(1);
+                self.in_flight = Some(len as u16);
+                self.send(Message::Data, cx.now());
+            }
+        }
+
+        let (message, endpoint) = match (self.outgoing, self.endpoint) {
+            (Some(message), Some(endpoint)) if self.retransmit_at <= cx.now() => {
+                (message, endpoint)
+            }
+            _ => return Ok(()),
+        };
+        if self.retransmissions > MAX_RETRANSMISSIONS {
+            net_debug!("tftp: {} is not answering", endpoint);
+            self.outgoing = None;
+            self.fail(Failure::TimedOut);
+            return Ok(());
+        }
+        self.retransmit_at = cx.now() + self.retransmit_timeout;
+        self.retransmissions += 1;
+
+        let src_addr = match cx.get_source_address(endpoint.addr) {
+            Some(src_addr) => src_addr,
+            None => {
+                net_debug!("tftp: no source address for {}", endpoint.addr);
+                return Ok(());
+            }
+        };
+
+        let mut buffer = [0; 4 + MAX_BLOCK_SIZE as usize];
+        let len = if message == Message::Data {
+            let len = self.in_flight.unwrap_or(0) as usize;
+            let mut packet = TftpPacket::new_unchecked(&mut buffer[..]);
+            packet.set_opcode(TftpOpcode::Data);
+            packet.set_block_num(self.block);
+            let len = self
+                .tx_buffer
+                .read_allocated(0, &mut packet.payload_mut()[..len]);
+            packet.header_len() + len
+        } else {
+            let repr = match message {
+                Message::Request => match self.kind {
+                    RequestKind::Read => TftpRepr::ReadRequest {
+                        filename: &self.filename,
+                        mode: self.mode,
+                        options: self.options,
+                    },
+                    RequestKind::Write => TftpRepr::WriteRequest {
+                        filename: &self.filename,
+                        mode: self.mode,
+                        options: self.options,
+                    },
+                },
+                Message::OptionAck => TftpRepr::OptionAck {
+                    options: self.options,
+                },
+                Message::Error(code) => TftpRepr::Error { code, message: "" },
+                _ => TftpRepr::Ack {
+                    block_num: self.block,
+                },
+            };
+            repr.emit(&mut TftpPacket::new_unchecked(&mut buffer[..]));
+            repr.buffer_len()
+        };
+
+        let udp_repr = UdpRepr {
+            src_port: self.local_port,
+            dst_port: endpoint.port,
+        };
+        let ip_repr = IpRepr::new(
+            src_addr,
+            endpoint.addr,
+            IpProtocol::Udp,
+            udp_repr.header_len() + len,
+            self.hop_limit.unwrap_or(DEFAULT_HOP_LIMIT),
+        );
+        net_trace!("tftp: sending {:?} to {}", message, endpoint);
+        emit(cx, (ip_repr, udp_repr, &buffer[..len]))?;
+
+        // Errors, and the final acknowledgement, are not retransmitted.
+        if matches!(message, Message::Error(_)) || self.state == State::Finished {
+            self.outgoing = None;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn poll_at(&self, _cx: &Context) -> PollAt {
+        match self.outgoing {
+            Some(_) => PollAt::Time(self.retransmit_at),
+            None if self.state == State::Sending
+                && self.in_flight.is_none()
+                && (self.tx_buffer.len() >= self.transfer_block_size as usize || self.closing) =>
+            {
+                PollAt::Now
+            }
+            None => PollAt::Ingress,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::wire::Ipv4Address;
+
+    const SERVER: IpAddress = IpAddress::Ipv4(Ipv4Address([192, 168, 1, 2]));
+    const SERVER_TID: u16 = 50000;
+
+    fn socket() -> Socket<'static> {
+        Socket::new(
+            SocketBuffer::new(vec![0; 4096]),
+            SocketBuffer::new(vec![0; 4096]),
+        )
+    }
+
+    fn send(s: &mut Socket, cx: &mut Context) -> Option<(UdpRepr, std::vec::Vec<u8>)> {
+        let mut sent = None;
+        s.dispatch(cx, |_, (_, udp_repr, payload)| {
+            sent = Some((udp_repr, payload.to_vec()));
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+        sent
+    }
+
+    fn parse(payload: &[u8]) -> TftpRepr<'_> {
+        TftpRepr::parse(&TftpPacket::new_checked(payload).unwrap()).unwrap()
+    }
+
+    fn recv(s: &mut Socket, cx: &mut Context, src_port: u16, dst_port: u16, repr: TftpRepr) {
+        let mut payload = vec![0; repr.buffer_len()];
+        repr.emit(&mut TftpPacket::new_unchecked(&mut payload[..]));
+        let ip_repr = IpRepr::new(
+            SERVER,
+            IpAddress::Ipv4(Ipv4Address([192, 168, 1, 1])),
+            IpProtocol::Udp,
+            0,
+            64,
+        );
+        let udp_repr = UdpRepr { src_port, dst_port };
+        assert!(s.accepts(&ip_repr, &udp_repr));
+        s.process(cx, &ip_repr, &udp_repr, &payload);
+    }
+
+    #[test]
+    fn test_client_read() {
+        let mut s = socket();
+        let mut cx = Context::mock();
+        s.set_block_size(1024);
+        s.start_read(&mut cx, SERVER, "boot.bin").unwrap();
+
+        let (udp_repr, _) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(udp_repr.dst_port, TFTP_SERVER_PORT);
+        let port = udp_repr.src_port;
+
+        // The server decreases the block size.
+        let options = TftpTransferOptions {
+            block_size: Some(16),
+            timeout: None,
+        };
+        recv(
+            &mut s,
+            &mut cx,
+            SERVER_TID,
+            port,
+            TftpRepr::OptionAck { options },
+        );
+        let (_, payload) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(parse(&payload), TftpRepr::Ack { block_num: 0 });
+
+        let data = [0x2a; 16];
+        recv(
+            &mut s,
+            &mut cx,
+            SERVER_TID,
+            port,
+            TftpRepr::Data {
+                block_num: 1,
+                data: &data,
+            },
+        );
+        let (_, payload) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(parse(&payload), TftpRepr::Ack { block_num: 1 });
+
+        // Another transfer identifier is refused.
+        let ip_repr = IpRepr::new(
+            SERVER,
+            IpAddress::Ipv4(Ipv4Address([192, 168, 1, 1])),
+            IpProtocol::Udp,
+            0,
+            64,
+        );
+        let udp_repr = UdpRepr {
+            src_port: SERVER_TID + 1,
+            dst_port: port,
+        };
+        assert!(!s.accepts(&ip_repr, &udp_repr));
+
+        recv(
+            &mut s,
+            &mut cx,
+            SERVER_TID,
+            port,
+            TftpRepr::Data {
+                block_num: 2,
+                data: &data[..4],
+            },
+        );
+        assert_eq!(s.state(), State::Finished);
+        let (_, payload) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(parse(&payload), TftpRepr::Ack { block_num: 2 });
+        assert_eq!(send(&mut s, &mut cx), None);
+
+        let mut buffer = [0; 64];
+        assert_eq!(s.recv_slice(&mut buffer), Ok(20));
+        assert_eq!(s.recv_slice(&mut buffer), Err(RecvError::Finished));
+    }
+
+    #[test]
+    fn test_client_write_timeout() {
+        let mut s = socket();
+        let mut cx = Context::mock();
+        s.start_write(&mut cx, SERVER, "log.txt").unwrap();
+        assert_eq!(s.send_slice(&[0x2a; 600]), Ok(600));
+        s.close();
+
+        let (udp_repr, _) = send(&mut s, &mut cx).unwrap();
+        let port = udp_repr.src_port;
+        recv(
+            &mut s,
+            &mut cx,
+            SERVER_TID,
+            port,
+            TftpRepr::Ack { block_num: 0 },
+        );
+
+        let (_, payload) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(&payload[..4], &[0, 3, 0, 1]);
+        assert_eq!(payload.len(), 4 + 512);
+
+        // The block is retransmitted until the retries run out.
+        for _ in 0..MAX_RETRANSMISSIONS {
+            assert_eq!(send(&mut s, &mut cx), None);
+            cx.set_now(cx.now() + DEFAULT_TIMEOUT);
+            let (_, retransmitted) = send(&mut s, &mut cx).unwrap();
+            assert_eq!(retransmitted, payload);
+        }
+        cx.set_now(cx.now() + DEFAULT_TIMEOUT);
+        assert_eq!(send(&mut s, &mut cx), None);
+        assert_eq!(s.state(), State::Failed);
+        assert_eq!(s.failure(), Some(Failure::TimedOut));
+    }
+
+    #[test]
+    fn test_server_read() {
+        let mut s = socket();
+        let mut cx = Context::mock();
+        s.listen().unwrap();
+
+        recv(
+            &mut s,
+            &mut cx,
+            SERVER_TID,
+            TFTP_SERVER_PORT,
+            TftpRepr::ReadRequest {
+                filename: "boot.bin",
+                mode: TftpMode::Octet,
+                options: TftpTransferOptions {
+                    block_size: Some(1024),
+                    timeout: Some(3),
+                },
+            },
+        );
+        assert_eq!(s.state(), State::Requested);
+        let request = s.request().unwrap();
+        assert_eq!(request.kind, RequestKind::Read);
+        assert_eq!(request.filename, "boot.bin");
+
+        s.accept().unwrap();
+        let (udp_repr, payload) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(udp_repr.dst_port, SERVER_TID);
+        let port = udp_repr.src_port;
+        assert_eq!(
+            parse(&payload),
+            TftpRepr::OptionAck {
+                options: TftpTransferOptions {
+                    block_size: Some(512),
+                    timeout: Some(3)
+                }
+            }
+        );
+
+        assert_eq!(s.send_slice(b"firmware"), Ok(8));
+        s.close();
+        assert_eq!(send(&mut s, &mut cx), None);
+        recv(
+            &mut s,
+            &mut cx,
+            SERVER_TID,
+            port,
+            TftpRepr::Ack { block_num: 0 },
+        );
+        let (_, payload) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(
+            parse(&payload),
+            TftpRepr::Data {
+                block_num: 1,
+                data: b"firmware"
+            }
+        );
+        recv(
+            &mut s,
+            &mut cx,
+            SERVER_TID,
+            port,
+            TftpRepr::Ack { block_num: 1 },
+        );
+        assert_eq!(s.state(), State::Finished);
+    }
+
+    #[test]
+    fn test_server_reject() {
+        let mut s = socket();
+        let mut cx = Context::mock();
+        s.listen().unwrap();
+
+        recv(
+            &mut s,
+            &mut cx,
+            SERVER_TID,
+            TFTP_SERVER_PORT,
+            TftpRepr::WriteRequest {
+                filename: "boot.bin",
+                mode: TftpMode::Octet,
+                options: TftpTransferOptions::default(),
+            },
+        );
+        s.reject(TftpErrorCode::AccessViolation).unwrap();
+        assert_eq!(s.state(), State::Listening);
+        let (_, payload) = send(&mut s, &mut cx).unwrap();
+        assert_eq!(
+            parse(&payload),
+            TftpRepr::Error {
+                code: TftpErrorCode::AccessViolation,
+                message: ""
+            }
+        );
+        assert_eq!(send(&mut s, &mut cx), None);
+    }
+}
@@ -0,0 +1,95 @@
+use heapless::Deque;
+
+use crate::time::Instant;
+
+/// Maximum number of transmit timestamps a single socket holds until they are read.
+pub(crate) const MAX_TX_TIMESTAMP_COUNT: usize = 8;
+
+/// The instant at which a packet enqueued on a socket was handed to the device.
+///
+/// Packets are numbered in the order they are enqueued, starting from the value returned
+/// by `tx_timestamp_id` on the socket when timestamping is enabled. Packets dropped
+/// instead of being sent, e.g. for lack of a route, have no timestamp.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TxTimestamp {
+    /// The number of the packet.
+    pub id: u32,
+    /// The instant at which the packet was sent.
+    pub timestamp: Instant,
+}
+
+/// The transmit timestamps recorded by a socket.
+///
+/// Packets leave the transmit buffer of a socket in the order they were enqueued,
+/// whether they are sent or dropped, so counting packets on both ends is enough to
+/// number them.
+#[derive(Debug)]
+pub(crate) struct TxTimestamps {
+    enabled: bool,
+    enqueued: u32,
+    dequeued: u32,
+    queue: Deque<TxTimestamp, MAX_TX_TIMESTAMP_COUNT>,
+}
+
+impl TxTimestamps {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            enqueued: 0,
+            dequeued: 0,
+            queue: Deque::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable timestamping. Disabling it discards the timestamps not read yet.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.queue.clear();
+        }
+    }
+
+    /// Return the number the next packet enqueued will have.
+    pub fn next_id(&self) -> u32 {
+        self.enqueued
+    }
+
+    /// Account for a packet enqueued in the transmit buffer.
+    pub fn enqueued(&mut self) {
+        self.enqueued = self.enqueued.wrapping_add(1);
+    }
+
+    /// Account for the packet at the front of the transmit buffer leaving it, and
+    /// timestamp it if it was sent. The oldest timestamp is discarded if too many
+    /// were not read.
+    pub fn dequeued(&mut self, sent_at: Option<Instant>) {
+        if let (true, Some(timestamp)) = (self.enabled, sent_at) {
+            if self.queue.is_full() {
+                self.queue.pop_front();
+            }
+            // NOTE(unwrap): there is room, see above.
+            self.queue
+                .push_back(TxTimestamp {
+                    id: self.dequeued,
+                    timestamp,
+                })
+                .unwrap();
+        }
+        self.dequeued = self.dequeued.wrapping_add(1);
+    }
+
+    /// Account for the transmit buffer being emptied.
+    pub fn reset(&mut self) {
+        self.dequeued = self.enqueued;
+        self.queue.clear();
+    }
+
+    pub fn pop(&mut self) -> Option<TxTimestamp> {
+        self.queue.pop_front()
+    }
+}
@@ -3,17 +3,67 @@ use core::cmp::min;
 use core::task::Waker;
 
 use crate::iface::Context;
-use crate::socket::PollAt;
 #[cfg(feature = "async")]
 use crate::socket::WakerRegistration;
+#[cfg(feature = "proto-igmp")]
+use crate::socket::{MembershipError, Memberships};
+use crate::socket::{PollAt, TxTimestamp, TxTimestamps};
 use crate::storage::Empty;
-use crate::wire::{IpEndpoint, IpListenEndpoint, IpProtocol, IpRepr, UdpRepr};
+use crate::time::Instant;
+#[cfg(feature = "proto-ipv6")]
+use crate::wire::Ipv6ExtHeaders;
+use crate::wire::{IpAddress, IpEndpoint, IpListenEndpoint, IpProtocol, IpRepr, UdpRepr};
+
+/// Metadata of a UDP packet held in a socket buffer.
+///
+/// Besides the remote endpoint, received packets carry the ancillary data that
+/// `IP_PKTINFO`/`IPV6_RECVPKTINFO`, `IP_RECVTTL`, `IPV6_RECVTCLASS` and `SO_TIMESTAMP`
+/// provide on other stacks.
+///
+/// For packets sent with [Socket::send_with_metadata], the hop limit, traffic class and
+/// flow label override the settings of the socket when they are not `None`. A unicast
+/// local address is used as the source address if the socket is not bound to an
+/// address, so that replies to the metadata of a received packet come from the address
+/// it was sent to. The timestamp is ignored, see [Socket::set_tx_timestamping] to record
+/// the instant at which packets are sent.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UdpMetadata {
+    /// The remote endpoint the packet was received from or is sent to.
+    pub endpoint: IpEndpoint,
+    /// The local address the packet was sent to, which may be a broadcast or multicast
+    /// address.
+    pub local_address: Option<IpAddress>,
+    /// The time-to-live (IPv4) or hop limit (IPv6) of the packet.
+    pub hop_limit: Option<u8>,
+    /// The traffic class (IPv6) or type of service (IPv4) of the packet. For IPv4 only
+    /// the DSCP, i.e. the upper six bits, is used.
+    pub traffic_class: Option<u8>,
+    /// The flow label of the packet. It is always `None` for received IPv4 packets and
+    /// ignored when sending IPv4 packets.
+    pub flow_label: Option<u32>,
+    /// The instant at which the packet was received.
+    pub timestamp: Option<Instant>,
+}
+
+impl From<IpEndpoint> for UdpMetadata {
+    fn from(endpoint: IpEndpoint) -> UdpMetadata {
+        UdpMetadata {
+            endpoint,
+            local_address: None,
+            hop_limit: None,
+            traffic_class: None,
+            flow_label: None,
+            timestamp: None,
+        }
+    }
+}
 
 /// A UDP packet metadata.
-pub type PacketMetadata = crate::storage::PacketMetadata<IpEndpoint>;
+pub type PacketMetadata = crate::storage::PacketMetadata<UdpMetadata>;
 
 /// A UDP packet ring buffer.
-pub type PacketBuffer<'a> = crate::storage::PacketBuffer<'a, IpEndpoint>;
+pub type PacketBuffer<'a> = crate::storage::PacketBuffer<'a, UdpMetadata>;
 
 /// Error returned by [`Socket::bind`]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -23,12 +73,23 @@ pub enum BindError {
     Unaddressable,
 }
 
+/// Error returned by [`Socket::connect`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConnectError {
+    InvalidState,
+    Unaddressable,
+}
+
 /// Error returned by [`Socket::send`]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SendError {
     Unaddressable,
     BufferFull,
+    /// The destination is a broadcast address, and the socket is not permitted to send
+    /// broadcasts. See [Socket::set_broadcast].
+    BroadcastNotPermitted,
 }
 
 /// Error returned by [`Socket::recv`]
@@ -45,16 +106,39 @@ pub enum RecvError {
 #[derive(Debug)]
 pub struct Socket<'a> {
     endpoint: IpListenEndpoint,
+    /// The peer set by connect(), if any.
+    remote_endpoint: Option<IpEndpoint>,
     rx_buffer: PacketBuffer<'a>,
     tx_buffer: PacketBuffer<'a>,
     /// The time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
     hop_limit: Option<u8>,
+    /// The traffic class (IPv6) or type of service (IPv4) value used in outgoing packets.
+    traffic_class: Option<u8>,
+    /// The IPv6 flow label used in outgoing packets.
+    flow_label: Option<u32>,
+    /// The extension headers inserted in outgoing IPv6 packets.
+    #[cfg(feature = "proto-ipv6")]
+    ipv6_ext_headers: Ipv6ExtHeaders,
+    /// Whether packets may be sent to broadcast addresses.
+    broadcast: bool,
+    /// The multicast groups joined through this socket.
+    #[cfg(feature = "proto-igmp")]
+    memberships: Memberships,
+    tx_timestamps: TxTimestamps,
     #[cfg(feature = "async")]
     rx_waker: WakerRegistration,
     #[cfg(feature = "async")]
     tx_waker: WakerRegistration,
     #[cfg(feature = "async")]
     state_waker: WakerRegistration,
+    /// Minimum amount of free payload space in the transmit buffer before the send wakers
+    /// are woken as a result of a packet being sent.
+    #[cfg(feature = "async")]
+    send_low_water: usize,
+    /// Amount of payload in the receive buffer before the receive wakers are woken as a
+    /// result of a packet being received.
+    #[cfg(feature = "async")]
+    recv_high_water: usize,
 }
 
 impl<'a> Socket<'a> {
@@ -62,15 +146,28 @@ impl<'a> Socket<'a> {
     pub fn new(rx_buffer: PacketBuffer<'a>, tx_buffer: PacketBuffer<'a>) -> Socket<'a> {
         Socket {
             endpoint: IpListenEndpoint::default(),
+            remote_endpoint: None,
             rx_buffer,
             tx_buffer,
             hop_limit: None,
+            traffic_class: None,
+            flow_label: None,
+            #[cfg(feature = "proto-ipv6")]
+            ipv6_ext_headers: Ipv6ExtHeaders::new(),
+            broadcast: false,
+            #[cfg(feature = "proto-igmp")]
+            memberships: Memberships::new(),
+            tx_timestamps: TxTimestamps::new(),
             #[cfg(feature = "async")]
             rx_waker: WakerRegistration::new(),
             #[cfg(feature = "async")]
             tx_waker: WakerRegistration::new(),
             #[cfg(feature = "async")]
             state_waker: WakerRegistration::new(),
+            #[cfg(feature = "async")]
+            send_low_water: 1,
+            #[cfg(feature = "async")]
+            recv_high_water: 0,
         }
     }
 
@@ -170,6 +267,50 @@ impl<'a> Socket<'a> {
         self.state_waker.add(waker)
     }
 
+    /// Return the send low-water mark.
+    ///
+    /// See also the [set_send_low_water](#method.set_send_low_water) method.
+    #[cfg(feature = "async")]
+    pub fn send_low_water(&self) -> usize {
+        self.send_low_water
+    }
+
+    /// Set the send low-water mark.
+    ///
+    /// When a packet is sent, the send wakers are only woken once at least this many
+    /// octets of payload space are free in the transmit buffer. This prevents an executor
+    /// from repeatedly waking a task that sends datagrams larger than the space each sent
+    /// packet frees. Closing the socket always wakes the send wakers.
+    ///
+    /// The value is clamped to the payload capacity of the transmit buffer. By default,
+    /// it is 1.
+    #[cfg(feature = "async")]
+    pub fn set_send_low_water(&mut self, low_water: usize) {
+        self.send_low_water = low_water
+    }
+
+    /// Return the receive high-water mark.
+    ///
+    /// See also the [set_recv_high_water](#method.set_recv_high_water) method.
+    #[cfg(feature = "async")]
+    pub fn recv_high_water(&self) -> usize {
+        self.recv_high_water
+    }
+
+    /// Set the receive high-water mark.
+    ///
+    /// When a packet is received, the receive wakers are only woken once at least this
+    /// many octets of payload are in the receive buffer, or once no more packets fit in it,
+    /// so that an application draining the buffer in batches is not woken for every
+    /// packet. Closing the socket always wakes the receive wakers.
+    ///
+    /// The value is clamped to the payload capacity of the receive buffer. By default, it
+    /// is 0: the receive wakers are woken for every packet.
+    #[cfg(feature = "async")]
+    pub fn set_recv_high_water(&mut self, high_water: usize) {
+        self.recv_high_water = high_water
+    }
+
     /// Clears all the wakers that were assigned to this socket
     #[cfg(feature = "async")]
     pub fn clear_wakers(&mut self) {
@@ -202,6 +343,14 @@ impl<'a> Socket<'a> {
         self.endpoint
     }
 
+    /// Return the connected remote endpoint, if any.
+    ///
+    /// See also the [connect](#method.connect) method.
+    #[inline]
+    pub fn remote_endpoint(&self) -> Option<IpEndpoint> {
+        self.remote_endpoint
+    }
+
     /// Return the time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
     ///
     /// See also the [set_hop_limit](#method.set_hop_limit) method
@@ -229,6 +378,103 @@ impl<'a> Socket<'a> {
         self.hop_limit = hop_limit
     }
 
+    /// Return the traffic class (IPv6) or type of service (IPv4) value used in outgoing
+    /// packets.
+    ///
+    /// See also the [set_traffic_class](#method.set_traffic_class) method
+    pub fn traffic_class(&self) -> Option<u8> {
+        self.traffic_class
+    }
+
+    /// Set the traffic class (IPv6) or type of service (IPv4) value used in outgoing packets.
+    ///
+    /// For IPv4 only the DSCP, i.e. the upper six bits, is used. A socket without an
+    /// explicitly set traffic class sends packets with a traffic class of zero.
+    pub fn set_traffic_class(&mut self, traffic_class: Option<u8>) {
+        self.traffic_class = traffic_class
+    }
+
+    /// Return the IPv6 flow label used in outgoing packets.
+    ///
+    /// See also the [set_flow_label](#method.set_flow_label) method
+    pub fn flow_label(&self) -> Option<u32> {
+        self.flow_label
+    }
+
+    /// Set the IPv6 flow label used in outgoing packets.
+    ///
+    /// Only the lower 20 bits are used, and IPv4 packets are not affected. A socket
+    /// without an explicitly set flow label sends packets with a flow label of zero.
+    pub fn set_flow_label(&mut self, flow_label: Option<u32>) {
+        self.flow_label = flow_label
+    }
+
+    /// Return the extension headers inserted in outgoing IPv6 packets.
+    ///
+    /// See also the [set_ipv6_ext_headers](#method.set_ipv6_ext_headers) method
+    #[cfg(feature = "proto-ipv6")]
+    pub fn ipv6_ext_headers(&self) -> &Ipv6ExtHeaders {
+        &self.ipv6_ext_headers
+    }
+
+    /// Set the extension headers inserted between the IPv6 header and the UDP header of
+    /// outgoing packets, e.g. a Router Alert option.
+    ///
+    /// The headers are not inserted on IEEE 802.15.4 links, and IPv4 packets are not
+    /// affected.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn set_ipv6_ext_headers(&mut self, headers: Ipv6ExtHeaders) {
+        self.ipv6_ext_headers = headers
+    }
+
+    /// Return whether the socket may send packets to broadcast addresses.
+    ///
+    /// See also the [set_broadcast](#method.set_broadcast) method
+    pub fn broadcast(&self) -> bool {
+        self.broadcast
+    }
+
+    /// Permit or forbid sending packets to broadcast addresses, like `SO_BROADCAST`.
+    ///
+    /// When permitted, packets can be sent to the limited broadcast address
+    /// (255.255.255.255) as well as to the directed broadcast address of any subnet
+    /// configured on the interface. Broadcasts are forbidden by default; sending to the
+    /// limited broadcast address then fails with `SendError::BroadcastNotPermitted`, and
+    /// packets to a directed broadcast address are dropped when dispatched.
+    ///
+    /// Receiving broadcast packets is always possible.
+    pub fn set_broadcast(&mut self, broadcast: bool) {
+        self.broadcast = broadcast
+    }
+
+    /// Return whether the instant at which each packet is sent is recorded.
+    ///
+    /// See also the [set_tx_timestamping](#method.set_tx_timestamping) method.
+    pub fn tx_timestamping(&self) -> bool {
+        self.tx_timestamps.enabled()
+    }
+
+    /// Enable or disable recording the instant at which each packet is sent, to be
+    /// read with [poll_tx_timestamp](#method.poll_tx_timestamp).
+    ///
+    /// The instant at which each packet is received is always recorded in its
+    /// [metadata](struct.UdpMetadata.html).
+    pub fn set_tx_timestamping(&mut self, enabled: bool) {
+        self.tx_timestamps.set_enabled(enabled)
+    }
+
+    /// Return the number the next packet enqueued will have in its transmit timestamp.
+    pub fn tx_timestamp_id(&self) -> u32 {
+        self.tx_timestamps.next_id()
+    }
+
+    /// Dequeue the oldest transmit timestamp, if any.
+    ///
+    /// Only the last few timestamps are kept until they are read.
+    pub fn poll_tx_timestamp(&mut self) -> Option<TxTimestamp> {
+        self.tx_timestamps.pop()
+    }
+
     /// Bind the socket to the given endpoint.
     ///
     /// This function returns `Err(Error::Illegal)` if the socket was open
@@ -256,14 +502,80 @@ impl<'a> Socket<'a> {
         Ok(())
     }
 
+    /// Connect the socket to the given remote endpoint.
+    ///
+    /// Once connected, packets can be sent without specifying the remote endpoint, see
+    /// [send_connected](#method.send_connected), and only packets coming from that endpoint
+    /// are received. Packets already in the receive buffer are kept. Connecting again
+    /// replaces the remote endpoint.
+    ///
+    /// This function returns `Err(Error::Illegal)` if the socket is not bound
+    /// (see [is_open](#method.is_open)), and `Err(Error::Unaddressable)`
+    /// if the address or port in the given endpoint is unspecified.
+    pub fn connect<T: Into<IpEndpoint>>(&mut self, remote_endpoint: T) -> Result<(), ConnectError> {
+        let remote_endpoint = remote_endpoint.into();
+        if remote_endpoint.addr.is_unspecified() || remote_endpoint.port == 0 {
+            return Err(ConnectError::Unaddressable);
+        }
+
+        if !self.is_open() {
+            return Err(ConnectError::InvalidState);
+        }
+
+        net_trace!("udp:{}: connected to {}", self.endpoint, remote_endpoint);
+        self.remote_endpoint = Some(remote_endpoint);
+        Ok(())
+    }
+
+    /// Forget the remote endpoint set by [connect](#method.connect), so that packets from
+    /// any endpoint are received again.
+    pub fn disconnect(&mut self) {
+        self.remote_endpoint = None;
+    }
+
+    /// Check whether the socket is connected to a remote endpoint.
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        self.remote_endpoint.is_some()
+    }
+
+    /// Join an IPv4 multicast group.
+    ///
+    /// The interface joins the group on the next poll, and stays a member for as long
+    /// as any socket or the interface itself asked for it. Once a socket joined a group,
+    /// it only receives multicast packets sent to the groups it joined.
+    ///
+    /// Returns `Ok(true)` if the socket was not already a member of the group.
+    #[cfg(feature = "proto-igmp")]
+    pub fn join_multicast<T: Into<IpAddress>>(&mut self, addr: T) -> Result<bool, MembershipError> {
+        self.memberships.join(addr.into())
+    }
+
+    /// Leave an IPv4 multicast group joined with [join_multicast](#method.join_multicast).
+    ///
+    /// Returns `true` if the socket was a member of the group.
+    #[cfg(feature = "proto-igmp")]
+    pub fn leave_multicast<T: Into<IpAddress>>(&mut self, addr: T) -> bool {
+        self.memberships.leave(addr.into())
+    }
+
+    #[cfg(feature = "proto-igmp")]
+    pub(crate) fn multicast_groups(&self) -> &[crate::wire::Ipv4Address] {
+        self.memberships.groups()
+    }
+
     /// Close the socket.
     pub fn close(&mut self) {
-        // Clear the bound endpoint of the socket.
+        // Clear the bound and connected endpoints of the socket.
         self.endpoint = IpListenEndpoint::default();
+        self.remote_endpoint = None;
+        #[cfg(feature = "proto-igmp")]
+        self.memberships.clear();
 
         // Reset the RX and TX buffers of the socket.
         self.tx_buffer.reset();
         self.rx_buffer.reset();
+        self.tx_timestamps.reset();
 
         #[cfg(feature = "async")]
         {
@@ -327,6 +639,25 @@ impl<'a> Socket<'a> {
         size: usize,
         remote_endpoint: IpEndpoint,
     ) -> Result<&mut [u8], SendError> {
+        self.send_with_metadata(size, remote_endpoint.into())
+    }
+
+    /// Enqueue a packet to be sent to the remote endpoint in `metadata`, and return a
+    /// pointer to its payload.
+    ///
+    /// The hop limit, traffic class and flow label in `metadata` apply to this packet
+    /// only, overriding the settings of the socket. Otherwise this function behaves
+    /// identically to [send](#method.send).
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a hop limit value of 0 is given.
+    pub fn send_with_metadata(
+        &mut self,
+        size: usize,
+        metadata: UdpMetadata,
+    ) -> Result<&mut [u8], SendError> {
+        let remote_endpoint = metadata.endpoint;
         if self.endpoint.port == 0 {
             return Err(SendError::Unaddressable);
         }
@@ -336,11 +667,18 @@ impl<'a> Socket<'a> {
         if remote_endpoint.port == 0 {
             return Err(SendError::Unaddressable);
         }
+        if remote_endpoint.addr.is_broadcast() && !self.broadcast {
+            return Err(SendError::BroadcastNotPermitted);
+        }
+        if let Some(0) = metadata.hop_limit {
+            panic!("the time-to-live value of a packet must not be zero")
+        }
 
         let payload_buf = self
             .tx_buffer
-            .enqueue(size, remote_endpoint)
+            .enqueue(size, metadata)
             .map_err(|_| SendError::BufferFull)?;
+        self.tx_timestamps.enqueued();
 
         net_trace!(
             "udp:{}:{}: buffer to send {} octets",
@@ -377,8 +715,9 @@ impl<'a> Socket<'a> {
 
         let size = self
             .tx_buffer
-            .enqueue_with_infallible(max_size, remote_endpoint, f)
+            .enqueue_with_infallible(max_size, remote_endpoint.into(), f)
             .map_err(|_| SendError::BufferFull)?;
+        self.tx_timestamps.enqueued();
 
         net_trace!(
             "udp:{}:{}: buffer to send {} octets",
@@ -402,21 +741,87 @@ impl<'a> Socket<'a> {
         Ok(())
     }
 
+    /// Enqueue a packet to be sent to the remote endpoint in `metadata`, and fill it
+    /// from a slice.
+    ///
+    /// See also [send_with_metadata](#method.send_with_metadata).
+    pub fn send_slice_with_metadata(
+        &mut self,
+        data: &[u8],
+        metadata: UdpMetadata,
+    ) -> Result<(), SendError> {
+        self.send_with_metadata(data.len(), metadata)?
+            .copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Enqueue a packet to be sent to the connected remote endpoint, and return a pointer
+    /// to its payload.
+    ///
+    /// This function returns `Err(Error::Unaddressable)` if the socket is not
+    /// [connected](#method.connect), and otherwise behaves identically to
+    /// [send](#method.send).
+    pub fn send_connected(&mut self, size: usize) -> Result<&mut [u8], SendError> {
+        let remote_endpoint = self.remote_endpoint.ok_or(SendError::Unaddressable)?;
+        self.send(size, remote_endpoint)
+    }
+
+    /// Enqueue a packet to be sent to the connected remote endpoint, and fill it from a slice.
+    ///
+    /// See also [send_connected](#method.send_connected).
+    pub fn send_slice_connected(&mut self, data: &[u8]) -> Result<(), SendError> {
+        self.send_connected(data.len())?.copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Enqueue several packets, each to be sent to its own remote endpoint.
+    ///
+    /// Packets are enqueued in order until the iterator is exhausted or a packet cannot be
+    /// enqueued, and the number of packets enqueued is returned. The packet that could not be
+    /// enqueued is consumed from the iterator; pass `iter.by_ref()` to be able to resume
+    /// with the packets following it. All enqueued packets are sent on the next poll of the
+    /// interface.
+    ///
+    /// If not even the first packet can be enqueued, the error returned by
+    /// [send_slice](#method.send_slice) for it is returned instead.
+    pub fn send_batch<'b, I>(&mut self, packets: I) -> Result<usize, SendError>
+    where
+        I: IntoIterator<Item = (IpEndpoint, &'b [u8])>,
+    {
+        let mut count = 0;
+        for (remote_endpoint, data) in packets {
+            match self.send_slice(data, remote_endpoint) {
+                Ok(()) => count += 1,
+                Err(e) if count == 0 => return Err(e),
+                Err(_) => break,
+            }
+        }
+        Ok(count)
+    }
+
     /// Dequeue a packet received from a remote endpoint, and return the endpoint as well
     /// as a pointer to the payload.
     ///
     /// This function returns `Err(Error::Exhausted)` if the receive buffer is empty.
     pub fn recv(&mut self) -> Result<(&[u8], IpEndpoint), RecvError> {
-        let (remote_endpoint, payload_buf) =
-            self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
+        let (payload_buf, metadata) = self.recv_with_metadata()?;
+        Ok((payload_buf, metadata.endpoint))
+    }
+
+    /// Dequeue a packet received from a remote endpoint, and return its metadata as well
+    /// as a pointer to the payload.
+    ///
+    /// This function otherwise behaves identically to [recv](#method.recv).
+    pub fn recv_with_metadata(&mut self) -> Result<(&[u8], UdpMetadata), RecvError> {
+        let (metadata, payload_buf) = self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
 
         net_trace!(
             "udp:{}:{}: receive {} buffered octets",
             self.endpoint,
-            remote_endpoint,
+            metadata.endpoint,
             payload_buf.len()
         );
-        Ok((payload_buf, remote_endpoint))
+        Ok((payload_buf, metadata))
     }
 
     /// Dequeue a packet received from a remote endpoint, copy the payload into the given slice,
@@ -424,10 +829,45 @@ impl<'a> Socket<'a> {
     ///
     /// See also [recv](#method.recv).
     pub fn recv_slice(&mut self, data: &mut [u8]) -> Result<(usize, IpEndpoint), RecvError> {
-        let (buffer, endpoint) = self.recv().map_err(|_| RecvError::Exhausted)?;
+        let (length, metadata) = self.recv_slice_with_metadata(data)?;
+        Ok((length, metadata.endpoint))
+    }
+
+    /// Dequeue a packet received from a remote endpoint, copy the payload into the given slice,
+    /// and return the amount of octets copied as well as the packet metadata.
+    ///
+    /// See also [recv_with_metadata](#method.recv_with_metadata).
+    pub fn recv_slice_with_metadata(
+        &mut self,
+        data: &mut [u8],
+    ) -> Result<(usize, UdpMetadata), RecvError> {
+        let (buffer, metadata) = self.recv_with_metadata()?;
         let length = min(data.len(), buffer.len());
         data[..length].copy_from_slice(&buffer[..length]);
-        Ok((length, endpoint))
+        Ok((length, metadata))
+    }
+
+    /// Dequeue up to `max` packets received from remote endpoints, calling `f` with the
+    /// payload and remote endpoint of each, and return the number of packets dequeued.
+    ///
+    /// This function returns `Err(Error::Exhausted)` if the receive buffer is empty.
+    pub fn recv_batch<F>(&mut self, max: usize, mut f: F) -> Result<usize, RecvError>
+    where
+        F: FnMut(&[u8], IpEndpoint),
+    {
+        if self.rx_buffer.is_empty() {
+            return Err(RecvError::Exhausted);
+        }
+
+        let mut count = 0;
+        while count < max {
+            match self.recv() {
+                Ok((payload_buf, remote_endpoint)) => f(payload_buf, remote_endpoint),
+                Err(RecvError::Exhausted) => break,
+            }
+            count += 1;
+        }
+        Ok(count)
     }
 
     /// Peek at a packet received from a remote endpoint, and return the endpoint as well
@@ -437,17 +877,18 @@ impl<'a> Socket<'a> {
     /// It returns `Err(Error::Exhausted)` if the receive buffer is empty.
     pub fn peek(&mut self) -> Result<(&[u8], &IpEndpoint), RecvError> {
         let endpoint = self.endpoint;
-        self.rx_buffer.peek().map_err(|_| RecvError::Exhausted).map(
-            |(remote_endpoint, payload_buf)| {
+        self.rx_buffer
+            .peek()
+            .map_err(|_| RecvError::Exhausted)
+            .map(|(metadata, payload_buf)| {
                 net_trace!(
                     "udp:{}:{}: peek {} buffered octets",
                     endpoint,
-                    remote_endpoint,
+                    metadata.endpoint,
                     payload_buf.len()
                 );
-                (payload_buf, remote_endpoint)
-            },
-        )
+                (payload_buf, &metadata.endpoint)
+            })
     }
 
     /// Peek at a packet received from a remote endpoint, copy the payload into the given slice,
@@ -463,17 +904,26 @@ impl<'a> Socket<'a> {
         Ok((length, endpoint))
     }
 
-    pub(crate) fn accepts(&self, _cx: &mut Context, ip_repr: &IpRepr, repr: &UdpRepr) -> bool {
+    pub(crate) fn accepts(&self, cx: &mut Context, ip_repr: &IpRepr, repr: &UdpRepr) -> bool {
         if self.endpoint.port != repr.dst_port {
             return false;
         }
         if self.endpoint.addr.is_some()
             && self.endpoint.addr != Some(ip_repr.dst_addr())
-            && !ip_repr.dst_addr().is_broadcast()
+            && !cx.is_broadcast(&ip_repr.dst_addr())
             && !ip_repr.dst_addr().is_multicast()
         {
             return false;
         }
+        if let Some(remote_endpoint) = self.remote_endpoint {
+            if remote_endpoint.addr != ip_repr.src_addr() || remote_endpoint.port != repr.src_port {
+                return false;
+            }
+        }
+        #[cfg(feature = "proto-igmp")]
+        if !self.memberships.accepts(ip_repr.dst_addr()) {
+            return false;
+        }
 
         true
     }
@@ -501,7 +951,16 @@ impl<'a> Socket<'a> {
             size
         );
 
-        match self.rx_buffer.enqueue(size, remote_endpoint) {
+        let metadata = UdpMetadata {
+            endpoint: remote_endpoint,
+            local_address: Some(ip_repr.dst_addr()),
+            hop_limit: Some(ip_repr.hop_limit()),
+            traffic_class: Some(ip_repr.traffic_class()),
+            flow_label: ip_repr.flow_label(),
+            timestamp: Some(cx.now()),
+        };
+
+        match self.rx_buffer.enqueue(size, metadata) {
             Ok(buf) => buf.copy_from_slice(payload),
             Err(_) => net_trace!(
                 "udp:{}:{}: buffer full, dropped incoming packet",
@@ -511,7 +970,12 @@ impl<'a> Socket<'a> {
         }
 
         #[cfg(feature = "async")]
-        self.rx_waker.wake_all();
+        if self.rx_buffer.is_full()
+            || self.rx_buffer.payload_bytes_count()
+                >= self.recv_high_water.min(self.rx_buffer.payload_capacity())
+        {
+            self.rx_waker.wake_all();
+        }
     }
 
     pub(crate) fn dispatch<F, E>(&mut self, cx: &mut Context, emit: F) -> Result<(), E>
@@ -519,10 +983,25 @@ impl<'a> Socket<'a> {
         F: FnOnce(&mut Context, (IpRepr, UdpRepr, &[u8])) -> Result<(), E>,
     {
         let endpoint = self.endpoint;
-        let hop_limit = self.hop_limit.unwrap_or(64);
-
-        let res = self.tx_buffer.dequeue_with(|remote_endpoint, payload_buf| {
-            let src_addr = match endpoint.addr {
+        let hop_limit = self.hop_limit;
+        let traffic_class = self.traffic_class;
+        let flow_label = self.flow_label;
+        let broadcast = self.broadcast;
+        let now = cx.now();
+        let mut sent = false;
+
+        let res = self.tx_buffer.dequeue_with(|metadata, payload_buf| {
+            let remote_endpoint = metadata.endpoint;
+            if !broadcast && cx.is_broadcast(&remote_endpoint.addr) {
+                net_debug!(
+                    "udp:{}:{}: broadcast not permitted, dropping.",
+                    endpoint,
+                    remote_endpoint
+                );
+                return Ok(());
+            }
+            let local_addr = metadata.local_address.filter(|addr| addr.is_unicast());
+            let src_addr = match endpoint.addr.or(local_addr) {
                 Some(addr) => addr,
                 None => match cx.get_source_address(remote_endpoint.addr) {
                     Some(addr) => addr,
@@ -548,21 +1027,36 @@ impl<'a> Socket<'a> {
                 src_port: endpoint.port,
                 dst_port: remote_endpoint.port,
             };
-            let ip_repr = IpRepr::new(
+            let mut ip_repr = IpRepr::new(
                 src_addr,
                 remote_endpoint.addr,
                 IpProtocol::Udp,
                 repr.header_len() + payload_buf.len(),
-                hop_limit,
+                metadata.hop_limit.or(hop_limit).unwrap_or(64),
             );
-            emit(cx, (ip_repr, repr, payload_buf))
+            if let Some(traffic_class) = metadata.traffic_class.or(traffic_class) {
+                ip_repr.set_traffic_class(traffic_class);
+            }
+            if let Some(flow_label) = metadata.flow_label.or(flow_label) {
+                ip_repr.set_flow_label(flow_label);
+            }
+            emit(cx, (ip_repr, repr, payload_buf)).map(|()| sent = true)
         });
         match res {
             Err(Empty) => Ok(()),
             Ok(Err(e)) => Err(e),
             Ok(Ok(())) => {
+                self.tx_timestamps
+                    .dequeued(if sent { Some(now) } else { None });
+
                 #[cfg(feature = "async")]
-                self.tx_waker.wake_all();
+                {
+                    let capacity = self.tx_buffer.payload_capacity();
+                    let free = capacity - self.tx_buffer.payload_bytes_count();
+                    if free >= self.send_low_water.min(capacity) {
+                        self.tx_waker.wake_all();
+                    }
+                }
                 Ok(())
             }
         }
@@ -581,6 +1075,10 @@ impl<'a> Socket<'a> {
 mod test {
     use super::*;
     use crate::wire::{IpRepr, UdpRepr};
+    #[cfg(feature = "proto-ipv4")]
+    use crate::wire::{Ipv4Address, Ipv4Repr};
+    #[cfg(feature = "proto-ipv6")]
+    use crate::wire::{Ipv6Address, Ipv6Repr};
 
     fn buffer(packets: usize) -> PacketBuffer<'static> {
         PacketBuffer::new(vec![PacketMetadata::EMPTY; packets], vec![0; 16 * packets])
@@ -599,16 +1097,27 @@ mod test {
     cfg_if::cfg_if! {
         if #[cfg(feature = "proto-ipv4")] {
             use crate::wire::Ipv4Address as IpvXAddress;
-            use crate::wire::Ipv4Repr as IpvXRepr;
-            use IpRepr::Ipv4 as IpReprIpvX;
 
             const LOCAL_ADDR: IpvXAddress = IpvXAddress([192, 168, 1, 1]);
             const REMOTE_ADDR: IpvXAddress = IpvXAddress([192, 168, 1, 2]);
             const OTHER_ADDR: IpvXAddress = IpvXAddress([192, 168, 1, 3]);
+
+            const fn ip_repr(
+                src_addr: IpvXAddress,
+                dst_addr: IpvXAddress,
+                hop_limit: u8,
+            ) -> IpRepr {
+                IpRepr::Ipv4(Ipv4Repr {
+                    src_addr,
+                    dst_addr,
+                    next_header: IpProtocol::Udp,
+                    payload_len: 8 + 6,
+                    hop_limit,
+                    dscp: 0,
+                })
+            }
         } else {
             use crate::wire::Ipv6Address as IpvXAddress;
-            use crate::wire::Ipv6Repr as IpvXRepr;
-            use IpRepr::Ipv6 as IpReprIpvX;
 
             const LOCAL_ADDR: IpvXAddress = IpvXAddress([
                 0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
@@ -619,6 +1128,22 @@ mod test {
             const OTHER_ADDR: IpvXAddress = IpvXAddress([
                 0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3,
             ]);
+
+            const fn ip_repr(
+                src_addr: IpvXAddress,
+                dst_addr: IpvXAddress,
+                hop_limit: u8,
+            ) -> IpRepr {
+                IpRepr::Ipv6(Ipv6Repr {
+                    src_addr,
+                    dst_addr,
+                    next_header: IpProtocol::Udp,
+                    payload_len: 8 + 6,
+                    hop_limit,
+                    traffic_class: 0,
+                    flow_label: 0,
+                })
+            }
         }
     }
 
@@ -631,29 +1156,11 @@ mod test {
         port: REMOTE_PORT,
     };
 
-    pub const LOCAL_IP_REPR: IpRepr = IpReprIpvX(IpvXRepr {
-        src_addr: LOCAL_ADDR,
-        dst_addr: REMOTE_ADDR,
-        next_header: IpProtocol::Udp,
-        payload_len: 8 + 6,
-        hop_limit: 64,
-    });
-
-    pub const REMOTE_IP_REPR: IpRepr = IpReprIpvX(IpvXRepr {
-        src_addr: REMOTE_ADDR,
-        dst_addr: LOCAL_ADDR,
-        next_header: IpProtocol::Udp,
-        payload_len: 8 + 6,
-        hop_limit: 64,
-    });
-
-    pub const BAD_IP_REPR: IpRepr = IpReprIpvX(IpvXRepr {
-        src_addr: REMOTE_ADDR,
-        dst_addr: OTHER_ADDR,
-        next_header: IpProtocol::Udp,
-        payload_len: 8 + 6,
-        hop_limit: 64,
-    });
+    pub const LOCAL_IP_REPR: IpRepr = ip_repr(LOCAL_ADDR, REMOTE_ADDR, 64);
+
+    pub const REMOTE_IP_REPR: IpRepr = ip_repr(REMOTE_ADDR, LOCAL_ADDR, 64);
+
+    pub const BAD_IP_REPR: IpRepr = ip_repr(REMOTE_ADDR, OTHER_ADDR, 64);
 
     const LOCAL_UDP_REPR: UdpRepr = UdpRepr {
         src_port: LOCAL_PORT,
@@ -762,6 +1269,199 @@ mod test {
         assert!(socket.can_send());
     }
 
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_tx_timestamps() {
+        use crate::wire::Ipv4Address;
+
+        let mut socket = socket(buffer(0), buffer(3));
+        let mut cx = Context::mock();
+        let directed = IpEndpoint::new(Ipv4Address::new(192, 168, 1, 255).into(), REMOTE_PORT);
+
+        assert_eq!(socket.bind(LOCAL_END), Ok(()));
+        assert!(!socket.tx_timestamping());
+        socket.set_tx_timestamping(true);
+        assert_eq!(socket.tx_timestamp_id(), 0);
+
+        // The second packet is dropped, since broadcasts are not permitted.
+        assert_eq!(socket.send_slice(PAYLOAD, REMOTE_END), Ok(()));
+        assert_eq!(socket.send_slice(PAYLOAD, directed), Ok(()));
+        assert_eq!(socket.send_slice(PAYLOAD, REMOTE_END), Ok(()));
+        assert_eq!(socket.tx_timestamp_id(), 3);
+
+        for millis in [10, 20, 30] {
+            cx.set_now(Instant::from_millis(millis));
+            assert_eq!(socket.dispatch(&mut cx, |_, _| Ok::<_, ()>(())), Ok(()));
+        }
+        assert_eq!(
+            socket.poll_tx_timestamp(),
+            Some(TxTimestamp {
+                id: 0,
+                timestamp: Instant::from_millis(10),
+            })
+        );
+        assert_eq!(
+            socket.poll_tx_timestamp(),
+            Some(TxTimestamp {
+                id: 2,
+                timestamp: Instant::from_millis(30),
+            })
+        );
+        assert_eq!(socket.poll_tx_timestamp(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_send_broadcast() {
+        use crate::wire::Ipv4Address;
+
+        let mut socket = socket(buffer(0), buffer(2));
+        let mut cx = Context::mock();
+        let limited = IpEndpoint::new(Ipv4Address::BROADCAST.into(), REMOTE_PORT);
+        let directed = IpEndpoint::new(Ipv4Address::new(192, 168, 1, 255).into(), REMOTE_PORT);
+
+        assert_eq!(socket.bind(LOCAL_PORT), Ok(()));
+        assert!(!socket.broadcast());
+        assert_eq!(
+            socket.send_slice(PAYLOAD, limited),
+            Err(SendError::BroadcastNotPermitted)
+        );
+        // The directed broadcast address is only known to the interface, so such packets
+        // are dropped when dispatched.
+        assert_eq!(socket.send_slice(PAYLOAD, directed), Ok(()));
+        assert_eq!(
+            socket.dispatch(&mut cx, |_, _| unreachable!()),
+            Ok::<_, ()>(())
+        );
+        assert!(socket.tx_buffer.is_empty());
+
+        socket.set_broadcast(true);
+        assert_eq!(socket.send_slice(PAYLOAD, limited), Ok(()));
+        assert_eq!(socket.send_slice(PAYLOAD, directed), Ok(()));
+        for endpoint in [limited, directed] {
+            assert_eq!(
+                socket.dispatch(&mut cx, |_, (ip_repr, _, _)| {
+                    assert_eq!(ip_repr.dst_addr(), endpoint.addr);
+                    Ok::<_, ()>(())
+                }),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_recv_directed_broadcast() {
+        use crate::wire::Ipv4Address;
+
+        let mut socket = socket(buffer(1), buffer(0));
+        let mut cx = Context::mock();
+
+        let local_end = IpEndpoint::new(Ipv4Address::new(192, 168, 1, 1).into(), LOCAL_PORT);
+        assert_eq!(socket.bind(local_end), Ok(()));
+        let ip_repr = |dst_addr: Ipv4Address| {
+            IpRepr::new(
+                Ipv4Address::new(192, 168, 1, 2).into(),
+                dst_addr.into(),
+                IpProtocol::Udp,
+                8 + 6,
+                64,
+            )
+        };
+        let directed = Ipv4Address::new(192, 168, 1, 255);
+        let other_subnet = Ipv4Address::new(192, 168, 2, 255);
+        assert!(socket.accepts(&mut cx, &ip_repr(directed), &REMOTE_UDP_REPR));
+        assert!(socket.accepts(&mut cx, &ip_repr(Ipv4Address::BROADCAST), &REMOTE_UDP_REPR));
+        assert!(!socket.accepts(&mut cx, &ip_repr(other_subnet), &REMOTE_UDP_REPR));
+    }
+
+    #[test]
+    fn test_connect() {
+        let mut socket = socket(buffer(1), buffer(1));
+        let mut cx = Context::mock();
+
+        assert_eq!(socket.connect(REMOTE_END), Err(ConnectError::InvalidState));
+        assert_eq!(socket.bind(LOCAL_END), Ok(()));
+        assert_eq!(
+            socket.connect(IpEndpoint::new(REMOTE_ADDR.into_address(), 0)),
+            Err(ConnectError::Unaddressable)
+        );
+        assert_eq!(
+            socket.send_slice_connected(b"abcdef"),
+            Err(SendError::Unaddressable)
+        );
+
+        assert_eq!(socket.connect(REMOTE_END), Ok(()));
+        assert!(socket.is_connected());
+        assert_eq!(socket.remote_endpoint(), Some(REMOTE_END));
+
+        assert_eq!(socket.send_slice_connected(b"abcdef"), Ok(()));
+        assert_eq!(
+            socket.dispatch(&mut cx, |_, (ip_repr, udp_repr, payload)| {
+                assert_eq!(ip_repr, LOCAL_IP_REPR);
+                assert_eq!(udp_repr, LOCAL_UDP_REPR);
+                assert_eq!(payload, PAYLOAD);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+
+        // Packets from other endpoints are filtered out.
+        assert!(socket.accepts(&mut cx, &REMOTE_IP_REPR, &REMOTE_UDP_REPR));
+        let other_udp_repr = UdpRepr {
+            src_port: REMOTE_PORT + 1,
+            ..REMOTE_UDP_REPR
+        };
+        assert!(!socket.accepts(&mut cx, &REMOTE_IP_REPR, &other_udp_repr));
+
+        socket.disconnect();
+        assert!(!socket.is_connected());
+        assert!(socket.accepts(&mut cx, &REMOTE_IP_REPR, &other_udp_repr));
+    }
+
+    #[test]
+    #[cfg(feature = "proto-igmp")]
+    fn test_multicast_membership() {
+        use crate::wire::Ipv4Address;
+
+        let mut socket = socket(buffer(1), buffer(0));
+        let mut cx = Context::mock();
+
+        let group_a = Ipv4Address::new(224, 0, 0, 251);
+        let group_b = Ipv4Address::new(239, 1, 2, 3);
+        let ip_repr = |dst_addr: Ipv4Address| {
+            IpRepr::new(
+                Ipv4Address::new(192, 168, 1, 2).into(),
+                dst_addr.into(),
+                IpProtocol::Udp,
+                8 + 6,
+                64,
+            )
+        };
+
+        assert_eq!(socket.bind(LOCAL_PORT), Ok(()));
+        assert!(socket.accepts(&mut cx, &ip_repr(group_a), &REMOTE_UDP_REPR));
+        assert!(socket.accepts(&mut cx, &ip_repr(group_b), &REMOTE_UDP_REPR));
+
+        assert_eq!(
+            socket.join_multicast(Ipv4Address::new(192, 168, 1, 1)),
+            Err(MembershipError::Unaddressable)
+        );
+        assert_eq!(socket.join_multicast(group_a), Ok(true));
+        assert_eq!(socket.join_multicast(group_a), Ok(false));
+        assert_eq!(socket.multicast_groups(), &[group_a]);
+        assert!(socket.accepts(&mut cx, &ip_repr(group_a), &REMOTE_UDP_REPR));
+        assert!(!socket.accepts(&mut cx, &ip_repr(group_b), &REMOTE_UDP_REPR));
+
+        assert!(socket.leave_multicast(group_a));
+        assert!(!socket.leave_multicast(group_a));
+        assert!(socket.accepts(&mut cx, &ip_repr(group_b), &REMOTE_UDP_REPR));
+
+        assert_eq!(socket.join_multicast(group_b), Ok(true));
+        socket.close();
+        assert!(socket.multicast_groups().is_empty());
+    }
+
     #[test]
     fn test_recv_process() {
         let mut socket = socket(buffer(1), buffer(0));
@@ -783,6 +1483,273 @@ mod test {
         assert!(!socket.can_recv());
     }
 
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_waker_water_marks() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::task::Wake;
+
+        #[derive(Default)]
+        struct CountingWaker(AtomicUsize);
+
+        impl Wake for CountingWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let mut socket = socket(buffer(4), buffer(4));
+        let mut cx = Context::mock();
+        assert_eq!(socket.bind(LOCAL_END), Ok(()));
+        socket.set_recv_high_water(12);
+        socket.set_send_low_water(56);
+
+        let counter = Arc::new(CountingWaker::default());
+        socket.register_recv_waker(&Waker::from(counter.clone()));
+        socket.process(&mut cx, &REMOTE_IP_REPR, &REMOTE_UDP_REPR, PAYLOAD);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+        socket.process(&mut cx, &REMOTE_IP_REPR, &REMOTE_UDP_REPR, PAYLOAD);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+        let counter = Arc::new(CountingWaker::default());
+        socket.register_send_waker(&Waker::from(counter.clone()));
+        for _ in 0..2 {
+            socket.send_slice(&[0; 16], REMOTE_END).unwrap();
+        }
+        socket.dispatch(&mut cx, |_, _| Ok::<_, ()>(())).unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+        socket.dispatch(&mut cx, |_, _| Ok::<_, ()>(())).unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_send_batch() {
+        let mut socket = socket(buffer(0), buffer(2));
+        let mut cx = Context::mock();
+
+        assert_eq!(
+            socket.send_batch([(REMOTE_END, &b"abcdef"[..])]),
+            Err(SendError::Unaddressable)
+        );
+        assert_eq!(socket.bind(LOCAL_END), Ok(()));
+
+        let packets = [
+            (REMOTE_END, &b"abcdef"[..]),
+            (REMOTE_END, &b"abcdef"[..]),
+            (REMOTE_END, &b"123456"[..]),
+        ];
+        let mut packets = packets.into_iter();
+        assert_eq!(socket.send_batch(packets.by_ref()), Ok(2));
+        assert_eq!(packets.next(), None);
+        assert_eq!(
+            socket.send_batch([(REMOTE_END, &b"123456"[..])]),
+            Err(SendError::BufferFull)
+        );
+
+        for _ in 0..2 {
+            assert_eq!(
+                socket.dispatch(&mut cx, |_, (ip_repr, udp_repr, payload)| {
+                    assert_eq!(ip_repr, LOCAL_IP_REPR);
+                    assert_eq!(udp_repr, LOCAL_UDP_REPR);
+                    assert_eq!(payload, PAYLOAD);
+                    Ok::<_, ()>(())
+                }),
+                Ok(())
+            );
+        }
+        assert_eq!(socket.poll_at(&cx), PollAt::Ingress);
+    }
+
+    #[test]
+    fn test_recv_batch() {
+        let mut socket = socket(buffer(3), buffer(0));
+        let mut cx = Context::mock();
+
+        assert_eq!(socket.bind(LOCAL_PORT), Ok(()));
+        assert_eq!(socket.recv_batch(8, |_, _| ()), Err(RecvError::Exhausted));
+
+        for _ in 0..3 {
+            socket.process(&mut cx, &REMOTE_IP_REPR, &REMOTE_UDP_REPR, PAYLOAD);
+        }
+
+        let mut received = 0;
+        let batch = socket.recv_batch(2, |payload, endpoint| {
+            assert_eq!(payload, PAYLOAD);
+            assert_eq!(endpoint, REMOTE_END);
+            received += 1;
+        });
+        assert_eq!(batch, Ok(2));
+        assert_eq!(received, 2);
+        assert_eq!(socket.recv_batch(8, |_, _| ()), Ok(1));
+        assert!(!socket.can_recv());
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_recv_with_metadata() {
+        let mut socket = socket(buffer(1), buffer(0));
+        let mut cx = Context::mock();
+        cx.set_now(Instant::from_millis(1234));
+
+        assert_eq!(socket.bind(LOCAL_PORT), Ok(()));
+        assert_eq!(socket.recv_with_metadata(), Err(RecvError::Exhausted));
+
+        let remote_addr = Ipv4Address::new(192, 168, 1, 2);
+        let local_addr = Ipv4Address::new(192, 168, 1, 1);
+        let ip_repr = IpRepr::Ipv4(Ipv4Repr {
+            src_addr: remote_addr,
+            dst_addr: local_addr,
+            next_header: IpProtocol::Udp,
+            payload_len: 8 + 6,
+            hop_limit: 5,
+            dscp: 0x2e,
+        });
+        socket.process(&mut cx, &ip_repr, &REMOTE_UDP_REPR, PAYLOAD);
+
+        let mut data = [0; 16];
+        assert_eq!(
+            socket.recv_slice_with_metadata(&mut data),
+            Ok((
+                PAYLOAD.len(),
+                UdpMetadata {
+                    endpoint: IpEndpoint::new(remote_addr.into(), REMOTE_PORT),
+                    local_address: Some(local_addr.into()),
+                    hop_limit: Some(5),
+                    traffic_class: Some(0xb8),
+                    flow_label: None,
+                    timestamp: Some(Instant::from_millis(1234)),
+                }
+            ))
+        );
+        assert_eq!(&data[..PAYLOAD.len()], PAYLOAD);
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv6")]
+    fn test_recv_with_metadata_ipv6() {
+        let mut socket = socket(buffer(1), buffer(0));
+        let mut cx = Context::mock();
+        cx.set_now(Instant::from_millis(1234));
+
+        assert_eq!(socket.bind(LOCAL_PORT), Ok(()));
+
+        let remote_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+        let local_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+        let ip_repr = IpRepr::Ipv6(Ipv6Repr {
+            src_addr: remote_addr,
+            dst_addr: local_addr,
+            next_header: IpProtocol::Udp,
+            payload_len: 8 + 6,
+            hop_limit: 5,
+            traffic_class: 0xb8,
+            flow_label: 0x12345,
+        });
+        socket.process(&mut cx, &ip_repr, &REMOTE_UDP_REPR, PAYLOAD);
+
+        let mut data = [0; 16];
+        assert_eq!(
+            socket.recv_slice_with_metadata(&mut data),
+            Ok((
+                PAYLOAD.len(),
+                UdpMetadata {
+                    endpoint: IpEndpoint::new(remote_addr.into(), REMOTE_PORT),
+                    local_address: Some(local_addr.into()),
+                    hop_limit: Some(5),
+                    traffic_class: Some(0xb8),
+                    flow_label: Some(0x12345),
+                    timestamp: Some(Instant::from_millis(1234)),
+                }
+            ))
+        );
+        assert_eq!(&data[..PAYLOAD.len()], PAYLOAD);
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_send_with_metadata() {
+        let mut socket = socket(buffer(0), buffer(2));
+        let mut cx = Context::mock();
+        let remote_end = IpEndpoint::new(Ipv4Address::new(192, 168, 1, 2).into(), REMOTE_PORT);
+
+        assert_eq!(socket.bind(LOCAL_PORT), Ok(()));
+        socket.set_traffic_class(Some(0x28));
+        socket.set_flow_label(Some(0x1234));
+
+        // The socket settings apply unless overridden for a single packet.
+        assert_eq!(socket.send_slice(PAYLOAD, remote_end), Ok(()));
+        let metadata = UdpMetadata {
+            hop_limit: Some(0x2a),
+            traffic_class: Some(0xb8),
+            flow_label: Some(0x54321),
+            ..remote_end.into()
+        };
+        assert_eq!(socket.send_slice_with_metadata(PAYLOAD, metadata), Ok(()));
+
+        // IPv4 has no flow label, so it is ignored.
+        assert_eq!(
+            socket.dispatch(&mut cx, |_, (ip_repr, _, _)| {
+                assert_eq!(ip_repr.hop_limit(), 64);
+                assert_eq!(ip_repr.traffic_class(), 0x28);
+                assert_eq!(ip_repr.flow_label(), None);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+        assert_eq!(
+            socket.dispatch(&mut cx, |_, (ip_repr, _, _)| {
+                assert_eq!(ip_repr.hop_limit(), 0x2a);
+                assert_eq!(ip_repr.traffic_class(), 0xb8);
+                assert_eq!(ip_repr.flow_label(), None);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv6")]
+    fn test_send_with_metadata_ipv6() {
+        let mut socket = socket(buffer(0), buffer(2));
+        let mut cx = Context::mock();
+        let remote_end = IpEndpoint::new(
+            Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2).into(),
+            REMOTE_PORT,
+        );
+
+        assert_eq!(socket.bind(LOCAL_PORT), Ok(()));
+        socket.set_traffic_class(Some(0x28));
+        socket.set_flow_label(Some(0x1234));
+
+        assert_eq!(socket.send_slice(PAYLOAD, remote_end), Ok(()));
+        let metadata = UdpMetadata {
+            hop_limit: Some(0x2a),
+            traffic_class: Some(0xb8),
+            flow_label: Some(0x54321),
+            ..remote_end.into()
+        };
+        assert_eq!(socket.send_slice_with_metadata(PAYLOAD, metadata), Ok(()));
+
+        assert_eq!(
+            socket.dispatch(&mut cx, |_, (ip_repr, _, _)| {
+                assert_eq!(ip_repr.hop_limit(), 64);
+                assert_eq!(ip_repr.traffic_class(), 0x28);
+                assert_eq!(ip_repr.flow_label(), Some(0x1234));
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+        assert_eq!(
+            socket.dispatch(&mut cx, |_, (ip_repr, _, _)| {
+                assert_eq!(ip_repr.hop_limit(), 0x2a);
+                assert_eq!(ip_repr.traffic_class(), 0xb8);
+                assert_eq!(ip_repr.flow_label(), Some(0x54321));
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+    }
+
     #[test]
     fn test_peek_process() {
         let mut socket = socket(buffer(1), buffer(0));
@@ -840,17 +1807,8 @@ mod test {
         s.set_hop_limit(Some(0x2a));
         assert_eq!(s.send_slice(b"abcdef", REMOTE_END), Ok(()));
         assert_eq!(
-            s.dispatch(&mut cx, |_, (ip_repr, _, _)| {
-                assert_eq!(
-                    ip_repr,
-                    IpReprIpvX(IpvXRepr {
-                        src_addr: LOCAL_ADDR,
-                        dst_addr: REMOTE_ADDR,
-                        next_header: IpProtocol::Udp,
-                        payload_len: 8 + 6,
-                        hop_limit: 0x2a,
-                    })
-                );
+            s.dispatch(&mut cx, |_, (repr, _, _)| {
+                assert_eq!(repr, ip_repr(LOCAL_ADDR, REMOTE_ADDR, 0x2a));
                 Ok::<_, ()>(())
             }),
             Ok(())
@@ -1,6 +1,18 @@
 use core::task::Waker;
 
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
+/// Number of wakers a registration holds when there is no allocator.
+#[cfg(not(feature = "alloc"))]
+const WAKER_REGISTRATION_SIZE: usize = 4;
+
+/// Utility struct to register and wake wakers, holding up to a fixed number of them.
+#[cfg(not(feature = "alloc"))]
+pub type WakerRegistration = BoundedWakerRegistration<WAKER_REGISTRATION_SIZE>;
+
 /// Linked list of wakers to be woken
+#[cfg(feature = "alloc")]
 #[derive(Debug)]
 struct WakerNext {
     waker: Waker,
@@ -8,6 +20,7 @@ struct WakerNext {
 }
 
 /// Utility struct to register and wake a waker.
+#[cfg(feature = "alloc")]
 #[derive(Debug)]
 pub struct WakerRegistration {
     waker: Option<WakerNext>,
@@ -15,6 +28,7 @@ pub struct WakerRegistration {
     verbose: bool,
 }
 
+#[cfg(feature = "alloc")]
 impl WakerRegistration {
     pub const fn new() -> Self {
         Self {
@@ -125,3 +139,164 @@ impl WakerRegistration {
         self
     }
 }
+
+/// Utility struct to register and wake up to `N` wakers, without allocating.
+///
+/// Adding a waker to a full registration wakes the registered wakers and replaces
+/// them with it; their tasks poll again and add their wakers again if they still wait.
+#[cfg(any(test, not(feature = "alloc")))]
+#[derive(Debug)]
+pub struct BoundedWakerRegistration<const N: usize> {
+    wakers: heapless::Vec<Waker, N>,
+    wake_on_add: bool,
+    verbose: bool,
+}
+
+#[cfg(any(test, not(feature = "alloc")))]
+impl<const N: usize> BoundedWakerRegistration<N> {
+    pub const fn new() -> Self {
+        Self {
+            wakers: heapless::Vec::new(),
+            wake_on_add: false,
+            verbose: false,
+        }
+    }
+
+    /// Register a waker. Overwrites the previous wakers, if any.
+    pub fn register(&mut self, w: &Waker) {
+        match self.wakers.first() {
+            // Keep the wakers if the first one wakes the same task, as the linked list
+            // registration does.
+            Some(w2) if w2.will_wake(w) => {}
+            _ => {
+                self.wakers.clear();
+                // The push only fails if the registration holds no wakers at all.
+                let _ = self.wakers.push(w.clone());
+            }
+        }
+    }
+
+    /// Adds a waker to the registered ones.
+    pub fn add(&mut self, w: &Waker) {
+        if !self.wakers.is_empty() {
+            if self.wakers.iter().any(|w2| w2.will_wake(w)) {
+                return;
+            }
+            if self.wakers.is_full() {
+                if self.verbose {
+                    net_trace!("wake_all: full");
+                }
+                self.wake_all_internal();
+            }
+            let _ = self.wakers.push(w.clone());
+            return;
+        }
+
+        self.register(w);
+        if self.wake_on_add {
+            if self.verbose {
+                net_trace!("wake_all: deferred");
+            }
+            self.wake_on_add = false;
+            self.wake_all_internal();
+        }
+    }
+
+    /// Wake all registered wakers, if any.
+    fn wake_all_internal(&mut self) {
+        for waker in self.wakers.iter() {
+            waker.wake_by_ref();
+        }
+        self.wakers.clear();
+    }
+
+    /// Wake all registered wakers, if any.
+    pub fn wake_all(&mut self) {
+        if !self.wakers.is_empty() {
+            if self.verbose {
+                net_trace!("wake_all: hit");
+            }
+            self.wake_all_internal();
+        } else {
+            if self.verbose {
+                net_trace!("wake_all: queued");
+            }
+            self.wake_on_add = true;
+        }
+    }
+
+    /// Clears all registered wakers without waking them
+    pub fn clear(&mut self) {
+        self.wake_on_add = false;
+        self.wakers.clear();
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn with_verbose_logging(mut self) -> Self {
+        self.verbose = true;
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    #[derive(Default)]
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.wake_by_ref()
+        }
+
+        fn wake_by_ref(self: &Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn counter() -> (Arc<Counter>, Waker) {
+        let counter = Arc::new(Counter::default());
+        (counter.clone(), Waker::from(counter))
+    }
+
+    fn count(counter: &Counter) -> usize {
+        counter.0.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn test_bounded_add() {
+        let mut registration = BoundedWakerRegistration::<2>::new();
+        let (a, waker_a) = counter();
+        let (b, waker_b) = counter();
+        let (c, waker_c) = counter();
+
+        registration.add(&waker_a);
+        registration.add(&waker_a);
+        registration.add(&waker_b);
+        // The registration is full, so the wakers are woken to make room.
+        registration.add(&waker_c);
+        assert_eq!((count(&a), count(&b), count(&c)), (1, 1, 0));
+
+        registration.wake_all();
+        assert_eq!((count(&a), count(&b), count(&c)), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_bounded_wake_on_add() {
+        let mut registration = BoundedWakerRegistration::<2>::new();
+        let (a, waker_a) = counter();
+
+        // A wake without wakers is delivered to the next waker added.
+        registration.wake_all();
+        registration.add(&waker_a);
+        assert_eq!(count(&a), 1);
+        registration.add(&waker_a);
+        registration.clear();
+        registration.wake_all();
+        assert_eq!(count(&a), 1);
+    }
+}
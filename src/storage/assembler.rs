@@ -67,7 +67,7 @@ impl Contig {
 }
 
 #[cfg(feature = "alloc")]
-use alloc::boxed::Box;
+use alloc::{boxed::Box, vec};
 #[cfg(feature = "alloc")]
 const CONTIG_COUNT: usize = 32;
 
@@ -76,14 +76,16 @@ const CONTIG_COUNT: usize = 4;
 
 /// A buffer (re)assembler.
 ///
-/// Currently, up to a hardcoded limit of 4 or 32 holes can be tracked in the buffer.
+/// By default, up to 4 or 32 holes (without or with the `alloc` feature) can be tracked in
+/// the buffer. With the `alloc` feature, a different limit can be chosen with
+/// [with_capacity](#method.with_capacity).
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Assembler {
     #[cfg(not(feature = "alloc"))]
     contigs: [Contig; CONTIG_COUNT],
     #[cfg(feature = "alloc")]
-    contigs: Box<[Contig; CONTIG_COUNT]>,
+    contigs: Box<[Contig]>,
 }
 
 impl fmt::Display for Assembler {
@@ -110,10 +112,29 @@ impl Assembler {
         #[cfg(not(feature = "alloc"))]
         let contigs = [Contig::empty(); CONTIG_COUNT];
         #[cfg(feature = "alloc")]
-        let contigs = Box::new([Contig::empty(); CONTIG_COUNT]);
+        let contigs = vec![Contig::empty(); CONTIG_COUNT].into_boxed_slice();
         Assembler { contigs }
     }
 
+    /// Create a new buffer assembler able to track up to `count` contiguous ranges.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `count` is zero.
+    #[cfg(feature = "alloc")]
+    pub fn with_capacity(count: usize) -> Assembler {
+        if count == 0 {
+            panic!("assembler must be able to track at least one contiguous range")
+        }
+        let contigs = vec![Contig::empty(); count].into_boxed_slice();
+        Assembler { contigs }
+    }
+
+    /// Return the maximum number of contiguous ranges that can be tracked.
+    pub fn capacity(&self) -> usize {
+        self.contigs.len()
+    }
+
     pub fn clear(&mut self) {
         self.contigs.fill(Contig::empty());
     }
@@ -361,7 +382,7 @@ mod test {
             #[cfg(not(feature = "alloc"))]
             let mut contigs = [Contig::empty(); CONTIG_COUNT];
             #[cfg(feature = "alloc")]
-            let mut contigs = Box::new([Contig::empty(); CONTIG_COUNT]);
+            let mut contigs = vec![Contig::empty(); CONTIG_COUNT].into_boxed_slice();
             for (i, &(hole_size, data_size)) in vec.iter().enumerate() {
                 contigs[i] = Contig {
                     hole_size,
@@ -480,6 +501,18 @@ mod test {
         assert_eq!(assr_before, assr);
     }
 
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_with_capacity() {
+        let mut assr = Assembler::with_capacity(2);
+        assert_eq!(assr.capacity(), 2);
+        assert_eq!(assr.add(10, 3), Ok(()));
+        assert_eq!(assr.add(20, 3), Ok(()));
+        assert_eq!(assr.add(30, 3), Err(TooManyHolesError));
+        // Filling in the front hole never needs an extra contig.
+        assert_eq!(assr.add_then_remove_front(0, 10), Ok(13));
+    }
+
     #[test]
     fn test_empty_remove_front() {
         let mut assr = contigs![];
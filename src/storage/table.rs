@@ -0,0 +1,208 @@
+use managed::ManagedSlice;
+
+/// Opaque struct with space for storing one entry of a [`Table`].
+///
+/// This is public so you can use it to allocate space for the tables of an
+/// Interface.
+#[derive(Debug)]
+pub struct TableStorage<T> {
+    inner: Option<T>,
+}
+
+impl<T> TableStorage<T> {
+    pub const EMPTY: Self = Self { inner: None };
+}
+
+/// A table of entries with a fixed capacity.
+///
+/// The entries are kept in the order they were added, and stored either in a
+/// caller-provided slice, or in memory allocated when the table is created.
+#[derive(Debug)]
+pub struct Table<'a, T: 'a> {
+    storage: ManagedSlice<'a, TableStorage<T>>,
+    len: usize,
+}
+
+impl<'a, T: 'a> Table<'a, T> {
+    /// Create a table with the given storage.
+    ///
+    /// During creation, every entry in `storage` is cleared.
+    pub fn new<S>(storage: S) -> Table<'a, T>
+    where
+        S: Into<ManagedSlice<'a, TableStorage<T>>>,
+    {
+        let mut storage = storage.into();
+        for slot in storage.iter_mut() {
+            slot.inner = None;
+        }
+        Table { storage, len: 0 }
+    }
+
+    /// Create a table holding up to `capacity` entries.
+    #[cfg(feature = "alloc")]
+    pub fn with_capacity(capacity: usize) -> Table<'a, T> {
+        let storage: alloc::vec::Vec<_> = (0..capacity).map(|_| TableStorage::EMPTY).collect();
+        Table::new(storage)
+    }
+
+    /// Return the maximum number of entries in the table.
+    pub fn capacity(&self) -> usize {
+        self.storage.len()
+    }
+
+    /// Return the number of entries in the table.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return whether the table is full.
+    pub fn is_full(&self) -> bool {
+        self.len == self.capacity()
+    }
+
+    /// Return the entry at `index`, if any.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.iter().nth(index)
+    }
+
+    /// Add an entry at the end of the table.
+    ///
+    /// Returns the entry back if the table is full.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+        self.storage[self.len].inner = Some(item);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Remove and return the entry at `index`, shifting the following entries.
+    ///
+    /// # Panics
+    /// This function panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "table index out of bounds");
+        let item = self.storage[index].inner.take().unwrap();
+        self.storage[index..self.len].rotate_left(1);
+        self.len -= 1;
+        item
+    }
+
+    /// Keep only the entries for which `f` returns true.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        let mut index = 0;
+        while index < self.len {
+            // NOTE(unwrap): the first `len` slots are always occupied.
+            if f(self.storage[index].inner.as_ref().unwrap()) {
+                index += 1;
+            } else {
+                self.remove(index);
+            }
+        }
+    }
+
+    /// Remove every entry.
+    pub fn clear(&mut self) {
+        for slot in self.storage[..self.len].iter_mut() {
+            slot.inner = None;
+        }
+        self.len = 0;
+    }
+
+    /// Return an iterator over the entries.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.storage[..self.len]
+            .iter()
+            .filter_map(|slot| slot.inner.as_ref())
+    }
+
+    /// Return an iterator over the entries, allowing to modify them.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.storage[..self.len]
+            .iter_mut()
+            .filter_map(|slot| slot.inner.as_mut())
+    }
+}
+
+/// Tables of key-value pairs can be used as maps, each key being present at most once.
+impl<'a, K: PartialEq + 'a, V: 'a> Table<'a, (K, V)> {
+    fn position(&self, key: &K) -> Option<usize> {
+        self.iter().position(|(k, _)| k == key)
+    }
+
+    /// Return the value of `key`, if any.
+    pub fn get_value(&self, key: &K) -> Option<&V> {
+        self.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// Return the value of `key`, if any, allowing to modify it.
+    pub fn get_value_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// Set the value of `key`.
+    ///
+    /// Returns the previous value, if any, or the pair back if the key is new and the
+    /// table is full.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        match self.get_value_mut(&key) {
+            Some(old) => Ok(Some(core::mem::replace(old, value))),
+            None => self.push((key, value)).map(|()| None),
+        }
+    }
+
+    /// Remove `key` and return its value, if any.
+    pub fn remove_key(&mut self, key: &K) -> Option<V> {
+        let index = self.position(key)?;
+        Some(self.remove(index).1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        let mut storage = [TableStorage::EMPTY; 3];
+        let mut table = Table::new(&mut storage[..]);
+        assert_eq!(table.capacity(), 3);
+        assert!(table.is_empty());
+
+        assert_eq!(table.push(1), Ok(()));
+        assert_eq!(table.push(2), Ok(()));
+        assert_eq!(table.push(3), Ok(()));
+        assert_eq!(table.push(4), Err(4));
+        assert!(table.is_full());
+
+        assert_eq!(table.remove(0), 1);
+        assert_eq!(table.get(0), Some(&2));
+        table.retain(|&item| item != 2);
+        assert_eq!(table.iter().copied().collect::<std::vec::Vec<_>>(), [3]);
+
+        table.clear();
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_table_map() {
+        let mut table = Table::with_capacity(2);
+        assert_eq!(table.insert(1, 'a'), Ok(None));
+        assert_eq!(table.insert(2, 'b'), Ok(None));
+        assert_eq!(table.insert(1, 'c'), Ok(Some('a')));
+        assert_eq!(table.insert(3, 'd'), Err((3, 'd')));
+        assert_eq!(table.get_value(&1), Some(&'c'));
+
+        assert_eq!(table.remove_key(&1), Some('c'));
+        assert_eq!(table.remove_key(&1), None);
+        assert_eq!(table.get_value(&2), Some(&'b'));
+        assert_eq!(table.insert(3, 'd'), Ok(None));
+    }
+}
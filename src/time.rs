@@ -1,13 +1,15 @@
 /*! Time structures.
 
 The `time` module contains structures used to represent both
-absolute and relative time.
+absolute and relative time, with a precision of one microsecond.
 
  - [Instant] is used to represent absolute time.
  - [Duration] is used to represent relative time.
+ - [Clock] is a source of the current time.
 
 [Instant]: struct.Instant.html
 [Duration]: struct.Duration.html
+[Clock]: trait.Clock.html
 */
 
 use core::{fmt, ops};
@@ -154,7 +156,11 @@ impl From<Instant> for ::std::time::SystemTime {
 
 impl fmt::Display for Instant {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}.{:0>3}s", self.secs(), self.millis())
+        if self.micros % 1000 == 0 {
+            write!(f, "{}.{:0>3}s", self.secs(), self.millis())
+        } else {
+            write!(f, "{}.{:0>6}s", self.secs(), self.micros())
+        }
     }
 }
 
@@ -250,7 +256,11 @@ impl Duration {
 
 impl fmt::Display for Duration {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}.{:03}s", self.secs(), self.millis())
+        if self.micros % 1000 == 0 {
+            write!(f, "{}.{:03}s", self.secs(), self.millis())
+        } else {
+            write!(f, "{}.{:06}s", self.secs(), self.micros())
+        }
     }
 }
 
@@ -357,6 +367,54 @@ impl From<Duration> for ::core::time::Duration {
     }
 }
 
+/// A monotonic source of the current time.
+///
+/// An interface can be polled with a clock instead of a timestamp, see
+/// [`Interface::poll_clock`](crate::iface::Interface::poll_clock), so that it can be
+/// driven from a hardware timer directly. Closures returning an [Instant] are clocks.
+pub trait Clock {
+    /// Return the current time. It must never be earlier than a time returned before.
+    fn now(&self) -> Instant;
+}
+
+impl<F: Fn() -> Instant> Clock for F {
+    fn now(&self) -> Instant {
+        self()
+    }
+}
+
+/// A clock counting the time elapsed since its creation, using the monotonic clock of
+/// the operating system.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct StdClock {
+    start: ::std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl StdClock {
+    /// Create a clock starting at [Instant::ZERO].
+    pub fn new() -> StdClock {
+        StdClock {
+            start: ::std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for StdClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now(&self) -> Instant {
+        Instant::from_micros(self.start.elapsed().as_micros() as i64)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -388,6 +446,8 @@ mod test {
         assert_eq!(format!("{}", Instant::from_millis(74)), "0.074s");
         assert_eq!(format!("{}", Instant::from_millis(5674)), "5.674s");
         assert_eq!(format!("{}", Instant::from_millis(5000)), "5.000s");
+        assert_eq!(format!("{}", Instant::from_micros(5000250)), "5.000250s");
+        assert_eq!(format!("{}", Duration::from_micros(1074001)), "1.074001s");
     }
 
     #[test]
@@ -467,4 +527,17 @@ mod test {
         std_duration = duration.into();
         assert_eq!(std_duration, ::core::time::Duration::from_millis(4934));
     }
+
+    #[test]
+    fn test_clock() {
+        let clock = || Instant::from_micros(1500);
+        assert_eq!(Clock::now(&clock), Instant::from_micros(1500));
+
+        #[cfg(feature = "std")]
+        {
+            let clock = StdClock::new();
+            let earlier = clock.now();
+            assert!(clock.now() >= earlier);
+        }
+    }
 }
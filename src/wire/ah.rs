@@ -0,0 +1,316 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+
+use super::{Error, IpProtocol, Result};
+
+/// A read/write wrapper around an Authentication Header packet buffer.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+// Format of the AH header, see https://www.rfc-editor.org/rfc/rfc4302#section-2
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// | Next Header   |  Payload Len  |          RESERVED             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                 Security Parameters Index (SPI)               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Sequence Number Field                      |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// +                Integrity Check Value-ICV (variable)           |
+// |                                                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+mod field {
+    use crate::wire::field::*;
+
+    pub const NEXT_HEADER: usize = 0;
+    pub const PAYLOAD_LEN: usize = 1;
+    pub const RESERVED: Field = 2..4;
+    pub const SPI: Field = 4..8;
+    pub const SEQUENCE_NUMBER: Field = 8..12;
+}
+
+/// The length of an AH header without its integrity check value.
+pub const HEADER_LEN: usize = field::SEQUENCE_NUMBER.end;
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Imbue a raw octet buffer with AH packet structure.
+    pub const fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short.
+    ///
+    /// The result of this check is invalidated by calling [set_header_len].
+    ///
+    /// [set_header_len]: #method.set_header_len
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < HEADER_LEN || len < self.header_len() || self.header_len() < HEADER_LEN {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the Next Header field.
+    #[inline]
+    pub fn next_header(&self) -> IpProtocol {
+        let data = self.buffer.as_ref();
+        IpProtocol::from(data[field::NEXT_HEADER])
+    }
+
+    /// Return the length of the header, including the integrity check value, in octets.
+    ///
+    /// The Payload Len field gives the length of the header in 32-bit words, minus 2.
+    #[inline]
+    pub fn header_len(&self) -> usize {
+        let data = self.buffer.as_ref();
+        (data[field::PAYLOAD_LEN] as usize + 2) * 4
+    }
+
+    /// Return the Security Parameters Index field.
+    #[inline]
+    pub fn spi(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u32(&data[field::SPI])
+    }
+
+    /// Return the Sequence Number field.
+    #[inline]
+    pub fn sequence_number(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u32(&data[field::SEQUENCE_NUMBER])
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
+    /// Return a pointer to the integrity check value, including its padding.
+    #[inline]
+    pub fn icv(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[HEADER_LEN..self.header_len()]
+    }
+
+    /// Return a pointer to the payload.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[self.header_len()..]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the Next Header field.
+    #[inline]
+    pub fn set_next_header(&mut self, value: IpProtocol) {
+        let data = self.buffer.as_mut();
+        data[field::NEXT_HEADER] = value.into()
+    }
+
+    /// Set the length of the header, including the integrity check value, in octets, and
+    /// clear the reserved field.
+    ///
+    /// # Panics
+    /// This function panics if the length is not a multiple of 4 between 12 and 1028.
+    #[inline]
+    pub fn set_header_len(&mut self, value: usize) {
+        assert!(value % 4 == 0 && (HEADER_LEN..=(255 + 2) * 4).contains(&value));
+        let data = self.buffer.as_mut();
+        data[field::PAYLOAD_LEN] = (value / 4 - 2) as u8;
+        data[field::RESERVED].fill(0);
+    }
+
+    /// Set the Security Parameters Index field.
+    #[inline]
+    pub fn set_spi(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::SPI], value)
+    }
+
+    /// Set the Sequence Number field.
+    #[inline]
+    pub fn set_sequence_number(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::SEQUENCE_NUMBER], value)
+    }
+
+    /// Return a mutable pointer to the integrity check value, including its padding.
+    #[inline]
+    pub fn icv_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        let data = self.buffer.as_mut();
+        &mut data[HEADER_LEN..header_len]
+    }
+
+    /// Return a mutable pointer to the payload.
+    #[inline]
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        let data = self.buffer.as_mut();
+        &mut data[header_len..]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+/// A high-level representation of an Authentication Header.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Repr {
+    pub next_header: IpProtocol,
+    pub spi: u32,
+    pub sequence_number: u32,
+    /// The length of the integrity check value, including its padding.
+    pub icv_len: usize,
+}
+
+impl Repr {
+    /// Parse an AH packet and return a high-level representation.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(packet: &Packet<&T>) -> Result<Repr> {
+        packet.check_len()?;
+        // The SPI 0 is reserved for local use, and must never be sent on the wire.
+        if packet.spi() == 0 {
+            return Err(Error);
+        }
+        Ok(Repr {
+            next_header: packet.next_header(),
+            spi: packet.spi(),
+            sequence_number: packet.sequence_number(),
+            icv_len: packet.header_len() - HEADER_LEN,
+        })
+    }
+
+    /// Return the length of a header that will be emitted from this high-level representation.
+    pub const fn buffer_len(&self) -> usize {
+        HEADER_LEN + self.icv_len
+    }
+
+    /// Emit a high-level representation into an AH packet, with an integrity check value
+    /// of zeros.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) {
+        packet.set_next_header(self.next_header);
+        packet.set_header_len(self.buffer_len());
+        packet.set_spi(self.spi);
+        packet.set_sequence_number(self.sequence_number);
+        packet.icv_mut().fill(0);
+    }
+}
+
+impl<T: AsRef<[u8]>> fmt::Display for Packet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Repr::parse(&Packet::new_unchecked(self.buffer.as_ref())) {
+            Ok(repr) => write!(f, "{repr}"),
+            Err(err) => write!(f, "AH ({err})"),
+        }
+    }
+}
+
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "AH next_header={} spi=0x{:08x} seq={} icv_len={}",
+            self.next_header, self.spi, self.sequence_number, self.icv_len
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static PACKET_BYTES: [u8; 28] = [
+        0x11, 0x04, 0x00, 0x00, 0x00, 0x00, 0x10, 0x01, 0x00, 0x00, 0x00, 0x2a, 0x01, 0x02, 0x03,
+        0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0xde, 0xad, 0xbe, 0xef,
+    ];
+
+    static ICV_BYTES: [u8; 12] = [
+        0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c,
+    ];
+
+    static PAYLOAD_BYTES: [u8; 4] = [0xde, 0xad, 0xbe, 0xef];
+
+    #[test]
+    fn test_deconstruct() {
+        let packet = Packet::new_checked(&PACKET_BYTES[..]).unwrap();
+        assert_eq!(packet.next_header(), IpProtocol::Udp);
+        assert_eq!(packet.header_len(), 24);
+        assert_eq!(packet.spi(), 0x1001);
+        assert_eq!(packet.sequence_number(), 42);
+        assert_eq!(packet.icv(), &ICV_BYTES[..]);
+        assert_eq!(packet.payload(), &PAYLOAD_BYTES[..]);
+    }
+
+    #[test]
+    fn test_construct() {
+        let mut bytes = vec![0xa5; 28];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_next_header(IpProtocol::Udp);
+        packet.set_header_len(24);
+        packet.set_spi(0x1001);
+        packet.set_sequence_number(42);
+        packet.icv_mut().copy_from_slice(&ICV_BYTES[..]);
+        packet.payload_mut().copy_from_slice(&PAYLOAD_BYTES[..]);
+        assert_eq!(&*packet.into_inner(), &PACKET_BYTES[..]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let packet = Packet::new_unchecked(&PACKET_BYTES[..]);
+        assert_eq!(
+            Repr::parse(&packet),
+            Ok(Repr {
+                next_header: IpProtocol::Udp,
+                spi: 0x1001,
+                sequence_number: 42,
+                icv_len: 12
+            })
+        );
+
+        let mut bytes = PACKET_BYTES;
+        bytes[6..8].fill(0);
+        assert_eq!(Repr::parse(&Packet::new_unchecked(&bytes[..])), Err(Error));
+        assert_eq!(Packet::new_checked(&PACKET_BYTES[..20]), Err(Error));
+    }
+
+    #[test]
+    fn test_emit() {
+        let repr = Repr {
+            next_header: IpProtocol::Udp,
+            spi: 0x1001,
+            sequence_number: 42,
+            icv_len: 12,
+        };
+        let mut bytes = vec![0xa5; 28];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet);
+        assert_eq!(packet.icv_mut(), &[0; 12][..]);
+        packet.icv_mut().copy_from_slice(&ICV_BYTES[..]);
+        packet.payload_mut().copy_from_slice(&PAYLOAD_BYTES[..]);
+        assert_eq!(&*packet.into_inner(), &PACKET_BYTES[..]);
+    }
+}
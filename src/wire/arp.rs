@@ -16,7 +16,19 @@ enum_with_unknown! {
     /// ARP operation type.
     pub enum Operation(u16) {
         Request = 1,
-        Reply = 2
+        Reply = 2,
+        /// Reverse ARP request, see [RFC 903](https://www.rfc-editor.org/rfc/rfc903).
+        RequestReverse = 3,
+        /// Reverse ARP reply.
+        ReplyReverse = 4
+    }
+}
+
+impl Operation {
+    /// Return whether the operation is a Reverse ARP one, carried with the
+    /// [RARP](super::EthernetProtocol::Rarp) EtherType rather than the ARP one.
+    pub const fn is_reverse(&self) -> bool {
+        matches!(self, Operation::RequestReverse | Operation::ReplyReverse)
     }
 }
 
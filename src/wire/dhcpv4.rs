@@ -136,6 +136,74 @@ pub struct DhcpOption<'a> {
     pub data: &'a [u8],
 }
 
+/// The contents of a Relay Agent Information option (option 82, RFC 3046).
+///
+/// Relay agents add this option to the messages they forward to a server, to tell it
+/// which port of the relay a client is attached to. Servers key leases by these
+/// identifiers to implement per-port address assignment policies, and must echo the
+/// option back in their replies. Both identifiers are opaque.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RelayAgentInfo<'a> {
+    /// The Agent Circuit ID sub-option, identifying the circuit the request came in on.
+    pub circuit_id: Option<&'a [u8]>,
+    /// The Agent Remote ID sub-option, identifying the remote host end of the circuit.
+    pub remote_id: Option<&'a [u8]>,
+}
+
+impl<'a> RelayAgentInfo<'a> {
+    /// Parse the data of a Relay Agent Information option.
+    ///
+    /// Unknown sub-options are skipped. Returns `Err(Error)` if a sub-option is truncated.
+    pub fn parse(mut data: &'a [u8]) -> Result<Self> {
+        let mut info = Self::default();
+        while !data.is_empty() {
+            if data.len() < 2 || data.len() < 2 + data[1] as usize {
+                return Err(Error);
+            }
+            let (kind, value) = (data[0], &data[2..2 + data[1] as usize]);
+            match kind {
+                field::SUBOPT_AGENT_CIRCUIT_ID => info.circuit_id = Some(value),
+                field::SUBOPT_AGENT_REMOTE_ID => info.remote_id = Some(value),
+                _ => {}
+            }
+            data = &data[2 + value.len()..];
+        }
+        Ok(info)
+    }
+
+    /// Return the length of the option data emitted from this representation.
+    pub fn buffer_len(&self) -> usize {
+        [self.circuit_id, self.remote_id]
+            .iter()
+            .flatten()
+            .map(|value| 2 + value.len())
+            .sum()
+    }
+
+    /// Emit the option data into a buffer of at least [`buffer_len`](#method.buffer_len)
+    /// octets, to be sent as the data of an option of kind
+    /// `field::OPT_RELAY_AGENT_INFORMATION`.
+    ///
+    /// Returns `Err(Error)` if a sub-option is longer than 255 octets.
+    pub fn emit(&self, buffer: &mut [u8]) -> Result<()> {
+        let mut buffer = buffer;
+        for (kind, value) in [
+            (field::SUBOPT_AGENT_CIRCUIT_ID, self.circuit_id),
+            (field::SUBOPT_AGENT_REMOTE_ID, self.remote_id),
+        ] {
+            if let Some(value) = value {
+                let len = u8::try_from(value.len()).map_err(|_| Error)?;
+                buffer[0] = kind;
+                buffer[1] = len;
+                buffer[2..2 + value.len()].copy_from_slice(value);
+                buffer = &mut buffer[2 + value.len()..];
+            }
+        }
+        Ok(())
+    }
+}
+
 /// A read/write wrapper around a Dynamic Host Configuration Protocol packet buffer.
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -255,6 +323,14 @@ pub(crate) mod field {
     pub const OPT_REBINDING_TIME_VALUE: u8 = 59;
     pub const OPT_VENDOR_CLASS_ID: u8 = 60;
     pub const OPT_CLIENT_ID: u8 = 61;
+
+    // Relay Agent Information (RFC 3046)
+    pub const OPT_RELAY_AGENT_INFORMATION: u8 = 82;
+    pub const SUBOPT_AGENT_CIRCUIT_ID: u8 = 1;
+    pub const SUBOPT_AGENT_REMOTE_ID: u8 = 2;
+
+    // Captive-Portal Identification in DHCP and Router Advertisements (RFC 8910)
+    pub const OPT_CAPTIVE_PORTAL: u8 = 114;
 }
 
 impl<T: AsRef<[u8]>> Packet<T> {
@@ -421,6 +497,22 @@ impl<T: AsRef<[u8]>> Packet<T> {
         })
     }
 
+    /// Return an iterator over the options [`Repr::parse`] does not represent, such as
+    /// vendor-specific information or site-specific options.
+    #[inline]
+    pub fn additional_options(&self) -> impl Iterator<Item = DhcpOption<'_>> + '_ {
+        self.options()
+            .filter(|option| !Repr::represents(option.kind))
+    }
+
+    /// Return the Relay Agent Information option added by a relay agent, if there is
+    /// a well-formed one.
+    pub fn relay_agent_info(&self) -> Option<RelayAgentInfo<'_>> {
+        self.options()
+            .find(|option| option.kind == field::OPT_RELAY_AGENT_INFORMATION)
+            .and_then(|option| RelayAgentInfo::parse(option.data).ok())
+    }
+
     pub fn get_sname(&self) -> Result<&str> {
         let data = &self.buffer.as_ref()[field::SNAME];
         let len = data.iter().position(|&x| x == 0).ok_or(Error)?;
@@ -681,6 +773,8 @@ pub struct Repr<'a> {
     pub renew_duration: Option<u32>,
     /// The DHCP IP rebind duration (T2 interval), in seconds, if specified in the packet.
     pub rebind_duration: Option<u32>,
+    /// The URI of the captive portal API of the network.
+    pub captive_portal: Option<&'a str>,
     /// When returned from [`Repr::parse`], this field will be `None`.
     /// However, when calling [`Repr::emit`], this field should contain only
     /// additional DHCP options not known to smoltcp.
@@ -721,6 +815,9 @@ impl<'a> Repr<'a> {
         if let Some(list) = self.parameter_request_list {
             len += list.len() + 2;
         }
+        if let Some(uri) = self.captive_portal {
+            len += uri.len() + 2;
+        }
         for opt in self.additional_options {
             len += 2 + opt.data.len()
         }
@@ -728,7 +825,30 @@ impl<'a> Repr<'a> {
         len
     }
 
+    /// Return whether options of this kind are represented by the fields of [`Repr`].
+    const fn represents(kind: u8) -> bool {
+        matches!(
+            kind,
+            field::OPT_DHCP_MESSAGE_TYPE
+                | field::OPT_REQUESTED_IP
+                | field::OPT_CLIENT_ID
+                | field::OPT_SERVER_IDENTIFIER
+                | field::OPT_ROUTER
+                | field::OPT_SUBNET_MASK
+                | field::OPT_MAX_DHCP_MESSAGE_SIZE
+                | field::OPT_RENEWAL_TIME_VALUE
+                | field::OPT_REBINDING_TIME_VALUE
+                | field::OPT_IP_LEASE_TIME
+                | field::OPT_PARAMETER_REQUEST_LIST
+                | field::OPT_DOMAIN_NAME_SERVER
+                | field::OPT_CAPTIVE_PORTAL
+        )
+    }
+
     /// Parse a DHCP packet and return a high-level representation.
+    ///
+    /// The options it does not represent are available through
+    /// [`Packet::additional_options`].
     pub fn parse<T>(packet: &'a Packet<&'a T>) -> Result<Self>
     where
         T: AsRef<[u8]> + ?Sized,
@@ -767,6 +887,7 @@ impl<'a> Repr<'a> {
         let mut lease_duration = None;
         let mut renew_duration = None;
         let mut rebind_duration = None;
+        let mut captive_portal = None;
 
         for option in packet.options() {
             let data = option.data;
@@ -814,7 +935,7 @@ impl<'a> Repr<'a> {
                 (field::OPT_DOMAIN_NAME_SERVER, _) => {
                     let mut servers = Vec::new();
                     const IP_ADDR_BYTE_LEN: usize = 4;
-                    for chunk in data.chunks(IP_ADDR_BYTE_LEN) {
+                    for chunk in data.chunks_exact(IP_ADDR_BYTE_LEN) {
                         // We ignore push failures because that will only happen
                         // if we attempt to push more than 4 addresses, and the only
                         // solution to that is to support more addresses.
@@ -822,6 +943,9 @@ impl<'a> Repr<'a> {
                     }
                     dns_servers = Some(servers);
                 }
+                (field::OPT_CAPTIVE_PORTAL, _) => {
+                    captive_portal = core::str::from_utf8(data).ok();
+                }
                 _ => {}
             }
         }
@@ -848,6 +972,7 @@ impl<'a> Repr<'a> {
             lease_duration,
             renew_duration,
             rebind_duration,
+            captive_portal,
             message_type: message_type?,
             additional_options: &[],
         })
@@ -941,6 +1066,12 @@ impl<'a> Repr<'a> {
                     data: val,
                 })?;
             }
+            if let Some(uri) = self.captive_portal {
+                options.emit(DhcpOption {
+                    kind: field::OPT_CAPTIVE_PORTAL,
+                    data: uri.as_bytes(),
+                })?;
+            }
 
             if let Some(dns_servers) = &self.dns_servers {
                 const IP_SIZE: usize = core::mem::size_of::<u32>();
@@ -1002,6 +1133,9 @@ impl<'a> fmt::Display for Repr<'a> {
         if let Some(lease_duration) = self.lease_duration.as_ref() {
             write!(f, " lease-duration={}", lease_duration)?;
         }
+        if let Some(uri) = self.captive_portal {
+            write!(f, " captive-portal={}", uri)?;
+        }
         Ok(())
     }
 }
@@ -1242,6 +1376,7 @@ mod test {
             renew_duration: None,
             rebind_duration: None,
             lease_duration: Some(0xffff_ffff), // Infinite lease
+            captive_portal: None,
             additional_options: &[],
         }
     }
@@ -1268,6 +1403,7 @@ mod test {
             server_identifier: None,
             parameter_request_list: Some(&[1, 3, 6, 42]),
             dns_servers: None,
+            captive_portal: None,
             additional_options: &[],
         }
     }
@@ -1301,6 +1437,72 @@ mod test {
         repr.emit(&mut packet).unwrap();
     }
 
+    #[test]
+    fn test_emit_offer_captive_portal() {
+        let repr = Repr {
+            captive_portal: Some("https://portal.example.net/api"),
+            ..offer_repr()
+        };
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet).unwrap();
+
+        let packet = Packet::new_unchecked(&bytes);
+        let repr_parsed = Repr::parse(&packet).unwrap();
+        assert_eq!(repr_parsed, repr);
+    }
+
+    #[test]
+    fn test_additional_options() {
+        static VENDOR: &[DhcpOption] = &[DhcpOption {
+            kind: 43,
+            data: &[1, 4, 0, 0, 0, 1],
+        }];
+        let repr = Repr {
+            captive_portal: Some("https://portal.example.net/api"),
+            additional_options: VENDOR,
+            ..offer_repr()
+        };
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet).unwrap();
+
+        let packet = Packet::new_unchecked(&bytes);
+        assert!(packet.additional_options().eq(VENDOR.iter().copied()));
+    }
+
+    #[test]
+    fn test_relay_agent_info() {
+        let info = RelayAgentInfo {
+            circuit_id: Some(b"eth0:12"),
+            remote_id: Some(&[0x02, 0x00, 0x00, 0x00, 0x00, 0x01]),
+        };
+        let mut data = vec![0xa5; info.buffer_len()];
+        info.emit(&mut data).unwrap();
+        assert_eq!(&data[..9], b"\x01\x07eth0:12");
+
+        let options = [DhcpOption {
+            kind: field::OPT_RELAY_AGENT_INFORMATION,
+            data: &data,
+        }];
+        let repr = Repr {
+            additional_options: &options,
+            ..offer_repr()
+        };
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        repr.emit(&mut Packet::new_unchecked(&mut bytes)).unwrap();
+        assert_eq!(Packet::new_unchecked(&bytes).relay_agent_info(), Some(info));
+
+        assert_eq!(RelayAgentInfo::parse(&[0x01, 0x03, 0x00]), Err(Error));
+        assert_eq!(
+            RelayAgentInfo::parse(&[0x09, 0x01, 0x00, 0x02, 0x01, 0x07]),
+            Ok(RelayAgentInfo {
+                circuit_id: None,
+                remote_id: Some(&[0x07]),
+            })
+        );
+    }
+
     #[test]
     fn test_emit_offer_dns() {
         let repr = {
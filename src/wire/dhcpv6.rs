@@ -1,11 +1,11 @@
 // See https://datatracker.ietf.org/doc/html/rfc8415 for the DHCPv6 specification.
 
-use alloc::borrow::Cow;
 use byteorder::{ByteOrder, NetworkEndian};
-use core::{iter, fmt};
+use core::{fmt, iter};
 use heapless::Vec;
 
 use super::{Error, Result};
+use crate::wire::Ipv6Address;
 
 pub const SERVER_PORT: u16 = 547;
 pub const CLIENT_PORT: u16 = 546;
@@ -151,7 +151,13 @@ pub(crate) mod field {
     pub const MTYPE: usize = 0;
     pub const XID: Field = 1..4;
     pub const OPTIONS: Rest = 4..;
-    
+
+    // Relay-forward and Relay-reply messages replace the transaction ID with these.
+    pub const HOP_COUNT: usize = 1;
+    pub const LINK_ADDRESS: Field = 2..18;
+    pub const PEER_ADDRESS: Field = 18..34;
+    pub const RELAY_OPTIONS: Rest = 34..;
+
     // The Client Identifier option is used to carry a DUID (see Section 11)
     // that identifies the client.  The format of the Client Identifier
     // option is:
@@ -1513,6 +1519,10 @@ pub(crate) mod field {
     // An updated INF_MAX_RT value applies only to the network interface on
     // which the client received the INF_MAX_RT option.
     pub const OPT_INF_MAX_RT: u16 = 83;
+
+    // The Captive-Portal option (RFC 8910) contains the URI of the captive portal API
+    // of the network, as UTF-8 without a terminating NUL character.
+    pub const OPT_CAPTIVE_PORTAL: u16 = 103;
 }
 
 impl<T: AsRef<[u8]>> Packet<T> {
@@ -1564,6 +1574,64 @@ impl<T: AsRef<[u8]>> Packet<T> {
     pub fn options(&self) -> impl Iterator<Item = Dhcpv6Option<'_>> + '_ {
         parse_options(&self.buffer.as_ref()[field::OPTIONS])
     }
+
+    /// Return an iterator over the options [`Repr::parse`] does not represent, such as
+    /// vendor-specific information or options defined after this implementation.
+    #[inline]
+    pub fn additional_options(&self) -> impl Iterator<Item = Dhcpv6Option<'_>> + '_ {
+        self.options()
+            .filter(|option| !Repr::represents(option.kind))
+    }
+
+    /// Ensure that no relay accessor method will panic if called on a Relay-forward or
+    /// Relay-reply message.
+    /// Returns `Err(Error)` if the buffer is too short.
+    pub fn check_relay_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < field::RELAY_OPTIONS.start {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the number of relay agents that have already relayed the message.
+    pub fn relay_hop_count(&self) -> u8 {
+        self.buffer.as_ref()[field::HOP_COUNT]
+    }
+
+    /// Returns the address identifying the link the client is on.
+    pub fn relay_link_address(&self) -> Ipv6Address {
+        Ipv6Address::from_bytes(&self.buffer.as_ref()[field::LINK_ADDRESS])
+    }
+
+    /// Returns the address of the client or relay agent the message was received from.
+    pub fn relay_peer_address(&self) -> Ipv6Address {
+        Ipv6Address::from_bytes(&self.buffer.as_ref()[field::PEER_ADDRESS])
+    }
+
+    /// Return an iterator over the options of a Relay-forward or Relay-reply message.
+    #[inline]
+    pub fn relay_options(&self) -> impl Iterator<Item = Dhcpv6Option<'_>> + '_ {
+        parse_options(&self.buffer.as_ref()[field::RELAY_OPTIONS])
+    }
+
+    /// Return the message carried by a Relay-forward or Relay-reply message, if any.
+    pub fn relay_message(&self) -> Option<&[u8]> {
+        self.relay_options()
+            .find(|option| option.kind == field::OPT_RELAY_MSG)
+            .map(|option| option.data)
+    }
+
+    /// Return the Interface-Id a relay agent added to a Relay-forward message, if any.
+    ///
+    /// Servers may key leases by this opaque value to assign addresses per relay port,
+    /// and must copy it unchanged into the Relay-reply message.
+    pub fn interface_id(&self) -> Option<&[u8]> {
+        self.relay_options()
+            .find(|option| option.kind == field::OPT_INTERFACE_ID)
+            .map(|option| option.data)
+    }
 }
 
 /// Return an iterator over the options.
@@ -1678,7 +1746,10 @@ pub struct Repr<'a> {
     pub ia_ta: Option<ReprIaTa<'a>>,
     /// DNS Servers
     pub dns_servers: Option<ReprDnsServers>,
-    /// When returned from [`Repr::parse`], this field will be `None`.
+    /// The URI of the captive portal API of the network
+    pub captive_portal: Option<&'a str>,
+    /// When returned from [`Repr::parse`], this field will be empty, the options
+    /// not known to smoltcp are iterated with [`Packet::additional_options`] instead.
     /// However, when calling [`Repr::emit`], this field should contain only
     /// additional DHCP options not known to smoltcp.
     pub additional_options: &'a [Dhcpv6Option<'a>],
@@ -1707,6 +1778,9 @@ impl<'a> Repr<'a> {
         if let Some(dns) = self.dns_servers.as_ref() {
             len += 4 + dns.data_len();
         }
+        if let Some(uri) = self.captive_portal {
+            len += 4 + uri.len();
+        }
         if let Some(options) = self.request_options.as_ref() {
             len += 4;
             for _ in options {
@@ -1720,12 +1794,32 @@ impl<'a> Repr<'a> {
         len
     }
 
+    /// Return whether options of this kind are represented by the fields of [`Repr`].
+    const fn represents(kind: u16) -> bool {
+        matches!(
+            kind,
+            field::OPT_CLIENTID
+                | field::OPT_SERVERID
+                | field::OPT_ELAPSED_TIME
+                | field::OPT_IA_NA
+                | field::OPT_IA_TA
+                | field::OPT_DNS_SERVERS
+                | field::OPT_ORO
+                | field::OPT_CAPTIVE_PORTAL
+        )
+    }
+
     /// Parse a DHCP packet and return a high-level representation.
-    pub fn parse<T>(packet: &'a Packet<&'a T>) -> Result<Self>
+    ///
+    /// The representation borrows the buffer of the packet rather than the packet itself.
+    /// The options it does not represent are available through
+    /// [`Packet::additional_options`].
+    pub fn parse<T>(packet: &Packet<&'a T>) -> Result<Self>
     where
         T: AsRef<[u8]> + ?Sized,
     {
         let transaction_id = packet.transaction_id();
+        let buffer: &'a T = packet.buffer;
 
         let message_type = MessageType::from(packet.buffer.as_ref()[field::MTYPE]);
         let mut client_id = None;
@@ -1735,8 +1829,9 @@ impl<'a> Repr<'a> {
         let mut ia_ta = None;
         let mut dns_servers = None;
         let mut request_options = None;
+        let mut captive_portal = None;
 
-        for option in packet.options() {
+        for option in parse_options(&buffer.as_ref()[field::OPTIONS]) {
             let data = option.data;
             match (option.kind, data.len()) {
                 (field::OPT_CLIENTID, _) => {
@@ -1765,12 +1860,15 @@ impl<'a> Repr<'a> {
                 }
                 (field::OPT_ORO, _) => {
                     let mut options = Vec::new();
-                    const REQUEST_OPTION_BYTE_LEN: usize = 4;
-                    for chunk in data.chunks(REQUEST_OPTION_BYTE_LEN) {
+                    const REQUEST_OPTION_BYTE_LEN: usize = 2;
+                    for chunk in data.chunks_exact(REQUEST_OPTION_BYTE_LEN) {
                         options.push(NetworkEndian::read_u16(chunk)).ok();
                     }
                     request_options = Some(options);
                 }
+                (field::OPT_CAPTIVE_PORTAL, _) => {
+                    captive_portal = core::str::from_utf8(data).ok();
+                }
                 _ => {}
             }
         }
@@ -1785,6 +1883,7 @@ impl<'a> Repr<'a> {
             ia_na,
             ia_ta,
             dns_servers,
+            captive_portal,
             additional_options: &[],
         })
     }
@@ -1834,6 +1933,13 @@ impl<'a> Repr<'a> {
                 dns.emit(&mut dhcp_options)?;
             }
 
+            if let Some(uri) = self.captive_portal {
+                dhcp_options.emit(Dhcpv6Option {
+                    kind: field::OPT_CAPTIVE_PORTAL,
+                    data: uri.as_bytes(),
+                })?;
+            }
+
             if let Some(request_options) = &self.request_options {
                 const REQUEST_OPTION_SIZE: usize = core::mem::size_of::<u16>();
                 let mut options = [0; MAX_REQUEST_OPTIONS * REQUEST_OPTION_SIZE];
@@ -1906,6 +2012,9 @@ impl<'a> fmt::Display for Repr<'a> {
         if let Some(dns_servers) = self.dns_servers.as_ref() {
             write!(f, " dns-servers {dns_servers}")?;
         }
+        if let Some(uri) = self.captive_portal {
+            write!(f, " captive-portal={uri}")?;
+        }
         Ok(())
     }
 }
@@ -1945,8 +2054,10 @@ impl<'a> ReprIaNa<'a> {
         len
     }
 
-    pub fn parse(mut data: &'a [u8]) -> Result<Self>
-    {
+    pub fn parse(mut data: &'a [u8]) -> Result<Self> {
+        if data.len() < 12 {
+            return Err(Error);
+        }
         let iaid = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
         data = &data[4..];
         let t1 = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
@@ -2064,8 +2175,10 @@ impl<'a> ReprIaTa<'a> {
         len
     }
 
-    pub fn parse(mut data: &'a [u8]) -> Result<Self>
-    {
+    pub fn parse(mut data: &'a [u8]) -> Result<Self> {
+        if data.len() < 4 {
+            return Err(Error);
+        }
         let iaid = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
         data = &data[4..];
         
@@ -2183,8 +2296,10 @@ impl<'a> ReprIaAddr<'a> {
         len
     }
 
-    pub fn parse(mut data: &'a [u8]) -> Result<Self>
-    {
+    pub fn parse(mut data: &'a [u8]) -> Result<Self> {
+        if data.len() < 24 {
+            return Err(Error);
+        }
         let addr = super::ipv6::Address::from_bytes(&data[0..16]);
         data = &data[16..];
         let preferred_lifetime = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
@@ -2302,8 +2417,10 @@ impl<'a> ReprIaPrefix<'a> {
         len
     }
 
-    pub fn parse(mut data: &'a [u8]) -> Result<Self>
-    {
+    pub fn parse(mut data: &'a [u8]) -> Result<Self> {
+        if data.len() < 25 {
+            return Err(Error);
+        }
         let preferred_lifetime = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
         data = &data[4..];
         let valid_lifetime = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
@@ -2417,7 +2534,60 @@ impl fmt::Display for StatusCode {
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ReprStatusCode<'a> {
     pub status_code: StatusCode,
-    pub status_message: Cow<'a, str>,
+    pub status_message: StatusMessage<'a>,
+}
+
+/// The message of a status code option.
+///
+/// The message should be UTF-8, but is kept as received; invalid sequences are
+/// displayed as U+FFFD REPLACEMENT CHARACTER.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct StatusMessage<'a>(pub &'a [u8]);
+
+impl<'a> StatusMessage<'a> {
+    /// Return the message as bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Return the message as a string, if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        core::str::from_utf8(self.0).ok()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<'a> defmt::Format for StatusMessage<'a> {
+    fn format(&self, f: defmt::Formatter) {
+        match self.as_str() {
+            Some(message) => defmt::write!(f, "{=str}", message),
+            None => defmt::write!(f, "{=[u8]}", self.0),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for StatusMessage<'a> {
+    fn from(message: &'a str) -> Self {
+        StatusMessage(message.as_bytes())
+    }
+}
+
+impl<'a> fmt::Display for StatusMessage<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rest = self.0;
+        loop {
+            match core::str::from_utf8(rest) {
+                Ok(valid) => return f.write_str(valid),
+                Err(err) => {
+                    let (valid, invalid) = rest.split_at(err.valid_up_to());
+                    // NOTE(unwrap): the bytes were just checked to be valid.
+                    f.write_str(core::str::from_utf8(valid).unwrap())?;
+                    f.write_str("\u{FFFD}")?;
+                    rest = &invalid[err.error_len().unwrap_or(invalid.len())..];
+                }
+            }
+        }
+    }
 }
 
 impl<'a> ReprStatusCode<'a> {
@@ -2437,7 +2607,7 @@ impl<'a> ReprStatusCode<'a> {
 
         Ok(Self {
             status_code: StatusCode::from(status_code),
-            status_message: String::from_utf8_lossy(&data[2..])
+            status_message: StatusMessage(&data[2..]),
         })
     }
 
@@ -2546,4 +2716,90 @@ impl<T: AsRef<[u8]>> PrettyPrint for Packet<T> {
         };
         write!(f, "{indent}{packet}")
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_status_code() {
+        let repr = ReprStatusCode::parse(b"\x00\x02no addrs\xff").unwrap();
+        assert_eq!(repr.status_code, StatusCode::from(2));
+        assert_eq!(repr.status_message.as_bytes(), b"no addrs\xff");
+        assert_eq!(repr.status_message.as_str(), None);
+        assert_eq!(format!("{}", repr.status_message), "no addrs\u{FFFD}");
+
+        let message = StatusMessage::from("ok");
+        assert_eq!(message.as_str(), Some("ok"));
+        assert_eq!(format!("{message}"), "ok");
+    }
+
+    #[test]
+    fn test_relay_forward() {
+        let bytes = [
+            0x0c, 0x01, // relay-forw, hop count
+            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, // link
+            0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02, // peer
+            0x00, 0x12, 0x00, 0x04, b'p', b'o', b'r', b't', // interface-id
+            0x00, 0x09, 0x00, 0x04, // relay message
+            0x01, 0x12, 0x34, 0x56, // solicit, transaction id
+        ];
+
+        let packet = Packet::new_unchecked(&bytes);
+        assert_eq!(packet.check_relay_len(), Ok(()));
+        assert_eq!(packet.relay_hop_count(), 1);
+        assert_eq!(
+            packet.relay_link_address(),
+            Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)
+        );
+        assert_eq!(
+            packet.relay_peer_address(),
+            Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2)
+        );
+        assert_eq!(packet.interface_id(), Some(&b"port"[..]));
+
+        let inner = Packet::new_checked(packet.relay_message().unwrap()).unwrap();
+        assert_eq!(inner.transaction_id(), 0x123456);
+
+        assert_eq!(
+            Packet::new_unchecked(&bytes[..33]).check_relay_len(),
+            Err(Error)
+        );
+    }
+
+    #[test]
+    fn test_additional_options() {
+        // Vendor-specific information, with enterprise number 32473.
+        let vendor_opts = [Dhcpv6Option {
+            kind: 17,
+            data: b"\x00\x00\x7e\xd9\x00\x01\x00\x02hi",
+        }];
+        let repr = Repr {
+            message_type: MessageType::Reply,
+            transaction_id: 0x123456,
+            client_id: Some(b"client"),
+            server_id: Some(b"server"),
+            elapsed_time: None,
+            request_options: None,
+            ia_na: None,
+            ia_ta: None,
+            dns_servers: None,
+            captive_portal: None,
+            additional_options: &vendor_opts,
+        };
+        let mut bytes = vec![0; repr.buffer_len()];
+        repr.emit(&mut Packet::new_unchecked(&mut bytes[..]))
+            .unwrap();
+
+        let repr = {
+            // The representation outlives the packet it was parsed from.
+            let packet = Packet::new_checked(&bytes[..]).unwrap();
+            Repr::parse(&packet).unwrap()
+        };
+        assert_eq!(repr.server_id, Some(&b"server"[..]));
+        assert!(repr.additional_options.is_empty());
+
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert!(packet.additional_options().eq(vendor_opts.iter().copied()));
+    }
+}
@@ -16,6 +16,7 @@ enum_with_unknown! {
     pub enum Opcode(u8) {
         Query  = 0x00,
         Status = 0x01,
+        Update = 0x05,
     }
 }
 enum_with_unknown! {
@@ -42,7 +43,12 @@ enum_with_unknown! {
         Ns    = 0x0002,
         Cname = 0x0005,
         Soa   = 0x0006,
+        Ptr   = 0x000c,
+        Txt   = 0x0010,
         Aaaa  = 0x001c,
+        Srv   = 0x0021,
+        Opt   = 0x0029,
+        Tsig  = 0x00fa,
     }
 }
 
@@ -74,6 +80,12 @@ mod field {
 
 // DNS class IN (Internet)
 const CLASS_IN: u16 = 1;
+// Multicast DNS uses the top bit of the class field as the unicast-response bit in
+// questions and as the cache-flush bit in records (RFC 6762 § 5.4 and § 10.2).
+const CLASS_MASK: u16 = 0x7fff;
+// Classes with a special meaning in DNS UPDATE messages (RFC 2136 § 2.5).
+const CLASS_NONE: u16 = 254;
+const CLASS_ANY: u16 = 255;
 
 /// A read/write wrapper around a DNS packet buffer.
 #[derive(Debug, PartialEq, Eq)]
@@ -237,6 +249,13 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         NetworkEndian::write_u16(field, (old & !mask) | val);
     }
 
+    pub fn set_rcode(&mut self, val: Rcode) {
+        let field = &mut self.buffer.as_mut()[field::FLAGS];
+        let val: u8 = val.into();
+        let old = NetworkEndian::read_u16(field);
+        NetworkEndian::write_u16(field, (old & !0xF) | (val as u16 & 0xF));
+    }
+
     pub fn set_question_count(&mut self, val: u16) {
         let field = &mut self.buffer.as_mut()[field::QDCOUNT];
         NetworkEndian::write_u16(field, val)
@@ -303,7 +322,7 @@ impl<'a> Question<'a> {
         let class = NetworkEndian::read_u16(&rest[2..4]);
         let rest = &rest[4..];
 
-        if class != CLASS_IN {
+        if class & CLASS_MASK != CLASS_IN {
             return Err(Error);
         }
 
@@ -350,9 +369,79 @@ impl<'a> RecordData<'a> {
                 Ok(RecordData::Aaaa(Ipv6Address::from_bytes(data)))
             }
             Type::Cname => Ok(RecordData::Cname(data)),
+            Type::Ptr => Ok(RecordData::Ptr(data)),
+            Type::Txt => Ok(RecordData::Txt(data)),
+            Type::Srv => {
+                if data.len() < 7 {
+                    return Err(Error);
+                }
+                Ok(RecordData::Srv {
+                    priority: NetworkEndian::read_u16(&data[0..2]),
+                    weight: NetworkEndian::read_u16(&data[2..4]),
+                    port: NetworkEndian::read_u16(&data[4..6]),
+                    target: &data[6..],
+                })
+            }
             x => Ok(RecordData::Other(x, data)),
         }
     }
+
+    /// Return the record type of the data.
+    pub fn type_(&self) -> Type {
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            RecordData::A(_) => Type::A,
+            #[cfg(feature = "proto-ipv6")]
+            RecordData::Aaaa(_) => Type::Aaaa,
+            RecordData::Cname(_) => Type::Cname,
+            RecordData::Ptr(_) => Type::Ptr,
+            RecordData::Txt(_) => Type::Txt,
+            RecordData::Srv { .. } => Type::Srv,
+            RecordData::Other(type_, _) => type_,
+        }
+    }
+
+    /// Return the length of the record data that will be emitted.
+    pub fn buffer_len(&self) -> usize {
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            RecordData::A(_) => 4,
+            #[cfg(feature = "proto-ipv6")]
+            RecordData::Aaaa(_) => 16,
+            RecordData::Cname(data)
+            | RecordData::Ptr(data)
+            | RecordData::Txt(data)
+            | RecordData::Other(_, data) => data.len(),
+            RecordData::Srv { target, .. } => 6 + target.len(),
+        }
+    }
+
+    /// Emit the record data into a buffer.
+    ///
+    /// Names are copied verbatim, so they must not contain compression pointers.
+    pub fn emit(&self, buffer: &mut [u8]) {
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            RecordData::A(addr) => buffer[..4].copy_from_slice(addr.as_bytes()),
+            #[cfg(feature = "proto-ipv6")]
+            RecordData::Aaaa(addr) => buffer[..16].copy_from_slice(addr.as_bytes()),
+            RecordData::Cname(data)
+            | RecordData::Ptr(data)
+            | RecordData::Txt(data)
+            | RecordData::Other(_, data) => buffer[..data.len()].copy_from_slice(data),
+            RecordData::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                NetworkEndian::write_u16(&mut buffer[0..2], priority);
+                NetworkEndian::write_u16(&mut buffer[2..4], weight);
+                NetworkEndian::write_u16(&mut buffer[4..6], port);
+                buffer[6..6 + target.len()].copy_from_slice(target);
+            }
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -363,6 +452,14 @@ pub enum RecordData<'a> {
     #[cfg(feature = "proto-ipv6")]
     Aaaa(Ipv6Address),
     Cname(&'a [u8]),
+    Ptr(&'a [u8]),
+    Txt(&'a [u8]),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: &'a [u8],
+    },
     Other(Type, &'a [u8]),
 }
 
@@ -380,7 +477,8 @@ impl<'a> Record<'a> {
         let len = NetworkEndian::read_u16(&rest[8..10]) as usize;
         let rest = &rest[10..];
 
-        if class != CLASS_IN {
+        // The class of an OPT pseudo-record holds the sender's UDP payload size.
+        if type_ != Type::Opt && class & CLASS_MASK != CLASS_IN {
             return Err(Error);
         }
 
@@ -396,6 +494,24 @@ impl<'a> Record<'a> {
             },
         ))
     }
+
+    /// Return the length of a record that will be emitted from this high-level representation.
+    pub fn buffer_len(&self) -> usize {
+        self.name.len() + 10 + self.data.buffer_len()
+    }
+
+    /// Emit a high-level representation into a buffer.
+    ///
+    /// The name is copied verbatim, so it must not contain compression pointers.
+    pub fn emit(&self, buffer: &mut [u8]) {
+        buffer[..self.name.len()].copy_from_slice(self.name);
+        let rest = &mut buffer[self.name.len()..];
+        NetworkEndian::write_u16(&mut rest[0..2], self.data.type_().into());
+        NetworkEndian::write_u16(&mut rest[2..4], CLASS_IN);
+        NetworkEndian::write_u32(&mut rest[4..8], self.ttl);
+        NetworkEndian::write_u16(&mut rest[8..10], self.data.buffer_len() as u16);
+        self.data.emit(&mut rest[10..]);
+    }
 }
 
 /// High-level DNS packet representation.
@@ -408,12 +524,24 @@ pub struct Repr<'a> {
     pub opcode: Opcode,
     pub flags: Flags,
     pub question: Question<'a>,
+    /// If set, an EDNS0 OPT pseudo-record advertising this UDP payload size is
+    /// added to the additional section ([RFC 6891]).
+    ///
+    /// [RFC 6891]: https://tools.ietf.org/html/rfc6891
+    pub edns_udp_payload_size: Option<u16>,
 }
 
+/// Length of an OPT pseudo-record without options.
+const OPT_RECORD_LEN: usize = 11;
+
 impl<'a> Repr<'a> {
     /// Return the length of a packet that will be emitted from this high-level representation.
     pub const fn buffer_len(&self) -> usize {
-        field::HEADER_END + self.question.buffer_len()
+        let opt_len = match self.edns_udp_payload_size {
+            Some(_) => OPT_RECORD_LEN,
+            None => 0,
+        };
+        field::HEADER_END + self.question.buffer_len() + opt_len
     }
 
     /// Emit a high-level representation into a DNS packet.
@@ -427,8 +555,209 @@ impl<'a> Repr<'a> {
         packet.set_question_count(1);
         packet.set_answer_record_count(0);
         packet.set_authority_record_count(0);
+        packet.set_additional_record_count(self.edns_udp_payload_size.is_some() as u16);
+        self.question.emit(packet.payload_mut());
+
+        if let Some(size) = self.edns_udp_payload_size {
+            let opt = &mut packet.payload_mut()[self.question.buffer_len()..];
+            opt[0] = 0; // root name
+            NetworkEndian::write_u16(&mut opt[1..3], Type::Opt.into());
+            NetworkEndian::write_u16(&mut opt[3..5], size);
+            // Extended RCODE, version 0 and no flags.
+            NetworkEndian::write_u32(&mut opt[5..9], 0);
+            NetworkEndian::write_u16(&mut opt[9..11], 0);
+        }
+    }
+}
+
+/// A change in the update section of a DNS UPDATE message ([RFC 2136 § 2.5]).
+///
+/// [RFC 2136 § 2.5]: https://tools.ietf.org/html/rfc2136#section-2.5
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UpdateRecord<'a> {
+    /// Add a record to an RRset.
+    Add(Record<'a>),
+    /// Delete all the records of a type from a name.
+    DeleteRrset { name: &'a [u8], type_: Type },
+    /// Delete a record from an RRset.
+    DeleteRecord {
+        name: &'a [u8],
+        data: RecordData<'a>,
+    },
+}
+
+impl<'a> UpdateRecord<'a> {
+    /// Return the length of a record that will be emitted from this high-level representation.
+    pub fn buffer_len(&self) -> usize {
+        match self {
+            UpdateRecord::Add(record) => record.buffer_len(),
+            UpdateRecord::DeleteRrset { name, .. } => name.len() + 10,
+            UpdateRecord::DeleteRecord { name, data } => name.len() + 10 + data.buffer_len(),
+        }
+    }
+
+    /// Emit a high-level representation into a buffer.
+    ///
+    /// Names are copied verbatim, so they must not contain compression pointers.
+    pub fn emit(&self, buffer: &mut [u8]) {
+        let (name, type_, class, data) = match self {
+            UpdateRecord::Add(record) => return record.emit(buffer),
+            UpdateRecord::DeleteRrset { name, type_ } => (name, *type_, CLASS_ANY, None),
+            UpdateRecord::DeleteRecord { name, data } => {
+                (name, data.type_(), CLASS_NONE, Some(data))
+            }
+        };
+        buffer[..name.len()].copy_from_slice(name);
+        let rest = &mut buffer[name.len()..];
+        NetworkEndian::write_u16(&mut rest[0..2], type_.into());
+        NetworkEndian::write_u16(&mut rest[2..4], class);
+        NetworkEndian::write_u32(&mut rest[4..8], 0);
+        let len = data.map_or(0, |data| data.buffer_len());
+        NetworkEndian::write_u16(&mut rest[8..10], len as u16);
+        if let Some(data) = data {
+            data.emit(&mut rest[10..]);
+        }
+    }
+}
+
+/// High-level DNS UPDATE message representation ([RFC 2136]).
+///
+/// Prerequisites are not supported, the changes are applied unconditionally.
+///
+/// [RFC 2136]: https://tools.ietf.org/html/rfc2136
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UpdateRepr<'a> {
+    pub transaction_id: u16,
+    /// Name of the zone to update, in wire format.
+    pub zone: &'a [u8],
+    pub updates: &'a [UpdateRecord<'a>],
+}
+
+impl<'a> UpdateRepr<'a> {
+    /// Return the length of a packet that will be emitted from this high-level representation.
+    pub fn buffer_len(&self) -> usize {
+        let zone = Question {
+            name: self.zone,
+            type_: Type::Soa,
+        };
+        let updates: usize = self.updates.iter().map(|u| u.buffer_len()).sum();
+        field::HEADER_END + zone.buffer_len() + updates
+    }
+
+    /// Emit a high-level representation into a DNS packet.
+    pub fn emit<T: ?Sized>(&self, packet: &mut Packet<&mut T>)
+    where
+        T: AsRef<[u8]> + AsMut<[u8]>,
+    {
+        packet.set_transaction_id(self.transaction_id);
+        packet.set_flags(Flags::empty());
+        packet.set_opcode(Opcode::Update);
+        packet.set_rcode(Rcode::NoError);
+        // The zone, prerequisite and update sections take the place of the question,
+        // answer and authority sections.
+        packet.set_question_count(1);
+        packet.set_answer_record_count(0);
+        packet.set_authority_record_count(self.updates.len() as u16);
         packet.set_additional_record_count(0);
-        self.question.emit(packet.payload_mut())
+
+        let zone = Question {
+            name: self.zone,
+            type_: Type::Soa,
+        };
+        zone.emit(packet.payload_mut());
+        let mut offset = zone.buffer_len();
+        for update in self.updates {
+            update.emit(&mut packet.payload_mut()[offset..]);
+            offset += update.buffer_len();
+        }
+    }
+}
+
+/// A message authentication code algorithm for TSIG ([RFC 8945]).
+///
+/// Implementations hold the shared secret key, and usually compute an HMAC with
+/// a hash function such as SHA-256.
+///
+/// [RFC 8945]: https://tools.ietf.org/html/rfc8945
+pub trait TsigMac {
+    /// Return the name of the algorithm in wire format, such as `b"\x0bhmac-sha256\x00"`.
+    fn algorithm(&self) -> &[u8];
+
+    /// Return the length of the MAC, in octets.
+    fn mac_len(&self) -> usize;
+
+    /// Compute the MAC of the concatenation of `data` into `mac`, which is
+    /// [`mac_len`](#tymethod.mac_len) octets long.
+    fn sign(&self, data: &[&[u8]], mac: &mut [u8]);
+}
+
+/// High-level representation of the TSIG record signing a message.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TsigRepr<'a> {
+    /// Name of the key in wire format, in canonical (lower case) form.
+    pub key_name: &'a [u8],
+    /// Time of signing, in seconds since the UNIX epoch.
+    pub time_signed: u64,
+    /// Permitted difference between the time of signing and the time of verification, in seconds.
+    pub fudge: u16,
+}
+
+impl<'a> TsigRepr<'a> {
+    /// Return the length of the TSIG record that will be emitted with `mac`.
+    pub fn buffer_len(&self, mac: &dyn TsigMac) -> usize {
+        self.key_name.len() + 10 + mac.algorithm().len() + 16 + mac.mac_len()
+    }
+
+    /// Sign the DNS message in `buffer[..len]` with `mac`, appending the TSIG record
+    /// after it, and return the length of the signed message.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is too short to hold the record.
+    pub fn emit(&self, mac: &dyn TsigMac, buffer: &mut [u8], len: usize) -> usize {
+        let algorithm = mac.algorithm();
+        let mut time_fudge = [0; 8];
+        NetworkEndian::write_u48(&mut time_fudge[0..6], self.time_signed);
+        NetworkEndian::write_u16(&mut time_fudge[6..8], self.fudge);
+
+        // The record is emitted in place, its fields are the TSIG variables of
+        // RFC 8945 § 4.3.3 in a different order.
+        let (message, rest) = buffer.split_at_mut(len);
+        let rest = &mut rest[..self.buffer_len(mac)];
+        let (name, rest) = rest.split_at_mut(self.key_name.len());
+        name.copy_from_slice(self.key_name);
+        let (header, rest) = rest.split_at_mut(10);
+        NetworkEndian::write_u16(&mut header[0..2], Type::Tsig.into());
+        NetworkEndian::write_u16(&mut header[2..4], CLASS_ANY);
+        NetworkEndian::write_u32(&mut header[4..8], 0);
+        let (alg, rest) = rest.split_at_mut(algorithm.len());
+        alg.copy_from_slice(algorithm);
+        let (time, rest) = rest.split_at_mut(8);
+        time.copy_from_slice(&time_fudge);
+        NetworkEndian::write_u16(&mut rest[0..2], mac.mac_len() as u16);
+        let (digest, trailer) = rest[2..].split_at_mut(mac.mac_len());
+        // Original ID, no error and no other data.
+        NetworkEndian::write_u16(
+            &mut trailer[0..2],
+            NetworkEndian::read_u16(&message[field::ID]),
+        );
+        NetworkEndian::write_u32(&mut trailer[2..6], 0);
+
+        mac.sign(
+            &[message, name, &header[2..8], alg, time, &trailer[2..6]],
+            digest,
+        );
+
+        let rdata_len = algorithm.len() + 16 + mac.mac_len();
+        NetworkEndian::write_u16(&mut header[8..10], rdata_len as u16);
+        let mut packet = Packet::new_unchecked(&mut *message);
+        let count = packet.additional_record_count();
+        packet.set_additional_record_count(count + 1);
+
+        len + self.buffer_len(mac)
     }
 }
 
@@ -777,6 +1106,7 @@ mod test {
                 name,
                 type_: Type::A,
             },
+            edns_udp_payload_size: None,
         };
 
         let mut buf = Vec::new();
@@ -790,4 +1120,142 @@ mod test {
         ];
         assert_eq!(&buf, want);
     }
+
+    #[test]
+    fn test_emit_edns() {
+        let repr = Repr {
+            transaction_id: 0x1234,
+            flags: Flags::RECURSION_DESIRED,
+            opcode: Opcode::Query,
+            question: Question {
+                name: b"\x03org\x00",
+                type_: Type::A,
+            },
+            edns_udp_payload_size: Some(1232),
+        };
+
+        let mut buf = vec![0; repr.buffer_len()];
+        repr.emit(&mut Packet::new_unchecked(&mut buf));
+
+        let packet = Packet::new_checked(&buf[..]).unwrap();
+        assert_eq!(packet.additional_record_count(), 1);
+        let (rest, _) = Question::parse(packet.payload()).unwrap();
+        let (rest, opt) = Record::parse(rest).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(opt.name, &[0]);
+        assert_eq!(opt.data, RecordData::Other(Type::Opt, &[]));
+        assert_eq!(&buf[buf.len() - 8..buf.len() - 6], &[0x04, 0xd0]);
+    }
+
+    #[test]
+    fn test_emit_update_tsig() {
+        use core::cell::RefCell;
+
+        // Returns the length of the signed data as MAC, and records the data.
+        struct TestMac(RefCell<Vec<u8>>);
+        impl TsigMac for TestMac {
+            fn algorithm(&self) -> &[u8] {
+                b"\x04test\x00"
+            }
+            fn mac_len(&self) -> usize {
+                2
+            }
+            fn sign(&self, data: &[&[u8]], mac: &mut [u8]) {
+                let data = data.concat();
+                NetworkEndian::write_u16(mac, data.len() as u16);
+                *self.0.borrow_mut() = data;
+            }
+        }
+
+        let updates = [
+            UpdateRecord::DeleteRrset {
+                name: b"\x04host\x00",
+                type_: Type::A,
+            },
+            UpdateRecord::Add(Record {
+                name: b"\x04host\x00",
+                ttl: 60,
+                data: RecordData::A(Ipv4Address([192, 0, 2, 1])),
+            }),
+        ];
+        let repr = UpdateRepr {
+            transaction_id: 0x1234,
+            zone: b"\x00",
+            updates: &updates,
+        };
+        let tsig = TsigRepr {
+            key_name: b"\x03key\x00",
+            time_signed: 0x0102_0304_0506,
+            fudge: 300,
+        };
+        let mac = TestMac(RefCell::new(Vec::new()));
+
+        let len = repr.buffer_len();
+        let mut buf = vec![0; len + tsig.buffer_len(&mac)];
+        repr.emit(&mut Packet::new_unchecked(&mut buf[..len]));
+        let message = buf[..len].to_vec();
+        assert_eq!(tsig.emit(&mac, &mut buf, len), buf.len());
+
+        #[rustfmt::skip]
+        assert_eq!(&message[..], &[
+            0x12, 0x34, 0x28, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00,
+            // zone
+            0x00, 0x00, 0x06, 0x00, 0x01,
+            // delete RRset
+            0x04, b'h', b'o', b's', b't', 0x00, 0x00, 0x01, 0x00, 0xff,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            // add
+            0x04, b'h', b'o', b's', b't', 0x00, 0x00, 0x01, 0x00, 0x01,
+            0x00, 0x00, 0x00, 0x3c, 0x00, 0x04, 192, 0, 2, 1,
+        ][..]);
+
+        #[rustfmt::skip]
+        let variables = [
+            0x03, b'k', b'e', b'y', 0x00, 0x00, 0xff, 0x00, 0x00, 0x00, 0x00,
+            0x04, b't', b'e', b's', b't', 0x00,
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x01, 0x2c, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(*mac.0.borrow(), [&message[..], &variables[..]].concat());
+
+        #[rustfmt::skip]
+        assert_eq!(&buf[len..], &[
+            0x03, b'k', b'e', b'y', 0x00, 0x00, 0xfa, 0x00, 0xff, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x18,
+            0x04, b't', b'e', b's', b't', 0x00,
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x01, 0x2c,
+            0x00, 0x02, 0x00, (message.len() + variables.len()) as u8,
+            0x12, 0x34, 0x00, 0x00, 0x00, 0x00,
+        ][..]);
+        assert_eq!(Packet::new_unchecked(&buf).additional_record_count(), 1);
+    }
+
+    #[test]
+    fn test_emit_parse_srv() {
+        let record = Record {
+            name: b"\x07printer\x04_ipp\x04_tcp\x05local\x00",
+            ttl: 120,
+            data: RecordData::Srv {
+                priority: 0,
+                weight: 5,
+                port: 631,
+                target: b"\x04host\x05local\x00",
+            },
+        };
+
+        let mut buf = vec![0; record.buffer_len()];
+        record.emit(&mut buf);
+        assert_eq!(Record::parse(&buf), Ok((&[][..], record)));
+    }
+
+    #[test]
+    fn test_parse_mdns_cache_flush() {
+        // Answer for host.local with the cache-flush bit set in the class field.
+        let bytes = &[
+            0x04, 0x68, 0x6f, 0x73, 0x74, 0x05, 0x6c, 0x6f, 0x63, 0x61, 0x6c, 0x00, 0x00, 0x01,
+            0x80, 0x01, 0x00, 0x00, 0x00, 0x78, 0x00, 0x04, 0xc0, 0xa8, 0x01, 0x01,
+        ];
+        let (rest, record) = Record::parse(bytes).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(record.data, RecordData::A(Ipv4Address::new(192, 168, 1, 1)));
+    }
 }
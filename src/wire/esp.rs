@@ -0,0 +1,324 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+
+use super::{Error, IpProtocol, Result};
+
+/// A read/write wrapper around an Encapsulating Security Payload packet buffer.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+// Format of the ESP packet, see https://www.rfc-editor.org/rfc/rfc4303#section-2
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |               Security Parameters Index (SPI)                 |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                      Sequence Number                          |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Payload Data* (variable)                   |
+// ~                                                               ~
+// |                                                               |
+// +               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |               |     Padding (0-255 bytes)                     |
+// +-+-+-+-+-+-+-+-+               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                               |  Pad Length   | Next Header   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Integrity Check Value-ICV   (variable)                |
+// ~                                                               ~
+// |                                                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+mod field {
+    use crate::wire::field::*;
+
+    pub const SPI: Field = 0..4;
+    pub const SEQUENCE_NUMBER: Field = 4..8;
+}
+
+/// The length of an ESP header.
+pub const HEADER_LEN: usize = field::SEQUENCE_NUMBER.end;
+
+/// The length of the Pad Length and Next Header fields of an ESP trailer.
+pub const TRAILER_LEN: usize = 2;
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Imbue a raw octet buffer with ESP packet structure.
+    pub const fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short.
+    pub fn check_len(&self) -> Result<()> {
+        if self.buffer.as_ref().len() < HEADER_LEN {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the Security Parameters Index field.
+    #[inline]
+    pub fn spi(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u32(&data[field::SPI])
+    }
+
+    /// Return the Sequence Number field.
+    #[inline]
+    pub fn sequence_number(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u32(&data[field::SEQUENCE_NUMBER])
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
+    /// Return a pointer to the payload, the initialization vector, the encrypted data and
+    /// trailer, and the integrity check value.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[HEADER_LEN..]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the Security Parameters Index field.
+    #[inline]
+    pub fn set_spi(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::SPI], value)
+    }
+
+    /// Set the Sequence Number field.
+    #[inline]
+    pub fn set_sequence_number(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::SEQUENCE_NUMBER], value)
+    }
+
+    /// Return a mutable pointer to the payload.
+    #[inline]
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        &mut data[HEADER_LEN..]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+/// A high-level representation of an Encapsulating Security Payload header.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Repr {
+    pub spi: u32,
+    pub sequence_number: u32,
+}
+
+impl Repr {
+    /// Parse an ESP packet and return a high-level representation.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(packet: &Packet<&T>) -> Result<Repr> {
+        packet.check_len()?;
+        // The SPI 0 is reserved for local use, and must never be sent on the wire.
+        if packet.spi() == 0 {
+            return Err(Error);
+        }
+        Ok(Repr {
+            spi: packet.spi(),
+            sequence_number: packet.sequence_number(),
+        })
+    }
+
+    /// Return the length of a header that will be emitted from this high-level representation.
+    pub const fn buffer_len(&self) -> usize {
+        HEADER_LEN
+    }
+
+    /// Emit a high-level representation into an ESP packet.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) {
+        packet.set_spi(self.spi);
+        packet.set_sequence_number(self.sequence_number);
+    }
+}
+
+/// A high-level representation of the trailer at the end of the encrypted data of an
+/// Encapsulating Security Payload packet.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Trailer {
+    pub pad_len: u8,
+    pub next_header: IpProtocol,
+}
+
+impl Trailer {
+    /// Return the trailer padding `payload_len` octets of data to a multiple of `alignment`,
+    /// the block size of the cipher or 4, whichever is larger.
+    ///
+    /// # Panics
+    /// This function panics if the alignment is zero or larger than 256.
+    pub fn new(payload_len: usize, alignment: usize, next_header: IpProtocol) -> Trailer {
+        assert!(alignment > 0 && alignment <= 256);
+        let pad_len = (alignment - (payload_len + TRAILER_LEN) % alignment) % alignment;
+        Trailer {
+            pad_len: pad_len as u8,
+            next_header,
+        }
+    }
+
+    /// Parse the trailer at the end of decrypted `data`, checking that the padding has the
+    /// default contents.
+    pub fn parse(data: &[u8]) -> Result<Trailer> {
+        if data.len() < TRAILER_LEN {
+            return Err(Error);
+        }
+        let pad_len = data[data.len() - 2];
+        let next_header = IpProtocol::from(data[data.len() - 1]);
+        let trailer = Trailer {
+            pad_len,
+            next_header,
+        };
+        if data.len() < trailer.buffer_len() {
+            return Err(Error);
+        }
+        let padding = &data[data.len() - trailer.buffer_len()..data.len() - TRAILER_LEN];
+        if padding
+            .iter()
+            .enumerate()
+            .any(|(i, &octet)| octet != (i + 1) as u8)
+        {
+            return Err(Error);
+        }
+        Ok(trailer)
+    }
+
+    /// Return the length of the padding and trailer.
+    pub const fn buffer_len(&self) -> usize {
+        self.pad_len as usize + TRAILER_LEN
+    }
+
+    /// Emit the padding and trailer into the last octets of `data`.
+    ///
+    /// # Panics
+    /// This function panics if `data` is shorter than the padding and trailer.
+    pub fn emit(&self, data: &mut [u8]) {
+        let len = data.len();
+        let trailer = &mut data[len - self.buffer_len()..];
+        for (i, octet) in trailer[..self.pad_len as usize].iter_mut().enumerate() {
+            *octet = (i + 1) as u8;
+        }
+        trailer[self.pad_len as usize] = self.pad_len;
+        trailer[self.pad_len as usize + 1] = self.next_header.into();
+    }
+}
+
+impl<T: AsRef<[u8]>> fmt::Display for Packet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Repr::parse(&Packet::new_unchecked(self.buffer.as_ref())) {
+            Ok(repr) => write!(f, "{repr}"),
+            Err(err) => write!(f, "ESP ({err})"),
+        }
+    }
+}
+
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ESP spi=0x{:08x} seq={}", self.spi, self.sequence_number)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static PACKET_BYTES: [u8; 12] = [
+        0x00, 0x00, 0x10, 0x01, 0x00, 0x00, 0x00, 0x2a, 0xde, 0xad, 0xbe, 0xef,
+    ];
+
+    static PAYLOAD_BYTES: [u8; 4] = [0xde, 0xad, 0xbe, 0xef];
+
+    #[test]
+    fn test_deconstruct() {
+        let packet = Packet::new_checked(&PACKET_BYTES[..]).unwrap();
+        assert_eq!(packet.spi(), 0x1001);
+        assert_eq!(packet.sequence_number(), 42);
+        assert_eq!(packet.payload(), &PAYLOAD_BYTES[..]);
+    }
+
+    #[test]
+    fn test_construct() {
+        let mut bytes = vec![0xa5; 12];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_spi(0x1001);
+        packet.set_sequence_number(42);
+        packet.payload_mut().copy_from_slice(&PAYLOAD_BYTES[..]);
+        assert_eq!(&*packet.into_inner(), &PACKET_BYTES[..]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let packet = Packet::new_unchecked(&PACKET_BYTES[..]);
+        assert_eq!(
+            Repr::parse(&packet),
+            Ok(Repr {
+                spi: 0x1001,
+                sequence_number: 42
+            })
+        );
+
+        let mut bytes = PACKET_BYTES;
+        bytes[2..4].fill(0);
+        assert_eq!(Repr::parse(&Packet::new_unchecked(&bytes[..])), Err(Error));
+        assert_eq!(Packet::new_checked(&PACKET_BYTES[..7]), Err(Error));
+    }
+
+    #[test]
+    fn test_emit() {
+        let repr = Repr {
+            spi: 0x1001,
+            sequence_number: 42,
+        };
+        let mut bytes = vec![0xa5; 12];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet);
+        packet.payload_mut().copy_from_slice(&PAYLOAD_BYTES[..]);
+        assert_eq!(&*packet.into_inner(), &PACKET_BYTES[..]);
+    }
+
+    #[test]
+    fn test_trailer() {
+        let trailer = Trailer::new(5, 8, IpProtocol::Udp);
+        assert_eq!(trailer.pad_len, 1);
+        assert_eq!(Trailer::new(6, 8, IpProtocol::Udp).pad_len, 0);
+        assert_eq!(Trailer::new(7, 4, IpProtocol::Udp).pad_len, 3);
+
+        let mut bytes = [0xa5; 12];
+        let trailer = Trailer::new(7, 4, IpProtocol::Tcp);
+        trailer.emit(&mut bytes);
+        assert_eq!(&bytes[7..], &[0x01, 0x02, 0x03, 0x03, 0x06]);
+        assert_eq!(Trailer::parse(&bytes), Ok(trailer));
+
+        bytes[8] = 0x00;
+        assert_eq!(Trailer::parse(&bytes), Err(Error));
+        assert_eq!(Trailer::parse(&bytes[10..]), Err(Error));
+    }
+}
@@ -8,7 +8,10 @@ enum_with_unknown! {
     pub enum EtherType(u16) {
         Ipv4 = 0x0800,
         Arp  = 0x0806,
-        Ipv6 = 0x86DD
+        Rarp = 0x8035,
+        Ipv6 = 0x86DD,
+        MacControl = 0x8808,
+        Ptp  = 0x88F7
     }
 }
 
@@ -18,11 +21,38 @@ impl fmt::Display for EtherType {
             EtherType::Ipv4 => write!(f, "IPv4"),
             EtherType::Ipv6 => write!(f, "IPv6"),
             EtherType::Arp => write!(f, "ARP"),
+            EtherType::Rarp => write!(f, "RARP"),
+            EtherType::MacControl => write!(f, "MAC Control"),
+            EtherType::Ptp => write!(f, "PTP"),
             EtherType::Unknown(id) => write!(f, "0x{id:04x}"),
         }
     }
 }
 
+/// Encapsulation of the network layer protocol in an Ethernet frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Encapsulation {
+    /// The EtherType follows the source address.
+    #[default]
+    EthernetII,
+    /// The frame is an IEEE 802.3 frame with a length field, and the EtherType
+    /// follows an IEEE 802.2 LLC header and a SNAP header, as in [RFC 1042].
+    ///
+    /// [RFC 1042]: https://www.rfc-editor.org/rfc/rfc1042
+    Snap,
+}
+
+impl Encapsulation {
+    /// Return the length of the headers preceding the payload.
+    pub const fn header_len(&self) -> usize {
+        match self {
+            Encapsulation::EthernetII => HEADER_LEN,
+            Encapsulation::Snap => HEADER_LEN + SNAP_HEADER_LEN,
+        }
+    }
+}
+
 /// A six-octet Ethernet II address.
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -93,11 +123,29 @@ mod field {
     pub const SOURCE: Field = 6..12;
     pub const ETHERTYPE: Field = 12..14;
     pub const PAYLOAD: Rest = 14..;
+
+    // IEEE 802.3 frames carry a length instead of the EtherType, and
+    // IEEE 802.2 LLC and SNAP headers before the payload.
+    pub const LENGTH: Field = ETHERTYPE;
+    pub const LLC: Field = 14..17;
+    pub const SNAP_OUI: Field = 17..20;
+    pub const SNAP_ETHERTYPE: Field = 20..22;
+    pub const SNAP_PAYLOAD: Rest = 22..;
+
+    /// The largest value of the length field, larger values are EtherTypes.
+    pub const MAX_LENGTH: u16 = 1500;
+    /// The DSAP, SSAP and control field of an LLC header followed by a SNAP header.
+    pub const LLC_SNAP: [u8; 3] = [0xaa, 0xaa, 0x03];
+    /// The OUI of a SNAP header carrying an EtherType.
+    pub const SNAP_OUI_ETHERTYPE: [u8; 3] = [0x00, 0x00, 0x00];
 }
 
 /// The Ethernet header length
 pub const HEADER_LEN: usize = field::PAYLOAD.start;
 
+/// The length of the LLC and SNAP headers following an IEEE 802.3 header.
+pub const SNAP_HEADER_LEN: usize = field::SNAP_PAYLOAD.start - field::PAYLOAD.start;
+
 impl<T: AsRef<[u8]>> Frame<T> {
     /// Imbue a raw octet buffer with Ethernet frame structure.
     pub const fn new_unchecked(buffer: T) -> Frame<T> {
@@ -116,9 +164,13 @@ impl<T: AsRef<[u8]>> Frame<T> {
 
     /// Ensure that no accessor method will panic if called.
     /// Returns `Err(Error)` if the buffer is too short.
+    ///
+    /// The result of this check is invalidated by calling [set_snap_header].
+    ///
+    /// [set_snap_header]: #method.set_snap_header
     pub fn check_len(&self) -> Result<()> {
         let len = self.buffer.as_ref().len();
-        if len < HEADER_LEN {
+        if len < HEADER_LEN || (self.is_length_encoded() && len < self.payload_end()) {
             Err(Error)
         } else {
             Ok(())
@@ -155,21 +207,77 @@ impl<T: AsRef<[u8]>> Frame<T> {
         Address::from_bytes(&data[field::SOURCE])
     }
 
+    /// Query whether the frame is an IEEE 802.3 frame, with a length field
+    /// in place of the EtherType.
+    #[inline]
+    pub fn is_length_encoded(&self) -> bool {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::LENGTH]) <= field::MAX_LENGTH
+    }
+
+    /// Query whether the frame is an IEEE 802.3 frame carrying an EtherType
+    /// in LLC and SNAP headers.
+    #[inline]
+    pub fn is_snap(&self) -> bool {
+        let data = self.buffer.as_ref();
+        self.is_length_encoded()
+            && NetworkEndian::read_u16(&data[field::LENGTH]) as usize >= SNAP_HEADER_LEN
+            && data.len() >= field::SNAP_PAYLOAD.start
+            && data[field::LLC] == field::LLC_SNAP
+            && data[field::SNAP_OUI] == field::SNAP_OUI_ETHERTYPE
+    }
+
+    /// Return the encapsulation of the payload.
+    #[inline]
+    pub fn encapsulation(&self) -> Encapsulation {
+        if self.is_snap() {
+            Encapsulation::Snap
+        } else {
+            Encapsulation::EthernetII
+        }
+    }
+
     /// Return the EtherType field, without checking for 802.1Q.
+    ///
+    /// For LLC/SNAP frames, this is the EtherType of the SNAP header, and for
+    /// other IEEE 802.3 frames it is the length field as an unknown EtherType.
     #[inline]
     pub fn ethertype(&self) -> EtherType {
         let data = self.buffer.as_ref();
-        let raw = NetworkEndian::read_u16(&data[field::ETHERTYPE]);
-        EtherType::from(raw)
+        let field = if self.is_snap() {
+            field::SNAP_ETHERTYPE
+        } else {
+            field::ETHERTYPE
+        };
+        EtherType::from(NetworkEndian::read_u16(&data[field]))
+    }
+
+    /// Return the offset of the payload.
+    fn payload_start(&self) -> usize {
+        self.encapsulation().header_len()
+    }
+
+    /// Return the offset following the payload, which excludes the padding of
+    /// IEEE 802.3 frames.
+    fn payload_end(&self) -> usize {
+        let data = self.buffer.as_ref();
+        if self.is_length_encoded() {
+            HEADER_LEN + NetworkEndian::read_u16(&data[field::LENGTH]) as usize
+        } else {
+            data.len()
+        }
     }
 }
 
 impl<'a, T: AsRef<[u8]> + ?Sized> Frame<&'a T> {
     /// Return a pointer to the payload, without checking for 802.1Q.
+    ///
+    /// For LLC/SNAP frames, the payload follows the SNAP header.
     #[inline]
     pub fn payload(&self) -> &'a [u8] {
+        let range = self.payload_start()..self.payload_end();
         let data = self.buffer.as_ref();
-        &data[field::PAYLOAD]
+        &data[range]
     }
 }
 
@@ -195,11 +303,40 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Frame<T> {
         NetworkEndian::write_u16(&mut data[field::ETHERTYPE], value.into())
     }
 
+    /// Turn the frame into an LLC/SNAP frame carrying the given EtherType,
+    /// with a payload extending to the end of the buffer.
+    ///
+    /// # Panics
+    /// This function panics if the buffer is shorter than the headers, or if
+    /// the payload is longer than an IEEE 802.3 frame can carry.
+    #[inline]
+    pub fn set_snap_header(&mut self, value: EtherType) {
+        let data = self.buffer.as_mut();
+        let len = data.len() - HEADER_LEN;
+        assert!(len >= SNAP_HEADER_LEN && len <= field::MAX_LENGTH as usize);
+        NetworkEndian::write_u16(&mut data[field::LENGTH], len as u16);
+        data[field::LLC].copy_from_slice(&field::LLC_SNAP);
+        data[field::SNAP_OUI].copy_from_slice(&field::SNAP_OUI_ETHERTYPE);
+        NetworkEndian::write_u16(&mut data[field::SNAP_ETHERTYPE], value.into())
+    }
+
+    /// Set the EtherType of the frame with the given encapsulation.
+    #[inline]
+    pub fn set_encapsulated_ethertype(&mut self, encapsulation: Encapsulation, value: EtherType) {
+        match encapsulation {
+            Encapsulation::EthernetII => self.set_ethertype(value),
+            Encapsulation::Snap => self.set_snap_header(value),
+        }
+    }
+
     /// Return a mutable pointer to the payload.
+    ///
+    /// For LLC/SNAP frames, the payload follows the SNAP header.
     #[inline]
     pub fn payload_mut(&mut self) -> &mut [u8] {
+        let range = self.payload_start()..self.payload_end();
         let data = self.buffer.as_mut();
-        &mut data[field::PAYLOAD]
+        &mut data[range]
     }
 }
 
@@ -211,9 +348,14 @@ impl<T: AsRef<[u8]>> AsRef<[u8]> for Frame<T> {
 
 impl<T: AsRef<[u8]>> fmt::Display for Frame<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.encapsulation() {
+            Encapsulation::EthernetII => "EthernetII",
+            Encapsulation::Snap => "LLC/SNAP",
+        };
         write!(
             f,
-            "EthernetII src={} dst={} type={}",
+            "{} src={} dst={} type={}",
+            name,
             self.src_addr(),
             self.dst_addr(),
             self.ethertype()
@@ -237,7 +379,7 @@ impl<T: AsRef<[u8]>> PrettyPrint for Frame<T> {
 
         match frame.ethertype() {
             #[cfg(feature = "proto-ipv4")]
-            EtherType::Arp => {
+            EtherType::Arp | EtherType::Rarp => {
                 indent.increase(f)?;
                 super::ArpPacket::<&[u8]>::pretty_print(&frame.payload(), f, indent)
             }
@@ -251,6 +393,11 @@ impl<T: AsRef<[u8]>> PrettyPrint for Frame<T> {
                 indent.increase(f)?;
                 super::Ipv6Packet::<&[u8]>::pretty_print(&frame.payload(), f, indent)
             }
+            EtherType::MacControl => {
+                indent.increase(f)?;
+                let packet = super::PausePacket::new_unchecked(frame.payload());
+                write!(f, "{indent}{packet}")
+            }
             ether_type => {
                 indent.increase(f)?;
                 write!(f, "{indent}(unknown ether-type: {ether_type})")
@@ -353,6 +500,44 @@ mod test_ipv4 {
         frame.payload_mut().copy_from_slice(&PAYLOAD_BYTES[..]);
         assert_eq!(&frame.into_inner()[..], &FRAME_BYTES[..]);
     }
+
+    static SNAP_FRAME_BYTES: [u8; 64] = [
+        0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x00, 0x0c, 0xaa,
+        0xaa, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00, 0xaa, 0x00, 0x00, 0xff, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+    ];
+
+    static SNAP_PAYLOAD_BYTES: [u8; 4] = [0xaa, 0x00, 0x00, 0xff];
+
+    #[test]
+    fn test_deconstruct_snap() {
+        let frame = Frame::new_checked(&SNAP_FRAME_BYTES[..]).unwrap();
+        assert!(frame.is_length_encoded());
+        assert!(frame.is_snap());
+        assert_eq!(frame.encapsulation(), Encapsulation::Snap);
+        assert_eq!(frame.ethertype(), EtherType::Ipv4);
+        assert_eq!(frame.payload(), &SNAP_PAYLOAD_BYTES[..]);
+    }
+
+    #[test]
+    fn test_construct_snap() {
+        let mut bytes = vec![0xa5; 26];
+        let mut frame = Frame::new_unchecked(&mut bytes);
+        frame.set_dst_addr(Address([0x01, 0x02, 0x03, 0x04, 0x05, 0x06]));
+        frame.set_src_addr(Address([0x11, 0x12, 0x13, 0x14, 0x15, 0x16]));
+        frame.set_encapsulated_ethertype(Encapsulation::Snap, EtherType::Ipv4);
+        frame.payload_mut().copy_from_slice(&SNAP_PAYLOAD_BYTES[..]);
+        assert_eq!(&frame.into_inner()[..], &SNAP_FRAME_BYTES[..26]);
+    }
+
+    #[test]
+    fn test_length_overflow() {
+        let mut bytes = SNAP_FRAME_BYTES;
+        bytes[13] = 0x40;
+        assert_eq!(Frame::new_checked(&bytes[..]).err(), Some(Error));
+    }
 }
 
 #[cfg(test)]
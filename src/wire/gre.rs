@@ -0,0 +1,433 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+
+use super::{Error, Result};
+use crate::wire::ip::checksum;
+
+enum_with_unknown! {
+    /// Type of the payload of a GRE packet, an EtherType.
+    pub enum Protocol(u16) {
+        Ipv4 = 0x0800,
+        Ipv6 = 0x86DD
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Protocol::Ipv4 => write!(f, "IPv4"),
+            Protocol::Ipv6 => write!(f, "IPv6"),
+            Protocol::Unknown(id) => write!(f, "0x{id:04x}"),
+        }
+    }
+}
+
+/// A read/write wrapper around a Generic Routing Encapsulation packet buffer.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+// Format of the GRE header, see https://www.rfc-editor.org/rfc/rfc2890#section-2
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |C| |K|S| Reserved0       | Ver |         Protocol Type         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |      Checksum (optional)      |       Reserved1 (Optional)    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                         Key (optional)                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                 Sequence Number (Optional)                    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+mod field {
+    use crate::wire::field::*;
+
+    pub const FLAGS: Field = 0..2;
+    pub const PROTOCOL: Field = 2..4;
+    pub const CHECKSUM: Field = 4..6;
+
+    pub const FLG_C: u16 = 0x8000;
+    pub const FLG_K: u16 = 0x2000;
+    pub const FLG_S: u16 = 0x1000;
+    pub const VERSION: u16 = 0x0007;
+}
+
+/// The length of a GRE header without any of its optional fields.
+pub const HEADER_LEN: usize = field::PROTOCOL.end;
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Imbue a raw octet buffer with GRE packet structure.
+    pub const fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short.
+    ///
+    /// The result of this check is invalidated by calling [set_flags].
+    ///
+    /// [set_flags]: #method.set_flags
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < HEADER_LEN || len < self.header_len() {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    fn flags(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::FLAGS])
+    }
+
+    /// Return the checksum present flag.
+    #[inline]
+    pub fn checksum_present(&self) -> bool {
+        self.flags() & field::FLG_C != 0
+    }
+
+    /// Return the key present flag.
+    #[inline]
+    pub fn key_present(&self) -> bool {
+        self.flags() & field::FLG_K != 0
+    }
+
+    /// Return the sequence number present flag.
+    #[inline]
+    pub fn sequence_present(&self) -> bool {
+        self.flags() & field::FLG_S != 0
+    }
+
+    /// Return the version field.
+    #[inline]
+    pub fn version(&self) -> u8 {
+        (self.flags() & field::VERSION) as u8
+    }
+
+    /// Return the protocol type field.
+    #[inline]
+    pub fn protocol_type(&self) -> Protocol {
+        let data = self.buffer.as_ref();
+        Protocol::from(NetworkEndian::read_u16(&data[field::PROTOCOL]))
+    }
+
+    /// Return the length of the header, including the optional fields.
+    pub fn header_len(&self) -> usize {
+        let mut len = HEADER_LEN;
+        if self.checksum_present() {
+            len += 4;
+        }
+        if self.key_present() {
+            len += 4;
+        }
+        if self.sequence_present() {
+            len += 4;
+        }
+        len
+    }
+
+    /// Return the checksum field, if present.
+    #[inline]
+    pub fn checksum(&self) -> Option<u16> {
+        let data = self.buffer.as_ref();
+        self.checksum_present()
+            .then(|| NetworkEndian::read_u16(&data[field::CHECKSUM]))
+    }
+
+    /// Return the key field, if present.
+    #[inline]
+    pub fn key(&self) -> Option<u32> {
+        let data = self.buffer.as_ref();
+        let offset = self.key_offset();
+        self.key_present()
+            .then(|| NetworkEndian::read_u32(&data[offset..offset + 4]))
+    }
+
+    /// Return the sequence number field, if present.
+    #[inline]
+    pub fn sequence_number(&self) -> Option<u32> {
+        let data = self.buffer.as_ref();
+        let offset = self.sequence_offset();
+        self.sequence_present()
+            .then(|| NetworkEndian::read_u32(&data[offset..offset + 4]))
+    }
+
+    fn key_offset(&self) -> usize {
+        if self.checksum_present() {
+            HEADER_LEN + 4
+        } else {
+            HEADER_LEN
+        }
+    }
+
+    fn sequence_offset(&self) -> usize {
+        if self.key_present() {
+            self.key_offset() + 4
+        } else {
+            self.key_offset()
+        }
+    }
+
+    /// Validate the packet checksum, if present.
+    pub fn verify_checksum(&self) -> bool {
+        if cfg!(fuzzing) || !self.checksum_present() {
+            return true;
+        }
+        checksum::data(self.buffer.as_ref()) == !0
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
+    /// Return a pointer to the payload.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        let header_len = self.header_len();
+        let data = self.buffer.as_ref();
+        &data[header_len..]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the flags and the version field, which determine the optional fields present.
+    #[inline]
+    pub fn set_flags(&mut self, checksum: bool, key: bool, sequence: bool) {
+        let mut flags = 0;
+        if checksum {
+            flags |= field::FLG_C;
+        }
+        if key {
+            flags |= field::FLG_K;
+        }
+        if sequence {
+            flags |= field::FLG_S;
+        }
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::FLAGS], flags)
+    }
+
+    /// Set the protocol type field.
+    #[inline]
+    pub fn set_protocol_type(&mut self, value: Protocol) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::PROTOCOL], value.into())
+    }
+
+    /// Set the key field.
+    ///
+    /// # Panics
+    /// This function panics if the key present flag is not set.
+    #[inline]
+    pub fn set_key(&mut self, value: u32) {
+        assert!(self.key_present());
+        let offset = self.key_offset();
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[offset..offset + 4], value)
+    }
+
+    /// Set the sequence number field.
+    ///
+    /// # Panics
+    /// This function panics if the sequence number present flag is not set.
+    #[inline]
+    pub fn set_sequence_number(&mut self, value: u32) {
+        assert!(self.sequence_present());
+        let offset = self.sequence_offset();
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[offset..offset + 4], value)
+    }
+
+    /// Compute and fill in the checksum field, and zero the reserved field after it.
+    ///
+    /// # Panics
+    /// This function panics if the checksum present flag is not set.
+    pub fn fill_checksum(&mut self) {
+        assert!(self.checksum_present());
+        let data = self.buffer.as_mut();
+        data[field::CHECKSUM.start..field::CHECKSUM.end + 2].fill(0);
+        let checksum = !checksum::data(data);
+        NetworkEndian::write_u16(&mut data[field::CHECKSUM], checksum)
+    }
+
+    /// Return a mutable pointer to the payload.
+    #[inline]
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        let data = self.buffer.as_mut();
+        &mut data[header_len..]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+/// A high-level representation of a Generic Routing Encapsulation header.
+///
+/// Checksums are verified when present, but never emitted.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Repr {
+    pub protocol: Protocol,
+    pub key: Option<u32>,
+    pub sequence_number: Option<u32>,
+}
+
+impl Repr {
+    /// Parse a GRE packet and return a high-level representation.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(packet: &Packet<&T>) -> Result<Repr> {
+        packet.check_len()?;
+        if packet.version() != 0 || !packet.verify_checksum() {
+            return Err(Error);
+        }
+        Ok(Repr {
+            protocol: packet.protocol_type(),
+            key: packet.key(),
+            sequence_number: packet.sequence_number(),
+        })
+    }
+
+    /// Return the length of a header that will be emitted from this high-level representation.
+    pub const fn buffer_len(&self) -> usize {
+        let mut len = HEADER_LEN;
+        if self.key.is_some() {
+            len += 4;
+        }
+        if self.sequence_number.is_some() {
+            len += 4;
+        }
+        len
+    }
+
+    /// Emit a high-level representation into a GRE packet.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) {
+        packet.set_flags(false, self.key.is_some(), self.sequence_number.is_some());
+        packet.set_protocol_type(self.protocol);
+        if let Some(key) = self.key {
+            packet.set_key(key);
+        }
+        if let Some(sequence_number) = self.sequence_number {
+            packet.set_sequence_number(sequence_number);
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> fmt::Display for Packet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Repr::parse(&Packet::new_unchecked(self.buffer.as_ref())) {
+            Ok(repr) => write!(f, "{repr}"),
+            Err(err) => write!(f, "GRE ({err})"),
+        }
+    }
+}
+
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GRE proto={}", self.protocol)?;
+        if let Some(key) = self.key {
+            write!(f, " key={key}")?;
+        }
+        if let Some(sequence_number) = self.sequence_number {
+            write!(f, " seq={sequence_number}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static PACKET_BYTES: [u8; 16] = [
+        0x30, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x2a, 0x00, 0x00, 0x00, 0x07, 0xaa, 0x00, 0x00,
+        0xff,
+    ];
+
+    static PAYLOAD_BYTES: [u8; 4] = [0xaa, 0x00, 0x00, 0xff];
+
+    fn packet_repr() -> Repr {
+        Repr {
+            protocol: Protocol::Ipv4,
+            key: Some(42),
+            sequence_number: Some(7),
+        }
+    }
+
+    #[test]
+    fn test_deconstruct() {
+        let packet = Packet::new_checked(&PACKET_BYTES[..]).unwrap();
+        assert!(!packet.checksum_present());
+        assert!(packet.key_present());
+        assert!(packet.sequence_present());
+        assert_eq!(packet.version(), 0);
+        assert_eq!(packet.protocol_type(), Protocol::Ipv4);
+        assert_eq!(packet.header_len(), 12);
+        assert_eq!(packet.checksum(), None);
+        assert_eq!(packet.key(), Some(42));
+        assert_eq!(packet.sequence_number(), Some(7));
+        assert_eq!(packet.payload(), &PAYLOAD_BYTES[..]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let packet = Packet::new_unchecked(&PACKET_BYTES[..]);
+        assert_eq!(Repr::parse(&packet), Ok(packet_repr()));
+    }
+
+    #[test]
+    fn test_emit() {
+        let repr = packet_repr();
+        assert_eq!(repr.buffer_len(), 12);
+        let mut bytes = vec![0xa5; 16];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet);
+        packet.payload_mut().copy_from_slice(&PAYLOAD_BYTES[..]);
+        assert_eq!(&*packet.into_inner(), &PACKET_BYTES[..]);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut bytes = vec![0; 12];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_flags(true, false, false);
+        packet.set_protocol_type(Protocol::Ipv6);
+        packet.payload_mut().copy_from_slice(&PAYLOAD_BYTES[..]);
+        packet.fill_checksum();
+        assert!(packet.verify_checksum());
+        assert_eq!(
+            Repr::parse(&Packet::new_unchecked(&packet.into_inner()[..])),
+            Ok(Repr {
+                protocol: Protocol::Ipv6,
+                key: None,
+                sequence_number: None,
+            })
+        );
+
+        bytes[11] ^= 1;
+        assert_eq!(Repr::parse(&Packet::new_unchecked(&bytes[..])), Err(Error));
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert_eq!(Packet::new_checked(&PACKET_BYTES[..3]), Err(Error));
+        assert_eq!(Packet::new_checked(&PACKET_BYTES[..11]), Err(Error));
+    }
+}
@@ -1,7 +1,17 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use core::{fmt, str};
+
+use super::{Error, Result};
 #[cfg(feature = "proto-ipv4")]
 use crate::wire::icmpv4;
 #[cfg(feature = "proto-ipv6")]
 use crate::wire::icmpv6;
+use crate::wire::ip::checksum;
+use crate::wire::IpAddress;
+#[cfg(feature = "proto-ipv4")]
+use crate::wire::Ipv4Address;
+#[cfg(feature = "proto-ipv6")]
+use crate::wire::Ipv6Address;
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -23,3 +33,219 @@ impl<'a> From<icmpv6::Repr<'a>> for Repr<'a> {
         Repr::Ipv6(s)
     }
 }
+
+enum_with_unknown! {
+    /// Extended Echo Reply message code, see [RFC 8335 § 3].
+    ///
+    /// [RFC 8335 § 3]: https://www.rfc-editor.org/rfc/rfc8335#section-3
+    pub enum ExtendedEchoCode(u8) {
+        /// The probed interface was found
+        NoError            = 0,
+        /// The query could not be parsed
+        MalformedQuery     = 1,
+        /// No interface matches the query
+        NoSuchInterface    = 2,
+        /// The probed interface is not in the neighbor table of the proxy interface
+        NoSuchTableEntry   = 3,
+        /// Several interfaces match the query
+        MultipleInterfaces = 4
+    }
+}
+
+impl fmt::Display for ExtendedEchoCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExtendedEchoCode::NoError => write!(f, "no error"),
+            ExtendedEchoCode::MalformedQuery => write!(f, "malformed query"),
+            ExtendedEchoCode::NoSuchInterface => write!(f, "no such interface"),
+            ExtendedEchoCode::NoSuchTableEntry => write!(f, "no such table entry"),
+            ExtendedEchoCode::MultipleInterfaces => write!(f, "multiple interfaces satisfy query"),
+            ExtendedEchoCode::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+enum_with_unknown! {
+    /// Neighbor table state of a probed interface, as reported by an Extended Echo Reply.
+    pub enum ExtendedEchoState(u8) {
+        /// The probed interface is local, or the reply carries an error
+        Reserved   = 0,
+        Incomplete = 1,
+        Reachable  = 2,
+        Stale      = 3,
+        Delay      = 4,
+        Probe      = 5,
+        Failed     = 6
+    }
+}
+
+impl fmt::Display for ExtendedEchoState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExtendedEchoState::Reserved => write!(f, "reserved"),
+            ExtendedEchoState::Incomplete => write!(f, "incomplete"),
+            ExtendedEchoState::Reachable => write!(f, "reachable"),
+            ExtendedEchoState::Stale => write!(f, "stale"),
+            ExtendedEchoState::Delay => write!(f, "delay"),
+            ExtendedEchoState::Probe => write!(f, "probe"),
+            ExtendedEchoState::Failed => write!(f, "failed"),
+            ExtendedEchoState::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+// Format of the ICMP Extension Structure carrying an Interface Identification Object,
+// see https://www.rfc-editor.org/rfc/rfc4884#section-7 and
+// https://www.rfc-editor.org/rfc/rfc8335#section-2.1
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |Version|      (Reserved)       |           Checksum            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |             Length            |   Class-Num   |    C-Type     |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                   Object payload (variable)                   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+mod field {
+    use crate::wire::field::*;
+
+    pub const VERSION: usize = 0;
+    pub const CHECKSUM: Field = 2..4;
+    pub const OBJECT_LENGTH: Field = 4..6;
+    pub const CLASS_NUM: usize = 6;
+    pub const C_TYPE: usize = 7;
+    pub const PAYLOAD: usize = 8;
+
+    pub const ADDR_AFI: Field = 0..2;
+    pub const ADDR_LEN: usize = 2;
+    pub const ADDR: usize = 4;
+
+    pub const VERSION_2: u8 = 0x20;
+    pub const CLASS_INTERFACE_IDENT: u8 = 3;
+    pub const C_TYPE_NAME: u8 = 1;
+    pub const C_TYPE_INDEX: u8 = 2;
+    pub const C_TYPE_ADDRESS: u8 = 3;
+
+    pub const AFI_IPV4: u16 = 1;
+    pub const AFI_IPV6: u16 = 2;
+}
+
+/// The interface probed by an Extended Echo Request.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InterfaceIdent<'a> {
+    /// The interface with the given name.
+    Name(&'a str),
+    /// The interface with the given ifIndex.
+    Index(u32),
+    /// The interface with the given address.
+    Address(IpAddress),
+}
+
+impl<'a> InterfaceIdent<'a> {
+    /// Parse the ICMP Extension Structure following an Extended Echo Request header.
+    pub(super) fn parse(data: &'a [u8]) -> Result<InterfaceIdent<'a>> {
+        if data.len() < field::PAYLOAD
+            || data[field::VERSION] & 0xf0 != field::VERSION_2
+            || checksum::data(data) != !0
+            || NetworkEndian::read_u16(&data[field::OBJECT_LENGTH]) as usize
+                != data.len() - field::OBJECT_LENGTH.start
+            || data[field::CLASS_NUM] != field::CLASS_INTERFACE_IDENT
+        {
+            return Err(Error);
+        }
+        let payload = &data[field::PAYLOAD..];
+        match data[field::C_TYPE] {
+            field::C_TYPE_NAME => {
+                let len = payload
+                    .iter()
+                    .rposition(|&b| b != 0)
+                    .map_or(0, |pos| pos + 1);
+                let name = str::from_utf8(&payload[..len]).map_err(|_| Error)?;
+                Ok(InterfaceIdent::Name(name))
+            }
+            field::C_TYPE_INDEX if payload.len() == 4 => {
+                Ok(InterfaceIdent::Index(NetworkEndian::read_u32(payload)))
+            }
+            field::C_TYPE_ADDRESS if payload.len() >= field::ADDR => {
+                let len = payload[field::ADDR_LEN] as usize;
+                let addr = payload.get(field::ADDR..field::ADDR + len).ok_or(Error)?;
+                match NetworkEndian::read_u16(&payload[field::ADDR_AFI]) {
+                    #[cfg(feature = "proto-ipv4")]
+                    field::AFI_IPV4 if len == 4 => Ok(InterfaceIdent::Address(
+                        Ipv4Address::from_bytes(addr).into(),
+                    )),
+                    #[cfg(feature = "proto-ipv6")]
+                    field::AFI_IPV6 if len == 16 => Ok(InterfaceIdent::Address(
+                        Ipv6Address::from_bytes(addr).into(),
+                    )),
+                    _ => Err(Error),
+                }
+            }
+            _ => Err(Error),
+        }
+    }
+
+    /// Return the length of the object payload, padded to a multiple of 4 octets.
+    const fn payload_len(&self) -> usize {
+        let len = match self {
+            InterfaceIdent::Name(name) => name.len(),
+            InterfaceIdent::Index(_) => 4,
+            #[cfg(feature = "proto-ipv4")]
+            InterfaceIdent::Address(IpAddress::Ipv4(_)) => field::ADDR + 4,
+            #[cfg(feature = "proto-ipv6")]
+            InterfaceIdent::Address(IpAddress::Ipv6(_)) => field::ADDR + 16,
+        };
+        (len + 3) & !3
+    }
+
+    /// Return the length of the ICMP Extension Structure that will be emitted.
+    pub const fn buffer_len(&self) -> usize {
+        field::PAYLOAD + self.payload_len()
+    }
+
+    /// Emit the ICMP Extension Structure following an Extended Echo Request header.
+    pub(super) fn emit(&self, data: &mut [u8]) {
+        let data = &mut data[..self.buffer_len()];
+        let object_len = data.len() - field::OBJECT_LENGTH.start;
+        data.fill(0);
+        data[field::VERSION] = field::VERSION_2;
+        NetworkEndian::write_u16(&mut data[field::OBJECT_LENGTH], object_len as u16);
+        data[field::CLASS_NUM] = field::CLASS_INTERFACE_IDENT;
+        let payload = &mut data[field::PAYLOAD..];
+        let c_type = match *self {
+            InterfaceIdent::Name(name) => {
+                payload[..name.len()].copy_from_slice(name.as_bytes());
+                field::C_TYPE_NAME
+            }
+            InterfaceIdent::Index(index) => {
+                NetworkEndian::write_u32(payload, index);
+                field::C_TYPE_INDEX
+            }
+            InterfaceIdent::Address(ref addr) => {
+                let (afi, bytes) = match addr {
+                    #[cfg(feature = "proto-ipv4")]
+                    IpAddress::Ipv4(addr) => (field::AFI_IPV4, addr.as_bytes()),
+                    #[cfg(feature = "proto-ipv6")]
+                    IpAddress::Ipv6(addr) => (field::AFI_IPV6, addr.as_bytes()),
+                };
+                NetworkEndian::write_u16(&mut payload[field::ADDR_AFI], afi);
+                payload[field::ADDR_LEN] = bytes.len() as u8;
+                payload[field::ADDR..field::ADDR + bytes.len()].copy_from_slice(bytes);
+                field::C_TYPE_ADDRESS
+            }
+        };
+        data[field::C_TYPE] = c_type;
+        let checksum = !checksum::data(data);
+        NetworkEndian::write_u16(&mut data[field::CHECKSUM], checksum);
+    }
+}
+
+impl<'a> fmt::Display for InterfaceIdent<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InterfaceIdent::Name(name) => write!(f, "name={name}"),
+            InterfaceIdent::Index(index) => write!(f, "index={index}"),
+            InterfaceIdent::Address(addr) => write!(f, "addr={addr}"),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use byteorder::{ByteOrder, NetworkEndian};
 use core::{cmp, fmt};
 
+use super::icmp::{ExtendedEchoCode, ExtendedEchoState, InterfaceIdent};
 use super::{Error, Result};
 use crate::phy::ChecksumCapabilities;
 use crate::wire::ip::checksum;
@@ -28,7 +29,11 @@ enum_with_unknown! {
         /// Timestamp
         Timestamp      = 13,
         /// Timestamp reply
-        TimestampReply = 14
+        TimestampReply = 14,
+        /// Extended echo request
+        ExtendedEchoRequest = 42,
+        /// Extended echo reply
+        ExtendedEchoReply   = 43
     }
 }
 
@@ -45,6 +50,8 @@ impl fmt::Display for Message {
             Message::ParamProblem => write!(f, "parameter problem"),
             Message::Timestamp => write!(f, "timestamp"),
             Message::TimestampReply => write!(f, "timestamp reply"),
+            Message::ExtendedEchoRequest => write!(f, "extended echo request"),
+            Message::ExtendedEchoReply => write!(f, "extended echo reply"),
             Message::Unknown(id) => write!(f, "{id}"),
         }
     }
@@ -179,7 +186,18 @@ mod field {
     pub const ECHO_IDENT: Field = 4..6;
     pub const ECHO_SEQNO: Field = 6..8;
 
+    pub const NEXT_HOP_MTU: Field = 6..8;
+
+    pub const EXT_ECHO_SEQNO: usize = 6;
+    pub const EXT_ECHO_FLAGS: usize = 7;
+
     pub const HEADER_END: usize = 8;
+
+    pub const EXT_ECHO_FLG_L: u8 = 0x01;
+    pub const EXT_ECHO_STATE_SHIFT: u8 = 5;
+    pub const EXT_ECHO_FLG_A: u8 = 0x04;
+    pub const EXT_ECHO_FLG_4: u8 = 0x02;
+    pub const EXT_ECHO_FLG_6: u8 = 0x01;
 }
 
 impl<T: AsRef<[u8]>> Packet<T> {
@@ -259,6 +277,37 @@ impl<T: AsRef<[u8]>> Packet<T> {
         NetworkEndian::read_u16(&data[field::ECHO_SEQNO])
     }
 
+    /// Return the sequence number field (for extended echo request and reply packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not an extended echo request or reply packet.
+    #[inline]
+    pub fn ext_echo_seq_no(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::EXT_ECHO_SEQNO]
+    }
+
+    /// Return the flags field (for extended echo request and reply packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not an extended echo request or reply packet.
+    #[inline]
+    pub fn ext_echo_flags(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::EXT_ECHO_FLAGS]
+    }
+
+    /// Return the next-hop MTU field (for "fragmentation required" destination
+    /// unreachable packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a destination unreachable packet.
+    #[inline]
+    pub fn next_hop_mtu(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::NEXT_HOP_MTU])
+    }
+
     /// Return the header length.
     /// The result depends on the value of the message type field.
     pub fn header_len(&self) -> usize {
@@ -335,6 +384,37 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         NetworkEndian::write_u16(&mut data[field::ECHO_SEQNO], value)
     }
 
+    /// Set the sequence number field (for extended echo request and reply packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not an extended echo request or reply packet.
+    #[inline]
+    pub fn set_ext_echo_seq_no(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::EXT_ECHO_SEQNO] = value
+    }
+
+    /// Set the flags field (for extended echo request and reply packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not an extended echo request or reply packet.
+    #[inline]
+    pub fn set_ext_echo_flags(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::EXT_ECHO_FLAGS] = value
+    }
+
+    /// Set the next-hop MTU field (for "fragmentation required" destination
+    /// unreachable packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a destination unreachable packet.
+    #[inline]
+    pub fn set_next_hop_mtu(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::NEXT_HOP_MTU], value)
+    }
+
     /// Compute and fill in the header checksum.
     pub fn fill_checksum(&mut self) {
         self.set_checksum(0);
@@ -382,11 +462,36 @@ pub enum Repr<'a> {
         header: Ipv4Repr,
         data: &'a [u8],
     },
+    /// A destination unreachable message with the "fragmentation required" reason,
+    /// carrying the MTU of the next hop (RFC 1191). Routers predating RFC 1191 set
+    /// the MTU to zero.
+    FragRequired {
+        mtu: u16,
+        header: Ipv4Repr,
+        data: &'a [u8],
+    },
     TimeExceeded {
         reason: TimeExceeded,
         header: Ipv4Repr,
         data: &'a [u8],
     },
+    ExtendedEchoRequest {
+        ident: u16,
+        seq_no: u8,
+        /// Whether the probed interface belongs to the node receiving the request, rather
+        /// than being one of its neighbors.
+        local: bool,
+        interface: InterfaceIdent<'a>,
+    },
+    ExtendedEchoReply {
+        ident: u16,
+        seq_no: u8,
+        code: ExtendedEchoCode,
+        state: ExtendedEchoState,
+        active: bool,
+        ipv4: bool,
+        ipv6: bool,
+    },
 }
 
 impl<'a> Repr<'a> {
@@ -429,17 +534,26 @@ impl<'a> Repr<'a> {
                     return Err(Error);
                 }
 
-                Ok(Repr::DstUnreachable {
-                    reason: DstUnreachable::from(code),
-                    header: Ipv4Repr {
-                        src_addr: ip_packet.src_addr(),
-                        dst_addr: ip_packet.dst_addr(),
-                        next_header: ip_packet.next_header(),
-                        payload_len: payload.len(),
-                        hop_limit: ip_packet.hop_limit(),
-                    },
-                    data: payload,
-                })
+                let header = Ipv4Repr {
+                    src_addr: ip_packet.src_addr(),
+                    dst_addr: ip_packet.dst_addr(),
+                    next_header: ip_packet.next_header(),
+                    payload_len: payload.len(),
+                    hop_limit: ip_packet.hop_limit(),
+                    dscp: ip_packet.dscp(),
+                };
+                match DstUnreachable::from(code) {
+                    DstUnreachable::FragRequired => Ok(Repr::FragRequired {
+                        mtu: packet.next_hop_mtu(),
+                        header,
+                        data: payload,
+                    }),
+                    reason => Ok(Repr::DstUnreachable {
+                        reason,
+                        header,
+                        data: payload,
+                    }),
+                }
             }
 
             (Message::TimeExceeded, code) => {
@@ -460,11 +574,32 @@ impl<'a> Repr<'a> {
                         next_header: ip_packet.next_header(),
                         payload_len: payload.len(),
                         hop_limit: ip_packet.hop_limit(),
+                        dscp: ip_packet.dscp(),
                     },
                     data: payload,
                 })
             }
 
+            (Message::ExtendedEchoRequest, 0) => Ok(Repr::ExtendedEchoRequest {
+                ident: packet.echo_ident(),
+                seq_no: packet.ext_echo_seq_no(),
+                local: packet.ext_echo_flags() & field::EXT_ECHO_FLG_L != 0,
+                interface: InterfaceIdent::parse(packet.data())?,
+            }),
+
+            (Message::ExtendedEchoReply, code) => {
+                let flags = packet.ext_echo_flags();
+                Ok(Repr::ExtendedEchoReply {
+                    ident: packet.echo_ident(),
+                    seq_no: packet.ext_echo_seq_no(),
+                    code: ExtendedEchoCode::from(code),
+                    state: ExtendedEchoState::from(flags >> field::EXT_ECHO_STATE_SHIFT),
+                    active: flags & field::EXT_ECHO_FLG_A != 0,
+                    ipv4: flags & field::EXT_ECHO_FLG_4 != 0,
+                    ipv6: flags & field::EXT_ECHO_FLG_6 != 0,
+                })
+            }
+
             _ => Err(Error),
         }
     }
@@ -476,9 +611,14 @@ impl<'a> Repr<'a> {
                 field::ECHO_SEQNO.end + data.len()
             }
             &Repr::DstUnreachable { header, data, .. }
+            | &Repr::FragRequired { header, data, .. }
             | &Repr::TimeExceeded { header, data, .. } => {
                 field::UNUSED.end + header.buffer_len() + data.len()
             }
+            &Repr::ExtendedEchoRequest { interface, .. } => {
+                field::HEADER_END + interface.buffer_len()
+            }
+            &Repr::ExtendedEchoReply { .. } => field::HEADER_END,
         }
     }
 
@@ -530,6 +670,17 @@ impl<'a> Repr<'a> {
                 payload.copy_from_slice(data)
             }
 
+            Repr::FragRequired { mtu, header, data } => {
+                packet.set_msg_type(Message::DstUnreachable);
+                packet.set_msg_code(DstUnreachable::FragRequired.into());
+                packet.set_next_hop_mtu(mtu);
+
+                let mut ip_packet = Ipv4Packet::new_unchecked(packet.data_mut());
+                header.emit(&mut ip_packet, checksum_caps);
+                let payload = &mut ip_packet.into_inner()[header.buffer_len()..];
+                payload.copy_from_slice(data)
+            }
+
             Repr::TimeExceeded {
                 reason,
                 header,
@@ -543,6 +694,45 @@ impl<'a> Repr<'a> {
                 let payload = &mut ip_packet.into_inner()[header.buffer_len()..];
                 payload.copy_from_slice(data)
             }
+
+            Repr::ExtendedEchoRequest {
+                ident,
+                seq_no,
+                local,
+                interface,
+            } => {
+                packet.set_msg_type(Message::ExtendedEchoRequest);
+                packet.set_echo_ident(ident);
+                packet.set_ext_echo_seq_no(seq_no);
+                packet.set_ext_echo_flags(if local { field::EXT_ECHO_FLG_L } else { 0 });
+                interface.emit(packet.data_mut())
+            }
+
+            Repr::ExtendedEchoReply {
+                ident,
+                seq_no,
+                code,
+                state,
+                active,
+                ipv4,
+                ipv6,
+            } => {
+                let mut flags = u8::from(state) << field::EXT_ECHO_STATE_SHIFT;
+                for (set, flag) in [
+                    (active, field::EXT_ECHO_FLG_A),
+                    (ipv4, field::EXT_ECHO_FLG_4),
+                    (ipv6, field::EXT_ECHO_FLG_6),
+                ] {
+                    if set {
+                        flags |= flag;
+                    }
+                }
+                packet.set_msg_type(Message::ExtendedEchoReply);
+                packet.set_msg_code(code.into());
+                packet.set_echo_ident(ident);
+                packet.set_ext_echo_seq_no(seq_no);
+                packet.set_ext_echo_flags(flags);
+            }
         }
 
         if checksum_caps.icmpv4.tx() {
@@ -604,9 +794,31 @@ impl<'a> fmt::Display for Repr<'a> {
             Repr::DstUnreachable { reason, .. } => {
                 write!(f, "ICMPv4 destination unreachable ({reason})")
             }
+            Repr::FragRequired { mtu, .. } => {
+                write!(f, "ICMPv4 fragmentation required (mtu {mtu})")
+            }
             Repr::TimeExceeded { reason, .. } => {
                 write!(f, "ICMPv4 time exceeded ({reason})")
             }
+            Repr::ExtendedEchoRequest {
+                ident,
+                seq_no,
+                local,
+                interface,
+            } => write!(
+                f,
+                "ICMPv4 extended echo request id={ident} seq={seq_no} local={local} {interface}"
+            ),
+            Repr::ExtendedEchoReply {
+                ident,
+                seq_no,
+                code,
+                state,
+                ..
+            } => write!(
+                f,
+                "ICMPv4 extended echo reply id={ident} seq={seq_no} ({code}) state={state}"
+            ),
         }
     }
 }
@@ -694,6 +906,123 @@ mod test {
         assert_eq!(&packet.into_inner()[..], &ECHO_PACKET_BYTES[..]);
     }
 
+    static EXT_ECHO_REQUEST_BYTES: [u8; 20] = [
+        0x2a, 0x00, 0xbe, 0xca, 0x12, 0x34, 0x05, 0x01, 0x20, 0x00, 0xdc, 0xee, 0x00, 0x08, 0x03,
+        0x02, 0x00, 0x00, 0x00, 0x07,
+    ];
+
+    static EXT_ECHO_REPLY_BYTES: [u8; 8] = [0x2b, 0x00, 0xbd, 0x85, 0x12, 0x34, 0x05, 0x46];
+
+    #[test]
+    fn test_ext_echo_deconstruct() {
+        let packet = Packet::new_unchecked(&EXT_ECHO_REQUEST_BYTES[..]);
+        assert_eq!(packet.msg_type(), Message::ExtendedEchoRequest);
+        assert_eq!(packet.echo_ident(), 0x1234);
+        assert_eq!(packet.ext_echo_seq_no(), 5);
+        assert_eq!(packet.ext_echo_flags(), 0x01);
+        assert_eq!(packet.data(), &EXT_ECHO_REQUEST_BYTES[8..]);
+        assert!(packet.verify_checksum());
+    }
+
+    #[test]
+    fn test_ext_echo_parse_emit() {
+        let request = Repr::ExtendedEchoRequest {
+            ident: 0x1234,
+            seq_no: 5,
+            local: true,
+            interface: InterfaceIdent::Index(7),
+        };
+        let reply = Repr::ExtendedEchoReply {
+            ident: 0x1234,
+            seq_no: 5,
+            code: ExtendedEchoCode::NoError,
+            state: ExtendedEchoState::Reachable,
+            active: true,
+            ipv4: true,
+            ipv6: false,
+        };
+        for (repr, bytes) in [
+            (request, &EXT_ECHO_REQUEST_BYTES[..]),
+            (reply, &EXT_ECHO_REPLY_BYTES[..]),
+        ] {
+            let packet = Packet::new_unchecked(bytes);
+            assert_eq!(
+                Repr::parse(&packet, &ChecksumCapabilities::default()),
+                Ok(repr)
+            );
+            let mut buffer = vec![0xa5; repr.buffer_len()];
+            repr.emit(
+                &mut Packet::new_unchecked(&mut buffer),
+                &ChecksumCapabilities::default(),
+            );
+            assert_eq!(&buffer[..], bytes);
+        }
+
+        let mut bytes = EXT_ECHO_REQUEST_BYTES;
+        bytes[14] = 2;
+        assert_eq!(
+            Repr::parse(
+                &Packet::new_unchecked(&bytes[..]),
+                &ChecksumCapabilities::ignored()
+            ),
+            Err(Error)
+        );
+    }
+
+    #[test]
+    fn test_ext_echo_interface() {
+        for interface in [
+            InterfaceIdent::Name("eth0"),
+            InterfaceIdent::Name("wlan10"),
+            InterfaceIdent::Address(crate::wire::Ipv4Address::new(192, 168, 1, 1).into()),
+        ] {
+            let repr = Repr::ExtendedEchoRequest {
+                ident: 1,
+                seq_no: 2,
+                local: false,
+                interface,
+            };
+            let mut buffer = vec![0xa5; repr.buffer_len()];
+            let mut packet = Packet::new_unchecked(&mut buffer);
+            repr.emit(&mut packet, &ChecksumCapabilities::default());
+            assert_eq!(packet.data_mut().len() % 4, 0);
+            let packet = Packet::new_unchecked(&buffer[..]);
+            assert_eq!(
+                Repr::parse(&packet, &ChecksumCapabilities::default()),
+                Ok(repr)
+            );
+        }
+    }
+
+    #[test]
+    fn test_frag_required_parse_emit() {
+        let repr = Repr::FragRequired {
+            mtu: 1400,
+            header: Ipv4Repr {
+                src_addr: crate::wire::Ipv4Address::new(192, 168, 1, 1),
+                dst_addr: crate::wire::Ipv4Address::new(10, 0, 0, 1),
+                next_header: crate::wire::IpProtocol::Udp,
+                payload_len: 8,
+                hop_limit: 63,
+                dscp: 0x2e,
+            },
+            data: &[0x12, 0x34, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00],
+        };
+        let mut buffer = vec![0; repr.buffer_len()];
+        repr.emit(
+            &mut Packet::new_unchecked(&mut buffer),
+            &ChecksumCapabilities::default(),
+        );
+        let packet = Packet::new_unchecked(&buffer[..]);
+        assert_eq!(packet.msg_type(), Message::DstUnreachable);
+        assert_eq!(packet.msg_code(), u8::from(DstUnreachable::FragRequired));
+        assert_eq!(packet.next_hop_mtu(), 1400);
+        assert_eq!(
+            Repr::parse(&packet, &ChecksumCapabilities::default()),
+            Ok(repr)
+        );
+    }
+
     #[test]
     fn test_check_len() {
         let bytes = [0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
@@ -1,12 +1,15 @@
 use byteorder::{ByteOrder, NetworkEndian};
 use core::{cmp, fmt};
 
+use super::icmp::{ExtendedEchoCode, ExtendedEchoState, InterfaceIdent};
 use super::{Error, Result};
 use crate::phy::ChecksumCapabilities;
 use crate::wire::ip::checksum;
 use crate::wire::MldRepr;
 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
 use crate::wire::NdiscRepr;
+#[cfg(feature = "proto-rpl")]
+use crate::wire::RplRepr;
 use crate::wire::{IpAddress, IpProtocol, Ipv6Packet, Ipv6Repr};
 
 enum_with_unknown! {
@@ -37,7 +40,13 @@ enum_with_unknown! {
         /// Redirect
         Redirect        = 0x89,
         /// Multicast Listener Report
-        MldReport       = 0x8f
+        MldReport       = 0x8f,
+        /// RPL Control Message
+        RplControl      = 0x9b,
+        /// Extended Echo Request
+        ExtendedEchoRequest = 0xa0,
+        /// Extended Echo Reply
+        ExtendedEchoReply   = 0xa1
     }
 }
 
@@ -94,6 +103,9 @@ impl fmt::Display for Message {
             Message::Redirect => write!(f, "redirect"),
             Message::MldQuery => write!(f, "multicast listener query"),
             Message::MldReport => write!(f, "multicast listener report"),
+            Message::RplControl => write!(f, "RPL control message"),
+            Message::ExtendedEchoRequest => write!(f, "extended echo request"),
+            Message::ExtendedEchoReply => write!(f, "extended echo reply"),
             Message::Unknown(id) => write!(f, "{id}"),
         }
     }
@@ -204,8 +216,17 @@ pub(super) mod field {
     pub const ECHO_IDENT: Field = 4..6;
     pub const ECHO_SEQNO: Field = 6..8;
 
+    pub const EXT_ECHO_SEQNO: usize = 6;
+    pub const EXT_ECHO_FLAGS: usize = 7;
+
     pub const HEADER_END: usize = 8;
 
+    pub const EXT_ECHO_FLG_L: u8 = 0x01;
+    pub const EXT_ECHO_STATE_SHIFT: u8 = 5;
+    pub const EXT_ECHO_FLG_A: u8 = 0x04;
+    pub const EXT_ECHO_FLG_4: u8 = 0x02;
+    pub const EXT_ECHO_FLG_6: u8 = 0x01;
+
     // NDISC: See https://tools.ietf.org/html/rfc4861
     // Router Advertisement message offsets
     pub const CUR_HOP_LIMIT: usize = 4;
@@ -265,7 +286,10 @@ impl<T: AsRef<[u8]>> Packet<T> {
     /// Returns `Err(Error)` if the buffer is too short.
     pub fn check_len(&self) -> Result<()> {
         let len = self.buffer.as_ref().len();
-        if len < field::HEADER_END || len < self.header_len() {
+        if len < field::CHECKSUM.end || len < self.header_len() {
+            Err(Error)
+        } else if len < field::HEADER_END && self.msg_type() != Message::RplControl {
+            // RPL DIS messages are the only ones shorter than the usual header.
             Err(Error)
         } else {
             Ok(())
@@ -312,6 +336,20 @@ impl<T: AsRef<[u8]>> Packet<T> {
         NetworkEndian::read_u16(&data[field::ECHO_SEQNO])
     }
 
+    /// Return the sequence number field (for extended echo request and reply packets).
+    #[inline]
+    pub fn ext_echo_seq_no(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::EXT_ECHO_SEQNO]
+    }
+
+    /// Return the flags field (for extended echo request and reply packets).
+    #[inline]
+    pub fn ext_echo_flags(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::EXT_ECHO_FLAGS]
+    }
+
     /// Return the MTU field (for packet too big messages).
     #[inline]
     pub fn pkt_too_big_mtu(&self) -> u32 {
@@ -343,6 +381,8 @@ impl<T: AsRef<[u8]>> Packet<T> {
             Message::Redirect => field::DEST_ADDR.end,
             Message::MldQuery => field::QUERY_NUM_SRCS.end,
             Message::MldReport => field::NR_MCAST_RCRDS.end,
+            Message::ExtendedEchoRequest => field::HEADER_END,
+            Message::ExtendedEchoReply => field::HEADER_END,
             // For packets that are not included in RFC 4443, do not
             // include the last 32 bits of the ICMPv6 header in
             // `header_bytes`. This must be done so that these bytes
@@ -448,6 +488,20 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         NetworkEndian::write_u16(&mut data[field::ECHO_SEQNO], value)
     }
 
+    /// Set the sequence number field (for extended echo request and reply packets).
+    #[inline]
+    pub fn set_ext_echo_seq_no(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::EXT_ECHO_SEQNO] = value
+    }
+
+    /// Set the flags field (for extended echo request and reply packets).
+    #[inline]
+    pub fn set_ext_echo_flags(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::EXT_ECHO_FLAGS] = value
+    }
+
     /// Set the MTU field (for packet too big messages).
     ///
     /// # Panics
@@ -532,9 +586,28 @@ pub enum Repr<'a> {
         seq_no: u16,
         data: &'a [u8],
     },
+    ExtendedEchoRequest {
+        ident: u16,
+        seq_no: u8,
+        /// Whether the probed interface belongs to the node receiving the request, rather
+        /// than being one of its neighbors.
+        local: bool,
+        interface: InterfaceIdent<'a>,
+    },
+    ExtendedEchoReply {
+        ident: u16,
+        seq_no: u8,
+        code: ExtendedEchoCode,
+        state: ExtendedEchoState,
+        active: bool,
+        ipv4: bool,
+        ipv6: bool,
+    },
     #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
     Ndisc(NdiscRepr<'a>),
     Mld(MldRepr<'a>),
+    #[cfg(feature = "proto-rpl")]
+    Rpl(RplRepr<'a>),
 }
 
 impl<'a> Repr<'a> {
@@ -566,6 +639,8 @@ impl<'a> Repr<'a> {
                 next_header: ip_packet.next_header(),
                 payload_len: payload.len(),
                 hop_limit: ip_packet.hop_limit(),
+                traffic_class: ip_packet.traffic_class(),
+                flow_label: ip_packet.flow_label(),
             };
             Ok((payload, repr))
         }
@@ -619,9 +694,29 @@ impl<'a> Repr<'a> {
                 seq_no: packet.echo_seq_no(),
                 data: packet.payload(),
             }),
+            (Message::ExtendedEchoRequest, 0) => Ok(Repr::ExtendedEchoRequest {
+                ident: packet.echo_ident(),
+                seq_no: packet.ext_echo_seq_no(),
+                local: packet.ext_echo_flags() & field::EXT_ECHO_FLG_L != 0,
+                interface: InterfaceIdent::parse(packet.payload())?,
+            }),
+            (Message::ExtendedEchoReply, code) => {
+                let flags = packet.ext_echo_flags();
+                Ok(Repr::ExtendedEchoReply {
+                    ident: packet.echo_ident(),
+                    seq_no: packet.ext_echo_seq_no(),
+                    code: ExtendedEchoCode::from(code),
+                    state: ExtendedEchoState::from(flags >> field::EXT_ECHO_STATE_SHIFT),
+                    active: flags & field::EXT_ECHO_FLG_A != 0,
+                    ipv4: flags & field::EXT_ECHO_FLG_4 != 0,
+                    ipv6: flags & field::EXT_ECHO_FLG_6 != 0,
+                })
+            }
             #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
             (msg_type, 0) if msg_type.is_ndisc() => NdiscRepr::parse(packet).map(Repr::Ndisc),
             (msg_type, 0) if msg_type.is_mld() => MldRepr::parse(packet).map(Repr::Mld),
+            #[cfg(feature = "proto-rpl")]
+            (Message::RplControl, _) => RplRepr::parse(packet).map(Repr::Rpl),
             _ => Err(Error),
         }
     }
@@ -638,9 +733,15 @@ impl<'a> Repr<'a> {
             &Repr::EchoRequest { data, .. } | &Repr::EchoReply { data, .. } => {
                 field::ECHO_SEQNO.end + data.len()
             }
+            &Repr::ExtendedEchoRequest { interface, .. } => {
+                field::HEADER_END + interface.buffer_len()
+            }
+            &Repr::ExtendedEchoReply { .. } => field::HEADER_END,
             #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
             &Repr::Ndisc(ndisc) => ndisc.buffer_len(),
             &Repr::Mld(mld) => mld.buffer_len(),
+            #[cfg(feature = "proto-rpl")]
+            &Repr::Rpl(rpl) => rpl.buffer_len(),
         }
     }
 
@@ -734,10 +835,53 @@ impl<'a> Repr<'a> {
                 packet.payload_mut()[..data_len].copy_from_slice(&data[..data_len])
             }
 
+            Repr::ExtendedEchoRequest {
+                ident,
+                seq_no,
+                local,
+                interface,
+            } => {
+                packet.set_msg_type(Message::ExtendedEchoRequest);
+                packet.set_msg_code(0);
+                packet.set_echo_ident(ident);
+                packet.set_ext_echo_seq_no(seq_no);
+                packet.set_ext_echo_flags(if local { field::EXT_ECHO_FLG_L } else { 0 });
+                interface.emit(packet.payload_mut())
+            }
+
+            Repr::ExtendedEchoReply {
+                ident,
+                seq_no,
+                code,
+                state,
+                active,
+                ipv4,
+                ipv6,
+            } => {
+                let mut flags = u8::from(state) << field::EXT_ECHO_STATE_SHIFT;
+                for (set, flag) in [
+                    (active, field::EXT_ECHO_FLG_A),
+                    (ipv4, field::EXT_ECHO_FLG_4),
+                    (ipv6, field::EXT_ECHO_FLG_6),
+                ] {
+                    if set {
+                        flags |= flag;
+                    }
+                }
+                packet.set_msg_type(Message::ExtendedEchoReply);
+                packet.set_msg_code(code.into());
+                packet.set_echo_ident(ident);
+                packet.set_ext_echo_seq_no(seq_no);
+                packet.set_ext_echo_flags(flags);
+            }
+
             #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
             Repr::Ndisc(ndisc) => ndisc.emit(packet),
 
             Repr::Mld(mld) => mld.emit(packet),
+
+            #[cfg(feature = "proto-rpl")]
+            Repr::Rpl(rpl) => rpl.emit(packet),
         }
 
         if checksum_caps.icmpv6.tx() {
@@ -806,7 +950,26 @@ impl<'a> fmt::Display for Repr<'a> {
             },
             Repr::ParamProblem { reason, .. } => {
                 write!(f, "ICMPv6 param problem ({reason})")
-            },
+            }
+            Repr::ExtendedEchoRequest {
+                ident,
+                seq_no,
+                local,
+                interface,
+            } => write!(
+                f,
+                "ICMPv6 extended echo request id={ident} seq={seq_no} local={local} {interface}"
+            ),
+            Repr::ExtendedEchoReply {
+                ident,
+                seq_no,
+                code,
+                state,
+                ..
+            } => write!(
+                f,
+                "ICMPv6 extended echo reply id={ident} seq={seq_no} ({code}) state={state}"
+            ),
             Repr::Ndisc(ndisc) => {
                 write!(f, "ICMPv6 ndisc")?;
                 match ndisc {
@@ -815,8 +978,20 @@ impl<'a> fmt::Display for Repr<'a> {
                         if let Some(lladdr) = lladdr.as_ref() {
                             write!(f, " lladdr={}", lladdr)?;
                         }
-                    },
-                    NdiscRepr::RouterAdvert { hop_limit, flags, router_lifetime, reachable_time, retrans_time, lladdr, mtu, prefix_info } => {
+                    }
+                    NdiscRepr::RouterAdvert {
+                        hop_limit,
+                        flags,
+                        router_lifetime,
+                        reachable_time,
+                        retrans_time,
+                        lladdr,
+                        mtu,
+                        prefix_info,
+                        captive_portal,
+                        pref64,
+                        sixlowpan_context,
+                    } => {
                         write!(f, " router advert")?;
                         if let Some(lladdr) = lladdr.as_ref() {
                             write!(f, " lladdr={}", lladdr)?;
@@ -836,23 +1011,54 @@ impl<'a> fmt::Display for Repr<'a> {
                             write!(f, " preferred-lifetime={}", info.preferred_lifetime)?;
                             write!(f, " prefix={}/{}", info.prefix, info.prefix_len)?;
                         }
-                    },
-                    NdiscRepr::NeighborSolicit { target_addr, lladdr } => {
+                        if let Some(uri) = captive_portal {
+                            write!(f, " captive-portal={}", uri)?;
+                        }
+                        if let Some(pref64) = pref64.as_ref() {
+                            write!(f, " pref64={}", pref64.prefix)?;
+                            write!(f, " pref64-lifetime={}", pref64.lifetime)?;
+                        }
+                        if let Some(context) = sixlowpan_context.as_ref() {
+                            write!(f, " 6lowpan-context={}", context.prefix)?;
+                            write!(f, " 6lowpan-cid={}", context.context_id)?;
+                        }
+                    }
+                    NdiscRepr::NeighborSolicit {
+                        target_addr,
+                        lladdr,
+                        aro,
+                    } => {
                         write!(f, " neighbor solicit")?;
                         if let Some(lladdr) = lladdr.as_ref() {
                             write!(f, " lladdr={}", lladdr)?;
                         }
                         write!(f, " target={}", target_addr)?;
-                    },
-                    NdiscRepr::NeighborAdvert { flags, target_addr, lladdr } => {
+                        if let Some(aro) = aro.as_ref() {
+                            write!(f, " aro-lifetime={}", aro.lifetime)?;
+                        }
+                    }
+                    NdiscRepr::NeighborAdvert {
+                        flags,
+                        target_addr,
+                        lladdr,
+                        aro,
+                    } => {
                         write!(f, " neighbor advert")?;
                         write!(f, " flags={:?}", flags)?;
                         if let Some(lladdr) = lladdr.as_ref() {
                             write!(f, " lladdr={}", lladdr)?;
                         }
                         write!(f, " target-addr={}", target_addr)?;
-                    },
-                    NdiscRepr::Redirect { target_addr, dest_addr, lladdr, redirected_hdr } => {
+                        if let Some(aro) = aro.as_ref() {
+                            write!(f, " aro-status={}", aro.status)?;
+                        }
+                    }
+                    NdiscRepr::Redirect {
+                        target_addr,
+                        dest_addr,
+                        lladdr,
+                        redirected_hdr,
+                    } => {
                         write!(f, " redirect")?;
                         if let Some(lladdr) = lladdr.as_ref() {
                             write!(f, " lladdr={}", lladdr)?;
@@ -868,7 +1074,9 @@ impl<'a> fmt::Display for Repr<'a> {
             },
             Repr::Mld(_) => {
                 write!(f, "ICMPv6 mld")
-            },
+            }
+            #[cfg(feature = "proto-rpl")]
+            Repr::Rpl(rpl) => write!(f, "ICMPv6 {rpl}"),
         }
     }
 }
@@ -950,6 +1158,8 @@ mod test {
                 next_header: IpProtocol::Udp,
                 payload_len: 12,
                 hop_limit: 0x40,
+                traffic_class: 0,
+                flow_label: 0,
             },
             data: &PKT_TOO_BIG_UDP_PAYLOAD,
         }
@@ -1062,4 +1272,43 @@ mod test {
         );
         assert_eq!(&*packet.into_inner(), &PKT_TOO_BIG_BYTES[..]);
     }
+
+    #[test]
+    fn test_ext_echo_parse_emit() {
+        let request = Repr::ExtendedEchoRequest {
+            ident: 0x1234,
+            seq_no: 5,
+            local: false,
+            interface: InterfaceIdent::Address(Ipv6Address::LINK_LOCAL_ALL_ROUTERS.into()),
+        };
+        let reply = Repr::ExtendedEchoReply {
+            ident: 0x1234,
+            seq_no: 5,
+            code: ExtendedEchoCode::NoSuchTableEntry,
+            state: ExtendedEchoState::Reserved,
+            active: false,
+            ipv4: false,
+            ipv6: false,
+        };
+        for repr in [request, reply] {
+            let mut bytes = vec![0xa5; repr.buffer_len()];
+            let mut packet = Packet::new_unchecked(&mut bytes);
+            repr.emit(
+                &MOCK_IP_ADDR_1,
+                &MOCK_IP_ADDR_2,
+                &mut packet,
+                &ChecksumCapabilities::default(),
+            );
+            let packet = Packet::new_checked(&bytes[..]).unwrap();
+            assert_eq!(
+                Repr::parse(
+                    &MOCK_IP_ADDR_1,
+                    &MOCK_IP_ADDR_2,
+                    &packet,
+                    &ChecksumCapabilities::default(),
+                ),
+                Ok(repr)
+            );
+        }
+    }
 }
@@ -262,20 +262,21 @@ impl<T: AsRef<[u8]>> Frame<T> {
             return Err(Error);
         }
 
-        let mut offset = field::ADDRESSING.start + 2;
-
-        // Calculate the size of the addressing field.
-        offset += self.dst_addressing_mode().size();
-        offset += self.src_addressing_mode().size();
-
-        if !self.pan_id_compression() {
-            offset += 2;
-        }
-
+        let offset = field::ADDRESSING.start + self.addressing_len();
         if offset > self.buffer.as_ref().len() {
             return Err(Error);
         }
 
+        if self.security_enabled() && self.addressing_fields().is_some() {
+            // The auxiliary security header needs at least the security control and the
+            // frame counter fields, followed by the key identifier.
+            if offset + 5 > self.buffer.as_ref().len()
+                || self.payload_start() > self.buffer.as_ref().len()
+            {
+                return Err(Error);
+            }
+        }
+
         Ok(())
     }
 
@@ -360,17 +361,34 @@ impl<T: AsRef<[u8]>> Frame<T> {
             | FrameType::Unknown(_) => return None,
         }
 
-        let mut offset = 2;
+        Some(&self.buffer.as_ref()[field::ADDRESSING][..self.addressing_len()])
+    }
 
-        // Calculate the size of the addressing field.
-        offset += self.dst_addressing_mode().size();
-        offset += self.src_addressing_mode().size();
+    /// Return the size of the destination PAN field, which is only present with a
+    /// destination address.
+    fn dst_pan_size(&self) -> usize {
+        match self.dst_addressing_mode() {
+            AddressingMode::Absent => 0,
+            _ => 2,
+        }
+    }
 
-        if !self.pan_id_compression() {
-            offset += 2;
+    /// Return the size of the source PAN field, which is only present with a source
+    /// address, when it isn't compressed.
+    fn src_pan_size(&self) -> usize {
+        match self.src_addressing_mode() {
+            AddressingMode::Absent => 0,
+            _ if self.pan_id_compression() => 0,
+            _ => 2,
         }
+    }
 
-        Some(&self.buffer.as_ref()[field::ADDRESSING][..offset])
+    /// Return the size of the addressing fields.
+    fn addressing_len(&self) -> usize {
+        self.dst_pan_size()
+            + self.dst_addressing_mode().size()
+            + self.src_pan_size()
+            + self.src_addressing_mode().size()
     }
 
     /// Return the destination PAN field.
@@ -416,7 +434,7 @@ impl<T: AsRef<[u8]>> Frame<T> {
         }
 
         let addressing_fields = self.addressing_fields()?;
-        let offset = self.dst_addressing_mode().size() + 2;
+        let offset = self.dst_pan_size() + self.dst_addressing_mode().size();
 
         match self.src_addressing_mode() {
             AddressingMode::Absent => None,
@@ -431,16 +449,13 @@ impl<T: AsRef<[u8]>> Frame<T> {
     #[inline]
     pub fn src_addr(&self) -> Option<Address> {
         let addressing_fields = self.addressing_fields()?;
-        let mut offset = match self.dst_addressing_mode() {
+        let offset = match self.dst_addressing_mode() {
             AddressingMode::Absent => 0,
             AddressingMode::Short => 2,
             AddressingMode::Extended => 8,
             _ => return None, // TODO(thvdveld): what do we do here?
-        } + 2;
-
-        if !self.pan_id_compression() {
-            offset += 2;
-        }
+        } + self.dst_pan_size()
+            + self.src_pan_size();
 
         match self.src_addressing_mode() {
             AddressingMode::Absent => Some(Address::Absent),
@@ -580,7 +595,7 @@ impl<'a, T: AsRef<[u8]> + ?Sized> Frame<&'a T> {
     #[inline]
     pub fn payload(&self) -> Option<&'a [u8]> {
         match self.frame_type() {
-            FrameType::Data => {
+            FrameType::Beacon | FrameType::Data | FrameType::MacCommand => {
                 let index = self.payload_start();
                 let data = &self.buffer.as_ref();
 
@@ -675,7 +690,7 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Frame<T> {
             AddressingMode::Short => 2,
             AddressingMode::Extended => 8,
             _ => unreachable!(),
-        } + 2;
+        } + self.dst_pan_size();
 
         let data = &mut self.buffer.as_mut()[field::ADDRESSING];
         data[offset..offset + 2].copy_from_slice(&value.as_bytes());
@@ -689,7 +704,7 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Frame<T> {
             AddressingMode::Short => 2,
             AddressingMode::Extended => 8,
             _ => unreachable!(),
-        } + 2;
+        } + self.dst_pan_size();
 
         let offset = offset + if self.pan_id_compression() { 0 } else { 2 };
 
@@ -726,7 +741,7 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Frame<T> {
     #[inline]
     pub fn payload_mut(&mut self) -> Option<&mut [u8]> {
         match self.frame_type() {
-            FrameType::Data => {
+            FrameType::Beacon | FrameType::Data | FrameType::MacCommand => {
                 let index = self.payload_start();
                 let data = self.buffer.as_mut();
                 Some(&mut data[index..])
@@ -792,18 +807,17 @@ impl Repr {
     /// Return the length of a buffer required to hold a packet with the payload of a given length.
     #[inline]
     pub const fn buffer_len(&self) -> usize {
-        3 + 2
-            + match self.dst_addr {
-                Some(Address::Absent) | None => 0,
-                Some(Address::Short(_)) => 2,
-                Some(Address::Extended(_)) => 8,
-            }
-            + if !self.pan_id_compression { 2 } else { 0 }
-            + match self.src_addr {
-                Some(Address::Absent) | None => 0,
-                Some(Address::Short(_)) => 2,
-                Some(Address::Extended(_)) => 8,
-            }
+        3 + match self.dst_addr {
+            Some(Address::Absent) | None => 0,
+            Some(Address::Short(_)) => 2 + 2,
+            Some(Address::Extended(_)) => 2 + 8,
+        } + match self.src_addr {
+            Some(Address::Absent) | None => 0,
+            Some(Address::Short(_)) if self.pan_id_compression => 2,
+            Some(Address::Extended(_)) if self.pan_id_compression => 8,
+            Some(Address::Short(_)) => 2 + 2,
+            Some(Address::Extended(_)) => 2 + 8,
+        }
     }
 
     /// Emit a high-level representation into an IEEE802.15.4 frame.
@@ -836,6 +850,271 @@ impl Repr {
     }
 }
 
+/// The superframe specification of a beacon.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SuperframeSpecification {
+    /// The beacon order, 15 when beacons are only sent on request.
+    pub beacon_order: u8,
+    /// The superframe order, 15 when beacons are only sent on request.
+    pub superframe_order: u8,
+    /// The last slot of the contention access period.
+    pub final_cap_slot: u8,
+    /// Whether the battery life extension is enabled.
+    pub battery_life_extension: bool,
+    /// Whether the beacon was sent by the PAN coordinator.
+    pub pan_coordinator: bool,
+    /// Whether the coordinator accepts association requests.
+    pub association_permit: bool,
+}
+
+impl SuperframeSpecification {
+    /// Return the specification of a PAN where beacons are only sent on request.
+    pub const fn nonbeacon(pan_coordinator: bool, association_permit: bool) -> Self {
+        SuperframeSpecification {
+            beacon_order: 15,
+            superframe_order: 15,
+            final_cap_slot: 15,
+            battery_life_extension: false,
+            pan_coordinator,
+            association_permit,
+        }
+    }
+
+    fn from_raw(raw: u16) -> Self {
+        SuperframeSpecification {
+            beacon_order: (raw & 0xf) as u8,
+            superframe_order: ((raw >> 4) & 0xf) as u8,
+            final_cap_slot: ((raw >> 8) & 0xf) as u8,
+            battery_life_extension: raw & (1 << 12) != 0,
+            pan_coordinator: raw & (1 << 14) != 0,
+            association_permit: raw & (1 << 15) != 0,
+        }
+    }
+
+    fn to_raw(self) -> u16 {
+        (self.beacon_order as u16 & 0xf)
+            | (self.superframe_order as u16 & 0xf) << 4
+            | (self.final_cap_slot as u16 & 0xf) << 8
+            | (self.battery_life_extension as u16) << 12
+            | (self.pan_coordinator as u16) << 14
+            | (self.association_permit as u16) << 15
+    }
+}
+
+/// A high-level representation of the payload of a beacon frame.
+///
+/// Guaranteed time slots and pending addresses are skipped when parsing, and never
+/// emitted.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BeaconRepr<'a> {
+    pub superframe_spec: SuperframeSpecification,
+    /// The beacon payload, given to the higher layer.
+    pub payload: &'a [u8],
+}
+
+impl<'a> BeaconRepr<'a> {
+    /// Parse the payload of a beacon frame.
+    pub fn parse(payload: &'a [u8]) -> Result<BeaconRepr<'a>> {
+        if payload.len() < 4 {
+            return Err(Error);
+        }
+        let superframe_spec = SuperframeSpecification::from_raw(LittleEndian::read_u16(payload));
+
+        // Skip the guaranteed time slot descriptors, and their directions.
+        let mut offset = 3;
+        let descriptors = (payload[2] & 0b111) as usize;
+        if descriptors > 0 {
+            offset += 1 + 3 * descriptors;
+        }
+        // Skip the short and extended pending addresses.
+        let pending = *payload.get(offset).ok_or(Error)?;
+        offset += 1 + 2 * (pending & 0b111) as usize + 8 * ((pending >> 4) & 0b111) as usize;
+        if offset > payload.len() {
+            return Err(Error);
+        }
+
+        Ok(BeaconRepr {
+            superframe_spec,
+            payload: &payload[offset..],
+        })
+    }
+
+    /// Return the length of the payload of a beacon frame.
+    pub const fn buffer_len(&self) -> usize {
+        4 + self.payload.len()
+    }
+
+    /// Emit the payload of a beacon frame into a buffer.
+    pub fn emit(&self, buffer: &mut [u8]) {
+        LittleEndian::write_u16(&mut buffer[0..2], self.superframe_spec.to_raw());
+        // No guaranteed time slots, and no pending addresses.
+        buffer[2] = 0;
+        buffer[3] = 0;
+        buffer[4..4 + self.payload.len()].copy_from_slice(self.payload);
+    }
+}
+
+enum_with_unknown! {
+    /// IEEE 802.15.4 MAC command identifier.
+    pub enum CommandId(u8) {
+        AssociationRequest = 0x01,
+        AssociationResponse = 0x02,
+        DisassociationNotification = 0x03,
+        DataRequest = 0x04,
+        PanIdConflictNotification = 0x05,
+        OrphanNotification = 0x06,
+        BeaconRequest = 0x07,
+        CoordinatorRealignment = 0x08,
+        GtsRequest = 0x09,
+    }
+}
+
+enum_with_unknown! {
+    /// The status of an association response.
+    pub enum AssociationStatus(u8) {
+        Success = 0x00,
+        PanAtCapacity = 0x01,
+        PanAccessDenied = 0x02,
+    }
+}
+
+impl fmt::Display for AssociationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssociationStatus::Success => write!(f, "success"),
+            AssociationStatus::PanAtCapacity => write!(f, "PAN at capacity"),
+            AssociationStatus::PanAccessDenied => write!(f, "PAN access denied"),
+            AssociationStatus::Unknown(id) => write!(f, "0x{id:02x}"),
+        }
+    }
+}
+
+/// The capabilities of a device requesting association.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CapabilityInformation {
+    /// Whether the device is a full-function device, which can act as a coordinator.
+    pub full_function_device: bool,
+    /// Whether the device is powered from the mains.
+    pub mains_powered: bool,
+    /// Whether the receiver of the device is enabled when it is idle.
+    pub receiver_on_when_idle: bool,
+    /// Whether the device can secure the MAC frames.
+    pub security_capable: bool,
+    /// Whether the coordinator should allocate a short address to the device.
+    pub allocate_address: bool,
+}
+
+impl CapabilityInformation {
+    fn from_raw(raw: u8) -> Self {
+        CapabilityInformation {
+            full_function_device: raw & (1 << 1) != 0,
+            mains_powered: raw & (1 << 2) != 0,
+            receiver_on_when_idle: raw & (1 << 3) != 0,
+            security_capable: raw & (1 << 6) != 0,
+            allocate_address: raw & (1 << 7) != 0,
+        }
+    }
+
+    fn to_raw(self) -> u8 {
+        (self.full_function_device as u8) << 1
+            | (self.mains_powered as u8) << 2
+            | (self.receiver_on_when_idle as u8) << 3
+            | (self.security_capable as u8) << 6
+            | (self.allocate_address as u8) << 7
+    }
+}
+
+/// A high-level representation of the payload of a MAC command frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum CommandRepr {
+    AssociationRequest {
+        capability: CapabilityInformation,
+    },
+    /// The answer of a coordinator to an association request. The short address is
+    /// `ff:fe` when the device must use its extended address, and `ff:ff` when the
+    /// association failed.
+    AssociationResponse {
+        short_addr: Address,
+        status: AssociationStatus,
+    },
+    DisassociationNotification {
+        reason: u8,
+    },
+    DataRequest,
+    BeaconRequest,
+}
+
+impl CommandRepr {
+    /// Parse the payload of a MAC command frame.
+    pub fn parse(payload: &[u8]) -> Result<CommandRepr> {
+        let (&id, content) = payload.split_first().ok_or(Error)?;
+        match (CommandId::from(id), content.len()) {
+            (CommandId::AssociationRequest, 1..) => Ok(CommandRepr::AssociationRequest {
+                capability: CapabilityInformation::from_raw(content[0]),
+            }),
+            (CommandId::AssociationResponse, 3..) => Ok(CommandRepr::AssociationResponse {
+                short_addr: Address::Short([content[1], content[0]]),
+                status: AssociationStatus::from(content[2]),
+            }),
+            (CommandId::DisassociationNotification, 1..) => {
+                Ok(CommandRepr::DisassociationNotification { reason: content[0] })
+            }
+            (CommandId::DataRequest, _) => Ok(CommandRepr::DataRequest),
+            (CommandId::BeaconRequest, _) => Ok(CommandRepr::BeaconRequest),
+            _ => Err(Error),
+        }
+    }
+
+    /// Return the identifier of the command.
+    pub const fn command_id(&self) -> CommandId {
+        match self {
+            CommandRepr::AssociationRequest { .. } => CommandId::AssociationRequest,
+            CommandRepr::AssociationResponse { .. } => CommandId::AssociationResponse,
+            CommandRepr::DisassociationNotification { .. } => CommandId::DisassociationNotification,
+            CommandRepr::DataRequest => CommandId::DataRequest,
+            CommandRepr::BeaconRequest => CommandId::BeaconRequest,
+        }
+    }
+
+    /// Return the length of the payload of a MAC command frame.
+    pub const fn buffer_len(&self) -> usize {
+        match self {
+            CommandRepr::AssociationRequest { .. } => 2,
+            CommandRepr::AssociationResponse { .. } => 4,
+            CommandRepr::DisassociationNotification { .. } => 2,
+            CommandRepr::DataRequest | CommandRepr::BeaconRequest => 1,
+        }
+    }
+
+    /// Emit the payload of a MAC command frame into a buffer.
+    ///
+    /// # Panics
+    /// This function panics if the short address of an association response is not a
+    /// short address.
+    pub fn emit(&self, buffer: &mut [u8]) {
+        buffer[0] = self.command_id().into();
+        match *self {
+            CommandRepr::AssociationRequest { capability } => buffer[1] = capability.to_raw(),
+            CommandRepr::AssociationResponse { short_addr, status } => {
+                let short_addr = match short_addr {
+                    Address::Short(short_addr) => short_addr,
+                    _ => panic!("not a short address"),
+                };
+                buffer[1] = short_addr[1];
+                buffer[2] = short_addr[0];
+                buffer[3] = status.into();
+            }
+            CommandRepr::DisassociationNotification { reason } => buffer[1] = reason,
+            CommandRepr::DataRequest | CommandRepr::BeaconRequest => {}
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1009,4 +1288,127 @@ mod test {
             0x31,0x01,0x00,0x00, // frame counter
         ][..],
     }
+
+    vector_test! {
+        beacon
+        [
+            0x00, 0x80, // frame control
+            0x01, // sequence number
+            0x34, 0x12, // source PAN id
+            0x01, 0x00, // short source address
+            0xff, 0xcf, // superframe specification
+            0x00, // GTS specification
+            0x00, // pending address specification
+            0xab, // beacon payload
+        ];
+        frame_type -> FrameType::Beacon,
+        dst_addressing_mode -> AddressingMode::Absent,
+        src_addressing_mode -> AddressingMode::Short,
+        dst_pan_id -> None,
+        dst_addr -> Some(Address::Absent),
+        src_pan_id -> Some(Pan(0x1234)),
+        src_addr -> Some(Address::Short([0x00, 0x01])),
+        payload -> Some(&[0xff, 0xcf, 0x00, 0x00, 0xab][..]),
+    }
+
+    #[test]
+    fn test_beacon_repr() {
+        let payload = [0xff, 0xcf, 0x00, 0x00, 0xab];
+        let repr = BeaconRepr::parse(&payload).unwrap();
+        assert_eq!(
+            repr,
+            BeaconRepr {
+                superframe_spec: SuperframeSpecification::nonbeacon(true, true),
+                payload: &[0xab],
+            }
+        );
+
+        let mut buffer = [0; 5];
+        repr.emit(&mut buffer);
+        assert_eq!(buffer, payload);
+
+        // A guaranteed time slot and a pending short address are skipped.
+        let payload = [
+            0xff, 0x4f, 0x81, 0x00, 0x01, 0x00, 0x11, 0x01, 0x34, 0x12, 0xab,
+        ];
+        let repr = BeaconRepr::parse(&payload).unwrap();
+        assert!(!repr.superframe_spec.association_permit);
+        assert_eq!(repr.payload, &[0xab]);
+        assert!(BeaconRepr::parse(&payload[..9]).is_err());
+    }
+
+    #[test]
+    fn test_truncated_secured_beacon() {
+        let mut bytes = [
+            0x08, 0x80, // frame control, with security enabled
+            0x01, // sequence number
+            0x34, 0x12, // source PAN id
+            0x01, 0x00, // short source address
+            0x15, // security control, with a 5-byte key identifier
+            0x01, 0x00, 0x00, 0x00, // frame counter
+            0x01, 0x02, 0x03, 0x04, 0x05, // key identifier
+            0xff, 0xcf, 0x00, 0x00, // beacon payload
+        ];
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        assert_eq!(frame.payload(), Some(&[0xff, 0xcf, 0x00, 0x00][..]));
+
+        // Truncated within the key identifier and within the frame counter.
+        assert!(Frame::new_checked(&bytes[..15]).is_err());
+        assert!(Frame::new_checked(&bytes[..10]).is_err());
+        assert!(Frame::new_checked(&bytes[..7]).is_err());
+
+        // A key identifier mode without a key identifier.
+        bytes[7] = 0x05;
+        assert!(Frame::new_checked(&bytes[..12]).is_ok());
+    }
+
+    #[test]
+    fn test_association_request() {
+        let bytes = [
+            0x23, 0xc8, // frame control
+            0x02, // sequence number
+            0x34, 0x12, // destination PAN id
+            0x01, 0x00, // short destination address
+            0xff, 0xff, // source PAN id
+            0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, // extended source address
+            0x01, 0x88, // association request
+        ];
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        let repr = Repr::parse(&frame).unwrap();
+        assert_eq!(repr.frame_type, FrameType::MacCommand);
+        assert_eq!(repr.dst_pan_id, Some(Pan(0x1234)));
+        assert_eq!(repr.src_pan_id, Some(Pan::BROADCAST));
+        assert_eq!(
+            repr.src_addr,
+            Some(Address::Extended([1, 2, 3, 4, 5, 6, 7, 8]))
+        );
+        let command = CommandRepr::parse(frame.payload().unwrap()).unwrap();
+        let capability = CapabilityInformation {
+            receiver_on_when_idle: true,
+            allocate_address: true,
+            ..CapabilityInformation::default()
+        };
+        assert_eq!(command, CommandRepr::AssociationRequest { capability });
+
+        let mut buffer = [0; 19];
+        let header_len = repr.buffer_len();
+        assert_eq!(header_len + command.buffer_len(), buffer.len());
+        repr.emit(&mut Frame::new_unchecked(&mut buffer[..header_len]));
+        command.emit(&mut buffer[header_len..]);
+        assert_eq!(buffer, bytes);
+    }
+
+    #[test]
+    fn test_association_response() {
+        let command = CommandRepr::AssociationResponse {
+            short_addr: Address::Short([0x12, 0x34]),
+            status: AssociationStatus::Success,
+        };
+        let mut buffer = [0; 4];
+        command.emit(&mut buffer);
+        assert_eq!(buffer, [0x02, 0x34, 0x12, 0x00]);
+        assert_eq!(CommandRepr::parse(&buffer), Ok(command));
+        assert!(CommandRepr::parse(&buffer[..3]).is_err());
+        assert_eq!(CommandRepr::parse(&[0x07]), Ok(CommandRepr::BeaconRequest));
+    }
 }
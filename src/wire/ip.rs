@@ -51,13 +51,19 @@ enum_with_unknown! {
         HopByHop  = 0x00,
         Icmp      = 0x01,
         Igmp      = 0x02,
+        IpIp      = 0x04,
         Tcp       = 0x06,
         Udp       = 0x11,
+        Ipv6      = 0x29,
         Ipv6Route = 0x2b,
         Ipv6Frag  = 0x2c,
+        Gre       = 0x2f,
+        Esp       = 0x32,
+        Ah        = 0x33,
         Icmpv6    = 0x3a,
         Ipv6NoNxt = 0x3b,
-        Ipv6Opts  = 0x3c
+        Ipv6Opts  = 0x3c,
+        Sctp      = 0x84
     }
 }
 
@@ -67,13 +73,19 @@ impl fmt::Display for Protocol {
             Protocol::HopByHop => write!(f, "Hop-by-Hop"),
             Protocol::Icmp => write!(f, "ICMP"),
             Protocol::Igmp => write!(f, "IGMP"),
+            Protocol::IpIp => write!(f, "IP-in-IP"),
             Protocol::Tcp => write!(f, "TCP"),
             Protocol::Udp => write!(f, "UDP"),
+            Protocol::Ipv6 => write!(f, "IPv6"),
             Protocol::Ipv6Route => write!(f, "IPv6-Route"),
             Protocol::Ipv6Frag => write!(f, "IPv6-Frag"),
+            Protocol::Gre => write!(f, "GRE"),
+            Protocol::Esp => write!(f, "ESP"),
+            Protocol::Ah => write!(f, "AH"),
             Protocol::Icmpv6 => write!(f, "ICMPv6"),
             Protocol::Ipv6NoNxt => write!(f, "IPv6-NoNxt"),
             Protocol::Ipv6Opts => write!(f, "IPv6-Opts"),
+            Protocol::Sctp => write!(f, "SCTP"),
             Protocol::Unknown(id) => write!(f, "0x{id:02x}"),
         }
     }
@@ -581,6 +593,7 @@ impl Repr {
                 next_header,
                 payload_len,
                 hop_limit,
+                dscp: 0,
             }),
             #[cfg(feature = "proto-ipv6")]
             (Address::Ipv6(src_addr), Address::Ipv6(dst_addr)) => Self::Ipv6(Ipv6Repr {
@@ -589,6 +602,8 @@ impl Repr {
                 next_header,
                 payload_len,
                 hop_limit,
+                traffic_class: 0,
+                flow_label: 0,
             }),
             #[allow(unreachable_patterns)]
             _ => panic!("IP version mismatch: src={src_addr:?} dst={dst_addr:?}"),
@@ -665,6 +680,51 @@ impl Repr {
         }
     }
 
+    /// Return the traffic class.
+    ///
+    /// For IPv4 this is the type of service octet, with the DSCP in the upper six bits
+    /// and the ECN bits cleared.
+    pub const fn traffic_class(&self) -> u8 {
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            Repr::Ipv4(Ipv4Repr { dscp, .. }) => dscp << 2,
+            #[cfg(feature = "proto-ipv6")]
+            Repr::Ipv6(Ipv6Repr { traffic_class, .. }) => traffic_class,
+        }
+    }
+
+    /// Set the traffic class. For IPv4 only the DSCP, i.e. the upper six bits, is used.
+    pub fn set_traffic_class(&mut self, value: u8) {
+        match self {
+            #[cfg(feature = "proto-ipv4")]
+            Repr::Ipv4(Ipv4Repr { dscp, .. }) => *dscp = value >> 2,
+            #[cfg(feature = "proto-ipv6")]
+            Repr::Ipv6(Ipv6Repr { traffic_class, .. }) => *traffic_class = value,
+        }
+    }
+
+    /// Return the flow label, or `None` for IPv4, which has no flow label.
+    pub const fn flow_label(&self) -> Option<u32> {
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            Repr::Ipv4(_) => None,
+            #[cfg(feature = "proto-ipv6")]
+            Repr::Ipv6(Ipv6Repr { flow_label, .. }) => Some(flow_label),
+        }
+    }
+
+    /// Set the flow label. IPv4 has no flow label, so this does nothing for IPv4.
+    pub fn set_flow_label(&mut self, value: u32) {
+        match self {
+            #[cfg(feature = "proto-ipv4")]
+            Repr::Ipv4(_) => {
+                let _ = value;
+            }
+            #[cfg(feature = "proto-ipv6")]
+            Repr::Ipv6(Ipv6Repr { flow_label, .. }) => *flow_label = value & 0x000f_ffff,
+        }
+    }
+
     /// Return the length of a header that will be emitted from this high-level representation.
     pub const fn header_len(&self) -> usize {
         match *self {
@@ -799,17 +859,19 @@ pub mod checksum {
 
 use crate::wire::pretty_print::PrettyIndent;
 
+#[cfg_attr(not(feature = "proto-ipv6"), allow(unused_mut))]
 pub fn pretty_print_ip_payload<T: Into<Repr>>(
     f: &mut fmt::Formatter,
     indent: &mut PrettyIndent,
     ip_repr: T,
     mut payload: &[u8],
 ) -> fmt::Result {
-    #[cfg(feature = "proto-ipv4")]
     use super::pretty_print::PrettyPrint;
     use crate::wire::ip::checksum::format_checksum;
     #[cfg(feature = "proto-ipv4")]
-    use crate::wire::{Icmpv4Packet, Icmpv6Packet};
+    use crate::wire::Icmpv4Packet;
+    #[cfg(feature = "proto-ipv6")]
+    use crate::wire::Icmpv6Packet;
     use crate::wire::{TcpPacket, TcpRepr};
 
     let checksum_caps = ChecksumCapabilities::ignored();
@@ -823,6 +885,7 @@ pub fn pretty_print_ip_payload<T: Into<Repr>>(
                 indent.increase(f)?;
                 Icmpv4Packet::<&[u8]>::pretty_print(&payload, f, indent)
             }
+            #[cfg(feature = "proto-ipv6")]
             Protocol::Icmpv6 => {
                 indent.increase(f)?;
                 Icmpv6Packet::<&[u8]>::pretty_print(&payload, f, indent)
@@ -853,6 +916,7 @@ pub fn pretty_print_ip_payload<T: Into<Repr>>(
                     }
                 }
             }
+            #[cfg(feature = "proto-ipv6")]
             Protocol::HopByHop => {
                 indent.increase(f)?;
                 match crate::wire::Ipv6HopByHopHeader::new_checked(payload) {
@@ -632,6 +632,8 @@ pub struct Repr {
     pub next_header: Protocol,
     pub payload_len: usize,
     pub hop_limit: u8,
+    /// The 6-bit differentiated services code point.
+    pub dscp: u8,
 }
 
 impl Repr {
@@ -668,6 +670,7 @@ impl Repr {
             next_header: packet.next_header(),
             payload_len,
             hop_limit: packet.hop_limit(),
+            dscp: packet.dscp(),
         })
     }
 
@@ -685,7 +688,7 @@ impl Repr {
     ) {
         packet.set_version(4);
         packet.set_header_len(field::DST_ADDR.end as u8);
-        packet.set_dscp(0);
+        packet.set_dscp(self.dscp);
         packet.set_ecn(0);
         let total_len = packet.header_len() as u16 + self.payload_len as u16;
         packet.set_total_len(total_len);
@@ -897,6 +900,7 @@ mod test {
             next_header: Protocol::Icmp,
             payload_len: 4,
             hop_limit: 64,
+            dscp: 0,
         }
     }
 
@@ -938,6 +942,24 @@ mod test {
         assert_eq!(&*packet.into_inner(), &REPR_PACKET_BYTES[..]);
     }
 
+    #[test]
+    fn test_emit_parse_dscp() {
+        let repr = Repr {
+            dscp: 46,
+            ..packet_repr()
+        };
+        let mut bytes = vec![0xa5; repr.buffer_len() + REPR_PAYLOAD_BYTES.len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet, &ChecksumCapabilities::default());
+        assert_eq!(packet.dscp(), 46);
+        assert_eq!(packet.ecn(), 0);
+        let packet = Packet::new_unchecked(&*packet.into_inner());
+        assert_eq!(
+            Repr::parse(&packet, &ChecksumCapabilities::default()),
+            Ok(repr)
+        );
+    }
+
     #[test]
     fn test_unspecified() {
         assert!(Address::UNSPECIFIED.is_unspecified());
@@ -52,6 +52,14 @@ impl Address {
         0x02,
     ]);
 
+    /// The link-local [all RPL nodes multicast address].
+    ///
+    /// [all RPL nodes multicast address]: https://tools.ietf.org/html/rfc6550#section-20.19
+    pub const LINK_LOCAL_ALL_RPL_NODES: Address = Address([
+        0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x1a,
+    ]);
+
     /// The [loopback address].
     ///
     /// [loopback address]: https://tools.ietf.org/html/rfc4291#section-2.5.3
@@ -667,6 +675,10 @@ pub struct Repr {
     pub payload_len: usize,
     /// The 8-bit hop limit field.
     pub hop_limit: u8,
+    /// The 8-bit traffic class field.
+    pub traffic_class: u8,
+    /// The 20-bit flow label field.
+    pub flow_label: u32,
 }
 
 impl Repr {
@@ -683,6 +695,8 @@ impl Repr {
             next_header: packet.next_header(),
             payload_len: packet.payload_len() as usize,
             hop_limit: packet.hop_limit(),
+            traffic_class: packet.traffic_class(),
+            flow_label: packet.flow_label(),
         })
     }
 
@@ -697,8 +711,8 @@ impl Repr {
         // Make no assumptions about the original state of the packet buffer.
         // Make sure to set every byte.
         packet.set_version(6);
-        packet.set_traffic_class(0);
-        packet.set_flow_label(0);
+        packet.set_traffic_class(self.traffic_class);
+        packet.set_flow_label(self.flow_label);
         packet.set_payload_len(self.payload_len as u16);
         packet.set_hop_limit(self.hop_limit);
         packet.set_next_header(self.next_header);
@@ -1082,6 +1096,8 @@ mod test {
             next_header: Protocol::Udp,
             payload_len: 12,
             hop_limit: 64,
+            traffic_class: 0,
+            flow_label: 0,
         }
     }
 
@@ -1174,6 +1190,22 @@ mod test {
         assert_eq!(repr, packet_repr());
     }
 
+    #[test]
+    fn test_repr_emit_parse_traffic_class_flow_label() {
+        let repr = Repr {
+            traffic_class: 0xb8,
+            flow_label: 0x54321,
+            ..packet_repr()
+        };
+        let mut bytes = vec![0xff; repr.buffer_len() + repr.payload_len];
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        repr.emit(&mut packet);
+        assert_eq!(packet.traffic_class(), 0xb8);
+        assert_eq!(packet.flow_label(), 0x54321);
+        let packet = Packet::new_unchecked(&*packet.into_inner());
+        assert_eq!(Repr::parse(&packet), Ok(repr));
+    }
+
     #[test]
     fn test_repr_parse_bad_version() {
         let mut bytes = vec![0; 40];
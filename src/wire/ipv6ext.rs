@@ -0,0 +1,168 @@
+use heapless::Vec;
+
+use super::{Error, Result};
+use super::{IpProtocol, Ipv6HopByHopHeader, Ipv6Option, Ipv6OptionRepr};
+
+/// The maximum length of the options of a single extension header.
+pub const MAX_OPTIONS_LEN: usize = 30;
+
+/// A set of IPv6 extension headers to insert between the IPv6 header and the
+/// upper-layer header of a packet.
+///
+/// Options are added one at a time, and each header is padded to a multiple of
+/// 8 octets when emitted. The Hop-by-Hop Options header, if any, is emitted first,
+/// followed by the Destination Options header.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExtHeaders {
+    hop_by_hop: Vec<u8, MAX_OPTIONS_LEN>,
+    destination: Vec<u8, MAX_OPTIONS_LEN>,
+}
+
+impl ExtHeaders {
+    /// Create an empty set of extension headers.
+    pub const fn new() -> Self {
+        Self {
+            hop_by_hop: Vec::new(),
+            destination: Vec::new(),
+        }
+    }
+
+    /// Add an option to the Hop-by-Hop Options header.
+    ///
+    /// Returns `Err(Error)` if it does not fit in the header.
+    pub fn push_hop_by_hop(&mut self, option: Ipv6OptionRepr) -> Result<()> {
+        push_option(&mut self.hop_by_hop, option)
+    }
+
+    /// Add an option to the Destination Options header.
+    ///
+    /// Returns `Err(Error)` if it does not fit in the header.
+    pub fn push_destination(&mut self, option: Ipv6OptionRepr) -> Result<()> {
+        push_option(&mut self.destination, option)
+    }
+
+    /// Return whether no header is to be inserted.
+    pub fn is_empty(&self) -> bool {
+        self.hop_by_hop.is_empty() && self.destination.is_empty()
+    }
+
+    /// Return the type of the first header, or `next_header` if there is none.
+    pub fn first_header(&self, next_header: IpProtocol) -> IpProtocol {
+        if !self.hop_by_hop.is_empty() {
+            IpProtocol::HopByHop
+        } else if !self.destination.is_empty() {
+            IpProtocol::Ipv6Opts
+        } else {
+            next_header
+        }
+    }
+
+    /// Return the length, in bytes, of the emitted headers.
+    pub fn buffer_len(&self) -> usize {
+        header_len(&self.hop_by_hop) + header_len(&self.destination)
+    }
+
+    /// Emit the headers into `buffer`, the last one pointing to `next_header`.
+    ///
+    /// # Panics
+    /// This function panics if `buffer` is shorter than [buffer_len](#method.buffer_len).
+    pub fn emit(&self, next_header: IpProtocol, buffer: &mut [u8]) {
+        let (hop_by_hop, destination) = buffer.split_at_mut(header_len(&self.hop_by_hop));
+        let after_hop_by_hop = if self.destination.is_empty() {
+            next_header
+        } else {
+            IpProtocol::Ipv6Opts
+        };
+        emit_header(&self.hop_by_hop, after_hop_by_hop, hop_by_hop);
+        emit_header(&self.destination, next_header, destination);
+    }
+}
+
+fn push_option(options: &mut Vec<u8, MAX_OPTIONS_LEN>, option: Ipv6OptionRepr) -> Result<()> {
+    let start = options.len();
+    options
+        .resize_default(start + option.buffer_len())
+        .map_err(|_| Error)?;
+    let option: Ipv6OptionRepr = option;
+    option.emit(&mut Ipv6Option::new_unchecked(&mut options[start..]));
+    Ok(())
+}
+
+fn header_len(options: &[u8]) -> usize {
+    match options.len() {
+        0 => 0,
+        len => (len + 2 + 7) / 8 * 8,
+    }
+}
+
+fn emit_header(options: &[u8], next_header: IpProtocol, buffer: &mut [u8]) {
+    let len = header_len(options);
+    if len == 0 {
+        return;
+    }
+    let mut header = Ipv6HopByHopHeader::new_unchecked(&mut buffer[..len]);
+    header.set_next_header(next_header);
+    header.set_header_len((len / 8 - 1) as u8);
+
+    let buffer = header.options_mut();
+    buffer[..options.len()].copy_from_slice(options);
+    let padding = match buffer.len() - options.len() {
+        0 => return,
+        1 => Ipv6OptionRepr::Pad1,
+        n => Ipv6OptionRepr::PadN(n as u8 - 2),
+    };
+    padding.emit(&mut Ipv6Option::new_unchecked(&mut buffer[options.len()..]));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A Router Alert option padded with a PadN option.
+    static ROUTER_ALERT: [u8; 8] = [0x3a, 0x00, 0x05, 0x02, 0x00, 0x00, 0x01, 0x00];
+
+    #[test]
+    fn test_emit() {
+        let mut headers = ExtHeaders::new();
+        assert!(headers.is_empty());
+        headers
+            .push_hop_by_hop(Ipv6OptionRepr::Unknown {
+                type_: 5.into(),
+                length: 2,
+                data: &[0, 0],
+            })
+            .unwrap();
+        assert_eq!(
+            headers.first_header(IpProtocol::Icmpv6),
+            IpProtocol::HopByHop
+        );
+        assert_eq!(headers.buffer_len(), 8);
+
+        let mut bytes = [0xff; 8];
+        headers.emit(IpProtocol::Icmpv6, &mut bytes);
+        assert_eq!(bytes, ROUTER_ALERT);
+    }
+
+    #[test]
+    fn test_emit_both() {
+        let mut headers = ExtHeaders::new();
+        headers.push_hop_by_hop(Ipv6OptionRepr::PadN(0)).unwrap();
+        headers.push_destination(Ipv6OptionRepr::PadN(4)).unwrap();
+        assert_eq!(headers.buffer_len(), 16);
+
+        let mut bytes = [0xff; 16];
+        headers.emit(IpProtocol::Udp, &mut bytes);
+        assert_eq!(bytes[0], u8::from(IpProtocol::Ipv6Opts));
+        assert_eq!(bytes[8], u8::from(IpProtocol::Udp));
+        assert_eq!(bytes[9], 0);
+    }
+
+    #[test]
+    fn test_full() {
+        let mut headers = ExtHeaders::new();
+        headers.push_destination(Ipv6OptionRepr::PadN(28)).unwrap();
+        assert_eq!(headers.push_destination(Ipv6OptionRepr::Pad1), Err(Error));
+        assert_eq!(headers.buffer_len(), 32);
+    }
+}
@@ -53,6 +53,7 @@ let repr = Ipv4Repr {
     next_header: IpProtocol::Tcp,
     payload_len: 10,
     hop_limit:   64,
+    dscp: 0,
 };
 let mut buffer = vec![0; repr.buffer_len() + repr.payload_len];
 { // emission
@@ -77,6 +78,8 @@ mod field {
 
 pub mod pretty_print;
 
+#[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
+mod ah;
 #[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
 mod arp;
 #[cfg(feature = "proto-dhcpv4")]
@@ -85,9 +88,13 @@ pub(crate) mod dhcpv4;
 pub(crate) mod dhcpv6;
 #[cfg(feature = "proto-dns")]
 pub(crate) mod dns;
+#[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
+mod esp;
 #[cfg(feature = "medium-ethernet")]
 mod ethernet;
 #[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
+mod gre;
+#[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
 mod icmp;
 #[cfg(feature = "proto-ipv4")]
 mod icmpv4;
@@ -103,6 +110,8 @@ mod ipv4;
 #[cfg(feature = "proto-ipv6")]
 mod ipv6;
 #[cfg(feature = "proto-ipv6")]
+mod ipv6ext;
+#[cfg(feature = "proto-ipv6")]
 mod ipv6fragment;
 #[cfg(feature = "proto-ipv6")]
 mod ipv6hopbyhop;
@@ -122,21 +131,39 @@ mod ndisc;
     any(feature = "medium-ethernet", feature = "medium-ieee802154")
 ))]
 mod ndiscoption;
+#[cfg(feature = "medium-ethernet")]
+mod pause;
+#[cfg(feature = "phy-ppp")]
+mod ppp;
+#[cfg(any(test, feature = "proptest"))]
+pub mod proptest;
+mod ptp;
+#[cfg(feature = "proto-rpl")]
+mod rpl;
+mod sctp;
 #[cfg(all(feature = "proto-sixlowpan", feature = "medium-ieee802154"))]
 mod sixlowpan;
+#[cfg(feature = "proto-stamp")]
+mod stamp;
 mod tcp;
+#[cfg(feature = "proto-tftp")]
+mod tftp;
 mod udp;
+#[cfg(feature = "medium-ethernet")]
+mod vxlan;
+mod wireguard;
 
 use core::fmt;
 
 use crate::phy::Medium;
 
-pub use self::pretty_print::PrettyPrinter;
+pub use self::pretty_print::{dissect, Dissection, PrettyPrinter};
 
 #[cfg(feature = "medium-ethernet")]
 pub use self::ethernet::{
-    Address as EthernetAddress, EtherType as EthernetProtocol, Frame as EthernetFrame,
-    Repr as EthernetRepr, HEADER_LEN as ETHERNET_HEADER_LEN,
+    Address as EthernetAddress, Encapsulation as EthernetEncapsulation,
+    EtherType as EthernetProtocol, Frame as EthernetFrame, Repr as EthernetRepr,
+    HEADER_LEN as ETHERNET_HEADER_LEN, SNAP_HEADER_LEN as ETHERNET_SNAP_HEADER_LEN,
 };
 
 #[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
@@ -154,13 +181,31 @@ pub use self::sixlowpan::{
         UdpNhcRepr as SixlowpanUdpNhcRepr,
     },
     AddressContext as SixlowpanAddressContext, NextHeader as SixlowpanNextHeader, SixlowpanPacket,
+    ADDRESS_CONTEXT_COUNT as SIXLOWPAN_ADDRESS_CONTEXT_COUNT,
 };
 
 #[cfg(feature = "medium-ieee802154")]
 pub use self::ieee802154::{
     Address as Ieee802154Address, AddressingMode as Ieee802154AddressingMode,
-    Frame as Ieee802154Frame, FrameType as Ieee802154FrameType,
-    FrameVersion as Ieee802154FrameVersion, Pan as Ieee802154Pan, Repr as Ieee802154Repr,
+    AssociationStatus as Ieee802154AssociationStatus, BeaconRepr as Ieee802154BeaconRepr,
+    CapabilityInformation as Ieee802154CapabilityInformation, CommandId as Ieee802154CommandId,
+    CommandRepr as Ieee802154CommandRepr, Frame as Ieee802154Frame,
+    FrameType as Ieee802154FrameType, FrameVersion as Ieee802154FrameVersion, Pan as Ieee802154Pan,
+    Repr as Ieee802154Repr, SuperframeSpecification as Ieee802154SuperframeSpecification,
+};
+
+#[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
+pub use self::esp::{
+    Packet as EspPacket, Repr as EspRepr, Trailer as EspTrailer, HEADER_LEN as ESP_HEADER_LEN,
+    TRAILER_LEN as ESP_TRAILER_LEN,
+};
+
+#[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
+pub use self::ah::{Packet as AhPacket, Repr as AhRepr, HEADER_LEN as AH_HEADER_LEN};
+
+#[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
+pub use self::gre::{
+    Packet as GrePacket, Protocol as GreProtocol, Repr as GreRepr, HEADER_LEN as GRE_HEADER_LEN,
 };
 
 pub use self::ip::{
@@ -190,6 +235,9 @@ pub use self::ipv6option::{
 #[cfg(feature = "proto-ipv6")]
 pub use self::ipv6hopbyhop::{Header as Ipv6HopByHopHeader, Repr as Ipv6HopByHopRepr};
 
+#[cfg(feature = "proto-ipv6")]
+pub use self::ipv6ext::ExtHeaders as Ipv6ExtHeaders;
+
 #[cfg(feature = "proto-ipv6")]
 pub use self::ipv6fragment::{Header as Ipv6FragmentHeader, Repr as Ipv6FragmentRepr};
 
@@ -215,7 +263,10 @@ pub use self::icmpv6::{
 };
 
 #[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
-pub use self::icmp::Repr as IcmpRepr;
+pub use self::icmp::{
+    ExtendedEchoCode as IcmpExtendedEchoCode, ExtendedEchoState as IcmpExtendedEchoState,
+    InterfaceIdent as IcmpInterfaceIdent, Repr as IcmpRepr,
+};
 
 #[cfg(all(
     feature = "proto-ipv6",
@@ -230,41 +281,123 @@ pub use self::ndisc::{
     any(feature = "medium-ethernet", feature = "medium-ieee802154")
 ))]
 pub use self::ndiscoption::{
-    NdiscOption, PrefixInfoFlags as NdiscPrefixInfoFlags,
+    AddressRegistration as NdiscAddressRegistration,
+    AddressRegistrationStatus as NdiscAddressRegistrationStatus, NdiscOption,
+    Pref64 as NdiscPref64, PrefixInfoFlags as NdiscPrefixInfoFlags,
     PrefixInformation as NdiscPrefixInformation, RedirectedHeader as NdiscRedirectedHeader,
-    Repr as NdiscOptionRepr, Type as NdiscOptionType,
+    Repr as NdiscOptionRepr, SixlowpanContext as NdiscSixlowpanContext, Type as NdiscOptionType,
 };
 
 #[cfg(feature = "proto-ipv6")]
 pub use self::mld::{AddressRecord as MldAddressRecord, Repr as MldRepr};
 
+#[cfg(feature = "medium-ethernet")]
+pub use self::pause::{
+    Packet as PausePacket, Repr as PauseRepr, MAX_QUANTA as PAUSE_MAX_QUANTA,
+    MULTICAST_ADDR as PAUSE_MULTICAST_ADDR, PAYLOAD_LEN as PAUSE_PAYLOAD_LEN,
+};
+
+#[cfg(feature = "proto-rpl")]
+pub use self::rpl::{
+    Code as RplCode, DodagConfiguration as RplDodagConfiguration,
+    ModeOfOperation as RplModeOfOperation, OptionRepr as RplOptionRepr,
+    OptionType as RplOptionType, OptionsIterator as RplOptionsIterator, Repr as RplRepr, RplOption,
+    SolicitedInformation as RplSolicitedInformation, TransitInformation as RplTransitInformation,
+    INFINITE_RANK as RPL_INFINITE_RANK,
+};
+
+pub use self::sctp::{
+    Chunk as SctpChunk, ChunkIterator as SctpChunkIterator, ChunkRepr as SctpChunkRepr,
+    ChunkType as SctpChunkType, InitRepr as SctpInitRepr, Packet as SctpPacket, Repr as SctpRepr,
+    CHUNK_HEADER_LEN as SCTP_CHUNK_HEADER_LEN, HEADER_LEN as SCTP_HEADER_LEN,
+};
+
 pub use self::udp::{Packet as UdpPacket, Repr as UdpRepr, HEADER_LEN as UDP_HEADER_LEN};
 
+#[cfg(feature = "medium-ethernet")]
+pub use self::vxlan::{
+    Packet as VxlanPacket, Repr as VxlanRepr, HEADER_LEN as VXLAN_HEADER_LEN,
+    MAX_VNI as VXLAN_MAX_VNI, PORT as VXLAN_PORT,
+};
+
+pub use self::wireguard::{
+    MessageType as WireguardMessageType, Packet as WireguardPacket, Repr as WireguardRepr,
+    COOKIE_REPLY_LEN as WIREGUARD_COOKIE_REPLY_LEN,
+    HANDSHAKE_INITIATION_LEN as WIREGUARD_HANDSHAKE_INITIATION_LEN,
+    HANDSHAKE_RESPONSE_LEN as WIREGUARD_HANDSHAKE_RESPONSE_LEN, PORT as WIREGUARD_PORT,
+    TAG_LEN as WIREGUARD_TAG_LEN, TRANSPORT_HEADER_LEN as WIREGUARD_TRANSPORT_HEADER_LEN,
+};
+
 pub use self::tcp::{
     Control as TcpControl, Packet as TcpPacket, Repr as TcpRepr, SeqNumber as TcpSeqNumber,
-    TcpOption, HEADER_LEN as TCP_HEADER_LEN,
+    TcpOption, TimestampRepr as TcpTimestampRepr, HEADER_LEN as TCP_HEADER_LEN,
 };
 
 #[cfg(feature = "proto-dhcpv4")]
 pub use self::dhcpv4::{
     DhcpOption, DhcpOptionWriter, MessageType as DhcpMessageType, Packet as DhcpPacket,
-    Repr as DhcpRepr, CLIENT_PORT as DHCP_CLIENT_PORT,
+    RelayAgentInfo as DhcpRelayAgentInfo, Repr as DhcpRepr, CLIENT_PORT as DHCP_CLIENT_PORT,
     MAX_DNS_SERVER_COUNT as DHCP_MAX_DNS_SERVER_COUNT, SERVER_PORT as DHCP_SERVER_PORT,
 };
 
+/// The maximum length of a captive portal URI kept by the DHCP sockets, which is also the
+/// maximum length of a DHCPv4 option.
+#[cfg(any(feature = "proto-dhcpv4", feature = "proto-dhcpv6"))]
+pub const DHCP_MAX_CAPTIVE_PORTAL_LEN: usize = 255;
+
 #[cfg(feature = "proto-dhcpv6")]
 pub use self::dhcpv6::{
     Dhcpv6Option, Dhcpv6OptionWriter, MessageType as Dhcpv6MessageType, Packet as Dhcpv6Packet,
-    Repr as Dhcpv6Repr, CLIENT_PORT as DHCPV6_CLIENT_PORT,
-    ReprIaNa as Dhcpv6ReprIaNa, ReprIaTa as Dhcpv6ReprIaTa, ReprIaAddr as Dhcpv6ReprIaAddr,
-    ReprIaPrefix as Dhcpv6ReprIaPrefix,
-    ReprDnsServers as Dhcpv6ReprDnsServers, ReprStatusCode as Dhcpv6ReprStatusCode, StatusCode as Dhcpv6StatusCode,
-    MAX_REQUEST_OPTIONS as DHCPV6_MAX_REQUEST_OPTIONS, SERVER_PORT as DHCPV6_SERVER_PORT,
+    Repr as Dhcpv6Repr, ReprDnsServers as Dhcpv6ReprDnsServers, ReprIaAddr as Dhcpv6ReprIaAddr,
+    ReprIaNa as Dhcpv6ReprIaNa, ReprIaPrefix as Dhcpv6ReprIaPrefix, ReprIaTa as Dhcpv6ReprIaTa,
+    ReprStatusCode as Dhcpv6ReprStatusCode, StatusCode as Dhcpv6StatusCode,
+    StatusMessage as Dhcpv6StatusMessage, CLIENT_PORT as DHCPV6_CLIENT_PORT,
     MAX_DNS_ADDRESSES as DHCPV6_MAX_DNS_ADDRESSES, MAX_IA_ADDRESSES as DHCPV6_MAX_IA_ADDRESSES,
+    MAX_REQUEST_OPTIONS as DHCPV6_MAX_REQUEST_OPTIONS, SERVER_PORT as DHCPV6_SERVER_PORT,
+};
+
+#[cfg(feature = "phy-ppp")]
+pub use self::ppp::{
+    fcs_update as ppp_fcs_update, Code as PppCode, ConfigOption as PppConfigOption,
+    ConfigOptions as PppConfigOptions, IpcpOption as PppIpcpOption,
+    Ipv6cpOption as PppIpv6cpOption, LcpOption as PppLcpOption, Packet as PppPacket,
+    Protocol as PppProtocol, FCS_GOOD as PPP_FCS_GOOD, FCS_INIT as PPP_FCS_INIT,
+    HDLC_ADDRESS as PPP_HDLC_ADDRESS, HDLC_CONTROL as PPP_HDLC_CONTROL,
+    HDLC_ESCAPE as PPP_HDLC_ESCAPE, HDLC_FLAG as PPP_HDLC_FLAG, HEADER_LEN as PPP_HEADER_LEN,
+};
+
+#[cfg(feature = "medium-ethernet")]
+pub use self::ptp::ETHERNET_MULTICAST_ADDR as PTP_ETHERNET_MULTICAST_ADDR;
+#[cfg(feature = "proto-ipv4")]
+pub use self::ptp::IPV4_MULTICAST_ADDR as PTP_IPV4_MULTICAST_ADDR;
+#[cfg(feature = "proto-ipv6")]
+pub use self::ptp::IPV6_MULTICAST_ADDR as PTP_IPV6_MULTICAST_ADDR;
+pub use self::ptp::{
+    Message as PtpMessage, MessageType as PtpMessageType, Packet as PtpPacket,
+    PortIdentity as PtpPortIdentity, Repr as PtpRepr, Timestamp as PtpTimestamp,
+    EVENT_PORT as PTP_EVENT_PORT, FLAG_TWO_STEP as PTP_FLAG_TWO_STEP,
+    GENERAL_PORT as PTP_GENERAL_PORT, HEADER_LEN as PTP_HEADER_LEN,
 };
 
 #[cfg(feature = "proto-dns")]
-pub use self::dns::{Packet as DnsPacket, Repr as DnsRepr, Type as DnsQueryType};
+pub use self::dns::{
+    Packet as DnsPacket, Repr as DnsRepr, TsigMac as DnsTsigMac, Type as DnsQueryType,
+};
+
+#[cfg(feature = "proto-tftp")]
+pub use self::tftp::{
+    ErrorCode as TftpErrorCode, Mode as TftpMode, Opcode as TftpOpcode, Packet as TftpPacket,
+    Repr as TftpRepr, TransferOptions as TftpTransferOptions,
+    DEFAULT_BLOCK_SIZE as TFTP_DEFAULT_BLOCK_SIZE, MAX_BLOCK_SIZE as TFTP_MAX_BLOCK_SIZE,
+    MIN_BLOCK_SIZE as TFTP_MIN_BLOCK_SIZE, SERVER_PORT as TFTP_SERVER_PORT,
+};
+
+#[cfg(feature = "proto-stamp")]
+pub use self::stamp::{
+    ErrorEstimate as StampErrorEstimate, Packet as StampPacket,
+    ReflectorRepr as StampReflectorRepr, SenderRepr as StampSenderRepr,
+    PACKET_LEN as STAMP_PACKET_LEN, PORT as STAMP_PORT,
+};
 
 /// Parsing a packet failed.
 ///
@@ -6,8 +6,11 @@ use crate::time::Duration;
 use crate::wire::icmpv6::{field, Message, Packet};
 use crate::wire::Ipv6Address;
 use crate::wire::RawHardwareAddress;
+use crate::wire::{
+    NdiscAddressRegistration, NdiscPref64, NdiscPrefixInformation, NdiscRedirectedHeader,
+    NdiscSixlowpanContext,
+};
 use crate::wire::{NdiscOption, NdiscOptionRepr};
-use crate::wire::{NdiscPrefixInformation, NdiscRedirectedHeader};
 
 bitflags! {
     #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -208,15 +211,20 @@ pub enum Repr<'a> {
         lladdr: Option<RawHardwareAddress>,
         mtu: Option<u32>,
         prefix_info: Option<NdiscPrefixInformation>,
+        captive_portal: Option<&'a str>,
+        pref64: Option<NdiscPref64>,
+        sixlowpan_context: Option<NdiscSixlowpanContext>,
     },
     NeighborSolicit {
         target_addr: Ipv6Address,
         lladdr: Option<RawHardwareAddress>,
+        aro: Option<NdiscAddressRegistration>,
     },
     NeighborAdvert {
         flags: NeighborFlags,
         target_addr: Ipv6Address,
         lladdr: Option<RawHardwareAddress>,
+        aro: Option<NdiscAddressRegistration>,
     },
     Redirect {
         target_addr: Ipv6Address,
@@ -269,12 +277,19 @@ impl<'a> Repr<'a> {
                 Ok(Repr::RouterSolicit { lladdr })
             }
             Message::RouterAdvert => {
-                let (mut lladdr, mut mtu, mut prefix_info) = (None, None, None);
+                let (mut lladdr, mut mtu, mut prefix_info, mut captive_portal, mut pref64) =
+                    (None, None, None, None, None);
+                let mut sixlowpan_context = None;
                 foreach_option(packet.payload(), |opt| {
                     match opt {
                         NdiscOptionRepr::SourceLinkLayerAddr(addr) => lladdr = Some(addr),
                         NdiscOptionRepr::Mtu(val) => mtu = Some(val),
                         NdiscOptionRepr::PrefixInformation(info) => prefix_info = Some(info),
+                        NdiscOptionRepr::CaptivePortal(uri) => captive_portal = Some(uri),
+                        NdiscOptionRepr::Pref64(info) => pref64 = Some(info),
+                        NdiscOptionRepr::SixlowpanContext(context) => {
+                            sixlowpan_context = Some(context)
+                        }
                         _ => {}
                     }
                     Ok(())
@@ -288,13 +303,19 @@ impl<'a> Repr<'a> {
                     lladdr,
                     mtu,
                     prefix_info,
+                    captive_portal,
+                    pref64,
+                    sixlowpan_context,
                 })
             }
             Message::NeighborSolicit => {
-                let mut lladdr = None;
+                let (mut lladdr, mut aro) = (None, None);
                 foreach_option(packet.payload(), |opt| {
                     match opt {
                         NdiscOptionRepr::SourceLinkLayerAddr(addr) => lladdr = Some(addr),
+                        NdiscOptionRepr::AddressRegistration(registration) => {
+                            aro = Some(registration)
+                        }
                         _ => {}
                     }
                     Ok(())
@@ -302,13 +323,17 @@ impl<'a> Repr<'a> {
                 Ok(Repr::NeighborSolicit {
                     target_addr: packet.target_addr(),
                     lladdr,
+                    aro,
                 })
             }
             Message::NeighborAdvert => {
-                let mut lladdr = None;
+                let (mut lladdr, mut aro) = (None, None);
                 foreach_option(packet.payload(), |opt| {
                     match opt {
                         NdiscOptionRepr::TargetLinkLayerAddr(addr) => lladdr = Some(addr),
+                        NdiscOptionRepr::AddressRegistration(registration) => {
+                            aro = Some(registration)
+                        }
                         _ => {}
                     }
                     Ok(())
@@ -317,6 +342,7 @@ impl<'a> Repr<'a> {
                     flags: packet.neighbor_flags(),
                     target_addr: packet.target_addr(),
                     lladdr,
+                    aro,
                 })
             }
             Message::Redirect => {
@@ -353,6 +379,9 @@ impl<'a> Repr<'a> {
                 lladdr,
                 mtu,
                 prefix_info,
+                captive_portal,
+                pref64,
+                sixlowpan_context,
                 ..
             } => {
                 let mut offset = 0;
@@ -365,13 +394,26 @@ impl<'a> Repr<'a> {
                 if let Some(prefix_info) = prefix_info {
                     offset += NdiscOptionRepr::PrefixInformation(prefix_info).buffer_len();
                 }
+                if let Some(uri) = captive_portal {
+                    offset += NdiscOptionRepr::CaptivePortal(uri).buffer_len();
+                }
+                if let Some(pref64) = pref64 {
+                    offset += NdiscOptionRepr::Pref64(pref64).buffer_len();
+                }
+                if let Some(context) = sixlowpan_context {
+                    offset += NdiscOptionRepr::SixlowpanContext(context).buffer_len();
+                }
                 field::RETRANS_TM.end + offset
             }
-            &Repr::NeighborSolicit { lladdr, .. } | &Repr::NeighborAdvert { lladdr, .. } => {
+            &Repr::NeighborSolicit { lladdr, aro, .. }
+            | &Repr::NeighborAdvert { lladdr, aro, .. } => {
                 let mut offset = field::TARGET_ADDR.end;
                 if let Some(lladdr) = lladdr {
                     offset += NdiscOptionRepr::SourceLinkLayerAddr(lladdr).buffer_len();
                 }
+                if let Some(aro) = aro {
+                    offset += NdiscOptionRepr::AddressRegistration(aro).buffer_len();
+                }
                 offset
             }
             &Repr::Redirect {
@@ -417,6 +459,9 @@ impl<'a> Repr<'a> {
                 lladdr,
                 mtu,
                 prefix_info,
+                captive_portal,
+                pref64,
+                sixlowpan_context,
             } => {
                 packet.set_msg_type(Message::RouterAdvert);
                 packet.set_msg_code(0);
@@ -441,21 +486,47 @@ impl<'a> Repr<'a> {
                 if let Some(prefix_info) = prefix_info {
                     let mut opt_pkt =
                         NdiscOption::new_unchecked(&mut packet.payload_mut()[offset..]);
-                    NdiscOptionRepr::PrefixInformation(prefix_info).emit(&mut opt_pkt)
+                    NdiscOptionRepr::PrefixInformation(prefix_info).emit(&mut opt_pkt);
+                    offset += NdiscOptionRepr::PrefixInformation(prefix_info).buffer_len();
+                }
+                if let Some(uri) = captive_portal {
+                    let mut opt_pkt =
+                        NdiscOption::new_unchecked(&mut packet.payload_mut()[offset..]);
+                    NdiscOptionRepr::CaptivePortal(uri).emit(&mut opt_pkt);
+                    offset += NdiscOptionRepr::CaptivePortal(uri).buffer_len();
+                }
+                if let Some(pref64) = pref64 {
+                    let mut opt_pkt =
+                        NdiscOption::new_unchecked(&mut packet.payload_mut()[offset..]);
+                    NdiscOptionRepr::Pref64(pref64).emit(&mut opt_pkt);
+                    offset += NdiscOptionRepr::Pref64(pref64).buffer_len();
+                }
+                if let Some(context) = sixlowpan_context {
+                    let mut opt_pkt =
+                        NdiscOption::new_unchecked(&mut packet.payload_mut()[offset..]);
+                    NdiscOptionRepr::SixlowpanContext(context).emit(&mut opt_pkt)
                 }
             }
 
             Repr::NeighborSolicit {
                 target_addr,
                 lladdr,
+                aro,
             } => {
                 packet.set_msg_type(Message::NeighborSolicit);
                 packet.set_msg_code(0);
                 packet.clear_reserved();
                 packet.set_target_addr(target_addr);
+                let mut offset = 0;
                 if let Some(lladdr) = lladdr {
                     let mut opt_pkt = NdiscOption::new_unchecked(packet.payload_mut());
                     NdiscOptionRepr::SourceLinkLayerAddr(lladdr).emit(&mut opt_pkt);
+                    offset += NdiscOptionRepr::SourceLinkLayerAddr(lladdr).buffer_len();
+                }
+                if let Some(aro) = aro {
+                    let mut opt_pkt =
+                        NdiscOption::new_unchecked(&mut packet.payload_mut()[offset..]);
+                    NdiscOptionRepr::AddressRegistration(aro).emit(&mut opt_pkt);
                 }
             }
 
@@ -463,15 +534,23 @@ impl<'a> Repr<'a> {
                 flags,
                 target_addr,
                 lladdr,
+                aro,
             } => {
                 packet.set_msg_type(Message::NeighborAdvert);
                 packet.set_msg_code(0);
                 packet.clear_reserved();
                 packet.set_neighbor_flags(flags);
                 packet.set_target_addr(target_addr);
+                let mut offset = 0;
                 if let Some(lladdr) = lladdr {
                     let mut opt_pkt = NdiscOption::new_unchecked(packet.payload_mut());
                     NdiscOptionRepr::TargetLinkLayerAddr(lladdr).emit(&mut opt_pkt);
+                    offset += NdiscOptionRepr::TargetLinkLayerAddr(lladdr).buffer_len();
+                }
+                if let Some(aro) = aro {
+                    let mut opt_pkt =
+                        NdiscOption::new_unchecked(&mut packet.payload_mut()[offset..]);
+                    NdiscOptionRepr::AddressRegistration(aro).emit(&mut opt_pkt);
                 }
             }
 
@@ -529,6 +608,9 @@ mod test {
             lladdr: Some(EthernetAddress([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]).into()),
             mtu: None,
             prefix_info: None,
+            captive_portal: None,
+            pref64: None,
+            sixlowpan_context: None,
         })
     }
 
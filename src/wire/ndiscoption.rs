@@ -4,7 +4,7 @@ use core::fmt;
 
 use super::{Error, Result};
 use crate::time::Duration;
-use crate::wire::{Ipv6Address, Ipv6Packet, Ipv6Repr, MAX_HARDWARE_ADDRESS_LEN};
+use crate::wire::{Ipv6Address, Ipv6Cidr, Ipv6Packet, Ipv6Repr, MAX_HARDWARE_ADDRESS_LEN};
 
 use crate::wire::RawHardwareAddress;
 
@@ -20,7 +20,15 @@ enum_with_unknown! {
         /// Redirected Header
         RedirectedHeader    = 0x4,
         /// MTU
-        Mtu                 = 0x5
+        Mtu                 = 0x5,
+        /// Address Registration
+        AddressRegistration = 0x21,
+        /// 6LoWPAN Context
+        SixlowpanContext    = 0x22,
+        /// Captive-Portal
+        CaptivePortal       = 0x25,
+        /// PREF64
+        Pref64              = 0x26
     }
 }
 
@@ -32,11 +40,40 @@ impl fmt::Display for Type {
             Type::PrefixInformation => write!(f, "prefix information"),
             Type::RedirectedHeader => write!(f, "redirected header"),
             Type::Mtu => write!(f, "mtu"),
+            Type::AddressRegistration => write!(f, "address registration"),
+            Type::SixlowpanContext => write!(f, "6lowpan context"),
+            Type::CaptivePortal => write!(f, "captive portal"),
+            Type::Pref64 => write!(f, "pref64"),
             Type::Unknown(id) => write!(f, "{id}"),
         }
     }
 }
 
+enum_with_unknown! {
+    /// Status of an Address Registration, see [RFC 6775 § 4.1].
+    ///
+    /// [RFC 6775 § 4.1]: https://tools.ietf.org/html/rfc6775#section-4.1
+    pub enum AddressRegistrationStatus(u8) {
+        /// The address was registered
+        Success           = 0,
+        /// The address is already registered by another node
+        Duplicate         = 1,
+        /// The router has no room left in its neighbor cache
+        NeighborCacheFull = 2
+    }
+}
+
+impl fmt::Display for AddressRegistrationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressRegistrationStatus::Success => write!(f, "success"),
+            AddressRegistrationStatus::Duplicate => write!(f, "duplicate"),
+            AddressRegistrationStatus::NeighborCacheFull => write!(f, "neighbor cache full"),
+            AddressRegistrationStatus::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
 bitflags! {
     #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct PrefixInfoFlags: u8 {
@@ -142,6 +179,64 @@ mod field {
 
     //  MTU
     pub const MTU: Field = 4..8;
+
+    // PREF64 Option fields.
+    //  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    //  |     Type      |    Length     |     Scaled Lifetime     | PLC |
+    //  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    //  |                                                               |
+    //  +                                                               +
+    //  |              Highest 96 bits of the Prefix                    |
+    //  +                                                               +
+    //  |                                                               |
+    //  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+    // Scaled lifetime, in units of 8 seconds, and Prefix Length Code.
+    pub const PREF64_LT_PLC: Field = 2..4;
+    // Highest 96 bits of the prefix.
+    pub const PREF64_PREFIX: Field = 4..16;
+
+    // Address Registration Option fields.
+    //  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    //  |     Type      |   Length = 2  |    Status     |   Reserved    |
+    //  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    //  |           Reserved            |     Registration Lifetime     |
+    //  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    //  |                                                               |
+    //  +                            EUI-64                             +
+    //  |                                                               |
+    //  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+    // Status of the registration.
+    pub const ARO_STATUS: usize = 2;
+    // Reserved bits.
+    pub const ARO_RESERVED: Field = 3..6;
+    // Registration lifetime, in units of 60 seconds.
+    pub const ARO_LIFETIME: Field = 6..8;
+    // EUI-64 of the registering node.
+    pub const ARO_EUI64: Field = 8..16;
+
+    // 6LoWPAN Context Option fields.
+    //  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    //  |     Type      |     Length    |Context Length | Res |C|  CID  |
+    //  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    //  |            Reserved           |         Valid Lifetime        |
+    //  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    //  .                                                               .
+    //  .                       Context Prefix                          .
+    //  .                                                               .
+    //  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+    // Length of the context prefix.
+    pub const CONTEXT_LEN: usize = 2;
+    // Compression flag and context identifier.
+    pub const CONTEXT_C_CID: usize = 3;
+    // Reserved bits.
+    pub const CONTEXT_RESERVED: Field = 4..6;
+    // Valid lifetime, in units of 60 seconds.
+    pub const CONTEXT_VALID_LT: Field = 6..8;
+    // Context prefix, of 8 or 16 octets.
+    pub const CONTEXT_PREFIX: Rest = 8..;
 }
 
 /// Core getter methods relevant to any type of NDISC option.
@@ -188,6 +283,12 @@ impl<T: AsRef<[u8]>> NdiscOption<T> {
                     Type::SourceLinkLayerAddr | Type::TargetLinkLayerAddr | Type::Mtu => Ok(()),
                     Type::PrefixInformation if data_range.end >= field::PREFIX.end => Ok(()),
                     Type::RedirectedHeader if data_range.end >= field::REDIR_MIN_SZ => Ok(()),
+                    Type::CaptivePortal => Ok(()),
+                    Type::Pref64 if data_range.end >= field::PREF64_PREFIX.end => Ok(()),
+                    Type::AddressRegistration if data_range.end >= field::ARO_EUI64.end => Ok(()),
+                    Type::SixlowpanContext if data_range.end > field::CONTEXT_PREFIX.start => {
+                        Ok(())
+                    }
                     Type::Unknown(_) => Ok(()),
                     _ => Err(Error),
                 }
@@ -236,6 +337,96 @@ impl<T: AsRef<[u8]>> NdiscOption<T> {
     }
 }
 
+/// Getter methods only relevant for the PREF64 option.
+impl<T: AsRef<[u8]>> NdiscOption<T> {
+    /// Return the lifetime of the NAT64 prefix.
+    #[inline]
+    pub fn pref64_lifetime(&self) -> Duration {
+        let data = self.buffer.as_ref();
+        let scaled = NetworkEndian::read_u16(&data[field::PREF64_LT_PLC]) >> 3;
+        Duration::from_secs(scaled as u64 * 8)
+    }
+
+    /// Return the Prefix Length Code of the NAT64 prefix.
+    #[inline]
+    pub fn pref64_plc(&self) -> u8 {
+        self.buffer.as_ref()[field::PREF64_LT_PLC.end - 1] & 0x7
+    }
+
+    /// Return the NAT64 prefix, with its lowest 32 bits cleared.
+    #[inline]
+    pub fn pref64_prefix(&self) -> Ipv6Address {
+        let mut prefix = [0; 16];
+        prefix[..12].copy_from_slice(&self.buffer.as_ref()[field::PREF64_PREFIX]);
+        Ipv6Address(prefix)
+    }
+}
+
+/// Getter methods only relevant for the Address Registration option.
+impl<T: AsRef<[u8]>> NdiscOption<T> {
+    /// Return the status of the registration.
+    #[inline]
+    pub fn registration_status(&self) -> AddressRegistrationStatus {
+        AddressRegistrationStatus::from(self.buffer.as_ref()[field::ARO_STATUS])
+    }
+
+    /// Return the lifetime of the registration.
+    #[inline]
+    pub fn registration_lifetime(&self) -> Duration {
+        let data = self.buffer.as_ref();
+        let minutes = NetworkEndian::read_u16(&data[field::ARO_LIFETIME]);
+        Duration::from_secs(minutes as u64 * 60)
+    }
+
+    /// Return the EUI-64 of the registering node.
+    #[inline]
+    pub fn registration_eui64(&self) -> [u8; 8] {
+        let mut eui64 = [0; 8];
+        eui64.copy_from_slice(&self.buffer.as_ref()[field::ARO_EUI64]);
+        eui64
+    }
+}
+
+/// Getter methods only relevant for the 6LoWPAN Context option.
+impl<T: AsRef<[u8]>> NdiscOption<T> {
+    /// Return the length of the context prefix.
+    #[inline]
+    pub fn context_len(&self) -> u8 {
+        self.buffer.as_ref()[field::CONTEXT_LEN]
+    }
+
+    /// Return whether the context may be used for compression.
+    #[inline]
+    pub fn context_compression(&self) -> bool {
+        self.buffer.as_ref()[field::CONTEXT_C_CID] & 0x10 != 0
+    }
+
+    /// Return the context identifier.
+    #[inline]
+    pub fn context_id(&self) -> u8 {
+        self.buffer.as_ref()[field::CONTEXT_C_CID] & 0x0f
+    }
+
+    /// Return the valid lifetime of the context.
+    #[inline]
+    pub fn context_valid_lifetime(&self) -> Duration {
+        let data = self.buffer.as_ref();
+        let minutes = NetworkEndian::read_u16(&data[field::CONTEXT_VALID_LT]);
+        Duration::from_secs(minutes as u64 * 60)
+    }
+
+    /// Return the context prefix, with the bits following its length cleared.
+    #[inline]
+    pub fn context_prefix(&self) -> Ipv6Address {
+        let data = &self.buffer.as_ref()[field::DATA(self.data_len())];
+        let data = &data[field::CONTEXT_PREFIX.start - 2..];
+        let len = data.len().min(16);
+        let mut prefix = [0; 16];
+        prefix[..len].copy_from_slice(&data[..len]);
+        Ipv6Address(Ipv6Address(prefix).mask(self.context_len().min(128)))
+    }
+}
+
 /// Getter methods only relevant for the Prefix Information option.
 impl<T: AsRef<[u8]>> NdiscOption<T> {
     /// Return the prefix length.
@@ -319,6 +510,99 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> NdiscOption<T> {
     }
 }
 
+/// Setter methods only relevant for the PREF64 option.
+impl<T: AsRef<[u8]> + AsMut<[u8]>> NdiscOption<T> {
+    /// Set the lifetime and Prefix Length Code of the NAT64 prefix.
+    ///
+    /// The lifetime is rounded up to a multiple of 8 seconds, and capped at 65528 seconds.
+    #[inline]
+    pub fn set_pref64_lifetime_plc(&mut self, lifetime: Duration, plc: u8) {
+        let scaled = ((lifetime.secs() + 7) / 8).min(0x1fff) as u16;
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(
+            &mut data[field::PREF64_LT_PLC],
+            scaled << 3 | plc as u16 & 0x7,
+        );
+    }
+
+    /// Set the NAT64 prefix. Only its highest 96 bits are written.
+    #[inline]
+    pub fn set_pref64_prefix(&mut self, prefix: Ipv6Address) {
+        let data = self.buffer.as_mut();
+        data[field::PREF64_PREFIX].copy_from_slice(&prefix.as_bytes()[..12]);
+    }
+}
+
+/// Setter methods only relevant for the Address Registration option.
+impl<T: AsRef<[u8]> + AsMut<[u8]>> NdiscOption<T> {
+    /// Set the status of the registration.
+    #[inline]
+    pub fn set_registration_status(&mut self, status: AddressRegistrationStatus) {
+        self.buffer.as_mut()[field::ARO_STATUS] = status.into();
+    }
+
+    /// Set the lifetime of the registration, rounded up to a multiple of 60 seconds.
+    #[inline]
+    pub fn set_registration_lifetime(&mut self, time: Duration) {
+        let minutes = ((time.secs() + 59) / 60).min(0xffff) as u16;
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::ARO_LIFETIME], minutes);
+    }
+
+    /// Set the EUI-64 of the registering node.
+    #[inline]
+    pub fn set_registration_eui64(&mut self, eui64: [u8; 8]) {
+        self.buffer.as_mut()[field::ARO_EUI64].copy_from_slice(&eui64);
+    }
+
+    /// Clear the reserved bits.
+    #[inline]
+    pub fn clear_registration_reserved(&mut self) {
+        self.buffer.as_mut()[field::ARO_RESERVED].fill(0);
+    }
+}
+
+/// Setter methods only relevant for the 6LoWPAN Context option.
+impl<T: AsRef<[u8]> + AsMut<[u8]>> NdiscOption<T> {
+    /// Set the length of the context prefix.
+    #[inline]
+    pub fn set_context_len(&mut self, value: u8) {
+        self.buffer.as_mut()[field::CONTEXT_LEN] = value;
+    }
+
+    /// Set the compression flag and the context identifier.
+    #[inline]
+    pub fn set_context_c_cid(&mut self, compression: bool, context_id: u8) {
+        let c = if compression { 0x10 } else { 0 };
+        self.buffer.as_mut()[field::CONTEXT_C_CID] = c | context_id & 0x0f;
+    }
+
+    /// Set the valid lifetime of the context, rounded up to a multiple of 60 seconds.
+    #[inline]
+    pub fn set_context_valid_lifetime(&mut self, time: Duration) {
+        let minutes = ((time.secs() + 59) / 60).min(0xffff) as u16;
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::CONTEXT_VALID_LT], minutes);
+    }
+
+    /// Clear the reserved bits.
+    #[inline]
+    pub fn clear_context_reserved(&mut self) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::CONTEXT_RESERVED], 0);
+    }
+
+    /// Set the context prefix, filling the option data to its end.
+    #[inline]
+    pub fn set_context_prefix(&mut self, prefix: Ipv6Address) {
+        let len = self.data_len();
+        let data = &mut self.buffer.as_mut()[field::DATA(len)];
+        let data = &mut data[field::CONTEXT_PREFIX.start - 2..];
+        let len = data.len();
+        data.copy_from_slice(&prefix.as_bytes()[..len]);
+    }
+}
+
 /// Setter methods only relevant for the Prefix Information option.
 impl<T: AsRef<[u8]> + AsMut<[u8]>> NdiscOption<T> {
     /// Set the prefix length.
@@ -411,6 +695,58 @@ pub struct RedirectedHeader<'a> {
     pub data: &'a [u8],
 }
 
+/// The NAT64 prefix advertised by a router, see [RFC 8781].
+///
+/// [RFC 8781]: https://tools.ietf.org/html/rfc8781
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Pref64 {
+    /// How long the prefix may be used for, a multiple of 8 seconds.
+    pub lifetime: Duration,
+    /// The prefix, of length 32, 40, 48, 56, 64 or 96.
+    pub prefix: Ipv6Cidr,
+}
+
+impl Pref64 {
+    // The prefix lengths encoded by each Prefix Length Code.
+    const PREFIX_LENS: [u8; 6] = [96, 64, 56, 48, 40, 32];
+}
+
+/// The registration of an address with a router, see [RFC 6775 § 4.1].
+///
+/// A host sends it in a Neighbor Solicitation, and the router answers with its
+/// status in a Neighbor Advertisement.
+///
+/// [RFC 6775 § 4.1]: https://tools.ietf.org/html/rfc6775#section-4.1
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AddressRegistration {
+    /// The status of the registration, always `Success` in a solicitation.
+    pub status: AddressRegistrationStatus,
+    /// How long the router keeps the registration, a multiple of 60 seconds.
+    ///
+    /// A lifetime of zero removes the registration.
+    pub lifetime: Duration,
+    /// The EUI-64 identifying the registering node.
+    pub eui64: [u8; 8],
+}
+
+/// A 6LoWPAN compression context advertised by a router, see [RFC 6775 § 4.2].
+///
+/// [RFC 6775 § 4.2]: https://tools.ietf.org/html/rfc6775#section-4.2
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SixlowpanContext {
+    /// The context identifier, from 0 to 15.
+    pub context_id: u8,
+    /// Whether the context may be used for compression, and not only for decompression.
+    pub compression: bool,
+    /// How long the context is valid for, a multiple of 60 seconds.
+    pub valid_lifetime: Duration,
+    /// The context prefix.
+    pub prefix: Ipv6Cidr,
+}
+
 /// A high-level representation of an NDISC Option.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -420,6 +756,13 @@ pub enum Repr<'a> {
     PrefixInformation(PrefixInformation),
     RedirectedHeader(RedirectedHeader<'a>),
     Mtu(u32),
+    /// The URI of the captive portal API of the network, see [RFC 8910].
+    ///
+    /// [RFC 8910]: https://tools.ietf.org/html/rfc8910
+    CaptivePortal(&'a str),
+    Pref64(Pref64),
+    AddressRegistration(AddressRegistration),
+    SixlowpanContext(SixlowpanContext),
     Unknown {
         type_: u8,
         length: u8,
@@ -435,14 +778,15 @@ impl<'a> Repr<'a> {
     {
         match opt.option_type() {
             Type::SourceLinkLayerAddr => {
-                if opt.data_len() == 1 {
+                // IEEE 802.15.4 extended addresses take two units, see RFC 4944 § 8.
+                if opt.data_len() == 1 || opt.data_len() == 2 {
                     Ok(Repr::SourceLinkLayerAddr(opt.link_layer_addr()))
                 } else {
                     Err(Error)
                 }
             }
             Type::TargetLinkLayerAddr => {
-                if opt.data_len() == 1 {
+                if opt.data_len() == 1 || opt.data_len() == 2 {
                     Ok(Repr::TargetLinkLayerAddr(opt.link_layer_addr()))
                 } else {
                     Err(Error)
@@ -485,6 +829,53 @@ impl<'a> Repr<'a> {
                     Err(Error)
                 }
             }
+            Type::CaptivePortal => {
+                if opt.data_len() == 0 {
+                    return Err(Error);
+                }
+                // The URI is padded with NUL characters to the end of the option.
+                let data = opt.data();
+                let len = data.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+                match core::str::from_utf8(&data[..len]) {
+                    Ok(uri) => Ok(Repr::CaptivePortal(uri)),
+                    Err(_) => Err(Error),
+                }
+            }
+            Type::Pref64 => {
+                if opt.data_len() != 2 {
+                    return Err(Error);
+                }
+                let prefix_len = *Pref64::PREFIX_LENS
+                    .get(opt.pref64_plc() as usize)
+                    .ok_or(Error)?;
+                Ok(Repr::Pref64(Pref64 {
+                    lifetime: opt.pref64_lifetime(),
+                    prefix: Ipv6Cidr::new(opt.pref64_prefix(), prefix_len),
+                }))
+            }
+            Type::AddressRegistration => {
+                if opt.data_len() != 2 {
+                    return Err(Error);
+                }
+                Ok(Repr::AddressRegistration(AddressRegistration {
+                    status: opt.registration_status(),
+                    lifetime: opt.registration_lifetime(),
+                    eui64: opt.registration_eui64(),
+                }))
+            }
+            Type::SixlowpanContext => {
+                // The prefix is carried in 8 octets up to a length of 64, and 16 otherwise.
+                let data_len = if opt.context_len() > 64 { 3 } else { 2 };
+                if opt.context_len() > 128 || opt.data_len() != data_len {
+                    return Err(Error);
+                }
+                Ok(Repr::SixlowpanContext(SixlowpanContext {
+                    context_id: opt.context_id(),
+                    compression: opt.context_compression(),
+                    valid_lifetime: opt.context_valid_lifetime(),
+                    prefix: Ipv6Cidr::new(opt.context_prefix(), opt.context_len()),
+                }))
+            }
             Type::Unknown(id) => {
                 // A length of 0 is invalid.
                 if opt.data_len() != 0 {
@@ -513,6 +904,16 @@ impl<'a> Repr<'a> {
                 (8 + header.buffer_len() + data.len() + 7) / 8 * 8
             }
             &Repr::Mtu(_) => field::MTU.end,
+            &Repr::CaptivePortal(uri) => (2 + uri.len() + 7) / 8 * 8,
+            &Repr::Pref64(_) => field::PREF64_PREFIX.end,
+            &Repr::AddressRegistration(_) => field::ARO_EUI64.end,
+            &Repr::SixlowpanContext(SixlowpanContext { prefix, .. }) => {
+                if prefix.prefix_len() > 64 {
+                    24
+                } else {
+                    16
+                }
+            }
             &Repr::Unknown { length, .. } => field::DATA(length).end,
         }
     }
@@ -567,6 +968,46 @@ impl<'a> Repr<'a> {
                 opt.set_data_len(1);
                 opt.set_mtu(mtu);
             }
+            Repr::CaptivePortal(uri) => {
+                opt.set_option_type(Type::CaptivePortal);
+                opt.set_data_len(((uri.len() + 2 + 7) / 8) as u8); // round to next multiple of 8.
+                let data = opt.data_mut();
+                data[..uri.len()].copy_from_slice(uri.as_bytes());
+                data[uri.len()..].fill(0);
+            }
+            Repr::Pref64(Pref64 { lifetime, prefix }) => {
+                opt.set_option_type(Type::Pref64);
+                opt.set_data_len(2);
+                let plc = Pref64::PREFIX_LENS
+                    .iter()
+                    .position(|&len| len == prefix.prefix_len())
+                    .unwrap_or(0);
+                opt.set_pref64_lifetime_plc(lifetime, plc as u8);
+                opt.set_pref64_prefix(prefix.address());
+            }
+            Repr::AddressRegistration(registration) => {
+                opt.set_option_type(Type::AddressRegistration);
+                opt.set_data_len(2);
+                opt.set_registration_status(registration.status);
+                opt.clear_registration_reserved();
+                opt.set_registration_lifetime(registration.lifetime);
+                opt.set_registration_eui64(registration.eui64);
+            }
+            Repr::SixlowpanContext(context) => {
+                opt.set_option_type(Type::SixlowpanContext);
+                opt.set_data_len(if context.prefix.prefix_len() > 64 {
+                    3
+                } else {
+                    2
+                });
+                opt.set_context_len(context.prefix.prefix_len());
+                opt.set_context_c_cid(context.compression, context.context_id);
+                opt.clear_context_reserved();
+                opt.set_context_valid_lifetime(context.valid_lifetime);
+                opt.set_context_prefix(Ipv6Address(
+                    context.prefix.address().mask(context.prefix.prefix_len()),
+                ));
+            }
             Repr::Unknown {
                 type_: id,
                 length,
@@ -601,6 +1042,22 @@ impl<'a> fmt::Display for Repr<'a> {
             Repr::Mtu(mtu) => {
                 write!(f, "MTU mtu={mtu}")
             }
+            Repr::CaptivePortal(uri) => {
+                write!(f, "CaptivePortal uri={uri}")
+            }
+            Repr::Pref64(Pref64 { prefix, lifetime }) => {
+                write!(f, "Pref64 prefix={prefix} lifetime={lifetime}")
+            }
+            Repr::AddressRegistration(AddressRegistration {
+                status, lifetime, ..
+            }) => {
+                write!(f, "AddressRegistration status={status} lifetime={lifetime}")
+            }
+            Repr::SixlowpanContext(SixlowpanContext {
+                context_id, prefix, ..
+            }) => {
+                write!(f, "6LoWPANContext cid={context_id} prefix={prefix}")
+            }
             Repr::Unknown {
                 type_: id, length, ..
             } => {
@@ -634,9 +1091,12 @@ impl<T: AsRef<[u8]>> PrettyPrint for NdiscOption<T> {
 #[cfg(test)]
 mod test {
     use super::Error;
-    use super::{NdiscOption, PrefixInfoFlags, PrefixInformation, Repr, Type};
+    use super::{
+        AddressRegistration, AddressRegistrationStatus, NdiscOption, Pref64, PrefixInfoFlags,
+        PrefixInformation, Repr, SixlowpanContext, Type,
+    };
     use crate::time::Duration;
-    use crate::wire::{EthernetAddress, Ipv6Address};
+    use crate::wire::{EthernetAddress, Ipv6Address, Ipv6Cidr};
 
     static PREFIX_OPT_BYTES: [u8; 32] = [
         0x03, 0x04, 0x40, 0xc0, 0x00, 0x00, 0x03, 0x84, 0x00, 0x00, 0x03, 0xe8, 0x00, 0x00, 0x00,
@@ -737,4 +1197,104 @@ mod test {
             Ok(Repr::Mtu(1500))
         );
     }
+
+    #[test]
+    fn test_repr_captive_portal() {
+        let bytes = [
+            0x25, 0x02, 0x68, 0x74, 0x74, 0x70, 0x3a, 0x2f, 0x2f, 0x70, 0x6f, 0x72, 0x74, 0x61,
+            0x6c, 0x00,
+        ];
+        let repr = Repr::CaptivePortal("http://portal");
+        assert_eq!(
+            Repr::parse(&NdiscOption::new_checked(&bytes).unwrap()),
+            Ok(repr)
+        );
+
+        let mut buffer = [0xff; 16];
+        assert_eq!(repr.buffer_len(), 16);
+        repr.emit(&mut NdiscOption::new_unchecked(&mut buffer));
+        assert_eq!(buffer, bytes);
+    }
+
+    #[test]
+    fn test_repr_pref64() {
+        let bytes = [
+            0x26, 0x02, 0x07, 0x08, 0x00, 0x64, 0xff, 0x9b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        let repr = Repr::Pref64(Pref64 {
+            lifetime: Duration::from_secs(1800),
+            prefix: Ipv6Cidr::new(Ipv6Address::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0), 96),
+        });
+        assert_eq!(
+            Repr::parse(&NdiscOption::new_checked(&bytes).unwrap()),
+            Ok(repr)
+        );
+
+        let mut buffer = [0xff; 16];
+        assert_eq!(repr.buffer_len(), 16);
+        repr.emit(&mut NdiscOption::new_unchecked(&mut buffer));
+        assert_eq!(buffer, bytes);
+
+        // Prefix Length Codes above 5 are invalid.
+        let mut bytes = bytes;
+        bytes[3] |= 0x06;
+        assert_eq!(Repr::parse(&NdiscOption::new_unchecked(&bytes)), Err(Error));
+    }
+
+    #[test]
+    fn test_repr_sixlowpan_context() {
+        let bytes = [
+            0x22, 0x02, 0x40, 0x13, 0x00, 0x00, 0x00, 0x0a, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00,
+            0x00, 0x01,
+        ];
+        let repr = Repr::SixlowpanContext(SixlowpanContext {
+            context_id: 3,
+            compression: true,
+            valid_lifetime: Duration::from_secs(600),
+            prefix: Ipv6Cidr::new(Ipv6Address::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0), 64),
+        });
+        assert_eq!(
+            Repr::parse(&NdiscOption::new_checked(&bytes).unwrap()),
+            Ok(repr)
+        );
+
+        let mut buffer = [0xff; 16];
+        assert_eq!(repr.buffer_len(), 16);
+        repr.emit(&mut NdiscOption::new_unchecked(&mut buffer));
+        assert_eq!(buffer, bytes);
+
+        // Prefixes longer than 64 bits are carried in 16 octets.
+        let mut bytes = bytes;
+        bytes[2] = 0x50;
+        assert_eq!(Repr::parse(&NdiscOption::new_unchecked(&bytes)), Err(Error));
+    }
+
+    #[test]
+    fn test_repr_address_registration() {
+        let bytes = [
+            0x21, 0x02, 0x01, 0x00, 0x00, 0x00, 0x00, 0x05, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x01,
+        ];
+        let repr = Repr::AddressRegistration(AddressRegistration {
+            status: AddressRegistrationStatus::Duplicate,
+            lifetime: Duration::from_secs(300),
+            eui64: [0x02, 0, 0, 0, 0, 0, 0, 0x01],
+        });
+        assert_eq!(
+            Repr::parse(&NdiscOption::new_checked(&bytes).unwrap()),
+            Ok(repr)
+        );
+
+        let mut buffer = [0xff; 16];
+        assert_eq!(repr.buffer_len(), 16);
+        repr.emit(&mut NdiscOption::new_unchecked(&mut buffer));
+        assert_eq!(buffer, bytes);
+
+        // The option is always 16 octets long.
+        let mut bytes = [0; 24];
+        bytes[0] = 0x21;
+        bytes[1] = 0x03;
+        assert_eq!(Repr::parse(&NdiscOption::new_unchecked(&bytes)), Err(Error));
+    }
 }
@@ -0,0 +1,260 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+
+use super::{Error, Result};
+use crate::time::Duration;
+use crate::wire::EthernetAddress;
+
+/// The Ethernet address PAUSE frames are sent to, which bridges do not forward.
+pub const MULTICAST_ADDR: EthernetAddress = EthernetAddress([0x01, 0x80, 0xc2, 0x00, 0x00, 0x01]);
+
+/// The largest pause, in quanta.
+pub const MAX_QUANTA: u16 = u16::MAX;
+
+/// A read/write wrapper around an IEEE 802.3x MAC Control PAUSE frame payload.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+// Format of a MAC Control frame, following the Ethernet header, see IEEE 802.3 Annex 31B.
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |        Opcode (0x0001)        |         Pause quanta          |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                   Reserved (zero), 42 octets                  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+mod field {
+    use crate::wire::field::*;
+
+    pub const OPCODE: Field = 0..2;
+    pub const QUANTA: Field = 2..4;
+    pub const RESERVED: Field = 4..46;
+
+    pub const OPCODE_PAUSE: u16 = 0x0001;
+}
+
+/// The length of a PAUSE frame payload, padding the frame to the minimum Ethernet frame
+/// length.
+pub const PAYLOAD_LEN: usize = field::RESERVED.end;
+
+/// The length of a pause quantum, in bit times.
+const QUANTUM_BITS: u64 = 512;
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Imbue a raw octet buffer with PAUSE frame structure.
+    pub const fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short.
+    ///
+    /// The reserved octets are not checked, as some devices strip the padding.
+    pub fn check_len(&self) -> Result<()> {
+        if self.buffer.as_ref().len() < field::QUANTA.end {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the MAC Control opcode field.
+    #[inline]
+    pub fn opcode(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::OPCODE])
+    }
+
+    /// Return the pause time field, in quanta of 512 bit times.
+    #[inline]
+    pub fn quanta(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::QUANTA])
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the MAC Control opcode field.
+    #[inline]
+    pub fn set_opcode(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::OPCODE], value)
+    }
+
+    /// Set the pause time field, in quanta of 512 bit times.
+    #[inline]
+    pub fn set_quanta(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::QUANTA], value)
+    }
+
+    /// Clear the reserved octets following the pause time field.
+    #[inline]
+    pub fn clear_reserved(&mut self) {
+        let data = self.buffer.as_mut();
+        data[field::QUANTA.end..].fill(0);
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+/// A high-level representation of a PAUSE frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Repr {
+    /// How long the receiver should stop sending, in quanta of 512 bit times. Zero
+    /// lets it resume at once.
+    pub quanta: u16,
+}
+
+impl Repr {
+    /// Parse a PAUSE frame payload and return a high-level representation.
+    ///
+    /// Other MAC Control frames, such as priority-based flow control ones, are rejected.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(packet: &Packet<&T>) -> Result<Repr> {
+        packet.check_len()?;
+        if packet.opcode() != field::OPCODE_PAUSE {
+            return Err(Error);
+        }
+        Ok(Repr {
+            quanta: packet.quanta(),
+        })
+    }
+
+    /// Return the length of a payload that will be emitted from this high-level
+    /// representation.
+    pub const fn buffer_len(&self) -> usize {
+        PAYLOAD_LEN
+    }
+
+    /// Emit a high-level representation into a PAUSE frame payload.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) {
+        packet.set_opcode(field::OPCODE_PAUSE);
+        packet.set_quanta(self.quanta);
+        packet.clear_reserved();
+    }
+
+    /// Return how long the pause lasts on a link of `link_speed` bits per second,
+    /// rounded up to the next microsecond.
+    ///
+    /// # Panics
+    /// This function panics if `link_speed` is zero.
+    pub fn duration(&self, link_speed: u64) -> Duration {
+        let bits = self.quanta as u64 * QUANTUM_BITS;
+        Duration::from_micros((bits * 1_000_000 + link_speed - 1) / link_speed)
+    }
+
+    /// Return the number of quanta covering `duration` on a link of `link_speed` bits
+    /// per second, saturating at [`MAX_QUANTA`].
+    pub fn quanta_for(duration: Duration, link_speed: u64) -> u16 {
+        let bit_micros = duration.total_micros() as u128 * link_speed as u128;
+        let quantum_micros = QUANTUM_BITS as u128 * 1_000_000;
+        ((bit_micros + quantum_micros - 1) / quantum_micros).min(MAX_QUANTA as u128) as u16
+    }
+}
+
+impl<T: AsRef<[u8]>> fmt::Display for Packet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Repr::parse(&Packet::new_unchecked(self.buffer.as_ref())) {
+            Ok(repr) => write!(f, "{repr}"),
+            Err(err) => write!(f, "PAUSE ({err})"),
+        }
+    }
+}
+
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PAUSE quanta={}", self.quanta)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static PAYLOAD_BYTES: [u8; 46] = {
+        let mut bytes = [0; 46];
+        bytes[1] = 0x01;
+        bytes[2] = 0x12;
+        bytes[3] = 0x34;
+        bytes
+    };
+
+    #[test]
+    fn test_deconstruct() {
+        let packet = Packet::new_checked(&PAYLOAD_BYTES[..]).unwrap();
+        assert_eq!(packet.opcode(), 0x0001);
+        assert_eq!(packet.quanta(), 0x1234);
+    }
+
+    #[test]
+    fn test_construct() {
+        let mut bytes = vec![0xa5; 46];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_opcode(0x0001);
+        packet.set_quanta(0x1234);
+        packet.clear_reserved();
+        assert_eq!(&*packet.into_inner(), &PAYLOAD_BYTES[..]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let packet = Packet::new_checked(&PAYLOAD_BYTES[..4]).unwrap();
+        assert_eq!(Repr::parse(&packet), Ok(Repr { quanta: 0x1234 }));
+
+        // Priority-based flow control.
+        let bytes = [0x01, 0x01, 0x00, 0x01];
+        assert_eq!(Repr::parse(&Packet::new_unchecked(&bytes[..])), Err(Error));
+        assert_eq!(Packet::new_checked(&bytes[..3]), Err(Error));
+    }
+
+    #[test]
+    fn test_emit() {
+        let repr = Repr { quanta: 0x1234 };
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        repr.emit(&mut Packet::new_unchecked(&mut bytes));
+        assert_eq!(&bytes[..], &PAYLOAD_BYTES[..]);
+    }
+
+    #[test]
+    fn test_duration() {
+        // 512 bit times are 5.12µs at 100 Mbit/s.
+        let repr = Repr { quanta: 100 };
+        assert_eq!(repr.duration(100_000_000), Duration::from_micros(512));
+        assert_eq!(
+            Repr { quanta: 1 }.duration(100_000_000),
+            Duration::from_micros(6)
+        );
+        assert_eq!(Repr { quanta: 0 }.duration(100_000_000), Duration::ZERO);
+
+        assert_eq!(
+            Repr::quanta_for(Duration::from_micros(512), 100_000_000),
+            100
+        );
+        assert_eq!(
+            Repr::quanta_for(Duration::from_secs(1), 1_000_000_000),
+            MAX_QUANTA
+        );
+    }
+}
@@ -0,0 +1,396 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+
+use super::{Error, Result};
+
+enum_with_unknown! {
+    /// PPP protocol number.
+    pub enum Protocol(u16) {
+        Ipv4   = 0x0021,
+        Ipv6   = 0x0057,
+        Ipcp   = 0x8021,
+        Ipv6cp = 0x8057,
+        Lcp    = 0xc021,
+        Pap    = 0xc023,
+        Chap   = 0xc223
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Protocol::Ipv4 => write!(f, "IPv4"),
+            Protocol::Ipv6 => write!(f, "IPv6"),
+            Protocol::Ipcp => write!(f, "IPCP"),
+            Protocol::Ipv6cp => write!(f, "IPv6CP"),
+            Protocol::Lcp => write!(f, "LCP"),
+            Protocol::Pap => write!(f, "PAP"),
+            Protocol::Chap => write!(f, "CHAP"),
+            Protocol::Unknown(id) => write!(f, "0x{id:04x}"),
+        }
+    }
+}
+
+enum_with_unknown! {
+    /// Code of a LCP, IPCP or IPv6CP packet.
+    pub enum Code(u8) {
+        ConfigureRequest = 1,
+        ConfigureAck     = 2,
+        ConfigureNak     = 3,
+        ConfigureReject  = 4,
+        TerminateRequest = 5,
+        TerminateAck     = 6,
+        CodeReject       = 7,
+        ProtocolReject   = 8,
+        EchoRequest      = 9,
+        EchoReply        = 10,
+        DiscardRequest   = 11
+    }
+}
+
+enum_with_unknown! {
+    /// Type of a LCP configuration option.
+    pub enum LcpOption(u8) {
+        Mru                       = 1,
+        Accm                      = 2,
+        AuthProtocol              = 3,
+        MagicNumber               = 5,
+        ProtocolCompression       = 7,
+        AddressControlCompression = 8
+    }
+}
+
+enum_with_unknown! {
+    /// Type of a IPCP configuration option.
+    pub enum IpcpOption(u8) {
+        IpAddress    = 3,
+        PrimaryDns   = 129,
+        SecondaryDns = 131
+    }
+}
+
+enum_with_unknown! {
+    /// Type of a IPv6CP configuration option.
+    pub enum Ipv6cpOption(u8) {
+        InterfaceId = 1
+    }
+}
+
+/// The flag sequence delimiting HDLC-like frames.
+pub const HDLC_FLAG: u8 = 0x7e;
+/// The control escape octet of HDLC-like frames.
+pub const HDLC_ESCAPE: u8 = 0x7d;
+/// The all-stations address of HDLC-like frames.
+pub const HDLC_ADDRESS: u8 = 0xff;
+/// The unnumbered information control field of HDLC-like frames.
+pub const HDLC_CONTROL: u8 = 0x03;
+
+/// The initial value of the frame check sequence.
+pub const FCS_INIT: u16 = 0xffff;
+/// The frame check sequence of a frame followed by its own (complemented) frame check sequence.
+pub const FCS_GOOD: u16 = 0xf0b8;
+
+/// Update the 16-bit frame check sequence of [RFC 1662] with `data`.
+///
+/// The sender appends the complement of the result to the frame, least significant
+/// octet first; the receiver checks that the result over the whole frame is [FCS_GOOD].
+///
+/// [RFC 1662]: https://www.rfc-editor.org/rfc/rfc1662#appendix-C.2
+pub fn fcs_update(mut fcs: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        fcs ^= byte as u16;
+        for _ in 0..8 {
+            fcs = if fcs & 1 != 0 {
+                (fcs >> 1) ^ 0x8408
+            } else {
+                fcs >> 1
+            };
+        }
+    }
+    fcs
+}
+
+/// A read/write wrapper around a LCP, IPCP or IPv6CP packet buffer.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+mod field {
+    #![allow(non_snake_case)]
+
+    use crate::wire::field::*;
+
+    pub const CODE: usize = 0;
+    pub const IDENTIFIER: usize = 1;
+    pub const LENGTH: Field = 2..4;
+
+    pub const fn DATA(length: u16) -> Field {
+        LENGTH.end..length as usize
+    }
+}
+
+/// The length of a LCP, IPCP or IPv6CP packet header.
+pub const HEADER_LEN: usize = field::LENGTH.end;
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Imbue a raw octet buffer with PPP control packet structure.
+    pub const fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is shorter than the length field.
+    ///
+    /// The result of this check is invalidated by calling [set_length].
+    ///
+    /// [set_length]: #method.set_length
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < HEADER_LEN {
+            return Err(Error);
+        }
+        let length = self.length() as usize;
+        if length < HEADER_LEN || length > len {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the code field.
+    #[inline]
+    pub fn code(&self) -> Code {
+        let data = self.buffer.as_ref();
+        Code::from(data[field::CODE])
+    }
+
+    /// Return the identifier field.
+    #[inline]
+    pub fn identifier(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::IDENTIFIER]
+    }
+
+    /// Return the length field, including the header.
+    #[inline]
+    pub fn length(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::LENGTH])
+    }
+
+    /// Return the data following the header, up to the length field.
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+        &data[field::DATA(self.length())]
+    }
+
+    /// Return an iterator over the configuration options of a Configure-Request,
+    /// Configure-Ack, Configure-Nak or Configure-Reject packet.
+    pub fn options(&self) -> ConfigOptions<'_> {
+        ConfigOptions { data: self.data() }
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the code field.
+    #[inline]
+    pub fn set_code(&mut self, value: Code) {
+        let data = self.buffer.as_mut();
+        data[field::CODE] = value.into()
+    }
+
+    /// Set the identifier field.
+    #[inline]
+    pub fn set_identifier(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::IDENTIFIER] = value
+    }
+
+    /// Set the length field.
+    #[inline]
+    pub fn set_length(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::LENGTH], value)
+    }
+
+    /// Return a mutable pointer to the data following the header, up to the length field.
+    #[inline]
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        let length = self.length();
+        let data = self.buffer.as_mut();
+        &mut data[field::DATA(length)]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+impl<T: AsRef<[u8]>> fmt::Display for Packet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PPP code={:?} id={} len={}",
+            self.code(),
+            self.identifier(),
+            self.length()
+        )
+    }
+}
+
+/// A configuration option of a LCP, IPCP or IPv6CP packet.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConfigOption<'a> {
+    /// The type of the option, which depends on the control protocol.
+    pub kind: u8,
+    /// The data of the option.
+    pub data: &'a [u8],
+}
+
+impl<'a> ConfigOption<'a> {
+    /// Return the length of the option, including its type and length fields.
+    pub const fn buffer_len(&self) -> usize {
+        2 + self.data.len()
+    }
+
+    /// Emit the option into `buffer`, which must be [buffer_len] octets long.
+    ///
+    /// [buffer_len]: #method.buffer_len
+    pub fn emit(&self, buffer: &mut [u8]) {
+        buffer[0] = self.kind;
+        buffer[1] = self.buffer_len() as u8;
+        buffer[2..].copy_from_slice(self.data);
+    }
+}
+
+/// An iterator over the configuration options of a LCP, IPCP or IPv6CP packet.
+///
+/// The iterator ends after the first malformed option, which is returned as an error.
+#[derive(Debug, Clone)]
+pub struct ConfigOptions<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for ConfigOptions<'a> {
+    type Item = Result<ConfigOption<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let length = match self.data.get(1) {
+            Some(&length) if length >= 2 && length as usize <= self.data.len() => length as usize,
+            _ => {
+                self.data = &[];
+                return Some(Err(Error));
+            }
+        };
+        let option = ConfigOption {
+            kind: self.data[0],
+            data: &self.data[2..length],
+        };
+        self.data = &self.data[length..];
+        Some(Ok(option))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static PACKET_BYTES: [u8; 14] = [
+        0x01, 0x2a, 0x00, 0x0e, 0x01, 0x04, 0x05, 0xdc, 0x05, 0x06, 0x12, 0x34, 0x56, 0x78,
+    ];
+
+    #[test]
+    fn test_fcs() {
+        let fcs = !fcs_update(FCS_INIT, b"123456789");
+        assert_eq!(fcs, 0x906e);
+        let fcs = fcs_update(FCS_INIT, b"123456789");
+        assert_eq!(fcs_update(fcs, &(!fcs).to_le_bytes()), FCS_GOOD);
+    }
+
+    #[test]
+    fn test_deconstruct() {
+        let packet = Packet::new_checked(&PACKET_BYTES[..]).unwrap();
+        assert_eq!(packet.code(), Code::ConfigureRequest);
+        assert_eq!(packet.identifier(), 42);
+        assert_eq!(packet.length(), 14);
+        let mut options = packet.options();
+        assert_eq!(
+            options.next(),
+            Some(Ok(ConfigOption {
+                kind: LcpOption::Mru.into(),
+                data: &[0x05, 0xdc]
+            }))
+        );
+        assert_eq!(
+            options.next(),
+            Some(Ok(ConfigOption {
+                kind: LcpOption::MagicNumber.into(),
+                data: &[0x12, 0x34, 0x56, 0x78]
+            }))
+        );
+        assert_eq!(options.next(), None);
+    }
+
+    #[test]
+    fn test_construct() {
+        let mut bytes = vec![0xa5; 14];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_code(Code::ConfigureRequest);
+        packet.set_identifier(42);
+        packet.set_length(14);
+        let data = packet.data_mut();
+        ConfigOption {
+            kind: LcpOption::Mru.into(),
+            data: &[0x05, 0xdc],
+        }
+        .emit(&mut data[..4]);
+        ConfigOption {
+            kind: LcpOption::MagicNumber.into(),
+            data: &[0x12, 0x34, 0x56, 0x78],
+        }
+        .emit(&mut data[4..]);
+        assert_eq!(&*packet.into_inner(), &PACKET_BYTES[..]);
+    }
+
+    #[test]
+    fn test_check_len() {
+        assert_eq!(Packet::new_checked(&PACKET_BYTES[..3]), Err(Error));
+        assert_eq!(Packet::new_checked(&PACKET_BYTES[..13]), Err(Error));
+        assert!(Packet::new_checked(&[0x09, 0x01, 0x00, 0x03][..]).is_err());
+    }
+
+    #[test]
+    fn test_malformed_option() {
+        let bytes = [0x01, 0x01, 0x00, 0x0a, 0x01, 0x04, 0x05, 0xdc, 0x05, 0x01];
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        let mut options = packet.options();
+        assert!(matches!(options.next(), Some(Ok(_))));
+        assert_eq!(options.next(), Some(Err(Error)));
+        assert_eq!(options.next(), None);
+    }
+}
@@ -43,6 +43,8 @@ assert_eq!(
 use core::fmt;
 use core::marker::PhantomData;
 
+use crate::phy::Medium;
+
 /// Indentation state.
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -124,3 +126,165 @@ impl<'a, T: PrettyPrint> fmt::Display for PrettyPrinter<'a, T> {
         T::pretty_print(&self.buffer, f, &mut PrettyIndent::new(self.prefix))
     }
 }
+
+/// Number of octets on each line of a hexadecimal dump.
+const HEX_DUMP_WIDTH: usize = 16;
+
+/// A listing of a frame captured on a device, see [`dissect`].
+#[derive(Debug, Clone, Copy)]
+pub struct Dissection<'a> {
+    medium: Medium,
+    buffer: &'a [u8],
+    prefix: &'static str,
+    hex_dump: bool,
+}
+
+/// Return a listing of `buffer`, a frame sent or received by a device with the given
+/// medium, for use where a `Display` is expected.
+///
+/// The listing decodes every layer of the frame understood by this crate, from the
+/// link layer to the application layer, followed by a hexadecimal dump of the whole
+/// frame. It never panics, even if the frame is truncated or malformed, so it can be
+/// used to log problem packets from the capture hooks of an application.
+///
+/// # Example
+///
+/// ```rust
+/// # #[cfg(all(feature = "medium-ip", feature = "proto-ipv4"))]
+/// # {
+/// use smoltcp::phy::Medium;
+/// use smoltcp::wire;
+///
+/// let buffer = [
+///     0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x40, 0x00, 0x40, 0x01, 0xd2, 0x79, 0x11, 0x12,
+///     0x13, 0x14, 0x21, 0x22, 0x23, 0x24, 0x08, 0x00, 0x8e, 0xfe, 0x12, 0x34, 0xab, 0xcd,
+/// ];
+/// println!("{}", wire::dissect(Medium::Ip, &buffer));
+/// # }
+/// ```
+pub fn dissect(medium: Medium, buffer: &[u8]) -> Dissection<'_> {
+    Dissection {
+        medium,
+        buffer,
+        prefix: "",
+        hex_dump: true,
+    }
+}
+
+impl<'a> Dissection<'a> {
+    /// Indent the listing by the width of `prefix`, and start it with `prefix`.
+    pub fn with_prefix(self, prefix: &'static str) -> Self {
+        Dissection { prefix, ..self }
+    }
+
+    /// Omit the hexadecimal dump of the frame from the listing.
+    pub fn without_hex_dump(self) -> Self {
+        Dissection {
+            hex_dump: false,
+            ..self
+        }
+    }
+
+    fn fmt_layers(&self, f: &mut fmt::Formatter, indent: &mut PrettyIndent) -> fmt::Result {
+        match self.medium {
+            #[cfg(feature = "medium-ethernet")]
+            Medium::Ethernet => {
+                crate::wire::EthernetFrame::<&[u8]>::pretty_print(&self.buffer, f, indent)
+            }
+            #[cfg(feature = "medium-ip")]
+            Medium::Ip if self.buffer.is_empty() => write!(f, "{indent}({})", crate::wire::Error),
+            #[cfg(feature = "medium-ip")]
+            Medium::Ip => match crate::wire::IpVersion::of_packet(self.buffer) {
+                #[cfg(feature = "proto-ipv4")]
+                Ok(crate::wire::IpVersion::Ipv4) => {
+                    crate::wire::Ipv4Packet::<&[u8]>::pretty_print(&self.buffer, f, indent)
+                }
+                #[cfg(feature = "proto-ipv6")]
+                Ok(crate::wire::IpVersion::Ipv6) => {
+                    crate::wire::Ipv6Packet::<&[u8]>::pretty_print(&self.buffer, f, indent)
+                }
+                _ => write!(f, "{indent}unrecognized IP version"),
+            },
+            #[cfg(feature = "medium-ieee802154")]
+            Medium::Ieee802154 => match crate::wire::Ieee802154Frame::new_checked(self.buffer) {
+                Ok(frame) => write!(f, "{indent}{frame}"),
+                Err(err) => write!(f, "{indent}IEEE802.15.4 ({err})"),
+            },
+        }
+    }
+}
+
+impl<'a> fmt::Display for Dissection<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_layers(f, &mut PrettyIndent::new(self.prefix))?;
+        if self.hex_dump {
+            for (index, line) in self.buffer.chunks(HEX_DUMP_WIDTH).enumerate() {
+                write!(
+                    f,
+                    "\n{:2$}{:04x}:",
+                    "",
+                    index * HEX_DUMP_WIDTH,
+                    self.prefix.len()
+                )?;
+                for octet in line {
+                    write!(f, " {octet:02x}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+    fn test_dissect_ethernet() {
+        let buffer = [
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x20, 0x00, 0x00, 0x40, 0x00, 0x40, 0x01, 0xd2, 0x79, 0x11, 0x12,
+            0x13, 0x14, 0x21, 0x22, 0x23, 0x24, 0x08, 0x00, 0x8e, 0xfe, 0x12, 0x34, 0xab, 0xcd,
+            0xaa, 0x00, 0x00, 0xff,
+        ];
+        assert_eq!(
+            format!("{}", dissect(Medium::Ethernet, &buffer).with_prefix("<- ")),
+            "<- EthernetII src=11-12-13-14-15-16 dst=01-02-03-04-05-06 type=IPv4\n   \
+             \\ IPv4 src=17.18.19.20 dst=33.34.35.36 proto=ICMP (checksum incorrect)\n    \
+             \\ ICMPv4 echo request id=4660 seq=43981 len=4\n   \
+             0000: 01 02 03 04 05 06 11 12 13 14 15 16 08 00 45 00\n   \
+             0010: 00 20 00 00 40 00 40 01 d2 79 11 12 13 14 21 22\n   \
+             0020: 23 24 08 00 8e fe 12 34 ab cd aa 00 00 ff"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "medium-ethernet")]
+    fn test_dissect_truncated() {
+        assert_eq!(
+            format!("{}", dissect(Medium::Ethernet, &[0xff; 4])),
+            "(wire::Error)\n0000: ff ff ff ff"
+        );
+        assert_eq!(
+            format!("{}", dissect(Medium::Ethernet, &[]).without_hex_dump()),
+            "(wire::Error)"
+        );
+    }
+
+    #[test]
+    fn test_dissect_empty() {
+        let media = [
+            #[cfg(feature = "medium-ethernet")]
+            Medium::Ethernet,
+            #[cfg(feature = "medium-ip")]
+            Medium::Ip,
+            #[cfg(feature = "medium-ieee802154")]
+            Medium::Ieee802154,
+        ];
+        for medium in media {
+            let listing = format!("{}", dissect(medium, &[]));
+            assert!(listing.ends_with("(wire::Error)"), "{medium:?}: {listing}");
+        }
+    }
+}
@@ -0,0 +1,834 @@
+/*! Randomized round-trip checks of packet representations.
+
+The `proptest` module generates random, valid high-level representations of the
+packets understood by the `wire` module, and checks that:
+
+ * emitting a representation, then parsing the result, returns the same representation;
+ * parsing the emitted packet after flipping some of its bits or truncating it
+   never panics.
+
+It does not depend on any property testing crate, so it can be used from the test suite
+of an application, e.g. one adding its own options to these packets, just as well as
+from the test suite of _smoltcp_. The generators are deterministic for a given seed,
+and a failing check panics, which makes it easy to reproduce a failure.
+
+This module is only available with the `proptest` feature.
+
+# Examples
+
+```rust
+# #[cfg(feature = "proptest")]
+# {
+use smoltcp::wire::proptest::{check_all, Gen};
+
+check_all(&mut Gen::new(0x5eed), 100);
+# }
+```
+*/
+
+use crate::phy::ChecksumCapabilities;
+use crate::rand::Rand;
+#[cfg(feature = "proto-igmp")]
+use crate::time::Duration;
+use crate::wire::*;
+
+/// The maximum length of the packets emitted by the checks.
+const MAX_PACKET_LEN: usize = 1500;
+
+/// The number of mutated copies of each emitted packet that are parsed.
+const MUTATION_COUNT: usize = 16;
+
+/// The maximum length of the payloads of generated packets.
+const MAX_PAYLOAD_LEN: usize = 64;
+
+/// Arbitrary bytes that generated variable-length fields are taken from.
+static BYTES: [u8; MAX_PAYLOAD_LEN] = [
+    0x4c, 0x6f, 0x72, 0x65, 0x6d, 0x20, 0x69, 0x70, 0x73, 0x75, 0x6d, 0x00, 0xff, 0x01, 0x80, 0x7f,
+    0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff,
+    0x63, 0x82, 0x53, 0x63, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c,
+    0x0d, 0x0e, 0x0f, 0x10, 0xa5, 0x5a, 0xc3, 0x3c, 0x96, 0x69, 0xe1, 0x1e, 0xd2, 0x2d, 0xb4, 0x4b,
+];
+
+/// Strings that generated text fields are chosen from.
+#[cfg(any(feature = "proto-dhcpv4", feature = "proto-dhcpv6"))]
+static STRINGS: [&str; 4] = [
+    "",
+    "http://example.com/captive",
+    "https://portal.example.org/api/v1?id=42",
+    "no addresses available",
+];
+
+/// A deterministic generator of random values and packet representations.
+#[derive(Debug)]
+pub struct Gen {
+    rand: Rand,
+}
+
+impl Gen {
+    /// Create a generator from a seed.
+    pub const fn new(seed: u64) -> Gen {
+        Gen {
+            rand: Rand::new(seed),
+        }
+    }
+
+    /// Return a random `u8`.
+    pub fn u8(&mut self) -> u8 {
+        self.rand.rand_u8()
+    }
+
+    /// Return a random `u16`.
+    pub fn u16(&mut self) -> u16 {
+        self.rand.rand_u16()
+    }
+
+    /// Return a random `u32`.
+    pub fn u32(&mut self) -> u32 {
+        self.rand.rand_u32()
+    }
+
+    /// Return a random `bool`.
+    pub fn bool(&mut self) -> bool {
+        self.u32() & 1 != 0
+    }
+
+    /// Return a random number lower than `n`.
+    ///
+    /// # Panics
+    /// This function panics if `n` is zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        self.u32() as usize % n
+    }
+
+    /// Fill `data` with random octets.
+    pub fn fill(&mut self, data: &mut [u8]) {
+        for octet in data.iter_mut() {
+            *octet = self.u8();
+        }
+    }
+
+    /// Return a value generated by `f` half of the time, and `None` otherwise.
+    pub fn option<T, F: FnOnce(&mut Self) -> T>(&mut self, f: F) -> Option<T> {
+        if self.bool() {
+            Some(f(self))
+        } else {
+            None
+        }
+    }
+
+    /// Return a random slice of at most `max_len` arbitrary octets.
+    pub fn bytes(&mut self, max_len: usize) -> &'static [u8] {
+        let len = self.below(max_len.min(BYTES.len()) + 1);
+        let start = self.below(BYTES.len() - len + 1);
+        &BYTES[start..start + len]
+    }
+
+    /// Return a random Ethernet address.
+    #[cfg(feature = "medium-ethernet")]
+    pub fn ethernet_address(&mut self) -> EthernetAddress {
+        let mut bytes = [0; 6];
+        self.fill(&mut bytes);
+        EthernetAddress(bytes)
+    }
+
+    /// Return a random IPv4 address.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn ipv4_address(&mut self) -> Ipv4Address {
+        let mut bytes = [0; 4];
+        self.fill(&mut bytes);
+        Ipv4Address::from_bytes(&bytes)
+    }
+
+    /// Return a random IPv6 address.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn ipv6_address(&mut self) -> Ipv6Address {
+        let mut bytes = [0; 16];
+        self.fill(&mut bytes);
+        Ipv6Address::from_bytes(&bytes)
+    }
+
+    /// Return random source and destination addresses of the same family.
+    pub fn ip_address_pair(&mut self) -> (IpAddress, IpAddress) {
+        match self.below(2) {
+            #[cfg(feature = "proto-ipv4")]
+            0 => (
+                IpAddress::Ipv4(self.ipv4_address()),
+                IpAddress::Ipv4(self.ipv4_address()),
+            ),
+            #[cfg(feature = "proto-ipv6")]
+            _ => (
+                IpAddress::Ipv6(self.ipv6_address()),
+                IpAddress::Ipv6(self.ipv6_address()),
+            ),
+            #[cfg(not(feature = "proto-ipv6"))]
+            _ => (
+                IpAddress::Ipv4(self.ipv4_address()),
+                IpAddress::Ipv4(self.ipv4_address()),
+            ),
+        }
+    }
+
+    /// Return a random, valid Ethernet II header.
+    #[cfg(feature = "medium-ethernet")]
+    pub fn ethernet_repr(&mut self) -> EthernetRepr {
+        EthernetRepr {
+            src_addr: self.ethernet_address(),
+            dst_addr: self.ethernet_address(),
+            // Smaller values are the length of IEEE 802.3 frames.
+            ethertype: EthernetProtocol::from(self.u16().max(0x0600)),
+        }
+    }
+
+    /// Return a random, valid ARP packet.
+    #[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+    pub fn arp_repr(&mut self) -> ArpRepr {
+        ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::from(self.u16()),
+            source_hardware_addr: self.ethernet_address(),
+            source_protocol_addr: self.ipv4_address(),
+            target_hardware_addr: self.ethernet_address(),
+            target_protocol_addr: self.ipv4_address(),
+        }
+    }
+
+    /// Return a random, valid IPv4 header.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn ipv4_repr(&mut self) -> Ipv4Repr {
+        Ipv4Repr {
+            src_addr: self.ipv4_address(),
+            dst_addr: self.ipv4_address(),
+            next_header: IpProtocol::from(self.u8()),
+            payload_len: self.below(MAX_PAYLOAD_LEN + 1),
+            hop_limit: self.u8(),
+            dscp: self.u8() & 0x3f,
+        }
+    }
+
+    /// Return a random, valid IPv6 header.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn ipv6_repr(&mut self) -> Ipv6Repr {
+        Ipv6Repr {
+            src_addr: self.ipv6_address(),
+            dst_addr: self.ipv6_address(),
+            next_header: IpProtocol::from(self.u8()),
+            payload_len: self.below(MAX_PAYLOAD_LEN + 1),
+            hop_limit: self.u8(),
+            traffic_class: self.u8(),
+            flow_label: self.u32() & 0xf_ffff,
+        }
+    }
+
+    /// Return a random, valid UDP header.
+    pub fn udp_repr(&mut self) -> UdpRepr {
+        UdpRepr {
+            src_port: self.u16(),
+            dst_port: self.u16().max(1),
+        }
+    }
+
+    /// Return a random, valid TCP segment carrying `payload`.
+    pub fn tcp_repr<'a>(&mut self, payload: &'a [u8]) -> TcpRepr<'a> {
+        let ack_number = self.option(|gen| TcpSeqNumber(gen.u32() as i32));
+        let sack_permitted = self.bool();
+        let mut sack_ranges = [None; 3];
+        // SACK ranges are only emitted in acknowledgements of connections where they
+        // were not just being negotiated.
+        if ack_number.is_some() && !sack_permitted {
+            for range in sack_ranges.iter_mut().take(self.below(4)) {
+                *range = Some((self.u32(), self.u32()));
+            }
+        }
+        let mut repr = TcpRepr {
+            src_port: self.u16().max(1),
+            dst_port: self.u16().max(1),
+            control: match self.below(5) {
+                0 => TcpControl::None,
+                1 => TcpControl::Psh,
+                2 => TcpControl::Syn,
+                3 => TcpControl::Fin,
+                _ => TcpControl::Rst,
+            },
+            seq_number: TcpSeqNumber(self.u32() as i32),
+            ack_number,
+            window_len: self.u16(),
+            window_scale: self.option(|gen| gen.u8() % 15),
+            max_seg_size: self.option(Gen::u16),
+            sack_permitted,
+            sack_ranges,
+            timestamp: self.option(|gen| TcpTimestampRepr {
+                tsval: gen.u32(),
+                tsecr: gen.u32(),
+            }),
+            urgent_at: self.option(Gen::u16),
+            payload,
+        };
+        // The options must fit in the 40 octets following the fixed header.
+        for index in (0..3).rev() {
+            if repr.header_len() > 60 {
+                repr.sack_ranges[index] = None;
+            }
+        }
+        repr
+    }
+
+    /// Return a random, valid ICMPv4 message carrying `data`.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn icmpv4_repr<'a>(&mut self, data: &'a [u8]) -> Icmpv4Repr<'a> {
+        let (ident, seq_no) = (self.u16(), self.u16());
+        if self.bool() {
+            Icmpv4Repr::EchoRequest {
+                ident,
+                seq_no,
+                data,
+            }
+        } else {
+            Icmpv4Repr::EchoReply {
+                ident,
+                seq_no,
+                data,
+            }
+        }
+    }
+
+    /// Return a random, valid ICMPv6 message carrying `data`.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn icmpv6_repr<'a>(&mut self, data: &'a [u8]) -> Icmpv6Repr<'a> {
+        let (ident, seq_no) = (self.u16(), self.u16());
+        if self.bool() {
+            Icmpv6Repr::EchoRequest {
+                ident,
+                seq_no,
+                data,
+            }
+        } else {
+            Icmpv6Repr::EchoReply {
+                ident,
+                seq_no,
+                data,
+            }
+        }
+    }
+
+    /// Return a random, valid IGMP message.
+    #[cfg(feature = "proto-igmp")]
+    pub fn igmp_repr(&mut self) -> IgmpRepr {
+        let group_addr = if self.below(4) == 0 {
+            Ipv4Address::UNSPECIFIED
+        } else {
+            Ipv4Address::new(224 + self.below(16) as u8, self.u8(), self.u8(), self.u8())
+        };
+        let version = if self.bool() {
+            IgmpVersion::Version1
+        } else {
+            IgmpVersion::Version2
+        };
+        match self.below(3) {
+            0 => IgmpRepr::MembershipQuery {
+                // Only IGMPv2 queries have a maximum response time, which is emitted in
+                // tenths of a second.
+                max_resp_time: match version {
+                    IgmpVersion::Version1 => Duration::ZERO,
+                    IgmpVersion::Version2 => {
+                        Duration::from_millis(100 * (1 + self.below(127)) as u64)
+                    }
+                },
+                group_addr,
+                version,
+            },
+            1 => IgmpRepr::MembershipReport {
+                group_addr,
+                version,
+            },
+            _ => IgmpRepr::LeaveGroup { group_addr },
+        }
+    }
+
+    /// Return a random, valid ESP header.
+    pub fn esp_repr(&mut self) -> EspRepr {
+        EspRepr {
+            spi: self.u32().max(1),
+            sequence_number: self.u32(),
+        }
+    }
+
+    /// Return a random, valid Authentication Header.
+    pub fn ah_repr(&mut self) -> AhRepr {
+        AhRepr {
+            next_header: IpProtocol::from(self.u8()),
+            spi: self.u32().max(1),
+            sequence_number: self.u32(),
+            icv_len: 4 * self.below(9),
+        }
+    }
+
+    /// Return a random, valid GRE header.
+    pub fn gre_repr(&mut self) -> GreRepr {
+        GreRepr {
+            protocol: GreProtocol::from(self.u16()),
+            key: self.option(Gen::u32),
+            sequence_number: self.option(Gen::u32),
+        }
+    }
+
+    /// Return a random, valid VXLAN header.
+    #[cfg(feature = "medium-ethernet")]
+    pub fn vxlan_repr(&mut self) -> VxlanRepr {
+        VxlanRepr {
+            vni: self.u32() & 0xff_ffff,
+        }
+    }
+
+    /// Return a random, valid DHCPv4 packet.
+    ///
+    /// The renewal and rebinding times are never emitted, so they are always `None`.
+    #[cfg(feature = "proto-dhcpv4")]
+    pub fn dhcpv4_repr(&mut self) -> DhcpRepr<'static> {
+        let mut dns_servers = heapless::Vec::new();
+        for _ in 0..self.below(DHCP_MAX_DNS_SERVER_COUNT + 1) {
+            // NOTE(unwrap): the number of servers is within the capacity.
+            dns_servers.push(self.ipv4_address()).unwrap();
+        }
+        DhcpRepr {
+            message_type: DhcpMessageType::from(1 + self.below(8) as u8),
+            transaction_id: self.u32(),
+            secs: self.u16(),
+            client_hardware_address: self.ethernet_address(),
+            client_ip: self.ipv4_address(),
+            your_ip: self.ipv4_address(),
+            server_ip: self.ipv4_address(),
+            router: self.option(Gen::ipv4_address),
+            subnet_mask: self.option(Gen::ipv4_address),
+            relay_agent_ip: self.ipv4_address(),
+            broadcast: self.bool(),
+            requested_ip: self.option(Gen::ipv4_address),
+            client_identifier: self.option(Gen::ethernet_address),
+            server_identifier: self.option(Gen::ipv4_address),
+            parameter_request_list: self.option(|gen| gen.bytes(16)),
+            dns_servers: self.option(|_| dns_servers),
+            max_size: self.option(Gen::u16),
+            lease_duration: self.option(Gen::u32),
+            renew_duration: None,
+            rebind_duration: None,
+            captive_portal: self.option(|gen| STRINGS[gen.below(STRINGS.len())]),
+            additional_options: &[],
+        }
+    }
+
+    /// Return a random, valid DHCPv6 status code option.
+    #[cfg(feature = "proto-dhcpv6")]
+    pub fn dhcpv6_status_code_repr(&mut self) -> Dhcpv6ReprStatusCode<'static> {
+        Dhcpv6ReprStatusCode {
+            status_code: Dhcpv6StatusCode::from(self.u16()),
+            status_message: Dhcpv6StatusMessage(self.bytes(MAX_PAYLOAD_LEN)),
+        }
+    }
+
+    /// Return a random, valid DHCPv6 IA Address option.
+    #[cfg(feature = "proto-dhcpv6")]
+    pub fn dhcpv6_ia_addr_repr(&mut self) -> Dhcpv6ReprIaAddr<'static> {
+        Dhcpv6ReprIaAddr {
+            addr: self.ipv6_address(),
+            preferred_lifetime: self.u32(),
+            valid_lifetime: self.u32(),
+            prefix: self.option(|gen| Dhcpv6ReprIaPrefix {
+                preferred_lifetime: gen.u32(),
+                valid_lifetime: gen.u32(),
+                prefix_len: gen.below(129) as u8,
+                prefix: gen.ipv6_address(),
+                additional_options: &[],
+            }),
+            additional_options: &[],
+        }
+    }
+
+    /// Return random, valid DHCPv6 IA Address options.
+    #[cfg(feature = "proto-dhcpv6")]
+    fn dhcpv6_ia_addr_reprs(
+        &mut self,
+    ) -> heapless::Vec<Dhcpv6ReprIaAddr<'static>, DHCPV6_MAX_IA_ADDRESSES> {
+        let mut addresses = heapless::Vec::new();
+        for _ in 0..self.below(4) {
+            // NOTE(unwrap): the number of addresses is within the capacity.
+            addresses.push(self.dhcpv6_ia_addr_repr()).unwrap();
+        }
+        addresses
+    }
+
+    /// Return a random, valid DHCPv6 packet.
+    #[cfg(feature = "proto-dhcpv6")]
+    pub fn dhcpv6_repr(&mut self) -> Dhcpv6Repr<'static> {
+        let mut request_options = heapless::Vec::new();
+        for _ in 0..self.below(DHCPV6_MAX_REQUEST_OPTIONS + 1) {
+            // NOTE(unwrap): the number of options is within the capacity.
+            request_options.push(self.u16()).unwrap();
+        }
+        let mut dns_servers = Dhcpv6ReprDnsServers {
+            addresses: heapless::Vec::new(),
+        };
+        for _ in 0..self.below(5) {
+            // NOTE(unwrap): the number of servers is within the capacity.
+            dns_servers.addresses.push(self.ipv6_address()).unwrap();
+        }
+        Dhcpv6Repr {
+            message_type: Dhcpv6MessageType::from(self.u8()),
+            transaction_id: self.u32() & 0xff_ffff,
+            client_id: self.option(|gen| gen.bytes(32)),
+            server_id: self.option(|gen| gen.bytes(32)),
+            elapsed_time: self.option(Gen::u16),
+            request_options: self.option(|_| request_options),
+            ia_na: self.option(|gen| Dhcpv6ReprIaNa {
+                iaid: gen.u32(),
+                t1: gen.u32(),
+                t2: gen.u32(),
+                addresses: gen.dhcpv6_ia_addr_reprs(),
+                status_code: gen.option(Gen::dhcpv6_status_code_repr),
+                additional_options: &[],
+            }),
+            ia_ta: self.option(|gen| Dhcpv6ReprIaTa {
+                iaid: gen.u32(),
+                addresses: gen.dhcpv6_ia_addr_reprs(),
+                status_code: gen.option(Gen::dhcpv6_status_code_repr),
+                additional_options: &[],
+            }),
+            dns_servers: self.option(|_| dns_servers),
+            captive_portal: self.option(|gen| STRINGS[gen.below(STRINGS.len())]),
+            additional_options: &[],
+        }
+    }
+}
+
+/// Parse mutated copies of `packet` with `parse`, which must not panic.
+///
+/// Each copy has a few random bits flipped, and half of the copies are also truncated.
+///
+/// # Panics
+/// This function panics if `packet` is longer than 1500 octets.
+pub fn check_mutations<F>(gen: &mut Gen, packet: &[u8], mut parse: F)
+where
+    F: FnMut(&[u8]),
+{
+    let mut buffer = [0; MAX_PACKET_LEN];
+    for _ in 0..MUTATION_COUNT {
+        let mut len = packet.len();
+        buffer[..len].copy_from_slice(packet);
+        if len > 0 {
+            for _ in 0..1 + gen.below(4) {
+                let index = gen.below(len);
+                buffer[index] ^= 1 << gen.below(8);
+            }
+        }
+        if gen.bool() {
+            len = gen.below(len + 1);
+        }
+        parse(&buffer[..len]);
+    }
+}
+
+/// Check an Ethernet II header.
+#[cfg(feature = "medium-ethernet")]
+pub fn check_ethernet(gen: &mut Gen) {
+    let repr = gen.ethernet_repr();
+    let mut buffer = [0; ETHERNET_HEADER_LEN];
+    repr.emit(&mut EthernetFrame::new_unchecked(&mut buffer[..]));
+    let frame = EthernetFrame::new_checked(&buffer[..]).unwrap();
+    assert_eq!(EthernetRepr::parse(&frame), Ok(repr));
+
+    check_mutations(gen, &buffer, |data| {
+        if let Ok(frame) = EthernetFrame::new_checked(data) {
+            let _ = EthernetRepr::parse(&frame);
+        }
+    });
+}
+
+/// Check an ARP packet.
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+pub fn check_arp(gen: &mut Gen) {
+    let repr = gen.arp_repr();
+    let mut buffer = [0; MAX_PACKET_LEN];
+    let buffer = &mut buffer[..repr.buffer_len()];
+    repr.emit(&mut ArpPacket::new_unchecked(&mut *buffer));
+    let packet = ArpPacket::new_checked(&*buffer).unwrap();
+    assert_eq!(ArpRepr::parse(&packet), Ok(repr));
+
+    check_mutations(gen, buffer, |data| {
+        if let Ok(packet) = ArpPacket::new_checked(data) {
+            let _ = ArpRepr::parse(&packet);
+        }
+    });
+}
+
+/// Check an IPv4 header.
+#[cfg(feature = "proto-ipv4")]
+pub fn check_ipv4(gen: &mut Gen) {
+    let caps = ChecksumCapabilities::default();
+    let repr = gen.ipv4_repr();
+    let mut buffer = [0; MAX_PACKET_LEN];
+    let buffer = &mut buffer[..repr.buffer_len() + repr.payload_len];
+    repr.emit(&mut Ipv4Packet::new_unchecked(&mut *buffer), &caps);
+    let packet = Ipv4Packet::new_checked(&*buffer).unwrap();
+    assert_eq!(Ipv4Repr::parse(&packet, &caps), Ok(repr));
+
+    check_mutations(gen, buffer, |data| {
+        if let Ok(packet) = Ipv4Packet::new_checked(data) {
+            let _ = Ipv4Repr::parse(&packet, &ChecksumCapabilities::ignored());
+        }
+    });
+}
+
+/// Check an IPv6 header.
+#[cfg(feature = "proto-ipv6")]
+pub fn check_ipv6(gen: &mut Gen) {
+    let repr = gen.ipv6_repr();
+    let mut buffer = [0; MAX_PACKET_LEN];
+    let buffer = &mut buffer[..repr.buffer_len() + repr.payload_len];
+    repr.emit(&mut Ipv6Packet::new_unchecked(&mut *buffer));
+    let packet = Ipv6Packet::new_checked(&*buffer).unwrap();
+    assert_eq!(Ipv6Repr::parse(&packet), Ok(repr));
+
+    check_mutations(gen, buffer, |data| {
+        if let Ok(packet) = Ipv6Packet::new_checked(data) {
+            let _ = Ipv6Repr::parse(&packet);
+        }
+    });
+}
+
+/// Check a UDP datagram.
+pub fn check_udp(gen: &mut Gen) {
+    let caps = ChecksumCapabilities::default();
+    let (src_addr, dst_addr) = gen.ip_address_pair();
+    let repr = gen.udp_repr();
+    let payload = gen.bytes(MAX_PAYLOAD_LEN);
+    let mut buffer = [0; MAX_PACKET_LEN];
+    let buffer = &mut buffer[..repr.header_len() + payload.len()];
+    repr.emit(
+        &mut UdpPacket::new_unchecked(&mut *buffer),
+        &src_addr,
+        &dst_addr,
+        payload.len(),
+        |data| data.copy_from_slice(payload),
+        &caps,
+    );
+    let packet = UdpPacket::new_checked(&*buffer).unwrap();
+    assert_eq!(
+        UdpRepr::parse(&packet, &src_addr, &dst_addr, &caps),
+        Ok(repr)
+    );
+    assert_eq!(packet.payload(), payload);
+
+    check_mutations(gen, buffer, |data| {
+        if let Ok(packet) = UdpPacket::new_checked(data) {
+            let caps = ChecksumCapabilities::ignored();
+            let _ = UdpRepr::parse(&packet, &src_addr, &dst_addr, &caps);
+        }
+    });
+}
+
+/// Check a TCP segment.
+pub fn check_tcp(gen: &mut Gen) {
+    let caps = ChecksumCapabilities::default();
+    let (src_addr, dst_addr) = gen.ip_address_pair();
+    let payload = gen.bytes(MAX_PAYLOAD_LEN);
+    let repr = gen.tcp_repr(payload);
+    let mut buffer = [0; MAX_PACKET_LEN];
+    let buffer = &mut buffer[..repr.buffer_len()];
+    repr.emit(
+        &mut TcpPacket::new_unchecked(&mut *buffer),
+        &src_addr,
+        &dst_addr,
+        &caps,
+    );
+    let packet = TcpPacket::new_checked(&*buffer).unwrap();
+    assert_eq!(
+        TcpRepr::parse(&packet, &src_addr, &dst_addr, &caps),
+        Ok(repr)
+    );
+
+    check_mutations(gen, buffer, |data| {
+        if let Ok(packet) = TcpPacket::new_checked(data) {
+            let caps = ChecksumCapabilities::ignored();
+            let _ = TcpRepr::parse(&packet, &src_addr, &dst_addr, &caps);
+        }
+    });
+}
+
+/// Check an ICMPv4 message.
+#[cfg(feature = "proto-ipv4")]
+pub fn check_icmpv4(gen: &mut Gen) {
+    let caps = ChecksumCapabilities::default();
+    let data = gen.bytes(MAX_PAYLOAD_LEN);
+    let repr = gen.icmpv4_repr(data);
+    let mut buffer = [0; MAX_PACKET_LEN];
+    let buffer = &mut buffer[..repr.buffer_len()];
+    repr.emit(&mut Icmpv4Packet::new_unchecked(&mut *buffer), &caps);
+    let packet = Icmpv4Packet::new_checked(&*buffer).unwrap();
+    assert_eq!(Icmpv4Repr::parse(&packet, &caps), Ok(repr));
+
+    check_mutations(gen, buffer, |data| {
+        if let Ok(packet) = Icmpv4Packet::new_checked(data) {
+            let _ = Icmpv4Repr::parse(&packet, &ChecksumCapabilities::ignored());
+        }
+    });
+}
+
+/// Check an ICMPv6 message.
+#[cfg(feature = "proto-ipv6")]
+pub fn check_icmpv6(gen: &mut Gen) {
+    let caps = ChecksumCapabilities::default();
+    let src_addr = IpAddress::Ipv6(gen.ipv6_address());
+    let dst_addr = IpAddress::Ipv6(gen.ipv6_address());
+    let data = gen.bytes(MAX_PAYLOAD_LEN);
+    let repr = gen.icmpv6_repr(data);
+    let mut buffer = [0; MAX_PACKET_LEN];
+    let buffer = &mut buffer[..repr.buffer_len()];
+    repr.emit(
+        &src_addr,
+        &dst_addr,
+        &mut Icmpv6Packet::new_unchecked(&mut *buffer),
+        &caps,
+    );
+    let packet = Icmpv6Packet::new_checked(&*buffer).unwrap();
+    assert_eq!(
+        Icmpv6Repr::parse(&src_addr, &dst_addr, &packet, &caps),
+        Ok(repr)
+    );
+
+    check_mutations(gen, buffer, |data| {
+        if let Ok(packet) = Icmpv6Packet::new_checked(data) {
+            let caps = ChecksumCapabilities::ignored();
+            let _ = Icmpv6Repr::parse(&src_addr, &dst_addr, &packet, &caps);
+        }
+    });
+}
+
+/// Check an IGMP message.
+#[cfg(feature = "proto-igmp")]
+pub fn check_igmp(gen: &mut Gen) {
+    let repr = gen.igmp_repr();
+    let mut buffer = [0; MAX_PACKET_LEN];
+    let buffer = &mut buffer[..repr.buffer_len()];
+    repr.emit(&mut IgmpPacket::new_unchecked(&mut *buffer));
+    let packet = IgmpPacket::new_checked(&*buffer).unwrap();
+    assert_eq!(IgmpRepr::parse(&packet), Ok(repr));
+
+    check_mutations(gen, buffer, |data| {
+        if let Ok(packet) = IgmpPacket::new_checked(data) {
+            let _ = IgmpRepr::parse(&packet);
+        }
+    });
+}
+
+/// Check ESP, AH and GRE headers.
+pub fn check_tunnels(gen: &mut Gen) {
+    let mut buffer = [0; MAX_PACKET_LEN];
+
+    let repr = gen.esp_repr();
+    let esp = &mut buffer[..repr.buffer_len()];
+    repr.emit(&mut EspPacket::new_unchecked(&mut *esp));
+    assert_eq!(EspRepr::parse(&EspPacket::new_unchecked(&*esp)), Ok(repr));
+    check_mutations(gen, esp, |data| {
+        let _ = EspRepr::parse(&EspPacket::new_unchecked(data));
+    });
+
+    let repr = gen.ah_repr();
+    let ah = &mut buffer[..repr.buffer_len()];
+    repr.emit(&mut AhPacket::new_unchecked(&mut *ah));
+    assert_eq!(AhRepr::parse(&AhPacket::new_unchecked(&*ah)), Ok(repr));
+    check_mutations(gen, ah, |data| {
+        let _ = AhRepr::parse(&AhPacket::new_unchecked(data));
+    });
+
+    let repr = gen.gre_repr();
+    let gre = &mut buffer[..repr.buffer_len()];
+    repr.emit(&mut GrePacket::new_unchecked(&mut *gre));
+    assert_eq!(GreRepr::parse(&GrePacket::new_unchecked(&*gre)), Ok(repr));
+    check_mutations(gen, gre, |data| {
+        let _ = GreRepr::parse(&GrePacket::new_unchecked(data));
+    });
+
+    #[cfg(feature = "medium-ethernet")]
+    {
+        let repr = gen.vxlan_repr();
+        let vxlan = &mut buffer[..repr.buffer_len()];
+        repr.emit(&mut VxlanPacket::new_unchecked(&mut *vxlan));
+        assert_eq!(
+            VxlanRepr::parse(&VxlanPacket::new_unchecked(&*vxlan)),
+            Ok(repr)
+        );
+        check_mutations(gen, vxlan, |data| {
+            let _ = VxlanRepr::parse(&VxlanPacket::new_unchecked(data));
+        });
+    }
+}
+
+/// Check a DHCPv4 packet.
+#[cfg(feature = "proto-dhcpv4")]
+pub fn check_dhcpv4(gen: &mut Gen) {
+    let repr = gen.dhcpv4_repr();
+    let mut buffer = [0; MAX_PACKET_LEN];
+    let buffer = &mut buffer[..repr.buffer_len()];
+    repr.emit(&mut DhcpPacket::new_unchecked(&mut *buffer))
+        .unwrap();
+    let packet = DhcpPacket::new_checked(&*buffer).unwrap();
+    assert_eq!(DhcpRepr::parse(&packet), Ok(repr));
+
+    check_mutations(gen, buffer, |data| {
+        if let Ok(packet) = DhcpPacket::new_checked(data) {
+            let _ = DhcpRepr::parse(&packet);
+        }
+    });
+}
+
+/// Check a DHCPv6 packet.
+#[cfg(feature = "proto-dhcpv6")]
+pub fn check_dhcpv6(gen: &mut Gen) {
+    let repr = gen.dhcpv6_repr();
+    let mut buffer = [0; MAX_PACKET_LEN];
+    let buffer = &mut buffer[..repr.buffer_len()];
+    repr.emit(&mut Dhcpv6Packet::new_unchecked(&mut *buffer))
+        .unwrap();
+    let packet = Dhcpv6Packet::new_checked(&*buffer).unwrap();
+    assert_eq!(Dhcpv6Repr::parse(&packet), Ok(repr));
+
+    check_mutations(gen, buffer, |data| {
+        if let Ok(packet) = Dhcpv6Packet::new_checked(data) {
+            let _ = Dhcpv6Repr::parse(&packet);
+        }
+    });
+}
+
+/// Run every check `iterations` times.
+pub fn check_all(gen: &mut Gen, iterations: usize) {
+    for _ in 0..iterations {
+        #[cfg(feature = "medium-ethernet")]
+        check_ethernet(gen);
+        #[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+        check_arp(gen);
+        #[cfg(feature = "proto-ipv4")]
+        check_ipv4(gen);
+        #[cfg(feature = "proto-ipv6")]
+        check_ipv6(gen);
+        check_udp(gen);
+        check_tcp(gen);
+        #[cfg(feature = "proto-ipv4")]
+        check_icmpv4(gen);
+        #[cfg(feature = "proto-ipv6")]
+        check_icmpv6(gen);
+        #[cfg(feature = "proto-igmp")]
+        check_igmp(gen);
+        check_tunnels(gen);
+        #[cfg(feature = "proto-dhcpv4")]
+        check_dhcpv4(gen);
+        #[cfg(feature = "proto-dhcpv6")]
+        check_dhcpv6(gen);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_all() {
+        check_all(&mut Gen::new(0x5eed), 500);
+    }
+}
@@ -0,0 +1,738 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+
+use super::{Error, Result};
+#[cfg(feature = "medium-ethernet")]
+use crate::wire::EthernetAddress;
+#[cfg(feature = "proto-ipv4")]
+use crate::wire::Ipv4Address;
+#[cfg(feature = "proto-ipv6")]
+use crate::wire::Ipv6Address;
+
+/// The UDP port of the PTP event messages, which are timestamped.
+pub const EVENT_PORT: u16 = 319;
+/// The UDP port of the PTP general messages.
+pub const GENERAL_PORT: u16 = 320;
+
+/// The Ethernet address of the PTP messages sent over IEEE 802.3.
+#[cfg(feature = "medium-ethernet")]
+pub const ETHERNET_MULTICAST_ADDR: EthernetAddress =
+    EthernetAddress([0x01, 0x1b, 0x19, 0x00, 0x00, 0x00]);
+/// The IPv4 address of the PTP messages sent over UDP.
+#[cfg(feature = "proto-ipv4")]
+pub const IPV4_MULTICAST_ADDR: Ipv4Address = Ipv4Address::new(224, 0, 1, 129);
+/// The IPv6 address of the PTP messages sent over UDP.
+#[cfg(feature = "proto-ipv6")]
+pub const IPV6_MULTICAST_ADDR: Ipv6Address = Ipv6Address::new(0xff0e, 0, 0, 0, 0, 0, 0, 0x0181);
+
+/// The flag of the messages whose precise timestamp follows in another message.
+pub const FLAG_TWO_STEP: u16 = 0x0200;
+
+enum_with_unknown! {
+    /// PTP message type.
+    pub enum MessageType(u8) {
+        Sync                = 0x0,
+        DelayReq            = 0x1,
+        PdelayReq           = 0x2,
+        PdelayResp          = 0x3,
+        FollowUp            = 0x8,
+        DelayResp           = 0x9,
+        PdelayRespFollowUp  = 0xa,
+        Announce            = 0xb,
+        Signaling           = 0xc,
+        Management          = 0xd
+    }
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MessageType::Sync => write!(f, "Sync"),
+            MessageType::DelayReq => write!(f, "Delay_Req"),
+            MessageType::PdelayReq => write!(f, "Pdelay_Req"),
+            MessageType::PdelayResp => write!(f, "Pdelay_Resp"),
+            MessageType::FollowUp => write!(f, "Follow_Up"),
+            MessageType::DelayResp => write!(f, "Delay_Resp"),
+            MessageType::PdelayRespFollowUp => write!(f, "Pdelay_Resp_Follow_Up"),
+            MessageType::Announce => write!(f, "Announce"),
+            MessageType::Signaling => write!(f, "Signaling"),
+            MessageType::Management => write!(f, "Management"),
+            MessageType::Unknown(id) => write!(f, "0x{id:x}"),
+        }
+    }
+}
+
+impl MessageType {
+    /// Query whether the message is an event message, which is timestamped when it is
+    /// sent and received.
+    pub const fn is_event(&self) -> bool {
+        matches!(
+            *self,
+            MessageType::Sync
+                | MessageType::DelayReq
+                | MessageType::PdelayReq
+                | MessageType::PdelayResp
+        )
+    }
+}
+
+/// A PTP timestamp, in seconds and nanoseconds of the PTP timescale.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Timestamp {
+    /// The seconds, of which only the lower 48 bits are carried.
+    pub seconds: u64,
+    /// The nanoseconds, less than 1_000_000_000.
+    pub nanoseconds: u32,
+}
+
+impl Timestamp {
+    /// The length of a timestamp in a message.
+    pub const LEN: usize = 10;
+
+    fn parse(data: &[u8]) -> Result<Timestamp> {
+        let timestamp = Timestamp {
+            seconds: NetworkEndian::read_u48(&data[0..6]),
+            nanoseconds: NetworkEndian::read_u32(&data[6..10]),
+        };
+        if timestamp.nanoseconds >= 1_000_000_000 {
+            return Err(Error);
+        }
+        Ok(timestamp)
+    }
+
+    fn emit(&self, data: &mut [u8]) {
+        NetworkEndian::write_u48(&mut data[0..6], self.seconds & 0xffff_ffff_ffff);
+        NetworkEndian::write_u32(&mut data[6..10], self.nanoseconds);
+    }
+}
+
+impl From<crate::phy::HardwareTimestamp> for Timestamp {
+    fn from(timestamp: crate::phy::HardwareTimestamp) -> Timestamp {
+        Timestamp {
+            seconds: timestamp.seconds,
+            nanoseconds: timestamp.nanoseconds,
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:09}s", self.seconds, self.nanoseconds)
+    }
+}
+
+/// A PTP port identity, the identity of a clock and the number of one of its ports.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PortIdentity {
+    pub clock_identity: [u8; 8],
+    pub port_number: u16,
+}
+
+impl PortIdentity {
+    /// The length of a port identity in a message.
+    pub const LEN: usize = 10;
+
+    fn parse(data: &[u8]) -> PortIdentity {
+        let mut clock_identity = [0; 8];
+        clock_identity.copy_from_slice(&data[0..8]);
+        PortIdentity {
+            clock_identity,
+            port_number: NetworkEndian::read_u16(&data[8..10]),
+        }
+    }
+
+    fn emit(&self, data: &mut [u8]) {
+        data[0..8].copy_from_slice(&self.clock_identity);
+        NetworkEndian::write_u16(&mut data[8..10], self.port_number);
+    }
+}
+
+impl fmt::Display for PortIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for octet in &self.clock_identity {
+            write!(f, "{octet:02x}")?;
+        }
+        write!(f, "-{}", self.port_number)
+    }
+}
+
+/// A read/write wrapper around a Precision Time Protocol message buffer.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+// Format of the PTP common header, see IEEE 1588-2019 section 13.3.
+mod field {
+    use crate::wire::field::*;
+
+    pub const MESSAGE_TYPE: usize = 0;
+    pub const VERSION: usize = 1;
+    pub const LENGTH: Field = 2..4;
+    pub const DOMAIN: usize = 4;
+    pub const RESERVED0: usize = 5;
+    pub const FLAGS: Field = 6..8;
+    pub const CORRECTION: Field = 8..16;
+    pub const RESERVED1: Field = 16..20;
+    pub const SOURCE_PORT: Field = 20..30;
+    pub const SEQUENCE_ID: Field = 30..32;
+    pub const CONTROL: usize = 32;
+    pub const LOG_INTERVAL: usize = 33;
+
+    pub const TIMESTAMP: Field = 34..44;
+    pub const REQUESTING_PORT: Field = 44..54;
+}
+
+/// The length of the PTP common header.
+pub const HEADER_LEN: usize = field::LOG_INTERVAL + 1;
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Imbue a raw octet buffer with PTP message structure.
+    pub const fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method of the common header will panic if called.
+    /// Returns `Err(Error)` if the buffer is shorter than the header, or than the
+    /// message length field.
+    ///
+    /// The result of this check is invalidated by calling [set_message_length].
+    ///
+    /// [set_message_length]: #method.set_message_length
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < HEADER_LEN {
+            return Err(Error);
+        }
+        let message_length = self.message_length() as usize;
+        if message_length < HEADER_LEN || message_length > len {
+            return Err(Error);
+        }
+        Ok(())
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the transport specific field.
+    #[inline]
+    pub fn transport_specific(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::MESSAGE_TYPE] >> 4
+    }
+
+    /// Return the message type field.
+    #[inline]
+    pub fn message_type(&self) -> MessageType {
+        let data = self.buffer.as_ref();
+        MessageType::from(data[field::MESSAGE_TYPE] & 0x0f)
+    }
+
+    /// Return the PTP version field.
+    #[inline]
+    pub fn version(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::VERSION] & 0x0f
+    }
+
+    /// Return the message length field.
+    #[inline]
+    pub fn message_length(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::LENGTH])
+    }
+
+    /// Return the domain number field.
+    #[inline]
+    pub fn domain_number(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::DOMAIN]
+    }
+
+    /// Return the flag field.
+    #[inline]
+    pub fn flags(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::FLAGS])
+    }
+
+    /// Return the correction field, in nanoseconds multiplied by 2<sup>16</sup>.
+    #[inline]
+    pub fn correction(&self) -> i64 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_i64(&data[field::CORRECTION])
+    }
+
+    /// Return the source port identity field.
+    #[inline]
+    pub fn source_port_identity(&self) -> PortIdentity {
+        let data = self.buffer.as_ref();
+        PortIdentity::parse(&data[field::SOURCE_PORT])
+    }
+
+    /// Return the sequence identifier field.
+    #[inline]
+    pub fn sequence_id(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::SEQUENCE_ID])
+    }
+
+    /// Return the control field.
+    #[inline]
+    pub fn control(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::CONTROL]
+    }
+
+    /// Return the log message interval field.
+    #[inline]
+    pub fn log_message_interval(&self) -> i8 {
+        let data = self.buffer.as_ref();
+        data[field::LOG_INTERVAL] as i8
+    }
+
+    /// Return the timestamp at the start of the body of the Sync, Delay_Req,
+    /// Follow_Up and Delay_Resp messages.
+    ///
+    /// # Panics
+    /// This function may panic if the message is too short.
+    #[inline]
+    pub fn timestamp(&self) -> Result<Timestamp> {
+        let data = self.buffer.as_ref();
+        Timestamp::parse(&data[field::TIMESTAMP])
+    }
+
+    /// Return the requesting port identity of the Delay_Resp message.
+    ///
+    /// # Panics
+    /// This function may panic if the message is too short.
+    #[inline]
+    pub fn requesting_port_identity(&self) -> PortIdentity {
+        let data = self.buffer.as_ref();
+        PortIdentity::parse(&data[field::REQUESTING_PORT])
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
+    /// Return a pointer to the body of the message.
+    #[inline]
+    pub fn body(&self) -> &'a [u8] {
+        let message_length = self.message_length() as usize;
+        let data = self.buffer.as_ref();
+        &data[HEADER_LEN..message_length]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the transport specific and message type fields.
+    #[inline]
+    pub fn set_message_type(&mut self, transport_specific: u8, value: MessageType) {
+        let data = self.buffer.as_mut();
+        data[field::MESSAGE_TYPE] = (transport_specific << 4) | (u8::from(value) & 0x0f);
+    }
+
+    /// Set the PTP version field, and clear the minor version.
+    #[inline]
+    pub fn set_version(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::VERSION] = value & 0x0f;
+    }
+
+    /// Set the message length field.
+    #[inline]
+    pub fn set_message_length(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::LENGTH], value)
+    }
+
+    /// Set the domain number field.
+    #[inline]
+    pub fn set_domain_number(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::DOMAIN] = value;
+    }
+
+    /// Set the flag field.
+    #[inline]
+    pub fn set_flags(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::FLAGS], value)
+    }
+
+    /// Set the correction field, in nanoseconds multiplied by 2<sup>16</sup>.
+    #[inline]
+    pub fn set_correction(&mut self, value: i64) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_i64(&mut data[field::CORRECTION], value)
+    }
+
+    /// Clear the reserved fields.
+    #[inline]
+    pub fn clear_reserved(&mut self) {
+        let data = self.buffer.as_mut();
+        data[field::RESERVED0] = 0;
+        data[field::RESERVED1].fill(0);
+    }
+
+    /// Set the source port identity field.
+    #[inline]
+    pub fn set_source_port_identity(&mut self, value: PortIdentity) {
+        let data = self.buffer.as_mut();
+        value.emit(&mut data[field::SOURCE_PORT])
+    }
+
+    /// Set the sequence identifier field.
+    #[inline]
+    pub fn set_sequence_id(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::SEQUENCE_ID], value)
+    }
+
+    /// Set the control field.
+    #[inline]
+    pub fn set_control(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::CONTROL] = value;
+    }
+
+    /// Set the log message interval field.
+    #[inline]
+    pub fn set_log_message_interval(&mut self, value: i8) {
+        let data = self.buffer.as_mut();
+        data[field::LOG_INTERVAL] = value as u8;
+    }
+
+    /// Set the timestamp at the start of the body of the Sync, Delay_Req, Follow_Up
+    /// and Delay_Resp messages.
+    #[inline]
+    pub fn set_timestamp(&mut self, value: Timestamp) {
+        let data = self.buffer.as_mut();
+        value.emit(&mut data[field::TIMESTAMP])
+    }
+
+    /// Set the requesting port identity of the Delay_Resp message.
+    #[inline]
+    pub fn set_requesting_port_identity(&mut self, value: PortIdentity) {
+        let data = self.buffer.as_mut();
+        value.emit(&mut data[field::REQUESTING_PORT])
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+/// The body of a PTP message used by an ordinary clock with the delay request-response
+/// mechanism.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Message {
+    Sync {
+        origin_timestamp: Timestamp,
+    },
+    DelayReq {
+        origin_timestamp: Timestamp,
+    },
+    FollowUp {
+        precise_origin_timestamp: Timestamp,
+    },
+    DelayResp {
+        receive_timestamp: Timestamp,
+        requesting_port_identity: PortIdentity,
+    },
+}
+
+impl Message {
+    /// Return the type of the message.
+    pub const fn message_type(&self) -> MessageType {
+        match self {
+            Message::Sync { .. } => MessageType::Sync,
+            Message::DelayReq { .. } => MessageType::DelayReq,
+            Message::FollowUp { .. } => MessageType::FollowUp,
+            Message::DelayResp { .. } => MessageType::DelayResp,
+        }
+    }
+
+    /// Return the value of the control field, kept for compatibility with PTP version 1.
+    const fn control(&self) -> u8 {
+        match self {
+            Message::Sync { .. } => 0,
+            Message::DelayReq { .. } => 1,
+            Message::FollowUp { .. } => 2,
+            Message::DelayResp { .. } => 3,
+        }
+    }
+
+    /// Return the length of the body of the message.
+    const fn body_len(&self) -> usize {
+        match self {
+            Message::DelayResp { .. } => Timestamp::LEN + PortIdentity::LEN,
+            _ => Timestamp::LEN,
+        }
+    }
+}
+
+/// A high-level representation of a Precision Time Protocol message.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Repr {
+    pub domain_number: u8,
+    pub flags: u16,
+    /// The correction, in nanoseconds multiplied by 2<sup>16</sup>.
+    pub correction: i64,
+    pub source_port_identity: PortIdentity,
+    pub sequence_id: u16,
+    pub log_message_interval: i8,
+    pub message: Message,
+}
+
+impl Repr {
+    /// Parse a PTP version 2 message and return a high-level representation.
+    ///
+    /// Returns `Err(Error)` for the messages other than Sync, Delay_Req, Follow_Up and
+    /// Delay_Resp.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(packet: &Packet<&T>) -> Result<Repr> {
+        packet.check_len()?;
+        if packet.version() != 2 {
+            return Err(Error);
+        }
+
+        let body_len = packet.body().len();
+        let message = match packet.message_type() {
+            MessageType::Sync if body_len >= Timestamp::LEN => Message::Sync {
+                origin_timestamp: packet.timestamp()?,
+            },
+            MessageType::DelayReq if body_len >= Timestamp::LEN => Message::DelayReq {
+                origin_timestamp: packet.timestamp()?,
+            },
+            MessageType::FollowUp if body_len >= Timestamp::LEN => Message::FollowUp {
+                precise_origin_timestamp: packet.timestamp()?,
+            },
+            MessageType::DelayResp if body_len >= Timestamp::LEN + PortIdentity::LEN => {
+                Message::DelayResp {
+                    receive_timestamp: packet.timestamp()?,
+                    requesting_port_identity: packet.requesting_port_identity(),
+                }
+            }
+            _ => return Err(Error),
+        };
+
+        Ok(Repr {
+            domain_number: packet.domain_number(),
+            flags: packet.flags(),
+            correction: packet.correction(),
+            source_port_identity: packet.source_port_identity(),
+            sequence_id: packet.sequence_id(),
+            log_message_interval: packet.log_message_interval(),
+            message,
+        })
+    }
+
+    /// Return the length of a message that will be emitted from this high-level representation.
+    pub const fn buffer_len(&self) -> usize {
+        HEADER_LEN + self.message.body_len()
+    }
+
+    /// Emit a high-level representation into a PTP message.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) {
+        packet.set_message_type(0, self.message.message_type());
+        packet.set_version(2);
+        packet.set_message_length(self.buffer_len() as u16);
+        packet.set_domain_number(self.domain_number);
+        packet.set_flags(self.flags);
+        packet.set_correction(self.correction);
+        packet.clear_reserved();
+        packet.set_source_port_identity(self.source_port_identity);
+        packet.set_sequence_id(self.sequence_id);
+        packet.set_control(self.message.control());
+        packet.set_log_message_interval(self.log_message_interval);
+
+        match self.message {
+            Message::Sync { origin_timestamp } | Message::DelayReq { origin_timestamp } => {
+                packet.set_timestamp(origin_timestamp)
+            }
+            Message::FollowUp {
+                precise_origin_timestamp,
+            } => packet.set_timestamp(precise_origin_timestamp),
+            Message::DelayResp {
+                receive_timestamp,
+                requesting_port_identity,
+            } => {
+                packet.set_timestamp(receive_timestamp);
+                packet.set_requesting_port_identity(requesting_port_identity);
+            }
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> fmt::Display for Packet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Repr::parse(&Packet::new_unchecked(self.buffer.as_ref())) {
+            Ok(repr) => write!(f, "{repr}"),
+            Err(err) => write!(f, "PTP ({err})"),
+        }
+    }
+}
+
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PTP {} domain={} src={} seq={}",
+            self.message.message_type(),
+            self.domain_number,
+            self.source_port_identity,
+            self.sequence_id
+        )?;
+        match self.message {
+            Message::Sync { origin_timestamp } | Message::DelayReq { origin_timestamp } => {
+                write!(f, " origin={origin_timestamp}")
+            }
+            Message::FollowUp {
+                precise_origin_timestamp,
+            } => write!(f, " origin={precise_origin_timestamp}"),
+            Message::DelayResp {
+                receive_timestamp,
+                requesting_port_identity,
+            } => write!(
+                f,
+                " receive={receive_timestamp} requesting={requesting_port_identity}"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static DELAY_RESP_BYTES: [u8; 54] = [
+        0x09, 0x02, 0x00, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x11, 0x22, 0xff, 0xfe, 0x33, 0x44, 0x55, 0x00, 0x01,
+        0x12, 0x34, 0x03, 0x7f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2a, 0x00, 0x00, 0x01, 0xf4, 0x66,
+        0x77, 0x88, 0xff, 0xfe, 0x99, 0xaa, 0xbb, 0x00, 0x02,
+    ];
+
+    fn delay_resp_repr() -> Repr {
+        Repr {
+            domain_number: 0,
+            flags: 0,
+            correction: 0x10000,
+            source_port_identity: PortIdentity {
+                clock_identity: [0x00, 0x11, 0x22, 0xff, 0xfe, 0x33, 0x44, 0x55],
+                port_number: 1,
+            },
+            sequence_id: 0x1234,
+            log_message_interval: 127,
+            message: Message::DelayResp {
+                receive_timestamp: Timestamp {
+                    seconds: 42,
+                    nanoseconds: 500,
+                },
+                requesting_port_identity: PortIdentity {
+                    clock_identity: [0x66, 0x77, 0x88, 0xff, 0xfe, 0x99, 0xaa, 0xbb],
+                    port_number: 2,
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_deconstruct() {
+        let packet = Packet::new_checked(&DELAY_RESP_BYTES[..]).unwrap();
+        assert_eq!(packet.transport_specific(), 0);
+        assert_eq!(packet.message_type(), MessageType::DelayResp);
+        assert_eq!(packet.version(), 2);
+        assert_eq!(packet.message_length(), 54);
+        assert_eq!(packet.domain_number(), 0);
+        assert_eq!(packet.flags(), 0);
+        assert_eq!(packet.correction(), 0x10000);
+        assert_eq!(packet.sequence_id(), 0x1234);
+        assert_eq!(packet.control(), 3);
+        assert_eq!(packet.log_message_interval(), 127);
+        assert_eq!(
+            packet.timestamp(),
+            Ok(Timestamp {
+                seconds: 42,
+                nanoseconds: 500
+            })
+        );
+        assert_eq!(packet.requesting_port_identity().port_number, 2);
+        assert_eq!(packet.body().len(), 20);
+    }
+
+    #[test]
+    fn test_parse() {
+        let packet = Packet::new_unchecked(&DELAY_RESP_BYTES[..]);
+        assert_eq!(Repr::parse(&packet), Ok(delay_resp_repr()));
+    }
+
+    #[test]
+    fn test_emit() {
+        let repr = delay_resp_repr();
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        repr.emit(&mut Packet::new_unchecked(&mut bytes));
+        assert_eq!(&bytes[..], &DELAY_RESP_BYTES[..]);
+    }
+
+    #[test]
+    fn test_sync_roundtrip() {
+        let repr = Repr {
+            domain_number: 24,
+            flags: FLAG_TWO_STEP,
+            correction: -1,
+            source_port_identity: PortIdentity::default(),
+            sequence_id: 7,
+            log_message_interval: -3,
+            message: Message::Sync {
+                origin_timestamp: Timestamp::default(),
+            },
+        };
+        assert_eq!(repr.buffer_len(), 44);
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        repr.emit(&mut Packet::new_unchecked(&mut bytes));
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert!(packet.message_type().is_event());
+        assert_eq!(packet.control(), 0);
+        assert_eq!(Repr::parse(&packet), Ok(repr));
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(Packet::new_checked(&DELAY_RESP_BYTES[..33]), Err(Error));
+        // Message length beyond the buffer.
+        assert_eq!(Packet::new_checked(&DELAY_RESP_BYTES[..53]), Err(Error));
+
+        // Body too short for a Delay_Resp.
+        let mut bytes = DELAY_RESP_BYTES;
+        bytes[3] = 44;
+        assert_eq!(Repr::parse(&Packet::new_unchecked(&bytes[..])), Err(Error));
+
+        // Nanoseconds out of range.
+        let mut bytes = DELAY_RESP_BYTES;
+        bytes[40..44].copy_from_slice(&[0x3b, 0x9a, 0xca, 0x00]);
+        assert_eq!(Repr::parse(&Packet::new_unchecked(&bytes[..])), Err(Error));
+
+        // PTP version 1.
+        let mut bytes = DELAY_RESP_BYTES;
+        bytes[1] = 1;
+        assert_eq!(Repr::parse(&Packet::new_unchecked(&bytes[..])), Err(Error));
+    }
+}
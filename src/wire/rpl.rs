@@ -0,0 +1,1261 @@
+// Packet implementation for the RPL control messages, the routing protocol for
+// low-power and lossy networks. See [RFC 6550].
+//
+// [RFC 6550]: https://tools.ietf.org/html/rfc6550
+
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+
+use super::{Error, Result};
+use crate::wire::icmpv6::{Message, Packet};
+use crate::wire::{Ipv6Address, Ipv6Cidr};
+
+enum_with_unknown! {
+    /// RPL control message code.
+    pub enum Code(u8) {
+        /// DODAG Information Solicitation
+        DodagInformationSolicitation      = 0x00,
+        /// DODAG Information Object
+        DodagInformationObject            = 0x01,
+        /// Destination Advertisement Object
+        DestinationAdvertisementObject    = 0x02,
+        /// Destination Advertisement Object Acknowledgment
+        DestinationAdvertisementObjectAck = 0x03
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Code::DodagInformationSolicitation => write!(f, "DIS"),
+            Code::DodagInformationObject => write!(f, "DIO"),
+            Code::DestinationAdvertisementObject => write!(f, "DAO"),
+            Code::DestinationAdvertisementObjectAck => write!(f, "DAO-ACK"),
+            Code::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+enum_with_unknown! {
+    /// How a DODAG maintains downward routes, see [RFC 6550 § 6.3.1].
+    ///
+    /// [RFC 6550 § 6.3.1]: https://tools.ietf.org/html/rfc6550#section-6.3.1
+    pub enum ModeOfOperation(u8) {
+        /// No downward routes are maintained
+        NoDownwardRoutes           = 0,
+        /// Only the root keeps downward routes, and uses source routing
+        NonStoring                 = 1,
+        /// Every node keeps the downward routes of its sub-DODAG
+        StoringWithoutMulticast    = 2,
+        /// Like `StoringWithoutMulticast`, with multicast support
+        StoringWithMulticast       = 3
+    }
+}
+
+impl fmt::Display for ModeOfOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ModeOfOperation::NoDownwardRoutes => write!(f, "no downward routes"),
+            ModeOfOperation::NonStoring => write!(f, "non-storing"),
+            ModeOfOperation::StoringWithoutMulticast => write!(f, "storing"),
+            ModeOfOperation::StoringWithMulticast => write!(f, "storing with multicast"),
+            ModeOfOperation::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+enum_with_unknown! {
+    /// RPL control message option type.
+    pub enum OptionType(u8) {
+        /// 1 byte of padding
+        Pad1                 = 0x00,
+        /// Multiple bytes of padding
+        PadN                 = 0x01,
+        /// DAG Metric Container
+        DagMetricContainer   = 0x02,
+        /// Route Information
+        RouteInformation     = 0x03,
+        /// DODAG Configuration
+        DodagConfiguration   = 0x04,
+        /// RPL Target
+        RplTarget            = 0x05,
+        /// Transit Information
+        TransitInformation   = 0x06,
+        /// Solicited Information
+        SolicitedInformation = 0x07,
+        /// Prefix Information
+        PrefixInformation    = 0x08
+    }
+}
+
+impl fmt::Display for OptionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OptionType::Pad1 => write!(f, "Pad1"),
+            OptionType::PadN => write!(f, "PadN"),
+            OptionType::DagMetricContainer => write!(f, "DAG metric container"),
+            OptionType::RouteInformation => write!(f, "route information"),
+            OptionType::DodagConfiguration => write!(f, "DODAG configuration"),
+            OptionType::RplTarget => write!(f, "RPL target"),
+            OptionType::TransitInformation => write!(f, "transit information"),
+            OptionType::SolicitedInformation => write!(f, "solicited information"),
+            OptionType::PrefixInformation => write!(f, "prefix information"),
+            OptionType::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+/// The rank of a node that is not part of a DODAG, or is leaving it.
+pub const INFINITE_RANK: u16 = 0xffff;
+
+// Format of the RPL control messages, following the type, code and checksum
+// of the ICMPv6 header.
+//
+// DODAG Information Solicitation:
+//  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//  |     Flags     |   Reserved    |   Option(s)...
+//  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// DODAG Information Object:
+//  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//  | RPLInstanceID |Version Number |             Rank              |
+//  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//  |G|0| MOP | Prf |     DTSN      |     Flags     |   Reserved    |
+//  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//  |                            DODAGID                            |
+//  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//  |   Option(s)...
+//  +-+-+-+-+-+-+-+-+
+//
+// Destination Advertisement Object:
+//  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//  | RPLInstanceID |K|D|   Flags   |   Reserved    | DAOSequence   |
+//  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//  |                      DODAGID (if D is set)                    |
+//  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//  |   Option(s)...
+//  +-+-+-+-+-+-+-+-+
+//
+// Destination Advertisement Object Acknowledgment:
+//  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//  | RPLInstanceID |D|  Reserved   |  DAOSequence  |    Status     |
+//  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//  |                      DODAGID (if D is set)                    |
+//  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//  |   Option(s)...
+//  +-+-+-+-+-+-+-+-+
+//
+// See https://tools.ietf.org/html/rfc6550#section-6 for details.
+mod field {
+    use crate::wire::field::*;
+
+    pub const DIS_FLAGS: usize = 4;
+    pub const DIS_RESERVED: usize = 5;
+    pub const DIS_OPTIONS: usize = 6;
+
+    pub const INSTANCE_ID: usize = 4;
+
+    pub const DIO_VERSION: usize = 5;
+    pub const DIO_RANK: Field = 6..8;
+    pub const DIO_G_MOP_PRF: usize = 8;
+    pub const DIO_DTSN: usize = 9;
+    pub const DIO_FLAGS: usize = 10;
+    pub const DIO_RESERVED: usize = 11;
+    pub const DIO_DODAG_ID: Field = 12..28;
+    pub const DIO_OPTIONS: usize = 28;
+
+    pub const DAO_K_D: usize = 5;
+    pub const DAO_RESERVED: usize = 6;
+    pub const DAO_SEQUENCE: usize = 7;
+
+    pub const DAO_ACK_D: usize = 5;
+    pub const DAO_ACK_SEQUENCE: usize = 6;
+    pub const DAO_ACK_STATUS: usize = 7;
+
+    // The DODAGID of DAO and DAO-ACK messages, present if the D flag is set.
+    pub const DODAG_ID: Field = 8..24;
+    pub const OPTIONS: usize = 8;
+
+    pub const DIO_FLG_G: u8 = 0x80;
+    pub const DAO_FLG_K: u8 = 0x80;
+    pub const DAO_FLG_D: u8 = 0x40;
+    pub const DAO_ACK_FLG_D: u8 = 0x80;
+}
+
+/// Getters for the RPL control message headers.
+/// See [RFC 6550 § 6].
+///
+/// [RFC 6550 § 6]: https://tools.ietf.org/html/rfc6550#section-6
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Return the RPLInstanceID field.
+    #[inline]
+    pub fn rpl_instance_id(&self) -> u8 {
+        self.buffer.as_ref()[field::INSTANCE_ID]
+    }
+
+    /// Return the Version Number field (for DIO messages).
+    #[inline]
+    pub fn rpl_dio_version(&self) -> u8 {
+        self.buffer.as_ref()[field::DIO_VERSION]
+    }
+
+    /// Return the Rank field (for DIO messages).
+    #[inline]
+    pub fn rpl_dio_rank(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buffer.as_ref()[field::DIO_RANK])
+    }
+
+    /// Return the Grounded flag (for DIO messages).
+    #[inline]
+    pub fn rpl_dio_grounded(&self) -> bool {
+        self.buffer.as_ref()[field::DIO_G_MOP_PRF] & field::DIO_FLG_G != 0
+    }
+
+    /// Return the Mode of Operation field (for DIO messages).
+    #[inline]
+    pub fn rpl_dio_mode_of_operation(&self) -> ModeOfOperation {
+        ModeOfOperation::from((self.buffer.as_ref()[field::DIO_G_MOP_PRF] >> 3) & 0x07)
+    }
+
+    /// Return the DODAG Preference field (for DIO messages).
+    #[inline]
+    pub fn rpl_dio_preference(&self) -> u8 {
+        self.buffer.as_ref()[field::DIO_G_MOP_PRF] & 0x07
+    }
+
+    /// Return the Destination Advertisement Trigger Sequence Number (for DIO messages).
+    #[inline]
+    pub fn rpl_dio_dtsn(&self) -> u8 {
+        self.buffer.as_ref()[field::DIO_DTSN]
+    }
+
+    /// Return the DODAGID field (for DIO messages).
+    #[inline]
+    pub fn rpl_dio_dodag_id(&self) -> Ipv6Address {
+        Ipv6Address::from_bytes(&self.buffer.as_ref()[field::DIO_DODAG_ID])
+    }
+
+    /// Return the K flag (for DAO messages).
+    #[inline]
+    pub fn rpl_dao_expect_ack(&self) -> bool {
+        self.buffer.as_ref()[field::DAO_K_D] & field::DAO_FLG_K != 0
+    }
+
+    /// Return the DAOSequence field (for DAO and DAO-ACK messages).
+    #[inline]
+    pub fn rpl_dao_sequence(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        match Code::from(self.msg_code()) {
+            Code::DestinationAdvertisementObject => data[field::DAO_SEQUENCE],
+            _ => data[field::DAO_ACK_SEQUENCE],
+        }
+    }
+
+    /// Return the Status field (for DAO-ACK messages).
+    #[inline]
+    pub fn rpl_dao_ack_status(&self) -> u8 {
+        self.buffer.as_ref()[field::DAO_ACK_STATUS]
+    }
+
+    /// Return the DODAGID field, if present (for DAO and DAO-ACK messages).
+    #[inline]
+    pub fn rpl_dao_dodag_id(&self) -> Option<Ipv6Address> {
+        let data = self.buffer.as_ref();
+        self.rpl_dao_dodag_id_present()
+            .then(|| Ipv6Address::from_bytes(&data[field::DODAG_ID]))
+    }
+
+    /// Return whether the D flag is set (for DAO and DAO-ACK messages).
+    fn rpl_dao_dodag_id_present(&self) -> bool {
+        let data = self.buffer.as_ref();
+        match Code::from(self.msg_code()) {
+            Code::DestinationAdvertisementObject => data[field::DAO_K_D] & field::DAO_FLG_D != 0,
+            Code::DestinationAdvertisementObjectAck => {
+                data[field::DAO_ACK_D] & field::DAO_ACK_FLG_D != 0
+            }
+            _ => false,
+        }
+    }
+
+    /// Return the offset of the options of the message.
+    fn rpl_options_offset(&self) -> usize {
+        match Code::from(self.msg_code()) {
+            Code::DodagInformationSolicitation => field::DIS_OPTIONS,
+            Code::DodagInformationObject => field::DIO_OPTIONS,
+            _ if self.rpl_dao_dodag_id_present() => field::DODAG_ID.end,
+            _ => field::OPTIONS,
+        }
+    }
+}
+
+/// Setters for the RPL control message headers.
+/// See [RFC 6550 § 6].
+///
+/// [RFC 6550 § 6]: https://tools.ietf.org/html/rfc6550#section-6
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the RPLInstanceID field.
+    #[inline]
+    pub fn set_rpl_instance_id(&mut self, value: u8) {
+        self.buffer.as_mut()[field::INSTANCE_ID] = value;
+    }
+
+    /// Set the Version Number field (for DIO messages).
+    #[inline]
+    pub fn set_rpl_dio_version(&mut self, value: u8) {
+        self.buffer.as_mut()[field::DIO_VERSION] = value;
+    }
+
+    /// Set the Rank field (for DIO messages).
+    #[inline]
+    pub fn set_rpl_dio_rank(&mut self, value: u16) {
+        NetworkEndian::write_u16(&mut self.buffer.as_mut()[field::DIO_RANK], value);
+    }
+
+    /// Set the Grounded flag, Mode of Operation and DODAG Preference fields (for DIO
+    /// messages).
+    #[inline]
+    pub fn set_rpl_dio_g_mop_prf(&mut self, grounded: bool, mop: ModeOfOperation, prf: u8) {
+        let g = if grounded { field::DIO_FLG_G } else { 0 };
+        self.buffer.as_mut()[field::DIO_G_MOP_PRF] = g | (u8::from(mop) & 0x07) << 3 | prf & 0x07;
+    }
+
+    /// Set the Destination Advertisement Trigger Sequence Number (for DIO messages).
+    #[inline]
+    pub fn set_rpl_dio_dtsn(&mut self, value: u8) {
+        self.buffer.as_mut()[field::DIO_DTSN] = value;
+    }
+
+    /// Set the DODAGID field (for DIO messages).
+    #[inline]
+    pub fn set_rpl_dio_dodag_id(&mut self, value: Ipv6Address) {
+        self.buffer.as_mut()[field::DIO_DODAG_ID].copy_from_slice(value.as_bytes());
+    }
+
+    /// Set the K and D flags, and the DAOSequence field (for DAO messages).
+    #[inline]
+    pub fn set_rpl_dao_flags_sequence(&mut self, expect_ack: bool, dodag_id: bool, seq: u8) {
+        let k = if expect_ack { field::DAO_FLG_K } else { 0 };
+        let d = if dodag_id { field::DAO_FLG_D } else { 0 };
+        let data = self.buffer.as_mut();
+        data[field::DAO_K_D] = k | d;
+        data[field::DAO_SEQUENCE] = seq;
+    }
+
+    /// Set the D flag, and the DAOSequence and Status fields (for DAO-ACK messages).
+    #[inline]
+    pub fn set_rpl_dao_ack_flags_sequence(&mut self, dodag_id: bool, seq: u8, status: u8) {
+        let data = self.buffer.as_mut();
+        data[field::DAO_ACK_D] = if dodag_id { field::DAO_ACK_FLG_D } else { 0 };
+        data[field::DAO_ACK_SEQUENCE] = seq;
+        data[field::DAO_ACK_STATUS] = status;
+    }
+
+    /// Set the DODAGID field (for DAO and DAO-ACK messages).
+    #[inline]
+    pub fn set_rpl_dao_dodag_id(&mut self, value: Ipv6Address) {
+        self.buffer.as_mut()[field::DODAG_ID].copy_from_slice(value.as_bytes());
+    }
+
+    /// Clear the reserved fields and the flags defined by no standard.
+    #[inline]
+    fn clear_rpl_reserved(&mut self) {
+        let data = self.buffer.as_mut();
+        match Code::from(data[1]) {
+            Code::DodagInformationSolicitation => {
+                data[field::DIS_FLAGS] = 0;
+                data[field::DIS_RESERVED] = 0;
+            }
+            Code::DodagInformationObject => {
+                data[field::DIO_FLAGS] = 0;
+                data[field::DIO_RESERVED] = 0;
+            }
+            Code::DestinationAdvertisementObject => data[field::DAO_RESERVED] = 0,
+            _ => (),
+        }
+    }
+}
+
+/// A read/write wrapper around an RPL control message option.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RplOption<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+// Format of an RPL control message option
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+- - - - - - - - -
+// |  Option Type  | Option Length | Option Data
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+- - - - - - - - -
+//
+// See https://tools.ietf.org/html/rfc6550#section-6.7 for details.
+mod opt_field {
+    #![allow(non_snake_case)]
+
+    use crate::wire::field::*;
+
+    pub const TYPE: usize = 0;
+    pub const LENGTH: usize = 1;
+
+    pub const fn DATA(length: u8) -> Field {
+        2..length as usize + 2
+    }
+
+    // DODAG Configuration option, of 14 octets.
+    pub const CONF_FLAGS_A_PCS: usize = 2;
+    pub const CONF_DIO_INT_DOUBL: usize = 3;
+    pub const CONF_DIO_INT_MIN: usize = 4;
+    pub const CONF_DIO_REDUN: usize = 5;
+    pub const CONF_MAX_RANK_INCR: Field = 6..8;
+    pub const CONF_MIN_HOP_RANK_INCR: Field = 8..10;
+    pub const CONF_OCP: Field = 10..12;
+    pub const CONF_RESERVED: usize = 12;
+    pub const CONF_DEF_LIFETIME: usize = 13;
+    pub const CONF_LIFETIME_UNIT: Field = 14..16;
+    pub const CONF_LEN: u8 = 14;
+
+    // RPL Target option: flags, prefix length and a prefix of variable length.
+    pub const TARGET_FLAGS: usize = 2;
+    pub const TARGET_PREFIX_LEN: usize = 3;
+    pub const TARGET_PREFIX: usize = 4;
+
+    // Transit Information option, of 4 octets, or 20 with a parent address.
+    pub const TRANSIT_FLAGS: usize = 2;
+    pub const TRANSIT_PATH_CONTROL: usize = 3;
+    pub const TRANSIT_PATH_SEQ: usize = 4;
+    pub const TRANSIT_PATH_LIFETIME: usize = 5;
+    pub const TRANSIT_PARENT: Field = 6..22;
+    pub const TRANSIT_LEN: u8 = 4;
+    pub const TRANSIT_PARENT_LEN: u8 = 20;
+
+    // Solicited Information option, of 19 octets.
+    pub const SOLICITED_INSTANCE_ID: usize = 2;
+    pub const SOLICITED_FLAGS: usize = 3;
+    pub const SOLICITED_DODAG_ID: Field = 4..20;
+    pub const SOLICITED_VERSION: usize = 20;
+    pub const SOLICITED_LEN: u8 = 19;
+
+    pub const CONF_FLG_A: u8 = 0x08;
+    pub const TRANSIT_FLG_E: u8 = 0x80;
+    pub const SOLICITED_FLG_V: u8 = 0x80;
+    pub const SOLICITED_FLG_I: u8 = 0x40;
+    pub const SOLICITED_FLG_D: u8 = 0x20;
+}
+
+impl<T: AsRef<[u8]>> RplOption<T> {
+    /// Create a raw octet buffer with an RPL option structure.
+    pub const fn new_unchecked(buffer: T) -> RplOption<T> {
+        RplOption { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<RplOption<T>> {
+        let opt = Self::new_unchecked(buffer);
+        opt.check_len()?;
+        Ok(opt)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short for the option.
+    pub fn check_len(&self) -> Result<()> {
+        let data = self.buffer.as_ref();
+        if data.is_empty() {
+            return Err(Error);
+        }
+        if self.option_type() == OptionType::Pad1 {
+            return Ok(());
+        }
+        if data.len() <= opt_field::LENGTH
+            || data.len() < opt_field::DATA(data[opt_field::LENGTH]).end
+        {
+            return Err(Error);
+        }
+        Ok(())
+    }
+
+    /// Return the option type.
+    #[inline]
+    pub fn option_type(&self) -> OptionType {
+        OptionType::from(self.buffer.as_ref()[opt_field::TYPE])
+    }
+
+    /// Return the length of the option data.
+    ///
+    /// # Panics
+    /// This function panics if this is a Pad1 option.
+    #[inline]
+    pub fn data_len(&self) -> u8 {
+        self.buffer.as_ref()[opt_field::LENGTH]
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> RplOption<&'a T> {
+    /// Return the option data.
+    ///
+    /// # Panics
+    /// This function panics if this is a Pad1 option.
+    #[inline]
+    pub fn data(&self) -> &'a [u8] {
+        let len = self.data_len();
+        &self.buffer.as_ref()[opt_field::DATA(len)]
+    }
+}
+
+/// The parameters of a DODAG, distributed in DIO messages, see [RFC 6550 § 6.7.6].
+///
+/// [RFC 6550 § 6.7.6]: https://tools.ietf.org/html/rfc6550#section-6.7.6
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DodagConfiguration {
+    pub authentication_enabled: bool,
+    pub path_control_size: u8,
+    /// The number of times the DIO interval doubles, `DIOIntervalDoublings`.
+    pub dio_interval_doublings: u8,
+    /// The shortest DIO interval is `2 ^ dio_interval_min` milliseconds.
+    pub dio_interval_min: u8,
+    /// The number of consistent DIOs that suppress sending one, `DIORedundancyConstant`.
+    pub dio_redundancy_constant: u8,
+    pub max_rank_increase: u16,
+    pub min_hop_rank_increase: u16,
+    /// The objective function, 0 for [RFC 6552].
+    ///
+    /// [RFC 6552]: https://tools.ietf.org/html/rfc6552
+    pub objective_code_point: u16,
+    /// The lifetime of routes, in units of `lifetime_unit` seconds.
+    pub default_lifetime: u8,
+    pub lifetime_unit: u16,
+}
+
+impl Default for DodagConfiguration {
+    /// The default values of [RFC 6550 § 17].
+    ///
+    /// [RFC 6550 § 17]: https://tools.ietf.org/html/rfc6550#section-17
+    fn default() -> Self {
+        DodagConfiguration {
+            authentication_enabled: false,
+            path_control_size: 0,
+            dio_interval_doublings: 20,
+            dio_interval_min: 3,
+            dio_redundancy_constant: 10,
+            max_rank_increase: 0,
+            min_hop_rank_increase: 256,
+            objective_code_point: 0,
+            default_lifetime: 0xff,
+            lifetime_unit: 0xffff,
+        }
+    }
+}
+
+/// The route to the targets preceding it in a DAO message, see [RFC 6550 § 6.7.8].
+///
+/// [RFC 6550 § 6.7.8]: https://tools.ietf.org/html/rfc6550#section-6.7.8
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TransitInformation {
+    /// Whether the targets are outside of the RPL domain.
+    pub external: bool,
+    pub path_control: u8,
+    pub path_sequence: u8,
+    /// The lifetime of the route, in units of the lifetime unit of the DODAG. A
+    /// lifetime of zero removes the route.
+    pub path_lifetime: u8,
+    /// The parent of the advertising node, only sent in non-storing mode.
+    pub parent_address: Option<Ipv6Address>,
+}
+
+/// The DODAGs that should answer a DIS message, see [RFC 6550 § 6.7.9].
+///
+/// [RFC 6550 § 6.7.9]: https://tools.ietf.org/html/rfc6550#section-6.7.9
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SolicitedInformation {
+    pub rpl_instance_id: Option<u8>,
+    pub dodag_id: Option<Ipv6Address>,
+    pub version_number: Option<u8>,
+}
+
+/// A high-level representation of an RPL control message option.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OptionRepr<'a> {
+    Pad1,
+    PadN(u8),
+    DodagConfiguration(DodagConfiguration),
+    /// A destination reachable through the node sending a DAO message.
+    RplTarget(Ipv6Cidr),
+    TransitInformation(TransitInformation),
+    SolicitedInformation(SolicitedInformation),
+    Unknown {
+        type_: OptionType,
+        length: u8,
+        data: &'a [u8],
+    },
+}
+
+impl<'a> OptionRepr<'a> {
+    /// Parse an RPL control message option and return a high-level representation.
+    pub fn parse<T>(opt: &RplOption<&'a T>) -> Result<OptionRepr<'a>>
+    where
+        T: AsRef<[u8]> + ?Sized,
+    {
+        let data = opt.buffer.as_ref();
+        match opt.option_type() {
+            OptionType::Pad1 => Ok(OptionRepr::Pad1),
+            OptionType::PadN => Ok(OptionRepr::PadN(opt.data_len())),
+            OptionType::DodagConfiguration => {
+                if opt.data_len() != opt_field::CONF_LEN {
+                    return Err(Error);
+                }
+                let flags = data[opt_field::CONF_FLAGS_A_PCS];
+                Ok(OptionRepr::DodagConfiguration(DodagConfiguration {
+                    authentication_enabled: flags & opt_field::CONF_FLG_A != 0,
+                    path_control_size: flags & 0x07,
+                    dio_interval_doublings: data[opt_field::CONF_DIO_INT_DOUBL],
+                    dio_interval_min: data[opt_field::CONF_DIO_INT_MIN],
+                    dio_redundancy_constant: data[opt_field::CONF_DIO_REDUN],
+                    max_rank_increase: NetworkEndian::read_u16(
+                        &data[opt_field::CONF_MAX_RANK_INCR],
+                    ),
+                    min_hop_rank_increase: NetworkEndian::read_u16(
+                        &data[opt_field::CONF_MIN_HOP_RANK_INCR],
+                    ),
+                    objective_code_point: NetworkEndian::read_u16(&data[opt_field::CONF_OCP]),
+                    default_lifetime: data[opt_field::CONF_DEF_LIFETIME],
+                    lifetime_unit: NetworkEndian::read_u16(&data[opt_field::CONF_LIFETIME_UNIT]),
+                }))
+            }
+            OptionType::RplTarget => {
+                if opt.data_len() < 2 {
+                    return Err(Error);
+                }
+                let prefix_len = data[opt_field::TARGET_PREFIX_LEN];
+                let prefix = &opt.data()[2..];
+                if prefix_len > 128 || prefix.len() < (prefix_len as usize + 7) / 8 {
+                    return Err(Error);
+                }
+                let mut bytes = [0; 16];
+                let len = prefix.len().min(16);
+                bytes[..len].copy_from_slice(&prefix[..len]);
+                let addr = Ipv6Address(Ipv6Address(bytes).mask(prefix_len));
+                Ok(OptionRepr::RplTarget(Ipv6Cidr::new(addr, prefix_len)))
+            }
+            OptionType::TransitInformation => {
+                let parent_address = match opt.data_len() {
+                    opt_field::TRANSIT_LEN => None,
+                    opt_field::TRANSIT_PARENT_LEN => {
+                        Some(Ipv6Address::from_bytes(&data[opt_field::TRANSIT_PARENT]))
+                    }
+                    _ => return Err(Error),
+                };
+                Ok(OptionRepr::TransitInformation(TransitInformation {
+                    external: data[opt_field::TRANSIT_FLAGS] & opt_field::TRANSIT_FLG_E != 0,
+                    path_control: data[opt_field::TRANSIT_PATH_CONTROL],
+                    path_sequence: data[opt_field::TRANSIT_PATH_SEQ],
+                    path_lifetime: data[opt_field::TRANSIT_PATH_LIFETIME],
+                    parent_address,
+                }))
+            }
+            OptionType::SolicitedInformation => {
+                if opt.data_len() != opt_field::SOLICITED_LEN {
+                    return Err(Error);
+                }
+                let flags = data[opt_field::SOLICITED_FLAGS];
+                Ok(OptionRepr::SolicitedInformation(SolicitedInformation {
+                    rpl_instance_id: (flags & opt_field::SOLICITED_FLG_I != 0)
+                        .then(|| data[opt_field::SOLICITED_INSTANCE_ID]),
+                    dodag_id: (flags & opt_field::SOLICITED_FLG_D != 0)
+                        .then(|| Ipv6Address::from_bytes(&data[opt_field::SOLICITED_DODAG_ID])),
+                    version_number: (flags & opt_field::SOLICITED_FLG_V != 0)
+                        .then(|| data[opt_field::SOLICITED_VERSION]),
+                }))
+            }
+            type_ => Ok(OptionRepr::Unknown {
+                type_,
+                length: opt.data_len(),
+                data: opt.data(),
+            }),
+        }
+    }
+
+    /// Return the length of an option that will be emitted from this high-level
+    /// representation.
+    pub const fn buffer_len(&self) -> usize {
+        match *self {
+            OptionRepr::Pad1 => 1,
+            OptionRepr::PadN(length) => opt_field::DATA(length).end,
+            OptionRepr::DodagConfiguration(_) => opt_field::DATA(opt_field::CONF_LEN).end,
+            OptionRepr::RplTarget(prefix) => {
+                opt_field::TARGET_PREFIX + (prefix.prefix_len() as usize + 7) / 8
+            }
+            OptionRepr::TransitInformation(TransitInformation {
+                parent_address: None,
+                ..
+            }) => opt_field::DATA(opt_field::TRANSIT_LEN).end,
+            OptionRepr::TransitInformation(_) => opt_field::DATA(opt_field::TRANSIT_PARENT_LEN).end,
+            OptionRepr::SolicitedInformation(_) => opt_field::DATA(opt_field::SOLICITED_LEN).end,
+            OptionRepr::Unknown { length, .. } => opt_field::DATA(length).end,
+        }
+    }
+
+    /// Emit a high-level representation into an RPL control message option.
+    pub fn emit<T>(&self, opt: &mut RplOption<&mut T>)
+    where
+        T: AsRef<[u8]> + AsMut<[u8]> + ?Sized,
+    {
+        let len = self.buffer_len();
+        let data = &mut opt.buffer.as_mut()[..len];
+        data.fill(0);
+        if let OptionRepr::Pad1 = self {
+            return;
+        }
+        data[opt_field::LENGTH] = (len - 2) as u8;
+        let type_ = match *self {
+            OptionRepr::Pad1 => unreachable!(),
+            OptionRepr::PadN(_) => OptionType::PadN,
+            OptionRepr::DodagConfiguration(conf) => {
+                let a = if conf.authentication_enabled {
+                    opt_field::CONF_FLG_A
+                } else {
+                    0
+                };
+                data[opt_field::CONF_FLAGS_A_PCS] = a | conf.path_control_size & 0x07;
+                data[opt_field::CONF_DIO_INT_DOUBL] = conf.dio_interval_doublings;
+                data[opt_field::CONF_DIO_INT_MIN] = conf.dio_interval_min;
+                data[opt_field::CONF_DIO_REDUN] = conf.dio_redundancy_constant;
+                NetworkEndian::write_u16(
+                    &mut data[opt_field::CONF_MAX_RANK_INCR],
+                    conf.max_rank_increase,
+                );
+                NetworkEndian::write_u16(
+                    &mut data[opt_field::CONF_MIN_HOP_RANK_INCR],
+                    conf.min_hop_rank_increase,
+                );
+                NetworkEndian::write_u16(&mut data[opt_field::CONF_OCP], conf.objective_code_point);
+                data[opt_field::CONF_RESERVED] = 0;
+                data[opt_field::CONF_DEF_LIFETIME] = conf.default_lifetime;
+                NetworkEndian::write_u16(
+                    &mut data[opt_field::CONF_LIFETIME_UNIT],
+                    conf.lifetime_unit,
+                );
+                OptionType::DodagConfiguration
+            }
+            OptionRepr::RplTarget(prefix) => {
+                data[opt_field::TARGET_FLAGS] = 0;
+                data[opt_field::TARGET_PREFIX_LEN] = prefix.prefix_len();
+                let addr = prefix.address().mask(prefix.prefix_len());
+                let len = len - opt_field::TARGET_PREFIX;
+                data[opt_field::TARGET_PREFIX..].copy_from_slice(&addr[..len]);
+                OptionType::RplTarget
+            }
+            OptionRepr::TransitInformation(transit) => {
+                data[opt_field::TRANSIT_FLAGS] = if transit.external {
+                    opt_field::TRANSIT_FLG_E
+                } else {
+                    0
+                };
+                data[opt_field::TRANSIT_PATH_CONTROL] = transit.path_control;
+                data[opt_field::TRANSIT_PATH_SEQ] = transit.path_sequence;
+                data[opt_field::TRANSIT_PATH_LIFETIME] = transit.path_lifetime;
+                if let Some(parent) = transit.parent_address {
+                    data[opt_field::TRANSIT_PARENT].copy_from_slice(parent.as_bytes());
+                }
+                OptionType::TransitInformation
+            }
+            OptionRepr::SolicitedInformation(info) => {
+                let mut flags = 0;
+                if let Some(id) = info.rpl_instance_id {
+                    flags |= opt_field::SOLICITED_FLG_I;
+                    data[opt_field::SOLICITED_INSTANCE_ID] = id;
+                }
+                if let Some(dodag_id) = info.dodag_id {
+                    flags |= opt_field::SOLICITED_FLG_D;
+                    data[opt_field::SOLICITED_DODAG_ID].copy_from_slice(dodag_id.as_bytes());
+                }
+                if let Some(version) = info.version_number {
+                    flags |= opt_field::SOLICITED_FLG_V;
+                    data[opt_field::SOLICITED_VERSION] = version;
+                }
+                data[opt_field::SOLICITED_FLAGS] = flags;
+                OptionType::SolicitedInformation
+            }
+            OptionRepr::Unknown {
+                type_,
+                length,
+                data: payload,
+            } => {
+                data[opt_field::DATA(length)].copy_from_slice(&payload[..length as usize]);
+                type_
+            }
+        };
+        data[opt_field::TYPE] = type_.into();
+    }
+}
+
+impl<'a> fmt::Display for OptionRepr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OptionRepr::Pad1 => write!(f, "{}", OptionType::Pad1),
+            OptionRepr::PadN(len) => write!(f, "{} length={}", OptionType::PadN, len),
+            OptionRepr::DodagConfiguration(conf) => write!(
+                f,
+                "{} min-hop-rank-increase={} lifetime={}x{}s",
+                OptionType::DodagConfiguration,
+                conf.min_hop_rank_increase,
+                conf.default_lifetime,
+                conf.lifetime_unit
+            ),
+            OptionRepr::RplTarget(prefix) => write!(f, "{} {}", OptionType::RplTarget, prefix),
+            OptionRepr::TransitInformation(transit) => write!(
+                f,
+                "{} path-lifetime={}",
+                OptionType::TransitInformation,
+                transit.path_lifetime
+            ),
+            OptionRepr::SolicitedInformation(_) => {
+                write!(f, "{}", OptionType::SolicitedInformation)
+            }
+            OptionRepr::Unknown { type_, length, .. } => write!(f, "{type_} length={length}"),
+        }
+    }
+}
+
+/// An iterator over the options of an RPL control message.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OptionsIterator<'a> {
+    data: &'a [u8],
+    hit_error: bool,
+}
+
+impl<'a> OptionsIterator<'a> {
+    /// Create an iterator over the options in `data`.
+    pub const fn new(data: &'a [u8]) -> OptionsIterator<'a> {
+        OptionsIterator {
+            data,
+            hit_error: false,
+        }
+    }
+}
+
+impl<'a> Iterator for OptionsIterator<'a> {
+    type Item = Result<OptionRepr<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() || self.hit_error {
+            return None;
+        }
+        match RplOption::new_checked(self.data).and_then(|opt| OptionRepr::parse(&opt)) {
+            Ok(repr) => {
+                // Options are parsed from the length field, so their representation
+                // takes the same room.
+                let len = match repr {
+                    OptionRepr::Pad1 => 1,
+                    _ => opt_field::DATA(self.data[opt_field::LENGTH]).end,
+                };
+                self.data = &self.data[len..];
+                Some(Ok(repr))
+            }
+            Err(e) => {
+                self.hit_error = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// A high-level representation of an RPL control message.
+///
+/// The options are kept in their wire format, see [`Repr::options`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Repr<'a> {
+    DodagInformationSolicitation {
+        options: &'a [u8],
+    },
+    DodagInformationObject {
+        rpl_instance_id: u8,
+        version_number: u8,
+        rank: u16,
+        grounded: bool,
+        mode_of_operation: ModeOfOperation,
+        dodag_preference: u8,
+        dtsn: u8,
+        dodag_id: Ipv6Address,
+        options: &'a [u8],
+    },
+    DestinationAdvertisementObject {
+        rpl_instance_id: u8,
+        expect_ack: bool,
+        sequence: u8,
+        dodag_id: Option<Ipv6Address>,
+        options: &'a [u8],
+    },
+    DestinationAdvertisementObjectAck {
+        rpl_instance_id: u8,
+        sequence: u8,
+        status: u8,
+        dodag_id: Option<Ipv6Address>,
+        options: &'a [u8],
+    },
+}
+
+impl<'a> Repr<'a> {
+    /// Parse an RPL control message and return a high-level representation.
+    pub fn parse<T>(packet: &Packet<&'a T>) -> Result<Repr<'a>>
+    where
+        T: AsRef<[u8]> + ?Sized,
+    {
+        let data = packet.buffer.as_ref();
+        if packet.msg_type() != Message::RplControl {
+            return Err(Error);
+        }
+        let header_len = match Code::from(packet.msg_code()) {
+            Code::DodagInformationSolicitation => field::DIS_OPTIONS,
+            Code::DodagInformationObject => field::DIO_OPTIONS,
+            Code::DestinationAdvertisementObject | Code::DestinationAdvertisementObjectAck => {
+                field::OPTIONS
+            }
+            Code::Unknown(_) => return Err(Error),
+        };
+        if data.len() < header_len {
+            return Err(Error);
+        }
+        // The DODAGID of a DAO or DAO-ACK must be there when the D flag says so.
+        if packet.rpl_dao_dodag_id_present() && data.len() < field::DODAG_ID.end {
+            return Err(Error);
+        }
+        let options = &data[packet.rpl_options_offset()..];
+        // Reject messages whose options are truncated.
+        for opt in OptionsIterator::new(options) {
+            opt?;
+        }
+
+        match Code::from(packet.msg_code()) {
+            Code::DodagInformationSolicitation => {
+                Ok(Repr::DodagInformationSolicitation { options })
+            }
+            Code::DodagInformationObject => Ok(Repr::DodagInformationObject {
+                rpl_instance_id: packet.rpl_instance_id(),
+                version_number: packet.rpl_dio_version(),
+                rank: packet.rpl_dio_rank(),
+                grounded: packet.rpl_dio_grounded(),
+                mode_of_operation: packet.rpl_dio_mode_of_operation(),
+                dodag_preference: packet.rpl_dio_preference(),
+                dtsn: packet.rpl_dio_dtsn(),
+                dodag_id: packet.rpl_dio_dodag_id(),
+                options,
+            }),
+            Code::DestinationAdvertisementObject => Ok(Repr::DestinationAdvertisementObject {
+                rpl_instance_id: packet.rpl_instance_id(),
+                expect_ack: packet.rpl_dao_expect_ack(),
+                sequence: packet.rpl_dao_sequence(),
+                dodag_id: packet.rpl_dao_dodag_id(),
+                options,
+            }),
+            Code::DestinationAdvertisementObjectAck => {
+                Ok(Repr::DestinationAdvertisementObjectAck {
+                    rpl_instance_id: packet.rpl_instance_id(),
+                    sequence: packet.rpl_dao_sequence(),
+                    status: packet.rpl_dao_ack_status(),
+                    dodag_id: packet.rpl_dao_dodag_id(),
+                    options,
+                })
+            }
+            Code::Unknown(_) => unreachable!(),
+        }
+    }
+
+    /// Return an iterator over the options of the message.
+    pub fn options(&self) -> OptionsIterator<'a> {
+        match *self {
+            Repr::DodagInformationSolicitation { options }
+            | Repr::DodagInformationObject { options, .. }
+            | Repr::DestinationAdvertisementObject { options, .. }
+            | Repr::DestinationAdvertisementObjectAck { options, .. } => {
+                OptionsIterator::new(options)
+            }
+        }
+    }
+
+    /// Return the length of a packet that will be emitted from this high-level
+    /// representation.
+    pub const fn buffer_len(&self) -> usize {
+        match *self {
+            Repr::DodagInformationSolicitation { options } => field::DIS_OPTIONS + options.len(),
+            Repr::DodagInformationObject { options, .. } => field::DIO_OPTIONS + options.len(),
+            Repr::DestinationAdvertisementObject {
+                dodag_id, options, ..
+            }
+            | Repr::DestinationAdvertisementObjectAck {
+                dodag_id, options, ..
+            } => match dodag_id {
+                Some(_) => field::DODAG_ID.end + options.len(),
+                None => field::OPTIONS + options.len(),
+            },
+        }
+    }
+
+    /// Emit a high-level representation into an RPL control message.
+    pub fn emit<T>(&self, packet: &mut Packet<&mut T>)
+    where
+        T: AsRef<[u8]> + AsMut<[u8]> + ?Sized,
+    {
+        packet.set_msg_type(Message::RplControl);
+        let (code, options) = match *self {
+            Repr::DodagInformationSolicitation { options } => {
+                (Code::DodagInformationSolicitation, options)
+            }
+            Repr::DodagInformationObject {
+                rpl_instance_id,
+                version_number,
+                rank,
+                grounded,
+                mode_of_operation,
+                dodag_preference,
+                dtsn,
+                dodag_id,
+                options,
+            } => {
+                packet.set_rpl_instance_id(rpl_instance_id);
+                packet.set_rpl_dio_version(version_number);
+                packet.set_rpl_dio_rank(rank);
+                packet.set_rpl_dio_g_mop_prf(grounded, mode_of_operation, dodag_preference);
+                packet.set_rpl_dio_dtsn(dtsn);
+                packet.set_rpl_dio_dodag_id(dodag_id);
+                (Code::DodagInformationObject, options)
+            }
+            Repr::DestinationAdvertisementObject {
+                rpl_instance_id,
+                expect_ack,
+                sequence,
+                dodag_id,
+                options,
+            } => {
+                packet.set_rpl_instance_id(rpl_instance_id);
+                packet.set_rpl_dao_flags_sequence(expect_ack, dodag_id.is_some(), sequence);
+                if let Some(dodag_id) = dodag_id {
+                    packet.set_rpl_dao_dodag_id(dodag_id);
+                }
+                (Code::DestinationAdvertisementObject, options)
+            }
+            Repr::DestinationAdvertisementObjectAck {
+                rpl_instance_id,
+                sequence,
+                status,
+                dodag_id,
+                options,
+            } => {
+                packet.set_rpl_instance_id(rpl_instance_id);
+                packet.set_rpl_dao_ack_flags_sequence(dodag_id.is_some(), sequence, status);
+                if let Some(dodag_id) = dodag_id {
+                    packet.set_rpl_dao_dodag_id(dodag_id);
+                }
+                (Code::DestinationAdvertisementObjectAck, options)
+            }
+        };
+        packet.set_msg_code(code.into());
+        packet.clear_rpl_reserved();
+        let offset = packet.rpl_options_offset();
+        packet.buffer.as_mut()[offset..offset + options.len()].copy_from_slice(options);
+    }
+}
+
+impl<'a> fmt::Display for Repr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Repr::DodagInformationSolicitation { .. } => write!(f, "RPL DIS"),
+            Repr::DodagInformationObject {
+                rpl_instance_id,
+                version_number,
+                rank,
+                dodag_id,
+                ..
+            } => write!(
+                f,
+                "RPL DIO instance={rpl_instance_id} dodag={dodag_id} version={version_number} rank={rank}"
+            ),
+            Repr::DestinationAdvertisementObject {
+                rpl_instance_id,
+                sequence,
+                ..
+            } => write!(f, "RPL DAO instance={rpl_instance_id} seq={sequence}"),
+            Repr::DestinationAdvertisementObjectAck {
+                rpl_instance_id,
+                sequence,
+                status,
+                ..
+            } => write!(
+                f,
+                "RPL DAO-ACK instance={rpl_instance_id} seq={sequence} status={status}"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::phy::ChecksumCapabilities;
+    use crate::wire::{Icmpv6Packet, Icmpv6Repr};
+
+    const SRC_ADDR: Ipv6Address =
+        Ipv6Address([0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01]);
+    const DST_ADDR: Ipv6Address =
+        Ipv6Address([0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x1a]);
+    const DODAG_ID: Ipv6Address =
+        Ipv6Address([0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01]);
+
+    static DIO_BYTES: [u8; 44] = [
+        0x9b, 0x01, 0x00, 0x00, 0x1e, 0xf0, 0x01, 0x00, 0x90, 0x05, 0x00, 0x00, 0xfd, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x04, 0x0e,
+        0x00, 0x08, 0x0c, 0x0a, 0x07, 0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x1e, 0x00, 0x3c,
+    ];
+
+    fn dio_options() -> [u8; 16] {
+        let mut options = [0; 16];
+        OptionRepr::DodagConfiguration(DodagConfiguration {
+            dio_interval_doublings: 8,
+            dio_interval_min: 12,
+            dio_redundancy_constant: 10,
+            max_rank_increase: 1792,
+            min_hop_rank_increase: 256,
+            objective_code_point: 1,
+            default_lifetime: 30,
+            lifetime_unit: 60,
+            ..DodagConfiguration::default()
+        })
+        .emit(&mut RplOption::new_unchecked(&mut options[..]));
+        options
+    }
+
+    #[test]
+    fn test_dio_deconstruct() {
+        let mut bytes = DIO_BYTES;
+        // Fill in the checksum.
+        Icmpv6Packet::new_unchecked(&mut bytes[..])
+            .fill_checksum(&SRC_ADDR.into(), &DST_ADDR.into());
+        let packet = Icmpv6Packet::new_checked(&bytes[..]).unwrap();
+        let repr = Icmpv6Repr::parse(
+            &SRC_ADDR.into(),
+            &DST_ADDR.into(),
+            &packet,
+            &ChecksumCapabilities::default(),
+        )
+        .unwrap();
+        let options = dio_options();
+        let expected = Repr::DodagInformationObject {
+            rpl_instance_id: 0x1e,
+            version_number: 0xf0,
+            rank: 256,
+            grounded: true,
+            mode_of_operation: ModeOfOperation::StoringWithoutMulticast,
+            dodag_preference: 0,
+            dtsn: 5,
+            dodag_id: DODAG_ID,
+            options: &options,
+        };
+        assert_eq!(repr, Icmpv6Repr::Rpl(expected));
+
+        let mut buffer = [0xff; 44];
+        assert_eq!(expected.buffer_len(), 44);
+        let mut packet = Icmpv6Packet::new_unchecked(&mut buffer[..]);
+        Icmpv6Repr::Rpl(expected).emit(
+            &SRC_ADDR.into(),
+            &DST_ADDR.into(),
+            &mut packet,
+            &ChecksumCapabilities::default(),
+        );
+        assert_eq!(buffer, bytes);
+    }
+
+    #[test]
+    fn test_dao_repr() {
+        let mut options = [0; 32];
+        let target = OptionRepr::RplTarget(Ipv6Cidr::new(DODAG_ID, 128));
+        let transit = OptionRepr::TransitInformation(TransitInformation {
+            external: false,
+            path_control: 0,
+            path_sequence: 1,
+            path_lifetime: 30,
+            parent_address: None,
+        });
+        target.emit(&mut RplOption::new_unchecked(&mut options[..]));
+        let len = target.buffer_len();
+        transit.emit(&mut RplOption::new_unchecked(&mut options[len..]));
+        let len = len + transit.buffer_len();
+        assert_eq!(len, 26);
+
+        let repr = Repr::DestinationAdvertisementObject {
+            rpl_instance_id: 0x1e,
+            expect_ack: true,
+            sequence: 7,
+            dodag_id: Some(DODAG_ID),
+            options: &options[..len],
+        };
+        let mut buffer = vec![0; repr.buffer_len()];
+        repr.emit(&mut Icmpv6Packet::new_unchecked(&mut buffer[..]));
+        assert_eq!(&buffer[..8], &[0x9b, 0x02, 0, 0, 0x1e, 0xc0, 0x00, 0x07]);
+
+        let packet = Icmpv6Packet::new_unchecked(&buffer[..]);
+        let parsed = Repr::parse(&packet).unwrap();
+        assert_eq!(parsed, repr);
+        let mut options = parsed.options();
+        assert_eq!(options.next(), Some(Ok(target)));
+        assert_eq!(options.next(), Some(Ok(transit)));
+        assert_eq!(options.next(), None);
+    }
+
+    #[test]
+    fn test_truncated_dao() {
+        // The D flag is set but the DODAGID is missing.
+        let buffer = [0x9b, 0x02, 0, 0, 0, 0x40, 0, 0, 0, 0, 0, 0];
+        let packet = Icmpv6Packet::new_unchecked(&buffer[..]);
+        assert_eq!(Repr::parse(&packet), Err(Error));
+        assert_eq!(
+            Icmpv6Repr::parse(
+                &SRC_ADDR.into(),
+                &DST_ADDR.into(),
+                &packet,
+                &ChecksumCapabilities::ignored()
+            ),
+            Err(Error)
+        );
+    }
+
+    #[test]
+    fn test_truncated_dao_ack() {
+        // The D flag is set but the DODAGID is missing.
+        let buffer = [0x9b, 0x03, 0, 0, 0, 0x80, 0, 0, 0, 0, 0, 0];
+        let packet = Icmpv6Packet::new_unchecked(&buffer[..]);
+        assert_eq!(Repr::parse(&packet), Err(Error));
+        assert_eq!(
+            Icmpv6Repr::parse(
+                &SRC_ADDR.into(),
+                &DST_ADDR.into(),
+                &packet,
+                &ChecksumCapabilities::ignored()
+            ),
+            Err(Error)
+        );
+    }
+
+    #[test]
+    fn test_truncated_options() {
+        let mut buffer = [0x9b, 0x00, 0, 0, 0, 0, 0x04, 0x0e, 0x00];
+        assert_eq!(
+            Repr::parse(&Icmpv6Packet::new_unchecked(&buffer[..])),
+            Err(Error)
+        );
+        // Padding is accepted.
+        buffer[6..].copy_from_slice(&[0x00, 0x01, 0x00]);
+        assert_eq!(
+            Repr::parse(&Icmpv6Packet::new_unchecked(&buffer[..])),
+            Ok(Repr::DodagInformationSolicitation {
+                options: &[0x00, 0x01, 0x00],
+            })
+        );
+    }
+}
@@ -0,0 +1,1095 @@
+use byteorder::{ByteOrder, LittleEndian, NetworkEndian};
+use core::fmt;
+
+use super::{Error, Result};
+use crate::phy::ChecksumCapabilities;
+
+enum_with_unknown! {
+    /// SCTP chunk type.
+    pub enum ChunkType(u8) {
+        Data             = 0,
+        Init             = 1,
+        InitAck          = 2,
+        Sack             = 3,
+        Heartbeat        = 4,
+        HeartbeatAck     = 5,
+        Abort            = 6,
+        Shutdown         = 7,
+        ShutdownAck      = 8,
+        Error            = 9,
+        CookieEcho       = 10,
+        CookieAck        = 11,
+        ShutdownComplete = 14
+    }
+}
+
+impl fmt::Display for ChunkType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChunkType::Data => write!(f, "DATA"),
+            ChunkType::Init => write!(f, "INIT"),
+            ChunkType::InitAck => write!(f, "INIT ACK"),
+            ChunkType::Sack => write!(f, "SACK"),
+            ChunkType::Heartbeat => write!(f, "HEARTBEAT"),
+            ChunkType::HeartbeatAck => write!(f, "HEARTBEAT ACK"),
+            ChunkType::Abort => write!(f, "ABORT"),
+            ChunkType::Shutdown => write!(f, "SHUTDOWN"),
+            ChunkType::ShutdownAck => write!(f, "SHUTDOWN ACK"),
+            ChunkType::Error => write!(f, "ERROR"),
+            ChunkType::CookieEcho => write!(f, "COOKIE ECHO"),
+            ChunkType::CookieAck => write!(f, "COOKIE ACK"),
+            ChunkType::ShutdownComplete => write!(f, "SHUTDOWN COMPLETE"),
+            ChunkType::Unknown(id) => write!(f, "0x{id:02x}"),
+        }
+    }
+}
+
+/// The CRC32c lookup table, for the reflected Castagnoli polynomial.
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Update a running CRC32c, before its final inversion, with the octets of a buffer.
+fn crc32c_update(crc: u32, data: &[u8]) -> u32 {
+    data.iter().fold(crc, |crc, &byte| {
+        CRC32C_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// A read/write wrapper around a Stream Control Transmission Protocol packet buffer.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+// Format of the SCTP common header, see https://www.rfc-editor.org/rfc/rfc9260#section-3.1
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Source Port Number        |     Destination Port Number   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                      Verification Tag                         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                           Checksum                            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+mod field {
+    #![allow(non_snake_case)]
+
+    use crate::wire::field::*;
+
+    pub const SRC_PORT: Field = 0..2;
+    pub const DST_PORT: Field = 2..4;
+    pub const VERIFICATION_TAG: Field = 4..8;
+    pub const CHECKSUM: Field = 8..12;
+
+    pub const fn CHUNKS(length: usize) -> Field {
+        CHECKSUM.end..length
+    }
+
+    // Chunk header, see https://www.rfc-editor.org/rfc/rfc9260#section-3.2
+    pub const CHUNK_TYPE: usize = 0;
+    pub const CHUNK_FLAGS: usize = 1;
+    pub const CHUNK_LENGTH: Field = 2..4;
+
+    // DATA chunk value.
+    pub const DATA_TSN: Field = 0..4;
+    pub const DATA_STREAM_ID: Field = 4..6;
+    pub const DATA_STREAM_SEQ: Field = 6..8;
+    pub const DATA_PPID: Field = 8..12;
+
+    pub const DATA_FLG_I: u8 = 0x08;
+    pub const DATA_FLG_U: u8 = 0x04;
+    pub const DATA_FLG_B: u8 = 0x02;
+    pub const DATA_FLG_E: u8 = 0x01;
+
+    // INIT and INIT ACK chunk values.
+    pub const INIT_TAG: Field = 0..4;
+    pub const INIT_A_RWND: Field = 4..8;
+    pub const INIT_OUTBOUND_STREAMS: Field = 8..10;
+    pub const INIT_INBOUND_STREAMS: Field = 10..12;
+    pub const INIT_TSN: Field = 12..16;
+
+    // SACK chunk value.
+    pub const SACK_CUMULATIVE_TSN: Field = 0..4;
+    pub const SACK_A_RWND: Field = 4..8;
+    pub const SACK_GAP_BLOCKS: Field = 8..10;
+    pub const SACK_DUP_TSNS: Field = 10..12;
+
+    // HEARTBEAT and HEARTBEAT ACK chunk values.
+    pub const HEARTBEAT_PARAM_TYPE: Field = 0..2;
+    pub const HEARTBEAT_PARAM_LENGTH: Field = 2..4;
+
+    pub const HEARTBEAT_INFO: u16 = 1;
+
+    // SHUTDOWN chunk value.
+    pub const SHUTDOWN_CUMULATIVE_TSN: Field = 0..4;
+
+    // ABORT and SHUTDOWN COMPLETE chunk flags.
+    pub const FLG_T: u8 = 0x01;
+}
+
+/// The length of the SCTP common header.
+pub const HEADER_LEN: usize = field::CHECKSUM.end;
+
+/// The length of an SCTP chunk header.
+pub const CHUNK_HEADER_LEN: usize = field::CHUNK_LENGTH.end;
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Imbue a raw octet buffer with SCTP packet structure.
+    pub const fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short.
+    pub fn check_len(&self) -> Result<()> {
+        if self.buffer.as_ref().len() < HEADER_LEN {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the source port field.
+    #[inline]
+    pub fn src_port(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::SRC_PORT])
+    }
+
+    /// Return the destination port field.
+    #[inline]
+    pub fn dst_port(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::DST_PORT])
+    }
+
+    /// Return the verification tag field.
+    #[inline]
+    pub fn verification_tag(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u32(&data[field::VERIFICATION_TAG])
+    }
+
+    /// Return the checksum field.
+    ///
+    /// The CRC32c is transmitted least significant octet first, and is returned as such.
+    #[inline]
+    pub fn checksum(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        LittleEndian::read_u32(&data[field::CHECKSUM])
+    }
+
+    /// Validate the packet checksum.
+    ///
+    /// # Fuzzing
+    /// This function always returns `true` when fuzzing.
+    pub fn verify_checksum(&self) -> bool {
+        if cfg!(fuzzing) {
+            return true;
+        }
+
+        self.checksum() == self.compute_checksum()
+    }
+
+    /// Compute the CRC32c of the packet, as if its checksum field were zero.
+    fn compute_checksum(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        let crc = crc32c_update(!0, &data[..field::CHECKSUM.start]);
+        let crc = crc32c_update(crc, &[0; 4]);
+        !crc32c_update(crc, &data[field::CHECKSUM.end..])
+    }
+
+    /// Return an iterator over the chunks of the packet.
+    pub fn chunks(&self) -> ChunkIterator<'_> {
+        let data = self.buffer.as_ref();
+        ChunkIterator::new(&data[field::CHUNKS(data.len())])
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
+    /// Return a pointer to the chunks.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[field::CHUNKS(data.len())]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the source port field.
+    #[inline]
+    pub fn set_src_port(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::SRC_PORT], value)
+    }
+
+    /// Set the destination port field.
+    #[inline]
+    pub fn set_dst_port(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::DST_PORT], value)
+    }
+
+    /// Set the verification tag field.
+    #[inline]
+    pub fn set_verification_tag(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::VERIFICATION_TAG], value)
+    }
+
+    /// Set the checksum field.
+    #[inline]
+    pub fn set_checksum(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        LittleEndian::write_u32(&mut data[field::CHECKSUM], value)
+    }
+
+    /// Compute and fill in the CRC32c checksum, over the whole buffer.
+    pub fn fill_checksum(&mut self) {
+        let checksum = self.compute_checksum();
+        self.set_checksum(checksum)
+    }
+
+    /// Return a mutable pointer to the chunks.
+    #[inline]
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        let length = data.len();
+        &mut data[field::CHUNKS(length)]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+/// A read/write wrapper around an SCTP chunk buffer.
+///
+/// The buffer spans the chunk as given by its length field, without the trailing padding.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Chunk<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+impl<T: AsRef<[u8]>> Chunk<T> {
+    /// Imbue a raw octet buffer with SCTP chunk structure.
+    pub const fn new_unchecked(buffer: T) -> Chunk<T> {
+        Chunk { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Chunk<T>> {
+        let chunk = Self::new_unchecked(buffer);
+        chunk.check_len()?;
+        Ok(chunk)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is shorter than the chunk length field.
+    ///
+    /// The result of this check is invalidated by calling [set_chunk_len].
+    ///
+    /// [set_chunk_len]: #method.set_chunk_len
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < CHUNK_HEADER_LEN || self.chunk_len() < CHUNK_HEADER_LEN || len < self.chunk_len() {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consume the chunk, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the chunk type field.
+    #[inline]
+    pub fn chunk_type(&self) -> ChunkType {
+        let data = self.buffer.as_ref();
+        ChunkType::from(data[field::CHUNK_TYPE])
+    }
+
+    /// Return the chunk flags field.
+    #[inline]
+    pub fn flags(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::CHUNK_FLAGS]
+    }
+
+    /// Return the chunk length field, which includes the chunk header but not the padding.
+    #[inline]
+    pub fn chunk_len(&self) -> usize {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::CHUNK_LENGTH]) as usize
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Chunk<&'a T> {
+    /// Return a pointer to the chunk value.
+    #[inline]
+    pub fn value(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[CHUNK_HEADER_LEN..self.chunk_len()]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Chunk<T> {
+    /// Set the chunk type field.
+    #[inline]
+    pub fn set_chunk_type(&mut self, value: ChunkType) {
+        let data = self.buffer.as_mut();
+        data[field::CHUNK_TYPE] = value.into()
+    }
+
+    /// Set the chunk flags field.
+    #[inline]
+    pub fn set_flags(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::CHUNK_FLAGS] = value
+    }
+
+    /// Set the chunk length field.
+    #[inline]
+    pub fn set_chunk_len(&mut self, value: usize) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::CHUNK_LENGTH], value as u16)
+    }
+
+    /// Return a mutable pointer to the chunk value.
+    #[inline]
+    pub fn value_mut(&mut self) -> &mut [u8] {
+        let chunk_len = self.chunk_len();
+        let data = self.buffer.as_mut();
+        &mut data[CHUNK_HEADER_LEN..chunk_len]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Chunk<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+/// An iterator over the chunks of an SCTP packet.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChunkIterator<'a> {
+    data: &'a [u8],
+    hit_error: bool,
+}
+
+impl<'a> ChunkIterator<'a> {
+    /// Create a new `ChunkIterator`, used to iterate over the chunks following the
+    /// SCTP common header.
+    pub const fn new(data: &'a [u8]) -> ChunkIterator<'a> {
+        ChunkIterator {
+            data,
+            hit_error: false,
+        }
+    }
+}
+
+impl<'a> Iterator for ChunkIterator<'a> {
+    type Item = Result<Chunk<&'a [u8]>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() || self.hit_error {
+            return None;
+        }
+        match Chunk::new_checked(self.data) {
+            Ok(chunk) => {
+                let chunk_len = chunk.chunk_len();
+                // The padding of the last chunk is sometimes left out, so tolerate that.
+                let padded_len = ((chunk_len + 3) & !3).min(self.data.len());
+                let (chunk, rest) = self.data.split_at(padded_len);
+                self.data = rest;
+                Some(Ok(Chunk::new_unchecked(&chunk[..chunk_len])))
+            }
+            Err(e) => {
+                self.hit_error = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// A high-level representation of an INIT or INIT ACK chunk.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InitRepr<'a> {
+    pub initiate_tag: u32,
+    pub a_rwnd: u32,
+    pub outbound_streams: u16,
+    pub inbound_streams: u16,
+    pub initial_tsn: u32,
+    /// The optional and variable-length parameters, such as the State Cookie of an INIT ACK.
+    pub params: &'a [u8],
+}
+
+/// A high-level representation of an SCTP chunk.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ChunkRepr<'a> {
+    Data {
+        immediate: bool,
+        unordered: bool,
+        beginning: bool,
+        ending: bool,
+        tsn: u32,
+        stream_id: u16,
+        stream_seq: u16,
+        ppid: u32,
+        data: &'a [u8],
+    },
+    Init(InitRepr<'a>),
+    InitAck(InitRepr<'a>),
+    Sack {
+        cumulative_tsn_ack: u32,
+        a_rwnd: u32,
+        /// The Gap Ack Blocks, as a sequence of 16-bit start and end offset pairs.
+        gap_blocks: &'a [u8],
+        /// The Duplicate TSNs, as a sequence of 32-bit TSNs.
+        duplicate_tsns: &'a [u8],
+    },
+    Heartbeat {
+        info: &'a [u8],
+    },
+    HeartbeatAck {
+        info: &'a [u8],
+    },
+    Abort {
+        tag_reflected: bool,
+        causes: &'a [u8],
+    },
+    Shutdown {
+        cumulative_tsn_ack: u32,
+    },
+    ShutdownAck,
+    Error {
+        causes: &'a [u8],
+    },
+    CookieEcho {
+        cookie: &'a [u8],
+    },
+    CookieAck,
+    ShutdownComplete {
+        tag_reflected: bool,
+    },
+}
+
+impl<'a> ChunkRepr<'a> {
+    /// Parse an SCTP chunk and return a high-level representation.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(chunk: &Chunk<&'a T>) -> Result<ChunkRepr<'a>> {
+        chunk.check_len()?;
+        let flags = chunk.flags();
+        let value = chunk.value();
+        match chunk.chunk_type() {
+            ChunkType::Data => {
+                // A DATA chunk without user data is a protocol violation.
+                if value.len() <= field::DATA_PPID.end {
+                    return Err(Error);
+                }
+                Ok(ChunkRepr::Data {
+                    immediate: flags & field::DATA_FLG_I != 0,
+                    unordered: flags & field::DATA_FLG_U != 0,
+                    beginning: flags & field::DATA_FLG_B != 0,
+                    ending: flags & field::DATA_FLG_E != 0,
+                    tsn: NetworkEndian::read_u32(&value[field::DATA_TSN]),
+                    stream_id: NetworkEndian::read_u16(&value[field::DATA_STREAM_ID]),
+                    stream_seq: NetworkEndian::read_u16(&value[field::DATA_STREAM_SEQ]),
+                    ppid: NetworkEndian::read_u32(&value[field::DATA_PPID]),
+                    data: &value[field::DATA_PPID.end..],
+                })
+            }
+            ChunkType::Init => Ok(ChunkRepr::Init(InitRepr::parse(value)?)),
+            ChunkType::InitAck => Ok(ChunkRepr::InitAck(InitRepr::parse(value)?)),
+            ChunkType::Sack => {
+                if value.len() < field::SACK_DUP_TSNS.end {
+                    return Err(Error);
+                }
+                let gap_blocks = NetworkEndian::read_u16(&value[field::SACK_GAP_BLOCKS]) as usize;
+                let dup_tsns = NetworkEndian::read_u16(&value[field::SACK_DUP_TSNS]) as usize;
+                let gap_end = field::SACK_DUP_TSNS.end + gap_blocks * 4;
+                if value.len() != gap_end + dup_tsns * 4 {
+                    return Err(Error);
+                }
+                Ok(ChunkRepr::Sack {
+                    cumulative_tsn_ack: NetworkEndian::read_u32(&value[field::SACK_CUMULATIVE_TSN]),
+                    a_rwnd: NetworkEndian::read_u32(&value[field::SACK_A_RWND]),
+                    gap_blocks: &value[field::SACK_DUP_TSNS.end..gap_end],
+                    duplicate_tsns: &value[gap_end..],
+                })
+            }
+            ChunkType::Heartbeat => Ok(ChunkRepr::Heartbeat {
+                info: parse_heartbeat_info(value)?,
+            }),
+            ChunkType::HeartbeatAck => Ok(ChunkRepr::HeartbeatAck {
+                info: parse_heartbeat_info(value)?,
+            }),
+            ChunkType::Abort => Ok(ChunkRepr::Abort {
+                tag_reflected: flags & field::FLG_T != 0,
+                causes: value,
+            }),
+            ChunkType::Shutdown => {
+                if value.len() != field::SHUTDOWN_CUMULATIVE_TSN.end {
+                    return Err(Error);
+                }
+                Ok(ChunkRepr::Shutdown {
+                    cumulative_tsn_ack: NetworkEndian::read_u32(
+                        &value[field::SHUTDOWN_CUMULATIVE_TSN],
+                    ),
+                })
+            }
+            ChunkType::ShutdownAck if value.is_empty() => Ok(ChunkRepr::ShutdownAck),
+            ChunkType::Error => Ok(ChunkRepr::Error { causes: value }),
+            ChunkType::CookieEcho => Ok(ChunkRepr::CookieEcho { cookie: value }),
+            ChunkType::CookieAck if value.is_empty() => Ok(ChunkRepr::CookieAck),
+            ChunkType::ShutdownComplete if value.is_empty() => Ok(ChunkRepr::ShutdownComplete {
+                tag_reflected: flags & field::FLG_T != 0,
+            }),
+            _ => Err(Error),
+        }
+    }
+
+    /// Return the type of the chunk.
+    pub const fn chunk_type(&self) -> ChunkType {
+        match self {
+            ChunkRepr::Data { .. } => ChunkType::Data,
+            ChunkRepr::Init(_) => ChunkType::Init,
+            ChunkRepr::InitAck(_) => ChunkType::InitAck,
+            ChunkRepr::Sack { .. } => ChunkType::Sack,
+            ChunkRepr::Heartbeat { .. } => ChunkType::Heartbeat,
+            ChunkRepr::HeartbeatAck { .. } => ChunkType::HeartbeatAck,
+            ChunkRepr::Abort { .. } => ChunkType::Abort,
+            ChunkRepr::Shutdown { .. } => ChunkType::Shutdown,
+            ChunkRepr::ShutdownAck => ChunkType::ShutdownAck,
+            ChunkRepr::Error { .. } => ChunkType::Error,
+            ChunkRepr::CookieEcho { .. } => ChunkType::CookieEcho,
+            ChunkRepr::CookieAck => ChunkType::CookieAck,
+            ChunkRepr::ShutdownComplete { .. } => ChunkType::ShutdownComplete,
+        }
+    }
+
+    /// Return the value of the chunk length field that will be emitted from this
+    /// high-level representation.
+    pub const fn chunk_len(&self) -> usize {
+        CHUNK_HEADER_LEN
+            + match self {
+                ChunkRepr::Data { data, .. } => field::DATA_PPID.end + data.len(),
+                ChunkRepr::Init(init) | ChunkRepr::InitAck(init) => {
+                    field::INIT_TSN.end + init.params.len()
+                }
+                ChunkRepr::Sack {
+                    gap_blocks,
+                    duplicate_tsns,
+                    ..
+                } => field::SACK_DUP_TSNS.end + gap_blocks.len() + duplicate_tsns.len(),
+                ChunkRepr::Heartbeat { info } | ChunkRepr::HeartbeatAck { info } => {
+                    field::HEARTBEAT_PARAM_LENGTH.end + info.len()
+                }
+                ChunkRepr::Abort { causes, .. } | ChunkRepr::Error { causes } => causes.len(),
+                ChunkRepr::Shutdown { .. } => field::SHUTDOWN_CUMULATIVE_TSN.end,
+                ChunkRepr::CookieEcho { cookie } => cookie.len(),
+                ChunkRepr::ShutdownAck
+                | ChunkRepr::CookieAck
+                | ChunkRepr::ShutdownComplete { .. } => 0,
+            }
+    }
+
+    /// Return the length of a chunk that will be emitted from this high-level representation,
+    /// including the padding to a multiple of 4 octets.
+    pub const fn buffer_len(&self) -> usize {
+        (self.chunk_len() + 3) & !3
+    }
+
+    /// Emit a high-level representation into an SCTP chunk, and zero its padding.
+    ///
+    /// # Panics
+    /// This function panics if the buffer is shorter than [buffer_len].
+    ///
+    /// [buffer_len]: #method.buffer_len
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]> + ?Sized>(&self, chunk: &mut Chunk<&mut T>) {
+        let chunk_len = self.chunk_len();
+        chunk.buffer.as_mut()[chunk_len..self.buffer_len()].fill(0);
+        chunk.set_chunk_type(self.chunk_type());
+        chunk.set_chunk_len(chunk_len);
+
+        let mut flags = 0;
+        let value = chunk.value_mut();
+        match *self {
+            ChunkRepr::Data {
+                immediate,
+                unordered,
+                beginning,
+                ending,
+                tsn,
+                stream_id,
+                stream_seq,
+                ppid,
+                data,
+            } => {
+                for (set, flag) in [
+                    (immediate, field::DATA_FLG_I),
+                    (unordered, field::DATA_FLG_U),
+                    (beginning, field::DATA_FLG_B),
+                    (ending, field::DATA_FLG_E),
+                ] {
+                    if set {
+                        flags |= flag;
+                    }
+                }
+                NetworkEndian::write_u32(&mut value[field::DATA_TSN], tsn);
+                NetworkEndian::write_u16(&mut value[field::DATA_STREAM_ID], stream_id);
+                NetworkEndian::write_u16(&mut value[field::DATA_STREAM_SEQ], stream_seq);
+                NetworkEndian::write_u32(&mut value[field::DATA_PPID], ppid);
+                value[field::DATA_PPID.end..].copy_from_slice(data);
+            }
+            ChunkRepr::Init(ref init) | ChunkRepr::InitAck(ref init) => init.emit(value),
+            ChunkRepr::Sack {
+                cumulative_tsn_ack,
+                a_rwnd,
+                gap_blocks,
+                duplicate_tsns,
+            } => {
+                NetworkEndian::write_u32(
+                    &mut value[field::SACK_CUMULATIVE_TSN],
+                    cumulative_tsn_ack,
+                );
+                NetworkEndian::write_u32(&mut value[field::SACK_A_RWND], a_rwnd);
+                NetworkEndian::write_u16(
+                    &mut value[field::SACK_GAP_BLOCKS],
+                    (gap_blocks.len() / 4) as u16,
+                );
+                NetworkEndian::write_u16(
+                    &mut value[field::SACK_DUP_TSNS],
+                    (duplicate_tsns.len() / 4) as u16,
+                );
+                let (gaps, dups) = value[field::SACK_DUP_TSNS.end..].split_at_mut(gap_blocks.len());
+                gaps.copy_from_slice(gap_blocks);
+                dups.copy_from_slice(duplicate_tsns);
+            }
+            ChunkRepr::Heartbeat { info } | ChunkRepr::HeartbeatAck { info } => {
+                NetworkEndian::write_u16(
+                    &mut value[field::HEARTBEAT_PARAM_TYPE],
+                    field::HEARTBEAT_INFO,
+                );
+                NetworkEndian::write_u16(
+                    &mut value[field::HEARTBEAT_PARAM_LENGTH],
+                    (field::HEARTBEAT_PARAM_LENGTH.end + info.len()) as u16,
+                );
+                value[field::HEARTBEAT_PARAM_LENGTH.end..].copy_from_slice(info);
+            }
+            ChunkRepr::Abort {
+                tag_reflected,
+                causes,
+            } => {
+                if tag_reflected {
+                    flags |= field::FLG_T;
+                }
+                value.copy_from_slice(causes);
+            }
+            ChunkRepr::Error { causes } => value.copy_from_slice(causes),
+            ChunkRepr::Shutdown { cumulative_tsn_ack } => {
+                NetworkEndian::write_u32(
+                    &mut value[field::SHUTDOWN_CUMULATIVE_TSN],
+                    cumulative_tsn_ack,
+                );
+            }
+            ChunkRepr::CookieEcho { cookie } => value.copy_from_slice(cookie),
+            ChunkRepr::ShutdownComplete { tag_reflected } => {
+                if tag_reflected {
+                    flags |= field::FLG_T;
+                }
+            }
+            ChunkRepr::ShutdownAck | ChunkRepr::CookieAck => (),
+        }
+        chunk.set_flags(flags);
+    }
+}
+
+impl<'a> InitRepr<'a> {
+    fn parse(value: &'a [u8]) -> Result<InitRepr<'a>> {
+        if value.len() < field::INIT_TSN.end {
+            return Err(Error);
+        }
+        let init = InitRepr {
+            initiate_tag: NetworkEndian::read_u32(&value[field::INIT_TAG]),
+            a_rwnd: NetworkEndian::read_u32(&value[field::INIT_A_RWND]),
+            outbound_streams: NetworkEndian::read_u16(&value[field::INIT_OUTBOUND_STREAMS]),
+            inbound_streams: NetworkEndian::read_u16(&value[field::INIT_INBOUND_STREAMS]),
+            initial_tsn: NetworkEndian::read_u32(&value[field::INIT_TSN]),
+            params: &value[field::INIT_TSN.end..],
+        };
+        // The tag and the stream counts must not be zero.
+        if init.initiate_tag == 0 || init.outbound_streams == 0 || init.inbound_streams == 0 {
+            return Err(Error);
+        }
+        Ok(init)
+    }
+
+    fn emit(&self, value: &mut [u8]) {
+        NetworkEndian::write_u32(&mut value[field::INIT_TAG], self.initiate_tag);
+        NetworkEndian::write_u32(&mut value[field::INIT_A_RWND], self.a_rwnd);
+        NetworkEndian::write_u16(
+            &mut value[field::INIT_OUTBOUND_STREAMS],
+            self.outbound_streams,
+        );
+        NetworkEndian::write_u16(
+            &mut value[field::INIT_INBOUND_STREAMS],
+            self.inbound_streams,
+        );
+        NetworkEndian::write_u32(&mut value[field::INIT_TSN], self.initial_tsn);
+        value[field::INIT_TSN.end..].copy_from_slice(self.params);
+    }
+}
+
+/// Return the sender-specific information of the Heartbeat Info parameter.
+fn parse_heartbeat_info(value: &[u8]) -> Result<&[u8]> {
+    if value.len() < field::HEARTBEAT_PARAM_LENGTH.end
+        || NetworkEndian::read_u16(&value[field::HEARTBEAT_PARAM_TYPE]) != field::HEARTBEAT_INFO
+        || NetworkEndian::read_u16(&value[field::HEARTBEAT_PARAM_LENGTH]) as usize != value.len()
+    {
+        return Err(Error);
+    }
+    Ok(&value[field::HEARTBEAT_PARAM_LENGTH.end..])
+}
+
+/// A high-level representation of an SCTP common header.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Repr {
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub verification_tag: u32,
+}
+
+impl Repr {
+    /// Parse an SCTP packet and return a high-level representation of its common header.
+    ///
+    /// The chunks are available through [Packet::chunks].
+    pub fn parse<T>(packet: &Packet<&T>, checksum_caps: &ChecksumCapabilities) -> Result<Repr>
+    where
+        T: AsRef<[u8]> + ?Sized,
+    {
+        packet.check_len()?;
+        // Source and destination ports must be present.
+        if packet.src_port() == 0 || packet.dst_port() == 0 {
+            return Err(Error);
+        }
+        // Valid checksum is expected.
+        if checksum_caps.sctp.rx() && !packet.verify_checksum() {
+            return Err(Error);
+        }
+        Ok(Repr {
+            src_port: packet.src_port(),
+            dst_port: packet.dst_port(),
+            verification_tag: packet.verification_tag(),
+        })
+    }
+
+    /// Return the length of the packet that will be emitted from this high-level
+    /// representation, followed by `chunks`.
+    pub fn buffer_len(&self, chunks: &[ChunkRepr]) -> usize {
+        HEADER_LEN + chunks.iter().map(|chunk| chunk.buffer_len()).sum::<usize>()
+    }
+
+    /// Emit a high-level representation into an SCTP packet, followed by `chunks`.
+    ///
+    /// The checksum covers the whole buffer, which must therefore be exactly
+    /// [buffer_len] octets long.
+    ///
+    /// [buffer_len]: #method.buffer_len
+    pub fn emit<T>(
+        &self,
+        packet: &mut Packet<&mut T>,
+        chunks: &[ChunkRepr],
+        checksum_caps: &ChecksumCapabilities,
+    ) where
+        T: AsRef<[u8]> + AsMut<[u8]> + ?Sized,
+    {
+        packet.set_src_port(self.src_port);
+        packet.set_dst_port(self.dst_port);
+        packet.set_verification_tag(self.verification_tag);
+
+        let mut payload = packet.payload_mut();
+        for chunk in chunks {
+            let (head, rest) = payload.split_at_mut(chunk.buffer_len());
+            chunk.emit(&mut Chunk::new_unchecked(head));
+            payload = rest;
+        }
+
+        if checksum_caps.sctp.tx() {
+            packet.fill_checksum()
+        } else {
+            // make sure we get a consistently zeroed checksum,
+            // since implementations might rely on it
+            packet.set_checksum(0);
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> fmt::Display for Packet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let packet = Packet::new_unchecked(self.buffer.as_ref());
+        match Repr::parse(&packet, &ChecksumCapabilities::ignored()) {
+            Ok(repr) => {
+                write!(f, "{repr}")?;
+                for chunk in packet.chunks() {
+                    match chunk.and_then(|chunk| ChunkRepr::parse(&chunk)) {
+                        Ok(chunk) => write!(f, " {chunk}")?,
+                        Err(err) => write!(f, " ({err})")?,
+                    }
+                }
+                Ok(())
+            }
+            Err(err) => write!(f, "SCTP ({err})"),
+        }
+    }
+}
+
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SCTP src={} dst={} tag=0x{:08x}",
+            self.src_port, self.dst_port, self.verification_tag
+        )
+    }
+}
+
+impl<'a> fmt::Display for ChunkRepr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.chunk_type())?;
+        match *self {
+            ChunkRepr::Data {
+                tsn,
+                stream_id,
+                stream_seq,
+                ppid,
+                data,
+                ..
+            } => write!(
+                f,
+                " tsn={tsn} sid={stream_id} ssn={stream_seq} ppid={ppid} len={}",
+                data.len()
+            ),
+            ChunkRepr::Init(ref init) | ChunkRepr::InitAck(ref init) => write!(
+                f,
+                " tag=0x{:08x} a_rwnd={} os={} mis={} tsn={}",
+                init.initiate_tag,
+                init.a_rwnd,
+                init.outbound_streams,
+                init.inbound_streams,
+                init.initial_tsn
+            ),
+            ChunkRepr::Sack {
+                cumulative_tsn_ack,
+                a_rwnd,
+                gap_blocks,
+                duplicate_tsns,
+            } => write!(
+                f,
+                " cum_tsn={cumulative_tsn_ack} a_rwnd={a_rwnd} gaps={} dups={}",
+                gap_blocks.len() / 4,
+                duplicate_tsns.len() / 4
+            ),
+            ChunkRepr::Shutdown { cumulative_tsn_ack } => {
+                write!(f, " cum_tsn={cumulative_tsn_ack}")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static PACKET_BYTES: [u8; 48] = [
+        0x0b, 0x59, 0x0b, 0x59, 0x12, 0x34, 0x56, 0x78, 0x4a, 0x87, 0x86, 0xb9, 0x00, 0x03, 0x00,
+        0x13, 0x00, 0x00, 0x00, 0x2a, 0x00, 0x01, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x61, 0x62,
+        0x63, 0x00, 0x03, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x29, 0x00, 0x00, 0x10, 0x00, 0x00,
+        0x00, 0x00, 0x00,
+    ];
+
+    static DATA_CHUNK: ChunkRepr = ChunkRepr::Data {
+        immediate: false,
+        unordered: false,
+        beginning: true,
+        ending: true,
+        tsn: 42,
+        stream_id: 1,
+        stream_seq: 5,
+        ppid: 0,
+        data: b"abc",
+    };
+
+    static SACK_CHUNK: ChunkRepr = ChunkRepr::Sack {
+        cumulative_tsn_ack: 41,
+        a_rwnd: 4096,
+        gap_blocks: &[],
+        duplicate_tsns: &[],
+    };
+
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(!crc32c_update(!0, b"123456789"), 0xe306_9283);
+    }
+
+    #[test]
+    fn test_deconstruct() {
+        let packet = Packet::new_checked(&PACKET_BYTES[..]).unwrap();
+        assert_eq!(packet.src_port(), 2905);
+        assert_eq!(packet.dst_port(), 2905);
+        assert_eq!(packet.verification_tag(), 0x12345678);
+        assert!(packet.verify_checksum());
+
+        let mut chunks = packet.chunks();
+        let chunk = chunks.next().unwrap().unwrap();
+        assert_eq!(chunk.chunk_type(), ChunkType::Data);
+        assert_eq!(chunk.flags(), 0x03);
+        assert_eq!(chunk.chunk_len(), 19);
+        assert_eq!(chunk.value().len(), 15);
+        let chunk = chunks.next().unwrap().unwrap();
+        assert_eq!(chunk.chunk_type(), ChunkType::Sack);
+        assert_eq!(chunk.chunk_len(), 16);
+        assert!(chunks.next().is_none());
+    }
+
+    #[test]
+    fn test_construct() {
+        let mut bytes = vec![0xa5; 48];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_src_port(2905);
+        packet.set_dst_port(2905);
+        packet.set_verification_tag(0x12345678);
+        packet.payload_mut().copy_from_slice(&PACKET_BYTES[12..]);
+        packet.fill_checksum();
+        assert_eq!(&*packet.into_inner(), &PACKET_BYTES[..]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let packet = Packet::new_unchecked(&PACKET_BYTES[..]);
+        let repr = Repr::parse(&packet, &ChecksumCapabilities::default()).unwrap();
+        assert_eq!(
+            repr,
+            Repr {
+                src_port: 2905,
+                dst_port: 2905,
+                verification_tag: 0x12345678,
+            }
+        );
+        let chunks: Vec<_> = packet
+            .chunks()
+            .map(|chunk| ChunkRepr::parse(&chunk.unwrap()).unwrap())
+            .collect();
+        assert_eq!(chunks, [DATA_CHUNK, SACK_CHUNK]);
+
+        let mut bytes = PACKET_BYTES;
+        bytes[47] = 1;
+        let packet = Packet::new_unchecked(&bytes[..]);
+        assert_eq!(
+            Repr::parse(&packet, &ChecksumCapabilities::default()),
+            Err(Error)
+        );
+        assert!(Repr::parse(&packet, &ChecksumCapabilities::ignored()).is_ok());
+    }
+
+    #[test]
+    fn test_emit() {
+        let repr = Repr {
+            src_port: 2905,
+            dst_port: 2905,
+            verification_tag: 0x12345678,
+        };
+        let chunks = [DATA_CHUNK, SACK_CHUNK];
+        let mut bytes = vec![0xa5; repr.buffer_len(&chunks)];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet, &chunks, &ChecksumCapabilities::default());
+        assert_eq!(&*packet.into_inner(), &PACKET_BYTES[..]);
+    }
+
+    #[test]
+    fn test_chunks() {
+        let chunks = [
+            ChunkRepr::Init(InitRepr {
+                initiate_tag: 0xcafe,
+                a_rwnd: 65535,
+                outbound_streams: 10,
+                inbound_streams: 65535,
+                initial_tsn: 1,
+                params: &[0x00, 0x0c, 0x00, 0x06, 0x00, 0x05, 0x00, 0x00],
+            }),
+            ChunkRepr::Sack {
+                cumulative_tsn_ack: 7,
+                a_rwnd: 1024,
+                gap_blocks: &[0x00, 0x02, 0x00, 0x03],
+                duplicate_tsns: &[0x00, 0x00, 0x00, 0x05],
+            },
+            ChunkRepr::Heartbeat { info: b"hello" },
+            ChunkRepr::Abort {
+                tag_reflected: true,
+                causes: &[],
+            },
+            ChunkRepr::Shutdown {
+                cumulative_tsn_ack: 9,
+            },
+            ChunkRepr::ShutdownAck,
+            ChunkRepr::CookieEcho { cookie: b"cookie" },
+            ChunkRepr::CookieAck,
+            ChunkRepr::ShutdownComplete {
+                tag_reflected: false,
+            },
+        ];
+        for chunk in chunks {
+            let mut bytes = vec![0xa5; chunk.buffer_len()];
+            chunk.emit(&mut Chunk::new_unchecked(&mut bytes[..]));
+            assert_eq!(bytes.len() % 4, 0);
+            let parsed = Chunk::new_checked(&bytes[..]).unwrap();
+            assert_eq!(parsed.chunk_len(), chunk.chunk_len());
+            assert_eq!(ChunkRepr::parse(&parsed), Ok(chunk));
+        }
+    }
+}
@@ -10,10 +10,24 @@ use crate::wire::IpProtocol;
 
 const ADDRESS_CONTEXT_LENGTH: usize = 8;
 
+/// The number of context identifiers, and so of address contexts, of a 6LoWPAN network.
+pub const ADDRESS_CONTEXT_COUNT: usize = 16;
+
+/// The 64-bit prefix of an address context, used to decompress addresses whose
+/// prefix is elided.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct AddressContext(pub [u8; ADDRESS_CONTEXT_LENGTH]);
 
+impl AddressContext {
+    /// Create an address context from the first 64 bits of a prefix.
+    pub fn from_prefix(prefix: ipv6::Address) -> Self {
+        let mut bytes = [0; ADDRESS_CONTEXT_LENGTH];
+        bytes.copy_from_slice(&prefix.as_bytes()[..ADDRESS_CONTEXT_LENGTH]);
+        AddressContext(bytes)
+    }
+}
+
 /// The representation of an unresolved address. 6LoWPAN compression of IPv6 addresses can be with
 /// and without context information. The decompression with context information is not yet
 /// implemented.
@@ -58,19 +72,16 @@ const LINK_LOCAL_PREFIX: [u8; 2] = [0xfe, 0x80];
 const EUI64_MIDDLE_VALUE: [u8; 2] = [0xff, 0xfe];
 
 impl<'a> UnresolvedAddress<'a> {
+    /// Resolve the address, with the address contexts indexed by their context identifier.
     pub fn resolve(
         self,
         ll_address: Option<LlAddress>,
-        addr_context: &[AddressContext],
+        addr_context: &[Option<AddressContext>],
     ) -> Result<ipv6::Address> {
         let mut bytes = [0; 16];
 
         let copy_context = |index: usize, bytes: &mut [u8]| -> Result<()> {
-            if index >= addr_context.len() {
-                return Err(Error);
-            }
-
-            let context = addr_context[index];
+            let context = addr_context.get(index).copied().flatten().ok_or(Error)?;
             bytes[..ADDRESS_CONTEXT_LENGTH].copy_from_slice(&context.0);
 
             Ok(())
@@ -1163,12 +1174,13 @@ pub mod iphc {
         /// Parse a 6LoWPAN IPHC header and return a high-level representation.
         ///
         /// The `ll_src_addr` and `ll_dst_addr` are the link-local addresses used for resolving the
-        /// IPv6 packets.
+        /// IPv6 packets, and `addr_context` holds the address contexts indexed by their context
+        /// identifier.
         pub fn parse<T: AsRef<[u8]> + ?Sized>(
             packet: &Packet<&T>,
             ll_src_addr: Option<LlAddress>,
             ll_dst_addr: Option<LlAddress>,
-            addr_context: &[AddressContext],
+            addr_context: &[Option<AddressContext>],
         ) -> Result<Self> {
             // Ensure basic accessors will work.
             packet.check_len()?;
@@ -0,0 +1,421 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+
+use super::{Error, Result};
+
+/// The UDP port STAMP session-reflectors receive test packets on, see RFC 8762 § 4.1.
+pub const PORT: u16 = 862;
+
+/// The length of an unauthenticated STAMP test packet, whether sent by a session-sender
+/// or a session-reflector.
+pub const PACKET_LEN: usize = 44;
+
+/// The error estimate of a timestamp, see RFC 4656 § 4.1.2.
+///
+/// The error of the timestamp is `multiplier * 2^(scale - 32)` seconds.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ErrorEstimate {
+    /// Whether the clock is synchronized to UTC.
+    pub synchronized: bool,
+    /// Whether the timestamp is in the PTPv2 format rather than the NTP one.
+    pub ptp: bool,
+    /// The scale of the error, up to 63.
+    pub scale: u8,
+    /// The multiplier of the error, which must not be zero.
+    pub multiplier: u8,
+}
+
+impl ErrorEstimate {
+    const S: u16 = 0x8000;
+    const Z: u16 = 0x4000;
+
+    /// Decode an error estimate field.
+    pub const fn from_bits(bits: u16) -> Self {
+        Self {
+            synchronized: bits & Self::S != 0,
+            ptp: bits & Self::Z != 0,
+            scale: ((bits >> 8) & 0x3f) as u8,
+            multiplier: bits as u8,
+        }
+    }
+
+    /// Encode the error estimate field.
+    pub const fn bits(&self) -> u16 {
+        let mut bits = ((self.scale as u16 & 0x3f) << 8) | self.multiplier as u16;
+        if self.synchronized {
+            bits |= Self::S;
+        }
+        if self.ptp {
+            bits |= Self::Z;
+        }
+        bits
+    }
+}
+
+/// A read/write wrapper around an unauthenticated STAMP test packet buffer.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+mod field {
+    use crate::wire::field::*;
+
+    pub const SEQ_NUM: Field = 0..4;
+    pub const TIMESTAMP: Field = 4..12;
+    pub const ERROR_ESTIMATE: Field = 12..14;
+
+    // Only in the packets of session-reflectors.
+    pub const RECEIVE_TIMESTAMP: Field = 16..24;
+    pub const SENDER_SEQ_NUM: Field = 24..28;
+    pub const SENDER_TIMESTAMP: Field = 28..36;
+    pub const SENDER_ERROR_ESTIMATE: Field = 36..38;
+    pub const SENDER_TTL: usize = 40;
+}
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Imbue a raw octet buffer with STAMP packet structure.
+    pub const fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is shorter than an unauthenticated packet.
+    pub fn check_len(&self) -> Result<()> {
+        if self.buffer.as_ref().len() < PACKET_LEN {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the sequence number field.
+    #[inline]
+    pub fn seq_num(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u32(&data[field::SEQ_NUM])
+    }
+
+    /// Return the timestamp field, the instant at which the packet was sent.
+    #[inline]
+    pub fn timestamp(&self) -> u64 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u64(&data[field::TIMESTAMP])
+    }
+
+    /// Return the error estimate field.
+    #[inline]
+    pub fn error_estimate(&self) -> ErrorEstimate {
+        let data = self.buffer.as_ref();
+        ErrorEstimate::from_bits(NetworkEndian::read_u16(&data[field::ERROR_ESTIMATE]))
+    }
+
+    /// Return the receive timestamp field of a reflected packet.
+    #[inline]
+    pub fn receive_timestamp(&self) -> u64 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u64(&data[field::RECEIVE_TIMESTAMP])
+    }
+
+    /// Return the session-sender sequence number field of a reflected packet.
+    #[inline]
+    pub fn sender_seq_num(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u32(&data[field::SENDER_SEQ_NUM])
+    }
+
+    /// Return the session-sender timestamp field of a reflected packet.
+    #[inline]
+    pub fn sender_timestamp(&self) -> u64 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u64(&data[field::SENDER_TIMESTAMP])
+    }
+
+    /// Return the session-sender error estimate field of a reflected packet.
+    #[inline]
+    pub fn sender_error_estimate(&self) -> ErrorEstimate {
+        let data = self.buffer.as_ref();
+        ErrorEstimate::from_bits(NetworkEndian::read_u16(&data[field::SENDER_ERROR_ESTIMATE]))
+    }
+
+    /// Return the session-sender TTL field of a reflected packet.
+    #[inline]
+    pub fn sender_ttl(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::SENDER_TTL]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the sequence number field.
+    #[inline]
+    pub fn set_seq_num(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::SEQ_NUM], value)
+    }
+
+    /// Set the timestamp field.
+    #[inline]
+    pub fn set_timestamp(&mut self, value: u64) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u64(&mut data[field::TIMESTAMP], value)
+    }
+
+    /// Set the error estimate field.
+    #[inline]
+    pub fn set_error_estimate(&mut self, value: ErrorEstimate) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::ERROR_ESTIMATE], value.bits())
+    }
+
+    /// Set the receive timestamp field of a reflected packet.
+    #[inline]
+    pub fn set_receive_timestamp(&mut self, value: u64) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u64(&mut data[field::RECEIVE_TIMESTAMP], value)
+    }
+
+    /// Set the session-sender sequence number field of a reflected packet.
+    #[inline]
+    pub fn set_sender_seq_num(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::SENDER_SEQ_NUM], value)
+    }
+
+    /// Set the session-sender timestamp field of a reflected packet.
+    #[inline]
+    pub fn set_sender_timestamp(&mut self, value: u64) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u64(&mut data[field::SENDER_TIMESTAMP], value)
+    }
+
+    /// Set the session-sender error estimate field of a reflected packet.
+    #[inline]
+    pub fn set_sender_error_estimate(&mut self, value: ErrorEstimate) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::SENDER_ERROR_ESTIMATE], value.bits())
+    }
+
+    /// Set the session-sender TTL field of a reflected packet.
+    #[inline]
+    pub fn set_sender_ttl(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::SENDER_TTL] = value
+    }
+
+    /// Clear the fields following the error estimate, which must be zero in the
+    /// packets of session-senders.
+    fn clear_sender_mbz(&mut self) {
+        let data = self.buffer.as_mut();
+        data[field::ERROR_ESTIMATE.end..PACKET_LEN].fill(0)
+    }
+
+    /// Clear the fields that must be zero in the packets of session-reflectors.
+    fn clear_reflector_mbz(&mut self) {
+        let data = self.buffer.as_mut();
+        data[field::ERROR_ESTIMATE.end..field::RECEIVE_TIMESTAMP.start].fill(0);
+        data[field::SENDER_ERROR_ESTIMATE.end..field::SENDER_TTL].fill(0);
+        data[field::SENDER_TTL + 1..PACKET_LEN].fill(0)
+    }
+}
+
+/// A high-level representation of an unauthenticated test packet sent by a
+/// session-sender.
+///
+/// Timestamps are in the 64-bit NTP format, or the truncated PTPv2 one as told by
+/// the error estimate.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SenderRepr {
+    pub seq_num: u32,
+    pub timestamp: u64,
+    pub error_estimate: ErrorEstimate,
+}
+
+impl SenderRepr {
+    /// Parse a session-sender test packet.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(packet: &Packet<&T>) -> Result<Self> {
+        packet.check_len()?;
+        Ok(Self {
+            seq_num: packet.seq_num(),
+            timestamp: packet.timestamp(),
+            error_estimate: packet.error_estimate(),
+        })
+    }
+
+    /// Return the length of a packet that will be emitted from this high-level
+    /// representation.
+    pub const fn buffer_len(&self) -> usize {
+        PACKET_LEN
+    }
+
+    /// Emit a high-level representation into a session-sender test packet.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) {
+        packet.clear_sender_mbz();
+        packet.set_seq_num(self.seq_num);
+        packet.set_timestamp(self.timestamp);
+        packet.set_error_estimate(self.error_estimate);
+    }
+}
+
+/// A high-level representation of an unauthenticated test packet sent back by a
+/// session-reflector.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReflectorRepr {
+    pub seq_num: u32,
+    pub timestamp: u64,
+    pub error_estimate: ErrorEstimate,
+    pub receive_timestamp: u64,
+    pub sender_seq_num: u32,
+    pub sender_timestamp: u64,
+    pub sender_error_estimate: ErrorEstimate,
+    pub sender_ttl: u8,
+}
+
+impl ReflectorRepr {
+    /// Parse a session-reflector test packet.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(packet: &Packet<&T>) -> Result<Self> {
+        packet.check_len()?;
+        Ok(Self {
+            seq_num: packet.seq_num(),
+            timestamp: packet.timestamp(),
+            error_estimate: packet.error_estimate(),
+            receive_timestamp: packet.receive_timestamp(),
+            sender_seq_num: packet.sender_seq_num(),
+            sender_timestamp: packet.sender_timestamp(),
+            sender_error_estimate: packet.sender_error_estimate(),
+            sender_ttl: packet.sender_ttl(),
+        })
+    }
+
+    /// Return the length of a packet that will be emitted from this high-level
+    /// representation.
+    pub const fn buffer_len(&self) -> usize {
+        PACKET_LEN
+    }
+
+    /// Emit a high-level representation into a session-reflector test packet.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) {
+        packet.clear_reflector_mbz();
+        packet.set_seq_num(self.seq_num);
+        packet.set_timestamp(self.timestamp);
+        packet.set_error_estimate(self.error_estimate);
+        packet.set_receive_timestamp(self.receive_timestamp);
+        packet.set_sender_seq_num(self.sender_seq_num);
+        packet.set_sender_timestamp(self.sender_timestamp);
+        packet.set_sender_error_estimate(self.sender_error_estimate);
+        packet.set_sender_ttl(self.sender_ttl);
+    }
+}
+
+impl fmt::Display for SenderRepr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "STAMP test seq={}", self.seq_num)
+    }
+}
+
+impl fmt::Display for ReflectorRepr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "STAMP reflected seq={} sender_seq={}",
+            self.seq_num, self.sender_seq_num
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static REFLECTOR_BYTES: [u8; 44] = [
+        0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x02, 0x80, 0x00, 0x00, 0x00, 0x0c, 0x01, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x01, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x8c, 0x01, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x00,
+    ];
+
+    fn reflector_repr() -> ReflectorRepr {
+        ReflectorRepr {
+            seq_num: 7,
+            timestamp: 0x0000_0002_8000_0000,
+            error_estimate: ErrorEstimate {
+                synchronized: false,
+                ptp: false,
+                scale: 12,
+                multiplier: 1,
+            },
+            receive_timestamp: 0x0000_0001_4000_0000,
+            sender_seq_num: 7,
+            sender_timestamp: 0x0000_0001_0000_0000,
+            sender_error_estimate: ErrorEstimate {
+                synchronized: true,
+                ptp: false,
+                scale: 12,
+                multiplier: 1,
+            },
+            sender_ttl: 63,
+        }
+    }
+
+    #[test]
+    fn test_error_estimate() {
+        let estimate = ErrorEstimate::from_bits(0xcc01);
+        assert!(estimate.synchronized);
+        assert!(estimate.ptp);
+        assert_eq!(estimate.scale, 12);
+        assert_eq!(estimate.multiplier, 1);
+        assert_eq!(estimate.bits(), 0xcc01);
+    }
+
+    #[test]
+    fn test_reflector_parse() {
+        let packet = Packet::new_checked(&REFLECTOR_BYTES[..]).unwrap();
+        assert_eq!(ReflectorRepr::parse(&packet), Ok(reflector_repr()));
+    }
+
+    #[test]
+    fn test_reflector_emit() {
+        let repr = reflector_repr();
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        repr.emit(&mut Packet::new_unchecked(&mut bytes));
+        assert_eq!(&bytes[..], &REFLECTOR_BYTES[..]);
+    }
+
+    #[test]
+    fn test_sender_parse_emit() {
+        let repr = SenderRepr {
+            seq_num: 7,
+            timestamp: 0x0000_0001_0000_0000,
+            error_estimate: ErrorEstimate::from_bits(0x8c01),
+        };
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        repr.emit(&mut Packet::new_unchecked(&mut bytes));
+        assert!(bytes[14..].iter().all(|&b| b == 0));
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(SenderRepr::parse(&packet), Ok(repr));
+    }
+
+    #[test]
+    fn test_check_len() {
+        assert_eq!(Packet::new_checked(&[0; PACKET_LEN - 1][..]), Err(Error));
+        assert!(Packet::new_checked(&[0; PACKET_LEN][..]).is_ok());
+    }
+}
@@ -107,6 +107,7 @@ mod field {
     pub const OPT_WS: u8 = 0x03;
     pub const OPT_SACKPERM: u8 = 0x04;
     pub const OPT_SACKRNG: u8 = 0x05;
+    pub const OPT_TSTAMP: u8 = 0x08;
 }
 
 pub const HEADER_LEN: usize = field::URGENT.end;
@@ -600,6 +601,7 @@ pub enum TcpOption<'a> {
     WindowScale(u8),
     SackPermitted,
     SackRange([Option<(u32, u32)>; 3]),
+    TimeStamp { tsval: u32, tsecr: u32 },
     Unknown { kind: u8, data: &'a [u8] },
 }
 
@@ -663,6 +665,13 @@ impl<'a> TcpOption<'a> {
                         });
                         option = TcpOption::SackRange(sack_ranges);
                     }
+                    (field::OPT_TSTAMP, 10) => {
+                        option = TcpOption::TimeStamp {
+                            tsval: NetworkEndian::read_u32(&data[0..4]),
+                            tsecr: NetworkEndian::read_u32(&data[4..8]),
+                        }
+                    }
+                    (field::OPT_TSTAMP, _) => return Err(Error),
                     (_, _) => option = TcpOption::Unknown { kind, data },
                 }
             }
@@ -678,6 +687,7 @@ impl<'a> TcpOption<'a> {
             TcpOption::WindowScale(_) => 3,
             TcpOption::SackPermitted => 2,
             TcpOption::SackRange(s) => s.iter().filter(|s| s.is_some()).count() * 8 + 2,
+            TcpOption::TimeStamp { .. } => 10,
             TcpOption::Unknown { data, .. } => 2 + data.len(),
         }
     }
@@ -725,6 +735,11 @@ impl<'a> TcpOption<'a> {
                                 NetworkEndian::write_u32(&mut buffer[pos + 4..], second);
                             });
                     }
+                    &TcpOption::TimeStamp { tsval, tsecr } => {
+                        buffer[0] = field::OPT_TSTAMP;
+                        NetworkEndian::write_u32(&mut buffer[2..], tsval);
+                        NetworkEndian::write_u32(&mut buffer[6..], tsecr);
+                    }
                     &TcpOption::Unknown {
                         kind,
                         data: provided,
@@ -769,6 +784,16 @@ impl Control {
     }
 }
 
+/// The contents of a Timestamps option, as described in RFC 7323.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimestampRepr {
+    /// The current value of the timestamp clock of the sender.
+    pub tsval: u32,
+    /// The most recent timestamp value received from the remote, or zero if none.
+    pub tsecr: u32,
+}
+
 /// A high-level representation of a Transmission Control Protocol packet.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -783,6 +808,8 @@ pub struct Repr<'a> {
     pub max_seg_size: Option<u16>,
     pub sack_permitted: bool,
     pub sack_ranges: [Option<(u32, u32)>; 3],
+    pub timestamp: Option<TimestampRepr>,
+    pub urgent_at: Option<u16>,
     pub payload: &'a [u8],
 }
 
@@ -824,15 +851,18 @@ impl<'a> Repr<'a> {
             false => None,
         };
         // The PSH flag is ignored.
-        // The URG flag and the urgent field is ignored. This behavior is standards-compliant,
-        // however, most deployed systems (e.g. Linux) are *not* standards-compliant, and would
-        // cut the byte at the urgent pointer from the stream.
+        // The urgent field is only meaningful if the URG flag is set.
+        let urgent_at = match packet.urg() {
+            true => Some(packet.urgent_at()),
+            false => None,
+        };
 
         let mut max_seg_size = None;
         let mut window_scale = None;
         let mut options = packet.options();
         let mut sack_permitted = false;
         let mut sack_ranges = [None, None, None];
+        let mut timestamp = None;
         while !options.is_empty() {
             let (next_options, option) = TcpOption::parse(options)?;
             match option {
@@ -859,6 +889,9 @@ impl<'a> Repr<'a> {
                 }
                 TcpOption::SackPermitted => sack_permitted = true,
                 TcpOption::SackRange(slice) => sack_ranges = slice,
+                TcpOption::TimeStamp { tsval, tsecr } => {
+                    timestamp = Some(TimestampRepr { tsval, tsecr })
+                }
                 _ => (),
             }
             options = next_options;
@@ -875,6 +908,8 @@ impl<'a> Repr<'a> {
             max_seg_size: max_seg_size,
             sack_permitted: sack_permitted,
             sack_ranges: sack_ranges,
+            timestamp: timestamp,
+            urgent_at: urgent_at,
             payload: packet.payload(),
         })
     }
@@ -902,6 +937,9 @@ impl<'a> Repr<'a> {
         if sack_range_len > 0 {
             length += sack_range_len + 2;
         }
+        if self.timestamp.is_some() {
+            length += 10;
+        }
         if length % 4 != 0 {
             length += 4 - length % 4;
         }
@@ -955,12 +993,21 @@ impl<'a> Repr<'a> {
                 let tmp = options;
                 options = TcpOption::SackRange(self.sack_ranges).emit(tmp);
             }
+            if let Some(timestamp) = self.timestamp {
+                let tmp = options;
+                options = TcpOption::TimeStamp {
+                    tsval: timestamp.tsval,
+                    tsecr: timestamp.tsecr,
+                }
+                .emit(tmp);
+            }
 
             if !options.is_empty() {
                 TcpOption::EndOfList.emit(options);
             }
         }
-        packet.set_urgent_at(0);
+        packet.set_urg(self.urgent_at.is_some());
+        packet.set_urgent_at(self.urgent_at.unwrap_or(0));
         packet.payload_mut()[..self.payload.len()].copy_from_slice(self.payload);
 
         if checksum_caps.tcp.tx() {
@@ -1035,6 +1082,7 @@ impl<'a, T: AsRef<[u8]> + ?Sized> fmt::Display for Packet<&'a T> {
                 TcpOption::WindowScale(value) => write!(f, " ws={value}")?,
                 TcpOption::SackPermitted => write!(f, " sACK")?,
                 TcpOption::SackRange(slice) => write!(f, " sACKr{slice:?}")?, // debug print conveniently includes the []s
+                TcpOption::TimeStamp { tsval, tsecr } => write!(f, " ts={tsval}/{tsecr}")?,
                 TcpOption::Unknown { kind, .. } => write!(f, " opt({kind})")?,
             }
             options = next_options;
@@ -1058,6 +1106,9 @@ impl<'a> fmt::Display for Repr<'a> {
             write!(f, " ack={ack_number}")?;
         }
         write!(f, " win={}", self.window_len)?;
+        if let Some(urgent_at) = self.urgent_at {
+            write!(f, " urg={urgent_at}")?;
+        }
         write!(f, " len={}", self.payload.len())?;
         if let Some(max_seg_size) = self.max_seg_size {
             write!(f, " mss={max_seg_size}")?;
@@ -1187,6 +1238,8 @@ mod test {
             max_seg_size: None,
             sack_permitted: false,
             sack_ranges: [None, None, None],
+            timestamp: None,
+            urgent_at: None,
             payload: &PAYLOAD_BYTES,
         }
     }
@@ -1220,6 +1273,35 @@ mod test {
         assert_eq!(&*packet.into_inner(), &SYN_PACKET_BYTES[..]);
     }
 
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_emit_parse_urgent() {
+        let mut repr = packet_repr();
+        repr.control = Control::Psh;
+        repr.ack_number = Some(SeqNumber(0x089abcde));
+        repr.urgent_at = Some(3);
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(
+            &mut packet,
+            &SRC_ADDR.into(),
+            &DST_ADDR.into(),
+            &ChecksumCapabilities::default(),
+        );
+        assert!(packet.urg());
+        assert_eq!(packet.urgent_at(), 3);
+
+        let packet = Packet::new_unchecked(&bytes[..]);
+        let parsed = Repr::parse(
+            &packet,
+            &SRC_ADDR.into(),
+            &DST_ADDR.into(),
+            &ChecksumCapabilities::default(),
+        )
+        .unwrap();
+        assert_eq!(parsed, repr);
+    }
+
     #[test]
     #[cfg(feature = "proto-ipv4")]
     fn test_header_len_multiple_of_4() {
@@ -1266,6 +1348,13 @@ mod test {
                 0x00, 0x26, 0x25, 0xa0, 0x34, 0x3e, 0xfc, 0xea, 0x34, 0x40, 0xae, 0xf0
             ]
         );
+        assert_option_parses!(
+            TcpOption::TimeStamp {
+                tsval: 0x01020304,
+                tsecr: 0x0a0b0c0d
+            },
+            &[0x08, 0x0a, 0x01, 0x02, 0x03, 0x04, 0x0a, 0x0b, 0x0c, 0x0d]
+        );
         assert_option_parses!(
             TcpOption::Unknown {
                 kind: 12,
@@ -1283,5 +1372,6 @@ mod test {
         assert_eq!(TcpOption::parse(&[0xc, 0x01]), Err(Error));
         assert_eq!(TcpOption::parse(&[0x2, 0x02]), Err(Error));
         assert_eq!(TcpOption::parse(&[0x3, 0x02]), Err(Error));
+        assert_eq!(TcpOption::parse(&[0x8, 0x02]), Err(Error));
     }
 }
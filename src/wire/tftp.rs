@@ -0,0 +1,605 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+use core::str;
+
+use super::{Error, Result};
+
+/// The UDP port TFTP servers receive requests on.
+pub const SERVER_PORT: u16 = 69;
+
+/// The block size used unless another one is negotiated.
+pub const DEFAULT_BLOCK_SIZE: u16 = 512;
+
+/// The smallest block size that can be negotiated, see RFC 2348.
+pub const MIN_BLOCK_SIZE: u16 = 8;
+
+/// The largest block size that can be negotiated, see RFC 2348.
+pub const MAX_BLOCK_SIZE: u16 = 65464;
+
+enum_with_unknown! {
+    /// TFTP opcode.
+    pub enum Opcode(u16) {
+        Read      = 1,
+        Write     = 2,
+        Data      = 3,
+        Ack       = 4,
+        Error     = 5,
+        OptionAck = 6
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Opcode::Read => write!(f, "RRQ"),
+            Opcode::Write => write!(f, "WRQ"),
+            Opcode::Data => write!(f, "DATA"),
+            Opcode::Ack => write!(f, "ACK"),
+            Opcode::Error => write!(f, "ERROR"),
+            Opcode::OptionAck => write!(f, "OACK"),
+            Opcode::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+enum_with_unknown! {
+    /// TFTP error code.
+    pub enum ErrorCode(u16) {
+        Undefined         = 0,
+        FileNotFound      = 1,
+        AccessViolation   = 2,
+        DiskFull          = 3,
+        IllegalOperation  = 4,
+        UnknownTid        = 5,
+        FileExists        = 6,
+        NoSuchUser        = 7,
+        OptionNegotiation = 8
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorCode::Undefined => write!(f, "undefined"),
+            ErrorCode::FileNotFound => write!(f, "file not found"),
+            ErrorCode::AccessViolation => write!(f, "access violation"),
+            ErrorCode::DiskFull => write!(f, "disk full"),
+            ErrorCode::IllegalOperation => write!(f, "illegal operation"),
+            ErrorCode::UnknownTid => write!(f, "unknown transfer id"),
+            ErrorCode::FileExists => write!(f, "file exists"),
+            ErrorCode::NoSuchUser => write!(f, "no such user"),
+            ErrorCode::OptionNegotiation => write!(f, "option negotiation failed"),
+            ErrorCode::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+/// The transfer mode of a TFTP request.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    NetAscii,
+    Octet,
+    Mail,
+}
+
+impl Mode {
+    fn parse(value: &str) -> Result<Mode> {
+        if value.eq_ignore_ascii_case("netascii") {
+            Ok(Mode::NetAscii)
+        } else if value.eq_ignore_ascii_case("octet") {
+            Ok(Mode::Octet)
+        } else if value.eq_ignore_ascii_case("mail") {
+            Ok(Mode::Mail)
+        } else {
+            Err(Error)
+        }
+    }
+
+    const fn as_str(&self) -> &'static str {
+        match self {
+            Mode::NetAscii => "netascii",
+            Mode::Octet => "octet",
+            Mode::Mail => "mail",
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// The options of a TFTP request or option acknowledgement.
+///
+/// Only the block size (RFC 2348) and timeout interval (RFC 2349) options are
+/// represented; the other options are ignored.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TransferOptions {
+    /// The number of octets in a block.
+    pub block_size: Option<u16>,
+    /// The timeout interval, in seconds.
+    pub timeout: Option<u8>,
+}
+
+impl TransferOptions {
+    fn parse(mut data: &[u8]) -> Result<TransferOptions> {
+        let mut options = TransferOptions::default();
+        while !data.is_empty() {
+            let name = take_string(&mut data)?;
+            let value = take_string(&mut data)?;
+            if name.eq_ignore_ascii_case("blksize") {
+                let block_size: u16 = value.parse().map_err(|_| Error)?;
+                if !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size) {
+                    return Err(Error);
+                }
+                options.block_size = Some(block_size);
+            } else if name.eq_ignore_ascii_case("timeout") {
+                let timeout: u8 = value.parse().map_err(|_| Error)?;
+                if timeout == 0 {
+                    return Err(Error);
+                }
+                options.timeout = Some(timeout);
+            }
+        }
+        Ok(options)
+    }
+
+    fn buffer_len(&self) -> usize {
+        let mut len = 0;
+        if let Some(block_size) = self.block_size {
+            len += "blksize".len() + 1 + decimal_len(block_size as u32) + 1;
+        }
+        if let Some(timeout) = self.timeout {
+            len += "timeout".len() + 1 + decimal_len(timeout as u32) + 1;
+        }
+        len
+    }
+
+    fn emit(&self, mut data: &mut [u8]) {
+        if let Some(block_size) = self.block_size {
+            put_string(&mut data, b"blksize");
+            put_decimal(&mut data, block_size as u32);
+        }
+        if let Some(timeout) = self.timeout {
+            put_string(&mut data, b"timeout");
+            put_decimal(&mut data, timeout as u32);
+        }
+    }
+}
+
+/// Split a NUL-terminated string off the front of `data`.
+fn take_string<'a>(data: &mut &'a [u8]) -> Result<&'a str> {
+    let end = data.iter().position(|&octet| octet == 0).ok_or(Error)?;
+    let value = str::from_utf8(&data[..end]).map_err(|_| Error)?;
+    *data = &data[end + 1..];
+    Ok(value)
+}
+
+fn put_string(data: &mut &mut [u8], value: &[u8]) {
+    let (head, tail) = core::mem::take(data).split_at_mut(value.len() + 1);
+    head[..value.len()].copy_from_slice(value);
+    head[value.len()] = 0;
+    *data = tail;
+}
+
+fn decimal_len(mut value: u32) -> usize {
+    let mut len = 1;
+    while value >= 10 {
+        value /= 10;
+        len += 1;
+    }
+    len
+}
+
+fn put_decimal(data: &mut &mut [u8], mut value: u32) {
+    let mut digits = [0; 10];
+    let len = decimal_len(value);
+    for digit in digits[..len].iter_mut().rev() {
+        *digit = b'0' + (value % 10) as u8;
+        value /= 10;
+    }
+    put_string(data, &digits[..len])
+}
+
+/// A read/write wrapper around a Trivial File Transfer Protocol packet buffer.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+mod field {
+    use crate::wire::field::*;
+
+    pub const OPCODE: Field = 0..2;
+    pub const BLOCK_NUM: Field = 2..4;
+    pub const ERROR_CODE: Field = 2..4;
+}
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Imbue a raw octet buffer with TFTP packet structure.
+    pub const fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short.
+    ///
+    /// The result of this check is invalidated by calling [set_opcode].
+    ///
+    /// [set_opcode]: #method.set_opcode
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < field::OPCODE.end || len < self.header_len() {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the length of the fixed fields of the packet.
+    pub fn header_len(&self) -> usize {
+        match self.opcode() {
+            Opcode::Data | Opcode::Ack | Opcode::Error => field::BLOCK_NUM.end,
+            _ => field::OPCODE.end,
+        }
+    }
+
+    /// Return the opcode field.
+    #[inline]
+    pub fn opcode(&self) -> Opcode {
+        let data = self.buffer.as_ref();
+        Opcode::from(NetworkEndian::read_u16(&data[field::OPCODE]))
+    }
+
+    /// Return the block number field of a DATA or ACK packet.
+    #[inline]
+    pub fn block_num(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::BLOCK_NUM])
+    }
+
+    /// Return the error code field of an ERROR packet.
+    #[inline]
+    pub fn error_code(&self) -> ErrorCode {
+        let data = self.buffer.as_ref();
+        ErrorCode::from(NetworkEndian::read_u16(&data[field::ERROR_CODE]))
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
+    /// Return a pointer to the payload, which follows the fixed fields.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        let header_len = self.header_len();
+        let data = self.buffer.as_ref();
+        &data[header_len..]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the opcode field.
+    #[inline]
+    pub fn set_opcode(&mut self, value: Opcode) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::OPCODE], value.into())
+    }
+
+    /// Set the block number field of a DATA or ACK packet.
+    #[inline]
+    pub fn set_block_num(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::BLOCK_NUM], value)
+    }
+
+    /// Set the error code field of an ERROR packet.
+    #[inline]
+    pub fn set_error_code(&mut self, value: ErrorCode) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::ERROR_CODE], value.into())
+    }
+
+    /// Return a mutable pointer to the payload.
+    #[inline]
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        let data = self.buffer.as_mut();
+        &mut data[header_len..]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+/// A high-level representation of a Trivial File Transfer Protocol packet.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Repr<'a> {
+    ReadRequest {
+        filename: &'a str,
+        mode: Mode,
+        options: TransferOptions,
+    },
+    WriteRequest {
+        filename: &'a str,
+        mode: Mode,
+        options: TransferOptions,
+    },
+    Data {
+        block_num: u16,
+        data: &'a [u8],
+    },
+    Ack {
+        block_num: u16,
+    },
+    Error {
+        code: ErrorCode,
+        message: &'a str,
+    },
+    OptionAck {
+        options: TransferOptions,
+    },
+}
+
+impl<'a> Repr<'a> {
+    /// Parse a TFTP packet and return a high-level representation.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(packet: &Packet<&'a T>) -> Result<Repr<'a>> {
+        packet.check_len()?;
+        let mut payload = packet.payload();
+        match packet.opcode() {
+            opcode @ (Opcode::Read | Opcode::Write) => {
+                let filename = take_string(&mut payload)?;
+                let mode = Mode::parse(take_string(&mut payload)?)?;
+                let options = TransferOptions::parse(payload)?;
+                if opcode == Opcode::Read {
+                    Ok(Repr::ReadRequest {
+                        filename,
+                        mode,
+                        options,
+                    })
+                } else {
+                    Ok(Repr::WriteRequest {
+                        filename,
+                        mode,
+                        options,
+                    })
+                }
+            }
+            Opcode::Data => Ok(Repr::Data {
+                block_num: packet.block_num(),
+                data: payload,
+            }),
+            Opcode::Ack => Ok(Repr::Ack {
+                block_num: packet.block_num(),
+            }),
+            Opcode::Error => Ok(Repr::Error {
+                code: packet.error_code(),
+                message: take_string(&mut payload)?,
+            }),
+            Opcode::OptionAck => Ok(Repr::OptionAck {
+                options: TransferOptions::parse(payload)?,
+            }),
+            Opcode::Unknown(_) => Err(Error),
+        }
+    }
+
+    /// Return the opcode of the packet.
+    pub const fn opcode(&self) -> Opcode {
+        match self {
+            Repr::ReadRequest { .. } => Opcode::Read,
+            Repr::WriteRequest { .. } => Opcode::Write,
+            Repr::Data { .. } => Opcode::Data,
+            Repr::Ack { .. } => Opcode::Ack,
+            Repr::Error { .. } => Opcode::Error,
+            Repr::OptionAck { .. } => Opcode::OptionAck,
+        }
+    }
+
+    /// Return the length of a packet that will be emitted from this high-level representation.
+    pub fn buffer_len(&self) -> usize {
+        field::OPCODE.end
+            + match self {
+                Repr::ReadRequest {
+                    filename,
+                    mode,
+                    options,
+                }
+                | Repr::WriteRequest {
+                    filename,
+                    mode,
+                    options,
+                } => filename.len() + 1 + mode.as_str().len() + 1 + options.buffer_len(),
+                Repr::Data { data, .. } => 2 + data.len(),
+                Repr::Ack { .. } => 2,
+                Repr::Error { message, .. } => 2 + message.len() + 1,
+                Repr::OptionAck { options } => options.buffer_len(),
+            }
+    }
+
+    /// Emit a high-level representation into a TFTP packet.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) {
+        packet.set_opcode(self.opcode());
+        match *self {
+            Repr::ReadRequest {
+                filename,
+                mode,
+                options,
+            }
+            | Repr::WriteRequest {
+                filename,
+                mode,
+                options,
+            } => {
+                let mut payload = packet.payload_mut();
+                put_string(&mut payload, filename.as_bytes());
+                put_string(&mut payload, mode.as_str().as_bytes());
+                options.emit(payload);
+            }
+            Repr::Data { block_num, data } => {
+                packet.set_block_num(block_num);
+                packet.payload_mut()[..data.len()].copy_from_slice(data);
+            }
+            Repr::Ack { block_num } => packet.set_block_num(block_num),
+            Repr::Error { code, message } => {
+                packet.set_error_code(code);
+                put_string(&mut packet.payload_mut(), message.as_bytes());
+            }
+            Repr::OptionAck { options } => options.emit(packet.payload_mut()),
+        }
+    }
+}
+
+impl<'a> fmt::Display for Repr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Repr::ReadRequest { filename, mode, .. }
+            | Repr::WriteRequest { filename, mode, .. } => {
+                write!(f, "TFTP {} {} mode={}", self.opcode(), filename, mode)
+            }
+            Repr::Data { block_num, data } => {
+                write!(f, "TFTP DATA block={} len={}", block_num, data.len())
+            }
+            Repr::Ack { block_num } => write!(f, "TFTP ACK block={block_num}"),
+            Repr::Error { code, message } => write!(f, "TFTP ERROR ({code}) {message}"),
+            Repr::OptionAck { .. } => write!(f, "TFTP OACK"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static RRQ_BYTES: [u8; 37] = [
+        0x00, 0x01, b'b', b'o', b'o', b't', b'.', b'b', b'i', b'n', 0x00, b'o', b'c', b't', b'e',
+        b't', 0x00, b'b', b'l', b'k', b's', b'i', b'z', b'e', 0x00, b'1', b'4', b'6', b'8', 0x00,
+        b't', b's', b'i', b'z', b'e', 0x00, b'0',
+    ];
+
+    static DATA_BYTES: [u8; 7] = [0x00, 0x03, 0x00, 0x2a, 0xaa, 0x00, 0xff];
+
+    #[test]
+    fn test_deconstruct_data() {
+        let packet = Packet::new_checked(&DATA_BYTES[..]).unwrap();
+        assert_eq!(packet.opcode(), Opcode::Data);
+        assert_eq!(packet.block_num(), 42);
+        assert_eq!(packet.payload(), &[0xaa, 0x00, 0xff]);
+    }
+
+    #[test]
+    fn test_parse_data() {
+        let packet = Packet::new_unchecked(&DATA_BYTES[..]);
+        assert_eq!(
+            Repr::parse(&packet),
+            Ok(Repr::Data {
+                block_num: 42,
+                data: &[0xaa, 0x00, 0xff]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_request() {
+        // The tsize option is not terminated.
+        let packet = Packet::new_unchecked(&RRQ_BYTES[..]);
+        assert_eq!(Repr::parse(&packet), Err(Error));
+
+        let packet = Packet::new_unchecked(&RRQ_BYTES[..30]);
+        assert_eq!(
+            Repr::parse(&packet),
+            Ok(Repr::ReadRequest {
+                filename: "boot.bin",
+                mode: Mode::Octet,
+                options: TransferOptions {
+                    block_size: Some(1468),
+                    timeout: None
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_emit_request() {
+        let repr = Repr::ReadRequest {
+            filename: "boot.bin",
+            mode: Mode::Octet,
+            options: TransferOptions {
+                block_size: Some(1468),
+                timeout: None,
+            },
+        };
+        assert_eq!(repr.buffer_len(), 30);
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        repr.emit(&mut Packet::new_unchecked(&mut bytes[..]));
+        assert_eq!(&bytes[..], &RRQ_BYTES[..30]);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let reprs = [
+            Repr::WriteRequest {
+                filename: "log.txt",
+                mode: Mode::NetAscii,
+                options: TransferOptions::default(),
+            },
+            Repr::Data {
+                block_num: 1,
+                data: &[],
+            },
+            Repr::Ack { block_num: 65535 },
+            Repr::Error {
+                code: ErrorCode::FileNotFound,
+                message: "no such file",
+            },
+            Repr::OptionAck {
+                options: TransferOptions {
+                    block_size: Some(8),
+                    timeout: Some(255),
+                },
+            },
+        ];
+        for repr in reprs {
+            let mut bytes = vec![0xa5; repr.buffer_len()];
+            repr.emit(&mut Packet::new_unchecked(&mut bytes[..]));
+            assert_eq!(Repr::parse(&Packet::new_unchecked(&bytes[..])), Ok(repr));
+        }
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(Packet::new_checked(&DATA_BYTES[..1]), Err(Error));
+        assert_eq!(Packet::new_checked(&DATA_BYTES[..3]), Err(Error));
+
+        let bytes = [
+            0x00, 0x06, b'b', b'l', b'k', b's', b'i', b'z', b'e', 0x00, b'7', 0x00,
+        ];
+        assert_eq!(Repr::parse(&Packet::new_unchecked(&bytes[..])), Err(Error));
+
+        let bytes = [0x00, 0x01, b'a', 0x00, b'b', b'i', b'n', 0x00];
+        assert_eq!(Repr::parse(&Packet::new_unchecked(&bytes[..])), Err(Error));
+
+        let bytes = [0x00, 0x07];
+        assert_eq!(Repr::parse(&Packet::new_unchecked(&bytes[..])), Err(Error));
+    }
+}
@@ -0,0 +1,765 @@
+use byteorder::{ByteOrder, LittleEndian};
+use core::fmt;
+
+use super::{Error, Result};
+use crate::wire::field::Field;
+
+/// The UDP port WireGuard listens on by default.
+pub const PORT: u16 = 51820;
+
+enum_with_unknown! {
+    /// The type of a WireGuard message.
+    pub enum MessageType(u8) {
+        HandshakeInitiation = 1,
+        HandshakeResponse   = 2,
+        CookieReply         = 3,
+        TransportData       = 4
+    }
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MessageType::HandshakeInitiation => write!(f, "handshake initiation"),
+            MessageType::HandshakeResponse => write!(f, "handshake response"),
+            MessageType::CookieReply => write!(f, "cookie reply"),
+            MessageType::TransportData => write!(f, "transport data"),
+            MessageType::Unknown(id) => write!(f, "0x{id:02x}"),
+        }
+    }
+}
+
+/// A read/write wrapper around a WireGuard message buffer.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+// Format of the WireGuard messages, see https://www.wireguard.com/protocol/
+//
+// All the messages start with a type and 3 reserved octets, and their integers are
+// little-endian.
+//
+// Handshake initiation:       Handshake response:         Cookie reply:
+// +--------+----------+       +--------+----------+       +--------+----------+
+// | type 1 | reserved |       | type 2 | reserved |       | type 3 | reserved |
+// +--------+----------+       +--------+----------+       +--------+----------+
+// |      sender       |       |      sender       |       |     receiver      |
+// +-------------------+       +-------------------+       +-------------------+
+// | ephemeral (32)    |       |     receiver      |       | nonce (24)        |
+// +-------------------+       +-------------------+       +-------------------+
+// | static (32+16)    |       | ephemeral (32)    |       | cookie (16+16)    |
+// +-------------------+       +-------------------+       +-------------------+
+// | timestamp (12+16) |       | empty (0+16)      |
+// +-------------------+       +-------------------+       Transport data:
+// | mac1 (16)         |       | mac1 (16)         |       +--------+----------+
+// +-------------------+       +-------------------+       | type 4 | reserved |
+// | mac2 (16)         |       | mac2 (16)         |       +--------+----------+
+// +-------------------+       +-------------------+       |     receiver      |
+//                                                         +-------------------+
+//                                                         |   counter (8)     |
+//                                                         +-------------------+
+//                                                         | packet (n+16)     |
+//                                                         +-------------------+
+mod field {
+    use crate::wire::field::*;
+
+    pub const MESSAGE_TYPE: usize = 0;
+    pub const RESERVED: Field = 1..4;
+
+    pub mod initiation {
+        use super::Field;
+
+        pub const SENDER: Field = 4..8;
+        pub const EPHEMERAL: Field = 8..40;
+        pub const STATIC: Field = 40..88;
+        pub const TIMESTAMP: Field = 88..116;
+        pub const MAC1: Field = 116..132;
+        pub const MAC2: Field = 132..148;
+    }
+
+    pub mod response {
+        use super::Field;
+
+        pub const SENDER: Field = 4..8;
+        pub const RECEIVER: Field = 8..12;
+        pub const EPHEMERAL: Field = 12..44;
+        pub const EMPTY: Field = 44..60;
+        pub const MAC1: Field = 60..76;
+        pub const MAC2: Field = 76..92;
+    }
+
+    pub mod cookie {
+        use super::Field;
+
+        pub const RECEIVER: Field = 4..8;
+        pub const NONCE: Field = 8..32;
+        pub const COOKIE: Field = 32..64;
+    }
+
+    pub mod transport {
+        use super::{Field, Rest};
+
+        pub const RECEIVER: Field = 4..8;
+        pub const COUNTER: Field = 8..16;
+        pub const PACKET: Rest = 16..;
+    }
+}
+
+/// The length of a handshake initiation message.
+pub const HANDSHAKE_INITIATION_LEN: usize = field::initiation::MAC2.end;
+
+/// The length of a handshake response message.
+pub const HANDSHAKE_RESPONSE_LEN: usize = field::response::MAC2.end;
+
+/// The length of a cookie reply message.
+pub const COOKIE_REPLY_LEN: usize = field::cookie::COOKIE.end;
+
+/// The length of the header of a transport data message.
+pub const TRANSPORT_HEADER_LEN: usize = field::transport::PACKET.start;
+
+/// The length of the authentication tag of an encrypted field.
+pub const TAG_LEN: usize = 16;
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Imbue a raw octet buffer with WireGuard message structure.
+    pub const fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short, or if the message type is unknown.
+    ///
+    /// The handshake and cookie messages must have their exact length.
+    ///
+    /// The result of this check is invalidated by calling [set_message_type].
+    ///
+    /// [set_message_type]: #method.set_message_type
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len == 0 {
+            return Err(Error);
+        }
+        let valid = match self.message_type() {
+            MessageType::HandshakeInitiation => len == HANDSHAKE_INITIATION_LEN,
+            MessageType::HandshakeResponse => len == HANDSHAKE_RESPONSE_LEN,
+            MessageType::CookieReply => len == COOKIE_REPLY_LEN,
+            MessageType::TransportData => len >= TRANSPORT_HEADER_LEN + TAG_LEN,
+            MessageType::Unknown(_) => false,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(Error)
+        }
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the message type field.
+    #[inline]
+    pub fn message_type(&self) -> MessageType {
+        let data = self.buffer.as_ref();
+        MessageType::from(data[field::MESSAGE_TYPE])
+    }
+
+    /// Return the sender index field of a handshake message.
+    ///
+    /// # Panics
+    /// This function panics if the message is not a handshake message.
+    #[inline]
+    pub fn sender_index(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        match self.message_type() {
+            MessageType::HandshakeInitiation => {
+                LittleEndian::read_u32(&data[field::initiation::SENDER])
+            }
+            MessageType::HandshakeResponse => {
+                LittleEndian::read_u32(&data[field::response::SENDER])
+            }
+            _ => panic!("{} has no sender index", self.message_type()),
+        }
+    }
+
+    /// Return the receiver index field of a handshake response, cookie reply, or transport
+    /// data message.
+    ///
+    /// # Panics
+    /// This function panics if the message is a handshake initiation.
+    #[inline]
+    pub fn receiver_index(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        LittleEndian::read_u32(&data[self.receiver_field()])
+    }
+
+    /// Return the counter field of a transport data message.
+    ///
+    /// # Panics
+    /// This function panics if the message is not a transport data message.
+    #[inline]
+    pub fn counter(&self) -> u64 {
+        assert_eq!(self.message_type(), MessageType::TransportData);
+        let data = self.buffer.as_ref();
+        LittleEndian::read_u64(&data[field::transport::COUNTER])
+    }
+
+    fn receiver_field(&self) -> Field {
+        match self.message_type() {
+            MessageType::HandshakeResponse => field::response::RECEIVER,
+            MessageType::CookieReply => field::cookie::RECEIVER,
+            MessageType::TransportData => field::transport::RECEIVER,
+            _ => panic!("{} has no receiver index", self.message_type()),
+        }
+    }
+
+    fn ephemeral_field(&self) -> Field {
+        match self.message_type() {
+            MessageType::HandshakeInitiation => field::initiation::EPHEMERAL,
+            MessageType::HandshakeResponse => field::response::EPHEMERAL,
+            _ => panic!("{} has no ephemeral key", self.message_type()),
+        }
+    }
+
+    fn mac_fields(&self) -> (Field, Field) {
+        match self.message_type() {
+            MessageType::HandshakeInitiation => (field::initiation::MAC1, field::initiation::MAC2),
+            MessageType::HandshakeResponse => (field::response::MAC1, field::response::MAC2),
+            _ => panic!("{} has no macs", self.message_type()),
+        }
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
+    /// Return a pointer to the unencrypted ephemeral public key of a handshake message.
+    ///
+    /// # Panics
+    /// This function panics if the message is not a handshake message.
+    #[inline]
+    pub fn ephemeral(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[self.ephemeral_field()]
+    }
+
+    /// Return a pointer to the encrypted static public key of a handshake initiation.
+    #[inline]
+    pub fn encrypted_static(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[field::initiation::STATIC]
+    }
+
+    /// Return a pointer to the encrypted timestamp of a handshake initiation.
+    #[inline]
+    pub fn encrypted_timestamp(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[field::initiation::TIMESTAMP]
+    }
+
+    /// Return a pointer to the encrypted empty field of a handshake response.
+    #[inline]
+    pub fn encrypted_nothing(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[field::response::EMPTY]
+    }
+
+    /// Return a pointer to the part of a handshake message covered by its first MAC.
+    ///
+    /// # Panics
+    /// This function panics if the message is not a handshake message.
+    #[inline]
+    pub fn mac1_data(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[..self.mac_fields().0.start]
+    }
+
+    /// Return a pointer to the first MAC of a handshake message.
+    ///
+    /// # Panics
+    /// This function panics if the message is not a handshake message.
+    #[inline]
+    pub fn mac1(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[self.mac_fields().0]
+    }
+
+    /// Return a pointer to the part of a handshake message covered by its second MAC.
+    ///
+    /// # Panics
+    /// This function panics if the message is not a handshake message.
+    #[inline]
+    pub fn mac2_data(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[..self.mac_fields().1.start]
+    }
+
+    /// Return a pointer to the second MAC of a handshake message.
+    ///
+    /// # Panics
+    /// This function panics if the message is not a handshake message.
+    #[inline]
+    pub fn mac2(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[self.mac_fields().1]
+    }
+
+    /// Return a pointer to the nonce of a cookie reply.
+    #[inline]
+    pub fn nonce(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[field::cookie::NONCE]
+    }
+
+    /// Return a pointer to the encrypted cookie of a cookie reply.
+    #[inline]
+    pub fn encrypted_cookie(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[field::cookie::COOKIE]
+    }
+
+    /// Return a pointer to the encrypted packet of a transport data message, followed by
+    /// its authentication tag.
+    #[inline]
+    pub fn encrypted_packet(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[field::transport::PACKET]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the message type field, and clear the reserved field.
+    #[inline]
+    pub fn set_message_type(&mut self, value: MessageType) {
+        let data = self.buffer.as_mut();
+        data[field::MESSAGE_TYPE] = value.into();
+        data[field::RESERVED].fill(0);
+    }
+
+    /// Set the sender index field of a handshake message.
+    ///
+    /// # Panics
+    /// This function panics if the message is not a handshake message.
+    #[inline]
+    pub fn set_sender_index(&mut self, value: u32) {
+        let field = match self.message_type() {
+            MessageType::HandshakeInitiation => field::initiation::SENDER,
+            MessageType::HandshakeResponse => field::response::SENDER,
+            _ => panic!("{} has no sender index", self.message_type()),
+        };
+        let data = self.buffer.as_mut();
+        LittleEndian::write_u32(&mut data[field], value)
+    }
+
+    /// Set the receiver index field of a handshake response, cookie reply, or transport
+    /// data message.
+    ///
+    /// # Panics
+    /// This function panics if the message is a handshake initiation.
+    #[inline]
+    pub fn set_receiver_index(&mut self, value: u32) {
+        let field = self.receiver_field();
+        let data = self.buffer.as_mut();
+        LittleEndian::write_u32(&mut data[field], value)
+    }
+
+    /// Set the counter field of a transport data message.
+    ///
+    /// # Panics
+    /// This function panics if the message is not a transport data message.
+    #[inline]
+    pub fn set_counter(&mut self, value: u64) {
+        assert_eq!(self.message_type(), MessageType::TransportData);
+        let data = self.buffer.as_mut();
+        LittleEndian::write_u64(&mut data[field::transport::COUNTER], value)
+    }
+
+    /// Return a mutable pointer to the ephemeral public key of a handshake message.
+    ///
+    /// # Panics
+    /// This function panics if the message is not a handshake message.
+    #[inline]
+    pub fn ephemeral_mut(&mut self) -> &mut [u8] {
+        let field = self.ephemeral_field();
+        let data = self.buffer.as_mut();
+        &mut data[field]
+    }
+
+    /// Return a mutable pointer to the encrypted static public key of a handshake
+    /// initiation.
+    #[inline]
+    pub fn encrypted_static_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        &mut data[field::initiation::STATIC]
+    }
+
+    /// Return a mutable pointer to the encrypted timestamp of a handshake initiation.
+    #[inline]
+    pub fn encrypted_timestamp_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        &mut data[field::initiation::TIMESTAMP]
+    }
+
+    /// Return a mutable pointer to the encrypted empty field of a handshake response.
+    #[inline]
+    pub fn encrypted_nothing_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        &mut data[field::response::EMPTY]
+    }
+
+    /// Return a mutable pointer to the first MAC of a handshake message.
+    ///
+    /// # Panics
+    /// This function panics if the message is not a handshake message.
+    #[inline]
+    pub fn mac1_mut(&mut self) -> &mut [u8] {
+        let field = self.mac_fields().0;
+        let data = self.buffer.as_mut();
+        &mut data[field]
+    }
+
+    /// Return a mutable pointer to the second MAC of a handshake message.
+    ///
+    /// # Panics
+    /// This function panics if the message is not a handshake message.
+    #[inline]
+    pub fn mac2_mut(&mut self) -> &mut [u8] {
+        let field = self.mac_fields().1;
+        let data = self.buffer.as_mut();
+        &mut data[field]
+    }
+
+    /// Return a mutable pointer to the nonce of a cookie reply.
+    #[inline]
+    pub fn nonce_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        &mut data[field::cookie::NONCE]
+    }
+
+    /// Return a mutable pointer to the encrypted cookie of a cookie reply.
+    #[inline]
+    pub fn encrypted_cookie_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        &mut data[field::cookie::COOKIE]
+    }
+
+    /// Return a mutable pointer to the encrypted packet of a transport data message,
+    /// followed by its authentication tag.
+    #[inline]
+    pub fn encrypted_packet_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        &mut data[field::transport::PACKET]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+/// A high-level representation of a WireGuard message.
+///
+/// The cryptographic fields are opaque, and must have the length of their field.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Repr<'a> {
+    HandshakeInitiation {
+        sender_index: u32,
+        ephemeral: &'a [u8],
+        encrypted_static: &'a [u8],
+        encrypted_timestamp: &'a [u8],
+        mac1: &'a [u8],
+        mac2: &'a [u8],
+    },
+    HandshakeResponse {
+        sender_index: u32,
+        receiver_index: u32,
+        ephemeral: &'a [u8],
+        encrypted_nothing: &'a [u8],
+        mac1: &'a [u8],
+        mac2: &'a [u8],
+    },
+    CookieReply {
+        receiver_index: u32,
+        nonce: &'a [u8],
+        encrypted_cookie: &'a [u8],
+    },
+    TransportData {
+        receiver_index: u32,
+        counter: u64,
+        encrypted_packet: &'a [u8],
+    },
+}
+
+impl<'a> Repr<'a> {
+    /// Parse a WireGuard message and return a high-level representation.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(packet: &Packet<&'a T>) -> Result<Repr<'a>> {
+        packet.check_len()?;
+        match packet.message_type() {
+            MessageType::HandshakeInitiation => Ok(Repr::HandshakeInitiation {
+                sender_index: packet.sender_index(),
+                ephemeral: packet.ephemeral(),
+                encrypted_static: packet.encrypted_static(),
+                encrypted_timestamp: packet.encrypted_timestamp(),
+                mac1: packet.mac1(),
+                mac2: packet.mac2(),
+            }),
+            MessageType::HandshakeResponse => Ok(Repr::HandshakeResponse {
+                sender_index: packet.sender_index(),
+                receiver_index: packet.receiver_index(),
+                ephemeral: packet.ephemeral(),
+                encrypted_nothing: packet.encrypted_nothing(),
+                mac1: packet.mac1(),
+                mac2: packet.mac2(),
+            }),
+            MessageType::CookieReply => Ok(Repr::CookieReply {
+                receiver_index: packet.receiver_index(),
+                nonce: packet.nonce(),
+                encrypted_cookie: packet.encrypted_cookie(),
+            }),
+            MessageType::TransportData => Ok(Repr::TransportData {
+                receiver_index: packet.receiver_index(),
+                counter: packet.counter(),
+                encrypted_packet: packet.encrypted_packet(),
+            }),
+            MessageType::Unknown(_) => Err(Error),
+        }
+    }
+
+    /// Return the type of the message.
+    pub const fn message_type(&self) -> MessageType {
+        match self {
+            Repr::HandshakeInitiation { .. } => MessageType::HandshakeInitiation,
+            Repr::HandshakeResponse { .. } => MessageType::HandshakeResponse,
+            Repr::CookieReply { .. } => MessageType::CookieReply,
+            Repr::TransportData { .. } => MessageType::TransportData,
+        }
+    }
+
+    /// Return the length of a message that will be emitted from this high-level
+    /// representation.
+    pub const fn buffer_len(&self) -> usize {
+        match self {
+            Repr::HandshakeInitiation { .. } => HANDSHAKE_INITIATION_LEN,
+            Repr::HandshakeResponse { .. } => HANDSHAKE_RESPONSE_LEN,
+            Repr::CookieReply { .. } => COOKIE_REPLY_LEN,
+            Repr::TransportData {
+                encrypted_packet, ..
+            } => TRANSPORT_HEADER_LEN + encrypted_packet.len(),
+        }
+    }
+
+    /// Emit a high-level representation into a WireGuard message.
+    ///
+    /// # Panics
+    /// This function panics if a cryptographic field does not have the length of its field.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) {
+        packet.set_message_type(self.message_type());
+        match *self {
+            Repr::HandshakeInitiation {
+                sender_index,
+                ephemeral,
+                encrypted_static,
+                encrypted_timestamp,
+                mac1,
+                mac2,
+            } => {
+                packet.set_sender_index(sender_index);
+                packet.ephemeral_mut().copy_from_slice(ephemeral);
+                packet
+                    .encrypted_static_mut()
+                    .copy_from_slice(encrypted_static);
+                packet
+                    .encrypted_timestamp_mut()
+                    .copy_from_slice(encrypted_timestamp);
+                packet.mac1_mut().copy_from_slice(mac1);
+                packet.mac2_mut().copy_from_slice(mac2);
+            }
+            Repr::HandshakeResponse {
+                sender_index,
+                receiver_index,
+                ephemeral,
+                encrypted_nothing,
+                mac1,
+                mac2,
+            } => {
+                packet.set_sender_index(sender_index);
+                packet.set_receiver_index(receiver_index);
+                packet.ephemeral_mut().copy_from_slice(ephemeral);
+                packet
+                    .encrypted_nothing_mut()
+                    .copy_from_slice(encrypted_nothing);
+                packet.mac1_mut().copy_from_slice(mac1);
+                packet.mac2_mut().copy_from_slice(mac2);
+            }
+            Repr::CookieReply {
+                receiver_index,
+                nonce,
+                encrypted_cookie,
+            } => {
+                packet.set_receiver_index(receiver_index);
+                packet.nonce_mut().copy_from_slice(nonce);
+                packet
+                    .encrypted_cookie_mut()
+                    .copy_from_slice(encrypted_cookie);
+            }
+            Repr::TransportData {
+                receiver_index,
+                counter,
+                encrypted_packet,
+            } => {
+                packet.set_receiver_index(receiver_index);
+                packet.set_counter(counter);
+                packet.encrypted_packet_mut()[..encrypted_packet.len()]
+                    .copy_from_slice(encrypted_packet);
+            }
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> fmt::Display for Packet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Repr::parse(&Packet::new_unchecked(self.buffer.as_ref())) {
+            Ok(repr) => write!(f, "{repr}"),
+            Err(err) => write!(f, "WireGuard ({err})"),
+        }
+    }
+}
+
+impl<'a> fmt::Display for Repr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Repr::HandshakeInitiation { sender_index, .. } => {
+                write!(f, "WireGuard handshake initiation sender={sender_index:08x}")
+            }
+            Repr::HandshakeResponse {
+                sender_index,
+                receiver_index,
+                ..
+            } => write!(
+                f,
+                "WireGuard handshake response sender={sender_index:08x} receiver={receiver_index:08x}"
+            ),
+            Repr::CookieReply { receiver_index, .. } => {
+                write!(f, "WireGuard cookie reply receiver={receiver_index:08x}")
+            }
+            Repr::TransportData {
+                receiver_index,
+                counter,
+                encrypted_packet,
+            } => write!(
+                f,
+                "WireGuard transport data receiver={receiver_index:08x} counter={counter} len={}",
+                encrypted_packet.len()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static DATA_BYTES: [u8; 36] = [
+        0x04, 0x00, 0x00, 0x00, 0x78, 0x56, 0x34, 0x12, 0x2a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0xaa, 0xaa, 0xaa, 0xaa, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb,
+        0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb,
+    ];
+
+    fn response_bytes() -> [u8; HANDSHAKE_RESPONSE_LEN] {
+        let mut bytes = [0; HANDSHAKE_RESPONSE_LEN];
+        bytes[0] = 0x02;
+        bytes[4..8].copy_from_slice(&[0x01, 0x00, 0x00, 0x00]);
+        bytes[8..12].copy_from_slice(&[0x02, 0x00, 0x00, 0x00]);
+        bytes[12..44].fill(0x11);
+        bytes[44..60].fill(0x22);
+        bytes[60..76].fill(0x33);
+        bytes[76..92].fill(0x44);
+        bytes
+    }
+
+    #[test]
+    fn test_deconstruct_data() {
+        let packet = Packet::new_checked(&DATA_BYTES[..]).unwrap();
+        assert_eq!(packet.message_type(), MessageType::TransportData);
+        assert_eq!(packet.receiver_index(), 0x12345678);
+        assert_eq!(packet.counter(), 42);
+        assert_eq!(packet.encrypted_packet(), &DATA_BYTES[16..]);
+    }
+
+    #[test]
+    fn test_construct_data() {
+        let mut bytes = vec![0xa5; 36];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_message_type(MessageType::TransportData);
+        packet.set_receiver_index(0x12345678);
+        packet.set_counter(42);
+        packet
+            .encrypted_packet_mut()
+            .copy_from_slice(&DATA_BYTES[16..]);
+        assert_eq!(&*packet.into_inner(), &DATA_BYTES[..]);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let bytes = response_bytes();
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.mac1_data(), &bytes[..60]);
+        assert_eq!(packet.mac2_data(), &bytes[..76]);
+        assert_eq!(
+            Repr::parse(&packet),
+            Ok(Repr::HandshakeResponse {
+                sender_index: 1,
+                receiver_index: 2,
+                ephemeral: &[0x11; 32],
+                encrypted_nothing: &[0x22; 16],
+                mac1: &[0x33; 16],
+                mac2: &[0x44; 16],
+            })
+        );
+    }
+
+    #[test]
+    fn test_emit_response() {
+        let repr = Repr::HandshakeResponse {
+            sender_index: 1,
+            receiver_index: 2,
+            ephemeral: &[0x11; 32],
+            encrypted_nothing: &[0x22; 16],
+            mac1: &[0x33; 16],
+            mac2: &[0x44; 16],
+        };
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        repr.emit(&mut Packet::new_unchecked(&mut bytes));
+        assert_eq!(&bytes[..], &response_bytes()[..]);
+    }
+
+    #[test]
+    fn test_check_len() {
+        let bytes = response_bytes();
+        assert_eq!(Packet::new_checked(&bytes[..91]), Err(Error));
+        assert_eq!(Packet::new_checked(&DATA_BYTES[..31]), Err(Error));
+        assert_eq!(Packet::new_checked(&[0x05; 148][..]), Err(Error));
+        assert_eq!(Packet::new_checked(&[0u8; 0][..]), Err(Error));
+
+        let mut bytes = [0; HANDSHAKE_INITIATION_LEN];
+        bytes[0] = 0x01;
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.ephemeral().len(), 32);
+        assert_eq!(packet.encrypted_static().len(), 48);
+        assert_eq!(packet.encrypted_timestamp().len(), 28);
+        assert_eq!(packet.mac1_data().len(), 116);
+    }
+}